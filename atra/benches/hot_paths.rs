@@ -0,0 +1,41 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use atra::bench::{
+    HtmlExtractionWorkload, LinkStateDiscoveryWorkload, LinkStateUpsertWorkload,
+    QueueChurnWorkload, UrlNormalizationWorkload, WarcWriteWorkload, Workload, HTML_SIZE,
+    QUEUE_ELEMENTS, URL_COUNT, WARC_RECORDS,
+};
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn bench_workload<W: Workload + Default>(c: &mut Criterion, throughput: Throughput) {
+    let mut workload = W::default();
+    let mut group = c.benchmark_group(W::NAME);
+    group.throughput(throughput);
+    group.sample_size(10);
+    group.bench_function("run", |b| b.iter(|| workload.run()));
+    group.finish();
+}
+
+fn hot_paths(c: &mut Criterion) {
+    bench_workload::<UrlNormalizationWorkload>(c, Throughput::Elements(URL_COUNT as u64));
+    bench_workload::<QueueChurnWorkload>(c, Throughput::Elements(QUEUE_ELEMENTS as u64));
+    bench_workload::<LinkStateUpsertWorkload>(c, Throughput::Elements(URL_COUNT as u64));
+    bench_workload::<LinkStateDiscoveryWorkload>(c, Throughput::Elements(URL_COUNT as u64));
+    bench_workload::<WarcWriteWorkload>(c, Throughput::Elements(WARC_RECORDS as u64));
+    bench_workload::<HtmlExtractionWorkload>(c, Throughput::Bytes(HTML_SIZE as u64));
+}
+
+criterion_group!(benches, hot_paths);
+criterion_main!(benches);
@@ -12,9 +12,17 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::app::gdbr_export::parse_language;
+use crate::app::requeue::RequeueFilter;
+use crate::app::view::{
+    BulkExportArgs, ParquetCompression, ViewFilter, ViewFormat, WebGraphFormat, WebGraphNodes,
+};
 use crate::config::crawl::UserAgent;
+use crate::crawl::BackfillField;
 use crate::seed::SeedDefinition;
 use clap::{Parser, Subcommand};
+use isolang::Language;
+use std::num::NonZeroUsize;
 use std::str::FromStr;
 
 #[derive(Parser, Debug, Default)]
@@ -25,6 +33,22 @@ pub struct AtraArgs {
     #[arg(long)]
     pub generate_example_config: bool,
 
+    /// Prints the features and the resulting priority of the url for the discovered config.
+    #[arg(long, value_name = "URL")]
+    pub explain_priority: Option<String>,
+
+    /// Prints the settings used for the origin after applying the profiles of the discovered config.
+    #[arg(long, value_name = "ORIGIN")]
+    pub show_effective: Option<String>,
+
+    /// Prints the JSON Schema of a machine readable output.
+    #[arg(
+        long,
+        value_name = "NAME",
+        value_parser = clap::builder::PossibleValuesParser::new(crate::schemas::SCHEMA_NAMES.iter().copied())
+    )]
+    pub print_schema: Option<String>,
+
     /// The mode of Atra
     #[command(subcommand)]
     pub mode: Option<RunMode>,
@@ -60,6 +84,13 @@ pub enum RunMode {
         /// Log to file
         #[arg(long)]
         log_to_file: bool,
+        /// Only validates the seeds against the blacklist and the budget, nothing is fetched.
+        #[arg(long)]
+        dry_run: bool,
+        /// Shows the status of the crawl instead of the log, the log is written to a file.
+        /// Falls back to plain logging if stdout is not a terminal.
+        #[arg(long)]
+        tui: bool,
         /// The seed url to be crawled.
         seeds: SeedDefinition,
     },
@@ -84,9 +115,42 @@ pub enum RunMode {
         /// Log to file
         #[arg(long)]
         log_to_file: bool,
+        /// Only validates the seeds against the blacklist and the budget, nothing is fetched.
+        #[arg(long)]
+        dry_run: bool,
+        /// Shows the status of the crawl instead of the log, the log is written to a file.
+        /// Falls back to plain logging if stdout is not a terminal.
+        #[arg(long)]
+        tui: bool,
+        /// Listen on this unix socket for secondaries helping with the crawl.
+        #[arg(long, value_name = "SOCKET")]
+        listen: Option<String>,
+        /// The path of a previous crawl, the urls stored there are not crawled again
+        /// until their recrawl interval is over.
+        #[arg(long, value_name = "PATH")]
+        baseline: Option<String>,
         /// Seed to be crawled
         seeds: SeedDefinition,
     },
+    /// Fetch every url of a list once without extracting links.
+    /// Running it again with the same list resumes the fetch.
+    FETCHLIST {
+        /// The number of threads used by this application.
+        #[arg(short, long)]
+        threads: Option<usize>,
+        /// The folder containing the required configs.
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Overrides the sub-root dir name from fetchlist_<name of the list>.
+        /// If it is an absolute path the complete root is replaced.
+        #[arg(long)]
+        override_root_dir_name: Option<String>,
+        /// Log to file
+        #[arg(long)]
+        log_to_file: bool,
+        /// The file with one url per line
+        list: String,
+    },
     /// Continue a crawl that was somehow ended.
     RECOVER {
         /// The number of threads used by this application.
@@ -98,6 +162,11 @@ pub enum RunMode {
         /// The path to the folder with the atra data
         path: String,
     },
+    /// Confirm the next wave of a running crawl in wave mode that waits for the confirmation.
+    CONTINUE {
+        /// The path to the folder with the atra data
+        path: String,
+    },
     /// Initializes Atra for Multi by creating the default config filee
     INIT,
 
@@ -112,6 +181,49 @@ pub enum RunMode {
         /// Show the headers of every page
         #[arg(short, long)]
         headers: bool,
+        /// The output format, jsonl writes one JSON object per crawled page
+        #[arg(long, value_enum, default_value_t = ViewFormat::Text)]
+        format: ViewFormat,
+        /// The file for the jsonl output, if not set it is written to stdout
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Also export the pages past their unavailable_after date
+        #[arg(long)]
+        include_expired: bool,
+        /// Export the decoded text of every page as NFC normalized UTF-8 (only jsonl)
+        #[arg(long)]
+        text: bool,
+        /// Only show the pages matching this expression, like
+        /// "status >= 400 && format == 'HTML' && origin ~ 'example.de'"
+        #[arg(long, value_parser = ViewFilter::from_str)]
+        filter: Option<ViewFilter>,
+        /// Show the audit trail of the changes applied to the crawl instead of the pages
+        #[arg(long)]
+        audit: bool,
+        /// Export the webgraph of the crawl to this file instead of showing the pages
+        #[arg(long, value_name = "PATH")]
+        export_webgraph: Option<String>,
+        /// The format of the exported webgraph
+        #[arg(long, value_enum, default_value_t = WebGraphFormat::Graphml)]
+        webgraph_format: WebGraphFormat,
+        /// The nodes of the exported webgraph, one per url or one per origin
+        #[arg(long, value_enum, default_value_t = WebGraphNodes::Url)]
+        webgraph_nodes: WebGraphNodes,
+        /// Export the pages and their outlinks as pages.parquet and edges.parquet to this folder
+        #[arg(long, value_name = "DIR")]
+        export_parquet: Option<String>,
+        /// The compression codec of the exported Parquet files
+        #[arg(long, value_enum, default_value_t = ParquetCompression::Zstd)]
+        parquet_compression: ParquetCompression,
+        /// The maximum number of rows of a row group in the exported Parquet files
+        #[arg(long, default_value_t = NonZeroUsize::new(65536).unwrap())]
+        parquet_row_group_size: NonZeroUsize,
+        /// List the stored pages with the most similar bodies to the page of this url
+        #[arg(long, value_name = "URL")]
+        similar_to: Option<String>,
+        /// The number of pages listed by --similar-to
+        #[arg(long, default_value_t = 10)]
+        similar_limit: usize,
         /// The path to the folder with the atra data
         path: String,
     },
@@ -120,9 +232,114 @@ pub enum RunMode {
         /// Directory for the dumps
         #[arg(short, long)]
         output_dir: Option<String>,
+        /// Also dump the pages past their unavailable_after date
+        #[arg(long)]
+        include_expired: bool,
         /// The path to the crawl
         crawl_path: String,
-    }
+    },
+    /// Rebuild the crawl db of a crawl from its warc files.
+    REBUILDDB {
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// List the pages past their unavailable_after date.
+    EXPIRED {
+        /// Remove the listed pages from the crawl db
+        #[arg(long)]
+        prune: bool,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Clean up the leftovers of an aborted crawl: truncate torn warc records and delete the
+    /// data files no stored page refers to.
+    MAINTAIN {
+        /// Only list the actions, nothing is changed.
+        #[arg(long)]
+        dry_run: bool,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Export the texts of the stored html pages of a language as train data for the GDBR
+    /// classifier, to be labeled by hand.
+    #[command(name = "gdbr-export")]
+    GDBREXPORT {
+        /// The csv file to create
+        #[arg(short, long)]
+        output: String,
+        /// Only export the pages detected as this language, e.g. deu.
+        #[arg(short, long, value_parser = parse_language)]
+        language: Language,
+        /// Prefill the column is_class with the prediction of the configured classifier.
+        #[arg(long)]
+        prelabel: bool,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Check the block digests of all records in the warc files of a crawl, fails if any
+    /// record is corrupt.
+    VERIFY {
+        /// The path to the folder with the atra data
+        #[arg(long)]
+        path: String,
+    },
+    /// Requeue the failed urls of a crawl, e.g. after fixing the cause of the failures.
+    REQUEUE {
+        #[command(flatten)]
+        filter: RequeueFilter,
+        /// Only report the number of matching urls per origin, nothing is changed.
+        #[arg(long)]
+        dry_run: bool,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Compute the language and GDBR fields missing in the metas of an older crawl.
+    BACKFILL {
+        /// Only backfill this field, can be set multiple times.
+        /// If not set, every field is backfilled.
+        #[arg(short, long, value_enum)]
+        field: Vec<BackfillField>,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Import the responses recorded in HAR files, e.g. exported by a browser after a login.
+    IMPORTHAR {
+        /// Enqueue the links extracted from the imported pages.
+        #[arg(long)]
+        follow_links: bool,
+        /// The path to the folder with the atra data
+        path: String,
+        /// The HAR files to import.
+        #[arg(required = true)]
+        har_files: Vec<String>,
+    },
+    /// Import the response records of warc files written by other crawlers, e.g. Heritrix.
+    IMPORT {
+        /// The path to the folder with the atra data
+        path: String,
+        /// The warc files to import, `.warc.gz` files are decompressed.
+        #[arg(required = true)]
+        warc_files: Vec<String>,
+    },
+    /// Export the stored pages with their text as bulk index actions for OpenSearch or
+    /// Elasticsearch, posted to an endpoint or written to an NDJSON file.
+    BULK {
+        #[command(flatten)]
+        args: BulkExportArgs,
+        /// The path to the folder with the atra data
+        path: String,
+    },
+    /// Help a multi crawl started with --listen by fetching the urls it leases.
+    WORKER {
+        /// The number of connections to the primary, every connection fetches one url at a time.
+        #[arg(short, long, default_value_t = 1)]
+        connections: usize,
+        /// Log to file
+        #[arg(long)]
+        log_to_file: bool,
+        /// The unix socket of the primary
+        connect: String,
+    },
 }
 
 #[cfg(test)]
@@ -137,6 +354,9 @@ mod test {
     fn works() {
         let args = AtraArgs {
             generate_example_config: false,
+            explain_priority: None,
+            show_effective: None,
+            print_schema: None,
             mode: Some(crate::app::args::RunMode::SINGLE {
                 session_name: None,
                 depth: 1,
@@ -149,6 +369,8 @@ mod test {
                 ),
                 log_level: max_level(),
                 timeout: None,
+                dry_run: false,
+                tui: false,
             }),
         };
 
@@ -12,30 +12,53 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::app::checkpoint::{spawn_checkpoint_writer, Checkpoint};
 use crate::app::consumer::{GlobalError, GlobalErrorConsumer};
+use crate::app::fetchlist::{spawn_progress_writer, FetchList, FetchListError};
 use crate::app::instruction::RunInstruction;
+use crate::app::live_events::LiveEvents;
 use crate::app::logging::configure_logging;
+use crate::app::metrics::{render_metrics, serve_metrics, MetricsResources, OriginQuery};
+use crate::app::recover::{requeue_stranded, RecoverError};
+use crate::app::terminal::{is_status_screen_available, spawn_status_screen};
+use crate::blacklist::{add_entries_audited, watch_blacklist_file};
 use crate::contexts::local::{LocalContext, LocalContextInitError};
 use crate::contexts::traits::*;
 use crate::contexts::worker::{WorkerContext, WorkerContextCreationError};
 use crate::contexts::Context;
-use crate::crawl::{crawl, ErrorConsumer, ExitState};
+use crate::cooperative::{listen, CooperativeError};
+use crate::crawl::{
+    crawl, finish_wave, log_unreachable_origins, CrawlEvent, CrawlEvents, ErrorConsumer,
+    ExitState, StopReason, WaveError, WaveTransition,
+};
 use crate::link_state::{LinkStateLike, LinkStateManager, RawLinkState};
-use crate::queue::{QueueError, SupportsForcedQueueElement, UrlQueue, UrlQueueElement};
+use crate::origin_metadata::OriginMetadataManager;
+use crate::queue::{
+    QueueError, QueueFileState, SupportsForcedQueueElement, UrlQueue, UrlQueueElement,
+};
 use crate::runtime::{
     AtraRuntime, GracefulShutdownWithGuard, OptionalAtraHandle, RuntimeContext, ShutdownReceiver,
+    ShutdownSender,
 };
-use crate::sync::{ContinueOrStop, WorkerBarrier};
-use crate::url::{AtraUri, UrlWithDepth};
+use crate::schemas::{AuditLog, AuditSource, OriginMetadataRecord, SeenFilterStatisticsRecord};
+use crate::seed::{SeedDefinition, StructuredSeeds};
+use crate::sync::{CancellationTokenProvider, ContinueOrStop, WorkerBarrier};
+use crate::tls_info::{log_tls_summary, TlsInfoManager};
+use crate::unique_content::UniqueContentManager;
+use crate::url::filter::UrlFilter;
+use crate::url::guard::GuardianEvents;
+use crate::url::{AtraOriginProvider, AtraUri, UrlWithDepth};
 use rocksdb::IteratorMode;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::io;
 use std::num::NonZeroUsize;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use thiserror::Error;
 use time::OffsetDateTime;
 use tokio::select;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
 use tokio::task::JoinSet;
 
 #[derive(Debug, Error)]
@@ -44,9 +67,18 @@ pub enum AtraRunError {
     #[error(transparent)] WorkerContextInitialisation(#[from] WorkerContextCreationError),
     #[error(transparent)] Crawl(#[from] GlobalError),
     #[error(transparent)] Queue(#[from] QueueError),
+    #[error(transparent)] Cooperative(#[from] CooperativeError),
+    #[error("Failed to serve the metrics: {0}")] Metrics(io::Error),
+    #[error("Failed to read the seeds: {0}")] Seeds(io::Error),
+    #[error("Failed to open the audit trail: {0}")] Audit(io::Error),
+    #[error(transparent)] FetchList(#[from] FetchListError),
+    #[error(transparent)] Recover(#[from] RecoverError),
+    #[error(transparent)] Wave(#[from] WaveError),
+    #[error("The crawl was already started.")] AlreadyStarted,
 }
 
 /// The application
+#[derive(Debug)]
 pub struct Atra {
     /// The runtime of atra
     handle: OptionalAtraHandle,
@@ -56,6 +88,18 @@ pub struct Atra {
 
     /// The hard shutdown
     shutdown: GracefulShutdownWithGuard,
+
+    /// The events emitted by all workers
+    events: CrawlEvents,
+
+    /// The events emitted by the guardian
+    guardian_events: GuardianEvents,
+
+    /// The audit trail of the running crawl
+    audit: OnceLock<AuditLog>,
+
+    /// The url filters asked after the configured ones
+    url_filters: Vec<Arc<dyn UrlFilter>>,
 }
 
 /// From tokio
@@ -94,13 +138,36 @@ impl Atra {
             mode,
             shutdown,
             handle,
+            events: CrawlEvents::new(),
+            guardian_events: GuardianEvents::new(),
+            audit: OnceLock::new(),
+            url_filters: Vec::new(),
         }
     }
 
+    /// Appends [filters] to the url filters of every crawl started by this application.
+    pub fn with_url_filters(mut self, filters: Vec<Arc<dyn UrlFilter>>) -> Self {
+        self.url_filters.extend(filters);
+        self
+    }
+
     pub fn shutdown(&self) -> &GracefulShutdownWithGuard {
         &self.shutdown
     }
 
+    pub fn events(&self) -> &CrawlEvents {
+        &self.events
+    }
+
+    pub fn guardian_events(&self) -> &GuardianEvents {
+        &self.guardian_events
+    }
+
+    /// The audit trail, available after the crawl was started.
+    pub fn audit(&self) -> Option<&AuditLog> {
+        self.audit.get()
+    }
+
     /// Returns the application, the runtime and the master shutdown token.
     /// Canceling the token immediately stops the application.
     pub fn build_with_runtime(mode: ApplicationMode) -> (Self, AtraRuntime) {
@@ -154,68 +221,342 @@ impl Atra {
     // }
 
     /// Start the application
-    pub async fn run(&mut self, instruction: RunInstruction) -> Result<(), AtraRunError> {
+    pub async fn run(&self, mut instruction: RunInstruction) -> Result<(), AtraRunError> {
+        if instruction.tui {
+            if is_status_screen_available() {
+                // The log lines would garble the status screen.
+                instruction.config.system.log_to_file = true;
+            } else {
+                println!("Not a user attended terminal. Falling back to plain logging.");
+                instruction.tui = false;
+            }
+        }
         configure_logging(&instruction.config);
         let result = self.run_without_logger(instruction).await;
         result
     }
 
-    async fn run_without_logger(
-        &mut self,
+    pub async fn run_without_logger(
+        &self,
         RunInstruction {
-            config,
+            mut config,
             seeds,
             recover_mode,
+            fetch_list,
+            tui,
             ..
         }: RunInstruction,
     ) -> Result<(), AtraRunError> {
+        // The overrides of structured seeds have to be part of the config of the context.
+        let structured = match &seeds {
+            Some(SeedDefinition::Structured(path)) => {
+                let structured = StructuredSeeds::read(path).map_err(AtraRunError::Seeds)?;
+                structured.apply_to(&mut config.crawl);
+                Some(structured)
+            }
+            _ => None,
+        };
+        let seeds_digest = seeds
+            .as_ref()
+            .map(SeedDefinition::digest)
+            .transpose()
+            .map_err(AtraRunError::Seeds)?;
         let shutdown_and_handle = RuntimeContext::new(self.shutdown.clone(), self.handle.clone());
-        let context = Arc::new(LocalContext::new(config, &shutdown_and_handle)?);
+        let mut context = LocalContext::new(config, &shutdown_and_handle)?
+            .with_crawl_events(self.events.clone())
+            .with_guardian_events(self.guardian_events.clone())
+            .with_url_filters(self.url_filters.iter().cloned());
+        if let Some(digest) = seeds_digest {
+            context = context.with_seeds_digest(digest);
+        }
+        let context = Arc::new(context);
         drop(shutdown_and_handle);
+        let audit =
+            AuditLog::open(context.configs().paths.root_path()).map_err(AtraRunError::Audit)?;
+        let audit = self.audit.get_or_init(|| audit).clone();
+
+        // Stops serving the metrics when the crawl returns.
+        let _metrics_guard = match context.configs().system.metrics.as_ref() {
+            Some(cfg) => {
+                let metrics_shutdown = self.shutdown.get().child().create_child();
+                let render_context = context.clone();
+                let patch_context = context.clone();
+                let patch_audit = audit.clone();
+                let lookup_context = context.clone();
+                let query_context = context.clone();
+                serve_metrics(
+                    cfg.address,
+                    move || {
+                        let context = render_context.clone();
+                        async move { render_metrics(context.as_ref()).await }
+                    },
+                    move |patterns, peer| {
+                        let context = patch_context.clone();
+                        let audit = patch_audit.clone();
+                        async move {
+                            add_entries_audited(
+                                context.get_blacklist_manager(),
+                                patterns,
+                                AuditSource::Http {
+                                    peer: peer.to_string(),
+                                },
+                                Some(&audit),
+                            )
+                            .await
+                        }
+                    },
+                    move |origin| {
+                        lookup_context
+                            .get_origin_metadata_manager()
+                            .get(&origin)
+                            .map(|metadata| OriginMetadataRecord::new(&origin, &metadata))
+                    },
+                    move |query| {
+                        let context = query_context.clone();
+                        // Scans the link states away from the workers.
+                        async move {
+                            tokio::task::spawn_blocking(move || {
+                                let link_states = context.get_link_state_manager();
+                                match query {
+                                    OriginQuery::Summaries { offset, limit } => link_states
+                                        .origin_summaries(offset, limit)
+                                        .map(serde_json::to_value),
+                                    OriginQuery::Urls {
+                                        origin,
+                                        kind,
+                                        offset,
+                                        limit,
+                                    } => link_states
+                                        .origin_urls(&origin, kind, offset, limit)
+                                        .map(serde_json::to_value),
+                                }
+                            })
+                            .await
+                            .map_err(|err| err.to_string())?
+                            .map_err(|err| err.to_string())?
+                            .map_err(|err| err.to_string())
+                        }
+                    },
+                    MetricsResources {
+                        audit: Some(audit.clone()),
+                        events: Some(LiveEvents::new(
+                            self.events.clone(),
+                            self.guardian_events.clone(),
+                        )),
+                        pause: Some(self.events.pause().clone()),
+                        control_token: cfg.control_token.clone(),
+                    },
+                    metrics_shutdown.clone(),
+                )
+                .await
+                .map_err(AtraRunError::Metrics)?;
+                Some(metrics_shutdown.clone_token().drop_guard())
+            }
+            None => None,
+        };
+
+        // Stops watching the blacklist file when the crawl returns.
+        let _blacklist_guard = context.configs().system.blacklist_watch.clone().map(|cfg| {
+            let watch_shutdown = self.shutdown.get().child().create_child();
+            let watch_context = context.clone();
+            let watch_audit = audit.clone();
+            let shutdown = watch_shutdown.clone();
+            tokio::spawn(async move {
+                watch_blacklist_file(
+                    watch_context.get_blacklist_manager(),
+                    &cfg.path,
+                    cfg.interval.unsigned_abs(),
+                    Some(&watch_audit),
+                    shutdown,
+                )
+                .await
+            });
+            watch_shutdown.clone_token().drop_guard()
+        });
 
-        if let Some(seeds) = seeds {
+        if let Some(structured) = structured {
+            structured.fill_queue(context.url_queue()).await?;
+        } else if let Some(seeds) = seeds {
             seeds.fill_queue(context.url_queue()).await;
         }
+        // A clean queue already contains the urls in progress, a recovered queue only lacks
+        // them and anything else has to be rebuilt from the link states.
         if recover_mode {
             let _guard = self.shutdown.guard();
             let queue = context.url_queue();
-            for (k, v) in context
-                .get_link_state_manager()
-                .iter(IteratorMode::Start)
-                .filter_map(|value| value.ok())
+            let state = queue.opened_state();
+            let journal = context.dequeue_journal();
+            let has_open_entries =
+                journal.is_some_and(|journal| !journal.open_entries().is_empty());
+            let root = context.configs().paths.root_path();
+            let checkpoint = Checkpoint::read_from(root).unwrap_or_else(|err| {
+                log::warn!("Ignore the damaged checkpoint of {root}: {err}");
+                None
+            });
+            if let Some(ref checkpoint) = checkpoint {
+                log::info!(
+                    "Found a checkpoint of {} with {} urls in progress on {} reserved origins.",
+                    checkpoint.created_at,
+                    checkpoint.in_flight.len(),
+                    checkpoint.reserved_origins.len()
+                );
+            }
+            let has_checkpointed_urls = checkpoint
+                .as_ref()
+                .is_some_and(|checkpoint| !checkpoint.in_flight.is_empty());
+            if !state.is_usable() {
+                log::warn!("The queue is {state}, recover it from the link states.");
+                for (k, v) in context
+                    .get_link_state_manager()
+                    .iter(IteratorMode::Start)
+                    .filter_map(|value| value.ok())
+                {
+                    let raw = unsafe { RawLinkState::from_slice_unchecked(v.as_ref()) };
+                    let uri: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
+                    if !raw.kind().is_processed_and_stored() {
+                        queue.force_enqueue(UrlQueueElement::new(
+                            raw.is_seed().is_yes(),
+                            0,
+                            false,
+                            UrlWithDepth::new(uri, raw.depth()),
+                        ))?;
+                    }
+                }
+                if let Some(journal) = journal {
+                    journal.clear().map_err(RecoverError::from)?;
+                }
+            } else if has_open_entries
+                || has_checkpointed_urls
+                || !matches!(state, QueueFileState::Clean)
             {
-                let raw = unsafe { RawLinkState::from_slice_unchecked(v.as_ref()) };
-                let uri: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
-
-                if !raw.kind().is_processed_and_stored() {
-                    queue.force_enqueue(UrlQueueElement::new(
-                        raw.is_seed().is_yes(),
-                        0,
-                        false,
-                        UrlWithDepth::new(uri, raw.depth()),
-                    ))?;
+                if let Some(journal) = journal {
+                    log::info!("Recover with the queue ({state}), requeue the urls in progress.");
+                    let report = requeue_stranded(
+                        context.get_link_state_manager(),
+                        queue,
+                        journal,
+                        checkpoint.as_ref(),
+                        &context.configs().system.recovery,
+                        OffsetDateTime::now_utc(),
+                    )
+                    .await?;
+                    log::info!("{report}");
                 }
             }
+            // Its urls are queued again or were rebuilt from the link states.
+            if checkpoint.is_some() {
+                Checkpoint::remove(root).map_err(RecoverError::Checkpoint)?;
+            }
         }
+        let fetch_list = match fetch_list {
+            Some(path) => {
+                let _guard = self.shutdown.guard();
+                let list = Arc::new(FetchList::read(&path).map_err(FetchListError::from)?);
+                let progress = list.fill_queue(context.as_ref()).await?;
+                progress
+                    .write_to(context.configs().paths.root_path())
+                    .map_err(FetchListError::from)?;
+                log::info!(
+                    "The fetch list {path} has {} urls, {} are pending.",
+                    progress.total,
+                    progress.pending
+                );
+                Some(list)
+            }
+            None => None,
+        };
+        // Stops writing checkpoints when the crawl returns.
+        let checkpoint_interval = context.configs().system.recovery.checkpoint_interval;
+        let _checkpoint_guard = checkpoint_interval.map(|interval| {
+            let checkpoint_shutdown = self.shutdown.get().child().create_child();
+            spawn_checkpoint_writer(
+                context.clone(),
+                context.configs().paths.root_path().to_path_buf(),
+                interval.unsigned_abs(),
+                checkpoint_shutdown.clone(),
+            );
+            checkpoint_shutdown.clone_token().drop_guard()
+        });
+        // Stops writing the progress when the crawl returns.
+        let _progress_guard = fetch_list.as_ref().map(|list| {
+            let progress_shutdown = self.shutdown.get().child().create_child();
+            spawn_progress_writer(list.clone(), context.clone(), progress_shutdown.clone());
+            progress_shutdown.clone_token().drop_guard()
+        });
+        // Stops the crawl like CTRL-C after the maximal duration.
+        let _duration_guard = context.crawl_limits().max_duration().map(|duration| {
+            let duration_shutdown = self.shutdown.get().child().create_child();
+            let shutdown = duration_shutdown.clone();
+            let context = context.clone();
+            tokio::spawn(async move {
+                select! {
+                    _ = shutdown.wait() => {}
+                    _ = tokio::time::sleep(duration.unsigned_abs()) => {
+                        context.crawl_limits().stop(StopReason::MaxDuration(duration));
+                    }
+                }
+            });
+            duration_shutdown.clone_token().drop_guard()
+        });
+        // Toggles the pause of the workers with SIGUSR1 until the crawl returns.
+        #[cfg(unix)]
+        let _pause_signal_guard = {
+            let signal_shutdown = self.shutdown.get().child().create_child();
+            let shutdown = signal_shutdown.clone();
+            let pause = self.events.pause().clone();
+            match signal(SignalKind::user_defined1()) {
+                Ok(mut signals) => {
+                    tokio::spawn(async move {
+                        loop {
+                            select! {
+                                _ = shutdown.wait() => break,
+                                received = signals.recv() => {
+                                    if received.is_none() {
+                                        break;
+                                    }
+                                    if pause.toggle() {
+                                        log::info!("Paused the crawl by SIGUSR1.");
+                                    } else {
+                                        log::info!("Resumed the crawl by SIGUSR1.");
+                                    }
+                                }
+                            }
+                        }
+                    });
+                }
+                Err(err) => log::warn!("Failed to listen for SIGUSR1: {err}"),
+            }
+            signal_shutdown.clone_token().drop_guard()
+        };
+        // Leaves the status screen when the crawl returns or is shut down.
+        let _tui_guard = tui.then(|| {
+            let tui_shutdown = self.shutdown.get().child().create_child();
+            spawn_status_screen(context.clone(), tui_shutdown.clone());
+            tui_shutdown.clone_token().drop_guard()
+        });
         if self.shutdown.get().child().is_shutdown() {
             log::warn!("Shutdown before doing anything!");
             return Ok(());
         }
-        match self.mode {
+        let result = match self.mode {
             ApplicationMode::Single => {
                 let start = OffsetDateTime::now_utc();
                 let mut recrawl_ct = 0;
                 loop {
                     let guard = self.shutdown().guard();
                     let shutdown = self.shutdown.get().child().clone();
-                    let barrier = WorkerBarrier::new_with_dependence_to(
+                    let barrier = Arc::new(WorkerBarrier::new_with_dependence_to(
                         unsafe { NonZeroUsize::new_unchecked(1) },
                         &shutdown,
-                    );
+                    ));
+                    context
+                        .crawl_events()
+                        .metrics()
+                        .observe_workers(barrier.clone());
                     let value = match crawl(
                         WorkerContext::create(0, recrawl_ct, context.clone())?,
                         shutdown,
-                        Arc::new(barrier),
+                        barrier,
                         GlobalErrorConsumer::new(),
                     )
                     .await
@@ -240,6 +581,28 @@ impl Atra {
                             .map(|value| value.to_string())
                             .unwrap_or("# ERROR COUNTING#".to_string())
                     );
+                    let normalization_stats = context.url_normalizer().stats();
+                    log::info!(
+                        "Normalized {} urls, exceptions suppressed the normalization of {} urls ({} by origin, {} by signature)",
+                        normalization_stats.normalized(),
+                        normalization_stats.suppressed(),
+                        normalization_stats.suppressed_by_origin(),
+                        normalization_stats.suppressed_by_signature()
+                    );
+                    let unique_content = context.get_unique_content_manager().global_counts();
+                    log::info!(
+                        "Fetched {} pages with a payload, {} of them are unique",
+                        unique_content.fetched,
+                        unique_content.unique_payloads
+                    );
+                    log_unreachable_origins(context.crawl_events().metrics());
+                    if let Some(tls_capture) = context.configs().crawl.tls_capture.as_ref() {
+                        log_tls_summary(
+                            &context.get_tls_info_manager().origins(),
+                            tls_capture,
+                            OffsetDateTime::now_utc(),
+                        );
+                    }
 
                     if self.shutdown.get().is_shutdown() {
                         log::info!("Shutting down.");
@@ -256,6 +619,17 @@ impl Atra {
                         }
                     }
 
+                    let transition =
+                        finish_wave(context.as_ref(), self.shutdown.get().child().clone()).await?;
+                    match transition {
+                        WaveTransition::Started(_) => continue,
+                        WaveTransition::Shutdown => {
+                            log::info!("Shutting down.");
+                            break;
+                        }
+                        WaveTransition::Finished => {}
+                    }
+
                     if self.try_recrawls(context.as_ref()).await {
                         recrawl_ct += 1;
                     } else {
@@ -268,20 +642,34 @@ impl Atra {
             ApplicationMode::Multi(worker) => {
                 let start = OffsetDateTime::now_utc();
                 let mut recrawl_ct = 0;
+                let worker_count = worker.unwrap_or(num_cpus());
+                let listener_shutdown = self.shutdown.get().child().create_child();
+                let cooperative = match context.configs().system.cooperative.as_ref() {
+                    Some(cfg) => Some(listen(
+                        cfg,
+                        worker_count.get(),
+                        context.clone(),
+                        listener_shutdown.clone(),
+                    )?),
+                    None => None,
+                };
 
                 loop {
                     let mut set = JoinSet::new();
-                    let worker_count = worker.unwrap_or(num_cpus());
                     let barrier = Arc::new(WorkerBarrier::new_with_dependence_to(
                         worker_count,
                         self.shutdown.get().child(),
                     ));
+                    context
+                        .crawl_events()
+                        .metrics()
+                        .observe_workers(barrier.clone());
                     for i in 0..worker_count.get() {
                         log::info!("Spawn Worker: {i}");
                         let b = barrier.clone();
                         let shutdown = self.shutdown.clone();
                         let context = WorkerContext::create(i, recrawl_ct, context.clone())?;
-                        set.spawn(async move {
+                        let worker = async move {
                             // This has to be a drop guard to make sure, that we do not fail to wait for a thread.
                             let shutdown = shutdown;
                             let context = context;
@@ -337,15 +725,21 @@ impl Atra {
 
                             b.trigger_cancellation();
                             (i, state)
-                        });
+                        };
+                        // A panicked worker leaves the barrier, the others do not wait for it.
+                        let barrier = barrier.clone();
+                        set.spawn(async move { barrier.run_worker(i, worker).await });
                     }
                     let mut is_stop = false;
                     while let Some(res) = set.join_next().await {
                         match res {
-                            Ok((i, s)) => {
+                            Ok(Some((i, s))) => {
                                 log::info!("Stopped worker {i} due to {s}.");
                                 is_stop |= matches!(s, ExitState::Shutdown)
                             }
+                            Ok(None) => {
+                                log::warn!("A worker panicked, the others continue without it.");
+                            }
                             Err(err) => {
                                 log::error!("Thread join error: {err}");
                                 log::error!("Trying to shut down in a safe manner...");
@@ -368,12 +762,54 @@ impl Atra {
                             .map(|value| value.to_string())
                             .unwrap_or("# ERROR COUNTING#".to_string())
                     );
+                    let normalization_stats = context.url_normalizer().stats();
+                    log::info!(
+                        "Normalized {} urls, exceptions suppressed the normalization of {} urls ({} by origin, {} by signature)",
+                        normalization_stats.normalized(),
+                        normalization_stats.suppressed(),
+                        normalization_stats.suppressed_by_origin(),
+                        normalization_stats.suppressed_by_signature()
+                    );
+                    let unique_content = context.get_unique_content_manager().global_counts();
+                    log::info!(
+                        "Fetched {} pages with a payload, {} of them are unique",
+                        unique_content.fetched,
+                        unique_content.unique_payloads
+                    );
+                    log_unreachable_origins(context.crawl_events().metrics());
+                    if let Some(tls_capture) = context.configs().crawl.tls_capture.as_ref() {
+                        log_tls_summary(
+                            &context.get_tls_info_manager().origins(),
+                            tls_capture,
+                            OffsetDateTime::now_utc(),
+                        );
+                    }
 
                     if is_stop || self.shutdown.get().is_shutdown() {
                         log::info!("Stopped by shutdown.");
                         break;
                     }
 
+                    if let Some((primary, _)) = cooperative.as_ref() {
+                        // The urls leased to secondaries may still discover new links.
+                        primary.wait_until_idle().await;
+                        if !context.url_queue().is_empty().await {
+                            log::info!("The secondaries discovered new urls, continue crawling.");
+                            continue;
+                        }
+                    }
+
+                    let transition =
+                        finish_wave(context.as_ref(), self.shutdown.get().child().clone()).await?;
+                    match transition {
+                        WaveTransition::Started(_) => continue,
+                        WaveTransition::Shutdown => {
+                            log::info!("Stopped by shutdown.");
+                            break;
+                        }
+                        WaveTransition::Finished => {}
+                    }
+
                     log::info!("Start to check if we have some kind of recrawl.");
 
                     if self.try_recrawls(context.as_ref()).await {
@@ -383,15 +819,49 @@ impl Atra {
                         break;
                     }
                 }
+                if let Some((_, handle)) = cooperative {
+                    listener_shutdown.shutdown();
+                    if let Err(err) = handle.await {
+                        log::error!("Thread join error: {err}");
+                    }
+                }
                 Ok(())
             }
+        };
+        context.crawl_events().emit(CrawlEvent::Finished);
+        // Written whatever stopped the crawl, the counters outlive panicked workers.
+        let mut statistics = context.crawl_events().statistics().snapshot();
+        statistics.stop_reason = context
+            .crawl_limits()
+            .stop_reason()
+            .map(|reason| reason.to_string());
+        statistics.paused_millis =
+            u64::try_from(context.crawl_events().pause().paused_for().as_millis())
+                .unwrap_or(u64::MAX);
+        statistics.seen_filter = context
+            .get_link_state_manager()
+            .seen_filter_stats()
+            .map(|stats| SeenFilterStatisticsRecord {
+                keys: stats.keys,
+                layers: stats.layers,
+                estimated_false_positive_rate: stats.estimated_false_positive_rate,
+                skipped_reads: stats.skipped_reads,
+                false_positives: stats.false_positives,
+            });
+        match statistics.write_to(context.configs().paths.root_path()) {
+            Ok(()) => log::info!("Crawl statistics:\n{statistics}"),
+            Err(err) => log::error!("Failed to write the crawl statistics: {err}"),
         }
+        if let Some(list) = fetch_list {
+            list.write_progress(context.as_ref())?;
+        }
+        result
     }
 
     /// Returns true if there are more thins to crawl
     async fn try_recrawls<C>(&self, context: &C) -> bool
     where
-        C: SupportsUrlQueue + SupportsLinkState,
+        C: SupportsUrlQueue + SupportsLinkState + SupportsUrlPriority,
     {
         log::info!("Start to check if we have some kind of recrawl.");
 
@@ -401,11 +871,16 @@ impl Atra {
             .await
         {
             let queue = context.url_queue();
+            let url_priority = context.url_priority();
             context
                 .get_link_state_manager()
                 .collect_recrawlable_links(|is_seed, url| {
+                    let priority = url_priority.score(&url, url.atra_origin().as_ref(), true);
                     queue
-                        .force_enqueue(UrlQueueElement::new(is_seed.is_yes(), 0, false, url))
+                        .force_enqueue(
+                            UrlQueueElement::new(is_seed.is_yes(), 0, false, url)
+                                .with_priority(priority),
+                        )
                         .unwrap()
                 })
                 .await;
@@ -508,7 +983,7 @@ mod test {
     }
 
     async fn execute_crawl(config: AtraConfig, seeds: Option<SeedDefinition>) {
-        let (mut app, shutdown) = Atra::create_contained_with(ApplicationMode::Single, None);
+        let (app, shutdown) = Atra::create_contained_with(ApplicationMode::Single, None);
 
         let barrier_copy = shutdown.clone();
         let a = async move {
@@ -524,7 +999,9 @@ mod test {
                 config,
                 seeds,
                 recover_mode: false,
+                fetch_list: None,
                 mode: ApplicationMode::Single,
+                tui: false,
             })
             .await
             .expect("no errors");
@@ -571,7 +1048,7 @@ mod test {
             .filter_map(|value| value.ok())
             .map(|(k, v)| {
                 let k: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
-                let v: SlimCrawlResult = bincode::deserialize(v.as_ref()).unwrap();
+                let v = SlimCrawlResult::decode(v.as_ref()).unwrap();
                 (k, v)
             })
         {
@@ -676,7 +1153,7 @@ mod test {
     #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
     async fn can_multithread() {
         init();
-        let (mut app, shutdown) = Atra::create_contained_with(ApplicationMode::Multi(None), None);
+        let (app, shutdown) = Atra::create_contained_with(ApplicationMode::Multi(None), None);
 
         let mut config: CrawlConfig = CrawlConfig::default();
         config.budget.default = BudgetSetting::Absolute {
@@ -704,6 +1181,8 @@ mod test {
                 "https://ticktoo.com/".to_string(),
             ])),
             recover_mode: false,
+            fetch_list: None,
+            tui: false,
             mode: ApplicationMode::Multi(None),
         })
         .await
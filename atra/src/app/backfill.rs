@@ -0,0 +1,520 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess, SupportsGdbrRegistry};
+use crate::contexts::BaseContext;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{score_gdbr, BackfillField, BackfillNote, SlimCrawlResult};
+use crate::data::{process, RawData};
+use crate::database::{open_db, DatabaseError, OpenDBError};
+use crate::fetching::ResponseData;
+use crate::gdbr::identifier::{GdbrIdentifierRegistry, InitHelper};
+use crate::io::errors::ErrorWithPath;
+use crate::io::fs::FileSystemAccess;
+use crate::toolkit::detect_language;
+use camino::Utf8PathBuf;
+use liblinear::solver::L2R_L2LOSS_SVR;
+use rocksdb::{Direction, IteratorMode};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use svm::error::SvmCreationError;
+use text_processing::stopword_registry::StopWordRegistry;
+use text_processing::tf_idf::{Idf, Tf};
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// The name of the progress marker in the root of the session.
+const PROGRESS_MARKER: &str = "backfill.progress";
+/// The number of scanned entries between two updates of the progress marker.
+const PROGRESS_INTERVAL: usize = 1_000;
+
+/// Errors that stop a backfill.
+#[derive(Debug, Error)]
+pub enum BackfillError {
+    #[error("The session at {0} is currently crawled, stop the crawl before the backfill.")]
+    SessionInUse(Utf8PathBuf),
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    IoWithPath(#[from] ErrorWithPath),
+    #[error(transparent)]
+    Svm(#[from] SvmCreationError<Idf>),
+}
+
+/// The result of a backfill.
+#[derive(Debug, Default)]
+pub struct BackfillReport {
+    /// True iff the backfill continued at a progress marker.
+    pub resumed: bool,
+    /// The number of scanned entries.
+    pub scanned: usize,
+    /// The entries without missing fields.
+    pub complete: usize,
+    /// The entries with at least one backfilled field.
+    pub updated: usize,
+    /// The number of restored languages.
+    pub languages: usize,
+    /// The number of restored GDBR scores.
+    pub gdbr_scores: usize,
+    /// The entries skipped because their body is not readable.
+    pub unreadable: usize,
+    /// The entries of an older layout rewritten in the current layout.
+    pub migrated: usize,
+    /// The entries skipped because they can not be decoded.
+    pub undecodable: usize,
+}
+
+impl Display for BackfillReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.resumed {
+            writeln!(f, "Resumed at the progress marker.")?;
+        }
+        writeln!(f, "Scanned:          {}", self.scanned)?;
+        writeln!(f, "Complete:         {}", self.complete)?;
+        writeln!(f, "Updated:          {}", self.updated)?;
+        writeln!(f, "    Languages:    {}", self.languages)?;
+        writeln!(f, "    GDBR scores:  {}", self.gdbr_scores)?;
+        writeln!(f, "Unreadable:       {}", self.unreadable)?;
+        writeln!(f, "Migrated:         {}", self.migrated)?;
+        writeln!(f, "Undecodable:      {}", self.undecodable)
+    }
+}
+
+/// The minimal context needed to decode and classify the stored bodies.
+pub(super) struct BackfillContext {
+    config: Config,
+    fs: FileSystemAccess,
+    gdbr_registry: Option<GdbrIdentifierRegistry<Tf, Idf, L2R_L2LOSS_SVR>>,
+}
+
+impl BackfillContext {
+    pub(super) fn new(config: &Config) -> Result<Self, BackfillError> {
+        let fs = FileSystemAccess::new(
+            config.session.service.clone(),
+            config.session.collection.clone(),
+            config.session.crawl_job_id,
+            config.paths.root_path().to_path_buf(),
+            config.paths.dir_big_files(),
+            config.session.dat_file_layout,
+        )?;
+        let gdbr_registry = if let Some(ref cfg) = config.crawl.gbdr {
+            let stop_word_registry = config
+                .crawl
+                .stopword_registry
+                .as_ref()
+                .map(StopWordRegistry::initialize)
+                .transpose()?;
+            let helper = InitHelper {
+                gdbr_config: Some(cfg),
+                stop_word_registry: stop_word_registry.as_ref(),
+            };
+            GdbrIdentifierRegistry::new_from_config(&helper)?
+        } else {
+            None
+        };
+        Ok(Self {
+            config: config.clone(),
+            fs,
+            gdbr_registry,
+        })
+    }
+}
+
+impl BaseContext for BackfillContext {}
+
+impl SupportsConfigs for BackfillContext {
+    fn configs(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl SupportsFileSystemAccess for BackfillContext {
+    type FileSystem = FileSystemAccess;
+
+    fn fs(&self) -> &FileSystemAccess {
+        &self.fs
+    }
+}
+
+impl SupportsGdbrRegistry for BackfillContext {
+    type Registry = GdbrIdentifierRegistry<Tf, Idf, L2R_L2LOSS_SVR>;
+
+    fn gdbr_registry(&self) -> Option<&Self::Registry> {
+        self.gdbr_registry.as_ref()
+    }
+}
+
+/// Returns the requested [fields] that are missing in the meta of [entry].
+/// A field is not missing if an earlier backfill already tried to compute it.
+fn missing_fields(entry: &SlimCrawlResult, fields: &[BackfillField]) -> Vec<BackfillField> {
+    fields
+        .iter()
+        .copied()
+        .filter(|field| {
+            let is_set = match field {
+                BackfillField::Language => entry.meta.language.is_some(),
+                BackfillField::Gdbr => entry.meta.gdbr_score.is_some(),
+            };
+            !is_set
+                && !entry
+                    .meta
+                    .backfill
+                    .as_ref()
+                    .is_some_and(|note| note.contains(*field))
+        })
+        .collect()
+}
+
+/// Computes the [missing] fields of [entry] from its body.
+/// Returns false if the body can not be read or decoded.
+async fn backfill_entry(
+    context: &BackfillContext,
+    entry: &mut SlimCrawlResult,
+    missing: Vec<BackfillField>,
+    report: &mut BackfillReport,
+) -> bool {
+    let content = match unsafe { entry.clone().inflate_unchecked() } {
+        Ok(result) => result.content,
+        Err(err) => {
+            log::warn!("Failed to read the body of {}: {err}", entry.meta.url);
+            return false;
+        }
+    };
+    match &content {
+        RawData::None => return false,
+        RawData::ExternalFile { path } if !path.exists() => {
+            log::warn!("The body of {} at {path} is missing.", entry.meta.url);
+            return false;
+        }
+        _ => {}
+    }
+    let meta = &mut entry.meta;
+    let response = ResponseData::new(
+        content,
+        meta.url.clone(),
+        meta.headers.clone(),
+        meta.status_code,
+        meta.final_redirect_destination.clone(),
+    );
+    let decoded = match process(context, &response, &meta.file_information).await {
+        Ok(decoded) => decoded,
+        Err(err) => {
+            log::warn!("Failed to decode the body of {}: {err}", meta.url);
+            return false;
+        }
+    };
+    for field in &missing {
+        match field {
+            BackfillField::Language => {
+                meta.set_language(
+                    detect_language(context, &meta.file_information, &decoded)
+                        .ok()
+                        .flatten(),
+                );
+                if meta.language.is_some() {
+                    report.languages += 1;
+                }
+            }
+            BackfillField::Gdbr => {
+                meta.gdbr_score = score_gdbr(
+                    context,
+                    &meta.file_information,
+                    &decoded,
+                    meta.language_information().as_ref(),
+                );
+                if meta.gdbr_score.is_some() {
+                    report.gdbr_scores += 1;
+                }
+            }
+        }
+    }
+    let mut fields = meta
+        .backfill
+        .take()
+        .map(|note| note.fields)
+        .unwrap_or_default();
+    fields.extend(missing);
+    meta.backfill = Some(BackfillNote {
+        atra_version: env!("CARGO_PKG_VERSION").to_string(),
+        backfilled_at: OffsetDateTime::now_utc(),
+        fields,
+    });
+    true
+}
+
+/// Computes the requested [fields] missing in the metas of the crawl db of [config] from the
+/// stored bodies and updates the entries in place. If no field is requested, every field is
+/// backfilled. Entries with unreadable bodies are skipped and can be retried later. Entries
+/// of an older layout are rewritten in the current layout.
+///
+/// The crawl db is streamed and every entry is written on its own. The last scanned entry
+/// is recorded in `backfill.progress`, an interrupted backfill continues after it. A session
+/// that is currently crawled holds the lock of its database and is refused.
+pub(crate) async fn backfill(
+    config: &Config,
+    fields: &[BackfillField],
+) -> Result<BackfillReport, BackfillError> {
+    let db = match open_db(config.paths.dir_database()) {
+        Ok(db) => Arc::new(db),
+        Err(OpenDBError::RocksDB(err)) if err.to_string().to_lowercase().contains("lock") => {
+            return Err(BackfillError::SessionInUse(
+                config.paths.root_path().to_path_buf(),
+            ))
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let crawl_db = CrawlDB::new(db, config)?;
+    let context = BackfillContext::new(config)?;
+
+    let mut fields = if fields.is_empty() {
+        vec![BackfillField::Language, BackfillField::Gdbr]
+    } else {
+        fields.to_vec()
+    };
+    if context.gdbr_registry.is_none() && fields.contains(&BackfillField::Gdbr) {
+        log::warn!("No gdbr identifier configured, the GDBR scores are not backfilled.");
+        fields.retain(|field| *field != BackfillField::Gdbr);
+    }
+
+    let marker = config.paths.root_path().join(PROGRESS_MARKER);
+    let resume_at = if marker.exists() {
+        Some(std::fs::read(&marker)?)
+    } else {
+        None
+    };
+    let mut report = BackfillReport {
+        resumed: resume_at.is_some(),
+        ..BackfillReport::default()
+    };
+    let mode = match resume_at {
+        Some(ref key) => IteratorMode::From(key, Direction::Forward),
+        None => IteratorMode::Start,
+    };
+
+    for value in crawl_db.iter(mode) {
+        let (k, v) = value?;
+        if resume_at.as_deref() == Some(k.as_ref()) {
+            continue;
+        }
+        report.scanned += 1;
+        if report.scanned % PROGRESS_INTERVAL == 0 {
+            std::fs::write(&marker, &k)?;
+        }
+        let legacy = SlimCrawlResult::is_legacy(&v);
+        let mut entry = match SlimCrawlResult::decode(&v) {
+            Ok(entry) => entry,
+            Err(err) => {
+                log::warn!(
+                    "Failed to read the entry of {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                report.undecodable += 1;
+                continue;
+            }
+        };
+        let missing = missing_fields(&entry, &fields);
+        let updated = if missing.is_empty() {
+            report.complete += 1;
+            false
+        } else if backfill_entry(&context, &mut entry, missing, &mut report).await {
+            report.updated += 1;
+            true
+        } else {
+            report.unreadable += 1;
+            false
+        };
+        if updated || legacy {
+            crawl_db.add(&entry)?;
+        }
+        if legacy {
+            report.migrated += 1;
+        }
+    }
+
+    if marker.exists() {
+        std::fs::remove_file(&marker)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::backfill::{backfill, BackfillError, PROGRESS_MARKER};
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::{create_test_data, encode_legacy};
+    use crate::crawl::{BackfillField, SlimCrawlResult, StoredDataHint};
+    use crate::data::RawVecData;
+    use crate::database::{open_db, CRAWL_DB_CF};
+    use crate::toolkit::LanguageInformation;
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use std::sync::Arc;
+
+    const TEXT: &str = "<html><body><p>The quick brown fox jumps over the lazy dog. \
+        This page was crawled by an older version and is missing its language.</p></body></html>";
+
+    fn entry(url: &str, hint: Option<StoredDataHint>) -> SlimCrawlResult {
+        let mut result = create_test_data(
+            UrlWithDepth::from_url(url).unwrap(),
+            Some(RawVecData::from_vec(TEXT.as_bytes().to_vec())),
+        );
+        result.meta.language = None;
+        let hint = hint.unwrap_or_else(|| StoredDataHint::InMemory(TEXT.as_bytes().to_vec()));
+        SlimCrawlResult::new(&result, hint)
+    }
+
+    /// Writes [entries] in the layout of a crawl of an older version.
+    fn create_fixture(config: &Config, entries: &[SlimCrawlResult]) {
+        let db = open_db(config.paths.dir_database()).unwrap();
+        let cf = db.cf_handle(CRAWL_DB_CF).unwrap();
+        for entry in entries {
+            db.put_cf(&cf, entry.meta.url.url.as_bytes(), encode_legacy(entry))
+                .unwrap();
+        }
+    }
+
+    fn read_raw(config: &Config, url: &str) -> Vec<u8> {
+        let db = open_db(config.paths.dir_database()).unwrap();
+        let cf = db.cf_handle(CRAWL_DB_CF).unwrap();
+        db.get_cf(&cf, url.as_bytes()).unwrap().unwrap()
+    }
+
+    fn read(config: &Config, url: &str) -> SlimCrawlResult {
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, config).unwrap();
+        crawl_db
+            .get(&UrlWithDepth::from_url(url).unwrap())
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn restores_the_missing_languages() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+
+        let stripped = entry("https://www.example.com/stripped", None);
+        let unreadable = entry(
+            "https://www.example.com/unreadable",
+            Some(StoredDataHint::None),
+        );
+        let mut complete = entry("https://www.example.com/complete", None);
+        complete.meta.language = Some(LanguageInformation::DEU);
+        create_fixture(
+            &config,
+            &[stripped.clone(), unreadable.clone(), complete.clone()],
+        );
+        {
+            let db = open_db(config.paths.dir_database()).unwrap();
+            let cf = db.cf_handle(CRAWL_DB_CF).unwrap();
+            db.put_cf(&cf, b"https://www.example.com/garbage", b"garbage")
+                .unwrap();
+        }
+
+        let report = backfill(&config, &[BackfillField::Language]).await.unwrap();
+        assert!(!report.resumed);
+        assert_eq!(4, report.scanned);
+        assert_eq!(1, report.complete);
+        assert_eq!(1, report.updated);
+        assert_eq!(1, report.languages);
+        assert_eq!(1, report.unreadable);
+        assert_eq!(3, report.migrated);
+        assert_eq!(1, report.undecodable);
+        for url in [
+            "https://www.example.com/stripped",
+            "https://www.example.com/unreadable",
+            "https://www.example.com/complete",
+        ] {
+            assert!(!SlimCrawlResult::is_legacy(&read_raw(&config, url)));
+        }
+
+        let restored = read(&config, "https://www.example.com/stripped");
+        assert_eq!(
+            Some(LanguageInformation::ENG.lang()),
+            restored.meta.language.map(|value| value.lang())
+        );
+        let note = restored.meta.backfill.as_ref().unwrap();
+        assert_eq!(env!("CARGO_PKG_VERSION"), note.atra_version);
+        assert_eq!(vec![BackfillField::Language], note.fields);
+        let mut untouched = restored.clone();
+        untouched.meta.language = None;
+        untouched.meta.backfill = None;
+        assert_eq!(stripped, untouched);
+
+        assert_eq!(
+            unreadable,
+            read(&config, "https://www.example.com/unreadable")
+        );
+        assert_eq!(complete, read(&config, "https://www.example.com/complete"));
+        assert!(!root.path().join(PROGRESS_MARKER).exists());
+
+        // The note prevents a second computation.
+        let report = backfill(&config, &[BackfillField::Language]).await.unwrap();
+        assert_eq!(0, report.updated);
+        assert_eq!(2, report.complete);
+        assert_eq!(0, report.migrated);
+        assert_eq!(1, report.undecodable);
+        assert_eq!(restored, read(&config, "https://www.example.com/stripped"));
+    }
+
+    #[tokio::test]
+    async fn resumes_after_the_progress_marker() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+
+        let first = entry("https://www.example.com/a", None);
+        let second = entry("https://www.example.com/b", None);
+        create_fixture(&config, &[first.clone(), second.clone()]);
+        std::fs::write(
+            root.path().join(PROGRESS_MARKER),
+            first.meta.url.url.as_bytes(),
+        )
+        .unwrap();
+
+        let report = backfill(&config, &[]).await.unwrap();
+        assert!(report.resumed);
+        assert_eq!(1, report.scanned);
+        assert_eq!(1, report.updated);
+        assert_eq!(first, read(&config, "https://www.example.com/a"));
+        assert!(read(&config, "https://www.example.com/b")
+            .meta
+            .language
+            .is_some());
+        assert!(!root.path().join(PROGRESS_MARKER).exists());
+    }
+
+    #[test]
+    fn refuses_a_session_in_use() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        let _db = open_db(config.paths.dir_database()).unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let result = runtime.block_on(backfill(&config, &[]));
+        assert!(matches!(result, Err(BackfillError::SessionInUse(_))));
+    }
+}
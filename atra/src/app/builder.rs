@@ -0,0 +1,280 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::atra::{Atra, AtraRunError};
+use crate::app::instruction::RunInstruction;
+use crate::app::ApplicationMode;
+use crate::config::Config;
+use crate::crawl::CrawlEvent;
+use crate::runtime::{AtraRuntime, GracefulShutdownWithGuard};
+use crate::schemas::{AuditAction, AuditOutcome, AuditSource};
+use crate::seed::SeedDefinition;
+use crate::url::filter::UrlFilter;
+use crate::url::guard::GuardianEventReceiver;
+use camino::Utf8PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Builds a [AtraCrawlHandle] for embedding atra in another application.
+#[derive(Debug)]
+pub struct AtraBuilder {
+    config: Config,
+    seeds: Option<SeedDefinition>,
+    mode: ApplicationMode,
+    recover_mode: bool,
+    fetch_list: Option<Utf8PathBuf>,
+    configure_logging: bool,
+    tui: bool,
+    url_filters: Vec<Arc<dyn UrlFilter>>,
+}
+
+impl AtraBuilder {
+    /// Creates a builder for a crawl in [ApplicationMode::Single] without seeds.
+    pub fn new(config: Config) -> Self {
+        Self {
+            config,
+            seeds: None,
+            mode: ApplicationMode::Single,
+            recover_mode: false,
+            fetch_list: None,
+            configure_logging: false,
+            tui: false,
+            url_filters: Vec::new(),
+        }
+    }
+
+    /// The seeds put into the queue before crawling.
+    pub fn with_seeds(mut self, seeds: SeedDefinition) -> Self {
+        self.seeds = Some(seeds);
+        self
+    }
+
+    /// The mode used for the crawl.
+    pub fn with_mode(mut self, mode: ApplicationMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// If set, all urls not yet processed are put back into the queue.
+    pub fn with_recover_mode(mut self, recover_mode: bool) -> Self {
+        self.recover_mode = recover_mode;
+        self
+    }
+
+    /// If set, the queue is replaced by the urls of the list that are not fetched yet.
+    /// Disable [crate::config::CrawlConfig::extract_links] to only fetch the listed urls.
+    pub fn with_fetch_list(mut self, fetch_list: impl Into<Utf8PathBuf>) -> Self {
+        self.fetch_list = Some(fetch_list.into());
+        self
+    }
+
+    /// If set, atra installs its own logger as configured in the [Config].
+    /// Leave this unset if the embedding application already has a logger.
+    pub fn with_logging(mut self, configure_logging: bool) -> Self {
+        self.configure_logging = configure_logging;
+        self
+    }
+
+    /// If set, the status of the crawl is shown in the terminal instead of the log lines.
+    /// Only has an effect together with [Self::with_logging] and if stdout is a terminal.
+    pub fn with_tui(mut self, tui: bool) -> Self {
+        self.tui = tui;
+        self
+    }
+
+    /// Appends [filter] to the configured [crate::config::CrawlConfig::url_filters],
+    /// it is asked for every found url the configured filters did not decide.
+    pub fn with_url_filter(mut self, filter: impl UrlFilter + 'static) -> Self {
+        self.url_filters.push(Arc::new(filter));
+        self
+    }
+
+    /// Builds a handle running on the runtime it is awaited in.
+    pub fn build(self) -> AtraCrawlHandle {
+        let atra = Atra::new(self.mode, GracefulShutdownWithGuard::new(), None);
+        self.build_for(atra)
+    }
+
+    /// Builds a handle together with a runtime as configured by the [ApplicationMode].
+    pub fn build_with_runtime(self) -> (AtraCrawlHandle, AtraRuntime) {
+        let (atra, runtime) = Atra::build_with_runtime(self.mode);
+        (self.build_for(atra), runtime)
+    }
+
+    fn build_for(self, atra: Atra) -> AtraCrawlHandle {
+        AtraCrawlHandle {
+            atra: atra.with_url_filters(self.url_filters),
+            instruction: Mutex::new(Some(RunInstruction {
+                mode: self.mode,
+                config: self.config,
+                seeds: self.seeds,
+                recover_mode: self.recover_mode,
+                fetch_list: self.fetch_list,
+                tui: self.tui && self.configure_logging,
+            })),
+            configure_logging: self.configure_logging,
+        }
+    }
+}
+
+impl From<RunInstruction> for AtraBuilder {
+    fn from(
+        RunInstruction {
+            mode,
+            config,
+            seeds,
+            recover_mode,
+            fetch_list,
+            tui,
+        }: RunInstruction,
+    ) -> Self {
+        Self {
+            config,
+            seeds,
+            mode,
+            recover_mode,
+            fetch_list,
+            configure_logging: true,
+            tui,
+            url_filters: Vec::new(),
+        }
+    }
+}
+
+/// A handle to a crawl created by the [AtraBuilder].
+///
+/// ```
+/// use atra::{AtraBuilder, Config, CrawlEvent, SeedDefinition};
+/// use atra::config::BudgetSetting;
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use tokio::net::TcpListener;
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// // A local server answering every request with the same page.
+/// let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+/// let url = format!("http://{}/", listener.local_addr().unwrap());
+/// tokio::spawn(async move {
+///     while let Ok((mut stream, _)) = listener.accept().await {
+///         let mut buf = [0u8; 4096];
+///         let _ = stream.read(&mut buf).await;
+///         let body = "<html><body><p>Hello Atra!</p></body></html>";
+///         let response = format!(
+///             "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+///             body.len(),
+///             body
+///         );
+///         let _ = stream.write_all(response.as_bytes()).await;
+///     }
+/// });
+///
+/// let root = camino_tempfile::tempdir().unwrap();
+/// let mut config = Config::default();
+/// config.paths.root = root.path().join("atra_data");
+/// config.crawl.budget.default = BudgetSetting::Absolute {
+///     depth: 1,
+///     recrawl_interval: None,
+///     request_timeout: None,
+/// };
+///
+/// let handle = AtraBuilder::new(config)
+///     .with_seeds(SeedDefinition::Single(url.clone()))
+///     .build();
+/// let mut events = handle.subscribe();
+///
+/// handle.run().await.unwrap();
+/// handle.shutdown();
+/// handle.shutdown();
+///
+/// let mut crawled = Vec::new();
+/// while let Ok(event) = events.try_recv() {
+///     if let CrawlEvent::Crawled { url, .. } = event {
+///         crawled.push(url.try_as_str().into_owned());
+///     }
+/// }
+/// assert_eq!(vec![url], crawled);
+///
+/// handle.close().await;
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct AtraCrawlHandle {
+    atra: Atra,
+    instruction: Mutex<Option<RunInstruction>>,
+    configure_logging: bool,
+}
+
+impl AtraCrawlHandle {
+    /// Subscribe to all events emitted after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<CrawlEvent> {
+        self.atra.events().subscribe()
+    }
+
+    /// Subscribe to all reservations, releases and cooldowns of origins emitted after this call.
+    pub fn subscribe_guardian(&self) -> GuardianEventReceiver {
+        self.atra.guardian_events().subscribe()
+    }
+
+    /// Runs the crawl until there is nothing left to crawl or [Self::shutdown] is called.
+    /// A handle can only be run once, every further call fails with [AtraRunError::AlreadyStarted].
+    pub async fn run(&self) -> Result<(), AtraRunError> {
+        let instruction = self
+            .instruction
+            .lock()
+            .map_or(None, |mut instruction| instruction.take());
+        let Some(instruction) = instruction else {
+            return Err(AtraRunError::AlreadyStarted);
+        };
+        if self.configure_logging {
+            self.atra.run(instruction).await
+        } else {
+            self.atra.run_without_logger(instruction).await
+        }
+    }
+
+    /// Tells all workers to stop after their current url.
+    /// Calling this more than once has no further effect.
+    pub fn shutdown(&self) {
+        self.atra.shutdown().get().shutdown()
+    }
+
+    /// Like [Self::shutdown], the first request is recorded in the audit trail
+    /// as requested by the signal called [name].
+    pub fn shutdown_by_signal(&self, name: &str) {
+        if !self.is_shutdown() {
+            if let Some(audit) = self.atra.audit() {
+                audit.append(
+                    AuditSource::Signal {
+                        name: name.to_string(),
+                    },
+                    AuditAction::Shutdown,
+                    AuditOutcome::Applied,
+                );
+            }
+        }
+        self.shutdown()
+    }
+
+    /// Returns true if [Self::shutdown] was called.
+    pub fn is_shutdown(&self) -> bool {
+        self.atra.shutdown().get().is_shutdown()
+    }
+
+    /// Drops the handle and waits until everything spawned by the crawl is stopped.
+    pub async fn close(self) {
+        let shutdown = self.atra.shutdown().get().clone();
+        drop(self);
+        shutdown.wait().await;
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contexts::local::LocalContext;
+use crate::contexts::traits::{SupportsUrlGuarding, SupportsUrlQueue};
+use crate::queue::{DequeueJournal, UrlQueueElement};
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::url::guard::UrlGuardian;
+use crate::url::{AtraUrlOrigin, UrlWithDepth};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::select;
+use tokio::task::JoinHandle;
+
+/// The name of the checkpoint in the root of the session.
+pub const CHECKPOINT_FILE: &str = "checkpoint.bin";
+
+/// A snapshot of the in-memory state of a running crawl that is lost if the process is killed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    #[serde(with = "time::serde::timestamp")]
+    pub created_at: OffsetDateTime,
+    /// The origins reserved by a worker.
+    pub reserved_origins: Vec<AtraUrlOrigin>,
+    /// The urls that were dequeued but not finished.
+    pub in_flight: Vec<UrlQueueElement<UrlWithDepth>>,
+}
+
+impl Checkpoint {
+    /// Takes a snapshot of the guardian and the urls in progress of [context].
+    /// Both are only locked while they are copied, the workers are not stopped. An url may
+    /// finish between the two copies, recover skips the finished urls.
+    pub async fn capture<C>(context: &C) -> Self
+    where
+        C: SupportsUrlQueue + SupportsUrlGuarding,
+    {
+        Self::capture_from(context.dequeue_journal(), context.get_guardian()).await
+    }
+
+    /// Takes a snapshot of the open entries of [journal] and the reservations of [guardian].
+    pub async fn capture_from(
+        journal: Option<&DequeueJournal>,
+        guardian: &impl UrlGuardian,
+    ) -> Self {
+        let in_flight = journal
+            .map(|journal| journal.open_entries())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| UrlQueueElement::new(entry.is_seed, 0, false, entry.url))
+            .collect();
+        let reserved_origins = guardian.currently_reserved_origins().await;
+        Self {
+            created_at: OffsetDateTime::now_utc(),
+            reserved_origins,
+            in_flight,
+        }
+    }
+
+    fn path(root: &Utf8Path) -> Utf8PathBuf {
+        root.join(CHECKPOINT_FILE)
+    }
+
+    /// Replaces the [CHECKPOINT_FILE] in [root], a reader never sees a partial file.
+    pub fn write_to(&self, root: &Utf8Path) -> io::Result<()> {
+        let path = Self::path(root);
+        let tmp = path.with_extension("bin.tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&tmp)?);
+            bincode::serialize_into(&mut writer, self).map_err(io::Error::other)?;
+            writer.flush()?;
+            writer.into_inner()?.sync_all()?;
+        }
+        std::fs::rename(tmp, path)
+    }
+
+    /// Reads the checkpoint in [root], None if there is none.
+    pub fn read_from(root: &Utf8Path) -> io::Result<Option<Self>> {
+        let file = match File::open(Self::path(root)) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        bincode::deserialize_from(BufReader::new(file))
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Removes the checkpoint in [root], e.g. after its urls were requeued.
+    pub fn remove(root: &Utf8Path) -> io::Result<()> {
+        match std::fs::remove_file(Self::path(root)) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Writes a [Checkpoint] of [context] to [root] every [interval] until the [shutdown].
+pub fn spawn_checkpoint_writer(
+    context: Arc<LocalContext>,
+    root: Utf8PathBuf,
+    interval: Duration,
+    shutdown: ShutdownChild,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = shutdown.wait() => break,
+                _ = tokio::time::sleep(interval) => {}
+            }
+            let checkpoint = Checkpoint::capture(context.as_ref()).await;
+            let root = root.clone();
+            match tokio::task::spawn_blocking(move || checkpoint.write_to(&root)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => log::error!("Failed to write the checkpoint: {err}"),
+                Err(err) => log::error!("Thread join error: {err}"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Checkpoint, CHECKPOINT_FILE};
+    use crate::app::recover::requeue_stranded;
+    use crate::config::RecoveryConfig;
+    use crate::database::open_db;
+    use crate::link_state::{
+        DatabaseLinkStateManager, LinkStateKind, LinkStateLike, LinkStateManager,
+    };
+    use crate::queue::{DequeueJournal, JournalEntry, UrlQueue, UrlQueueElement, UrlQueueWrapper};
+    use crate::url::guard::{InMemoryUrlGuardian, UrlGuardian};
+    use crate::url::{AtraOriginProvider, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use time::OffsetDateTime;
+
+    const URLS: [&str; 3] = [
+        "https://www.example.com/a",
+        "https://www.example.com/b",
+        "https://www.other.de/c",
+    ];
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn recover_requeues_the_urls_in_progress_of_the_checkpoint() {
+        let root = Utf8TempDir::new().unwrap();
+        let queue_path = root.path().join("queue.tmp");
+        let journal_path = root.path().join("queue.tmp.journal");
+        let db = Arc::new(open_db(root.path().join("rocksdb")).unwrap());
+        let link_states = DatabaseLinkStateManager::new(db);
+        let guardian = InMemoryUrlGuardian::new();
+
+        {
+            let queue = UrlQueueWrapper::open(&queue_path).unwrap();
+            for value in URLS {
+                queue
+                    .enqueue(UrlQueueElement::new(false, 0, false, url(value)))
+                    .await
+                    .unwrap();
+            }
+            let journal = DequeueJournal::open(&journal_path).unwrap();
+            let mut in_flight = Vec::new();
+            for _ in 0..2 {
+                let element = queue.dequeue().await.unwrap().unwrap();
+                link_states
+                    .update_link_state_no_meta_and_payload(
+                        &element.target,
+                        LinkStateKind::ReservedForCrawl,
+                    )
+                    .await
+                    .unwrap();
+                journal
+                    .record_dequeued(JournalEntry::new(
+                        element.target.clone(),
+                        false,
+                        OffsetDateTime::now_utc(),
+                    ))
+                    .unwrap();
+                in_flight.push(element);
+            }
+            let guard = guardian.try_reserve(&in_flight[0].target).await.unwrap();
+
+            Checkpoint::capture_from(Some(&journal), &guardian)
+                .await
+                .write_to(root.path())
+                .unwrap();
+
+            // Killed while crawling: nothing is returned to the queue and the guardian is gone.
+            // The journal is not synced, a power loss can take it with it.
+            for element in in_flight {
+                std::mem::forget(element);
+            }
+            std::mem::forget(guard);
+            drop(journal);
+            std::fs::remove_file(&journal_path).unwrap();
+        }
+
+        let checkpoint = Checkpoint::read_from(root.path()).unwrap().unwrap();
+        assert_eq!(2, checkpoint.in_flight.len());
+        assert_eq!(
+            vec![url(URLS[0]).atra_origin().unwrap()],
+            checkpoint.reserved_origins
+        );
+
+        let queue = UrlQueueWrapper::open(&queue_path).unwrap();
+        assert_eq!(1, queue.len().await);
+        let journal = DequeueJournal::open(&journal_path).unwrap();
+        let config = RecoveryConfig {
+            checkpoint_interval: None,
+            ..RecoveryConfig::default()
+        };
+        let report = requeue_stranded(
+            &link_states,
+            &queue,
+            &journal,
+            Some(&checkpoint),
+            &config,
+            OffsetDateTime::now_utc(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(2, report.from_checkpoint);
+        assert_eq!(2, report.requeued());
+
+        let mut queued = HashSet::new();
+        queue
+            .for_each_queued(|element| {
+                assert!(queued.insert(element.target.try_as_str().into_owned()));
+            })
+            .unwrap();
+        let expected: HashSet<_> = URLS.into_iter().map(String::from).collect();
+        assert_eq!(expected, queued);
+        for value in &checkpoint.in_flight {
+            let state = link_states
+                .get_link_state_sync(&value.target)
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+        }
+
+        Checkpoint::remove(root.path()).unwrap();
+        assert!(!root.path().join(CHECKPOINT_FILE).exists());
+        assert!(Checkpoint::read_from(root.path()).unwrap().is_none());
+        Checkpoint::remove(root.path()).unwrap();
+    }
+}
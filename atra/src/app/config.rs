@@ -14,7 +14,7 @@
 
 use crate::config::Config as AtraConfig;
 use camino::Utf8Path;
-use config::Config;
+use config::{Config, ConfigError};
 
 /// Try to load the config from the [`path`]
 pub fn try_load_from_path<P: AsRef<Utf8Path>>(path: P) -> Result<AtraConfig, config::ConfigError> {
@@ -26,6 +26,7 @@ pub fn try_load_from_path<P: AsRef<Utf8Path>>(path: P) -> Result<AtraConfig, con
         .add_source(config::Environment::with_prefix("ATRA").separator("."))
         .build()?
         .try_deserialize()
+        .and_then(resolve_profiles)
 }
 
 /// Tries to find a config at the default configs
@@ -38,7 +39,7 @@ pub fn discover_or_default() -> Result<AtraConfig, config::ConfigError> {
         .add_source(config::Environment::with_prefix("ATRA").separator("."))
         .build()
     {
-        Ok(value) => value.try_deserialize(),
+        Ok(value) => value.try_deserialize().and_then(resolve_profiles),
         Err(_) => Ok(Default::default()),
     }
 }
@@ -53,6 +54,16 @@ pub fn discover() -> Result<AtraConfig, config::ConfigError> {
         .add_source(config::Environment::with_prefix("ATRA").separator("."))
         .build()?
         .try_deserialize()
+        .and_then(resolve_profiles)
+}
+
+/// Resolves the crawl profiles of the loaded [config].
+fn resolve_profiles(mut config: AtraConfig) -> Result<AtraConfig, ConfigError> {
+    config
+        .crawl
+        .resolve_profiles()
+        .map_err(|err| ConfigError::Foreign(Box::new(err)))?;
+    Ok(config)
 }
 
 #[cfg(test)]
@@ -12,19 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::config::crawl::{CookieSettings, CrawlBudget, RedirectPolicy, UserAgent};
+use crate::config::bandwidth::{BandwidthConfig, BandwidthLimit};
+use crate::config::crawl::{
+    CookieSettings, CrawlBudget, ErrorPageExtraction, HttpVersionPolicy, ProxySetting,
+    QueueFairness, RedirectPolicy, RobotsFallback, StreamingBodies, UserAgent,
+};
+use crate::config::header_persistence::{HeaderPattern, HeaderPersistence};
+use crate::config::session::DatFileLayout;
 use crate::config::{BudgetSetting, CrawlConfig, SessionConfig};
 use crate::extraction::extractor::Extractor;
+use crate::extraction::StructuredExtractionConfig;
 use crate::gdbr::identifier::{
     FilterMode, GdbrIdentifierConfig, GdbrIdentifierRegistryConfig,
     LanguageBoundGdbrIdentifierConfig,
 };
+use crate::toolkit::text_normalization::TextNormalizationConfig;
 use isolang::Language;
 use liblinear::parameter::serde::GenericParameters;
 use reqwest::header::{HeaderMap, CONTENT_LENGTH, HOST};
 use rust_stemmers::Algorithm;
 use std::collections::HashMap;
-use std::num::NonZeroU64;
+use std::num::{NonZeroU64, NonZeroUsize};
 use svm::config::{DocumentClassifierConfig, SvmRecognizerConfig};
 use text_processing::configs::StopwordRegistryConfig;
 use text_processing::stopword_registry::StopWordRepository;
@@ -43,25 +51,50 @@ pub fn create_example_config() -> crate::config::configs::Config {
             service: "My Service".to_string(),
             collection: "MyCollection".to_string(),
             crawl_job_id: 0,
+            dat_file_layout: DatFileLayout::ContentAddressed,
         },
         crawl: CrawlConfig {
             user_agent: UserAgent::Custom("My User Agent".to_string()),
             respect_robots_txt: true,
+            extract_links: true,
             respect_nofollow: true,
+            respect_robots_meta: true,
+            respect_noindex: true,
             crawl_forms: false,
             crawl_embedded_data: false,
             crawl_javascript: true,
             crawl_onclick_by_heuristic: true,
+            crawl_redirect_hints: true,
+            extract_from_error_pages: ErrorPageExtraction::Never,
             apply_gdbr_filter_if_possible: false,
+            min_language_confidence: 80,
+            min_language_tokens: 20,
             store_only_html_in_warc: true,
             store_big_file_hints_in_warc: true,
+            text_sidecar: false,
+            deduplicate_by_digest: false,
+            publication_window: None,
             max_file_size: Some(NonZeroU64::new(1.gigabytes().as_u64()).unwrap()),
+            download_filter: None,
+            streaming_bodies: StreamingBodies::Capture {
+                max_bytes: 1.megabytes().as_u64(),
+            },
             max_robots_age: Some(Duration::seconds(60 * 24)),
+            robots_revalidation: None,
+            robots_overrides: HashMap::new(),
+            robots_on_client_error: RobotsFallback::AllowAll,
+            robots_on_server_error: RobotsFallback::DenyAll,
             ignore_sitemap: false,
+            trust_sitemap_hints: false,
             subdomains: false,
             cache: true,
             use_cookies: true,
             generate_web_graph: true,
+            url_normalization: Default::default(),
+            url_priority: Default::default(),
+            profiles: Default::default(),
+            origins: Default::default(),
+            effective_origins: Default::default(),
             cookies: Some(CookieSettings {
                 default: Some("My Default cookie".to_string()),
                 per_host: Some({
@@ -79,9 +112,32 @@ pub fn create_example_config() -> crate::config::configs::Config {
                 hm.insert(CONTENT_LENGTH, "123".parse().unwrap());
                 hm
             }),
-            proxies: Some(vec!["myproxie.com".to_string()]),
+            accept_language: Some("de-DE,de;q=0.9,en;q=0.5".to_string()),
+            language_variants: vec!["de".to_string(), "en".to_string()],
+            // Credentials are never part of the example.
+            auth: HashMap::new(),
+            header_persistence: HeaderPersistence {
+                drop_headers: vec![HeaderPattern::new("x-internal-*").unwrap()],
+                redact_headers: vec![HeaderPattern::new("set-cookie").unwrap()],
+            },
+            faithful_headers: true,
+            proxies: Some(vec![
+                ProxySetting::PerOrigin {
+                    url: "socks5h://127.0.0.1:9050".to_string(),
+                    origins: vec!["example.onion".to_string().into()],
+                },
+                ProxySetting::All("myproxie.com".to_string()),
+            ]),
             tld: false,
             delay: Some(Duration::seconds(10)),
+            max_parallel_per_origin: NonZeroUsize::new(2).unwrap(),
+            politeness_coordination_dir: None,
+            bandwidth: BandwidthConfig {
+                global: None,
+                per_origin: Some(BandwidthLimit::new(
+                    NonZeroU64::new(256.kilobytes().as_u64()).unwrap(),
+                )),
+            },
             budget: CrawlBudget {
                 default: BudgetSetting::Normal {
                     depth: 2,
@@ -111,13 +167,42 @@ pub fn create_example_config() -> crate::config::configs::Config {
                     hm
                 }),
             },
+            max_pages_total: Some(1_000_000),
+            max_bytes_total: Some(500.gigabytes()),
+            max_duration: Some(Duration::days(7)),
+            wave_mode: false,
+            wave_confirmation: false,
+            seed_tags: HashMap::new(),
             max_queue_age: 30,
+            queue_fairness: QueueFairness::OriginRoundRobin,
+            max_throttled_retries: 5,
+            fetch_retry: None,
+            rediscovery: None,
+            unique_content_floor: Some(10),
+            unique_content_min_samples: 100,
+            trap_detection: None,
+            allowlist: Vec::new(),
+            url_filters: Vec::new(),
             redirect_limit: 5,
             redirect_policy: RedirectPolicy::Loose,
+            http_version: HttpVersionPolicy::Auto,
             accept_invalid_certs: true,
+            tls_capture: None,
+            origin_metadata: None,
             link_extractors: Extractor::default(),
             max_extraction_depth: Some(20),
+            max_data_uri_size: 1.megabytes().as_u64(),
+            extraction_timeout: Some(Duration::minutes(5)),
+            extractor_method_timeout: Some(Duration::seconds(60)),
+            structured_extraction: StructuredExtractionConfig {
+                max_document_size: 16.megabytes().as_u64(),
+                max_links: 10_000,
+            },
             decode_big_files_up_to: Some(1.gigabytes().as_u64()),
+            text_normalization: TextNormalizationConfig {
+                collapse_whitespace: true,
+            },
+            text_sample_size: 2.megabytes().as_u64(),
             stopword_registry: Some(StopwordRegistryConfig {
                 registries: vec![
                     StopWordRepository::IsoDefault,
@@ -0,0 +1,283 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::blacklist::{Blacklist, BlacklistType, PolyBlackList, PolyBlackListError};
+use crate::config::{BudgetSetting, Config};
+use crate::io::simple_line::SupportsSimpleLineReader;
+use crate::seed::{read_seeds, SeedDefinition, StructuredSeeds};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use itertools::Itertools;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use thiserror::Error;
+
+/// Errors of a dry run.
+#[derive(Debug, Error)]
+pub enum DryRunError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Blacklist(#[from] PolyBlackListError),
+    #[error("None of the seeds would be enqueued.")]
+    NothingToEnqueue,
+}
+
+/// What would happen to a seed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum SeedVerdict {
+    Enqueued,
+    InvalidUrl(String),
+    NoOrigin,
+    Blacklisted,
+    OutOfBudget,
+}
+
+impl Display for SeedVerdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SeedVerdict::Enqueued => write!(f, "enqueued"),
+            SeedVerdict::InvalidUrl(err) => write!(f, "invalid url: {err}"),
+            SeedVerdict::NoOrigin => write!(f, "no origin"),
+            SeedVerdict::Blacklisted => write!(f, "blacklisted"),
+            SeedVerdict::OutOfBudget => write!(f, "out of budget"),
+        }
+    }
+}
+
+/// A seed with the verdict of the dry run.
+#[derive(Debug, Clone)]
+pub struct DryRunEntry {
+    pub seed: String,
+    pub origin: Option<AtraUrlOrigin>,
+    pub verdict: SeedVerdict,
+}
+
+/// The result of validating the seeds without fetching anything.
+#[derive(Debug, Default)]
+pub struct DryRunReport {
+    pub entries: Vec<DryRunEntry>,
+    /// The effective budget of every origin of an enqueued seed.
+    pub budgets: BTreeMap<AtraUrlOrigin, BudgetSetting>,
+}
+
+impl DryRunReport {
+    /// The number of seeds that would be enqueued.
+    pub fn enqueued(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|value| value.verdict == SeedVerdict::Enqueued)
+            .count()
+    }
+}
+
+impl Display for DryRunReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let width = self
+            .entries
+            .iter()
+            .map(|value| value.seed.len())
+            .max()
+            .unwrap_or_default()
+            .max(4);
+        writeln!(f, "{:width$}    {:20}    Verdict", "Seed", "Origin")?;
+        for value in &self.entries {
+            let origin = value.origin.as_ref().map_or("-", |value| value.as_ref());
+            writeln!(f, "{:width$}    {origin:20}    {}", value.seed, value.verdict)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Budgets:")?;
+        for (origin, budget) in &self.budgets {
+            writeln!(f, "{:20}    {}", origin.as_ref(), describe_budget(budget))?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Enqueued seeds:   {} of {}", self.enqueued(), self.entries.len())
+    }
+}
+
+fn describe_budget(budget: &BudgetSetting) -> String {
+    let specific = match budget {
+        BudgetSetting::SinglePage { .. } => String::new(),
+        BudgetSetting::SeedOnly {
+            depth_on_website, ..
+        } => format!("depth_on_website: {depth_on_website}, "),
+        BudgetSetting::Normal {
+            depth_on_website,
+            depth,
+            ..
+        } => format!("depth_on_website: {depth_on_website}, depth: {depth}, "),
+        BudgetSetting::Absolute { depth, .. } => format!("depth: {depth}, "),
+    };
+    let recrawl = budget
+        .get_recrawl_interval()
+        .map_or_else(|| "-".to_string(), |value| value.to_string());
+    let timeout = budget
+        .get_request_timeout()
+        .map_or_else(|| "-".to_string(), |value| value.to_string());
+    format!("{budget}({specific}recrawl_interval: {recrawl}, request_timeout: {timeout})")
+}
+
+/// Reads the blacklist of [config] without creating or changing the file.
+fn read_blacklist(config: &Config) -> Result<PolyBlackList, DryRunError> {
+    let path = config.paths.file_blacklist();
+    if !path.is_file() {
+        return Ok(PolyBlackList::default());
+    }
+    let entries = BufReader::new(File::open(path)?)
+        .to_simple_line_reader()
+        .filter_ok(|value| !value.is_empty())
+        .flatten()
+        .collect_vec();
+    Ok(PolyBlackList::new(entries.len() as u64, entries)?)
+}
+
+/// Checks the [seeds] against the blacklist and the budgets of [config] like a crawl would.
+/// Nothing is fetched and nothing is written to the session folder.
+pub(crate) fn dry_run(
+    config: &Config,
+    seeds: &SeedDefinition,
+) -> Result<DryRunReport, DryRunError> {
+    let mut crawl = Cow::Borrowed(&config.crawl);
+    let seeds = match seeds {
+        SeedDefinition::Single(value) => vec![(value.clone(), None)],
+        SeedDefinition::Multi(values) => values.iter().map(|value| (value.clone(), None)).collect(),
+        SeedDefinition::File(path) => read_seeds(path)?
+            .into_iter()
+            .sorted()
+            .map(|value| (value, None))
+            .collect(),
+        SeedDefinition::Structured(path) => {
+            let structured = StructuredSeeds::read(path)?;
+            structured.apply_to(crawl.to_mut());
+            structured
+                .seeds()
+                .iter()
+                .map(|seed| (seed.url.try_as_str().into_owned(), Some(seed.url.depth)))
+                .collect()
+        }
+    };
+    let blacklist = read_blacklist(config)?;
+
+    let mut report = DryRunReport::default();
+    for (seed, depth) in seeds {
+        let url = match UrlWithDepth::from_url(seed.as_str()) {
+            Ok(mut url) => {
+                if let Some(depth) = depth {
+                    url.depth = depth;
+                }
+                url
+            }
+            Err(err) => {
+                report.entries.push(DryRunEntry {
+                    seed,
+                    origin: None,
+                    verdict: SeedVerdict::InvalidUrl(err.to_string()),
+                });
+                continue;
+            }
+        };
+        let Some(origin) = url.atra_origin() else {
+            report.entries.push(DryRunEntry {
+                seed,
+                origin: None,
+                verdict: SeedVerdict::NoOrigin,
+            });
+            continue;
+        };
+        let budget = crawl.budget_for(&origin);
+        let verdict = if blacklist.has_match_for(&url.try_as_str()) {
+            SeedVerdict::Blacklisted
+        } else if !budget.is_in_budget(&url) {
+            SeedVerdict::OutOfBudget
+        } else {
+            report.budgets.insert(origin.clone(), budget.clone());
+            SeedVerdict::Enqueued
+        };
+        report.entries.push(DryRunEntry {
+            seed,
+            origin: Some(origin),
+            verdict,
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::dry_run::{dry_run, SeedVerdict};
+    use crate::config::{BudgetSetting, Config};
+    use crate::seed::SeedDefinition;
+    use crate::url::AtraUrlOrigin;
+    use camino_tempfile::Utf8TempDir;
+    use std::collections::HashMap;
+
+    #[test]
+    fn validates_the_seeds_without_writing() {
+        let dir = Utf8TempDir::new().unwrap();
+        let blacklist = dir.path().join("blacklist.txt");
+        std::fs::write(&blacklist, "# comment\nblocked\\.example\\.org\n").unwrap();
+
+        let mut config = Config::default();
+        config.paths.root = dir.path().join("session");
+        config.paths.files.blacklist = blacklist;
+        config.crawl.budget.per_host = Some(HashMap::from([(
+            "other.de".into(),
+            BudgetSetting::SeedOnly {
+                depth_on_website: 3,
+                recrawl_interval: None,
+                request_timeout: None,
+            },
+        )]));
+
+        let report = dry_run(
+            &config,
+            &SeedDefinition::Multi(vec![
+                "https://www.example.com/".to_string(),
+                "not a url".to_string(),
+                "https://blocked.example.org/".to_string(),
+                "https://www.other.de/start".to_string(),
+            ]),
+        )
+        .unwrap();
+
+        let verdicts: Vec<_> = report.entries.iter().map(|value| &value.verdict).collect();
+        assert_eq!(&SeedVerdict::Enqueued, verdicts[0]);
+        assert!(matches!(verdicts[1], SeedVerdict::InvalidUrl(_)));
+        assert_eq!(&SeedVerdict::Blacklisted, verdicts[2]);
+        assert_eq!(&SeedVerdict::Enqueued, verdicts[3]);
+        assert_eq!(2, report.enqueued());
+        assert_eq!(2, report.budgets.len());
+        assert!(matches!(
+            report.budgets.get(&AtraUrlOrigin::from("other.de")),
+            Some(BudgetSetting::SeedOnly {
+                depth_on_website: 3,
+                ..
+            })
+        ));
+        assert_eq!(
+            Some(&config.crawl.budget.default),
+            report.budgets.get(&AtraUrlOrigin::from("example.com"))
+        );
+        assert!(!config.paths.root.exists());
+
+        let report = dry_run(
+            &config,
+            &SeedDefinition::Single("https://blocked.example.org/".to_string()),
+        )
+        .unwrap();
+        assert_eq!(0, report.enqueued());
+    }
+}
@@ -17,14 +17,19 @@ use std::fs::File;
 use std::io::{BufWriter, Write};
 use camino::Utf8PathBuf;
 use rocksdb::IteratorMode;
-use serde::Serialize;
+use time::OffsetDateTime;
 use crate::app::instruction::{InstructionError, string_to_config_path};
 use crate::contexts::local::LocalContext;
+use crate::contexts::traits::{SupportsOriginMetadata, SupportsTlsInfo};
 use crate::crawl::{SlimCrawlResult, StoredDataHint};
+use crate::origin_metadata::OriginMetadataManager;
+use crate::schemas::{DumpRecord, OriginMetadataRecord, OriginTlsRecord};
+use crate::tls_info::TlsInfoManager;
 use crate::url::AtraUri;
 use crate::warc_ext::WarcSkipInstruction;
 
-pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(), InstructionError> {
+/// Dumps the crawl at [crawl_path], expired entries are skipped unless [include_expired] is set.
+pub(crate) fn dump(crawl_path: String, output_dir: Option<String>, include_expired: bool) -> Result<(), InstructionError> {
     let config = string_to_config_path(&crawl_path)?;
     let local = LocalContext::new_without_runtime(config)
         .expect("Was not able to load context for reading!");
@@ -41,11 +46,12 @@ pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(),
     let output_data = output_dir.join("meta.jsonbulk");
     let mut writer = BufWriter::new(File::options().write(true).create_new(true).open(output_data)?);
     let mut warc_files = HashSet::new();
+    let now = OffsetDateTime::now_utc();
     for value in local.crawl_db().iter(IteratorMode::Start) {
         match value {
             Ok((k, v)) => {
                 let uri: AtraUri = unsafe{std::str::from_utf8_unchecked(k.as_ref())}.parse().expect("This should never fail!");
-                let data: SlimCrawlResult = match bincode::deserialize_from(v.as_ref()) {
+                let data = match SlimCrawlResult::decode(v.as_ref()) {
                     Ok(value) => {
                         value
                     }
@@ -54,6 +60,9 @@ pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(),
                         continue
                     }
                 };
+                if !include_expired && data.meta.is_expired_at(now) {
+                    continue
+                }
                 match &data.stored_data_hint {
                     StoredDataHint::Warc(value) => {
                         match value {
@@ -73,7 +82,7 @@ pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(),
                     }
                     _ => {}
                 }
-                serde_json::to_writer(&mut writer, &Entry{url: uri, meta: data}).map_err(InstructionError::DumbSerialisationError)?;
+                serde_json::to_writer(&mut writer, &DumpRecord::new(uri, data)).map_err(InstructionError::DumbSerialisationError)?;
                 write!(&mut writer, "\n")?;
             }
             Err(_) => {
@@ -83,6 +92,28 @@ pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(),
     }
     writer.flush()?;
     drop(writer);
+    let tls_origins = local.get_tls_info_manager().origins();
+    if !tls_origins.is_empty() {
+        let tls_path = output_dir.join("tls_origins.jsonl");
+        let mut writer = BufWriter::new(File::options().write(true).create_new(true).open(tls_path)?);
+        for (origin, info) in &tls_origins {
+            let certificates = local.get_tls_info_manager().certificates(origin);
+            let record = OriginTlsRecord::new(origin, info, &certificates);
+            serde_json::to_writer(&mut writer, &record).map_err(InstructionError::DumbSerialisationError)?;
+            write!(&mut writer, "\n")?;
+        }
+        writer.flush()?;
+    }
+    let origins = local.get_origin_metadata_manager().origins();
+    if !origins.is_empty() {
+        let metadata_path = output_dir.join("origin_metadata.jsonl");
+        let mut writer = BufWriter::new(File::options().write(true).create_new(true).open(metadata_path)?);
+        for (origin, metadata) in &origins {
+            serde_json::to_writer(&mut writer, &OriginMetadataRecord::new(origin, metadata)).map_err(InstructionError::DumbSerialisationError)?;
+            write!(&mut writer, "\n")?;
+        }
+        writer.flush()?;
+    }
     let warc_path = output_dir.join("warc_files.txt");
     let mut writer = BufWriter::new(File::options().write(true).create_new(true).open(warc_path)?);
     for value in warc_files {
@@ -91,10 +122,3 @@ pub(crate) fn dump(crawl_path: String, output_dir: Option<String>) -> Result<(),
     writer.flush()?;
     Ok(())
 }
-
-
-#[derive(Debug, Serialize)]
-struct Entry {
-    url: AtraUri,
-    meta: SlimCrawlResult
-}
@@ -33,9 +33,63 @@ impl Into<ExitCode> for InstructionError {
             InstructionError::RootAlreadyExists(_) => {
                 ExitCode::from(5)
             }
+            InstructionError::BaselineNotFound(_) => {
+                ExitCode::from(9)
+            }
             InstructionError::DumbSerialisationError(_) => {
                 ExitCode::from(70)
             }
+            InstructionError::RebuildError(_) => {
+                ExitCode::from(71)
+            }
+            InstructionError::ExportSerialisationError(_) => {
+                ExitCode::from(72)
+            }
+            InstructionError::InvalidUrl(_) => {
+                ExitCode::from(6)
+            }
+            InstructionError::ExpiredError(_) => {
+                ExitCode::from(73)
+            }
+            InstructionError::DryRunError(_) => {
+                ExitCode::from(7)
+            }
+            InstructionError::RequeueError(_) => {
+                ExitCode::from(74)
+            }
+            InstructionError::SecondaryError(_) => {
+                ExitCode::from(75)
+            }
+            InstructionError::ProfileError(_) => {
+                ExitCode::from(8)
+            }
+            InstructionError::BackfillError(_) => {
+                ExitCode::from(76)
+            }
+            InstructionError::ImportHarError(_) => {
+                ExitCode::from(77)
+            }
+            InstructionError::ImportWarcError(_) => {
+                ExitCode::from(78)
+            }
+            InstructionError::SimilarPagesError(_) => {
+                ExitCode::from(72)
+            }
+            InstructionError::ParquetExportError(_) => {
+                ExitCode::from(72)
+            }
+            InstructionError::BulkExportError(_) => {
+                ExitCode::from(72)
+            }
+            InstructionError::MaintainError(_) => {
+                ExitCode::from(79)
+            }
+            InstructionError::GdbrExportError(_) => {
+                ExitCode::from(80)
+            }
+            InstructionError::VerifyError(_) => {
+                ExitCode::from(81)
+            }
         }
     }
 }
@@ -74,9 +128,30 @@ impl Into<ExitCode> for AtraRunError {
             AtraRunError::WorkerContextInitialisation(_) => {
                 ExitCode::from(40)
             }
+            AtraRunError::Cooperative(_) => {
+                ExitCode::from(41)
+            }
+            AtraRunError::Metrics(_) => {
+                ExitCode::from(42)
+            }
+            AtraRunError::FetchList(_) => {
+                ExitCode::from(43)
+            }
+            AtraRunError::Recover(_) => {
+                ExitCode::from(44)
+            }
+            AtraRunError::Seeds(_) => {
+                ExitCode::from(45)
+            }
+            AtraRunError::Audit(_) => {
+                ExitCode::from(46)
+            }
             AtraRunError::Queue(_) => {
                 ExitCode::from(50)
             }
+            AtraRunError::AlreadyStarted => {
+                ExitCode::from(60)
+            }
             AtraRunError::Crawl(value) => {
                 match value {
                     GlobalError::SlimCrawlError(_) => {
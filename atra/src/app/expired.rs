@@ -0,0 +1,262 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::session::DatFileLayout;
+use crate::config::Config;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{SlimCrawlResult, StoredDataHint};
+use crate::database::{open_db, DatabaseError, OpenDBError};
+use crate::io::content_addressed::ContentAddressedStore;
+use crate::url::UrlWithDepth;
+use camino::Utf8PathBuf;
+use rocksdb::IteratorMode;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// Errors that stop the listing of the expired entries.
+#[derive(Debug, Error)]
+pub enum ExpiredError {
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+}
+
+/// An entry past its `unavailable_after` date.
+#[derive(Debug, Clone)]
+pub struct ExpiredEntry {
+    pub url: UrlWithDepth,
+    pub expires_at: OffsetDateTime,
+    /// The external file with the data of the entry.
+    pub external_file: Option<Utf8PathBuf>,
+}
+
+/// The entries past their expiry.
+#[derive(Debug, Default)]
+pub struct ExpiredReport {
+    /// The expired entries
+    pub expired: Vec<ExpiredEntry>,
+    /// True iff the entries were removed from the crawl db
+    pub pruned: bool,
+}
+
+impl Display for ExpiredReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for value in &self.expired {
+            let expires_at = value
+                .expires_at
+                .format(&Rfc3339)
+                .unwrap_or_else(|_| value.expires_at.to_string());
+            writeln!(f, "{expires_at}    {}", value.url.url)?;
+        }
+        if self.pruned {
+            writeln!(f, "Pruned entries:   {}", self.expired.len())
+        } else {
+            writeln!(f, "Expired entries:  {}", self.expired.len())
+        }
+    }
+}
+
+/// Lists the entries of the crawl db of [config] that are expired at [now].
+///
+/// If [prune] is set the entries are removed from the crawl db and their external files are
+/// released. The warc files are left untouched, they are append only and shared by many entries.
+pub(crate) fn expired_entries(
+    config: &Config,
+    now: OffsetDateTime,
+    prune: bool,
+) -> Result<ExpiredReport, ExpiredError> {
+    let db = Arc::new(open_db(config.paths.dir_database())?);
+    let crawl_db = CrawlDB::new(db, config)?;
+
+    let mut report = ExpiredReport {
+        expired: Vec::new(),
+        pruned: prune,
+    };
+    for value in crawl_db.iter(IteratorMode::Start) {
+        let (k, v) = match value {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to read from the crawl db: {err}");
+                continue;
+            }
+        };
+        let data = match SlimCrawlResult::decode(v.as_ref()) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!(
+                    "Failed to deserialize data from {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                continue;
+            }
+        };
+        if !data.meta.is_expired_at(now) {
+            continue;
+        }
+        let expires_at = data
+            .meta
+            .unavailable_after
+            .and_then(|value| value.expires_at)
+            .expect("An expired entry always has an expiry!");
+        let external_file = match data.stored_data_hint {
+            StoredDataHint::External(path) => Some(path),
+            _ => None,
+        };
+        report.expired.push(ExpiredEntry {
+            url: data.meta.url,
+            expires_at,
+            external_file,
+        });
+    }
+
+    if prune {
+        let store = match config.session.dat_file_layout {
+            DatFileLayout::Flat => None,
+            DatFileLayout::ContentAddressed => {
+                Some(ContentAddressedStore::new(config.paths.dir_big_files()))
+            }
+        };
+        for value in &report.expired {
+            log::info!("Pruning {}", value.url);
+            crawl_db.remove(&value.url)?;
+            if let Some(ref path) = value.external_file {
+                let released = match store {
+                    Some(ref store) if store.contains_path(path) => store.release(path).map(|_| ()),
+                    _ => std::fs::remove_file(path),
+                };
+                if let Err(err) = released {
+                    log::warn!("Failed to release the file {path} of {}: {err}", value.url);
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::expired::expired_entries;
+    use crate::config::session::DatFileLayout;
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::database::open_db;
+    use crate::io::content_addressed::ContentAddressedStore;
+    use crate::robots::UnavailableAfter;
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use std::sync::Arc;
+    use time::OffsetDateTime;
+
+    fn create_fixture(config: &Config) {
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, config).unwrap();
+        for (url, directive) in [
+            ("https://www.example.com/expired", "01 Jan 2000 00:00:00 GMT"),
+            ("https://www.example.com/live", "2999-01-01"),
+        ] {
+            let mut result = create_test_data(UrlWithDepth::from_url(url).unwrap(), None);
+            result.meta.unavailable_after = Some(UnavailableAfter::parse(directive));
+            crawl_db
+                .add(&SlimCrawlResult::new(
+                    &result,
+                    StoredDataHint::InMemory(vec![1, 2, 3]),
+                ))
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn lists_and_prunes_the_expired_entries() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        create_fixture(&config);
+
+        let now = OffsetDateTime::now_utc();
+        let report = expired_entries(&config, now, false).unwrap();
+        assert_eq!(1, report.expired.len());
+        assert_eq!(
+            "https://www.example.com/expired",
+            report.expired[0].url.try_as_str()
+        );
+        assert_eq!(946684800, report.expired[0].expires_at.unix_timestamp());
+
+        let report = expired_entries(&config, now, true).unwrap();
+        assert_eq!(1, report.expired.len());
+        assert!(expired_entries(&config, now, false)
+            .unwrap()
+            .expired
+            .is_empty());
+
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, &config).unwrap();
+        assert_eq!(1, crawl_db.len());
+        assert!(crawl_db
+            .get(&UrlWithDepth::from_url("https://www.example.com/live").unwrap())
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn pruning_respects_the_references() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        config.session.dat_file_layout = DatFileLayout::ContentAddressed;
+
+        let big_files = config.paths.dir_big_files();
+        std::fs::create_dir_all(&big_files).unwrap();
+        let store = ContentAddressedStore::new(&big_files);
+        let mut stored = Vec::new();
+        for name in ["first.dat", "second.dat"] {
+            let path = big_files.join(name);
+            std::fs::write(&path, vec![7u8; 1024 * 1024]).unwrap();
+            stored.push(store.insert(path).unwrap());
+        }
+        assert_eq!(stored[0], stored[1]);
+
+        {
+            let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+            let crawl_db = CrawlDB::new(db, &config).unwrap();
+            for (url, directive) in [
+                ("https://www.example.com/expired", "01 Jan 2000 00:00:00 GMT"),
+                ("https://www.example.com/live", "2999-01-01"),
+            ] {
+                let mut result = create_test_data(UrlWithDepth::from_url(url).unwrap(), None);
+                result.meta.unavailable_after = Some(UnavailableAfter::parse(directive));
+                crawl_db
+                    .add(&SlimCrawlResult::new(
+                        &result,
+                        StoredDataHint::External(stored[0].clone()),
+                    ))
+                    .unwrap();
+            }
+        }
+
+        let report = expired_entries(&config, OffsetDateTime::now_utc(), true).unwrap();
+        assert_eq!(1, report.expired.len());
+        assert_eq!(Some(&stored[0]), report.expired[0].external_file.as_ref());
+        assert!(stored[0].exists());
+        assert_eq!(1, store.references(&stored[0]).unwrap());
+    }
+}
@@ -0,0 +1,426 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::{BudgetSetting, CrawlConfig};
+use crate::contexts::traits::{SupportsConfigs, SupportsLinkState, SupportsUrlQueue};
+use crate::link_state::{
+    IsSeedYesNo, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStateManager, RecrawlYesNo,
+};
+use crate::queue::{QueueError, UrlQueue, UrlQueueElement};
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::schemas::FetchListProgress;
+use crate::seed::read_seeds;
+use crate::url::UrlWithDepth;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use thiserror::Error;
+use tokio::select;
+use tokio::task::JoinHandle;
+
+/// The urls written to the queue at once.
+const BATCH_SIZE: usize = 1_000;
+/// The time between two updates of the progress file.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Errors while filling the queue from a fetch list or writing its progress.
+#[derive(Debug, Error)]
+pub enum FetchListError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    LinkState(#[from] LinkStateDBError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+}
+
+/// Restricts the [config] to the urls in the queue: no links are extracted, no sitemaps are
+/// read, nothing is recrawled and no url is dropped from the queue. The request timeouts of
+/// the budgets are kept.
+pub fn configure_for_fetch_list(config: &mut CrawlConfig) {
+    fn single_page(budget: &BudgetSetting) -> BudgetSetting {
+        BudgetSetting::SinglePage {
+            recrawl_interval: None,
+            request_timeout: budget.get_request_timeout().copied(),
+        }
+    }
+
+    config.extract_links = false;
+    config.ignore_sitemap = true;
+    config.max_queue_age = 0;
+    config.budget.default = single_page(&config.budget.default);
+    if let Some(per_host) = config.budget.per_host.as_mut() {
+        for budget in per_host.values_mut() {
+            *budget = single_page(budget);
+        }
+    }
+}
+
+/// The distinct urls of a fetch list. The link states of the urls record what was already
+/// fetched, a fetch list can be filled into the queue of the same session again and again.
+#[derive(Debug)]
+pub struct FetchList {
+    urls: Vec<UrlWithDepth>,
+    invalid: usize,
+}
+
+impl FetchList {
+    /// Reads the list at [path] with the same format as a seed file, one url per line.
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::from_lines(read_seeds(path)?))
+    }
+
+    /// Parses the [lines], invalid urls are logged and counted. Every url is kept once.
+    pub fn from_lines(lines: impl IntoIterator<Item = impl AsRef<str>>) -> Self {
+        let mut seen = HashSet::new();
+        let mut urls = Vec::new();
+        let mut invalid = 0;
+        for line in lines {
+            match UrlWithDepth::from_url(line.as_ref()) {
+                Ok(url) => {
+                    if seen.insert(url.url().clone()) {
+                        urls.push(url);
+                    }
+                }
+                Err(err) => {
+                    log::warn!("Skip {} of the fetch list: {err}", line.as_ref());
+                    invalid += 1;
+                }
+            }
+        }
+        Self { urls, invalid }
+    }
+
+    /// The distinct urls of the list.
+    pub fn urls(&self) -> &[UrlWithDepth] {
+        &self.urls
+    }
+
+    /// Replaces the queue of the [context] with the urls of the list that are neither fetched
+    /// nor failed. Urls that were reserved or fetched but not stored when a previous run was
+    /// interrupted are reset to discovered. Returns the progress before crawling.
+    pub async fn fill_queue<C>(&self, context: &C) -> Result<FetchListProgress, FetchListError>
+    where
+        C: SupportsUrlQueue + SupportsLinkState,
+        C::LinkStateManager: LinkStateManager<Error = LinkStateDBError>,
+    {
+        let queue = context.url_queue();
+        let mut dropped = 0usize;
+        while let Some(element) = queue.dequeue().await? {
+            element.drop_from_queue();
+            dropped += 1;
+        }
+        if dropped > 0 {
+            log::info!("Dropped {dropped} urls of a previous run from the queue.");
+        }
+
+        let manager = context.get_link_state_manager();
+        let mut fetched = 0;
+        let mut failed = 0;
+        let mut batch = Vec::with_capacity(BATCH_SIZE);
+        for url in &self.urls {
+            match manager.get_link_state(url).await?.map(|state| state.kind()) {
+                Some(LinkStateKind::ProcessedAndStored) => {
+                    fetched += 1;
+                    continue;
+                }
+                Some(LinkStateKind::InternalError) => {
+                    failed += 1;
+                    continue;
+                }
+                Some(LinkStateKind::Discovered) => {}
+                Some(_) => {
+                    manager
+                        .update_link_state_no_meta_and_payload(url, LinkStateKind::Discovered)
+                        .await?
+                }
+                None => {
+                    manager
+                        .update_link_state_no_payload(
+                            url,
+                            LinkStateKind::Discovered,
+                            Some(IsSeedYesNo::Yes),
+                            Some(RecrawlYesNo::No),
+                        )
+                        .await?
+                }
+            }
+            batch.push(UrlQueueElement::new(true, 0, false, url.clone()));
+            if batch.len() >= BATCH_SIZE {
+                queue.enqueue_all(std::mem::take(&mut batch)).await?;
+            }
+        }
+        if !batch.is_empty() {
+            queue.enqueue_all(batch).await?;
+        }
+        Ok(FetchListProgress::new(
+            self.urls.len(),
+            fetched,
+            failed,
+            self.invalid,
+        ))
+    }
+
+    /// Counts the fetched and failed urls of the list by their link states.
+    pub fn progress<M>(&self, manager: &M) -> Result<FetchListProgress, M::Error>
+    where
+        M: LinkStateManager,
+    {
+        let mut fetched = 0;
+        let mut failed = 0;
+        for url in &self.urls {
+            match manager.get_link_state_sync(url)?.map(|state| state.kind()) {
+                Some(LinkStateKind::ProcessedAndStored) => fetched += 1,
+                Some(LinkStateKind::InternalError) => failed += 1,
+                _ => {}
+            }
+        }
+        Ok(FetchListProgress::new(
+            self.urls.len(),
+            fetched,
+            failed,
+            self.invalid,
+        ))
+    }
+
+    /// Writes the current progress to the root of the session of the [context].
+    pub fn write_progress<C>(&self, context: &C) -> Result<FetchListProgress, FetchListError>
+    where
+        C: SupportsConfigs + SupportsLinkState,
+        C::LinkStateManager: LinkStateManager<Error = LinkStateDBError>,
+    {
+        let progress = self.progress(context.get_link_state_manager())?;
+        progress.write_to(context.configs().paths.root_path())?;
+        log::info!(
+            "Finished {:.2}% of the fetch list, {} urls are pending.",
+            progress.completion_percent,
+            progress.pending
+        );
+        Ok(progress)
+    }
+}
+
+/// Writes the progress of the [list] periodically until the [shutdown].
+pub fn spawn_progress_writer<C>(
+    list: Arc<FetchList>,
+    context: Arc<C>,
+    shutdown: ShutdownChild,
+) -> JoinHandle<()>
+where
+    C: SupportsConfigs + SupportsLinkState + Send + Sync + 'static,
+    C::LinkStateManager: LinkStateManager<Error = LinkStateDBError>,
+{
+    tokio::spawn(async move {
+        loop {
+            select! {
+                _ = shutdown.wait() => break,
+                _ = tokio::time::sleep(PROGRESS_INTERVAL) => {}
+            }
+            let list = list.clone();
+            let context = context.clone();
+            match tokio::task::spawn_blocking(move || list.write_progress(context.as_ref())).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => {
+                    log::error!("Failed to write the progress of the fetch list: {err}")
+                }
+                Err(err) => log::error!("Thread join error: {err}"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{configure_for_fetch_list, FetchList};
+    use crate::config::{BudgetSetting, Config, CrawlConfig};
+    use crate::contexts::traits::{SupportsCrawling, SupportsLinkState, SupportsPolling};
+    use crate::data::RawData;
+    use crate::fetching::FetchedRequestData;
+    use crate::link_state::{LinkStateKind, LinkStateLike, LinkStateManager};
+    use crate::queue::UrlQueuePollResult;
+    use crate::runtime::ShutdownPhantom;
+    use crate::schemas::test::assert_matches_schema;
+    use crate::schemas::{FetchListProgress, VersionedSchema, FETCH_LIST_PROGRESS_FILE};
+    use crate::test_impls::{FakeClientProvider, FakeResponse, TestContext, TestErrorConsumer};
+    use crate::url::{AtraUri, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::StatusCode;
+    use std::collections::HashMap;
+    use time::Duration;
+
+    const LIST: &[&str] = &[
+        "https://www.example.com/a.html",
+        "https://www.example.com/b.html",
+        "https://www.example.com/c.html",
+        "https://www.other.de/",
+        "https://www.other.de/x.html",
+        "https://www.example.com/a.html",
+        "not a url",
+        "https://www.third.org/y.html",
+        "https://www.other.de/x.html",
+    ];
+
+    fn body(url: &str) -> String {
+        format!("<html><body><a href=\"/linked.html\">{url}</a></body></html>")
+    }
+
+    fn fixture() -> (TestContext<FakeClientProvider>, Utf8TempDir) {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = CrawlConfig::default();
+        configure_for_fetch_list(&mut config);
+        let mut config = Config::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            config,
+        );
+        config.paths.root = root.path().to_path_buf();
+
+        let provider = FakeClientProvider::new();
+        for url in LIST.iter().filter(|url| url.starts_with("https")) {
+            provider.insert(
+                url.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(body(url).into_bytes()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+        (TestContext::new(config, provider), root)
+    }
+
+    /// Crawls at most [limit] urls of the queue, returns the number of crawled urls.
+    async fn crawl_queue(context: &TestContext<FakeClientProvider>, limit: usize) -> usize {
+        let mut crawled = 0;
+        while crawled < limit {
+            match context.poll_next_free_url_no_shutdown(None).await {
+                UrlQueuePollResult::Ok(guard) => {
+                    context
+                        .create_crawl_task(guard.get_guarded_seed())
+                        .unwrap()
+                        .run(context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+                        .await
+                        .unwrap();
+                    crawled += 1;
+                }
+                UrlQueuePollResult::Abort(_) => break,
+                UrlQueuePollResult::Err(err) => panic!("Failed to poll: {err}"),
+            }
+        }
+        crawled
+    }
+
+    #[test]
+    fn the_budget_only_allows_the_listed_urls() {
+        let mut config = CrawlConfig::default();
+        config.budget.default = BudgetSetting::Absolute {
+            depth: 3,
+            recrawl_interval: Some(Duration::days(1)),
+            request_timeout: Some(Duration::seconds(5)),
+        };
+        configure_for_fetch_list(&mut config);
+        assert!(!config.extract_links);
+        assert!(config.ignore_sitemap);
+        assert_eq!(0, config.max_queue_age);
+        assert_eq!(
+            BudgetSetting::SinglePage {
+                recrawl_interval: None,
+                request_timeout: Some(Duration::seconds(5)),
+            },
+            config.budget.default
+        );
+    }
+
+    #[tokio::test]
+    async fn an_interrupted_fetch_list_resumes_and_fetches_every_url_once() {
+        let (context, root) = fixture();
+        let list = FetchList::from_lines(LIST);
+        assert_eq!(6, list.urls().len());
+
+        let progress = list.fill_queue(&context).await.unwrap();
+        assert_eq!(6, progress.pending);
+        assert_eq!(1, progress.invalid);
+        assert_eq!(3, crawl_queue(&context, 3).await);
+
+        // The process dies while fetching the next url.
+        let interrupted = UrlWithDepth::from_url("https://www.third.org/y.html").unwrap();
+        context
+            .get_link_state_manager()
+            .update_link_state_no_meta_and_payload(&interrupted, LinkStateKind::ReservedForCrawl)
+            .await
+            .unwrap();
+
+        let progress = list.write_progress(&context).unwrap();
+        assert_eq!(3, progress.fetched);
+        assert_eq!(3, progress.pending);
+        assert!((progress.completion_percent - 50.0).abs() < f64::EPSILON);
+        let written: serde_json::Value = serde_json::from_str(
+            &std::fs::read_to_string(root.path().join(FETCH_LIST_PROGRESS_FILE)).unwrap(),
+        )
+        .unwrap();
+        assert_matches_schema(FetchListProgress::NAME, &written);
+        assert_eq!(3, written["fetched"]);
+
+        let progress = list.fill_queue(&context).await.unwrap();
+        assert_eq!(3, progress.fetched);
+        assert_eq!(3, progress.pending);
+        assert_eq!(3, crawl_queue(&context, usize::MAX).await);
+
+        let progress = list.write_progress(&context).unwrap();
+        assert!(progress.is_complete());
+        assert!((progress.completion_percent - 100.0).abs() < f64::EPSILON);
+
+        // A third run has nothing left to do.
+        let progress = list.fill_queue(&context).await.unwrap();
+        assert!(progress.is_complete());
+        assert_eq!(0, crawl_queue(&context, usize::MAX).await);
+
+        let mut fetches = HashMap::<AtraUri, usize>::new();
+        for (url, _) in context.provider().validators() {
+            *fetches.entry(url).or_default() += 1;
+        }
+        let (stored, _) = context.get_all_crawled_websites();
+        assert_eq!(6, stored.len());
+        for url in list.urls() {
+            assert_eq!(
+                Some(&1),
+                fetches.get(url.url()),
+                "{url} was not fetched once"
+            );
+            let result = stored.get(url.url()).expect("Every url is stored.");
+            assert_eq!(
+                RawData::from_vec(body(&url.try_as_str()).into_bytes()),
+                result.content
+            );
+            assert!(result.meta.links.is_none());
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(url)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        }
+        assert_eq!(6, fetches.len());
+    }
+}
@@ -0,0 +1,342 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::backfill::{BackfillContext, BackfillError};
+use crate::config::Config;
+use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess, SupportsGdbrRegistry};
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{score_gdbr, SlimCrawlResult};
+use crate::data::{process, RawData};
+use crate::database::{open_db_read_only, DatabaseError, OpenDBError};
+use crate::fetching::ResponseData;
+use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::toolkit::plain_text::plain_text;
+use camino::Utf8Path;
+use isolang::Language;
+use rocksdb::IteratorMode;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::Arc;
+use thiserror::Error;
+
+/// Errors that stop the export of the train data.
+#[derive(Debug, Error)]
+pub enum GdbrExportError {
+    #[error("No gdbr identifier is configured, the texts can not be prelabeled.")]
+    NoClassifier,
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    #[error(transparent)]
+    Context(#[from] BackfillError),
+}
+
+/// The result of an export.
+#[derive(Debug, Default)]
+pub struct GdbrExportReport {
+    /// The number of scanned entries.
+    pub scanned: usize,
+    /// The number of written rows.
+    pub exported: usize,
+    /// The rows prefilled with the prediction of the current model.
+    pub prelabeled: usize,
+    /// The entries skipped because they are no html or in an other language.
+    pub skipped: usize,
+    /// The entries skipped because their body is not readable or has no text.
+    pub unreadable: usize,
+}
+
+impl Display for GdbrExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Scanned:          {}", self.scanned)?;
+        writeln!(f, "Exported:         {}", self.exported)?;
+        writeln!(f, "    Prelabeled:   {}", self.prelabeled)?;
+        writeln!(f, "Skipped:          {}", self.skipped)?;
+        writeln!(f, "Unreadable:       {}", self.unreadable)
+    }
+}
+
+/// Parses an ISO 639-3 or ISO 639-1 code like `deu` or `de`.
+pub fn parse_language(value: &str) -> Result<Language, String> {
+    Language::from_639_3(value)
+        .or_else(|| Language::from_639_1(value))
+        .ok_or_else(|| format!("{value} is no ISO 639-3 or ISO 639-1 language code."))
+}
+
+/// Returns the text of the html [entry] for a row of the train data, with the prediction of
+/// the gdbr identifier if [prelabel] is set. None if the body can not be read or has no text.
+async fn text_of<C>(
+    context: &C,
+    entry: &SlimCrawlResult,
+    prelabel: bool,
+) -> Option<(String, Option<bool>)>
+where
+    C: SupportsConfigs + SupportsFileSystemAccess + SupportsGdbrRegistry,
+{
+    let content = match unsafe { entry.clone().inflate_unchecked() } {
+        Ok(result) => result.content,
+        Err(err) => {
+            log::warn!("Failed to read the body of {}: {err}", entry.meta.url);
+            return None;
+        }
+    };
+    match &content {
+        RawData::None => return None,
+        RawData::ExternalFile { path } if !path.exists() => {
+            log::warn!("The body of {} at {path} is missing.", entry.meta.url);
+            return None;
+        }
+        _ => {}
+    }
+    let meta = &entry.meta;
+    let response = ResponseData::new(
+        content,
+        meta.url.clone(),
+        meta.headers.clone(),
+        meta.status_code,
+        meta.final_redirect_destination.clone(),
+    );
+    let decoded = match process(context, &response, &meta.file_information).await {
+        Ok(decoded) => decoded,
+        Err(err) => {
+            log::warn!("Failed to decode the body of {}: {err}", meta.url);
+            return None;
+        }
+    };
+    let text = match plain_text(&meta.file_information, &decoded) {
+        Ok(text) => text?,
+        Err(err) => {
+            log::warn!("Failed to read the text of {}: {err}", meta.url);
+            return None;
+        }
+    };
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    if !prelabel {
+        return Some((text.to_string(), None));
+    }
+    // The labels of the train data are 1 and -1, a positive score is closer to the class.
+    let prediction = score_gdbr(
+        context,
+        &meta.file_information,
+        &decoded,
+        meta.language_information().as_ref(),
+    )
+    .map(|score| score.0 > 0.0);
+    Some((text.to_string(), prediction))
+}
+
+/// Writes the text of every html page in [entries] detected as [language] as `is_class,text`
+/// row to [output], the format read by `svm::read_train_data`. The column `is_class` is empty
+/// for a manual labeling, unless [prelabel] is set. Then it is prefilled with the prediction
+/// of the gdbr identifier of [context].
+pub(crate) async fn export_gdbr_train_data<C, I, W>(
+    context: &C,
+    entries: I,
+    language: Language,
+    prelabel: bool,
+    output: W,
+) -> Result<GdbrExportReport, GdbrExportError>
+where
+    C: SupportsConfigs + SupportsFileSystemAccess + SupportsGdbrRegistry,
+    I: IntoIterator<Item = SlimCrawlResult>,
+    W: Write,
+{
+    if prelabel && context.gdbr_registry().is_none() {
+        return Err(GdbrExportError::NoClassifier);
+    }
+    let mut writer = csv::Writer::from_writer(output);
+    writer.write_record(["is_class", "text"])?;
+    let mut report = GdbrExportReport::default();
+    for entry in entries {
+        report.scanned += 1;
+        if entry.meta.file_information.format != InterpretedProcessibleFileFormat::HTML
+            || entry.meta.language.as_ref().map(|value| value.lang()) != Some(language)
+        {
+            report.skipped += 1;
+            continue;
+        }
+        let Some((text, prediction)) = text_of(context, &entry, prelabel).await else {
+            report.unreadable += 1;
+            continue;
+        };
+        let is_class = match prediction {
+            Some(value) => {
+                report.prelabeled += 1;
+                if value {
+                    "true"
+                } else {
+                    "false"
+                }
+            }
+            None => "",
+        };
+        writer.write_record([is_class, text.as_str()])?;
+        report.exported += 1;
+    }
+    writer.flush()?;
+    Ok(report)
+}
+
+/// Exports the train data of the stored pages of [config] to the new file [output],
+/// see [export_gdbr_train_data]. The crawl db is opened read-only.
+pub(crate) async fn gdbr_export(
+    config: &Config,
+    output: &Utf8Path,
+    language: Language,
+    prelabel: bool,
+) -> Result<GdbrExportReport, GdbrExportError> {
+    let db = Arc::new(open_db_read_only(config.paths.dir_database())?);
+    let crawl_db = CrawlDB::new(db, config)?;
+    let context = BackfillContext::new(config)?;
+    let entries = crawl_db
+        .iter(IteratorMode::Start)
+        .filter_map(|value| match value {
+            Ok((k, v)) => match SlimCrawlResult::decode(&v) {
+                Ok(entry) => Some(entry),
+                Err(err) => {
+                    log::warn!(
+                        "Failed to read the entry of {} with: {err}",
+                        String::from_utf8_lossy(k.as_ref())
+                    );
+                    None
+                }
+            },
+            Err(err) => {
+                log::warn!("Failed to read from the crawl db: {err}");
+                None
+            }
+        });
+    let output = BufWriter::new(File::options().write(true).create_new(true).open(output)?);
+    export_gdbr_train_data(&context, entries, language, prelabel, output).await
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::gdbr_export::{export_gdbr_train_data, parse_language, GdbrExportError};
+    use crate::contexts::traits::SupportsCrawlResults;
+    use crate::crawl::test::create_test_data;
+    use crate::data::RawVecData;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::test_impls::TestContext;
+    use crate::toolkit::LanguageInformation;
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use isolang::Language;
+    use std::fs::File;
+    use svm::read_train_data;
+    use text_processing::tf_idf::Idf;
+
+    #[tokio::test]
+    async fn the_export_can_be_read_as_train_data() {
+        let context = TestContext::default();
+        for (url, html, language, format) in [
+            (
+                "https://www.example.com/a",
+                "<html><body><p>Wir verwenden \"Cookies\", sagt er.\nJa, wirklich.</p></body></html>",
+                LanguageInformation::DEU,
+                InterpretedProcessibleFileFormat::HTML,
+            ),
+            (
+                "https://www.example.com/b",
+                "<html><body><p>Ihre Daten, \"sicher\"\r\nbei uns</p></body></html>",
+                LanguageInformation::DEU,
+                InterpretedProcessibleFileFormat::HTML,
+            ),
+            (
+                "https://www.example.com/english",
+                "<html><body><p>We use cookies.</p></body></html>",
+                LanguageInformation::ENG,
+                InterpretedProcessibleFileFormat::HTML,
+            ),
+            (
+                "https://www.example.com/text",
+                "Nur Text",
+                LanguageInformation::DEU,
+                InterpretedProcessibleFileFormat::PlainText,
+            ),
+            (
+                "https://www.example.com/empty",
+                "<html><body> </body></html>",
+                LanguageInformation::DEU,
+                InterpretedProcessibleFileFormat::HTML,
+            ),
+        ] {
+            let mut result = create_test_data(
+                UrlWithDepth::from_url(url).unwrap(),
+                Some(RawVecData::from_vec(html.as_bytes().to_vec())),
+            );
+            result.meta.language = Some(language);
+            result.meta.file_information.format = format;
+            context.store_crawled_website(&result).await.unwrap();
+        }
+        let mut entries: Vec<_> = context
+            .crawled_websites
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect();
+        entries.sort_by_key(|value| value.meta.url.to_string());
+
+        assert!(matches!(
+            export_gdbr_train_data(&context, entries.clone(), Language::Deu, true, Vec::new())
+                .await,
+            Err(GdbrExportError::NoClassifier)
+        ));
+
+        let root = Utf8TempDir::new().unwrap();
+        let path = root.path().join("train.csv");
+        let report = export_gdbr_train_data(
+            &context,
+            entries,
+            parse_language("deu").unwrap(),
+            false,
+            File::create(&path).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(5, report.scanned);
+        assert_eq!(2, report.exported);
+        assert_eq!(0, report.prelabeled);
+        assert_eq!(2, report.skipped);
+        assert_eq!(1, report.unreadable);
+
+        let read: Vec<_> = read_train_data::<Idf>(&path).unwrap().collect();
+        assert_eq!(2, read.len());
+        assert_eq!(
+            "Wir verwenden \"Cookies\", sagt er.\nJa, wirklich.",
+            read[0].text
+        );
+        assert_eq!("Ihre Daten, \"sicher\"\r\nbei uns", read[1].text);
+        assert!(read.iter().all(|value| !value.is_class));
+    }
+
+    #[test]
+    fn parses_the_language_codes() {
+        assert_eq!(Ok(Language::Deu), parse_language("deu"));
+        assert_eq!(Ok(Language::Deu), parse_language("de"));
+        assert!(parse_language("xx").is_err());
+    }
+}
@@ -0,0 +1,325 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::contexts::local::{LocalContext, LocalContextInitError};
+use crate::contexts::traits::{
+    SupportsConfigs, SupportsCrawlEvents, SupportsCrawlResults, SupportsFileSystemAccess,
+    SupportsGdbrRegistry, SupportsLinkSeeding, SupportsLinkState, SupportsUrlQueue,
+};
+use crate::contexts::worker::{WorkerContext, WorkerContextCreationError};
+use crate::crawl::{analyze_page, CrawlEvent, ImportNote};
+use crate::fetching::har::Har;
+use crate::link_state::{IsSeedYesNo, LinkStateKind, LinkStateManager};
+use crate::queue::{UrlQueue, UrlQueueElement};
+use crate::url::UrlWithDepth;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// Errors that stop an import.
+#[derive(Debug, Error)]
+pub enum ImportHarError {
+    #[error(transparent)]
+    Context(#[from] LocalContextInitError),
+    #[error(transparent)]
+    Worker(#[from] WorkerContextCreationError),
+    #[error("Failed to read {0}: {1}")]
+    IO(Utf8PathBuf, std::io::Error),
+    #[error("{0} is not a valid HAR file: {1}")]
+    InvalidHar(Utf8PathBuf, serde_json::Error),
+    #[error("Failed to store {0}: {1}")]
+    Store(String, String),
+}
+
+/// The result of an import.
+#[derive(Debug, Default)]
+pub struct ImportHarReport {
+    /// The number of read entries.
+    pub entries: usize,
+    /// The entries processed and stored like fetched pages.
+    pub imported: usize,
+    /// The entries that can not be imported, with the reason.
+    pub skipped: Vec<String>,
+    /// The extracted urls enqueued with `--follow-links`.
+    pub enqueued: usize,
+}
+
+impl Display for ImportHarReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Entries:          {}", self.entries)?;
+        writeln!(f, "Imported:         {}", self.imported)?;
+        writeln!(f, "Enqueued links:   {}", self.enqueued)?;
+        writeln!(f, "Skipped:          {}", self.skipped.len())?;
+        for reason in &self.skipped {
+            writeln!(f, "    {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports the [har_files] into the session of [config].
+pub async fn import_har(
+    config: &Config,
+    har_files: &[Utf8PathBuf],
+    follow_links: bool,
+) -> Result<ImportHarReport, ImportHarError> {
+    // The worker writes the bodies to its own warc files, like a crawl does.
+    let context = WorkerContext::create(
+        0,
+        0,
+        Arc::new(LocalContext::new_without_runtime(config.clone())?),
+    )?;
+    let mut report = ImportHarReport::default();
+    for file in har_files {
+        import_har_file(&context, file, follow_links, &mut report).await?;
+    }
+    Ok(report)
+}
+
+/// Processes and stores the responses of the HAR [file] like fetched pages. There is no
+/// politeness or budget involved. The extracted links are only enqueued if [follow_links]
+/// is set. Entries without a body or with an unsupported scheme are skipped.
+pub async fn import_har_file<C>(
+    context: &C,
+    file: &Utf8Path,
+    follow_links: bool,
+    report: &mut ImportHarReport,
+) -> Result<(), ImportHarError>
+where
+    C: SupportsConfigs
+        + SupportsFileSystemAccess
+        + SupportsGdbrRegistry
+        + SupportsCrawlEvents
+        + SupportsCrawlResults
+        + SupportsLinkState
+        + SupportsLinkSeeding
+        + SupportsUrlQueue,
+{
+    let reader = File::open(file).map_err(|err| ImportHarError::IO(file.to_path_buf(), err))?;
+    let har: Har = serde_json::from_reader(BufReader::new(reader))
+        .map_err(|err| ImportHarError::InvalidHar(file.to_path_buf(), err))?;
+    if har
+        .log
+        .version
+        .as_deref()
+        .is_some_and(|version| version != "1.2")
+    {
+        log::warn!(
+            "{file} is a HAR {}, only 1.2 is supported. Try to import it anyway.",
+            har.log.version.as_deref().unwrap_or_default()
+        );
+    }
+
+    let imported_at = OffsetDateTime::now_utc();
+    for entry in har.log.entries {
+        report.entries += 1;
+        let imported = match entry.to_response() {
+            Ok(imported) => imported,
+            Err(err) => {
+                log::warn!("Skip an entry of {file}: {err}");
+                report.skipped.push(err.to_string());
+                continue;
+            }
+        };
+        let target = imported.url;
+        let Some(mut result) = analyze_page(context, imported.page, &target).await else {
+            report
+                .skipped
+                .push(format!("The response of {target} can not be processed."));
+            continue;
+        };
+        result.meta.import = Some(ImportNote {
+            source: file.to_string(),
+            imported_at,
+        });
+        result.meta.fetch_timings = imported.timings;
+
+        if follow_links {
+            if let Some(links) = &result.meta.links {
+                let links = HashSet::from_iter(links.iter().cloned());
+                // The outgoing links are enqueued by the context, the ones on the seed are ours.
+                let on_seed = context
+                    .handle_links(&target, &links)
+                    .await
+                    .map_err(|err| ImportHarError::Store(target.to_string(), err.to_string()))?;
+                for (url, score) in on_seed {
+                    report.enqueued += enqueue_if_unknown(context, url, score).await?;
+                }
+            }
+        }
+
+        context
+            .store_crawled_website(&result)
+            .await
+            .map_err(|err| ImportHarError::Store(target.to_string(), err.to_string()))?;
+        context
+            .get_link_state_manager()
+            .update_link_state_no_payload(&target, LinkStateKind::ProcessedAndStored, None, None)
+            .await
+            .map_err(|err| ImportHarError::Store(target.to_string(), err.to_string()))?;
+        context
+            .crawl_events()
+            .emit(CrawlEvent::stored(target.clone(), &result, None));
+        log::info!("Imported: {target}");
+        report.imported += 1;
+    }
+    Ok(())
+}
+
+/// Enqueues the [url] if it has no link state yet, returns the number of enqueued urls.
+async fn enqueue_if_unknown<C>(
+    context: &C,
+    url: UrlWithDepth,
+    priority: i8,
+) -> Result<usize, ImportHarError>
+where
+    C: SupportsLinkState + SupportsUrlQueue,
+{
+    let manager = context.get_link_state_manager();
+    let known = manager
+        .get_link_state(&url)
+        .await
+        .map_err(|err| ImportHarError::Store(url.to_string(), err.to_string()))?;
+    if known.is_some() {
+        return Ok(0);
+    }
+    manager
+        .update_link_state_no_payload(&url, LinkStateKind::Discovered, Some(IsSeedYesNo::No), None)
+        .await
+        .map_err(|err| ImportHarError::Store(url.to_string(), err.to_string()))?;
+    context
+        .url_queue()
+        .enqueue(UrlQueueElement::new(false, 0, false, url.clone()).with_priority(priority))
+        .await
+        .map_err(|err| ImportHarError::Store(url.to_string(), err.to_string()))?;
+    Ok(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::data::RawData;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::link_state::LinkStateLike;
+    use crate::test_impls::TestContext;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/samples/flow.har");
+
+    #[tokio::test]
+    async fn imports_the_entries_of_a_har_file() {
+        let context = TestContext::default();
+        let mut report = ImportHarReport::default();
+        import_har_file(&context, Utf8Path::new(FIXTURE), true, &mut report)
+            .await
+            .unwrap();
+        assert_eq!(3, report.entries);
+        assert_eq!(3, report.imported, "{report}");
+        assert!(report.skipped.is_empty(), "{report}");
+
+        let (stored, _) = context.get_all_crawled_websites();
+        assert_eq!(3, stored.len());
+        for result in stored.values() {
+            let import = result
+                .meta
+                .import
+                .as_ref()
+                .expect("Not flagged as imported!");
+            assert_eq!(FIXTURE, import.source);
+        }
+
+        let account = UrlWithDepth::from_url("https://shop.example.com/account").unwrap();
+        let page = &stored[&account.url].meta;
+        assert_eq!(
+            InterpretedProcessibleFileFormat::HTML,
+            page.file_information.format
+        );
+        let links: Vec<String> = page
+            .links
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|link| link.to_string())
+            .collect();
+        assert!(
+            links
+                .iter()
+                .any(|link| link.contains("https://shop.example.com/orders")),
+            "{links:?}"
+        );
+        assert!(
+            links
+                .iter()
+                .any(|link| link.contains("https://other.example.org/help")),
+            "{links:?}"
+        );
+        let timings = page.fetch_timings.as_ref().unwrap();
+        assert_eq!(time::Duration::milliseconds(120), timings.total);
+        assert_eq!(Some(time::Duration::milliseconds(80)), timings.wait);
+        assert_eq!(None, timings.ssl);
+
+        // The base64 body is decoded.
+        let orders = UrlWithDepth::from_url("https://shop.example.com/api/orders.json").unwrap();
+        match &stored[&orders.url].content {
+            RawData::InMemory { data } => {
+                assert_eq!(
+                    br#"{"orders":[{"id":1,"total":"12.50"}]}"#.as_slice(),
+                    data.as_slice()
+                )
+            }
+            other => panic!("Unexpected content {other:?}"),
+        }
+
+        for (url, kind) in [
+            (
+                "https://shop.example.com/account",
+                LinkStateKind::ProcessedAndStored,
+            ),
+            ("https://shop.example.com/orders", LinkStateKind::Discovered),
+            ("https://other.example.org/help", LinkStateKind::Discovered),
+        ] {
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(&UrlWithDepth::from_url(url).unwrap())
+                .await
+                .unwrap()
+                .unwrap_or_else(|| panic!("No link state for {url}"));
+            assert_eq!(kind, state.kind(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn links_are_only_followed_on_request() {
+        let context = TestContext::default();
+        let mut report = ImportHarReport::default();
+        import_har_file(&context, Utf8Path::new(FIXTURE), false, &mut report)
+            .await
+            .unwrap();
+        assert_eq!(3, report.imported);
+        assert_eq!(0, report.enqueued);
+        assert!(context.url_queue().is_empty().await);
+        let orders = UrlWithDepth::from_url("https://shop.example.com/orders").unwrap();
+        assert!(context
+            .get_link_state_manager()
+            .get_link_state(&orders)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
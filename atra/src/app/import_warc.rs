@@ -0,0 +1,308 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::contexts::local::{LocalContext, LocalContextInitError};
+use crate::contexts::traits::{
+    SupportsConfigs, SupportsCrawlEvents, SupportsCrawlResults, SupportsFileSystemAccess,
+    SupportsGdbrRegistry, SupportsLinkState,
+};
+use crate::contexts::worker::{WorkerContext, WorkerContextCreationError};
+use crate::crawl::{analyze_page, CrawlEvent, ImportNote};
+use crate::fetching::warc_record::warc_response_to_fetched;
+use crate::link_state::{LinkStateKind, LinkStateManager};
+use crate::warc_ext::WarcRecordScanner;
+use camino::{Utf8Path, Utf8PathBuf};
+use flate2::read::MultiGzDecoder;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+use warc::reader::WarcCursorReadError;
+use warc::record_type::WarcRecordType;
+
+/// Errors that stop an import.
+#[derive(Debug, Error)]
+pub enum ImportWarcError {
+    #[error(transparent)]
+    Context(#[from] LocalContextInitError),
+    #[error(transparent)]
+    Worker(#[from] WorkerContextCreationError),
+    #[error("Failed to read {0}: {1}")]
+    IO(Utf8PathBuf, std::io::Error),
+    #[error("{0} is not a valid warc file: {1}")]
+    InvalidWarc(Utf8PathBuf, WarcCursorReadError),
+    #[error("Failed to store {0}: {1}")]
+    Store(String, String),
+}
+
+/// The result of an import.
+#[derive(Debug, Default)]
+pub struct ImportWarcReport {
+    /// The number of read records.
+    pub records: usize,
+    /// The responses processed and stored like fetched pages.
+    pub imported: usize,
+    /// The number of records that are no responses, by their WARC-Type.
+    pub other_records: BTreeMap<String, usize>,
+    /// The responses that can not be imported, with the reason.
+    pub skipped: Vec<String>,
+}
+
+impl Display for ImportWarcReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Records:          {}", self.records)?;
+        writeln!(f, "Imported:         {}", self.imported)?;
+        writeln!(
+            f,
+            "Other records:    {}",
+            self.other_records.values().sum::<usize>()
+        )?;
+        for (record_type, count) in &self.other_records {
+            writeln!(f, "    {record_type}: {count}")?;
+        }
+        writeln!(f, "Skipped:          {}", self.skipped.len())?;
+        for reason in &self.skipped {
+            writeln!(f, "    {reason}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports the [warc_files] into the session of [config].
+pub async fn import_warc(
+    config: &Config,
+    warc_files: &[Utf8PathBuf],
+) -> Result<ImportWarcReport, ImportWarcError> {
+    // The worker writes the bodies to its own warc files, like a crawl does.
+    let context = WorkerContext::create(
+        0,
+        0,
+        Arc::new(LocalContext::new_without_runtime(config.clone())?),
+    )?;
+    let mut report = ImportWarcReport::default();
+    for file in warc_files {
+        import_warc_file(&context, file, &mut report).await?;
+    }
+    Ok(report)
+}
+
+/// Processes and stores the `response` records of the warc [file] like fetched pages,
+/// a `.gz` file is decompressed. The link states are set as if the pages were crawled
+/// at the WARC-Date. Other records and responses that can not be converted are skipped.
+pub async fn import_warc_file<C>(
+    context: &C,
+    file: &Utf8Path,
+    report: &mut ImportWarcReport,
+) -> Result<(), ImportWarcError>
+where
+    C: SupportsConfigs
+        + SupportsFileSystemAccess
+        + SupportsGdbrRegistry
+        + SupportsCrawlEvents
+        + SupportsCrawlResults
+        + SupportsLinkState,
+{
+    let reader = File::open(file).map_err(|err| ImportWarcError::IO(file.to_path_buf(), err))?;
+    let reader: Box<dyn BufRead> = if file.extension() == Some("gz") {
+        Box::new(BufReader::new(MultiGzDecoder::new(reader)))
+    } else {
+        Box::new(BufReader::new(reader))
+    };
+    let mut scanner = WarcRecordScanner::new(reader);
+
+    let imported_at = OffsetDateTime::now_utc();
+    while let Some((header, _, block)) = scanner
+        .next_record_with_body()
+        .map_err(|err| ImportWarcError::InvalidWarc(file.to_path_buf(), err))?
+    {
+        report.records += 1;
+        match header.get_warc_type() {
+            Ok(WarcRecordType::Response) => {}
+            Ok(other) => {
+                *report.other_records.entry(other.to_string()).or_default() += 1;
+                continue;
+            }
+            Err(err) => {
+                report
+                    .skipped
+                    .push(format!("A record of {file} is invalid: {err}"));
+                continue;
+            }
+        }
+        let imported = match warc_response_to_fetched(&header, &block) {
+            Ok(imported) => imported,
+            Err(err) => {
+                log::warn!("Skip a record of {file}: {err}");
+                report.skipped.push(err.to_string());
+                continue;
+            }
+        };
+        drop(block);
+        let target = imported.url;
+        let Some(mut result) = analyze_page(context, imported.page, &target).await else {
+            report
+                .skipped
+                .push(format!("The response of {target} can not be processed."));
+            continue;
+        };
+        result.meta.created_at = imported.fetched_at;
+        result.meta.import = Some(ImportNote {
+            source: file.to_string(),
+            imported_at,
+        });
+
+        context
+            .store_crawled_website(&result)
+            .await
+            .map_err(|err| ImportWarcError::Store(target.to_string(), err.to_string()))?;
+        context
+            .get_link_state_manager()
+            .update_link_state_at(
+                &target,
+                LinkStateKind::ProcessedAndStored,
+                imported.fetched_at,
+            )
+            .await
+            .map_err(|err| ImportWarcError::Store(target.to_string(), err.to_string()))?;
+        context
+            .crawl_events()
+            .emit(CrawlEvent::stored(target.clone(), &result, None));
+        log::info!("Imported: {target}");
+        report.imported += 1;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::app::view::{export_jsonl, JsonlOptions};
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::link_state::LinkStateLike;
+    use crate::test_impls::TestContext;
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const FIXTURE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/testdata/samples/heritrix.warc"
+    );
+
+    #[tokio::test]
+    async fn imports_the_responses_of_a_warc_file() {
+        let context = TestContext::default();
+        let mut report = ImportWarcReport::default();
+        import_warc_file(&context, Utf8Path::new(FIXTURE), &mut report)
+            .await
+            .unwrap();
+        assert_eq!(6, report.records, "{report}");
+        assert_eq!(2, report.imported, "{report}");
+        assert_eq!(
+            BTreeMap::from([
+                ("metadata".to_string(), 1usize),
+                ("request".to_string(), 1usize),
+                ("warcinfo".to_string(), 1usize),
+            ]),
+            report.other_records
+        );
+        assert_eq!(1, report.skipped.len(), "{report}");
+        assert!(
+            report.skipped[0].contains("dns:www.example.com"),
+            "{report}"
+        );
+
+        let (stored, _) = context.get_all_crawled_websites();
+        assert_eq!(2, stored.len());
+        // The WARC-Date of the responses, 2023-05-04T10:20:30Z.
+        let fetched_at = OffsetDateTime::from_unix_timestamp(1_683_195_630).unwrap();
+
+        let page = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let meta = &stored[&page.url].meta;
+        assert_eq!(
+            InterpretedProcessibleFileFormat::HTML,
+            meta.file_information.format
+        );
+        assert_eq!(fetched_at, meta.created_at);
+        assert_eq!(FIXTURE, meta.import.as_ref().unwrap().source);
+        let links: Vec<String> = meta
+            .links
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|link| link.to_string())
+            .collect();
+        assert!(
+            links
+                .iter()
+                .any(|link| link.contains("https://www.example.com/about")),
+            "{links:?}"
+        );
+
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&page)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        assert_eq!(fetched_at, state.timestamp());
+
+        // The chunked body is decoded.
+        let text = UrlWithDepth::from_url("https://www.example.com/robots.txt").unwrap();
+        assert_eq!(
+            b"User-agent: *\nDisallow:\n".as_slice(),
+            stored[&text.url].content.as_in_memory().unwrap().as_slice()
+        );
+    }
+
+    #[tokio::test]
+    async fn imported_pages_are_viewable() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+
+        let compressed = root.path().join("heritrix.warc.gz");
+        let mut encoder =
+            GzEncoder::new(File::create(&compressed).unwrap(), Compression::default());
+        encoder.write_all(&std::fs::read(FIXTURE).unwrap()).unwrap();
+        encoder.finish().unwrap();
+
+        let report = import_warc(&config, &[compressed]).await.unwrap();
+        assert_eq!(2, report.imported, "{report}");
+
+        let local = LocalContext::new_without_runtime(config).unwrap();
+        let mut out = Vec::new();
+        let written = export_jsonl(&local, &mut out, JsonlOptions::default(), None).unwrap();
+        assert_eq!(2, written);
+        let lines: Vec<serde_json::Value> = String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let page = lines
+            .iter()
+            .find(|line| line["url"] == "https://www.example.com/")
+            .expect("The page is not viewable!");
+        assert_eq!(200, page["status_code"]);
+        assert_eq!("HTML", page["format"]);
+        assert_eq!(1, page["warc"].as_array().unwrap().len());
+    }
+}
@@ -12,6 +12,19 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::app::backfill::BackfillError;
+use crate::app::dry_run::DryRunError;
+use crate::app::expired::ExpiredError;
+use crate::app::gdbr_export::GdbrExportError;
+use crate::app::import_har::ImportHarError;
+use crate::app::import_warc::ImportWarcError;
+use crate::app::maintain::MaintainError;
+use crate::app::rebuild::RebuildError;
+use crate::app::requeue::RequeueError;
+use crate::app::secondary::SecondaryError;
+use crate::app::verify::VerifyError;
+use crate::app::view::{BulkExportError, ParquetExportError, SimilarPagesError};
+use crate::config::profiles::ProfileError;
 use camino::Utf8PathBuf;
 use thiserror::Error;
 
@@ -26,6 +39,42 @@ pub enum InstructionError {
     ConfigDeserializationError(serde_json::Error),
     #[error("The path {0} already exists.")]
     RootAlreadyExists(Utf8PathBuf),
+    #[error("The baseline {0} is not the folder of a crawl.")]
+    BaselineNotFound(Utf8PathBuf),
     #[error(transparent)]
     DumbSerialisationError(serde_json::Error),
+    #[error(transparent)]
+    RebuildError(#[from] RebuildError),
+    #[error(transparent)]
+    ExportSerialisationError(serde_json::Error),
+    #[error(transparent)]
+    InvalidUrl(#[from] crate::url::ParseError),
+    #[error(transparent)]
+    ExpiredError(#[from] ExpiredError),
+    #[error(transparent)]
+    DryRunError(#[from] DryRunError),
+    #[error(transparent)]
+    RequeueError(#[from] RequeueError),
+    #[error(transparent)]
+    SecondaryError(#[from] SecondaryError),
+    #[error(transparent)]
+    ProfileError(#[from] ProfileError),
+    #[error(transparent)]
+    BackfillError(#[from] BackfillError),
+    #[error(transparent)]
+    ImportHarError(#[from] ImportHarError),
+    #[error(transparent)]
+    ImportWarcError(#[from] ImportWarcError),
+    #[error(transparent)]
+    SimilarPagesError(#[from] SimilarPagesError),
+    #[error(transparent)]
+    ParquetExportError(#[from] ParquetExportError),
+    #[error(transparent)]
+    BulkExportError(#[from] BulkExportError),
+    #[error(transparent)]
+    MaintainError(#[from] MaintainError),
+    #[error(transparent)]
+    GdbrExportError(#[from] GdbrExportError),
+    #[error(transparent)]
+    VerifyError(#[from] VerifyError),
 }
@@ -15,6 +15,7 @@
 use crate::app::ApplicationMode;
 use crate::config::Config;
 use crate::seed::SeedDefinition;
+use camino::Utf8PathBuf;
 
 /// The kind of instruction provided by the args.
 #[derive(Debug)]
@@ -30,4 +31,8 @@ pub struct RunInstruction {
     pub config: Config,
     pub seeds: Option<SeedDefinition>,
     pub recover_mode: bool,
+    /// The file with the urls of a fetch list, they replace the content of the queue.
+    pub fetch_list: Option<Utf8PathBuf>,
+    /// Shows the status screen instead of logging to the console.
+    pub tui: bool,
 }
@@ -18,18 +18,39 @@ mod instruction;
 use crate::app::args::RunMode;
 use crate::app::config::{discover, discover_or_default, try_load_from_path};
 use crate::app::constants::{create_example_config, ATRA_LOGO, ATRA_WELCOME};
-use crate::app::view::view;
+use crate::app::view::{
+    export_audit_trail, export_bulk, export_jsonl, export_matching_pages, export_parquet,
+    export_web_graph, view, write_similar_pages, JsonlOptions, ParquetExportOptions, ViewFormat,
+    WebGraphExportOptions,
+};
 use crate::app::{ApplicationMode, AtraArgs};
-use crate::config::{BudgetSetting, Config};
+use crate::config::{BudgetSetting, Config, CooperativeConfig};
 use crate::contexts::local::LocalContext;
+use crate::crawl::confirm_next_wave;
+use crate::queue::priority::UrlPriorityScorer;
+use crate::schemas::json_schema;
+use crate::seed::SeedDefinition;
+use crate::url::{AtraUrlOrigin, UrlWithDepth};
 use camino::Utf8PathBuf;
 pub use error::*;
 pub use instruction::*;
 use std::fs::File;
 use std::io::{BufReader, BufWriter, ErrorKind};
 use std::num::NonZeroUsize;
-use time::Duration;
+use time::{Duration, OffsetDateTime};
 use crate::app::dump::dump;
+use crate::app::fetchlist::configure_for_fetch_list;
+use crate::app::rebuild::rebuild_db;
+use crate::app::expired::expired_entries;
+use crate::app::dry_run::{dry_run, DryRunError};
+use crate::app::requeue::requeue_failed;
+use crate::app::backfill::backfill;
+use crate::app::import_har::import_har;
+use crate::app::import_warc::import_warc;
+use crate::app::maintain::maintain;
+use crate::app::gdbr_export::gdbr_export;
+use crate::app::verify::{verify, VerifyError};
+use crate::app::secondary::run_secondary;
 
 /// Consumes the args and returns everything necessary to execute Atra
 pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, InstructionError> {
@@ -45,6 +66,8 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 log_level,
                 log_to_file,
                 delay,
+                dry_run: only_validate,
+                tui,
             } => {
                 let mut config = discover_or_default().unwrap_or_default();
 
@@ -95,11 +118,17 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
 
                 config.system.log_to_file = log_to_file;
 
+                if only_validate {
+                    return validate_seeds(&config, &seeds);
+                }
+
                 Ok(Instruction::RunInstruction(RunInstruction {
                     mode: ApplicationMode::Single,
                     config,
                     seeds: Some(seeds),
                     recover_mode: false,
+                    fetch_list: None,
+                    tui,
                 }))
             }
             RunMode::MULTI {
@@ -109,7 +138,11 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 threads,
                 override_log_level: log_level,
                 log_to_file,
-                override_root_dir_name
+                override_root_dir_name,
+                dry_run: only_validate,
+                listen,
+                baseline,
+                tui,
             } => {
                 let mut config = match configs_folder {
                     None => discover(),
@@ -158,6 +191,24 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                     config.system.log_level = log_level;
                 }
 
+                if let Some(socket) = listen {
+                    config.system.cooperative = Some(CooperativeConfig::new(socket.into()));
+                }
+
+                if let Some(baseline) = baseline {
+                    let baseline = Utf8PathBuf::from(baseline);
+                    if !baseline.is_dir() {
+                        return Err(InstructionError::BaselineNotFound(baseline));
+                    }
+                    let mut baseline_config = try_load_from_path(&baseline)?;
+                    baseline_config.paths.root = baseline;
+                    config.system.baseline = Some(baseline_config.paths.dir_database());
+                }
+
+                if only_validate {
+                    return validate_seeds(&config, &seeds);
+                }
+
                 Ok(Instruction::RunInstruction(RunInstruction {
                     mode: ApplicationMode::Multi(
                         threads.map(|value| NonZeroUsize::new(value)).flatten(),
@@ -165,6 +216,70 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                     config,
                     seeds: Some(seeds),
                     recover_mode: false,
+                    fetch_list: None,
+                    tui,
+                }))
+            }
+            RunMode::FETCHLIST {
+                threads,
+                config: configs_folder,
+                override_root_dir_name,
+                log_to_file,
+                list,
+            } => {
+                let list = Utf8PathBuf::from(list);
+                if !list.is_file() {
+                    return Err(std::io::Error::new(
+                        ErrorKind::NotFound,
+                        format!("The fetch list {list} is not a file!"),
+                    )
+                    .into());
+                }
+
+                let mut config = match configs_folder {
+                    None => discover(),
+                    Some(path) => try_load_from_path(path),
+                }?;
+
+                let root = if let Some(override_root_dir_name) = override_root_dir_name {
+                    let check_if_absolute = Utf8PathBuf::from(&override_root_dir_name);
+                    if check_if_absolute.is_absolute() {
+                        check_if_absolute
+                    } else {
+                        config.paths.root_path().join(override_root_dir_name)
+                    }
+                } else {
+                    config
+                        .paths
+                        .root_path()
+                        .join(format!("fetchlist_{}", list.file_stem().unwrap_or("list")))
+                };
+
+                if root.is_dir() {
+                    log::info!("Resume the fetch list in {root}.");
+                    config = try_load_from_path(&root)?;
+                }
+                config.paths.root = root;
+
+                log::info!(
+                    "Session Info: {} - {} - {}",
+                    config.session.service,
+                    config.session.collection,
+                    config.session.crawl_job_id
+                );
+
+                configure_for_fetch_list(&mut config.crawl);
+                config.system.log_to_file = log_to_file;
+
+                Ok(Instruction::RunInstruction(RunInstruction {
+                    mode: ApplicationMode::Multi(
+                        threads.map(|value| NonZeroUsize::new(value)).flatten(),
+                    ),
+                    config,
+                    seeds: None,
+                    recover_mode: false,
+                    fetch_list: Some(list),
+                    tui: false,
                 }))
             }
             RunMode::INIT => {
@@ -209,6 +324,7 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 } else if path.is_file() {
                     let file = File::options().read(true).open(&path)?;
                     let mut cfg: Config = serde_json::from_reader(BufReader::new(file)).map_err(InstructionError::ConfigDeserializationError)?;
+                    cfg.crawl.resolve_profiles()?;
                     cfg.paths.root = if let Some(parent) = path.parent() {
                         parent.to_path_buf()
                     } else {
@@ -259,6 +375,8 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                     config,
                     seeds: None,
                     recover_mode: true,
+                    fetch_list: None,
+                    tui: false,
                 }))
             }
             RunMode::VIEW {
@@ -266,8 +384,135 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 internals,
                 extracted_links,
                 headers,
+                format,
+                output,
+                include_expired,
+                text,
+                filter,
+                audit,
+                export_webgraph,
+                webgraph_format,
+                webgraph_nodes,
+                export_parquet: parquet_dir,
+                parquet_compression,
+                parquet_row_group_size,
+                similar_to,
+                similar_limit,
             } => {
                 let config = string_to_config_path(&path)?;
+                if let Some(similar_to) = similar_to {
+                    let local = LocalContext::new_without_runtime(config)
+                        .expect("Was not able to load context for reading!");
+                    let written = if let Some(output) = output {
+                        let file = File::options().write(true).create_new(true).open(output)?;
+                        write_similar_pages(
+                            &local,
+                            &similar_to,
+                            similar_limit,
+                            format,
+                            BufWriter::new(file),
+                        )
+                    } else {
+                        write_similar_pages(
+                            &local,
+                            &similar_to,
+                            similar_limit,
+                            format,
+                            std::io::stdout().lock(),
+                        )
+                    }?;
+                    log::info!("Found {written} similar pages.");
+                    return Ok(Instruction::Nothing);
+                }
+                if let Some(export_webgraph) = export_webgraph {
+                    let graph = BufReader::new(File::open(config.paths.file_web_graph())?);
+                    let local = LocalContext::new_without_runtime(config)
+                        .expect("Was not able to load context for reading!");
+                    let file = File::options()
+                        .write(true)
+                        .create_new(true)
+                        .open(export_webgraph)?;
+                    let counts = export_web_graph(
+                        graph,
+                        BufWriter::new(file),
+                        WebGraphExportOptions {
+                            format: webgraph_format,
+                            nodes: webgraph_nodes,
+                        },
+                        &local,
+                    )?;
+                    log::info!(
+                        "Exported {} nodes and {} edges of the webgraph.",
+                        counts.nodes,
+                        counts.edges
+                    );
+                    return Ok(Instruction::Nothing);
+                }
+                if let Some(parquet_dir) = parquet_dir {
+                    let local = LocalContext::new_without_runtime(config)
+                        .expect("Was not able to load context for reading!");
+                    let counts = export_parquet(
+                        &local,
+                        &Utf8PathBuf::from(parquet_dir),
+                        ParquetExportOptions {
+                            compression: parquet_compression,
+                            row_group_size: parquet_row_group_size,
+                            include_expired,
+                        },
+                    )?;
+                    log::info!(
+                        "Exported {} pages and {} edges to parquet.",
+                        counts.pages,
+                        counts.edges
+                    );
+                    return Ok(Instruction::Nothing);
+                }
+                if audit {
+                    let root = config.paths.root_path();
+                    let written = if let Some(output) = output {
+                        let file = File::options().write(true).create_new(true).open(output)?;
+                        export_audit_trail(root, BufWriter::new(file), format)
+                    } else {
+                        export_audit_trail(root, std::io::stdout().lock(), format)
+                    }?;
+                    log::info!("Exported {written} audit records.");
+                    return Ok(Instruction::Nothing);
+                }
+                if format == ViewFormat::Jsonl {
+                    let text = text.then_some(config.crawl.text_normalization);
+                    let local = LocalContext::new_without_runtime(config)
+                        .expect("Was not able to load context for reading!");
+                    let options = JsonlOptions {
+                        internals,
+                        extracted_links,
+                        headers,
+                        include_expired,
+                        text,
+                    };
+                    let written = if let Some(output) = output {
+                        let file = File::options().write(true).create_new(true).open(output)?;
+                        export_jsonl(&local, BufWriter::new(file), options, filter.as_ref())
+                    } else {
+                        export_jsonl(&local, std::io::stdout().lock(), options, filter.as_ref())
+                    }
+                    .map_err(InstructionError::ExportSerialisationError)?;
+                    log::info!("Exported {written} entries.");
+                    return Ok(Instruction::Nothing);
+                }
+                if let Some(filter) = filter {
+                    let local = LocalContext::new_without_runtime(config)
+                        .expect("Was not able to load context for reading!");
+                    let written = if let Some(output) = output {
+                        let file = File::options().write(true).create_new(true).open(output)?;
+                        let writer = BufWriter::new(file);
+                        export_matching_pages(&local, &filter, writer, include_expired)
+                    } else {
+                        let stdout = std::io::stdout().lock();
+                        export_matching_pages(&local, &filter, stdout, include_expired)
+                    }?;
+                    log::info!("Found {written} matching pages.");
+                    return Ok(Instruction::Nothing);
+                }
                 println!("{}\n\n{}\n\n\n", ATRA_WELCOME, ATRA_LOGO);
                 let runtime = tokio::runtime::Builder::new_current_thread()
                     .enable_all()
@@ -280,8 +525,125 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 });
                 Ok(Instruction::Nothing)
             }
-            RunMode::DUMP { crawl_path, output_dir } => {
-                dump(crawl_path, output_dir)?;
+            RunMode::DUMP { crawl_path, output_dir, include_expired } => {
+                dump(crawl_path, output_dir, include_expired)?;
+                Ok(Instruction::Nothing)
+            }
+            RunMode::REBUILDDB { path } => {
+                let config = string_to_config_path(&path)?;
+                let report = rebuild_db(&config)?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::CONTINUE { path } => {
+                let config = string_to_config_path(&path)?;
+                match confirm_next_wave(config.paths.root_path())? {
+                    Some(wave) => println!("Confirmed the start of wave {wave}."),
+                    None => println!("No finished wave is waiting for a further wave."),
+                }
+                Ok(Instruction::Nothing)
+            }
+            RunMode::EXPIRED { path, prune } => {
+                let config = string_to_config_path(&path)?;
+                let report = expired_entries(&config, OffsetDateTime::now_utc(), prune)?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::MAINTAIN { dry_run, path } => {
+                let config = string_to_config_path(&path)?;
+                let report = maintain(&config, dry_run)?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::GDBREXPORT {
+                output,
+                language,
+                prelabel,
+                path,
+            } => {
+                let config = string_to_config_path(&path)?;
+                let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+                let report = runtime.block_on(gdbr_export(
+                    &config,
+                    &Utf8PathBuf::from(output),
+                    language,
+                    prelabel,
+                ))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::VERIFY { path } => {
+                let config = string_to_config_path(&path)?;
+                let report = verify(&config)?;
+                println!("{report}");
+                if !report.corrupt.is_empty() {
+                    return Err(VerifyError::Corrupt(report.corrupt.len()).into());
+                }
+                Ok(Instruction::Nothing)
+            }
+            RunMode::REQUEUE {
+                filter,
+                dry_run,
+                path,
+            } => {
+                let config = string_to_config_path(&path)?;
+                let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+                let report = runtime.block_on(requeue_failed(&config, &filter, dry_run))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::BACKFILL { field, path } => {
+                let config = string_to_config_path(&path)?;
+                let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+                let report = runtime.block_on(backfill(&config, &field))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::IMPORTHAR {
+                follow_links,
+                path,
+                har_files,
+            } => {
+                let config = string_to_config_path(&path)?;
+                let har_files: Vec<Utf8PathBuf> =
+                    har_files.into_iter().map(Utf8PathBuf::from).collect();
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                let report = runtime.block_on(import_har(&config, &har_files, follow_links))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::IMPORT { path, warc_files } => {
+                let config = string_to_config_path(&path)?;
+                let warc_files: Vec<Utf8PathBuf> =
+                    warc_files.into_iter().map(Utf8PathBuf::from).collect();
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                let report = runtime.block_on(import_warc(&config, &warc_files))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::BULK { args, path } => {
+                let config = string_to_config_path(&path)?;
+                let text = config.crawl.text_normalization;
+                let local = LocalContext::new_without_runtime(config)
+                    .expect("Was not able to load context for reading!");
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                let report = runtime.block_on(export_bulk(&local, &args, text))?;
+                println!("{report}");
+                Ok(Instruction::Nothing)
+            }
+            RunMode::WORKER {
+                connections,
+                log_to_file,
+                connect,
+            } => {
+                let processed = run_secondary(&connect, connections, log_to_file)?;
+                println!("Fetched {processed} urls for {connect}.");
                 Ok(Instruction::Nothing)
             }
         }
@@ -306,12 +668,48 @@ pub(crate) fn prepare_instruction(args: AtraArgs) -> Result<Instruction, Instruc
                 }
             }
             Ok(Instruction::Nothing)
+        } else if let Some(url) = args.explain_priority {
+            let config = discover_or_default().unwrap_or_default();
+            let url = UrlWithDepth::from_url(url.as_str())?;
+            let scorer = UrlPriorityScorer::new(&config.crawl.url_priority);
+            println!("{}", scorer.explain(&url, false));
+            Ok(Instruction::Nothing)
+        } else if let Some(origin) = args.show_effective {
+            let config = discover_or_default()?;
+            println!(
+                "{}",
+                config
+                    .crawl
+                    .effective_for(&AtraUrlOrigin::from(origin.trim()))
+            );
+            Ok(Instruction::Nothing)
+        } else if let Some(name) = args.print_schema {
+            if let Some(schema) = json_schema(&name) {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&schema)
+                        .map_err(InstructionError::ExportSerialisationError)?
+                );
+            }
+            Ok(Instruction::Nothing)
         } else {
             Ok(Instruction::Nothing)
         }
     }
 }
 
+/// Prints the dry run of the [seeds], fails if no seed would be enqueued.
+fn validate_seeds(
+    config: &Config,
+    seeds: &SeedDefinition,
+) -> Result<Instruction, InstructionError> {
+    let report = dry_run(config, seeds)?;
+    println!("{report}");
+    if report.enqueued() == 0 {
+        return Err(DryRunError::NothingToEnqueue.into());
+    }
+    Ok(Instruction::Nothing)
+}
 
 pub(crate) fn string_to_config_path(path: &str) -> Result<Config, InstructionError> {
     let path = Utf8PathBuf::from(path);
@@ -323,6 +721,7 @@ pub(crate) fn string_to_config_path(path: &str) -> Result<Config, InstructionErr
     } else if path.is_file() {
         let file = File::options().read(true).open(&path)?;
         let mut cfg: Config = serde_json::from_reader(BufReader::new(file)).map_err(InstructionError::ConfigDeserializationError)?;
+        cfg.crawl.resolve_profiles()?;
         cfg.paths.root = if let Some(parent) = path.parent() {
             parent.to_path_buf()
         } else {
@@ -0,0 +1,356 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crawl::{CrawlEvent, CrawlEvents};
+use crate::url::guard::{GuardianChangedEvent, GuardianEvents};
+use crate::url::Depth;
+use data_encoding::BASE64;
+use serde::Serialize;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Appended to the key of the client to accept the handshake, see RFC 6455.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Frames of the client with a longer payload close the connection,
+/// a client is only expected to send control frames.
+const MAX_CLIENT_FRAME: u64 = 4 * 1024;
+const OPCODE_TEXT: u8 = 0x1;
+const OPCODE_CLOSE: u8 = 0x8;
+const OPCODE_PING: u8 = 0x9;
+const OPCODE_PONG: u8 = 0xA;
+/// The status code of a close frame after the crawl finished.
+const CLOSE_NORMAL: u16 = 1000;
+
+/// The events of the crawl and of the guardian, streamed to every client of `GET /events`.
+#[derive(Debug, Clone)]
+pub struct LiveEvents {
+    crawl: CrawlEvents,
+    guardian: GuardianEvents,
+}
+
+impl LiveEvents {
+    pub fn new(crawl: CrawlEvents, guardian: GuardianEvents) -> Self {
+        Self { crawl, guardian }
+    }
+}
+
+/// The state of a worker in a [LiveEvent::WorkerStateChanged].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkerState {
+    Fetching,
+    Idle,
+}
+
+/// An event sent to the clients as JSON text frame, the variant is the field `type`.
+/// The durations are in milliseconds.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    UrlQueued {
+        url: String,
+    },
+    UrlCrawled {
+        url: String,
+        status: u16,
+        bytes: Option<u64>,
+        duration: Option<u64>,
+    },
+    OriginReserved {
+        origin: String,
+        depth: Depth,
+    },
+    OriginReleased {
+        origin: String,
+        duration: u64,
+    },
+    WorkerStateChanged {
+        worker_id: usize,
+        state: WorkerState,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        url: Option<String>,
+    },
+    CrawlFinished,
+    /// The client was too slow and missed [dropped] events since it connected.
+    EventsDropped {
+        dropped: u64,
+    },
+}
+
+impl LiveEvent {
+    /// The event sent for the [event] of the crawl, None if it is not sent.
+    fn from_crawl_event(event: CrawlEvent) -> Option<Self> {
+        let event = match event {
+            CrawlEvent::Queued { url } => Self::UrlQueued {
+                url: url.url().to_string(),
+            },
+            CrawlEvent::Crawled {
+                url,
+                status_code,
+                bytes,
+                duration,
+            } => Self::UrlCrawled {
+                url: url.url().to_string(),
+                status: status_code.as_u16(),
+                bytes,
+                duration: duration.map(millis),
+            },
+            CrawlEvent::Fetching { worker_id, url } => Self::WorkerStateChanged {
+                worker_id,
+                state: WorkerState::Fetching,
+                url: Some(url.url().to_string()),
+            },
+            CrawlEvent::QueueEmpty { worker_id } => Self::WorkerStateChanged {
+                worker_id,
+                state: WorkerState::Idle,
+                url: None,
+            },
+            CrawlEvent::Finished => Self::CrawlFinished,
+            CrawlEvent::Skipped { .. }
+            | CrawlEvent::Failed { .. }
+            | CrawlEvent::ExtractorFailed { .. }
+            | CrawlEvent::RobotsChanged { .. }
+            | CrawlEvent::OriginUnreachable { .. } => return None,
+        };
+        Some(event)
+    }
+
+    /// The event sent for the [event] of the guardian, None if it is not sent.
+    fn from_guardian_event(event: GuardianChangedEvent) -> Option<Self> {
+        let event = match event {
+            GuardianChangedEvent::OriginReserved { origin, depth } => Self::OriginReserved {
+                origin: origin.to_string(),
+                depth,
+            },
+            GuardianChangedEvent::OriginReleased {
+                origin,
+                duration_held,
+            } => Self::OriginReleased {
+                origin: origin.to_string(),
+                duration: millis(duration_held),
+            },
+            GuardianChangedEvent::OriginCooldown { .. }
+            | GuardianChangedEvent::OriginCompleted { .. } => return None,
+        };
+        Some(event)
+    }
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+/// The value of the `Sec-WebSocket-Accept` header for the [key] of the client.
+fn accept_key(key: &str) -> String {
+    BASE64.encode(&openssl::sha::sha1(
+        format!("{}{WEBSOCKET_GUID}", key.trim()).as_bytes(),
+    ))
+}
+
+/// A complete frame sent by the client.
+struct ClientFrame {
+    opcode: u8,
+    payload: Vec<u8>,
+    /// The length of the frame with its header.
+    length: usize,
+}
+
+/// Reads the frame at the start of [buffer], None if it is not complete yet.
+/// Fragmented frames are not joined, the server only answers control frames.
+fn parse_client_frame(buffer: &[u8]) -> io::Result<Option<ClientFrame>> {
+    let [first, second, ..] = *buffer else {
+        return Ok(None);
+    };
+    let (length, mut offset) = match second & 0x7F {
+        126 => match buffer.get(2..4) {
+            Some(value) => (u16::from_be_bytes([value[0], value[1]]) as u64, 4),
+            None => return Ok(None),
+        },
+        127 => match buffer.get(2..10) {
+            Some(value) => (u64::from_be_bytes(value.try_into().unwrap()), 10),
+            None => return Ok(None),
+        },
+        length => (length as u64, 2),
+    };
+    if length > MAX_CLIENT_FRAME {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The client sent a frame of {length} bytes."),
+        ));
+    }
+    let mask = if second & 0x80 != 0 {
+        let Some(mask) = buffer.get(offset..offset + 4) else {
+            return Ok(None);
+        };
+        offset += 4;
+        Some([mask[0], mask[1], mask[2], mask[3]])
+    } else {
+        None
+    };
+    let end = offset + length as usize;
+    let Some(payload) = buffer.get(offset..end) else {
+        return Ok(None);
+    };
+    let mut payload = payload.to_vec();
+    if let Some(mask) = mask {
+        for (i, value) in payload.iter_mut().enumerate() {
+            *value ^= mask[i % 4];
+        }
+    }
+    Ok(Some(ClientFrame {
+        opcode: first & 0x0F,
+        payload,
+        length: end,
+    }))
+}
+
+/// Writes an unmasked, unfragmented frame.
+async fn write_frame<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    opcode: u8,
+    payload: &[u8],
+) -> io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        length @ 0..=125 => frame.push(length as u8),
+        length @ 126..=0xFFFF => {
+            frame.push(126);
+            frame.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+        length => {
+            frame.push(127);
+            frame.extend_from_slice(&(length as u64).to_be_bytes());
+        }
+    }
+    frame.extend_from_slice(payload);
+    writer.write_all(&frame).await
+}
+
+async fn write_event<W: AsyncWrite + Unpin>(writer: &mut W, event: &LiveEvent) -> io::Result<()> {
+    let text = serde_json::to_string(event).map_err(io::Error::other)?;
+    write_frame(writer, OPCODE_TEXT, text.as_bytes()).await
+}
+
+/// Accepts the WebSocket handshake with the [key] of the client and streams the [events]
+/// until the crawl finished or the client closed the connection.
+///
+/// The events are buffered by broadcast channels, a client too slow to keep up never blocks
+/// the crawl. It misses the oldest events instead and is told about it by an `events_dropped`
+/// event with the number of events it missed so far.
+pub async fn stream_live_events(
+    mut stream: TcpStream,
+    key: &str,
+    events: &LiveEvents,
+) -> io::Result<()> {
+    // Subscribed before the handshake is answered, the client misses nothing after it.
+    let mut crawl = events.crawl.subscribe();
+    let mut guardian = events.guardian.subscribe();
+    let response = format!(
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)
+    );
+    stream.write_all(response.as_bytes()).await?;
+    let (mut reader, mut writer) = stream.split();
+    let mut received = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let mut crawl_missed = 0u64;
+    let mut reported_missed = 0u64;
+    loop {
+        let event = select! {
+            event = crawl.recv() => match event {
+                Ok(event) => LiveEvent::from_crawl_event(event),
+                Err(RecvError::Lagged(missed)) => {
+                    crawl_missed += missed;
+                    None
+                }
+                Err(RecvError::Closed) => break,
+            },
+            event = guardian.recv() => match event {
+                Some(event) => LiveEvent::from_guardian_event(event),
+                None => break,
+            },
+            read = reader.read(&mut buffer) => {
+                let read = read?;
+                if read == 0 {
+                    return Ok(());
+                }
+                received.extend_from_slice(&buffer[..read]);
+                while let Some(frame) = parse_client_frame(&received)? {
+                    received.drain(..frame.length);
+                    match frame.opcode {
+                        OPCODE_PING => {
+                            write_frame(&mut writer, OPCODE_PONG, &frame.payload).await?
+                        }
+                        OPCODE_CLOSE => {
+                            let code = frame.payload.get(..2).unwrap_or_default();
+                            write_frame(&mut writer, OPCODE_CLOSE, code).await?;
+                            return writer.shutdown().await;
+                        }
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+        };
+        let missed = crawl_missed + guardian.missed();
+        if missed > reported_missed {
+            reported_missed = missed;
+            write_event(&mut writer, &LiveEvent::EventsDropped { dropped: missed }).await?;
+        }
+        if let Some(event) = event {
+            write_event(&mut writer, &event).await?;
+            if event == LiveEvent::CrawlFinished {
+                break;
+            }
+        }
+    }
+    write_frame(&mut writer, OPCODE_CLOSE, &CLOSE_NORMAL.to_be_bytes()).await?;
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{accept_key, parse_client_frame, OPCODE_CLOSE};
+
+    #[test]
+    fn accepts_the_key_of_the_client() {
+        // The example of RFC 6455.
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    #[test]
+    fn reads_the_masked_frames_of_the_client() {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![0x80 | OPCODE_CLOSE, 0x80 | 2];
+        frame.extend_from_slice(&mask);
+        frame.extend([0x03 ^ mask[0], 0xe8 ^ mask[1]]);
+        assert!(parse_client_frame(&frame[..5]).unwrap().is_none());
+        frame.push(0x81);
+        let parsed = parse_client_frame(&frame).unwrap().unwrap();
+        assert_eq!(OPCODE_CLOSE, parsed.opcode);
+        assert_eq!(vec![0x03, 0xe8], parsed.payload);
+        assert_eq!(8, parsed.length);
+
+        let too_large = [0x81, 0x80 | 127, 0, 0, 0, 0, 0, 1, 0, 0];
+        assert!(parse_client_frame(&too_large).is_err());
+    }
+}
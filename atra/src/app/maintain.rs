@@ -0,0 +1,483 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::rebuild::collect_warc_files;
+use crate::config::Config;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{SlimCrawlResult, StoredDataHint};
+use crate::database::{open_db, DatabaseError, OpenDBError};
+use crate::io::fs::LAYOUT_FILE_NAME;
+use crate::warc_ext::WarcRecordScanner;
+use camino::{Utf8Path, Utf8PathBuf};
+use rocksdb::IteratorMode;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::sync::Arc;
+use thiserror::Error;
+use warc::parser::parse_warc_header;
+
+/// The name of a decoded file is the name of its source followed by this and the encoding.
+const DECODED_MARKER: &str = "_decoded_";
+
+/// Errors that stop the maintenance of a session.
+#[derive(Debug, Error)]
+pub enum MaintainError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error("The session {0} is used by an active crawl.")]
+    ActiveCrawl(Utf8PathBuf),
+}
+
+/// A change to the files of a session.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MaintenanceAction {
+    /// Cut the torn record at [offset] off the warc file.
+    TruncateWarc {
+        path: Utf8PathBuf,
+        offset: u64,
+        bytes: u64,
+    },
+    /// Delete a data file no stored page refers to.
+    DeleteOrphan { path: Utf8PathBuf, bytes: u64 },
+}
+
+impl MaintenanceAction {
+    /// The number of bytes freed by the action.
+    pub fn bytes(&self) -> u64 {
+        match self {
+            MaintenanceAction::TruncateWarc { bytes, .. }
+            | MaintenanceAction::DeleteOrphan { bytes, .. } => *bytes,
+        }
+    }
+}
+
+impl Display for MaintenanceAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MaintenanceAction::TruncateWarc {
+                path,
+                offset,
+                bytes,
+            } => write!(f, "truncate {path} at {offset} ({bytes} bytes)"),
+            MaintenanceAction::DeleteOrphan { path, bytes } => {
+                write!(f, "delete   {path} ({bytes} bytes)")
+            }
+        }
+    }
+}
+
+/// A warc file with a broken record that is not its last one.
+#[derive(Debug, Clone)]
+pub struct CorruptWarc {
+    pub path: Utf8PathBuf,
+    pub offset: u64,
+    pub reason: String,
+}
+
+/// The actions of a maintenance run.
+#[derive(Debug, Default)]
+pub struct MaintenanceReport {
+    pub actions: Vec<MaintenanceAction>,
+    /// The corrupt warc files, they are left untouched. The records after the corruption
+    /// may refer to data files, no orphans are deleted if there is any.
+    pub corrupt: Vec<CorruptWarc>,
+    /// True iff the actions were only listed
+    pub dry_run: bool,
+}
+
+impl MaintenanceReport {
+    /// The number of bytes freed by all actions.
+    pub fn reclaimed(&self) -> u64 {
+        self.actions.iter().map(MaintenanceAction::bytes).sum()
+    }
+}
+
+impl Display for MaintenanceReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for value in &self.actions {
+            writeln!(f, "{value}")?;
+        }
+        for value in &self.corrupt {
+            writeln!(
+                f,
+                "corrupt  {} after {}: {}",
+                value.path, value.offset, value.reason
+            )?;
+        }
+        if !self.corrupt.is_empty() {
+            writeln!(
+                f,
+                "The orphans are kept, not every warc file could be read."
+            )?;
+        }
+        if self.dry_run {
+            writeln!(f, "Reclaimable bytes:  {}", self.reclaimed())
+        } else {
+            writeln!(f, "Reclaimed bytes:    {}", self.reclaimed())
+        }
+    }
+}
+
+/// Cleans up the leftovers of an aborted session of [config]: a torn record at the end of
+/// a warc file is truncated and the data files in the big files folder that are neither
+/// referenced by the crawl db nor by a warc record are deleted, with the decoded files of
+/// them. If [dry_run] is set the actions are only listed.
+///
+/// Refuses to run while a crawl holds the lock of the crawl db.
+pub(crate) fn maintain(config: &Config, dry_run: bool) -> Result<MaintenanceReport, MaintainError> {
+    let db_path = config.paths.dir_database();
+    if !db_path.is_dir() {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("There is no crawl database at {db_path}!"),
+        )
+        .into());
+    }
+    // Held until the maintenance is done, no crawl can start in the meantime.
+    let db = match open_db(&db_path) {
+        Ok(db) => Arc::new(db),
+        Err(OpenDBError::RocksDB(err)) if err.to_string().contains("LOCK") => {
+            return Err(MaintainError::ActiveCrawl(
+                config.paths.root_path().to_path_buf(),
+            ));
+        }
+        Err(err) => return Err(err.into()),
+    };
+    let crawl_db = CrawlDB::new(db, config)?;
+
+    let mut report = MaintenanceReport {
+        dry_run,
+        ..MaintenanceReport::default()
+    };
+    let mut referenced = HashSet::new();
+    for value in crawl_db.iter(IteratorMode::Start) {
+        let (k, v) = match value {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to read from the crawl db: {err}");
+                continue;
+            }
+        };
+        let data = match SlimCrawlResult::decode(v.as_ref()) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!(
+                    "Failed to deserialize data from {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                continue;
+            }
+        };
+        if let StoredDataHint::External(path) = data.stored_data_hint {
+            if let Some(name) = path.file_name() {
+                referenced.insert(name.to_string());
+            }
+        }
+    }
+
+    for path in collect_warc_files(config)? {
+        log::info!("Scanning {path}");
+        let file = File::open(&path)?;
+        let file_size = file.metadata()?.len();
+        let mut scanner = WarcRecordScanner::new(BufReader::new(file));
+        loop {
+            let offset = scanner.offset();
+            match scanner.next_record() {
+                Ok(Some(record)) => {
+                    if let Some(Ok(value)) = record.header.get_external_bin_file() {
+                        let external =
+                            Utf8PathBuf::from(String::from_utf8_lossy(value.as_ref()).into_owned());
+                        if let Some(name) = external.file_name() {
+                            referenced.insert(name.to_string());
+                        }
+                    }
+                }
+                Ok(None) => break,
+                Err(err) => {
+                    if is_torn_record(&path, offset, file_size)? {
+                        report.actions.push(MaintenanceAction::TruncateWarc {
+                            path: path.clone(),
+                            offset,
+                            bytes: file_size - offset,
+                        });
+                    } else {
+                        log::error!("Failed to read {path} at {offset}: {err}");
+                        report.corrupt.push(CorruptWarc {
+                            path: path.clone(),
+                            offset,
+                            reason: err.to_string(),
+                        });
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    let big_files = config.paths.dir_big_files();
+    if report.corrupt.is_empty() && big_files.is_dir() {
+        let mut files = Vec::new();
+        collect_data_files(&big_files, &mut files)?;
+        files.sort();
+        for path in files {
+            if path.parent() == Some(big_files.as_path())
+                && path.file_name() == Some(LAYOUT_FILE_NAME)
+            {
+                continue;
+            }
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            // A decoded file lives as long as its source.
+            let source = name
+                .rsplit_once(DECODED_MARKER)
+                .map_or(name, |(source, _)| source);
+            if referenced.contains(name) || referenced.contains(source) {
+                continue;
+            }
+            let mut bytes = path.metadata()?.len();
+            let references = path.with_extension("refs");
+            if references.is_file() {
+                bytes += references.metadata()?.len();
+            }
+            report
+                .actions
+                .push(MaintenanceAction::DeleteOrphan { path, bytes });
+        }
+    }
+
+    if !dry_run {
+        for action in &report.actions {
+            log::info!("{action}");
+            match action {
+                MaintenanceAction::TruncateWarc { path, offset, .. } => {
+                    let file = File::options().write(true).open(path)?;
+                    file.set_len(*offset)?;
+                    file.sync_all()?;
+                }
+                MaintenanceAction::DeleteOrphan { path, .. } => {
+                    std::fs::remove_file(path)?;
+                    let references = path.with_extension("refs");
+                    if references.is_file() {
+                        std::fs::remove_file(references)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Returns true if the record at [offset] does not fit into the [file_size], like a record
+/// a crawl was not able to finish before it was aborted. The Content-Length declared by the
+/// header decides, a record that fits but is broken anyway is corrupt and not torn.
+fn is_torn_record(path: &Utf8Path, offset: u64, file_size: u64) -> std::io::Result<bool> {
+    let mut reader = BufReader::new(File::open(path)?);
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut raw_header = Vec::with_capacity(1024);
+    loop {
+        if reader.read_until(b'\n', &mut raw_header)? == 0 {
+            // The file ends within the header.
+            return Ok(true);
+        }
+        if raw_header.ends_with(b"\r\n\r\n") {
+            break;
+        }
+    }
+    let Ok((_, header)) = parse_warc_header(&raw_header) else {
+        return Ok(false);
+    };
+    let Ok(content_length) = header.get_content_length() else {
+        return Ok(false);
+    };
+    Ok(offset + raw_header.len() as u64 + *content_length + 4 > file_size)
+}
+
+/// Collects the files below [folder] into [files], the `.refs` files of the content
+/// addressed store are left out.
+fn collect_data_files(folder: &Utf8Path, files: &mut Vec<Utf8PathBuf>) -> std::io::Result<()> {
+    for entry in folder.read_dir_utf8()? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_data_files(entry.path(), files)?;
+        } else if file_type.is_file() && entry.path().extension() != Some("refs") {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::maintain::{maintain, MaintainError, MaintenanceAction};
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::{create_test_data, create_test_data_unknown};
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::data::RawVecData;
+    use crate::database::open_db;
+    use crate::io::fs::{AtraFS, FileSystemAccess};
+    use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
+    use crate::url::UrlWithDepth;
+    use crate::warc_ext::{write_warc, WarcRecordScanner};
+    use camino::Utf8PathBuf;
+    use camino_tempfile::Utf8TempDir;
+    use std::io::{BufReader, Write};
+    use std::sync::Arc;
+
+    /// Creates a session with a torn record at the end of its warc file, returns the path
+    /// of the warc file and its length before the torn record was appended.
+    async fn create_session(config: &Config) -> (Utf8PathBuf, u64) {
+        let fs = FileSystemAccess::new(
+            config.session.service.clone(),
+            config.session.collection.clone(),
+            config.session.crawl_job_id,
+            config.paths.root_path().to_path_buf(),
+            config.paths.dir_big_files(),
+            config.session.dat_file_layout,
+        )
+        .unwrap();
+        let writer = ThreadsafeMultiFileWarcWriter::new_for_worker(Arc::new(
+            fs.create_worker_file_provider(0, 0).unwrap(),
+        ))
+        .unwrap();
+
+        let big_files = config.paths.dir_big_files();
+        std::fs::create_dir_all(&big_files).unwrap();
+        for name in [
+            "in_warc.dat",
+            "in_db.dat",
+            "in_db.dat_decoded_UTF-8",
+            "orphan.dat",
+        ] {
+            std::fs::write(big_files.join(name), vec![1u8; 1000]).unwrap();
+        }
+        std::fs::write(big_files.join("orphan.dat_decoded_UTF-8"), vec![1u8; 500]).unwrap();
+
+        {
+            let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+            let crawl_db = CrawlDB::new(db, config).unwrap();
+            let in_warc = create_test_data_unknown(
+                UrlWithDepth::from_url("https://www.example.com/in_warc").unwrap(),
+                RawVecData::from_external(big_files.join("in_warc.dat")),
+            );
+            let instruction = writer
+                .execute_on_writer(|value| write_warc(value, &in_warc))
+                .await
+                .unwrap();
+            crawl_db
+                .add(&SlimCrawlResult::new(
+                    &in_warc,
+                    StoredDataHint::Warc(instruction),
+                ))
+                .unwrap();
+            let in_db = create_test_data(
+                UrlWithDepth::from_url("https://www.example.com/in_db").unwrap(),
+                None,
+            );
+            crawl_db
+                .add(&SlimCrawlResult::new(
+                    &in_db,
+                    StoredDataHint::External(big_files.join("in_db.dat")),
+                ))
+                .unwrap();
+            writer.flush().await.unwrap();
+        }
+
+        // Appends the last record without its last bytes, like an aborted write.
+        let warc = writer.current_file().await;
+        let original = std::fs::read(&warc).unwrap();
+        let mut scanner = WarcRecordScanner::new(BufReader::new(original.as_slice()));
+        let mut last = 0;
+        while let Some(record) = scanner.next_record().unwrap() {
+            last = record.pointer.file_offset() as usize;
+        }
+        let mut file = std::fs::File::options().append(true).open(&warc).unwrap();
+        file.write_all(&original[last..original.len() - 10])
+            .unwrap();
+        (warc, original.len() as u64)
+    }
+
+    #[tokio::test]
+    async fn removes_the_leftovers_of_an_aborted_session() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        let (warc, original_length) = create_session(&config).await;
+        let torn_length = std::fs::metadata(&warc).unwrap().len();
+        let big_files = config.paths.dir_big_files();
+
+        let report = maintain(&config, true).unwrap();
+        assert!(report.corrupt.is_empty());
+        assert_eq!(
+            vec![
+                MaintenanceAction::TruncateWarc {
+                    path: warc.clone(),
+                    offset: original_length,
+                    bytes: torn_length - original_length,
+                },
+                MaintenanceAction::DeleteOrphan {
+                    path: big_files.join("orphan.dat"),
+                    bytes: 1000,
+                },
+                MaintenanceAction::DeleteOrphan {
+                    path: big_files.join("orphan.dat_decoded_UTF-8"),
+                    bytes: 500,
+                },
+            ],
+            report.actions
+        );
+        assert_eq!(torn_length, std::fs::metadata(&warc).unwrap().len());
+        assert!(big_files.join("orphan.dat").exists());
+        assert!(big_files.join("orphan.dat_decoded_UTF-8").exists());
+
+        let report = maintain(&config, false).unwrap();
+        assert_eq!(torn_length - original_length + 1500, report.reclaimed());
+        assert_eq!(original_length, std::fs::metadata(&warc).unwrap().len());
+        assert!(!big_files.join("orphan.dat").exists());
+        assert!(!big_files.join("orphan.dat_decoded_UTF-8").exists());
+        for name in ["in_warc.dat", "in_db.dat", "in_db.dat_decoded_UTF-8"] {
+            assert!(big_files.join(name).exists(), "{name} was deleted");
+        }
+
+        let report = maintain(&config, false).unwrap();
+        assert!(report.actions.is_empty());
+        assert!(report.corrupt.is_empty());
+    }
+
+    #[tokio::test]
+    async fn refuses_to_run_during_a_crawl() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        create_session(&config).await;
+
+        let _db = open_db(config.paths.dir_database()).unwrap();
+        assert!(matches!(
+            maintain(&config, true),
+            Err(MaintainError::ActiveCrawl(_))
+        ));
+        assert!(config.paths.dir_big_files().join("orphan.dat").exists());
+    }
+}
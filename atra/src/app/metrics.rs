@@ -0,0 +1,1748 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::live_events::{stream_live_events, LiveEvents};
+use crate::blacklist::{BlacklistError, RejectedEntry};
+use crate::contexts::traits::{
+    SupportsConfigs, SupportsCrawlEvents, SupportsCrawling, SupportsLinkState, SupportsMetaInfo,
+    SupportsTlsInfo, SupportsUniqueContent, SupportsUrlGuarding, SupportsUrlQueue,
+};
+use crate::crawl::{CrawlPause, DAT_FILE_SIZE_KINDS, EXTRACTOR_FAILURE_KINDS, STATUS_CODE_CLASSES};
+use crate::fetching::HttpVersion;
+use crate::link_state::{LinkStateKind, LinkStateManager};
+use crate::queue::UrlQueue;
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::schemas::{AuditLog, OriginMetadataRecord};
+use crate::tls_info::TlsInfoManager;
+use crate::unique_content::UniqueContentManager;
+use crate::url::guard::UrlGuardian;
+use crate::url::AtraUrlOrigin;
+use std::fmt::{Display, Write as _};
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// The path of the endpoint.
+const METRICS_PATH: &str = "/metrics";
+/// The path of the endpoint extending the blacklist.
+const BLACKLIST_PATH: &str = "/blacklist";
+/// The path of the endpoint showing the audit trail.
+const AUDIT_PATH: &str = "/audit";
+/// The path of the endpoint showing the metadata of an origin.
+const ORIGIN_PATH: &str = "/origin";
+/// The path of the endpoint listing the link states of the origins.
+/// The urls of an origin are listed at `/origins/<origin>/urls`.
+const ORIGINS_PATH: &str = "/origins";
+/// The path of the WebSocket streaming the events of the crawl.
+const EVENTS_PATH: &str = "/events";
+/// The path of the endpoint pausing the workers.
+const PAUSE_PATH: &str = "/control/pause";
+/// The path of the endpoint resuming the paused workers.
+const RESUME_PATH: &str = "/control/resume";
+/// The number of entries of a listing if the limit is missing.
+const DEFAULT_PAGE_LIMIT: usize = 100;
+/// Listings with a larger limit are rejected.
+const MAX_PAGE_LIMIT: usize = 1000;
+/// The content type of the Prometheus text format.
+const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+/// The content type of the answers of the blacklist endpoint.
+const JSON_CONTENT_TYPE: &str = "application/json";
+/// The content type of the audit trail.
+const JSONL_CONTENT_TYPE: &str = "application/x-ndjson";
+/// Requests with a longer head are rejected.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+/// Blacklist patches with a longer body are rejected.
+const MAX_BLACKLIST_BODY: usize = 1024 * 1024;
+/// The open connections get this long after the shutdown to finish, e.g. to send the end
+/// of the crawl to the clients of the events.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(1);
+
+/// The optional sources of the endpoints, the endpoints without one answer with 404.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsResources {
+    /// The audit trail served at `GET /audit`.
+    pub audit: Option<AuditLog>,
+    /// The events streamed over a WebSocket at `GET /events`.
+    pub events: Option<LiveEvents>,
+    /// The pause of the workers, set by `POST /control/pause` and `POST /control/resume`.
+    pub pause: Option<CrawlPause>,
+    /// The token the requests changing the crawl have to carry, without one they are only
+    /// accepted from loopback.
+    pub control_token: Option<String>,
+}
+
+/// A query for the link states of the origins.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum OriginQuery {
+    /// The link states of the origins counted by their kind.
+    Summaries { offset: usize, limit: usize },
+    /// The link states of the urls of an origin, only of the kind if set.
+    Urls {
+        origin: String,
+        kind: Option<LinkStateKind>,
+        offset: usize,
+        limit: usize,
+    },
+}
+
+impl OriginQuery {
+    /// Parses a query of `GET /origins` or `GET /origins/<origin>/urls`,
+    /// None if [path] is neither of them.
+    fn parse(path: &str, query: &str) -> Option<Result<Self, String>> {
+        let origin = match path.strip_prefix(ORIGINS_PATH)? {
+            "" => None,
+            rest => Some(rest.strip_prefix('/')?.strip_suffix("/urls")?),
+        };
+        let mut offset = 0usize;
+        let mut limit = DEFAULT_PAGE_LIMIT;
+        let mut kind = None;
+        for (key, value) in url::form_urlencoded::parse(query.as_bytes()) {
+            match key.as_ref() {
+                "offset" => match value.parse() {
+                    Ok(value) => offset = value,
+                    Err(_) => return Some(Err(format!("Invalid offset {value}."))),
+                },
+                "limit" => match value.parse() {
+                    Ok(value) if (1..=MAX_PAGE_LIMIT).contains(&value) => limit = value,
+                    _ => {
+                        return Some(Err(format!(
+                            "Expected a limit between 1 and {MAX_PAGE_LIMIT} but got {value}."
+                        )))
+                    }
+                },
+                "status" if origin.is_some() => match parse_link_state_kind(&value) {
+                    Some(value) => kind = Some(value),
+                    None => return Some(Err(format!("Unknown status {value}."))),
+                },
+                _ => {}
+            }
+        }
+        Some(Ok(match origin {
+            None => Self::Summaries { offset, limit },
+            Some(origin) => {
+                // The origin is a host, it never contains a `+`, `&` or `=`.
+                let origin = url::form_urlencoded::parse(origin.as_bytes())
+                    .next()
+                    .map(|(origin, _)| origin.into_owned())
+                    .unwrap_or_default();
+                if origin.is_empty() {
+                    return Some(Err("Expected an origin.".to_string()));
+                }
+                Self::Urls {
+                    origin: AtraUrlOrigin::from(origin.as_str()).to_string(),
+                    kind,
+                    offset,
+                    limit,
+                }
+            }
+        }))
+    }
+}
+
+/// Parses the name of a [LinkStateKind] as serialized, the case is ignored.
+fn parse_link_state_kind(value: &str) -> Option<LinkStateKind> {
+    [
+        LinkStateKind::Discovered,
+        LinkStateKind::ReservedForCrawl,
+        LinkStateKind::Crawled,
+        LinkStateKind::ProcessedAndStored,
+        LinkStateKind::InternalError,
+    ]
+    .into_iter()
+    .find(|kind| kind.as_ref().eq_ignore_ascii_case(value))
+}
+
+/// Renders the metrics of the [context] in the Prometheus text format.
+/// The counters are read from atomics, the queue and the guardian are asked directly.
+/// The unique-content ratio is only rendered for origins with enough samples.
+pub async fn render_metrics<C>(context: &C) -> String
+where
+    C: SupportsUrlQueue
+        + SupportsUrlGuarding
+        + SupportsMetaInfo
+        + SupportsCrawlEvents
+        + SupportsUniqueContent
+        + SupportsTlsInfo
+        + SupportsLinkState
+        + SupportsCrawling
+        + SupportsConfigs,
+{
+    let metrics = context.crawl_events().metrics();
+    let mut out = String::new();
+    write_family(
+        &mut out,
+        "atra_queue_length",
+        "gauge",
+        "The number of urls in the queue.",
+        [(None, context.url_queue().len().await as u64)],
+    );
+    write_family(
+        &mut out,
+        "atra_queue_floating_urls",
+        "gauge",
+        "The number of urls taken from the queue but not yet finished.",
+        [(None, context.url_queue().floating_url_count() as u64)],
+    );
+    write_family(
+        &mut out,
+        "atra_discovered_websites_total",
+        "counter",
+        "The number of discovered websites.",
+        [(None, context.discovered_websites() as u64)],
+    );
+    write_family(
+        &mut out,
+        "atra_crawled_pages_total",
+        "counter",
+        "The number of crawled pages by the class of their status code.",
+        STATUS_CODE_CLASSES
+            .into_iter()
+            .zip(metrics.crawled_by_status_class())
+            .map(|(class, value)| (Some(("status_class", class)), value)),
+    );
+    write_family(
+        &mut out,
+        "atra_responses_total",
+        "counter",
+        "The number of responses by their http version.",
+        HttpVersion::ALL
+            .into_iter()
+            .zip(metrics.responses_by_http_version())
+            .map(|(version, value)| (Some(("http_version", version.label())), value)),
+    );
+    write_family(
+        &mut out,
+        "atra_failed_fetches_total",
+        "counter",
+        "The number of failed fetches.",
+        [(None, metrics.failed_fetches())],
+    );
+    write_family(
+        &mut out,
+        "atra_skipped_downloads_total",
+        "counter",
+        "The number of pages stored without their body because of its type or size.",
+        [(None, metrics.skipped_downloads())],
+    );
+    write_family(
+        &mut out,
+        "atra_skipped_data_uris_total",
+        "counter",
+        "The number of embedded data uris not stored because of their size.",
+        [(None, metrics.skipped_data_uris())],
+    );
+    write_family(
+        &mut out,
+        "atra_extractor_failures_total",
+        "counter",
+        "The number of failed extractor methods by the kind of the failure.",
+        EXTRACTOR_FAILURE_KINDS
+            .into_iter()
+            .zip(metrics.extractor_failures())
+            .map(|(kind, value)| (Some(("kind", kind)), value)),
+    );
+    write_family(
+        &mut out,
+        "atra_warc_bytes_total",
+        "counter",
+        "The number of bytes written to the warc files.",
+        [(None, metrics.warc_bytes())],
+    );
+    write_family(
+        &mut out,
+        "atra_dat_file_bytes_total",
+        "counter",
+        "The number of bytes of the external data files, as stored and uncompressed.",
+        DAT_FILE_SIZE_KINDS
+            .into_iter()
+            .zip(metrics.dat_file_bytes())
+            .map(|(kind, value)| (Some(("kind", kind)), value)),
+    );
+    write_family(
+        &mut out,
+        "atra_unreachable_origins",
+        "gauge",
+        "The number of origins given up because every request failed to connect.",
+        [(None, metrics.unreachable_origins().len() as u64)],
+    );
+    if let Some(limiter) = context.bandwidth_limiter() {
+        write_family(
+            &mut out,
+            "atra_download_throughput_bytes_per_second",
+            "gauge",
+            "The bytes per second downloaded over the last seconds.",
+            [(None, limiter.throughput())],
+        );
+    }
+    if let Some(stats) = context.get_link_state_manager().cache_stats() {
+        write_family(
+            &mut out,
+            "atra_link_state_cache_hits_total",
+            "counter",
+            "The number of link states read from the cache.",
+            [(None, stats.hits)],
+        );
+        write_family(
+            &mut out,
+            "atra_link_state_cache_misses_total",
+            "counter",
+            "The number of link states read from the database to fill the cache.",
+            [(None, stats.misses)],
+        );
+    }
+    if let Some(stats) = context.get_link_state_manager().seen_filter_stats() {
+        write_family(
+            &mut out,
+            "atra_seen_filter_urls",
+            "gauge",
+            "The number of urls in the filter of the seen urls.",
+            [(None, stats.keys)],
+        );
+        write_family(
+            &mut out,
+            "atra_seen_filter_skipped_reads_total",
+            "counter",
+            "The number of discovered urls not read from the link states.",
+            [(None, stats.skipped_reads)],
+        );
+        write_family(
+            &mut out,
+            "atra_seen_filter_false_positives_total",
+            "counter",
+            "The number of discovered urls reported as seen by the filter but unknown.",
+            [(None, stats.false_positives)],
+        );
+    }
+    let unique_content = context.get_unique_content_manager();
+    let global = unique_content.global_counts();
+    write_family(
+        &mut out,
+        "atra_fetched_payloads_total",
+        "counter",
+        "The number of fetched pages with a payload.",
+        [(None, global.fetched)],
+    );
+    write_family(
+        &mut out,
+        "atra_unique_payloads_total",
+        "counter",
+        "The number of fetched pages with a payload not seen before in the crawl.",
+        [(None, global.unique_payloads)],
+    );
+    let min_samples = context.configs().crawl.unique_content_min_samples;
+    let origins = unique_content
+        .origin_counts()
+        .into_iter()
+        .filter(|(_, counts)| counts.fetched >= min_samples)
+        .filter_map(|(origin, counts)| Some((origin.to_string(), counts.ratio()?)))
+        .collect::<Vec<_>>();
+    write_family(
+        &mut out,
+        "atra_unique_content_ratio",
+        "gauge",
+        "The share of unique payloads among the fetched pages of the crawl and of each origin.",
+        global.ratio().map(|ratio| (None, ratio)).into_iter().chain(
+            origins
+                .iter()
+                .map(|(origin, ratio)| (Some(("origin", origin.as_str())), *ratio)),
+        ),
+    );
+    let tls_origins = context
+        .get_tls_info_manager()
+        .origins()
+        .into_iter()
+        .map(|(origin, info)| (origin.to_string(), info))
+        .collect::<Vec<_>>();
+    if !tls_origins.is_empty() {
+        write_family(
+            &mut out,
+            "atra_tls_certificate_expiry_seconds",
+            "gauge",
+            "The end of the validity of the certificate of each origin as unix timestamp.",
+            tls_origins.iter().filter_map(|(origin, info)| {
+                Some((
+                    Some(("origin", origin.as_str())),
+                    info.capture.leaf()?.not_after.unix_timestamp(),
+                ))
+            }),
+        );
+        write_family(
+            &mut out,
+            "atra_tls_certificate_changes_total",
+            "counter",
+            "The number of times the certificate of each origin changed during the crawl.",
+            tls_origins.iter().map(|(origin, info)| {
+                (
+                    Some(("origin", origin.as_str())),
+                    info.previous_fingerprints.len(),
+                )
+            }),
+        );
+    }
+    write_family(
+        &mut out,
+        "atra_reserved_origins",
+        "gauge",
+        "The number of origins currently reserved by a worker.",
+        [(
+            None,
+            context
+                .get_guardian()
+                .currently_reserved_origins()
+                .await
+                .len() as u64,
+        )],
+    );
+    write_family(
+        &mut out,
+        "atra_guardian_events_dropped_total",
+        "counter",
+        "The number of guardian events missed by lagging subscribers.",
+        [(None, context.get_guardian().dropped_events())],
+    );
+    if let Some((crawling, waiting)) = metrics.worker_states() {
+        write_family(
+            &mut out,
+            "atra_workers",
+            "gauge",
+            "The number of workers by their state.",
+            [
+                (Some(("state", "crawling")), crawling as u64),
+                (Some(("state", "waiting")), waiting as u64),
+            ],
+        );
+    }
+    let pause = context.crawl_events().pause();
+    write_family(
+        &mut out,
+        "atra_paused",
+        "gauge",
+        "1 if the workers are paused, otherwise 0.",
+        [(None, u8::from(pause.is_paused()))],
+    );
+    write_family(
+        &mut out,
+        "atra_paused_seconds_total",
+        "counter",
+        "The seconds the workers were paused.",
+        [(None, pause.paused_for().as_secs_f64())],
+    );
+    write_family(
+        &mut out,
+        "atra_crawl_started_at_seconds",
+        "gauge",
+        "The start of the crawl as unix timestamp.",
+        [(
+            None,
+            context.crawl_started_at().unix_timestamp().max(0) as u64,
+        )],
+    );
+    out
+}
+
+/// Writes a metric family with its help and type.
+fn write_family<'a, V: Display>(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    values: impl IntoIterator<Item = (Option<(&'a str, &'a str)>, V)>,
+) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+    for (label, value) in values {
+        match label {
+            Some((key, label)) => {
+                let _ = writeln!(out, "{name}{{{key}=\"{label}\"}} {value}");
+            }
+            None => {
+                let _ = writeln!(out, "{name} {value}");
+            }
+        }
+    }
+}
+
+/// Serves the output of [render] at `GET /metrics` and passes the JSON array of patterns
+/// posted to `/blacklist` with the address of the client to [patch_blacklist] on the [address]
+/// until the [shutdown]. The lines of the audit trail are served at `GET /audit`, the
+/// metadata returned by [lookup_origin] at `GET /origin?name=<origin>`. The pages of the
+/// link states returned by [query_origins] are served at `GET /origins?offset=&limit=` and
+/// `GET /origins/<origin>/urls?status=&offset=&limit=`. The events of the [resources] are
+/// streamed to the WebSocket clients of `GET /events`, the workers are paused and resumed by
+/// `POST /control/pause` and `POST /control/resume`. The requests changing the crawl are only
+/// accepted with the control token of the [resources], without one only from loopback.
+/// Returns the bound address, which differs from [address] if its port is 0.
+pub async fn serve_metrics<F, Fut, P, PFut, O, Q, QFut>(
+    address: SocketAddr,
+    render: F,
+    patch_blacklist: P,
+    lookup_origin: O,
+    query_origins: Q,
+    resources: MetricsResources,
+    shutdown: ShutdownChild,
+) -> io::Result<(SocketAddr, JoinHandle<()>)>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = String> + Send + 'static,
+    P: Fn(Vec<String>, SocketAddr) -> PFut + Send + Sync + 'static,
+    PFut: Future<Output = Result<u64, RejectedEntry>> + Send + 'static,
+    O: Fn(AtraUrlOrigin) -> Option<OriginMetadataRecord> + Send + Sync + 'static,
+    Q: Fn(OriginQuery) -> QFut + Send + Sync + 'static,
+    QFut: Future<Output = Result<serde_json::Value, String>> + Send + 'static,
+{
+    let listener = TcpListener::bind(address).await?;
+    let address = listener.local_addr()?;
+    log::info!("Serve the metrics on http://{address}{METRICS_PATH}");
+    let handlers = Arc::new((
+        render,
+        patch_blacklist,
+        lookup_origin,
+        query_origins,
+        resources,
+    ));
+    let handle = tokio::spawn(async move {
+        let mut connections = JoinSet::new();
+        loop {
+            let (stream, peer) = select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        log::error!("Failed to accept a metrics request. {err}");
+                        continue;
+                    }
+                },
+                _ = shutdown.wait() => break,
+            };
+            let handlers = handlers.clone();
+            connections.spawn(async move {
+                let (render, patch_blacklist, lookup_origin, query_origins, resources) =
+                    handlers.as_ref();
+                let answered = respond(
+                    stream,
+                    peer,
+                    render,
+                    patch_blacklist,
+                    lookup_origin,
+                    query_origins,
+                    resources,
+                )
+                .await;
+                if let Err(err) = answered {
+                    log::debug!("Failed to answer a metrics request. {err}");
+                }
+            });
+            while connections.try_join_next().is_some() {}
+        }
+        let finishing = async { while connections.join_next().await.is_some() {} };
+        let _ = tokio::time::timeout(SHUTDOWN_GRACE, finishing).await;
+        connections.abort_all();
+    });
+    Ok((address, handle))
+}
+
+/// Returns the trimmed value of the header [name] in [head], the case of the name is ignored.
+fn header_value<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+/// Returns the value of the `Content-Length` header in [head], 0 if it is missing.
+fn content_length(head: &str) -> Option<usize> {
+    match header_value(head, "content-length") {
+        Some(value) => value.parse().ok(),
+        None => Some(0),
+    }
+}
+
+/// Returns why the request with the [head] of the [peer] may not change the crawl, None if it
+/// may. With a [token] the request has to carry it as `Authorization: Bearer <token>`, without
+/// one it has to come from a loopback address.
+fn deny_control(head: &str, peer: SocketAddr, token: Option<&str>) -> Option<&'static str> {
+    match token {
+        Some(token) => {
+            match header_value(head, "authorization")
+                .and_then(|value| value.strip_prefix("Bearer "))
+            {
+                Some(sent) if tokens_match(sent, token) => None,
+                Some(_) => Some("The control token does not match."),
+                None => Some("Expected the control token as Authorization: Bearer <token>."),
+            }
+        }
+        None if peer.ip().to_canonical().is_loopback() => None,
+        None => Some("Without a control token the crawl is only changed from loopback."),
+    }
+}
+
+/// Compares the tokens in a time independent of the first difference.
+fn tokens_match(sent: &str, token: &str) -> bool {
+    sent.len() == token.len()
+        && sent
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+/// Answers a single http request and closes the [stream], a WebSocket is kept open.
+async fn respond<F, Fut, P, PFut, O, Q, QFut>(
+    mut stream: TcpStream,
+    peer: SocketAddr,
+    render: &F,
+    patch_blacklist: &P,
+    lookup_origin: &O,
+    query_origins: &Q,
+    resources: &MetricsResources,
+) -> io::Result<()>
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = String>,
+    P: Fn(Vec<String>, SocketAddr) -> PFut,
+    PFut: Future<Output = Result<u64, RejectedEntry>>,
+    O: Fn(AtraUrlOrigin) -> Option<OriginMetadataRecord>,
+    Q: Fn(OriginQuery) -> QFut,
+    QFut: Future<Output = Result<serde_json::Value, String>>,
+{
+    let mut head = Vec::with_capacity(1024);
+    let mut buffer = [0u8; 1024];
+    let head_end = loop {
+        if let Some(position) = head.windows(4).position(|window| window == b"\r\n\r\n") {
+            break position + 4;
+        }
+        if head.len() > MAX_REQUEST_HEAD {
+            return write_response(
+                &mut stream,
+                "431 Request Header Fields Too Large",
+                CONTENT_TYPE,
+                "",
+            )
+            .await;
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        head.extend_from_slice(&buffer[..read]);
+    };
+    let body = head.split_off(head_end);
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let method = request_line.next().unwrap_or_default();
+    let target = request_line.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    if method == "POST" && matches!(path, BLACKLIST_PATH | PAUSE_PATH | RESUME_PATH) {
+        if let Some(reason) = deny_control(&head, peer, resources.control_token.as_deref()) {
+            log::warn!("Rejected {method} {path} of {peer}: {reason}");
+            return write_json_error(&mut stream, "403 Forbidden", reason).await;
+        }
+    }
+    match (method, path) {
+        ("GET", METRICS_PATH) => {
+            write_response(&mut stream, "200 OK", CONTENT_TYPE, &render().await).await
+        }
+        ("POST", BLACKLIST_PATH) => {
+            post_blacklist(&mut stream, &head, body, peer, patch_blacklist).await
+        }
+        ("GET", AUDIT_PATH) => get_audit(&mut stream, resources).await,
+        ("GET", ORIGIN_PATH) => get_origin(&mut stream, query, lookup_origin).await,
+        ("GET", path) if path.starts_with(ORIGINS_PATH) => {
+            get_origins(&mut stream, path, query, query_origins).await
+        }
+        ("GET", EVENTS_PATH) => get_events(stream, &head, resources).await,
+        ("POST", PAUSE_PATH) => post_pause(&mut stream, true, peer, resources).await,
+        ("POST", RESUME_PATH) => post_pause(&mut stream, false, peer, resources).await,
+        (_, METRICS_PATH)
+        | (_, BLACKLIST_PATH)
+        | (_, AUDIT_PATH)
+        | (_, ORIGIN_PATH)
+        | (_, ORIGINS_PATH)
+        | (_, EVENTS_PATH)
+        | (_, PAUSE_PATH)
+        | (_, RESUME_PATH) => {
+            write_response(&mut stream, "405 Method Not Allowed", CONTENT_TYPE, "").await
+        }
+        _ => write_response(&mut stream, "404 Not Found", CONTENT_TYPE, "").await,
+    }
+}
+
+/// Passes the JSON array of patterns in the [body] of the request with the [head] to
+/// [patch_blacklist], the [body] starts with the bytes read with the [head].
+async fn post_blacklist<P, PFut>(
+    stream: &mut TcpStream,
+    head: &str,
+    mut body: Vec<u8>,
+    peer: SocketAddr,
+    patch_blacklist: &P,
+) -> io::Result<()>
+where
+    P: Fn(Vec<String>, SocketAddr) -> PFut,
+    PFut: Future<Output = Result<u64, RejectedEntry>>,
+{
+    let Some(length) = content_length(head) else {
+        return write_json_error(stream, "400 Bad Request", "Invalid Content-Length.").await;
+    };
+    if length > MAX_BLACKLIST_BODY {
+        return write_json_error(stream, "413 Payload Too Large", "The body is too large.").await;
+    }
+    let mut buffer = [0u8; 1024];
+    while body.len() < length {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Ok(());
+        }
+        body.extend_from_slice(&buffer[..read]);
+    }
+    body.truncate(length);
+    let patterns: Vec<String> = match serde_json::from_slice(&body) {
+        Ok(patterns) => patterns,
+        Err(err) => {
+            return write_json_error(
+                stream,
+                "400 Bad Request",
+                &format!("Expected a JSON array of patterns: {err}"),
+            )
+            .await;
+        }
+    };
+    match patch_blacklist(patterns, peer).await {
+        Ok(version) => {
+            let body = serde_json::json!({ "version": version }).to_string();
+            write_response(stream, "200 OK", JSON_CONTENT_TYPE, &body).await
+        }
+        Err(err) if err.reason == BlacklistError::AlreadyContained => {
+            write_json_error(stream, "409 Conflict", &err.to_string()).await
+        }
+        Err(err) => write_json_error(stream, "400 Bad Request", &err.to_string()).await,
+    }
+}
+
+/// Serves the lines of the audit trail of the [resources].
+async fn get_audit(stream: &mut TcpStream, resources: &MetricsResources) -> io::Result<()> {
+    let Some(audit) = resources.audit.as_ref() else {
+        return write_response(stream, "404 Not Found", CONTENT_TYPE, "").await;
+    };
+    match std::fs::read_to_string(audit.path()) {
+        Ok(trail) => write_response(stream, "200 OK", JSONL_CONTENT_TYPE, &trail).await,
+        Err(err) => {
+            log::error!("Failed to read the audit trail {}: {err}", audit.path());
+            write_response(stream, "500 Internal Server Error", CONTENT_TYPE, "").await
+        }
+    }
+}
+
+/// Serves the metadata returned by [lookup_origin] for the origin in the [query].
+async fn get_origin<O>(stream: &mut TcpStream, query: &str, lookup_origin: &O) -> io::Result<()>
+where
+    O: Fn(AtraUrlOrigin) -> Option<OriginMetadataRecord>,
+{
+    let name = url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "name")
+        .map(|(_, value)| value.into_owned())
+        .filter(|value| !value.is_empty());
+    let Some(name) = name else {
+        return write_json_error(
+            stream,
+            "400 Bad Request",
+            "Expected the origin as query parameter name.",
+        )
+        .await;
+    };
+    let Some(record) = lookup_origin(AtraUrlOrigin::from(name.as_str())) else {
+        return write_json_error(
+            stream,
+            "404 Not Found",
+            &format!("No metadata for the origin {name}."),
+        )
+        .await;
+    };
+    match serde_json::to_string(&record) {
+        Ok(body) => write_response(stream, "200 OK", JSON_CONTENT_TYPE, &body).await,
+        Err(err) => {
+            log::error!("Failed to serialize the metadata of {name}: {err}");
+            write_response(stream, "500 Internal Server Error", CONTENT_TYPE, "").await
+        }
+    }
+}
+
+/// Serves the page of the link states returned by [query_origins] for the [path] and [query].
+async fn get_origins<Q, QFut>(
+    stream: &mut TcpStream,
+    path: &str,
+    query: &str,
+    query_origins: &Q,
+) -> io::Result<()>
+where
+    Q: Fn(OriginQuery) -> QFut,
+    QFut: Future<Output = Result<serde_json::Value, String>>,
+{
+    match OriginQuery::parse(path, query) {
+        Some(Ok(origin_query)) => match query_origins(origin_query).await {
+            Ok(page) => {
+                let body = page.to_string();
+                write_response(stream, "200 OK", JSON_CONTENT_TYPE, &body).await
+            }
+            Err(err) => {
+                log::error!("Failed to list the link states of the origins: {err}");
+                write_json_error(stream, "500 Internal Server Error", &err).await
+            }
+        },
+        Some(Err(message)) => write_json_error(stream, "400 Bad Request", &message).await,
+        None => write_response(stream, "404 Not Found", CONTENT_TYPE, "").await,
+    }
+}
+
+/// Upgrades the request with the [head] to a WebSocket streaming the events of the [resources].
+async fn get_events(
+    mut stream: TcpStream,
+    head: &str,
+    resources: &MetricsResources,
+) -> io::Result<()> {
+    let Some(events) = resources.events.as_ref() else {
+        return write_response(&mut stream, "404 Not Found", CONTENT_TYPE, "").await;
+    };
+    let is_upgrade =
+        header_value(head, "upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    match header_value(head, "sec-websocket-key").filter(|_| is_upgrade) {
+        Some(key) => stream_live_events(stream, key, events).await,
+        None => {
+            write_json_error(
+                &mut stream,
+                "426 Upgrade Required",
+                "Expected the upgrade to a WebSocket.",
+            )
+            .await
+        }
+    }
+}
+
+/// Pauses or resumes the workers with the pause of the [resources], answers with its state.
+async fn post_pause(
+    stream: &mut TcpStream,
+    paused: bool,
+    peer: SocketAddr,
+    resources: &MetricsResources,
+) -> io::Result<()> {
+    let Some(pause) = resources.pause.as_ref() else {
+        return write_response(stream, "404 Not Found", CONTENT_TYPE, "").await;
+    };
+    let changed = if paused {
+        pause.pause()
+    } else {
+        pause.resume()
+    };
+    if changed {
+        log::info!(
+            "The crawl was {} by {peer}.",
+            if paused { "paused" } else { "resumed" }
+        );
+    }
+    let body = serde_json::json!({
+        "paused": pause.is_paused(),
+        "paused_seconds": pause.paused_for().as_secs_f64(),
+    })
+    .to_string();
+    write_response(stream, "200 OK", JSON_CONTENT_TYPE, &body).await
+}
+
+async fn write_json_error(stream: &mut TcpStream, status: &str, message: &str) -> io::Result<()> {
+    let body = serde_json::json!({ "error": message }).to_string();
+    write_response(stream, status, JSON_CONTENT_TYPE, &body).await
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+#[cfg(test)]
+mod test {
+    use super::{deny_control, render_metrics, serve_metrics, MetricsResources, OriginQuery};
+    use crate::app::live_events::LiveEvents;
+    use crate::blacklist::{
+        add_entries_audited, Blacklist, BlacklistManager, InMemoryBlacklistManager, PolyBlackList,
+    };
+    use crate::config::{BudgetSetting, Config, CrawlConfig};
+    use crate::contexts::traits::{
+        SupportsCrawlEvents, SupportsCrawling, SupportsPolling, SupportsTlsInfo,
+        SupportsUniqueContent, SupportsUrlGuarding, SupportsUrlQueue,
+    };
+    use crate::crawl::{crawl, CrawlEvent, CrawlPause, ExitState};
+    use crate::data::RawData;
+    use crate::database::open_db;
+    use crate::fetching::{FetchedRequestData, HttpVersion};
+    use crate::link_state::{DatabaseLinkStateManager, LinkStateKind, LinkStateManager};
+    use crate::origin_metadata::test::sample_origin_metadata;
+    use crate::queue::{UrlQueue, UrlQueueElement, UrlQueuePollResult};
+    use crate::runtime::{
+        GracefulShutdown, GracefulShutdownWithGuard, ShutdownPhantom, ShutdownSender,
+    };
+    use crate::schemas::{
+        read_audit_trail, AuditAction, AuditLog, AuditOutcome, AuditSource, OriginMetadataRecord,
+    };
+    use crate::sync::WorkerBarrier;
+    use crate::test_impls::{FakeClientProvider, FakeResponse, TestContext, TestErrorConsumer};
+    use crate::tls_info::test::sample_tls_info;
+    use crate::tls_info::TlsInfoManager;
+    use crate::unique_content::UniqueContentManager;
+    use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::StatusCode;
+    use rocksdb::DB;
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use time::OffsetDateTime;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+    use tokio_util::sync::CancellationToken;
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    async fn request(address: std::net::SocketAddr, path: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    async fn post(address: std::net::SocketAddr, path: &str, body: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST {path} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                    body.len()
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    /// Posts an empty body to [path] with the control [token].
+    async fn post_authorized(address: std::net::SocketAddr, path: &str, token: &str) -> String {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                format!(
+                    "POST {path} HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer {token}\r\nContent-Length: 0\r\n\r\n"
+                )
+                .as_bytes(),
+            )
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    /// Upgrades a connection to `GET /events` to a WebSocket.
+    async fn connect_events(address: std::net::SocketAddr) -> TcpStream {
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(
+                b"GET /events HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            head.push(stream.read_u8().await.unwrap());
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(
+            head.starts_with("HTTP/1.1 101 Switching Protocols\r\n"),
+            "{head}"
+        );
+        assert!(head.contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n"));
+        stream
+    }
+
+    /// Reads the events sent as text frames until the server closes the WebSocket.
+    async fn read_events(stream: &mut TcpStream) -> Vec<serde_json::Value> {
+        let mut events = Vec::new();
+        loop {
+            let opcode = stream.read_u8().await.unwrap() & 0x0F;
+            let length = match stream.read_u8().await.unwrap() {
+                126 => stream.read_u16().await.unwrap() as usize,
+                127 => stream.read_u64().await.unwrap() as usize,
+                length => length as usize,
+            };
+            let mut payload = vec![0; length];
+            stream.read_exact(&mut payload).await.unwrap();
+            match opcode {
+                0x1 => events.push(serde_json::from_slice(&payload).unwrap()),
+                0x8 => return events,
+                opcode => panic!("Unexpected opcode {opcode}."),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn renders_the_metrics_of_the_context() {
+        let mut config = Config::default();
+        config.crawl.unique_content_min_samples = 4;
+        let context = TestContext::new(config, FakeClientProvider::new());
+        for value in ["https://www.example.com/", "https://www.example.com/a.html"] {
+            context
+                .url_queue()
+                .enqueue(UrlQueueElement::new(false, 0, false, url(value)))
+                .await
+                .unwrap();
+        }
+        let events = context.crawl_events();
+        for status_code in [StatusCode::OK, StatusCode::OK, StatusCode::NOT_FOUND] {
+            events.emit(CrawlEvent::Crawled {
+                url: url("https://www.example.com/b.html"),
+                status_code,
+                bytes: None,
+                duration: None,
+            });
+        }
+        events.metrics().add_warc_bytes(1234);
+        events.metrics().add_dat_file_bytes(100, 1000);
+        events.metrics().record_http_version(HttpVersion::Http11);
+        events
+            .metrics()
+            .observe_workers(Arc::new(WorkerBarrier::new(
+                NonZeroUsize::new(4).unwrap(),
+                CancellationToken::new(),
+            )));
+
+        let unique_content = context.get_unique_content_manager();
+        for (origin, digest) in [
+            ("www.example.com", "a"),
+            ("www.example.com", "a"),
+            ("www.example.com", "b"),
+            ("www.example.com", "a"),
+            ("other.example.com", "a"),
+        ] {
+            unique_content
+                .register_payload(&AtraUrlOrigin::from(origin), digest.as_bytes())
+                .await
+                .unwrap();
+        }
+
+        let mut tls_info =
+            sample_tls_info(OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap());
+        tls_info.previous_fingerprints.push("ab".to_string());
+        context
+            .get_tls_info_manager()
+            .store(&AtraUrlOrigin::from("example.com"), tls_info)
+            .unwrap();
+
+        let rendered = render_metrics(&context).await;
+        let lines = rendered.lines().collect::<Vec<_>>();
+        for expected in [
+            "# TYPE atra_queue_length gauge",
+            "atra_fetched_payloads_total 5",
+            "atra_unique_payloads_total 2",
+            "atra_unique_content_ratio 0.4",
+            "atra_unique_content_ratio{origin=\"www.example.com\"} 0.5",
+            "atra_tls_certificate_expiry_seconds{origin=\"example.com\"} 1893456000",
+            "atra_tls_certificate_changes_total{origin=\"example.com\"} 1",
+            "atra_queue_length 2",
+            "atra_paused 0",
+            "atra_crawled_pages_total{status_class=\"2xx\"} 2",
+            "atra_crawled_pages_total{status_class=\"4xx\"} 1",
+            "atra_crawled_pages_total{status_class=\"5xx\"} 0",
+            "atra_responses_total{http_version=\"HTTP/1.1\"} 1",
+            "atra_responses_total{http_version=\"HTTP/2\"} 0",
+            "atra_failed_fetches_total 0",
+            "atra_skipped_downloads_total 0",
+            "atra_skipped_data_uris_total 0",
+            "atra_extractor_failures_total{kind=\"timeout\"} 0",
+            "atra_warc_bytes_total 1234",
+            "atra_dat_file_bytes_total{kind=\"stored\"} 100",
+            "atra_dat_file_bytes_total{kind=\"uncompressed\"} 1000",
+            "atra_unreachable_origins 0",
+            "atra_reserved_origins 0",
+            "atra_guardian_events_dropped_total 0",
+            "atra_workers{state=\"crawling\"} 4",
+            "atra_workers{state=\"waiting\"} 0",
+        ] {
+            assert!(
+                lines.contains(&expected),
+                "Missing {expected} in:\n{rendered}"
+            );
+        }
+        assert!(!rendered.contains("other.example.com"));
+        // The in-memory link states have no cache.
+        assert!(!rendered.contains("atra_link_state_cache"));
+        assert!(!rendered.contains("atra_seen_filter"));
+    }
+
+    #[tokio::test]
+    async fn serves_the_metrics_over_http() {
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { "atra_queue_length 7\n".to_string() },
+            |_, _| async { Ok(0) },
+            |_| None,
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources::default(),
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+
+        let response = request(address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: text/plain; version=0.0.4"));
+        assert!(
+            response.ends_with("\r\n\r\natra_queue_length 7\n"),
+            "{response}"
+        );
+
+        for path in ["/other", "/audit"] {
+            let response = request(address, path).await;
+            assert!(
+                response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+                "{response}"
+            );
+        }
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+        assert!(TcpStream::connect(address).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn patches_the_blacklist_over_http() {
+        let dir = Utf8TempDir::new().unwrap();
+        let manager = Arc::new(
+            InMemoryBlacklistManager::<PolyBlackList>::open(
+                dir.path().join("blacklist.txt"),
+                GracefulShutdownWithGuard::new(),
+            )
+            .unwrap(),
+        );
+        let audit = AuditLog::open(dir.path()).unwrap();
+        let shutdown = GracefulShutdown::new();
+        let patch_manager = manager.clone();
+        let patch_audit = audit.clone();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            move |patterns, peer| {
+                let manager = patch_manager.clone();
+                let audit = patch_audit.clone();
+                async move {
+                    add_entries_audited(
+                        manager.as_ref(),
+                        patterns,
+                        AuditSource::Http {
+                            peer: peer.to_string(),
+                        },
+                        Some(&audit),
+                    )
+                    .await
+                }
+            },
+            |_| None,
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources {
+                audit: Some(audit),
+                ..MetricsResources::default()
+            },
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+
+        let response = post(address, "/blacklist", r#"["example\\.com", "other\\.org"]"#).await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.ends_with("{\"version\":2}"), "{response}");
+        let blacklist = manager.get_blacklist().await;
+        assert!(blacklist.has_match_for("https://www.example.com/"));
+        assert!(blacklist.has_match_for("https://other.org/a"));
+
+        let response = post(address, "/blacklist", r#"["example\\.com"]"#).await;
+        assert!(
+            response.starts_with("HTTP/1.1 409 Conflict\r\n"),
+            "{response}"
+        );
+        let response = post(address, "/blacklist", r#"["a", ""]"#).await;
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{response}"
+        );
+        let response = post(address, "/blacklist", r#"{"pattern": "a"}"#).await;
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{response}"
+        );
+        assert_eq!(2, manager.current_version().await);
+
+        let response = request(address, "/blacklist").await;
+        assert!(
+            response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"),
+            "{response}"
+        );
+
+        // The malformed body never reached the blacklist.
+        let records = read_audit_trail(dir.path()).unwrap();
+        assert_eq!(
+            vec![
+                (
+                    AuditAction::BlacklistPatch {
+                        entries: vec!["example\\.com".to_string(), "other\\.org".to_string()],
+                        version_before: 0,
+                        version_after: 2,
+                    },
+                    AuditOutcome::Applied
+                ),
+                (
+                    AuditAction::BlacklistPatch {
+                        entries: vec!["example\\.com".to_string()],
+                        version_before: 2,
+                        version_after: 2,
+                    },
+                    AuditOutcome::Rejected {
+                        reason: "The blacklist entry \"example\\\\.com\" was rejected: The entry is already in the blacklist.".to_string()
+                    }
+                ),
+            ],
+            records
+                .iter()
+                .take(2)
+                .map(|record| (record.action.clone(), record.outcome.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(3, records.len());
+        assert!(matches!(records[2].outcome, AuditOutcome::Rejected { .. }));
+        assert!(records.iter().all(|record| matches!(
+            &record.source,
+            AuditSource::Http { peer } if peer.starts_with("127.0.0.1:")
+        )));
+
+        let response = request(address, "/audit").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: application/x-ndjson"));
+        assert_eq!(
+            3,
+            response.split("\r\n\r\n").nth(1).unwrap().lines().count()
+        );
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_the_metadata_of_an_origin() {
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            |_, _| async { Ok(0) },
+            |origin: AtraUrlOrigin| {
+                (origin.as_ref() == "example.com:8080")
+                    .then(|| OriginMetadataRecord::new(&origin, &sample_origin_metadata()))
+            },
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources::default(),
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+
+        let response = request(address, "/origin?name=example.com%3A8080").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(response.contains("Content-Type: application/json"));
+        let body: serde_json::Value =
+            serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap();
+        assert_eq!("origin-metadata-record", body["schema"]);
+        assert_eq!("example.com:8080", body["origin"]);
+        assert_eq!(2, body["pages"]);
+        assert_eq!(1, body["languages"]["counts"]["de"]);
+
+        let response = request(address, "/origin?name=example.org").await;
+        assert!(
+            response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+            "{response}"
+        );
+        assert!(response.contains("example.org"));
+        for path in ["/origin", "/origin?name="] {
+            let response = request(address, path).await;
+            assert!(
+                response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+                "{response}"
+            );
+        }
+        let response = post(address, "/origin?name=example.com", "").await;
+        assert!(
+            response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"),
+            "{response}"
+        );
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn serves_the_link_states_of_the_origins() {
+        let dir = Utf8TempDir::new().unwrap();
+        let db: Arc<DB> = open_db(dir.path().join("db")).unwrap().into();
+        let link_states = Arc::new(DatabaseLinkStateManager::new(db));
+        for (value, kind) in [
+            (
+                "https://www.example.com/a",
+                LinkStateKind::ProcessedAndStored,
+            ),
+            ("https://www.example.com/b", LinkStateKind::Discovered),
+            ("https://www.example.com/c", LinkStateKind::Discovered),
+            ("https://example.org/", LinkStateKind::InternalError),
+        ] {
+            link_states
+                .update_link_state_no_meta_and_payload(&url(value), kind)
+                .await
+                .unwrap();
+        }
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            |_, _| async { Ok(0) },
+            |_| None,
+            move |query| {
+                let link_states = link_states.clone();
+                async move {
+                    tokio::task::spawn_blocking(move || match query {
+                        OriginQuery::Summaries { offset, limit } => serde_json::to_value(
+                            link_states.origin_summaries(offset, limit).unwrap(),
+                        ),
+                        OriginQuery::Urls {
+                            origin,
+                            kind,
+                            offset,
+                            limit,
+                        } => serde_json::to_value(
+                            link_states
+                                .origin_urls(&origin, kind, offset, limit)
+                                .unwrap(),
+                        ),
+                    })
+                    .await
+                    .unwrap()
+                    .map_err(|err| err.to_string())
+                }
+            },
+            MetricsResources::default(),
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+        let body = |response: &str| -> serde_json::Value {
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+            serde_json::from_str(response.split("\r\n\r\n").nth(1).unwrap()).unwrap()
+        };
+
+        let page = body(&request(address, "/origins?limit=1").await);
+        assert_eq!(1, page["next_offset"]);
+        assert_eq!("example.com", page["items"][0]["origin"]);
+        assert_eq!(1, page["items"][0]["crawled"]);
+        assert_eq!(2, page["items"][0]["queued"]);
+        let page = body(&request(address, "/origins?offset=1&limit=1").await);
+        assert!(page["next_offset"].is_null());
+        assert_eq!("example.org", page["items"][0]["origin"]);
+        assert_eq!(1, page["items"][0]["errored"]);
+
+        let page = body(
+            &request(
+                address,
+                "/origins/Example.com/urls?status=discovered&offset=1",
+            )
+            .await,
+        );
+        assert_eq!(1, page["items"].as_array().unwrap().len());
+        assert_eq!("https://www.example.com/c", page["items"][0]["url"]);
+        assert_eq!("Discovered", page["items"][0]["kind"]);
+        let page = body(&request(address, "/origins/example.org/urls").await);
+        assert_eq!("https://example.org/", page["items"][0]["url"]);
+
+        for path in [
+            "/origins?limit=0",
+            "/origins?limit=1001",
+            "/origins?offset=-1",
+            "/origins/example.com/urls?status=done",
+            "/origins//urls",
+        ] {
+            let response = request(address, path).await;
+            assert!(
+                response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+                "{path}: {response}"
+            );
+        }
+        for path in ["/origins/example.com", "/originsexample.com"] {
+            let response = request(address, path).await;
+            assert!(
+                response.starts_with("HTTP/1.1 404 Not Found\r\n"),
+                "{path}: {response}"
+            );
+        }
+        let response = post(address, "/origins", "").await;
+        assert!(
+            response.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"),
+            "{response}"
+        );
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn streams_the_events_of_a_crawl_over_a_websocket() {
+        const FIRST: &str = r#"<html><body><a href="/second.html">Second</a></body></html>"#;
+        const SECOND: &str = "<html><body>Second</body></html>";
+        let mut config = CrawlConfig::default();
+        config.respect_robots_txt = false;
+        config.ignore_sitemap = true;
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 1,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        let config = Config::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            config,
+        );
+        let provider = FakeClientProvider::new();
+        for (page, body) in [
+            ("https://www.example.com/", FIRST),
+            ("https://www.example.com/second.html", SECOND),
+        ] {
+            provider.insert(
+                page.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(body.as_bytes().to_vec()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+        let context = TestContext::new(config, provider);
+        context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(
+                true,
+                0,
+                false,
+                url("https://www.example.com/"),
+            ))
+            .await
+            .unwrap();
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            |_, _| async { Ok(0) },
+            |_| None,
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources {
+                events: Some(LiveEvents::new(
+                    context.crawl_events().clone(),
+                    context.get_guardian().events().clone(),
+                )),
+                ..MetricsResources::default()
+            },
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+        let mut stream = connect_events(address).await;
+
+        while let UrlQueuePollResult::Ok(guard) = context.poll_next_free_url_no_shutdown(None).await
+        {
+            context
+                .create_crawl_task(guard.get_guarded_seed())
+                .unwrap()
+                .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+                .await
+                .unwrap();
+        }
+        // Emitted by the application after all workers stopped.
+        context.crawl_events().emit(CrawlEvent::Finished);
+
+        let events = tokio::time::timeout(Duration::from_secs(10), read_events(&mut stream))
+            .await
+            .unwrap();
+        // The events of the crawl and of the guardian keep their order but are interleaved.
+        let (origin_events, crawl_events): (Vec<_>, Vec<_>) = events
+            .into_iter()
+            .partition(|event| event["type"].as_str().unwrap().starts_with("origin_"));
+        assert_eq!(
+            vec![
+                serde_json::json!({
+                    "type": "worker_state_changed",
+                    "worker_id": 0,
+                    "state": "fetching",
+                    "url": "https://www.example.com/"
+                }),
+                serde_json::json!({
+                    "type": "url_queued",
+                    "url": "https://www.example.com/second.html"
+                }),
+                serde_json::json!({
+                    "type": "url_crawled",
+                    "url": "https://www.example.com/",
+                    "status": 200,
+                    "bytes": FIRST.len(),
+                }),
+                serde_json::json!({
+                    "type": "worker_state_changed",
+                    "worker_id": 0,
+                    "state": "fetching",
+                    "url": "https://www.example.com/second.html"
+                }),
+                serde_json::json!({
+                    "type": "url_crawled",
+                    "url": "https://www.example.com/second.html",
+                    "status": 200,
+                    "bytes": SECOND.len(),
+                }),
+                serde_json::json!({ "type": "crawl_finished" }),
+            ],
+            crawl_events
+        );
+        let origin = url("https://www.example.com/")
+            .atra_origin()
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            vec![
+                ("origin_reserved", origin.as_str()),
+                ("origin_released", origin.as_str())
+            ],
+            origin_events
+                .iter()
+                .map(|event| (
+                    event["type"].as_str().unwrap(),
+                    event["origin"].as_str().unwrap()
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(origin_events[1]["duration"].is_u64());
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_paused_crawl_fetches_nothing_until_it_is_resumed() {
+        let mut config = CrawlConfig::default();
+        config.respect_robots_txt = false;
+        config.ignore_sitemap = true;
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 1,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        let config = Config::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            config,
+        );
+        let provider = FakeClientProvider::new();
+        for (page, body) in [
+            (
+                "https://www.example.com/",
+                r#"<html><body><a href="/second.html">Second</a></body></html>"#,
+            ),
+            (
+                "https://www.example.com/second.html",
+                "<html><body>Second</body></html>",
+            ),
+        ] {
+            provider.insert(
+                page.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(body.as_bytes().to_vec()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+        let context = TestContext::new(config, provider.clone());
+        context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(
+                true,
+                0,
+                false,
+                url("https://www.example.com/"),
+            ))
+            .await
+            .unwrap();
+        let pause = context.crawl_events().pause().clone();
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            |_, _| async { Ok(0) },
+            |_| None,
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources {
+                pause: Some(pause.clone()),
+                ..MetricsResources::default()
+            },
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+
+        let response = post(address, "/control/pause", "").await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains(r#""paused":true"#), "{response}");
+
+        let crawling = crawl(
+            context,
+            ShutdownPhantom::<true>,
+            Arc::new(WorkerBarrier::new(
+                NonZeroUsize::new(1).unwrap(),
+                CancellationToken::new(),
+            )),
+            TestErrorConsumer::new(),
+        );
+        let control = async {
+            // The worker is parked for the whole interval.
+            tokio::time::sleep(Duration::from_millis(300)).await;
+            assert!(provider.requests().is_empty());
+            let resumed_at = tokio::time::Instant::now();
+            let response = post(address, "/control/resume", "").await;
+            assert!(response.contains(r#""paused":false"#), "{response}");
+            resumed_at
+        };
+        let (result, resumed_at) = tokio::time::timeout(Duration::from_secs(30), async {
+            tokio::join!(crawling, control)
+        })
+        .await
+        .unwrap();
+        assert_eq!(ExitState::NoMoreElements, result.unwrap());
+        let requests = provider.requests();
+        assert_eq!(2, requests.len());
+        assert!(requests.iter().all(|(_, at)| *at >= resumed_at));
+        assert!(!pause.is_paused());
+        assert!(pause.paused_for() >= Duration::from_millis(300));
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+
+    #[test]
+    fn only_accepts_changes_from_loopback_or_with_the_control_token() {
+        let head = |authorization: &str| {
+            format!("POST /control/pause HTTP/1.1\r\nHost: localhost\r\n{authorization}\r\n")
+        };
+        let loopback = "127.0.0.1:4000".parse().unwrap();
+        let mapped = "[::ffff:127.0.0.1]:4000".parse().unwrap();
+        let remote = "192.0.2.1:4000".parse().unwrap();
+        assert_eq!(None, deny_control(&head(""), loopback, None));
+        assert_eq!(None, deny_control(&head(""), mapped, None));
+        assert!(deny_control(&head(""), remote, None).is_some());
+
+        let token = Some("secret");
+        let authorized = head("Authorization: Bearer secret\r\n");
+        assert_eq!(None, deny_control(&authorized, remote, token));
+        assert_eq!(None, deny_control(&authorized, loopback, token));
+        assert!(deny_control(&head("Authorization: Bearer secreT\r\n"), remote, token).is_some());
+        assert!(deny_control(&head("Authorization: secret\r\n"), remote, token).is_some());
+        assert!(deny_control(&head(""), loopback, token).is_some());
+    }
+
+    #[tokio::test]
+    async fn rejects_changes_without_the_control_token() {
+        let pause = CrawlPause::new();
+        let shutdown = GracefulShutdown::new();
+        let (address, handle) = serve_metrics(
+            "127.0.0.1:0".parse().unwrap(),
+            || async { String::new() },
+            |_, _| async { Ok(1) },
+            |_| None,
+            |_| async { Ok(serde_json::Value::Null) },
+            MetricsResources {
+                pause: Some(pause.clone()),
+                control_token: Some("secret".to_string()),
+                ..MetricsResources::default()
+            },
+            shutdown.child().clone(),
+        )
+        .await
+        .unwrap();
+
+        for path in ["/control/pause", "/blacklist"] {
+            let response = post(address, path, r#"["example\\.com"]"#).await;
+            assert!(
+                response.starts_with("HTTP/1.1 403 Forbidden\r\n"),
+                "{response}"
+            );
+            let response = post_authorized(address, path, "other").await;
+            assert!(
+                response.starts_with("HTTP/1.1 403 Forbidden\r\n"),
+                "{response}"
+            );
+        }
+        assert!(!pause.is_paused());
+        // Reading is not restricted.
+        let response = request(address, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+
+        let response = post_authorized(address, "/control/pause", "secret").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(pause.is_paused());
+
+        shutdown.child().shutdown();
+        handle.await.unwrap();
+    }
+}
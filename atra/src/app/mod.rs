@@ -14,24 +14,39 @@
 
 mod args;
 mod atra;
+mod builder;
 mod constants;
 pub mod consumer;
+mod fetchlist;
+mod live_events;
 mod logging;
+mod metrics;
 
 mod config;
 mod instruction;
-#[cfg(test)]
 mod terminal;
 mod view;
 mod exitcode_conversions;
 mod dump;
+mod rebuild;
+mod expired;
+mod dry_run;
+mod requeue;
+mod recover;
+mod checkpoint;
+mod backfill;
+mod import_har;
+mod import_warc;
+mod secondary;
+mod maintain;
+mod gdbr_export;
+mod verify;
 
 use std::process::ExitCode;
 use crate::app::instruction::{prepare_instruction, Instruction, RunInstruction};
 pub use args::AtraArgs;
-pub use atra::ApplicationMode;
-use atra::Atra;
-use crate::app::atra::AtraRunError;
+pub use atra::{ApplicationMode, AtraRunError};
+pub use builder::{AtraBuilder, AtraCrawlHandle};
 
 /// Execute the [`args`]
 pub fn exec_args(args: AtraArgs) -> ExitCode {
@@ -60,14 +75,12 @@ pub fn exec_args(args: AtraArgs) -> ExitCode {
 
 /// Execute the [`instruction`]
 fn execute(instruction: RunInstruction) -> Result<(), AtraRunError> {
-    let (mut atra, runtime) = Atra::build_with_runtime(instruction.mode);
+    let (handle, runtime) = AtraBuilder::from(instruction).build_with_runtime();
 
     let result = runtime.block_on(async move {
-        let shutdown = atra.shutdown().get().clone();
-
         let shutdown_result = {
             let ctrl_c = tokio::signal::ctrl_c();
-            let future = atra.run(instruction);
+            let future = handle.run();
             tokio::pin!(future);
 
             let mut shutdown_result: Option<Result<(), AtraRunError>> = None;
@@ -79,7 +92,7 @@ fn execute(instruction: RunInstruction) -> Result<(), AtraRunError> {
                 }
                 _ = ctrl_c => {
                     log::info!("Starting with shutdown by CTRL-C.");
-                    shutdown.shutdown();
+                    handle.shutdown_by_signal("SIGINT");
                 }
             }
 
@@ -94,9 +107,8 @@ fn execute(instruction: RunInstruction) -> Result<(), AtraRunError> {
         if let Err(err) = &shutdown_result {
             log::error!("Exit with error: {err}");
         }
-        drop(atra);
         log::info!("Waiting for complete shutdown...");
-        shutdown.wait().await;
+        handle.close().await;
         shutdown_result
     });
     log::info!("Complete shutdown.");
@@ -119,6 +131,9 @@ mod test {
         let args = AtraArgs {
             mode: None,
             generate_example_config: true,
+            explain_priority: None,
+            show_effective: None,
+            print_schema: None,
         };
         crate::exec_args(args);
     }
@@ -136,8 +151,13 @@ mod test {
                 agent: UserAgent::Custom("TestCrawl/Atra/v0.1.0".to_string()),
                 log_to_file: true,
                 delay: None,
+                dry_run: false,
+                tui: false,
             }),
             generate_example_config: false,
+            explain_priority: None,
+            show_effective: None,
+            print_schema: None,
         };
 
         crate::exec_args(args);
@@ -170,6 +190,8 @@ mod test {
                 "https://ticktoo.com/".to_string(),
             ])),
             recover_mode: false,
+            fetch_list: None,
+            tui: false,
         }).expect("This should not fail.")
     }
 }
@@ -0,0 +1,525 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{CrawlResultMeta, SlimCrawlResult, StoredDataHint};
+use crate::database::{open_db, DatabaseError, OpenDBError};
+use crate::format::AtraFileInformation;
+use crate::link_state::{LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateRockDB};
+use crate::robots::UnavailableAfter;
+use crate::url::UrlWithDepth;
+use crate::warc_ext::{
+    unpack_header, ScannedWarcRecord, WarcRecordScanner, WarcSkipInstruction,
+    WarcSkipInstructionKind, WarcSkipPointerWithPath,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use encoding_rs::Encoding;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use std::sync::Arc;
+use strum::Display;
+use thiserror::Error;
+use warc::header::WarcHeader;
+use warc::record_type::WarcRecordType;
+
+/// Errors that stop the rebuild of the crawl db.
+#[derive(Debug, Error)]
+pub enum RebuildError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    LinkState(#[from] LinkStateDBError),
+}
+
+/// Why a record could not be attributed to a crawled url.
+#[derive(Debug, Display, Clone, Eq, PartialEq)]
+pub enum UnattributedReason {
+    #[strum(to_string = "The record was not written by atra.")]
+    NotAnAtraRecord,
+    #[strum(to_string = "The record has no usable WARC-Target-URI.")]
+    NoTargetUri,
+    #[strum(to_string = "The record has no usable WARC-Date.")]
+    NoDate,
+    #[strum(to_string = "The stored http header can not be parsed.")]
+    BadHttpHeader,
+    #[strum(to_string = "The block digest does not match the body.")]
+    DigestMismatch,
+    #[strum(to_string = "The continuation chain is incomplete.")]
+    IncompleteChain,
+    #[strum(to_string = "The file is corrupt after this offset: {0}")]
+    CorruptFile(String),
+}
+
+/// A record that could not be attributed.
+#[derive(Debug, Clone)]
+pub struct UnattributedRecord {
+    pub path: Utf8PathBuf,
+    pub offset: u64,
+    pub reason: UnattributedReason,
+}
+
+/// The result of a rebuild.
+#[derive(Debug, Default)]
+pub struct RebuildReport {
+    /// The number of warc files scanned
+    pub files: usize,
+    /// The number of records read
+    pub records: usize,
+    /// The number of urls written to the crawl db
+    pub rebuilt: usize,
+    /// The number of records dropped or replaced because of another record for the same url
+    pub superseded: usize,
+    /// The records that could not be attributed to any url
+    pub unattributed: Vec<UnattributedRecord>,
+}
+
+impl Display for RebuildReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Scanned files:          {}", self.files)?;
+        writeln!(f, "Scanned records:        {}", self.records)?;
+        writeln!(f, "Rebuilt entries:        {}", self.rebuilt)?;
+        writeln!(f, "Superseded entries:     {}", self.superseded)?;
+        writeln!(f, "Unattributed records:   {}", self.unattributed.len())?;
+        for value in &self.unattributed {
+            writeln!(f, "    {}@{}: {}", value.path, value.offset, value.reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// The first segment of a continuation chain, holds everything necessary to
+/// describe the whole chain.
+struct ChainStart {
+    path: Utf8PathBuf,
+    offset: u64,
+    header: WarcHeader,
+    header_signature: Vec<u8>,
+}
+
+#[derive(Default)]
+struct PendingChain {
+    start: Option<ChainStart>,
+    segments: BTreeMap<u64, WarcSkipPointerWithPath>,
+    total_length: Option<u64>,
+    body_octets: u64,
+}
+
+impl PendingChain {
+    fn is_complete(&self) -> bool {
+        self.start.is_some()
+            && self.total_length == Some(self.body_octets)
+            && self.segments.keys().copied().eq(1..=self.segments.len() as u64)
+    }
+}
+
+/// Rebuilds the crawl db of the session described by [config] from its warc files.
+///
+/// An existing database is moved aside, the rebuilt entries and their link states are
+/// written to a fresh one. Only what is archived can be restored: the depth, the
+/// extracted links and the language of an entry are lost and the url is the
+/// WARC-Target-URI, i.e. the final redirect destination if there was one.
+pub(crate) fn rebuild_db(config: &Config) -> Result<RebuildReport, RebuildError> {
+    let db_path = config.paths.dir_database();
+    if db_path.exists() {
+        let backup = Utf8PathBuf::from(format!(
+            "{}_{}.bak",
+            db_path,
+            time::OffsetDateTime::now_utc().unix_timestamp()
+        ));
+        log::warn!("Moving the existing database from {db_path} to {backup}.");
+        std::fs::rename(&db_path, &backup)?;
+    }
+
+    let db = Arc::new(open_db(&db_path)?);
+    let crawl_db = CrawlDB::new(db.clone(), config)?;
+    let link_states = LinkStateRockDB::new(db);
+
+    let mut report = RebuildReport::default();
+    let mut pending: HashMap<Vec<u8>, PendingChain> = HashMap::new();
+
+    for path in collect_warc_files(config)? {
+        log::info!("Scanning {path}");
+        report.files += 1;
+        let mut scanner = WarcRecordScanner::new(BufReader::new(File::open(&path)?));
+        loop {
+            let offset = scanner.offset();
+            let record = match scanner.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                Err(err) => {
+                    log::error!("Failed to read {path} at {offset}: {err}");
+                    report.unattributed.push(UnattributedRecord {
+                        path: path.clone(),
+                        offset,
+                        reason: UnattributedReason::CorruptFile(err.to_string()),
+                    });
+                    break;
+                }
+            };
+            report.records += 1;
+
+            // The request and conversion records only document the paired response, the
+            // warcinfo record describes the crawl of the file.
+            if matches!(
+                record.header.get_warc_type(),
+                Ok(WarcRecordType::Request | WarcRecordType::Conversion | WarcRecordType::WarcInfo)
+            ) {
+                continue;
+            }
+
+            if record.block_digest_matches == Some(false) {
+                report.unattributed.push(UnattributedRecord {
+                    path: path.clone(),
+                    offset,
+                    reason: UnattributedReason::DigestMismatch,
+                });
+                continue;
+            }
+
+            let restored = if let Some(segment) = record.header.get_segment_number() {
+                let segment = segment.copied().unwrap_or(0);
+                let origin = match record.header.get_segment_origin_id() {
+                    Some(Ok(value)) => value.as_ref().to_vec(),
+                    _ => {
+                        report.unattributed.push(UnattributedRecord {
+                            path: path.clone(),
+                            offset,
+                            reason: UnattributedReason::IncompleteChain,
+                        });
+                        continue;
+                    }
+                };
+                let chain = pending.entry(origin.clone()).or_default();
+                add_to_chain(chain, &path, segment, record);
+                if !chain.is_complete() {
+                    continue;
+                }
+                let chain = pending.remove(&origin).unwrap();
+                restore_chain(chain)
+            } else {
+                restore_single(&path, record)
+            };
+
+            match restored {
+                Ok(slim) => {
+                    store(&crawl_db, &link_states, slim, &mut report)?;
+                }
+                Err(reason) => {
+                    report.unattributed.push(UnattributedRecord {
+                        path: path.clone(),
+                        offset,
+                        reason,
+                    });
+                }
+            }
+        }
+    }
+
+    for (_, chain) in pending {
+        if let Some(start) = chain.start {
+            report.unattributed.push(UnattributedRecord {
+                path: start.path,
+                offset: start.offset,
+                reason: UnattributedReason::IncompleteChain,
+            });
+        } else {
+            for pointer in chain.segments.values() {
+                report.unattributed.push(UnattributedRecord {
+                    path: pointer.path().to_path_buf(),
+                    offset: pointer.file_offset(),
+                    reason: UnattributedReason::IncompleteChain,
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Collects the warc files of all workers, in the same form as the workers
+/// create the paths.
+pub(super) fn collect_warc_files(config: &Config) -> Result<Vec<Utf8PathBuf>, std::io::Error> {
+    let collection_root = config
+        .paths
+        .root_path()
+        .join(&config.session.collection);
+    let mut files = Vec::new();
+    if !collection_root.is_dir() {
+        log::warn!("There is no collection at {collection_root}!");
+        return Ok(files);
+    }
+    for worker in collection_root.read_dir_utf8()? {
+        let worker = worker?;
+        if !worker.file_type()?.is_dir() || !worker.file_name().starts_with("worker_") {
+            continue;
+        }
+        let worker_root = collection_root.join(worker.file_name());
+        for file in worker_root.read_dir_utf8()? {
+            let file = file?;
+            if file.file_type()?.is_file() && file.file_name().ends_with(".warc") {
+                files.push(worker_root.join(file.file_name()));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn add_to_chain(chain: &mut PendingChain, path: &Utf8Path, segment: u64, record: ScannedWarcRecord) {
+    let offset = record.pointer.file_offset();
+    chain.body_octets += record.pointer.body_octet_count();
+    if let Some(Ok(total_length)) = record.header.get_segment_total_length() {
+        chain.total_length = Some(*total_length);
+    }
+    chain.segments.insert(
+        segment,
+        WarcSkipPointerWithPath::new(path.to_path_buf(), record.pointer),
+    );
+    if let Some(header_signature) = record.header_signature {
+        chain.start = Some(ChainStart {
+            path: path.to_path_buf(),
+            offset,
+            header: record.header,
+            header_signature,
+        });
+    }
+}
+
+fn restore_chain(chain: PendingChain) -> Result<SlimCrawlResult, UnattributedReason> {
+    let start = chain.start.unwrap();
+    let is_base64 = is_base64(&start.header);
+    let hint = StoredDataHint::Warc(WarcSkipInstruction::new_multi(
+        chain.segments.into_values().collect(),
+        start.header_signature.len() as u32,
+        is_base64,
+    ));
+    restore_meta(&start.header, &start.header_signature, is_base64, hint)
+}
+
+fn restore_single(
+    path: &Utf8Path,
+    record: ScannedWarcRecord,
+) -> Result<SlimCrawlResult, UnattributedReason> {
+    let header_signature = match (record.header.get_warc_type(), record.header_signature) {
+        (Ok(WarcRecordType::Response), Some(header_signature)) => header_signature,
+        _ => return Err(UnattributedReason::NotAnAtraRecord),
+    };
+    let is_base64 = is_base64(&record.header);
+
+    let hint = if let Some(external) = record.header.get_external_bin_file() {
+        match external {
+            Ok(value) => StoredDataHint::External(Utf8PathBuf::from(
+                String::from_utf8_lossy(value.as_ref()).into_owned(),
+            )),
+            Err(_) => return Err(UnattributedReason::NotAnAtraRecord),
+        }
+    } else {
+        let header_signature_octet_count = header_signature.len() as u32;
+        let kind = if record.pointer.body_octet_count() == header_signature_octet_count as u64 {
+            WarcSkipInstructionKind::NoData
+        } else if is_base64 {
+            WarcSkipInstructionKind::Base64
+        } else {
+            WarcSkipInstructionKind::Normal
+        };
+        StoredDataHint::Warc(WarcSkipInstruction::new_single(
+            WarcSkipPointerWithPath::new(path.to_path_buf(), record.pointer),
+            header_signature_octet_count,
+            kind,
+        ))
+    };
+
+    restore_meta(&record.header, &header_signature, is_base64, hint)
+}
+
+fn is_base64(header: &WarcHeader) -> bool {
+    header
+        .get_atra_is_base64()
+        .and_then(Result::ok)
+        .copied()
+        .unwrap_or(false)
+}
+
+fn restore_meta(
+    header: &WarcHeader,
+    header_signature: &[u8],
+    is_base64: bool,
+    stored_data_hint: StoredDataHint,
+) -> Result<SlimCrawlResult, UnattributedReason> {
+    let url = match header.get_target_uri() {
+        Some(Ok(value)) => UrlWithDepth::from_url(&*String::from_utf8_lossy(value.as_ref()))
+            .map_err(|_| UnattributedReason::NoTargetUri)?,
+        _ => return Err(UnattributedReason::NoTargetUri),
+    };
+    let created_at = *header
+        .get_date()
+        .map_err(|_| UnattributedReason::NoDate)?;
+    let (status_code, headers) =
+        unpack_header(header_signature).ok_or(UnattributedReason::BadHttpHeader)?;
+    let recognized_encoding: Option<&'static Encoding> = header
+        .get_atra_content_encoding()
+        .and_then(Result::ok)
+        .and_then(|value| Encoding::for_label(value.name().as_bytes()));
+    let file_information = AtraFileInformation::restore(Some(&headers), is_base64);
+
+    Ok(SlimCrawlResult {
+        meta: CrawlResultMeta::new(
+            created_at,
+            url,
+            status_code,
+            file_information,
+            recognized_encoding,
+            (!headers.is_empty()).then_some(headers),
+            None,
+            None,
+            None,
+        ),
+        stored_data_hint,
+    })
+}
+
+/// Stores [slim] iff there is no newer entry for the same url.
+fn store(
+    crawl_db: &CrawlDB,
+    link_states: &LinkStateRockDB,
+    slim: SlimCrawlResult,
+    report: &mut RebuildReport,
+) -> Result<(), RebuildError> {
+    if let Some(existing) = crawl_db.get(&slim.meta.url)? {
+        report.superseded += 1;
+        if existing.meta.created_at >= slim.meta.created_at {
+            return Ok(());
+        }
+    } else {
+        report.rebuilt += 1;
+    }
+    crawl_db.add(&slim)?;
+    let expiry = slim
+        .meta
+        .unavailable_after
+        .as_ref()
+        .and_then(UnavailableAfter::to_payload);
+    link_states.update_state(
+        &slim.meta.url,
+        LinkStateKind::ProcessedAndStored,
+        None,
+        None,
+        Some(expiry),
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::rebuild::rebuild_db;
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::{create_test_data, create_test_data_unknown};
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::data::RawVecData;
+    use crate::database::open_db;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::io::fs::{AtraFS, FileSystemAccess};
+    use crate::link_state::{LinkStateDB, LinkStateKind, LinkStateLike, LinkStateRockDB};
+    use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
+    use crate::url::UrlWithDepth;
+    use crate::warc_ext::write_warc;
+    use camino_tempfile::Utf8TempDir;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn can_rebuild_a_deleted_db() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+
+        let fs = FileSystemAccess::new(
+            config.session.service.clone(),
+            config.session.collection.clone(),
+            config.session.crawl_job_id,
+            config.paths.root_path().to_path_buf(),
+            config.paths.dir_big_files(),
+            config.session.dat_file_layout,
+        )
+        .unwrap();
+        let writer =
+            ThreadsafeMultiFileWarcWriter::new_for_worker(Arc::new(fs.create_worker_file_provider(0, 0).unwrap()))
+                .unwrap();
+
+        let mut base64 = create_test_data_unknown(
+            UrlWithDepth::from_url("https://www.example.com/binary").unwrap(),
+            RawVecData::from_vec(vec![0u8, 1, 2, 3, 255, 254, 253]),
+        );
+        base64.meta.file_information.format = InterpretedProcessibleFileFormat::Unknown;
+
+        let results = vec![
+            create_test_data(UrlWithDepth::from_url("https://www.example.com/").unwrap(), None),
+            base64,
+            create_test_data(
+                UrlWithDepth::from_url("https://www.example.com/empty").unwrap(),
+                Some(RawVecData::from_vec(Vec::new())),
+            ),
+        ];
+
+        let mut expected = Vec::new();
+        {
+            let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+            let crawl_db = CrawlDB::new(db, &config).unwrap();
+            for result in &results {
+                let instruction = writer
+                    .execute_on_writer(|value| write_warc(value, result))
+                    .await
+                    .unwrap();
+                crawl_db
+                    .add(&SlimCrawlResult::new(result, StoredDataHint::Warc(instruction)))
+                    .unwrap();
+            }
+            writer.flush().await.unwrap();
+            for result in &results {
+                let slim = crawl_db.get(&result.meta.url).unwrap().unwrap();
+                let body = unsafe { slim.get_content() }.unwrap().left().unwrap();
+                expected.push((slim, body));
+            }
+        }
+
+        std::fs::remove_dir_all(config.paths.dir_database()).unwrap();
+
+        let report = rebuild_db(&config).unwrap();
+        assert_eq!(3, report.rebuilt);
+        assert!(report.unattributed.is_empty());
+
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db.clone(), &config).unwrap();
+        let link_states = LinkStateRockDB::new(db);
+        for (original, body) in expected {
+            let rebuilt = crawl_db.get(&original.meta.url).unwrap().unwrap();
+            assert_eq!(original.stored_data_hint, rebuilt.stored_data_hint);
+            assert_eq!(original.meta.status_code, rebuilt.meta.status_code);
+            assert_eq!(original.meta.headers, rebuilt.meta.headers);
+            let rebuilt_body = unsafe { rebuilt.get_content() }.unwrap().left().unwrap();
+            assert_eq!(body, rebuilt_body);
+            let state = link_states.get_state(&original.meta.url).unwrap().unwrap();
+            assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        }
+    }
+}
@@ -0,0 +1,379 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::checkpoint::Checkpoint;
+use crate::config::RecoveryConfig;
+use crate::link_state::{
+    DatabaseLinkStateManager, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStateManager,
+    LinkStateRockDB, RawLinkState,
+};
+use crate::queue::{
+    DequeueJournal, QueueError, RawAgingQueueFile, SupportsForcedQueueElement, UrlQueue,
+    UrlQueueElement, UrlQueueWrapper,
+};
+use crate::url::{AtraUri, UrlWithDepth};
+use rocksdb::IteratorMode;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::io;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// Errors that stop the requeue of the stranded urls.
+#[derive(Debug, Error)]
+pub enum RecoverError {
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    LinkState(#[from] LinkStateDBError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+    #[error("Failed to clear the dequeue journal: {0}")]
+    Journal(#[from] io::Error),
+    #[error("Failed to remove the checkpoint: {0}")]
+    Checkpoint(io::Error),
+}
+
+/// The number of stranded urls found by [requeue_stranded].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RecoverReport {
+    /// The urls of the journal that were requeued.
+    pub from_journal: usize,
+    /// The urls of the journal that were finished or are already queued.
+    pub skipped_journal: usize,
+    /// The urls of the checkpoint that were requeued, they are missing in the journal.
+    pub from_checkpoint: usize,
+    /// The stranded urls found in the link states that were requeued.
+    pub from_link_states: usize,
+    /// The stranded urls found in the link states beyond the limit, they are not requeued.
+    pub over_limit: usize,
+}
+
+impl RecoverReport {
+    /// The number of requeued urls.
+    pub fn requeued(&self) -> usize {
+        self.from_journal + self.from_checkpoint + self.from_link_states
+    }
+}
+
+impl Display for RecoverReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Requeued {} urls of the dequeue journal ({} were finished or queued), {} urls of the checkpoint and {} stranded urls of the link states",
+            self.from_journal, self.skipped_journal, self.from_checkpoint, self.from_link_states
+        )?;
+        if self.over_limit > 0 {
+            write!(f, ", {} stranded urls exceeded the limit", self.over_limit)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if [url] was stored or failed.
+fn is_finished(
+    link_states: &DatabaseLinkStateManager<LinkStateRockDB>,
+    url: &UrlWithDepth,
+) -> Result<bool, RecoverError> {
+    Ok(link_states.get_link_state_sync(url)?.is_some_and(|state| {
+        state.kind().is_processed_and_stored() || state.kind().is_internal_error()
+    }))
+}
+
+/// Returns true if [kind] is only set while an url is in progress.
+fn is_transient(kind: LinkStateKind) -> bool {
+    kind.is_reserved_for_crawl() || kind.is_crawled()
+}
+
+/// Collects the urls with a transient link state older than [stranded_before] that are not
+/// in [queued]. At most [limit] urls are collected, the rest is counted in [report].
+fn scan_link_states(
+    link_states: &DatabaseLinkStateManager<LinkStateRockDB>,
+    queued: &mut HashSet<String>,
+    stranded_before: OffsetDateTime,
+    limit: usize,
+    report: &mut RecoverReport,
+) -> Result<Vec<UrlQueueElement<UrlWithDepth>>, RecoverError> {
+    let mut found = Vec::new();
+    for value in link_states.iter(IteratorMode::Start) {
+        let (k, v) = value?;
+        let key = String::from_utf8_lossy(k.as_ref());
+        let state = match RawLinkState::from_slice(v.as_ref()) {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("Failed to read the link state of {key} with: {err}");
+                continue;
+            }
+        };
+        if !is_transient(state.kind()) || state.timestamp() > stranded_before {
+            continue;
+        }
+        if queued.contains(key.as_ref()) {
+            continue;
+        }
+        if found.len() >= limit {
+            report.over_limit += 1;
+            continue;
+        }
+        let uri: AtraUri = match key.parse() {
+            Ok(uri) => uri,
+            Err(err) => {
+                log::warn!("Failed to parse {key} with: {err}");
+                continue;
+            }
+        };
+        queued.insert(key.into_owned());
+        found.push(UrlQueueElement::new(
+            state.is_seed().is_yes(),
+            0,
+            false,
+            UrlWithDepth::new(uri, state.depth()),
+        ));
+    }
+    report.from_link_states = found.len();
+    Ok(found)
+}
+
+/// Requeues the urls that were in progress when the crawl ended unexpectedly and resets their
+/// link states to discovered.
+///
+/// The open entries of the [journal] are exact, they are requeued unless they were finished
+/// or are queued again. The urls in progress of the [checkpoint] that are missing in the journal
+/// are put back into the queue the same way. Afterwards the link states that are still reserved
+/// or crawled after [RecoveryConfig::stranded_after] are requeued, at most
+/// [RecoveryConfig::max_requeued]. The journal is cleared when everything is requeued.
+pub(crate) async fn requeue_stranded(
+    link_states: &DatabaseLinkStateManager<LinkStateRockDB>,
+    queue: &UrlQueueWrapper<RawAgingQueueFile>,
+    journal: &DequeueJournal,
+    checkpoint: Option<&Checkpoint>,
+    config: &RecoveryConfig,
+    now: OffsetDateTime,
+) -> Result<RecoverReport, RecoverError> {
+    let mut queued = HashSet::new();
+    queue.for_each_queued(|element| {
+        queued.insert(element.target.try_as_str().into_owned());
+    })?;
+
+    let mut report = RecoverReport::default();
+    let mut stranded = Vec::new();
+    for entry in journal.open_entries() {
+        let key = entry.url.try_as_str().into_owned();
+        if is_finished(link_states, &entry.url)? || !queued.insert(key) {
+            report.skipped_journal += 1;
+            continue;
+        }
+        stranded.push(UrlQueueElement::new(entry.is_seed, 0, false, entry.url));
+    }
+    report.from_journal = stranded.len();
+
+    let mut returned = Vec::new();
+    for element in checkpoint
+        .iter()
+        .flat_map(|checkpoint| &checkpoint.in_flight)
+    {
+        let key = element.target.try_as_str().into_owned();
+        if is_finished(link_states, &element.target)? || !queued.insert(key) {
+            continue;
+        }
+        returned.push(element.clone());
+    }
+    report.from_checkpoint = returned.len();
+
+    stranded.extend(scan_link_states(
+        link_states,
+        &mut queued,
+        now - config.stranded_after,
+        config.max_requeued,
+        &mut report,
+    )?);
+
+    for element in stranded.iter().chain(&returned) {
+        link_states
+            .update_link_state_no_meta_and_payload(&element.target, LinkStateKind::Discovered)
+            .await?;
+    }
+    if !stranded.is_empty() {
+        queue.enqueue_all(stranded).await?;
+    }
+    for element in returned {
+        queue.force_enqueue(element)?;
+    }
+    journal.clear()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::recover::{requeue_stranded, RecoverReport};
+    use crate::config::RecoveryConfig;
+    use crate::database::open_db;
+    use crate::link_state::{
+        DatabaseLinkStateManager, IsSeedYesNo, LinkState, LinkStateDB, LinkStateKind,
+        LinkStateLike, LinkStateManager, LinkStateRockDB, RecrawlYesNo,
+    };
+    use crate::queue::{DequeueJournal, JournalEntry, UrlQueue, UrlQueueElement, UrlQueueWrapper};
+    use crate::url::{Depth, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use time::{Duration, OffsetDateTime};
+
+    /// Dequeued and reserved, the crash happened while fetching.
+    const IN_FLIGHT: &str = "https://www.example.com/in-flight";
+    /// Dequeued, the crash happened before the link state was reserved.
+    const NOT_RESERVED: &str = "https://www.example.com/not-reserved";
+    /// Dequeued and stored, the crash happened before the completion was journaled.
+    const STORED: &str = "https://www.example.com/stored";
+    /// Reserved a long time ago, the journal was lost.
+    const OLD_RESERVED: &str = "https://www.other.de/old-reserved";
+    /// Crawled a long time ago, the journal was lost.
+    const OLD_CRAWLED: &str = "https://www.other.de/old-crawled";
+    /// Reserved recently without a journal entry.
+    const FRESH_RESERVED: &str = "https://www.other.de/fresh-reserved";
+    /// Reserved a long time ago but queued again.
+    const QUEUED: &str = "https://www.other.de/queued";
+
+    fn state(kind: LinkStateKind, timestamp: OffsetDateTime) -> LinkState {
+        LinkState::without_payload(
+            kind,
+            LinkStateKind::Discovered,
+            RecrawlYesNo::No,
+            IsSeedYesNo::No,
+            timestamp,
+            Depth::ZERO,
+        )
+    }
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    #[tokio::test]
+    async fn requeues_exactly_the_stranded_urls() {
+        let root = Utf8TempDir::new().unwrap();
+        let now = OffsetDateTime::now_utc();
+        let long_ago = now - Duration::hours(1);
+
+        let db = Arc::new(open_db(root.path().join("rocksdb")).unwrap());
+        let raw = LinkStateRockDB::new(db.clone());
+        for (value, kind, timestamp) in [
+            (IN_FLIGHT, LinkStateKind::ReservedForCrawl, now),
+            (NOT_RESERVED, LinkStateKind::Discovered, long_ago),
+            (STORED, LinkStateKind::ProcessedAndStored, now),
+            (OLD_RESERVED, LinkStateKind::ReservedForCrawl, long_ago),
+            (OLD_CRAWLED, LinkStateKind::Crawled, long_ago),
+            (FRESH_RESERVED, LinkStateKind::ReservedForCrawl, now),
+            (QUEUED, LinkStateKind::ReservedForCrawl, long_ago),
+        ] {
+            raw.set_state(&url(value), &state(kind, timestamp)).unwrap();
+        }
+        let link_states = DatabaseLinkStateManager::new(db);
+
+        let queue = UrlQueueWrapper::open(root.path().join("queue.tmp")).unwrap();
+        queue
+            .enqueue(UrlQueueElement::new(false, 0, false, url(QUEUED)))
+            .await
+            .unwrap();
+
+        let journal = DequeueJournal::open(root.path().join("queue.tmp.journal")).unwrap();
+        for value in [IN_FLIGHT, NOT_RESERVED, STORED] {
+            journal
+                .record_dequeued(JournalEntry::new(url(value), false, now))
+                .unwrap();
+        }
+
+        let config = RecoveryConfig {
+            stranded_after: Duration::minutes(5),
+            max_requeued: 10,
+            checkpoint_interval: None,
+        };
+        let report = requeue_stranded(&link_states, &queue, &journal, None, &config, now)
+            .await
+            .unwrap();
+        assert_eq!(
+            RecoverReport {
+                from_journal: 2,
+                skipped_journal: 1,
+                from_checkpoint: 0,
+                from_link_states: 2,
+                over_limit: 0,
+            },
+            report
+        );
+
+        let mut queued = HashSet::new();
+        queue
+            .for_each_queued(|element| {
+                assert!(queued.insert(element.target.try_as_str().into_owned()));
+            })
+            .unwrap();
+        let expected: HashSet<_> = [IN_FLIGHT, NOT_RESERVED, OLD_RESERVED, OLD_CRAWLED, QUEUED]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(expected, queued);
+
+        for value in [IN_FLIGHT, NOT_RESERVED, OLD_RESERVED, OLD_CRAWLED] {
+            let state = link_states
+                .get_link_state_sync(&url(value))
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+        }
+        let state = link_states
+            .get_link_state_sync(&url(FRESH_RESERVED))
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ReservedForCrawl, state.kind());
+        assert!(journal.open_entries().is_empty());
+
+        // Everything stranded is queued again, a second run finds nothing.
+        let report = requeue_stranded(&link_states, &queue, &journal, None, &config, now)
+            .await
+            .unwrap();
+        assert_eq!(0, report.requeued());
+    }
+
+    #[tokio::test]
+    async fn requeues_at_most_the_limit_from_the_link_states() {
+        let root = Utf8TempDir::new().unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        let db = Arc::new(open_db(root.path().join("rocksdb")).unwrap());
+        let raw = LinkStateRockDB::new(db.clone());
+        for i in 0..5 {
+            raw.set_state(
+                &url(&format!("https://www.example.com/{i}")),
+                &state(LinkStateKind::ReservedForCrawl, now - Duration::hours(1)),
+            )
+            .unwrap();
+        }
+        let link_states = DatabaseLinkStateManager::new(db);
+        let queue = UrlQueueWrapper::open(root.path().join("queue.tmp")).unwrap();
+        let journal = DequeueJournal::open(root.path().join("queue.tmp.journal")).unwrap();
+
+        let config = RecoveryConfig {
+            stranded_after: Duration::minutes(5),
+            max_requeued: 3,
+            checkpoint_interval: None,
+        };
+        let report = requeue_stranded(&link_states, &queue, &journal, None, &config, now)
+            .await
+            .unwrap();
+        assert_eq!(3, report.from_link_states);
+        assert_eq!(2, report.over_limit);
+        assert_eq!(3, queue.len().await);
+    }
+}
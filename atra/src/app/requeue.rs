@@ -0,0 +1,489 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::traits::FetchFailureKind;
+use crate::config::Config;
+use crate::database::{open_db, OpenDBError};
+use crate::link_state::{
+    LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStateRockDB, RawLinkState,
+};
+use crate::queue::priority::UrlPriorityScorer;
+use crate::queue::{
+    QueueError, QueueFileError, RawAgingQueueFile, UrlQueue, UrlQueueElement, UrlQueueWrapper,
+};
+use crate::schemas::{AuditAction, AuditLog, AuditOutcome, AuditSource};
+use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
+use regex::Regex;
+use rocksdb::IteratorMode;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// The number of urls written to the queue and the link state db at once.
+const BATCH_SIZE: usize = 1_000;
+
+/// Errors that stop a requeue.
+#[derive(Debug, Error)]
+pub enum RequeueError {
+    #[error(transparent)]
+    OpenDB(#[from] OpenDBError),
+    #[error(transparent)]
+    RocksDB(#[from] rocksdb::Error),
+    #[error(transparent)]
+    LinkState(#[from] LinkStateDBError),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+    #[error(transparent)]
+    QueueFile(#[from] QueueFileError),
+    #[error("Failed to open the audit trail: {0}")]
+    Audit(#[from] std::io::Error),
+}
+
+/// Parses an RFC 3339 timestamp like `2024-06-25T15:00:00Z`.
+pub fn parse_rfc3339(value: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    OffsetDateTime::parse(value, &Rfc3339)
+}
+
+/// Selects the failed urls to requeue.
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct RequeueFilter {
+    /// Only requeue the failures of this kind, can be set multiple times.
+    /// If not set, every failure is requeued.
+    #[arg(short, long, value_enum)]
+    pub kind: Vec<FetchFailureKind>,
+    /// Only requeue the urls with an origin matching this regex.
+    #[arg(short, long, value_parser = Regex::new)]
+    pub origin: Option<Regex>,
+    /// Only requeue the failures at or after this RFC 3339 timestamp.
+    #[arg(long, value_parser = parse_rfc3339)]
+    pub since: Option<OffsetDateTime>,
+    /// Only requeue the failures before this RFC 3339 timestamp.
+    #[arg(long, value_parser = parse_rfc3339)]
+    pub until: Option<OffsetDateTime>,
+}
+
+impl RequeueFilter {
+    /// Returns true iff the failed [state] of an url with [origin] is selected.
+    /// A failure without a recorded kind is only selected if no kind is requested.
+    pub fn matches(&self, origin: &AtraUrlOrigin, state: &RawLinkState) -> bool {
+        if !self.kind.is_empty() {
+            let Some(kind) = state.payload().and_then(FetchFailureKind::from_payload) else {
+                return false;
+            };
+            if !self.kind.contains(&kind) {
+                return false;
+            }
+        }
+        if let Some(ref pattern) = self.origin {
+            if !pattern.is_match(origin.as_ref()) {
+                return false;
+            }
+        }
+        let failed_at = state.timestamp();
+        if self.since.is_some_and(|since| failed_at < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| failed_at >= until) {
+            return false;
+        }
+        true
+    }
+}
+
+impl Display for RequeueFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if !self.kind.is_empty() {
+            parts.push(format!(
+                "kind={}",
+                self.kind
+                    .iter()
+                    .map(|kind| kind.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ));
+        }
+        if let Some(ref pattern) = self.origin {
+            parts.push(format!("origin={pattern}"));
+        }
+        for (name, value) in [("since", self.since), ("until", self.until)] {
+            if let Some(value) = value {
+                let value = value.format(&Rfc3339).unwrap_or_else(|_| value.to_string());
+                parts.push(format!("{name}={value}"));
+            }
+        }
+        if parts.is_empty() {
+            f.write_str("all failures")
+        } else {
+            f.write_str(&parts.join(" "))
+        }
+    }
+}
+
+/// The number of selected urls of an origin.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RequeueCount {
+    /// The urls matching the filter.
+    pub matched: usize,
+    /// The matched urls that are already in the queue.
+    pub already_queued: usize,
+    /// The matched urls outside of the current budget.
+    pub out_of_budget: usize,
+}
+
+impl RequeueCount {
+    /// The number of urls that are (or would be) requeued.
+    pub fn requeued(&self) -> usize {
+        self.matched - self.already_queued - self.out_of_budget
+    }
+}
+
+/// The result of a requeue, grouped by origin.
+#[derive(Debug, Default)]
+pub struct RequeueReport {
+    pub per_origin: BTreeMap<AtraUrlOrigin, RequeueCount>,
+    /// True iff nothing was changed.
+    pub dry_run: bool,
+}
+
+impl RequeueReport {
+    /// The number of urls that are (or would be) requeued.
+    pub fn requeued(&self) -> usize {
+        self.per_origin.values().map(RequeueCount::requeued).sum()
+    }
+}
+
+impl Display for RequeueReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:30}    {:>8}    {:>8}    {:>8}    {:>8}",
+            "Origin", "Matched", "Queued", "Budget", "Requeued"
+        )?;
+        for (origin, count) in &self.per_origin {
+            writeln!(
+                f,
+                "{:30}    {:>8}    {:>8}    {:>8}    {:>8}",
+                origin.as_ref(),
+                count.matched,
+                count.already_queued,
+                count.out_of_budget,
+                count.requeued()
+            )?;
+        }
+        writeln!(f)?;
+        if self.dry_run {
+            writeln!(f, "Would requeue:    {}", self.requeued())
+        } else {
+            writeln!(f, "Requeued:         {}", self.requeued())
+        }
+    }
+}
+
+/// Resets the failed link states of [config] selected by [filter] to discovered and enqueues
+/// their urls. Urls outside of the current budget or already in the queue are skipped.
+/// A requeue that is no [dry_run] is recorded in the audit trail of the session.
+///
+/// The link state db is streamed, the writes are done in batches. If [dry_run] is set
+/// only the counts are reported.
+pub(crate) async fn requeue_failed(
+    config: &Config,
+    filter: &RequeueFilter,
+    dry_run: bool,
+) -> Result<RequeueReport, RequeueError> {
+    let db = Arc::new(open_db(config.paths.dir_database())?);
+    let link_states = LinkStateRockDB::new(db);
+    let queue = UrlQueueWrapper::open(config.paths.file_queue())?;
+    let scorer = UrlPriorityScorer::new(&config.crawl.url_priority);
+
+    let mut queued = HashSet::new();
+    queue.for_each_queued(|element| {
+        queued.insert(element.target.url.as_bytes().to_vec());
+    })?;
+
+    let mut report = RequeueReport {
+        per_origin: BTreeMap::new(),
+        dry_run,
+    };
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    for value in link_states.iter(IteratorMode::Start) {
+        let (k, v) = value?;
+        let state = match RawLinkState::from_slice(v.as_ref()) {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!(
+                    "Failed to read the link state of {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                continue;
+            }
+        };
+        if state.kind() != LinkStateKind::InternalError {
+            continue;
+        }
+        let uri: AtraUri = match String::from_utf8_lossy(k.as_ref()).parse() {
+            Ok(uri) => uri,
+            Err(err) => {
+                log::warn!(
+                    "Failed to parse {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                continue;
+            }
+        };
+        let url = UrlWithDepth::new(uri, state.depth());
+        let Some(origin) = url.atra_origin() else {
+            continue;
+        };
+        if !filter.matches(&origin, &state) {
+            continue;
+        }
+        let count = report.per_origin.entry(origin.clone()).or_default();
+        count.matched += 1;
+        if queued.contains(k.as_ref()) {
+            count.already_queued += 1;
+            continue;
+        }
+        if !config.crawl.budget_for(&origin).is_in_budget(&url) {
+            count.out_of_budget += 1;
+            continue;
+        }
+        if dry_run {
+            continue;
+        }
+        let is_seed = state.is_seed().is_yes();
+        let priority = scorer.score(&url, Some(&origin), false);
+        batch.push(UrlQueueElement::new(is_seed, 0, false, url).with_priority(priority));
+        if batch.len() >= BATCH_SIZE {
+            write_batch(&link_states, &queue, std::mem::take(&mut batch)).await?;
+        }
+    }
+    if !batch.is_empty() {
+        write_batch(&link_states, &queue, batch).await?;
+    }
+    if !dry_run {
+        AuditLog::open(config.paths.root_path())?.append(
+            AuditSource::Cli {
+                command: "requeue".to_string(),
+            },
+            AuditAction::Requeue {
+                filter: filter.to_string(),
+                requeued: report.requeued(),
+            },
+            AuditOutcome::Applied,
+        );
+    }
+    Ok(report)
+}
+
+/// Enqueues the [batch] and resets the link states of its urls to discovered.
+async fn write_batch(
+    link_states: &LinkStateRockDB,
+    queue: &UrlQueueWrapper<RawAgingQueueFile>,
+    batch: Vec<UrlQueueElement<UrlWithDepth>>,
+) -> Result<(), RequeueError> {
+    log::info!("Requeue {} urls", batch.len());
+    for element in &batch {
+        link_states.update_state_no_payload(
+            &element.target,
+            LinkStateKind::Discovered,
+            None,
+            None,
+        )?;
+    }
+    queue.enqueue_all(batch).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::requeue::{requeue_failed, RequeueCount, RequeueFilter};
+    use crate::client::traits::FetchFailureKind;
+    use crate::config::Config;
+    use crate::database::open_db;
+    use crate::link_state::{
+        IsSeedYesNo, LinkState, LinkStateDB, LinkStateKind, LinkStateLike, LinkStateRockDB,
+        RecrawlYesNo,
+    };
+    use crate::queue::{UrlQueue, UrlQueueElement, UrlQueueWrapper};
+    use crate::schemas::{read_audit_trail, AuditAction};
+    use crate::url::{AtraUrlOrigin, Depth, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use std::collections::HashSet;
+    use std::sync::Arc;
+    use time::OffsetDateTime;
+
+    const TIMED_OUT: [&str; 3] = [
+        "https://www.example.com/a",
+        "https://www.example.com/b",
+        "https://www.other.de/c",
+    ];
+    const REFUSED: [&str; 2] = ["https://www.example.com/d", "https://www.other.de/e"];
+    const ALREADY_QUEUED: &str = "https://www.example.com/queued";
+    const CRAWLED: &str = "https://www.example.com/crawled";
+
+    fn failed(kind: FetchFailureKind) -> LinkState {
+        LinkState::with_payload(
+            LinkStateKind::InternalError,
+            LinkStateKind::ReservedForCrawl,
+            RecrawlYesNo::No,
+            IsSeedYesNo::No,
+            OffsetDateTime::now_utc() - time::Duration::minutes(1),
+            Depth::ZERO + (1, 1, 1),
+            kind.to_payload().to_vec(),
+        )
+    }
+
+    async fn create_fixture(config: &Config) {
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let link_states = LinkStateRockDB::new(db);
+        for url in TIMED_OUT.iter().chain([&ALREADY_QUEUED]) {
+            let url = UrlWithDepth::from_url(url).unwrap();
+            link_states
+                .set_state(&url, &failed(FetchFailureKind::Timeout))
+                .unwrap();
+        }
+        for url in REFUSED {
+            let url = UrlWithDepth::from_url(url).unwrap();
+            link_states
+                .set_state(&url, &failed(FetchFailureKind::Connect))
+                .unwrap();
+        }
+        link_states
+            .set_state(
+                &UrlWithDepth::from_url(CRAWLED).unwrap(),
+                &LinkState::without_payload(
+                    LinkStateKind::ProcessedAndStored,
+                    LinkStateKind::ProcessedAndStored,
+                    RecrawlYesNo::No,
+                    IsSeedYesNo::No,
+                    OffsetDateTime::now_utc(),
+                    Depth::ZERO,
+                ),
+            )
+            .unwrap();
+
+        let queue = UrlQueueWrapper::open(config.paths.file_queue()).unwrap();
+        queue
+            .enqueue(UrlQueueElement::new(
+                false,
+                0,
+                false,
+                UrlWithDepth::from_url(ALREADY_QUEUED).unwrap(),
+            ))
+            .await
+            .unwrap();
+    }
+
+    fn queued_urls(config: &Config) -> Vec<String> {
+        let queue = UrlQueueWrapper::open(config.paths.file_queue()).unwrap();
+        let mut found = Vec::new();
+        queue
+            .for_each_queued(|element| found.push(element.target.try_as_str().to_string()))
+            .unwrap();
+        found
+    }
+
+    #[tokio::test]
+    async fn requeues_only_the_selected_failures() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        create_fixture(&config).await;
+
+        let filter = RequeueFilter {
+            kind: vec![FetchFailureKind::Timeout],
+            ..RequeueFilter::default()
+        };
+
+        let report = requeue_failed(&config, &filter, true).await.unwrap();
+        assert_eq!(3, report.requeued());
+        assert_eq!(
+            Some(&RequeueCount {
+                matched: 3,
+                already_queued: 1,
+                out_of_budget: 0,
+            }),
+            report.per_origin.get(&AtraUrlOrigin::from("example.com"))
+        );
+        assert_eq!(vec![ALREADY_QUEUED.to_string()], queued_urls(&config));
+
+        let report = requeue_failed(&config, &filter, false).await.unwrap();
+        assert_eq!(3, report.requeued());
+
+        let queued = queued_urls(&config);
+        assert_eq!(4, queued.len());
+        let queued: HashSet<_> = queued.into_iter().collect();
+        let expected: HashSet<_> = TIMED_OUT
+            .iter()
+            .chain([&ALREADY_QUEUED])
+            .map(|value| value.to_string())
+            .collect();
+        assert_eq!(expected, queued);
+
+        // The requeued urls are discovered again, a second run does not find them.
+        let report = requeue_failed(&config, &filter, false).await.unwrap();
+        assert_eq!(0, report.requeued());
+        assert_eq!(4, queued_urls(&config).len());
+
+        // The dry run changed nothing and is not recorded.
+        assert_eq!(
+            vec![
+                AuditAction::Requeue {
+                    filter: "kind=Timeout".to_string(),
+                    requeued: 3,
+                },
+                AuditAction::Requeue {
+                    filter: "kind=Timeout".to_string(),
+                    requeued: 0,
+                },
+            ],
+            read_audit_trail(config.paths.root_path())
+                .unwrap()
+                .into_iter()
+                .map(|record| record.action)
+                .collect::<Vec<_>>()
+        );
+
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let link_states = LinkStateRockDB::new(db);
+        for url in TIMED_OUT {
+            let state = link_states
+                .get_state(&UrlWithDepth::from_url(url).unwrap())
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+            assert_eq!(None, state.payload());
+        }
+        for url in REFUSED {
+            let state = link_states
+                .get_state(&UrlWithDepth::from_url(url).unwrap())
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::InternalError, state.kind());
+            assert_eq!(
+                Some(FetchFailureKind::Connect),
+                state.payload().and_then(FetchFailureKind::from_payload)
+            );
+        }
+        let state = link_states
+            .get_state(&UrlWithDepth::from_url(ALREADY_QUEUED).unwrap())
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::InternalError, state.kind());
+    }
+}
@@ -0,0 +1,90 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::logging::configure_logging;
+use crate::contexts::local::{LocalContext, LocalContextInitError};
+use crate::cooperative::{CooperativeError, SecondaryConnection};
+use std::sync::Arc;
+use thiserror::Error;
+use tokio::net::UnixStream;
+use tokio::task::JoinSet;
+
+/// Errors that stop a secondary.
+#[derive(Debug, Error)]
+pub enum SecondaryError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Cooperative(#[from] CooperativeError),
+    #[error(transparent)]
+    Context(#[from] LocalContextInitError),
+}
+
+/// Opens [connections] to the primary listening on the unix [socket] and fetches the leased
+/// urls until the primary shuts down. Returns the number of fetched urls.
+///
+/// The secondary keeps its robots cache and temporary files in `secondaries/worker_<id>`
+/// of the session of the primary, everything else is stored by the primary.
+pub fn run_secondary(
+    socket: &str,
+    connections: usize,
+    log_to_file: bool,
+) -> Result<usize, SecondaryError> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async move {
+        let mut established = Vec::with_capacity(connections);
+        for _ in 0..connections.max(1) {
+            let stream = UnixStream::connect(socket).await?;
+            established.push(SecondaryConnection::connect(stream).await?);
+        }
+
+        let mut config = established[0].config().clone();
+        config.paths.root = config
+            .paths
+            .root_path()
+            .join("secondaries")
+            .join(format!("worker_{}", established[0].worker_id()));
+        config.system.cooperative = None;
+        config.system.log_to_file = log_to_file;
+        configure_logging(&config);
+        let context = Arc::new(LocalContext::new_without_runtime(config)?);
+
+        let mut set = JoinSet::new();
+        for connection in established {
+            let context = context.clone();
+            set.spawn(async move {
+                let worker_id = connection.worker_id();
+                (worker_id, connection.work(context.as_ref()).await)
+            });
+        }
+        let mut processed = 0;
+        while let Some(result) = set.join_next().await {
+            match result {
+                Ok((worker_id, Ok(value))) => {
+                    log::info!("Secondary {worker_id} fetched {value} urls.");
+                    processed += value;
+                }
+                Ok((worker_id, Err(err))) => {
+                    log::error!("Secondary {worker_id} failed: {err}");
+                }
+                Err(err) => {
+                    log::error!("Thread join error: {err}");
+                }
+            }
+        }
+        Ok(processed)
+    })
+}
@@ -12,59 +12,399 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::config::BudgetSetting;
-use crate::seed::UnguardedSeed;
-use crate::url::{AtraUrlOrigin, UrlWithDepth};
+use crate::contexts::local::LocalContext;
+use crate::contexts::traits::{
+    SupportsCrawlEvents, SupportsMetaInfo, SupportsUrlGuarding, SupportsUrlQueue,
+};
+use crate::crawl::{CrawlEvent, STATUS_CODE_CLASSES};
+use crate::extraction::extractor::ExtractorFailure;
+use crate::queue::UrlQueue;
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::url::guard::UrlGuardian;
+use crate::url::AtraUrlOrigin;
 use console::Term;
-use std::collections::VecDeque;
-use std::sync::RwLock;
-use time::Duration;
-use tokio::sync::watch::{Receiver, Sender};
-
-pub struct Terminal {
-    terminal: Term,
-    watched: RwLock<Vec<(Sender<CrawlTaskWatchState>, Receiver<CrawlTaskWatchState>)>>,
+use indicatif::{HumanBytes, HumanDuration};
+use itertools::Itertools;
+use std::collections::{BTreeMap, VecDeque};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+
+/// How often the crawl is sampled and the screen is redrawn.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+/// The number of sampled queue lengths kept for the sparkline.
+const QUEUE_HISTORY: usize = 240;
+/// The number of recent errors kept.
+const RECENT_ERRORS: usize = 20;
+/// The bars of the sparkline from the lowest to the highest value.
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Returns true if stdout is a terminal attended by a user. Otherwise the status screen
+/// would garble the output and atra falls back to plain logging.
+pub fn is_status_screen_available() -> bool {
+    console::user_attended()
 }
 
-pub struct CrawlTaskWatchState {
-    used: bool,
-    worker_id: Option<usize>,
-    meta_information: Option<MetaInformation>,
-    current: Option<CrawlTarget>,
-    buffer: VecDeque<CrawlTarget>,
+/// What a worker does, as far as the [CrawlEvent]s tell.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum WorkerStatus {
+    /// The worker fetches the url since the timestamp.
+    Fetching { url: String, since: OffsetDateTime },
+    /// The worker found no more urls in the queue.
+    Idle,
 }
 
-pub struct CrawlTarget {
-    target: UrlWithDepth,
+/// The state of the crawl at one point in time.
+#[derive(Debug, Default, Clone)]
+struct StatusSample {
+    queue_length: usize,
+    discovered: usize,
+    crawled_by_status_class: [u64; 6],
+    failed: u64,
+    skipped: u64,
+    /// The bytes written to the warc and the external data files.
+    bytes_written: u64,
+    /// The crawling and the waiting workers.
+    workers: Option<(usize, usize)>,
+    reserved_origins: Vec<AtraUrlOrigin>,
 }
 
-pub struct MetaInformation {
-    client: &'static str,
-    seed: UnguardedSeed,
-    origin: AtraUrlOrigin,
-    has_robots: bool,
-    interval: Duration,
-    budget_setting: BudgetSetting,
+impl StatusSample {
+    /// Samples the crawl with the same methods as the metrics endpoint.
+    async fn capture<C>(context: &C) -> Self
+    where
+        C: SupportsUrlQueue + SupportsUrlGuarding + SupportsMetaInfo + SupportsCrawlEvents,
+    {
+        let metrics = context.crawl_events().metrics();
+        let [dat_file_bytes, _] = metrics.dat_file_bytes();
+        Self {
+            queue_length: context.url_queue().len().await,
+            discovered: context.discovered_websites(),
+            crawled_by_status_class: metrics.crawled_by_status_class(),
+            failed: metrics.failed_fetches(),
+            skipped: metrics.skipped_downloads(),
+            bytes_written: metrics.warc_bytes() + dat_file_bytes,
+            workers: metrics.worker_states(),
+            reserved_origins: context.get_guardian().currently_reserved_origins().await,
+        }
+    }
 }
 
-impl CrawlTaskWatchState {
-    fn set_next(&mut self) {}
+/// Everything shown by the status screen, updated by the events and the samples.
+#[derive(Debug)]
+struct StatusScreen {
+    started_at: OffsetDateTime,
+    latest: StatusSample,
+    queue_history: VecDeque<usize>,
+    workers: BTreeMap<usize, WorkerStatus>,
+    recent_errors: VecDeque<String>,
+}
+
+impl StatusScreen {
+    fn new(started_at: OffsetDateTime) -> Self {
+        Self {
+            started_at,
+            latest: StatusSample::default(),
+            queue_history: VecDeque::with_capacity(QUEUE_HISTORY),
+            workers: BTreeMap::new(),
+            recent_errors: VecDeque::with_capacity(RECENT_ERRORS),
+        }
+    }
+
+    /// Updates the workers and the recent errors with the [event] received at [now].
+    fn observe(&mut self, event: CrawlEvent, now: OffsetDateTime) {
+        let error = match event {
+            CrawlEvent::Fetching { worker_id, url } => {
+                self.workers.insert(
+                    worker_id,
+                    WorkerStatus::Fetching {
+                        url: url.try_as_str().into_owned(),
+                        since: now,
+                    },
+                );
+                return;
+            }
+            CrawlEvent::QueueEmpty { worker_id } => {
+                self.workers.insert(worker_id, WorkerStatus::Idle);
+                return;
+            }
+            CrawlEvent::Failed { url, kind, reason } => {
+                format!("{} ({kind}): {reason}", url.try_as_str())
+            }
+            CrawlEvent::ExtractorFailed {
+                url,
+                method,
+                failure,
+            } => {
+                let url = url.try_as_str();
+                match failure {
+                    ExtractorFailure::Error(reason) => format!("{url} ({method}): {reason}"),
+                    ExtractorFailure::Panicked(reason) => {
+                        format!("{url} ({method}): panicked with {reason}")
+                    }
+                    ExtractorFailure::TimedOut => format!("{url} ({method}): timed out"),
+                }
+            }
+            CrawlEvent::OriginUnreachable {
+                origin, attempts, ..
+            } => format!("{origin} is unreachable, gave up after {attempts} attempts"),
+            CrawlEvent::Queued { .. }
+            | CrawlEvent::Crawled { .. }
+            | CrawlEvent::Skipped { .. }
+            | CrawlEvent::RobotsChanged { .. }
+            | CrawlEvent::Finished => return,
+        };
+        if self.recent_errors.len() == RECENT_ERRORS {
+            self.recent_errors.pop_back();
+        }
+        self.recent_errors.push_front(format!(
+            "{:02}:{:02}:{:02} {error}",
+            now.hour(),
+            now.minute(),
+            now.second()
+        ));
+    }
+
+    /// Keeps the [sample] as the latest one and remembers its queue length.
+    fn push_sample(&mut self, sample: StatusSample) {
+        if self.queue_history.len() == QUEUE_HISTORY {
+            self.queue_history.pop_front();
+        }
+        self.queue_history.push_back(sample.queue_length);
+        self.latest = sample;
+    }
 
-    pub fn reserve_for_worker(&mut self, worker_id: Option<usize>) {
-        self.reset();
-        self.used = true;
-        self.worker_id = worker_id;
+    /// Renders the screen as lines of at most [width] chars, drops the lines after [height].
+    fn render(&self, width: usize, height: usize, now: OffsetDateTime) -> Vec<String> {
+        let latest = &self.latest;
+        let running: Duration = (now - self.started_at).try_into().unwrap_or_default();
+        let mut lines = vec![
+            format!("Atra - running for {}", HumanDuration(running)),
+            format!(
+                "Queue: {}  Discovered: {}  Failed: {}  Skipped: {}  Written: {}",
+                latest.queue_length,
+                latest.discovered,
+                latest.failed,
+                latest.skipped,
+                HumanBytes(latest.bytes_written)
+            ),
+            format!(
+                "Crawled: {} ({})",
+                latest.crawled_by_status_class.iter().sum::<u64>(),
+                STATUS_CODE_CLASSES
+                    .iter()
+                    .zip(latest.crawled_by_status_class)
+                    .filter(|(_, value)| *value > 0)
+                    .map(|(class, value)| format!("{class}: {value}"))
+                    .join(", ")
+            ),
+            format!(
+                "Queue length: {} (max {})",
+                sparkline(&self.queue_history, width.saturating_sub(25)),
+                self.queue_history.iter().max().copied().unwrap_or_default()
+            ),
+            String::new(),
+            match latest.workers {
+                Some((crawling, waiting)) => {
+                    format!("Workers: {crawling} crawling, {waiting} waiting")
+                }
+                None => "Workers:".to_string(),
+            },
+        ];
+        lines.extend(self.workers.iter().map(|(id, status)| match status {
+            WorkerStatus::Fetching { url, since } => {
+                let fetching: Duration = (now - *since).try_into().unwrap_or_default();
+                format!(
+                    "  #{id:<3} {:>12}  {url}",
+                    HumanDuration(fetching).to_string()
+                )
+            }
+            WorkerStatus::Idle => format!("  #{id:<3} {:>12}", "idle"),
+        }));
+        lines.push(String::new());
+        lines.push(format!(
+            "Reserved origins ({}): {}",
+            latest.reserved_origins.len(),
+            latest.reserved_origins.iter().join(", ")
+        ));
+        lines.push(String::new());
+        lines.push("Recent errors:".to_string());
+        lines.extend(self.recent_errors.iter().map(|error| format!("  {error}")));
+        lines.truncate(height);
+        lines
+            .into_iter()
+            .map(|line| console::truncate_str(&line, width, "…").into_owned())
+            .collect()
     }
 
-    pub fn set_meta_information(&mut self, meta_information: MetaInformation) {
-        self.meta_information = Some(meta_information);
+    /// Redraws the screen on [term].
+    fn draw(&self, term: &Term, now: OffsetDateTime) -> io::Result<()> {
+        let (rows, columns) = term.size();
+        // A line in the last row would scroll the screen.
+        let lines = self.render(columns as usize, rows.saturating_sub(1) as usize, now);
+        term.move_cursor_to(0, 0)?;
+        for line in lines {
+            term.clear_line()?;
+            term.write_line(&line)?;
+        }
+        term.clear_to_end_of_screen()?;
+        term.flush()
     }
+}
+
+/// Draws the last [width] of the [values] as bars scaled to the highest of them.
+fn sparkline(values: &VecDeque<usize>, width: usize) -> String {
+    let shown = values.iter().skip(values.len().saturating_sub(width));
+    let max = shown.clone().max().copied().unwrap_or_default();
+    shown
+        .map(|value| {
+            if max == 0 {
+                SPARKS[0]
+            } else {
+                SPARKS[value * (SPARKS.len() - 1) / max]
+            }
+        })
+        .collect()
+}
+
+/// Shows the status screen on the alternate screen of the terminal and leaves it when dropped,
+/// also if the task drawing it is cancelled.
+struct AlternateScreen(Term);
+
+impl AlternateScreen {
+    fn enter(term: Term) -> io::Result<Self> {
+        term.write_str("\x1b[?1049h")?;
+        term.hide_cursor()?;
+        term.flush()?;
+        Ok(Self(term))
+    }
+}
+
+impl Drop for AlternateScreen {
+    fn drop(&mut self) {
+        let _ = self.0.show_cursor();
+        let _ = self.0.write_str("\x1b[?1049l");
+        let _ = self.0.flush();
+    }
+}
+
+/// Shows the status of the crawl of [context] until the [shutdown], e.g. by CTRL-C.
+/// The terminal is restored afterward.
+pub fn spawn_status_screen(context: Arc<LocalContext>, shutdown: ShutdownChild) -> JoinHandle<()> {
+    let mut events = context.crawl_events().subscribe();
+    tokio::spawn(async move {
+        let term = Term::buffered_stdout();
+        let _screen = match AlternateScreen::enter(term.clone()) {
+            Ok(screen) => screen,
+            Err(err) => {
+                log::error!("Failed to show the status screen: {err}");
+                return;
+            }
+        };
+        let mut status = StatusScreen::new(context.crawl_started_at());
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        loop {
+            select! {
+                _ = shutdown.wait() => break,
+                event = events.recv() => match event {
+                    Ok(event) => status.observe(event, OffsetDateTime::now_utc()),
+                    Err(RecvError::Lagged(missed)) => {
+                        log::debug!("The status screen missed {missed} events.");
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = interval.tick() => {
+                    status.push_sample(StatusSample::capture(context.as_ref()).await);
+                    if let Err(err) = status.draw(&term, OffsetDateTime::now_utc()) {
+                        log::error!("Failed to draw the status screen: {err}");
+                        break;
+                    }
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{sparkline, StatusSample, StatusScreen, WorkerStatus};
+    use crate::client::traits::FetchFailureKind;
+    use crate::crawl::CrawlEvent;
+    use crate::url::{AtraUrlOrigin, UrlWithDepth};
+    use std::collections::VecDeque;
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn the_sparkline_is_scaled_to_the_shown_values() {
+        let values = VecDeque::from([100, 0, 7, 14]);
+        assert_eq!("▁▄█", sparkline(&values, 3));
+        assert_eq!("█▁▁▁", sparkline(&values, 10));
+        assert_eq!("▁▁", sparkline(&VecDeque::from([0, 0]), 10));
+        assert_eq!("", sparkline(&VecDeque::new(), 10));
+    }
+
+    #[test]
+    fn shows_the_workers_and_the_recent_errors() {
+        let start = OffsetDateTime::UNIX_EPOCH;
+        let mut screen = StatusScreen::new(start);
+        let url = UrlWithDepth::from_url("https://www.example.com/a").unwrap();
+        screen.observe(
+            CrawlEvent::Fetching {
+                worker_id: 1,
+                url: url.clone(),
+            },
+            start,
+        );
+        screen.observe(CrawlEvent::QueueEmpty { worker_id: 0 }, start);
+        screen.observe(
+            CrawlEvent::Failed {
+                url,
+                kind: FetchFailureKind::Connect,
+                reason: "connection refused".to_string(),
+            },
+            start + Duration::seconds(5),
+        );
+        screen.push_sample(StatusSample {
+            queue_length: 42,
+            crawled_by_status_class: [0, 10, 2, 1, 0, 0],
+            workers: Some((1, 1)),
+            reserved_origins: vec![AtraUrlOrigin::from("www.example.com")],
+            ..StatusSample::default()
+        });
+        assert_eq!(
+            Some(&WorkerStatus::Idle),
+            screen.workers.get(&0),
+            "{:?}",
+            screen.workers
+        );
+
+        let lines = screen.render(200, 100, start + Duration::minutes(2));
+        let shown = lines.join("\n");
+        assert!(shown.contains("Queue: 42"), "{shown}");
+        assert!(
+            shown.contains("Crawled: 13 (2xx: 10, 3xx: 2, 4xx: 1)"),
+            "{shown}"
+        );
+        assert!(shown.contains("Workers: 1 crawling, 1 waiting"), "{shown}");
+        assert!(shown.contains("idle"), "{shown}");
+        assert!(shown.contains("https://www.example.com/a"), "{shown}");
+        assert!(
+            shown.contains("Reserved origins (1): www.example.com"),
+            "{shown}"
+        );
+        assert!(
+            shown.contains("00:00:05 https://www.example.com/a (Connect): connection refused"),
+            "{shown}"
+        );
 
-    pub fn reset(&mut self) {
-        self.used = false;
-        self.worker_id = None;
-        self.meta_information = None;
-        self.current = None;
-        self.buffer.clear();
+        let lines = screen.render(20, 3, start);
+        assert_eq!(3, lines.len());
+        assert!(lines
+            .iter()
+            .all(|line| console::measure_text_width(line) <= 20));
     }
 }
@@ -0,0 +1,240 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::rebuild::collect_warc_files;
+use crate::config::Config;
+use crate::warc_ext::WarcRecordScanner;
+use camino::Utf8PathBuf;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io::BufReader;
+use thiserror::Error;
+
+/// Errors of the verification of a session.
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error("{0} records of the warc files are corrupt.")]
+    Corrupt(usize),
+}
+
+/// A record whose block does not match its digest or that can not be read at all.
+#[derive(Debug, Clone)]
+pub struct CorruptRecord {
+    pub path: Utf8PathBuf,
+    pub offset: u64,
+    /// The target uri of the record, None if the record has none or can not be read.
+    pub url: Option<String>,
+    pub reason: String,
+}
+
+/// The result of a verification.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The number of scanned warc files.
+    pub files: usize,
+    /// The number of scanned records.
+    pub records: usize,
+    /// The records without a block digest.
+    pub unchecked: usize,
+    pub corrupt: Vec<CorruptRecord>,
+}
+
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for value in &self.corrupt {
+            writeln!(
+                f,
+                "corrupt  {} at {} ({}): {}",
+                value.path,
+                value.offset,
+                value.url.as_deref().unwrap_or("no url"),
+                value.reason
+            )?;
+        }
+        writeln!(f, "Files:      {}", self.files)?;
+        writeln!(f, "Records:    {}", self.records)?;
+        writeln!(f, "Unchecked:  {}", self.unchecked)?;
+        writeln!(f, "Corrupt:    {}", self.corrupt.len())
+    }
+}
+
+/// Streams through every record of the warc files of [config] and compares the digest of its
+/// block to the block digest stored in its header. A file is left after its first unreadable
+/// record, the offsets of the following records are unknown.
+pub(crate) fn verify(config: &Config) -> Result<VerifyReport, VerifyError> {
+    let mut report = VerifyReport::default();
+    for path in collect_warc_files(config)? {
+        log::info!("Verifying {path}");
+        report.files += 1;
+        let mut scanner = WarcRecordScanner::new(BufReader::new(File::open(&path)?));
+        loop {
+            let offset = scanner.offset();
+            let record = match scanner.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                Err(err) => {
+                    log::error!("Failed to read {path} at {offset}: {err}");
+                    report.corrupt.push(CorruptRecord {
+                        path: path.clone(),
+                        offset,
+                        url: None,
+                        reason: err.to_string(),
+                    });
+                    break;
+                }
+            };
+            report.records += 1;
+            let Some(Ok(expected)) = record.header.get_block_digest() else {
+                report.unchecked += 1;
+                continue;
+            };
+            if record.block_digest_matches == Some(true) {
+                continue;
+            }
+            let url = match record.header.get_target_uri() {
+                Some(Ok(value)) => Some(String::from_utf8_lossy(value.as_ref()).into_owned()),
+                _ => None,
+            };
+            let expected: &[u8] = expected.as_ref();
+            report.corrupt.push(CorruptRecord {
+                path: path.clone(),
+                offset,
+                url,
+                reason: format!(
+                    "The block has the digest {} but {} is stored.",
+                    String::from_utf8_lossy(&record.block_digest),
+                    String::from_utf8_lossy(expected)
+                ),
+            });
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::app::verify::verify;
+    use crate::config::Config;
+    use crate::crawl::test::create_test_data;
+    use crate::data::RawVecData;
+    use crate::io::fs::{AtraFS, FileSystemAccess};
+    use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
+    use crate::url::UrlWithDepth;
+    use crate::warc_ext::{write_warc, ReaderError, WarcSkipInstruction, WriterError};
+    use camino_tempfile::Utf8TempDir;
+    use std::io::{Read, Seek, SeekFrom, Write};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn detects_a_flipped_byte_in_a_stored_body() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        let fs = FileSystemAccess::new(
+            config.session.service.clone(),
+            config.session.collection.clone(),
+            config.session.crawl_job_id,
+            config.paths.root_path().to_path_buf(),
+            config.paths.dir_big_files(),
+            config.session.dat_file_layout,
+        )
+        .unwrap();
+        let writer = ThreadsafeMultiFileWarcWriter::new_for_worker(Arc::new(
+            fs.create_worker_file_provider(0, 0).unwrap(),
+        ))
+        .unwrap();
+        let instructions = writer
+            .execute_on_writer::<_, WriterError, _>(|writer| {
+                [
+                    "https://www.example.com/intact",
+                    "https://www.example.com/flipped",
+                ]
+                .into_iter()
+                .map(|url| {
+                    let result = create_test_data(
+                        UrlWithDepth::from_url(url).unwrap(),
+                        Some(RawVecData::from_vec(
+                            format!("<html><body>{url}</body></html>").into_bytes(),
+                        )),
+                    );
+                    write_warc(writer, &result)
+                })
+                .collect::<Result<Vec<_>, _>>()
+            })
+            .await
+            .unwrap();
+        writer.flush().await.unwrap();
+
+        let report = verify(&config).unwrap();
+        assert!(report.corrupt.is_empty(), "{report}");
+        assert_eq!(1, report.files);
+        assert!(report.records >= instructions.len());
+        assert_eq!(0, report.unchecked);
+        let records = report.records;
+
+        let WarcSkipInstruction::Single {
+            pointer,
+            header_signature_octet_count,
+            ..
+        } = &instructions[1]
+        else {
+            panic!("Expected a single record, got {:?}", instructions[1]);
+        };
+        // Flips the first byte of the payload.
+        let position = pointer.file_offset()
+            + pointer.warc_header_octet_count() as u64
+            + *header_signature_octet_count as u64;
+        let mut file = std::fs::File::options()
+            .read(true)
+            .write(true)
+            .open(pointer.path())
+            .unwrap();
+        let mut byte = [0u8; 1];
+        file.seek(SeekFrom::Start(position)).unwrap();
+        file.read_exact(&mut byte).unwrap();
+        file.seek(SeekFrom::Start(position)).unwrap();
+        file.write_all(&[byte[0] ^ 0xFF]).unwrap();
+        file.sync_all().unwrap();
+
+        // Without the verification the corruption goes unnoticed.
+        assert!(instructions[1].read().is_ok());
+        assert!(instructions[0].read_with(true).is_ok());
+        match instructions[1].read_with(true) {
+            Err(ReaderError::DigestMismatch {
+                expected,
+                actual,
+                path,
+                offset,
+            }) => {
+                assert_ne!(expected, actual);
+                assert_eq!(pointer.path(), path.as_path());
+                assert_eq!(pointer.file_offset(), offset);
+            }
+            other => panic!("Expected a digest mismatch, got {other:?}"),
+        }
+
+        let report = verify(&config).unwrap();
+        assert_eq!(1, report.files);
+        assert_eq!(records, report.records);
+        assert_eq!(0, report.unchecked);
+        assert_eq!(1, report.corrupt.len());
+        assert_eq!(pointer.file_offset(), report.corrupt[0].offset);
+        assert_eq!(
+            Some("https://www.example.com/flipped"),
+            report.corrupt[0].url.as_deref()
+        );
+    }
+}
@@ -12,7 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod audit;
+mod bulk;
+mod columnar;
 mod db_view;
+mod filter;
+mod jsonl;
+mod similar;
+mod webgraph;
 
 use std::borrow::Cow;
 use std::fmt::{Display, Formatter};
@@ -23,19 +30,30 @@ use console::{style, Term};
 use dialoguer::{Select, theme};
 use itertools::{Either, Itertools};
 use crate::contexts::local::LocalContext;
-use crate::contexts::traits::{SupportsLinkState, SupportsUrlQueue};
-use crate::crawl::{SlimCrawlResult, StoredDataHint};
+use crate::contexts::traits::{SupportsLinkState, SupportsOriginMetadata, SupportsTlsInfo, SupportsUniqueContent, SupportsUrlQueue};
+use crate::crawl::{AuxiliaryDocument, SitemapSummary, SlimCrawlResult, StoredDataHint};
 use crate::link_state::{LinkStateLike, LinkStateManager};
-use crate::url::AtraUri;
+use crate::origin_metadata::{OriginMetadataManager, ValueDistribution};
+use crate::tls_info::{OriginTlsInfo, TlsInfo, TlsInfoManager};
+use crate::unique_content::{UniqueContentCounts, UniqueContentManager};
+use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
 use crate::warc_ext::WarcSkipInstruction;
 use rocksdb::{Direction, Error, IteratorMode};
 use strum::{Display, VariantArray};
 use time::OffsetDateTime;
 use crate::app::view::db_view::{ControlledIterator, SlimEntry};
-use crate::data::RawVecData;
+use crate::data::{ExternalFileReader, RawVecData};
 use crate::format::supported::InterpretedProcessibleFileFormat;
 use std::fmt::Write as FmtWrite;
 
+pub use audit::export_audit_trail;
+pub use bulk::{export_bulk, BulkExportArgs, BulkExportError};
+pub use columnar::{export_parquet, ParquetCompression, ParquetExportError, ParquetExportOptions};
+pub use filter::{export_matching_pages, ViewFilter};
+pub use jsonl::{export_jsonl, JsonlOptions, ViewFormat};
+pub use similar::{write_similar_pages, SimilarPagesError};
+pub use webgraph::{export_web_graph, WebGraphExportOptions, WebGraphFormat, WebGraphNodes};
+
 #[derive(Debug, Display, VariantArray)]
 enum Targets {
     #[strum(to_string = "See the stats.")]
@@ -94,6 +112,9 @@ pub fn view(
         term.write_line(&format!("Links in Queue:        {}", local.url_queue().len_blocking())).unwrap();
         term.write_line(&format!("Links in CrawlDB:      {}", local.crawl_db().len())).unwrap();
         term.write_line(&format!("Links in StateManager: {}", local.get_link_state_manager().len())).unwrap();
+        term.write_line(&format!("Unique Payloads:       {}", format_unique_content(local.get_unique_content_manager().global_counts()))).unwrap();
+        term.write_line(&format!("TLS Origins:           {}", format_tls_origins(&local.get_tls_info_manager().origins()))).unwrap();
+        term.write_line(&format!("Origins With Metadata: {}", local.get_origin_metadata_manager().origins().len())).unwrap();
         term.write_line("Press Enter to continue...").unwrap();
         term.flush().unwrap();
         term.read_line().unwrap();
@@ -107,7 +128,7 @@ pub fn view(
             .take(n)
             .map_ok(|(k, v)| {
                 let k: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
-                let v: SlimCrawlResult = bincode::deserialize(v.as_ref()).unwrap();
+                let v = SlimCrawlResult::decode(v.as_ref()).unwrap();
                 (k, v)
             })
             .collect_vec()
@@ -268,6 +289,53 @@ enum EntryDialougeMode {
     Internals,
 }
 
+/// A one line description of a sitemap.
+fn describe_sitemap(summary: &SitemapSummary) -> String {
+    let lastmod = match (summary.lastmod_min, summary.lastmod_max) {
+        (Some(min), Some(max)) => format!("{min} - {max}"),
+        _ => "-".to_string(),
+    };
+    let changefreq = summary
+        .changefreq
+        .iter()
+        .map(|(name, count)| format!("{name}: {count}"))
+        .join(", ");
+    format!(
+        "{} urls, {} sitemaps, {} errors, lastmod: {lastmod}, changefreq: [{changefreq}]{}",
+        summary.url_count,
+        summary.sitemap_count,
+        summary.error_count,
+        if summary.compressed { ", gzip" } else { "" }
+    )
+}
+
+/// Describes the certificate of the connection of a page with the details stored for its origin.
+fn describe_tls(local: &LocalContext, url: &UrlWithDepth, tls: &TlsInfo) -> String {
+    let mut description = tls.fingerprint.clone();
+    let certificate = url.atra_origin().and_then(|origin| {
+        local
+            .get_tls_info_manager()
+            .certificates(&origin)
+            .into_iter()
+            .find(|certificate| certificate.fingerprint == tls.fingerprint)
+    });
+    if let Some(certificate) = certificate {
+        write!(
+            description,
+            " ({}, issued by {}, valid from {} to {})",
+            certificate.subject, certificate.issuer, certificate.not_before, certificate.not_after
+        )
+        .unwrap();
+    }
+    if tls.expired {
+        description.push_str(", expired");
+    }
+    if let Some(ref error) = tls.verification_error {
+        write!(description, ", not trusted: {error}").unwrap();
+    }
+    description
+}
+
 fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &LocalContext) {
     let mut view_data = String::new();
 
@@ -283,6 +351,12 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
     } else {
         writeln!(&mut view_data, "    Language: -!-").unwrap();
     }
+    if let Some(ref content_language) = v.meta.content_language {
+        writeln!(&mut view_data, "    Content-Language: {}", content_language).unwrap();
+    }
+    if let Some(ref variant) = v.meta.language_variant {
+        writeln!(&mut view_data, "    Language Variant: {}", variant).unwrap();
+    }
     let file_info = &v.meta.file_information;
     writeln!(&mut view_data, "    Atra Filetype: {}", file_info.format).unwrap();
     if let Some(ref mime) = file_info.mime {
@@ -293,6 +367,9 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
     if let Some(ref detected) = file_info.detected {
         writeln!(&mut view_data, "        Detected File Format: {}", detected.most_probable_file_format()).unwrap();
     }
+    if v.meta.sniff_window_truncated {
+        writeln!(&mut view_data, "        Detected on a partial read of the content").unwrap();
+    }
     writeln!(&mut view_data, "    Created At: {}", v.meta.created_at).unwrap();
     if let Some(encoding) = v.meta.recognized_encoding {
         writeln!(&mut view_data, "    Encoding: {}", encoding.name()).unwrap();
@@ -313,6 +390,15 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
         writeln!(&mut view_data, "    Linkstate: -!-").unwrap();
     }
 
+    if let Some(ref proxy) = v.meta.proxy {
+        writeln!(&mut view_data, "        Proxy: {proxy}").unwrap();
+    }
+    if let Some(ref tls) = v.meta.tls {
+        writeln!(&mut view_data, "    TLS: {}", describe_tls(context, &v.meta.url, tls)).unwrap();
+    }
+    if let Some(AuxiliaryDocument::Sitemap(ref summary)) = v.meta.auxiliary {
+        writeln!(&mut view_data, "    Sitemap: {}", describe_sitemap(summary)).unwrap();
+    }
     if let Some(ref redirect) = v.meta.final_redirect_destination {
         write!(&mut view_data, "        Redirect: {redirect}").unwrap();
     }
@@ -372,6 +458,13 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
                                 file_name
                             }
                         }
+                        InterpretedProcessibleFileFormat::CSS => {
+                            if !file_name.as_ref().ends_with(".css") {
+                                Cow::Owned(format!("{}.css", file_name))
+                            } else {
+                                file_name
+                            }
+                        }
                         InterpretedProcessibleFileFormat::PlainText | InterpretedProcessibleFileFormat::StructuredPlainText => {
                             if !file_name.as_ref().ends_with(".txt") {
                                 Cow::Owned(format!("{}.txt", file_name))
@@ -386,7 +479,7 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
                                 file_name
                             }
                         }
-                        InterpretedProcessibleFileFormat::XML => {
+                        InterpretedProcessibleFileFormat::XML | InterpretedProcessibleFileFormat::Feed => {
                             if !file_name.as_ref().ends_with(".xml") {
                                 Cow::Owned(format!("{}.xml", file_name))
                             } else {
@@ -468,7 +561,11 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
                                 }
                             }
                             RawVecData::ExternalFile { path: s_path } => {
-                                match std::fs::copy(s_path, &path) {
+                                let copied = ExternalFileReader::open(s_path).and_then(|mut reader| {
+                                    let mut out = File::options().write(true).create_new(true).open(&path)?;
+                                    std::io::copy(&mut reader, &mut out)
+                                });
+                                match copied {
                                     Ok(_) => {
                                         term.write_line(format!("Exported to {}", &path).as_str()).unwrap()
                                     }
@@ -584,6 +681,40 @@ fn entry_dialouge(term: &Term, uri: &AtraUri, v: &SlimCrawlResult, context: &Loc
 }
 
 
+fn format_unique_content(counts: UniqueContentCounts) -> String {
+    match counts.ratio() {
+        Some(ratio) => format!(
+            "{} of {} ({:.1}%)",
+            counts.unique_payloads,
+            counts.fetched,
+            ratio * 100.0
+        ),
+        None => "-".to_string(),
+    }
+}
+
+fn format_tls_origins(infos: &[(AtraUrlOrigin, OriginTlsInfo)]) -> String {
+    format!(
+        "{} ({} with a changed certificate)",
+        infos.len(),
+        infos.iter().filter(|(_, info)| info.changed()).count()
+    )
+}
+
+/// The values of [distribution] with their counts, the most common first.
+fn format_distribution(distribution: &ValueDistribution) -> String {
+    let mut values = distribution.counts.iter().collect::<Vec<_>>();
+    values.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    let mut formatted = values
+        .into_iter()
+        .map(|(value, count)| format!("{value} ({count})"))
+        .join(", ");
+    if distribution.overflow > 0 {
+        formatted.push_str(&format!(", {} more", distribution.overflow));
+    }
+    formatted
+}
+
 fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, headers: bool) {
     println!("##### ATRA STATS #####");
     println!(
@@ -595,8 +726,82 @@ fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, head
         "    Links in StateManager: {}",
         local.get_link_state_manager().len()
     );
+    println!(
+        "    Unique Payloads:       {}",
+        format_unique_content(local.get_unique_content_manager().global_counts())
+    );
+    let tls_origins = local.get_tls_info_manager().origins();
+    println!("    TLS Origins:           {}", format_tls_origins(&tls_origins));
+    let metadata_origins = local.get_origin_metadata_manager().origins();
+    println!("    Origins With Metadata: {}", metadata_origins.len());
     println!("##### ATRA STATS #####");
 
+    if !tls_origins.is_empty() {
+        println!("\n\nTLS Origins:\n");
+        for (origin, info) in &tls_origins {
+            let capture = &info.capture;
+            println!("{origin} ({}:{})", capture.host, capture.port);
+            println!("    Captured At: {}", capture.captured_at);
+            println!("    Protocol: {}", capture.protocol);
+            if let Some(ref cipher) = capture.cipher {
+                println!("    Cipher: {cipher}");
+            }
+            if let Some(ref error) = capture.verification_error {
+                println!("    Not Trusted: {error}");
+            }
+            for (i, certificate) in capture.chain.iter().enumerate() {
+                println!("    Certificate {i}:");
+                println!("        Subject: {}", certificate.subject);
+                println!("        Issuer: {}", certificate.issuer);
+                if !certificate.subject_alt_names.is_empty() {
+                    println!(
+                        "        Alternative Names: {}",
+                        certificate.subject_alt_names.join(", ")
+                    );
+                }
+                println!("        Serial Number: {}", certificate.serial_number);
+                println!("        Not Before: {}", certificate.not_before);
+                println!("        Not After: {}", certificate.not_after);
+                println!("        Fingerprint: {}", certificate.fingerprint);
+            }
+            if info.changed() {
+                println!(
+                    "    Changed During The Crawl: {} -> {}",
+                    info.previous_fingerprints.join(" -> "),
+                    info.fingerprint
+                );
+            }
+        }
+        println!("\n-----------------------\n");
+    }
+
+    if !metadata_origins.is_empty() {
+        println!("\n\nOrigin Metadata:\n");
+        for (origin, metadata) in &metadata_origins {
+            println!("{origin} ({} pages)", metadata.pages);
+            for (name, distribution) in [
+                ("Generators", &metadata.generators),
+                ("Feeds", &metadata.feeds),
+                ("Languages", &metadata.languages),
+                ("Servers", &metadata.servers),
+            ] {
+                if distribution.total() > 0 {
+                    println!("    {name}: {}", format_distribution(distribution));
+                }
+            }
+            if let Some(ref well_known) = metadata.well_known {
+                match well_known.robots_txt_size {
+                    Some(size) => println!("    Robots.txt: {size} bytes"),
+                    None => println!("    Robots.txt: -"),
+                }
+                if let Some(ref security_txt) = well_known.security_txt {
+                    println!("    Security.txt: {}", security_txt.contacts.join(", "));
+                }
+            }
+        }
+        println!("\n-----------------------\n");
+    }
+
     println!("\n\nCrawled Websides:\n");
     println!("\n-----------------------\n");
     for (k, v) in local
@@ -605,7 +810,7 @@ fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, head
         .filter_map(|value| value.ok())
         .map(|(k, v)| {
             let k: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
-            let v: SlimCrawlResult = bincode::deserialize(v.as_ref()).unwrap();
+            let v = SlimCrawlResult::decode(v.as_ref()).unwrap();
             (k, v)
         })
     {
@@ -621,6 +826,12 @@ fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, head
         } else {
             println!("        Language: -!-",);
         }
+        if let Some(ref content_language) = v.meta.content_language {
+            println!("        Content-Language: {}", content_language);
+        }
+        if let Some(ref variant) = v.meta.language_variant {
+            println!("        Language Variant: {}", variant);
+        }
         let file_info = v.meta.file_information;
         println!("        Atra Filetype: {}", file_info.format);
         if let Some(mime) = file_info.mime {
@@ -634,6 +845,9 @@ fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, head
                 detected.most_probable_file_format()
             );
         }
+        if v.meta.sniff_window_truncated {
+            println!("            Detected on a partial read of the content");
+        }
 
         println!("        Created At: {}", v.meta.created_at);
 
@@ -657,6 +871,15 @@ fn view_legacy(local: LocalContext, internals: bool, extracted_links: bool, head
             println!("        Linkstate: -!-");
         }
 
+        if let Some(proxy) = v.meta.proxy {
+            println!("        Proxy: {proxy}");
+        }
+        if let Some(ref tls) = v.meta.tls {
+            println!("        TLS: {}", describe_tls(&local, &v.meta.url, tls));
+        }
+        if let Some(AuxiliaryDocument::Sitemap(ref summary)) = v.meta.auxiliary {
+            println!("        Sitemap: {}", describe_sitemap(summary));
+        }
         if let Some(redirect) = v.meta.final_redirect_destination {
             println!("        Redirect: {redirect}");
         }
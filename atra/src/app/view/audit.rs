@@ -0,0 +1,40 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::view::ViewFormat;
+use crate::schemas::read_audit_trail;
+use camino::Utf8Path;
+use std::io;
+use std::io::Write;
+
+/// Writes the audit trail of the session in [root] to [writer], one line per record.
+/// Returns the number of written records.
+pub fn export_audit_trail<W: Write>(
+    root: &Utf8Path,
+    mut writer: W,
+    format: ViewFormat,
+) -> io::Result<usize> {
+    let records = read_audit_trail(root)?;
+    for record in &records {
+        match format {
+            ViewFormat::Text => writeln!(writer, "{record}")?,
+            ViewFormat::Jsonl => {
+                serde_json::to_writer(&mut writer, record)?;
+                writeln!(writer)?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(records.len())
+}
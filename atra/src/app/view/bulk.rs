@@ -0,0 +1,594 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::view::jsonl::{export_text, stored_entries};
+use crate::contexts::local::LocalContext;
+use crate::crawl::SlimCrawlResult;
+use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::toolkit::digest::labeled_xxh128_digest;
+use crate::toolkit::text_normalization::TextNormalizationConfig;
+use crate::url::AtraOriginProvider;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// The maximal length of a document id accepted by Elasticsearch.
+const MAX_ID_LENGTH: usize = 512;
+/// The delay before the first retry of a rejected batch, doubled for every further retry.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+/// The number of failed documents listed by the report.
+const REPORTED_FAILURES: usize = 20;
+/// The environment variable with the password, used if no password is given.
+const BULK_PASSWORD_ENV: &str = "ATRA_BULK_PASSWORD";
+
+/// Errors that stop a bulk export, failures of single documents are reported instead.
+#[derive(Debug, Error)]
+pub enum BulkExportError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    #[error("Either an endpoint or an output file is necessary for the bulk export.")]
+    NoTarget,
+}
+
+/// How the crawl is exported as bulk index actions for OpenSearch or Elasticsearch.
+#[derive(clap::Args, Debug, Clone)]
+pub struct BulkExportArgs {
+    /// Post the actions to `<ENDPOINT>/_bulk`, e.g. http://localhost:9200
+    #[arg(long, conflicts_with = "output")]
+    pub endpoint: Option<String>,
+    /// Write the NDJSON actions to this file instead of posting them
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// The user for the basic auth at the endpoint
+    #[arg(long)]
+    pub user: Option<String>,
+    /// The password for the basic auth, read from ATRA_BULK_PASSWORD if not set
+    #[arg(long)]
+    pub password: Option<String>,
+    /// The name of the index, `{origin}` and `{date}` (the day of the crawl) are replaced
+    #[arg(long, default_value = "atra-{date}")]
+    pub index: String,
+    /// The number of documents posted at once
+    #[arg(long, default_value_t = NonZeroUsize::new(500).unwrap())]
+    pub batch_size: NonZeroUsize,
+    /// How often a batch rejected with 429 or 503 is retried
+    #[arg(long, default_value_t = 5)]
+    pub max_retries: u32,
+    /// The maximal number of bytes of the exported text, longer texts are truncated
+    #[arg(long, default_value_t = 65536)]
+    pub max_text_size: usize,
+    /// Also export the pages past their unavailable_after date
+    #[arg(long)]
+    pub include_expired: bool,
+}
+
+impl BulkExportArgs {
+    /// The password for the basic auth, falls back to [BULK_PASSWORD_ENV].
+    fn password(&self) -> Option<String> {
+        self.password
+            .clone()
+            .or_else(|| std::env::var(BULK_PASSWORD_ENV).ok())
+    }
+}
+
+/// A crawled page as indexed document.
+#[derive(Debug, Clone, Serialize)]
+struct BulkDocument {
+    url: String,
+    origin: Option<String>,
+    status: u16,
+    mime: Vec<String>,
+    format: InterpretedProcessibleFileFormat,
+    detected_format: Option<String>,
+    language: Option<String>,
+    created_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    text_truncated: bool,
+}
+
+/// The failure of a single document.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BulkFailure {
+    pub url: String,
+    pub reason: String,
+}
+
+/// What the bulk export did.
+#[derive(Debug, Clone, Default)]
+pub struct BulkExportReport {
+    pub exported: usize,
+    pub failures: Vec<BulkFailure>,
+}
+
+impl Display for BulkExportReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Exported documents: {}", self.exported)?;
+        writeln!(f, "Failed documents:   {}", self.failures.len())?;
+        for failure in self.failures.iter().take(REPORTED_FAILURES) {
+            writeln!(f, "    {}: {}", failure.url, failure.reason)?;
+        }
+        if self.failures.len() > REPORTED_FAILURES {
+            writeln!(
+                f,
+                "    ... and {} more",
+                self.failures.len() - REPORTED_FAILURES
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The pair of lines of a single document in a bulk request.
+struct BulkAction {
+    url: String,
+    lines: Vec<u8>,
+}
+
+/// Exports the crawl of [local] to the endpoint or the file of [args],
+/// the text is normalized with [text].
+pub async fn export_bulk(
+    local: &LocalContext,
+    args: &BulkExportArgs,
+    text: TextNormalizationConfig,
+) -> Result<BulkExportReport, BulkExportError> {
+    let entries = stored_entries(local.crawl_db());
+    if let Some(endpoint) = &args.endpoint {
+        post_bulk(entries, endpoint, args, text).await
+    } else if let Some(output) = &args.output {
+        let file = std::fs::File::options()
+            .write(true)
+            .create_new(true)
+            .open(output)?;
+        write_bulk(entries, io::BufWriter::new(file), args, text)
+    } else {
+        Err(BulkExportError::NoTarget)
+    }
+}
+
+/// Writes the bulk actions of [entries] to [writer] and flushes after each batch,
+/// every batch can be posted to `_bulk` as it is.
+pub fn write_bulk<W: Write>(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    mut writer: W,
+    args: &BulkExportArgs,
+    text: TextNormalizationConfig,
+) -> Result<BulkExportReport, BulkExportError> {
+    let mut exported = 0usize;
+    let mut skipped = Vec::new();
+    let mut actions = bulk_actions(entries, args, text, &mut skipped);
+    loop {
+        let batch = actions
+            .by_ref()
+            .take(args.batch_size.get())
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        for action in &batch {
+            writer.write_all(&action.lines)?;
+        }
+        writer.flush()?;
+        exported += batch.len();
+    }
+    drop(actions);
+    Ok(BulkExportReport {
+        exported,
+        failures: skipped,
+    })
+}
+
+/// Posts the bulk actions of [entries] to [endpoint], one batch at a time.
+/// Batches rejected with 429 or 503 are retried with a growing delay, the documents of a
+/// batch that is finally rejected are reported as failed.
+async fn post_bulk(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    endpoint: &str,
+    args: &BulkExportArgs,
+    text: TextNormalizationConfig,
+) -> Result<BulkExportReport, BulkExportError> {
+    let client = reqwest::Client::builder().build()?;
+    let url = format!("{}/_bulk", endpoint.trim_end_matches('/'));
+    let password = args.password();
+    let mut exported = 0usize;
+    let mut failures = Vec::new();
+    let mut skipped = Vec::new();
+    let mut actions = bulk_actions(entries, args, text, &mut skipped);
+    loop {
+        let batch = actions
+            .by_ref()
+            .take(args.batch_size.get())
+            .collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        let body = batch
+            .iter()
+            .flat_map(|action| action.lines.iter().copied())
+            .collect::<Vec<_>>();
+        let mut attempt = 0u32;
+        let outcome = loop {
+            let mut request = client
+                .post(&url)
+                .header(CONTENT_TYPE, "application/x-ndjson")
+                .body(body.clone());
+            if let Some(user) = &args.user {
+                request = request.basic_auth(user, password.as_deref());
+            }
+            let outcome = match request.send().await {
+                Ok(response) if is_retryable(response.status()) => {
+                    Err(format!("Rejected with {}", response.status()))
+                }
+                Ok(response) if response.status().is_success() => {
+                    let value = response
+                        .bytes()
+                        .await
+                        .map_err(|err| err.to_string())
+                        .and_then(|body| {
+                            serde_json::from_slice::<serde_json::Value>(&body)
+                                .map_err(|err| err.to_string())
+                        });
+                    match value {
+                        Ok(value) => Ok(item_failures(&value, &batch)),
+                        Err(err) => break Err(format!("Invalid response: {err}")),
+                    }
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    break Err(format!("Rejected with {status}: {body}"));
+                }
+                Err(err) => Err(err.to_string()),
+            };
+            match outcome {
+                Ok(failed) => break Ok(failed),
+                Err(reason) if attempt >= args.max_retries => break Err(reason),
+                Err(reason) => {
+                    let delay = RETRY_DELAY * 2u32.saturating_pow(attempt);
+                    log::warn!("Retry the batch in {delay:?}: {reason}");
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        };
+        match outcome {
+            Ok(failed) => {
+                exported += batch.len() - failed.len();
+                failures.extend(failed);
+            }
+            Err(reason) => {
+                log::error!(
+                    "Failed to export a batch of {} documents: {reason}",
+                    batch.len()
+                );
+                failures.extend(batch.into_iter().map(|action| BulkFailure {
+                    url: action.url,
+                    reason: reason.clone(),
+                }));
+            }
+        }
+    }
+    drop(actions);
+    failures.extend(skipped);
+    Ok(BulkExportReport { exported, failures })
+}
+
+/// Returns true if a batch rejected with [status] can be sent again.
+fn is_retryable(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+    )
+}
+
+/// The documents of [batch] failed according to the bulk [response].
+fn item_failures(response: &serde_json::Value, batch: &[BulkAction]) -> Vec<BulkFailure> {
+    if response["errors"].as_bool() != Some(true) {
+        return Vec::new();
+    }
+    let Some(items) = response["items"].as_array() else {
+        return Vec::new();
+    };
+    items
+        .iter()
+        .zip(batch)
+        .filter_map(|(item, action)| {
+            let result = item.as_object()?.values().next()?;
+            let status = result["status"].as_u64().unwrap_or_default();
+            if (200..300).contains(&status) {
+                return None;
+            }
+            let reason = match &result["error"] {
+                serde_json::Value::Null => format!("Failed with {status}"),
+                serde_json::Value::String(reason) => reason.clone(),
+                error => error["reason"]
+                    .as_str()
+                    .map(|reason| {
+                        format!("{}: {reason}", error["type"].as_str().unwrap_or("error"))
+                    })
+                    .unwrap_or_else(|| error.to_string()),
+            };
+            Some(BulkFailure {
+                url: action.url.clone(),
+                reason,
+            })
+        })
+        .collect()
+}
+
+/// The bulk actions of [entries], documents that can not be serialized are
+/// added to [skipped].
+fn bulk_actions<'a>(
+    entries: impl IntoIterator<Item = SlimCrawlResult> + 'a,
+    args: &'a BulkExportArgs,
+    text: TextNormalizationConfig,
+    skipped: &'a mut Vec<BulkFailure>,
+) -> impl Iterator<Item = BulkAction> + 'a {
+    let now = OffsetDateTime::now_utc();
+    entries
+        .into_iter()
+        .filter(move |entry| args.include_expired || !entry.meta.is_expired_at(now))
+        .filter_map(move |entry| match bulk_action(&entry, args, text) {
+            Ok(action) => Some(action),
+            Err(err) => {
+                skipped.push(BulkFailure {
+                    url: entry.meta.url.try_as_str().into_owned(),
+                    reason: err.to_string(),
+                });
+                None
+            }
+        })
+}
+
+/// The action and the source of the document of [entry].
+fn bulk_action(
+    entry: &SlimCrawlResult,
+    args: &BulkExportArgs,
+    text: TextNormalizationConfig,
+) -> Result<BulkAction, serde_json::Error> {
+    let document = bulk_document(entry, args.max_text_size, text);
+    let mut lines = Vec::new();
+    serde_json::to_writer(
+        &mut lines,
+        &serde_json::json!({
+            "index": {
+                "_index": index_name(&args.index, &document, entry.meta.created_at),
+                "_id": document_id(&document.url),
+            }
+        }),
+    )?;
+    lines.push(b'\n');
+    serde_json::to_writer(&mut lines, &document)?;
+    lines.push(b'\n');
+    Ok(BulkAction {
+        url: document.url,
+        lines,
+    })
+}
+
+fn bulk_document(
+    entry: &SlimCrawlResult,
+    max_text_size: usize,
+    text: TextNormalizationConfig,
+) -> BulkDocument {
+    let meta = &entry.meta;
+    let (text, text_truncated) = match export_text(entry, text) {
+        Some((text, _)) => {
+            let (text, truncated) = truncate(text, max_text_size);
+            (Some(text), truncated)
+        }
+        None => (None, false),
+    };
+    BulkDocument {
+        url: meta.url.try_as_str().into_owned(),
+        origin: meta.url.atra_origin().map(|value| value.to_string()),
+        status: meta.status_code.as_u16(),
+        mime: meta
+            .file_information
+            .mime
+            .iter()
+            .flat_map(|value| value.iter())
+            .map(|value| value.to_string())
+            .collect(),
+        format: meta.file_information.format,
+        detected_format: meta
+            .file_information
+            .detected
+            .as_ref()
+            .map(|value| value.most_probable_file_format().to_string()),
+        language: meta
+            .language
+            .map(|value| value.lang().to_639_3().to_string()),
+        created_at: meta
+            .created_at
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| meta.created_at.to_string()),
+        text,
+        text_truncated,
+    }
+}
+
+/// Truncates [text] to at most [max_bytes] at a char boundary, returns true if it was truncated.
+fn truncate(mut text: String, max_bytes: usize) -> (String, bool) {
+    if text.len() <= max_bytes {
+        return (text, false);
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    (text, true)
+}
+
+/// The index of [document] by the [template], Elasticsearch only accepts lowercase names.
+fn index_name(template: &str, document: &BulkDocument, created_at: OffsetDateTime) -> String {
+    let date = format!(
+        "{:04}.{:02}.{:02}",
+        created_at.year(),
+        created_at.month() as u8,
+        created_at.day()
+    );
+    template
+        .replace("{origin}", document.origin.as_deref().unwrap_or("unknown"))
+        .replace("{date}", &date)
+        .to_lowercase()
+}
+
+/// The id of the document of [url], a digest of the url if it is too long.
+fn document_id(url: &str) -> String {
+    if url.len() <= MAX_ID_LENGTH {
+        url.to_string()
+    } else {
+        String::from_utf8_lossy(&labeled_xxh128_digest(url)).into_owned()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{bulk_actions, item_failures, truncate, write_bulk, BulkExportArgs};
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::toolkit::text_normalization::TextNormalizationConfig;
+    use crate::url::UrlWithDepth;
+    use std::num::NonZeroUsize;
+
+    fn args() -> BulkExportArgs {
+        BulkExportArgs {
+            endpoint: None,
+            output: None,
+            user: None,
+            password: None,
+            index: "atra-{origin}-{date}".to_string(),
+            batch_size: NonZeroUsize::new(1).unwrap(),
+            max_retries: 0,
+            max_text_size: 2,
+            include_expired: false,
+        }
+    }
+
+    fn create_entries() -> Vec<SlimCrawlResult> {
+        let with_text = SlimCrawlResult::new(
+            &create_test_data(
+                UrlWithDepth::from_url("https://www.google.de/").unwrap(),
+                None,
+            ),
+            StoredDataHint::InMemory("Hällo world".into()),
+        );
+        let without_text = SlimCrawlResult::new(
+            &create_test_data(
+                UrlWithDepth::from_url("https://www.ebay.de/").unwrap(),
+                None,
+            ),
+            StoredDataHint::None,
+        );
+        vec![with_text, without_text]
+    }
+
+    #[test]
+    fn writes_an_action_and_a_source_per_document() {
+        let mut out = Vec::new();
+        let report = write_bulk(
+            create_entries(),
+            &mut out,
+            &args(),
+            TextNormalizationConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(2, report.exported);
+        assert!(report.failures.is_empty());
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.ends_with('\n'));
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(4, lines.len());
+
+        let action = &lines[0]["index"];
+        assert_eq!("https://www.google.de/", action["_id"]);
+        let index = action["_index"].as_str().unwrap();
+        assert!(index.starts_with("atra-www.google.de-"), "{index}");
+        let document = &lines[1];
+        assert_eq!("https://www.google.de/", document["url"]);
+        assert_eq!("www.google.de", document["origin"]);
+        assert_eq!(200, document["status"]);
+        assert_eq!("HTML", document["format"]);
+        assert_eq!(0, document["mime"].as_array().unwrap().len());
+        assert_eq!("deu", document["language"]);
+        assert!(document["created_at"].is_string());
+        // The "ä" does not fit into the 2 bytes and is dropped as a whole.
+        assert_eq!("H", document["text"]);
+        assert_eq!(true, document["text_truncated"]);
+
+        assert_eq!("https://www.ebay.de/", lines[2]["index"]["_id"]);
+        assert!(lines[3].get("text").is_none());
+        assert!(lines[3].get("text_truncated").is_none());
+    }
+
+    #[test]
+    fn truncates_at_a_char_boundary() {
+        assert_eq!(("Hä".to_string(), true), truncate("Hällo".to_string(), 3));
+        assert_eq!(("H".to_string(), true), truncate("Hällo".to_string(), 2));
+        assert_eq!(
+            ("Hällo".to_string(), false),
+            truncate("Hällo".to_string(), 6)
+        );
+    }
+
+    #[test]
+    fn collects_the_failed_documents_of_a_response() {
+        let mut skipped = Vec::new();
+        let args = args();
+        let batch = bulk_actions(
+            create_entries(),
+            &args,
+            TextNormalizationConfig::default(),
+            &mut skipped,
+        )
+        .collect::<Vec<_>>();
+        assert!(skipped.is_empty());
+        let response = serde_json::json!({
+            "took": 3,
+            "errors": true,
+            "items": [
+                {"index": {"_id": "https://www.google.de/", "status": 201}},
+                {"index": {"_id": "https://www.ebay.de/", "status": 400, "error": {
+                    "type": "mapper_parsing_exception",
+                    "reason": "failed to parse field [status]"
+                }}}
+            ]
+        });
+        let failures = item_failures(&response, &batch);
+        assert_eq!(1, failures.len());
+        assert_eq!("https://www.ebay.de/", failures[0].url);
+        assert_eq!(
+            "mapper_parsing_exception: failed to parse field [status]",
+            failures[0].reason
+        );
+        assert!(item_failures(&serde_json::json!({"errors": false}), &batch).is_empty());
+    }
+}
@@ -0,0 +1,667 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::view::jsonl::stored_entries;
+use crate::contexts::local::LocalContext;
+use crate::crawl::SlimCrawlResult;
+use crate::data::{ExternalFileReader, RawData};
+use crate::extraction::html::LinkOrigin;
+use crate::extraction::marker::{ExtractorMethodHint, ExtractorMethodMeta};
+use crate::extraction::{ExtractedLink, FeedLinkOrigin, ScriptLinkKind};
+use crate::schemas::{edges_schema, pages_schema};
+use crate::toolkit::digest::{labeled_xxh128_digest, LabeledXxh128Digester};
+use crate::url::AtraOriginProvider;
+use arrow_array::builder::{
+    BooleanBuilder, StringBuilder, TimestampMillisecondBuilder, UInt16Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, SchemaRef};
+use camino::Utf8Path;
+use clap::ValueEnum;
+use itertools::Either;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, GzipLevel, ZstdLevel};
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use thiserror::Error;
+use time::OffsetDateTime;
+
+/// The name of the file with the pages.
+pub const PAGES_FILE_NAME: &str = "pages.parquet";
+/// The name of the file with the extracted links.
+pub const EDGES_FILE_NAME: &str = "edges.parquet";
+
+/// The compression codec of the exported Parquet files.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, ValueEnum)]
+pub enum ParquetCompression {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    Lz4,
+    #[default]
+    Zstd,
+}
+
+impl ParquetCompression {
+    fn codec(self) -> Compression {
+        match self {
+            ParquetCompression::Uncompressed => Compression::UNCOMPRESSED,
+            ParquetCompression::Snappy => Compression::SNAPPY,
+            ParquetCompression::Gzip => Compression::GZIP(GzipLevel::default()),
+            ParquetCompression::Lz4 => Compression::LZ4_RAW,
+            ParquetCompression::Zstd => Compression::ZSTD(ZstdLevel::default()),
+        }
+    }
+}
+
+/// How the Parquet files are written.
+#[derive(Debug, Copy, Clone)]
+pub struct ParquetExportOptions {
+    pub compression: ParquetCompression,
+    /// The maximum number of rows of a row group, at most this many rows are held in memory.
+    pub row_group_size: NonZeroUsize,
+    /// Also export the entries whose `unavailable_after` lies in the past.
+    pub include_expired: bool,
+}
+
+impl Default for ParquetExportOptions {
+    fn default() -> Self {
+        Self {
+            compression: ParquetCompression::default(),
+            row_group_size: NonZeroUsize::new(65536).unwrap(),
+            include_expired: false,
+        }
+    }
+}
+
+/// The number of rows written by [export_parquet].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct ParquetExportCounts {
+    pub pages: u64,
+    pub edges: u64,
+}
+
+/// Errors while exporting the Parquet files.
+#[derive(Debug, Error)]
+pub enum ParquetExportError {
+    #[error(transparent)]
+    IO(#[from] io::Error),
+    #[error(transparent)]
+    Parquet(#[from] ParquetError),
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+}
+
+/// Exports the crawl db of [local] to the files [PAGES_FILE_NAME] and [EDGES_FILE_NAME]
+/// in [output_dir].
+pub fn export_parquet(
+    local: &LocalContext,
+    output_dir: &Utf8Path,
+    options: ParquetExportOptions,
+) -> Result<ParquetExportCounts, ParquetExportError> {
+    write_parquet(stored_entries(local.crawl_db()), output_dir, options)
+}
+
+/// Streams [entries] to the files [PAGES_FILE_NAME] and [EDGES_FILE_NAME] in [output_dir],
+/// existing files are not overwritten.
+/// The rows are buffered until a row group is full, the bodies are read one at a time.
+/// Expired entries are skipped unless [ParquetExportOptions::include_expired] is set.
+pub fn write_parquet(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    output_dir: &Utf8Path,
+    options: ParquetExportOptions,
+) -> Result<ParquetExportCounts, ParquetExportError> {
+    std::fs::create_dir_all(output_dir)?;
+    let properties = || {
+        WriterProperties::builder()
+            .set_compression(options.compression.codec())
+            .set_max_row_group_size(options.row_group_size.get())
+            .build()
+    };
+    let mut pages = BatchWriter::<PageRows>::create(
+        &output_dir.join(PAGES_FILE_NAME),
+        pages_schema(),
+        properties(),
+        options.row_group_size,
+    )?;
+    let mut edges = BatchWriter::<EdgeRows>::create(
+        &output_dir.join(EDGES_FILE_NAME),
+        edges_schema(),
+        properties(),
+        options.row_group_size,
+    )?;
+
+    let now = OffsetDateTime::now_utc();
+    for entry in entries {
+        if !options.include_expired && entry.meta.is_expired_at(now) {
+            continue;
+        }
+        pages.push(|rows| rows.push(&entry))?;
+        if let Some(ref links) = entry.meta.links {
+            let src = entry.meta.url.try_as_str();
+            for link in links {
+                edges.push(|rows| rows.push(&src, link))?;
+            }
+        }
+    }
+
+    Ok(ParquetExportCounts {
+        pages: pages.close()?,
+        edges: edges.close()?,
+    })
+}
+
+/// The builders for the columns of a batch of rows.
+trait Rows: Default {
+    fn len(&self) -> usize;
+    fn finish(&mut self) -> Vec<ArrayRef>;
+}
+
+/// Buffers the rows and writes them as a record batch once [BatchWriter::batch_size] is reached.
+struct BatchWriter<R> {
+    writer: ArrowWriter<BufWriter<File>>,
+    schema: SchemaRef,
+    rows: R,
+    batch_size: usize,
+    written: u64,
+}
+
+impl<R: Rows> BatchWriter<R> {
+    fn create(
+        path: &Utf8Path,
+        schema: SchemaRef,
+        properties: WriterProperties,
+        batch_size: NonZeroUsize,
+    ) -> Result<Self, ParquetExportError> {
+        let file = File::options().write(true).create_new(true).open(path)?;
+        Ok(Self {
+            writer: ArrowWriter::try_new(BufWriter::new(file), schema.clone(), Some(properties))?,
+            schema,
+            rows: R::default(),
+            batch_size: batch_size.get(),
+            written: 0,
+        })
+    }
+
+    /// Appends a row with [append] and writes the batch if it is full.
+    fn push(&mut self, append: impl FnOnce(&mut R)) -> Result<(), ParquetExportError> {
+        append(&mut self.rows);
+        if self.rows.len() >= self.batch_size {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ParquetExportError> {
+        let len = self.rows.len();
+        if len == 0 {
+            return Ok(());
+        }
+        let batch = RecordBatch::try_new(self.schema.clone(), self.rows.finish())?;
+        self.writer.write(&batch)?;
+        self.written += len as u64;
+        Ok(())
+    }
+
+    /// Writes the remaining rows and the footer, returns the number of written rows.
+    fn close(mut self) -> Result<u64, ParquetExportError> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(self.written)
+    }
+}
+
+/// The columns of [crate::schemas::pages_schema].
+struct PageRows {
+    url: StringBuilder,
+    origin: StringBuilder,
+    status: UInt16Builder,
+    format: StringBuilder,
+    language: StringBuilder,
+    timestamp: TimestampMillisecondBuilder,
+    bytes: UInt64Builder,
+    digest: StringBuilder,
+}
+
+impl Default for PageRows {
+    fn default() -> Self {
+        Self {
+            url: StringBuilder::new(),
+            origin: StringBuilder::new(),
+            status: UInt16Builder::new(),
+            format: StringBuilder::new(),
+            language: StringBuilder::new(),
+            timestamp: TimestampMillisecondBuilder::new().with_timezone("UTC"),
+            bytes: UInt64Builder::new(),
+            digest: StringBuilder::new(),
+        }
+    }
+}
+
+impl PageRows {
+    fn push(&mut self, entry: &SlimCrawlResult) {
+        let meta = &entry.meta;
+        self.url.append_value(meta.url.try_as_str());
+        self.origin
+            .append_option(meta.url.atra_origin().map(|origin| origin.to_string()));
+        self.status.append_value(meta.status_code.as_u16());
+        self.format
+            .append_value(meta.file_information.format.to_string());
+        self.language
+            .append_option(meta.language.map(|value| value.lang().to_639_3()));
+        self.timestamp
+            .append_value((meta.created_at.unix_timestamp_nanos() / 1_000_000) as i64);
+        let (bytes, digest) = match body_size_and_digest(entry) {
+            Ok(Some((bytes, digest))) => (Some(bytes), Some(digest)),
+            Ok(None) => (None, None),
+            Err(err) => {
+                log::warn!("Failed to read the body of {}: {err}", meta.url);
+                (None, None)
+            }
+        };
+        self.bytes.append_option(bytes);
+        self.digest
+            .append_option(digest.map(|value| String::from_utf8_lossy(&value).into_owned()));
+    }
+}
+
+impl Rows for PageRows {
+    fn len(&self) -> usize {
+        self.url.len()
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(self.url.finish()),
+            Arc::new(self.origin.finish()),
+            Arc::new(self.status.finish()),
+            Arc::new(self.format.finish()),
+            Arc::new(self.language.finish()),
+            Arc::new(self.timestamp.finish()),
+            Arc::new(self.bytes.finish()),
+            Arc::new(self.digest.finish()),
+        ]
+    }
+}
+
+/// Returns the size and the digest of the stored body of [entry], none if there is no body.
+fn body_size_and_digest(entry: &SlimCrawlResult) -> io::Result<Option<(u64, Vec<u8>)>> {
+    let content = unsafe { entry.get_content() }.map_err(io::Error::other)?;
+    match content {
+        Either::Right(data) => Ok(Some((data.len() as u64, labeled_xxh128_digest(data)))),
+        Either::Left(RawData::InMemory { data }) => {
+            Ok(Some((data.len() as u64, labeled_xxh128_digest(data))))
+        }
+        Either::Left(RawData::ExternalFile { path }) => {
+            let mut digester = LabeledXxh128Digester::default();
+            let bytes = io::copy(
+                &mut BufReader::new(ExternalFileReader::open(path)?),
+                &mut digester,
+            )?;
+            Ok(Some((bytes, digester.finish())))
+        }
+        Either::Left(RawData::None) => Ok(None),
+    }
+}
+
+/// The columns of [crate::schemas::edges_schema].
+#[derive(Default)]
+struct EdgeRows {
+    src: StringBuilder,
+    dst: StringBuilder,
+    kind: StringBuilder,
+    rel: StringBuilder,
+    nofollow: BooleanBuilder,
+    from_error_page: BooleanBuilder,
+}
+
+impl EdgeRows {
+    fn push(&mut self, src: &str, link: &ExtractedLink) {
+        let (kind, url, hint) = match link {
+            ExtractedLink::OnSeed {
+                url,
+                extraction_method,
+            } => ("on_seed", url, extraction_method),
+            ExtractedLink::Outgoing {
+                url,
+                extraction_method,
+            } => ("outgoing", url, extraction_method),
+            ExtractedLink::Data {
+                url,
+                extraction_method,
+                ..
+            } => ("data", url, extraction_method),
+        };
+        let (rel, nofollow) = relation(hint);
+        self.src.append_value(src);
+        self.dst.append_value(url.try_as_str());
+        self.kind.append_value(kind);
+        self.rel.append_option(rel);
+        self.nofollow.append_value(nofollow);
+        self.from_error_page.append_value(hint.from_error_page);
+    }
+}
+
+impl Rows for EdgeRows {
+    fn len(&self) -> usize {
+        self.src.len()
+    }
+
+    fn finish(&mut self) -> Vec<ArrayRef> {
+        vec![
+            Arc::new(self.src.finish()),
+            Arc::new(self.dst.finish()),
+            Arc::new(self.kind.finish()),
+            Arc::new(self.rel.finish()),
+            Arc::new(self.nofollow.finish()),
+            Arc::new(self.from_error_page.finish()),
+        ]
+    }
+}
+
+/// Returns how the link with [hint] was found and if it was marked as nofollow.
+/// A link found in an archive is described by the extractor of the archived file.
+fn relation(hint: &ExtractorMethodHint) -> (Option<&'static str>, bool) {
+    let Some(ref meta) = hint.meta else {
+        return (None, false);
+    };
+    let rel = match meta {
+        ExtractorMethodMeta::Html(origin) => match origin {
+            LinkOrigin::Href => "href",
+            LinkOrigin::HrefNoFollow => return (Some("href"), true),
+            LinkOrigin::Embedded => "embedded",
+            LinkOrigin::Form => "form",
+            LinkOrigin::JavaScript => "javascript",
+            LinkOrigin::JavaScriptEmbedded => "javascript_embedded",
+            LinkOrigin::OnClick => "onclick",
+            LinkOrigin::Style => "style",
+            LinkOrigin::MetaRefresh => "meta_refresh",
+            LinkOrigin::JavaScriptRedirect => "javascript_redirect",
+        },
+        ExtractorMethodMeta::Zip { underlying, .. } => return relation(underlying),
+        ExtractorMethodMeta::Feed(origin) => match origin {
+            FeedLinkOrigin::Link => "link",
+            FeedLinkOrigin::Guid => "guid",
+            FeedLinkOrigin::Enclosure => "enclosure",
+            FeedLinkOrigin::SelfLink => "self",
+        },
+        ExtractorMethodMeta::JavaScript(kind) => match kind {
+            ScriptLinkKind::Href => "href",
+            ScriptLinkKind::Redirect => "javascript_redirect",
+        },
+    };
+    (Some(rel), false)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        write_parquet, ParquetCompression, ParquetExportOptions, EDGES_FILE_NAME, PAGES_FILE_NAME,
+    };
+    use crate::app::view::jsonl::stored_entries;
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::database::open_db;
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::extraction::html::LinkOrigin;
+    use crate::extraction::marker::{ExtractorMethodHint, ExtractorMethodMeta};
+    use crate::extraction::ExtractedLink;
+    use crate::robots::UnavailableAfter;
+    use crate::schemas::{
+        EDGES_PARQUET_NAME, PAGES_PARQUET_NAME, PARQUET_SCHEMA_KEY, PARQUET_VERSION_KEY,
+    };
+    use crate::toolkit::digest::labeled_xxh128_digest;
+    use crate::url::{AtraOriginProvider, UrlWithDepth};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{TimestampMillisecondType, UInt16Type, UInt64Type};
+    use arrow_array::RecordBatch;
+    use arrow_schema::SchemaRef;
+    use camino::Utf8Path;
+    use camino_tempfile::Utf8TempDir;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::collections::HashMap;
+    use std::fs::File;
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+
+    /// Reads the schema, the number of row groups and all batches of the Parquet file at [path].
+    fn read_parquet(path: &Utf8Path) -> (SchemaRef, usize, Vec<RecordBatch>) {
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap()).unwrap();
+        let schema = builder.schema().clone();
+        let row_groups = builder.metadata().num_row_groups();
+        let batches = builder
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        (schema, row_groups, batches)
+    }
+
+    /// Stores a page in memory, one in an external file, one without a body
+    /// and an expired page in the crawl db of a fresh session.
+    fn create_session(root: &Utf8Path) -> (Config, Vec<u8>, Vec<u8>) {
+        let mut config = Config::default();
+        config.paths.root = root.to_path_buf();
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, &config).unwrap();
+
+        let in_memory_body = b"<html><body>in memory</body></html>".to_vec();
+        let external_body = b"<html><body>external</body></html>".repeat(100);
+        let external_path = root.join("external.dat");
+        std::fs::write(&external_path, &external_body).unwrap();
+
+        let url = |value: &str| UrlWithDepth::from_url(value).unwrap();
+        let mut without_body = SlimCrawlResult::new(
+            &create_test_data(url("https://www.example.com/none"), None),
+            StoredDataHint::None,
+        );
+        without_body
+            .meta
+            .links
+            .as_mut()
+            .unwrap()
+            .insert(ExtractedLink::Outgoing {
+                url: url("https://www.example.org/sponsored"),
+                extraction_method: ExtractorMethodHint::new_with_meta(
+                    ExtractorMethod::HtmlV1,
+                    ExtractorMethodMeta::Html(LinkOrigin::HrefNoFollow),
+                ),
+            });
+        let mut expired = SlimCrawlResult::new(
+            &create_test_data(url("https://www.example.com/expired"), None),
+            StoredDataHint::None,
+        );
+        expired.meta.unavailable_after = Some(UnavailableAfter::parse("2000-01-01T00:00:00Z"));
+
+        for entry in [
+            SlimCrawlResult::new(
+                &create_test_data(url("https://www.example.com/"), None),
+                StoredDataHint::InMemory(in_memory_body.clone()),
+            ),
+            SlimCrawlResult::new(
+                &create_test_data(url("https://www.example.com/external"), None),
+                StoredDataHint::External(external_path),
+            ),
+            without_body,
+            expired,
+        ] {
+            crawl_db.add(&entry).unwrap();
+        }
+        (config, in_memory_body, external_body)
+    }
+
+    #[test]
+    fn the_exported_files_round_trip() {
+        let root = Utf8TempDir::new().unwrap();
+        let (config, in_memory_body, external_body) = create_session(root.path());
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, &config).unwrap();
+
+        let output = root.path().join("export");
+        let options = ParquetExportOptions {
+            row_group_size: NonZeroUsize::new(2).unwrap(),
+            ..ParquetExportOptions::default()
+        };
+        let counts = write_parquet(stored_entries(&crawl_db), &output, options).unwrap();
+        assert_eq!(3, counts.pages);
+        assert_eq!(10, counts.edges);
+
+        let (schema, row_groups, batches) = read_parquet(&output.join(PAGES_FILE_NAME));
+        assert_eq!(PAGES_PARQUET_NAME, schema.metadata()[PARQUET_SCHEMA_KEY]);
+        assert_eq!("1.0", schema.metadata()[PARQUET_VERSION_KEY]);
+        assert_eq!(2, row_groups);
+        let mut pages = HashMap::new();
+        for batch in &batches {
+            let url = batch.column_by_name("url").unwrap().as_string::<i32>();
+            let origin = batch.column_by_name("origin").unwrap().as_string::<i32>();
+            let status = batch
+                .column_by_name("status")
+                .unwrap()
+                .as_primitive::<UInt16Type>();
+            let format = batch.column_by_name("format").unwrap().as_string::<i32>();
+            let language = batch.column_by_name("language").unwrap().as_string::<i32>();
+            let timestamp = batch
+                .column_by_name("timestamp")
+                .unwrap()
+                .as_primitive::<TimestampMillisecondType>();
+            let bytes = batch
+                .column_by_name("bytes")
+                .unwrap()
+                .as_primitive::<UInt64Type>();
+            let digest = batch.column_by_name("digest").unwrap().as_string::<i32>();
+            for i in 0..batch.num_rows() {
+                pages.insert(
+                    url.value(i).to_string(),
+                    (
+                        origin.value(i).to_string(),
+                        status.value(i),
+                        format.value(i).to_string(),
+                        language.value(i).to_string(),
+                        timestamp.value(i),
+                        bytes.is_valid(i).then(|| bytes.value(i)),
+                        digest.is_valid(i).then(|| digest.value(i).to_string()),
+                    ),
+                );
+            }
+        }
+        assert_eq!(3, pages.len());
+        assert!(!pages.contains_key("https://www.example.com/expired"));
+
+        for (url, body) in [
+            ("https://www.example.com/", Some(in_memory_body)),
+            ("https://www.example.com/external", Some(external_body)),
+            ("https://www.example.com/none", None),
+        ] {
+            let stored = crawl_db
+                .get(&UrlWithDepth::from_url(url).unwrap())
+                .unwrap()
+                .unwrap();
+            let (origin, status, format, language, timestamp, bytes, digest) = &pages[url];
+            assert_eq!(&stored.meta.url.atra_origin().unwrap().to_string(), origin);
+            assert_eq!(stored.meta.status_code.as_u16(), *status);
+            assert_eq!(stored.meta.file_information.format.to_string(), *format);
+            assert_eq!("deu", language);
+            assert_eq!(
+                (stored.meta.created_at.unix_timestamp_nanos() / 1_000_000) as i64,
+                *timestamp
+            );
+            assert_eq!(body.as_ref().map(|value| value.len() as u64), *bytes);
+            assert_eq!(
+                body.map(|value| String::from_utf8(labeled_xxh128_digest(value)).unwrap()),
+                *digest
+            );
+        }
+
+        let (schema, row_groups, batches) = read_parquet(&output.join(EDGES_FILE_NAME));
+        assert_eq!(EDGES_PARQUET_NAME, schema.metadata()[PARQUET_SCHEMA_KEY]);
+        assert_eq!(5, row_groups);
+        let mut edges = Vec::new();
+        for batch in &batches {
+            let src = batch.column_by_name("src").unwrap().as_string::<i32>();
+            let dst = batch.column_by_name("dst").unwrap().as_string::<i32>();
+            let kind = batch.column_by_name("kind").unwrap().as_string::<i32>();
+            let rel = batch.column_by_name("rel").unwrap().as_string::<i32>();
+            let nofollow = batch.column_by_name("nofollow").unwrap().as_boolean();
+            let from_error_page = batch
+                .column_by_name("from_error_page")
+                .unwrap()
+                .as_boolean();
+            for i in 0..batch.num_rows() {
+                assert!(!from_error_page.value(i));
+                edges.push((
+                    src.value(i).to_string(),
+                    dst.value(i).to_string(),
+                    kind.value(i).to_string(),
+                    rel.is_valid(i).then(|| rel.value(i).to_string()),
+                    nofollow.value(i),
+                ));
+            }
+        }
+        assert_eq!(10, edges.len());
+        assert!(edges.contains(&(
+            "https://www.example.com/none".to_string(),
+            "https://www.example.org/sponsored".to_string(),
+            "outgoing".to_string(),
+            Some("href".to_string()),
+            true,
+        )));
+        assert!(edges.contains(&(
+            "https://www.example.com/".to_string(),
+            "https://www.google.de/1".to_string(),
+            "on_seed".to_string(),
+            None,
+            false,
+        )));
+        assert_eq!(1, edges.iter().filter(|edge| edge.4).count());
+    }
+
+    #[test]
+    fn every_codec_can_be_read_back() {
+        let root = Utf8TempDir::new().unwrap();
+        let (config, _, _) = create_session(root.path());
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, &config).unwrap();
+
+        for compression in [
+            ParquetCompression::Uncompressed,
+            ParquetCompression::Snappy,
+            ParquetCompression::Gzip,
+            ParquetCompression::Lz4,
+            ParquetCompression::Zstd,
+        ] {
+            let output = root.path().join(format!("{compression:?}"));
+            let options = ParquetExportOptions {
+                compression,
+                include_expired: true,
+                ..ParquetExportOptions::default()
+            };
+            let counts = write_parquet(stored_entries(&crawl_db), &output, options).unwrap();
+            assert_eq!(4, counts.pages);
+            let (_, row_groups, batches) = read_parquet(&output.join(PAGES_FILE_NAME));
+            assert_eq!(1, row_groups);
+            let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+            assert_eq!(4, rows);
+            let (_, _, batches) = read_parquet(&output.join(EDGES_FILE_NAME));
+            let rows: usize = batches.iter().map(|batch| batch.num_rows()).sum();
+            assert_eq!(counts.edges as usize, rows);
+        }
+    }
+}
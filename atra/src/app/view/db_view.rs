@@ -34,7 +34,7 @@ impl Display for SlimEntry {
 impl From<(Box<[u8]>, Box<[u8]>)> for SlimEntry {
     fn from((k, v): (Box<[u8]>, Box<[u8]>)) -> Self {
         let k: AtraUri = String::from_utf8_lossy(k.as_ref()).parse().unwrap();
-        let v: SlimCrawlResult = bincode::deserialize(v.as_ref()).unwrap();
+        let v = SlimCrawlResult::decode(v.as_ref()).unwrap();
         Self(Arc::new((k, v)))
     }
 }
@@ -0,0 +1,739 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::requeue::parse_rfc3339;
+use crate::app::view::jsonl::stored_entries;
+use crate::contexts::local::LocalContext;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{CrawlResultMeta, SlimCrawlResult};
+use crate::url::AtraOriginProvider;
+use nom::branch::alt;
+use nom::bytes::complete::{tag, take_while, take_while1};
+use nom::character::complete::{digit1, multispace0};
+use nom::combinator::cut;
+use nom::error::{ErrorKind, ParseError};
+use nom::multi::fold_many0;
+use nom::sequence::{delimited, preceded};
+use nom::IResult;
+use regex::Regex;
+use std::borrow::Cow;
+use std::io::Write;
+use std::str::FromStr;
+use thiserror::Error;
+use time::{Date, Month, OffsetDateTime};
+
+/// A filter expression of the view like
+/// `status >= 400 && format == 'HTML' && origin ~ 'example.de'`.
+///
+/// Comparisons are combined with `&&`, `||`, `!` and parentheses, `&&` binds stronger than `||`.
+/// The fields `status` and `depth` are compared with numbers, `created_at` with an ISO date
+/// like `'2024-06-25'` or `'2024-06-25T15:00:00Z'`. The fields `format`, `mime`, `language`,
+/// `url` and `origin` support `==`, `!=`, `contains` and `~` for a regex.
+#[derive(Debug, Clone)]
+pub struct ViewFilter {
+    expression: Expression,
+}
+
+impl ViewFilter {
+    /// Returns true iff [entry] is selected by the filter.
+    pub fn matches(&self, entry: &SlimCrawlResult) -> bool {
+        self.expression.matches(&entry.meta)
+    }
+}
+
+impl FromStr for ViewFilter {
+    type Err = ViewFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let failure = match expression(s) {
+            Ok((rest, expression)) => {
+                let (rest, _) = multispace0::<_, Failure>(rest).unwrap_or((rest, ""));
+                if rest.is_empty() {
+                    return Ok(Self { expression });
+                }
+                Failure::new(rest, "Expected `&&`, `||` or the end of the filter")
+            }
+            Err(nom::Err::Error(failure)) | Err(nom::Err::Failure(failure)) => failure,
+            Err(nom::Err::Incomplete(_)) => Failure::new("", "The filter is incomplete"),
+        };
+        let offset = s.len() - failure.input.len();
+        Err(ViewFilterError {
+            message: failure.message.into_owned(),
+            filter: s.to_string(),
+            column: s[..offset].chars().count(),
+        })
+    }
+}
+
+/// A filter that can not be parsed, displayed with a caret below the [column].
+#[derive(Debug, Error)]
+#[error("{message} at column {}:\n    {filter}\n    {}^", .column + 1, padding(.column))]
+pub struct ViewFilterError {
+    pub message: String,
+    pub filter: String,
+    /// The zero based position of the error in characters.
+    pub column: usize,
+}
+
+fn padding(column: &usize) -> String {
+    " ".repeat(*column)
+}
+
+/// Iterates the entries of [crawl_db] selected by [filter], all entries if it is None.
+pub(super) fn filtered_entries<'a>(
+    crawl_db: &'a CrawlDB,
+    filter: Option<&'a ViewFilter>,
+) -> impl Iterator<Item = SlimCrawlResult> + 'a {
+    stored_entries(crawl_db)
+        .filter(move |entry| filter.map_or(true, |filter| filter.matches(entry)))
+}
+
+/// Lists the entries of the crawl db of [local] selected by [filter] with
+/// [write_matching_pages]. Returns the number of written entries.
+pub fn export_matching_pages<W: Write>(
+    local: &LocalContext,
+    filter: &ViewFilter,
+    writer: W,
+    include_expired: bool,
+) -> std::io::Result<usize> {
+    write_matching_pages(
+        filtered_entries(local.crawl_db(), Some(filter)),
+        writer,
+        include_expired,
+    )
+}
+
+/// Writes the status code, the format and the url of every entry as a single line to [writer].
+/// Expired entries are skipped unless [include_expired] is set.
+/// Returns the number of written entries.
+fn write_matching_pages<W: Write>(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    mut writer: W,
+    include_expired: bool,
+) -> std::io::Result<usize> {
+    let now = OffsetDateTime::now_utc();
+    let mut written = 0usize;
+    for entry in entries {
+        let meta = &entry.meta;
+        if !include_expired && meta.is_expired_at(now) {
+            continue;
+        }
+        writeln!(
+            writer,
+            "{}\t{}\t{}",
+            meta.status_code.as_u16(),
+            meta.file_information.format,
+            meta.url.try_as_str()
+        )?;
+        written += 1;
+    }
+    writer.flush()?;
+    Ok(written)
+}
+
+#[derive(Debug, Clone)]
+enum Expression {
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Not(Box<Expression>),
+    Condition(Condition),
+}
+
+impl Expression {
+    fn matches(&self, meta: &CrawlResultMeta) -> bool {
+        match self {
+            Expression::And(a, b) => a.matches(meta) && b.matches(meta),
+            Expression::Or(a, b) => a.matches(meta) || b.matches(meta),
+            Expression::Not(value) => !value.matches(meta),
+            Expression::Condition(condition) => condition.matches(meta),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Field {
+    Status,
+    Depth,
+    CreatedAt,
+    Format,
+    Mime,
+    Language,
+    Url,
+    Origin,
+}
+
+impl Field {
+    const NAMES: [(&'static str, Field); 8] = [
+        ("status", Field::Status),
+        ("depth", Field::Depth),
+        ("created_at", Field::CreatedAt),
+        ("format", Field::Format),
+        ("mime", Field::Mime),
+        ("language", Field::Language),
+        ("url", Field::Url),
+        ("origin", Field::Origin),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .find(|(value, _)| *value == name)
+            .map(|(_, field)| *field)
+    }
+
+    fn name(self) -> &'static str {
+        Self::NAMES
+            .iter()
+            .find(|(_, field)| *field == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    /// The formats, mimes and languages are compared ignoring their case.
+    fn ignores_case(self) -> bool {
+        matches!(self, Field::Format | Field::Mime | Field::Language)
+    }
+
+    /// The values of a textual field of [meta], empty if the value is missing.
+    fn values(self, meta: &CrawlResultMeta) -> Vec<Cow<str>> {
+        match self {
+            Field::Format => vec![Cow::Owned(meta.file_information.format.to_string())],
+            Field::Mime => meta
+                .file_information
+                .mime
+                .iter()
+                .flat_map(|mime| mime.iter())
+                .map(|value| Cow::Borrowed(value.essence_str()))
+                .collect(),
+            Field::Language => meta
+                .language
+                .iter()
+                .flat_map(|value| {
+                    let lang = value.lang();
+                    std::iter::once(lang.to_639_3()).chain(lang.to_639_1())
+                })
+                .map(Cow::Borrowed)
+                .collect(),
+            Field::Url => vec![meta.url.try_as_str()],
+            Field::Origin => meta
+                .url
+                .atra_origin()
+                .map(|value| Cow::Owned(value.to_string()))
+                .into_iter()
+                .collect(),
+            Field::Status | Field::Depth | Field::CreatedAt => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparison {
+    fn apply<T: Ord>(self, a: T, b: T) -> bool {
+        match self {
+            Comparison::Eq => a == b,
+            Comparison::Ne => a != b,
+            Comparison::Lt => a < b,
+            Comparison::Le => a <= b,
+            Comparison::Gt => a > b,
+            Comparison::Ge => a >= b,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operator {
+    Compare(Comparison),
+    Contains,
+    Regex,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Compare(Comparison::Eq) => "==",
+            Operator::Compare(Comparison::Ne) => "!=",
+            Operator::Compare(Comparison::Lt) => "<",
+            Operator::Compare(Comparison::Le) => "<=",
+            Operator::Compare(Comparison::Gt) => ">",
+            Operator::Compare(Comparison::Ge) => ">=",
+            Operator::Contains => "contains",
+            Operator::Regex => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum Literal {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Debug, Clone)]
+enum TextMatch {
+    Equals(String),
+    NotEquals(String),
+    Contains(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+enum Condition {
+    Number(Field, Comparison, u64),
+    CreatedAt(Comparison, OffsetDateTime),
+    Text(Field, TextMatch),
+}
+
+impl Condition {
+    /// Checks if [operator] and [literal] are applicable to [field].
+    /// Returns the message of the error and if it refers to the literal otherwise.
+    fn new(field: Field, operator: Operator, literal: Literal) -> Result<Self, (String, bool)> {
+        let unsupported = || {
+            let supported = match field {
+                Field::Status | Field::Depth | Field::CreatedAt => {
+                    "`==`, `!=`, `<`, `<=`, `>`, `>=`"
+                }
+                _ => "`==`, `!=`, `contains`, `~`",
+            };
+            Err((
+                format!(
+                    "`{}` is not supported for `{}`, use one of {supported}",
+                    operator.symbol(),
+                    field.name()
+                ),
+                false,
+            ))
+        };
+        match field {
+            Field::Status | Field::Depth => {
+                let Operator::Compare(comparison) = operator else {
+                    return unsupported();
+                };
+                match literal {
+                    Literal::Number(value) => Ok(Condition::Number(field, comparison, value)),
+                    Literal::Text(_) => {
+                        Err((format!("Expected a number for `{}`", field.name()), true))
+                    }
+                }
+            }
+            Field::CreatedAt => {
+                let Operator::Compare(comparison) = operator else {
+                    return unsupported();
+                };
+                match literal {
+                    Literal::Text(value) => match parse_date(&value) {
+                        Some(value) => Ok(Condition::CreatedAt(comparison, value)),
+                        None => Err((
+                            format!("Expected an ISO date like '2024-06-25' but got '{value}'"),
+                            true,
+                        )),
+                    },
+                    Literal::Number(_) => Err((
+                        "Expected a quoted ISO date like '2024-06-25' for `created_at`".to_string(),
+                        true,
+                    )),
+                }
+            }
+            _ => {
+                let Literal::Text(value) = literal else {
+                    return Err((
+                        format!("Expected a quoted string for `{}`", field.name()),
+                        true,
+                    ));
+                };
+                let matcher = match operator {
+                    Operator::Compare(Comparison::Eq) => TextMatch::Equals(value),
+                    Operator::Compare(Comparison::Ne) => TextMatch::NotEquals(value),
+                    Operator::Contains => TextMatch::Contains(value),
+                    Operator::Regex => match Regex::new(&value) {
+                        Ok(regex) => TextMatch::Regex(regex),
+                        Err(err) => return Err((format!("Invalid regex: {err}"), true)),
+                    },
+                    Operator::Compare(_) => return unsupported(),
+                };
+                Ok(Condition::Text(field, matcher))
+            }
+        }
+    }
+
+    fn matches(&self, meta: &CrawlResultMeta) -> bool {
+        match self {
+            Condition::Number(Field::Status, comparison, value) => {
+                comparison.apply(meta.status_code.as_u16() as u64, *value)
+            }
+            Condition::Number(_, comparison, value) => {
+                comparison.apply(meta.url.depth().depth_on_website, *value)
+            }
+            Condition::CreatedAt(comparison, value) => comparison.apply(meta.created_at, *value),
+            Condition::Text(field, matcher) => {
+                let values = field.values(meta);
+                let equals = |expected: &str| {
+                    values.iter().any(|value| {
+                        if field.ignores_case() {
+                            value.eq_ignore_ascii_case(expected)
+                        } else {
+                            value == expected
+                        }
+                    })
+                };
+                match matcher {
+                    TextMatch::Equals(expected) => equals(expected),
+                    TextMatch::NotEquals(expected) => !equals(expected),
+                    TextMatch::Contains(expected) => {
+                        values.iter().any(|value| value.contains(expected.as_str()))
+                    }
+                    TextMatch::Regex(regex) => values.iter().any(|value| regex.is_match(value)),
+                }
+            }
+        }
+    }
+}
+
+/// Parses an RFC 3339 timestamp or a date like `2024-06-25`, the latter at midnight UTC.
+fn parse_date(value: &str) -> Option<OffsetDateTime> {
+    if let Ok(value) = parse_rfc3339(value) {
+        return Some(value);
+    }
+    let mut parts = value.split('-');
+    let year = parts.next()?.parse::<i32>().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse::<u8>().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(
+        Date::from_calendar_date(year, month, day)
+            .ok()?
+            .midnight()
+            .assume_utc(),
+    )
+}
+
+/// A parse error with the remaining [input] at the position of the error.
+#[derive(Debug)]
+struct Failure<'a> {
+    input: &'a str,
+    message: Cow<'static, str>,
+}
+
+impl<'a> Failure<'a> {
+    fn new(input: &'a str, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            input,
+            message: message.into(),
+        }
+    }
+}
+
+impl<'a> ParseError<&'a str> for Failure<'a> {
+    fn from_error_kind(input: &'a str, _: ErrorKind) -> Self {
+        Self::new(input, "Unexpected input")
+    }
+
+    fn append(_: &'a str, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    /// Keeps the error that got further.
+    fn or(self, other: Self) -> Self {
+        if other.input.len() < self.input.len() {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+type FilterResult<'a, O> = IResult<&'a str, O, Failure<'a>>;
+
+fn fail<O>(input: &str, message: impl Into<Cow<'static, str>>) -> FilterResult<O> {
+    Err(nom::Err::Failure(Failure::new(input, message)))
+}
+
+/// Parses a disjunction of conjunctions.
+fn expression(input: &str) -> FilterResult<Expression> {
+    let (input, first) = conjunction(input)?;
+    fold_many0(
+        preceded(
+            delimited(multispace0, tag("||"), multispace0),
+            cut(conjunction),
+        ),
+        move || first.clone(),
+        |a, b| Expression::Or(Box::new(a), Box::new(b)),
+    )(input)
+}
+
+fn conjunction(input: &str) -> FilterResult<Expression> {
+    let (input, first) = unary(input)?;
+    fold_many0(
+        preceded(delimited(multispace0, tag("&&"), multispace0), cut(unary)),
+        move || first.clone(),
+        |a, b| Expression::And(Box::new(a), Box::new(b)),
+    )(input)
+}
+
+fn unary(input: &str) -> FilterResult<Expression> {
+    let (input, _) = multispace0(input)?;
+    if let Some(rest) = input.strip_prefix('!') {
+        let (rest, value) = unary(rest)?;
+        return Ok((rest, Expression::Not(Box::new(value))));
+    }
+    if let Some(rest) = input.strip_prefix('(') {
+        let (rest, value) = expression(rest)?;
+        let (rest, _) = multispace0(rest)?;
+        return match rest.strip_prefix(')') {
+            Some(rest) => Ok((rest, value)),
+            None => fail(rest, "Expected `)`"),
+        };
+    }
+    condition(input)
+}
+
+fn condition(input: &str) -> FilterResult<Expression> {
+    let Ok((rest, name)) =
+        take_while1::<_, _, Failure>(|c: char| c.is_ascii_alphanumeric() || c == '_')(input)
+    else {
+        return fail(input, "Expected a field, `!` or `(`");
+    };
+    let Some(field) = Field::from_name(name) else {
+        let names = Field::NAMES.map(|(name, _)| name).join("`, `");
+        return fail(
+            input,
+            format!("Unknown field `{name}`, expected one of `{names}`"),
+        );
+    };
+    let (operator_start, _) = multispace0(rest)?;
+    let Ok((rest, operator)) = operator(operator_start) else {
+        return fail(operator_start, "Expected an operator like `==`, `<` or `~`");
+    };
+    let (literal_start, _) = multispace0(rest)?;
+    let (rest, literal) = literal(literal_start)?;
+    match Condition::new(field, operator, literal) {
+        Ok(condition) => Ok((rest, Expression::Condition(condition))),
+        Err((message, false)) => fail(operator_start, message),
+        Err((message, true)) => fail(literal_start, message),
+    }
+}
+
+fn operator(input: &str) -> FilterResult<Operator> {
+    let (rest, symbol) = alt((
+        tag("=="),
+        tag("!="),
+        tag("<="),
+        tag(">="),
+        tag("<"),
+        tag(">"),
+        tag("~"),
+        tag("contains"),
+    ))(input)?;
+    let operator = match symbol {
+        "==" => Operator::Compare(Comparison::Eq),
+        "!=" => Operator::Compare(Comparison::Ne),
+        "<=" => Operator::Compare(Comparison::Le),
+        ">=" => Operator::Compare(Comparison::Ge),
+        "<" => Operator::Compare(Comparison::Lt),
+        ">" => Operator::Compare(Comparison::Gt),
+        "~" => Operator::Regex,
+        _ => Operator::Contains,
+    };
+    Ok((rest, operator))
+}
+
+/// Parses a number or a string in single or double quotes.
+fn literal(input: &str) -> FilterResult<Literal> {
+    if let Ok((rest, digits)) = digit1::<_, Failure>(input) {
+        return match digits.parse() {
+            Ok(value) => Ok((rest, Literal::Number(value))),
+            Err(_) => fail(input, "The number is too large"),
+        };
+    }
+    for quote in ['\'', '"'] {
+        if let Some(rest) = input.strip_prefix(quote) {
+            let (rest, value) = take_while(|c: char| c != quote)(rest)?;
+            return match rest.strip_prefix(quote) {
+                Some(rest) => Ok((rest, Literal::Text(value.to_string()))),
+                None => fail(input, "The string is not terminated"),
+            };
+        }
+    }
+    fail(input, "Expected a number or a quoted string")
+}
+
+#[cfg(test)]
+mod test {
+    use super::{filtered_entries, write_matching_pages, ViewFilter, ViewFilterError};
+    use crate::app::view::jsonl::{write_jsonl, JsonlOptions};
+    use crate::config::Config;
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::database::open_db;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::StatusCode;
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use time::{Date, Month};
+
+    fn entry(url: &str, status: StatusCode) -> SlimCrawlResult {
+        let mut entry = SlimCrawlResult::new(
+            &create_test_data(UrlWithDepth::from_url(url).unwrap(), None),
+            StoredDataHint::None,
+        );
+        entry.meta.status_code = status;
+        entry
+    }
+
+    fn matches(filter: &str, entry: &SlimCrawlResult) -> bool {
+        ViewFilter::from_str(filter).unwrap().matches(entry)
+    }
+
+    fn parse_error(filter: &str) -> ViewFilterError {
+        ViewFilter::from_str(filter).unwrap_err()
+    }
+
+    #[test]
+    fn compares_the_fields() {
+        let mut page = entry("https://www.example.de/news/1", StatusCode::NOT_FOUND);
+        page.meta.created_at = Date::from_calendar_date(2024, Month::June, 25)
+            .unwrap()
+            .with_hms(15, 0, 0)
+            .unwrap()
+            .assume_utc();
+
+        assert!(matches("status >= 400", &page));
+        assert!(matches("status == 404", &page));
+        assert!(!matches("status < 400", &page));
+        assert!(matches("depth == 0", &page));
+        assert!(matches("format == 'html'", &page));
+        assert!(!matches("format != \"HTML\"", &page));
+        assert!(matches("language == 'deu' && language == 'DE'", &page));
+        assert!(!matches("mime == 'text/html'", &page));
+        assert!(matches("mime != 'text/html'", &page));
+        assert!(matches("url contains '/news/'", &page));
+        assert!(matches("url ~ '/news/[0-9]+$'", &page));
+        assert!(matches("origin ~ 'example.de'", &page));
+        assert!(!matches("origin == 'example.de'", &page));
+        assert!(matches("created_at >= '2024-06-25'", &page));
+        assert!(matches("created_at < '2024-06-26'", &page));
+        assert!(!matches("created_at > '2024-06-25T15:00:00Z'", &page));
+    }
+
+    #[test]
+    fn combines_the_conditions() {
+        let page = entry("https://www.example.de/", StatusCode::OK);
+        assert!(matches(
+            "status == 404 || status == 200 && depth == 0",
+            &page
+        ));
+        assert!(!matches(
+            "(status == 404 || status == 200) && depth == 1",
+            &page
+        ));
+        assert!(matches("!(status >= 400)", &page));
+        assert!(matches("!!status == 200", &page));
+        assert!(!matches("!status == 200 || url contains 'ebay'", &page));
+        assert!(matches("  ( status==200&&format=='HTML' )  ", &page));
+    }
+
+    #[test]
+    fn reports_the_position_of_an_error() {
+        let err = parse_error("status >= 'abc'");
+        assert_eq!(10, err.column);
+        assert_eq!("Expected a number for `status`", err.message);
+        assert_eq!(
+            "Expected a number for `status` at column 11:\n    status >= 'abc'\n              ^",
+            err.to_string()
+        );
+
+        let err = parse_error("status >= 400 && stat == 1");
+        assert_eq!(17, err.column);
+        assert!(err.message.starts_with("Unknown field `stat`"));
+
+        assert_eq!(16, parse_error("status >= 400 &&").column);
+        assert_eq!(14, parse_error("(status == 200").column);
+        assert_eq!("Expected `)`", parse_error("(status == 200").message);
+        assert_eq!(14, parse_error("status == 200 status == 404").column);
+        assert_eq!(7, parse_error("status = 200").column);
+        assert_eq!(4, parse_error("url < 'a'").column);
+        assert_eq!(6, parse_error("url ~ '('").column);
+        assert!(parse_error("url ~ '('")
+            .message
+            .starts_with("Invalid regex"));
+        assert_eq!(7, parse_error("url == 'open").column);
+        assert_eq!(14, parse_error("created_at >= '25.06.2024'").column);
+        assert_eq!(0, parse_error("").column);
+        // The column counts characters and not bytes.
+        assert_eq!(14, parse_error("url == 'ä' && ?").column);
+    }
+
+    #[test]
+    fn filters_a_populated_store_while_streaming() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.paths.root = root.path().to_path_buf();
+        let db = Arc::new(open_db(config.paths.dir_database()).unwrap());
+        let crawl_db = CrawlDB::new(db, &config).unwrap();
+
+        let mut pdf = entry("https://www.example.de/missing.pdf", StatusCode::NOT_FOUND);
+        pdf.meta.file_information.format = InterpretedProcessibleFileFormat::PDF;
+        for entry in [
+            entry("https://www.example.de/", StatusCode::OK),
+            entry("https://www.example.de/missing", StatusCode::NOT_FOUND),
+            entry(
+                "https://www.example.com/error",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            pdf,
+        ] {
+            crawl_db.add(&entry).unwrap();
+        }
+
+        let filter =
+            ViewFilter::from_str("status >= 400 && format == 'HTML' && origin ~ 'example.de'")
+                .unwrap();
+        let mut out = Vec::new();
+        let written = write_jsonl(
+            filtered_entries(&crawl_db, Some(&filter)),
+            &mut out,
+            JsonlOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(1, written);
+        let line: serde_json::Value =
+            serde_json::from_str(String::from_utf8(out).unwrap().trim()).unwrap();
+        assert_eq!("https://www.example.de/missing", line["url"]);
+
+        let filter = ViewFilter::from_str("status >= 400").unwrap();
+        let mut out = Vec::new();
+        let written =
+            write_matching_pages(filtered_entries(&crawl_db, Some(&filter)), &mut out, false)
+                .unwrap();
+        assert_eq!(3, written);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("500\tHTML\thttps://www.example.com/error\n"));
+        assert!(out.contains("404\tPDF\thttps://www.example.de/missing.pdf\n"));
+
+        assert_eq!(4, filtered_entries(&crawl_db, None).count());
+    }
+}
@@ -0,0 +1,635 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::view::filter::{filtered_entries, ViewFilter};
+use crate::contexts::local::LocalContext;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::{AuxiliaryDocument, FetchTimings, SlimCrawlResult, StoredDataHint};
+use crate::data::RawData;
+use crate::schemas::{
+    PageFetchTimings, PageHeaderFidelity, PageInternals, PageLanguage, PagePublication,
+    PageRecord, PageTls, PageUnavailableAfter, PageWarcPointer, VersionedSchema,
+};
+use crate::toolkit::text_normalization::{normalize_text, TextNormalizationConfig, TextProvenance};
+use crate::warc_ext::{WarcSkipInstruction, WarcSkipPointerWithPath};
+use clap::ValueEnum;
+use encoding_rs::Encoding;
+use rocksdb::IteratorMode;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::io::Write;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+/// The output format of the view.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, ValueEnum)]
+pub enum ViewFormat {
+    /// Human readable output, interactive if the terminal supports it.
+    #[default]
+    Text,
+    /// One JSON object per stored crawl result.
+    Jsonl,
+}
+
+/// Which optional fields are written for every entry.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct JsonlOptions {
+    pub internals: bool,
+    pub extracted_links: bool,
+    pub headers: bool,
+    /// Also export the entries whose `unavailable_after` lies in the past.
+    pub include_expired: bool,
+    /// Export the decoded text of the body, normalized with this config.
+    pub text: Option<TextNormalizationConfig>,
+}
+
+/// Streams the crawl db of [local] to [writer], one line per entry selected by [filter].
+/// Entries that can not be deserialized are skipped.
+/// Returns the number of written entries.
+pub fn export_jsonl<W: Write>(
+    local: &LocalContext,
+    writer: W,
+    options: JsonlOptions,
+    filter: Option<&ViewFilter>,
+) -> Result<usize, serde_json::Error> {
+    write_jsonl(filtered_entries(local.crawl_db(), filter), writer, options)
+}
+
+/// Iterates [crawl_db] one entry at a time.
+/// Entries that can not be deserialized are skipped.
+pub(super) fn stored_entries(crawl_db: &CrawlDB) -> impl Iterator<Item = SlimCrawlResult> + '_ {
+    crawl_db
+        .iter(IteratorMode::Start)
+        .filter_map(|value| value.ok())
+        .filter_map(|(k, v)| match SlimCrawlResult::decode(v.as_ref()) {
+            Ok(value) => Some(value),
+            Err(err) => {
+                log::warn!(
+                    "Failed to deserialize data from {} with: {err}",
+                    String::from_utf8_lossy(k.as_ref())
+                );
+                None
+            }
+        })
+}
+
+/// Writes every entry as a single line to [writer] and flushes after each line,
+/// an interrupted export always leaves complete lines behind.
+/// Expired entries are skipped unless [JsonlOptions::include_expired] is set.
+pub fn write_jsonl<W: Write>(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    mut writer: W,
+    options: JsonlOptions,
+) -> Result<usize, serde_json::Error> {
+    let now = OffsetDateTime::now_utc();
+    let mut written = 0usize;
+    for entry in entries {
+        if !options.include_expired && entry.meta.is_expired_at(now) {
+            continue;
+        }
+        serde_json::to_writer(&mut writer, &page_record(&entry, options))?;
+        writer.write_all(b"\n").map_err(serde_json::Error::io)?;
+        writer.flush().map_err(serde_json::Error::io)?;
+        written += 1;
+    }
+    Ok(written)
+}
+
+fn fetch_timings(value: &FetchTimings) -> PageFetchTimings {
+    let millis = |value: Duration| value.as_seconds_f64() * 1000.0;
+    PageFetchTimings {
+        started_at: format_date(value.started_at),
+        total: millis(value.total),
+        blocked: value.blocked.map(millis),
+        dns: value.dns.map(millis),
+        connect: value.connect.map(millis),
+        ssl: value.ssl.map(millis),
+        send: value.send.map(millis),
+        wait: value.wait.map(millis),
+        receive: value.receive.map(millis),
+    }
+}
+
+fn warc_pointer(value: &WarcSkipPointerWithPath) -> PageWarcPointer {
+    PageWarcPointer {
+        path: value.path().to_string(),
+        file_offset: value.pointer().file_offset(),
+        warc_header_octet_count: value.pointer().warc_header_octet_count(),
+        body_octet_count: value.pointer().body_octet_count(),
+    }
+}
+
+fn internals(hint: &StoredDataHint) -> PageInternals {
+    let mut internals = PageInternals {
+        stored_as: "none".to_string(),
+        external_path: None,
+        in_memory_size: None,
+        header_signature_octet_count: None,
+        is_base64: None,
+    };
+    match hint {
+        StoredDataHint::External(path) => {
+            internals.stored_as = "external".to_string();
+            internals.external_path = Some(path.to_string());
+        }
+        StoredDataHint::Warc(WarcSkipInstruction::Single {
+            header_signature_octet_count,
+            ..
+        }) => {
+            internals.stored_as = "warc".to_string();
+            internals.header_signature_octet_count = Some(*header_signature_octet_count);
+        }
+        StoredDataHint::Warc(WarcSkipInstruction::Multiple {
+            header_signature_octet_count,
+            is_base64,
+            ..
+        }) => {
+            internals.stored_as = "warc".to_string();
+            internals.header_signature_octet_count = Some(*header_signature_octet_count);
+            internals.is_base64 = Some(*is_base64);
+        }
+        StoredDataHint::InMemory(value) => {
+            internals.stored_as = "in_memory".to_string();
+            internals.in_memory_size = Some(value.len());
+        }
+        StoredDataHint::None => {}
+    }
+    internals
+}
+
+/// Converts [entry] to the record written for it.
+fn page_record(entry: &SlimCrawlResult, options: JsonlOptions) -> PageRecord {
+    let meta = &entry.meta;
+    let warc = match &entry.stored_data_hint {
+        StoredDataHint::Warc(WarcSkipInstruction::Single { pointer, .. }) => {
+            vec![warc_pointer(pointer)]
+        }
+        StoredDataHint::Warc(WarcSkipInstruction::Multiple { pointers, .. }) => {
+            pointers.iter().map(warc_pointer).collect()
+        }
+        _ => Vec::new(),
+    };
+    let headers = if options.headers {
+        meta.headers.as_ref().map(|headers| {
+            let mut result: BTreeMap<String, Vec<String>> = BTreeMap::new();
+            for (k, v) in headers.iter() {
+                result
+                    .entry(k.as_str().to_string())
+                    .or_default()
+                    .push(String::from_utf8_lossy(v.as_bytes()).into_owned());
+            }
+            result
+        })
+    } else {
+        None
+    };
+    let links = if options.extracted_links {
+        meta.links
+            .as_ref()
+            .map(|links| links.iter().map(|value| value.to_string()).collect())
+    } else {
+        None
+    };
+    let (text, text_provenance) = match options.text.and_then(|config| export_text(entry, config)) {
+        Some((text, provenance)) => (Some(text), Some(provenance)),
+        None => (None, None),
+    };
+    PageRecord {
+        schema: PageRecord::NAME.to_string(),
+        version: PageRecord::VERSION,
+        url: meta.url.try_as_str().into_owned(),
+        final_redirect_destination: meta.final_redirect_destination.clone(),
+        proxy: meta.proxy.clone(),
+        status_code: meta.status_code.as_u16(),
+        format: meta.file_information.format,
+        mime: meta.file_information.mime.clone(),
+        detected_format: meta
+            .file_information
+            .detected
+            .as_ref()
+            .map(|value| value.most_probable_file_format().to_string()),
+        detection_truncated: meta.sniff_window_truncated,
+        language: meta.language_information().map(|value| PageLanguage {
+            lang: value.lang().to_639_3().to_string(),
+            confidence: value.confidence(),
+            uncertain: value.is_uncertain(),
+        }),
+        content_language: meta.content_language.clone(),
+        language_variant: meta.language_variant.clone(),
+        created_at: format_date(meta.created_at),
+        unavailable_after: meta
+            .unavailable_after
+            .as_ref()
+            .map(|value| PageUnavailableAfter {
+                raw: value.raw.clone(),
+                expires_at: value.expires_at.map(format_date),
+            }),
+        sitemap: meta.auxiliary.as_ref().map(|value| match value {
+            AuxiliaryDocument::Sitemap(summary) => summary.clone(),
+        }),
+        publication: meta.publication.map(|value| PagePublication {
+            published_at: format_date(value.published_at),
+            source: value.source,
+            confidence: value.confidence,
+        }),
+        tag: meta.tag.clone(),
+        download_rejection: meta
+            .download_rejection
+            .as_ref()
+            .map(|value| value.to_string()),
+        original_url: meta.original_url.clone(),
+        imported_from: meta.import.as_ref().map(|value| value.source.clone()),
+        fetch_timings: meta.fetch_timings.as_ref().map(fetch_timings),
+        fuzzy_hash: meta.fuzzy_hash.as_ref().map(|value| value.to_string()),
+        simhash: meta.simhash.map(|value| value.to_string()),
+        header_fidelity: meta.header_fidelity.map(|value| PageHeaderFidelity {
+            dropped: value.dropped,
+            redacted: value.redacted,
+        }),
+        tls: meta.tls.as_ref().map(|value| PageTls {
+            fingerprint: value.fingerprint.clone(),
+            expired: value.expired,
+            verification_error: value.verification_error.clone(),
+        }),
+        warc,
+        internals: options
+            .internals
+            .then(|| internals(&entry.stored_data_hint)),
+        headers,
+        links,
+        text,
+        text_provenance,
+    }
+}
+
+/// Decodes the body of [entry] with the encoding recognized while crawling and normalizes it.
+/// The raw body is not modified. Entries without a recognized encoding and bodies stored
+/// as big files are left out.
+pub(super) fn export_text(
+    entry: &SlimCrawlResult,
+    config: TextNormalizationConfig,
+) -> Option<(String, TextProvenance)> {
+    let encoding = entry.meta.recognized_encoding?;
+    let body = match &entry.stored_data_hint {
+        StoredDataHint::InMemory(value) => Cow::Borrowed(value.as_slice()),
+        StoredDataHint::Warc(instruction) => match instruction.read() {
+            Ok(RawData::InMemory { data }) => Cow::Owned(data),
+            Ok(_) => return None,
+            Err(err) => {
+                log::warn!("Failed to read the body of {}: {err}", entry.meta.url);
+                return None;
+            }
+        },
+        StoredDataHint::External(_) | StoredDataHint::None => return None,
+    };
+    // The byte order mark is kept while decoding, the normalization removes and records it.
+    let (encoding, _) = Encoding::for_bom(&body).unwrap_or((encoding, 0));
+    let (decoded, _) = encoding.decode_without_bom_handling(&body);
+    let normalized = normalize_text(&decoded, Some(encoding), config);
+    Some((normalized.text.into_owned(), normalized.provenance))
+}
+
+fn format_date(value: OffsetDateTime) -> String {
+    value.format(&Rfc3339).unwrap_or_else(|_| value.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{write_jsonl, JsonlOptions};
+    use crate::config::header_persistence::{HeaderPattern, HeaderPersistence};
+    use crate::config::profiles::REDACTED;
+    use crate::config::{Config as AtraConfig, CrawlConfig};
+    use crate::contexts::traits::{SupportsCrawlResults, SupportsSlimCrawlResults};
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{
+        analyze_page, AuxiliaryDocument, HeaderFidelity, PublicationConfidence, PublicationEstimate,
+        PublicationSource, SitemapSummary, SlimCrawlResult, StoredDataHint,
+    };
+    use crate::data::RawData;
+    use crate::fetching::{DownloadRejection, FetchedRequestData};
+    use crate::robots::UnavailableAfter;
+    use crate::schemas::test::{assert_matches_schema, parse_minimal};
+    use crate::schemas::{PageRecord, VersionedSchema};
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::tls_info::TlsInfo;
+    use crate::toolkit::text_normalization::TextNormalizationConfig;
+    use crate::url::UrlWithDepth;
+    use crate::warc_ext::{
+        WarcSkipInstruction, WarcSkipInstructionKind, WarcSkipPointer, WarcSkipPointerWithPath,
+    };
+    use camino::Utf8PathBuf;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, SET_COOKIE};
+    use reqwest::StatusCode;
+    use time::OffsetDateTime;
+
+    fn create_entries() -> Vec<SlimCrawlResult> {
+        let warc = SlimCrawlResult::new(
+            &create_test_data(UrlWithDepth::from_url("https://www.google.de/").unwrap(), None),
+            StoredDataHint::Warc(WarcSkipInstruction::new_single(
+                WarcSkipPointerWithPath::new(
+                    Utf8PathBuf::from("test.warc"),
+                    WarcSkipPointer::new(12589, 1, 2),
+                ),
+                123,
+                WarcSkipInstructionKind::Normal,
+            )),
+        );
+        let in_memory = SlimCrawlResult::new(
+            &create_test_data(UrlWithDepth::from_url("https://www.ebay.de/").unwrap(), None),
+            StoredDataHint::InMemory(vec![1, 2, 3]),
+        );
+        vec![warc, in_memory]
+    }
+
+    #[test]
+    fn writes_one_object_per_line() {
+        let mut out = Vec::new();
+        let written = write_jsonl(create_entries(), &mut out, JsonlOptions::default()).unwrap();
+        assert_eq!(2, written);
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(2, lines.len());
+
+        let first = &lines[0];
+        assert_eq!("https://www.google.de/", first["url"]);
+        assert_eq!(200, first["status_code"]);
+        assert_eq!("HTML", first["format"]);
+        assert_eq!("test.warc", first["warc"][0]["path"]);
+        assert_eq!(12589, first["warc"][0]["file_offset"]);
+        assert_eq!(1, first["warc"][0]["warc_header_octet_count"]);
+        assert_eq!(2, first["warc"][0]["body_octet_count"]);
+        assert!(first.get("internals").is_none());
+        assert!(first.get("headers").is_none());
+        assert!(first.get("links").is_none());
+
+        assert_eq!(0, lines[1]["warc"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn respects_the_view_flags() {
+        let mut out = Vec::new();
+        let options = JsonlOptions {
+            internals: true,
+            extracted_links: true,
+            headers: true,
+            include_expired: false,
+            text: None,
+        };
+        write_jsonl(create_entries(), &mut out, options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert_eq!("warc", lines[0]["internals"]["stored_as"]);
+        assert_eq!(123, lines[0]["internals"]["header_signature_octet_count"]);
+        assert_eq!("text/html", lines[0]["headers"]["content-type"][0]);
+        assert_eq!(3, lines[0]["links"].as_array().unwrap().len());
+
+        assert_eq!("in_memory", lines[1]["internals"]["stored_as"]);
+        assert_eq!(3, lines[1]["internals"]["in_memory_size"]);
+    }
+
+    #[test]
+    fn skips_expired_entries() {
+        let mut entries = create_entries();
+        entries[0].meta.unavailable_after =
+            Some(UnavailableAfter::parse("2000-01-01T00:00:00Z"));
+        entries[1].meta.unavailable_after =
+            Some(UnavailableAfter::parse("2999-01-01T00:00:00Z"));
+
+        let mut out = Vec::new();
+        let written = write_jsonl(entries.clone(), &mut out, JsonlOptions::default()).unwrap();
+        assert_eq!(1, written);
+        let line: serde_json::Value =
+            serde_json::from_str(String::from_utf8(out).unwrap().trim()).unwrap();
+        assert_eq!("https://www.ebay.de/", line["url"]);
+        assert_eq!("2999-01-01T00:00:00Z", line["unavailable_after"]["expires_at"]);
+
+        let mut out = Vec::new();
+        let options = JsonlOptions {
+            include_expired: true,
+            ..JsonlOptions::default()
+        };
+        assert_eq!(2, write_jsonl(entries, &mut out, options).unwrap());
+    }
+
+    #[test]
+    fn exports_the_normalized_text() {
+        let mut entries = create_entries();
+        entries[1].stored_data_hint =
+            StoredDataHint::InMemory("\u{FEFF}Cafe\u{0301}\r\nna\u{0308}ive  text".into());
+        entries.push(SlimCrawlResult::new(
+            &create_test_data(
+                UrlWithDepth::from_url("https://www.bing.de/").unwrap(),
+                None,
+            ),
+            StoredDataHint::InMemory(b"no encoding".to_vec()),
+        ));
+        entries[2].meta.recognized_encoding = None;
+
+        let mut out = Vec::new();
+        let options = JsonlOptions {
+            text: Some(TextNormalizationConfig {
+                collapse_whitespace: true,
+            }),
+            ..JsonlOptions::default()
+        };
+        write_jsonl(entries.clone(), &mut out, options).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<serde_json::Value> = out
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        // The warc file of the first entry does not exist.
+        assert!(lines[0].get("text").is_none());
+        assert_eq!("Caf\u{00E9}\nn\u{00E4}ive text", lines[1]["text"]);
+        let provenance = &lines[1]["text_provenance"];
+        assert_eq!("UTF-8", provenance["original_encoding"]);
+        assert_eq!(true, provenance["bom_stripped"]);
+        assert_eq!(true, provenance["line_endings_normalized"]);
+        assert_eq!(true, provenance["whitespace_collapsed"]);
+        assert_eq!(true, provenance["nfc_normalized"]);
+        assert!(lines[2].get("text").is_none());
+        assert!(lines[2].get("text_provenance").is_none());
+
+        let mut out = Vec::new();
+        write_jsonl(entries, &mut out, JsonlOptions::default()).unwrap();
+        assert!(!String::from_utf8(out).unwrap().contains("text_provenance"));
+    }
+
+    #[test]
+    fn every_line_matches_the_schema() {
+        let mut entries = create_entries();
+        entries[0].meta.unavailable_after = Some(UnavailableAfter::parse("2999-01-01T00:00:00Z"));
+        entries[1].meta.proxy = Some("http://proxy.example.com:8080".to_string());
+        entries[1].meta.auxiliary = Some(AuxiliaryDocument::Sitemap(SitemapSummary {
+            url_count: 2,
+            ..SitemapSummary::default()
+        }));
+        entries[1].meta.publication = Some(PublicationEstimate {
+            published_at: OffsetDateTime::UNIX_EPOCH,
+            source: PublicationSource::UrlPath,
+            confidence: PublicationConfidence::Medium,
+        });
+        entries[1].meta.tag = Some("news".to_string());
+        entries[1].meta.tls = Some(TlsInfo {
+            fingerprint: "cd".repeat(32),
+            expired: true,
+            verification_error: Some("self-signed certificate".to_string()),
+        });
+        entries[0].meta.download_rejection =
+            Some(DownloadRejection::ContentType("video/mp4".to_string()));
+        let options = JsonlOptions {
+            internals: true,
+            extracted_links: true,
+            headers: true,
+            include_expired: true,
+            text: Some(TextNormalizationConfig::default()),
+        };
+        for options in [JsonlOptions::default(), options] {
+            let mut out = Vec::new();
+            write_jsonl(entries.clone(), &mut out, options).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            for line in out.lines() {
+                let value: serde_json::Value = serde_json::from_str(line).unwrap();
+                assert_matches_schema(PageRecord::NAME, &value);
+                let record: PageRecord = serde_json::from_value(value.clone()).unwrap();
+                assert_eq!(value, serde_json::to_value(&record).unwrap());
+                let minimal: PageRecord = parse_minimal(&value);
+                assert_eq!(record.url, minimal.url);
+                assert!(minimal.links.is_none());
+            }
+        }
+    }
+
+    /// Stores a page with a cookie and an internal header under [policy] and returns the
+    /// persisted meta and the exported record.
+    async fn persist_with(policy: HeaderPersistence) -> (SlimCrawlResult, serde_json::Value) {
+        let mut config = CrawlConfig::default();
+        config.header_persistence = policy;
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(SET_COOKIE, HeaderValue::from_static("session=secret"));
+        headers.insert("x-internal-trace", HeaderValue::from_static("node-7"));
+        let page = FetchedRequestData::new(
+            RawData::from_vec(b"<html><body>Hello</body></html>".to_vec()),
+            Some(headers),
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        let target = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let result = analyze_page(&context, page, &target).await.unwrap();
+        context.store_crawled_website(&result).await.unwrap();
+        let stored = context
+            .retrieve_slim_crawled_website(&target)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let options = JsonlOptions {
+            headers: true,
+            ..JsonlOptions::default()
+        };
+        let mut out = Vec::new();
+        write_jsonl(vec![stored.clone()], &mut out, options).unwrap();
+        let record = serde_json::from_slice(&out).unwrap();
+        (stored, record)
+    }
+
+    fn header_names(record: &serde_json::Value) -> Vec<&str> {
+        record["headers"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(|key| key.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn persists_and_exports_the_headers_by_the_policy() {
+        let (stored, record) = persist_with(HeaderPersistence::default()).await;
+        let headers = stored.meta.headers.as_ref().unwrap();
+        assert_eq!("session=secret", headers[SET_COOKIE]);
+        assert_eq!("node-7", headers["x-internal-trace"]);
+        assert_eq!(None, stored.meta.header_fidelity);
+        assert_eq!(
+            vec!["content-type", "set-cookie", "x-internal-trace"],
+            header_names(&record)
+        );
+        assert!(record.get("header_fidelity").is_none());
+
+        let (stored, record) = persist_with(HeaderPersistence {
+            drop_headers: vec![HeaderPattern::new("X-Internal-*").unwrap()],
+            redact_headers: Vec::new(),
+        })
+        .await;
+        let headers = stored.meta.headers.as_ref().unwrap();
+        assert_eq!("session=secret", headers[SET_COOKIE]);
+        assert!(!headers.contains_key("x-internal-trace"));
+        assert_eq!(
+            Some(HeaderFidelity {
+                dropped: 1,
+                redacted: 0
+            }),
+            stored.meta.header_fidelity
+        );
+        assert_eq!(vec!["content-type", "set-cookie"], header_names(&record));
+        assert_eq!(1, record["header_fidelity"]["dropped"]);
+        assert_eq!(0, record["header_fidelity"]["redacted"]);
+
+        let (stored, record) = persist_with(HeaderPersistence {
+            drop_headers: Vec::new(),
+            redact_headers: vec![
+                HeaderPattern::new("set-cookie").unwrap(),
+                HeaderPattern::new("x-internal-*").unwrap(),
+            ],
+        })
+        .await;
+        let headers = stored.meta.headers.as_ref().unwrap();
+        assert_eq!(REDACTED, headers[SET_COOKIE]);
+        assert_eq!(REDACTED, headers["x-internal-trace"]);
+        assert_eq!("text/html", headers[CONTENT_TYPE]);
+        assert_eq!(
+            Some(HeaderFidelity {
+                dropped: 0,
+                redacted: 2
+            }),
+            stored.meta.header_fidelity
+        );
+        assert_eq!(
+            vec!["content-type", "set-cookie", "x-internal-trace"],
+            header_names(&record)
+        );
+        assert_eq!(REDACTED, record["headers"]["set-cookie"][0]);
+        assert_eq!(REDACTED, record["headers"]["x-internal-trace"][0]);
+        assert_eq!(2, record["header_fidelity"]["redacted"]);
+    }
+}
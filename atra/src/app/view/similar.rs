@@ -0,0 +1,179 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::app::view::jsonl::stored_entries;
+use crate::app::view::ViewFormat;
+use crate::contexts::local::LocalContext;
+use crate::crawl::{CrawlResultMeta, SlimCrawlResult};
+use crate::database::DatabaseError;
+use crate::url::UrlWithDepth;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::io::Write;
+use thiserror::Error;
+
+/// Errors of a query for similar pages.
+#[derive(Debug, Error)]
+pub enum SimilarPagesError {
+    #[error(transparent)]
+    InvalidUrl(#[from] crate::url::ParseError),
+    #[error("The page {0} was not crawled.")]
+    NotCrawled(String),
+    #[error("The page {0} has no fuzzy hash, it has no body or was crawled by an older version.")]
+    NoFuzzyHash(String),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Serialisation(#[from] serde_json::Error),
+}
+
+/// A stored page similar to the queried page.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct SimilarPage {
+    /// The similarity of the fuzzy hashes of the bodies from 1 to 100.
+    pub similarity: u32,
+    /// The similarity of the simhashes of the texts from 0 to 100, if both pages have text.
+    pub text_similarity: Option<u32>,
+    pub url: String,
+}
+
+/// Returns the [limit] pages of [entries] most similar to [target], the most similar first.
+/// The entries are compared one at a time, only the best [limit] pages are kept.
+pub fn find_similar_pages(
+    entries: impl IntoIterator<Item = SlimCrawlResult>,
+    target: &CrawlResultMeta,
+    limit: usize,
+) -> Vec<SimilarPage> {
+    let Some(fuzzy_hash) = target.fuzzy_hash.as_ref() else {
+        return Vec::new();
+    };
+    let mut best = BinaryHeap::with_capacity(limit + 1);
+    for entry in entries {
+        if entry.meta.url.url == target.url.url {
+            continue;
+        }
+        let Some(other) = entry.meta.fuzzy_hash.as_ref() else {
+            continue;
+        };
+        let similarity = fuzzy_hash.similarity(other);
+        if similarity == 0 {
+            continue;
+        }
+        best.push(Reverse(SimilarPage {
+            similarity,
+            text_similarity: target
+                .simhash
+                .zip(entry.meta.simhash)
+                .map(|(a, b)| a.similarity(&b)),
+            url: entry.meta.url.try_as_str().into_owned(),
+        }));
+        if best.len() > limit {
+            best.pop();
+        }
+    }
+    best.into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(page)| page)
+        .collect()
+}
+
+/// Writes the [limit] pages of the crawl most similar to [url] to [writer].
+/// Returns the number of written pages.
+pub fn write_similar_pages<W: Write>(
+    local: &LocalContext,
+    url: &str,
+    limit: usize,
+    format: ViewFormat,
+    mut writer: W,
+) -> Result<usize, SimilarPagesError> {
+    let url = UrlWithDepth::from_url(url)?;
+    let target = local
+        .crawl_db()
+        .get(&url)?
+        .ok_or_else(|| SimilarPagesError::NotCrawled(url.to_string()))?;
+    if target.meta.fuzzy_hash.is_none() {
+        return Err(SimilarPagesError::NoFuzzyHash(url.to_string()));
+    }
+    let similar = find_similar_pages(stored_entries(local.crawl_db()), &target.meta, limit);
+    for page in &similar {
+        match format {
+            ViewFormat::Text => match page.text_similarity {
+                Some(text_similarity) => writeln!(
+                    writer,
+                    "{:>3} (text {:>3}) {}",
+                    page.similarity, text_similarity, page.url
+                )?,
+                None => writeln!(writer, "{:>3}            {}", page.similarity, page.url)?,
+            },
+            ViewFormat::Jsonl => {
+                serde_json::to_writer(&mut writer, page)?;
+                writer.write_all(b"\n")?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(similar.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::StoredDataHint;
+    use crate::toolkit::fuzzy_hash::{FuzzyHash, SimHash};
+
+    fn entry(url: &str, body: &str) -> SlimCrawlResult {
+        let mut data = create_test_data(UrlWithDepth::from_url(url).unwrap(), None);
+        data.meta.fuzzy_hash = Some(FuzzyHash::of(body.as_bytes()));
+        data.meta.simhash = Some(SimHash::of_text(body));
+        SlimCrawlResult::new(&data, StoredDataHint::None)
+    }
+
+    fn body(topic: &str, count: usize) -> String {
+        (0..count)
+            .map(|i| format!("<p>The {topic} number {i} is described in paragraph {i}.</p>\n"))
+            .collect()
+    }
+
+    #[test]
+    fn returns_the_most_similar_pages_first() {
+        let original = body("crawler", 200);
+        let target = entry("https://example.com/a", &original);
+        let entries = vec![
+            target.clone(),
+            entry("https://example.com/unrelated", &body("recipe", 200)),
+            entry(
+                "https://example.com/copy",
+                &original.replace("number 100 ", "number one hundred "),
+            ),
+            entry("https://example.com/same", &original),
+        ];
+
+        let similar = find_similar_pages(entries.clone(), &target.meta, 10);
+        let urls: Vec<&str> = similar.iter().map(|page| page.url.as_str()).collect();
+        assert_eq!(
+            vec!["https://example.com/same", "https://example.com/copy"],
+            urls
+        );
+        assert_eq!(100, similar[0].similarity);
+        assert_eq!(Some(100), similar[0].text_similarity);
+
+        let limited = find_similar_pages(entries, &target.meta, 1);
+        assert_eq!(1, limited.len());
+        assert_eq!("https://example.com/same", limited[0].url);
+    }
+}
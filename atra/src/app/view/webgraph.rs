@@ -0,0 +1,743 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contexts::local::LocalContext;
+use crate::contexts::traits::SupportsLinkState;
+use crate::link_state::{LinkStateKind, LinkStateLike, LinkStateManager};
+use crate::url::{AtraOriginProvider, AtraUri, Depth, UrlWithDepth};
+use crate::web_graph::{read_web_graph, WebGraphStatement};
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::io::{BufRead, Write};
+
+/// The format of the exported webgraph.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, ValueEnum)]
+pub enum WebGraphFormat {
+    /// GraphML, readable by Gephi, NetworkX, yEd, ...
+    #[default]
+    Graphml,
+    /// The DOT language of Graphviz.
+    Dot,
+}
+
+/// What a node of the exported webgraph is.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, ValueEnum)]
+pub enum WebGraphNodes {
+    /// Every normalized url is a node.
+    #[default]
+    Url,
+    /// Every origin is a node, the links between the urls of two origins are merged.
+    Origin,
+}
+
+/// How the webgraph is exported.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct WebGraphExportOptions {
+    pub format: WebGraphFormat,
+    pub nodes: WebGraphNodes,
+}
+
+/// The crawl status of an url in the webgraph.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct NodeStatus {
+    pub state: Option<LinkStateKind>,
+    pub status_code: Option<u16>,
+}
+
+/// Provides the crawl status of the urls in the webgraph.
+pub trait NodeStatusProvider {
+    fn node_status(&self, url: &str) -> NodeStatus;
+}
+
+impl NodeStatusProvider for LocalContext {
+    fn node_status(&self, url: &str) -> NodeStatus {
+        let Ok(url) = url.parse::<AtraUri>() else {
+            return NodeStatus::default();
+        };
+        let url = UrlWithDepth::new(url, Depth::ZERO);
+        NodeStatus {
+            state: self
+                .get_link_state_manager()
+                .get_link_state_sync(&url)
+                .ok()
+                .flatten()
+                .map(|state| state.kind()),
+            status_code: self
+                .crawl_db()
+                .get(&url)
+                .ok()
+                .flatten()
+                .map(|result| result.meta.status_code.as_u16()),
+        }
+    }
+}
+
+/// The number of nodes and edges written by [export_web_graph].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct WebGraphExportCounts {
+    pub nodes: u64,
+    pub edges: u64,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+enum NodeKind {
+    Url,
+    Origin,
+}
+
+impl NodeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            NodeKind::Url => "url",
+            NodeKind::Origin => "origin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct Edge {
+    source: u64,
+    target: u64,
+    relation: &'static str,
+    extractor: Option<String>,
+}
+
+/// Writes the nodes and edges in one of the [WebGraphFormat]s.
+trait GraphSink {
+    fn begin(&mut self) -> io::Result<()>;
+    fn node(
+        &mut self,
+        id: u64,
+        label: &str,
+        kind: NodeKind,
+        status: Option<NodeStatus>,
+    ) -> io::Result<()>;
+    fn edge(&mut self, edge: &Edge) -> io::Result<()>;
+    fn end(&mut self) -> io::Result<()>;
+}
+
+struct GraphmlSink<W>(W);
+
+impl<W: Write> GraphSink for GraphmlSink<W> {
+    fn begin(&mut self) -> io::Result<()> {
+        let w = &mut self.0;
+        writeln!(w, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            w,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:schemaLocation="http://graphml.graphdrawing.org/xmlns http://graphml.graphdrawing.org/xmlns/1.0/graphml.xsd">"#
+        )?;
+        for (id, target, typ) in [
+            ("label", "node", "string"),
+            ("kind", "node", "string"),
+            ("state", "node", "string"),
+            ("status_code", "node", "int"),
+            ("relation", "edge", "string"),
+            ("extractor", "edge", "string"),
+        ] {
+            writeln!(
+                w,
+                r#"  <key id="{id}" for="{target}" attr.name="{id}" attr.type="{typ}"/>"#
+            )?;
+        }
+        writeln!(w, r#"  <graph id="webgraph" edgedefault="directed">"#)
+    }
+
+    fn node(
+        &mut self,
+        id: u64,
+        label: &str,
+        kind: NodeKind,
+        status: Option<NodeStatus>,
+    ) -> io::Result<()> {
+        let w = &mut self.0;
+        write!(
+            w,
+            r#"    <node id="n{id}"><data key="label">{}</data><data key="kind">{}</data>"#,
+            escape_xml(label),
+            kind.as_str()
+        )?;
+        if let Some(status) = status {
+            if let Some(state) = status.state {
+                write!(w, r#"<data key="state">{state}</data>"#)?;
+            }
+            if let Some(status_code) = status.status_code {
+                write!(w, r#"<data key="status_code">{status_code}</data>"#)?;
+            }
+        }
+        writeln!(w, "</node>")
+    }
+
+    fn edge(&mut self, edge: &Edge) -> io::Result<()> {
+        let w = &mut self.0;
+        write!(
+            w,
+            r#"    <edge source="n{}" target="n{}"><data key="relation">{}</data>"#,
+            edge.source, edge.target, edge.relation
+        )?;
+        if let Some(ref extractor) = edge.extractor {
+            write!(
+                w,
+                r#"<data key="extractor">{}</data>"#,
+                escape_xml(extractor)
+            )?;
+        }
+        writeln!(w, "</edge>")
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        writeln!(self.0, "  </graph>")?;
+        writeln!(self.0, "</graphml>")?;
+        self.0.flush()
+    }
+}
+
+struct DotSink<W>(W);
+
+impl<W: Write> GraphSink for DotSink<W> {
+    fn begin(&mut self) -> io::Result<()> {
+        writeln!(self.0, "digraph webgraph {{")
+    }
+
+    fn node(
+        &mut self,
+        id: u64,
+        label: &str,
+        kind: NodeKind,
+        status: Option<NodeStatus>,
+    ) -> io::Result<()> {
+        let w = &mut self.0;
+        write!(
+            w,
+            r#"  n{id} [label="{}", kind="{}""#,
+            escape_dot(label),
+            kind.as_str()
+        )?;
+        if let Some(status) = status {
+            if let Some(state) = status.state {
+                write!(w, r#", state="{state}""#)?;
+            }
+            if let Some(status_code) = status.status_code {
+                write!(w, ", status_code={status_code}")?;
+            }
+        }
+        writeln!(w, "];")
+    }
+
+    fn edge(&mut self, edge: &Edge) -> io::Result<()> {
+        let w = &mut self.0;
+        write!(
+            w,
+            r#"  n{} -> n{} [relation="{}""#,
+            edge.source, edge.target, edge.relation
+        )?;
+        if let Some(ref extractor) = edge.extractor {
+            write!(w, r#", extractor="{}""#, escape_dot(extractor))?;
+        }
+        writeln!(w, "];")
+    }
+
+    fn end(&mut self) -> io::Result<()> {
+        writeln!(self.0, "}}")?;
+        self.0.flush()
+    }
+}
+
+/// Escapes [value] for the content or an attribute value of an XML element.
+/// Characters not allowed in XML 1.0 are replaced by U+FFFD.
+fn escape_xml(value: &str) -> Cow<str> {
+    fn needs_escape(c: char) -> bool {
+        matches!(c, '&' | '<' | '>' | '"' | '\'' | '\u{FFFE}' | '\u{FFFF}')
+            || (c < ' ' && !matches!(c, '\t' | '\n' | '\r'))
+    }
+    if !value.contains(needs_escape) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 16);
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if needs_escape(c) => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Escapes [value] for a quoted DOT string.
+/// Control characters are replaced by U+FFFD.
+fn escape_dot(value: &str) -> Cow<str> {
+    if !value.contains(|c: char| c == '"' || c == '\\' || c.is_control()) {
+        return Cow::Borrowed(value);
+    }
+    let mut escaped = String::with_capacity(value.len() + 16);
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push(char::REPLACEMENT_CHARACTER),
+            c => escaped.push(c),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Assigns the ids to the nodes and writes every node when it is seen for the first time.
+struct GraphWriter<'a, S, P> {
+    sink: S,
+    nodes: WebGraphNodes,
+    status: &'a P,
+    ids: HashMap<String, u64>,
+    merged_edges: HashSet<Edge>,
+    edges: u64,
+}
+
+impl<S: GraphSink, P: NodeStatusProvider> GraphWriter<'_, S, P> {
+    /// Returns the id of the node for [url], an url without an origin is kept as url.
+    fn url(&mut self, url: &str) -> io::Result<u64> {
+        if self.nodes == WebGraphNodes::Origin {
+            if let Some(origin) = url
+                .parse::<AtraUri>()
+                .ok()
+                .and_then(|url| url.atra_origin())
+            {
+                return self.origin(&origin.to_string());
+            }
+        }
+        if let Some(id) = self.ids.get(url) {
+            return Ok(*id);
+        }
+        let id = self.ids.len() as u64;
+        let status = self.status.node_status(url);
+        self.sink.node(id, url, NodeKind::Url, Some(status))?;
+        self.ids.insert(url.to_string(), id);
+        Ok(id)
+    }
+
+    /// Returns the id of the node for [origin].
+    fn origin(&mut self, origin: &str) -> io::Result<u64> {
+        let key = format!("o:{origin}");
+        if let Some(id) = self.ids.get(&key) {
+            return Ok(*id);
+        }
+        let id = self.ids.len() as u64;
+        self.sink.node(id, origin, NodeKind::Origin, None)?;
+        self.ids.insert(key, id);
+        Ok(id)
+    }
+
+    fn edge(&mut self, edge: Edge) -> io::Result<()> {
+        if self.nodes == WebGraphNodes::Origin {
+            if self.merged_edges.contains(&edge) {
+                return Ok(());
+            }
+            self.sink.edge(&edge)?;
+            self.merged_edges.insert(edge);
+        } else {
+            self.sink.edge(&edge)?;
+        }
+        self.edges += 1;
+        Ok(())
+    }
+}
+
+/// Streams the webgraph stored in [graph] to [writer].
+///
+/// Nodes are written when they are seen for the first time and edges right away,
+/// only the ids of the nodes are kept in memory. When exporting origins the
+/// merged edges are deduplicated.
+pub fn export_web_graph<R: BufRead, W: Write>(
+    graph: R,
+    writer: W,
+    options: WebGraphExportOptions,
+    status: &impl NodeStatusProvider,
+) -> io::Result<WebGraphExportCounts> {
+    match options.format {
+        WebGraphFormat::Graphml => {
+            export_to_sink(graph, GraphmlSink(writer), options.nodes, status)
+        }
+        WebGraphFormat::Dot => export_to_sink(graph, DotSink(writer), options.nodes, status),
+    }
+}
+
+fn export_to_sink<R: BufRead, S: GraphSink>(
+    graph: R,
+    mut sink: S,
+    nodes: WebGraphNodes,
+    status: &impl NodeStatusProvider,
+) -> io::Result<WebGraphExportCounts> {
+    sink.begin()?;
+    let mut writer = GraphWriter {
+        sink,
+        nodes,
+        status,
+        ids: HashMap::new(),
+        merged_edges: HashSet::new(),
+        edges: 0,
+    };
+
+    // A link is held back until the next statement, which may name its extractor.
+    let mut pending: Option<(String, String, Edge)> = None;
+    for statement in read_web_graph(graph) {
+        let statement = statement?;
+        if !matches!(statement, WebGraphStatement::LinkExtractor { .. }) {
+            if let Some((_, _, edge)) = pending.take() {
+                writer.edge(edge)?;
+            }
+        }
+        match statement {
+            WebGraphStatement::Seed { origin, seed } => match nodes {
+                WebGraphNodes::Url => {
+                    writer.url(&seed)?;
+                }
+                WebGraphNodes::Origin => {
+                    writer.origin(&origin)?;
+                }
+            },
+            WebGraphStatement::Link { from, to } => {
+                let edge = Edge {
+                    source: writer.url(&from)?,
+                    target: writer.url(&to)?,
+                    relation: "links_to",
+                    extractor: None,
+                };
+                pending = Some((from, to, edge));
+            }
+            WebGraphStatement::ErrorPageLink { from, to } => {
+                let edge = Edge {
+                    source: writer.url(&from)?,
+                    target: writer.url(&to)?,
+                    relation: "error_page_links_to",
+                    extractor: None,
+                };
+                pending = Some((from, to, edge));
+            }
+            WebGraphStatement::LinkExtractor {
+                from,
+                to,
+                extractor,
+            } => match pending.take() {
+                Some((pending_from, pending_to, mut edge))
+                    if pending_from == from && pending_to == to =>
+                {
+                    edge.extractor = Some(extractor);
+                    writer.edge(edge)?;
+                }
+                other => {
+                    if let Some((_, _, edge)) = other {
+                        writer.edge(edge)?;
+                    }
+                    // The same link found by another extractor of the page.
+                    let edge = Edge {
+                        source: writer.url(&from)?,
+                        target: writer.url(&to)?,
+                        relation: "links_to",
+                        extractor: Some(extractor),
+                    };
+                    writer.edge(edge)?;
+                }
+            },
+            WebGraphStatement::SitemapLink { sitemap, to } => {
+                let edge = Edge {
+                    source: writer.url(&sitemap)?,
+                    target: writer.url(&to)?,
+                    relation: "sitemap_lists",
+                    extractor: None,
+                };
+                writer.edge(edge)?;
+            }
+        }
+    }
+    if let Some((_, _, edge)) = pending.take() {
+        writer.edge(edge)?;
+    }
+    writer.sink.end()?;
+    Ok(WebGraphExportCounts {
+        nodes: writer.ids.len() as u64,
+        edges: writer.edges,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        export_web_graph, NodeStatus, NodeStatusProvider, WebGraphExportCounts,
+        WebGraphExportOptions, WebGraphFormat, WebGraphNodes,
+    };
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::link_state::LinkStateKind;
+    use crate::url::AtraUri;
+    use crate::web_graph::WebGraphEntry;
+    use std::collections::HashMap;
+    use xml::reader::XmlEvent;
+    use xml::EventReader;
+
+    const PAGE: &str = "https://www.test.de/a?b=1&c=<2>";
+    const OTHER: &str = "https://www.test.de/other";
+    const EXTERNAL: &str = "https://www.external.de/it's";
+    const EXTERNAL_2: &str = "https://www.external.de/other";
+
+    impl NodeStatusProvider for HashMap<String, NodeStatus> {
+        fn node_status(&self, url: &str) -> NodeStatus {
+            self.get(url).copied().unwrap_or_default()
+        }
+    }
+
+    fn uri(value: &str) -> AtraUri {
+        value.parse().unwrap()
+    }
+
+    /// Writes a small webgraph like the [QueuingWebGraphManager] does.
+    fn stored_graph() -> String {
+        let mut graph = String::from("@prefix : <http://atra.de/graph#> .\n");
+        for entry in [
+            WebGraphEntry::Seed {
+                origin: "test.de".into(),
+                seed: uri(PAGE),
+            },
+            WebGraphEntry::Link {
+                from: uri(PAGE),
+                to: uri(OTHER),
+                extractor: Some(ExtractorMethod::HtmlV1),
+                from_error_page: false,
+            },
+            WebGraphEntry::Link {
+                from: uri(PAGE),
+                to: uri(EXTERNAL),
+                extractor: Some(ExtractorMethod::HtmlV1),
+                from_error_page: false,
+            },
+            WebGraphEntry::Link {
+                from: uri(OTHER),
+                to: uri(EXTERNAL),
+                extractor: None,
+                from_error_page: false,
+            },
+            WebGraphEntry::Link {
+                from: uri(PAGE),
+                to: uri(EXTERNAL_2),
+                extractor: Some(ExtractorMethod::HtmlV1),
+                from_error_page: false,
+            },
+            WebGraphEntry::SitemapLink {
+                sitemap: uri(OTHER),
+                to: uri(PAGE),
+            },
+        ] {
+            entry.collect(&mut graph);
+        }
+        graph
+    }
+
+    fn statuses() -> HashMap<String, NodeStatus> {
+        HashMap::from([(
+            uri(PAGE).to_string(),
+            NodeStatus {
+                state: Some(LinkStateKind::ProcessedAndStored),
+                status_code: Some(200),
+            },
+        )])
+    }
+
+    fn export(
+        graph: &str,
+        format: WebGraphFormat,
+        nodes: WebGraphNodes,
+    ) -> (String, WebGraphExportCounts) {
+        let mut out = Vec::new();
+        let counts = export_web_graph(
+            graph.as_bytes(),
+            &mut out,
+            WebGraphExportOptions { format, nodes },
+            &statuses(),
+        )
+        .unwrap();
+        (String::from_utf8(out).unwrap(), counts)
+    }
+
+    #[derive(Debug, Default)]
+    struct ParsedGraph {
+        nodes: HashMap<String, HashMap<String, String>>,
+        edges: Vec<(String, String, HashMap<String, String>)>,
+    }
+
+    impl ParsedGraph {
+        fn node_by_label(&self, label: &str) -> &HashMap<String, String> {
+            self.nodes
+                .values()
+                .find(|data| data.get("label").map(String::as_str) == Some(label))
+                .unwrap_or_else(|| panic!("No node for {label}"))
+        }
+    }
+
+    /// A node or an edge with its data.
+    struct Element {
+        id: Option<String>,
+        endpoints: Option<(String, String)>,
+        data: HashMap<String, String>,
+    }
+
+    fn parse_graphml(graphml: &str) -> ParsedGraph {
+        let mut graph = ParsedGraph::default();
+        let mut path = Vec::new();
+        let mut current: Option<Element> = None;
+        let mut data_key = None;
+        for event in EventReader::new(graphml.as_bytes()) {
+            match event.expect("The GraphML has to be well-formed.") {
+                XmlEvent::StartElement {
+                    name, attributes, ..
+                } => {
+                    let attr = |key: &str| {
+                        attributes
+                            .iter()
+                            .find(|value| value.name.local_name == key)
+                            .map(|value| value.value.clone())
+                    };
+                    match name.local_name.as_str() {
+                        "node" => {
+                            current = Some(Element {
+                                id: Some(attr("id").unwrap()),
+                                endpoints: None,
+                                data: HashMap::new(),
+                            })
+                        }
+                        "edge" => {
+                            current = Some(Element {
+                                id: None,
+                                endpoints: Some((attr("source").unwrap(), attr("target").unwrap())),
+                                data: HashMap::new(),
+                            })
+                        }
+                        "data" => data_key = attr("key"),
+                        _ => {}
+                    }
+                    path.push(name.local_name);
+                }
+                XmlEvent::Characters(value) => {
+                    let key = data_key.take().expect("Only data has text content.");
+                    current.as_mut().unwrap().data.insert(key, value);
+                }
+                XmlEvent::EndElement { name } => {
+                    assert_eq!(Some(name.local_name), path.pop());
+                    if path.last().map(String::as_str) != Some("graph") {
+                        continue;
+                    }
+                    match current.take() {
+                        Some(Element {
+                            id: Some(id), data, ..
+                        }) => {
+                            assert!(graph.nodes.insert(id, data).is_none());
+                        }
+                        Some(Element {
+                            endpoints: Some((source, target)),
+                            data,
+                            ..
+                        }) => {
+                            graph.edges.push((source, target, data));
+                        }
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+        assert!(path.is_empty());
+        graph
+    }
+
+    #[test]
+    fn graphml_round_trip_by_url() {
+        let (graphml, counts) =
+            export(&stored_graph(), WebGraphFormat::Graphml, WebGraphNodes::Url);
+        assert!(graphml.contains("b=1&amp;c=%3C2%3E"));
+        assert!(graphml.contains("it&apos;s"));
+        let graph = parse_graphml(&graphml);
+
+        assert_eq!(WebGraphExportCounts { nodes: 4, edges: 5 }, counts);
+        assert_eq!(4, graph.nodes.len());
+        assert_eq!(5, graph.edges.len());
+        for (source, target, _) in &graph.edges {
+            assert!(graph.nodes.contains_key(source));
+            assert!(graph.nodes.contains_key(target));
+        }
+
+        let page = graph.node_by_label(&uri(PAGE).to_string());
+        assert_eq!("url", page["kind"]);
+        assert_eq!("ProcessedAndStored", page["state"]);
+        assert_eq!("200", page["status_code"]);
+        let external = graph.node_by_label(&uri(EXTERNAL).to_string());
+        assert!(!external.contains_key("status_code"));
+
+        let mut relations = graph
+            .edges
+            .iter()
+            .map(|(_, _, data)| {
+                (
+                    data["relation"].as_str(),
+                    data.get("extractor").map(String::as_str),
+                )
+            })
+            .collect::<Vec<_>>();
+        relations.sort();
+        assert_eq!(
+            vec![
+                ("links_to", None),
+                ("links_to", Some("HtmlV1")),
+                ("links_to", Some("HtmlV1")),
+                ("links_to", Some("HtmlV1")),
+                ("sitemap_lists", None),
+            ],
+            relations
+        );
+    }
+
+    #[test]
+    fn graphml_round_trip_by_origin() {
+        let (graphml, counts) = export(
+            &stored_graph(),
+            WebGraphFormat::Graphml,
+            WebGraphNodes::Origin,
+        );
+        let graph = parse_graphml(&graphml);
+
+        assert_eq!(2, graph.nodes.len());
+        assert_eq!("origin", graph.node_by_label("test.de")["kind"]);
+        assert_eq!("origin", graph.node_by_label("external.de")["kind"]);
+        // Both links found by html to the external origin are merged.
+        assert_eq!(4, graph.edges.len());
+        assert_eq!(WebGraphExportCounts { nodes: 2, edges: 4 }, counts);
+    }
+
+    #[test]
+    fn dot_escapes_the_labels() {
+        // Written by hand, the normalized urls never contain quotes or backslashes.
+        let graph = format!(
+            "{}<https://www.test.de/\"quoted\"\\> :links_to <{OTHER}> .\n",
+            stored_graph()
+        );
+        let (dot, counts) = export(&graph, WebGraphFormat::Dot, WebGraphNodes::Url);
+        assert_eq!(WebGraphExportCounts { nodes: 5, edges: 6 }, counts);
+        assert!(dot.starts_with("digraph webgraph {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains(r#"[label="https://www.test.de/\"quoted\"\\", kind="url"];"#));
+        assert!(dot.contains(r#"kind="url", state="ProcessedAndStored", status_code=200];"#));
+        assert!(dot.contains(r#"[relation="links_to", extractor="HtmlV1"];"#));
+        assert_eq!(6, dot.matches(" -> ").count());
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Generated fixtures, the same arguments always generate the same data.
+
+use crate::crawl::CrawlResult;
+use crate::data::RawVecData;
+use crate::fetching::{FetchedRequestData, ResponseData};
+use crate::format::mime::MimeType;
+use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::format::AtraFileInformation;
+use crate::toolkit::LanguageInformation;
+use crate::url::UrlWithDepth;
+use reqwest::StatusCode;
+use std::fmt::Write;
+use time::OffsetDateTime;
+
+/// Urls over 97 hosts with everything the url normalization rewrites.
+pub fn urls(count: usize) -> Vec<UrlWithDepth> {
+    urls_from(0, count)
+}
+
+/// The [count] urls of [urls] after the first [start] urls.
+pub fn urls_from(start: usize, count: usize) -> Vec<UrlWithDepth> {
+    (start..start + count)
+        .map(|i| {
+            UrlWithDepth::from_url(format!(
+                "HTTPS://www.Host{}.example.com:443/section/{}/./page-{i}/?b={i}&utm_source=bench&a={}&gclid={i}#top",
+                i % 97,
+                i % 13,
+                i % 7
+            ))
+            .unwrap()
+        })
+        .collect()
+}
+
+/// An html page of at least [size] bytes with relative and absolute links in anchors,
+/// images, forms and style attributes.
+pub fn html_page(size: usize) -> String {
+    let mut html = String::with_capacity(size + 1024);
+    html.push_str("<!DOCTYPE html><html><head><title>Bench</title></head><body>");
+    let mut i = 0usize;
+    while html.len() < size {
+        write!(
+            html,
+            r#"<div class="entry"><h2>Entry {i}</h2><p>Lorem ipsum dolor sit amet, consectetur adipiscing elit.
+<a href="/articles/{i}.html">Article {i}</a> and <a href="https://www.other{}.example.org/page?id={i}">elsewhere</a>.</p>
+<img src="img/{i}.png" alt="Image {i}"><form action="/search/{i}"><input name="q"></form>
+<span style="background: url('img/bg-{i}.jpg')">Styled</span></div>"#,
+            i % 31
+        )
+        .unwrap();
+        i += 1;
+    }
+    html.push_str("</body></html>");
+    html
+}
+
+/// Crawl results of small html pages with their bodies in memory.
+pub fn crawl_results(count: usize) -> Vec<CrawlResult> {
+    let body = html_page(4 * 1024).into_bytes();
+    (0..count)
+        .map(|i| {
+            CrawlResult::new(
+                OffsetDateTime::now_utc(),
+                ResponseData::from_response(
+                    FetchedRequestData::new(
+                        RawVecData::from_vec(body.clone()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    ),
+                    UrlWithDepth::from_url(format!("https://www.example.com/page/{i}.html"))
+                        .unwrap(),
+                ),
+                None,
+                Some(encoding_rs::UTF_8),
+                AtraFileInformation::new(
+                    InterpretedProcessibleFileFormat::HTML,
+                    Some(MimeType::new_single(mime::TEXT_HTML_UTF_8)),
+                    None,
+                ),
+                Some(LanguageInformation::ENG),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,129 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Workloads for the hot paths of a crawl, used by the criterion benchmarks of the `bench`
+//! feature and by the tests with loose time budgets.
+
+mod fixtures;
+mod workloads;
+
+pub use workloads::*;
+
+use std::time::{Duration, Instant};
+
+/// A hot path with its fixtures, prepared once and run repeatedly.
+pub trait Workload {
+    /// The name of the benchmark group.
+    const NAME: &'static str;
+
+    /// The time a single run of a debug build may take before the tests fail.
+    /// About an order of magnitude above the usual time, only big regressions fail.
+    const BUDGET: Duration;
+
+    /// Runs the workload once, returns the number of processed elements.
+    fn run(&mut self) -> usize;
+}
+
+/// The timings of some runs of a [Workload].
+#[derive(Debug, Copy, Clone)]
+pub struct Measurement {
+    pub name: &'static str,
+    /// The processed elements of a single run.
+    pub elements: usize,
+    pub runs: usize,
+    pub fastest: Duration,
+    pub mean: Duration,
+}
+
+impl Measurement {
+    /// Returns true if the fastest run is within the budget of [W].
+    pub fn is_within_budget<W: Workload>(&self) -> bool {
+        self.fastest <= W::BUDGET
+    }
+}
+
+/// Runs the [workload] [runs] times, at least once.
+pub fn measure<W: Workload>(workload: &mut W, runs: usize) -> Measurement {
+    let runs = runs.max(1);
+    let mut elements = 0;
+    let mut fastest = Duration::MAX;
+    let mut total = Duration::ZERO;
+    for _ in 0..runs {
+        let started = Instant::now();
+        elements = workload.run();
+        let elapsed = started.elapsed();
+        fastest = fastest.min(elapsed);
+        total += elapsed;
+    }
+    Measurement {
+        name: W::NAME,
+        elements,
+        runs,
+        fastest,
+        mean: total / runs as u32,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_within_budget<W: Workload>(mut workload: W, elements: usize) {
+        let measurement = measure(&mut workload, 1);
+        assert_eq!(elements, measurement.elements, "{measurement:?}");
+        assert!(
+            measurement.is_within_budget::<W>(),
+            "{} took {:?}, the budget is {:?}",
+            W::NAME,
+            measurement.fastest,
+            W::BUDGET
+        );
+    }
+
+    #[test]
+    fn url_normalization_is_within_budget() {
+        assert_within_budget(UrlNormalizationWorkload::default(), URL_COUNT);
+    }
+
+    #[test]
+    fn queue_churn_is_within_budget() {
+        assert_within_budget(QueueChurnWorkload::default(), QUEUE_ELEMENTS);
+    }
+
+    #[test]
+    fn link_state_upserts_are_within_budget() {
+        assert_within_budget(LinkStateUpsertWorkload::default(), URL_COUNT);
+    }
+
+    #[test]
+    fn link_state_discovery_is_within_budget() {
+        assert_within_budget(LinkStateDiscoveryWorkload::default(), URL_COUNT);
+    }
+
+    #[test]
+    fn warc_writing_is_within_budget() {
+        assert_within_budget(WarcWriteWorkload::default(), WARC_RECORDS);
+    }
+
+    #[test]
+    fn html_extraction_is_within_budget() {
+        let mut workload = HtmlExtractionWorkload::default();
+        let measurement = measure(&mut workload, 1);
+        assert!(measurement.elements > 1000, "{measurement:?}");
+        assert!(
+            measurement.is_within_budget::<HtmlExtractionWorkload>(),
+            "{measurement:?}"
+        );
+    }
+}
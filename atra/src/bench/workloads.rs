@@ -0,0 +1,333 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::bench::fixtures;
+use crate::bench::Workload;
+use crate::config::{Config, SeenFilterConfig};
+use crate::contexts::traits::{SupportsConfigs, SupportsGdbrRegistry};
+use crate::contexts::BaseContext;
+use crate::crawl::CrawlResult;
+use crate::database::open_db;
+use crate::extraction::html::extract_links;
+use crate::gdbr::identifier::GdbrIdentifierRegistry;
+use crate::io::errors::ErrorWithPath;
+use crate::link_state::{
+    DatabaseLinkStateManager, IsSeedYesNo, LinkStateKind, LinkStateManager, LinkStateRockDB,
+    RecrawlYesNo,
+};
+use crate::queue::{RawAgingQueueFile, UrlQueue, UrlQueueElement, UrlQueueWrapper};
+use crate::stores::warc::{RawMultifileWarcWriter, RawWriter, WarcFilePathProvider};
+use crate::url::normalization::{UrlNormalizationConfig, UrlNormalizer};
+use crate::url::UrlWithDepth;
+use crate::warc_ext::write_warc;
+use camino::Utf8PathBuf;
+use camino_tempfile::Utf8TempDir;
+use liblinear::solver::L2R_L2LOSS_SVR;
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use text_processing::tf_idf::{Idf, Tf};
+use tokio::runtime::Runtime;
+use warc::writer::WarcWriter;
+
+/// The number of urls normalized and upserted into the link states.
+pub const URL_COUNT: usize = 10_000;
+/// The size of the html page for the link extraction.
+pub const HTML_SIZE: usize = 1024 * 1024;
+/// The number of records written to a warc file.
+pub const WARC_RECORDS: usize = 1_000;
+/// The number of elements enqueued and dequeued again.
+pub const QUEUE_ELEMENTS: usize = 100_000;
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+}
+
+/// Normalizes [URL_COUNT] urls with all rules of the url normalization.
+pub struct UrlNormalizationWorkload {
+    normalizer: UrlNormalizer,
+    urls: Vec<UrlWithDepth>,
+}
+
+impl Default for UrlNormalizationWorkload {
+    fn default() -> Self {
+        let mut config = UrlNormalizationConfig::default();
+        config.sort_query_parameters = true;
+        config.strip_tracking_params = true;
+        config.strip_trailing_slash = true;
+        Self {
+            normalizer: UrlNormalizer::new(config),
+            urls: fixtures::urls(URL_COUNT),
+        }
+    }
+}
+
+impl Workload for UrlNormalizationWorkload {
+    const NAME: &'static str = "url_normalization";
+    const BUDGET: Duration = Duration::from_secs(5);
+
+    fn run(&mut self) -> usize {
+        for url in &self.urls {
+            self.normalizer.normalized(url);
+        }
+        self.urls.len()
+    }
+}
+
+/// Enqueues [QUEUE_ELEMENTS] urls into a queue file and dequeues all of them again.
+pub struct QueueChurnWorkload {
+    runtime: Runtime,
+    queue: UrlQueueWrapper<RawAgingQueueFile>,
+    urls: Vec<UrlWithDepth>,
+    _dir: Utf8TempDir,
+}
+
+impl Default for QueueChurnWorkload {
+    fn default() -> Self {
+        let dir = Utf8TempDir::new().unwrap();
+        Self {
+            runtime: runtime(),
+            queue: UrlQueueWrapper::open(dir.path().join("queue.q")).unwrap(),
+            urls: fixtures::urls(QUEUE_ELEMENTS),
+            _dir: dir,
+        }
+    }
+}
+
+impl Workload for QueueChurnWorkload {
+    const NAME: &'static str = "queue_churn";
+    const BUDGET: Duration = Duration::from_secs(120);
+
+    fn run(&mut self) -> usize {
+        self.runtime.block_on(async {
+            for chunk in self.urls.chunks(1_000) {
+                self.queue
+                    .enqueue_all(
+                        chunk
+                            .iter()
+                            .map(|url| UrlQueueElement::new(false, 0, false, url.clone())),
+                    )
+                    .await
+                    .unwrap();
+            }
+            let mut dequeued = 0;
+            while let Some(element) = self.queue.dequeue().await.unwrap() {
+                element.take();
+                dequeued += 1;
+            }
+            dequeued
+        })
+    }
+}
+
+/// Upserts the state of [URL_COUNT] urls into the link state database.
+pub struct LinkStateUpsertWorkload {
+    runtime: Runtime,
+    manager: DatabaseLinkStateManager<LinkStateRockDB>,
+    urls: Vec<UrlWithDepth>,
+    _dir: Utf8TempDir,
+}
+
+impl Default for LinkStateUpsertWorkload {
+    fn default() -> Self {
+        let dir = Utf8TempDir::new().unwrap();
+        let db = open_db(dir.path().join("db")).unwrap();
+        Self {
+            runtime: runtime(),
+            manager: DatabaseLinkStateManager::new(Arc::new(db)),
+            urls: fixtures::urls(URL_COUNT),
+            _dir: dir,
+        }
+    }
+}
+
+impl Workload for LinkStateUpsertWorkload {
+    const NAME: &'static str = "link_state_upserts";
+    const BUDGET: Duration = Duration::from_secs(30);
+
+    fn run(&mut self) -> usize {
+        self.runtime.block_on(async {
+            for url in &self.urls {
+                self.manager
+                    .update_link_state_no_payload(
+                        url,
+                        LinkStateKind::Discovered,
+                        Some(IsSeedYesNo::No),
+                        Some(RecrawlYesNo::No),
+                    )
+                    .await
+                    .unwrap();
+            }
+            self.urls.len()
+        })
+    }
+}
+
+/// Discovers [URL_COUNT] urls behind a seen filter, half of them were discovered by the
+/// previous run. The urls are generated by the run.
+pub struct LinkStateDiscoveryWorkload {
+    runtime: Runtime,
+    manager: DatabaseLinkStateManager<LinkStateRockDB>,
+    runs: usize,
+    _dir: Utf8TempDir,
+}
+
+impl Default for LinkStateDiscoveryWorkload {
+    fn default() -> Self {
+        let dir = Utf8TempDir::new().unwrap();
+        let db = open_db(dir.path().join("db")).unwrap();
+        Self {
+            runtime: runtime(),
+            manager: DatabaseLinkStateManager::new(Arc::new(db)).with_seen_filter(
+                &SeenFilterConfig {
+                    expected_urls: URL_COUNT,
+                },
+            ),
+            runs: 0,
+            _dir: dir,
+        }
+    }
+}
+
+impl Workload for LinkStateDiscoveryWorkload {
+    const NAME: &'static str = "link_state_discovery";
+    const BUDGET: Duration = Duration::from_secs(30);
+
+    fn run(&mut self) -> usize {
+        let urls = fixtures::urls_from(self.runs * URL_COUNT / 2, URL_COUNT);
+        self.runs += 1;
+        self.runtime.block_on(async {
+            for url in &urls {
+                self.manager
+                    .discover_link_state(url, Some(IsSeedYesNo::No), Some(RecrawlYesNo::No))
+                    .await
+                    .unwrap();
+            }
+            urls.len()
+        })
+    }
+}
+
+/// Provides numbered warc files in a directory.
+#[derive(Debug)]
+struct NumberedWarcFiles {
+    dir: Utf8PathBuf,
+    next: AtomicUsize,
+}
+
+impl WarcFilePathProvider for NumberedWarcFiles {
+    fn create_new_warc_file_path(&self) -> Result<Utf8PathBuf, ErrorWithPath> {
+        let number = self.next.fetch_add(1, Ordering::Relaxed);
+        Ok(self.dir.join(format!("{number}.warc")))
+    }
+}
+
+/// Writes [WARC_RECORDS] html pages into a fresh warc file.
+pub struct WarcWriteWorkload {
+    files: Arc<NumberedWarcFiles>,
+    results: Vec<CrawlResult>,
+    _dir: Utf8TempDir,
+}
+
+impl Default for WarcWriteWorkload {
+    fn default() -> Self {
+        let dir = Utf8TempDir::new().unwrap();
+        Self {
+            files: Arc::new(NumberedWarcFiles {
+                dir: dir.path().to_path_buf(),
+                next: AtomicUsize::new(0),
+            }),
+            results: fixtures::crawl_results(WARC_RECORDS),
+            _dir: dir,
+        }
+    }
+}
+
+impl Workload for WarcWriteWorkload {
+    const NAME: &'static str = "warc_write";
+    const BUDGET: Duration = Duration::from_secs(30);
+
+    fn run(&mut self) -> usize {
+        let path = self.files.create_new_warc_file_path().unwrap();
+        let mut writer = RawMultifileWarcWriter::new(
+            self.files.clone(),
+            WarcWriter::new(BufWriter::new(File::create_for_warc(&path).unwrap())),
+            path.clone(),
+        )
+        .unwrap();
+        for result in &self.results {
+            write_warc(&mut writer, result).unwrap();
+        }
+        drop(writer);
+        std::fs::remove_file(path).unwrap();
+        self.results.len()
+    }
+}
+
+/// A context with the default config and without a gdbr registry.
+struct ExtractionContext {
+    config: Config,
+}
+
+impl BaseContext for ExtractionContext {}
+
+impl SupportsConfigs for ExtractionContext {
+    fn configs(&self) -> &Config {
+        &self.config
+    }
+}
+
+impl SupportsGdbrRegistry for ExtractionContext {
+    type Registry = GdbrIdentifierRegistry<Tf, Idf, L2R_L2LOSS_SVR>;
+
+    fn gdbr_registry(&self) -> Option<&Self::Registry> {
+        None
+    }
+}
+
+/// Extracts the links of a html page with [HTML_SIZE] bytes.
+pub struct HtmlExtractionWorkload {
+    context: ExtractionContext,
+    root: UrlWithDepth,
+    html: String,
+}
+
+impl Default for HtmlExtractionWorkload {
+    fn default() -> Self {
+        let mut config = Config::default();
+        config.crawl.crawl_embedded_data = true;
+        config.crawl.crawl_forms = true;
+        Self {
+            context: ExtractionContext { config },
+            root: UrlWithDepth::from_url("https://www.example.com/index.html").unwrap(),
+            html: fixtures::html_page(HTML_SIZE),
+        }
+    }
+}
+
+impl Workload for HtmlExtractionWorkload {
+    const NAME: &'static str = "html_extraction";
+    const BUDGET: Duration = Duration::from_secs(60);
+
+    fn run(&mut self) -> usize {
+        extract_links(&self.root, &self.html, &self.context, None)
+            .map(|(_, links, _)| links.len())
+            .unwrap_or_default()
+    }
+}
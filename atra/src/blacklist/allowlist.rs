@@ -0,0 +1,269 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::blacklist::Blacklist;
+use crate::url::UrlWithDepth;
+use regex::{Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// The prefix of a regex entry of the allowlist.
+const REGEX_PREFIX: &str = "re:";
+
+/// The prefix of an entry matching every subdomain.
+const SUBDOMAIN_PREFIX: &str = "*.";
+
+/// An entry of the allowlist that is neither a host, a wildcard nor a valid regex.
+#[derive(Debug, Clone, Error)]
+#[error("The allowlist entry {0:?} is neither a host, a wildcard nor a regex starting with re:")]
+pub struct InvalidAllowlistPattern(String);
+
+/// An entry of the allowlist:
+/// - `www.example.com` allows exactly this host
+/// - `*.example.com` allows every subdomain of `example.com` at any depth, but not `example.com`
+/// - `re:<regex>` allows every url matched by the regex, like a blacklist entry
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AllowlistPattern {
+    Host(String),
+    Subdomains(String),
+    Regex(String),
+}
+
+impl AllowlistPattern {
+    pub fn new(pattern: &str) -> Result<Self, InvalidAllowlistPattern> {
+        let invalid = || InvalidAllowlistPattern(pattern.to_string());
+        if let Some(regex) = pattern.strip_prefix(REGEX_PREFIX) {
+            Regex::new(regex).map_err(|_| invalid())?;
+            return Ok(Self::Regex(regex.to_string()));
+        }
+        let normalized = pattern.trim().trim_end_matches('.').to_ascii_lowercase();
+        let (host, subdomains) = match normalized.strip_prefix(SUBDOMAIN_PREFIX) {
+            Some(host) => (host, true),
+            None => (normalized.as_str(), false),
+        };
+        let is_host = !host.is_empty()
+            && host.split('.').all(|label| {
+                !label.is_empty()
+                    && label
+                        .bytes()
+                        .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
+            });
+        if !is_host {
+            return Err(invalid());
+        }
+        Ok(if subdomains {
+            Self::Subdomains(host.to_string())
+        } else {
+            Self::Host(host.to_string())
+        })
+    }
+}
+
+impl Display for AllowlistPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AllowlistPattern::Host(host) => f.write_str(host),
+            AllowlistPattern::Subdomains(host) => write!(f, "{SUBDOMAIN_PREFIX}{host}"),
+            AllowlistPattern::Regex(regex) => write!(f, "{REGEX_PREFIX}{regex}"),
+        }
+    }
+}
+
+impl TryFrom<String> for AllowlistPattern {
+    type Error = InvalidAllowlistPattern;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<AllowlistPattern> for String {
+    fn from(value: AllowlistPattern) -> Self {
+        value.to_string()
+    }
+}
+
+/// The compiled [AllowlistPattern]s. The hosts are looked up in hash sets, every suffix of
+/// the host of an url after a dot is a candidate for the wildcards. The regex entries are
+/// compiled into a single [RegexSet].
+#[derive(Debug, Clone)]
+pub struct Allowlist {
+    hosts: HashSet<String>,
+    subdomains_of: HashSet<String>,
+    regexes: RegexSet,
+    empty: bool,
+}
+
+impl Allowlist {
+    pub fn new(patterns: &[AllowlistPattern]) -> Result<Self, regex::Error> {
+        let mut hosts = HashSet::new();
+        let mut subdomains_of = HashSet::new();
+        let mut regexes = Vec::new();
+        for pattern in patterns {
+            match pattern {
+                AllowlistPattern::Host(host) => {
+                    hosts.insert(host.clone());
+                }
+                AllowlistPattern::Subdomains(host) => {
+                    subdomains_of.insert(host.clone());
+                }
+                AllowlistPattern::Regex(regex) => regexes.push(regex.as_str()),
+            }
+        }
+        Ok(Self {
+            hosts,
+            subdomains_of,
+            regexes: RegexSet::new(regexes)?,
+            empty: patterns.is_empty(),
+        })
+    }
+
+    /// Returns true if there are no entries, every url is allowed.
+    pub fn is_empty(&self) -> bool {
+        self.empty
+    }
+
+    /// Returns true if the allowlist is empty or an entry matches the [url].
+    pub fn has_match_for(&self, url: &UrlWithDepth) -> bool {
+        if self.empty {
+            return true;
+        }
+        if let Some(host) = url.url.as_url().and_then(|value| value.host_str()) {
+            let host = host.trim_end_matches('.');
+            if self.hosts.contains(host) {
+                return true;
+            }
+            if !self.subdomains_of.is_empty()
+                && host
+                    .match_indices('.')
+                    .any(|(idx, _)| self.subdomains_of.contains(&host[idx + 1..]))
+            {
+                return true;
+            }
+        }
+        !self.regexes.is_empty() && self.regexes.is_match(&url.try_as_str())
+    }
+
+    /// Returns true if a found [url] may be enqueued: it is matched by the allowlist and not
+    /// matched by the [blacklist]. The blacklist always wins.
+    pub fn admits<B: Blacklist>(&self, blacklist: &B, url: &UrlWithDepth) -> bool {
+        self.has_match_for(url) && !blacklist.has_match_for(&url.try_as_str())
+    }
+}
+
+impl Default for Allowlist {
+    fn default() -> Self {
+        Self::new(&[]).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Allowlist, AllowlistPattern};
+    use crate::blacklist::{BlacklistType, PolyBlackList};
+    use crate::url::UrlWithDepth;
+
+    fn allowlist(patterns: &[&str]) -> Allowlist {
+        let patterns = patterns
+            .iter()
+            .map(|value| AllowlistPattern::new(value).unwrap())
+            .collect::<Vec<_>>();
+        Allowlist::new(&patterns).unwrap()
+    }
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    #[test]
+    fn parses_the_entries() {
+        assert_eq!(
+            AllowlistPattern::Host("www.example.com".to_string()),
+            AllowlistPattern::new(" WWW.Example.com. ").unwrap()
+        );
+        assert_eq!(
+            AllowlistPattern::Subdomains("uni-example.de".to_string()),
+            AllowlistPattern::new("*.uni-example.de").unwrap()
+        );
+        assert_eq!(
+            AllowlistPattern::Regex(r"^https://docs\.".to_string()),
+            AllowlistPattern::new(r"re:^https://docs\.").unwrap()
+        );
+        for invalid in [
+            "",
+            "*.",
+            "*example.com",
+            "a..b",
+            "a.*.b",
+            "https://a.b/",
+            "re:(",
+        ] {
+            assert!(AllowlistPattern::new(invalid).is_err(), "{invalid}");
+        }
+        for value in ["www.example.com", "*.uni-example.de", r"re:^https://docs\."] {
+            let pattern = AllowlistPattern::new(value).unwrap();
+            assert_eq!(value, pattern.to_string());
+            let json = serde_json::to_string(&pattern).unwrap();
+            assert_eq!(pattern, serde_json::from_str(&json).unwrap());
+        }
+    }
+
+    #[test]
+    fn an_empty_allowlist_allows_everything() {
+        let allowlist = Allowlist::default();
+        assert!(allowlist.is_empty());
+        assert!(allowlist.has_match_for(&url("https://www.example.com/")));
+        let blacklist = PolyBlackList::new(1, [r"example\.com"]).unwrap();
+        assert!(!allowlist.admits(&blacklist, &url("https://www.example.com/")));
+        assert!(allowlist.admits(&blacklist, &url("https://www.example.org/")));
+    }
+
+    #[test]
+    fn matches_hosts_wildcards_and_regexes() {
+        let allowlist = allowlist(&[
+            "www.example.com",
+            "*.uni-example.de",
+            r"re:^https://docs\.example\.org/v2/",
+        ]);
+        assert!(!allowlist.is_empty());
+
+        assert!(allowlist.has_match_for(&url("https://www.example.com/a")));
+        assert!(allowlist.has_match_for(&url("http://WWW.EXAMPLE.COM/b")));
+        assert!(!allowlist.has_match_for(&url("https://example.com/")));
+        assert!(!allowlist.has_match_for(&url("https://cdn.www.example.com/")));
+
+        assert!(allowlist.has_match_for(&url("https://www.uni-example.de/")));
+        assert!(allowlist.has_match_for(&url("https://a.b.uni-example.de/x")));
+        assert!(!allowlist.has_match_for(&url("https://uni-example.de/")));
+        assert!(!allowlist.has_match_for(&url("https://evil-uni-example.de/")));
+        assert!(!allowlist.has_match_for(&url("https://uni-example.de.evil.com/")));
+
+        assert!(allowlist.has_match_for(&url("https://docs.example.org/v2/index.html")));
+        assert!(!allowlist.has_match_for(&url("https://docs.example.org/v1/index.html")));
+    }
+
+    #[test]
+    fn the_blacklist_wins() {
+        let allowlist = allowlist(&["*.uni-example.de", "uni-example.de"]);
+        let blacklist = PolyBlackList::new(1, [r"^https?://cdn\.uni-example\.de/"]).unwrap();
+
+        assert!(allowlist.admits(&blacklist, &url("https://www.uni-example.de/")));
+        assert!(allowlist.admits(&blacklist, &url("https://uni-example.de/")));
+        assert!(!allowlist.admits(&blacklist, &url("https://cdn.uni-example.de/logo.png")));
+        assert!(!allowlist.admits(&blacklist, &url("https://www.example.com/")));
+    }
+}
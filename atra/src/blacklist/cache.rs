@@ -0,0 +1,310 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lru::LruCache;
+use regex::{Regex, RegexSet};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The number of urls with a cached verdict.
+const VERDICT_CAPACITY: NonZeroUsize = NonZeroUsize::new(8192).unwrap();
+
+/// The number of url prefixes with cached candidate rules.
+const PREFIX_CAPACITY: NonZeroUsize = NonZeroUsize::new(1024).unwrap();
+
+/// The verdicts served from the cache and the rules evaluated since the creation of a
+/// [CachedRuleSet].
+#[cfg(test)]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct VerdictCacheStats {
+    pub verdict_hits: u64,
+    pub rule_evaluations: u64,
+}
+
+#[derive(Debug)]
+struct Rule {
+    regex: Regex,
+    /// The literal every match starts with, None if the rule is not anchored at the start.
+    prefix: Option<String>,
+}
+
+#[derive(Debug)]
+struct VerdictCache {
+    /// The verdicts of exact urls, e.g. the assets referenced by every page.
+    verdicts: LruCache<Box<str>, bool>,
+    /// The indices of the rules that can match an url with the prefix.
+    candidates: LruCache<Box<str>, Arc<[usize]>>,
+}
+
+/// The rules of a regex blacklist with caches for its verdicts.
+///
+/// The caches belong to the rules: a patch of the blacklist manager increases its version and
+/// creates a new blacklist with empty caches, a stale verdict is never served.
+#[derive(Debug, Clone)]
+pub struct CachedRuleSet {
+    all: RegexSet,
+    rules: Arc<[Rule]>,
+    cache: Arc<Mutex<VerdictCache>>,
+    verdict_hits: Arc<AtomicU64>,
+    rule_evaluations: Arc<AtomicU64>,
+}
+
+impl CachedRuleSet {
+    pub fn new<S, I>(patterns: I) -> Result<Self, regex::Error>
+    where
+        S: AsRef<str>,
+        I: IntoIterator<Item = S>,
+    {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.as_ref().to_string())
+            .collect::<Vec<_>>();
+        let all = RegexSet::new(&patterns)?;
+        let rules = patterns
+            .iter()
+            .map(|pattern| {
+                Ok(Rule {
+                    regex: Regex::new(pattern)?,
+                    prefix: literal_prefix(pattern),
+                })
+            })
+            .collect::<Result<Arc<[_]>, regex::Error>>()?;
+        Ok(Self {
+            all,
+            rules,
+            cache: Arc::new(Mutex::new(VerdictCache {
+                verdicts: LruCache::new(VERDICT_CAPACITY),
+                candidates: LruCache::new(PREFIX_CAPACITY),
+            })),
+            verdict_hits: Arc::new(AtomicU64::new(0)),
+            rule_evaluations: Arc::new(AtomicU64::new(0)),
+        })
+    }
+
+    pub fn empty() -> Self {
+        Self::new(std::iter::empty::<&str>()).unwrap()
+    }
+
+    /// Returns true if any rule matches the [url], identical to a [RegexSet] of the rules.
+    /// Only the rules that can match an url with the prefix of [url] are evaluated.
+    pub fn is_match(&self, url: &str) -> bool {
+        let key = prefix_key(url);
+        let candidates = {
+            let mut cache = self.cache.lock().unwrap();
+            if let Some(verdict) = cache.verdicts.get(url) {
+                self.verdict_hits.fetch_add(1, Ordering::Relaxed);
+                return *verdict;
+            }
+            match cache.candidates.get(key) {
+                Some(candidates) => candidates.clone(),
+                None => {
+                    let candidates = self.candidates_for(key);
+                    cache.candidates.put(key.into(), candidates.clone());
+                    candidates
+                }
+            }
+        };
+        let verdict = self.evaluate(url, &candidates);
+        self.cache.lock().unwrap().verdicts.put(url.into(), verdict);
+        verdict
+    }
+
+    #[cfg(test)]
+    pub fn stats(&self) -> VerdictCacheStats {
+        VerdictCacheStats {
+            verdict_hits: self.verdict_hits.load(Ordering::Relaxed),
+            rule_evaluations: self.rule_evaluations.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The rules without a literal prefix or with one compatible with the [key].
+    /// An url starting with the [key] can only be matched by these rules.
+    fn candidates_for(&self, key: &str) -> Arc<[usize]> {
+        self.rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| {
+                rule.prefix.as_deref().map_or(true, |prefix| {
+                    prefix.starts_with(key) || key.starts_with(prefix)
+                })
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn evaluate(&self, url: &str, candidates: &[usize]) -> bool {
+        if candidates.len() == self.rules.len() {
+            // One pass over all rules is faster than testing them one by one.
+            self.rule_evaluations
+                .fetch_add(candidates.len() as u64, Ordering::Relaxed);
+            return self.all.is_match(url);
+        }
+        let mut evaluated = 0u64;
+        let verdict = candidates.iter().any(|index| {
+            evaluated += 1;
+            self.rules[*index].regex.is_match(url)
+        });
+        self.rule_evaluations
+            .fetch_add(evaluated, Ordering::Relaxed);
+        verdict
+    }
+}
+
+/// The prefix of [url] up to the end of its first path segment, e.g. `https://example.com/blog`.
+/// Urls sharing this prefix share the candidate rules.
+fn prefix_key(url: &str) -> &str {
+    const DELIMITERS: [char; 3] = ['/', '?', '#'];
+    let authority_start = url.find("://").map_or(0, |at| at + 3);
+    let authority_end = url[authority_start..]
+        .find(DELIMITERS)
+        .map_or(url.len(), |at| authority_start + at);
+    if !url[authority_end..].starts_with('/') {
+        return &url[..authority_end];
+    }
+    let segment_start = authority_end + 1;
+    let segment_end = url[segment_start..]
+        .find(DELIMITERS)
+        .map_or(url.len(), |at| segment_start + at);
+    &url[..segment_end]
+}
+
+/// The literal every match of the [pattern] starts with, None if the pattern is not anchored
+/// with a leading `^` or may contain an alternation.
+fn literal_prefix(pattern: &str) -> Option<String> {
+    let rest = pattern.strip_prefix('^')?;
+    if pattern.contains('|') {
+        return None;
+    }
+    let mut prefix = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        let literal = match c {
+            // Escaped letters and digits are classes, anchors or codes like \d, \b and \x41,
+            // \< and \> are word boundaries.
+            '\\' => match chars.next() {
+                Some(escaped)
+                    if escaped.is_ascii_punctuation() && !matches!(escaped, '<' | '>') =>
+                {
+                    escaped
+                }
+                _ => break,
+            },
+            '.' | '(' | ')' | '[' | ']' | '{' | '}' | '*' | '+' | '?' | '$' | '^' => break,
+            c => c,
+        };
+        match chars.peek() {
+            Some('?' | '*' | '{') => break,
+            Some('+') => {
+                prefix.push(literal);
+                break;
+            }
+            _ => prefix.push(literal),
+        }
+    }
+    Some(prefix)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{literal_prefix, prefix_key, CachedRuleSet};
+    use regex::RegexSet;
+
+    #[test]
+    fn extracts_the_literal_prefix_of_anchored_rules() {
+        for (pattern, expected) in [
+            (
+                r"^https://www\.example\.com/private",
+                Some("https://www.example.com/private"),
+            ),
+            (r"^https?://example\.com", Some("http")),
+            (r"^http://a\.de/x+y", Some("http://a.de/x")),
+            (r"^http://a\.de/\d+", Some("http://a.de/")),
+            (r"^http://a\.de/(?i)admin", Some("http://a.de/")),
+            (r"^http://a\.de/x{2}", Some("http://a.de/")),
+            (r"^(?i)HTTP://A\.DE", Some("")),
+            (r"^http://a\.de|^http://b\.de", None),
+            (r"www\.google\.de", None),
+            (r"(?i)^http://a\.de", None),
+        ] {
+            assert_eq!(
+                expected.map(str::to_string),
+                literal_prefix(pattern),
+                "{pattern}"
+            );
+        }
+    }
+
+    #[test]
+    fn keys_end_after_the_first_path_segment() {
+        assert_eq!(
+            "https://www.example.com/blog",
+            prefix_key("https://www.example.com/blog/2024/post.html")
+        );
+        assert_eq!(
+            "https://www.example.com/blog",
+            prefix_key("https://www.example.com/blog?page=2")
+        );
+        assert_eq!(
+            "https://www.example.com",
+            prefix_key("https://www.example.com?page=2")
+        );
+        assert_eq!(
+            "https://www.example.com/",
+            prefix_key("https://www.example.com/")
+        );
+        assert_eq!("google.de", prefix_key("google.de"));
+    }
+
+    #[test]
+    fn evaluates_fewer_rules_with_the_same_verdicts() {
+        let mut patterns = Vec::new();
+        for site in 0..58 {
+            for section in ["private", "admin", "tmp", "login", "cart"] {
+                patterns.push(format!(r"^https://site{site}\.example\.com/{section}/"));
+            }
+        }
+        for session in 0..10 {
+            patterns.push(format!(r"[?&]session{session}="));
+        }
+        assert_eq!(300, patterns.len());
+
+        let mut urls = Vec::new();
+        for index in 0..10_000 {
+            let site = index % 73;
+            urls.push(match index % 5 {
+                0 => format!("https://site{site}.example.com/private/page{index}.html"),
+                1 => format!("https://site{site}.example.com/blog/post{index}.html"),
+                2 => format!("https://site{site}.example.com/search?q={index}&session3=x"),
+                3 => format!("https://site{site}.example.com/assets/style.css"),
+                _ => format!("https://cdn.example.org/{index}/image.png"),
+            });
+        }
+
+        let uncached = RegexSet::new(&patterns).unwrap();
+        let cached = CachedRuleSet::new(&patterns).unwrap();
+        for url in &urls {
+            assert_eq!(uncached.is_match(url), cached.is_match(url), "{url}");
+        }
+        let stats = cached.stats();
+        let uncached_evaluations = (urls.len() * patterns.len()) as u64;
+        assert!(
+            stats.rule_evaluations * 10 < uncached_evaluations,
+            "{} of {uncached_evaluations} rules evaluated",
+            stats.rule_evaluations
+        );
+        // Every page references the same style sheet of its site.
+        assert!(stats.verdict_hits >= 1_900, "{stats:?}");
+    }
+}
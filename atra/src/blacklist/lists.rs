@@ -12,9 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::blacklist::cache::CachedRuleSet;
 use crate::blacklist::traits::{Blacklist, BlacklistType};
 use crate::blacklist::ManageableBlacklist;
-use regex::RegexSet;
 use std::str::FromStr;
 use thiserror::Error;
 
@@ -113,7 +113,7 @@ impl BlacklistType for EmptyBlackList {
 #[derive(Debug, Clone)]
 pub struct RegexBlackList {
     version: u64,
-    inner: RegexSet,
+    inner: CachedRuleSet,
 }
 
 impl ManageableBlacklist for RegexBlackList {}
@@ -138,7 +138,7 @@ impl BlacklistType for RegexBlackList {
     {
         Ok(Self {
             version,
-            inner: RegexSet::new(src)?,
+            inner: CachedRuleSet::new(src)?,
         })
     }
 }
@@ -149,7 +149,7 @@ impl FromStr for RegexBlackList {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(Self {
             version: 0,
-            inner: RegexSet::new(std::iter::once(s))?,
+            inner: CachedRuleSet::new(std::iter::once(s))?,
         })
     }
 }
@@ -158,7 +158,7 @@ impl Default for RegexBlackList {
     fn default() -> Self {
         Self {
             version: 0,
-            inner: RegexSet::empty(),
+            inner: CachedRuleSet::empty(),
         }
     }
 }
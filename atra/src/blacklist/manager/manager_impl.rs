@@ -353,7 +353,10 @@ impl PartialEq<Self> for BlacklistEntry {
 
 #[cfg(test)]
 mod test {
-    use super::InnerBlacklistManager;
+    use super::{InMemoryBlacklistManager, InnerBlacklistManager};
+    use crate::blacklist::{Blacklist, BlacklistManager, PolyBlackList};
+    use crate::runtime::GracefulShutdownWithGuard;
+    use camino_tempfile::Utf8TempDir;
     use scopeguard::defer;
 
     #[test]
@@ -420,4 +423,44 @@ mod test {
         assert!(values.contains(&"www.google.de".into()));
         assert!(values.contains(&"#.Ebay.com".into()));
     }
+
+    #[tokio::test]
+    async fn a_patch_replaces_the_cached_verdicts() {
+        let dir = Utf8TempDir::new().unwrap();
+        let manager = InMemoryBlacklistManager::<PolyBlackList>::open(
+            dir.path().join("blacklist.txt"),
+            GracefulShutdownWithGuard::new(),
+        )
+        .unwrap();
+        manager
+            .apply_patch([r"^https://www\.example\.com/private/".to_string()])
+            .await;
+        let blacklist = manager.get_blacklist().await;
+
+        let urls = [
+            "https://www.example.com/private/a.html",
+            "https://www.example.com/public/a.html",
+            "https://www.example.com/public/b.html?session=1",
+        ];
+        for _ in 0..2 {
+            assert_eq!(
+                [true, false, false],
+                urls.map(|url| blacklist.has_match_for(url))
+            );
+        }
+
+        manager
+            .apply_patch([
+                r"^https://www\.example\.com/public/a".to_string(),
+                r"[?&]session=".to_string(),
+            ])
+            .await;
+        assert_eq!(3, blacklist.version());
+        for _ in 0..2 {
+            assert_eq!(
+                [true, true, true],
+                urls.map(|url| blacklist.has_match_for(url))
+            );
+        }
+    }
 }
@@ -36,7 +36,7 @@ pub trait BlacklistManager {
 }
 
 /// Blacklist error
-#[derive(Debug, Copy, Clone, Error)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Error)]
 pub enum BlacklistError {
     /// A blacklist entry can not contain a newline.
     #[error("Tried to add something with a new line separator to the queue.")]
@@ -44,4 +44,24 @@ pub enum BlacklistError {
     /// A blacklist entry can not not be empty.
     #[error("Tried to add an empty string to the queue")]
     EmptyStringsNotAllowed,
+    /// A blacklist entry has to be a valid regex.
+    #[error("Tried to add an invalid regex to the blacklist.")]
+    InvalidRegex,
+    /// A blacklist entry can only be added once.
+    #[error("The entry is already in the blacklist.")]
+    AlreadyContained,
+}
+
+/// Checks if [value] can be added to a blacklist.
+pub fn validate_entry(value: &str) -> Result<(), BlacklistError> {
+    if value.is_empty() {
+        return Err(BlacklistError::EmptyStringsNotAllowed);
+    }
+    if value.contains('\n') {
+        return Err(BlacklistError::NewLinesNotAllowed);
+    }
+    if regex::Regex::new(value).is_err() {
+        return Err(BlacklistError::InvalidRegex);
+    }
+    Ok(())
 }
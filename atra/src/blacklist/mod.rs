@@ -12,24 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod allowlist;
+mod cache;
 mod lists;
 mod manage;
 mod manager;
+mod patching;
 mod traits;
 
+pub use allowlist::{Allowlist, AllowlistPattern, InvalidAllowlistPattern};
 use cfg_if::cfg_if;
 pub use manage::*;
 pub use manager::manager_impl::InMemoryBlacklistManager;
 pub use manager::manager_impl::InMemoryBlacklistManagerInitialisationError;
-#[cfg(test)]
-pub use manager::BlacklistError;
-pub use manager::BlacklistManager;
+pub use manager::{validate_entry, BlacklistError, BlacklistManager};
+pub use patching::*;
 pub use traits::*;
 
 cfg_if! {
     if #[cfg(test)] {
         pub use lists::*;
     } else {
-        pub use lists::PolyBlackList;
+        pub use lists::{PolyBlackList, PolyBlackListError};
     }
 }
@@ -0,0 +1,308 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::blacklist::{validate_entry, BlacklistError, BlacklistManager};
+use crate::io::simple_line::SupportsSimpleLineReader;
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::schemas::{AuditAction, AuditLog, AuditOutcome, AuditSource};
+use camino::Utf8Path;
+use std::collections::{BTreeSet, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::time::{Duration, SystemTime};
+use thiserror::Error;
+use tokio::select;
+
+/// An entry rejected by [add_entries].
+#[derive(Debug, Clone, Eq, PartialEq, Error)]
+#[error("The blacklist entry {entry:?} was rejected: {reason}")]
+pub struct RejectedEntry {
+    pub entry: String,
+    pub reason: BlacklistError,
+}
+
+/// Adds all [entries] to the blacklist of [manager], nothing is added if one of them is rejected.
+/// An entry is rejected if it is invalid, already blacklisted or contained twice.
+/// Returns the version of the blacklist after the change.
+pub async fn add_entries<M: BlacklistManager>(
+    manager: &M,
+    entries: Vec<String>,
+) -> Result<u64, RejectedEntry> {
+    let existing: HashSet<String> = manager
+        .get_patch(0)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    {
+        let mut seen = HashSet::with_capacity(entries.len());
+        for entry in &entries {
+            let reason = match validate_entry(entry) {
+                Err(reason) => Some(reason),
+                Ok(_) if existing.contains(entry) || !seen.insert(entry.as_str()) => {
+                    Some(BlacklistError::AlreadyContained)
+                }
+                Ok(_) => None,
+            };
+            if let Some(reason) = reason {
+                return Err(RejectedEntry {
+                    entry: entry.clone(),
+                    reason,
+                });
+            }
+        }
+    }
+    manager.apply_patch(entries).await;
+    Ok(manager.current_version().await)
+}
+
+/// Like [add_entries], the change requested by [source] is recorded in the [audit] trail.
+pub async fn add_entries_audited<M: BlacklistManager>(
+    manager: &M,
+    entries: Vec<String>,
+    source: AuditSource,
+    audit: Option<&AuditLog>,
+) -> Result<u64, RejectedEntry> {
+    let Some(audit) = audit else {
+        return add_entries(manager, entries).await;
+    };
+    let version_before = manager.current_version().await;
+    let result = add_entries(manager, entries.clone()).await;
+    let (version_after, outcome) = match &result {
+        Ok(version) => (*version, AuditOutcome::Applied),
+        Err(err) => (
+            version_before,
+            AuditOutcome::Rejected {
+                reason: err.to_string(),
+            },
+        ),
+    };
+    audit.append(
+        source,
+        AuditAction::BlacklistPatch {
+            entries,
+            version_before,
+            version_after,
+        },
+        outcome,
+    );
+    result
+}
+
+/// Reads the valid entries of the blacklist file at [path], invalid entries are logged.
+fn read_entries(path: &Utf8Path) -> io::Result<Vec<String>> {
+    let reader = BufReader::new(File::open(path)?).to_simple_line_reader();
+    let mut entries = Vec::new();
+    for line in reader {
+        let line = line?;
+        if line.is_empty() {
+            continue;
+        }
+        match validate_entry(&line) {
+            Ok(_) => entries.push(line),
+            Err(err) => log::warn!("Ignore the blacklist entry {line:?} of {path}: {err}"),
+        }
+    }
+    Ok(entries)
+}
+
+/// Adds the entries of the file at [path] that are not in the blacklist of [manager] yet.
+/// Returns the sorted added entries.
+async fn reload<M: BlacklistManager>(manager: &M, path: &Utf8Path) -> io::Result<Vec<String>> {
+    let existing: HashSet<String> = manager
+        .get_patch(0)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let mut added = BTreeSet::new();
+    for entry in read_entries(path)? {
+        if !existing.contains(&entry) {
+            added.insert(entry);
+        }
+    }
+    let added = added.into_iter().collect::<Vec<_>>();
+    if !added.is_empty() {
+        manager.apply_patch(added.clone()).await;
+    }
+    Ok(added)
+}
+
+/// Polls the blacklist file at [path] every [interval] until the [shutdown]. The file is read
+/// when it is modified and its new entries are added to the blacklist of [manager].
+/// Every reload adding entries is recorded in the [audit] trail.
+pub async fn watch_blacklist_file<M: BlacklistManager>(
+    manager: &M,
+    path: &Utf8Path,
+    interval: Duration,
+    audit: Option<&AuditLog>,
+    shutdown: ShutdownChild,
+) {
+    log::info!("Watch the blacklist file {path}");
+    let mut last_seen: Option<(SystemTime, u64)> = None;
+    loop {
+        let modified = std::fs::metadata(path)
+            .and_then(|meta| Ok((meta.modified()?, meta.len())))
+            .ok();
+        if modified.is_some() && modified != last_seen {
+            last_seen = modified;
+            let version_before = manager.current_version().await;
+            match reload(manager, path).await {
+                Ok(added) if added.is_empty() => {}
+                Ok(added) => {
+                    log::info!("Added {} entries of {path} to the blacklist.", added.len());
+                    if let Some(audit) = audit {
+                        audit.append(
+                            AuditSource::BlacklistFile {
+                                path: path.to_string(),
+                            },
+                            AuditAction::BlacklistPatch {
+                                entries: added,
+                                version_before,
+                                version_after: manager.current_version().await,
+                            },
+                            AuditOutcome::Applied,
+                        );
+                    }
+                }
+                Err(err) => log::error!("Failed to read the blacklist file {path}: {err}"),
+            }
+        }
+        select! {
+            _ = shutdown.wait() => break,
+            _ = tokio::time::sleep(interval) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{add_entries, watch_blacklist_file, RejectedEntry};
+    use crate::blacklist::{
+        Blacklist, BlacklistError, BlacklistManager, InMemoryBlacklistManager, PolyBlackList,
+    };
+    use crate::runtime::{GracefulShutdown, GracefulShutdownWithGuard, ShutdownSender};
+    use crate::schemas::{read_audit_trail, AuditAction, AuditLog};
+    use camino_tempfile::Utf8TempDir;
+    use std::time::Duration;
+
+    fn open_manager(dir: &Utf8TempDir) -> InMemoryBlacklistManager<PolyBlackList> {
+        InMemoryBlacklistManager::open(
+            dir.path().join("blacklist.txt"),
+            GracefulShutdownWithGuard::new(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_and_duplicate_entries() {
+        let dir = Utf8TempDir::new().unwrap();
+        let manager = open_manager(&dir);
+
+        let version = add_entries(&manager, vec!["example\\.com".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(1, version);
+
+        for (entries, rejected, reason) in [
+            (
+                vec!["other\\.com", ""],
+                "",
+                BlacklistError::EmptyStringsNotAllowed,
+            ),
+            (vec!["a\nb"], "a\nb", BlacklistError::NewLinesNotAllowed),
+            (vec!["("], "(", BlacklistError::InvalidRegex),
+            (
+                vec!["example\\.com"],
+                "example\\.com",
+                BlacklistError::AlreadyContained,
+            ),
+            (
+                vec!["other\\.com", "other\\.com"],
+                "other\\.com",
+                BlacklistError::AlreadyContained,
+            ),
+        ] {
+            let entries = entries.into_iter().map(String::from).collect();
+            assert_eq!(
+                Err(RejectedEntry {
+                    entry: rejected.to_string(),
+                    reason
+                }),
+                add_entries(&manager, entries).await
+            );
+        }
+        assert_eq!(1, manager.current_version().await);
+        assert!(!manager
+            .get_blacklist()
+            .await
+            .has_match_for("https://other.com/"));
+    }
+
+    #[tokio::test]
+    async fn reloads_the_watched_file() {
+        let dir = Utf8TempDir::new().unwrap();
+        let manager = open_manager(&dir);
+        let watched = dir.path().join("watched.txt");
+        std::fs::write(&watched, "# comment\nexample\\.com\n").unwrap();
+
+        let shutdown = GracefulShutdown::new();
+        let child = shutdown.child().create_child();
+        let audit = AuditLog::open(dir.path()).unwrap();
+        let watch = watch_blacklist_file(
+            &manager,
+            &watched,
+            Duration::from_millis(10),
+            Some(&audit),
+            child,
+        );
+        let check = async {
+            let blacklist = manager.get_blacklist().await;
+            while !blacklist.has_match_for("https://example.com/") {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            std::fs::write(&watched, "# comment\nexample\\.com\n(\nother\\.com\n").unwrap();
+            while !blacklist.has_match_for("https://other.com/") {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            shutdown.child().shutdown();
+        };
+        tokio::time::timeout(Duration::from_secs(5), async { tokio::join!(watch, check) })
+            .await
+            .unwrap();
+        assert_eq!(2, manager.current_version().await);
+
+        let actions = read_audit_trail(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|record| record.action)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                AuditAction::BlacklistPatch {
+                    entries: vec!["example\\.com".to_string()],
+                    version_before: 0,
+                    version_after: 1,
+                },
+                AuditAction::BlacklistPatch {
+                    entries: vec!["other\\.com".to_string()],
+                    version_before: 1,
+                    version_after: 2,
+                },
+            ],
+            actions
+        );
+    }
+}
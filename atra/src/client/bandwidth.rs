@@ -0,0 +1,270 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::bandwidth::{BandwidthConfig, BandwidthLimit};
+use crate::url::AtraUrlOrigin;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The number of origin buckets kept before the idle ones are dropped.
+const MAX_IDLE_ORIGINS: usize = 1024;
+
+/// The seconds the throughput is averaged over.
+const THROUGHPUT_WINDOW: usize = 5;
+
+/// Limits the bytes downloaded by all clients of a crawl, globally and per origin.
+/// Only created if a limit is set, so an unlimited crawl does not pay for it.
+#[derive(Debug)]
+pub struct BandwidthLimiter {
+    global: Option<TokenBucket>,
+    per_origin: Option<BandwidthLimit>,
+    origins: Mutex<HashMap<AtraUrlOrigin, Arc<TokenBucket>>>,
+    throughput: ThroughputMeter,
+}
+
+impl BandwidthLimiter {
+    /// Creates the limiter for the [config], None if nothing is limited.
+    pub fn from_config(config: &BandwidthConfig) -> Option<Self> {
+        if config.is_unlimited() {
+            return None;
+        }
+        let now = Instant::now();
+        Some(Self {
+            global: config.global.map(|limit| TokenBucket::new(limit, now)),
+            per_origin: config.per_origin,
+            origins: Mutex::new(HashMap::new()),
+            throughput: ThroughputMeter::new(now),
+        })
+    }
+
+    /// The throttle for a download from the [origin].
+    pub fn for_origin(&self, origin: Option<&AtraUrlOrigin>) -> Throttle<'_> {
+        let origin = match (self.per_origin, origin) {
+            (Some(limit), Some(origin)) => {
+                let now = Instant::now();
+                let mut origins = self.origins.lock().unwrap();
+                if origins.len() >= MAX_IDLE_ORIGINS && !origins.contains_key(origin) {
+                    // A full bucket without a download behaves like a new one.
+                    origins
+                        .retain(|_, bucket| Arc::strong_count(bucket) > 1 || !bucket.is_full(now));
+                }
+                Some(
+                    origins
+                        .entry(origin.clone())
+                        .or_insert_with(|| Arc::new(TokenBucket::new(limit, now)))
+                        .clone(),
+                )
+            }
+            _ => None,
+        };
+        Throttle {
+            limiter: self,
+            origin,
+        }
+    }
+
+    /// The bytes per second downloaded over the last few seconds.
+    pub fn throughput(&self) -> u64 {
+        self.throughput.bytes_per_second(Instant::now())
+    }
+}
+
+/// Throttles a single download.
+#[derive(Debug)]
+pub struct Throttle<'a> {
+    limiter: &'a BandwidthLimiter,
+    origin: Option<Arc<TokenBucket>>,
+}
+
+impl Throttle<'_> {
+    /// Counts the downloaded [bytes] and waits until the limits allow further bytes.
+    pub async fn consume(&self, bytes: usize) {
+        let now = Instant::now();
+        let bytes = bytes as u64;
+        self.limiter.throughput.record(bytes, now);
+        let global = self
+            .limiter
+            .global
+            .as_ref()
+            .map_or(Duration::ZERO, |bucket| bucket.take(bytes, now));
+        let origin = self
+            .origin
+            .as_ref()
+            .map_or(Duration::ZERO, |bucket| bucket.take(bytes, now));
+        let wait = global.max(origin);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// A token bucket refilled with the rate of a [BandwidthLimit] up to its burst.
+/// A chunk is taken as a whole even if the bucket runs into debt, the debt is paid by waiting.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: BandwidthLimit, now: Instant) -> Self {
+        let capacity = limit.burst().get() as f64;
+        Self {
+            rate: limit.bytes_per_second.get() as f64,
+            capacity,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                refilled_at: now,
+            }),
+        }
+    }
+
+    fn refill(&self, state: &mut BucketState, now: Instant) {
+        let elapsed = now.saturating_duration_since(state.refilled_at);
+        state.tokens = (state.tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+        state.refilled_at = now;
+    }
+
+    /// Takes [bytes] from the bucket and returns how long to wait until it is out of debt.
+    fn take(&self, bytes: u64, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state, now);
+        state.tokens -= bytes as f64;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.rate)
+        }
+    }
+
+    fn is_full(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        self.refill(&mut state, now);
+        state.tokens >= self.capacity
+    }
+}
+
+/// Counts the downloaded bytes per second for the last [THROUGHPUT_WINDOW] seconds.
+#[derive(Debug)]
+struct ThroughputMeter {
+    started_at: Instant,
+    /// The second since [started_at] and its bytes.
+    slots: Mutex<[(u64, u64); THROUGHPUT_WINDOW]>,
+}
+
+impl ThroughputMeter {
+    fn new(now: Instant) -> Self {
+        Self {
+            started_at: now,
+            slots: Mutex::new([(0, 0); THROUGHPUT_WINDOW]),
+        }
+    }
+
+    fn second_of(&self, now: Instant) -> u64 {
+        now.saturating_duration_since(self.started_at).as_secs()
+    }
+
+    fn record(&self, bytes: u64, now: Instant) {
+        let second = self.second_of(now);
+        let mut slots = self.slots.lock().unwrap();
+        let slot = &mut slots[(second % THROUGHPUT_WINDOW as u64) as usize];
+        if slot.0 != second {
+            *slot = (second, 0);
+        }
+        slot.1 += bytes;
+    }
+
+    /// The average over the completed seconds of the window.
+    fn bytes_per_second(&self, now: Instant) -> u64 {
+        let second = self.second_of(now);
+        let window = second.min(THROUGHPUT_WINDOW as u64 - 1);
+        if window == 0 {
+            return 0;
+        }
+        let slots = self.slots.lock().unwrap();
+        let bytes: u64 = slots
+            .iter()
+            .filter(|(slot, _)| *slot < second && *slot + window >= second)
+            .map(|(_, bytes)| *bytes)
+            .sum();
+        bytes / window
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BandwidthLimiter, ThroughputMeter, TokenBucket};
+    use crate::config::bandwidth::{BandwidthConfig, BandwidthLimit};
+    use crate::url::AtraOriginProvider;
+    use std::num::NonZeroU64;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn limit(bytes_per_second: u64, burst: Option<u64>) -> BandwidthLimit {
+        BandwidthLimit {
+            bytes_per_second: NonZeroU64::new(bytes_per_second).unwrap(),
+            burst: burst.and_then(NonZeroU64::new),
+        }
+    }
+
+    #[test]
+    fn the_bucket_allows_the_burst_and_then_the_rate() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new(limit(1000, Some(2000)), start);
+        assert_eq!(Duration::ZERO, bucket.take(2000, start));
+        assert_eq!(Duration::from_millis(500), bucket.take(500, start));
+        // The debt is paid after half a second, another half second refills 500 bytes.
+        let later = start + Duration::from_secs(1);
+        assert_eq!(Duration::ZERO, bucket.take(500, later));
+        assert!(!bucket.is_full(later));
+        assert!(bucket.is_full(later + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn averages_the_completed_seconds() {
+        let start = Instant::now();
+        let meter = ThroughputMeter::new(start);
+        meter.record(1000, start);
+        assert_eq!(0, meter.bytes_per_second(start));
+        meter.record(3000, start + Duration::from_millis(1500));
+        assert_eq!(2000, meter.bytes_per_second(start + Duration::from_secs(2)));
+        assert_eq!(0, meter.bytes_per_second(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn is_only_created_with_a_limit() {
+        assert!(BandwidthLimiter::from_config(&BandwidthConfig::default()).is_none());
+        let limiter = BandwidthLimiter::from_config(&BandwidthConfig {
+            global: None,
+            per_origin: Some(limit(1000, None)),
+        })
+        .unwrap();
+        let origin = "https://www.example.com/"
+            .parse::<url::Url>()
+            .unwrap()
+            .atra_origin();
+        assert!(limiter.for_origin(origin.as_ref()).origin.is_some());
+        assert!(limiter.for_origin(None).origin.is_none());
+        assert_eq!(1, limiter.origins.lock().unwrap().len());
+    }
+}
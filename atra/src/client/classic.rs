@@ -12,25 +12,34 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::config::crawl::RedirectPolicy;
+use crate::client::proxy::{ProxyRouter, RoutingResolver, SystemResolver};
+use crate::client::recording::RecordingClient;
+use crate::config::crawl::{HttpVersionPolicy, RedirectPolicy};
 use crate::config::Config;
 use crate::contexts::traits::{SupportsConfigs, SupportsCrawling};
 use crate::seed::BasicSeed;
 use crate::toolkit::domains::domain_name;
-use crate::url::{AtraOriginProvider, UrlWithDepth};
+use crate::toolkit::CaseInsensitiveString;
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
 use http_cache_reqwest::{CACacheManager, Cache, CacheMode, HttpCache, HttpCacheOptions};
+use reqwest::cookie::Jar;
 use reqwest::redirect::Attempt;
-use reqwest::Error;
+use reqwest::{Error, StatusCode};
 use reqwest_middleware::{ClientBuilder, ClientWithMiddleware};
 use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::Arc;
 use time::Duration;
+use url::Url;
 
-/// Builds the classic configured client used by Atra
+/// The redirects followed by the default policy of reqwest.
+const DEFAULT_REDIRECT_LIMIT: usize = 10;
+
+/// Builds the classic configured client used by Atra, the requests are routed by [proxies].
 pub fn build_classic_client<C: SupportsCrawling, T: BasicSeed>(
     context: &C,
     seed: &T,
     useragent: impl AsRef<str>,
+    proxies: &Arc<ProxyRouter>,
 ) -> Result<ClientWithMiddleware, Error>
 where
     C: SupportsCrawling + SupportsConfigs,
@@ -41,14 +50,13 @@ where
     let mut client = reqwest::Client::builder()
         .user_agent(useragent.as_ref())
         .danger_accept_invalid_certs(configs.crawl.accept_invalid_certs)
-        .tcp_keepalive(Duration::milliseconds(500).unsigned_abs())
-        .pool_idle_timeout(None);
+        .tls_info(configs.crawl.tls_capture.is_some())
+        .tcp_keepalive(Duration::milliseconds(500).unsigned_abs());
 
-    //todo
-    // http2_prior_knowledge
+    client = setup_connections(client, configs);
 
-    if let Some(ref headers) = configs.crawl.headers {
-        client = client.default_headers(headers.clone());
+    if let Some(headers) = configs.crawl.headers_for(seed.origin()) {
+        client = client.default_headers(headers.into_owned());
     }
 
     let url = seed.url();
@@ -57,8 +65,7 @@ where
 
     if let Some(timeout) = configs
         .crawl
-        .budget
-        .get_budget_for(&seed.origin())
+        .budget_for(seed.origin())
         .get_request_timeout()
         .copied()
     {
@@ -66,28 +73,17 @@ where
         client = client.timeout(timeout.unsigned_abs());
     }
 
-    client = if let Some(cookies) = &configs.crawl.cookies {
-        if let Some(cookie) = cookies.get_cookies_for(&seed.origin()) {
-            let cookie_store = reqwest::cookie::Jar::default();
-            if let Some(url) = url.clean_url().as_url() {
-                cookie_store.add_cookie_str(cookie.as_str(), url);
-            }
-            client.cookie_provider(cookie_store.into())
-        } else {
-            client.cookie_store(configs.crawl.use_cookies)
-        }
-    } else {
-        client.cookie_store(configs.crawl.use_cookies)
-    };
+    if let Some(cookies) = setup_cookies(configs, seed) {
+        client = client.cookie_provider(cookies);
+    }
 
-    if let Some(ref proxies) = configs.crawl.proxies {
-        for proxy in proxies {
-            match reqwest::Proxy::all(proxy) {
-                Ok(proxy) => {
-                    client = client.proxy(proxy);
-                }
-                _ => {}
-            }
+    if !proxies.is_empty() {
+        client = client.proxy(proxies.to_reqwest_proxy());
+        if proxies.has_remote_dns() {
+            client = client.dns_resolver(Arc::new(RoutingResolver::new(
+                proxies.clone(),
+                SystemResolver,
+            )));
         }
     }
 
@@ -103,57 +99,351 @@ where
     Ok(client.build())
 }
 
+/// Builds the client recording the header lines of the responses, None if the config does not
+/// ask for `faithful_headers`. The client only speaks HTTP/1, it follows the redirect policy
+/// and sends the cookies of the classic client but neither uses the proxies nor the cache.
+pub fn build_recording_client<C, T>(context: &C, seed: &T) -> Option<RecordingClient>
+where
+    C: SupportsConfigs,
+    T: BasicSeed,
+{
+    let configs = context.configs();
+    if !configs.crawl.faithful_headers {
+        return None;
+    }
+    if configs.crawl.http_version == HttpVersionPolicy::Http2PriorKnowledge {
+        log::warn!(
+            "The header lines are only recorded for HTTP/1, the responses of {} keep the normalized headers.",
+            seed.url()
+        );
+        return None;
+    }
+    let tls = match native_tls::TlsConnector::builder()
+        .danger_accept_invalid_certs(configs.crawl.accept_invalid_certs)
+        .build()
+    {
+        Ok(tls) => tls,
+        Err(err) => {
+            log::error!(
+                "Failed to set up tls for recording the header lines, the responses of {} keep the normalized headers: {err}",
+                seed.url()
+            );
+            return None;
+        }
+    };
+    let timeout = configs
+        .crawl
+        .budget_for(seed.origin())
+        .get_request_timeout()
+        .map(|timeout| timeout.unsigned_abs());
+    Some(RecordingClient::new(
+        tls,
+        &configs.system.connection_pool,
+        timeout,
+        RedirectRules::new(configs, seed.url()),
+        setup_cookies(configs, seed),
+    ))
+}
+
+/// The cookie store for the requests of [seed], None if no cookies are used.
+fn setup_cookies<T: BasicSeed>(config: &Config, seed: &T) -> Option<Arc<Jar>> {
+    if let Some(cookie) = config.crawl.cookies_for(seed.origin()) {
+        let cookie_store = Jar::default();
+        if let Some(url) = seed.url().clean_url().as_url() {
+            cookie_store.add_cookie_str(cookie.as_str(), url);
+        }
+        Some(Arc::new(cookie_store))
+    } else if config.crawl.use_cookies {
+        Some(Arc::new(Jar::default()))
+    } else {
+        None
+    }
+}
+
+/// Applies the http version and the pool of idle connections of the [config] to the [client].
+fn setup_connections(
+    mut client: reqwest::ClientBuilder,
+    config: &Config,
+) -> reqwest::ClientBuilder {
+    client = match config.crawl.http_version {
+        HttpVersionPolicy::Auto => client,
+        HttpVersionPolicy::Http1Only => client.http1_only(),
+        HttpVersionPolicy::Http2PriorKnowledge => client.http2_prior_knowledge(),
+    };
+    let pool = &config.system.connection_pool;
+    if let Some(max_idle) = pool.max_idle_per_host {
+        client = client.pool_max_idle_per_host(max_idle);
+    }
+    client.pool_idle_timeout(pool.idle_timeout.map(|timeout| timeout.unsigned_abs()))
+}
+
 fn setup_redirect_policy(config: &Config, url: &UrlWithDepth) -> reqwest::redirect::Policy {
     match config.crawl.redirect_policy {
         RedirectPolicy::Loose => reqwest::redirect::Policy::limited(config.crawl.redirect_limit),
         RedirectPolicy::Strict => {
-            let host_s = url.atra_origin().unwrap_or_default();
-            let default_policy = reqwest::redirect::Policy::default();
-            let initial_redirect = Arc::new(AtomicU8::new(0));
-            let initial_redirect_limit = if config.crawl.respect_robots_txt {
+            let rules = RedirectRules::new(config, url);
+            reqwest::redirect::Policy::custom(move |attempt: Attempt| {
+                match rules.check(attempt.status(), attempt.url(), attempt.previous()) {
+                    RedirectAction::Follow => attempt.follow(),
+                    RedirectAction::Stop => attempt.stop(),
+                    RedirectAction::TooManyRedirects => attempt.error("too many redirects"),
+                }
+            })
+        }
+    }
+}
+
+/// What to do with a redirect.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum RedirectAction {
+    Follow,
+    /// Returns the redirect as response.
+    Stop,
+    /// Fails the request.
+    TooManyRedirects,
+}
+
+/// The redirects followed for the urls of a seed as configured by the [RedirectPolicy].
+/// The clones share the number of the initial redirects.
+#[derive(Debug, Clone)]
+pub(crate) struct RedirectRules {
+    policy: RedirectPolicy,
+    redirect_limit: usize,
+    seed: UrlWithDepth,
+    origin: AtraUrlOrigin,
+    domain_name: CaseInsensitiveString,
+    subdomains: bool,
+    tld: bool,
+    initial_redirect: Arc<AtomicU8>,
+    initial_redirect_limit: u8,
+}
+
+impl RedirectRules {
+    pub(crate) fn new(config: &Config, url: &UrlWithDepth) -> Self {
+        let tld = config.crawl.tld;
+        Self {
+            policy: config.crawl.redirect_policy.clone(),
+            redirect_limit: config.crawl.redirect_limit,
+            seed: url.clone(),
+            origin: url.atra_origin().unwrap_or_default(),
+            domain_name: if tld {
+                url.domain_name().unwrap_or_default()
+            } else {
+                Default::default()
+            },
+            subdomains: config.crawl.subdomains,
+            tld,
+            initial_redirect: Arc::new(AtomicU8::new(0)),
+            initial_redirect_limit: if config.crawl.respect_robots_txt {
                 2
             } else {
                 1
-            };
-            let subdomains = config.crawl.subdomains;
-            let tld = config.crawl.tld;
-            let host_domain_name = if tld {
-                url.domain_name().unwrap_or_default()
+            },
+        }
+    }
+
+    /// Decides about the redirect with the [status] to [next], the [previous] urls are the
+    /// urls requested so far.
+    pub(crate) fn check(&self, status: StatusCode, next: &Url, previous: &[Url]) -> RedirectAction {
+        fn limited(previous: &[Url], limit: usize) -> RedirectAction {
+            if previous.len() >= limit {
+                RedirectAction::TooManyRedirects
             } else {
-                Default::default()
-            };
-            let redirect_limit = config.crawl.redirect_limit;
-
-            let to_mode = url.clone();
-
-            let custom_policy = {
-                move |attempt: Attempt| {
-                    let attempt_url = domain_name(attempt.url()).unwrap_or_default();
-
-                    if tld && attempt_url == host_domain_name
-                        || subdomains
-                            && attempt
-                                .url()
-                                .host_str()
-                                .unwrap_or_default()
-                                .ends_with(host_s.as_ref())
-                        || to_mode.url().same_host_url(&attempt.url())
-                    {
-                        default_policy.redirect(attempt)
-                    } else if attempt.previous().len() > redirect_limit {
-                        attempt.error("too many redirects")
-                    } else if attempt.status().is_redirection()
-                        && (0..initial_redirect_limit)
-                            .contains(&initial_redirect.load(Ordering::Relaxed))
-                    {
-                        initial_redirect.fetch_add(1, Ordering::Relaxed);
-                        default_policy.redirect(attempt)
-                    } else {
-                        attempt.stop()
+                RedirectAction::Follow
+            }
+        }
+
+        match self.policy {
+            RedirectPolicy::Loose => limited(previous, self.redirect_limit),
+            RedirectPolicy::Strict => {
+                let attempt_url = domain_name(next).unwrap_or_default();
+                if self.tld && attempt_url == self.domain_name
+                    || self.subdomains
+                        && next
+                            .host_str()
+                            .unwrap_or_default()
+                            .ends_with(self.origin.as_ref())
+                    || self.seed.url().same_host_url(next)
+                {
+                    limited(previous, DEFAULT_REDIRECT_LIMIT)
+                } else if previous.len() > self.redirect_limit {
+                    RedirectAction::TooManyRedirects
+                } else if status.is_redirection()
+                    && (0..self.initial_redirect_limit)
+                        .contains(&self.initial_redirect.load(Ordering::Relaxed))
+                {
+                    self.initial_redirect.fetch_add(1, Ordering::Relaxed);
+                    limited(previous, DEFAULT_REDIRECT_LIMIT)
+                } else {
+                    RedirectAction::Stop
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::client::build_classic_client;
+    use crate::client::proxy::ProxyRouter;
+    use crate::client::traits::AtraClient;
+    use crate::client::ClientWithUserAgent;
+    use crate::config::crawl::HttpVersionPolicy;
+    use crate::config::profiles::{OriginAssignment, OriginProfile};
+    use crate::config::Config;
+    use crate::contexts::traits::{SupportsCrawlResults, SupportsSlimCrawlResults};
+    use crate::crawl::CrawlResult;
+    use crate::fetching::{HttpVersion, ResponseData};
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
+    use crate::seed::UnguardedSeed;
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::url::UrlWithDepth;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use time::OffsetDateTime;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A minimal http server answering every request with an empty body.
+    /// Returns the address of the server and the received header lines in lowercase.
+    async fn header_stub() -> (SocketAddr, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let head = Arc::new(Mutex::new(Vec::new()));
+        let received = head.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut stream = BufReader::new(stream);
+                loop {
+                    let mut line = String::new();
+                    match stream.read_line(&mut line).await {
+                        Ok(0) | Err(_) => break,
+                        Ok(_) if line.trim_end().is_empty() => break,
+                        Ok(_) => received
+                            .lock()
+                            .unwrap()
+                            .push(line.trim_end().to_ascii_lowercase()),
                     }
                 }
-            };
-            reqwest::redirect::Policy::custom(custom_policy)
+                let mut stream = stream.into_inner();
+                let _ = stream
+                    .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n")
+                    .await;
+                let _ = stream.shutdown().await;
+            }
+        });
+        (address, head)
+    }
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[tokio::test]
+    async fn configured_headers_and_cookies_arrive() {
+        let (address, head) = header_stub().await;
+
+        let mut config = Config::default();
+        config.crawl.headers = Some(headers(&[
+            ("accept-language", "de"),
+            ("authorization", "Bearer global"),
+        ]));
+        config.crawl.origins.insert(
+            "127.0.0.1".to_string(),
+            OriginAssignment {
+                profile: None,
+                overrides: OriginProfile {
+                    headers: Some(headers(&[("authorization", "Bearer local")])),
+                    cookies: Some("session=abc".to_string()),
+                    ..Default::default()
+                },
+            },
+        );
+        config.crawl.resolve_profiles().unwrap();
+        let proxies = Arc::new(ProxyRouter::from_config(&config.crawl));
+        let context = TestContext::new(config, FakeClientProvider::new());
+
+        let url = format!("http://{address}/index.html");
+        let seed = UnguardedSeed::from_url(&url).unwrap();
+        let client = build_classic_client(&context, &seed, "atra-test", &proxies).unwrap();
+        let response = client.get(&url).send().await.unwrap();
+        assert!(response.status().is_success());
+
+        let head = head.lock().unwrap().clone();
+        for expected in [
+            "accept-language: de",
+            "authorization: bearer local",
+            "cookie: session=abc",
+        ] {
+            assert!(head.iter().any(|line| line == expected), "{head:?}");
         }
+        assert!(!head.iter().any(|line| line.contains("global")), "{head:?}");
+    }
+
+    /// Builds the client of [config] for the seed [url].
+    fn client_for(
+        config: Config,
+        url: &str,
+    ) -> (ClientWithUserAgent, TestContext<FakeClientProvider>) {
+        let proxies = Arc::new(ProxyRouter::from_config(&config.crawl));
+        let context = TestContext::new(config, FakeClientProvider::new());
+        let seed = UnguardedSeed::from_url(url).unwrap();
+        let inner = build_classic_client(&context, &seed, "atra-test", &proxies).unwrap();
+        let client =
+            ClientWithUserAgent::new("atra-test".to_string(), inner, proxies, HashMap::new());
+        (client, context)
+    }
+
+    #[tokio::test]
+    async fn the_http_version_is_persisted() {
+        let (address, _) = header_stub().await;
+        let url = format!("http://{address}/index.html");
+        let (client, context) = client_for(Config::default(), &url);
+
+        let fetched = client.retrieve(&context, &url, None).await.unwrap();
+        assert_eq!(Some(HttpVersion::Http11), fetched.http_version);
+
+        let target = UrlWithDepth::from_url(&url).unwrap();
+        let result = CrawlResult::new(
+            OffsetDateTime::now_utc(),
+            ResponseData::from_response(fetched, target.clone()),
+            None,
+            None,
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::Unknown, None, None),
+            None,
+        );
+        context.store_crawled_website(&result).await.unwrap();
+        let stored = context
+            .retrieve_slim_crawled_website(&target)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some(HttpVersion::Http11), stored.meta.http_version);
+    }
+
+    #[tokio::test]
+    async fn the_http_version_policy_reaches_the_client() {
+        let (address, _) = header_stub().await;
+        let url = format!("http://{address}/index.html");
+
+        let mut config = Config::default();
+        config.crawl.http_version = HttpVersionPolicy::Http1Only;
+        config.system.connection_pool.max_idle_per_host = Some(1);
+        let (client, context) = client_for(config, &url);
+        let fetched = client.retrieve(&context, &url, None).await.unwrap();
+        assert_eq!(Some(HttpVersion::Http11), fetched.http_version);
+
+        // The stub only speaks HTTP/1.1, it can not answer the HTTP/2 preface.
+        let mut config = Config::default();
+        config.crawl.http_version = HttpVersionPolicy::Http2PriorKnowledge;
+        let (client, context) = client_for(config, &url);
+        assert!(client.retrieve(&context, &url, None).await.is_err());
     }
 }
@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod bandwidth;
 mod classic;
 mod impls;
+pub mod proxy;
+mod recording;
 pub mod traits;
 
-pub use classic::build_classic_client;
+pub use classic::{build_classic_client, build_recording_client};
 pub use impls::ClientWithUserAgent;
+pub use recording::RecordingClient;
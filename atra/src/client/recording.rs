@@ -0,0 +1,401 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A HTTP/1 client recording the head of every response as received. The client of reqwest
+//! only exposes the normalized headers, the fetches with `faithful_headers` use this one.
+
+use crate::client::classic::{RedirectAction, RedirectRules};
+use crate::config::system::ConnectionPoolConfig;
+use crate::fetching::RawHeaders;
+use bytes::Bytes;
+use http::{Request, Response, Uri};
+use http_body_util::{BodyDataStream, Empty};
+use hyper::body::Incoming;
+use hyper::rt::{Read, ReadBuf, ReadBufCursor, Write};
+use hyper_tls::HttpsConnector;
+use hyper_util::client::legacy::connect::{Connected, Connection, HttpConnector};
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::{TokioExecutor, TokioTimer};
+use reqwest::cookie::{CookieStore, Jar};
+use reqwest::header::{
+    HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, COOKIE, LOCATION, SET_COOKIE, TRANSFER_ENCODING,
+};
+use reqwest::StatusCode;
+use std::future::Future;
+use std::io::IoSlice;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::{Instant, Sleep};
+use tokio_stream::Stream;
+use tower_service::Service;
+use url::Url;
+
+/// The largest head recorded, the header lines of larger heads are not recorded.
+const MAX_RECORDED_HEAD: usize = 400 * 1024;
+
+/// The recording of the head of the current response of a connection.
+#[derive(Debug)]
+enum HeadRecording {
+    /// The bytes read so far.
+    Reading(Vec<u8>),
+    /// The status line and the header lines, each ending with a line break.
+    Received(Vec<u8>),
+    /// The head outgrew [MAX_RECORDED_HEAD].
+    Overflowed,
+}
+
+impl HeadRecording {
+    fn record(&mut self, read: &[u8]) {
+        let HeadRecording::Reading(bytes) = self else {
+            return;
+        };
+        bytes.extend_from_slice(read);
+        while let Some(end) = memchr::memmem::find(bytes, b"\r\n\r\n") {
+            // Informational responses precede the final response.
+            let is_informational = bytes[..end]
+                .split(|&b| b == b' ')
+                .nth(1)
+                .is_some_and(|status| status.starts_with(b"1"));
+            if is_informational {
+                bytes.drain(..end + 4);
+            } else {
+                bytes.truncate(end + 2);
+                *self = HeadRecording::Received(std::mem::take(bytes));
+                return;
+            }
+        }
+        if bytes.len() > MAX_RECORDED_HEAD {
+            *self = HeadRecording::Overflowed;
+        }
+    }
+}
+
+/// The head of the last response of a connection as received. It is added to the extensions
+/// of every response served by the connection.
+#[derive(Debug, Clone)]
+pub struct RecordedHead(Arc<Mutex<HeadRecording>>);
+
+impl RecordedHead {
+    fn new() -> Self {
+        Self(Arc::new(Mutex::new(HeadRecording::Reading(Vec::new()))))
+    }
+
+    /// Starts the recording of the next response.
+    fn restart(&self) {
+        let mut recording = self.0.lock().unwrap();
+        if !matches!(*recording, HeadRecording::Reading(_)) {
+            *recording = HeadRecording::Reading(Vec::new());
+        }
+    }
+
+    /// The header lines of the received head, None if the head is not completely received
+    /// or not readable.
+    pub fn raw_headers(&self) -> Option<RawHeaders> {
+        let recording = self.0.lock().unwrap();
+        let HeadRecording::Received(head) = &*recording else {
+            return None;
+        };
+        let status_end = memchr::memmem::find(head, b"\r\n")?;
+        RawHeaders::parse(&head[status_end + 2..])
+    }
+}
+
+/// A connection recording the heads of the responses read from it. A request is only
+/// written after the previous response was read, every write starts a new recording.
+pub struct RecordingConnection<T> {
+    inner: T,
+    head: RecordedHead,
+}
+
+impl<T: Read + Unpin> Read for RecordingConnection<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        mut buf: ReadBufCursor<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let mut recording = this.head.0.lock().unwrap();
+        if !matches!(*recording, HeadRecording::Reading(_)) {
+            drop(recording);
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        let mut raw = vec![0; buf.remaining()];
+        let mut read = ReadBuf::new(&mut raw);
+        ready!(Pin::new(&mut this.inner).poll_read(cx, read.unfilled()))?;
+        recording.record(read.filled());
+        buf.put_slice(read.filled());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T: Write + Unpin> Write for RecordingConnection<T> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        this.head.restart();
+        Pin::new(&mut this.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<(), std::io::Error>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<Result<usize, std::io::Error>> {
+        let this = self.get_mut();
+        this.head.restart();
+        Pin::new(&mut this.inner).poll_write_vectored(cx, bufs)
+    }
+}
+
+impl<T: Connection> Connection for RecordingConnection<T> {
+    fn connected(&self) -> Connected {
+        self.inner.connected().extra(self.head.clone())
+    }
+}
+
+/// Wraps the connections of the connector [C] in [RecordingConnection]s.
+#[derive(Debug, Clone)]
+pub struct RecordingConnector<C>(C);
+
+impl<C> Service<Uri> for RecordingConnector<C>
+where
+    C: Service<Uri>,
+    C::Response: Send + 'static,
+    C::Error: Send + 'static,
+    C::Future: Send + 'static,
+{
+    type Response = RecordingConnection<C::Response>;
+    type Error = C::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.0.poll_ready(cx)
+    }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let connecting = self.0.call(uri);
+        Box::pin(async move {
+            Ok(RecordingConnection {
+                inner: connecting.await?,
+                head: RecordedHead::new(),
+            })
+        })
+    }
+}
+
+/// The body of a response, fails at the [deadline] of its request.
+struct TimedBody {
+    inner: Pin<Box<BodyDataStream<Incoming>>>,
+    deadline: Option<Pin<Box<Sleep>>>,
+}
+
+impl Stream for TimedBody {
+    type Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if let Some(deadline) = self.deadline.as_mut() {
+            if deadline.as_mut().poll(cx).is_ready() {
+                return Poll::Ready(Some(Err("The body was not received in time.".into())));
+            }
+        }
+        self.inner
+            .as_mut()
+            .poll_next(cx)
+            .map(|chunk| chunk.map(|chunk| chunk.map_err(Into::into)))
+    }
+}
+
+type RecordingHttpsClient = Client<RecordingConnector<HttpsConnector<HttpConnector>>, Empty<Bytes>>;
+
+/// The client for the fetches with `faithful_headers`, it returns the header lines of every
+/// response as received besides the normalized headers.
+#[derive(Clone)]
+pub struct RecordingClient {
+    inner: RecordingHttpsClient,
+    /// The time for a request with its redirects.
+    timeout: Option<Duration>,
+    redirects: RedirectRules,
+    cookies: Option<Arc<Jar>>,
+}
+
+impl RecordingClient {
+    pub(crate) fn new(
+        tls: native_tls::TlsConnector,
+        pool: &ConnectionPoolConfig,
+        timeout: Option<Duration>,
+        redirects: RedirectRules,
+        cookies: Option<Arc<Jar>>,
+    ) -> Self {
+        let mut http = HttpConnector::new();
+        http.enforce_http(false);
+        http.set_keepalive(Some(Duration::from_millis(500)));
+        http.set_connect_timeout(timeout);
+        let connector = RecordingConnector(HttpsConnector::from((
+            http,
+            tokio_native_tls::TlsConnector::from(tls),
+        )));
+        let mut builder = Client::builder(TokioExecutor::new());
+        builder
+            .pool_timer(TokioTimer::new())
+            .pool_idle_timeout(pool.idle_timeout.map(|timeout| timeout.unsigned_abs()));
+        if let Some(max_idle) = pool.max_idle_per_host {
+            builder.pool_max_idle_per_host(max_idle);
+        }
+        Self {
+            inner: builder.build(connector),
+            timeout,
+            redirects,
+            cookies,
+        }
+    }
+
+    /// Fetches [url] with the [headers] and follows the redirects. Returns the response, its
+    /// url and its header lines as received. The line of the transfer encoding is dropped,
+    /// the body is decoded by the client.
+    pub async fn get(
+        &self,
+        mut url: Url,
+        mut headers: HeaderMap,
+    ) -> Result<(reqwest::Response, Url, Option<RawHeaders>), reqwest_middleware::Error> {
+        if !headers.contains_key(ACCEPT) {
+            headers.insert(ACCEPT, HeaderValue::from_static("*/*"));
+        }
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+        let mut previous = Vec::new();
+        loop {
+            let response = self.send(&url, &headers, deadline).await?;
+            let next = match response.status() {
+                StatusCode::MOVED_PERMANENTLY
+                | StatusCode::FOUND
+                | StatusCode::SEE_OTHER
+                | StatusCode::TEMPORARY_REDIRECT
+                | StatusCode::PERMANENT_REDIRECT => response
+                    .headers()
+                    .get(LOCATION)
+                    .and_then(|location| location.to_str().ok())
+                    .and_then(|location| url.join(location).ok()),
+                _ => None,
+            };
+            if let Some(next) = next {
+                previous.push(url.clone());
+                match self.redirects.check(response.status(), &next, &previous) {
+                    RedirectAction::Follow => {
+                        // The credentials are only sent to the requested origin.
+                        if next.host_str() != url.host_str()
+                            || next.port_or_known_default() != url.port_or_known_default()
+                        {
+                            headers.remove(AUTHORIZATION);
+                        }
+                        url = next;
+                        continue;
+                    }
+                    RedirectAction::Stop => {}
+                    RedirectAction::TooManyRedirects => {
+                        return Err(reqwest_middleware::Error::Middleware(anyhow::anyhow!(
+                            "{url}: too many redirects"
+                        )));
+                    }
+                }
+            }
+            let mut raw_headers = response
+                .extensions()
+                .get::<RecordedHead>()
+                .and_then(RecordedHead::raw_headers);
+            if let Some(raw_headers) = raw_headers.as_mut() {
+                raw_headers.remove(&TRANSFER_ENCODING);
+            }
+            let response = response.map(|body| {
+                reqwest::Body::wrap_stream(TimedBody {
+                    inner: Box::pin(BodyDataStream::new(body)),
+                    deadline: deadline.map(|deadline| Box::pin(tokio::time::sleep_until(deadline))),
+                })
+            });
+            return Ok((reqwest::Response::from(response), url, raw_headers));
+        }
+    }
+
+    /// Sends a single request for [url] and receives the head of the response.
+    async fn send(
+        &self,
+        url: &Url,
+        headers: &HeaderMap,
+        deadline: Option<Instant>,
+    ) -> Result<Response<Incoming>, reqwest_middleware::Error> {
+        let uri = Uri::try_from(url.as_str())
+            .map_err(|err| reqwest_middleware::Error::Middleware(err.into()))?;
+        let mut request = Request::get(uri)
+            .body(Empty::new())
+            .map_err(|err| reqwest_middleware::Error::Middleware(err.into()))?;
+        *request.headers_mut() = headers.clone();
+        if let Some(cookies) = self
+            .cookies
+            .as_ref()
+            .and_then(|cookies| cookies.cookies(url))
+        {
+            request.headers_mut().insert(COOKIE, cookies);
+        }
+        let response = match deadline {
+            Some(deadline) => tokio::time::timeout_at(deadline, self.inner.request(request))
+                .await
+                .map_err(|err| reqwest_middleware::Error::Middleware(err.into()))?,
+            None => self.inner.request(request).await,
+        }
+        .map_err(|err| reqwest_middleware::Error::Middleware(err.into()))?;
+        if let Some(cookies) = &self.cookies {
+            cookies.set_cookies(&mut response.headers().get_all(SET_COOKIE).iter(), url);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::HeadRecording;
+
+    #[test]
+    fn records_the_final_head_in_pieces() {
+        let mut recording = HeadRecording::Reading(Vec::new());
+        recording
+            .record(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nX-Mixed-");
+        assert!(matches!(recording, HeadRecording::Reading(_)));
+        recording.record(b"CASE: Value\r\n\r\n<html>");
+        let HeadRecording::Received(head) = recording else {
+            panic!("The head is received.");
+        };
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\nSet-Cookie: a=1\r\nX-Mixed-CASE: Value\r\n".as_slice(),
+            head
+        );
+    }
+}
@@ -12,10 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::crawl::FetchRetryConfig;
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
-use crate::fetching::FetchedRequestData;
+use crate::fetching::{decode_delayed, encode_delayed, CacheValidators, FetchedRequestData};
 use reqwest::{IntoUrl, StatusCode};
+use serde::{Deserialize, Serialize};
 use std::error::Error;
+use time::OffsetDateTime;
 
 /// The client used by Atra to download the data.
 pub trait AtraClient {
@@ -31,11 +34,135 @@ pub trait AtraClient {
     where
         U: IntoUrl;
 
-    /// Perform a network request to a resource extracting all content
-    async fn retrieve<C, U>(&self, context: &C, url: U) -> Result<FetchedRequestData, Self::Error>
+    /// Perform a network request to a resource extracting all content.
+    /// If [validators] are provided the request is conditional.
+    async fn retrieve<C, U>(
+        &self,
+        context: &C,
+        url: U,
+        validators: Option<&CacheValidators>,
+    ) -> Result<FetchedRequestData, Self::Error>
     where
         C: SupportsConfigs + SupportsFileSystemAccess,
         U: IntoUrl;
+
+    /// Perform a network request to a resource asking for the content in the [language],
+    /// the `Accept-Language` replaces the configured one.
+    async fn retrieve_in_language<C, U>(
+        &self,
+        context: &C,
+        url: U,
+        language: &str,
+    ) -> Result<FetchedRequestData, Self::Error>
+    where
+        C: SupportsConfigs + SupportsFileSystemAccess,
+        U: IntoUrl;
+
+    /// Classifies the [error] returned when fetching [url].
+    fn classify_error(&self, _url: &str, _error: &Self::Error) -> FetchFailureKind {
+        FetchFailureKind::Other
+    }
+}
+
+/// How often a url is fetched before a retryable failure marks it as failed,
+/// if `crawl.fetch_retry` is not set.
+pub const MAX_FETCH_ATTEMPTS: u8 = 3;
+
+/// Returns the point in time when an url is fetched again after the [attempts]-th failed
+/// fetch, starting with 1, or None if the failure of the [kind] marks it as failed.
+/// Without a [config] only the failed proxies are retried, right away.
+pub fn next_fetch_attempt(
+    config: Option<&FetchRetryConfig>,
+    kind: FetchFailureKind,
+    attempts: u8,
+    now: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    match config {
+        None => (kind.is_retryable() && attempts < MAX_FETCH_ATTEMPTS).then_some(now),
+        Some(config) => {
+            if !kind.is_transient() {
+                return None;
+            }
+            config
+                .delay_before_retry(attempts, &mut rand::thread_rng())
+                .map(|delay| now + delay)
+        }
+    }
+}
+
+/// The kind of a failed fetch.
+/// Failing to connect through a proxy is distinct from failing to connect directly,
+/// the first one usually means the proxy is down and not the origin.
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display, clap::ValueEnum, Serialize, Deserialize,
+)]
+#[repr(u8)]
+pub enum FetchFailureKind {
+    /// The connection through a proxy failed.
+    ProxyConnect = 0u8,
+    /// The direct connection failed.
+    Connect = 1u8,
+    /// The request timed out.
+    Timeout = 2u8,
+    /// Any other failure.
+    Other = 3u8,
+    /// The tls handshake failed, usually because of an invalid certificate.
+    Tls = 4u8,
+    /// The server answered with a 5xx status code.
+    ServerError = 5u8,
+}
+
+impl FetchFailureKind {
+    /// Encodes the kind as payload of a failed link state.
+    pub fn to_payload(self) -> [u8; 1] {
+        [self as u8]
+    }
+
+    /// Decodes the kind from the payload of a failed link state.
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        match payload.first()? {
+            0 => Some(Self::ProxyConnect),
+            1 => Some(Self::Connect),
+            2 => Some(Self::Timeout),
+            3 => Some(Self::Other),
+            4 => Some(Self::Tls),
+            5 => Some(Self::ServerError),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the failure is not caused by the url, the url is retried later.
+    pub fn is_retryable(self) -> bool {
+        matches!(self, Self::ProxyConnect)
+    }
+
+    /// Returns true if the failure may disappear on its own, the url is retried later
+    /// if `crawl.fetch_retry` is set.
+    pub fn is_transient(self) -> bool {
+        matches!(
+            self,
+            Self::ProxyConnect | Self::Connect | Self::Timeout | Self::ServerError
+        )
+    }
+
+    /// Encodes the kind with the number of failed [attempts] as payload of a link state
+    /// waiting for a retry, the url is not fetched before [not_before].
+    pub fn to_retry_payload(self, attempts: u8, not_before: OffsetDateTime) -> [u8; 10] {
+        encode_delayed(self as u8, attempts, not_before)
+    }
+
+    /// Decodes the number of failed attempts from the payload of a link state waiting for a retry.
+    pub fn attempts_from_payload(payload: &[u8]) -> Option<u8> {
+        Self::from_payload(payload)?;
+        payload.get(1).copied()
+    }
+
+    /// Decodes the point in time before which the url is not fetched again from the payload
+    /// of a link state waiting for a retry.
+    pub fn not_before_from_payload(payload: &[u8]) -> Option<OffsetDateTime> {
+        let kind = Self::from_payload(payload)?;
+        decode_delayed(kind as u8, payload).map(|(_, not_before)| not_before)
+    }
 }
 
 pub trait AtraResponse {
@@ -47,3 +174,86 @@ pub trait AtraResponse {
     async fn text(self) -> Result<String, Self::Error>;
     async fn bytes(self) -> Result<Self::Bytes, Self::Error>;
 }
+
+#[cfg(test)]
+mod test {
+    use super::{next_fetch_attempt, FetchFailureKind, MAX_FETCH_ATTEMPTS};
+    use crate::config::crawl::FetchRetryConfig;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn the_delay_grows_until_the_attempts_are_exhausted() {
+        let config = FetchRetryConfig {
+            max_attempts: 5,
+            max_delay: Duration::seconds(30),
+            jitter_percent: 0,
+            ..FetchRetryConfig::default()
+        };
+        let mut rng = StdRng::seed_from_u64(42);
+        let delays = (0..=5)
+            .map(|attempt| config.delay_before_retry(attempt, &mut rng))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                None,
+                Some(Duration::seconds(10)),
+                Some(Duration::seconds(20)),
+                Some(Duration::seconds(30)),
+                Some(Duration::seconds(30)),
+                None
+            ],
+            delays
+        );
+
+        let config = FetchRetryConfig::default();
+        for _ in 0..100 {
+            let delay = config.delay_before_retry(2, &mut rng).unwrap();
+            assert!(Duration::seconds(16) <= delay && delay <= Duration::seconds(24));
+        }
+    }
+
+    #[test]
+    fn only_transient_failures_are_retried() {
+        let now = OffsetDateTime::now_utc();
+        let config = FetchRetryConfig {
+            jitter_percent: 0,
+            ..FetchRetryConfig::default()
+        };
+        for kind in [
+            FetchFailureKind::ProxyConnect,
+            FetchFailureKind::Connect,
+            FetchFailureKind::Timeout,
+            FetchFailureKind::ServerError,
+        ] {
+            assert_eq!(
+                Some(now + Duration::seconds(10)),
+                next_fetch_attempt(Some(&config), kind, 1, now)
+            );
+            assert_eq!(None, next_fetch_attempt(Some(&config), kind, 3, now));
+        }
+        for kind in [FetchFailureKind::Tls, FetchFailureKind::Other] {
+            assert_eq!(None, next_fetch_attempt(Some(&config), kind, 1, now));
+        }
+
+        // Without a config only a failed proxy is retried, right away.
+        assert_eq!(
+            Some(now),
+            next_fetch_attempt(None, FetchFailureKind::ProxyConnect, 1, now)
+        );
+        assert_eq!(
+            None,
+            next_fetch_attempt(
+                None,
+                FetchFailureKind::ProxyConnect,
+                MAX_FETCH_ATTEMPTS,
+                now
+            )
+        );
+        assert_eq!(
+            None,
+            next_fetch_attempt(None, FetchFailureKind::Timeout, 1, now)
+        );
+    }
+}
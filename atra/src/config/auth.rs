@@ -0,0 +1,101 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::profiles::REDACTED;
+use data_encoding::BASE64;
+use reqwest::header::{HeaderValue, InvalidHeaderValue};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// The credentials sent to an origin in the `Authorization` header.
+/// The secrets are redacted in the debug output.
+#[derive(Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub enum OriginAuth {
+    /// HTTP Basic authentication.
+    Basic { username: String, password: String },
+    /// A bearer token, e.g. of OAuth 2.0.
+    Bearer { token: String },
+}
+
+impl OriginAuth {
+    /// The value of the `Authorization` header, marked as sensitive.
+    pub fn header_value(&self) -> Result<HeaderValue, InvalidHeaderValue> {
+        let mut value = match self {
+            OriginAuth::Basic { username, password } => HeaderValue::from_str(&format!(
+                "Basic {}",
+                BASE64.encode(format!("{username}:{password}").as_bytes())
+            )),
+            OriginAuth::Bearer { token } => HeaderValue::from_str(&format!("Bearer {token}")),
+        }?;
+        value.set_sensitive(true);
+        Ok(value)
+    }
+}
+
+impl Debug for OriginAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OriginAuth::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .field("password", &REDACTED)
+                .finish(),
+            OriginAuth::Bearer { .. } => {
+                f.debug_struct("Bearer").field("token", &REDACTED).finish()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OriginAuth;
+
+    #[test]
+    fn builds_the_authorization_header() {
+        let basic = OriginAuth::Basic {
+            username: "Aladdin".to_string(),
+            password: "open sesame".to_string(),
+        };
+        let value = basic.header_value().unwrap();
+        assert_eq!(
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==",
+            value.to_str().unwrap()
+        );
+        assert!(value.is_sensitive());
+
+        let bearer = OriginAuth::Bearer {
+            token: "secret-token".to_string(),
+        };
+        assert_eq!(
+            "Bearer secret-token",
+            bearer.header_value().unwrap().to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn secrets_are_not_in_the_debug_output() {
+        let basic = OriginAuth::Basic {
+            username: "Aladdin".to_string(),
+            password: "open sesame".to_string(),
+        };
+        let bearer = OriginAuth::Bearer {
+            token: "secret-token".to_string(),
+        };
+        let shown = format!("{basic:?} {bearer:?}");
+        assert!(shown.contains("Aladdin"), "{shown}");
+        assert!(!shown.contains("open sesame"), "{shown}");
+        assert!(!shown.contains("secret-token"), "{shown}");
+    }
+}
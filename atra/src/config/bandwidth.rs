@@ -0,0 +1,57 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use std::num::NonZeroU64;
+
+/// The limits for the bytes downloaded by all workers together. Unlimited by default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct BandwidthConfig {
+    /// The limit for all downloads of the crawl.
+    pub global: Option<BandwidthLimit>,
+    /// The limit for the downloads from each origin.
+    pub per_origin: Option<BandwidthLimit>,
+}
+
+impl BandwidthConfig {
+    /// Returns true if no limit is set.
+    pub fn is_unlimited(&self) -> bool {
+        self.global.is_none() && self.per_origin.is_none()
+    }
+}
+
+/// A download rate in bytes per second.
+#[derive(Debug, Copy, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub struct BandwidthLimit {
+    pub bytes_per_second: NonZeroU64,
+    /// The bytes that can be downloaded at once after an idle time.
+    /// (default: the bytes of one second)
+    #[serde(default)]
+    pub burst: Option<NonZeroU64>,
+}
+
+impl BandwidthLimit {
+    pub fn new(bytes_per_second: NonZeroU64) -> Self {
+        Self {
+            bytes_per_second,
+            burst: None,
+        }
+    }
+
+    /// The bytes that can be downloaded at once after an idle time.
+    pub fn burst(&self) -> NonZeroU64 {
+        self.burst.unwrap_or(self.bytes_per_second)
+    }
+}
@@ -14,22 +14,44 @@
 
 // Inspired by spider_rs
 
+use crate::blacklist::AllowlistPattern;
+use crate::config::auth::OriginAuth;
+use crate::config::bandwidth::BandwidthConfig;
+use crate::config::header_persistence::HeaderPersistence;
+use crate::config::profiles::{
+    check_accept_language, check_headers, mark_sensitive_headers, merge_headers,
+    EffectiveOriginConfig, EffectiveOrigins, OriginAssignment, OriginProfile, ProfileError,
+};
+use crate::crawl::{PublicationConfidence, PublicationEstimate};
 use crate::extraction::extractor::Extractor;
+use crate::extraction::{ExtractedLink, StructuredExtractionConfig};
+use crate::fetching::DownloadRejection;
 use crate::gdbr::identifier::GdbrIdentifierRegistryConfig;
+use crate::queue::priority::PriorityConfig;
 use crate::toolkit::header_map_extensions::optional_header_map;
-use crate::url::{AtraUrlOrigin, UrlWithDepth};
-use reqwest::header::HeaderMap;
+use crate::toolkit::text_normalization::TextNormalizationConfig;
+use crate::url::filter::UrlFilterConfig;
+use crate::url::normalization::UrlNormalizationConfig;
+use crate::url::traps::TrapDetectionConfig;
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use camino::Utf8PathBuf;
+use rand::Rng;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE};
 use serde;
 use serde::{Deserialize, Serialize};
-use std::borrow::Borrow;
-use std::collections::HashMap;
+use std::borrow::{Borrow, Cow};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::Hash;
-use std::num::NonZeroU64;
+use std::num::{NonZeroU64, NonZeroUsize};
 use strum::Display;
 use strum::EnumString;
 use text_processing::configs::StopwordRegistryConfig;
 use text_processing::tf_idf::{Idf, Tf};
-use time::Duration;
+use time::{Date, Duration, OffsetDateTime, UtcOffset};
+use ubyte::ByteUnit;
+
+/// The default size of the text samples.
+pub const DEFAULT_TEXT_SAMPLE_SIZE: u64 = ByteUnit::Megabyte(1).as_u64();
 
 /// The general crawling settings for a single
 #[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
@@ -42,8 +64,16 @@ pub struct CrawlConfig {
     /// robots.txt file has a delay included. (default: true)
     pub respect_robots_txt: bool,
 
+    /// Extract the links of the fetched pages, if false only the urls in the queue are
+    /// crawled and the seeds are not registered in the web graph. (default: true)
+    pub extract_links: bool,
     /// Respect the nofollow attribute during the link extraction (default: true)
     pub respect_nofollow: bool,
+    /// Respect nofollow in the robots meta tags and the X-Robots-Tag header (default: true)
+    pub respect_robots_meta: bool,
+    /// Do not archive the body of pages with a noindex in the robots meta tags or the
+    /// X-Robots-Tag header (default: true)
+    pub respect_noindex: bool,
     /// Extract links to embedded data like audio/video files for the crawl-queue (default: false)
     pub crawl_embedded_data: bool,
     /// Extract links to embedded data like audio/video files for the crawl-queue (default: false)
@@ -52,23 +82,70 @@ pub struct CrawlConfig {
     pub crawl_javascript: bool,
     /// Try to extract links from tags with onclick attribute for the crawl-queue (default: false)
     pub crawl_onclick_by_heuristic: bool,
+    /// Extract the targets of meta refresh tags and of string literals assigned to the
+    /// `location` in scripts for the crawl-queue (default: true)
+    pub crawl_redirect_hints: bool,
+    /// Which links of the responses without a 2xx status code, like custom 404 pages, are
+    /// followed. (default: Never)
+    pub extract_from_error_pages: ErrorPageExtraction,
     /// Tries to apply a gdpr filter if one is defined.
     pub apply_gdbr_filter_if_possible: bool,
+    /// The GDBR classifier is skipped for pages whose language was detected with a lower
+    /// confidence, in percent. The language is marked as uncertain. (default: 0/Off)
+    pub min_language_confidence: u8,
+    /// The language of a text with fewer words is always uncertain, see
+    /// [CrawlConfig::min_language_confidence]. (default: 0/Off)
+    pub min_language_tokens: usize,
     /// Only store html-files in the warc
     pub store_only_html_in_warc: bool,
     /// Store the big file hints also in the warc
     pub store_big_file_hints_in_warc: bool,
+    /// Write the plain text of HTML and plain text pages as conversion record after their
+    /// response record in the warc. (default: false)
+    pub text_sidecar: bool,
+    /// If set, a body with the same digest as an already stored body is not written to the warc
+    /// again, the crawl result references the existing record. (default: false)
+    pub deduplicate_by_digest: bool,
+    /// If set, the publication date of every page is estimated and recorded in the meta, the
+    /// bodies of pages published outside of the window are not archived. (default: None/Off)
+    pub publication_window: Option<PublicationWindow>,
 
     /// If set generates the webgraph. This can impact the overall performance of the crawl.
     pub generate_web_graph: bool,
 
+    /// Configures the normalization of the urls before they are registered.
+    pub url_normalization: UrlNormalizationConfig,
+
+    /// Configures the priority of the urls in the queue.
+    pub url_priority: PriorityConfig,
+
     /// The maximum size to download. (in byte)
     pub max_file_size: Option<NonZeroU64>,
+    /// Decides by the headers of a response if its body is downloaded. (default: None/Off)
+    pub download_filter: Option<DownloadFilter>,
+    /// What is downloaded of a body streamed without an end, like the frames of a webcam.
+    /// (default: Skip)
+    pub streaming_bodies: StreamingBodies,
 
     /// The maximum age of a cached robots.txt. If None, it never gets too old.
     pub max_robots_age: Option<Duration>,
+    /// Refreshes the robots.txt of an origin during long crawls and applies the changed rules
+    /// to the queued urls. (default: None/Off)
+    pub robots_revalidation: Option<RobotsRevalidationConfig>,
+    /// The local robots.txt files used instead of the ones of the origins, the robots.txt of
+    /// these origins is never downloaded. (default: empty)
+    pub robots_overrides: HashMap<AtraUrlOrigin, Utf8PathBuf>,
+    /// What is allowed on an origin answering the robots.txt with a 4xx status code.
+    /// (default: AllowAll)
+    pub robots_on_client_error: RobotsFallback,
+    /// What is allowed on an origin answering the robots.txt with a 5xx status code.
+    /// (default: AllowAll)
+    pub robots_on_server_error: RobotsFallback,
     /// Prevent including the sitemap links with the crawl.
     pub ignore_sitemap: bool,
+    /// Let the `lastmod` and `changefreq` of the sitemaps decide when an url with a
+    /// recrawl interval is crawled again. Many sites lie about them. (default: false)
+    pub trust_sitemap_hints: bool,
     /// Allow sub-domains.
     pub subdomains: bool,
 
@@ -83,40 +160,144 @@ pub struct CrawlConfig {
     /// Headers to include with requests.
     #[serde(with = "optional_header_map")]
     pub headers: Option<HeaderMap>,
-    /// Use proxy list for performing network request.
-    pub proxies: Option<Vec<String>>,
+    /// The languages asked for with the `Accept-Language` header, like `de-DE,de;q=0.9`.
+    /// Replaces an `Accept-Language` of the [CrawlConfig::headers].
+    pub accept_language: Option<String>,
+    /// If not empty, pages answering with `Vary: Accept-Language` are fetched again once per
+    /// language and every variant is stored as a separate record. Multiplies the requests
+    /// for these pages. (default: empty)
+    pub language_variants: Vec<String>,
+    /// The credentials sent to the origins, only to exactly matching origins and never after
+    /// a redirect to another host.
+    pub auth: HashMap<AtraUrlOrigin, OriginAuth>,
+    /// Which response headers are persisted in the meta and the warc files. (default: all)
+    pub header_persistence: HeaderPersistence,
+    /// Writes the response headers to the warc as received, with their order, duplicates and
+    /// case, instead of the normalized headers. The urls without a proxy are fetched over
+    /// HTTP/1 by a client recording the response heads, without the http cache. (default: false)
+    pub faithful_headers: bool,
+    /// Use proxy list for performing network request. The first proxy serving the origin of
+    /// a url is used, urls without a proxy are fetched directly.
+    pub proxies: Option<Vec<ProxySetting>>,
     /// Allow all tlds for domain.
     pub tld: bool,
     /// Polite crawling delay
     pub delay: Option<Duration>,
+    /// The maximum number of workers crawling the same origin at the same time. (default: 1)
+    pub max_parallel_per_origin: NonZeroUsize,
+    /// A directory shared by all Atra processes on a machine. The processes record their
+    /// requests per origin in it, so the combined requests to an origin respect the strictest
+    /// delay of them. (default: None/Off)
+    pub politeness_coordination_dir: Option<Utf8PathBuf>,
+    /// The download rate limits for the whole crawl and per origin. (default: unlimited)
+    pub bandwidth: BandwidthConfig,
     /// The budget settings for this crawl
     pub budget: CrawlBudget,
+    /// Stops the whole crawl like CTRL-C after this many stored pages. (default: None/Off)
+    pub max_pages_total: Option<u64>,
+    /// Stops the whole crawl like CTRL-C after this many stored bytes of bodies,
+    /// e.g. "500 GB". (default: None/Off)
+    pub max_bytes_total: Option<ByteUnit>,
+    /// Stops the whole crawl like CTRL-C after it ran this long. (default: None/Off)
+    pub max_duration: Option<Duration>,
+    /// Crawls in waves by the distance to the seed: the origins of the seeds first, then the
+    /// origins linked by them and so on. A wave starts when the previous one is exhausted, a
+    /// summary of every wave is written to the root. (default: false)
+    pub wave_mode: bool,
+    /// Waits for `atra continue` before starting the next wave instead of continuing right
+    /// away. Only used with [CrawlConfig::wave_mode]. (default: false)
+    pub wave_confirmation: bool,
+    /// The tags of the structured seeds by their origin, recorded in the meta of every page
+    /// crawled from the origin. Set from the seed file when the crawl starts.
+    pub seed_tags: HashMap<AtraUrlOrigin, String>,
     /// How often can we fail to crawl an entry in the queue until it is dropped? (0 means never drop)
     /// By default 20
     pub max_queue_age: u32,
+    /// In which order the urls are taken from the queue while their origin is reserved by an
+    /// other worker. (default: Fifo)
+    pub queue_fairness: QueueFairness,
+    /// How often is an url retried after the server answered with 429 or 503 and a Retry-After?
+    /// If the retries are exhausted the response is stored as is. (default: 5)
+    pub max_throttled_retries: u8,
+    /// Retries the fetches failing with a transient error, like a timeout, a failed connection
+    /// or a 5xx status code, after a delay. If None, only a failed proxy is retried.
+    /// (default: None/Off)
+    pub fetch_retry: Option<FetchRetryConfig>,
+    /// Retries the seed of an origin after a delay if every request to the origin failed to
+    /// connect. If None, such an origin is reported as unreachable right away. (default: None/Off)
+    pub rediscovery: Option<RediscoveryConfig>,
+    /// Warn if less than this percentage of the pages fetched from an origin have a unique
+    /// payload, often a sign for a crawler trap. (default: None/Off)
+    pub unique_content_floor: Option<u8>,
+    /// The number of pages fetched from an origin before the [CrawlConfig::unique_content_floor]
+    /// is checked. (default: 100)
+    pub unique_content_min_samples: u64,
+    /// Stops enqueueing the found urls of infinite url spaces like calendars, faceted search
+    /// or session ids. (default: None/Off)
+    pub trap_detection: Option<TrapDetectionConfig>,
+    /// If not empty, only the found urls matching one of these entries are enqueued, the seeds
+    /// are always crawled. The blacklist wins over the allowlist. (default: [])
+    pub allowlist: Vec<AllowlistPattern>,
+    /// The filters asked in their order before a found url is enqueued, the first filter
+    /// accepting or rejecting the url decides. (default: [])
+    pub url_filters: Vec<UrlFilterConfig>,
 
     /// The max redirections allowed for request. (default: 5 like Google-Bot)
     pub redirect_limit: usize,
     /// The redirect policy type to use.
     pub redirect_policy: RedirectPolicy,
 
+    /// The http versions the client speaks. (default: Auto)
+    pub http_version: HttpVersionPolicy,
+
     /// Dangerously accept invalid certficates
     pub accept_invalid_certs: bool,
+    /// Capture the tls certificate chain of every https origin once per crawl. (default: None/Off)
+    pub tls_capture: Option<TlsCaptureConfig>,
+    /// Aggregate the metadata of every origin from cheap signals of its pages. (default: None/Off)
+    pub origin_metadata: Option<OriginMetadataConfig>,
 
     /// A custom configuration of extractors
     pub link_extractors: Extractor,
     /// The maximum depth for atra when extracting from an archive. (Default 20)
     pub max_extraction_depth: Option<usize>,
+    /// The embedded data uris found with [CrawlConfig::crawl_embedded_data] are stored as
+    /// separate records, larger payloads are skipped. (in Bytes) (default: 1MB)
+    pub max_data_uri_size: u64,
+    /// The time all extractors together may spend on a single url. If None, there is no
+    /// deadline. (default: 5min)
+    pub extraction_timeout: Option<Duration>,
+    /// The time a single extractor method may spend on a single url, capped by the remaining
+    /// [CrawlConfig::extraction_timeout]. If None, only the deadline applies. (default: 60s)
+    pub extractor_method_timeout: Option<Duration>,
+    /// The limits of the link extraction from json and csv documents.
+    pub structured_extraction: StructuredExtractionConfig,
 
     /// If this value is set Atra tries to decode and process files that are only downloaded as
     /// blob but do not overstep this provided size. (in Bytes) (default: None/Off)
     pub decode_big_files_up_to: Option<u64>,
 
+    /// The normalization applied to decoded text before it is exported or classified.
+    pub text_normalization: TextNormalizationConfig,
+
+    /// The bytes of decoded text read for the language detection of documents that are not
+    /// kept in memory and for the GDBR score of every document. (in Bytes) (default: 1MB)
+    pub text_sample_size: u64,
+
     /// Used to configure the stopword registry if needed.
     pub stopword_registry: Option<StopwordRegistryConfig>,
 
     /// Used to configure the gdbr feature
     pub gbdr: Option<GdbrIdentifierRegistryConfig<Tf, Idf>>,
+
+    /// Named bundles of per-origin settings, assigned to the origins by [CrawlConfig::origins].
+    pub profiles: BTreeMap<String, OriginProfile>,
+    /// Assigns a profile and inline overrides to origin patterns like `example.com`,
+    /// `*.example.com` or `*`. The inline overrides beat the profile, the profile beats the
+    /// global settings.
+    pub origins: BTreeMap<String, OriginAssignment>,
+    /// The [CrawlConfig::origins] merged with their profiles, set by [CrawlConfig::resolve_profiles].
+    pub effective_origins: EffectiveOrigins,
 }
 
 impl Default for CrawlConfig {
@@ -124,40 +305,534 @@ impl Default for CrawlConfig {
         Self {
             respect_robots_txt: true,
             ignore_sitemap: false,
+            trust_sitemap_hints: false,
             user_agent: UserAgent::default(),
+            extract_links: true,
             respect_nofollow: true,
+            respect_robots_meta: true,
+            respect_noindex: true,
             crawl_embedded_data: false,
             crawl_javascript: true,
             crawl_forms: false,
             crawl_onclick_by_heuristic: false,
+            crawl_redirect_hints: true,
+            extract_from_error_pages: ErrorPageExtraction::default(),
             store_only_html_in_warc: true,
             store_big_file_hints_in_warc: true,
+            text_sidecar: false,
+            deduplicate_by_digest: false,
+            publication_window: None,
             apply_gdbr_filter_if_possible: true,
+            min_language_confidence: 0,
+            min_language_tokens: 0,
             headers: None,
+            accept_language: None,
+            language_variants: Vec::new(),
+            auth: HashMap::new(),
+            header_persistence: HeaderPersistence::default(),
+            faithful_headers: false,
             delay: None,
+            max_parallel_per_origin: NonZeroUsize::MIN,
+            politeness_coordination_dir: None,
+            bandwidth: BandwidthConfig::default(),
             cache: false,
             proxies: None,
             tld: false,
+            http_version: HttpVersionPolicy::default(),
             accept_invalid_certs: false,
+            tls_capture: None,
+            origin_metadata: None,
             use_cookies: true,
             redirect_policy: RedirectPolicy::default(),
             redirect_limit: 5,
             budget: CrawlBudget::default(),
+            max_pages_total: None,
+            max_bytes_total: None,
+            max_duration: None,
+            wave_mode: false,
+            wave_confirmation: false,
+            seed_tags: HashMap::new(),
             subdomains: false,
             max_robots_age: None,
+            robots_revalidation: None,
+            robots_overrides: HashMap::new(),
+            robots_on_client_error: RobotsFallback::AllowAll,
+            robots_on_server_error: RobotsFallback::AllowAll,
             cookies: None,
             max_file_size: None,
+            download_filter: None,
+            streaming_bodies: StreamingBodies::Skip,
             max_queue_age: 20,
+            queue_fairness: QueueFairness::Fifo,
+            max_throttled_retries: 5,
+            fetch_retry: None,
+            rediscovery: None,
+            unique_content_floor: None,
+            unique_content_min_samples: 100,
+            trap_detection: None,
+            allowlist: Vec::new(),
+            url_filters: Vec::new(),
             max_extraction_depth: Some(10),
+            max_data_uri_size: ByteUnit::Megabyte(1).as_u64(),
+            extraction_timeout: Some(Duration::minutes(5)),
+            extractor_method_timeout: Some(Duration::seconds(60)),
+            structured_extraction: StructuredExtractionConfig::default(),
             link_extractors: Extractor::default(),
             decode_big_files_up_to: None,
+            text_normalization: TextNormalizationConfig::default(),
+            text_sample_size: DEFAULT_TEXT_SAMPLE_SIZE,
             stopword_registry: None,
             gbdr: None,
             generate_web_graph: true,
+            url_normalization: UrlNormalizationConfig::default(),
+            url_priority: PriorityConfig::default(),
+            profiles: BTreeMap::new(),
+            origins: BTreeMap::new(),
+            effective_origins: EffectiveOrigins::default(),
+        }
+    }
+}
+
+impl CrawlConfig {
+    /// Resolves the profiles of the [CrawlConfig::origins] into [CrawlConfig::effective_origins].
+    /// Has to be called once after loading the config, fails for undefined profiles and
+    /// headers managed by the client.
+    pub fn resolve_profiles(&mut self) -> Result<(), ProfileError> {
+        if let Some(headers) = &mut self.headers {
+            check_headers("the crawl config", headers)?;
+            mark_sensitive_headers(headers);
+        }
+        if let Some(language) = &self.accept_language {
+            check_accept_language("the crawl config", language)?;
+        }
+        for language in &self.language_variants {
+            check_accept_language("the language variants", language)?;
+        }
+        self.effective_origins = EffectiveOrigins::resolve(&self.profiles, &self.origins)?;
+        Ok(())
+    }
+
+    /// Returns the resolved settings assigned to the [origin].
+    pub fn origin_profile(&self, origin: &AtraUrlOrigin) -> Option<&OriginProfile> {
+        self.effective_origins
+            .get(origin.as_ref())
+            .map(|(_, found)| &found.settings)
+    }
+
+    /// Returns the budget for the [origin].
+    pub fn budget_for(&self, origin: &AtraUrlOrigin) -> &BudgetSetting {
+        match self
+            .origin_profile(origin)
+            .and_then(|found| found.budget.as_ref())
+        {
+            Some(budget) => budget,
+            None => self.budget.get_budget_for(origin),
+        }
+    }
+
+    /// Returns the polite delay for the [origin].
+    pub fn delay_for(&self, origin: &AtraUrlOrigin) -> Option<Duration> {
+        self.origin_profile(origin)
+            .and_then(|found| found.delay)
+            .or(self.delay)
+    }
+
+    /// Returns the headers for the [origin], the global headers are extended by the headers
+    /// of the origin and the `Accept-Language` for the origin.
+    pub fn headers_for(&self, origin: &AtraUrlOrigin) -> Option<Cow<HeaderMap>> {
+        let headers = match self
+            .origin_profile(origin)
+            .and_then(|found| found.headers.as_ref())
+        {
+            None => self.headers.as_ref().map(Cow::Borrowed),
+            Some(headers) => match &self.headers {
+                None => Some(Cow::Borrowed(headers)),
+                Some(global) => Some(Cow::Owned(merge_headers(global, headers))),
+            },
+        };
+        let Some(language) = self
+            .accept_language_for(origin)
+            .and_then(|value| HeaderValue::from_str(value).ok())
+        else {
+            return headers;
+        };
+        let mut headers = headers.map(Cow::into_owned).unwrap_or_default();
+        headers.insert(ACCEPT_LANGUAGE, language);
+        Some(Cow::Owned(headers))
+    }
+
+    /// Returns the `Accept-Language` for the [origin].
+    pub fn accept_language_for(&self, origin: &AtraUrlOrigin) -> Option<&str> {
+        self.origin_profile(origin)
+            .and_then(|found| found.accept_language.as_deref())
+            .or(self.accept_language.as_deref())
+    }
+
+    /// Returns the cookies for the [origin].
+    pub fn cookies_for(&self, origin: &AtraUrlOrigin) -> Option<&String> {
+        match self
+            .origin_profile(origin)
+            .and_then(|found| found.cookies.as_ref())
+        {
+            Some(cookies) => Some(cookies),
+            None => self
+                .cookies
+                .as_ref()
+                .and_then(|cookies| cookies.get_cookies_for(origin)),
+        }
+    }
+
+    /// Returns the link extractors for the [origin].
+    pub fn link_extractors_for(&self, origin: &AtraUrlOrigin) -> &Extractor {
+        self.origin_profile(origin)
+            .and_then(|found| found.link_extractors.as_ref())
+            .unwrap_or(&self.link_extractors)
+    }
+
+    /// Returns the settings used for the [origin] after applying all overrides.
+    pub fn effective_for(&self, origin: &AtraUrlOrigin) -> EffectiveOriginConfig {
+        let resolved = self.effective_origins.get(origin.as_ref());
+        let profile = resolved.map(|(_, found)| &found.settings);
+        EffectiveOriginConfig {
+            origin: origin.clone(),
+            pattern: resolved.map(|(pattern, _)| pattern.to_string()),
+            profile: resolved.and_then(|(_, found)| found.profile.clone()),
+            delay: self.delay_for(origin),
+            budget: self.budget_for(origin).clone(),
+            headers: self.headers_for(origin).map(Cow::into_owned),
+            cookies: self.cookies_for(origin).cloned(),
+            crawl_embedded_data: profile
+                .and_then(|found| found.crawl_embedded_data)
+                .unwrap_or(self.crawl_embedded_data),
+            crawl_forms: profile
+                .and_then(|found| found.crawl_forms)
+                .unwrap_or(self.crawl_forms),
+            crawl_javascript: profile
+                .and_then(|found| found.crawl_javascript)
+                .unwrap_or(self.crawl_javascript),
+            crawl_onclick_by_heuristic: profile
+                .and_then(|found| found.crawl_onclick_by_heuristic)
+                .unwrap_or(self.crawl_onclick_by_heuristic),
+            link_extractors: self.link_extractors_for(origin).clone(),
+        }
+    }
+}
+
+/// Skips the download of bodies by the Content-Type and Content-Length of the response.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq, Default)]
+#[serde(default)]
+pub struct DownloadFilter {
+    /// Bodies with a mime type starting with one of the prefixes are not downloaded,
+    /// e.g. `video/` or `audio/`.
+    pub deny_mime_prefixes: Vec<String>,
+    /// If not empty, only bodies with a mime type starting with one of the prefixes are
+    /// downloaded. The deny list beats the allow list.
+    pub allow_mime_prefixes: Vec<String>,
+    /// Ask the server with a HEAD request for the size if the response to the GET has no
+    /// usable Content-Length. (default: false)
+    pub probe_with_head: bool,
+}
+
+impl DownloadFilter {
+    /// Returns the rejection if the body of a response with the [content_type] is not downloaded.
+    /// The parameters of the Content-Type and the case are ignored.
+    pub fn check_content_type(&self, content_type: &str) -> Result<(), DownloadRejection> {
+        let mime = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let matches = |prefix: &String| mime.starts_with(&prefix.to_ascii_lowercase());
+        if self.deny_mime_prefixes.iter().any(matches)
+            || (!self.allow_mime_prefixes.is_empty()
+                && !self.allow_mime_prefixes.iter().any(matches))
+        {
+            Err(DownloadRejection::ContentType(mime))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// What is downloaded of a body streamed without an end, e.g. a `multipart/x-mixed-replace`
+/// or a `text/event-stream`. Without a limit the download only ends with the request timeout.
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum StreamingBodies {
+    /// The body is not downloaded, the page is stored without a body.
+    #[default]
+    Skip,
+    /// Only the first [max_bytes] of the body are downloaded, the record in the warc is
+    /// marked as truncated.
+    Capture { max_bytes: u64 },
+}
+
+/// Refreshes the robots.txt of an origin depending on the activity on the origin.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct RobotsRevalidationConfig {
+    /// The robots.txt is refreshed before the n-th page fetch of an origin since the last
+    /// refresh. (default: 100)
+    pub every_n_fetches: NonZeroU64,
+    /// The robots.txt is refreshed before every recrawl of an origin. (default: true)
+    pub on_recrawl: bool,
+}
+
+impl Default for RobotsRevalidationConfig {
+    fn default() -> Self {
+        Self {
+            every_n_fetches: NonZeroU64::new(100).unwrap(),
+            on_recrawl: true,
         }
     }
 }
 
+/// What is allowed on an origin without a downloadable robots.txt.
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum RobotsFallback {
+    /// Everything is allowed, as if the origin had an empty robots.txt.
+    #[default]
+    AllowAll,
+    /// Nothing is allowed.
+    DenyAll,
+}
+
+/// Retries the seeds of origins that were entirely unreachable, the delay grows with every attempt.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct RediscoveryConfig {
+    /// The attempts to reach the origin again, afterward it is reported as unreachable. (default: 3)
+    pub max_attempts: u8,
+    /// The delay before the first attempt. (default: 15min)
+    pub delay: Duration,
+    /// The delay is multiplied by this factor for each further attempt. (default: 2)
+    pub backoff_factor: u32,
+}
+
+impl Default for RediscoveryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::minutes(15),
+            backoff_factor: 2,
+        }
+    }
+}
+
+impl RediscoveryConfig {
+    /// The delay before the [attempt], starting with 1. None if the attempts are exhausted.
+    pub fn delay_before(&self, attempt: u8) -> Option<Duration> {
+        if attempt == 0 || attempt > self.max_attempts {
+            return None;
+        }
+        let factor = self
+            .backoff_factor
+            .saturating_pow(attempt as u32 - 1)
+            .min(i32::MAX as u32);
+        Some(self.delay.saturating_mul(factor as i32))
+    }
+}
+
+/// Retries the fetches failing with a transient error. The url waits in the queue for the
+/// retry, the delay grows with every attempt.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct FetchRetryConfig {
+    /// The attempts to fetch an url, including the first one. (default: 3)
+    pub max_attempts: u8,
+    /// The delay before the first retry. (default: 10s)
+    pub delay: Duration,
+    /// The delay is multiplied by this factor for each further retry. (default: 2)
+    pub backoff_factor: u32,
+    /// The upper bound of the delay. (default: 10min)
+    pub max_delay: Duration,
+    /// The delay is randomly shortened or extended by up to this percentage, so the retries of
+    /// the urls failing together are spread out. (default: 20)
+    pub jitter_percent: u8,
+}
+
+impl Default for FetchRetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            delay: Duration::seconds(10),
+            backoff_factor: 2,
+            max_delay: Duration::minutes(10),
+            jitter_percent: 20,
+        }
+    }
+}
+
+impl FetchRetryConfig {
+    /// The delay before the retry after the [attempt]-th failed fetch, starting with 1.
+    /// None if the attempts are exhausted.
+    pub fn delay_before_retry(&self, attempt: u8, rng: &mut impl Rng) -> Option<Duration> {
+        if attempt == 0 || attempt >= self.max_attempts {
+            return None;
+        }
+        let factor = self
+            .backoff_factor
+            .saturating_pow(attempt as u32 - 1)
+            .min(i32::MAX as u32);
+        let delay = self.delay.saturating_mul(factor as i32).min(self.max_delay);
+        let jitter = self.jitter_percent.min(100) as f64 / 100.0;
+        if jitter == 0.0 {
+            return Some(delay);
+        }
+        Some(delay * rng.gen_range(1.0 - jitter..=1.0 + jitter))
+    }
+}
+
+/// Captures the tls certificate chain of the origins at the first contact.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct TlsCaptureConfig {
+    /// Certificates expiring within this window after the end of the crawl are reported in
+    /// the summary. (default: 30 days)
+    pub expiry_warning_window: Duration,
+    /// The time the probe may spend on connecting and the handshake. (default: 10s)
+    pub probe_timeout: Duration,
+}
+
+impl Default for TlsCaptureConfig {
+    fn default() -> Self {
+        Self {
+            expiry_warning_window: Duration::days(30),
+            probe_timeout: Duration::seconds(10),
+        }
+    }
+}
+
+/// Aggregates the metadata of the origins from their pages and well-known uris.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct OriginMetadataConfig {
+    /// The distinct values kept per signal and origin, further values are only counted.
+    /// (default: 16)
+    pub max_distinct_values: usize,
+    /// Fetch the `/.well-known/security.txt` of every origin once per crawl. (default: true)
+    pub capture_security_txt: bool,
+}
+
+impl Default for OriginMetadataConfig {
+    fn default() -> Self {
+        Self {
+            max_distinct_values: 16,
+            capture_security_txt: true,
+        }
+    }
+}
+
+/// Limits the archived bodies to the pages published within a range of days.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct PublicationWindow {
+    /// The first day of the window (inclusive, UTC), open if None.
+    pub from: Option<Date>,
+    /// The last day of the window (inclusive, UTC), open if None.
+    pub until: Option<Date>,
+    /// What happens with the pages outside of the window. (default: StoreOnlyInRange)
+    pub mode: PublicationWindowMode,
+    /// Estimates with a lower confidence are treated like pages without an estimate. (default: Medium)
+    pub min_confidence: PublicationConfidence,
+    /// Archive the body of pages without a confident estimate. (default: true)
+    pub store_uncertain: bool,
+}
+
+impl Default for PublicationWindow {
+    fn default() -> Self {
+        Self {
+            from: None,
+            until: None,
+            mode: PublicationWindowMode::default(),
+            min_confidence: PublicationConfidence::Medium,
+            store_uncertain: true,
+        }
+    }
+}
+
+impl PublicationWindow {
+    /// Returns true if the day of [published_at] in UTC lies within the window.
+    pub fn contains(&self, published_at: OffsetDateTime) -> bool {
+        let day = published_at.to_offset(UtcOffset::UTC).date();
+        self.from.map_or(true, |from| from <= day) && self.until.map_or(true, |until| day <= until)
+    }
+
+    /// Returns true if the body of a page with the [estimate] is archived.
+    pub fn stores(&self, estimate: Option<&PublicationEstimate>) -> bool {
+        match self.mode {
+            PublicationWindowMode::AnnotateOnly => true,
+            PublicationWindowMode::StoreOnlyInRange => match estimate {
+                Some(estimate) if estimate.confidence >= self.min_confidence => {
+                    self.contains(estimate.published_at)
+                }
+                _ => self.store_uncertain,
+            },
+        }
+    }
+}
+
+/// What happens with the pages published outside of the [PublicationWindow].
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum PublicationWindowMode {
+    /// The pages are recorded as crawled and their links are followed, but their body is not archived.
+    #[default]
+    StoreOnlyInRange,
+    /// Every page is archived, the estimate is only recorded in the meta.
+    AnnotateOnly,
+}
+
+/// In which order the urls are taken from the queue.
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueFairness {
+    /// The urls are taken in the order of the queue. The urls of an origin reserved by an
+    /// other worker count as miss, a long reservation can starve the workers.
+    #[default]
+    Fifo,
+    /// The urls of an origin found reserved are skipped for the rest of the poll without
+    /// counting as miss, the next url of a free origin is taken. The skipped urls return to
+    /// the end of the queue.
+    OriginRoundRobin,
+}
+
+/// Which links of the responses without a 2xx status code are followed.
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+pub enum ErrorPageExtraction {
+    /// The links of error pages are not extracted.
+    #[default]
+    Never,
+    /// Only the links to the origin of the error page are followed.
+    SameOriginOnly,
+    /// Every link of an error page is followed.
+    Always,
+}
+
+impl ErrorPageExtraction {
+    /// Restricts the [links] extracted from the error [page] to the ones allowed by the policy
+    /// and marks them as found on an error page. Links back to the [page] are dropped, an error
+    /// page never rediscovers itself.
+    pub fn restrict(
+        &self,
+        page: &UrlWithDepth,
+        links: HashSet<ExtractedLink>,
+    ) -> HashSet<ExtractedLink> {
+        let origin = page.atra_origin();
+        links
+            .into_iter()
+            .filter(|link| match self {
+                ErrorPageExtraction::Never => false,
+                ErrorPageExtraction::SameOriginOnly => match link.url() {
+                    Some(url) => url != page && origin.is_some() && url.atra_origin() == origin,
+                    None => false,
+                },
+                ErrorPageExtraction::Always => link.url().map_or(true, |url| url != page),
+            })
+            .map(ExtractedLink::from_error_page)
+            .collect()
+    }
+}
+
 /// The cookie settings for each host.
 #[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub struct CookieSettings {
@@ -184,6 +859,21 @@ impl CookieSettings {
     }
 }
 
+/// A proxy for the network requests.
+/// The scheme of the url selects the protocol: `http`, `https`, `socks5` or `socks5h`.
+/// With `socks5h` the hostnames are resolved by the proxy instead of the local dns.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum ProxySetting {
+    /// A proxy url used for all origins, e.g. `socks5h://127.0.0.1:9050`
+    All(String),
+    /// A proxy url only used for the origins and their subdomains.
+    PerOrigin {
+        url: String,
+        origins: Vec<AtraUrlOrigin>,
+    },
+}
+
 /// Redirect policy configuration for request
 #[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
 pub enum RedirectPolicy {
@@ -194,6 +884,18 @@ pub enum RedirectPolicy {
     Strict,
 }
 
+/// The http versions spoken by the client.
+#[derive(Debug, Default, Copy, Clone, Deserialize, Serialize, Eq, PartialEq)]
+pub enum HttpVersionPolicy {
+    #[default]
+    /// HTTP/2 if the server offers it while negotiating TLS (ALPN), otherwise HTTP/1.1.
+    Auto,
+    /// Only HTTP/1.x, even if the server offers HTTP/2.
+    Http1Only,
+    /// HTTP/2 without negotiating it, also for plain http. Fails for servers without HTTP/2.
+    Http2PriorKnowledge,
+}
+
 /// The selected user agent
 #[derive(Debug, Default, Clone, Deserialize, Serialize, EnumString, Display, Eq, PartialEq)]
 pub enum UserAgent {
@@ -441,6 +1143,24 @@ impl BudgetSetting {
         .as_ref()
     }
 
+    /// Replaces the recrawl interval, None crawls only once.
+    pub fn set_recrawl_interval(&mut self, interval: Option<Duration>) {
+        match self {
+            BudgetSetting::SeedOnly {
+                recrawl_interval, ..
+            }
+            | BudgetSetting::Normal {
+                recrawl_interval, ..
+            }
+            | BudgetSetting::Absolute {
+                recrawl_interval, ..
+            }
+            | BudgetSetting::SinglePage {
+                recrawl_interval, ..
+            } => *recrawl_interval = interval,
+        }
+    }
+
     /// Returns true, iff the [url] is in the budget
     pub fn is_in_budget(&self, url: &UrlWithDepth) -> bool {
         let url_depth = url.depth();
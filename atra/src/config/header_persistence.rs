@@ -0,0 +1,253 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::profiles::REDACTED;
+use crate::crawl::HeaderFidelity;
+use crate::fetching::RawHeaders;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+
+/// Decides which response headers are persisted in the meta of a page and its warc record.
+/// Keeps every header by default.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct HeaderPersistence {
+    /// The headers that are not persisted at all.
+    pub drop_headers: Vec<HeaderPattern>,
+    /// The headers persisted with their values replaced by [REDACTED].
+    pub redact_headers: Vec<HeaderPattern>,
+}
+
+impl HeaderPersistence {
+    /// Returns true if every header is persisted as is.
+    pub fn keeps_all(&self) -> bool {
+        self.drop_headers.is_empty() && self.redact_headers.is_empty()
+    }
+
+    /// Drops and redacts the [headers], a header matching both lists is dropped.
+    /// Returns the number of dropped and redacted values.
+    pub fn apply(&self, headers: &mut HeaderMap) -> HeaderFidelity {
+        let mut fidelity = HeaderFidelity::default();
+        if self.keeps_all() {
+            return fidelity;
+        }
+        let mut persisted = HeaderMap::with_capacity(headers.len());
+        for (name, value) in headers.iter() {
+            if self
+                .drop_headers
+                .iter()
+                .any(|pattern| pattern.matches(name))
+            {
+                fidelity.dropped += 1;
+            } else if self
+                .redact_headers
+                .iter()
+                .any(|pattern| pattern.matches(name))
+            {
+                fidelity.redacted += 1;
+                persisted.append(name, HeaderValue::from_static(REDACTED));
+            } else {
+                persisted.append(name, value.clone());
+            }
+        }
+        *headers = persisted;
+        fidelity
+    }
+
+    /// Drops and redacts the [raw_headers] like [apply] does with the parsed headers.
+    pub fn apply_raw(&self, raw_headers: &mut RawHeaders) {
+        if self.keeps_all() {
+            return;
+        }
+        *raw_headers = raw_headers
+            .iter()
+            .filter_map(|(name, value)| {
+                if self
+                    .drop_headers
+                    .iter()
+                    .any(|pattern| pattern.matches_raw(name))
+                {
+                    None
+                } else if self
+                    .redact_headers
+                    .iter()
+                    .any(|pattern| pattern.matches_raw(name))
+                {
+                    Some((name.to_vec(), REDACTED.as_bytes().to_vec()))
+                } else {
+                    Some((name.to_vec(), value.to_vec()))
+                }
+            })
+            .collect();
+    }
+}
+
+/// A header name that is not a valid pattern.
+#[derive(Debug, Error)]
+#[error("The header pattern {0:?} is invalid, only a trailing * is allowed as wildcard.")]
+pub struct InvalidHeaderPattern(String);
+
+/// A case-insensitive header name, a trailing `*` matches any suffix like in `x-internal-*`.
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct HeaderPattern {
+    /// The lowercase name without the wildcard.
+    name: String,
+    wildcard: bool,
+}
+
+impl HeaderPattern {
+    pub fn new(pattern: &str) -> Result<Self, InvalidHeaderPattern> {
+        let normalized = pattern.trim().to_ascii_lowercase();
+        let (name, wildcard) = match normalized.strip_suffix('*') {
+            Some(prefix) => (prefix, true),
+            None => (normalized.as_str(), false),
+        };
+        if name.contains('*') || (!wildcard && name.is_empty()) {
+            return Err(InvalidHeaderPattern(pattern.to_string()));
+        }
+        Ok(Self {
+            name: name.to_string(),
+            wildcard,
+        })
+    }
+
+    /// Returns true if the header [name] matches the pattern.
+    pub fn matches(&self, name: &HeaderName) -> bool {
+        // Header names are always lowercase.
+        if self.wildcard {
+            name.as_str().starts_with(&self.name)
+        } else {
+            name.as_str() == self.name
+        }
+    }
+
+    /// Returns true if the header [name] as received matches the pattern, regardless of its
+    /// case.
+    pub fn matches_raw(&self, name: &[u8]) -> bool {
+        let pattern = self.name.as_bytes();
+        if self.wildcard {
+            name.get(..pattern.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(pattern))
+        } else {
+            name.eq_ignore_ascii_case(pattern)
+        }
+    }
+}
+
+impl Display for HeaderPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.wildcard {
+            write!(f, "{}*", self.name)
+        } else {
+            f.write_str(&self.name)
+        }
+    }
+}
+
+impl TryFrom<String> for HeaderPattern {
+    type Error = InvalidHeaderPattern;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<HeaderPattern> for String {
+    fn from(value: HeaderPattern) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{HeaderPattern, HeaderPersistence};
+    use crate::config::profiles::REDACTED;
+    use crate::crawl::HeaderFidelity;
+    use crate::fetching::RawHeaders;
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, SET_COOKIE};
+
+    fn pattern(value: &str) -> HeaderPattern {
+        HeaderPattern::new(value).unwrap()
+    }
+
+    #[test]
+    fn patterns_are_case_insensitive_with_a_trailing_wildcard() {
+        let internal = pattern("X-Internal-*");
+        assert!(internal.matches(&HeaderName::from_static("x-internal-trace")));
+        assert!(internal.matches(&HeaderName::from_static("x-internal-")));
+        assert!(!internal.matches(&HeaderName::from_static("x-internals")));
+        assert!(pattern("Set-Cookie").matches(&SET_COOKIE));
+        assert!(!pattern("set-cookie").matches(&HeaderName::from_static("set-cookie2")));
+        assert!(pattern("*").matches(&CONTENT_TYPE));
+        assert_eq!("x-internal-*", internal.to_string());
+
+        for invalid in ["", "x-*-id", "**"] {
+            assert!(HeaderPattern::new(invalid).is_err(), "{invalid}");
+        }
+        assert!(serde_json::from_str::<HeaderPattern>("\"x-*-id\"").is_err());
+    }
+
+    #[test]
+    fn drops_and_redacts_every_value() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("session=1"));
+        headers.append(SET_COOKIE, HeaderValue::from_static("tracking=2"));
+        headers.insert("x-internal-trace", HeaderValue::from_static("abc"));
+
+        let policy = HeaderPersistence {
+            drop_headers: vec![pattern("x-internal-*")],
+            redact_headers: vec![pattern("set-cookie"), pattern("x-internal-trace")],
+        };
+        let fidelity = policy.apply(&mut headers);
+        assert_eq!(
+            HeaderFidelity {
+                dropped: 1,
+                redacted: 2
+            },
+            fidelity
+        );
+        assert_eq!(2, headers.keys_len());
+        assert_eq!("text/html", headers[CONTENT_TYPE]);
+        assert!(headers
+            .get_all(SET_COOKIE)
+            .iter()
+            .all(|value| value == REDACTED));
+        assert_eq!(2, headers.get_all(SET_COOKIE).iter().count());
+    }
+
+    #[test]
+    fn drops_and_redacts_raw_lines_regardless_of_their_case() {
+        let mut raw_headers = RawHeaders::parse(
+            b"Content-Type: text/html\r\nSet-Cookie: session=1\r\nX-Internal-Trace: abc\r\nSET-COOKIE: tracking=2\r\n",
+        )
+        .unwrap();
+        let policy = HeaderPersistence {
+            drop_headers: vec![pattern("x-internal-*")],
+            redact_headers: vec![pattern("set-cookie")],
+        };
+        policy.apply_raw(&mut raw_headers);
+        assert_eq!(
+            vec![
+                (b"Content-Type".as_slice(), b"text/html".as_slice()),
+                (b"Set-Cookie", REDACTED.as_bytes()),
+                (b"SET-COOKIE", REDACTED.as_bytes()),
+            ],
+            raw_headers.iter().collect::<Vec<_>>()
+        );
+    }
+}
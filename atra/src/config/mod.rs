@@ -12,17 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod auth;
+pub mod bandwidth;
 pub mod configs;
 pub mod crawl;
+pub mod header_persistence;
 pub mod paths;
+pub mod profiles;
 pub mod session;
 pub mod system;
 
 pub use configs::Config;
 pub use crawl::BudgetSetting;
 pub use crawl::CrawlConfig;
+pub use crawl::{
+    DownloadFilter, ErrorPageExtraction, FetchRetryConfig, OriginMetadataConfig, PublicationWindow,
+    PublicationWindowMode, RediscoveryConfig, RobotsRevalidationConfig, StreamingBodies,
+    TlsCaptureConfig,
+};
 #[allow(unused_imports)]
 pub use paths::PathsConfig;
 #[allow(unused_imports)]
 pub use session::SessionConfig;
-pub use system::SystemConfig;
+pub use system::{
+    BlacklistWatchConfig, CooperativeConfig, DatFileCompressionConfig, LinkStateCacheCapacity,
+    MetricsConfig, RecoveryConfig, SeenFilterConfig, SystemConfig,
+};
@@ -70,6 +70,16 @@ impl PathsConfig {
             root => dir_big_files = directories.big_files;
         )
     }
+
+    /// The journal of the urls in progress, next to the queue file.
+    pub fn file_dequeue_journal(&self) -> Utf8PathBuf {
+        Utf8PathBuf::from(format!("{}.journal", self.file_queue()))
+    }
+
+    /// The queue of the urls held back for the later waves, next to the queue file.
+    pub fn file_wave_frontier(&self) -> Utf8PathBuf {
+        Utf8PathBuf::from(format!("{}.waves", self.file_queue()))
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -0,0 +1,627 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::BudgetSetting;
+use crate::extraction::extractor::Extractor;
+use crate::toolkit::header_map_extensions::optional_header_map;
+use crate::url::AtraUrlOrigin;
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONNECTION, CONTENT_LENGTH, COOKIE, HOST,
+    PROXY_AUTHORIZATION, TE, TRANSFER_ENCODING,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use thiserror::Error;
+use time::Duration;
+
+/// The pattern matching every origin.
+pub const ANY_ORIGIN: &str = "*";
+
+/// The headers managed by the client, configuring them breaks the requests.
+pub const FORBIDDEN_HEADERS: [HeaderName; 5] =
+    [HOST, CONTENT_LENGTH, TRANSFER_ENCODING, CONNECTION, TE];
+
+/// The headers with credentials as values, they are redacted in any output.
+pub const SENSITIVE_HEADERS: [HeaderName; 3] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE];
+
+/// The replacement for redacted values.
+pub const REDACTED: &str = "<redacted>";
+
+/// A bundle of settings for the origins it is assigned to.
+/// Unset values fall back to the global crawl config.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct OriginProfile {
+    /// Polite crawling delay
+    pub delay: Option<Duration>,
+    /// The budget of the origin
+    pub budget: Option<BudgetSetting>,
+    /// Headers added to the global headers, a header with the same name replaces the global one.
+    #[serde(with = "optional_header_map")]
+    pub headers: Option<HeaderMap>,
+    /// The `Accept-Language` sent to the origin, replaces the one of the headers.
+    pub accept_language: Option<String>,
+    /// Cookie string to use for network requests ex: "foo=bar; Domain=blog.spider"
+    pub cookies: Option<String>,
+    /// Extract links to embedded data like audio/video files
+    pub crawl_embedded_data: Option<bool>,
+    /// Extract links from form actions
+    pub crawl_forms: Option<bool>,
+    /// Extract links to/from javascript files
+    pub crawl_javascript: Option<bool>,
+    /// Try to extract links from tags with onclick attribute
+    pub crawl_onclick_by_heuristic: Option<bool>,
+    /// The extractors used for the origin
+    pub link_extractors: Option<Extractor>,
+}
+
+impl OriginProfile {
+    /// Returns the settings of self, the unset values are taken from [fallback].
+    /// The headers of both are merged, the headers of self win.
+    pub fn or(&self, fallback: &OriginProfile) -> OriginProfile {
+        let headers = match (&self.headers, &fallback.headers) {
+            (Some(headers), Some(fallback)) => Some(merge_headers(fallback, headers)),
+            (headers, fallback) => headers.as_ref().or(fallback.as_ref()).cloned(),
+        };
+        OriginProfile {
+            delay: self.delay.or(fallback.delay),
+            budget: self.budget.clone().or_else(|| fallback.budget.clone()),
+            headers,
+            accept_language: self
+                .accept_language
+                .clone()
+                .or_else(|| fallback.accept_language.clone()),
+            cookies: self.cookies.clone().or_else(|| fallback.cookies.clone()),
+            crawl_embedded_data: self.crawl_embedded_data.or(fallback.crawl_embedded_data),
+            crawl_forms: self.crawl_forms.or(fallback.crawl_forms),
+            crawl_javascript: self.crawl_javascript.or(fallback.crawl_javascript),
+            crawl_onclick_by_heuristic: self
+                .crawl_onclick_by_heuristic
+                .or(fallback.crawl_onclick_by_heuristic),
+            link_extractors: self
+                .link_extractors
+                .clone()
+                .or_else(|| fallback.link_extractors.clone()),
+        }
+    }
+}
+
+/// Assigns a profile and inline overrides to an origin pattern.
+/// The inline overrides beat the values of the profile.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct OriginAssignment {
+    /// The name of the profile in [crate::config::CrawlConfig::profiles]
+    pub profile: Option<String>,
+    /// The inline overrides
+    #[serde(flatten)]
+    pub overrides: OriginProfile,
+}
+
+/// The settings of an origin pattern after merging the inline overrides with the profile.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct ResolvedOrigin {
+    /// The name of the assigned profile
+    pub profile: Option<String>,
+    /// The merged settings
+    pub settings: OriginProfile,
+}
+
+/// The resolved settings for every origin pattern of a config.
+/// An origin pattern is either an origin like `example.com`, a wildcard like `*.example.com`
+/// matching the origin and all of its subdomains or `*` matching every origin.
+#[derive(Debug, Default, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(transparent)]
+pub struct EffectiveOrigins {
+    by_pattern: BTreeMap<String, ResolvedOrigin>,
+}
+
+/// The errors when resolving the profiles
+#[derive(Debug, Error)]
+pub enum ProfileError {
+    #[error("The origin {origin} references the undefined profile {profile}.")]
+    UndefinedProfile { origin: String, profile: String },
+    #[error("The origin pattern {0:?} is invalid, only a leading \"*.\" or \"*\" is allowed.")]
+    InvalidOriginPattern(String),
+    #[error("The header {header} of {scope} is managed by the client and can not be configured.")]
+    ForbiddenHeader { scope: String, header: String },
+    #[error("The accept language {value:?} of {scope} is not a valid header value.")]
+    InvalidAcceptLanguage { scope: String, value: String },
+}
+
+impl EffectiveOrigins {
+    /// Resolves the [origins] with the [profiles].
+    pub fn resolve(
+        profiles: &BTreeMap<String, OriginProfile>,
+        origins: &BTreeMap<String, OriginAssignment>,
+    ) -> Result<Self, ProfileError> {
+        for (name, profile) in profiles {
+            if let Some(headers) = &profile.headers {
+                check_headers(&format!("the profile {name}"), headers)?;
+            }
+            if let Some(language) = &profile.accept_language {
+                check_accept_language(&format!("the profile {name}"), language)?;
+            }
+        }
+        let mut by_pattern = BTreeMap::new();
+        for (pattern, assignment) in origins {
+            let normalized = normalize_pattern(pattern)?;
+            if let Some(headers) = &assignment.overrides.headers {
+                check_headers(&format!("the origin {pattern}"), headers)?;
+            }
+            if let Some(language) = &assignment.overrides.accept_language {
+                check_accept_language(&format!("the origin {pattern}"), language)?;
+            }
+            let mut settings = match &assignment.profile {
+                None => assignment.overrides.clone(),
+                Some(name) => match profiles.get(name) {
+                    None => {
+                        return Err(ProfileError::UndefinedProfile {
+                            origin: pattern.clone(),
+                            profile: name.clone(),
+                        })
+                    }
+                    Some(profile) => assignment.overrides.or(profile),
+                },
+            };
+            if let Some(headers) = &mut settings.headers {
+                mark_sensitive_headers(headers);
+            }
+            by_pattern.insert(
+                normalized,
+                ResolvedOrigin {
+                    profile: assignment.profile.clone(),
+                    settings,
+                },
+            );
+        }
+        Ok(Self { by_pattern })
+    }
+
+    /// Returns the most specific pattern matching the [origin] and its settings.
+    /// An exact origin beats the wildcards, a longer wildcard beats a shorter one.
+    pub fn get(&self, origin: &str) -> Option<(&str, &ResolvedOrigin)> {
+        if self.by_pattern.is_empty() {
+            return None;
+        }
+        let origin = origin.to_lowercase();
+        if let Some((pattern, found)) = self.by_pattern.get_key_value(&origin) {
+            return Some((pattern.as_str(), found));
+        }
+        let mut suffix = origin.as_str();
+        loop {
+            if let Some((pattern, found)) = self.by_pattern.get_key_value(&format!("*.{suffix}")) {
+                return Some((pattern.as_str(), found));
+            }
+            match suffix.split_once('.') {
+                Some((_, rest)) => suffix = rest,
+                None => break,
+            }
+        }
+        self.by_pattern
+            .get_key_value(ANY_ORIGIN)
+            .map(|(pattern, found)| (pattern.as_str(), found))
+    }
+}
+
+/// The settings used for an origin after applying all overrides.
+#[derive(Debug, Clone, Serialize)]
+pub struct EffectiveOriginConfig {
+    pub origin: AtraUrlOrigin,
+    /// The matching pattern of [crate::config::CrawlConfig::origins]
+    pub pattern: Option<String>,
+    /// The name of the assigned profile
+    pub profile: Option<String>,
+    pub delay: Option<Duration>,
+    pub budget: BudgetSetting,
+    #[serde(with = "optional_header_map")]
+    pub headers: Option<HeaderMap>,
+    pub cookies: Option<String>,
+    pub crawl_embedded_data: bool,
+    pub crawl_forms: bool,
+    pub crawl_javascript: bool,
+    pub crawl_onclick_by_heuristic: bool,
+    pub link_extractors: Extractor,
+}
+
+impl Display for EffectiveOriginConfig {
+    /// Renders the config as json, the cookies and the values of sensitive headers are redacted.
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut redacted = self.clone();
+        redacted.headers = self.headers.as_ref().map(redact_headers);
+        redacted.cookies = self.cookies.as_ref().map(|_| REDACTED.to_string());
+        let rendered = serde_json::to_string_pretty(&redacted).map_err(|_| std::fmt::Error)?;
+        write!(f, "{rendered}")
+    }
+}
+
+/// Returns the [base] headers extended by the [overrides], a header of the [overrides]
+/// replaces all values of the same name in [base].
+pub fn merge_headers(base: &HeaderMap, overrides: &HeaderMap) -> HeaderMap {
+    let mut merged = base.clone();
+    for name in overrides.keys() {
+        merged.remove(name);
+    }
+    for (name, value) in overrides {
+        merged.append(name, value.clone());
+    }
+    merged
+}
+
+/// Fails if [headers] contain one of the [FORBIDDEN_HEADERS], the [scope] names the place
+/// where they are configured.
+pub fn check_headers(scope: &str, headers: &HeaderMap) -> Result<(), ProfileError> {
+    match FORBIDDEN_HEADERS
+        .iter()
+        .find(|name| headers.contains_key(*name))
+    {
+        None => Ok(()),
+        Some(name) => Err(ProfileError::ForbiddenHeader {
+            scope: scope.to_string(),
+            header: name.to_string(),
+        }),
+    }
+}
+
+/// Fails if the [language] configured in the [scope] can not be sent as `Accept-Language`.
+pub fn check_accept_language(scope: &str, language: &str) -> Result<(), ProfileError> {
+    if !language.trim().is_empty() && HeaderValue::from_str(language).is_ok() {
+        return Ok(());
+    }
+    Err(ProfileError::InvalidAcceptLanguage {
+        scope: scope.to_string(),
+        value: language.to_string(),
+    })
+}
+
+/// Returns true if the values of the header with the [name] have to be redacted.
+pub fn is_sensitive_header(name: &HeaderName) -> bool {
+    SENSITIVE_HEADERS.contains(name)
+}
+
+/// Marks the values of the [SENSITIVE_HEADERS] as sensitive, their debug output is hidden.
+pub fn mark_sensitive_headers(headers: &mut HeaderMap) {
+    for (name, value) in headers.iter_mut() {
+        if is_sensitive_header(name) {
+            value.set_sensitive(true);
+        }
+    }
+}
+
+/// Returns a copy of [headers] with the values of sensitive headers replaced by [REDACTED].
+pub fn redact_headers(headers: &HeaderMap) -> HeaderMap {
+    let mut redacted = HeaderMap::with_capacity(headers.len());
+    for (name, value) in headers {
+        if value.is_sensitive() || is_sensitive_header(name) {
+            redacted.append(name, HeaderValue::from_static(REDACTED));
+        } else {
+            redacted.append(name, value.clone());
+        }
+    }
+    redacted
+}
+
+fn normalize_pattern(pattern: &str) -> Result<String, ProfileError> {
+    let normalized = pattern.trim().to_lowercase();
+    let host = normalized.strip_prefix("*.").unwrap_or(normalized.as_str());
+    if normalized != ANY_ORIGIN && (host.is_empty() || host.contains('*')) {
+        return Err(ProfileError::InvalidOriginPattern(pattern.to_string()));
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::profiles::{OriginAssignment, OriginProfile, ProfileError};
+    use crate::config::{BudgetSetting, CrawlConfig};
+    use crate::url::AtraUrlOrigin;
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use time::Duration;
+
+    fn headers(values: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in values {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn origin(value: &str) -> AtraUrlOrigin {
+        AtraUrlOrigin::from(value)
+    }
+
+    fn assigned(profile: Option<&str>, overrides: OriginProfile) -> OriginAssignment {
+        OriginAssignment {
+            profile: profile.map(str::to_string),
+            overrides,
+        }
+    }
+
+    #[test]
+    fn inline_beats_profile_beats_global() {
+        let mut config = CrawlConfig::default();
+        config.delay = Some(Duration::seconds(1));
+        config.headers = Some(headers(&[("x-a", "global"), ("x-b", "global")]));
+        config.profiles.insert(
+            "polite".to_string(),
+            OriginProfile {
+                delay: Some(Duration::seconds(5)),
+                budget: Some(BudgetSetting::SeedOnly {
+                    depth_on_website: 3,
+                    recrawl_interval: None,
+                    request_timeout: None,
+                }),
+                headers: Some(headers(&[("x-b", "profile"), ("x-c", "profile")])),
+                crawl_forms: Some(true),
+                ..Default::default()
+            },
+        );
+        config.origins.insert(
+            "example.com".to_string(),
+            assigned(
+                Some("polite"),
+                OriginProfile {
+                    delay: Some(Duration::seconds(10)),
+                    headers: Some(headers(&[("x-c", "inline")])),
+                    ..Default::default()
+                },
+            ),
+        );
+        config.resolve_profiles().unwrap();
+
+        let example = origin("example.com");
+        assert_eq!(Some(Duration::seconds(10)), config.delay_for(&example));
+        assert_eq!(
+            &BudgetSetting::SeedOnly {
+                depth_on_website: 3,
+                recrawl_interval: None,
+                request_timeout: None,
+            },
+            config.budget_for(&example)
+        );
+        assert_eq!(
+            headers(&[("x-a", "global"), ("x-b", "profile"), ("x-c", "inline")]),
+            config.headers_for(&example).unwrap().into_owned()
+        );
+        let effective = config.effective_for(&example);
+        assert!(effective.crawl_forms);
+        assert_eq!(config.crawl_javascript, effective.crawl_javascript);
+
+        let other = origin("example.org");
+        assert_eq!(Some(Duration::seconds(1)), config.delay_for(&other));
+        assert_eq!(&config.budget.default, config.budget_for(&other));
+        assert!(!config.effective_for(&other).crawl_forms);
+    }
+
+    #[test]
+    fn the_most_specific_pattern_wins() {
+        let mut config = CrawlConfig::default();
+        for (name, delay) in [("slow", 30), ("fast", 1)] {
+            config.profiles.insert(
+                name.to_string(),
+                OriginProfile {
+                    delay: Some(Duration::seconds(delay)),
+                    ..Default::default()
+                },
+            );
+        }
+        config
+            .origins
+            .insert("*".to_string(), assigned(Some("fast"), Default::default()));
+        config.origins.insert(
+            "*.Example.org".to_string(),
+            assigned(Some("slow"), Default::default()),
+        );
+        config.origins.insert(
+            "shop.example.org".to_string(),
+            assigned(
+                None,
+                OriginProfile {
+                    crawl_forms: Some(true),
+                    ..Default::default()
+                },
+            ),
+        );
+        config.resolve_profiles().unwrap();
+
+        for (value, pattern, delay) in [
+            ("example.org", Some("*.example.org"), Some(30)),
+            ("a.b.example.org", Some("*.example.org"), Some(30)),
+            ("shop.example.org", Some("shop.example.org"), None),
+            ("example.net", Some("*"), Some(1)),
+        ] {
+            let effective = config.effective_for(&origin(value));
+            assert_eq!(pattern, effective.pattern.as_deref(), "{value}");
+            assert_eq!(delay.map(Duration::seconds), effective.delay, "{value}");
+        }
+        assert!(
+            config
+                .effective_for(&origin("shop.example.org"))
+                .crawl_forms
+        );
+
+        config.origins.remove("*");
+        config.resolve_profiles().unwrap();
+        assert_eq!(None, config.effective_for(&origin("example.net")).pattern);
+    }
+
+    #[test]
+    fn undefined_profiles_are_rejected() {
+        let mut config = CrawlConfig::default();
+        config.origins.insert(
+            "example.com".to_string(),
+            assigned(Some("missing"), Default::default()),
+        );
+        assert!(matches!(
+            config.resolve_profiles(),
+            Err(ProfileError::UndefinedProfile { profile, .. }) if profile == "missing"
+        ));
+
+        let mut config = CrawlConfig::default();
+        config
+            .origins
+            .insert("shop.*.com".to_string(), Default::default());
+        assert!(matches!(
+            config.resolve_profiles(),
+            Err(ProfileError::InvalidOriginPattern(_))
+        ));
+    }
+
+    #[test]
+    fn shows_the_effective_settings_of_a_layered_config() {
+        const CONFIG: &str = r#"{
+            "crawl_forms": false,
+            "headers": {"x-customer": "none"},
+            "profiles": {
+                "customer": {
+                    "crawl_forms": true,
+                    "headers": {"x-customer": "acme"},
+                    "budget": {"depth_on_website": 2}
+                }
+            },
+            "origins": {
+                "*.acme.com": {"profile": "customer", "cookies": "session=1"}
+            }
+        }"#;
+        let mut config: CrawlConfig = serde_json::from_str(CONFIG).unwrap();
+        config.resolve_profiles().unwrap();
+
+        let shown = config.effective_for(&origin("shop.acme.com")).to_string();
+        let lines = shown.lines().map(str::trim).collect::<Vec<_>>();
+        for expected in [
+            r#""origin": "shop.acme.com","#,
+            r#""pattern": "*.acme.com","#,
+            r#""profile": "customer","#,
+            r#""x-customer": "acme""#,
+            r#""cookies": "<redacted>","#,
+            r#""crawl_forms": true,"#,
+            r#""depth_on_website": 2,"#,
+        ] {
+            assert!(lines.contains(&expected), "Missing {expected} in:\n{shown}");
+        }
+
+        let snapshot = serde_json::to_string(&config).unwrap();
+        let reloaded: CrawlConfig = serde_json::from_str(&snapshot).unwrap();
+        assert_eq!(config, reloaded);
+    }
+
+    #[test]
+    fn headers_managed_by_the_client_are_rejected() {
+        let mut config = CrawlConfig::default();
+        config.headers = Some(headers(&[("host", "example.com")]));
+        assert!(matches!(
+            config.resolve_profiles(),
+            Err(ProfileError::ForbiddenHeader { header, .. }) if header == "host"
+        ));
+
+        let mut config = CrawlConfig::default();
+        config.origins.insert(
+            "example.com".to_string(),
+            assigned(
+                None,
+                OriginProfile {
+                    headers: Some(headers(&[("x-a", "inline"), ("content-length", "5")])),
+                    ..Default::default()
+                },
+            ),
+        );
+        assert!(matches!(
+            config.resolve_profiles(),
+            Err(ProfileError::ForbiddenHeader { scope, header })
+                if scope == "the origin example.com" && header == "content-length"
+        ));
+    }
+
+    #[test]
+    fn the_accept_language_replaces_the_configured_header() {
+        let mut config = CrawlConfig::default();
+        config.headers = Some(headers(&[("accept-language", "fr"), ("x-a", "global")]));
+        config.accept_language = Some("de-DE,de;q=0.9".to_string());
+        config.origins.insert(
+            "example.com".to_string(),
+            assigned(
+                None,
+                OriginProfile {
+                    accept_language: Some("en".to_string()),
+                    ..Default::default()
+                },
+            ),
+        );
+        config.resolve_profiles().unwrap();
+
+        assert_eq!(
+            headers(&[("accept-language", "en"), ("x-a", "global")]),
+            config
+                .headers_for(&origin("example.com"))
+                .unwrap()
+                .into_owned()
+        );
+        assert_eq!(
+            headers(&[("accept-language", "de-DE,de;q=0.9"), ("x-a", "global")]),
+            config
+                .headers_for(&origin("example.org"))
+                .unwrap()
+                .into_owned()
+        );
+
+        config.headers = None;
+        config.accept_language = None;
+        assert_eq!(None, config.headers_for(&origin("example.org")));
+
+        config.accept_language = Some("de\nX-Injected: 1".to_string());
+        assert!(matches!(
+            config.resolve_profiles(),
+            Err(ProfileError::InvalidAcceptLanguage { scope, .. }) if scope == "the crawl config"
+        ));
+    }
+
+    #[test]
+    fn sensitive_values_are_redacted() {
+        let mut config = CrawlConfig::default();
+        config.headers = Some(headers(&[
+            ("authorization", "Bearer secret"),
+            ("accept-language", "de"),
+        ]));
+        config.origins.insert(
+            "example.com".to_string(),
+            assigned(
+                None,
+                OriginProfile {
+                    headers: Some(headers(&[("cookie", "session=secret")])),
+                    cookies: Some("session=secret".to_string()),
+                    ..Default::default()
+                },
+            ),
+        );
+        config.resolve_profiles().unwrap();
+
+        let effective = config.effective_for(&origin("example.com"));
+        let sent = effective.headers.as_ref().unwrap();
+        assert_eq!("Bearer secret", sent["authorization"]);
+        assert!(sent["authorization"].is_sensitive());
+        assert!(sent["cookie"].is_sensitive());
+        assert!(!sent["accept-language"].is_sensitive());
+        assert!(!format!("{sent:?}").contains("secret"));
+
+        let shown = effective.to_string();
+        assert!(!shown.contains("secret"), "{shown}");
+        assert!(shown.contains(r#""accept-language": "de""#), "{shown}");
+        assert!(
+            shown.contains(r#""authorization": "<redacted>""#),
+            "{shown}"
+        );
+    }
+}
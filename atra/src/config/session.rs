@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use serde::{Deserialize, Serialize};
+use strum::Display;
 
 /// The config of the session
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
@@ -27,6 +28,19 @@ pub struct SessionConfig {
     /// The crawl job id
     #[serde(default)]
     pub crawl_job_id: u64,
+    /// How the big files are stored, can not be changed for an existing session.
+    #[serde(default)]
+    pub dat_file_layout: DatFileLayout,
+}
+
+/// The layout of the big files of a session.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize, Eq, PartialEq, Display)]
+pub enum DatFileLayout {
+    /// Every file has a generated unique name in one folder.
+    #[default]
+    Flat,
+    /// Every file is stored once by the digest of its content, identical bodies share the file.
+    ContentAddressed,
 }
 
 fn _default_service_name() -> String {
@@ -43,6 +57,7 @@ impl Default for SessionConfig {
             service: "atra".to_string(),
             collection: "unnamed".to_string(),
             crawl_job_id: 0,
+            dat_file_layout: DatFileLayout::Flat,
         }
     }
 }
@@ -12,9 +12,13 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::web_graph::DEFAULT_CACHE_SIZE_WEB_GRAPH;
+use camino::Utf8PathBuf;
 use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
 use std::num::NonZeroUsize;
+use time::Duration;
 use ubyte::ByteUnit;
 
 /// The default cache size for the robots cache
@@ -55,6 +59,53 @@ pub struct SystemConfig {
     /// Log to a file?
     #[serde(default)]
     pub log_to_file: bool,
+
+    /// The part of a file read to detect its format.
+    #[serde(default)]
+    pub sniff_window: SniffWindowConfig,
+
+    /// If set, secondary processes can connect to the crawl and fetch urls for it.
+    #[serde(default)]
+    pub cooperative: Option<CooperativeConfig>,
+
+    /// If set, the progress of the crawl is exposed in the Prometheus text format.
+    #[serde(default)]
+    pub metrics: Option<MetricsConfig>,
+
+    /// If set, the entries of a blacklist file are added to the blacklist of the running crawl.
+    #[serde(default)]
+    pub blacklist_watch: Option<BlacklistWatchConfig>,
+
+    /// How recover finds the urls that were in progress when the crawl ended unexpectedly.
+    #[serde(default)]
+    pub recovery: RecoveryConfig,
+
+    /// The capacity of the in-memory cache in front of the link state database.
+    #[serde(default)]
+    pub link_state_cache: LinkStateCacheCapacity,
+
+    /// If set, a bloom filter of all known urls is kept in memory. The link state database is
+    /// only read for the discovered urls that may be known already.
+    #[serde(default)]
+    pub seen_filter: Option<SeenFilterConfig>,
+
+    /// If set, the external data files of compressible formats are stored zstd compressed.
+    #[serde(default)]
+    pub dat_file_compression: Option<DatFileCompressionConfig>,
+
+    /// The idle connections kept open by the clients for the next requests.
+    #[serde(default)]
+    pub connection_pool: ConnectionPoolConfig,
+
+    /// If set, the database of a previous session. The urls stored there are only crawled
+    /// again once their recrawl interval is over.
+    #[serde(default)]
+    pub baseline: Option<Utf8PathBuf>,
+
+    /// If set, the block digest of a warc record is checked whenever a stored body is read
+    /// back from it, a corrupt record fails the read.
+    #[serde(default)]
+    pub verify_warc_digests: bool,
 }
 
 const fn _default_log_level() -> log::LevelFilter {
@@ -82,6 +133,278 @@ impl Default for SystemConfig {
             max_temp_file_size_on_disc: _default_max_temp_file_size_on_disc(),
             log_level: _default_log_level(),
             log_to_file: false,
+            sniff_window: SniffWindowConfig::default(),
+            cooperative: None,
+            metrics: None,
+            blacklist_watch: None,
+            recovery: RecoveryConfig::default(),
+            link_state_cache: LinkStateCacheCapacity::default(),
+            seen_filter: None,
+            dat_file_compression: None,
+            connection_pool: ConnectionPoolConfig::default(),
+            baseline: None,
+            verify_warc_digests: false,
+        }
+    }
+}
+
+/// The default number of bytes read from the start of a file to detect its format.
+pub const DEFAULT_SNIFF_PREFIX_BYTES: usize = ByteUnit::Kibibyte(64).as_u64() as usize;
+/// The default number of bytes read from the end of a file to detect its format.
+pub const DEFAULT_SNIFF_SUFFIX_BYTES: usize = ByteUnit::Kibibyte(64).as_u64() as usize;
+
+/// Limits how much of a file is read to detect its format.
+/// The detection never reads more than `prefix_bytes + suffix_bytes` of a file.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct SniffWindowConfig {
+    /// The bytes read from the start of a file. (default: 64KiB)
+    #[serde(default = "_default_sniff_prefix_bytes")]
+    pub prefix_bytes: usize,
+    /// The bytes read from the end of a file, used to find the central directory
+    /// of zip based formats like OOXML. If set to 0 the end is never read. (default: 64KiB)
+    #[serde(default = "_default_sniff_suffix_bytes")]
+    pub suffix_bytes: usize,
+}
+
+const fn _default_sniff_prefix_bytes() -> usize {
+    DEFAULT_SNIFF_PREFIX_BYTES
+}
+const fn _default_sniff_suffix_bytes() -> usize {
+    DEFAULT_SNIFF_SUFFIX_BYTES
+}
+
+impl Default for SniffWindowConfig {
+    fn default() -> Self {
+        Self {
+            prefix_bytes: _default_sniff_prefix_bytes(),
+            suffix_bytes: _default_sniff_suffix_bytes(),
+        }
+    }
+}
+
+/// The default time a secondary has to send a heartbeat or the outcome of its url.
+pub const DEFAULT_LEASE_TIMEOUT: Duration = Duration::seconds(30);
+
+/// The primary side of a cooperative crawl. The primary owns the databases and the warc files,
+/// the secondaries connect to the unix socket and fetch the urls leased to them.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct CooperativeConfig {
+    /// The path of the unix socket the secondaries connect to.
+    pub socket: Utf8PathBuf,
+    /// A url is returned to the queue if its secondary does not answer in time. (default: 30s)
+    #[serde(default = "_default_lease_timeout")]
+    pub lease_timeout: Duration,
+}
+
+const fn _default_lease_timeout() -> Duration {
+    DEFAULT_LEASE_TIMEOUT
+}
+
+impl CooperativeConfig {
+    pub fn new(socket: Utf8PathBuf) -> Self {
+        Self {
+            socket,
+            lease_timeout: _default_lease_timeout(),
         }
     }
 }
+
+/// Serves the metrics of the crawl at `/metrics`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MetricsConfig {
+    /// The address of the http endpoint, e.g. `127.0.0.1:9187`.
+    pub address: SocketAddr,
+    /// The token the requests changing the crawl have to carry as `Authorization: Bearer
+    /// <token>`. Without one they are only accepted from loopback. (default: null)
+    #[serde(default)]
+    pub control_token: Option<String>,
+}
+
+impl MetricsConfig {
+    pub fn new(address: SocketAddr) -> Self {
+        Self {
+            address,
+            control_token: None,
+        }
+    }
+}
+
+/// The default time between two checks of a watched blacklist file.
+pub const DEFAULT_BLACKLIST_WATCH_INTERVAL: Duration = Duration::seconds(10);
+
+/// Polls a blacklist file for modifications and adds its new entries to the blacklist.
+/// Entries removed from the file stay in the blacklist.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BlacklistWatchConfig {
+    /// The path of the watched file, it has the same format as the blacklist file.
+    pub path: Utf8PathBuf,
+    /// The time between two checks for modifications. (default: 10s)
+    #[serde(default = "_default_blacklist_watch_interval")]
+    pub interval: Duration,
+}
+
+const fn _default_blacklist_watch_interval() -> Duration {
+    DEFAULT_BLACKLIST_WATCH_INTERVAL
+}
+
+impl BlacklistWatchConfig {
+    pub fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            interval: _default_blacklist_watch_interval(),
+        }
+    }
+}
+
+/// The default age of a link state in progress before recover considers its url stranded.
+pub const DEFAULT_STRANDED_AFTER: Duration = Duration::minutes(5);
+/// The default maximum of stranded urls requeued from the link states by recover.
+pub const DEFAULT_MAX_REQUEUED_STRANDED: usize = 100_000;
+/// The default time between two checkpoints of a running crawl.
+pub const DEFAULT_CHECKPOINT_INTERVAL: Duration = Duration::seconds(30);
+
+/// Limits how recover requeues the urls that were in progress when a crawl ended unexpectedly.
+/// The urls in the dequeue journal are always requeued, the link states are only a fallback.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct RecoveryConfig {
+    /// A reserved or crawled link state older than this is considered stranded. (default: 5min)
+    #[serde(default = "_default_stranded_after")]
+    pub stranded_after: Duration,
+    /// The maximum of stranded urls requeued from the link states, the rest is only counted.
+    /// (default: 100000)
+    #[serde(default = "_default_max_requeued_stranded")]
+    pub max_requeued: usize,
+    /// The time between two checkpoints of the urls in progress and the reserved origins.
+    /// No checkpoints are written if null. (default: 30s)
+    #[serde(default = "_default_checkpoint_interval")]
+    pub checkpoint_interval: Option<Duration>,
+}
+
+const fn _default_stranded_after() -> Duration {
+    DEFAULT_STRANDED_AFTER
+}
+const fn _default_max_requeued_stranded() -> usize {
+    DEFAULT_MAX_REQUEUED_STRANDED
+}
+const fn _default_checkpoint_interval() -> Option<Duration> {
+    Some(DEFAULT_CHECKPOINT_INTERVAL)
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self {
+            stranded_after: _default_stranded_after(),
+            max_requeued: _default_max_requeued_stranded(),
+            checkpoint_interval: _default_checkpoint_interval(),
+        }
+    }
+}
+
+/// The default number of link states kept in memory.
+pub const DEFAULT_LINK_STATE_CACHE_ENTRIES: usize = 65_536;
+
+/// Bounds the cache of the link states, the capacity is split evenly over the shards of the cache.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkStateCacheCapacity {
+    /// At most this many link states are kept. 0 disables the cache.
+    Entries(usize),
+    /// At most this many bytes of urls and link states are kept. 0 disables the cache.
+    Bytes(usize),
+}
+
+impl LinkStateCacheCapacity {
+    /// Returns true if nothing can be cached.
+    pub fn is_disabled(&self) -> bool {
+        matches!(self, Self::Entries(0) | Self::Bytes(0))
+    }
+}
+
+impl Default for LinkStateCacheCapacity {
+    fn default() -> Self {
+        Self::Entries(DEFAULT_LINK_STATE_CACHE_ENTRIES)
+    }
+}
+
+/// The default number of urls the seen filter is sized for.
+pub const DEFAULT_SEEN_FILTER_EXPECTED_URLS: usize = 10_000_000;
+
+/// Sizes the bloom filter in front of the link states. The filter grows if more urls are seen,
+/// but a filter sized for the crawl needs less memory and fewer lookups.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, PartialEq, Eq)]
+pub struct SeenFilterConfig {
+    /// The number of urls expected in the link states. (default: 10,000,000)
+    #[serde(default = "_default_seen_filter_expected_urls")]
+    pub expected_urls: usize,
+}
+
+const fn _default_seen_filter_expected_urls() -> usize {
+    DEFAULT_SEEN_FILTER_EXPECTED_URLS
+}
+
+impl Default for SeenFilterConfig {
+    fn default() -> Self {
+        Self {
+            expected_urls: _default_seen_filter_expected_urls(),
+        }
+    }
+}
+
+/// The default zstd compression level of the external data files.
+pub const DEFAULT_DAT_FILE_COMPRESSION_LEVEL: i32 = 3;
+
+/// Compresses the external data files of the configured formats while downloading them.
+/// Formats that are already compressed (zip, office documents, images, ...) are never compressed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct DatFileCompressionConfig {
+    /// The zstd compression level. (default: 3)
+    #[serde(default = "_default_dat_file_compression_level")]
+    pub level: i32,
+    /// The formats compressed. (default: HTML, JSON, XML, feeds, plain text, CSS, JavaScript and SVG)
+    #[serde(default = "_default_compressible_formats")]
+    pub formats: Vec<InterpretedProcessibleFileFormat>,
+}
+
+const fn _default_dat_file_compression_level() -> i32 {
+    DEFAULT_DAT_FILE_COMPRESSION_LEVEL
+}
+fn _default_compressible_formats() -> Vec<InterpretedProcessibleFileFormat> {
+    vec![
+        InterpretedProcessibleFileFormat::HTML,
+        InterpretedProcessibleFileFormat::JSON,
+        InterpretedProcessibleFileFormat::XML,
+        InterpretedProcessibleFileFormat::Feed,
+        InterpretedProcessibleFileFormat::PlainText,
+        InterpretedProcessibleFileFormat::StructuredPlainText,
+        InterpretedProcessibleFileFormat::CSS,
+        InterpretedProcessibleFileFormat::JavaScript,
+        InterpretedProcessibleFileFormat::SVG,
+    ]
+}
+
+impl DatFileCompressionConfig {
+    /// Returns true if a file of [format] is compressed.
+    pub fn compresses(&self, format: InterpretedProcessibleFileFormat) -> bool {
+        !format.is_compressed() && self.formats.contains(&format)
+    }
+}
+
+impl Default for DatFileCompressionConfig {
+    fn default() -> Self {
+        Self {
+            level: _default_dat_file_compression_level(),
+            formats: _default_compressible_formats(),
+        }
+    }
+}
+
+/// Tunes the pool of idle connections of a client, every origin has its own client.
+#[derive(Debug, Serialize, Deserialize, Copy, Clone, Default, PartialEq, Eq)]
+pub struct ConnectionPoolConfig {
+    /// The maximum of idle connections kept per host, unlimited if null. (default: null)
+    #[serde(default)]
+    pub max_idle_per_host: Option<usize>,
+    /// Idle connections are closed after this time, never if null. (default: null)
+    #[serde(default)]
+    pub idle_timeout: Option<Duration>,
+}
@@ -49,29 +49,45 @@ create_abstract_traits! {
         SupportsWorkerId,
         SupportsCrawling,
         SupportsDomainHandling,
+        SupportsUrlNormalization,
+        SupportsUrlFilter,
+        SupportsCrawlEvents,
+        SupportsCrawlLimits,
+        SupportsBaseline,
+        SupportsUniqueContent,
+        SupportsTlsInfo,
+        SupportsOriginMetadata,
     }
 }
 
 pub mod traits {
     use crate::blacklist::BlacklistManager;
+    use crate::client::bandwidth::BandwidthLimiter;
     use crate::client::traits::AtraClient;
     use crate::config::Config;
     use crate::contexts::BaseContext;
     use crate::crawl::SlimCrawlResult;
-    use crate::crawl::{CrawlResult, CrawlTask};
+    use crate::crawl::{Baseline, CrawlEvents, CrawlLimits, CrawlResult, CrawlTask, WaveFrontier};
     use crate::extraction::ExtractedLink;
     use crate::gdbr::identifier::GdbrRegistry;
     use crate::io::fs::AtraFS;
     use crate::link_state::LinkStateManager;
-    use crate::queue::{SupportsForcedQueueElement, UrlQueue, UrlQueuePollResult};
+    use crate::origin_metadata::OriginMetadataManager;
+    use crate::queue::priority::UrlPriorityScorer;
+    use crate::queue::{DequeueJournal, SupportsForcedQueueElement, UrlQueue, UrlQueuePollResult};
     use crate::recrawl_management::DomainLastCrawledManager;
     use crate::robots::RobotsManager;
     #[cfg(test)]
     use crate::runtime::ShutdownPhantom;
     use crate::runtime::ShutdownReceiver;
     use crate::seed::BasicSeed;
-    use crate::url::guard::UrlGuardian;
+    use crate::tls_info::TlsInfoManager;
+    use crate::unique_content::UniqueContentManager;
+    use crate::url::filter::UrlFilterChain;
+    use crate::url::guard::{GuardianEventReceiver, UrlGuardian};
+    use crate::url::normalization::UrlNormalizer;
     use crate::url::{UrlWithDepth, UrlWithGuard};
+    use crate::warc_ext::WarcSkipInstruction;
     use crate::web_graph::WebGraphManager;
     use std::collections::HashSet;
     use std::error::Error;
@@ -94,12 +110,20 @@ pub mod traits {
         async fn register_seed<S: BasicSeed>(&self, seed: &S) -> Result<(), Self::Error>;
 
         /// Register outgoing & data links.
-        /// Also returns a list of all urls existing on the seed, that can be registered.
+        /// Also returns a list of all urls existing on the seed, that can be registered,
+        /// together with the score the url filters gave them.
         async fn handle_links(
             &self,
             from: &UrlWithDepth,
             links: &HashSet<ExtractedLink>,
-        ) -> Result<Vec<UrlWithDepth>, Self::Error>;
+        ) -> Result<Vec<(UrlWithDepth, i8)>, Self::Error>;
+
+        /// Registers the urls [listed] in the sitemap at [sitemap].
+        async fn register_sitemap_links(
+            &self,
+            sitemap: &UrlWithDepth,
+            listed: &[UrlWithDepth],
+        ) -> Result<(), Self::Error>;
     }
 
     /// Used when some kind of link management happens
@@ -116,6 +140,11 @@ pub mod traits {
 
         /// Returns a reference to a [GuardedDomainManager]
         fn get_guardian(&self) -> &Self::Guardian;
+
+        /// Subscribe to the events of the guardian, e.g. for an external scheduler.
+        fn subscribe_to_guardian(&self) -> GuardianEventReceiver {
+            self.get_guardian().subscribe()
+        }
     }
 
     pub trait SupportsRobotsManager: BaseContext {
@@ -158,6 +187,16 @@ pub mod traits {
         /// Get the instance of the url queue.
         fn url_queue(&self) -> &Self::UrlQueue;
 
+        /// The journal of the dequeued urls in progress, if the queue is persistent.
+        fn dequeue_journal(&self) -> Option<&DequeueJournal> {
+            None
+        }
+
+        /// Holds the urls of the later waves back, if the crawl is in wave mode.
+        fn wave_frontier(&self) -> Option<&WaveFrontier> {
+            None
+        }
+
         // Retrieves the next seed if possible.
         // fn poll_next_seed(&self, shutdown_handle: impl ShutdownReceiver, max_miss: Option<u64>) -> UrlQueuePollResult<>
     }
@@ -205,6 +244,20 @@ pub mod traits {
             &self,
             result: SlimCrawlResult,
         ) -> Result<(), Self::Error>;
+
+        /// Retrieve the warc records of an already stored body with the [digest].
+        /// Always returns None if the deduplication by digest is disabled.
+        async fn retrieve_stored_payload(
+            &self,
+            digest: &[u8],
+        ) -> Result<Option<WarcSkipInstruction>, Self::Error>;
+
+        /// Remember that the body with the [digest] is stored at [instruction].
+        async fn register_stored_payload(
+            &self,
+            digest: &[u8],
+            instruction: &WarcSkipInstruction,
+        ) -> Result<(), Self::Error>;
     }
 
     pub trait SupportsCrawlResults: BaseContext {
@@ -263,6 +316,11 @@ pub mod traits {
 
         /// Provides an unique id for this crawl instance.
         fn create_crawl_id(&self) -> String;
+
+        /// The bandwidth limits shared by the clients of the crawl, None if unlimited.
+        fn bandwidth_limiter(&self) -> Option<&BandwidthLimiter> {
+            None
+        }
     }
 
     pub trait SupportsDomainHandling: BaseContext {
@@ -270,4 +328,62 @@ pub mod traits {
 
         fn get_domain_manager(&self) -> &Self::DomainHandler;
     }
+
+    /// The context normalizes urls before they are registered.
+    pub trait SupportsUrlNormalization: BaseContext {
+        /// Returns the normalizer used for all urls of this context.
+        fn url_normalizer(&self) -> &UrlNormalizer;
+    }
+
+    /// The context decides which found urls are enqueued.
+    pub trait SupportsUrlFilter: BaseContext {
+        /// Returns the filters asked for all found urls of this context.
+        fn url_filter(&self) -> &UrlFilterChain;
+    }
+
+    /// Used when someone can listen to the progress of the crawl.
+    pub trait SupportsCrawlEvents: BaseContext {
+        /// Returns the events all workers of this context emit to.
+        fn crawl_events(&self) -> &CrawlEvents;
+    }
+
+    /// The context stops the whole crawl once one of its limits is reached.
+    pub trait SupportsCrawlLimits: BaseContext {
+        /// Returns the limits shared by all workers of this context.
+        fn crawl_limits(&self) -> &CrawlLimits;
+    }
+
+    /// The context knows the pages stored by a previous session, an incremental crawl
+    /// does not fetch them again.
+    pub trait SupportsBaseline: BaseContext {
+        /// Returns the baseline of this context, None if every url is crawled.
+        fn baseline(&self) -> Option<&Baseline>;
+    }
+
+    /// The context scores the urls before they are put into the queue.
+    pub trait SupportsUrlPriority: BaseContext {
+        /// Returns the scorer used for all urls of this context.
+        fn url_priority(&self) -> &UrlPriorityScorer;
+    }
+
+    /// The context counts the unique payloads fetched from each origin.
+    pub trait SupportsUniqueContent: BaseContext {
+        type UniqueContentManager: UniqueContentManager;
+
+        fn get_unique_content_manager(&self) -> &Self::UniqueContentManager;
+    }
+
+    /// The context keeps the tls info captured for each origin.
+    pub trait SupportsTlsInfo: BaseContext {
+        type TlsInfoManager: TlsInfoManager;
+
+        fn get_tls_info_manager(&self) -> &Self::TlsInfoManager;
+    }
+
+    /// The context aggregates the metadata of each origin.
+    pub trait SupportsOriginMetadata: BaseContext {
+        type OriginMetadataManager: OriginMetadataManager;
+
+        fn get_origin_metadata_manager(&self) -> &Self::OriginMetadataManager;
+    }
 }
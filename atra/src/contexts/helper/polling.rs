@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::blacklist::{Blacklist, BlacklistManager};
+use crate::client::traits::FetchFailureKind;
+use crate::config::crawl::QueueFairness;
 use crate::contexts::traits::{
-    SupportsConfigs, SupportsLinkState, SupportsPolling, SupportsUrlGuarding, SupportsUrlQueue,
+    SupportsBlackList, SupportsConfigs, SupportsLinkState, SupportsPolling, SupportsUrlGuarding,
+    SupportsUrlQueue,
 };
+use crate::fetching::{RediscoveryRetry, ThrottledRetry};
 use crate::link_state::{LinkStateKind, LinkStateLike, LinkStateManager};
 use crate::queue::{
     AbortCause, EnqueueCalled, QueueExtractionError, UrlQueue, UrlQueueElement, UrlQueueElementRef,
@@ -23,16 +28,22 @@ use crate::queue::{
 use crate::runtime::ShutdownReceiver;
 use crate::sync::ContinueOrStop;
 use crate::url::guard::{GuardianError, UrlGuardian};
-use crate::url::{AtraOriginProvider, UrlWithDepth, UrlWithGuard};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth, UrlWithGuard};
+use std::collections::HashSet;
 use std::error::Error;
 use std::time::Duration;
+use time::OffsetDateTime;
 use tokio::select;
 use tokio::sync::watch::Receiver;
 use tokio::time::Instant;
 
 impl<C> SupportsPolling for C
 where
-    C: SupportsUrlQueue + SupportsConfigs + SupportsUrlGuarding + SupportsLinkState,
+    C: SupportsUrlQueue
+        + SupportsConfigs
+        + SupportsUrlGuarding
+        + SupportsLinkState
+        + SupportsBlackList,
 {
     type Guardian = C::Guardian;
 
@@ -46,10 +57,23 @@ where
         let queue = self.url_queue();
         let guardian = self.get_guardian();
         let manager = self.get_link_state_manager();
+        let blacklist = self.get_blacklist_manager().get_blacklist().await;
+        let waves = self.wave_frontier();
         const MISSED_KEEPER_CACHE: usize = 32;
         let mut missed_host_cache: Vec<UrlQueueElementRef<UrlWithDepth>> =
             Vec::with_capacity(MISSED_KEEPER_CACHE);
+        // Throttled urls that are not due yet, they return to the queue when dropped.
+        let mut deferred: Vec<UrlQueueElementRef<UrlWithDepth>> = Vec::new();
+        let mut next_due: Option<OffsetDateTime> = None;
         let max_age = self.configs().crawl.max_queue_age;
+        let round_robin = self.configs().crawl.queue_fairness == QueueFairness::OriginRoundRobin;
+        // The number of urls of occupied origins skipped in one round before all of them
+        // return to the queue and the origins are checked again.
+        const MAX_SKIPPED: usize = 1024;
+        // The origins found occupied in this round, only used with round robin.
+        let mut occupied_origins: HashSet<AtraUrlOrigin> = HashSet::new();
+        // The skipped urls of the occupied origins, they return to the queue when dropped.
+        let mut skipped: Vec<UrlQueueElementRef<UrlWithDepth>> = Vec::new();
         let mut waiter: Option<Receiver<EnqueueCalled>> = None;
         let mut missed = 0;
         let max_miss = max_miss.unwrap_or(u64::MAX);
@@ -81,7 +105,7 @@ where
             None
         }
 
-        let result = loop {
+        let mut result = loop {
             if queue.is_empty().await && !queue.has_floating_urls() {
                 break UrlQueuePollResult::Abort(AbortCause::QueueIsEmpty);
             }
@@ -104,6 +128,45 @@ where
                         continue;
                     }
 
+                    // The blacklist is shared with its manager, the current version applies
+                    // to the urls enqueued before a pattern was added.
+                    if blacklist.version() > 0
+                        && blacklist.has_match_for(&entry.target.try_as_str())
+                    {
+                        log::debug!("Drop {:?} from queue, it is blacklisted.", entry.target);
+                        entry.drop_from_queue();
+                        continue;
+                    }
+
+                    if let Some(waves) = waves {
+                        if waves.is_beyond(&entry.target) {
+                            log::trace!("Hold {:?} back for a later wave.", entry.target);
+                            // Returned to the queue if it can not be held.
+                            if let Err(err) = waves.hold(&entry).await {
+                                break UrlQueuePollResult::Err(QueueExtractionError::QueueError(
+                                    err,
+                                ));
+                            }
+                            entry.drop_from_queue();
+                            continue;
+                        }
+                    }
+
+                    if round_robin {
+                        if let Some(origin) = entry.target.atra_origin() {
+                            if occupied_origins.contains(&origin) {
+                                if skipped.len() < MAX_SKIPPED {
+                                    skipped.push(entry);
+                                    continue;
+                                }
+                                // Every url in sight is occupied, start the next round.
+                                missed += 1;
+                                skipped.clear();
+                                occupied_origins.clear();
+                            }
+                        }
+                    }
+
                     match manager.get_link_state(&entry.target).await {
                         Ok(Some(found)) => {
                             if drop_from_queue(self, &entry, &found).await {
@@ -111,6 +174,14 @@ where
                                 entry.drop_from_queue();
                                 continue;
                             }
+                            if let Some(not_before) = not_due_until(&found) {
+                                log::trace!("Defer {:?} until {not_before}.", entry.target);
+                                next_due =
+                                    Some(next_due.map_or(not_before, |due| due.min(not_before)));
+                                missed += 1;
+                                deferred.push(entry);
+                                continue;
+                            }
                         }
                         Err(err) => {
                             break UrlQueuePollResult::Err(QueueExtractionError::LinkState(err));
@@ -129,9 +200,15 @@ where
                         Err(GuardianError::NoOriginError(_)) => {
                             break UrlQueuePollResult::Abort(AbortCause::NoHost(entry.take()))
                         }
-                        Err(GuardianError::AlreadyOccupied(_)) => {
-                            missed += 1;
-                            missed_host_cache.push(entry);
+                        Err(GuardianError::AlreadyOccupied(origin)) => {
+                            // With round robin a miss is a round without a free origin.
+                            if round_robin {
+                                occupied_origins.insert(origin);
+                                skipped.push(entry);
+                            } else {
+                                missed += 1;
+                                missed_host_cache.push(entry);
+                            }
                         }
                     }
                 }
@@ -140,6 +217,25 @@ where
                         let guard_changes =
                             waiter.get_or_insert_with(|| queue.subscribe_to_change());
 
+                        let mut wait_for = Duration::from_millis(1_000);
+                        if !skipped.is_empty() {
+                            // Returning the skipped urls notifies the subscribers, ignore that
+                            // change. Their origins are checked again after waiting.
+                            missed += 1;
+                            skipped.clear();
+                            occupied_origins.clear();
+                            guard_changes.borrow_and_update();
+                        }
+                        if !deferred.is_empty() {
+                            // Returning the deferred urls notifies the subscribers, ignore that change.
+                            deferred.clear();
+                            guard_changes.borrow_and_update();
+                            if let Some(due) = next_due.take() {
+                                let until_due = due - OffsetDateTime::now_utc();
+                                wait_for = wait_for.min(until_due.try_into().unwrap_or_default());
+                            }
+                        }
+
                         let result = select! {
                             _ = guard_changes.changed() => {
                                 ContinueOrStop::Continue(false)
@@ -149,7 +245,7 @@ where
                                     UrlQueuePollResult::Abort(AbortCause::Shutdown)
                                 )
                             }
-                            _ = tokio::time::sleep_until(Instant::now() + Duration::from_millis(1_000)) => {
+                            _ = tokio::time::sleep_until(Instant::now() + wait_for) => {
                                 ContinueOrStop::Continue(true)
                             }
                         };
@@ -169,14 +265,36 @@ where
         if !result.is_ok() && !missed_host_cache.is_empty() {
             if let Some(result2) = process_entries(guardian, &mut missed_host_cache).await {
                 if result2.is_ok() {
-                    return result2;
+                    result = result2;
                 }
             }
         }
+        if let (Some(waves), UrlQueuePollResult::Ok(guard)) = (waves, &result) {
+            waves.record_served(guard.seed_url());
+        }
         return result;
     }
 }
 
+/// Returns the point in time when a throttled url, an url waiting for the retry of a failed
+/// fetch or a seed waiting for the re-discovery of its origin is due, None if it can be
+/// crawled now.
+fn not_due_until(state: &impl LinkStateLike) -> Option<OffsetDateTime> {
+    if state.kind() != LinkStateKind::Discovered {
+        return None;
+    }
+    let payload = state.payload()?;
+    let now = OffsetDateTime::now_utc();
+    if let Some(retry) = ThrottledRetry::from_payload(payload) {
+        return (!retry.is_due_at(now)).then_some(retry.not_before);
+    }
+    if let Some(retry) = RediscoveryRetry::from_payload(payload) {
+        return (!retry.is_due_at(now)).then_some(retry.not_before);
+    }
+    let not_before = FetchFailureKind::not_before_from_payload(payload)?;
+    (now < not_before).then_some(not_before)
+}
+
 async fn drop_from_queue<C: SupportsConfigs>(
     context: &C,
     entry: &UrlQueueElement,
@@ -186,7 +304,7 @@ async fn drop_from_queue<C: SupportsConfigs>(
         LinkStateKind::Discovered => false,
         LinkStateKind::ProcessedAndStored => {
             let budget = if let Some(origin) = entry.target.atra_origin() {
-                context.configs().crawl.budget.get_budget_for(&origin)
+                context.configs().crawl.budget_for(&origin)
             } else {
                 &context.configs().crawl.budget.default
             };
@@ -205,24 +323,33 @@ async fn drop_from_queue<C: SupportsConfigs>(
 
 #[cfg(test)]
 mod test {
-    use crate::config::crawl::CrawlBudget;
+    use crate::blacklist::BlacklistManager;
+    use crate::config::crawl::{CrawlBudget, QueueFairness};
     use crate::config::{Config, CrawlConfig, PathsConfig, SessionConfig, SystemConfig};
     use crate::contexts::traits::{
-        SupportsConfigs, SupportsLinkState, SupportsPolling, SupportsUrlGuarding, SupportsUrlQueue,
+        SupportsBlackList, SupportsConfigs, SupportsLinkState, SupportsPolling,
+        SupportsUrlGuarding, SupportsUrlQueue,
     };
     use crate::contexts::BaseContext;
-    use crate::queue::{QueueExtractionError, UrlQueue, UrlQueueElement, UrlQueuePollResult};
-    use crate::test_impls::{InMemoryLinkStateManager, TestUrlQueue};
+    use crate::fetching::ThrottledRetry;
+    use crate::link_state::{LinkStateKind, LinkStateManager};
+    use crate::queue::{
+        AbortCause, QueueExtractionError, UrlQueue, UrlQueueElement, UrlQueuePollResult,
+    };
+    use crate::test_impls::{InMemoryLinkStateManager, TestBlacklistManager, TestUrlQueue};
     use crate::url::guard::{InMemoryUrlGuardian, UrlGuardian};
     use crate::url::UrlWithDepth;
     use std::sync::Arc;
     use std::time::Duration;
+    use time::OffsetDateTime;
+    use tokio::time::Instant;
 
     struct Fake {
         queue: TestUrlQueue,
         configs: Config,
         guard: InMemoryUrlGuardian,
         link_state_manager: InMemoryLinkStateManager,
+        blacklist_manager: TestBlacklistManager,
     }
 
     impl Fake {
@@ -232,6 +359,7 @@ mod test {
                 configs,
                 guard: InMemoryUrlGuardian::new(),
                 link_state_manager: InMemoryLinkStateManager::new(),
+                blacklist_manager: TestBlacklistManager::new(None),
             }
         }
     }
@@ -271,6 +399,13 @@ mod test {
         }
     }
 
+    impl SupportsBlackList for Fake {
+        type BlacklistManager = TestBlacklistManager;
+        fn get_blacklist_manager(&self) -> &Self::BlacklistManager {
+            &self.blacklist_manager
+        }
+    }
+
     fn create_configs(max_queue_age: Option<u32>, budget_setting: Option<CrawlBudget>) -> Config {
         let mut cfg = CrawlConfig::default();
         if let Some(max_queue_age) = max_queue_age {
@@ -365,7 +500,7 @@ mod test {
                     }
                 },
             }
-            inp.changed().await.unwrap();
+            inp.recv().await.unwrap();
             match fake2.poll_next_free_url_no_shutdown(None).await {
                 UrlQueuePollResult::Ok(ok) => {
                     assert_eq!("https://www.test2.de/uniform", ok.seed_url().try_as_str());
@@ -399,4 +534,155 @@ mod test {
         let result = result.await;
         println!("{:?}", result)
     }
+
+    #[tokio::test]
+    async fn throttled_urls_are_deferred_until_due() {
+        let fake = Fake::new(create_configs(None, None));
+        let throttled = UrlWithDepth::from_url("https://www.test1.de/throttled").unwrap();
+        let not_before = OffsetDateTime::now_utc() + time::Duration::seconds(2);
+        fake.link_state_manager
+            .update_link_state_no_meta(
+                &throttled,
+                LinkStateKind::Discovered,
+                Some(Some(&ThrottledRetry::new(1, not_before).to_payload())),
+            )
+            .await
+            .unwrap();
+        fake.queue
+            .enqueue_all([
+                UrlQueueElement::new(false, 0, false, throttled),
+                UrlQueueElement::new(
+                    true,
+                    0,
+                    false,
+                    UrlWithDepth::from_url("https://www.test2.de").unwrap(),
+                ),
+            ])
+            .await
+            .unwrap();
+
+        let started = Instant::now();
+        let first = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!("https://www.test2.de/", first.seed_url().try_as_str());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(1, fake.queue.len().await);
+
+        let second = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!(
+            "https://www.test1.de/throttled",
+            second.seed_url().try_as_str()
+        );
+        assert!(OffsetDateTime::now_utc() >= not_before);
+    }
+
+    #[tokio::test]
+    async fn blacklisted_urls_are_dropped_from_the_queue() {
+        let fake = Fake::new(create_configs(None, None));
+        fake.queue
+            .enqueue_all([
+                UrlQueueElement::new(
+                    true,
+                    0,
+                    false,
+                    UrlWithDepth::from_url("https://www.test1.de").unwrap(),
+                ),
+                UrlQueueElement::new(
+                    true,
+                    0,
+                    false,
+                    UrlWithDepth::from_url("https://www.test2.de").unwrap(),
+                ),
+            ])
+            .await
+            .unwrap();
+        fake.blacklist_manager
+            .add("test1\\.de".to_string())
+            .await
+            .unwrap();
+
+        let next = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!("https://www.test2.de/", next.seed_url().try_as_str());
+        assert_eq!(0, fake.queue.len().await);
+    }
+
+    #[tokio::test]
+    async fn round_robin_serves_the_free_origins_during_a_long_reservation() {
+        async fn fill(fake: &Fake) {
+            let busy = (0..50).map(|i| format!("https://www.test1.de/{i}"));
+            let free = (0..3).map(|i| format!("https://www.test2.de/{i}"));
+            fake.queue
+                .enqueue_all(busy.chain(free).map(|url| {
+                    UrlQueueElement::new(false, 0, false, UrlWithDepth::from_url(&url).unwrap())
+                }))
+                .await
+                .unwrap();
+        }
+
+        let fifo = Fake::new(create_configs(None, None));
+        fill(&fifo).await;
+        let held = fifo.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!("https://www.test1.de/0", held.seed_url().try_as_str());
+        assert!(matches!(
+            fifo.poll_next_free_url_no_shutdown(Some(1)).await,
+            UrlQueuePollResult::Abort(AbortCause::TooManyMisses)
+        ));
+
+        let mut cfg = create_configs(None, None);
+        cfg.crawl.queue_fairness = QueueFairness::OriginRoundRobin;
+        let fake = Fake::new(cfg);
+        fill(&fake).await;
+        let held = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!("https://www.test1.de/0", held.seed_url().try_as_str());
+        for i in 0..3 {
+            let next = fake.poll_next_free_url_no_shutdown(Some(1)).await.unwrap();
+            assert_eq!(
+                format!("https://www.test2.de/{i}"),
+                next.seed_url().try_as_str()
+            );
+        }
+        // The urls of the reserved origin are still waiting in the queue.
+        assert_eq!(49, fake.queue.len().await);
+        drop(held);
+        let next = fake.poll_next_free_url_no_shutdown(Some(1)).await.unwrap();
+        assert!(next
+            .seed_url()
+            .try_as_str()
+            .starts_with("https://www.test1.de/"));
+    }
+
+    #[tokio::test]
+    async fn round_robin_counts_a_miss_per_round_if_all_origins_are_occupied() {
+        let mut cfg = create_configs(None, None);
+        cfg.crawl.queue_fairness = QueueFairness::OriginRoundRobin;
+        let fake = Fake::new(cfg);
+        fake.queue
+            .enqueue_all((0..3).flat_map(|i| {
+                [
+                    format!("https://www.test1.de/{i}"),
+                    format!("https://www.test2.de/{i}"),
+                ]
+                .map(|url| {
+                    UrlQueueElement::new(false, 0, false, UrlWithDepth::from_url(&url).unwrap())
+                })
+            }))
+            .await
+            .unwrap();
+        let held1 = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        let held2 = fake.poll_next_free_url_no_shutdown(None).await.unwrap();
+        assert_eq!("https://www.test1.de/0", held1.seed_url().try_as_str());
+        assert_eq!("https://www.test2.de/0", held2.seed_url().try_as_str());
+
+        // Two rounds over the four urls of the occupied origins are needed to exceed one miss.
+        let started = Instant::now();
+        assert!(matches!(
+            fake.poll_next_free_url_no_shutdown(Some(1)).await,
+            UrlQueuePollResult::Abort(AbortCause::TooManyMisses)
+        ));
+        assert!(started.elapsed() >= Duration::from_secs(2));
+        assert_eq!(4, fake.queue.len().await);
+
+        drop(held2);
+        let next = fake.poll_next_free_url_no_shutdown(Some(1)).await.unwrap();
+        assert_eq!("https://www.test2.de/1", next.seed_url().try_as_str());
+    }
 }
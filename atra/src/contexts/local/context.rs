@@ -12,36 +12,51 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::blacklist::{InMemoryBlacklistManager, PolyBlackList};
-use crate::client::{build_classic_client, ClientWithUserAgent};
+use crate::blacklist::{
+    Allowlist, BlacklistManager, InMemoryBlacklistManager, ManagedBlacklist, PolyBlackList,
+};
+use crate::client::bandwidth::BandwidthLimiter;
+use crate::client::proxy::ProxyRouter;
+use crate::client::{build_classic_client, build_recording_client, ClientWithUserAgent};
 use crate::config::configs::Config;
 use crate::contexts::local::errors::LinkHandlingError;
 use crate::contexts::local::LocalContextInitError;
 use crate::contexts::traits::*;
 use crate::contexts::BaseContext;
-use crate::crawl::db::CrawlDB;
-use crate::crawl::{CrawlTask, SlimCrawlResult};
+use crate::crawl::db::{CrawlDB, PayloadDigestDB};
+use crate::crawl::{
+    Baseline, CrawlEvent, CrawlEvents, CrawlLimits, CrawlTask, SlimCrawlResult, WaveFrontier,
+};
 use crate::database::open_db;
 use crate::database::DatabaseError;
+use crate::extraction::marker::ExtractorMethodHint;
 use crate::extraction::ExtractedLink;
 use crate::gdbr::identifier::{GdbrIdentifierRegistry, InitHelper};
 use crate::io::fs::FileSystemAccess;
 use crate::link_state::{
-    DatabaseLinkStateManager, IsSeedYesNo, LinkStateKind, LinkStateManager, LinkStateRockDB,
-    RecrawlYesNo,
+    DatabaseLinkStateManager, IsSeedYesNo, LinkStateManager, LinkStateRockDB, RecrawlYesNo,
 };
-use crate::queue::{RawAgingQueueFile, UrlQueue, UrlQueueElement, UrlQueueWrapper};
+use crate::origin_metadata::OriginMetadataDatabaseManager;
+use crate::queue::priority::UrlPriorityScorer;
+use crate::queue::{DequeueJournal, RawAgingQueueFile, UrlQueue, UrlQueueElement, UrlQueueWrapper};
 use crate::recrawl_management::DomainLastCrawledDatabaseManager;
-use crate::robots::OffMemoryRobotsManager;
+use crate::robots::{OffMemoryRobotsManager, RobotsPolicy};
 use crate::runtime::{GracefulShutdownGuard, GracefulShutdownWithGuard, RuntimeContext};
 use crate::seed::BasicSeed;
-use crate::url::guard::InMemoryUrlGuardian;
+use crate::tls_info::TlsInfoDatabaseManager;
+use crate::unique_content::UniqueContentDatabaseManager;
+use crate::url::filter::{UrlFilter, UrlFilterChain};
+use crate::url::guard::{GuardianEvents, InMemoryUrlGuardian};
+use crate::url::normalization::UrlNormalizer;
+use crate::url::traps::TrapDetector;
 use crate::url::{AtraOriginProvider, UrlWithDepth};
+use crate::warc_ext::{WarcInfo, WarcSkipInstruction};
 use crate::web_graph::{QueuingWebGraphManager, WebGraphEntry, WebGraphManager};
 use liblinear::solver::L2R_L2LOSS_SVR;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
 use rocksdb::DB;
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::fs::File;
 use std::io::BufWriter;
@@ -59,10 +74,13 @@ pub struct LocalContext {
     _db: Arc<DB>,
     file_provider: Arc<FileSystemAccess>,
     url_queue: UrlQueueWrapper<RawAgingQueueFile>,
+    dequeue_journal: DequeueJournal,
     link_state_manager: DatabaseLinkStateManager<LinkStateRockDB>,
     blacklist: InMemoryBlacklistManager<PolyBlackList>,
+    allowlist: Allowlist,
     robots: OffMemoryRobotsManager,
     crawled_data: CrawlDB,
+    payload_digests: Option<PayloadDigestDB>,
     host_manager: InMemoryUrlGuardian,
     configs: Config,
     web_graph_manager: Option<Arc<QueuingWebGraphManager>>,
@@ -70,6 +88,18 @@ pub struct LocalContext {
     stop_word_registry: Option<StopWordRegistry>,
     gdbr_filer_registry: Option<GdbrIdentifierRegistry<Tf, Idf, L2R_L2LOSS_SVR>>,
     domain_manager: DomainLastCrawledDatabaseManager,
+    unique_content_manager: UniqueContentDatabaseManager,
+    tls_info_manager: TlsInfoDatabaseManager,
+    origin_metadata_manager: OriginMetadataDatabaseManager,
+    url_normalizer: UrlNormalizer,
+    trap_detector: Option<TrapDetector>,
+    url_filter: UrlFilterChain,
+    crawl_events: CrawlEvents,
+    crawl_limits: CrawlLimits,
+    baseline: Option<Baseline>,
+    url_priority: UrlPriorityScorer,
+    wave_frontier: Option<WaveFrontier>,
+    bandwidth: Option<Arc<BandwidthLimiter>>,
     _guard: GracefulShutdownGuard,
 }
 
@@ -81,9 +111,10 @@ impl LocalContext {
 
     /// Creates the state for Atra.
     pub fn new(
-        configs: Config,
+        mut configs: Config,
         runtime_context: &RuntimeContext,
     ) -> Result<Self, LocalContextInitError> {
+        configs.crawl.resolve_profiles()?;
         let output_path = configs.paths.root_path();
         if !output_path.exists() {
             std::fs::create_dir_all(output_path)?;
@@ -101,23 +132,40 @@ impl LocalContext {
         )?;
 
         log::info!("Init file system.");
-        let file_provider = Arc::new(FileSystemAccess::new(
-            configs.session.service.clone(),
-            configs.session.collection.clone(),
-            configs.session.crawl_job_id,
-            configs.paths.root_path().to_path_buf(),
-            configs.paths.dir_big_files(),
-        )?);
+        let file_provider = Arc::new(
+            FileSystemAccess::new(
+                configs.session.service.clone(),
+                configs.session.collection.clone(),
+                configs.session.crawl_job_id,
+                configs.paths.root_path().to_path_buf(),
+                configs.paths.dir_big_files(),
+                configs.session.dat_file_layout,
+            )?
+            .with_warc_info(WarcInfo::new(&configs)),
+        );
 
         log::info!("Init internal database.");
         let db = Arc::new(open_db(configs.paths.dir_database())?);
 
         log::info!("Init link states database.");
-        let link_state_manager = DatabaseLinkStateManager::new(db.clone());
+        let mut link_state_manager =
+            DatabaseLinkStateManager::with_cache(db.clone(), configs.system.link_state_cache);
+        if let Some(ref seen_filter) = configs.system.seen_filter {
+            log::info!("Init seen filter.");
+            link_state_manager = link_state_manager.with_seen_filter(seen_filter);
+        }
         log::info!("Init crawled information database.");
         let crawled_data = CrawlDB::new(db.clone(), &configs)?;
+        let payload_digests = configs
+            .crawl
+            .deduplicate_by_digest
+            .then(|| PayloadDigestDB::new(db.clone()));
         log::info!("Init robots manager.");
-        let robots = OffMemoryRobotsManager::new(db.clone(), configs.system.robots_cache_size);
+        let robots = OffMemoryRobotsManager::new(
+            db.clone(),
+            configs.system.robots_cache_size,
+            RobotsPolicy::new(&configs.crawl),
+        );
         log::info!("Init web graph writer.");
 
         let web_graph_manager = configs
@@ -142,11 +190,13 @@ impl LocalContext {
             .transpose()?;
         log::info!("Init url queue.");
         let url_queue = UrlQueueWrapper::open(configs.paths.file_queue())?;
+        let dequeue_journal = DequeueJournal::open(configs.paths.file_dequeue_journal())?;
         log::info!("Init blacklist manager.");
         let blacklist = InMemoryBlacklistManager::open(
             configs.paths.file_blacklist(),
             runtime_context.shutdown_guard().clone(),
         )?;
+        let allowlist = Allowlist::new(&configs.crawl.allowlist)?;
 
         let gdbr_filer_registry = if let Some(ref cfg) = configs.crawl.gbdr {
             let helper = InitHelper {
@@ -161,30 +211,138 @@ impl LocalContext {
         };
 
         let domain_manager = DomainLastCrawledDatabaseManager::new(db.clone());
+        log::info!("Init unique content counters.");
+        let unique_content_manager = UniqueContentDatabaseManager::new(db.clone())?;
+        let tls_info_manager = TlsInfoDatabaseManager::new(db.clone())?;
+        let origin_metadata_manager = OriginMetadataDatabaseManager::new(db.clone())?;
+
+        let url_normalizer = UrlNormalizer::new(configs.crawl.url_normalization.clone());
+        let trap_detector = configs.crawl.trap_detection.clone().map(TrapDetector::new);
+        let url_filter = UrlFilterChain::new(&configs.crawl.url_filters);
+        let url_priority = UrlPriorityScorer::new(&configs.crawl.url_priority);
+        let wave_frontier = configs
+            .crawl
+            .wave_mode
+            .then(|| WaveFrontier::open(configs.paths.file_wave_frontier()))
+            .transpose()?;
+        let bandwidth = BandwidthLimiter::from_config(&configs.crawl.bandwidth).map(Arc::new);
+        let crawl_limits = CrawlLimits::new(
+            &configs.crawl,
+            Some(runtime_context.shutdown_guard().get().child().clone()),
+        );
+        let baseline = if let Some(ref path) = configs.system.baseline {
+            log::info!("Init baseline.");
+            Some(Baseline::open(path, &configs)?)
+        } else {
+            None
+        };
 
         Ok(LocalContext {
             _db: db,
             url_queue,
+            dequeue_journal,
             link_state_manager,
             blacklist,
+            allowlist,
             file_provider,
             crawled_data,
+            payload_digests,
             robots,
+            host_manager: InMemoryUrlGuardian::with_max_parallel(
+                configs.crawl.max_parallel_per_origin,
+            ),
             configs,
-            host_manager: InMemoryUrlGuardian::default(),
             started_at: OffsetDateTime::now_utc(),
             ct_discovered_websites: AtomicUsize::new(0),
             web_graph_manager,
             stop_word_registry,
             gdbr_filer_registry,
             domain_manager,
+            unique_content_manager,
+            tls_info_manager,
+            origin_metadata_manager,
+            url_normalizer,
+            trap_detector,
+            url_filter,
+            crawl_events: CrawlEvents::new(),
+            crawl_limits,
+            baseline,
+            url_priority,
+            wave_frontier,
+            bandwidth,
             _guard: runtime_context.shutdown_guard().guard(),
         })
     }
 
+    /// Replaces the events emitted to by the workers of this context.
+    pub fn with_crawl_events(self, crawl_events: CrawlEvents) -> Self {
+        Self {
+            crawl_events,
+            ..self
+        }
+    }
+
+    /// Records the [digest] of the seeds in the warcinfo records of the warc files.
+    /// Must be called before any worker is created.
+    pub fn with_seeds_digest(mut self, digest: String) -> Self {
+        match Arc::get_mut(&mut self.file_provider) {
+            Some(fs) => fs.set_seeds_digest(digest),
+            None => log::warn!("The warc files are already in use, the seeds are not recorded."),
+        }
+        self
+    }
+
+    /// Appends [filters] to the configured url filters of this context.
+    pub fn with_url_filters(
+        mut self,
+        filters: impl IntoIterator<Item = Arc<dyn UrlFilter>>,
+    ) -> Self {
+        for filter in filters {
+            self.url_filter.push(filter);
+        }
+        self
+    }
+
+    /// Replaces the events emitted to by the guardian of this context.
+    /// Must be called before any origin is reserved.
+    pub fn with_guardian_events(self, guardian_events: GuardianEvents) -> Self {
+        Self {
+            host_manager: InMemoryUrlGuardian::with_events(
+                self.configs.crawl.max_parallel_per_origin,
+                guardian_events,
+            ),
+            ..self
+        }
+    }
+
     pub fn crawl_db(&self) -> &CrawlDB {
         &self.crawled_data
     }
+
+    /// Returns true if the trap detection drops the found [url].
+    fn is_trap(&self, url: &UrlWithDepth) -> bool {
+        self.trap_detector
+            .as_ref()
+            .is_some_and(|detector| detector.check(url).is_some())
+    }
+
+    /// Returns the score of the found [url], None if the url filters reject it.
+    fn url_filter_score(&self, url: &UrlWithDepth, hint: &ExtractorMethodHint) -> Option<i8> {
+        let score = self.url_filter.decide(url, Some(hint.used_method));
+        if score.is_none() {
+            self.crawl_events.statistics().record_filtered(url);
+        }
+        score
+    }
+
+    /// Returns the blacklist if the found urls have to be admitted by the allowlist.
+    async fn allowlist_guard(&self) -> Option<ManagedBlacklist<PolyBlackList>> {
+        if self.allowlist.is_empty() {
+            None
+        } else {
+            Some(self.blacklist.get_blacklist().await)
+        }
+    }
 }
 
 unsafe impl Send for LocalContext {}
@@ -207,6 +365,30 @@ impl SupportsDomainHandling for LocalContext {
     }
 }
 
+impl SupportsUniqueContent for LocalContext {
+    type UniqueContentManager = UniqueContentDatabaseManager;
+
+    fn get_unique_content_manager(&self) -> &Self::UniqueContentManager {
+        &self.unique_content_manager
+    }
+}
+
+impl SupportsTlsInfo for LocalContext {
+    type TlsInfoManager = TlsInfoDatabaseManager;
+
+    fn get_tls_info_manager(&self) -> &Self::TlsInfoManager {
+        &self.tls_info_manager
+    }
+}
+
+impl SupportsOriginMetadata for LocalContext {
+    type OriginMetadataManager = OriginMetadataDatabaseManager;
+
+    fn get_origin_metadata_manager(&self) -> &Self::OriginMetadataManager {
+        &self.origin_metadata_manager
+    }
+}
+
 impl SupportsLinkSeeding for LocalContext {
     type Error = LinkHandlingError;
 
@@ -221,61 +403,140 @@ impl SupportsLinkSeeding for LocalContext {
         &self,
         from: &UrlWithDepth,
         links: &HashSet<ExtractedLink>,
-    ) -> Result<Vec<UrlWithDepth>, LinkHandlingError> {
+    ) -> Result<Vec<(UrlWithDepth, i8)>, LinkHandlingError> {
         let mut for_queue = Vec::with_capacity(links.len() / 2);
         let mut for_insert = Vec::with_capacity(links.len() / 2);
+        let allowlist_guard = self.allowlist_guard().await;
+        let is_denied = |url: &UrlWithDepth| {
+            allowlist_guard
+                .as_ref()
+                .is_some_and(|blacklist| !self.allowlist.admits(blacklist, url))
+        };
         for link in links {
             match link {
-                ExtractedLink::OnSeed { url, .. } => {
+                ExtractedLink::OnSeed {
+                    url,
+                    extraction_method,
+                } => {
+                    let url = self.url_normalizer.normalized(url);
                     if let Some(ref manager) = self.web_graph_manager {
-                        manager.add(WebGraphEntry::create_link(from, url)).await?;
+                        manager
+                            .add(WebGraphEntry::create_link(from, &url, extraction_method))
+                            .await?;
+                    }
+                    if is_denied(&url) || self.is_trap(&url) {
+                        continue;
                     }
-                    for_insert.push(url.clone());
+                    let Some(score) = self.url_filter_score(&url, extraction_method) else {
+                        continue;
+                    };
+                    for_insert.push((url, score));
                 }
-                ExtractedLink::Outgoing { url, .. } => {
+                ExtractedLink::Outgoing {
+                    url,
+                    extraction_method,
+                } => {
+                    let url = &self.url_normalizer.normalized(url);
                     if let Some(ref manager) = self.web_graph_manager {
-                        manager.add(WebGraphEntry::create_link(from, url)).await?;
-                    }
-                    if self.link_state_manager.get_link_state(url).await?.is_none() {
-                        let recrawl: Option<RecrawlYesNo> = if let Some(origin) = url.atra_origin()
-                        {
-                            let budget = self.configs.crawl.budget.get_budget_for(&origin);
-                            if budget.is_in_budget(url) {
-                                for_queue.push(UrlQueueElement::new(false, 0, false, url.clone()));
-                            }
-                            Some(budget.get_recrawl_interval().is_some().into())
-                        } else {
-                            None
-                        };
-
-                        self.link_state_manager
-                            .update_link_state_no_payload(
-                                url,
-                                LinkStateKind::Discovered,
-                                Some(IsSeedYesNo::No),
-                                recrawl,
-                            )
+                        manager
+                            .add(WebGraphEntry::create_link(from, url, extraction_method))
                             .await?;
                     }
+                    if is_denied(url) || self.is_trap(url) {
+                        continue;
+                    }
+                    let Some(score) = self.url_filter_score(url, extraction_method) else {
+                        continue;
+                    };
+                    let origin = url.atra_origin();
+                    let budget = origin
+                        .as_ref()
+                        .map(|origin| self.configs.crawl.budget_for(origin));
+                    let recrawl: Option<RecrawlYesNo> =
+                        budget.map(|budget| budget.get_recrawl_interval().is_some().into());
+                    let is_new = self
+                        .link_state_manager
+                        .discover_link_state(url, Some(IsSeedYesNo::No), recrawl)
+                        .await?;
+                    if is_new && budget.is_some_and(|budget| budget.is_in_budget(url)) {
+                        let priority = self
+                            .url_priority
+                            .score(url, origin.as_ref(), false)
+                            .saturating_add(score);
+                        for_queue.push(
+                            UrlQueueElement::new(false, 0, false, url.clone())
+                                .with_priority(priority),
+                        );
+                    }
                 }
                 ExtractedLink::Data { .. } => {
-                    // let parsed = data_url::DataUrl::process(&url.url.as_str())?;
-                    //
-                    // /// TODO: this is expensive. But mime does not provide a better API
-                    // let mime_type = MimeType::new_single(parsed.mime_type().to_string().parse()?);
-
-                    // todo data-urls: How to handle?
-                    log::warn!("data-urls are at the moment unsupported.")
+                    // Stored by the crawler, a data url is never queued.
                 }
             }
         }
         self.ct_discovered_websites
             .fetch_add(for_queue.len() + for_insert.len(), Ordering::Relaxed);
         if !for_queue.is_empty() {
+            for element in &for_queue {
+                self.crawl_events.emit(CrawlEvent::Queued {
+                    url: element.target.clone(),
+                });
+            }
             self.url_queue.enqueue_all(for_queue).await?;
         }
         Ok(for_insert)
     }
+
+    async fn register_sitemap_links(
+        &self,
+        sitemap: &UrlWithDepth,
+        listed: &[UrlWithDepth],
+    ) -> Result<(), LinkHandlingError> {
+        if let Some(ref manager) = self.web_graph_manager {
+            for url in listed {
+                manager
+                    .add(WebGraphEntry::create_sitemap_link(sitemap, url))
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SupportsUrlNormalization for LocalContext {
+    fn url_normalizer(&self) -> &UrlNormalizer {
+        &self.url_normalizer
+    }
+}
+
+impl SupportsCrawlEvents for LocalContext {
+    fn crawl_events(&self) -> &CrawlEvents {
+        &self.crawl_events
+    }
+}
+
+impl SupportsCrawlLimits for LocalContext {
+    fn crawl_limits(&self) -> &CrawlLimits {
+        &self.crawl_limits
+    }
+}
+
+impl SupportsBaseline for LocalContext {
+    fn baseline(&self) -> Option<&Baseline> {
+        self.baseline.as_ref()
+    }
+}
+
+impl SupportsUrlFilter for LocalContext {
+    fn url_filter(&self) -> &UrlFilterChain {
+        &self.url_filter
+    }
+}
+
+impl SupportsUrlPriority for LocalContext {
+    fn url_priority(&self) -> &UrlPriorityScorer {
+        &self.url_priority
+    }
 }
 
 impl SupportsLinkState for LocalContext {
@@ -335,6 +596,14 @@ impl SupportsUrlQueue for LocalContext {
     fn url_queue(&self) -> &Self::UrlQueue {
         &self.url_queue
     }
+
+    fn dequeue_journal(&self) -> Option<&DequeueJournal> {
+        Some(&self.dequeue_journal)
+    }
+
+    fn wave_frontier(&self) -> Option<&WaveFrontier> {
+        self.wave_frontier.as_ref()
+    }
 }
 
 impl SupportsGdbrRegistry for LocalContext {
@@ -379,6 +648,27 @@ impl SupportsSlimCrawlResults for LocalContext {
             pipe => pipe,
         }
     }
+
+    async fn retrieve_stored_payload(
+        &self,
+        digest: &[u8],
+    ) -> Result<Option<WarcSkipInstruction>, DatabaseError> {
+        match self.payload_digests {
+            Some(ref payload_digests) => payload_digests.get(digest),
+            None => Ok(None),
+        }
+    }
+
+    async fn register_stored_payload(
+        &self,
+        digest: &[u8],
+        instruction: &WarcSkipInstruction,
+    ) -> Result<(), DatabaseError> {
+        match self.payload_digests {
+            Some(ref payload_digests) => payload_digests.add(digest, instruction),
+            None => Ok(()),
+        }
+    }
 }
 
 impl SupportsCrawling for LocalContext {
@@ -390,11 +680,25 @@ impl SupportsCrawling for LocalContext {
         S: BasicSeed,
     {
         let useragent = self.configs.crawl.user_agent.get_user_agent().to_string();
-        let client = build_classic_client(self, &seed, &useragent)?;
-        let client = ClientWithUserAgent::new(useragent, client);
+        let proxies = Arc::new(ProxyRouter::from_config(&self.configs.crawl));
+        let client = build_classic_client(self, &seed, &useragent, &proxies)?;
+        let client =
+            ClientWithUserAgent::new(useragent, client, proxies, self.configs.crawl.auth.clone())
+                .with_bandwidth(self.bandwidth.clone())
+                .with_default_headers(
+                    self.configs
+                        .crawl
+                        .headers_for(seed.origin())
+                        .map(Cow::into_owned),
+                )
+                .with_recording(build_recording_client(self, &seed));
         Ok(CrawlTask::new(seed, client))
     }
 
+    fn bandwidth_limiter(&self) -> Option<&BandwidthLimiter> {
+        self.bandwidth.as_deref()
+    }
+
     fn create_crawl_id(&self) -> String {
         let mut result: String = "crawl".to_string();
         result.reserve(15 + 2 + 22);
@@ -13,10 +13,11 @@
 // limitations under the License.
 
 use crate::blacklist::{InMemoryBlacklistManagerInitialisationError, PolyBlackList};
-use crate::database::OpenDBError;
+use crate::config::profiles::ProfileError;
+use crate::database::{DatabaseError, OpenDBError};
 use crate::io::errors::ErrorWithPath;
 use crate::link_state::LinkStateDBError;
-use crate::queue::QueueError;
+use crate::queue::{QueueError, QueueFileError};
 use crate::web_graph::WebGraphError;
 use svm::error::SvmCreationError;
 use text_processing::tf_idf::Idf;
@@ -50,11 +51,17 @@ pub enum LocalContextInitError {
     #[error(transparent)]
     RocksDB(#[from] rocksdb::Error),
     #[error(transparent)]
-    QueueFile(#[from] queue_file::Error),
+    QueueFile(#[from] QueueFileError),
     #[error(transparent)]
     BlackList(#[from] InMemoryBlacklistManagerInitialisationError<PolyBlackList>),
     #[error(transparent)]
+    Allowlist(#[from] regex::Error),
+    #[error(transparent)]
     Svm(#[from] SvmCreationError<Idf>),
     #[error(transparent)]
     WebGraph(#[from] WebGraphError),
+    #[error(transparent)]
+    Profile(#[from] ProfileError),
+    #[error(transparent)]
+    Database(#[from] DatabaseError),
 }
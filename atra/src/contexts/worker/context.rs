@@ -12,19 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::client::bandwidth::BandwidthLimiter;
 use crate::config::Config;
 use crate::contexts::traits::*;
 use crate::contexts::worker::error::CrawlWriteError;
 use crate::crawl::StoredDataHint;
-use crate::crawl::{CrawlResult, CrawlTask, SlimCrawlResult};
+use crate::crawl::{
+    Baseline, CrawlEvents, CrawlLimits, CrawlResult, CrawlTask, SlimCrawlResult, WaveFrontier,
+};
 use crate::data::RawVecData;
 use crate::extraction::ExtractedLink;
 use crate::io::errors::ErrorWithPath;
 use crate::io::fs::{AtraFS, WorkerFileSystemAccess};
+use crate::queue::priority::UrlPriorityScorer;
+use crate::queue::DequeueJournal;
 use crate::seed::BasicSeed;
 use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
+use crate::toolkit::digest::labeled_xxh128_digest;
+use crate::url::filter::UrlFilterChain;
+use crate::url::normalization::UrlNormalizer;
 use crate::url::UrlWithDepth;
-use crate::warc_ext::write_warc;
+use crate::warc_ext::{write_warc, WarcSkipInstruction, WriterError};
 use std::collections::HashSet;
 use std::sync::Arc;
 use text_processing::stopword_registry::StopWordRegistry;
@@ -101,7 +109,9 @@ where
         to self.inner {
             async fn register_seed<S: BasicSeed>(&self, seed: &S) -> Result<(), Self::Error>;
 
-            async fn handle_links(&self, from: &UrlWithDepth, links: &HashSet<ExtractedLink>) -> Result<Vec<UrlWithDepth>, Self::Error>;
+            async fn handle_links(&self, from: &UrlWithDepth, links: &HashSet<ExtractedLink>) -> Result<Vec<(UrlWithDepth, i8)>, Self::Error>;
+
+            async fn register_sitemap_links(&self, sitemap: &UrlWithDepth, listed: &[UrlWithDepth]) -> Result<(), Self::Error>;
         }
     }
 }
@@ -193,6 +203,10 @@ where
             async fn can_poll(&self) -> bool;
 
             fn url_queue(&self) -> &Self::UrlQueue;
+
+            fn dequeue_journal(&self) -> Option<&DequeueJournal>;
+
+            fn wave_frontier(&self) -> Option<&WaveFrontier>;
         }
     }
 }
@@ -256,6 +270,10 @@ where
             async fn retrieve_slim_crawled_website(&self, url: &UrlWithDepth) -> Result<Option<SlimCrawlResult>, Self::Error>;
 
             async fn store_slim_crawled_website(&self, result: SlimCrawlResult) -> Result<(), Self::Error>;
+
+            async fn retrieve_stored_payload(&self, digest: &[u8]) -> Result<Option<WarcSkipInstruction>, Self::Error>;
+
+            async fn register_stored_payload(&self, digest: &[u8], instruction: &WarcSkipInstruction) -> Result<(), Self::Error>;
         }
     }
 }
@@ -272,34 +290,181 @@ where
     }
 }
 
+impl<T> SupportsUniqueContent for WorkerContext<T>
+where
+    T: SupportsUniqueContent,
+{
+    type UniqueContentManager = T::UniqueContentManager;
+    delegate::delegate! {
+        to self.inner {
+            fn get_unique_content_manager(&self) -> &Self::UniqueContentManager;
+        }
+    }
+}
+
+impl<T> SupportsTlsInfo for WorkerContext<T>
+where
+    T: SupportsTlsInfo,
+{
+    type TlsInfoManager = T::TlsInfoManager;
+    delegate::delegate! {
+        to self.inner {
+            fn get_tls_info_manager(&self) -> &Self::TlsInfoManager;
+        }
+    }
+}
+
+impl<T> SupportsOriginMetadata for WorkerContext<T>
+where
+    T: SupportsOriginMetadata,
+{
+    type OriginMetadataManager = T::OriginMetadataManager;
+    delegate::delegate! {
+        to self.inner {
+            fn get_origin_metadata_manager(&self) -> &Self::OriginMetadataManager;
+        }
+    }
+}
+
+impl<T> SupportsUrlNormalization for WorkerContext<T>
+where
+    T: SupportsUrlNormalization,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn url_normalizer(&self) -> &UrlNormalizer;
+        }
+    }
+}
+
+impl<T> SupportsUrlFilter for WorkerContext<T>
+where
+    T: SupportsUrlFilter,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn url_filter(&self) -> &UrlFilterChain;
+        }
+    }
+}
+
+impl<T> SupportsCrawlEvents for WorkerContext<T>
+where
+    T: SupportsCrawlEvents,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn crawl_events(&self) -> &CrawlEvents;
+        }
+    }
+}
+
+impl<T> SupportsCrawlLimits for WorkerContext<T>
+where
+    T: SupportsCrawlLimits,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn crawl_limits(&self) -> &CrawlLimits;
+        }
+    }
+}
+
+impl<T> SupportsBaseline for WorkerContext<T>
+where
+    T: SupportsBaseline,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn baseline(&self) -> Option<&Baseline>;
+        }
+    }
+}
+
+impl<T> SupportsUrlPriority for WorkerContext<T>
+where
+    T: SupportsUrlPriority,
+{
+    delegate::delegate! {
+        to self.inner {
+            fn url_priority(&self) -> &UrlPriorityScorer;
+        }
+    }
+}
+
 impl<T> SupportsCrawlResults for WorkerContext<T>
 where
-    T: AsyncContext + SupportsSlimCrawlResults + SupportsConfigs,
+    T: AsyncContext + SupportsSlimCrawlResults + SupportsConfigs + SupportsCrawlEvents,
 {
     type Error = CrawlWriteError<T::Error>;
 
     async fn store_crawled_website(&self, result: &CrawlResult) -> Result<(), Self::Error> {
         let hint = match &result.content {
             RawVecData::None => StoredDataHint::None,
-            RawVecData::InMemory { .. } => {
-                log::debug!("Store in warc: {}", result.meta.url);
-                StoredDataHint::Warc(
-                    self.worker_warc_writer
+            RawVecData::InMemory { data } => {
+                let digest = self
+                    .configs()
+                    .crawl
+                    .deduplicate_by_digest
+                    .then(|| labeled_xxh128_digest(data));
+                let stored = match digest {
+                    Some(ref digest) => self
+                        .inner
+                        .retrieve_stored_payload(digest)
+                        .await
+                        .map_err(CrawlWriteError::SlimError)?,
+                    None => None,
+                };
+                if let Some(instruction) = stored {
+                    log::debug!("Reference the stored body of: {}", result.meta.url);
+                    StoredDataHint::Warc(instruction)
+                } else {
+                    log::debug!("Store in warc: {}", result.meta.url);
+                    let instruction = self
+                        .worker_warc_writer
                         .execute_on_writer(|value| {
                             log::debug!("WARC-Writer start:");
                             write_warc(value, result)
                         })
-                        .await?,
-                )
+                        .await?;
+                    self.crawl_events()
+                        .metrics()
+                        .add_warc_bytes(instruction.octet_count());
+                    if let Some(digest) = digest {
+                        // Other workers read the record without waiting for this writer.
+                        self.worker_warc_writer
+                            .flush()
+                            .await
+                            .map_err(WriterError::from)?;
+                        self.inner
+                            .register_stored_payload(&digest, &instruction)
+                            .await
+                            .map_err(CrawlWriteError::SlimError)?;
+                    }
+                    StoredDataHint::Warc(instruction)
+                }
             }
             RawVecData::ExternalFile { path } => {
                 log::debug!("Store external");
                 if self.configs().crawl.store_big_file_hints_in_warc {
-                    self.worker_warc_writer
+                    let instruction = self
+                        .worker_warc_writer
                         .execute_on_writer(|value| write_warc(value, result))
                         .await?;
+                    self.crawl_events()
+                        .metrics()
+                        .add_warc_bytes(instruction.octet_count());
                 }
                 assert!(path.exists());
+                match (result.content.stored_size(), result.content.size()) {
+                    (Ok(stored), Ok(uncompressed)) => self
+                        .crawl_events()
+                        .metrics()
+                        .add_dat_file_bytes(stored, uncompressed),
+                    (Err(err), _) | (_, Err(err)) => {
+                        log::warn!("Failed to read the size of {path}: {err}")
+                    }
+                }
                 StoredDataHint::External(path.clone())
             }
         };
@@ -318,7 +483,12 @@ where
             .await
             .map_err(CrawlWriteError::SlimError)?
         {
-            Ok(Some(found.inflate(Some(&self.worker_warc_writer)).await?))
+            let verify_digests = self.configs().system.verify_warc_digests;
+            Ok(Some(
+                found
+                    .inflate(Some(&self.worker_warc_writer), verify_digests)
+                    .await?,
+            ))
         } else {
             Ok(None)
         }
@@ -339,12 +509,15 @@ where
                 S: BasicSeed;
 
             fn create_crawl_id(&self) -> String;
+
+            fn bandwidth_limiter(&self) -> Option<&BandwidthLimiter>;
         }
     }
 }
 
 #[cfg(test)]
 pub mod test {
+    use crate::config::session::DatFileLayout;
     use crate::config::Config;
     use crate::contexts::local::LocalContext;
     use crate::contexts::traits::{SupportsCrawlResults, SupportsSlimCrawlResults};
@@ -352,14 +525,18 @@ pub mod test {
     use crate::crawl::test::{
         create_test_data, create_test_data_unknown, create_testdata_with_on_seed,
     };
+    use crate::crawl::StoredDataHint;
     use crate::data::RawVecData;
     use crate::io::fs::{AtraFS, FileSystemAccess};
     use crate::runtime::RuntimeContext;
     use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
     use crate::url::UrlWithDepth;
-    use crate::warc_ext::SpecialWarcWriter;
+    use crate::warc_ext::{SpecialWarcWriter, WarcRecordScanner, WarcSkipInstruction};
     use camino::Utf8PathBuf;
+    use camino_tempfile::Utf8TempDir;
     use encoding_rs::UTF_8;
+    use std::fs::File;
+    use std::io::BufReader;
     use std::net::{IpAddr, Ipv4Addr};
     use std::path::Path;
     use std::sync::Arc;
@@ -469,6 +646,7 @@ pub mod test {
             0,
             Utf8PathBuf::from("test\\data"),
             Utf8PathBuf::from("test\\data\\blobs"),
+            DatFileLayout::Flat,
         )
         .unwrap();
 
@@ -601,4 +779,55 @@ pub mod test {
             .expect("Expected to exist!");
         assert_eq!(test_data1, retrieved);
     }
+
+    #[tokio::test]
+    async fn deduplicates_identical_bodies() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut cfg = Config::default();
+        cfg.paths.root = root.path().to_path_buf();
+        cfg.crawl.deduplicate_by_digest = true;
+
+        let local = Arc::new(LocalContext::new(cfg, &RuntimeContext::unbound()).unwrap());
+        let worker = WorkerContext::create(0, 0, local.clone()).unwrap();
+
+        const BODY: &[u8] = b"<html><body>Session expired, please log in again.</body></html>";
+        let first = create_test_data(
+            UrlWithDepth::from_url("https://www.example.com/?sid=1").unwrap(),
+            Some(RawVecData::from_vec(BODY.to_vec())),
+        );
+        let second = create_test_data(
+            UrlWithDepth::from_url("https://www.example.com/?sid=2").unwrap(),
+            Some(RawVecData::from_vec(BODY.to_vec())),
+        );
+        worker.store_crawled_website(&first).await.unwrap();
+        worker.store_crawled_website(&second).await.unwrap();
+        worker.worker_warc_writer.flush().await.unwrap();
+
+        let mut instructions = Vec::new();
+        for result in [&first, &second] {
+            let slim = worker
+                .retrieve_slim_crawled_website(&result.meta.url)
+                .await
+                .unwrap()
+                .unwrap();
+            let content = unsafe { slim.get_content() }.unwrap().left().unwrap();
+            assert_eq!(Some(&BODY.to_vec()), content.as_in_memory());
+            match slim.stored_data_hint {
+                StoredDataHint::Warc(instruction) => instructions.push(instruction),
+                other => panic!("Expected a warc instruction, got {other:?}"),
+            }
+        }
+        assert_eq!(instructions[0], instructions[1]);
+
+        let WarcSkipInstruction::Single { pointer, .. } = &instructions[0] else {
+            panic!("Expected a single record!")
+        };
+        let mut scanner =
+            WarcRecordScanner::new(BufReader::new(File::open(pointer.path()).unwrap()));
+        let mut records = 0;
+        while scanner.next_record().unwrap().is_some() {
+            records += 1;
+        }
+        assert_eq!(1, records);
+    }
 }
@@ -0,0 +1,34 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use thiserror::Error;
+
+/// Errors of the connection between a primary and a secondary.
+#[derive(Debug, Error)]
+pub enum CooperativeError {
+    #[error(transparent)]
+    IO(#[from] std::io::Error),
+    #[error(transparent)]
+    Serialisation(#[from] bincode::Error),
+    #[error(transparent)]
+    Config(#[from] serde_json::Error),
+    #[error("The message has {0} bytes, which is more than the allowed maximum.")]
+    MessageTooLarge(usize),
+    #[error("The other side speaks protocol version {0}.")]
+    ProtocolMismatch(u16),
+    #[error("Expected the message {0} but got something else.")]
+    UnexpectedMessage(&'static str),
+    #[error("The connection was closed before the handshake finished.")]
+    Closed,
+}
@@ -0,0 +1,153 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::UrlWithDepth;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The id of a lease, unique for a primary.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LeaseId(pub u64);
+
+impl Display for LeaseId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "lease-{}", self.0)
+    }
+}
+
+/// An url leased to a secondary.
+#[derive(Debug, Clone)]
+pub struct Lease {
+    pub worker_id: usize,
+    pub url: UrlWithDepth,
+    pub is_seed: bool,
+    pub expires_at: Instant,
+}
+
+/// The leases of all secondaries of a primary.
+/// A lease expires if the secondary sends neither a heartbeat nor the outcome in time.
+#[derive(Debug)]
+pub struct LeaseTable {
+    duration: Duration,
+    next_id: u64,
+    leases: HashMap<LeaseId, Lease>,
+}
+
+impl LeaseTable {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            next_id: 0,
+            leases: HashMap::new(),
+        }
+    }
+
+    /// Leases the [url] to the secondary with [worker_id].
+    pub fn grant(
+        &mut self,
+        worker_id: usize,
+        url: UrlWithDepth,
+        is_seed: bool,
+        now: Instant,
+    ) -> LeaseId {
+        let id = LeaseId(self.next_id);
+        self.next_id += 1;
+        self.leases.insert(
+            id,
+            Lease {
+                worker_id,
+                url,
+                is_seed,
+                expires_at: now + self.duration,
+            },
+        );
+        id
+    }
+
+    /// Extends all leases of the secondary with [worker_id].
+    pub fn renew(&mut self, worker_id: usize, now: Instant) {
+        for lease in self.leases.values_mut() {
+            if lease.worker_id == worker_id {
+                lease.expires_at = now + self.duration;
+            }
+        }
+    }
+
+    /// Removes the lease, returns None if it is unknown.
+    pub fn complete(&mut self, id: LeaseId) -> Option<Lease> {
+        self.leases.remove(&id)
+    }
+
+    /// Returns the point in time when the lease expires.
+    pub fn expires_at(&self, id: LeaseId) -> Option<Instant> {
+        self.leases.get(&id).map(|lease| lease.expires_at)
+    }
+
+    pub fn len(&self) -> usize {
+        self.leases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leases.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooperative::lease::LeaseTable;
+    use crate::url::UrlWithDepth;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    #[test]
+    fn renewal_extends_only_the_leases_of_the_worker() {
+        let start = Instant::now();
+        let mut table = LeaseTable::new(Duration::from_secs(10));
+        let first = table.grant(
+            1,
+            UrlWithDepth::from_url("https://www.example.com/").unwrap(),
+            true,
+            start,
+        );
+        let second = table.grant(
+            2,
+            UrlWithDepth::from_url("https://www.example.org/").unwrap(),
+            false,
+            start,
+        );
+        assert_ne!(first, second);
+        assert_eq!(2, table.len());
+
+        table.renew(1, start + Duration::from_secs(8));
+        assert_eq!(
+            Some(start + Duration::from_secs(18)),
+            table.expires_at(first)
+        );
+
+        assert_eq!(
+            Some(start + Duration::from_secs(10)),
+            table.expires_at(second)
+        );
+        assert_eq!(2, table.complete(second).unwrap().worker_id);
+        assert!(table.expires_at(second).is_none());
+
+        assert_eq!(1, table.complete(first).unwrap().worker_id);
+        assert!(table.complete(first).is_none());
+        assert!(table.is_empty());
+    }
+}
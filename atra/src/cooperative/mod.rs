@@ -0,0 +1,269 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod error;
+mod lease;
+mod primary;
+mod protocol;
+mod secondary;
+
+pub use error::CooperativeError;
+pub use primary::{listen, Primary};
+pub use secondary::SecondaryConnection;
+
+#[cfg(test)]
+mod test {
+    use crate::config::{BudgetSetting, Config, CrawlConfig};
+    use crate::contexts::traits::{SupportsCrawling, SupportsLinkState, SupportsUrlQueue};
+    use crate::cooperative::protocol::{read_message, write_message, Message, PROTOCOL_VERSION};
+    use crate::cooperative::{Primary, SecondaryConnection};
+    use crate::crawl::CrawlResult;
+    use crate::data::RawData;
+    use crate::extraction::ExtractedLink;
+    use crate::fetching::FetchedRequestData;
+    use crate::link_state::{LinkStateKind, LinkStateLike, LinkStateManager};
+    use crate::queue::{UrlQueue, UrlQueueElement};
+    use crate::runtime::{GracefulShutdown, ShutdownPhantom};
+    use crate::seed::UnguardedSeed;
+    use crate::test_impls::{FakeClientProvider, FakeResponse, TestContext, TestErrorConsumer};
+    use crate::url::{AtraUri, UrlWithDepth};
+    use reqwest::StatusCode;
+    use std::collections::{BTreeMap, HashMap, HashSet};
+    use std::time::Duration;
+    use tokio::net::UnixStream;
+
+    const SEED: &str = "https://www.example.com/";
+
+    fn fixture_config() -> Config {
+        let mut config = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        Config::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            config,
+        )
+    }
+
+    fn fixture_provider() -> FakeClientProvider {
+        let provider = FakeClientProvider::new();
+        let pages = [
+            (
+                "https://www.example.com/",
+                "<html><body><a href=\"/a.html\">a</a><a href=\"/b.html\">b</a></body></html>",
+            ),
+            (
+                "https://www.example.com/a.html",
+                "<html><body><a href=\"/\">home</a><a href=\"/b.html\">b</a></body></html>",
+            ),
+            (
+                "https://www.example.com/b.html",
+                "<html><body>The end.</body></html>",
+            ),
+        ];
+        for (url, body) in pages {
+            provider.insert(
+                url.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(body.as_bytes().to_vec()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+        provider
+    }
+
+    fn seed_context() -> TestContext<FakeClientProvider> {
+        TestContext::new(fixture_config(), FakeClientProvider::new())
+    }
+
+    /// Serves one secondary until the primary stored [expected] pages and no lease is left.
+    async fn crawl_cooperatively(
+        primary: &Primary,
+        worker_id: usize,
+        context: &TestContext<FakeClientProvider>,
+        expected: usize,
+    ) -> usize {
+        let secondary_context = TestContext::new(fixture_config(), fixture_provider());
+        let shutdown = GracefulShutdown::new();
+        let (primary_end, secondary_end) = UnixStream::pair().unwrap();
+
+        let serving = primary.serve(worker_id, context, primary_end, shutdown.child().clone());
+        let working = async {
+            let connection = SecondaryConnection::connect(secondary_end).await.unwrap();
+            assert_eq!(worker_id, connection.worker_id());
+            connection.work(&secondary_context).await.unwrap()
+        };
+        let stopping = async {
+            while context.get_all_crawled_websites().0.len() < expected
+                || primary.active_leases() > 0
+            {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+            shutdown.shutdown();
+        };
+        let (served, processed, _) = tokio::time::timeout(Duration::from_secs(30), async {
+            tokio::join!(serving, working, stopping)
+        })
+        .await
+        .expect("The cooperative crawl did not finish.");
+        served.unwrap();
+        assert!(secondary_context.get_all_crawled_websites().0.is_empty());
+        processed
+    }
+
+    fn summarize(
+        stored: &HashMap<AtraUri, CrawlResult>,
+    ) -> BTreeMap<String, (StatusCode, RawData<Vec<u8>>, HashSet<ExtractedLink>)> {
+        stored
+            .values()
+            .map(|result| {
+                (
+                    result.meta.url.try_as_str().to_string(),
+                    (
+                        result.meta.status_code,
+                        result.content.clone(),
+                        result
+                            .meta
+                            .links
+                            .iter()
+                            .flatten()
+                            .cloned()
+                            .collect::<HashSet<_>>(),
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn secondaries_store_the_same_data_as_a_single_process() {
+        let single = TestContext::new(fixture_config(), fixture_provider());
+        single
+            .create_crawl_task(UnguardedSeed::from_url(SEED).unwrap())
+            .unwrap()
+            .run(&single, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .unwrap();
+        let (expected, _) = single.get_all_crawled_websites();
+        assert_eq!(3, expected.len());
+
+        let context = seed_context();
+        context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(
+                true,
+                0,
+                false,
+                UrlWithDepth::from_url(SEED).unwrap(),
+            ))
+            .await
+            .unwrap();
+        let primary = Primary::new(Duration::from_secs(5), 1);
+        let processed = crawl_cooperatively(&primary, 1, &context, expected.len()).await;
+        assert_eq!(3, processed);
+
+        let (stored, _) = context.get_all_crawled_websites();
+        assert_eq!(summarize(&expected), summarize(&stored));
+        for result in stored.values() {
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(&result.meta.url)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        }
+        assert!(context.url_queue().is_empty().await);
+    }
+
+    #[tokio::test]
+    async fn the_lease_of_a_silent_secondary_returns_to_the_queue() {
+        let context = seed_context();
+        let seed = UrlWithDepth::from_url(SEED).unwrap();
+        context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(true, 0, false, seed.clone()))
+            .await
+            .unwrap();
+        let primary = Primary::new(Duration::from_millis(300), 1);
+        let (primary_end, mut secondary_end) = UnixStream::pair().unwrap();
+
+        let dying = async {
+            write_message(
+                &mut secondary_end,
+                &Message::Hello {
+                    protocol: PROTOCOL_VERSION,
+                },
+            )
+            .await
+            .unwrap();
+            assert!(matches!(
+                read_message(&mut secondary_end).await.unwrap(),
+                Some(Message::Welcome { worker_id: 1, .. })
+            ));
+            match read_message(&mut secondary_end).await.unwrap() {
+                Some(Message::Assign { url, is_seed, .. }) => {
+                    assert_eq!(seed, url);
+                    assert!(is_seed);
+                }
+                other => panic!("Expected an assignment but got {other:?}"),
+            }
+            assert_eq!(1, primary.active_leases());
+            // The secondary stops responding but keeps the connection open.
+            secondary_end
+        };
+        let (served, _silent) = tokio::time::timeout(Duration::from_secs(10), async {
+            tokio::join!(
+                primary.serve(1, &context, primary_end, ShutdownPhantom::<true>),
+                dying
+            )
+        })
+        .await
+        .expect("The lease did not expire.");
+        served.unwrap();
+
+        assert_eq!(0, primary.active_leases());
+        assert_eq!(1, context.url_queue().len().await);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::Discovered, state.kind());
+
+        // The next secondary gets the returned url.
+        assert_eq!(3, crawl_cooperatively(&primary, 2, &context, 3).await);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+    }
+}
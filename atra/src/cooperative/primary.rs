@@ -0,0 +1,564 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::blacklist::{Blacklist, BlacklistManager};
+use crate::client::traits::{next_fetch_attempt, FetchFailureKind};
+use crate::config::system::CooperativeConfig;
+use crate::contexts::local::LocalContext;
+use crate::contexts::traits::*;
+use crate::contexts::worker::WorkerContext;
+use crate::contexts::Context;
+use crate::cooperative::error::CooperativeError;
+use crate::cooperative::lease::{LeaseId, LeaseTable};
+use crate::cooperative::protocol::{Connection, Message, PROTOCOL_VERSION};
+use crate::crawl::{CrawlEvent, FetchOutcome};
+use crate::link_state::{IsSeedYesNo, LinkStateKind, LinkStateLike, LinkStateManager};
+use crate::origin_metadata::observe_origin_metadata;
+use crate::queue::{AbortCause, UrlQueue, UrlQueueElement, UrlQueuePollResult};
+use crate::recrawl_management::DomainLastCrawledManager;
+use crate::robots::UnavailableAfter;
+use crate::runtime::{ShutdownChild, ShutdownReceiver};
+use crate::seed::{BasicSeed, UnguardedSeed};
+use crate::unique_content::register_unique_content;
+use crate::url::{AtraOriginProvider, UrlWithDepth};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use time::OffsetDateTime;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::UnixListener;
+use tokio::select;
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
+
+/// How long a connection waits for new urls before polling the queue again.
+const IDLE_WAIT: Duration = Duration::from_millis(500);
+
+/// The number of occupied origins skipped before a poll gives up.
+const MAX_MISS: u64 = 64;
+
+/// The primary of a cooperative crawl. It owns the databases and leases the urls of its
+/// queue to the connected secondaries, one url per secondary at a time.
+#[derive(Debug)]
+pub struct Primary {
+    leases: Mutex<LeaseTable>,
+    lease_duration: Duration,
+    next_worker_id: AtomicUsize,
+}
+
+impl Primary {
+    /// Creates a primary that assigns the worker ids starting with [first_worker_id].
+    pub fn new(lease_duration: Duration, first_worker_id: usize) -> Self {
+        Self {
+            leases: Mutex::new(LeaseTable::new(lease_duration)),
+            lease_duration,
+            next_worker_id: AtomicUsize::new(first_worker_id),
+        }
+    }
+
+    /// Returns the worker id for the next secondary.
+    pub fn next_worker_id(&self) -> usize {
+        self.next_worker_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// The number of urls currently leased to secondaries.
+    pub fn active_leases(&self) -> usize {
+        self.leases.lock().unwrap().len()
+    }
+
+    /// Waits until no url is leased to a secondary.
+    pub async fn wait_until_idle(&self) {
+        while self.active_leases() > 0 {
+            tokio::time::sleep(IDLE_WAIT).await;
+        }
+    }
+
+    /// Serves the secondary with [worker_id] connected over [stream] until the [shutdown]
+    /// or until the secondary disconnects. A secondary that misses its lease is considered
+    /// dead, the url is returned to the queue and the connection is closed.
+    pub async fn serve<C, S>(
+        &self,
+        worker_id: usize,
+        context: &C,
+        stream: S,
+        shutdown: impl ShutdownReceiver,
+    ) -> Result<(), CooperativeError>
+    where
+        C: Context,
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let mut connection = Connection::new(stream);
+        match connection.recv().await? {
+            Some(Message::Hello {
+                protocol: PROTOCOL_VERSION,
+            }) => {}
+            Some(Message::Hello { protocol }) => {
+                return Err(CooperativeError::ProtocolMismatch(protocol))
+            }
+            Some(_) => return Err(CooperativeError::UnexpectedMessage("Hello")),
+            None => return Err(CooperativeError::Closed),
+        }
+        connection
+            .send(&Message::Welcome {
+                worker_id,
+                heartbeat_interval_ms: (self.lease_duration / 3).as_millis() as u64,
+                config: serde_json::to_string(context.configs())?,
+            })
+            .await?;
+        log::info!("The secondary {worker_id} connected.");
+
+        loop {
+            if shutdown.is_shutdown() {
+                log::info!("Shut down the secondary {worker_id}.");
+                let _ = connection.send(&Message::Shutdown).await;
+                return Ok(());
+            }
+            let guard = match context
+                .poll_next_free_url(shutdown.clone(), Some(MAX_MISS))
+                .await
+            {
+                UrlQueuePollResult::Ok(guard) => guard,
+                UrlQueuePollResult::Abort(AbortCause::NoHost(dropped)) => {
+                    log::warn!("Drop {} from queue due to NoDomain error.", dropped.target);
+                    continue;
+                }
+                UrlQueuePollResult::Abort(_) => {
+                    if !self.idle(worker_id, &mut connection, &shutdown).await? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                UrlQueuePollResult::Err(err) => {
+                    log::error!("Failed to poll an url for the secondary {worker_id}: {err}");
+                    if !self.idle(worker_id, &mut connection, &shutdown).await? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+            };
+
+            let seed = guard.get_unguarded_seed();
+            let Some(failed_attempts) = reserve(context, &seed).await else {
+                continue;
+            };
+            let url = seed.url().clone();
+            let is_seed = seed.is_original_seed();
+            let lease =
+                self.leases
+                    .lock()
+                    .unwrap()
+                    .grant(worker_id, url.clone(), is_seed, Instant::now());
+            log::debug!("Lease {url} to the secondary {worker_id} as {lease}.");
+            let outcome = match connection
+                .send(&Message::Assign {
+                    lease,
+                    url: url.clone(),
+                    is_seed,
+                })
+                .await
+            {
+                Ok(_) => {
+                    self.wait_for_outcome(worker_id, lease, &mut connection)
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            match outcome {
+                Ok(Some(outcome)) => {
+                    apply_outcome(context, &url, is_seed, failed_attempts, outcome).await;
+                    // The new links are queued before the lease is gone.
+                    self.leases.lock().unwrap().complete(lease);
+                    drop(guard);
+                }
+                Ok(None) => {
+                    self.return_to_queue(context, lease).await;
+                    return Ok(());
+                }
+                Err(err) => {
+                    self.return_to_queue(context, lease).await;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Waits a moment for new urls while receiving the heartbeats of the secondary.
+    /// Returns false if the secondary disconnected.
+    async fn idle<S>(
+        &self,
+        worker_id: usize,
+        connection: &mut Connection<S>,
+        shutdown: &impl ShutdownReceiver,
+    ) -> Result<bool, CooperativeError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let until = Instant::now() + IDLE_WAIT;
+        loop {
+            select! {
+                message = connection.recv() => match message? {
+                    Some(Message::Heartbeat) => {}
+                    Some(_) => return Err(CooperativeError::UnexpectedMessage("Heartbeat")),
+                    None => {
+                        log::info!("The secondary {worker_id} disconnected.");
+                        return Ok(false);
+                    }
+                },
+                _ = tokio::time::sleep_until(until) => return Ok(true),
+                _ = shutdown.wait() => return Ok(true),
+            }
+        }
+    }
+
+    /// Waits for the outcome of the [lease], returns None if the secondary disconnected
+    /// or the lease expired.
+    async fn wait_for_outcome<S>(
+        &self,
+        worker_id: usize,
+        lease: LeaseId,
+        connection: &mut Connection<S>,
+    ) -> Result<Option<FetchOutcome>, CooperativeError>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        loop {
+            let Some(expires_at) = self.leases.lock().unwrap().expires_at(lease) else {
+                return Ok(None);
+            };
+            select! {
+                message = connection.recv() => match message? {
+                    Some(Message::Heartbeat) => {
+                        self.leases.lock().unwrap().renew(worker_id, Instant::now());
+                    }
+                    Some(Message::Finished { lease: finished, outcome }) if finished == lease => {
+                        return Ok(Some(outcome));
+                    }
+                    Some(_) => return Err(CooperativeError::UnexpectedMessage("Finished")),
+                    None => {
+                        log::warn!("The secondary {worker_id} disconnected while holding {lease}.");
+                        return Ok(None);
+                    }
+                },
+                _ = tokio::time::sleep_until(expires_at) => {
+                    let renewed = self.leases.lock().unwrap().expires_at(lease);
+                    if renewed.is_some_and(|value| value > Instant::now()) {
+                        continue;
+                    }
+                    log::warn!("The {lease} of the secondary {worker_id} expired.");
+                    return Ok(None);
+                }
+            }
+        }
+    }
+
+    /// Removes the [lease] and returns its url to the queue.
+    async fn return_to_queue<C: Context>(&self, context: &C, lease: LeaseId) {
+        let Some(lease) = self.leases.lock().unwrap().complete(lease) else {
+            return;
+        };
+        log::info!("Return {} to the queue.", lease.url);
+        if let Err(err) = context
+            .get_link_state_manager()
+            .update_link_state_no_meta_and_payload(&lease.url, LinkStateKind::Discovered)
+            .await
+        {
+            log::error!("Failed recovery of linkstate of {}. {err}", lease.url);
+        }
+        if let Err(err) = context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(lease.is_seed, 0, false, lease.url))
+            .await
+        {
+            log::error!("Failed to return a leased url to the queue. {err}");
+        }
+    }
+}
+
+/// Checks if the url of the [seed] still has to be crawled and reserves it.
+/// Returns the previously failed attempts or None if the url is skipped.
+async fn reserve<C: Context>(context: &C, seed: &UnguardedSeed) -> Option<u8> {
+    let url = seed.url();
+    let budget = context.configs().crawl.budget_for(seed.origin());
+    match context.retrieve_slim_crawled_website(url).await {
+        Ok(Some(already_crawled)) => {
+            let is_due = budget.get_recrawl_interval().is_some_and(|recrawl| {
+                OffsetDateTime::now_utc() - already_crawled.meta.created_at >= *recrawl
+            });
+            if !is_due {
+                log::debug!("The url {} was already crawled.", url);
+                return None;
+            }
+        }
+        Ok(None) => {}
+        Err(err) => {
+            log::warn!("Failed to get the head information for {url}, try to continue. {err}")
+        }
+    }
+
+    let manager = context.get_link_state_manager();
+    let failed_attempts = match manager.get_link_state(url).await {
+        Ok(Some(value)) if value.kind() == LinkStateKind::Discovered => value
+            .payload()
+            .and_then(FetchFailureKind::attempts_from_payload)
+            .unwrap_or_default(),
+        Ok(_) => 0,
+        Err(err) => {
+            log::error!("Failed to read the linkstate of {url}. {err}");
+            return None;
+        }
+    };
+
+    if seed.is_original_seed() {
+        if let Err(err) = context.register_seed(seed).await {
+            log::error!("Failed to register the seed {url}. {err}");
+        }
+    }
+    context
+        .get_domain_manager()
+        .register_access(seed.origin())
+        .await;
+    if let Err(err) = manager
+        .update_link_state_no_payload(
+            url,
+            LinkStateKind::ReservedForCrawl,
+            Some(seed.is_original_seed().into()),
+            Some(budget.get_recrawl_interval().is_some().into()),
+        )
+        .await
+    {
+        log::info!(
+            "Failed setting of linkstate of {url}, continue without further processing. {err}"
+        );
+        return None;
+    }
+    Some(failed_attempts)
+}
+
+/// Stores the [outcome] of the leased [url] like the crawl task stores its own fetches.
+async fn apply_outcome<C: Context>(
+    context: &C,
+    url: &UrlWithDepth,
+    is_seed: bool,
+    failed_attempts: u8,
+    outcome: FetchOutcome,
+) {
+    let manager = context.get_link_state_manager();
+    match outcome {
+        FetchOutcome::Crawled(result) => {
+            if let Some(version) = result.meta.http_version {
+                context
+                    .crawl_events()
+                    .metrics()
+                    .record_http_version(version);
+            }
+            if let Some(links) = &result.meta.links {
+                let links = HashSet::from_iter(links.iter().cloned());
+                match context.handle_links(url, &links).await {
+                    Ok(on_seed) => enqueue_on_seed_links(context, on_seed).await,
+                    Err(err) => log::error!("Failed to handle the links of {url}. {err}"),
+                }
+            }
+            if let Err(err) = context.store_crawled_website(&result).await {
+                log::error!("Failed to store data for {url}. {err}");
+                let _ = manager
+                    .update_link_state_no_meta_and_payload(url, LinkStateKind::Discovered)
+                    .await;
+                return;
+            }
+            register_unique_content(context, &result).await;
+            observe_origin_metadata(context, &result);
+            let expiry = result
+                .meta
+                .unavailable_after
+                .as_ref()
+                .and_then(UnavailableAfter::to_payload);
+            if let Err(err) = manager
+                .update_link_state_no_meta(
+                    url,
+                    LinkStateKind::ProcessedAndStored,
+                    Some(expiry.as_ref().map(|value| value.as_slice())),
+                )
+                .await
+            {
+                log::error!("Failed setting of linkstate of {url}. {err}");
+            }
+            context
+                .crawl_events()
+                .emit(CrawlEvent::stored(url.clone(), &result, None));
+            context
+                .crawl_limits()
+                .record_stored(result.content.stored_size().unwrap_or_default());
+        }
+        FetchOutcome::NotAllowed => {
+            log::debug!("Dropped Seed: {}", url);
+            if let Err(err) = manager
+                .update_link_state_no_meta_and_payload(url, LinkStateKind::Discovered)
+                .await
+            {
+                log::error!("Failed setting of linkstate of {url}. {err}");
+            }
+        }
+        FetchOutcome::Unprocessable => {
+            if let Err(err) = manager
+                .update_link_state_no_meta_and_payload(url, LinkStateKind::InternalError)
+                .await
+            {
+                log::error!("Failed setting of linkstate of {url}. {err}");
+            }
+        }
+        FetchOutcome::Failed { kind, reason } => {
+            log::warn!("Failed to fetch {} with error {} ({kind})", url, reason);
+            context.crawl_events().emit(CrawlEvent::Failed {
+                url: url.clone(),
+                kind,
+                reason,
+            });
+            let attempts = failed_attempts.saturating_add(1);
+            if let Some(not_before) = next_fetch_attempt(
+                context.configs().crawl.fetch_retry.as_ref(),
+                kind,
+                attempts,
+                OffsetDateTime::now_utc(),
+            ) {
+                log::info!("Retry {url} after {not_before}, attempt {attempts} failed ({kind}).");
+                if let Err(err) = manager
+                    .update_link_state_no_meta(
+                        url,
+                        LinkStateKind::Discovered,
+                        Some(Some(kind.to_retry_payload(attempts, not_before).as_slice())),
+                    )
+                    .await
+                {
+                    log::error!("Failed recovery of linkstate of {url}. {err}");
+                }
+                if let Err(err) = context
+                    .url_queue()
+                    .enqueue(UrlQueueElement::new(is_seed, 0, false, url.clone()))
+                    .await
+                {
+                    log::error!("Failed to enqueue {url} for a retry. {err}");
+                }
+            } else if let Err(err) = manager
+                .update_link_state_no_meta(
+                    url,
+                    LinkStateKind::InternalError,
+                    Some(Some(kind.to_payload().as_slice())),
+                )
+                .await
+            {
+                log::error!("Failed recovery of linkstate of {url}. {err}");
+            }
+        }
+    }
+}
+
+/// Enqueues the unknown [links] on the seed of a crawled page. The crawl task follows them
+/// itself, the primary leases them one by one instead.
+async fn enqueue_on_seed_links<C: Context>(context: &C, links: Vec<(UrlWithDepth, i8)>) {
+    let blacklist = context.get_blacklist_manager().get_blacklist().await;
+    let manager = context.get_link_state_manager();
+    for (url, score) in links {
+        match manager.get_link_state(&url).await {
+            Ok(None) => {}
+            Ok(Some(_)) => continue,
+            Err(err) => {
+                log::error!("Failed to read the linkstate of {url}. {err}");
+                continue;
+            }
+        }
+        let Some(origin) = url.atra_origin() else {
+            continue;
+        };
+        let budget = context.configs().crawl.budget_for(&origin);
+        if blacklist.has_match_for(&url.try_as_str()) || !budget.is_in_budget(&url) {
+            log::debug!("Dropped: {url}");
+            continue;
+        }
+        if let Err(err) = manager
+            .update_link_state_no_payload(
+                &url,
+                LinkStateKind::Discovered,
+                Some(IsSeedYesNo::No),
+                Some(budget.get_recrawl_interval().is_some().into()),
+            )
+            .await
+        {
+            log::error!("Failed setting of linkstate of {url}. {err}");
+            continue;
+        }
+        if let Err(err) = context
+            .url_queue()
+            .enqueue(UrlQueueElement::new(false, 0, false, url).with_priority(score))
+            .await
+        {
+            log::error!("Failed to enqueue a link. {err}");
+        }
+    }
+}
+
+/// Accepts secondaries on the socket of the [config] until the [shutdown].
+/// Every secondary writes with its own worker id, starting with [first_worker_id].
+pub fn listen(
+    config: &CooperativeConfig,
+    first_worker_id: usize,
+    context: Arc<LocalContext>,
+    shutdown: ShutdownChild,
+) -> Result<(Arc<Primary>, JoinHandle<()>), CooperativeError> {
+    let socket = config.socket.clone();
+    let listener = UnixListener::bind(&socket)?;
+    let lease_duration = config.lease_timeout.unsigned_abs();
+    let primary = Arc::new(Primary::new(lease_duration, first_worker_id));
+    log::info!("Wait for secondaries on {socket}.");
+    let handle = {
+        let primary = primary.clone();
+        tokio::spawn(async move {
+            let mut connections = JoinSet::new();
+            loop {
+                let stream = select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, _)) => stream,
+                        Err(err) => {
+                            log::error!("Failed to accept a secondary. {err}");
+                            continue;
+                        }
+                    },
+                    _ = shutdown.wait() => break,
+                };
+                let worker_id = primary.next_worker_id();
+                let worker = match WorkerContext::create(worker_id, 0, context.clone()) {
+                    Ok(worker) => worker,
+                    Err(err) => {
+                        log::error!(
+                            "Failed to create the context of the secondary {worker_id}. {err}"
+                        );
+                        continue;
+                    }
+                };
+                let primary = primary.clone();
+                let shutdown = shutdown.clone();
+                connections.spawn(async move {
+                    if let Err(err) = primary.serve(worker_id, &worker, stream, shutdown).await {
+                        log::error!("The connection to the secondary {worker_id} failed. {err}");
+                    }
+                });
+            }
+            while connections.join_next().await.is_some() {}
+            if let Err(err) = std::fs::remove_file(&socket) {
+                log::warn!("Failed to remove the socket {socket}. {err}");
+            }
+        })
+    };
+    Ok((primary, handle))
+}
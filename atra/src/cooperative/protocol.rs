@@ -0,0 +1,194 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::cooperative::error::CooperativeError;
+use crate::cooperative::lease::LeaseId;
+use crate::crawl::FetchOutcome;
+use crate::url::UrlWithDepth;
+use serde::{Deserialize, Serialize};
+use std::io::ErrorKind;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The version of the protocol, the primary rejects secondaries with a different version.
+pub const PROTOCOL_VERSION: u16 = 1;
+
+/// The maximum size of a single message in bytes.
+pub const MAX_MESSAGE_SIZE: usize = 1024 * 1024 * 1024;
+
+/// A message between the primary and a secondary.
+/// Every message is sent as big endian u32 length followed by the bincode encoded message.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum Message {
+    /// The first message of a secondary after connecting.
+    Hello { protocol: u16 },
+    /// The answer of the primary to [Message::Hello], the config is encoded as json.
+    Welcome {
+        worker_id: usize,
+        heartbeat_interval_ms: u64,
+        config: String,
+    },
+    /// The primary leases the url to the secondary.
+    Assign {
+        lease: LeaseId,
+        url: UrlWithDepth,
+        is_seed: bool,
+    },
+    /// The secondary returns the outcome of the lease.
+    Finished {
+        lease: LeaseId,
+        outcome: FetchOutcome,
+    },
+    /// The secondary is still alive.
+    Heartbeat,
+    /// The primary shuts down, the secondary stops after receiving it.
+    Shutdown,
+}
+
+/// Writes the length prefixed [message] to the [writer].
+pub async fn write_message<W>(writer: &mut W, message: &Message) -> Result<(), CooperativeError>
+where
+    W: AsyncWrite + Unpin,
+{
+    let data = bincode::serialize(message)?;
+    if data.len() > MAX_MESSAGE_SIZE {
+        return Err(CooperativeError::MessageTooLarge(data.len()));
+    }
+    writer.write_u32(data.len() as u32).await?;
+    writer.write_all(&data).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Reads the next length prefixed message from the [reader].
+/// Returns None if the other side closed the connection.
+pub async fn read_message<R>(reader: &mut R) -> Result<Option<Message>, CooperativeError>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = match reader.read_u32().await {
+        Ok(len) => len as usize,
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    if len > MAX_MESSAGE_SIZE {
+        return Err(CooperativeError::MessageTooLarge(len));
+    }
+    let mut data = vec![0u8; len];
+    reader.read_exact(&mut data).await?;
+    Ok(Some(bincode::deserialize(&data)?))
+}
+
+/// One end of the connection between the primary and a secondary.
+/// The messages are read by a separate task, this makes [Connection::recv] cancel safe.
+pub struct Connection<S> {
+    writer: WriteHalf<S>,
+    messages: mpsc::Receiver<Result<Message, CooperativeError>>,
+    reader: JoinHandle<()>,
+}
+
+impl<S> Connection<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    pub fn new(stream: S) -> Self {
+        let (reader, writer) = tokio::io::split(stream);
+        let (sender, messages) = mpsc::channel(8);
+        let reader = tokio::spawn(Self::read_all(reader, sender));
+        Self {
+            writer,
+            messages,
+            reader,
+        }
+    }
+
+    async fn read_all(
+        mut reader: ReadHalf<S>,
+        sender: mpsc::Sender<Result<Message, CooperativeError>>,
+    ) {
+        loop {
+            let message = match read_message(&mut reader).await {
+                Ok(Some(message)) => Ok(message),
+                Ok(None) => break,
+                Err(err) => Err(err),
+            };
+            let failed = message.is_err();
+            if sender.send(message).await.is_err() || failed {
+                break;
+            }
+        }
+    }
+
+    pub async fn send(&mut self, message: &Message) -> Result<(), CooperativeError> {
+        write_message(&mut self.writer, message).await
+    }
+
+    /// Returns the next message or None if the other side closed the connection.
+    pub async fn recv(&mut self) -> Result<Option<Message>, CooperativeError> {
+        self.messages.recv().await.transpose()
+    }
+}
+
+impl<S> Drop for Connection<S> {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::cooperative::lease::LeaseId;
+    use crate::cooperative::protocol::{read_message, write_message, Message};
+    use crate::crawl::test::create_testdata_with_on_seed;
+    use crate::crawl::FetchOutcome;
+
+    #[tokio::test]
+    async fn messages_survive_the_framing() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        let result = create_testdata_with_on_seed(None);
+        let expected = result.clone();
+        let writer = tokio::spawn(async move {
+            write_message(&mut client, &Message::Heartbeat)
+                .await
+                .unwrap();
+            write_message(
+                &mut client,
+                &Message::Finished {
+                    lease: LeaseId(7),
+                    outcome: FetchOutcome::Crawled(result),
+                },
+            )
+            .await
+            .unwrap();
+        });
+
+        assert!(matches!(
+            read_message(&mut server).await.unwrap(),
+            Some(Message::Heartbeat)
+        ));
+        match read_message(&mut server).await.unwrap() {
+            Some(Message::Finished {
+                lease,
+                outcome: FetchOutcome::Crawled(result),
+            }) => {
+                assert_eq!(LeaseId(7), lease);
+                assert_eq!(expected, result);
+            }
+            other => panic!("Unexpected message {other:?}"),
+        }
+        writer.await.unwrap();
+        assert!(read_message(&mut server).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::contexts::traits::{
+    SupportsConfigs, SupportsCrawlEvents, SupportsCrawling, SupportsFileSystemAccess,
+    SupportsGdbrRegistry, SupportsRobotsManager,
+};
+use crate::cooperative::error::CooperativeError;
+use crate::cooperative::protocol::{Connection, Message, PROTOCOL_VERSION};
+use crate::crawl::FetchOutcome;
+use crate::seed::UnguardedSeed;
+use crate::url::{AtraOriginProvider, UrlWithDepth};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::select;
+use tokio::time::{Interval, MissedTickBehavior};
+
+/// The connection of a secondary to its primary. The secondary fetches and analyzes
+/// the leased urls and returns the outcome, it never stores anything itself.
+pub struct SecondaryConnection<S> {
+    worker_id: usize,
+    config: Config,
+    heartbeat_interval: Duration,
+    connection: Connection<S>,
+}
+
+impl<S> SecondaryConnection<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    /// Greets the primary over the [stream] and receives the worker id and the config of the crawl.
+    pub async fn connect(stream: S) -> Result<Self, CooperativeError> {
+        let mut connection = Connection::new(stream);
+        connection
+            .send(&Message::Hello {
+                protocol: PROTOCOL_VERSION,
+            })
+            .await?;
+        match connection.recv().await? {
+            Some(Message::Welcome {
+                worker_id,
+                heartbeat_interval_ms,
+                config,
+            }) => Ok(Self {
+                worker_id,
+                config: serde_json::from_str(&config)?,
+                heartbeat_interval: Duration::from_millis(heartbeat_interval_ms.max(1)),
+                connection,
+            }),
+            Some(_) => Err(CooperativeError::UnexpectedMessage("Welcome")),
+            None => Err(CooperativeError::Closed),
+        }
+    }
+
+    /// The worker id assigned by the primary.
+    pub fn worker_id(&self) -> usize {
+        self.worker_id
+    }
+
+    /// The config of the crawl of the primary.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Works on the leased urls until the primary shuts down or closes the connection.
+    /// Returns the number of processed urls.
+    pub async fn work<C>(mut self, context: &C) -> Result<usize, CooperativeError>
+    where
+        C: SupportsCrawling
+            + SupportsConfigs
+            + SupportsRobotsManager
+            + SupportsFileSystemAccess
+            + SupportsGdbrRegistry
+            + SupportsCrawlEvents,
+    {
+        let mut heartbeat = tokio::time::interval(self.heartbeat_interval);
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut processed = 0;
+        loop {
+            let message = select! {
+                message = self.connection.recv() => message?,
+                _ = heartbeat.tick() => {
+                    self.connection.send(&Message::Heartbeat).await?;
+                    continue;
+                }
+            };
+            match message {
+                Some(Message::Assign {
+                    lease,
+                    url,
+                    is_seed,
+                }) => {
+                    log::debug!("Secondary {} works on {url} ({lease}).", self.worker_id);
+                    let outcome = self.fetch(context, url, is_seed, &mut heartbeat).await?;
+                    self.connection
+                        .send(&Message::Finished { lease, outcome })
+                        .await?;
+                    processed += 1;
+                }
+                Some(Message::Shutdown) | None => {
+                    log::info!("Secondary {} stops.", self.worker_id);
+                    return Ok(processed);
+                }
+                Some(_) => return Err(CooperativeError::UnexpectedMessage("Assign")),
+            }
+        }
+    }
+
+    /// Fetches the [url] while sending heartbeats. Keeps the url until the politeness delay
+    /// expired, the primary releases the guard of the origin after receiving the outcome.
+    async fn fetch<C>(
+        &mut self,
+        context: &C,
+        url: UrlWithDepth,
+        is_seed: bool,
+        heartbeat: &mut Interval,
+    ) -> Result<FetchOutcome, CooperativeError>
+    where
+        C: SupportsCrawling
+            + SupportsConfigs
+            + SupportsRobotsManager
+            + SupportsFileSystemAccess
+            + SupportsGdbrRegistry
+            + SupportsCrawlEvents,
+    {
+        let Some(origin) = url.atra_origin() else {
+            return Ok(FetchOutcome::Unprocessable);
+        };
+        let seed = match UnguardedSeed::new(url, origin, is_seed) {
+            Ok(seed) => seed,
+            Err(err) => {
+                log::error!("Failed to create the seed: {err}");
+                return Ok(FetchOutcome::Unprocessable);
+            }
+        };
+        let mut task = match context.create_crawl_task(seed) {
+            Ok(task) => task,
+            Err(err) => {
+                log::error!("Failed to create the crawl task: {err}");
+                return Ok(FetchOutcome::Unprocessable);
+            }
+        };
+        let work = async {
+            let outcome = task.fetch_single(context).await;
+            task.wait_for_cooldown().await;
+            outcome
+        };
+        tokio::pin!(work);
+        loop {
+            select! {
+                outcome = &mut work => return Ok(outcome),
+                _ = heartbeat.tick() => self.connection.send(&Message::Heartbeat).await?,
+            }
+        }
+    }
+}
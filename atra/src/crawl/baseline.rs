@@ -0,0 +1,128 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::Config;
+use crate::crawl::db::CrawlDB;
+use crate::crawl::SlimCrawlResult;
+use crate::database::DBActionType::Read;
+use crate::database::{open_db_read_only, DatabaseError, OpenDBError, RawDatabaseError};
+use crate::declare_column_families;
+use crate::link_state::{LinkStateKind, RawLinkState};
+use crate::toolkit::bloom::BloomFilter;
+use crate::url::UrlWithDepth;
+use camino::Utf8Path;
+use rocksdb::{ReadOptions, DB};
+use std::sync::Arc;
+
+/// The probability that the filter sends an url not stored in the baseline to the database.
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// The database of a previous session, opened read-only. The urls stored there are not
+/// crawled again by an incremental crawl until their recrawl interval is over.
+///
+/// All stored urls are put into a bloom filter when the baseline is opened, only the urls
+/// that may be stored are looked up in the database.
+#[derive(Debug)]
+pub struct Baseline {
+    db: Arc<DB>,
+    crawl_db: CrawlDB,
+    filter: BloomFilter,
+}
+
+impl Baseline {
+    declare_column_families! {
+        self.db => crawl_handle(CRAWL_DB_CF)
+        self.db => link_state_handle(LINK_STATE_DB_CF)
+    }
+
+    /// Opens the database at [path] read-only, [configs] are the configs of the new session.
+    pub fn open(path: &Utf8Path, configs: &Config) -> Result<Self, OpenDBError> {
+        if !path.is_dir() {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("The baseline database {path} does not exist!"),
+            )
+            .into());
+        }
+        let db = Arc::new(open_db_read_only(path)?);
+        if db.cf_handle(Self::CRAWL_DB_CF).is_none()
+            || db.cf_handle(Self::LINK_STATE_DB_CF).is_none()
+        {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("The baseline {path} is not the database of a crawl!"),
+            )
+            .into());
+        }
+        let crawl_db = CrawlDB::new(db.clone(), configs)?;
+        let mut baseline = Self {
+            db,
+            crawl_db,
+            filter: BloomFilter::new(0, FALSE_POSITIVE_RATE),
+        };
+        baseline.filter = baseline.build_filter();
+        Ok(baseline)
+    }
+
+    /// Puts the urls of all stored pages into a filter. The language variants of a page
+    /// are keyed by the url and the language, only the url is put into the filter.
+    fn build_filter(&self) -> BloomFilter {
+        let handle = self.crawl_handle();
+        let scan = || {
+            let mut options = ReadOptions::default();
+            options.fill_cache(false);
+            let mut iter = self.db.raw_iterator_cf_opt(&handle, options);
+            iter.seek_to_first();
+            iter
+        };
+
+        let mut keys = 0usize;
+        let mut iter = scan();
+        while iter.valid() {
+            keys += 1;
+            iter.next();
+        }
+        let mut filter = BloomFilter::new(keys, FALSE_POSITIVE_RATE);
+        let mut iter = scan();
+        while let Some(key) = iter.key() {
+            let url = key.split(|value| *value == b' ').next().unwrap_or(key);
+            filter.insert(url);
+            iter.next();
+        }
+        log::info!("The baseline contains {keys} stored pages.");
+        filter
+    }
+
+    /// Returns the page stored for [url] by the baseline. None if it is not stored there
+    /// or if the baseline did not finish it, like a page it stored but had no time
+    /// to mark as processed before it stopped.
+    pub fn get(&self, url: &UrlWithDepth) -> Result<Option<SlimCrawlResult>, DatabaseError> {
+        if !self.filter.may_contain(url.as_ref()) {
+            return Ok(None);
+        }
+        let Some(stored) = self.crawl_db.get(url)? else {
+            return Ok(None);
+        };
+        let link_state = self
+            .db
+            .get_pinned_cf(&self.link_state_handle(), url)
+            .enrich_without_entry(Self::LINK_STATE_DB_CF, Read, url)?;
+        let finished = match link_state {
+            Some(raw) => RawLinkState::read_kind(&raw)
+                .is_ok_and(|kind| kind == LinkStateKind::ProcessedAndStored),
+            None => true,
+        };
+        Ok(finished.then_some(stored))
+    }
+}
@@ -12,9 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod coordination;
+mod data_uris;
 mod intervals;
+mod legacy;
+pub(super) mod publication;
+pub(super) mod rediscovery;
 pub(super) mod result;
-mod sitemaps;
+mod revalidation;
+pub(super) mod sitemaps;
 pub(super) mod slim;
 
 #[cfg(test)]
@@ -25,44 +31,66 @@ pub use result::test::*;
 #[allow(unused_imports)]
 pub use crate::blacklist::ManagedBlacklist;
 use crate::blacklist::{Blacklist, BlacklistManager};
-use crate::client::traits::AtraClient;
-use crate::config::BudgetSetting;
+use crate::client::traits::{next_fetch_attempt, AtraClient, FetchFailureKind};
+use crate::config::{BudgetSetting, CrawlConfig, ErrorPageExtraction};
 use crate::contexts::traits::{
-    SupportsBlackList, SupportsConfigs, SupportsCrawlResults, SupportsCrawling,
-    SupportsDomainHandling, SupportsFileSystemAccess, SupportsGdbrRegistry, SupportsLinkSeeding,
-    SupportsLinkState, SupportsRobotsManager, SupportsSlimCrawlResults, SupportsUrlQueue,
+    SupportsBaseline, SupportsBlackList, SupportsConfigs, SupportsCrawlEvents, SupportsCrawlLimits,
+    SupportsCrawlResults, SupportsCrawling, SupportsDomainHandling, SupportsFileSystemAccess,
+    SupportsGdbrRegistry, SupportsLinkSeeding, SupportsLinkState, SupportsRobotsManager,
+    SupportsSlimCrawlResults, SupportsTlsInfo, SupportsUniqueContent, SupportsUrlFilter,
+    SupportsUrlNormalization, SupportsUrlQueue, SupportsWorkerId,
 };
+use crate::crawl::crawler::data_uris::{data_uri_target, DataUri};
 use crate::crawl::crawler::intervals::InvervalManager;
-use crate::crawl::crawler::result::CrawlResult;
+use crate::crawl::crawler::publication::PublicationEstimate;
+use crate::crawl::crawler::rediscovery::{OriginReachability, Rediscovery};
+use crate::crawl::crawler::result::{AuxiliaryDocument, CrawlResult, FetchOutcome};
+use crate::crawl::crawler::revalidation::revalidate_robots;
 use crate::crawl::crawler::sitemaps::retrieve_and_parse;
-use crate::crawl::ErrorConsumer;
-use crate::data::{process, RawData, RawVecData};
-use crate::fetching::ResponseData;
-use crate::format::determine_format_for_response;
+use crate::crawl::crawler::slim::SlimCrawlResult;
+use crate::crawl::{CrawlEvent, ErrorConsumer};
+use crate::data::{process, Decoded, RawData, RawVecData};
+use crate::extraction::extractor::ExtractorResult;
+use crate::extraction::ExtractedLink;
+use crate::fetching::{
+    throttled_until, FetchedRequestData, RediscoveryRetry, ResponseData, ThrottledRetry,
+};
+use crate::format::{determine_format_for_response, AtraFileInformation};
 use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::gdbr::identifier::{GdbrRegistry, GdbrScore};
 use crate::io::fs::AtraFS;
 use crate::link_state::{
     IsSeedYesNo, LinkStateKind, LinkStateLike, LinkStateManager, RecrawlYesNo,
 };
+use crate::origin_metadata::{capture_well_known, observe_origin_metadata, PageSignals};
 use crate::queue::{QueueError, UrlQueue, UrlQueueElement};
-use crate::recrawl_management::DomainLastCrawledManager;
-use crate::robots::{GeneralRobotsInformation, RobotsInformation};
+use crate::recrawl_management::{next_crawl_at, DomainLastCrawledManager, SitemapHint};
+use crate::robots::{
+    GeneralRobotsInformation, RobotsInformation, RobotsMetaDirectives, UnavailableAfter,
+};
 use crate::runtime::ShutdownReceiver;
 use crate::seed::BasicSeed;
-use crate::toolkit::detect_language;
-use crate::url::UrlWithDepth;
+use crate::tls_info::{capture_tls_info, record_tls_certificate};
+use crate::toolkit::fuzzy_hash::{FuzzyHash, SimHash};
+use crate::toolkit::plain_text::plain_text;
+use crate::toolkit::{detect_language, read_text_sample, text_sample_limit, LanguageInformation};
+use crate::unique_content::register_unique_content;
+use crate::url::{AtraOriginProvider, UrlWithDepth};
+use camino::Utf8PathBuf;
 use itertools::Itertools;
 use log::LevelFilter;
-use sitemap::structs::Location;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT_LANGUAGE, CONTENT_TYPE, VARY};
+use reqwest::StatusCode;
 use smallvec::SmallVec;
-use std::collections::{HashSet, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::Display;
 use std::fs::File;
 use std::io;
 use std::io::Write;
 use std::sync::Arc;
+use std::time::Instant;
 use strum::EnumString;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 
 /// A crawler for a single website. Starts from the provided `seed` and
 #[derive(Debug)]
@@ -75,6 +103,9 @@ pub struct CrawlTask<S, Client> {
 
     /// All URLs visited.
     links_visited: HashSet<UrlWithDepth>,
+
+    /// The point in time when the origin of the seed can be contacted again.
+    cooldown_until: Option<tokio::time::Instant>,
 }
 
 impl<S, Client> CrawlTask<S, Client> {
@@ -84,8 +115,25 @@ impl<S, Client> CrawlTask<S, Client> {
             seed,
             client,
             links_visited: Default::default(),
+            cooldown_until: None,
+        }
+    }
+
+    /// Waits until the politeness delay after the last request of this task expired.
+    /// Has to be called before the guard of the seed is released, otherwise the next
+    /// worker may immediately contact the same origin.
+    pub async fn wait_for_cooldown(&self) {
+        if let Some(until) = self.cooldown_until {
+            tokio::time::sleep_until(until).await;
         }
     }
+
+    /// The time left until the politeness delay after the last request of this task expired.
+    pub fn remaining_cooldown(&self) -> Option<std::time::Duration> {
+        self.cooldown_until
+            .map(|until| until.saturating_duration_since(tokio::time::Instant::now()))
+            .filter(|remaining| !remaining.is_zero())
+    }
 }
 
 impl<S, Client> CrawlTask<S, Client>
@@ -108,6 +156,30 @@ where
         Self::update_linkstate(handler, context, target, link_state_type, None, None).await
     }
 
+    /// Sets the state of the [target] together with its payload, None removes the payload.
+    async fn update_linkstate_with_payload<C, E, EC>(
+        handler: &EC,
+        context: &C,
+        target: &UrlWithDepth,
+        link_state_type: LinkStateKind,
+        payload: Option<&[u8]>,
+    ) -> Result<(), EC::Error>
+    where
+        C: SupportsLinkState,
+        E: From<<<C as SupportsLinkState>::LinkStateManager as LinkStateManager>::Error>,
+        EC: ErrorConsumer<E>,
+    {
+        log::trace!("Update {link_state_type} with payload: ``{}``", target);
+        match context
+            .get_link_state_manager()
+            .update_link_state_no_meta(target, link_state_type, Some(payload))
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(error) => handler.consume_crawl_error(error.into()),
+        }
+    }
+
     async fn update_linkstate<C, E, EC>(
         handler: &EC,
         context: &C,
@@ -132,6 +204,124 @@ where
         }
     }
 
+    /// Hands the [links] to the context and queues the allowed ones on the seed.
+    async fn queue_on_seed_links<C, R, B>(
+        &self,
+        context: &C,
+        checker: &UrlChecker<'_, R, B>,
+        target: &UrlWithDepth,
+        links: &HashSet<ExtractedLink>,
+        queue: &mut VecDeque<(bool, UrlWithDepth)>,
+    ) -> Result<(), <C as SupportsLinkSeeding>::Error>
+    where
+        C: SupportsLinkSeeding + SupportsCrawlEvents,
+        R: RobotsInformation,
+        B: Blacklist,
+    {
+        let mut value = context.handle_links(target, links).await?;
+        log::debug!("{}: on_seed links: {}", target, value.len());
+        // The links with the better score of the url filters are crawled first.
+        value.sort_by_key(|(_, score)| std::cmp::Reverse(*score));
+        for (in_seed, _) in value {
+            if checker.check_if_allowed(self, &in_seed).await {
+                log::trace!("Queue: {}", target);
+                context.crawl_events().emit(CrawlEvent::Queued {
+                    url: in_seed.clone(),
+                });
+                queue.push_back((false, in_seed));
+            } else {
+                log::debug!("Dropped: {in_seed}");
+                if !checker.budget.is_in_budget(&in_seed) {
+                    context
+                        .crawl_events()
+                        .statistics()
+                        .record_budget_exhausted(&in_seed);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the page stored for [target] by the baseline of the [context] if it is not
+    /// fetched again. A page is fetched again once the [recrawl_interval] is over.
+    fn stored_in_baseline<C: SupportsBaseline>(
+        context: &C,
+        target: &UrlWithDepth,
+        recrawl_interval: Option<&Duration>,
+        hint: Option<&SitemapHint>,
+    ) -> Option<SlimCrawlResult> {
+        let stored = match context.baseline()?.get(target) {
+            Ok(stored) => stored?,
+            Err(err) => {
+                log::warn!("Failed to look up {target} in the baseline, crawl it. {err}");
+                return None;
+            }
+        };
+        if let Some(recrawl_interval) = recrawl_interval {
+            let now = OffsetDateTime::now_utc();
+            let next_crawl = next_crawl_at(hint, stored.meta.created_at, *recrawl_interval, now);
+            if next_crawl.is_some_and(|next_crawl| next_crawl <= now) {
+                log::debug!("The url {target} of the baseline is due for a recrawl.");
+                return None;
+            }
+        }
+        Some(stored)
+    }
+
+    /// Fetches and analyzes only the url of the seed without touching the link states or
+    /// storing anything. Used by the secondaries of a cooperative crawl.
+    pub async fn fetch_single<Cont>(&mut self, context: &Cont) -> FetchOutcome
+    where
+        Cont: SupportsConfigs
+            + SupportsRobotsManager
+            + SupportsFileSystemAccess
+            + SupportsGdbrRegistry
+            + SupportsCrawlEvents,
+    {
+        let configuration = &context.configs().crawl;
+        let configured_robots = Arc::new(
+            GeneralRobotsInformation::new(
+                context.get_robots_manager(),
+                self.client.user_agent().to_string(),
+                configuration.max_robots_age.clone(),
+            )
+            .bind_to_domain(&self.client, self.seed.url())
+            .await,
+        );
+        let target = self.seed.url().clone();
+        if !configured_robots
+            .check_if_allowed(&self.client, &target)
+            .await
+        {
+            log::debug!("Dropped Seed: {}", target);
+            return FetchOutcome::NotAllowed;
+        }
+
+        let mut interval_manager =
+            InvervalManager::new(&self.client, configuration, configured_robots.clone());
+        interval_manager.wait(&target).await;
+        log::info!("Crawl: {}", target);
+        let url_str = target.try_as_str().into_owned();
+        let fetched = self.client.retrieve(context, &url_str, None).await;
+        self.cooldown_until = interval_manager
+            .known_delay(&target)
+            .map(|delay| tokio::time::Instant::now() + delay);
+        match fetched {
+            Ok(page) => match analyze_page(context, page, &target).await {
+                Some(result) => FetchOutcome::Crawled(result),
+                None => FetchOutcome::Unprocessable,
+            },
+            Err(err) => {
+                let kind = self.client.classify_error(&url_str, &err);
+                log::warn!("Failed to fetch {} with error {} ({kind})", target, err);
+                FetchOutcome::Failed {
+                    kind,
+                    reason: err.to_string(),
+                }
+            }
+        }
+    }
+
     async fn pack_shutdown<C, E, EC>(
         handler: &EC,
         context: &C,
@@ -171,7 +361,16 @@ where
             + SupportsLinkSeeding
             + SupportsUrlQueue
             + SupportsCrawling
-            + SupportsDomainHandling,
+            + SupportsDomainHandling
+            + SupportsUrlNormalization
+            + SupportsUrlFilter
+            + SupportsCrawlEvents
+            + SupportsCrawlLimits
+            + SupportsBaseline
+            + SupportsUniqueContent
+            + SupportsTlsInfo
+            + SupportsOriginMetadata
+            + SupportsWorkerId,
         Shutdown: ShutdownReceiver,
         E: From<<Cont as SupportsSlimCrawlResults>::Error>
             + From<<Cont as SupportsLinkSeeding>::Error>
@@ -199,13 +398,19 @@ where
             .await,
         );
 
-        let budget = configuration
-            .budget
-            .get_budget_for(&self.seed.origin())
-            .clone();
+        let budget = configuration.budget_for(self.seed.origin()).clone();
 
         log::info!("Seed: {}, {}", self.seed.url(), budget);
 
+        capture_tls_info(context, self.seed.url()).await;
+        capture_well_known(
+            context,
+            &self.client,
+            configured_robots.as_ref(),
+            self.seed.url(),
+        )
+        .await;
+
         let blacklist = context.get_blacklist_manager().get_blacklist().await;
 
         log::debug!("Local blacklist initialized {:}", self.seed.url());
@@ -213,10 +418,12 @@ where
 
         queue.push_back((true, self.seed.url().clone()));
 
-        match context.register_seed(&self.seed).await {
-            Ok(_) => {}
-            Err(err) => {
-                consumer.consume_crawl_error(err.into())?;
+        if configuration.extract_links {
+            match context.register_seed(&self.seed).await {
+                Ok(_) => {}
+                Err(err) => {
+                    consumer.consume_crawl_error(err.into())?;
+                }
             }
         }
 
@@ -231,8 +438,11 @@ where
         let mut interval_manager =
             InvervalManager::new(&self.client, &configuration, configured_robots.clone());
 
+        // The hints of the listed urls, only collected if they are trusted.
+        let mut sitemap_hints = HashMap::new();
         if !context.configs().crawl.ignore_sitemap {
-            for value in retrieve_and_parse(
+            for sitemap in retrieve_and_parse(
+                context,
                 &self.client,
                 &self.seed.url(),
                 configured_robots.as_ref(),
@@ -240,20 +450,59 @@ where
                 None,
             )
             .await
-            .urls
             {
-                match value.loc {
-                    Location::None => {}
-                    Location::Url(url) => match UrlWithDepth::with_base(self.seed.url(), url) {
-                        Ok(url) => {
-                            queue.push_back((false, url));
-                        }
-                        Err(err) => {
-                            log::debug!("Failed to parse url from sitemap: {err}");
-                        }
-                    },
-                    Location::ParseErr(err) => {
-                        log::info!("Had error parsing sitemap: {err}");
+                let mut listed = sitemap.urls;
+                let mut sitemaps = sitemap.sitemaps;
+                for url in listed.iter_mut().chain(sitemaps.iter_mut()) {
+                    context.url_normalizer().normalize(url);
+                }
+                if configuration.trust_sitemap_hints {
+                    sitemap_hints.extend(
+                        listed
+                            .iter()
+                            .zip(sitemap.hints)
+                            .filter(|(_, hint)| !hint.is_empty())
+                            .map(|(url, hint)| (url.url.clone(), hint)),
+                    );
+                }
+                if let Err(err) = context
+                    .register_sitemap_links(&sitemap.url, &sitemaps)
+                    .await
+                {
+                    consumer.consume_crawl_error(err.into())?;
+                }
+                if let Err(err) = context.register_sitemap_links(&sitemap.url, &listed).await {
+                    consumer.consume_crawl_error(err.into())?;
+                }
+
+                let mut response_data = ResponseData::from_response(sitemap.fetched, sitemap.url);
+                let file_information = determine_format_for_response(context, &mut response_data);
+                let mut result = CrawlResult::new(
+                    OffsetDateTime::now_utc(),
+                    response_data,
+                    None,
+                    None,
+                    file_information,
+                    None,
+                );
+                result.meta.auxiliary = Some(AuxiliaryDocument::Sitemap(sitemap.summary));
+                result
+                    .meta
+                    .apply_header_persistence(&configuration.header_persistence);
+                log::debug!("Store the sitemap {}", result.meta.url);
+                if let Err(err) = context.store_crawled_website(&result).await {
+                    consumer.consume_crawl_error(err.into())?;
+                }
+
+                let url_filter = context.url_filter();
+                for url in listed {
+                    if url_filter.decide(&url, None).is_some() {
+                        context
+                            .crawl_events()
+                            .emit(CrawlEvent::Queued { url: url.clone() });
+                        queue.push_back((false, url));
+                    } else {
+                        context.crawl_events().statistics().record_filtered(&url);
                     }
                 }
             }
@@ -261,11 +510,7 @@ where
         let origin = self.seed.origin();
         let manager = context.get_domain_manager();
 
-        if let Some(recrawl_interval) = configuration
-            .budget
-            .get_budget_for(origin)
-            .get_recrawl_interval()
-        {
+        if let Some(recrawl_interval) = configuration.budget_for(origin).get_recrawl_interval() {
             let needs_recrawl_protection = if let Ok(Some(value)) = context
                 .get_link_state_manager()
                 .get_link_state(self.seed.url())
@@ -296,16 +541,65 @@ where
                         };
                     }
                 }
+                if configuration
+                    .robots_revalidation
+                    .as_ref()
+                    .is_some_and(|revalidation| revalidation.on_recrawl)
+                {
+                    if let Err(err) = revalidate_robots(
+                        context,
+                        &self.client,
+                        configured_robots.as_ref(),
+                        self.seed.url(),
+                        &mut queue,
+                    )
+                    .await
+                    {
+                        consumer.consume_crawl_error(err.into())?;
+                    }
+                }
             }
         }
 
+        // Tells if the origin of the seed is down, the attempts are known if it was down before.
+        let mut reachability = OriginReachability::default();
+        let mut rediscovery_attempts = 0;
+
         while let Some((is_seed, target)) = queue.pop_front() {
-            let old_link_state = match context
+            let (old_link_state, failed_attempts, throttled_attempts) = match context
                 .get_link_state_manager()
-                .get_link_state(self.seed.url())
+                .get_link_state(&target)
                 .await
             {
-                Ok(value) => value.map(|value| value.kind()),
+                Ok(Some(value)) => {
+                    if is_seed {
+                        if let Some(retry) =
+                            value.payload().and_then(RediscoveryRetry::from_payload)
+                        {
+                            rediscovery_attempts = retry.attempts;
+                        }
+                    }
+                    let (failed_attempts, throttled_attempts) =
+                        if value.kind() == LinkStateKind::Discovered {
+                            (
+                                value
+                                    .payload()
+                                    .and_then(FetchFailureKind::attempts_from_payload),
+                                value
+                                    .payload()
+                                    .and_then(ThrottledRetry::from_payload)
+                                    .map(|retry| retry.attempts),
+                            )
+                        } else {
+                            (None, None)
+                        };
+                    (
+                        Some(value.kind()),
+                        failed_attempts.unwrap_or_default(),
+                        throttled_attempts.unwrap_or_default(),
+                    )
+                }
+                Ok(None) => (None, 0, 0),
                 Err(err) => return consumer.consume_crawl_error(err.into()),
             };
 
@@ -321,6 +615,27 @@ where
             }
             log::trace!("Queue.len() => {}", queue.len());
 
+            if let Some(revalidation) = &configuration.robots_revalidation {
+                if manager.get_fetches_since_robots_refresh(origin).await
+                    >= revalidation.every_n_fetches.get()
+                {
+                    // The target is checked against the refreshed rules like the other queued urls.
+                    queue.push_front((is_seed, target));
+                    if let Err(err) = revalidate_robots(
+                        context,
+                        &self.client,
+                        configured_robots.as_ref(),
+                        self.seed.url(),
+                        &mut queue,
+                    )
+                    .await
+                    {
+                        consumer.consume_crawl_error(err.into())?;
+                    }
+                    continue;
+                }
+            }
+
             if !checker.check_if_allowed(self, &target).await {
                 log::debug!("Dropped Seed: {}", target);
                 let _ = Self::update_linkstate_no_meta(
@@ -334,18 +649,23 @@ where
             }
 
             manager.register_access(origin).await;
+            let mut previous = None;
             match context.retrieve_slim_crawled_website(&target).await {
                 Ok(value) => {
                     if let Some(already_crawled) = value {
-                        if let Some(recrawl) = configuration
-                            .budget
-                            .get_budget_for(origin)
-                            .get_recrawl_interval()
+                        if let Some(recrawl) =
+                            configuration.budget_for(origin).get_recrawl_interval()
                         {
-                            let time_since_crawled =
-                                OffsetDateTime::now_utc() - already_crawled.meta.created_at;
-
-                            if time_since_crawled.ge(recrawl) {
+                            let now = OffsetDateTime::now_utc();
+                            let next_crawl = next_crawl_at(
+                                sitemap_hints.get(&target.url),
+                                already_crawled.meta.created_at,
+                                *recrawl,
+                                now,
+                            );
+                            // Skipped while the recrawl is not due, from the moment it is due
+                            // the page is fetched again.
+                            if !next_crawl.is_some_and(|next_crawl| next_crawl <= now) {
                                 log::debug!("The url was already crawled.");
                                 continue;
                             }
@@ -370,11 +690,46 @@ where
                                     continue;
                                 }
                             }
+                            previous = Some(already_crawled);
                         } else {
                             log::debug!("The url {} was already crawled.", target);
                             continue;
                         }
                     } else {
+                        if let Some(stored) = Self::stored_in_baseline(
+                            context,
+                            &target,
+                            configuration.budget_for(origin).get_recrawl_interval(),
+                            sitemap_hints.get(&target.url),
+                        ) {
+                            log::debug!("The url {} is stored in the baseline.", target);
+                            context
+                                .crawl_events()
+                                .statistics()
+                                .record_baseline_skip(&target);
+                            if let Some(links) = stored.meta.links {
+                                let links = HashSet::from_iter(links);
+                                if let Err(err) = self
+                                    .queue_on_seed_links(
+                                        context, &checker, &target, &links, &mut queue,
+                                    )
+                                    .await
+                                {
+                                    log::error!(
+                                        "Failed to handle links with {err}. Stopping crawl."
+                                    );
+                                    let _ = consumer.consume_crawl_error(err.into());
+                                    return Self::pack_shutdown(
+                                        consumer,
+                                        context,
+                                        &target,
+                                        LinkStateKind::Discovered,
+                                    )
+                                    .await;
+                                }
+                            }
+                            continue;
+                        }
                         match Self::update_linkstate(
                             consumer,
                             context,
@@ -418,10 +773,131 @@ where
             if log::max_level() == LevelFilter::Trace {
                 log::trace!("Interval End: {}", OffsetDateTime::now_utc());
             }
+            // A paused crawl parks before every fetch, not only before the next seed.
+            let pause = context.crawl_events().pause();
+            if pause.is_paused() {
+                pause.wait_while_paused(&shutdown).await;
+                if shutdown.is_shutdown() {
+                    return Self::pack_shutdown(
+                        consumer,
+                        context,
+                        &target,
+                        LinkStateKind::Discovered,
+                    )
+                    .await;
+                }
+            }
             log::info!("Crawl: {}", target);
+            context.crawl_events().emit(CrawlEvent::Fetching {
+                worker_id: context.worker_id(),
+                url: target.clone(),
+            });
+            if configuration.robots_revalidation.is_some() {
+                manager.register_fetch(origin).await;
+            }
             let url_str = target.try_as_str().into_owned();
-            match self.client.retrieve(context, &url_str).await {
+            let validators = previous
+                .as_ref()
+                .and_then(|value: &SlimCrawlResult| value.meta.validators.as_ref());
+            let fetch_started = Instant::now();
+            let fetched = self.client.retrieve(context, &url_str, validators).await;
+            let fetch_duration = fetch_started.elapsed();
+            context.crawl_events().statistics().record_fetch(
+                context.worker_id(),
+                &target,
+                fetch_duration,
+                fetched.as_ref().map_or(0, |page| page.content.stored_size().unwrap_or_default()),
+            );
+            self.cooldown_until = interval_manager
+                .known_delay(&target)
+                .map(|delay| tokio::time::Instant::now() + delay);
+            match fetched {
                 Ok(page) => {
+                    reachability.record_response();
+                    if let Some(version) = page.http_version {
+                        context.crawl_events().metrics().record_http_version(version);
+                    }
+                    let tls =
+                        record_tls_certificate(context, &target, page.tls_certificate.as_ref())
+                            .await;
+                    if let Some(not_before) = throttled_until(
+                        page.status_code,
+                        page.headers.as_ref(),
+                        OffsetDateTime::now_utc(),
+                    ) {
+                        interval_manager.cool_down(&target, not_before).await;
+                        let attempts = throttled_attempts.saturating_add(1);
+                        if attempts <= configuration.max_throttled_retries {
+                            log::info!(
+                                "{target} is throttled ({}), retry {attempts} of {} after {not_before}.",
+                                page.status_code,
+                                configuration.max_throttled_retries
+                            );
+                            if Self::update_linkstate_with_payload(
+                                consumer,
+                                context,
+                                &target,
+                                LinkStateKind::Discovered,
+                                Some(
+                                    ThrottledRetry::new(attempts, not_before)
+                                        .to_payload()
+                                        .as_slice(),
+                                ),
+                            )
+                            .await
+                            .is_err()
+                            {
+                                log::error!("Failed recovery of linkstate of {target}.");
+                            }
+                            if let Err(err) = context
+                                .url_queue()
+                                .enqueue(UrlQueueElement::new(is_seed, 0, false, target.clone()))
+                                .await
+                            {
+                                consumer.consume_crawl_error(err.into())?;
+                            }
+                            continue;
+                        }
+                        log::info!(
+                            "{target} is still throttled ({}), store the response.",
+                            page.status_code
+                        );
+                    } else if page.status_code.is_server_error() {
+                        let kind = FetchFailureKind::ServerError;
+                        let attempts = failed_attempts.saturating_add(1);
+                        if let Some(not_before) = next_fetch_attempt(
+                            configuration.fetch_retry.as_ref(),
+                            kind,
+                            attempts,
+                            OffsetDateTime::now_utc(),
+                        ) {
+                            log::info!(
+                                "Retry {target} after {not_before}, attempt {attempts} failed ({}).",
+                                page.status_code
+                            );
+                            if Self::update_linkstate_with_payload(
+                                consumer,
+                                context,
+                                &target,
+                                LinkStateKind::Discovered,
+                                Some(kind.to_retry_payload(attempts, not_before).as_slice()),
+                            )
+                            .await
+                            .is_err()
+                            {
+                                log::error!("Failed recovery of linkstate of {target}.");
+                            }
+                            if let Err(err) = context
+                                .url_queue()
+                                .enqueue(UrlQueueElement::new(is_seed, 0, false, target.clone()))
+                                .await
+                            {
+                                consumer.consume_crawl_error(err.into())?;
+                            }
+                            continue;
+                        }
+                    }
+
                     if Self::update_linkstate_no_meta(
                         consumer,
                         context,
@@ -435,75 +911,75 @@ where
                     }
 
                     log::trace!("Fetched: {}", target);
-                    let mut response_data = ResponseData::from_response(page, target.clone());
-
-                    let file_information =
-                        determine_format_for_response(context, &mut response_data);
-
-                    let (language, analyzed, links) =
-                        match process(context, &response_data, &file_information).await {
-                            Ok(decoded) => {
-                                let lang = detect_language(context, &file_information, &decoded)
-                                    .ok()
-                                    .flatten();
-
-                                let result = context
-                                    .configs()
-                                    .crawl
-                                    .link_extractors
-                                    .extract_from_response(
+
+                    if page.not_modified {
+                        if let Some(previous) = previous.take() {
+                            log::info!("Not modified: {}", target);
+                            if let Some(links) = previous.meta.links {
+                                let links = HashSet::from_iter(links);
+                                if let Err(err) = self
+                                    .queue_on_seed_links(
+                                        context, &checker, &target, &links, &mut queue,
+                                    )
+                                    .await
+                                {
+                                    log::error!(
+                                        "Failed to handle links with {err}. Stopping crawl."
+                                    );
+                                    let _ = consumer.consume_crawl_error(err.into());
+                                    return Self::pack_shutdown(
+                                        consumer,
                                         context,
-                                        &response_data,
-                                        &file_information,
-                                        &decoded,
-                                        lang.as_ref(),
+                                        &target,
+                                        LinkStateKind::Discovered,
                                     )
                                     .await;
-
-                                (lang, decoded, result)
-                            }
-                            Err(err) => {
-                                log::error!(
-                                    "Failed to extract links for {} with {err}",
-                                    &response_data.url
-                                );
-                                let _ = Self::update_linkstate_no_meta(
-                                    consumer,
-                                    context,
-                                    &target,
-                                    LinkStateKind::InternalError,
-                                )
-                                .await;
-                                continue;
-                            }
-                        };
-                    log::trace!("Finished analysis: {}", target);
-
-                    if context.configs().crawl.store_only_html_in_warc {
-                        if file_information.format != InterpretedProcessibleFileFormat::HTML {
-                            response_data.content = match response_data.content {
-                                RawVecData::InMemory { data } => {
-                                    let path =
-                                        context.fs().create_unique_path_for_dat_file(&url_str);
-                                    match File::options().create_new(true).write(true).open(&path) {
-                                        Ok(mut out) => match out.write_all(&data) {
-                                            Ok(_) => RawData::from_external(path),
-                                            Err(err) => {
-                                                log::error!("Failed to store {} as file {} with {err}. Keep in memory.", url_str, path);
-                                                RawVecData::InMemory { data }
-                                            }
-                                        },
-                                        Err(err) => {
-                                            log::error!("Failed to store {} as file {} with {err}. Keep in memory.", url_str, path);
-                                            RawVecData::InMemory { data }
-                                        }
-                                    }
                                 }
-                                keep => keep,
                             }
+                            self.links_visited.insert(target.clone());
+                            if Self::update_linkstate_no_meta(
+                                consumer,
+                                context,
+                                &target,
+                                LinkStateKind::ProcessedAndStored,
+                            )
+                            .await
+                            .is_err()
+                            {
+                                log::error!("Failed setting of linkstate of {target}.");
+                            }
+                            context.crawl_events().emit(CrawlEvent::Crawled {
+                                url: target.clone(),
+                                status_code: StatusCode::NOT_MODIFIED,
+                                bytes: None,
+                                duration: Some(fetch_duration),
+                            });
+                            continue;
                         }
                     }
 
+                    let fetch_variants = !configuration.language_variants.is_empty()
+                        && page.status_code.is_success()
+                        && varies_by_language(page.headers.as_ref());
+                    let extract_started = Instant::now();
+                    let analyzed = analyze_page(context, page, &target).await;
+                    context
+                        .crawl_events()
+                        .statistics()
+                        .record_extract(context.worker_id(), extract_started.elapsed());
+                    let Some(mut result) = analyzed else {
+                        let _ = Self::update_linkstate_no_meta(
+                            consumer,
+                            context,
+                            &target,
+                            LinkStateKind::InternalError,
+                        )
+                        .await;
+                        continue;
+                    };
+                    result.meta.original_url = context.url_normalizer().take_original(&target);
+                    result.meta.tls = tls;
+
                     if shutdown.is_shutdown() {
                         return Self::pack_shutdown(
                             consumer,
@@ -513,31 +989,14 @@ where
                         )
                         .await;
                     }
-                    log::debug!(
-                        "Number of links in {}: {}",
-                        response_data.url,
-                        links.links.len()
-                    );
-                    let links = links.to_optional_links();
-                    log::trace!("Converted links");
-                    if let Some(links) = &links {
+                    if let Some(links) = &result.meta.links {
                         log::trace!("Handle extracted links");
-                        match context.handle_links(&target, links).await {
-                            Ok(value) => {
-                                log::debug!(
-                                    "{}: on_seed links: {}",
-                                    response_data.url,
-                                    value.len()
-                                );
-                                for in_seed in value {
-                                    if checker.check_if_allowed(self, &in_seed).await {
-                                        log::trace!("Queue: {}", target);
-                                        queue.push_back((false, in_seed));
-                                    } else {
-                                        log::debug!("Dropped: {in_seed}");
-                                    }
-                                }
-                            }
+                        let links = HashSet::from_iter(links.iter().cloned());
+                        match self
+                            .queue_on_seed_links(context, &checker, &target, &links, &mut queue)
+                            .await
+                        {
+                            Ok(_) => {}
                             Err(err) => {
                                 log::error!("Failed to handle links with {err}. Stopping crawl.");
                                 let _ = consumer.consume_crawl_error(err.into());
@@ -553,9 +1012,7 @@ where
                     } else {
                         log::trace!("No links");
                     }
-                    self.links_visited.insert(response_data.url.clone());
-                    let recognized_encoding = analyzed.encoding();
-                    drop(analyzed);
+                    self.links_visited.insert(result.meta.url.clone());
                     if shutdown.is_shutdown() {
                         return Self::pack_shutdown(
                             consumer,
@@ -566,17 +1023,14 @@ where
                         .await;
                     }
 
-                    log::trace!("CrawlResult {}", response_data.url);
-                    let result = CrawlResult::new(
-                        OffsetDateTime::now_utc(),
-                        response_data,
-                        links,
-                        recognized_encoding,
-                        file_information,
-                        language,
-                    );
                     log::debug!("Store {}", result.meta.url);
-                    match context.store_crawled_website(&result).await {
+                    let store_started = Instant::now();
+                    let stored = context.store_crawled_website(&result).await;
+                    context
+                        .crawl_events()
+                        .statistics()
+                        .record_store(context.worker_id(), store_started.elapsed());
+                    match stored {
                         Err(err) => {
                             log::error!("Failed to store data for {target}. Stopping crawl. {err}");
                             let _ = consumer.consume_crawl_error(err.into());
@@ -592,27 +1046,110 @@ where
                             log::debug!("Stored: {}", result.meta.url);
                         }
                     }
+                    register_unique_content(context, &result).await;
+                    observe_origin_metadata(context, &result);
+                    if let Err(err) = self
+                        .store_data_uris(context, &checker, &target, &result, &mut queue)
+                        .await
+                    {
+                        log::error!("Failed to handle links with {err}. Stopping crawl.");
+                        let _ = consumer.consume_crawl_error(err.into());
+                        return Self::pack_shutdown(
+                            consumer,
+                            context,
+                            &target,
+                            LinkStateKind::Discovered,
+                        )
+                        .await;
+                    }
 
-                    if Self::update_linkstate_no_meta(
+                    let expiry = result
+                        .meta
+                        .unavailable_after
+                        .as_ref()
+                        .and_then(UnavailableAfter::to_payload);
+                    if Self::update_linkstate_with_payload(
                         consumer,
                         context,
                         &target,
                         LinkStateKind::ProcessedAndStored,
+                        expiry.as_ref().map(|value| value.as_slice()),
                     )
                     .await
                     .is_err()
                     {
                         log::error!("Failed setting of linkstate of {target}.");
                     }
+                    context.crawl_events().emit(CrawlEvent::stored(
+                        target.clone(),
+                        &result,
+                        Some(fetch_duration),
+                    ));
+                    context
+                        .crawl_limits()
+                        .record_stored(result.content.stored_size().unwrap_or_default());
+                    if fetch_variants {
+                        self.store_language_variants(context, &mut interval_manager, &target)
+                            .await;
+                        self.cooldown_until = interval_manager
+                            .known_delay(&target)
+                            .map(|delay| tokio::time::Instant::now() + delay);
+                    }
                 }
                 Err(err) => {
-                    log::warn!("Failed to fetch {} with error {}", target, err);
+                    let kind = self.client.classify_error(&url_str, &err);
+                    reachability.record_failure(kind);
+                    log::warn!("Failed to fetch {} with error {} ({kind})", target, err);
+                    context.crawl_events().emit(CrawlEvent::Failed {
+                        url: target.clone(),
+                        kind,
+                        reason: err.to_string(),
+                    });
 
-                    if Self::update_linkstate_no_meta(
+                    let attempts = failed_attempts.saturating_add(1);
+                    // A seed failing to connect is left to the rediscovery of its origin.
+                    let next_attempt = if is_seed
+                        && kind == FetchFailureKind::Connect
+                        && configuration.rediscovery.is_some()
+                    {
+                        None
+                    } else {
+                        next_fetch_attempt(
+                            configuration.fetch_retry.as_ref(),
+                            kind,
+                            attempts,
+                            OffsetDateTime::now_utc(),
+                        )
+                    };
+                    if let Some(not_before) = next_attempt {
+                        log::info!(
+                            "Retry {target} after {not_before}, attempt {attempts} failed ({kind})."
+                        );
+                        if Self::update_linkstate_with_payload(
+                            consumer,
+                            context,
+                            &target,
+                            LinkStateKind::Discovered,
+                            Some(kind.to_retry_payload(attempts, not_before).as_slice()),
+                        )
+                        .await
+                        .is_err()
+                        {
+                            log::error!("Failed recovery of linkstate of {target}.");
+                        }
+                        if let Err(err) = context
+                            .url_queue()
+                            .enqueue(UrlQueueElement::new(is_seed, 0, false, target.clone()))
+                            .await
+                        {
+                            consumer.consume_crawl_error(err.into())?;
+                        }
+                    } else if Self::update_linkstate_with_payload(
                         consumer,
                         context,
                         &target,
                         LinkStateKind::InternalError,
+                        Some(kind.to_payload().as_slice()),
                     )
                     .await
                     .is_err()
@@ -622,22 +1159,546 @@ where
                 }
             }
         }
+
+        if reachability.is_unreachable() {
+            self.rediscover_or_give_up(context, consumer, &interval_manager, rediscovery_attempts)
+                .await?;
+        }
         Ok(())
     }
-}
-
-// Helper structs
-
-/// Internal helper for representing cause for not allowed
-#[derive(Debug, EnumString, strum::Display)]
-enum NotAllowedReasoning {
-    IsAlreadyVisited,
-    BlacklistHasMatch,
-    RobotSaysNo,
-    IsNotInBudget,
-}
 
-struct UrlChecker<'a, R: RobotsInformation, B: Blacklist> {
+    /// Requeues the seed for another attempt after every request to its origin failed to
+    /// connect. The seed waits in the queue until the delay passed, like a throttled url.
+    /// If the attempts are exhausted the origin is given up and reported as unreachable.
+    async fn rediscover_or_give_up<C, R, E, EC>(
+        &self,
+        context: &C,
+        consumer: &EC,
+        interval_manager: &InvervalManager<'_, Client, R>,
+        attempts: u8,
+    ) -> Result<(), EC::Error>
+    where
+        C: SupportsConfigs + SupportsLinkState + SupportsUrlQueue + SupportsCrawlEvents,
+        R: RobotsInformation,
+        E: From<<<C as SupportsLinkState>::LinkStateManager as LinkStateManager>::Error>
+            + From<QueueError>,
+        EC: ErrorConsumer<E>,
+    {
+        let seed = self.seed.url();
+        match Rediscovery::plan(&context.configs().crawl, attempts, OffsetDateTime::now_utc()) {
+            Rediscovery::Scheduled(retry) => {
+                log::warn!(
+                    "The origin {} of {seed} is unreachable, try again after {} (attempt {}).",
+                    self.seed.origin(),
+                    retry.not_before,
+                    retry.attempts
+                );
+                interval_manager.cool_down(seed, retry.not_before).await;
+                Self::update_linkstate_with_payload(
+                    consumer,
+                    context,
+                    seed,
+                    LinkStateKind::Discovered,
+                    Some(retry.to_payload().as_slice()),
+                )
+                .await?;
+                if let Err(err) = context
+                    .url_queue()
+                    .enqueue(UrlQueueElement::new(true, 0, false, seed.clone()))
+                    .await
+                {
+                    consumer.consume_crawl_error(err.into())?;
+                }
+            }
+            Rediscovery::GiveUp { attempts } => {
+                log::error!(
+                    "The origin {} of {seed} is unreachable, give up after {attempts} attempts to discover it again.",
+                    self.seed.origin()
+                );
+                context.crawl_events().emit(CrawlEvent::OriginUnreachable {
+                    origin: self.seed.origin().clone(),
+                    seed: seed.clone(),
+                    attempts,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Fetches [target] once per configured language variant and stores every variant as
+    /// a separate record. The links of the variants are not followed, the link state of
+    /// [target] is not touched.
+    async fn store_language_variants<C, R>(
+        &self,
+        context: &C,
+        interval_manager: &mut InvervalManager<'_, Client, R>,
+        target: &UrlWithDepth,
+    ) where
+        C: SupportsConfigs
+            + SupportsFileSystemAccess
+            + SupportsGdbrRegistry
+            + SupportsCrawlEvents
+            + SupportsCrawlResults,
+        R: RobotsInformation,
+    {
+        let url_str = target.try_as_str().into_owned();
+        for language in &context.configs().crawl.language_variants {
+            interval_manager.wait(target).await;
+            log::info!("Crawl the language variant {language} of {target}");
+            let fetched = self
+                .client
+                .retrieve_in_language(context, &url_str, language)
+                .await;
+            let page = match fetched {
+                Ok(page) => page,
+                Err(err) => {
+                    log::warn!(
+                        "Failed to fetch the language variant {language} of {target}: {err}"
+                    );
+                    continue;
+                }
+            };
+            let Some(mut result) = analyze_page(context, page, target).await else {
+                continue;
+            };
+            result.meta.language_variant = Some(language.clone());
+            if let Err(err) = context.store_crawled_website(&result).await {
+                log::error!(
+                    "Failed to store the language variant {language} of {target}: {err}"
+                );
+            }
+        }
+    }
+
+    /// Stores the data uris found in the [result] of [target] as separate records under the
+    /// url of [data_uri_target], they are never queued. The links in a stored data uri are
+    /// handled like the links of [target], the data uris in it are stored up to the
+    /// max_extraction_depth.
+    async fn store_data_uris<C, R, B>(
+        &self,
+        context: &C,
+        checker: &UrlChecker<'_, R, B>,
+        target: &UrlWithDepth,
+        result: &CrawlResult,
+        queue: &mut VecDeque<(bool, UrlWithDepth)>,
+    ) -> Result<(), <C as SupportsLinkSeeding>::Error>
+    where
+        C: SupportsConfigs
+            + SupportsFileSystemAccess
+            + SupportsGdbrRegistry
+            + SupportsCrawlEvents
+            + SupportsCrawlResults
+            + SupportsLinkSeeding,
+        R: RobotsInformation,
+        B: Blacklist,
+    {
+        let crawl = &context.configs().crawl;
+        let crawl_embedded_data = target
+            .atra_origin()
+            .and_then(|origin| crawl.origin_profile(&origin))
+            .and_then(|found| found.crawl_embedded_data)
+            .unwrap_or(crawl.crawl_embedded_data);
+        let within_depth =
+            |nesting: usize| crawl.max_extraction_depth.map_or(true, |max| nesting <= max);
+        if !crawl_embedded_data || !within_depth(1) {
+            return Ok(());
+        }
+        // The data uri, the url of the record it was found in and its nesting.
+        let mut pending: Vec<(UrlWithDepth, UrlWithDepth, usize)> = data_uris_in(result)
+            .map(|uri| (uri.clone(), target.clone(), 1))
+            .collect();
+        while let Some((uri, parent, nesting)) = pending.pop() {
+            let uri = uri.try_as_str();
+            let Some(data_target) = data_uri_target(&parent, &uri) else {
+                continue;
+            };
+            let Some(decoded) = DataUri::decode(&uri) else {
+                log::debug!("Failed to decode a data uri in {parent}.");
+                continue;
+            };
+            if decoded.payload.len() as u64 > crawl.max_data_uri_size {
+                log::debug!(
+                    "Skip the data uri {data_target} with {} bytes.",
+                    decoded.payload.len()
+                );
+                context.crawl_events().metrics().record_skipped_data_uri();
+                continue;
+            }
+            let headers = decoded
+                .media_type
+                .and_then(|value| HeaderValue::from_str(&value).ok())
+                .map(|value| HeaderMap::from_iter([(CONTENT_TYPE, value)]));
+            let page = FetchedRequestData {
+                content: RawData::from_vec(decoded.payload),
+                headers,
+                raw_headers: None,
+                status_code: StatusCode::OK,
+                final_url: None,
+                address: None,
+                defect: false,
+                not_modified: false,
+                proxy: None,
+                rejected: None,
+                truncated: false,
+                tls_certificate: None,
+                request_headers: None,
+                http_version: None,
+            };
+            let Some(data_result) = analyze_page(context, page, &data_target).await else {
+                continue;
+            };
+            if within_depth(nesting + 1) {
+                pending.extend(
+                    data_uris_in(&data_result)
+                        .map(|uri| (uri.clone(), data_target.clone(), nesting + 1)),
+                );
+            }
+            if let Some(links) = &data_result.meta.links {
+                let links = HashSet::from_iter(links.iter().cloned());
+                self.queue_on_seed_links(context, checker, &data_target, &links, queue)
+                    .await?;
+            }
+            log::debug!("Store the data uri {data_target}");
+            if let Err(err) = context.store_crawled_website(&data_result).await {
+                log::error!("Failed to store the data uri {data_target}: {err}");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The data uris among the links of the [result].
+fn data_uris_in(result: &CrawlResult) -> impl Iterator<Item = &UrlWithDepth> {
+    result.meta.links.iter().flatten().filter_map(|link| match link {
+        ExtractedLink::Data { url, .. } => Some(url),
+        _ => None,
+    })
+}
+
+/// Returns true if the response with the [headers] varies by the `Accept-Language`.
+fn varies_by_language(headers: Option<&HeaderMap>) -> bool {
+    headers.is_some_and(|headers| {
+        headers
+            .get_all(VARY)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .any(|value| value.trim().eq_ignore_ascii_case(ACCEPT_LANGUAGE.as_str()))
+    })
+}
+
+/// Analyzes the fetched [page] of [target] and creates its [CrawlResult], the extracted
+/// links are only stored in the result. Returns None if the page can not be processed.
+/// Failed extractor methods are emitted as [CrawlEvent::ExtractorFailed].
+pub(crate) async fn analyze_page<C>(
+    context: &C,
+    mut page: FetchedRequestData,
+    target: &UrlWithDepth,
+) -> Option<CrawlResult>
+where
+    C: SupportsConfigs + SupportsFileSystemAccess + SupportsGdbrRegistry + SupportsCrawlEvents,
+{
+    let url_str = target.try_as_str().into_owned();
+    let download_rejection = page.rejected.clone();
+    let truncated = page.truncated;
+    let raw_headers = page
+        .raw_headers
+        .take()
+        .filter(|_| context.configs().crawl.faithful_headers);
+    let mut response_data = ResponseData::from_response(page, target.clone());
+
+    let file_information = determine_format_for_response(context, &mut response_data);
+
+    let (language, gdbr_score, analyzed, links, directives) =
+        match process(context, &response_data, &file_information).await {
+            Ok(decoded) => {
+                let lang = detect_language(context, &file_information, &decoded)
+                    .ok()
+                    .flatten();
+                let gdbr_score = score_gdbr(context, &file_information, &decoded, lang.as_ref());
+
+                let directives = robots_meta_directives(
+                    &context.configs().crawl,
+                    &response_data,
+                    &file_information,
+                    &decoded,
+                );
+
+                let error_pages = context.configs().crawl.extract_from_error_pages;
+                let is_error_page = !response_data.status_code.is_success();
+                let mut result = if !context.configs().crawl.extract_links {
+                    ExtractorResult::default()
+                } else if directives.nofollow && context.configs().crawl.respect_robots_meta {
+                    log::debug!("Respecting the robots nofollow of {}", target);
+                    ExtractorResult::default()
+                } else if is_error_page && error_pages == ErrorPageExtraction::Never {
+                    log::debug!(
+                        "Not following the links of the error page {} ({})",
+                        target,
+                        response_data.status_code
+                    );
+                    ExtractorResult::default()
+                } else {
+                    let crawl = &context.configs().crawl;
+                    let extractors = match target.atra_origin() {
+                        Some(origin) => crawl.link_extractors_for(&origin),
+                        None => &crawl.link_extractors,
+                    };
+                    extractors
+                        .extract_from_response(
+                            context,
+                            &response_data,
+                            &file_information,
+                            &decoded,
+                            lang.as_ref(),
+                        )
+                        .await
+                };
+                if is_error_page {
+                    result.links = error_pages.restrict(target, std::mem::take(&mut result.links));
+                }
+                for (method, failure) in &result.failed_extractors {
+                    context.crawl_events().emit(CrawlEvent::ExtractorFailed {
+                        url: target.clone(),
+                        method: *method,
+                        failure: failure.clone(),
+                    });
+                }
+
+                (lang, gdbr_score, decoded, result, directives)
+            }
+            Err(err) => {
+                log::error!(
+                    "Failed to extract links for {} with {err}",
+                    &response_data.url
+                );
+                return None;
+            }
+        };
+    log::trace!("Finished analysis: {}", target);
+
+    let publication_window = context.configs().crawl.publication_window.as_ref();
+    let publication = publication_window.and_then(|_| {
+        let html = if file_information.format == InterpretedProcessibleFileFormat::HTML {
+            analyzed.as_in_memory().map(String::as_str)
+        } else {
+            None
+        };
+        PublicationEstimate::estimate(target, response_data.headers.as_ref(), html)
+    });
+
+    let origin_signals = context.configs().crawl.origin_metadata.is_some().then(|| {
+        let html = if file_information.format == InterpretedProcessibleFileFormat::HTML {
+            analyzed.as_in_memory().map(String::as_str)
+        } else {
+            None
+        };
+        PageSignals::extract(target, response_data.headers.as_ref(), html)
+    });
+
+    if directives.noindex && context.configs().crawl.respect_noindex {
+        log::debug!(
+            "Respecting the robots noindex of {}, the body is not archived.",
+            target
+        );
+        discard_body(context, &mut response_data, target);
+    } else if publication_window.is_some_and(|window| !window.stores(publication.as_ref())) {
+        log::debug!(
+            "{} is not published within the publication window, the body is not archived.",
+            target
+        );
+        discard_body(context, &mut response_data, target);
+    }
+
+    if context.configs().crawl.store_only_html_in_warc {
+        if file_information.format != InterpretedProcessibleFileFormat::HTML {
+            response_data.content = match response_data.content {
+                RawVecData::InMemory { data } => {
+                    let path = context.fs().create_unique_path_for_dat_file(&url_str);
+                    match File::options().create_new(true).write(true).open(&path) {
+                        Ok(mut out) => match out.write_all(&data) {
+                            Ok(_) => {
+                                drop(out);
+                                match context.fs().finalize_data_file(path.clone()) {
+                                    Ok(path) => RawData::from_external(path),
+                                    Err(err) => {
+                                        log::error!("Failed to finalize the data file {path} of {url_str} with {err}.");
+                                        RawData::from_external(path)
+                                    }
+                                }
+                            }
+                            Err(err) => {
+                                log::error!(
+                                    "Failed to store {} as file {} with {err}. Keep in memory.",
+                                    url_str,
+                                    path
+                                );
+                                RawVecData::InMemory { data }
+                            }
+                        },
+                        Err(err) => {
+                            log::error!(
+                                "Failed to store {} as file {} with {err}. Keep in memory.",
+                                url_str,
+                                path
+                            );
+                            RawVecData::InMemory { data }
+                        }
+                    }
+                }
+                keep => keep,
+            }
+        }
+    }
+
+    log::debug!(
+        "Number of links in {}: {}",
+        response_data.url,
+        links.links.len()
+    );
+    let links = links.to_optional_links();
+    let recognized_encoding = analyzed.encoding();
+    let unavailable_after = if file_information.format == InterpretedProcessibleFileFormat::HTML {
+        analyzed
+            .as_in_memory()
+            .and_then(|html| UnavailableAfter::from_html(html))
+    } else {
+        None
+    };
+    // A discarded body leaves no text behind either.
+    let has_body = !matches!(response_data.content, RawData::None);
+    let text = if context.configs().crawl.text_sidecar && has_body {
+        match plain_text(&file_information, &analyzed) {
+            Ok(text) => text,
+            Err(err) => {
+                log::warn!("Failed to read the plain text of {target}: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let simhash = match read_text_sample(&analyzed, text_sample_limit(context)) {
+        Ok(sample) => sample.map(|text| SimHash::of_text(&text)),
+        Err(err) => {
+            log::warn!("Failed to read the text sample for the simhash of {target}: {err}");
+            None
+        }
+    };
+    drop(analyzed);
+    let fuzzy_hash = match FuzzyHash::of_content(&response_data.content) {
+        Ok(fuzzy_hash) => fuzzy_hash,
+        Err(err) => {
+            log::warn!("Failed to compute the fuzzy hash of {target}: {err}");
+            None
+        }
+    };
+    log::trace!("CrawlResult {}", response_data.url);
+    let mut result = CrawlResult::new(
+        OffsetDateTime::now_utc(),
+        response_data,
+        links,
+        recognized_encoding,
+        file_information,
+        language,
+    );
+    if let Some(unavailable_after) = unavailable_after {
+        result.meta.add_unavailable_after(unavailable_after);
+    }
+    result.meta.gdbr_score = gdbr_score;
+    result.meta.publication = publication;
+    result.meta.tag = target
+        .atra_origin()
+        .and_then(|origin| context.configs().crawl.seed_tags.get(&origin).cloned());
+    result.meta.download_rejection = download_rejection;
+    result.meta.truncated = truncated;
+    result.meta.raw_headers = raw_headers;
+    result.text = text;
+    result.origin_signals = origin_signals;
+    result.meta.fuzzy_hash = fuzzy_hash;
+    result.meta.simhash = simhash;
+    result
+        .meta
+        .apply_header_persistence(&context.configs().crawl.header_persistence);
+    Some(result)
+}
+
+/// Removes the body of [target] from the [response], the page is still recorded as crawled.
+fn discard_body<C>(context: &C, response: &mut ResponseData, target: &UrlWithDepth)
+where
+    C: SupportsFileSystemAccess,
+{
+    if let RawData::ExternalFile { path } = std::mem::take(&mut response.content) {
+        if let Err(err) = context.fs().cleanup_data_file(&path) {
+            log::warn!("Failed to remove {path} of the discarded page {target}: {err}");
+        }
+    }
+}
+
+/// Scores a text sample of the [decoded] document with the GDBR classifier for its [language].
+/// Documents that are not kept in memory are sampled from their file. Documents with an
+/// uncertain language are not scored.
+pub(crate) fn score_gdbr<C>(
+    context: &C,
+    file_information: &AtraFileInformation,
+    decoded: &Decoded<String, Utf8PathBuf>,
+    language: Option<&LanguageInformation>,
+) -> Option<GdbrScore>
+where
+    C: SupportsConfigs + SupportsGdbrRegistry,
+{
+    if language.is_some_and(|value| value.is_uncertain()) {
+        log::debug!("The language is uncertain, the GDBR classifier is skipped.");
+        return None;
+    }
+    let identifier = context
+        .gdbr_registry()?
+        .get_by_language_or_default(language)?;
+    let sample = match read_text_sample(decoded, text_sample_limit(context)) {
+        Ok(sample) => sample?,
+        Err(err) => {
+            log::warn!("Failed to read the text sample for the GDBR score: {err}");
+            return None;
+        }
+    };
+    identifier.score_document(file_information.format, &sample)
+}
+
+/// Reads the robots directives of the `X-Robots-Tag` header and, for html, of the meta tags.
+fn robots_meta_directives(
+    config: &CrawlConfig,
+    response: &ResponseData,
+    file_information: &AtraFileInformation,
+    decoded: &Decoded<String, Utf8PathBuf>,
+) -> RobotsMetaDirectives {
+    if !config.respect_robots_meta && !config.respect_noindex {
+        return RobotsMetaDirectives::default();
+    }
+    let from_headers = response
+        .headers
+        .as_ref()
+        .map(RobotsMetaDirectives::from_headers)
+        .unwrap_or_default();
+    if file_information.format == InterpretedProcessibleFileFormat::HTML {
+        if let Some(html) = decoded.as_in_memory() {
+            return from_headers.merge(RobotsMetaDirectives::from_html(html));
+        }
+    }
+    from_headers
+}
+
+// Helper structs
+
+/// Internal helper for representing cause for not allowed
+#[derive(Debug, EnumString, strum::Display)]
+enum NotAllowedReasoning {
+    IsAlreadyVisited,
+    BlacklistHasMatch,
+    RobotSaysNo,
+    IsNotInBudget,
+}
+
+struct UrlChecker<'a, R: RobotsInformation, B: Blacklist> {
     budget: &'a BudgetSetting,
     configured_robots: &'a R,
     blacklist: &'a B,
@@ -710,28 +1771,64 @@ impl<'a, R: RobotsInformation, B: Blacklist> UrlChecker<'a, R, B> {
 
 #[cfg(test)]
 mod test {
-    use crate::config::{BudgetSetting, Config as AtraConfig, CrawlConfig};
-    use crate::contexts::traits::{SupportsCrawling, SupportsUrlQueue};
-    use crate::crawl::CrawlResult;
+    use super::analyze_page;
+    use super::data_uris::data_uri_target;
+    use crate::client::traits::FetchFailureKind;
+    use crate::config::{
+        BudgetSetting, Config as AtraConfig, CrawlConfig, ErrorPageExtraction, FetchRetryConfig,
+        PublicationWindow, PublicationWindowMode, RediscoveryConfig, RobotsRevalidationConfig,
+    };
+    use crate::contexts::traits::{
+        SupportsCrawlEvents, SupportsCrawlLimits, SupportsCrawlResults, SupportsCrawling,
+        SupportsLinkState, SupportsSlimCrawlResults, SupportsUrlQueue,
+    };
+    use crate::crawl::crawler::sitemaps::test::{gzip, SITEMAP, SITEMAP_INDEX};
+    use crate::crawl::db::CrawlDB;
+    use crate::crawl::{
+        AuxiliaryDocument, Baseline, CrawlEvent, CrawlLimits, CrawlResult, PublicationConfidence,
+        PublicationEstimate, PublicationSource, SlimCrawlResult, StopReason, StoredDataHint,
+    };
     use crate::data::RawData;
-    use crate::fetching::FetchedRequestData;
-    use crate::queue::UrlQueue;
-    use crate::runtime::ShutdownPhantom;
+    use crate::database::open_db;
+    use crate::extraction::extractor::{ApplyWhen, Extractor, ExtractorCommand};
+    use crate::extraction::extractor_method::mock::{self, MockedExtractor};
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::extraction::marker::ExtractorMethodHint;
+    use crate::extraction::ExtractedLink;
+    use crate::fetching::{FetchedRequestData, RediscoveryRetry, ResponseData, ThrottledRetry};
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
+    use crate::link_state::{
+        LinkStateDB, LinkStateKind, LinkStateLike, LinkStateManager, LinkStateRockDB,
+    };
+    use crate::queue::{UrlQueue, UrlQueueElement};
+    use crate::runtime::{GracefulShutdown, ShutdownPhantom};
     use crate::seed::UnguardedSeed;
-    use crate::test_impls::{FakeClientProvider, FakeResponse, TestContext, TestErrorConsumer};
+    use crate::test_impls::{
+        FakeClientProvider, FakeResponse, FakeResponseError, TestContext, TestErrorConsumer,
+    };
     use crate::toolkit::header_map_extensions::optional_header_map;
     use crate::toolkit::serde_ext::status_code;
-    use crate::url::AtraOriginProvider;
+    use crate::url::filter::{UrlFilterConfig, UrlFilterDecision};
+    use crate::url::{AtraOriginProvider, AtraUri, UrlWithDepth};
+    use crate::web_graph::WebGraphEntry;
+    use camino::Utf8Path;
+    use camino_tempfile::Utf8TempDir;
     use log::LevelFilter;
     use log4rs::append::file::FileAppender;
     use log4rs::config::{Appender, Config, Logger, Root};
     use log4rs::encode::pattern::PatternEncoder;
-    use reqwest::header::HeaderMap;
+    use reqwest::header::{
+        HeaderMap, HeaderValue, CONTENT_LANGUAGE, CONTENT_TYPE, ETAG, RETRY_AFTER, VARY,
+    };
     use reqwest::StatusCode;
     use serde::de::DeserializeOwned;
     use serde::{Deserialize, Serialize};
+    use std::collections::HashSet;
     use std::fmt::Debug;
-    use time::Duration;
+    use std::num::NonZeroU64;
+    use std::sync::Arc;
+    use time::{Date, Duration, Month, OffsetDateTime};
 
     fn init() {
         // let stdout = ConsoleAppender::builder().build();
@@ -947,6 +2044,2084 @@ mod test {
         }
     }
 
+    #[tokio::test]
+    async fn pausing_a_running_task_stops_its_next_fetch() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.respect_robots_txt = false;
+        // The task waits between its fetches, the pause lands in the middle of the task.
+        config.delay = Some(Duration::milliseconds(200));
+
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        for (page, body) in [
+            (
+                "https://www.example.com/",
+                "<html><body><a href=\"/second.html\">second</a><a href=\"/third.html\">third</a></body></html>",
+            ),
+            (
+                "https://www.example.com/second.html",
+                "<html><body>Second</body></html>",
+            ),
+            (
+                "https://www.example.com/third.html",
+                "<html><body>Third</body></html>",
+            ),
+        ] {
+            context.provider().insert(
+                page.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(body.as_bytes().to_vec()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+
+        let pause = context.crawl_events().pause().clone();
+        let mut events = context.crawl_events().subscribe();
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        let consumer = TestErrorConsumer::new();
+        let crawling = crawl_task.run(&context, ShutdownPhantom::<true>, &consumer);
+        let control = async {
+            loop {
+                if let CrawlEvent::Crawled { .. } = events.recv().await.unwrap() {
+                    break;
+                }
+            }
+            assert!(pause.pause());
+            // The task is parked with the links of the seed in its queue.
+            tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            assert_eq!(1, context.provider().requests().len());
+            let resumed_at = tokio::time::Instant::now();
+            assert!(pause.resume());
+            resumed_at
+        };
+        let (result, resumed_at) =
+            tokio::time::timeout(std::time::Duration::from_secs(30), async {
+                tokio::join!(crawling, control)
+            })
+            .await
+            .unwrap();
+        result.expect("The crawl should not fail.");
+
+        let requests = context.provider().requests();
+        assert_eq!(3, requests.len(), "{requests:?}");
+        assert!(requests[1..].iter().all(|(_, at)| *at >= resumed_at));
+    }
+
+    #[tokio::test]
+    async fn consecutive_fetches_to_the_same_origin_are_spaced_out() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.delay = Some(Duration::milliseconds(300));
+
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+
+        let page = |body: &'static str| {
+            Ok(FakeResponse::new(
+                Some(FetchedRequestData::new(
+                    RawData::from_vec(body.as_bytes().to_vec()),
+                    None,
+                    StatusCode::OK,
+                    None,
+                    None,
+                    false,
+                )),
+                1,
+            ))
+        };
+
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            page("<html><body><a href=\"/second.html\">second</a></body></html>"),
+        );
+        context.provider().insert(
+            "https://www.example.com/second.html".parse().unwrap(),
+            page("<html><body>The end.</body></html>"),
+        );
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let requests = context
+            .provider()
+            .requests()
+            .into_iter()
+            .filter(|(url, _)| !url.as_str().ends_with("robots.txt"))
+            .collect::<Vec<_>>();
+
+        assert_eq!(2, requests.len(), "{requests:?}");
+        let spacing = requests[1].1 - requests[0].1;
+        assert!(
+            spacing >= std::time::Duration::from_millis(300),
+            "The requests were only {}ms apart.",
+            spacing.as_millis()
+        );
+
+        crawl_task.wait_for_cooldown().await;
+        let since_last = tokio::time::Instant::now() - requests[1].1;
+        assert!(
+            since_last >= std::time::Duration::from_millis(300),
+            "The cooldown ended {}ms after the last request.",
+            since_last.as_millis()
+        );
+    }
+
+    #[tokio::test]
+    async fn archives_sitemaps_as_auxiliary_documents() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+
+        let page = |body: Vec<u8>| {
+            Ok(FakeResponse::new(
+                Some(FetchedRequestData::new(
+                    RawData::from_vec(body),
+                    None,
+                    StatusCode::OK,
+                    None,
+                    None,
+                    false,
+                )),
+                1,
+            ))
+        };
+
+        let url =
+            |path: &str| -> AtraUri { format!("https://www.example.com/{path}").parse().unwrap() };
+
+        context.provider().insert(
+            url("robots.txt"),
+            page(
+                b"User-agent: *\nAllow: /\nSitemap: https://www.example.com/sitemap_index.xml\n"
+                    .to_vec(),
+            ),
+        );
+        context.provider().insert(
+            url("sitemap_index.xml"),
+            page(SITEMAP_INDEX.as_bytes().to_vec()),
+        );
+        let compressed = gzip(SITEMAP);
+        context
+            .provider()
+            .insert(url("sitemap_pages.xml.gz"), page(compressed.clone()));
+        for path in ["", "about.html", "news.html"] {
+            context
+                .provider()
+                .insert(url(path), page(b"<html><body>Hello</body></html>".to_vec()));
+        }
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let (crawled, _) = context.get_all_crawled_websites();
+        let index = crawled
+            .get(&url("sitemap_index.xml"))
+            .expect("The sitemap index should be archived.");
+        let Some(AuxiliaryDocument::Sitemap(summary)) = &index.meta.auxiliary else {
+            panic!("The sitemap index is not marked as sitemap.")
+        };
+        assert!(summary.is_index());
+        assert_eq!(2, summary.sitemap_count);
+
+        let pages = crawled
+            .get(&url("sitemap_pages.xml.gz"))
+            .expect("The sitemap should be archived.");
+        assert_eq!(Some(&compressed), pages.content.as_in_memory());
+        let Some(AuxiliaryDocument::Sitemap(summary)) = &pages.meta.auxiliary else {
+            panic!("The sitemap is not marked as sitemap.")
+        };
+        assert!(summary.compressed);
+        assert_eq!(3, summary.url_count);
+        assert_eq!(Some(&2), summary.changefreq.get("daily"));
+
+        assert!(!crawled.contains_key(&url("sitemap_news.xml")));
+        for path in ["", "about.html", "news.html"] {
+            let page = crawled
+                .get(&url(path))
+                .expect("The listed urls should be crawled.");
+            assert_eq!(None, page.meta.auxiliary);
+        }
+
+        let sitemap_links = context
+            .link_net_manager
+            .entries()
+            .await
+            .into_iter()
+            .filter_map(|entry| match entry {
+                WebGraphEntry::SitemapLink { sitemap, to } => {
+                    Some((sitemap.to_string(), to.to_string()))
+                }
+                _ => None,
+            })
+            .collect::<HashSet<_>>();
+        let expected = [
+            ("sitemap_index.xml", "sitemap_pages.xml.gz"),
+            ("sitemap_index.xml", "sitemap_news.xml"),
+            ("sitemap_pages.xml.gz", ""),
+            ("sitemap_pages.xml.gz", "about.html"),
+            ("sitemap_pages.xml.gz", "news.html"),
+        ]
+        .map(|(sitemap, to)| (url(sitemap).to_string(), url(to).to_string()));
+        assert_eq!(HashSet::from(expected), sitemap_links);
+    }
+
+    fn recrawl_context() -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: Some(Duration::days(1)),
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+
+        TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        )
+    }
+
+    fn fake_page(body: &'static str, status_code: StatusCode) -> FakeResponse {
+        FakeResponse::new(
+            Some(FetchedRequestData::new(
+                RawData::from_vec(body.as_bytes().to_vec()),
+                None,
+                status_code,
+                None,
+                None,
+                false,
+            )),
+            1,
+        )
+    }
+
+    #[tokio::test]
+    async fn not_modified_keeps_the_previous_result() {
+        let context = recrawl_context();
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"v1\""));
+        let mut links = HashSet::new();
+        links.insert(ExtractedLink::OnSeed {
+            url: UrlWithDepth::with_base(&seed, "https://www.example.com/second.html").unwrap(),
+            extraction_method: ExtractorMethodHint::new_without_meta(ExtractorMethod::HtmlV1),
+        });
+        let previous = CrawlResult::new(
+            OffsetDateTime::now_utc() - Duration::days(2),
+            ResponseData::new(
+                RawData::from_vec(b"<html><body>Version 1</body></html>".to_vec()),
+                seed.clone(),
+                Some(headers),
+                StatusCode::OK,
+                None,
+            ),
+            Some(links),
+            Some(encoding_rs::UTF_8),
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::HTML, None, None),
+            None,
+        );
+        context.store_crawled_website(&previous).await.unwrap();
+        let stored_before = context
+            .ct_crawled_websites
+            .load(std::sync::atomic::Ordering::Relaxed);
+
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            Ok(fake_page("", StatusCode::NOT_MODIFIED)),
+        );
+        context.provider().insert(
+            "https://www.example.com/second.html".parse().unwrap(),
+            Ok(fake_page("<html><body>The end.</body></html>", StatusCode::OK)),
+        );
+
+        let before = OffsetDateTime::now_utc();
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let sent = context
+            .provider()
+            .validators()
+            .into_iter()
+            .find(|(url, _)| url == seed.url())
+            .and_then(|(_, validators)| validators)
+            .expect("The recrawl has to be conditional.");
+        assert_eq!(Some("\"v1\""), sent.etag.as_deref());
+
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(previous.meta, stored.meta);
+        assert_eq!(
+            stored_before + 1,
+            context
+                .ct_crawled_websites
+                .load(std::sync::atomic::Ordering::Relaxed),
+            "Only the second page is allowed to be stored."
+        );
+
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        assert!(state.timestamp() >= before);
+
+        assert!(context
+            .provider()
+            .requests()
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html"));
+    }
+
+    /// Crawls the seed of [recrawl_context] with a stored page crawled [age] ago, returns
+    /// true if the seed was fetched again.
+    async fn refetched_after(age: Duration) -> bool {
+        let context = recrawl_context();
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let previous = CrawlResult::new(
+            OffsetDateTime::now_utc() - age,
+            ResponseData::new(
+                RawData::from_vec(b"<html><body>Version 1</body></html>".to_vec()),
+                seed.clone(),
+                None,
+                StatusCode::OK,
+                None,
+            ),
+            None,
+            Some(encoding_rs::UTF_8),
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::HTML, None, None),
+            None,
+        );
+        context.store_crawled_website(&previous).await.unwrap();
+        context.provider().insert(
+            seed.url().clone(),
+            Ok(fake_page("<html><body>Version 2</body></html>", StatusCode::OK)),
+        );
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+        context
+            .provider()
+            .requests()
+            .iter()
+            .any(|(url, _)| url == seed.url())
+    }
+
+    #[tokio::test]
+    async fn a_stored_page_is_fetched_again_once_the_recrawl_interval_is_over() {
+        assert!(!refetched_after(Duration::days(1) - Duration::minutes(1)).await);
+        assert!(refetched_after(Duration::days(1)).await);
+        assert!(refetched_after(Duration::days(2)).await);
+    }
+
+    #[tokio::test]
+    async fn not_modified_without_validator_is_not_trusted() {
+        let context = recrawl_context();
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            Ok(fake_page("", StatusCode::NOT_MODIFIED)),
+        );
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let sent = context
+            .provider()
+            .validators()
+            .into_iter()
+            .find(|(url, _)| url == seed.url())
+            .expect("The seed has to be requested.");
+        assert!(sent.1.is_none());
+
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The response has to be stored like any other.");
+        assert_eq!(StatusCode::NOT_MODIFIED, stored.meta.status_code);
+    }
+
+    /// Recrawls the urls of [SITEMAP] two days after they were crawled, returns the
+    /// fetched pages besides the seed.
+    async fn recrawl_with_sitemap_hints(trust_sitemap_hints: bool) -> Vec<String> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: Some(Duration::days(10)),
+            request_timeout: None,
+        };
+        config.trust_sitemap_hints = trust_sitemap_hints;
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        for path in ["about.html", "news.html"] {
+            let url = UrlWithDepth::with_base(&seed, path).unwrap();
+            let previous = CrawlResult::new(
+                OffsetDateTime::now_utc() - Duration::days(2),
+                ResponseData::new(
+                    RawData::from_vec(b"<html><body>Before</body></html>".to_vec()),
+                    url,
+                    None,
+                    StatusCode::OK,
+                    None,
+                ),
+                None,
+                Some(encoding_rs::UTF_8),
+                AtraFileInformation::new(InterpretedProcessibleFileFormat::HTML, None, None),
+                None,
+            );
+            context.store_crawled_website(&previous).await.unwrap();
+        }
+        context.provider().insert(
+            "https://www.example.com/robots.txt".parse().unwrap(),
+            Ok(fake_page(
+                "User-agent: *\nAllow: /\nSitemap: https://www.example.com/sitemap.xml\n",
+                StatusCode::OK,
+            )),
+        );
+        context.provider().insert(
+            "https://www.example.com/sitemap.xml".parse().unwrap(),
+            Ok(fake_page(SITEMAP, StatusCode::OK)),
+        );
+        for path in ["", "about.html", "news.html"] {
+            context.provider().insert(
+                format!("https://www.example.com/{path}").parse().unwrap(),
+                Ok(fake_page("<html><body>After</body></html>", StatusCode::OK)),
+            );
+        }
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+        context
+            .provider()
+            .requests()
+            .into_iter()
+            .map(|(url, _)| url.to_string())
+            .filter(|url| url.ends_with(".html"))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn trusted_sitemap_hints_decide_the_recrawl() {
+        // about.html was not modified since the last crawl, news.html changes daily.
+        assert_eq!(
+            vec!["https://www.example.com/news.html".to_string()],
+            recrawl_with_sitemap_hints(true).await
+        );
+        // Without the hints the recrawl interval of ten days is not over yet.
+        assert!(recrawl_with_sitemap_hints(false).await.is_empty());
+    }
+
+    /// Crawls the three pages of www.example.com down to [depth_on_website] with the
+    /// [baseline], returns the context and the fetched pages.
+    async fn crawl_with_baseline(
+        depth_on_website: u64,
+        recrawl_interval: Option<Duration>,
+        baseline: Option<Baseline>,
+    ) -> (TestContext<FakeClientProvider>, Vec<String>) {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website,
+            recrawl_interval,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+
+        let mut context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        context.baseline = baseline;
+        for (path, body) in [
+            ("", "<html><body><a href=\"/a.html\">a</a></body></html>"),
+            ("a.html", "<html><body><a href=\"/b.html\">b</a></body></html>"),
+            ("b.html", "<html><body>The end.</body></html>"),
+        ] {
+            context.provider().insert(
+                format!("https://www.example.com/{path}").parse().unwrap(),
+                Ok(fake_page(body, StatusCode::OK)),
+            );
+        }
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let fetched = context
+            .provider()
+            .requests()
+            .into_iter()
+            .map(|(url, _)| url.to_string())
+            .filter(|url| !url.ends_with("robots.txt"))
+            .collect();
+        (context, fetched)
+    }
+
+    /// Writes the pages of the finished crawl in [context] with their link states to a
+    /// database in [path] and opens it as baseline.
+    async fn open_as_baseline(
+        context: &TestContext<FakeClientProvider>,
+        path: &Utf8Path,
+    ) -> Baseline {
+        let pages = context
+            .crawled_websites
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        {
+            let db = Arc::new(open_db(path).unwrap());
+            let crawl_db = CrawlDB::new(db.clone(), &context.configs).unwrap();
+            let link_states = LinkStateRockDB::new(db);
+            for page in pages {
+                crawl_db.add(&page).unwrap();
+                let state = context
+                    .get_link_state_manager()
+                    .get_link_state(&page.meta.url)
+                    .await
+                    .unwrap()
+                    .expect("A stored page has a link state.");
+                link_states.set_state(&page.meta.url, &state).unwrap();
+            }
+        }
+        Baseline::open(path, &context.configs).unwrap()
+    }
+
+    #[tokio::test]
+    async fn the_pages_of_the_baseline_are_skipped() {
+        let dir = Utf8TempDir::new().unwrap();
+        let (first, fetched) = crawl_with_baseline(2, None, None).await;
+        assert_eq!(
+            vec!["https://www.example.com/", "https://www.example.com/a.html"],
+            fetched
+        );
+        let baseline = open_as_baseline(&first, dir.path()).await;
+
+        let (second, fetched) = crawl_with_baseline(3, None, Some(baseline)).await;
+        // The links of the skipped pages are taken from the baseline.
+        assert_eq!(vec!["https://www.example.com/b.html"], fetched);
+        let statistics = second.crawl_events().statistics().snapshot();
+        assert_eq!(1, statistics.origins.len());
+        assert_eq!(2, statistics.origins[0].baseline_skipped);
+        assert!(second
+            .retrieve_slim_crawled_website(&"https://www.example.com/a.html".parse().unwrap())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn the_pages_of_the_baseline_are_fetched_after_the_recrawl_interval() {
+        let dir = Utf8TempDir::new().unwrap();
+        let (first, _) = crawl_with_baseline(2, None, None).await;
+        let baseline = open_as_baseline(&first, dir.path()).await;
+
+        // Every page of the baseline is older than a recrawl interval of zero.
+        let (second, fetched) = crawl_with_baseline(3, Some(Duration::ZERO), Some(baseline)).await;
+        assert_eq!(3, fetched.len(), "{fetched:?}");
+        let statistics = second.crawl_events().statistics().snapshot();
+        assert_eq!(0, statistics.origins[0].baseline_skipped);
+    }
+
+    #[tokio::test]
+    async fn unfinished_pages_of_the_baseline_are_fetched() {
+        let dir = Utf8TempDir::new().unwrap();
+        let (first, _) = crawl_with_baseline(2, None, None).await;
+        // The baseline stopped after storing a.html but before marking it as processed.
+        first
+            .get_link_state_manager()
+            .update_link_state_no_payload(
+                &"https://www.example.com/a.html".parse().unwrap(),
+                LinkStateKind::Crawled,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        let baseline = open_as_baseline(&first, dir.path()).await;
+
+        let (second, fetched) = crawl_with_baseline(3, None, Some(baseline)).await;
+        assert_eq!(
+            vec![
+                "https://www.example.com/a.html",
+                "https://www.example.com/b.html"
+            ],
+            fetched
+        );
+        let statistics = second.crawl_events().statistics().snapshot();
+        assert_eq!(1, statistics.origins[0].baseline_skipped);
+    }
+
+    fn throttled_page(retry_after: &'static str) -> FakeResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static(retry_after));
+        FakeResponse::new(
+            Some(FetchedRequestData::new(
+                RawData::from_vec(Vec::new()),
+                Some(headers),
+                StatusCode::TOO_MANY_REQUESTS,
+                None,
+                None,
+                false,
+            )),
+            1,
+        )
+    }
+
+    #[tokio::test]
+    async fn throttled_urls_are_requeued_until_they_succeed() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.ignore_sitemap = true;
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context.provider().insert_sequence(
+            seed.url().clone(),
+            [
+                Ok(throttled_page("0")),
+                Ok(throttled_page("Wed, 21 Oct 2015 07:28:00 GMT")),
+                Ok(fake_page(
+                    "<html><body>Finally.</body></html>",
+                    StatusCode::OK,
+                )),
+            ],
+        );
+
+        for attempt in 1..=2u8 {
+            let mut crawl_task = context
+                .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+                .unwrap();
+            crawl_task
+                .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+                .await
+                .expect("The crawl should not fail.");
+
+            assert!(context
+                .retrieve_slim_crawled_website(&seed)
+                .await
+                .unwrap()
+                .is_none());
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(&seed)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+            assert_eq!(
+                Some(attempt),
+                state
+                    .payload()
+                    .and_then(ThrottledRetry::from_payload)
+                    .map(|retry| retry.attempts)
+            );
+            let requeued = context
+                .url_queue()
+                .dequeue()
+                .await
+                .unwrap()
+                .expect("The throttled url has to be requeued.");
+            assert_eq!(seed, requeued.target);
+            requeued.drop_from_queue();
+        }
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        assert_eq!(
+            1,
+            context
+                .ct_crawled_websites
+                .load(std::sync::atomic::Ordering::Relaxed)
+        );
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(StatusCode::OK, stored.meta.status_code);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        assert!(context.url_queue().is_empty().await);
+        assert_eq!(
+            3,
+            context
+                .provider()
+                .requests()
+                .iter()
+                .filter(|(url, _)| url == seed.url())
+                .count()
+        );
+    }
+
+    fn rediscovery_context(max_attempts: u8) -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.ignore_sitemap = true;
+        config.rediscovery = Some(RediscoveryConfig {
+            max_attempts,
+            delay: time::Duration::ZERO,
+            backoff_factor: 2,
+        });
+        TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        )
+    }
+
+    /// Crawls the seed once and returns the seed if it was requeued.
+    async fn crawl_window(context: &TestContext<FakeClientProvider>) -> Option<UrlWithDepth> {
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+        let requeued = context.url_queue().dequeue().await.unwrap()?;
+        let target = requeued.target.clone();
+        requeued.drop_from_queue();
+        Some(target)
+    }
+
+    #[tokio::test]
+    async fn unreachable_origins_are_discovered_again() {
+        let context = rediscovery_context(3);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context.provider().insert_sequence(
+            seed.url().clone(),
+            [
+                Err(FakeResponseError::connect(1)),
+                Err(FakeResponseError::connect(2)),
+                Ok(fake_page(
+                    "<html><body>Back again.</body></html>",
+                    StatusCode::OK,
+                )),
+            ],
+        );
+
+        for attempt in 1..=2u8 {
+            assert_eq!(Some(&seed), crawl_window(&context).await.as_ref());
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(&seed)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+            assert_eq!(
+                Some(attempt),
+                state
+                    .payload()
+                    .and_then(RediscoveryRetry::from_payload)
+                    .map(|retry| retry.attempts)
+            );
+        }
+
+        assert_eq!(None, crawl_window(&context).await);
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The seed should be crawled after the origin came back.");
+        assert_eq!(StatusCode::OK, stored.meta.status_code);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+        assert!(context
+            .crawl_events()
+            .metrics()
+            .unreachable_origins()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn gives_up_origins_that_stay_unreachable() {
+        let context = rediscovery_context(2);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context
+            .provider()
+            .insert(seed.url().clone(), Err(FakeResponseError::connect(1)));
+        let mut events = context.crawl_events().subscribe();
+
+        assert_eq!(Some(&seed), crawl_window(&context).await.as_ref());
+        assert_eq!(Some(&seed), crawl_window(&context).await.as_ref());
+        assert_eq!(None, crawl_window(&context).await);
+
+        let origin = seed.atra_origin().unwrap();
+        let mut unreachable = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let CrawlEvent::OriginUnreachable { .. } = event {
+                unreachable.push(event);
+            }
+        }
+        assert_eq!(
+            vec![CrawlEvent::OriginUnreachable {
+                origin: origin.clone(),
+                seed: seed.clone(),
+                attempts: 2,
+            }],
+            unreachable
+        );
+        assert_eq!(
+            vec![origin],
+            context.crawl_events().metrics().unreachable_origins()
+        );
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::InternalError, state.kind());
+        assert_eq!(
+            3,
+            context
+                .provider()
+                .requests()
+                .iter()
+                .filter(|(url, _)| url == seed.url())
+                .count()
+        );
+    }
+
+    fn fetch_retry_context(max_attempts: u8) -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.ignore_sitemap = true;
+        config.fetch_retry = Some(FetchRetryConfig {
+            max_attempts,
+            delay: time::Duration::minutes(1),
+            jitter_percent: 0,
+            ..FetchRetryConfig::default()
+        });
+        TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        )
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried_after_a_delay() {
+        let context = fetch_retry_context(3);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context.provider().insert_sequence(
+            seed.url().clone(),
+            [
+                Err(FakeResponseError::timeout(1)),
+                Ok(fake_page(
+                    "<html><body>Overloaded.</body></html>",
+                    StatusCode::BAD_GATEWAY,
+                )),
+                Ok(fake_page(
+                    "<html><body>Back again.</body></html>",
+                    StatusCode::OK,
+                )),
+            ],
+        );
+
+        let expected = [
+            (FetchFailureKind::Timeout, time::Duration::minutes(1)),
+            (FetchFailureKind::ServerError, time::Duration::minutes(2)),
+        ];
+        for (attempt, (kind, delay)) in (1u8..).zip(expected) {
+            let started = OffsetDateTime::now_utc();
+            assert_eq!(Some(&seed), crawl_window(&context).await.as_ref());
+            let state = context
+                .get_link_state_manager()
+                .get_link_state(&seed)
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(LinkStateKind::Discovered, state.kind());
+            let payload = state.payload().unwrap();
+            assert_eq!(Some(kind), FetchFailureKind::from_payload(payload));
+            assert_eq!(
+                Some(attempt),
+                FetchFailureKind::attempts_from_payload(payload)
+            );
+            let not_before = FetchFailureKind::not_before_from_payload(payload).unwrap();
+            assert!(started + delay <= not_before);
+            assert!(not_before <= OffsetDateTime::now_utc() + delay + time::Duration::seconds(1));
+        }
+
+        assert_eq!(None, crawl_window(&context).await);
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The seed should be crawled after the failures passed.");
+        assert_eq!(StatusCode::OK, stored.meta.status_code);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+    }
+
+    #[tokio::test]
+    async fn gives_up_urls_that_keep_failing() {
+        let context = fetch_retry_context(2);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context
+            .provider()
+            .insert(seed.url().clone(), Err(FakeResponseError::timeout(1)));
+
+        assert_eq!(Some(&seed), crawl_window(&context).await.as_ref());
+        assert_eq!(None, crawl_window(&context).await);
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::InternalError, state.kind());
+        assert_eq!(
+            Some(FetchFailureKind::Timeout),
+            state.payload().and_then(FetchFailureKind::from_payload)
+        );
+
+        let other = fetch_retry_context(2);
+        other.provider().insert(
+            seed.url().clone(),
+            Ok(fake_page(
+                "<html><body>Still overloaded.</body></html>",
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )),
+        );
+        assert_eq!(Some(&seed), crawl_window(&other).await.as_ref());
+        assert_eq!(None, crawl_window(&other).await);
+        let stored = other
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The last server error should be stored as is.");
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, stored.meta.status_code);
+        assert_eq!(
+            2,
+            other
+                .provider()
+                .requests()
+                .iter()
+                .filter(|(url, _)| url == seed.url())
+                .count()
+        );
+    }
+
+    fn language_variants_context(languages: &[&str]) -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.ignore_sitemap = true;
+        config.language_variants = languages.iter().map(|value| value.to_string()).collect();
+        TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        )
+    }
+
+    fn negotiated_page(
+        body: &'static str,
+        vary: &'static str,
+        language: &'static str,
+    ) -> FakeResponse {
+        let mut headers = HeaderMap::new();
+        headers.insert(VARY, HeaderValue::from_static(vary));
+        headers.insert(CONTENT_LANGUAGE, HeaderValue::from_static(language));
+        FakeResponse::new(
+            Some(FetchedRequestData::new(
+                RawData::from_vec(body.as_bytes().to_vec()),
+                Some(headers),
+                StatusCode::OK,
+                None,
+                None,
+                false,
+            )),
+            1,
+        )
+    }
+
+    #[tokio::test]
+    async fn stores_the_language_variants_of_negotiated_pages() {
+        let context = language_variants_context(&["de", "fr"]);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context.provider().insert(
+            seed.url().clone(),
+            Ok(negotiated_page(
+                "<html><body>Hello World</body></html>",
+                "Accept-Encoding, accept-language",
+                "en",
+            )),
+        );
+        context.provider().insert_variant(
+            seed.url().clone(),
+            "de",
+            Ok(negotiated_page(
+                "<html><body>Hallo Welt</body></html>",
+                "Accept-Language",
+                "de",
+            )),
+        );
+
+        assert_eq!(None, crawl_window(&context).await);
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some("en"), stored.meta.content_language.as_deref());
+        assert_eq!(None, stored.meta.language_variant);
+
+        let variants = context.language_variants.read().unwrap();
+        assert_eq!(2, variants.len());
+        let german = &variants[&(seed.url().clone(), "de".to_string())];
+        assert_eq!(Some("de"), german.meta.content_language.as_deref());
+        assert_eq!(Some("de"), german.meta.language_variant.as_deref());
+        // The server has no french page and answers with its default.
+        let french = &variants[&(seed.url().clone(), "fr".to_string())];
+        assert_eq!(Some("en"), french.meta.content_language.as_deref());
+        assert_eq!(Some("fr"), french.meta.language_variant.as_deref());
+        assert_eq!(
+            3,
+            context
+                .provider()
+                .requests()
+                .iter()
+                .filter(|(url, _)| url == seed.url())
+                .count()
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_not_varying_by_language_are_fetched_once() {
+        let context = language_variants_context(&["de"]);
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        context.provider().insert(
+            seed.url().clone(),
+            Ok(negotiated_page(
+                "<html><body>Hello World</body></html>",
+                "Accept-Encoding",
+                "en",
+            )),
+        );
+
+        assert_eq!(None, crawl_window(&context).await);
+        assert!(context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .is_some());
+        assert!(context.language_variants.read().unwrap().is_empty());
+        assert_eq!(
+            1,
+            context
+                .provider()
+                .requests()
+                .iter()
+                .filter(|(url, _)| url == seed.url())
+                .count()
+        );
+    }
+
+    fn robots_context(
+        seed_headers: Option<HeaderMap>,
+        seed_body: &'static str,
+        respect_directives: bool,
+    ) -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.respect_robots_meta = respect_directives;
+        config.respect_noindex = respect_directives;
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        seeded_context(config, seed_headers, seed_body)
+    }
+
+    /// A context with the seed `https://www.example.com/` linking to a second page.
+    fn seeded_context(
+        config: CrawlConfig,
+        seed_headers: Option<HeaderMap>,
+        seed_body: &'static str,
+    ) -> TestContext<FakeClientProvider> {
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            Ok(FakeResponse::new(
+                Some(FetchedRequestData::new(
+                    RawData::from_vec(seed_body.as_bytes().to_vec()),
+                    seed_headers,
+                    StatusCode::OK,
+                    None,
+                    None,
+                    false,
+                )),
+                1,
+            )),
+        );
+        context.provider().insert(
+            "https://www.example.com/second.html".parse().unwrap(),
+            Ok(fake_page("<html><body>The end.</body></html>", StatusCode::OK)),
+        );
+        context
+    }
+
+    fn x_robots_tag(value: &'static str) -> Option<HeaderMap> {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert("X-Robots-Tag", HeaderValue::from_static(value));
+        Some(headers)
+    }
+
+    /// Crawls the seed, returns if the second page was requested and how the seed was stored.
+    async fn crawl_with_robots(
+        context: &TestContext<FakeClientProvider>,
+    ) -> (bool, StoredDataHint) {
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let state = context
+            .get_link_state_manager()
+            .get_link_state(&seed)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(LinkStateKind::ProcessedAndStored, state.kind());
+
+        let followed = context
+            .provider()
+            .requests()
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html");
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The seed is always recorded as crawled.");
+        (followed, stored.stored_data_hint)
+    }
+
+    const PAGE_WITH_LINK: &str = r#"<html><head></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#;
+
+    #[tokio::test]
+    async fn respects_nofollow_of_the_meta_tag() {
+        let context = robots_context(
+            None,
+            r#"<html><head><meta name="robots" content="nofollow"></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#,
+            true,
+        );
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(!followed);
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+    }
+
+    #[tokio::test]
+    async fn respects_noindex_of_the_meta_tag() {
+        let context = robots_context(
+            None,
+            r#"<html><head><meta name="robots" content="noindex"></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#,
+            true,
+        );
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert_eq!(StoredDataHint::None, stored);
+    }
+
+    #[tokio::test]
+    async fn respects_nofollow_of_the_header() {
+        let context = robots_context(x_robots_tag("nofollow"), PAGE_WITH_LINK, true);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(!followed);
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+    }
+
+    #[tokio::test]
+    async fn respects_noindex_of_the_header() {
+        let context = robots_context(x_robots_tag("noindex"), PAGE_WITH_LINK, true);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert_eq!(StoredDataHint::None, stored);
+    }
+
+    #[tokio::test]
+    async fn respects_none_of_the_header_and_the_meta_tag() {
+        let context = robots_context(x_robots_tag("none"), PAGE_WITH_LINK, true);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(!followed);
+        assert_eq!(StoredDataHint::None, stored);
+
+        let context = robots_context(
+            None,
+            r#"<html><head><meta name="robots" content="none"></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#,
+            true,
+        );
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(!followed);
+        assert_eq!(StoredDataHint::None, stored);
+    }
+
+    #[tokio::test]
+    async fn ignores_the_directives_if_configured() {
+        let context = robots_context(x_robots_tag("none"), PAGE_WITH_LINK, false);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+    }
+
+    #[tokio::test]
+    async fn refreshed_robots_drop_the_queued_urls_they_disallow() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.robots_revalidation = Some(RobotsRevalidationConfig {
+            every_n_fetches: NonZeroU64::new(1).unwrap(),
+            on_recrawl: false,
+        });
+        let context = seeded_context(
+            config,
+            None,
+            r#"<html><body><a href="/second.html">Second</a><a href="/private/a.html">A</a><a href="/private/b.html">B</a></body></html>"#,
+        );
+        let robots: AtraUri = "https://www.example.com/robots.txt".parse().unwrap();
+        context.provider().insert(
+            robots.clone(),
+            Ok(fake_page(
+                "User-agent: *\nDisallow: /private/",
+                StatusCode::OK,
+            )),
+        );
+        context.provider().insert_sequence(
+            robots,
+            [Ok(fake_page("User-agent: *\nAllow: /", StatusCode::OK))],
+        );
+        for url in [
+            "https://www.example.com/private/queued.html",
+            "https://www.other.com/private/queued.html",
+        ] {
+            context
+                .url_queue()
+                .enqueue(UrlQueueElement::new(
+                    false,
+                    0,
+                    false,
+                    UrlWithDepth::from_url(url).unwrap(),
+                ))
+                .await
+                .unwrap();
+        }
+        let mut events = context.crawl_events().subscribe();
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let requests = context.provider().requests();
+        assert!(requests
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html"));
+        assert!(
+            !requests
+                .iter()
+                .any(|(url, _)| url.as_str().contains("/private/")),
+            "{requests:?}"
+        );
+
+        let changes = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                CrawlEvent::RobotsChanged {
+                    origin,
+                    diff,
+                    dropped,
+                } => Some((origin, diff, dropped)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(1, changes.len(), "{changes:?}");
+        let (origin, diff, dropped) = &changes[0];
+        assert_eq!(
+            &UrlWithDepth::from_url("https://www.example.com/")
+                .unwrap()
+                .atra_origin()
+                .unwrap(),
+            origin
+        );
+        assert_eq!("disallowed=[/private/] allowed=[]", diff.to_string());
+        assert_eq!(3, *dropped);
+
+        let remaining = context.url_queue().dequeue().await.unwrap().unwrap();
+        assert_eq!(
+            "https://www.other.com/private/queued.html",
+            remaining.target.try_as_str()
+        );
+        remaining.drop_from_queue();
+        assert!(context.url_queue().is_empty().await);
+    }
+
+    const PUBLISHED_IN_2023: &str = r#"<html><head><meta property="article:published_time" content="2023-07-15T08:00:00Z"></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#;
+    const PUBLISHED_IN_2019: &str = r#"<html><head><meta property="article:published_time" content="2019-03-04T08:00:00Z"></head><body><a href="https://www.example.com/second.html">Next</a></body></html>"#;
+
+    fn publication_context(
+        window: PublicationWindow,
+        seed_body: &'static str,
+    ) -> TestContext<FakeClientProvider> {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.publication_window = Some(window);
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        seeded_context(config, None, seed_body)
+    }
+
+    fn window_of_2023() -> PublicationWindow {
+        PublicationWindow {
+            from: Some(Date::from_calendar_date(2023, Month::January, 1).unwrap()),
+            until: Some(Date::from_calendar_date(2023, Month::December, 31).unwrap()),
+            ..PublicationWindow::default()
+        }
+    }
+
+    async fn seed_publication(
+        context: &TestContext<FakeClientProvider>,
+    ) -> Option<PublicationEstimate> {
+        context
+            .retrieve_slim_crawled_website(
+                &UrlWithDepth::from_url("https://www.example.com/").unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap()
+            .meta
+            .publication
+    }
+
+    #[tokio::test]
+    async fn stores_pages_published_within_the_window() {
+        let context = publication_context(window_of_2023(), PUBLISHED_IN_2023);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+        let publication = seed_publication(&context).await.unwrap();
+        assert_eq!(PublicationSource::ArticleMeta, publication.source);
+        assert_eq!(PublicationConfidence::High, publication.confidence);
+    }
+
+    #[tokio::test]
+    async fn only_follows_the_links_of_pages_published_outside_of_the_window() {
+        let context = publication_context(window_of_2023(), PUBLISHED_IN_2019);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert_eq!(StoredDataHint::None, stored);
+        assert_eq!(
+            2019,
+            seed_publication(&context)
+                .await
+                .unwrap()
+                .published_at
+                .year()
+        );
+    }
+
+    #[tokio::test]
+    async fn annotates_pages_published_outside_of_the_window() {
+        let mut window = window_of_2023();
+        window.mode = PublicationWindowMode::AnnotateOnly;
+        let context = publication_context(window, PUBLISHED_IN_2019);
+        let (_, stored) = crawl_with_robots(&context).await;
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+        assert!(seed_publication(&context).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn uncertain_pages_follow_the_configured_action() {
+        let context = publication_context(window_of_2023(), PAGE_WITH_LINK);
+        let (_, stored) = crawl_with_robots(&context).await;
+        assert!(matches!(stored, StoredDataHint::InMemory(_)));
+        assert_eq!(None, seed_publication(&context).await);
+
+        let mut window = window_of_2023();
+        window.store_uncertain = false;
+        let context = publication_context(window, PAGE_WITH_LINK);
+        let (followed, stored) = crawl_with_robots(&context).await;
+        assert!(followed);
+        assert_eq!(StoredDataHint::None, stored);
+
+        // A time element alone is not confident enough.
+        let mut window = window_of_2023();
+        window.store_uncertain = false;
+        window.min_confidence = PublicationConfidence::High;
+        let context = publication_context(
+            window,
+            r#"<html><body><time datetime="2023-07-15">Published</time></body></html>"#,
+        );
+        let (_, stored) = crawl_with_robots(&context).await;
+        assert_eq!(StoredDataHint::None, stored);
+        assert_eq!(
+            Some(PublicationConfidence::Medium),
+            seed_publication(&context)
+                .await
+                .map(|publication| publication.confidence)
+        );
+    }
+
+    #[tokio::test]
+    async fn pages_record_the_tag_of_their_seed() {
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config
+            .seed_tags
+            .insert(seed.atra_origin().unwrap(), "news".to_string());
+        let context = seeded_context(config, None, PAGE_WITH_LINK);
+        let (followed, _) = crawl_with_robots(&context).await;
+        assert!(followed);
+
+        for url in [
+            "https://www.example.com/",
+            "https://www.example.com/second.html",
+        ] {
+            let found = context
+                .retrieve_slim_crawled_website(&UrlWithDepth::from_url(url).unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(Some("news"), found.meta.tag.as_deref(), "{url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_extractors_do_not_abort_the_page() {
+        mock::register(ExtractorMethod::Rtf, MockedExtractor::Panic);
+        mock::register(
+            ExtractorMethod::Xml,
+            MockedExtractor::Sleep(std::time::Duration::from_secs(30)),
+        );
+        let mut config = CrawlConfig::default();
+        config.extractor_method_timeout = Some(Duration::milliseconds(200));
+        config.link_extractors = Extractor(vec![
+            ExtractorCommand::new(ExtractorMethod::Rtf, ApplyWhen::Always),
+            ExtractorCommand::new(ExtractorMethod::Xml, ApplyWhen::Always),
+            ExtractorCommand::new_default_apply(ExtractorMethod::HtmlV1),
+        ]);
+        let context = TestContext::new(
+            AtraConfig::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let page = FetchedRequestData::new(
+            RawData::from_vec(PAGE_WITH_LINK.as_bytes().to_vec()),
+            None,
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        let target = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        let result = analyze_page(&context, page, &target)
+            .await
+            .expect("The page completes despite the failed extractors.");
+        let links = result.meta.links.expect("The links of HtmlV1 are kept.");
+        assert!(links.iter().any(|link| link
+            .to_string()
+            .contains("https://www.example.com/second.html")));
+        assert_eq!(
+            [0, 1, 1],
+            context.crawl_events().metrics().extractor_failures()
+        );
+    }
+
+    const ERROR_PAGE: &str = r#"<html><body><p>Not found, did you mean:</p><a href="https://www.example.com/second.html">Second</a><a href="https://www.other.com/">Other</a><a href="https://www.example.com/">Retry</a></body></html>"#;
+
+    /// Crawls the seed answering with [ERROR_PAGE] and a 404, returns the requested urls,
+    /// the queued urls and the targets of the links in the web graph with their flag.
+    async fn crawl_error_page(
+        policy: ErrorPageExtraction,
+    ) -> (Vec<String>, Vec<String>, Vec<(String, bool)>) {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.extract_from_error_pages = policy;
+        config.budget.default = BudgetSetting::Normal {
+            depth_on_website: 2,
+            depth: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        let context = seeded_context(config, None, "");
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            Ok(fake_page(ERROR_PAGE, StatusCode::NOT_FOUND)),
+        );
+
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let stored = context
+            .retrieve_slim_crawled_website(&seed)
+            .await
+            .unwrap()
+            .expect("The error page is recorded as crawled.");
+        assert_eq!(StatusCode::NOT_FOUND, stored.meta.status_code);
+
+        let requested = context
+            .provider()
+            .requests()
+            .into_iter()
+            .map(|(url, _)| url.to_string())
+            .collect::<Vec<_>>();
+        let mut queued = Vec::new();
+        while let Some(element) = context.url_queue().dequeue().await.unwrap() {
+            queued.push(element.target.try_as_str().into_owned());
+        }
+        let links = context
+            .link_net_manager
+            .entries()
+            .await
+            .into_iter()
+            .filter_map(|entry| match entry {
+                WebGraphEntry::Link {
+                    from,
+                    to,
+                    from_error_page,
+                    ..
+                } if from == *seed.url() => Some((to.to_string(), from_error_page)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        (requested, queued, links)
+    }
+
+    #[tokio::test]
+    async fn does_not_follow_the_links_of_error_pages_by_default() {
+        let (requested, queued, links) = crawl_error_page(ErrorPageExtraction::default()).await;
+        assert!(!requested.contains(&"https://www.example.com/second.html".to_string()));
+        assert!(queued.is_empty(), "{queued:?}");
+        assert!(links.is_empty(), "{links:?}");
+    }
+
+    #[tokio::test]
+    async fn follows_the_same_origin_links_of_error_pages() {
+        let (requested, queued, links) =
+            crawl_error_page(ErrorPageExtraction::SameOriginOnly).await;
+        assert!(requested.contains(&"https://www.example.com/second.html".to_string()));
+        assert!(queued.is_empty(), "{queued:?}");
+        assert_eq!(
+            vec![("https://www.example.com/second.html".to_string(), true)],
+            links
+        );
+    }
+
+    #[tokio::test]
+    async fn follows_every_link_of_error_pages() {
+        let (requested, queued, mut links) = crawl_error_page(ErrorPageExtraction::Always).await;
+        assert!(requested.contains(&"https://www.example.com/second.html".to_string()));
+        assert_eq!(vec!["https://www.other.com/".to_string()], queued);
+        links.sort();
+        assert_eq!(
+            vec![
+                ("https://www.example.com/second.html".to_string(), true),
+                ("https://www.other.com/".to_string(), true),
+            ],
+            links
+        );
+    }
+
+    #[tokio::test]
+    async fn collects_statistics_by_origin_and_worker() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 1,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        let context = seeded_context(config, None, PAGE_WITH_LINK);
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let statistics = context.crawl_events().statistics().snapshot();
+        assert_eq!(1, statistics.origins.len());
+        let origin = &statistics.origins[0];
+        assert_eq!("www.example.com", origin.origin);
+        assert_eq!(1, origin.pages);
+        assert_eq!(1, origin.fetches);
+        assert_eq!(PAGE_WITH_LINK.len() as u64, origin.bytes);
+        assert!(origin.average_fetch_millis >= 0.0);
+        assert!(origin.errors.is_empty());
+        assert!(origin.budget_exhausted, "The second page exceeds the budget.");
+        assert_eq!(
+            1,
+            statistics
+                .workers
+                .iter()
+                .map(|worker| worker.pages)
+                .sum::<u64>()
+        );
+        assert!(statistics.started_at <= statistics.finished_at);
+    }
+
+    #[tokio::test]
+    async fn rejected_urls_never_reach_the_link_states() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.url_filters = vec![UrlFilterConfig::Extension {
+            extensions: vec!["html".to_string()],
+            on_match: UrlFilterDecision::Reject,
+        }];
+        let context = seeded_context(config, None, PAGE_WITH_LINK);
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let second = UrlWithDepth::from_url("https://www.example.com/second.html").unwrap();
+        assert!(context
+            .get_link_state_manager()
+            .get_link_state(&second)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(!context
+            .provider()
+            .requests()
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html"));
+        let statistics = context.crawl_events().statistics().snapshot();
+        assert_eq!(1, statistics.origins[0].filtered);
+    }
+
+    #[tokio::test]
+    async fn urls_on_the_seed_with_a_better_score_are_crawled_first() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.url_filters = vec![UrlFilterConfig::PathPrefix {
+            prefix: "/preferred".to_string(),
+            on_match: UrlFilterDecision::Score(5),
+        }];
+        let context = seeded_context(
+            config,
+            None,
+            r#"<html><body><a href="https://www.example.com/second.html">Next</a><a href="https://www.example.com/preferred.html">Preferred</a></body></html>"#,
+        );
+        context.provider().insert(
+            "https://www.example.com/preferred.html".parse().unwrap(),
+            Ok(fake_page("<html><body>The end.</body></html>", StatusCode::OK)),
+        );
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        let requested = context
+            .provider()
+            .requests()
+            .into_iter()
+            .map(|(url, _)| url.to_string())
+            .filter(|url| url.ends_with(".html"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                "https://www.example.com/preferred.html".to_string(),
+                "https://www.example.com/second.html".to_string(),
+            ],
+            requested
+        );
+    }
+
+    #[tokio::test]
+    async fn the_page_limit_shuts_the_crawl_down() {
+        let mut config: CrawlConfig = CrawlConfig::default();
+        config.budget.default = BudgetSetting::SeedOnly {
+            depth_on_website: 2,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        config.ignore_sitemap = true;
+        config.max_pages_total = Some(3);
+        let mut context = seeded_context(
+            config.clone(),
+            None,
+            r#"<html><body><a href="https://www.example.com/1.html">1</a><a href="https://www.example.com/2.html">2</a><a href="https://www.example.com/3.html">3</a><a href="https://www.example.com/4.html">4</a></body></html>"#,
+        );
+        for page in 1..=4 {
+            context.provider().insert(
+                format!("https://www.example.com/{page}.html").parse().unwrap(),
+                Ok(fake_page("<html><body>A page.</body></html>", StatusCode::OK)),
+            );
+        }
+        let shutdown = GracefulShutdown::new();
+        context.crawl_limits = CrawlLimits::new(&config, Some(shutdown.child().clone()));
+
+        let mut crawl_task = context
+            .create_crawl_task(UnguardedSeed::from_url("https://www.example.com/").unwrap())
+            .unwrap();
+        crawl_task
+            .run(&context, shutdown.child().clone(), &TestErrorConsumer::new())
+            .await
+            .expect("The crawl should not fail.");
+
+        assert_eq!(
+            3,
+            context
+                .ct_crawled_websites
+                .load(std::sync::atomic::Ordering::Relaxed)
+        );
+        assert_eq!(
+            Some(StopReason::MaxPages(3)),
+            context.crawl_limits().stop_reason()
+        );
+        assert!(shutdown.is_shutdown());
+    }
+
+    const IMAGE_DATA_URI: &str = "data:image/png;base64,iVBORw0KGgo=";
+    const HTML_DATA_URI: &str = concat!(
+        "data:text/html;charset=utf-8,%3Chtml%3E%3Cbody%3E",
+        "%3Ca%20href%3D%22/second.html%22%3ENext%3C/a%3E",
+        "%3Cimg%20src%3D%22data:,nested%22%3E%3C/body%3E%3C/html%3E"
+    );
+
+    /// Crawls the fixture page with the data uris as seed.
+    async fn crawl_data_uris(mut config: CrawlConfig) -> TestContext<FakeClientProvider> {
+        config.ignore_sitemap = true;
+        config.crawl_embedded_data = true;
+        config.store_only_html_in_warc = false;
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let context = seeded_context(
+            config,
+            Some(headers),
+            include_str!("../../testdata/samples/data_uris.html"),
+        );
+        assert_eq!(None, crawl_window(&context).await);
+        context
+    }
+
+    async fn stored_data_uri(
+        context: &TestContext<FakeClientProvider>,
+        parent: &UrlWithDepth,
+        uri: &str,
+    ) -> Option<SlimCrawlResult> {
+        let target = data_uri_target(parent, uri).unwrap();
+        context.retrieve_slim_crawled_website(&target).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn stores_the_data_uris_of_a_page() {
+        let context = crawl_data_uris(CrawlConfig::default()).await;
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        let image = stored_data_uri(&context, &seed, IMAGE_DATA_URI)
+            .await
+            .expect("The image is stored.");
+        assert_eq!(
+            InterpretedProcessibleFileFormat::IMAGE,
+            image.meta.file_information.format
+        );
+        assert_eq!(
+            StoredDataHint::InMemory(b"\x89PNG\r\n\x1a\n".to_vec()),
+            image.stored_data_hint
+        );
+
+        let html = stored_data_uri(&context, &seed, HTML_DATA_URI)
+            .await
+            .expect("The html is stored.");
+        assert_eq!(
+            InterpretedProcessibleFileFormat::HTML,
+            html.meta.file_information.format
+        );
+        // The data uri in the html is stored under the url of the html.
+        let nested = stored_data_uri(&context, &html.meta.url, "data:,nested")
+            .await
+            .expect("The nested data uri is stored.");
+        assert_eq!(
+            StoredDataHint::InMemory(b"nested".to_vec()),
+            nested.stored_data_hint
+        );
+
+        // The relative link in the html is resolved against the page and followed.
+        let requests = context.provider().requests();
+        assert!(requests
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html"));
+        assert!(requests.iter().all(|(url, _)| url.scheme() != "data"));
+        assert_eq!(0, context.crawl_events().metrics().skipped_data_uris());
+    }
+
+    #[tokio::test]
+    async fn the_nesting_of_data_uris_is_bounded() {
+        let mut config = CrawlConfig::default();
+        config.max_extraction_depth = Some(1);
+        let context = crawl_data_uris(config).await;
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        let html = stored_data_uri(&context, &seed, HTML_DATA_URI)
+            .await
+            .expect("The html is stored.");
+        assert!(stored_data_uri(&context, &html.meta.url, "data:,nested")
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn oversized_data_uris_are_skipped() {
+        let mut config = CrawlConfig::default();
+        config.max_data_uri_size = 16;
+        let context = crawl_data_uris(config).await;
+        let seed = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        assert!(stored_data_uri(&context, &seed, IMAGE_DATA_URI)
+            .await
+            .is_some());
+        assert!(stored_data_uri(&context, &seed, HTML_DATA_URI)
+            .await
+            .is_none());
+        assert_eq!(1, context.crawl_events().metrics().skipped_data_uris());
+        assert!(!context
+            .provider()
+            .requests()
+            .iter()
+            .any(|(url, _)| url.as_str() == "https://www.example.com/second.html"));
+    }
+
+    #[tokio::test]
+    async fn records_the_proxy_in_the_meta() {
+        let context = robots_context(None, PAGE_WITH_LINK, true);
+        let mut fetched = FetchedRequestData::new(
+            RawData::from_vec(PAGE_WITH_LINK.as_bytes().to_vec()),
+            None,
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        fetched.proxy = Some("socks5h://127.0.0.1:9050".to_string());
+        context.provider().insert(
+            "https://www.example.com/".parse().unwrap(),
+            Ok(FakeResponse::new(Some(fetched), 1)),
+        );
+        let _ = crawl_with_robots(&context).await;
+
+        let seed = context
+            .retrieve_slim_crawled_website(
+                &UrlWithDepth::from_url("https://www.example.com/").unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(Some("socks5h://127.0.0.1:9050"), seed.meta.proxy.as_deref());
+        let second = context
+            .retrieve_slim_crawled_website(
+                &UrlWithDepth::from_url("https://www.example.com/second.html").unwrap(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, second.meta.proxy);
+    }
+
     #[tokio::test]
     async fn crawl_a_single_site_filtered() {
         // // init();
@@ -0,0 +1,336 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Shares the politeness of the origins between the Atra processes of a machine.
+//!
+//! Every origin has a small json file in the coordination directory with the latest request
+//! reserved by any process, the delay of that process and an active cooldown. The file is only
+//! read and written while holding its lock file.
+
+use crate::url::AtraUrlOrigin;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io;
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// Records not written for this long belong to a crashed or finished process and are ignored.
+const STALE_AFTER: Duration = Duration::from_secs(600);
+/// A lock is held for a few file operations, an older lock belongs to a crashed process.
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(5);
+/// The pause between two attempts to acquire a lock.
+const LOCK_RETRY: Duration = Duration::from_millis(2);
+
+/// The shared state of an origin, all times are unix timestamps in milliseconds.
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+struct OriginRecord {
+    /// The latest request reserved by any process.
+    last_request: i64,
+    /// The delay of the process that reserved the [OriginRecord::last_request].
+    delay: u64,
+    /// The origin must not be contacted before this time, e.g. after a 429.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cooldown_until: Option<i64>,
+    /// The last time a process wrote the record.
+    written_at: i64,
+}
+
+impl OriginRecord {
+    /// Returns the earliest time for the next request of a process with [delay].
+    fn next_request(&self, delay: u64) -> i64 {
+        let next = self
+            .last_request
+            .saturating_add(self.delay.max(delay) as i64);
+        self.cooldown_until.map_or(next, |until| until.max(next))
+    }
+}
+
+/// The politeness state shared by all processes using the same coordination directory.
+#[derive(Debug, Clone)]
+pub struct PolitenessCoordination {
+    dir: Utf8PathBuf,
+    stale_after: Duration,
+}
+
+impl PolitenessCoordination {
+    /// Opens the coordination directory, creates it if necessary.
+    pub fn open(dir: impl Into<Utf8PathBuf>) -> io::Result<Self> {
+        Self::with_stale_after(dir, STALE_AFTER)
+    }
+
+    /// Opens the coordination directory, records older than [stale_after] are ignored.
+    pub fn with_stale_after(
+        dir: impl Into<Utf8PathBuf>,
+        stale_after: Duration,
+    ) -> io::Result<Self> {
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+        Ok(Self { dir, stale_after })
+    }
+
+    /// Reserves the next request to [origin] for a process with [delay].
+    /// The request is spaced by the larger delay to the previous request of any process.
+    /// Returns the time to wait before sending the request.
+    pub async fn reserve(&self, origin: &AtraUrlOrigin, delay: Duration) -> io::Result<Duration> {
+        let delay = delay.as_millis() as u64;
+        self.update(origin, |record, now| {
+            let slot = record.map_or(now, |record| record.next_request(delay).max(now));
+            let reserved = OriginRecord {
+                last_request: slot,
+                delay,
+                cooldown_until: None,
+                written_at: now,
+            };
+            (
+                reserved,
+                Duration::from_millis(slot.saturating_sub(now) as u64),
+            )
+        })
+        .await
+    }
+
+    /// Tells the other processes that [origin] must not be contacted before [until].
+    pub async fn cool_down(&self, origin: &AtraUrlOrigin, until: OffsetDateTime) -> io::Result<()> {
+        let until = unix_millis(until);
+        self.update(origin, |record, now| {
+            let mut record = record.unwrap_or_default();
+            record.cooldown_until = Some(record.cooldown_until.map_or(until, |old| old.max(until)));
+            record.written_at = now;
+            (record, ())
+        })
+        .await
+    }
+
+    /// Updates the record of [origin] while holding its lock.
+    /// [update] gets the record if it is not stale and the current time.
+    async fn update<T>(
+        &self,
+        origin: &AtraUrlOrigin,
+        update: impl FnOnce(Option<OriginRecord>, i64) -> (OriginRecord, T),
+    ) -> io::Result<T> {
+        let name = file_name(origin);
+        let _lock = RecordLock::acquire(self.dir.join(format!("{name}.lock"))).await?;
+        let path = self.dir.join(format!("{name}.json"));
+        let now = unix_millis(OffsetDateTime::now_utc());
+        let stale_after = self.stale_after.as_millis() as i64;
+        let record = read_record(&path)?
+            .filter(|record| record.written_at.saturating_add(stale_after) >= now);
+        let (record, result) = update(record, now);
+        // Readers never see a half written record.
+        let tmp = self.dir.join(format!("{name}.tmp"));
+        std::fs::write(&tmp, serde_json::to_vec(&record)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(result)
+    }
+}
+
+fn unix_millis(time: OffsetDateTime) -> i64 {
+    (time.unix_timestamp_nanos() / 1_000_000) as i64
+}
+
+/// Returns a file name for [origin] that is valid on all platforms.
+fn file_name(origin: &AtraUrlOrigin) -> String {
+    origin
+        .as_ref()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Reads the record at [path], a missing or unreadable record is treated as no record.
+fn read_record(path: &Utf8Path) -> io::Result<Option<OriginRecord>> {
+    match std::fs::read(path) {
+        Ok(content) => match serde_json::from_slice(&content) {
+            Ok(record) => Ok(Some(record)),
+            Err(err) => {
+                log::warn!("Ignore the broken politeness record {path}: {err}");
+                Ok(None)
+            }
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// An exclusive lock on a record, released on drop.
+struct RecordLock {
+    path: Utf8PathBuf,
+}
+
+impl RecordLock {
+    async fn acquire(path: Utf8PathBuf) -> io::Result<Self> {
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if Self::is_stale(&path) {
+                        log::warn!("Remove the stale politeness lock {path}.");
+                        let _ = std::fs::remove_file(&path);
+                        continue;
+                    }
+                    tokio::time::sleep(LOCK_RETRY).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn is_stale(path: &Utf8Path) -> bool {
+        std::fs::metadata(path)
+            .and_then(|meta| meta.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > LOCK_STALE_AFTER)
+    }
+}
+
+impl Drop for RecordLock {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            log::warn!("Failed to release the politeness lock {}: {err}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use camino_tempfile::Utf8TempDir;
+    use std::fs::File;
+    use std::time::{Instant, SystemTime};
+
+    /// Sends [count] requests through [gate], returns when they were sent.
+    async fn requests(
+        gate: PolitenessCoordination,
+        origin: AtraUrlOrigin,
+        delay: Duration,
+        count: usize,
+    ) -> Vec<(Instant, Duration)> {
+        let mut sent = Vec::with_capacity(count);
+        for _ in 0..count {
+            let wait = gate.reserve(&origin, delay).await.unwrap();
+            tokio::time::sleep(wait).await;
+            sent.push((Instant::now(), delay));
+        }
+        sent
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn two_gates_keep_the_strictest_spacing() {
+        let dir = Utf8TempDir::new().unwrap();
+        let origin = AtraUrlOrigin::from("example.com");
+        let first = PolitenessCoordination::open(dir.path()).unwrap();
+        let second = PolitenessCoordination::open(dir.path()).unwrap();
+
+        let (a, b) = tokio::join!(
+            tokio::spawn(requests(
+                first,
+                origin.clone(),
+                Duration::from_millis(40),
+                5
+            )),
+            tokio::spawn(requests(second, origin, Duration::from_millis(80), 5)),
+        );
+        let mut sent: Vec<_> = a.unwrap().into_iter().chain(b.unwrap()).collect();
+        sent.sort_by_key(|(at, _)| *at);
+
+        // Tolerates the rounding to milliseconds and a late wake up of the previous request.
+        let tolerance = Duration::from_millis(10);
+        for pair in sent.windows(2) {
+            let (previous, previous_delay) = pair[0];
+            let (next, next_delay) = pair[1];
+            let spacing = next - previous;
+            assert!(
+                spacing + tolerance >= previous_delay.max(next_delay),
+                "Only {spacing:?} between two requests with the delays {previous_delay:?} and {next_delay:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cooldowns_are_shared() {
+        let dir = Utf8TempDir::new().unwrap();
+        let origin = AtraUrlOrigin::from("example.com");
+        let throttled = PolitenessCoordination::open(dir.path()).unwrap();
+        let other = PolitenessCoordination::open(dir.path()).unwrap();
+
+        throttled
+            .cool_down(
+                &origin,
+                OffsetDateTime::now_utc() + time::Duration::milliseconds(500),
+            )
+            .await
+            .unwrap();
+        let wait = other
+            .reserve(&origin, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(wait > Duration::from_millis(400), "{wait:?}");
+    }
+
+    #[tokio::test]
+    async fn stale_entries_of_a_crashed_process_age_out() {
+        let dir = Utf8TempDir::new().unwrap();
+        let origin = AtraUrlOrigin::from("example.com");
+        let survivor =
+            PolitenessCoordination::with_stale_after(dir.path(), Duration::from_millis(200))
+                .unwrap();
+
+        // The crashed process reserved a request in an hour and died while holding the lock.
+        let now = unix_millis(OffsetDateTime::now_utc());
+        let crashed = OriginRecord {
+            last_request: now + 3_600_000,
+            delay: 3_600_000,
+            cooldown_until: None,
+            written_at: now,
+        };
+        std::fs::write(
+            dir.path().join("example.com.json"),
+            serde_json::to_vec(&crashed).unwrap(),
+        )
+        .unwrap();
+        let lock = File::create(dir.path().join("example.com.lock")).unwrap();
+        lock.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+        drop(lock);
+
+        // While the record is fresh it is respected, the stale lock does not block.
+        let wait = survivor
+            .reserve(&origin, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert!(wait > Duration::from_secs(3_000), "{wait:?}");
+
+        // Only the record of the crashed process remains.
+        std::fs::write(
+            dir.path().join("example.com.json"),
+            serde_json::to_vec(&crashed).unwrap(),
+        )
+        .unwrap();
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        let wait = survivor
+            .reserve(&origin, Duration::from_millis(10))
+            .await
+            .unwrap();
+        assert_eq!(Duration::ZERO, wait);
+        assert!(!dir.path().join("example.com.lock").exists());
+    }
+}
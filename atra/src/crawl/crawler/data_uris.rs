@@ -0,0 +1,153 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::UrlWithDepth;
+use data_encoding::BASE64_NOPAD;
+
+/// The fragment of the url a data uri is stored under starts with this, followed by the hash.
+const FRAGMENT_PREFIX: &str = "data-";
+
+/// The decoded payload of a `data:` uri.
+#[derive(Debug, Eq, PartialEq)]
+pub(super) struct DataUri {
+    /// The media type with its parameters, None if the uri does not declare one.
+    pub media_type: Option<String>,
+    pub payload: Vec<u8>,
+}
+
+impl DataUri {
+    /// Decodes `data:[<media type>][;base64],<data>`, None if [uri] is no valid data uri.
+    pub fn decode(uri: &str) -> Option<Self> {
+        let rest = uri
+            .get(..5)
+            .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+            .map(|_| &uri[5..])?;
+        let (header, data) = rest.split_once(',')?;
+        let header = String::from_utf8_lossy(&percent_decode(header.as_bytes())).into_owned();
+        let (media_type, is_base64) = match header.rsplit_once(';') {
+            Some((media_type, last)) if last.trim().eq_ignore_ascii_case("base64") => {
+                (media_type.trim(), true)
+            }
+            _ => (header.trim(), false),
+        };
+        let payload = if is_base64 {
+            let mut encoded = percent_decode(data.as_bytes());
+            encoded.retain(|value| !value.is_ascii_whitespace());
+            while encoded.last() == Some(&b'=') {
+                encoded.pop();
+            }
+            BASE64_NOPAD.decode(&encoded).ok()?
+        } else {
+            percent_decode(data.as_bytes())
+        };
+        let media_type = match media_type {
+            "" => None,
+            // Only parameters, like `;charset=utf-8`, the type defaults to text/plain.
+            parameters if parameters.starts_with(';') => Some(format!("text/plain{parameters}")),
+            media_type => Some(media_type.to_string()),
+        };
+        Some(Self {
+            media_type,
+            payload,
+        })
+    }
+}
+
+/// Decodes the `%XX` escapes of [value], malformed escapes are kept as they are.
+fn percent_decode(value: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        if value[i] == b'%' {
+            let escaped = value
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+            if let Some(escaped) = escaped {
+                decoded.push(escaped);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(value[i]);
+        i += 1;
+    }
+    decoded
+}
+
+/// The url the data [uri] found in [parent] is stored under. It is the url of [parent] with
+/// a fragment derived from the hash of [uri], the same uri on the same page always gets
+/// the same url. None if the url of [parent] can not have a fragment.
+pub(super) fn data_uri_target(parent: &UrlWithDepth, uri: &str) -> Option<UrlWithDepth> {
+    let mut url = parent.url().clone();
+    let fragment = format!(
+        "{FRAGMENT_PREFIX}{:016x}",
+        twox_hash::xxh3::hash64(uri.as_bytes())
+    );
+    url.as_mut_url()?.set_fragment(Some(&fragment));
+    // Not created with UrlWithDepth::new, it drops the fragment.
+    Some(UrlWithDepth {
+        depth: parent.depth,
+        url,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{data_uri_target, DataUri};
+    use crate::url::UrlWithDepth;
+
+    #[test]
+    fn decodes_base64_payloads() {
+        let decoded = DataUri::decode("data:image/png;base64,iVBORw0K\nGgo=").unwrap();
+        assert_eq!(Some("image/png"), decoded.media_type.as_deref());
+        assert_eq!(b"\x89PNG\r\n\x1a\n".to_vec(), decoded.payload);
+    }
+
+    #[test]
+    fn decodes_percent_encoded_payloads() {
+        let decoded = DataUri::decode("DATA:text/html;charset=utf-8,%3Cp%3EHi%21%zz</p>").unwrap();
+        assert_eq!(
+            Some("text/html;charset=utf-8"),
+            decoded.media_type.as_deref()
+        );
+        assert_eq!(b"<p>Hi!%zz</p>".to_vec(), decoded.payload);
+
+        let decoded = DataUri::decode("data:;charset=utf-8,text").unwrap();
+        assert_eq!(
+            Some("text/plain;charset=utf-8"),
+            decoded.media_type.as_deref()
+        );
+        assert_eq!(None, DataUri::decode("data:,").unwrap().media_type);
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        assert_eq!(None, DataUri::decode("https://www.example.com/,a"));
+        assert_eq!(None, DataUri::decode("data:text/plain"));
+        assert_eq!(None, DataUri::decode("data:image/png;base64,a"));
+    }
+
+    #[test]
+    fn the_target_is_stable_per_page_and_uri() {
+        let page = UrlWithDepth::from_url("https://www.example.com/page").unwrap();
+        let first = data_uri_target(&page, "data:,a").unwrap();
+        assert_eq!(first, data_uri_target(&page, "data:,a").unwrap());
+        assert_ne!(first, data_uri_target(&page, "data:,b").unwrap());
+        let url = first.url().as_url().unwrap();
+        assert_eq!("/page", url.path());
+        assert!(url.fragment().unwrap().starts_with("data-"));
+        assert_eq!(page.depth, first.depth);
+    }
+}
@@ -13,13 +13,15 @@
 // limitations under the License.
 
 use crate::client::traits::AtraClient;
+use crate::config::profiles::EffectiveOrigins;
 use crate::config::CrawlConfig;
+use crate::crawl::crawler::coordination::PolitenessCoordination;
 use crate::robots::information::RobotsInformation;
 use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
 use std::collections::HashMap;
 use std::sync::Arc;
-use time::Duration;
-use tokio::time::Interval;
+use time::{Duration, OffsetDateTime};
+use tokio::time::{Interval, MissedTickBehavior};
 
 /// Manages the interval
 pub struct InvervalManager<'a, Client: AtraClient, R: RobotsInformation> {
@@ -27,7 +29,16 @@ pub struct InvervalManager<'a, Client: AtraClient, R: RobotsInformation> {
     configured_robots: Arc<R>,
     registered_intervals: HashMap<AtraUrlOrigin, Interval>,
     default_delay: Option<Duration>,
+    origins: EffectiveOrigins,
     no_domain_default: Interval,
+    coordination: Option<PolitenessCoordination>,
+}
+
+/// Creates an interval that never fires two ticks closer than [period].
+fn polite_interval(period: std::time::Duration) -> Interval {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval
 }
 
 impl<'a, Client, R: RobotsInformation> InvervalManager<'a, Client, R>
@@ -40,53 +51,115 @@ where
             configured_robots,
             registered_intervals: HashMap::new(),
             default_delay: config.delay.clone(),
+            origins: config.effective_origins.clone(),
             no_domain_default: if let Some(ref default) = config.delay {
-                tokio::time::interval(default.clone().unsigned_abs())
+                polite_interval(default.clone().unsigned_abs())
             } else {
-                tokio::time::interval(std::time::Duration::from_millis(1000))
+                polite_interval(std::time::Duration::from_millis(1000))
             },
+            coordination: config.politeness_coordination_dir.as_ref().and_then(|dir| {
+                PolitenessCoordination::open(dir)
+                    .inspect_err(|err| {
+                        log::warn!("Failed to open the politeness coordination at {dir}, only the local delays apply: {err}")
+                    })
+                    .ok()
+            }),
+        }
+    }
+
+    /// Returns the delay between two requests to the origin of [url], if it is already known.
+    pub fn known_delay(&self, url: &UrlWithDepth) -> Option<std::time::Duration> {
+        match url.atra_origin() {
+            None => Some(self.no_domain_default.period()),
+            Some(origin) => self
+                .registered_intervals
+                .get(&origin)
+                .map(|value| value.period()),
+        }
+    }
+
+    /// Tells the other processes sharing the politeness coordination that the origin of [url]
+    /// must not be contacted before [until].
+    pub async fn cool_down(&self, url: &UrlWithDepth, until: OffsetDateTime) {
+        let (Some(coordination), Some(origin)) = (&self.coordination, url.atra_origin()) else {
+            return;
+        };
+        if let Err(err) = coordination.cool_down(&origin, until).await {
+            log::warn!("Failed to share the cooldown of {origin}: {err}");
         }
     }
 
     pub async fn wait(&mut self, url: &UrlWithDepth) {
         if let Some(origin) = url.atra_origin() {
-            if let Some(interval) = self.registered_intervals.get_mut(&origin) {
+            let period = if let Some(interval) = self.registered_intervals.get_mut(&origin) {
                 log::trace!("Wait {origin} for {}ms!", interval.period().as_millis());
                 interval.tick().await;
                 log::trace!(
                     "Finished waiting {origin} for {}!",
                     interval.period().as_millis()
                 );
+                interval.period()
             } else {
-                let target_duration = if let Some(found) = self
+                let robots_delay = self
                     .configured_robots
                     .get_or_retrieve_delay(self.client, url)
-                    .await
-                {
-                    log::trace!("Wait found {found}");
-                    found.unsigned_abs()
-                } else if let Some(default) = self.default_delay {
-                    log::trace!("Wait default {default}");
-                    default.unsigned_abs()
-                } else {
-                    log::warn!("Fallback delay 1000ms for {}", url);
-                    #[cfg(test)]
-                    {
-                        std::time::Duration::from_millis(10)
+                    .await;
+                let default_delay = self
+                    .origins
+                    .get(origin.as_ref())
+                    .and_then(|(_, found)| found.settings.delay)
+                    .or(self.default_delay);
+                // The politeness of the config is the lower bound, robots.txt can only increase it.
+                let target_duration = match (robots_delay, default_delay) {
+                    (Some(found), Some(default)) => {
+                        log::trace!("Wait max of found {found} and default {default}");
+                        found.max(default).unsigned_abs()
+                    }
+                    (Some(found), None) => {
+                        log::trace!("Wait found {found}");
+                        found.unsigned_abs()
                     }
+                    (None, Some(default)) => {
+                        log::trace!("Wait default {default}");
+                        default.unsigned_abs()
+                    }
+                    (None, None) => {
+                        log::warn!("Fallback delay 1000ms for {}", url);
+                        #[cfg(test)]
+                        {
+                            std::time::Duration::from_millis(10)
+                        }
 
-                    #[cfg(not(test))]
-                    {
-                        std::time::Duration::from_millis(1000)
+                        #[cfg(not(test))]
+                        {
+                            std::time::Duration::from_millis(1000)
+                        }
                     }
                 };
                 self.registered_intervals
-                    .insert(origin.clone(), tokio::time::interval(target_duration));
+                    .insert(origin.clone(), polite_interval(target_duration));
                 self.registered_intervals
                     .get_mut(&origin)
                     .unwrap()
                     .tick()
                     .await;
+                target_duration
+            };
+            if let Some(ref coordination) = self.coordination {
+                // The other processes may have contacted the origin in the meantime.
+                match coordination.reserve(&origin, period).await {
+                    Ok(wait) if !wait.is_zero() => {
+                        log::trace!(
+                            "Wait {origin} for {}ms for other processes!",
+                            wait.as_millis()
+                        );
+                        tokio::time::sleep(wait).await;
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        log::warn!("Failed to coordinate the politeness of {origin}: {err}")
+                    }
+                }
             }
         } else {
             log::trace!("No host tick.");
@@ -0,0 +1,155 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The layout of the stored crawl results before it was versioned, only used to decode
+//! the entries of older crawl dbs, see [SlimCrawlResult::decode].
+
+use crate::crawl::crawler::result::CrawlResultMeta;
+use crate::crawl::crawler::slim::{SlimCrawlResult, StoredDataHint};
+use crate::extraction::extractor_method::ExtractorMethod;
+use crate::extraction::html::LinkOrigin;
+use crate::extraction::marker::{ExtractorMethodHint, ExtractorMethodMeta};
+use crate::extraction::ExtractedLink;
+use crate::format::AtraFileInformation;
+use crate::toolkit::header_map_extensions::optional_header_map;
+use crate::toolkit::serde_ext::status_code;
+use crate::toolkit::LanguageInformation;
+use crate::url::UrlWithDepth;
+use encoding_rs::Encoding;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use time::OffsetDateTime;
+
+/// A [SlimCrawlResult] written before the layout was versioned.
+#[derive(Deserialize)]
+pub(super) struct LegacySlimCrawlResult {
+    meta: LegacyCrawlResultMeta,
+    stored_data_hint: StoredDataHint,
+}
+
+/// A [CrawlResultMeta] before any field was appended.
+#[derive(Deserialize)]
+struct LegacyCrawlResultMeta {
+    created_at: OffsetDateTime,
+    url: UrlWithDepth,
+    #[serde(with = "status_code")]
+    status_code: StatusCode,
+    file_information: AtraFileInformation,
+    recognized_encoding: Option<&'static Encoding>,
+    #[serde(with = "optional_header_map")]
+    headers: Option<HeaderMap>,
+    final_redirect_destination: Option<String>,
+    links: Option<Vec<LegacyExtractedLink>>,
+    language: Option<LanguageInformation>,
+}
+
+/// An [ExtractedLink] with a [LegacyExtractorMethodHint].
+#[derive(Deserialize)]
+enum LegacyExtractedLink {
+    OnSeed {
+        url: UrlWithDepth,
+        extraction_method: LegacyExtractorMethodHint,
+    },
+    Outgoing {
+        url: UrlWithDepth,
+        extraction_method: LegacyExtractorMethodHint,
+    },
+    Data {
+        base: UrlWithDepth,
+        url: UrlWithDepth,
+        extraction_method: LegacyExtractorMethodHint,
+    },
+}
+
+/// An [ExtractorMethodHint] before the links were flagged as found on an error page.
+#[derive(Deserialize)]
+struct LegacyExtractorMethodHint {
+    used_method: ExtractorMethod,
+    meta: Option<LegacyExtractorMethodMeta>,
+}
+
+/// The [ExtractorMethodMeta] of a [LegacyExtractorMethodHint].
+#[derive(Deserialize)]
+enum LegacyExtractorMethodMeta {
+    Html(LinkOrigin),
+    Zip {
+        path: String,
+        underlying: Box<LegacyExtractorMethodHint>,
+    },
+}
+
+impl From<LegacySlimCrawlResult> for SlimCrawlResult {
+    fn from(value: LegacySlimCrawlResult) -> Self {
+        let meta = value.meta;
+        Self {
+            meta: CrawlResultMeta::new(
+                meta.created_at,
+                meta.url,
+                meta.status_code,
+                meta.file_information,
+                meta.recognized_encoding,
+                meta.headers,
+                meta.final_redirect_destination,
+                meta.links
+                    .map(|links| links.into_iter().map(ExtractedLink::from).collect()),
+                meta.language,
+            ),
+            stored_data_hint: value.stored_data_hint,
+        }
+    }
+}
+
+impl From<LegacyExtractedLink> for ExtractedLink {
+    fn from(value: LegacyExtractedLink) -> Self {
+        match value {
+            LegacyExtractedLink::OnSeed {
+                url,
+                extraction_method,
+            } => ExtractedLink::OnSeed {
+                url,
+                extraction_method: extraction_method.into(),
+            },
+            LegacyExtractedLink::Outgoing {
+                url,
+                extraction_method,
+            } => ExtractedLink::Outgoing {
+                url,
+                extraction_method: extraction_method.into(),
+            },
+            LegacyExtractedLink::Data {
+                base,
+                url,
+                extraction_method,
+            } => ExtractedLink::Data {
+                base,
+                url,
+                extraction_method: extraction_method.into(),
+            },
+        }
+    }
+}
+
+impl From<LegacyExtractorMethodHint> for ExtractorMethodHint {
+    fn from(value: LegacyExtractorMethodHint) -> Self {
+        let meta = value.meta.map(|meta| match meta {
+            LegacyExtractorMethodMeta::Html(origin) => ExtractorMethodMeta::Html(origin),
+            LegacyExtractorMethodMeta::Zip { path, underlying } => ExtractorMethodMeta::Zip {
+                path,
+                underlying: Box::new((*underlying).into()),
+            },
+        });
+        ExtractorMethodHint::new(value.used_method, meta)
+    }
+}
@@ -0,0 +1,415 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::robots::unavailable_after::{find_ignore_ascii_case, parse_date};
+use crate::static_selector;
+use crate::url::dates::{date_from_path, DatePrecision};
+use crate::url::UrlWithDepth;
+use reqwest::header::{HeaderMap, LAST_MODIFIED};
+use schemars::JsonSchema;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use time::{Duration, OffsetDateTime};
+
+const ARTICLE_PUBLISHED_TIME: &str = "article:published_time";
+
+/// Signals closer than this agree with each other.
+const AGREEMENT_TOLERANCE: Duration = Duration::days(1);
+
+static_selector!(META_WITH_CONTENT = "meta[content]");
+static_selector!(TIME_WITH_DATETIME = "time[datetime]");
+
+/// The source of a publication date, ordered from the most to the least reliable.
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub enum PublicationSource {
+    /// `<meta property="article:published_time">`
+    ArticleMeta,
+    /// `<time datetime>`, an element with `pubdate` or `itemprop="datePublished"` wins.
+    TimeElement,
+    /// A date in the path of the url like `/2023/07/15/slug`.
+    UrlPath,
+    /// The `Last-Modified` header.
+    LastModified,
+}
+
+/// How far a publication estimate can be trusted.
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub enum PublicationConfidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl PublicationConfidence {
+    fn raised(self) -> Self {
+        match self {
+            Self::Low => Self::Medium,
+            _ => Self::High,
+        }
+    }
+
+    fn lowered(self) -> Self {
+        match self {
+            Self::High => Self::Medium,
+            _ => Self::Low,
+        }
+    }
+}
+
+/// A publication date found by a single source.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PublicationSignal {
+    pub source: PublicationSource,
+    pub published_at: OffsetDateTime,
+    pub precision: DatePrecision,
+}
+
+impl PublicationSignal {
+    fn new(source: PublicationSource, published_at: OffsetDateTime) -> Self {
+        Self {
+            source,
+            published_at,
+            precision: DatePrecision::Day,
+        }
+    }
+
+    /// The confidence of the signal without the other signals.
+    fn confidence(&self) -> PublicationConfidence {
+        match (self.source, self.precision) {
+            (_, DatePrecision::Month) => PublicationConfidence::Low,
+            (PublicationSource::ArticleMeta, _) => PublicationConfidence::High,
+            (PublicationSource::TimeElement | PublicationSource::UrlPath, _) => {
+                PublicationConfidence::Medium
+            }
+            (PublicationSource::LastModified, _) => PublicationConfidence::Low,
+        }
+    }
+
+    /// Returns true if both signals name the same day, or the same month if one of them
+    /// only knows the month.
+    fn agrees_with(&self, other: &Self) -> bool {
+        if self.precision == DatePrecision::Month || other.precision == DatePrecision::Month {
+            let a = self.published_at.to_offset(time::UtcOffset::UTC);
+            let b = other.published_at.to_offset(time::UtcOffset::UTC);
+            a.year() == b.year() && a.month() == b.month()
+        } else {
+            (self.published_at - other.published_at).abs() <= AGREEMENT_TOLERANCE
+        }
+    }
+
+    /// Returns true if the [other] signal contradicts this one. A page modified after
+    /// its publication does not contradict it.
+    fn contradicts(&self, other: &Self) -> bool {
+        if self.agrees_with(other) {
+            return false;
+        }
+        !(other.source == PublicationSource::LastModified && other.published_at > self.published_at)
+    }
+}
+
+/// The estimated publication date of a page.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct PublicationEstimate {
+    /// The publication date, the first of the month if only the month is known.
+    pub published_at: OffsetDateTime,
+    /// The source of [PublicationEstimate::published_at].
+    pub source: PublicationSource,
+    pub confidence: PublicationConfidence,
+}
+
+impl PublicationEstimate {
+    /// Estimates the publication date of the page at [url] from its [headers] and, for html, its content.
+    pub fn estimate(
+        url: &UrlWithDepth,
+        headers: Option<&HeaderMap>,
+        html: Option<&str>,
+    ) -> Option<Self> {
+        Self::from_signals(&publication_signals(url, headers, html))
+    }
+
+    /// Uses the most reliable of the [signals]. The confidence is raised if most of the other
+    /// signals agree with it and lowered if most of them contradict it.
+    pub fn from_signals(signals: &[PublicationSignal]) -> Option<Self> {
+        let best = signals
+            .iter()
+            .min_by_key(|signal| (signal.source, signal.precision == DatePrecision::Month))?;
+        let (agreeing, contradicting) = signals
+            .iter()
+            .filter(|signal| !std::ptr::eq(*signal, best))
+            .fold((0usize, 0usize), |(agreeing, contradicting), signal| {
+                if best.agrees_with(signal) {
+                    (agreeing + 1, contradicting)
+                } else if best.contradicts(signal) {
+                    (agreeing, contradicting + 1)
+                } else {
+                    (agreeing, contradicting)
+                }
+            });
+        let confidence = match agreeing.cmp(&contradicting) {
+            Ordering::Greater => best.confidence().raised(),
+            Ordering::Less => best.confidence().lowered(),
+            Ordering::Equal => best.confidence(),
+        };
+        Some(Self {
+            published_at: best.published_at,
+            source: best.source,
+            confidence,
+        })
+    }
+}
+
+/// Collects the publication dates of the page at [url] found in the path of the [url],
+/// the `Last-Modified` of the [headers] and the meta tags and time elements of the [html].
+pub fn publication_signals(
+    url: &UrlWithDepth,
+    headers: Option<&HeaderMap>,
+    html: Option<&str>,
+) -> Vec<PublicationSignal> {
+    let mut signals = Vec::new();
+    if let Some(html) = html {
+        signals.extend(signals_from_html(html));
+    }
+    if let Some(found) = url.url().path().and_then(date_from_path) {
+        signals.push(PublicationSignal {
+            source: PublicationSource::UrlPath,
+            published_at: found.date.midnight().assume_utc(),
+            precision: found.precision,
+        });
+    }
+    let last_modified = headers
+        .and_then(|headers| headers.get(LAST_MODIFIED))
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_date);
+    if let Some(published_at) = last_modified {
+        signals.push(PublicationSignal::new(
+            PublicationSource::LastModified,
+            published_at,
+        ));
+    }
+    signals
+}
+
+/// Reads the first `article:published_time` and the `<time datetime>` of the publication.
+fn signals_from_html(html: &str) -> Vec<PublicationSignal> {
+    // Parsing the document is expensive, many pages have neither.
+    if find_ignore_ascii_case(html, ARTICLE_PUBLISHED_TIME).is_none()
+        && find_ignore_ascii_case(html, "<time").is_none()
+    {
+        return Vec::new();
+    }
+    let document = Html::parse_document(html);
+    let mut signals = Vec::new();
+    let article = document
+        .select(&META_WITH_CONTENT)
+        .filter(|element| {
+            element
+                .attr("property")
+                .or_else(|| element.attr("name"))
+                .is_some_and(|name| name.trim().eq_ignore_ascii_case(ARTICLE_PUBLISHED_TIME))
+        })
+        .filter_map(|element| element.attr("content"))
+        .find_map(parse_date);
+    if let Some(published_at) = article {
+        signals.push(PublicationSignal::new(
+            PublicationSource::ArticleMeta,
+            published_at,
+        ));
+    }
+    let mut first = None;
+    let mut marked = None;
+    for element in document.select(&TIME_WITH_DATETIME) {
+        let Some(published_at) = element.attr("datetime").and_then(parse_date) else {
+            continue;
+        };
+        let is_marked = element.attr("pubdate").is_some()
+            || element
+                .attr("itemprop")
+                .is_some_and(|value| value.trim().eq_ignore_ascii_case("datePublished"));
+        if is_marked {
+            marked = Some(published_at);
+            break;
+        }
+        first.get_or_insert(published_at);
+    }
+    if let Some(published_at) = marked.or(first) {
+        signals.push(PublicationSignal::new(
+            PublicationSource::TimeElement,
+            published_at,
+        ));
+    }
+    signals
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        publication_signals, PublicationConfidence, PublicationEstimate, PublicationSignal,
+        PublicationSource,
+    };
+    use crate::url::dates::DatePrecision;
+    use crate::url::UrlWithDepth;
+    use reqwest::header::{HeaderMap, HeaderValue, LAST_MODIFIED};
+    use time::{Date, Month, OffsetDateTime};
+
+    fn day(year: i32, month: Month, day: u8) -> OffsetDateTime {
+        Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .midnight()
+            .assume_utc()
+    }
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    fn last_modified(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(LAST_MODIFIED, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn reads_the_article_meta() {
+        let html = r#"<html><head><meta property="article:published_time" content="2023-07-15T10:00:00+02:00"></head><body></body></html>"#;
+        let estimate =
+            PublicationEstimate::estimate(&url("https://www.example.com/a.html"), None, Some(html))
+                .unwrap();
+        assert_eq!(PublicationSource::ArticleMeta, estimate.source);
+        assert_eq!(PublicationConfidence::High, estimate.confidence);
+        assert_eq!(
+            day(2023, Month::July, 15).replace_hour(8).unwrap(),
+            estimate.published_at
+        );
+    }
+
+    #[test]
+    fn reads_the_time_element() {
+        let html = r#"<html><body><time datetime="2020-01-01">Updated</time><time pubdate datetime="2019-03-04">Published</time></body></html>"#;
+        let estimate =
+            PublicationEstimate::estimate(&url("https://www.example.com/a.html"), None, Some(html))
+                .unwrap();
+        assert_eq!(PublicationSource::TimeElement, estimate.source);
+        assert_eq!(PublicationConfidence::Medium, estimate.confidence);
+        assert_eq!(day(2019, Month::March, 4), estimate.published_at);
+
+        let html = r#"<html><body><time datetime="2020-01-01">Date</time></body></html>"#;
+        let estimate =
+            PublicationEstimate::estimate(&url("https://www.example.com/a.html"), None, Some(html))
+                .unwrap();
+        assert_eq!(day(2020, Month::January, 1), estimate.published_at);
+    }
+
+    #[test]
+    fn reads_the_url_path() {
+        let estimate = PublicationEstimate::estimate(
+            &url("https://www.example.com/2023/07/15/slug"),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(PublicationSource::UrlPath, estimate.source);
+        assert_eq!(PublicationConfidence::Medium, estimate.confidence);
+        assert_eq!(day(2023, Month::July, 15), estimate.published_at);
+
+        let estimate =
+            PublicationEstimate::estimate(&url("https://www.example.com/2023/07/slug"), None, None)
+                .unwrap();
+        assert_eq!(PublicationConfidence::Low, estimate.confidence);
+        assert_eq!(day(2023, Month::July, 1), estimate.published_at);
+    }
+
+    #[test]
+    fn reads_the_last_modified_header() {
+        let headers = last_modified("Sat, 15 Jul 2023 10:00:00 GMT");
+        let estimate = PublicationEstimate::estimate(
+            &url("https://www.example.com/a.html"),
+            Some(&headers),
+            Some("<html><body>Nothing</body></html>"),
+        )
+        .unwrap();
+        assert_eq!(PublicationSource::LastModified, estimate.source);
+        assert_eq!(PublicationConfidence::Low, estimate.confidence);
+        assert_eq!(
+            day(2023, Month::July, 15).replace_hour(10).unwrap(),
+            estimate.published_at
+        );
+        assert!(
+            PublicationEstimate::estimate(&url("https://www.example.com/"), None, None).is_none()
+        );
+    }
+
+    #[test]
+    fn agreeing_signals_raise_the_confidence() {
+        let headers = last_modified("Sun, 16 Jul 2023 10:00:00 GMT");
+        let estimate = PublicationEstimate::estimate(
+            &url("https://www.example.com/2023/07/slug"),
+            Some(&headers),
+            None,
+        )
+        .unwrap();
+        assert_eq!(PublicationSource::UrlPath, estimate.source);
+        assert_eq!(PublicationConfidence::Medium, estimate.confidence);
+
+        let html =
+            r#"<html><body><time datetime="2023-07-15T12:00:00Z">Published</time></body></html>"#;
+        let estimate = PublicationEstimate::estimate(
+            &url("https://www.example.com/2023/07/15/slug"),
+            None,
+            Some(html),
+        )
+        .unwrap();
+        assert_eq!(PublicationSource::TimeElement, estimate.source);
+        assert_eq!(PublicationConfidence::High, estimate.confidence);
+    }
+
+    #[test]
+    fn conflicting_signals_lower_the_confidence() {
+        let html = r#"<html><head><meta property="article:published_time" content="2023-07-15"></head><body><time datetime="2021-01-01">Published</time></body></html>"#;
+        let signals = publication_signals(
+            &url("https://www.example.com/2020/05/01/slug"),
+            None,
+            Some(html),
+        );
+        assert_eq!(3, signals.len());
+        let estimate = PublicationEstimate::from_signals(&signals).unwrap();
+        assert_eq!(PublicationSource::ArticleMeta, estimate.source);
+        assert_eq!(day(2023, Month::July, 15), estimate.published_at);
+        assert_eq!(PublicationConfidence::Medium, estimate.confidence);
+
+        // A page modified after its publication is no conflict, one modified before is.
+        let published = PublicationSignal {
+            source: PublicationSource::UrlPath,
+            published_at: day(2023, Month::July, 15),
+            precision: DatePrecision::Day,
+        };
+        let modified = |published_at| PublicationSignal {
+            source: PublicationSource::LastModified,
+            published_at,
+            precision: DatePrecision::Day,
+        };
+        let estimate =
+            PublicationEstimate::from_signals(&[published, modified(day(2024, Month::May, 1))])
+                .unwrap();
+        assert_eq!(PublicationConfidence::Medium, estimate.confidence);
+        let estimate =
+            PublicationEstimate::from_signals(&[published, modified(day(2020, Month::May, 1))])
+                .unwrap();
+        assert_eq!(PublicationConfidence::Low, estimate.confidence);
+    }
+}
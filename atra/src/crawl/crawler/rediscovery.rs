@@ -0,0 +1,140 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::traits::FetchFailureKind;
+use crate::config::CrawlConfig;
+use crate::crawl::CrawlMetrics;
+use crate::fetching::RediscoveryRetry;
+use itertools::Itertools;
+use time::OffsetDateTime;
+
+/// Counts the outcomes of the requests a crawl task sent to the origin of its seed.
+#[derive(Debug, Default)]
+pub(super) struct OriginReachability {
+    responses: usize,
+    connect_failures: usize,
+    other_failures: usize,
+}
+
+impl OriginReachability {
+    /// Counts a request answered by the origin, no matter the status code.
+    pub fn record_response(&mut self) {
+        self.responses += 1;
+    }
+
+    /// Counts a failed request by the [kind] of the failure.
+    /// A failed proxy is not the fault of the origin and is retried on its own.
+    pub fn record_failure(&mut self, kind: FetchFailureKind) {
+        match kind {
+            FetchFailureKind::Connect => self.connect_failures += 1,
+            _ => self.other_failures += 1,
+        }
+    }
+
+    /// Returns true if the origin was contacted and every request failed to connect.
+    pub fn is_unreachable(&self) -> bool {
+        self.connect_failures > 0 && self.responses == 0 && self.other_failures == 0
+    }
+}
+
+/// What happens with a seed after its origin was unreachable.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(super) enum Rediscovery {
+    /// The seed waits in the queue for the next attempt.
+    Scheduled(RediscoveryRetry),
+    /// The attempts are exhausted and the origin is given up.
+    GiveUp { attempts: u8 },
+}
+
+impl Rediscovery {
+    /// Plans the next attempt after the origin was unreachable in the current one and
+    /// in [attempts] earlier ones.
+    pub fn plan(config: &CrawlConfig, attempts: u8, now: OffsetDateTime) -> Self {
+        let attempt = attempts.saturating_add(1);
+        match config
+            .rediscovery
+            .as_ref()
+            .and_then(|rediscovery| rediscovery.delay_before(attempt))
+        {
+            Some(delay) => Self::Scheduled(RediscoveryRetry::new(attempt, now + delay)),
+            None => Self::GiveUp { attempts },
+        }
+    }
+}
+
+/// Logs the origins given up because they were unreachable, their part of the crawl is empty.
+pub fn log_unreachable_origins(metrics: &CrawlMetrics) {
+    let origins = metrics.unreachable_origins();
+    if origins.is_empty() {
+        return;
+    }
+    log::warn!(
+        "{} origins were unreachable, nothing was crawled from them: {}",
+        origins.len(),
+        origins.iter().join(", ")
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::{OriginReachability, Rediscovery};
+    use crate::client::traits::FetchFailureKind;
+    use crate::config::{CrawlConfig, RediscoveryConfig};
+    use crate::fetching::RediscoveryRetry;
+    use time::{Duration, OffsetDateTime};
+
+    #[test]
+    fn only_connect_failures_make_an_origin_unreachable() {
+        let mut reachability = OriginReachability::default();
+        assert!(!reachability.is_unreachable());
+        reachability.record_failure(FetchFailureKind::Connect);
+        assert!(reachability.is_unreachable());
+        reachability.record_failure(FetchFailureKind::ProxyConnect);
+        assert!(!reachability.is_unreachable());
+
+        let mut reachability = OriginReachability::default();
+        reachability.record_failure(FetchFailureKind::Connect);
+        reachability.record_response();
+        assert!(!reachability.is_unreachable());
+    }
+
+    #[test]
+    fn the_delay_grows_until_the_attempts_are_exhausted() {
+        let now = OffsetDateTime::UNIX_EPOCH;
+        let mut config = CrawlConfig::default();
+        assert_eq!(
+            Rediscovery::GiveUp { attempts: 0 },
+            Rediscovery::plan(&config, 0, now)
+        );
+
+        config.rediscovery = Some(RediscoveryConfig {
+            max_attempts: 3,
+            delay: Duration::minutes(10),
+            backoff_factor: 3,
+        });
+        for (attempts, delay) in [(0, 10), (1, 30), (2, 90)] {
+            assert_eq!(
+                Rediscovery::Scheduled(RediscoveryRetry::new(
+                    attempts + 1,
+                    now + Duration::minutes(delay)
+                )),
+                Rediscovery::plan(&config, attempts, now)
+            );
+        }
+        assert_eq!(
+            Rediscovery::GiveUp { attempts: 3 },
+            Rediscovery::plan(&config, 3, now)
+        );
+    }
+}
@@ -12,20 +12,29 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::client::traits::FetchFailureKind;
+use crate::config::header_persistence::HeaderPersistence;
+use crate::crawl::crawler::publication::PublicationEstimate;
+use crate::crawl::crawler::sitemaps::SitemapSummary;
 use crate::data::RawVecData;
 use crate::extraction::ExtractedLink;
-use crate::fetching::ResponseData;
+use crate::fetching::{CacheValidators, DownloadRejection, HttpVersion, RawHeaders, ResponseData};
 use crate::format::AtraFileInformation;
+use crate::gdbr::identifier::GdbrScore;
+use crate::origin_metadata::PageSignals;
+use crate::robots::UnavailableAfter;
+use crate::tls_info::TlsInfo;
+use crate::toolkit::fuzzy_hash::{FuzzyHash, SimHash};
 use crate::toolkit::header_map_extensions::optional_header_map;
 use crate::toolkit::serde_ext::status_code;
 use crate::toolkit::LanguageInformation;
 use crate::url::UrlWithDepth;
 use encoding_rs::Encoding;
-use reqwest::header::HeaderMap;
+use reqwest::header::{HeaderMap, CONTENT_LANGUAGE};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
-use time::OffsetDateTime;
+use time::{Duration, OffsetDateTime};
 
 /// A container for the meta data
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
@@ -50,6 +59,59 @@ pub struct CrawlResultMeta {
     pub links: Option<Vec<ExtractedLink>>,
     /// The language identified by atra.
     pub language: Option<LanguageInformation>,
+    /// The `ETag` and `Last-Modified` of the response, used for conditional recrawls.
+    pub validators: Option<CacheValidators>,
+    /// The `unavailable_after` robots directive of the page.
+    pub unavailable_after: Option<UnavailableAfter>,
+    /// The proxy serving the fetch, without credentials. None if fetched directly.
+    pub proxy: Option<String>,
+    /// Set if the entry supports the crawl instead of being a part of the website.
+    pub auxiliary: Option<AuxiliaryDocument>,
+    /// The score of the GDBR classifier for a sample of the text.
+    pub gdbr_score: Option<GdbrScore>,
+    /// Set if fields were computed after the crawl by a backfill.
+    pub backfill: Option<BackfillNote>,
+    /// The estimated publication date, only set if a publication window is configured.
+    pub publication: Option<PublicationEstimate>,
+    /// The tag of the seed of the origin, only set for structured seeds with a tag.
+    pub tag: Option<String>,
+    /// Set if the body was not downloaded because of its type or size.
+    pub download_rejection: Option<DownloadRejection>,
+    /// Set if only the start of a body streamed without an end was downloaded.
+    pub truncated: bool,
+    /// The url as found before the url normalization, only set if the normalization rewrote it.
+    pub original_url: Option<String>,
+    /// Set if the page was imported instead of fetched by atra.
+    pub import: Option<ImportNote>,
+    /// The timings of the request, if they are known.
+    pub fetch_timings: Option<FetchTimings>,
+    /// The fuzzy hash of the stored body, used to find near duplicates.
+    pub fuzzy_hash: Option<FuzzyHash>,
+    /// The simhash of the words of the decoded text.
+    pub simhash: Option<SimHash>,
+    /// Set if the header persistence dropped or redacted headers, the [headers] are incomplete.
+    pub header_fidelity: Option<HeaderFidelity>,
+    /// The headers sent with the request, without credentials. Written to the request record
+    /// of the warc.
+    #[serde(with = "optional_header_map")]
+    pub request_headers: Option<HeaderMap>,
+    /// The `Content-Language` of the response.
+    pub content_language: Option<String>,
+    /// The language asked for with `Accept-Language`, only set for the language variants of
+    /// a page.
+    pub language_variant: Option<String>,
+    /// The http version of the response, None if it is not known.
+    pub http_version: Option<HttpVersion>,
+    /// The certificate of the connection, None if the response was not received over tls
+    /// or the tls info is not captured.
+    pub tls: Option<TlsInfo>,
+    /// The header lines of the response as received, preferred over the [headers] by the
+    /// warc writer. Only set with `faithful_headers`.
+    pub raw_headers: Option<RawHeaders>,
+    /// True if the file format was detected on a part of the content only.
+    pub sniff_window_truncated: bool,
+    /// True if the [language] is not reliable enough to run the GDBR classifier.
+    pub language_uncertain: bool,
 }
 
 impl CrawlResultMeta {
@@ -64,6 +126,17 @@ impl CrawlResultMeta {
         links: Option<Vec<ExtractedLink>>,
         language: Option<LanguageInformation>,
     ) -> Self {
+        let sniff_window_truncated = file_information.sniff_window_truncated;
+        let language_uncertain = language.is_some_and(|value| value.is_uncertain());
+        let validators = headers.as_ref().and_then(CacheValidators::from_headers);
+        let unavailable_after = headers.as_ref().and_then(UnavailableAfter::from_headers);
+        let content_language = headers
+            .as_ref()
+            .and_then(|headers| headers.get(CONTENT_LANGUAGE))
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
         Self {
             created_at,
             url,
@@ -74,8 +147,145 @@ impl CrawlResultMeta {
             final_redirect_destination,
             links,
             language,
+            validators,
+            unavailable_after,
+            proxy: None,
+            auxiliary: None,
+            gdbr_score: None,
+            backfill: None,
+            publication: None,
+            tag: None,
+            download_rejection: None,
+            truncated: false,
+            original_url: None,
+            import: None,
+            fetch_timings: None,
+            fuzzy_hash: None,
+            simhash: None,
+            header_fidelity: None,
+            request_headers: None,
+            content_language,
+            language_variant: None,
+            http_version: None,
+            tls: None,
+            raw_headers: None,
+            sniff_window_truncated,
+            language_uncertain,
+        }
+    }
+
+    /// Returns the [language] with its certainty, use it instead of the field for stored
+    /// results.
+    pub fn language_information(&self) -> Option<LanguageInformation> {
+        self.language
+            .map(|value| value.with_uncertain(self.language_uncertain))
+    }
+
+    /// Sets the [language] and its certainty.
+    pub fn set_language(&mut self, language: Option<LanguageInformation>) {
+        self.language_uncertain = language.is_some_and(|value| value.is_uncertain());
+        self.language = language;
+    }
+
+    /// Drops and redacts the headers according to the [policy] before they are persisted.
+    pub fn apply_header_persistence(&mut self, policy: &HeaderPersistence) {
+        if let Some(raw_headers) = self.raw_headers.as_mut() {
+            policy.apply_raw(raw_headers);
+        }
+        let Some(headers) = self.headers.as_mut() else {
+            return;
+        };
+        let fidelity = policy.apply(headers);
+        if fidelity.dropped > 0 || fidelity.redacted > 0 {
+            self.header_fidelity = Some(fidelity);
         }
     }
+
+    /// Adds an `unavailable_after` directive found in the content, the earliest expiry wins.
+    pub fn add_unavailable_after(&mut self, found: UnavailableAfter) {
+        self.unavailable_after = Some(match self.unavailable_after.take() {
+            Some(current) => current.earliest(found),
+            None => found,
+        });
+    }
+
+    /// Returns true if the page asked to be unavailable after a point before [now].
+    pub fn is_expired_at(&self, now: OffsetDateTime) -> bool {
+        self.unavailable_after
+            .as_ref()
+            .is_some_and(|value| value.is_expired_at(now))
+    }
+}
+
+/// A field of [CrawlResultMeta] that can be computed after the crawl.
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Hash, strum::Display, clap::ValueEnum, Serialize, Deserialize,
+)]
+pub enum BackfillField {
+    /// The [LanguageInformation] of the page.
+    Language,
+    /// The [GdbrScore] of the page.
+    Gdbr,
+}
+
+/// Records which fields of a [CrawlResultMeta] were computed by a backfill.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BackfillNote {
+    /// The version of atra that computed the fields.
+    pub atra_version: String,
+    /// The time of the backfill.
+    pub backfilled_at: OffsetDateTime,
+    /// The computed fields, including the ones without a result.
+    pub fields: Vec<BackfillField>,
+}
+
+impl BackfillNote {
+    /// Returns true if the backfill already tried to compute the [field].
+    pub fn contains(&self, field: BackfillField) -> bool {
+        self.fields.contains(&field)
+    }
+}
+
+/// Records where an imported page comes from.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ImportNote {
+    /// The imported file, e.g. a HAR file exported by a browser.
+    pub source: String,
+    /// The time of the import.
+    pub imported_at: OffsetDateTime,
+}
+
+/// The number of header values the header persistence removed from a page.
+#[derive(Debug, Default, Copy, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HeaderFidelity {
+    /// The values that were not persisted.
+    pub dropped: u32,
+    /// The values that were replaced by a marker.
+    pub redacted: u32,
+}
+
+/// The timings of a request, a phase is None if it did not apply or is unknown.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct FetchTimings {
+    /// The start of the request.
+    pub started_at: OffsetDateTime,
+    /// The time from the start of the request until the response was received.
+    pub total: Duration,
+    pub blocked: Option<Duration>,
+    pub dns: Option<Duration>,
+    pub connect: Option<Duration>,
+    pub ssl: Option<Duration>,
+    pub send: Option<Duration>,
+    /// The time waiting for the first byte of the response.
+    pub wait: Option<Duration>,
+    pub receive: Option<Duration>,
+}
+
+/// A document fetched to support the crawl, archived like any other page.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum AuxiliaryDocument {
+    /// A sitemap or sitemap index.
+    Sitemap(SitemapSummary),
 }
 
 // page_type = AtraFileFormat::format
@@ -87,6 +297,12 @@ pub struct CrawlResult {
     pub meta: CrawlResultMeta,
     /// The bytes of the resource.
     pub content: RawVecData,
+    /// The plain text of the resource, written to the warc if `crawl.text_sidecar` is set.
+    #[serde(default)]
+    pub text: Option<String>,
+    /// The signals of the page for the metadata of its origin, if `crawl.origin_metadata` is set.
+    #[serde(default)]
+    pub origin_signals: Option<PageSignals>,
 }
 
 impl CrawlResult {
@@ -103,40 +319,189 @@ impl CrawlResult {
             result.shrink_to_fit();
             result
         });
+        let mut meta = CrawlResultMeta::new(
+            created_at,
+            page.url,
+            page.status_code,
+            file_information,
+            recognized_encoding,
+            page.headers,
+            page.final_redirect_destination,
+            links,
+            language,
+        );
+        meta.proxy = page.proxy;
+        meta.request_headers = page.request_headers;
+        meta.http_version = page.http_version;
         Self {
-            meta: CrawlResultMeta::new(
-                created_at,
-                page.url,
-                page.status_code,
-                file_information,
-                recognized_encoding,
-                page.headers,
-                page.final_redirect_destination,
-                links,
-                language,
-            ),
+            meta,
             content: page.content,
+            text: None,
+            origin_signals: None,
         }
     }
 }
 
+/// The outcome of fetching a single url without touching the databases,
+/// whoever owns the databases stores it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum FetchOutcome {
+    /// The page was fetched and analyzed.
+    Crawled(CrawlResult),
+    /// The robots.txt forbids fetching the url.
+    NotAllowed,
+    /// The fetch failed.
+    Failed {
+        kind: FetchFailureKind,
+        reason: String,
+    },
+    /// The page was fetched but can not be processed.
+    Unprocessable,
+}
+
 #[cfg(test)]
 pub mod test {
-    use crate::crawl::CrawlResult;
+    use crate::crawl::{CrawlResult, SlimCrawlResult, StoredDataHint};
     use crate::data::RawVecData;
     use crate::extraction::extractor_method::ExtractorMethod;
-    use crate::extraction::marker::ExtractorMethodHint;
+    use crate::extraction::html::LinkOrigin;
+    use crate::extraction::marker::{ExtractorMethodHint, ExtractorMethodMeta};
     use crate::extraction::ExtractedLink;
     use crate::fetching::ResponseData;
     use crate::format::supported::InterpretedProcessibleFileFormat;
     use crate::format::AtraFileInformation;
+    use crate::toolkit::header_map_extensions::optional_header_map;
+    use crate::toolkit::serde_ext::status_code;
     use crate::toolkit::LanguageInformation;
     use crate::url::UrlWithDepth;
+    use encoding_rs::Encoding;
     use reqwest::header::HeaderMap;
     use reqwest::StatusCode;
+    use serde::Serialize;
     use std::collections::HashSet;
     use time::OffsetDateTime;
 
+    /// A [SlimCrawlResult] as written before the layout was versioned.
+    #[derive(Serialize)]
+    struct OldSlimCrawlResult {
+        meta: OldCrawlResultMeta,
+        stored_data_hint: StoredDataHint,
+    }
+
+    #[derive(Serialize)]
+    struct OldCrawlResultMeta {
+        created_at: OffsetDateTime,
+        url: UrlWithDepth,
+        #[serde(with = "status_code")]
+        status_code: StatusCode,
+        file_information: AtraFileInformation,
+        recognized_encoding: Option<&'static Encoding>,
+        #[serde(with = "optional_header_map")]
+        headers: Option<HeaderMap>,
+        final_redirect_destination: Option<String>,
+        links: Option<Vec<OldExtractedLink>>,
+        language: Option<LanguageInformation>,
+    }
+
+    #[derive(Serialize)]
+    enum OldExtractedLink {
+        OnSeed {
+            url: UrlWithDepth,
+            extraction_method: OldExtractorMethodHint,
+        },
+        Outgoing {
+            url: UrlWithDepth,
+            extraction_method: OldExtractorMethodHint,
+        },
+        Data {
+            base: UrlWithDepth,
+            url: UrlWithDepth,
+            extraction_method: OldExtractorMethodHint,
+        },
+    }
+
+    #[derive(Serialize)]
+    struct OldExtractorMethodHint {
+        used_method: ExtractorMethod,
+        meta: Option<OldExtractorMethodMeta>,
+    }
+
+    #[derive(Serialize)]
+    enum OldExtractorMethodMeta {
+        Html(LinkOrigin),
+        Zip {
+            path: String,
+            underlying: Box<OldExtractorMethodHint>,
+        },
+    }
+
+    impl From<&ExtractorMethodHint> for OldExtractorMethodHint {
+        fn from(value: &ExtractorMethodHint) -> Self {
+            Self {
+                used_method: value.used_method,
+                meta: value.meta.as_ref().map(|meta| match meta {
+                    ExtractorMethodMeta::Html(origin) => OldExtractorMethodMeta::Html(*origin),
+                    ExtractorMethodMeta::Zip { path, underlying } => OldExtractorMethodMeta::Zip {
+                        path: path.clone(),
+                        underlying: Box::new(underlying.as_ref().into()),
+                    },
+                    other => panic!("{other:?} is not part of the old layout."),
+                }),
+            }
+        }
+    }
+
+    /// Encodes the fields of [slim] that existed before the layout of the crawl db was
+    /// versioned, like an entry of a crawl db written by an older version of atra.
+    pub fn encode_legacy(slim: &SlimCrawlResult) -> Vec<u8> {
+        let meta = &slim.meta;
+        let links = meta.links.as_ref().map(|links| {
+            links
+                .iter()
+                .map(|link| match link {
+                    ExtractedLink::OnSeed {
+                        url,
+                        extraction_method,
+                    } => OldExtractedLink::OnSeed {
+                        url: url.clone(),
+                        extraction_method: extraction_method.into(),
+                    },
+                    ExtractedLink::Outgoing {
+                        url,
+                        extraction_method,
+                    } => OldExtractedLink::Outgoing {
+                        url: url.clone(),
+                        extraction_method: extraction_method.into(),
+                    },
+                    ExtractedLink::Data {
+                        base,
+                        url,
+                        extraction_method,
+                    } => OldExtractedLink::Data {
+                        base: base.clone(),
+                        url: url.clone(),
+                        extraction_method: extraction_method.into(),
+                    },
+                })
+                .collect()
+        });
+        bincode::serialize(&OldSlimCrawlResult {
+            meta: OldCrawlResultMeta {
+                created_at: meta.created_at,
+                url: meta.url.clone(),
+                status_code: meta.status_code,
+                file_information: meta.file_information.clone(),
+                recognized_encoding: meta.recognized_encoding,
+                headers: meta.headers.clone(),
+                final_redirect_destination: meta.final_redirect_destination.clone(),
+                links,
+                language: meta.language,
+            },
+            stored_data_hint: slim.stored_data_hint.clone(),
+        })
+        .unwrap()
+    }
+
     pub fn create_testdata_with_on_seed(content: Option<RawVecData>) -> CrawlResult {
         create_test_data(
             UrlWithDepth::from_url("https://www.google.de/").unwrap(),
@@ -0,0 +1,91 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::traits::AtraClient;
+use crate::contexts::traits::{SupportsCrawlEvents, SupportsDomainHandling, SupportsUrlQueue};
+use crate::crawl::CrawlEvent;
+use crate::queue::{QueueError, UrlQueue};
+use crate::recrawl_management::DomainLastCrawledManager;
+use crate::robots::{AnyRobotsInformation, RobotsDiff, RobotsManager};
+use crate::url::{AtraOriginProvider, UrlWithDepth};
+use std::collections::VecDeque;
+
+/// Downloads the robots.txt of the origin of [url] again and applies it right away.
+/// If the rules for our agent changed, the diff is logged and the queued urls of the origin
+/// that are no longer allowed are dropped from the local [queue] and the url queue.
+pub(super) async fn revalidate_robots<C, Client, R>(
+    context: &C,
+    client: &Client,
+    robots: &AnyRobotsInformation<'_, R>,
+    url: &UrlWithDepth,
+    queue: &mut VecDeque<(bool, UrlWithDepth)>,
+) -> Result<(), QueueError>
+where
+    C: SupportsUrlQueue + SupportsDomainHandling + SupportsCrawlEvents,
+    Client: AtraClient,
+    R: RobotsManager,
+{
+    let Some(origin) = url.atra_origin() else {
+        return Ok(());
+    };
+    context
+        .get_domain_manager()
+        .reset_fetches_since_robots_refresh(&origin)
+        .await;
+    let (previous, current) = match robots.refresh(client, url).await {
+        Ok(refreshed) => refreshed,
+        Err(err) => {
+            log::warn!("Failed to refresh the robots.txt of {origin}, keep the old rules: {err}");
+            return Ok(());
+        }
+    };
+
+    let diff = match previous {
+        Some(previous) => {
+            let diff = RobotsDiff::between(&previous, &current);
+            if diff.is_empty() {
+                log::debug!("The robots.txt of {origin} is unchanged.");
+                return Ok(());
+            }
+            log::warn!("The robots.txt of {origin} changed: {diff}");
+            diff
+        }
+        // Nothing to compare with, the queued urls are checked anyway.
+        None => RobotsDiff::default(),
+    };
+
+    let allowed = |target: &UrlWithDepth| {
+        target.atra_origin().as_ref() != Some(&origin) || current.allowed(&target.try_as_str())
+    };
+    let queued = queue.len();
+    queue.retain(|(_, target)| allowed(target));
+    let dropped = queued - queue.len()
+        + context
+            .url_queue()
+            .retain(|element| allowed(&element.target))
+            .await?;
+    if dropped > 0 {
+        log::warn!(
+            "Dropped {dropped} queued urls of {origin}, they are disallowed by the refreshed robots.txt."
+        );
+    }
+    if dropped > 0 || !diff.is_empty() {
+        context.crawl_events().emit(CrawlEvent::RobotsChanged {
+            origin,
+            diff,
+            dropped,
+        });
+    }
+    Ok(())
+}
@@ -12,76 +12,440 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::client::traits::{AtraClient, AtraResponse};
+use crate::client::traits::AtraClient;
+use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
 use crate::crawl::crawler::intervals::InvervalManager;
+use crate::data::{RawData, RawVecData};
+use crate::fetching::FetchedRequestData;
+use crate::io::fs::AtraFS;
+use crate::recrawl_management::{ChangeFrequency, SitemapHint};
 use crate::robots::information::RobotsInformation;
 use crate::toolkit::CaseInsensitiveString;
 use crate::url::UrlWithDepth;
-use sitemap::reader::SiteMapEntity;
-use sitemap::structs::{SiteMapEntry, UrlEntry};
-use std::borrow::Cow;
-use std::collections::HashMap;
-use std::io::Cursor;
+use flate2::read::GzDecoder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sitemap::reader::{SiteMapEntity, SiteMapReader};
+use sitemap::structs::{ChangeFreq, LastMod, Location};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::io;
+use std::io::{BufReader, Read};
+use time::OffsetDateTime;
 
-/// Holds the parsed side maps
+/// The maximum number of sitemaps retrieved for a seed, including the ones listed in an index.
+const MAX_SITEMAPS_PER_SEED: usize = 64;
+
+/// The maximum size of a decompressed sitemap, the sitemap protocol allows 50MiB.
+const MAX_DECOMPRESSED_SIZE: u64 = 50 * 1024 * 1024;
+
+/// The magic bytes of gzip.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// A summary of a sitemap or sitemap index.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct SitemapSummary {
+    /// The number of listed urls.
+    pub url_count: u64,
+    /// The number of listed sitemaps, only found in a sitemap index.
+    pub sitemap_count: u64,
+    /// The number of entries that failed to parse.
+    pub error_count: u64,
+    /// The oldest `lastmod` of the listed entries.
+    #[schemars(with = "Option<String>")]
+    pub lastmod_min: Option<OffsetDateTime>,
+    /// The newest `lastmod` of the listed entries.
+    #[schemars(with = "Option<String>")]
+    pub lastmod_max: Option<OffsetDateTime>,
+    /// The number of urls per `changefreq`.
+    pub changefreq: BTreeMap<String, u64>,
+    /// True iff the sitemap was fetched gzip compressed.
+    pub compressed: bool,
+}
+
+impl SitemapSummary {
+    /// True iff the sitemap is a sitemap index.
+    pub fn is_index(&self) -> bool {
+        self.sitemap_count > 0 && self.url_count == 0
+    }
+
+    fn register_lastmod(&mut self, lastmod: &LastMod) {
+        let Some(value) = lastmod_to_datetime(lastmod) else {
+            return;
+        };
+        if self.lastmod_min.map_or(true, |min| value < min) {
+            self.lastmod_min = Some(value);
+        }
+        if self.lastmod_max.map_or(true, |max| value > max) {
+            self.lastmod_max = Some(value);
+        }
+    }
+
+    fn register_changefreq(&mut self, changefreq: Option<ChangeFrequency>) {
+        if let Some(changefreq) = changefreq {
+            *self.changefreq.entry(changefreq.to_string()).or_default() += 1;
+        }
+    }
+}
+
+/// The point in time of a `lastmod`, None if it is missing or malformed.
+fn lastmod_to_datetime(lastmod: &LastMod) -> Option<OffsetDateTime> {
+    let LastMod::DateTime(value) = lastmod else {
+        return None;
+    };
+    OffsetDateTime::from_unix_timestamp(value.timestamp()).ok()
+}
+
+/// The `changefreq`, None if it is missing or malformed.
+fn changefreq_to_frequency(changefreq: &ChangeFreq) -> Option<ChangeFrequency> {
+    match changefreq {
+        ChangeFreq::None => None,
+        ChangeFreq::Always => Some(ChangeFrequency::Always),
+        ChangeFreq::Hourly => Some(ChangeFrequency::Hourly),
+        ChangeFreq::Daily => Some(ChangeFrequency::Daily),
+        ChangeFreq::Weekly => Some(ChangeFrequency::Weekly),
+        ChangeFreq::Monthly => Some(ChangeFrequency::Monthly),
+        ChangeFreq::Yearly => Some(ChangeFrequency::Yearly),
+        ChangeFreq::Never => Some(ChangeFrequency::Never),
+    }
+}
+
+/// The content of a parsed sitemap.
+#[derive(Debug, Default)]
+pub struct SiteMapContent {
+    pub summary: SitemapSummary,
+    /// The listed urls.
+    pub urls: Vec<String>,
+    /// The recrawl hints of the listed urls, in the same order.
+    pub hints: Vec<SitemapHint>,
+    /// The listed sitemaps.
+    pub sitemaps: Vec<String>,
+}
+
+/// A retrieved and parsed sitemap.
 #[derive(Debug)]
-pub struct ParsedSiteMapEntries {
-    pub urls: Vec<UrlEntry>,
-    pub sitemaps: Vec<SiteMapEntry>,
+pub struct ParsedSiteMap {
+    /// The url of the sitemap.
+    pub url: UrlWithDepth,
+    /// The sitemap as it was fetched, compressed sitemaps stay compressed.
+    pub fetched: FetchedRequestData,
+    pub summary: SitemapSummary,
+    /// The listed urls, relative to the seed.
+    pub urls: Vec<UrlWithDepth>,
+    /// The recrawl hints of the listed urls, in the same order.
+    pub hints: Vec<SitemapHint>,
+    /// The listed sitemaps, relative to the seed.
+    pub sitemaps: Vec<UrlWithDepth>,
+}
+
+/// Returns the url of [location], a broken url is counted as error in [summary].
+fn read_location(location: Location, summary: &mut SitemapSummary) -> Option<String> {
+    match location {
+        Location::None => None,
+        Location::Url(url) => Some(url.to_string()),
+        Location::ParseErr(err) => {
+            log::debug!("Failed to parse url from sitemap: {err}");
+            summary.error_count += 1;
+            None
+        }
+    }
+}
+
+/// Parses the sitemap or sitemap index in [content]. Gzip compressed content is decompressed.
+pub fn parse_sitemap(content: &RawVecData) -> io::Result<SiteMapContent> {
+    let mut result = SiteMapContent::default();
+    let Some(cursor) = content.cursor()? else {
+        return Ok(result);
+    };
+    result.summary.compressed = content
+        .peek(GZIP_MAGIC.len())?
+        .is_some_and(|value| value == GZIP_MAGIC);
+    let reader: Box<dyn Read + '_> = if result.summary.compressed {
+        Box::new(GzDecoder::new(cursor).take(MAX_DECOMPRESSED_SIZE))
+    } else {
+        Box::new(cursor)
+    };
+
+    for entity in SiteMapReader::new(BufReader::new(reader)) {
+        match entity {
+            SiteMapEntity::Url(entry) => {
+                let changefreq = changefreq_to_frequency(&entry.changefreq);
+                result.summary.url_count += 1;
+                result.summary.register_lastmod(&entry.lastmod);
+                result.summary.register_changefreq(changefreq);
+                if let Some(url) = read_location(entry.loc, &mut result.summary) {
+                    result.urls.push(url);
+                    result.hints.push(SitemapHint {
+                        lastmod: lastmod_to_datetime(&entry.lastmod),
+                        changefreq,
+                    });
+                }
+            }
+            SiteMapEntity::SiteMap(entry) => {
+                result.summary.sitemap_count += 1;
+                result.summary.register_lastmod(&entry.lastmod);
+                if let Some(url) = read_location(entry.loc, &mut result.summary) {
+                    result.sitemaps.push(url);
+                }
+            }
+            SiteMapEntity::Err(err) => {
+                log::info!("Was not able to process sitemap entry {err}");
+                result.summary.error_count += 1;
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Resolves the [value] relative to [base], None if it can not be parsed.
+fn resolve(base: &UrlWithDepth, value: String) -> Option<UrlWithDepth> {
+    match UrlWithDepth::with_base(base, value) {
+        Ok(url) => Some(url),
+        Err(err) => {
+            log::debug!("Failed to parse url from sitemap: {err}");
+            None
+        }
+    }
 }
 
-/// Retrieves and parses sitemaps form [url]
-/// todo: use
-pub async fn retrieve_and_parse<'a, Client: AtraClient, R: RobotsInformation>(
+/// Resolves the [values] relative to [base], unparseable urls are dropped.
+fn resolve_all(base: &UrlWithDepth, values: Vec<String>) -> Vec<UrlWithDepth> {
+    values
+        .into_iter()
+        .filter_map(|value| resolve(base, value))
+        .collect()
+}
+
+/// Retrieves and parses the sitemaps of [url] announced in the robots.txt or configured
+/// in [external_sitemaps]. The sitemaps listed in a sitemap index are retrieved as well.
+pub async fn retrieve_and_parse<'a, C, Client, R>(
+    context: &C,
     client: &Client,
     url: &UrlWithDepth,
     configured_robots: &R,
     interval: &mut InvervalManager<'a, impl AtraClient, impl RobotsInformation>,
     external_sitemaps: Option<&HashMap<CaseInsensitiveString, Vec<String>>>,
-) -> ParsedSiteMapEntries {
-    let mut sitemap_urls: Vec<Cow<str>> = Vec::new();
+) -> Vec<ParsedSiteMap>
+where
+    C: SupportsConfigs + SupportsFileSystemAccess,
+    Client: AtraClient,
+    R: RobotsInformation,
+{
+    let mut pending: VecDeque<UrlWithDepth> = VecDeque::new();
     if let Ok(robot) = configured_robots.get_or_retrieve(client, url).await {
         if let Some(sitemaps) = robot.sitemaps() {
-            for value in sitemaps.iter() {
-                sitemap_urls.push(Cow::Owned(value.to_string()))
-            }
+            pending.extend(resolve_all(
+                url,
+                sitemaps.iter().map(|value| value.to_string()).collect(),
+            ));
         }
     }
 
     if let Some(external_sitemap_urls) = external_sitemaps {
         if let Some(ref domain) = url.domain() {
             if let Some(sitemaps) = external_sitemap_urls.get(domain) {
-                for sitemap_url in sitemaps {
-                    sitemap_urls.push(Cow::Borrowed(sitemap_url.as_str()))
-                }
+                pending.extend(resolve_all(url, sitemaps.clone()));
             }
         }
     }
 
-    let mut urls: Vec<UrlEntry> = Vec::new();
-    let mut sitemaps: Vec<SiteMapEntry> = Vec::new();
-
-    for sitemap_url in sitemap_urls {
+    let mut visited = HashSet::new();
+    let mut result = Vec::new();
+    while let Some(sitemap_url) = pending.pop_front() {
+        if !visited.insert(sitemap_url.url.clone()) {
+            continue;
+        }
+        if visited.len() > MAX_SITEMAPS_PER_SEED {
+            log::info!(
+                "Reached the limit of {MAX_SITEMAPS_PER_SEED} sitemaps for {url}, ignore the remaining {}.",
+                pending.len() + 1
+            );
+            break;
+        }
         interval.wait(url).await;
-        if let Ok(result) = client.get(sitemap_url.as_ref()).await {
-            if let Ok(text) = result.text().await {
-                let parser = sitemap::reader::SiteMapReader::new(Cursor::new(text));
-                for entity in parser {
-                    match entity {
-                        SiteMapEntity::Url(url_entry) => {
-                            urls.push(url_entry);
-                        }
-                        SiteMapEntity::SiteMap(sitemap_entry) => {
-                            sitemaps.push(sitemap_entry);
-                        }
-                        SiteMapEntity::Err(error) => {
-                            log::info!("Was not able to process sitemap entry {}", error)
-                        }
-                    }
+        let url_str = sitemap_url.try_as_str().into_owned();
+        let fetched = match client.retrieve(context, &url_str, None).await {
+            Ok(fetched) => fetched,
+            Err(err) => {
+                log::info!("Failed to retrieve the sitemap {url_str}: {err}");
+                continue;
+            }
+        };
+        if !fetched.status_code.is_success() {
+            log::debug!("The sitemap {url_str} returned {}.", fetched.status_code);
+            if let RawData::ExternalFile { ref path } = fetched.content {
+                if let Err(err) = context.fs().cleanup_data_file(path) {
+                    log::warn!("Failed to remove {path} of the sitemap {url_str}: {err}");
                 }
             }
+            continue;
         }
+        let content = parse_sitemap(&fetched.content).unwrap_or_else(|err| {
+            log::info!("Failed to read the sitemap {url_str}: {err}");
+            SiteMapContent::default()
+        });
+        let sitemaps = resolve_all(url, content.sitemaps);
+        pending.extend(sitemaps.iter().cloned());
+        let (urls, hints) = content
+            .urls
+            .into_iter()
+            .zip(content.hints)
+            .filter_map(|(value, hint)| Some((resolve(url, value)?, hint)))
+            .unzip();
+        result.push(ParsedSiteMap {
+            url: sitemap_url,
+            fetched,
+            summary: content.summary,
+            urls,
+            hints,
+            sitemaps,
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+pub mod test {
+    use crate::crawl::crawler::sitemaps::parse_sitemap;
+    use crate::data::RawData;
+    use crate::recrawl_management::{next_crawl_at, ChangeFrequency, SitemapHint};
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use time::format_description::well_known::Rfc3339;
+    use time::{Duration, OffsetDateTime};
+
+    pub const SITEMAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+    <url>
+        <loc>https://www.example.com/</loc>
+        <lastmod>2024-01-02T10:00:00+00:00</lastmod>
+        <changefreq>daily</changefreq>
+    </url>
+    <url>
+        <loc>https://www.example.com/about.html</loc>
+        <lastmod>2023-05-01T00:00:00+00:00</lastmod>
+        <changefreq>monthly</changefreq>
+    </url>
+    <url>
+        <loc>https://www.example.com/news.html</loc>
+        <changefreq>daily</changefreq>
+    </url>
+</urlset>"#;
+
+    pub const SITEMAP_INDEX: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+    <sitemap>
+        <loc>https://www.example.com/sitemap_pages.xml.gz</loc>
+        <lastmod>2024-02-01T00:00:00+00:00</lastmod>
+    </sitemap>
+    <sitemap>
+        <loc>https://www.example.com/sitemap_news.xml</loc>
+    </sitemap>
+</sitemapindex>"#;
+
+    pub fn gzip(value: &str) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(value.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn date(value: &str) -> Option<OffsetDateTime> {
+        Some(OffsetDateTime::parse(value, &Rfc3339).unwrap())
     }
 
-    return ParsedSiteMapEntries { urls, sitemaps };
+    #[test]
+    fn summarizes_a_sitemap() {
+        let parsed = parse_sitemap(&RawData::from_vec(SITEMAP.as_bytes().to_vec())).unwrap();
+        assert_eq!(3, parsed.urls.len());
+        assert!(parsed.sitemaps.is_empty());
+        let summary = parsed.summary;
+        assert_eq!(3, summary.url_count);
+        assert_eq!(0, summary.sitemap_count);
+        assert_eq!(0, summary.error_count);
+        assert!(!summary.is_index());
+        assert!(!summary.compressed);
+        assert_eq!(date("2023-05-01T00:00:00Z"), summary.lastmod_min);
+        assert_eq!(date("2024-01-02T10:00:00Z"), summary.lastmod_max);
+        assert_eq!(Some(&2), summary.changefreq.get("daily"));
+        assert_eq!(Some(&1), summary.changefreq.get("monthly"));
+        assert_eq!(2, summary.changefreq.len());
+    }
+
+    #[test]
+    fn summarizes_a_sitemap_index() {
+        let parsed = parse_sitemap(&RawData::from_vec(SITEMAP_INDEX.as_bytes().to_vec())).unwrap();
+        assert!(parsed.urls.is_empty());
+        assert_eq!(
+            vec![
+                "https://www.example.com/sitemap_pages.xml.gz".to_string(),
+                "https://www.example.com/sitemap_news.xml".to_string()
+            ],
+            parsed.sitemaps
+        );
+        assert_eq!(2, parsed.summary.sitemap_count);
+        assert!(parsed.summary.is_index());
+        assert_eq!(parsed.summary.lastmod_min, parsed.summary.lastmod_max);
+        assert!(parsed.summary.changefreq.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_recrawl_hints_of_the_urls() {
+        let sitemap = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+    <url>
+        <loc>https://www.example.com/changed.html</loc>
+        <lastmod>2024-03-01T00:00:00+00:00</lastmod>
+        <changefreq>yearly</changefreq>
+    </url>
+    <url>
+        <loc>https://www.example.com/unchanged.html</loc>
+        <lastmod>2024-01-01T00:00:00+00:00</lastmod>
+    </url>
+    <url>
+        <loc>https://www.example.com/daily.html</loc>
+        <lastmod>yesterday</lastmod>
+        <changefreq>daily</changefreq>
+    </url>
+    <url>
+        <loc>https://www.example.com/unknown.html</loc>
+        <changefreq>sometimes</changefreq>
+    </url>
+</urlset>"#;
+        let parsed = parse_sitemap(&RawData::from_vec(sitemap.as_bytes().to_vec())).unwrap();
+        assert_eq!(parsed.urls.len(), parsed.hints.len());
+        assert_eq!(
+            SitemapHint {
+                lastmod: date("2024-03-01T00:00:00Z"),
+                changefreq: Some(ChangeFrequency::Yearly),
+            },
+            parsed.hints[0]
+        );
+        assert!(parsed.hints[3].is_empty());
+
+        let crawled_at = date("2024-02-01T00:00:00Z").unwrap();
+        let now = date("2024-04-01T00:00:00Z").unwrap();
+        let interval = Duration::days(7);
+        let next: Vec<_> = parsed
+            .hints
+            .iter()
+            .map(|hint| next_crawl_at(Some(hint), crawled_at, interval, now))
+            .collect();
+        assert_eq!(
+            vec![
+                date("2024-03-01T00:00:00Z"),
+                None,
+                Some(crawled_at + Duration::DAY),
+                Some(crawled_at + interval),
+            ],
+            next
+        );
+    }
+
+    #[test]
+    fn decompresses_gzip_sitemaps() {
+        let parsed = parse_sitemap(&RawData::from_vec(gzip(SITEMAP))).unwrap();
+        assert!(parsed.summary.compressed);
+        assert_eq!(3, parsed.summary.url_count);
+        assert_eq!("https://www.example.com/about.html", parsed.urls[1]);
+    }
 }
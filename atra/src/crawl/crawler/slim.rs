@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::crawl::crawler::legacy::LegacySlimCrawlResult;
 use crate::crawl::crawler::result::{CrawlResult, CrawlResultMeta};
 use crate::data::{RawData, RawVecData};
 use crate::warc_ext::{ReaderError, WarcSkipInstruction};
@@ -20,6 +21,15 @@ use itertools::Either;
 use serde::{Deserialize, Serialize};
 use crate::io::file_owner::FileOwner;
 
+/// The first bytes of an encoded [SlimCrawlResult], followed by the version of the layout.
+/// An entry without them was written before the layout was versioned. Such an entry starts
+/// with the year it was created in, which is never as large as the magic.
+const ENCODED_MAGIC: [u8; 4] = *b"ATRS";
+
+/// The version of the layout of an encoded [SlimCrawlResult]. Increase it when a field is
+/// added and keep decoding the previous versions in [SlimCrawlResult::decode].
+const ENCODED_VERSION: u8 = 1;
+
 /// The header information of a [CrawlResult]
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq)]
 pub struct SlimCrawlResult {
@@ -50,6 +60,32 @@ impl SlimCrawlResult {
         }
     }
 
+    /// Encodes this with the version of its layout, as stored in the crawl db.
+    pub fn encode(&self) -> bincode::Result<Vec<u8>> {
+        let mut encoded = Vec::from(ENCODED_MAGIC);
+        encoded.push(ENCODED_VERSION);
+        bincode::serialize_into(&mut encoded, self)?;
+        Ok(encoded)
+    }
+
+    /// Returns true iff [encoded] was written before the layout was versioned.
+    pub fn is_legacy(encoded: &[u8]) -> bool {
+        !encoded.starts_with(&ENCODED_MAGIC)
+    }
+
+    /// Decodes an entry of the crawl db, an entry written before the layout was versioned
+    /// is migrated to the current layout.
+    pub fn decode(encoded: &[u8]) -> bincode::Result<Self> {
+        match encoded.strip_prefix(ENCODED_MAGIC.as_slice()) {
+            Some([ENCODED_VERSION, rest @ ..]) => bincode::deserialize(rest),
+            Some(rest) => Err(Box::new(bincode::ErrorKind::Custom(format!(
+                "The crawl result has the unsupported version {:?}.",
+                rest.first()
+            )))),
+            None => bincode::deserialize::<LegacySlimCrawlResult>(encoded).map(Self::from),
+        }
+    }
+
     /// Inflates the [SlimCrawlResult] to a normal [CrawlResult].
     /// You may provide an associated [body] if necessary
     pub unsafe fn inflate_with(self, body: Option<Vec<u8>>) -> CrawlResult {
@@ -72,6 +108,8 @@ impl SlimCrawlResult {
         CrawlResult {
             meta: self.meta,
             content,
+            text: None,
+            origin_signals: None,
         }
     }
 
@@ -106,12 +144,19 @@ impl SlimCrawlResult {
         Ok(CrawlResult {
             meta: self.meta,
             content,
+            text: None,
+            origin_signals: None,
         })
     }
 
     /// Inflates the [SlimCrawlResult] to a normal [CrawlResult].
-    /// You may provide an associated [file_owner] if necessary
-    pub async fn inflate(self, file_owner: Option<&impl FileOwner>) -> Result<CrawlResult, ReaderError> {
+    /// You may provide an associated [file_owner] if necessary.
+    /// If [verify_digests] is set, a body read from a warc file is checked against its digest.
+    pub async fn inflate(
+        self,
+        file_owner: Option<&impl FileOwner>,
+        verify_digests: bool,
+    ) -> Result<CrawlResult, ReaderError> {
         let content = match self.stored_data_hint {
             StoredDataHint::External(value) => RawData::from_external(value),
             StoredDataHint::InMemory(value) => RawData::from_vec(value),
@@ -120,21 +165,28 @@ impl SlimCrawlResult {
             }
             StoredDataHint::Warc(instruction) => {
                 instruction
-                    .read_in_context(file_owner)
+                    .read_in_context(file_owner, verify_digests)
                     .await?
             }
         };
         Ok(CrawlResult {
             meta: self.meta,
             content,
+            text: None,
+            origin_signals: None,
         })
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::crawl::crawler::result::test::create_test_data;
+    use crate::crawl::crawler::result::test::{create_test_data, encode_legacy};
     use crate::crawl::crawler::slim::{SlimCrawlResult, StoredDataHint};
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::extraction::html::LinkOrigin;
+    use crate::extraction::marker::{ExtractorMethodHint, ExtractorMethodMeta};
+    use crate::extraction::ExtractedLink;
+    use crate::toolkit::LanguageInformation;
     use crate::url::UrlWithDepth;
     use crate::warc_ext::{WarcSkipInstruction, WarcSkipInstructionKind, WarcSkipPointer, WarcSkipPointerWithPath};
     use camino::Utf8PathBuf;
@@ -164,4 +216,60 @@ mod test {
         let slim2 = bincode::deserialize::<SlimCrawlResult>(&data).unwrap();
         assert_eq!(slim2, slim)
     }
+
+    #[test]
+    fn the_certainty_of_the_language_is_stored() {
+        let mut x = create_test_data(
+            UrlWithDepth::from_url("https://www.google.de").unwrap(),
+            None,
+        );
+        x.meta.set_language(Some(LanguageInformation::DEU.with_uncertain(true)));
+        let slim = SlimCrawlResult::new(&x, StoredDataHint::None);
+        let data = bincode::serialize(&slim).unwrap();
+        let slim2 = bincode::deserialize::<SlimCrawlResult>(&data).unwrap();
+        assert!(slim2.meta.language_uncertain);
+        assert!(slim2.meta.language_information().unwrap().is_uncertain());
+    }
+
+    #[test]
+    fn an_entry_of_an_older_version_is_decoded() {
+        let mut x = create_test_data(
+            UrlWithDepth::from_url("https://www.google.de").unwrap(),
+            None,
+        );
+        x.meta.links.as_mut().unwrap().push(ExtractedLink::Outgoing {
+            url: UrlWithDepth::from_url("https://www.example.com/in/archive").unwrap(),
+            extraction_method: ExtractorMethodHint::new_with_meta(
+                ExtractorMethod::Zip,
+                ExtractorMethodMeta::Zip {
+                    path: "archive/index.html".to_string(),
+                    underlying: Box::new(ExtractorMethodHint::new_with_meta(
+                        ExtractorMethod::HtmlV1,
+                        ExtractorMethodMeta::Html(LinkOrigin::Href),
+                    )),
+                },
+            ),
+        });
+        let slim = SlimCrawlResult::new(&x, StoredDataHint::InMemory(b"hello".to_vec()));
+
+        let decoded = SlimCrawlResult::decode(&encode_legacy(&slim)).unwrap();
+        assert_eq!(slim, decoded);
+        assert_eq!(
+            slim,
+            SlimCrawlResult::decode(&decoded.encode().unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn an_entry_of_an_unknown_version_is_rejected() {
+        let x = create_test_data(
+            UrlWithDepth::from_url("https://www.google.de").unwrap(),
+            None,
+        );
+        let mut encoded = SlimCrawlResult::new(&x, StoredDataHint::None)
+            .encode()
+            .unwrap();
+        encoded[4] += 1;
+        assert!(SlimCrawlResult::decode(&encoded).is_err());
+    }
 }
@@ -14,11 +14,12 @@
 
 use crate::config::Config;
 use crate::crawl::SlimCrawlResult;
-use crate::database::DBActionType::{Read, Write};
+use crate::database::DBActionType::{Delete, Read, Write};
 use crate::database::{execute_iter, get_len, DatabaseError, RawDatabaseError, RawIOError};
 use crate::db_health_check;
 use crate::declare_column_families;
 use crate::url::UrlWithDepth;
+use crate::warc_ext::WarcSkipInstruction;
 use rocksdb::{DBIteratorWithThreadMode, DBWithThreadMode, IteratorMode, MultiThreaded, DB};
 use std::sync::Arc;
 
@@ -45,20 +46,31 @@ impl CrawlDB {
         Ok(Self { db })
     }
 
-    /// Adds a single [value]
+    /// Adds a single [value], a language variant does not replace the page.
     pub fn add(&self, value: &SlimCrawlResult) -> Result<(), DatabaseError> {
-        let key = &value.meta.url.url;
-        let serialized = match bincode::serialize(&value) {
+        let key = Self::key_of(value);
+        let serialized = match value.encode() {
             Ok(value) => value,
-            Err(err) => return Err(err.enrich_ser(Self::CRAWL_DB_CF, key, value.clone())),
+            Err(err) => return Err(err.enrich_ser(Self::CRAWL_DB_CF, &key, value.clone())),
         };
         self.db
-            .put_cf(&self.cf_handle(), key, &serialized)
-            .enrich_with_entry(Self::CRAWL_DB_CF, Write, key, &serialized)?;
+            .put_cf(&self.cf_handle(), &key, &serialized)
+            .enrich_with_entry(Self::CRAWL_DB_CF, Write, &key, &serialized)?;
 
         Ok(())
     }
 
+    /// The key of [value] is its url, the language variants of a page are keyed by the url
+    /// and the language separated by a space. An url never contains a space.
+    fn key_of(value: &SlimCrawlResult) -> Vec<u8> {
+        let mut key = value.meta.url.url.as_bytes().to_vec();
+        if let Some(language) = &value.meta.language_variant {
+            key.push(b' ');
+            key.extend_from_slice(language.as_bytes());
+        }
+        key
+    }
+
     /// Gets the complete entry for the [url]
     pub fn get(&self, url: &UrlWithDepth) -> Result<Option<SlimCrawlResult>, DatabaseError> {
         let handle = self.cf_handle();
@@ -69,7 +81,7 @@ impl CrawlDB {
                 Read,
                 url,
             )? {
-                Ok(Some(match bincode::deserialize(pinned.as_ref()) {
+                Ok(Some(match SlimCrawlResult::decode(pinned.as_ref()) {
                     Ok(value) => value,
                     Err(err) => return Err(err.enrich_de(Self::CRAWL_DB_CF, key, pinned.to_vec())),
                 }))
@@ -81,6 +93,14 @@ impl CrawlDB {
         }
     }
 
+    /// Removes the entry for the [url], the archived data itself is not touched.
+    pub fn remove(&self, url: &UrlWithDepth) -> Result<(), DatabaseError> {
+        self.db
+            .delete_cf(&self.cf_handle(), url.url.as_bytes())
+            .enrich_without_entry(Self::CRAWL_DB_CF, Delete, url)?;
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         get_len(&self.db, self.cf_handle())
     }
@@ -96,3 +116,63 @@ impl CrawlDB {
         &self.db
     }
 }
+
+/// Maps the digest of a body to the warc records it is stored in.
+#[derive(Debug, Clone)]
+pub struct PayloadDigestDB {
+    db: Arc<DB>,
+}
+
+impl PayloadDigestDB {
+    declare_column_families! {
+        self.db => cf_handle(PAYLOAD_DIGEST_DB_CF)
+    }
+
+    /// Panics if the needed CFs are not configured.
+    pub fn new(db: Arc<DB>) -> Self {
+        db_health_check!(db: [
+            Self::PAYLOAD_DIGEST_DB_CF => (
+                if test payload_digest_cf_options
+                else "The cf for the payload digests is missing!"
+            )
+        ]);
+        Self { db }
+    }
+
+    /// Remembers that the body with the [digest] is stored at [instruction].
+    pub fn add(
+        &self,
+        digest: &[u8],
+        instruction: &WarcSkipInstruction,
+    ) -> Result<(), DatabaseError> {
+        let serialized = match bincode::serialize(instruction) {
+            Ok(value) => value,
+            Err(err) => {
+                return Err(err.enrich_ser(Self::PAYLOAD_DIGEST_DB_CF, digest, instruction.clone()))
+            }
+        };
+        self.db
+            .put_cf(&self.cf_handle(), digest, &serialized)
+            .enrich_with_entry(Self::PAYLOAD_DIGEST_DB_CF, Write, digest, &serialized)?;
+        Ok(())
+    }
+
+    /// Gets the warc records of the body with the [digest].
+    pub fn get(&self, digest: &[u8]) -> Result<Option<WarcSkipInstruction>, DatabaseError> {
+        let handle = self.cf_handle();
+        if !self.db.key_may_exist_cf(&handle, digest) {
+            return Ok(None);
+        }
+        match self
+            .db
+            .get_pinned_cf(&handle, digest)
+            .enrich_without_entry(Self::PAYLOAD_DIGEST_DB_CF, Read, digest)?
+        {
+            Some(pinned) => match bincode::deserialize(pinned.as_ref()) {
+                Ok(value) => Ok(Some(value)),
+                Err(err) => Err(err.enrich_de(Self::PAYLOAD_DIGEST_DB_CF, digest, pinned.to_vec())),
+            },
+            None => Ok(None),
+        }
+    }
+}
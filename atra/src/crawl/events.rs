@@ -0,0 +1,211 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::traits::FetchFailureKind;
+use crate::crawl::metrics::CrawlMetrics;
+use crate::crawl::pause::CrawlPause;
+use crate::crawl::statistics::CrawlStatistics;
+use crate::crawl::CrawlResult;
+use crate::extraction::extractor::ExtractorFailure;
+use crate::extraction::extractor_method::ExtractorMethod;
+use crate::fetching::DownloadRejection;
+use crate::robots::RobotsDiff;
+use crate::url::{AtraUrlOrigin, UrlWithDepth};
+use reqwest::StatusCode;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// The number of events a subscriber can lag behind before it misses some.
+const EVENT_CAPACITY: usize = 1024;
+
+/// An event emitted while crawling.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CrawlEvent {
+    /// The worker started to fetch the url.
+    Fetching { worker_id: usize, url: UrlWithDepth },
+    /// The url was put into the queue.
+    Queued { url: UrlWithDepth },
+    /// The url was fetched and stored. A revalidated url has [StatusCode::NOT_MODIFIED].
+    /// The [bytes] of the body as stored and the [duration] of the fetch, if known.
+    Crawled {
+        url: UrlWithDepth,
+        status_code: StatusCode,
+        bytes: Option<u64>,
+        duration: Option<Duration>,
+    },
+    /// The url was stored without its body, the body was rejected before or while downloading.
+    Skipped {
+        url: UrlWithDepth,
+        rejection: DownloadRejection,
+    },
+    /// Fetching the url failed.
+    Failed {
+        url: UrlWithDepth,
+        kind: FetchFailureKind,
+        reason: String,
+    },
+    /// An extractor method failed on the url, the other methods still ran.
+    ExtractorFailed {
+        url: UrlWithDepth,
+        method: ExtractorMethod,
+        failure: ExtractorFailure,
+    },
+    /// The worker found no more urls in the queue and stopped.
+    QueueEmpty { worker_id: usize },
+    /// The refreshed robots.txt of the origin changed the rules for our agent, [dropped]
+    /// queued urls of the origin are no longer allowed and were removed.
+    RobotsChanged {
+        origin: AtraUrlOrigin,
+        diff: RobotsDiff,
+        dropped: usize,
+    },
+    /// Every request to the origin of the [seed] failed to connect, also after the [attempts]
+    /// to discover it again. The origin is given up.
+    OriginUnreachable {
+        origin: AtraUrlOrigin,
+        seed: UrlWithDepth,
+        attempts: u8,
+    },
+    /// All workers stopped, the crawl is over.
+    Finished,
+}
+
+impl CrawlEvent {
+    /// The event for the stored page [url] with the [result]. Without the [fetch_duration]
+    /// the duration of the fetch is taken from the fetch timings of the result.
+    pub fn stored(
+        url: UrlWithDepth,
+        result: &CrawlResult,
+        fetch_duration: Option<Duration>,
+    ) -> Self {
+        let meta = &result.meta;
+        match &meta.download_rejection {
+            Some(rejection) => CrawlEvent::Skipped {
+                url,
+                rejection: rejection.clone(),
+            },
+            None => CrawlEvent::Crawled {
+                url,
+                status_code: meta.status_code,
+                bytes: result.content.stored_size().ok(),
+                duration: fetch_duration.or_else(|| {
+                    let timings = meta.fetch_timings.as_ref()?;
+                    timings.total.try_into().ok()
+                }),
+            },
+        }
+    }
+}
+
+/// Distributes [CrawlEvent]s to all subscribers and counts them in the [CrawlMetrics]
+/// and the [CrawlStatistics]. Emitting without any subscriber only updates the counters.
+/// The [CrawlPause] of the workers is shared the same way.
+#[derive(Debug, Clone)]
+pub struct CrawlEvents {
+    sender: broadcast::Sender<CrawlEvent>,
+    metrics: Arc<CrawlMetrics>,
+    statistics: Arc<CrawlStatistics>,
+    pause: CrawlPause,
+}
+
+impl CrawlEvents {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self {
+            sender,
+            metrics: Arc::new(CrawlMetrics::default()),
+            statistics: Arc::new(CrawlStatistics::new()),
+            pause: CrawlPause::new(),
+        }
+    }
+
+    /// The metrics of all events emitted so far.
+    pub fn metrics(&self) -> &CrawlMetrics {
+        &self.metrics
+    }
+
+    /// The statistics by origin and by worker gathered so far.
+    pub fn statistics(&self) -> &CrawlStatistics {
+        &self.statistics
+    }
+
+    /// Pauses and resumes the workers.
+    pub fn pause(&self) -> &CrawlPause {
+        &self.pause
+    }
+
+    /// Subscribe to all events emitted after this call.
+    pub fn subscribe(&self) -> broadcast::Receiver<CrawlEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Emits the event to all current subscribers.
+    pub fn emit(&self, event: CrawlEvent) {
+        match &event {
+            CrawlEvent::Crawled {
+                url, status_code, ..
+            } => {
+                self.metrics.record_crawled(*status_code);
+                self.statistics.record_page(url, Some(*status_code));
+            }
+            CrawlEvent::Skipped { url, .. } => {
+                self.metrics.record_skipped();
+                self.statistics.record_page(url, None);
+            }
+            CrawlEvent::Failed { url, kind, .. } => {
+                self.metrics.record_failed();
+                self.statistics.record_failure(url, *kind);
+            }
+            CrawlEvent::ExtractorFailed { failure, .. } => {
+                self.metrics.record_extractor_failure(failure)
+            }
+            CrawlEvent::OriginUnreachable { origin, .. } => {
+                self.metrics.record_unreachable(origin.clone())
+            }
+            CrawlEvent::Queued { .. }
+            | CrawlEvent::Fetching { .. }
+            | CrawlEvent::QueueEmpty { .. }
+            | CrawlEvent::RobotsChanged { .. }
+            | CrawlEvent::Finished => {}
+        }
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(event);
+        }
+    }
+}
+
+impl Default for CrawlEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CrawlEvent, CrawlEvents};
+
+    #[test]
+    fn emitting_without_subscribers_is_fine() {
+        let events = CrawlEvents::new();
+        events.emit(CrawlEvent::QueueEmpty { worker_id: 0 });
+        let mut receiver = events.subscribe();
+        events.emit(CrawlEvent::QueueEmpty { worker_id: 1 });
+        assert_eq!(
+            CrawlEvent::QueueEmpty { worker_id: 1 },
+            receiver.try_recv().unwrap()
+        );
+        assert!(receiver.try_recv().is_err());
+    }
+}
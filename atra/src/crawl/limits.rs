@@ -0,0 +1,142 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::CrawlConfig;
+use crate::runtime::{ShutdownChild, ShutdownSender};
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use time::Duration;
+use ubyte::ByteUnit;
+
+/// The global limit that stopped the crawl.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StopReason {
+    MaxPages(u64),
+    MaxBytes(ByteUnit),
+    MaxDuration(Duration),
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::MaxPages(limit) => write!(f, "Reached the limit of {limit} stored pages."),
+            StopReason::MaxBytes(limit) => write!(f, "Reached the limit of {limit} stored bytes."),
+            StopReason::MaxDuration(limit) => write!(f, "Reached the maximal duration of {limit}."),
+        }
+    }
+}
+
+/// The limits of the whole crawl over all workers and origins.
+///
+/// The stored pages and bytes are atomic counters. The first limit reached shuts the crawl
+/// down like CTRL-C: the workers finish their current page and the session can be recovered.
+#[derive(Debug)]
+pub struct CrawlLimits {
+    max_pages: Option<u64>,
+    max_bytes: Option<ByteUnit>,
+    max_duration: Option<Duration>,
+    pages: AtomicU64,
+    bytes: AtomicU64,
+    reason: OnceLock<StopReason>,
+    shutdown: Option<ShutdownChild>,
+}
+
+impl CrawlLimits {
+    /// The limits of the [config], reaching one of them shuts down the [shutdown].
+    pub fn new(config: &CrawlConfig, shutdown: Option<ShutdownChild>) -> Self {
+        Self {
+            max_pages: config.max_pages_total,
+            max_bytes: config.max_bytes_total,
+            max_duration: config.max_duration,
+            pages: AtomicU64::new(0),
+            bytes: AtomicU64::new(0),
+            reason: OnceLock::new(),
+            shutdown,
+        }
+    }
+
+    /// The maximal duration of the crawl, watched by whoever runs the crawl.
+    pub fn max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+
+    /// Counts a stored page with a body of [bytes] and stops the crawl if a limit is reached.
+    pub fn record_stored(&self, bytes: u64) {
+        let pages = self.pages.fetch_add(1, Ordering::Relaxed) + 1;
+        let bytes = self.bytes.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if let Some(limit) = self.max_pages.filter(|limit| pages >= *limit) {
+            self.stop(StopReason::MaxPages(limit));
+        } else if let Some(limit) = self.max_bytes.filter(|limit| bytes >= limit.as_u64()) {
+            self.stop(StopReason::MaxBytes(limit));
+        }
+    }
+
+    /// Stops the crawl because of [reason], only the first reason is kept.
+    pub fn stop(&self, reason: StopReason) {
+        if self.reason.set(reason).is_ok() {
+            log::info!("Stop the crawl: {reason}");
+        }
+        if let Some(shutdown) = &self.shutdown {
+            shutdown.shutdown();
+        }
+    }
+
+    /// The limit that stopped the crawl, None if no limit was reached.
+    pub fn stop_reason(&self) -> Option<StopReason> {
+        self.reason.get().copied()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CrawlLimits, StopReason};
+    use crate::config::CrawlConfig;
+    use crate::runtime::GracefulShutdown;
+    use ubyte::ToByteUnit;
+
+    #[test]
+    fn the_first_limit_reached_stops_the_crawl() {
+        let shutdown = GracefulShutdown::new();
+        let mut config = CrawlConfig::default();
+        config.max_pages_total = Some(3);
+        config.max_bytes_total = Some(1.kilobytes());
+        let limits = CrawlLimits::new(&config, Some(shutdown.child().clone()));
+
+        limits.record_stored(600);
+        assert_eq!(None, limits.stop_reason());
+        assert!(!shutdown.is_shutdown());
+        limits.record_stored(600);
+        assert_eq!(
+            Some(StopReason::MaxBytes(1.kilobytes())),
+            limits.stop_reason()
+        );
+        assert!(shutdown.is_shutdown());
+        // The workers finishing their page keep the first reason.
+        limits.record_stored(0);
+        assert_eq!(
+            Some(StopReason::MaxBytes(1.kilobytes())),
+            limits.stop_reason()
+        );
+    }
+
+    #[test]
+    fn no_limit_never_stops() {
+        let limits = CrawlLimits::new(&CrawlConfig::default(), None);
+        for _ in 0..100 {
+            limits.record_stored(u32::MAX as u64);
+        }
+        assert_eq!(None, limits.stop_reason());
+    }
+}
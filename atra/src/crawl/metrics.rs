@@ -0,0 +1,187 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::extraction::extractor::ExtractorFailure;
+use crate::fetching::HttpVersion;
+use crate::sync::WorkerBarrier;
+use crate::url::AtraUrlOrigin;
+use reqwest::StatusCode;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// The labels of the status code classes, the last one collects invalid codes.
+pub const STATUS_CODE_CLASSES: [&str; 6] = ["1xx", "2xx", "3xx", "4xx", "5xx", "other"];
+
+/// The labels of the kinds of an [ExtractorFailure].
+pub const EXTRACTOR_FAILURE_KINDS: [&str; 3] = ["error", "panic", "timeout"];
+
+/// The labels of the sizes of the external data files.
+pub const DAT_FILE_SIZE_KINDS: [&str; 2] = ["stored", "uncompressed"];
+
+/// Counters of the crawl. The workers only update atomics, nothing on the hot path is locked.
+#[derive(Debug, Default)]
+pub struct CrawlMetrics {
+    crawled_by_status_class: [AtomicU64; 6],
+    failed_fetches: AtomicU64,
+    skipped_downloads: AtomicU64,
+    skipped_data_uris: AtomicU64,
+    extractor_failures: [AtomicU64; 3],
+    warc_bytes: AtomicU64,
+    dat_file_bytes: [AtomicU64; 2],
+    responses_by_http_version: [AtomicU64; 5],
+    unreachable_origins: RwLock<Vec<AtraUrlOrigin>>,
+    barrier: RwLock<Option<Arc<WorkerBarrier>>>,
+}
+
+impl CrawlMetrics {
+    /// Counts a crawled page with the [status_code].
+    pub fn record_crawled(&self, status_code: StatusCode) {
+        let class = match status_code.as_u16() / 100 {
+            value @ 1..=5 => value as usize - 1,
+            _ => STATUS_CODE_CLASSES.len() - 1,
+        };
+        self.crawled_by_status_class[class].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a failed fetch.
+    pub fn record_failed(&self) {
+        self.failed_fetches.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a page stored without its rejected body.
+    pub fn record_skipped(&self) {
+        self.skipped_downloads.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a data uri that was not stored because it is too large.
+    pub fn record_skipped_data_uri(&self) {
+        self.skipped_data_uris.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts a failed extractor method by the kind of the [failure].
+    pub fn record_extractor_failure(&self, failure: &ExtractorFailure) {
+        let kind = match failure {
+            ExtractorFailure::Error(_) => 0,
+            ExtractorFailure::Panicked(_) => 1,
+            ExtractorFailure::TimedOut => 2,
+        };
+        self.extractor_failures[kind].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Adds the [bytes] written to a warc file.
+    pub fn add_warc_bytes(&self, bytes: u64) {
+        self.warc_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Adds the [stored] bytes of an external data file and the bytes of its [uncompressed] content.
+    pub fn add_dat_file_bytes(&self, stored: u64, uncompressed: u64) {
+        self.dat_file_bytes[0].fetch_add(stored, Ordering::Relaxed);
+        self.dat_file_bytes[1].fetch_add(uncompressed, Ordering::Relaxed);
+    }
+
+    /// Counts a response with the http [version].
+    pub fn record_http_version(&self, version: HttpVersion) {
+        self.responses_by_http_version[version as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records an origin that was given up because it was unreachable. Happens rarely, so a lock is fine.
+    pub fn record_unreachable(&self, origin: AtraUrlOrigin) {
+        let mut unreachable = self.unreachable_origins.write().unwrap();
+        if !unreachable.contains(&origin) {
+            unreachable.push(origin);
+        }
+    }
+
+    /// Sets the barrier of the currently running workers.
+    pub fn observe_workers(&self, barrier: Arc<WorkerBarrier>) {
+        *self.barrier.write().unwrap() = Some(barrier);
+    }
+
+    /// The crawled pages for each of the [STATUS_CODE_CLASSES].
+    pub fn crawled_by_status_class(&self) -> [u64; 6] {
+        std::array::from_fn(|i| self.crawled_by_status_class[i].load(Ordering::Relaxed))
+    }
+
+    pub fn failed_fetches(&self) -> u64 {
+        self.failed_fetches.load(Ordering::Relaxed)
+    }
+
+    pub fn skipped_downloads(&self) -> u64 {
+        self.skipped_downloads.load(Ordering::Relaxed)
+    }
+
+    pub fn skipped_data_uris(&self) -> u64 {
+        self.skipped_data_uris.load(Ordering::Relaxed)
+    }
+
+    /// The failed extractor methods for each of the [EXTRACTOR_FAILURE_KINDS].
+    pub fn extractor_failures(&self) -> [u64; 3] {
+        std::array::from_fn(|i| self.extractor_failures[i].load(Ordering::Relaxed))
+    }
+
+    pub fn warc_bytes(&self) -> u64 {
+        self.warc_bytes.load(Ordering::Relaxed)
+    }
+
+    /// The bytes of the external data files for each of the [DAT_FILE_SIZE_KINDS].
+    pub fn dat_file_bytes(&self) -> [u64; 2] {
+        std::array::from_fn(|i| self.dat_file_bytes[i].load(Ordering::Relaxed))
+    }
+
+    /// The responses for each of the [HttpVersion::ALL].
+    pub fn responses_by_http_version(&self) -> [u64; 5] {
+        std::array::from_fn(|i| self.responses_by_http_version[i].load(Ordering::Relaxed))
+    }
+
+    /// The origins given up because they were unreachable, in the order they were given up.
+    pub fn unreachable_origins(&self) -> Vec<AtraUrlOrigin> {
+        self.unreachable_origins.read().unwrap().clone()
+    }
+
+    /// Returns the number of crawling and waiting workers, none if no worker was started yet.
+    pub fn worker_states(&self) -> Option<(usize, usize)> {
+        let barrier = self.barrier.read().unwrap();
+        let barrier = barrier.as_ref()?;
+        let waiting = barrier.waiting_workers();
+        Some((barrier.active_workers().saturating_sub(waiting), waiting))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CrawlMetrics;
+    use crate::fetching::HttpVersion;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn status_codes_are_counted_by_class() {
+        let metrics = CrawlMetrics::default();
+        metrics.record_crawled(StatusCode::OK);
+        metrics.record_crawled(StatusCode::NOT_MODIFIED);
+        metrics.record_crawled(StatusCode::NOT_FOUND);
+        metrics.record_crawled(StatusCode::GONE);
+        metrics.record_crawled(StatusCode::from_u16(999).unwrap());
+        assert_eq!([0, 1, 1, 2, 0, 1], metrics.crawled_by_status_class());
+        assert_eq!(None, metrics.worker_states());
+    }
+
+    #[test]
+    fn responses_are_counted_by_http_version() {
+        let metrics = CrawlMetrics::default();
+        metrics.record_http_version(HttpVersion::Http11);
+        metrics.record_http_version(HttpVersion::Http2);
+        metrics.record_http_version(HttpVersion::Http2);
+        assert_eq!([0, 0, 1, 2, 0], metrics.responses_by_http_version());
+    }
+}
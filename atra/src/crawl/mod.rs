@@ -15,21 +15,38 @@
 use std::error::Error;
 use std::io;
 use std::sync::Arc;
+use std::time::SystemTime;
 
 use strum::{Display, EnumString};
+use time::OffsetDateTime;
 use tokio::task::yield_now;
 
-pub use crawler::result::CrawlResult;
+pub use baseline::Baseline;
+pub use crawler::publication::{
+    PublicationConfidence, PublicationEstimate, PublicationSignal, PublicationSource,
+};
+pub use crawler::rediscovery::log_unreachable_origins;
+pub use crawler::result::{
+    AuxiliaryDocument, BackfillField, BackfillNote, CrawlResult, CrawlResultMeta, FetchOutcome,
+    FetchTimings, HeaderFidelity, ImportNote,
+};
+pub use crawler::sitemaps::SitemapSummary;
 pub use crawler::slim::*;
 pub use crawler::*;
+pub use events::{CrawlEvent, CrawlEvents};
+pub use limits::{CrawlLimits, StopReason};
+pub use metrics::{CrawlMetrics, DAT_FILE_SIZE_KINDS, EXTRACTOR_FAILURE_KINDS, STATUS_CODE_CLASSES};
+pub use pause::CrawlPause;
+pub use waves::{confirm_next_wave, finish_wave, WaveError, WaveFrontier, WaveTransition};
 
 use crate::contexts::traits::{
-    SupportsCrawlResults, SupportsCrawling, SupportsLinkSeeding, SupportsLinkState,
-    SupportsPolling, SupportsSlimCrawlResults,
+    SupportsCrawlEvents, SupportsCrawlResults, SupportsCrawling, SupportsLinkSeeding,
+    SupportsLinkState, SupportsPolling, SupportsSlimCrawlResults, SupportsUrlQueue,
+    SupportsWorkerId,
 };
 use crate::contexts::Context;
 use crate::queue::QueueError;
-use crate::queue::{AbortCause, QueueExtractionError, UrlQueuePollResult};
+use crate::queue::{AbortCause, JournalEntry, QueueExtractionError, UrlQueuePollResult};
 use crate::runtime::ShutdownReceiver;
 use crate::sync::{ContinueOrStop, WorkerBarrier};
 
@@ -37,8 +54,15 @@ use crate::link_state::LinkStateManager;
 #[cfg(test)]
 pub use crawler::result::test;
 
+mod baseline;
 mod crawler;
 pub mod db;
+mod events;
+mod limits;
+mod metrics;
+mod pause;
+mod statistics;
+mod waves;
 
 /// The exit state of the crawl task
 #[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString, Display)]
@@ -94,6 +118,13 @@ where
             }
         }
 
+        // A paused worker parks before it takes the next url, the urls in flight are finished.
+        let pause = context.crawl_events().pause();
+        if pause.is_paused() {
+            pause.wait_while_paused(&shutdown).await;
+            continue;
+        }
+
         // todo: keep all alive as long as there is the possebility to encounter a new url with a different url.
         let provider = context.poll_next_free_url(shutdown.clone(), None).await;
 
@@ -104,12 +135,45 @@ where
                     patience = PATIENCE;
                 }
 
-                match context.create_crawl_task(guard.get_guarded_seed()) {
-                    Ok(mut task) => task.run(&context, shutdown.clone(), &consumer).await?,
-                    Err(err) => {
-                        consumer.consume_crawl_error(err.into())?;
+                let journal = context.dequeue_journal();
+                if let Some(journal) = journal {
+                    let entry = JournalEntry::new(
+                        guard.seed_url().clone(),
+                        guard.is_seed(),
+                        OffsetDateTime::now_utc(),
+                    );
+                    if let Err(err) = journal.record_dequeued(entry) {
+                        log::warn!(
+                            "Failed to journal the dequeue of {}: {err}",
+                            guard.seed_url()
+                        );
                     }
                 }
+                let result = match context.create_crawl_task(guard.get_guarded_seed()) {
+                    Ok(mut task) => {
+                        let result = task.run(&context, shutdown.clone(), &consumer).await;
+                        if !shutdown.is_shutdown() {
+                            // Keep the guard until the origin can be contacted again.
+                            if let Some(remaining) = task.remaining_cooldown() {
+                                guard
+                                    .guard()
+                                    .announce_cooldown(SystemTime::now() + remaining);
+                            }
+                            task.wait_for_cooldown().await;
+                        }
+                        result
+                    }
+                    Err(err) => consumer.consume_crawl_error(err.into()),
+                };
+                if let Some(journal) = journal {
+                    if let Err(err) = journal.record_completed(guard.seed_url()) {
+                        log::warn!(
+                            "Failed to journal the completion of {}: {err}",
+                            guard.seed_url()
+                        );
+                    }
+                }
+                result?
             }
             UrlQueuePollResult::Abort(cause) => {
                 if patience < 0 {
@@ -119,6 +183,11 @@ where
                         .await
                     {
                         log::debug!("Shutting down worker due to patience!");
+                        if matches!(value, Ok(ExitState::NoMoreElements)) {
+                            context.crawl_events().emit(CrawlEvent::QueueEmpty {
+                                worker_id: context.worker_id(),
+                            });
+                        }
                         return value;
                     }
                 } else {
@@ -0,0 +1,145 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::runtime::ShutdownReceiver;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::watch;
+
+/// Pauses the dequeuing of all workers sharing it, the fetches in flight are finished.
+/// The state is a watch channel, a paused worker parks on it until the crawl is resumed.
+#[derive(Debug, Clone)]
+pub struct CrawlPause {
+    /// When the current pause started, None while crawling.
+    paused_since: Arc<watch::Sender<Option<Instant>>>,
+    /// The milliseconds of the finished pauses.
+    paused_millis: Arc<AtomicU64>,
+}
+
+impl CrawlPause {
+    pub fn new() -> Self {
+        Self {
+            paused_since: Arc::new(watch::Sender::new(None)),
+            paused_millis: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Pauses the crawl, returns false if it was already paused.
+    pub fn pause(&self) -> bool {
+        self.paused_since.send_if_modified(|since| {
+            if since.is_some() {
+                return false;
+            }
+            *since = Some(Instant::now());
+            true
+        })
+    }
+
+    /// Resumes the crawl and wakes all paused workers, returns false if it was not paused.
+    pub fn resume(&self) -> bool {
+        self.paused_since
+            .send_if_modified(|since| match since.take() {
+                Some(since) => {
+                    let millis = u64::try_from(since.elapsed().as_millis()).unwrap_or(u64::MAX);
+                    self.paused_millis.fetch_add(millis, Ordering::Relaxed);
+                    true
+                }
+                None => false,
+            })
+    }
+
+    /// Resumes a paused crawl and pauses a running one. Returns true if it is paused now.
+    pub fn toggle(&self) -> bool {
+        if self.resume() {
+            false
+        } else {
+            self.pause();
+            true
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_since.borrow().is_some()
+    }
+
+    /// The time paused in total, including the current pause.
+    pub fn paused_for(&self) -> Duration {
+        let current = self
+            .paused_since
+            .borrow()
+            .map(|since| since.elapsed())
+            .unwrap_or_default();
+        Duration::from_millis(self.paused_millis.load(Ordering::Relaxed)) + current
+    }
+
+    /// Waits until the crawl is resumed or the [shutdown] is requested.
+    /// Returns immediately if the crawl is not paused.
+    pub async fn wait_while_paused<S: ShutdownReceiver>(&self, shutdown: &S) {
+        let mut receiver = self.paused_since.subscribe();
+        if receiver.borrow_and_update().is_none() {
+            return;
+        }
+        log::info!("The crawl is paused, waiting to be resumed.");
+        select! {
+            _ = receiver.wait_for(Option::is_none) => {}
+            _ = shutdown.wait() => {}
+        }
+    }
+}
+
+impl Default for CrawlPause {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CrawlPause;
+    use crate::runtime::ShutdownPhantom;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn resuming_wakes_the_waiting_workers() {
+        let pause = CrawlPause::new();
+        assert!(pause.pause());
+        assert!(!pause.pause());
+        let waiting = (0..3)
+            .map(|_| {
+                let pause = pause.clone();
+                tokio::spawn(async move {
+                    pause.wait_while_paused(&ShutdownPhantom::<true>).await;
+                })
+            })
+            .collect::<Vec<_>>();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(waiting.iter().all(|value| !value.is_finished()));
+        assert!(pause.paused_for() >= Duration::from_millis(50));
+
+        assert!(!pause.toggle());
+        for value in waiting {
+            tokio::time::timeout(Duration::from_secs(1), value)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        assert!(!pause.is_paused());
+        assert!(!pause.resume());
+        let paused_for = pause.paused_for();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(paused_for, pause.paused_for());
+    }
+}
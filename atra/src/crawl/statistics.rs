@@ -0,0 +1,364 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::client::traits::FetchFailureKind;
+use crate::schemas::{
+    CrawlStatisticsRecord, OriginStatisticsRecord, VersionedSchema, WorkerStatisticsRecord,
+};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use reqwest::StatusCode;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+use time::OffsetDateTime;
+
+/// The failure kinds in the order of their discriminants.
+const FAILURE_KINDS: [FetchFailureKind; 6] = [
+    FetchFailureKind::ProxyConnect,
+    FetchFailureKind::Connect,
+    FetchFailureKind::Timeout,
+    FetchFailureKind::Other,
+    FetchFailureKind::Tls,
+    FetchFailureKind::ServerError,
+];
+
+/// The counters of a single origin.
+#[derive(Debug, Default)]
+struct OriginCounters {
+    pages: AtomicU64,
+    bytes: AtomicU64,
+    fetches: AtomicU64,
+    fetch_nanos: AtomicU64,
+    failures: [AtomicU64; 6],
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    budget_exhausted: AtomicBool,
+    filtered: AtomicU64,
+    baseline_skipped: AtomicU64,
+}
+
+/// The counters of a single worker.
+#[derive(Debug, Default)]
+struct WorkerCounters {
+    pages: AtomicU64,
+    fetch_nanos: AtomicU64,
+    extract_nanos: AtomicU64,
+    store_nanos: AtomicU64,
+}
+
+/// Statistics of the crawl by origin and by worker, persisted as `stats.json` at the end.
+///
+/// The workers only lock to register an origin or worker seen for the first time, everything
+/// else is an atomic counter. A panicking worker never leaves the collector unusable,
+/// a poisoned lock is taken over as is.
+#[derive(Debug)]
+pub struct CrawlStatistics {
+    started_at: OffsetDateTime,
+    origins: RwLock<HashMap<AtraUrlOrigin, Arc<OriginCounters>>>,
+    workers: RwLock<Vec<Arc<WorkerCounters>>>,
+}
+
+impl CrawlStatistics {
+    pub fn new() -> Self {
+        Self {
+            started_at: OffsetDateTime::now_utc(),
+            origins: RwLock::new(HashMap::new()),
+            workers: RwLock::new(Vec::new()),
+        }
+    }
+
+    fn origin(&self, url: &UrlWithDepth) -> Option<Arc<OriginCounters>> {
+        let origin = url.atra_origin()?;
+        if let Some(counters) = self
+            .origins
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&origin)
+        {
+            return Some(counters.clone());
+        }
+        Some(
+            self.origins
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .entry(origin)
+                .or_default()
+                .clone(),
+        )
+    }
+
+    fn worker(&self, worker_id: usize) -> Arc<WorkerCounters> {
+        if let Some(counters) = self
+            .workers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(worker_id)
+        {
+            return counters.clone();
+        }
+        let mut workers = self.workers.write().unwrap_or_else(PoisonError::into_inner);
+        if workers.len() <= worker_id {
+            workers.resize_with(worker_id + 1, Default::default);
+        }
+        workers[worker_id].clone()
+    }
+
+    /// Counts the stored page [url] with the [status_code], None if it is not known.
+    pub fn record_page(&self, url: &UrlWithDepth, status_code: Option<StatusCode>) {
+        let Some(counters) = self.origin(url) else {
+            return;
+        };
+        counters.pages.fetch_add(1, Ordering::Relaxed);
+        match status_code {
+            Some(value) if value.is_client_error() => {
+                counters.client_errors.fetch_add(1, Ordering::Relaxed);
+            }
+            Some(value) if value.is_server_error() => {
+                counters.server_errors.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    /// Counts a failed fetch of [url] by its [kind].
+    pub fn record_failure(&self, url: &UrlWithDepth, kind: FetchFailureKind) {
+        if let Some(counters) = self.origin(url) {
+            counters.failures[kind as usize].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds a fetch of [url] by the worker, successful or not, with the [bytes] of the body.
+    pub fn record_fetch(
+        &self,
+        worker_id: usize,
+        url: &UrlWithDepth,
+        duration: Duration,
+        bytes: u64,
+    ) {
+        let nanos = duration.as_nanos() as u64;
+        self.worker(worker_id)
+            .fetch_nanos
+            .fetch_add(nanos, Ordering::Relaxed);
+        if let Some(counters) = self.origin(url) {
+            counters.fetches.fetch_add(1, Ordering::Relaxed);
+            counters.fetch_nanos.fetch_add(nanos, Ordering::Relaxed);
+            counters.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+    }
+
+    /// Adds the time the worker spent to analyze a page and extract its data.
+    pub fn record_extract(&self, worker_id: usize, duration: Duration) {
+        self.worker(worker_id)
+            .extract_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Adds the time the worker spent to store a page and counts the page as processed.
+    pub fn record_store(&self, worker_id: usize, duration: Duration) {
+        let counters = self.worker(worker_id);
+        counters.pages.fetch_add(1, Ordering::Relaxed);
+        counters
+            .store_nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// Marks the origin of [url] as exhausted, a link was dropped because it is out of the budget.
+    pub fn record_budget_exhausted(&self, url: &UrlWithDepth) {
+        if let Some(counters) = self.origin(url) {
+            counters.budget_exhausted.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a found url of the origin of [url] rejected by the url filters.
+    pub fn record_filtered(&self, url: &UrlWithDepth) {
+        if let Some(counters) = self.origin(url) {
+            counters.filtered.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts an url of the origin of [url] not fetched because the baseline stored it.
+    pub fn record_baseline_skip(&self, url: &UrlWithDepth) {
+        if let Some(counters) = self.origin(url) {
+            counters.baseline_skipped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// The statistics gathered so far, the origins are sorted by name.
+    pub fn snapshot(&self) -> CrawlStatisticsRecord {
+        let origins = self
+            .origins
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .map(|(origin, counters)| (origin.to_string(), counters.clone()))
+            .collect::<BTreeMap<_, _>>();
+        let origins = origins
+            .into_iter()
+            .map(|(origin, counters)| {
+                let fetches = counters.fetches.load(Ordering::Relaxed);
+                let mut errors = BTreeMap::new();
+                for (kind, count) in FAILURE_KINDS.iter().zip(&counters.failures) {
+                    let count = count.load(Ordering::Relaxed);
+                    if count > 0 {
+                        errors.insert(kind.to_string(), count);
+                    }
+                }
+                for (class, count) in [
+                    ("4xx", &counters.client_errors),
+                    ("5xx", &counters.server_errors),
+                ] {
+                    let count = count.load(Ordering::Relaxed);
+                    if count > 0 {
+                        errors.insert(class.to_string(), count);
+                    }
+                }
+                OriginStatisticsRecord {
+                    origin,
+                    pages: counters.pages.load(Ordering::Relaxed),
+                    bytes: counters.bytes.load(Ordering::Relaxed),
+                    fetches,
+                    average_fetch_millis: if fetches == 0 {
+                        0.0
+                    } else {
+                        counters.fetch_nanos.load(Ordering::Relaxed) as f64
+                            / fetches as f64
+                            / 1_000_000.0
+                    },
+                    errors,
+                    budget_exhausted: counters.budget_exhausted.load(Ordering::Relaxed),
+                    filtered: counters.filtered.load(Ordering::Relaxed),
+                    baseline_skipped: counters.baseline_skipped.load(Ordering::Relaxed),
+                }
+            })
+            .collect();
+        let workers = self
+            .workers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .enumerate()
+            .map(|(worker_id, counters)| WorkerStatisticsRecord {
+                worker_id,
+                pages: counters.pages.load(Ordering::Relaxed),
+                fetch_millis: counters.fetch_nanos.load(Ordering::Relaxed) / 1_000_000,
+                extract_millis: counters.extract_nanos.load(Ordering::Relaxed) / 1_000_000,
+                store_millis: counters.store_nanos.load(Ordering::Relaxed) / 1_000_000,
+            })
+            .collect();
+        CrawlStatisticsRecord {
+            schema: CrawlStatisticsRecord::NAME.to_string(),
+            version: CrawlStatisticsRecord::VERSION,
+            started_at: self.started_at,
+            finished_at: OffsetDateTime::now_utc(),
+            origins,
+            workers,
+            stop_reason: None,
+            paused_millis: 0,
+            seen_filter: None,
+        }
+    }
+}
+
+impl Default for CrawlStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CrawlStatistics;
+    use crate::client::traits::FetchFailureKind;
+    use crate::url::UrlWithDepth;
+    use reqwest::StatusCode;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn counts_by_origin_and_worker() {
+        let statistics = CrawlStatistics::new();
+        let first = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let second = UrlWithDepth::from_url("https://www.example.com/missing").unwrap();
+        let other = UrlWithDepth::from_url("https://other.example.org/").unwrap();
+
+        statistics.record_fetch(0, &first, Duration::from_millis(20), 100);
+        statistics.record_extract(0, Duration::from_millis(5));
+        statistics.record_store(0, Duration::from_millis(3));
+        statistics.record_page(&first, Some(StatusCode::OK));
+        statistics.record_fetch(2, &second, Duration::from_millis(40), 10);
+        statistics.record_page(&second, Some(StatusCode::NOT_FOUND));
+        statistics.record_fetch(2, &other, Duration::from_millis(30), 0);
+        statistics.record_failure(&other, FetchFailureKind::Timeout);
+        statistics.record_budget_exhausted(&second);
+        statistics.record_filtered(&second);
+        statistics.record_filtered(&first);
+        statistics.record_baseline_skip(&other);
+
+        let snapshot = statistics.snapshot();
+        assert_eq!(2, snapshot.origins.len());
+        let origin = &snapshot.origins[1];
+        assert_eq!("www.example.com", origin.origin);
+        assert_eq!(2, origin.pages);
+        assert_eq!(2, origin.fetches);
+        assert_eq!(110, origin.bytes);
+        assert!((origin.average_fetch_millis - 30.0).abs() < 0.001);
+        assert_eq!(BTreeMap::from([("4xx".to_string(), 1u64)]), origin.errors);
+        assert!(origin.budget_exhausted);
+        assert_eq!(2, origin.filtered);
+        assert_eq!(0, origin.baseline_skipped);
+
+        let origin = &snapshot.origins[0];
+        assert_eq!(0, origin.pages);
+        assert_eq!(1, origin.fetches);
+        assert_eq!(
+            BTreeMap::from([(FetchFailureKind::Timeout.to_string(), 1u64)]),
+            origin.errors
+        );
+        assert!(!origin.budget_exhausted);
+        assert_eq!(0, origin.filtered);
+        assert_eq!(1, origin.baseline_skipped);
+
+        // The worker 1 never reported anything.
+        assert_eq!(3, snapshot.workers.len());
+        assert_eq!(1, snapshot.workers[0].pages);
+        assert_eq!(20, snapshot.workers[0].fetch_millis);
+        assert_eq!(5, snapshot.workers[0].extract_millis);
+        assert_eq!(3, snapshot.workers[0].store_millis);
+        assert_eq!(0, snapshot.workers[1].pages);
+        assert_eq!(70, snapshot.workers[2].fetch_millis);
+        assert!(snapshot.started_at <= snapshot.finished_at);
+    }
+
+    #[test]
+    fn survives_a_panicking_worker() {
+        let statistics = Arc::new(CrawlStatistics::new());
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        statistics.record_page(&url, None);
+        let poisoning = statistics.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = poisoning.origins.write().unwrap();
+            let _workers = poisoning.workers.write().unwrap();
+            panic!("The worker died.");
+        })
+        .join();
+
+        statistics.record_page(&url, None);
+        statistics.record_store(1, Duration::from_millis(1));
+        let snapshot = statistics.snapshot();
+        assert_eq!(2, snapshot.origins[0].pages);
+        assert_eq!(1, snapshot.workers[1].pages);
+    }
+}
@@ -0,0 +1,591 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contexts::traits::{SupportsConfigs, SupportsCrawlEvents, SupportsUrlQueue};
+use crate::crawl::CrawlMetrics;
+use crate::queue::{
+    QueueError, QueueFileError, RawAgingQueueFile, UrlQueue, UrlQueueElement, UrlQueueWrapper,
+};
+use crate::runtime::ShutdownReceiver;
+use crate::schemas::{VersionedSchema, WaveSummary};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use camino::Utf8Path;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use thiserror::Error;
+use time::OffsetDateTime;
+use tokio::select;
+
+/// How often the confirmation file of the next wave is checked.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Errors when finishing a wave.
+#[derive(Debug, Error)]
+pub enum WaveError {
+    #[error("Failed to write the summary of the wave: {0}")]
+    Summary(#[from] io::Error),
+    #[error(transparent)]
+    Queue(#[from] QueueError),
+}
+
+/// The counters of the [CrawlMetrics] a wave summary is computed from.
+#[derive(Debug, Copy, Clone, Default)]
+struct WaveCounters {
+    pages: u64,
+    failed: u64,
+    bytes: u64,
+}
+
+impl WaveCounters {
+    fn capture(metrics: &CrawlMetrics) -> Self {
+        let [dat_file_bytes, _] = metrics.dat_file_bytes();
+        Self {
+            pages: metrics.crawled_by_status_class().iter().sum::<u64>()
+                + metrics.skipped_downloads(),
+            failed: metrics.failed_fetches(),
+            bytes: metrics.warc_bytes() + dat_file_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct WaveState {
+    /// The urls served in the current wave.
+    served: usize,
+    /// The origins served in the current wave.
+    wave_origins: HashSet<AtraUrlOrigin>,
+    /// The origins served in all waves so far.
+    visited_origins: HashSet<AtraUrlOrigin>,
+    /// The counters when the current wave started.
+    started_with: WaveCounters,
+}
+
+/// Holds the urls of the later waves back while a crawl in wave mode crawls the current wave.
+///
+/// The wave of an url is its distance to the seed, the number of origins between the seed
+/// and the url. The first wave crawls the origins of the seeds, every further wave the origins
+/// linked by the previous wave. The crawl task of an url crawls its origin, so the waves
+/// are exactly the [crate::config::BudgetSetting::Normal] depths.
+///
+/// The held urls are persisted in a queue of their own, see
+/// [crate::config::PathsConfig::file_wave_frontier]. They survive a crash and a restarted
+/// crawl continues with them.
+#[derive(Debug)]
+pub struct WaveFrontier {
+    current: AtomicU64,
+    /// The urls of the later waves.
+    held: UrlQueueWrapper<RawAgingQueueFile>,
+    state: Mutex<WaveState>,
+}
+
+impl WaveFrontier {
+    /// Opens the frontier with the urls held at [path].
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, QueueFileError> {
+        Ok(Self {
+            current: AtomicU64::new(0),
+            held: UrlQueueWrapper::open(path)?,
+            state: Mutex::new(WaveState::default()),
+        })
+    }
+
+    /// The wave of the [url].
+    pub fn wave_of(url: &UrlWithDepth) -> u64 {
+        url.depth().distance_to_seed
+    }
+
+    /// The wave currently crawled.
+    pub fn current_wave(&self) -> u64 {
+        self.current.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the [url] belongs to a later wave than the current one.
+    pub fn is_beyond(&self, url: &UrlWithDepth) -> bool {
+        Self::wave_of(url) > self.current_wave()
+    }
+
+    /// Holds the [element] back until its wave starts. It is persisted before it may be
+    /// dropped from the queue it was dequeued from.
+    pub async fn hold(&self, element: &UrlQueueElement<UrlWithDepth>) -> Result<(), QueueError> {
+        self.held
+            .enqueue(UrlQueueElement {
+                is_seed: element.is_seed,
+                age: element.age,
+                host_was_in_use: element.host_was_in_use,
+                target: element.target.clone(),
+                priority: element.priority,
+            })
+            .await
+    }
+
+    /// Records that the [url] is crawled in the current wave.
+    pub fn record_served(&self, url: &UrlWithDepth) {
+        let mut state = self.state.lock().unwrap();
+        state.served += 1;
+        if let Some(origin) = url.atra_origin() {
+            state.wave_origins.insert(origin);
+        }
+    }
+
+    /// The number of urls held back for the later waves.
+    pub fn held_len(&self) -> usize {
+        self.held.len_blocking()
+    }
+
+    /// The wave started after the current one, None if no url is held.
+    pub fn next_wave(&self) -> Result<Option<u64>, QueueError> {
+        let mut next_wave = None;
+        self.held.for_each_queued(|element| {
+            let wave = Self::wave_of(&element.target);
+            next_wave = Some(next_wave.map_or(wave, |found: u64| found.min(wave)));
+        })?;
+        Ok(next_wave)
+    }
+
+    /// The wave started after the current one with its held urls, None if no url is held.
+    fn next_wave_elements(
+        &self,
+    ) -> Result<Option<(u64, Vec<UrlQueueElement<UrlWithDepth>>)>, QueueError> {
+        let mut next: Option<(u64, Vec<UrlQueueElement<UrlWithDepth>>)> = None;
+        self.held.for_each_queued(|element| {
+            let wave = Self::wave_of(&element.target);
+            let found = next.get_or_insert_with(|| (wave, Vec::new()));
+            if wave < found.0 {
+                *found = (wave, Vec::new());
+            }
+            if wave == found.0 {
+                found.1.push(element);
+            }
+        })?;
+        Ok(next)
+    }
+
+    /// Summarizes the current wave with the counters of the [metrics] since it started.
+    /// Returns None if no url was served in the wave, e.g. after restarting a crawl between
+    /// two waves.
+    fn summarize(
+        &self,
+        metrics: &CrawlMetrics,
+        finished_at: OffsetDateTime,
+    ) -> Result<Option<WaveSummary>, QueueError> {
+        if self.state.lock().unwrap().served == 0 {
+            return Ok(None);
+        }
+        let next = self.next_wave_elements()?;
+        let state = self.state.lock().unwrap();
+        let counters = WaveCounters::capture(metrics);
+        let next_wave = next.as_ref().map(|(next_wave, _)| *next_wave);
+        let next_wave_elements = next
+            .as_ref()
+            .map_or(&[][..], |(_, elements)| elements.as_slice());
+        let new_origins: HashSet<AtraUrlOrigin> = next_wave_elements
+            .iter()
+            .filter_map(|element| element.target.atra_origin())
+            .filter(|origin| {
+                !state.wave_origins.contains(origin) && !state.visited_origins.contains(origin)
+            })
+            .collect();
+        Ok(Some(WaveSummary {
+            schema: WaveSummary::NAME.to_string(),
+            version: WaveSummary::VERSION,
+            wave: self.current_wave(),
+            pages: counters.pages - state.started_with.pages,
+            failed: counters.failed - state.started_with.failed,
+            bytes: counters.bytes - state.started_with.bytes,
+            origins: state.wave_origins.len(),
+            new_origins: new_origins.len(),
+            next_wave,
+            next_wave_urls: next_wave_elements.len(),
+            finished_at,
+        }))
+    }
+
+    /// Starts the next wave and moves its urls to the [queue]. Returns the wave with the number
+    /// of its urls, None if no url is held.
+    async fn advance<Q>(
+        &self,
+        queue: &Q,
+        metrics: &CrawlMetrics,
+    ) -> Result<Option<(u64, usize)>, QueueError>
+    where
+        Q: UrlQueue<UrlWithDepth>,
+    {
+        let Some((next_wave, elements)) = self.next_wave_elements()? else {
+            return Ok(None);
+        };
+        let urls = elements.len();
+        // Enqueued before they are removed from the frontier, a crash in between only
+        // duplicates them.
+        queue.enqueue_all(elements).await?;
+        self.held
+            .retain(|element| Self::wave_of(&element.target) != next_wave)
+            .await?;
+        let mut state = self.state.lock().unwrap();
+        state.served = 0;
+        let served = std::mem::take(&mut state.wave_origins);
+        state.visited_origins.extend(served);
+        state.started_with = WaveCounters::capture(metrics);
+        self.current.store(next_wave, Ordering::Release);
+        Ok(Some((next_wave, urls)))
+    }
+}
+
+/// How the crawl continues after its queue was exhausted.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WaveTransition {
+    /// The wave was started, its urls are in the queue.
+    Started(u64),
+    /// No url is left for a further wave, or the crawl is not in wave mode.
+    Finished,
+    /// The crawl was shut down while waiting for the confirmation of the next wave.
+    Shutdown,
+}
+
+/// Finishes the current wave of a crawl in wave mode after its queue was exhausted.
+///
+/// Writes the [WaveSummary] of the wave to the root of the session and starts the next
+/// wave. With [crate::config::CrawlConfig::wave_confirmation] the next wave only starts after
+/// its confirmation file was created, e.g. by `atra continue`.
+pub async fn finish_wave<C>(
+    context: &C,
+    shutdown: impl ShutdownReceiver,
+) -> Result<WaveTransition, WaveError>
+where
+    C: SupportsUrlQueue + SupportsConfigs + SupportsCrawlEvents,
+{
+    let Some(frontier) = context.wave_frontier() else {
+        return Ok(WaveTransition::Finished);
+    };
+    let metrics = context.crawl_events().metrics();
+    let root = context.configs().paths.root_path();
+    let current = frontier.current_wave();
+    match frontier.summarize(metrics, OffsetDateTime::now_utc())? {
+        Some(summary) => {
+            summary.write_to(root)?;
+            log::info!(
+                "Finished wave {current}: {} pages ({} failed, {} bytes) on {} origins. The next wave has {} urls on {} new origins.",
+                summary.pages,
+                summary.failed,
+                summary.bytes,
+                summary.origins,
+                summary.next_wave_urls,
+                summary.new_origins
+            );
+        }
+        None => log::info!("Nothing was crawled in wave {current}."),
+    }
+    // Also a wave restarted without any url left waits, its confirmation may still be missing.
+    if let Some(next_wave) = frontier.next_wave()? {
+        if context.configs().crawl.wave_confirmation
+            && !wait_for_confirmation(root, next_wave, shutdown).await
+        {
+            return Ok(WaveTransition::Shutdown);
+        }
+    }
+    match frontier.advance(context.url_queue(), metrics).await? {
+        Some((next_wave, urls)) => {
+            log::info!("Start wave {next_wave} with {urls} urls.");
+            Ok(WaveTransition::Started(next_wave))
+        }
+        None => {
+            log::info!("No url is left for a further wave.");
+            Ok(WaveTransition::Finished)
+        }
+    }
+}
+
+/// Waits until the confirmation file of the [wave] exists in [root].
+/// Returns false if the crawl was shut down before.
+async fn wait_for_confirmation(
+    root: &Utf8Path,
+    wave: u64,
+    shutdown: impl ShutdownReceiver,
+) -> bool {
+    let confirmation = WaveSummary::confirmation_file(root, wave);
+    if !confirmation.exists() {
+        log::info!("Waiting for `atra continue {root}` to start wave {wave}.");
+    }
+    loop {
+        if confirmation.exists() {
+            return true;
+        }
+        select! {
+            _ = shutdown.wait() => return false,
+            _ = tokio::time::sleep(CONFIRMATION_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// Confirms the start of the wave after the latest finished wave of the crawl in [root].
+/// Returns the confirmed wave, None if no wave finished yet or no further wave follows.
+pub fn confirm_next_wave(root: &Utf8Path) -> io::Result<Option<u64>> {
+    let Some(next_wave) = WaveSummary::read_latest(root)?.and_then(|summary| summary.next_wave)
+    else {
+        return Ok(None);
+    };
+    std::fs::File::create(WaveSummary::confirmation_file(root, next_wave))?;
+    Ok(Some(next_wave))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{confirm_next_wave, finish_wave, WaveTransition};
+    use crate::config::{BudgetSetting, Config, CrawlConfig};
+    use crate::contexts::traits::{
+        SupportsCrawlEvents, SupportsCrawling, SupportsPolling, SupportsUrlQueue,
+    };
+    use crate::crawl::CrawlEvent;
+    use crate::data::RawData;
+    use crate::fetching::FetchedRequestData;
+    use crate::queue::{UrlQueue, UrlQueueElement, UrlQueuePollResult};
+    use crate::runtime::ShutdownPhantom;
+    use crate::schemas::test::assert_matches_schema;
+    use crate::schemas::{VersionedSchema, WaveSummary};
+    use crate::test_impls::{FakeClientProvider, FakeResponse, TestContext, TestErrorConsumer};
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::StatusCode;
+    use std::pin::pin;
+    use std::time::Duration;
+    use tokio::sync::broadcast::Receiver;
+
+    /// The pages and their links, the seeds are on first.de and other.de.
+    const PAGES: &[(&str, &[&str])] = &[
+        (
+            "https://www.first.de/",
+            &[
+                "/about.html",
+                "https://www.second.de/",
+                "https://www.third.org/",
+            ],
+        ),
+        (
+            "https://www.first.de/about.html",
+            &["https://www.second.de/"],
+        ),
+        ("https://www.other.de/", &["https://www.second.de/"]),
+        ("https://www.second.de/", &["https://www.fourth.com/"]),
+        ("https://www.third.org/", &[]),
+    ];
+
+    fn fixture(root: &Utf8TempDir) -> TestContext<FakeClientProvider> {
+        let mut config = CrawlConfig::default();
+        config.wave_mode = true;
+        config.wave_confirmation = true;
+        config.respect_robots_txt = false;
+        config.ignore_sitemap = true;
+        // Only the first two waves are in the budget.
+        config.budget.default = BudgetSetting::Normal {
+            depth_on_website: 0,
+            depth: 1,
+            recrawl_interval: None,
+            request_timeout: None,
+        };
+        let mut config = Config::new(
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            config,
+        );
+        config.paths.root = root.path().to_path_buf();
+
+        let provider = FakeClientProvider::new();
+        for (url, links) in PAGES {
+            let links: String = links
+                .iter()
+                .map(|link| format!("<a href=\"{link}\">{link}</a>"))
+                .collect();
+            provider.insert(
+                url.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawData::from_vec(
+                            format!("<html><body>{links}</body></html>").into_bytes(),
+                        ),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    1,
+                )),
+            );
+        }
+        TestContext::new(config, provider)
+    }
+
+    /// Crawls until the queue is exhausted, returns the polled urls in their order.
+    async fn crawl_queue(context: &TestContext<FakeClientProvider>) -> Vec<UrlWithDepth> {
+        let mut polled = Vec::new();
+        loop {
+            match context.poll_next_free_url_no_shutdown(None).await {
+                UrlQueuePollResult::Ok(guard) => {
+                    polled.push(guard.seed_url().clone());
+                    context
+                        .create_crawl_task(guard.get_guarded_seed())
+                        .unwrap()
+                        .run(context, ShutdownPhantom::<true>, &TestErrorConsumer::new())
+                        .await
+                        .unwrap();
+                }
+                UrlQueuePollResult::Abort(_) => break polled,
+                UrlQueuePollResult::Err(err) => panic!("Failed to poll: {err}"),
+            }
+        }
+    }
+
+    /// The pages crawled since the last call.
+    fn crawled_pages(events: &mut Receiver<CrawlEvent>) -> Vec<UrlWithDepth> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                CrawlEvent::Crawled { url, .. } => Some(url),
+                _ => None,
+            })
+            .collect()
+    }
+
+    async fn enqueue_seeds(context: &TestContext<FakeClientProvider>) {
+        context
+            .url_queue()
+            .enqueue_all(
+                ["https://www.first.de/", "https://www.other.de/"].map(|seed| {
+                    UrlQueueElement::new(true, 0, false, UrlWithDepth::from_url(seed).unwrap())
+                }),
+            )
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn crawls_the_waves_in_order_and_waits_for_the_confirmation() {
+        let root = Utf8TempDir::new().unwrap();
+        let context = fixture(&root);
+        let frontier = context.wave_frontier().unwrap();
+        let mut events = context.crawl_events().subscribe();
+        enqueue_seeds(&context).await;
+
+        assert_eq!(2, crawl_queue(&context).await.len());
+        let first_wave = crawled_pages(&mut events);
+        assert_eq!(3, first_wave.len(), "{first_wave:?}");
+        assert!(
+            first_wave
+                .iter()
+                .all(|url| url.depth().distance_to_seed == 0),
+            "{first_wave:?}"
+        );
+        assert_eq!(0, frontier.current_wave());
+        assert_eq!(2, frontier.held_len());
+        assert!(context.url_queue().is_empty().await);
+
+        // The summary is written right away, the next wave waits for the confirmation.
+        let mut finishing = pin!(finish_wave(&context, ShutdownPhantom::<true>));
+        assert!(
+            tokio::time::timeout(Duration::from_millis(600), &mut finishing)
+                .await
+                .is_err()
+        );
+        assert!(context.url_queue().is_empty().await);
+        let summary = WaveSummary::read_latest(root.path()).unwrap().unwrap();
+        assert_matches_schema(WaveSummary::NAME, &serde_json::to_value(&summary).unwrap());
+        assert_eq!(
+            (0, 3, 0, 2, 2, Some(1), 2),
+            (
+                summary.wave,
+                summary.pages,
+                summary.failed,
+                summary.origins,
+                summary.new_origins,
+                summary.next_wave,
+                summary.next_wave_urls
+            )
+        );
+
+        assert_eq!(Some(1), confirm_next_wave(root.path()).unwrap());
+        assert_eq!(WaveTransition::Started(1), finishing.await.unwrap());
+        assert_eq!(1, frontier.current_wave());
+        assert_eq!(0, frontier.held_len());
+        assert_eq!(2, context.url_queue().len().await);
+
+        let polled = crawl_queue(&context).await;
+        assert!(
+            polled.iter().all(|url| url.depth().distance_to_seed == 1),
+            "{polled:?}"
+        );
+        let second_wave = crawled_pages(&mut events);
+        assert_eq!(2, second_wave.len(), "{second_wave:?}");
+        assert!(
+            second_wave
+                .iter()
+                .all(|url| url.depth().distance_to_seed == 1),
+            "{second_wave:?}"
+        );
+
+        // The links of the second wave are out of the budget, no further wave follows.
+        assert_eq!(
+            WaveTransition::Finished,
+            finish_wave(&context, ShutdownPhantom::<true>)
+                .await
+                .unwrap()
+        );
+        let summary = WaveSummary::read_latest(root.path()).unwrap().unwrap();
+        assert_eq!(
+            (1, 2, 2, 0, None, 0),
+            (
+                summary.wave,
+                summary.pages,
+                summary.origins,
+                summary.new_origins,
+                summary.next_wave,
+                summary.next_wave_urls
+            )
+        );
+        assert_eq!(None, confirm_next_wave(root.path()).unwrap());
+    }
+
+    #[tokio::test]
+    async fn the_held_urls_survive_a_crash() {
+        let root = Utf8TempDir::new().unwrap();
+        let crashed = fixture(&root);
+        enqueue_seeds(&crashed).await;
+        assert_eq!(2, crawl_queue(&crashed).await.len());
+        assert_eq!(2, crashed.wave_frontier().unwrap().held_len());
+        // Nothing is dropped or flushed, like after a crash.
+        std::mem::forget(crashed);
+
+        let context = fixture(&root);
+        let frontier = context.wave_frontier().unwrap();
+        assert_eq!(0, frontier.current_wave());
+        assert_eq!(2, frontier.held_len());
+        assert!(context.url_queue().is_empty().await);
+
+        std::fs::File::create(WaveSummary::confirmation_file(root.path(), 1)).unwrap();
+        assert_eq!(
+            WaveTransition::Started(1),
+            finish_wave(&context, ShutdownPhantom::<true>)
+                .await
+                .unwrap()
+        );
+        assert_eq!(0, frontier.held_len());
+        let polled = crawl_queue(&context).await;
+        assert_eq!(2, polled.len(), "{polled:?}");
+        assert!(
+            polled.iter().all(|url| url.depth().distance_to_seed == 1),
+            "{polled:?}"
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, IoSliceMut, Read, Seek};
+use strum::Display;
+
+/// The compression of an external data file.
+///
+/// The compression is recorded in the name of the file, so every reference to the path
+/// (the slim crawl result, the external file hint in the warc, ...) knows how to read it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize, Display)]
+pub enum DataCompression {
+    /// Written through a zstd encoder, the name ends with `.zst`.
+    Zstd,
+}
+
+impl DataCompression {
+    /// The extension appended to the name of a zstd compressed file.
+    pub const ZSTD_EXTENSION: &'static str = "zst";
+
+    /// The compression of the file at [path], none if it is stored as is.
+    pub fn of_path(path: impl AsRef<Utf8Path>) -> Option<Self> {
+        match path.as_ref().extension() {
+            Some(Self::ZSTD_EXTENSION) => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// The [path] with the extension of this compression appended.
+    pub fn apply_to_path(self, path: impl AsRef<Utf8Path>) -> Utf8PathBuf {
+        match self {
+            DataCompression::Zstd => {
+                Utf8PathBuf::from(format!("{}.{}", path.as_ref(), Self::ZSTD_EXTENSION))
+            }
+        }
+    }
+}
+
+/// Reads an external data file, a compressed file is transparently decompressed.
+pub enum ExternalFileReader {
+    Plain(File),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<File>>),
+}
+
+impl ExternalFileReader {
+    /// Opens the external data file at [path].
+    pub fn open(path: impl AsRef<Utf8Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let file = File::options().read(true).open(path)?;
+        match DataCompression::of_path(path) {
+            None => Ok(Self::Plain(file)),
+            Some(DataCompression::Zstd) => Ok(Self::Zstd(zstd::stream::read::Decoder::new(file)?)),
+        }
+    }
+
+    /// Returns a seekable file with the uncompressed content.
+    /// A compressed file is decompressed into an anonymous temp file.
+    pub fn into_seekable(self) -> io::Result<File> {
+        match self {
+            ExternalFileReader::Plain(file) => Ok(file),
+            ExternalFileReader::Zstd(mut decoder) => {
+                let mut temp = tempfile::tempfile()?;
+                io::copy(&mut decoder, &mut temp)?;
+                temp.rewind()?;
+                Ok(temp)
+            }
+        }
+    }
+}
+
+impl Read for ExternalFileReader {
+    delegate::delegate! {
+        to match self {
+            Self::Plain(reader) => reader,
+            Self::Zstd(reader) => reader,
+        } {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>;
+            fn read_vectored(&mut self, bufs: &mut [IoSliceMut<'_>]) -> io::Result<usize>;
+            fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize>;
+            fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize>;
+            fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()>;
+        }
+    }
+}
+
+/// The content of an external data file at a path readable by tools that do not know
+/// about the compression. A decompressed copy is deleted when this is dropped.
+pub enum UncompressedFile<'a> {
+    Original(&'a Utf8Path),
+    Decompressed(camino_tempfile::NamedUtf8TempFile),
+}
+
+impl<'a> UncompressedFile<'a> {
+    /// Provides the uncompressed content of the external data file at [path].
+    pub fn provide(path: &'a Utf8Path) -> io::Result<Self> {
+        match DataCompression::of_path(path) {
+            None => Ok(Self::Original(path)),
+            Some(_) => {
+                let mut temp = camino_tempfile::NamedUtf8TempFile::new()?;
+                io::copy(&mut ExternalFileReader::open(path)?, temp.as_file_mut())?;
+                Ok(Self::Decompressed(temp))
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        match self {
+            UncompressedFile::Original(path) => path,
+            UncompressedFile::Decompressed(temp) => temp.path(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::{DataCompression, ExternalFileReader, UncompressedFile};
+    use camino::Utf8PathBuf;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn the_compression_is_recorded_in_the_name() {
+        let path = Utf8PathBuf::from("big_files/abc_1_2.dat");
+        assert_eq!(None, DataCompression::of_path(&path));
+        let compressed = DataCompression::Zstd.apply_to_path(&path);
+        assert_eq!("big_files/abc_1_2.dat.zst", compressed.as_str());
+        assert_eq!(
+            Some(DataCompression::Zstd),
+            DataCompression::of_path(&compressed)
+        );
+    }
+
+    #[test]
+    fn reads_compressed_files_transparently() {
+        let dir = camino_tempfile::tempdir().unwrap();
+        let content = "<p>Hello World!</p>\n".repeat(1000).into_bytes();
+        let path = DataCompression::Zstd.apply_to_path(dir.path().join("page.dat"));
+        let mut encoder =
+            zstd::stream::write::Encoder::new(std::fs::File::create(&path).unwrap(), 3).unwrap();
+        encoder.write_all(&content).unwrap();
+        encoder.finish().unwrap();
+
+        let mut read = Vec::new();
+        ExternalFileReader::open(&path)
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(content, read);
+
+        let uncompressed = UncompressedFile::provide(&path).unwrap();
+        assert_ne!(path, uncompressed.path());
+        assert_eq!(content, std::fs::read(uncompressed.path()).unwrap());
+        let temp_path = uncompressed.path().to_path_buf();
+        drop(uncompressed);
+        assert!(!temp_path.exists());
+    }
+}
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod compression;
 mod decoded;
 mod processing;
 mod raw;
@@ -20,4 +21,6 @@ pub use processing::process;
 
 pub use raw::*;
 
+pub use compression::*;
+
 pub use decoded::*;
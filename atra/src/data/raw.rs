@@ -12,8 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::data::{DataCompression, ExternalFileReader};
 use crate::format::FileContentReader;
-use crate::toolkit::CursorWithLifeline;
 use camino::Utf8PathBuf;
 use serde::{Deserialize, Serialize};
 use std::cmp::min;
@@ -44,7 +44,8 @@ pub enum RawData<T> {
     None,
     /// We got some data
     InMemory { data: T },
-    /// If we are too big we store it in a separate file on the file system
+    /// If we are too big we store it in a separate file on the file system,
+    /// the name records the [DataCompression] of the file.
     ExternalFile { path: Utf8PathBuf },
 }
 
@@ -68,6 +69,14 @@ impl<T> RawData<T> {
             RawData::ExternalFile { .. } => None,
         }
     }
+
+    /// The compression of an external file, all read methods decompress it transparently.
+    pub fn compression(&self) -> Option<DataCompression> {
+        match self {
+            RawData::ExternalFile { path } => DataCompression::of_path(path),
+            _ => None,
+        }
+    }
 }
 
 impl RawData<Vec<u8>> {
@@ -92,10 +101,13 @@ impl<T: AsRef<[u8]>> FileContentReader for RawData<T> {
     }
 
     #[inline(always)]
-    fn cursor(
-        &mut self,
-    ) -> Result<Option<CursorWithLifeline<impl Seek + Read>>, Self::Error> {
-        Ok(RawData::cursor(self)?.map(CursorWithLifeline::new))
+    fn peek(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error> {
+        RawData::peek(self, n)
+    }
+
+    #[inline(always)]
+    fn peek_tail(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error> {
+        RawData::peek_tail(self, n)
     }
 
     #[inline(always)]
@@ -105,17 +117,26 @@ impl<T: AsRef<[u8]>> FileContentReader for RawData<T> {
 }
 
 impl<T: AsRef<[u8]>> RawData<T> {
+    /// The size of the uncompressed data.
     pub fn size(&self) -> io::Result<u64> {
         match self {
             RawData::None => Ok(0),
             RawData::InMemory { data } => Ok(data.as_ref().len() as u64),
-            RawData::ExternalFile { path } => {
-                let file = File::options().read(true).open(path)?;
-                Ok(file.metadata()?.len())
+            RawData::ExternalFile { path } => match ExternalFileReader::open(path)? {
+                ExternalFileReader::Plain(file) => Ok(file.metadata()?.len()),
+                mut compressed => io::copy(&mut compressed, &mut io::sink()),
             },
         }
     }
 
+    /// The size of the data as stored, an external file may be compressed.
+    pub fn stored_size(&self) -> io::Result<u64> {
+        match self {
+            RawData::ExternalFile { path } => Ok(std::fs::metadata(path)?.len()),
+            other => other.size(),
+        }
+    }
+
     pub fn cursor(
         &self,
     ) -> io::Result<Option<DataHolderCursor>> {
@@ -126,15 +147,69 @@ impl<T: AsRef<[u8]>> RawData<T> {
                 cursor: Cursor::new(data.as_ref()),
             })),
             RawData::ExternalFile { path } => {
-                let file = File::options()
-                    .read(true)
-                    .open(path)?;
+                let file = ExternalFileReader::open(path)?.into_seekable()?;
                 let len = file.metadata()?.len();
                 Ok(Some(DataHolderCursor::FileSystem { len, cursor: file }))
             },
         }
     }
 
+    /// Reads at most [n] bytes from the start of the data.
+    pub fn peek(&self, n: usize) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            RawData::None => Ok(None),
+            RawData::InMemory { data } => {
+                let data = data.as_ref();
+                Ok(Some(data[..min(n, data.len())].to_vec()))
+            }
+            RawData::ExternalFile { path } => {
+                let file = ExternalFileReader::open(path)?;
+                let mut result = Vec::with_capacity(min(n as u64, std::fs::metadata(path)?.len()) as usize);
+                file.take(n as u64).read_to_end(&mut result)?;
+                Ok(Some(result))
+            }
+        }
+    }
+
+    /// Reads at most [n] bytes from the end of the data, an uncompressed external file
+    /// is only read after seeking to the requested suffix.
+    pub fn peek_tail(&self, n: usize) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            RawData::None => Ok(None),
+            RawData::InMemory { data } => {
+                let data = data.as_ref();
+                Ok(Some(data[data.len() - min(n, data.len())..].to_vec()))
+            }
+            RawData::ExternalFile { path } => match ExternalFileReader::open(path)? {
+                ExternalFileReader::Plain(mut file) => {
+                    let len = file.metadata()?.len();
+                    let start = len.saturating_sub(n as u64);
+                    file.seek(SeekFrom::Start(start))?;
+                    let mut result = Vec::with_capacity((len - start) as usize);
+                    file.read_to_end(&mut result)?;
+                    Ok(Some(result))
+                }
+                mut compressed => {
+                    // A compressed file can not seek, only the last bytes read are kept.
+                    let mut result = Vec::new();
+                    let mut buf = [0u8; 8 * 1024];
+                    loop {
+                        let read = compressed.read(&mut buf)?;
+                        if read == 0 {
+                            break;
+                        }
+                        result.extend_from_slice(&buf[..read]);
+                        if result.len() > 2 * n {
+                            result.drain(..result.len() - n);
+                        }
+                    }
+                    result.drain(..result.len().saturating_sub(n));
+                    Ok(Some(result))
+                }
+            },
+        }
+    }
+
     pub fn peek_bom(&self) -> io::Result<[u8; 3]> {
         let mut peek = [0u8; 3];
         match self {
@@ -146,10 +221,9 @@ impl<T: AsRef<[u8]>> RawData<T> {
                 Ok(peek)
             }
             RawData::ExternalFile { path } => {
-                let mut file = File::options()
-                    .read(true)
-                    .open(path)?;
-                file.read(&mut peek)?;
+                let mut found = Vec::with_capacity(3);
+                ExternalFileReader::open(path)?.take(3).read_to_end(&mut found)?;
+                (&mut peek[..found.len()]).copy_from_slice(&found);
                 Ok(peek)
             }
         }
@@ -12,19 +12,31 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::database::{CRAWL_DB_CF, DOMAIN_MANAGER_DB_CF, LINK_STATE_DB_CF, ROBOTS_TXT_DB_CF};
+use crate::database::{
+    CRAWL_DB_CF, DOMAIN_MANAGER_DB_CF, LINK_STATE_DB_CF, LINK_STATE_ORIGIN_INDEX_DB_CF,
+    ORIGIN_INFO_DB_CF, ORIGIN_METADATA_DB_CF, PAYLOAD_DIGEST_DB_CF, ROBOTS_TXT_DB_CF,
+    UNIQUE_CONTENT_DB_CF,
+};
 use crate::link_state::RawLinkState;
 use rocksdb::statistics::StatsLevel;
 use rocksdb::{BlockBasedOptions, DBCompressionType, Options, SliceTransform};
 
 /// Creates the open option
-pub(crate) fn create_open_options() -> (Options, [(&'static str, Options); 4]) {
+pub(crate) fn create_open_options() -> (Options, [(&'static str, Options); 9]) {
     let db_options = db_options();
     let cf_options = [
         (LINK_STATE_DB_CF, link_state_cf_options()),
+        (
+            LINK_STATE_ORIGIN_INDEX_DB_CF,
+            link_state_origin_index_cf_options(),
+        ),
         (CRAWL_DB_CF, crawled_page_cf_options()),
         (ROBOTS_TXT_DB_CF, robots_txt_cf_options()),
         (DOMAIN_MANAGER_DB_CF, domain_manager_cf_options()),
+        (UNIQUE_CONTENT_DB_CF, unique_content_cf_options()),
+        (PAYLOAD_DIGEST_DB_CF, payload_digest_cf_options()),
+        (ORIGIN_INFO_DB_CF, origin_info_cf_options()),
+        (ORIGIN_METADATA_DB_CF, origin_metadata_cf_options()),
     ];
     (db_options, cf_options)
 }
@@ -53,6 +65,13 @@ pub fn link_state_cf_options() -> Options {
     options
 }
 
+pub fn link_state_origin_index_cf_options() -> Options {
+    let mut options: Options = Default::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    options
+}
+
 pub fn robots_txt_cf_options() -> Options {
     let mut options: Options = Default::default();
     options.create_if_missing(true);
@@ -69,6 +88,46 @@ pub fn domain_manager_cf_options() -> Options {
     options
 }
 
+pub fn unique_content_cf_options() -> Options {
+    let mut options: Options = Default::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+
+    // Most of the lookups are for digests that were never seen before.
+    let mut bb_options = BlockBasedOptions::default();
+    bb_options.set_bloom_filter(10.0, false);
+    options.set_block_based_table_factory(&bb_options);
+
+    options
+}
+
+pub fn origin_info_cf_options() -> Options {
+    let mut options: Options = Default::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    options
+}
+
+pub fn origin_metadata_cf_options() -> Options {
+    let mut options: Options = Default::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+    options
+}
+
+pub fn payload_digest_cf_options() -> Options {
+    let mut options: Options = Default::default();
+    options.create_if_missing(true);
+    options.create_missing_column_families(true);
+
+    // Most of the bodies are unique.
+    let mut bb_options = BlockBasedOptions::default();
+    bb_options.set_bloom_filter(10.0, false);
+    options.set_block_based_table_factory(&bb_options);
+
+    options
+}
+
 pub fn crawled_page_cf_options() -> Options {
     let mut options: Options = Default::default();
     options.create_if_missing(true);
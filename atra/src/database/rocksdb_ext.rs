@@ -21,9 +21,14 @@ use std::path::Path;
 use thiserror::Error;
 
 pub const LINK_STATE_DB_CF: &'static str = "ls";
+pub const LINK_STATE_ORIGIN_INDEX_DB_CF: &'static str = "lo";
 pub const CRAWL_DB_CF: &'static str = "cr";
 pub const ROBOTS_TXT_DB_CF: &'static str = "rt";
 pub const DOMAIN_MANAGER_DB_CF: &'static str = "dm";
+pub const UNIQUE_CONTENT_DB_CF: &'static str = "uc";
+pub const PAYLOAD_DIGEST_DB_CF: &'static str = "pd";
+pub const ORIGIN_INFO_DB_CF: &'static str = "oi";
+pub const ORIGIN_METADATA_DB_CF: &'static str = "om";
 
 /// Errors when opening a database.
 #[derive(Debug, Error)]
@@ -69,6 +74,17 @@ pub fn open_db<P: AsRef<Path>>(path: P) -> Result<DB, OpenDBError> {
     open_db_internal(&db, path, cfs)
 }
 
+/// Opens the existing database at [path] read-only, nothing is created if it is missing.
+/// Only the cfs the database already has are opened.
+pub fn open_db_read_only<P: AsRef<Path>>(path: P) -> Result<DB, OpenDBError> {
+    let (db, cfs) = create_open_options();
+    let existing = DB::list_cf(&db, path.as_ref())?;
+    let cfs = cfs
+        .into_iter()
+        .filter(|(name, _)| existing.iter().any(|value| value.as_str() == *name));
+    Ok(DB::open_cf_with_opts_for_read_only(&db, path, cfs, false)?)
+}
+
 /// A save method to open a [DB] without knowing all the cfs
 fn open_db_internal<P, I, N>(opts: &Options, path: P, cf_options: I) -> Result<DB, OpenDBError>
 where
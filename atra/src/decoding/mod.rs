@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
-use crate::data::{Decoded, RawData, RawVecData};
+use crate::data::{Decoded, ExternalFileReader, RawData, RawVecData};
 use crate::fetching::ResponseData;
 use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::format::AtraFileInformation;
@@ -104,6 +104,13 @@ where
         _ => {}
     }
 
+    // A BOM takes precedence over every declared encoding, as required by the WHATWG encoding
+    // spec. Otherwise a wrong declaration may decode "without errors" to mojibake.
+    let bom_buf = content.peek_bom()?;
+    if let Some((encoding, _)) = Encoding::for_bom(&bom_buf) {
+        return do_decode(content, name, encoding);
+    }
+
     let mut decodings = get_decoders_by_mime(identified_type).unwrap_or_default();
 
     // use probably defective encodings from header and body somewhere?
@@ -165,7 +172,7 @@ where
 
     yield_now().await;
 
-    decode_by_bom(content, name, url)
+    decode_by_detection(content, name, url)
 }
 
 fn get_decoders_by_mime<'a>(
@@ -188,75 +195,69 @@ fn get_decoders_by_mime<'a>(
     }
 }
 
-/// Decodes by BOM only.
-fn decode_by_bom<'a>(
+/// Decodes by the encoding guessed from the content, the BOM was already checked by [decode].
+fn decode_by_detection<'a>(
     content: &'a RawVecData,
     name: &str,
     url: Option<&UrlWithDepth>,
 ) -> Result<Decoded<Cow<'a, str>, Utf8PathBuf>, DecodingError> {
-    let bom_buf = content.peek_bom()?;
-
-    if let Some((encoder, _)) = Encoding::for_bom(&bom_buf) {
-        do_decode(content, name, encoder)
-    } else {
-        let mut enc = EncodingDetector::new();
-
-        let result = match content {
-            RawVecData::InMemory { data } => enc.feed(data.as_ref(), true),
-            RawVecData::None => unreachable!(),
-            other => {
-                let mut reader = BufReader::new(other.cursor()?.unwrap());
-                let mut has_non_ascii = false;
-                loop {
-                    let buf = reader.fill_buf()?;
-                    if buf.is_empty() {
-                        break;
-                    }
-                    if enc.feed(buf, false) {
-                        has_non_ascii = true
-                    }
-                    let needed = buf.len();
-                    reader.consume(needed);
+    let mut enc = EncodingDetector::new();
+
+    let result = match content {
+        RawVecData::InMemory { data } => enc.feed(data.as_ref(), true),
+        RawVecData::None => unreachable!(),
+        RawVecData::ExternalFile { path } => {
+            let mut reader = BufReader::new(ExternalFileReader::open(path)?);
+            let mut has_non_ascii = false;
+            loop {
+                let buf = reader.fill_buf()?;
+                if buf.is_empty() {
+                    break;
+                }
+                if enc.feed(buf, false) {
+                    has_non_ascii = true
                 }
-                has_non_ascii
+                let needed = buf.len();
+                reader.consume(needed);
             }
-        };
+            has_non_ascii
+        }
+    };
 
-        if result {
-            let domain = match url {
-                None => {
-                    None
-                }
-                Some(url) => {
-                    url.domain()
-                }
-            };
-            let (selected_encoding, is_probably_right) = if let Some(domain) = domain.as_ref()
-                .map(|value| psl::domain(value.as_bytes()))
-                .flatten()
-            {
-                enc.guess_assess(Some(domain.suffix().as_bytes()), false)
-            } else {
-                enc.guess_assess(None, false)
-            };
-            if is_probably_right {
-                let result = do_decode(content, name, selected_encoding)?;
-                if result.had_errors() {
-                    let try_utf8 = do_decode(content, name, UTF_8)?;
-                    if try_utf8.had_errors() {
-                        Ok(result)
-                    } else {
-                        Ok(try_utf8)
-                    }
-                } else {
+    if result {
+        let domain = match url {
+            None => {
+                None
+            }
+            Some(url) => {
+                url.domain()
+            }
+        };
+        let (selected_encoding, is_probably_right) = if let Some(domain) = domain.as_ref()
+            .map(|value| psl::domain(value.as_bytes()))
+            .flatten()
+        {
+            enc.guess_assess(Some(domain.suffix().as_bytes()), false)
+        } else {
+            enc.guess_assess(None, false)
+        };
+        if is_probably_right {
+            let result = do_decode(content, name, selected_encoding)?;
+            if result.had_errors() {
+                let try_utf8 = do_decode(content, name, UTF_8)?;
+                if try_utf8.had_errors() {
                     Ok(result)
+                } else {
+                    Ok(try_utf8)
                 }
             } else {
-                do_decode(content, name, UTF_8)
+                Ok(result)
             }
         } else {
             do_decode(content, name, UTF_8)
         }
+    } else {
+        do_decode(content, name, UTF_8)
     }
 }
 
@@ -292,7 +293,7 @@ fn do_decode<'a>(
                 out_path.set_file_name(name);
             }
             let mut output = File::options().write(true).open(&out_path)?;
-            let mut reader = BufReader::new(File::options().read(true).open(&path)?);
+            let mut reader = BufReader::new(ExternalFileReader::open(path)?);
 
             // Bare metal platforms usually have very small amounts of RAM
             // (in the order of hundreds of KB)
@@ -603,4 +604,34 @@ mod test {
     multi_test_for!(KOI8_R);
     multi_test_for!(KOI8_U);
     multi_test_for!(X_MAC_CYRILLIC);
+
+    #[tokio::test]
+    async fn the_bom_takes_precedence_over_a_wrong_declaration() {
+        const DATA: &'static str =
+            "<html><body><p>Grüße aus der Behörde, € 5</p></body></html>";
+        let mut content = vec![0xEF, 0xBB, 0xBF];
+        content.extend_from_slice(DATA.as_bytes());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=iso-8859-1"),
+        );
+        let data = FetchedRequestData::new(
+            RawData::from_vec(content),
+            Some(headers),
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        let mut website = ResponseData::from_response(
+            data,
+            UrlWithDepth::from_url("https://www.example.com").unwrap(),
+        );
+        let context = TestContext::default();
+        let format = determine_format_for_response(&context, &mut website);
+        let decoded = decode_page(&context, &website, &format).await.unwrap();
+        assert_eq!(encoding_rs::UTF_8, decoded.encoding().unwrap());
+        assert_eq!(DATA, decoded.as_in_memory().unwrap().as_ref());
+    }
 }
@@ -0,0 +1,246 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use compact_str::CompactString;
+use std::collections::HashSet;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+/// Tries to extract all links from a stylesheet or the value of a style attribute.
+/// Collects the targets of `url(...)` and of `@import "..."`. Strings, comments and
+/// references to fragments like `url(#filter)` are ignored.
+pub fn extract_links(css: &str) -> HashSet<CompactString> {
+    let mut links = HashSet::new();
+    let mut chars = css.char_indices().peekable();
+    let mut import_found = false;
+
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '/' if chars.next_if(|(_, next)| *next == '*').is_some() => {
+                skip_comment(&mut chars);
+            }
+            '"' | '\'' => {
+                let value = read_string(&mut chars, c);
+                if import_found {
+                    push_link(&mut links, value);
+                }
+                import_found = false;
+            }
+            '\\' => {
+                chars.next();
+                import_found = false;
+            }
+            '@' => {
+                let name = read_identifier(&mut chars);
+                import_found = name.eq_ignore_ascii_case("import");
+            }
+            c if is_identifier_char(c) => {
+                let name = read_identifier_from(&mut chars, c);
+                if name.eq_ignore_ascii_case("url")
+                    && chars.next_if(|(_, next)| *next == '(').is_some()
+                {
+                    if let Some(value) = read_url(&mut chars) {
+                        push_link(&mut links, value);
+                    }
+                }
+                import_found = false;
+            }
+            c if c.is_whitespace() => {}
+            _ => import_found = false,
+        }
+    }
+
+    links
+}
+
+fn push_link(links: &mut HashSet<CompactString>, value: String) {
+    let value = value.trim();
+    if value.is_empty() || value.starts_with('#') {
+        return;
+    }
+    links.insert(CompactString::new(value));
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '-' || c == '_' || !c.is_ascii()
+}
+
+fn read_identifier(chars: &mut Peekable<CharIndices>) -> String {
+    let mut identifier = String::new();
+    while let Some((_, c)) = chars.next_if(|(_, c)| is_identifier_char(*c)) {
+        identifier.push(c);
+    }
+    identifier
+}
+
+fn read_identifier_from(chars: &mut Peekable<CharIndices>, first: char) -> String {
+    let mut identifier = String::from(first);
+    identifier.push_str(&read_identifier(chars));
+    identifier
+}
+
+fn skip_comment(chars: &mut Peekable<CharIndices>) {
+    let mut star = false;
+    for (_, c) in chars {
+        if star && c == '/' {
+            return;
+        }
+        star = c == '*';
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<CharIndices>) {
+    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+}
+
+/// Reads an escape sequence after the backslash, see https://www.w3.org/TR/css-syntax-3/#consume-escaped-code-point
+fn read_escape(chars: &mut Peekable<CharIndices>) -> Option<char> {
+    let (_, first) = chars.next()?;
+    if !first.is_ascii_hexdigit() {
+        return Some(first);
+    }
+    let mut hex = String::from(first);
+    while hex.len() < 6 {
+        match chars.next_if(|(_, c)| c.is_ascii_hexdigit()) {
+            Some((_, c)) => hex.push(c),
+            None => break,
+        }
+    }
+    chars.next_if(|(_, c)| c.is_whitespace());
+    let value = u32::from_str_radix(&hex, 16).ok()?;
+    Some(
+        char::from_u32(value)
+            .filter(|c| *c != '\0')
+            .unwrap_or('\u{FFFD}'),
+    )
+}
+
+/// Reads a string until the [quote], the opening quote is already consumed.
+fn read_string(chars: &mut Peekable<CharIndices>, quote: char) -> String {
+    let mut value = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            c if c == quote => break,
+            '\n' => break,
+            '\\' => {
+                if chars.next_if(|(_, c)| *c == '\n').is_some() {
+                    continue;
+                }
+                if let Some(c) = read_escape(chars) {
+                    value.push(c)
+                }
+            }
+            c => value.push(c),
+        }
+    }
+    value
+}
+
+/// Reads the content of `url(...)`, the opening bracket is already consumed.
+fn read_url(chars: &mut Peekable<CharIndices>) -> Option<String> {
+    skip_whitespace(chars);
+    let (_, first) = *chars.peek()?;
+    if first == '"' || first == '\'' {
+        chars.next();
+        let value = read_string(chars, first);
+        skip_whitespace(chars);
+        // Modifiers like in url("a.png" crossorigin(anonymous)) are not part of the link.
+        let mut depth = 0usize;
+        for (_, c) in chars.by_ref() {
+            match c {
+                '(' => depth += 1,
+                ')' if depth == 0 => break,
+                ')' => depth -= 1,
+                _ => {}
+            }
+        }
+        return Some(value);
+    }
+    let mut value = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            ')' => return Some(value),
+            '\\' => {
+                if let Some(c) = read_escape(chars) {
+                    value.push(c)
+                }
+            }
+            c if c.is_whitespace() => {
+                skip_whitespace(chars);
+                return match chars.next() {
+                    Some((_, ')')) | None => Some(value),
+                    Some(_) => {
+                        // A bad url, skip the rest like a browser would.
+                        for (_, c) in chars.by_ref() {
+                            if c == ')' {
+                                break;
+                            }
+                        }
+                        None
+                    }
+                };
+            }
+            '"' | '\'' | '(' => {
+                for (_, c) in chars.by_ref() {
+                    if c == ')' {
+                        break;
+                    }
+                }
+                return None;
+            }
+            c => value.push(c),
+        }
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::extraction::css::extract_links;
+    use compact_str::CompactString;
+    use std::collections::HashSet;
+
+    fn links(values: &[&str]) -> HashSet<CompactString> {
+        values.iter().map(CompactString::new).collect()
+    }
+
+    #[test]
+    fn extracts_imports_fonts_and_images() {
+        let found = extract_links(include_str!("../../testdata/samples/stylesheet.css"));
+        assert_eq!(
+            links(&[
+                "base.css",
+                "https://fonts.example.com/css?family=Open+Sans",
+                "/print.css",
+                "fonts/open-sans.woff2",
+                "fonts/open-sans.woff",
+                "../img/background image.png",
+                "img/icon.svg",
+                "//cdn.example.com/sprite.png",
+                "img/escaped).png",
+                "data:image/png;base64,iVBORw0KGgo=",
+            ]),
+            found
+        );
+    }
+
+    #[test]
+    fn extracts_from_style_attributes() {
+        assert_eq!(
+            links(&["a.png", "b.png"]),
+            extract_links("background: URL( 'a.png' ) no-repeat; border-image: url(b.png) 30")
+        );
+        assert!(extract_links("color: red; content: 'url(c.png)'").is_empty());
+    }
+}
@@ -12,33 +12,191 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::fs::File;
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess, SupportsGdbrRegistry};
 use crate::data::{Decoded, RawVecData};
-use crate::decoding::{decode};
+use crate::decoding::decode;
 use crate::extraction::extractor::{ExtractorData, ExtractorResult};
-use crate::extraction::{LinkExtractionError};
+use crate::extraction::LinkExtractionError;
 use crate::format::{determine_format, FileFormatData, ZipFileContent};
 use crate::io::serial::{SerialProvider, SerialProviderKind};
 use crate::io::templating::{file_name_template, FileNameTemplate};
 use crate::io::unique_path_provider::{UniquePathProvider, UniquePathProviderWithTemplate};
-use crate::toolkit::{detect_language};
+use crate::toolkit::detect_language;
 use crate::url::UrlWithDepth;
 use camino_tempfile::Utf8TempDir;
-use std::io::{BufWriter, Read, Seek};
-use std::sync::{LazyLock};
+use flate2::read::MultiGzDecoder;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Seek, Write};
+use std::sync::LazyLock;
 use tokio::task::yield_now;
 
-/// Extract data fom
+/// The magic bytes at the start of gzip data.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+static TEMPLATE: LazyLock<FileNameTemplate> =
+    LazyLock::new(|| file_name_template!("unpacked" _ serial ".tmp").unwrap());
+
+/// Unpacks the files to a temp dir if they are too big for the memory.
+fn temp_name_provider(temp_dir: &Utf8TempDir) -> UniquePathProviderWithTemplate {
+    UniquePathProviderWithTemplate::new(
+        UniquePathProvider::new(
+            temp_dir.path(),
+            SerialProvider::new(SerialProviderKind::Long),
+        ),
+        TEMPLATE.clone(),
+    )
+}
+
+/// The maximal decompressed size of a single file, the `max_file_size` of the crawl
+/// bounded by what fits into the memory and the temp files.
+fn decompression_limit<C: SupportsConfigs>(context: &C) -> u64 {
+    let configs = context.configs();
+    let unpackable = configs
+        .system
+        .max_file_size_in_memory
+        .max(configs.system.max_temp_file_size_on_disc);
+    configs
+        .crawl
+        .max_file_size
+        .map_or(unpackable, |limit| limit.get().min(unpackable))
+}
+
+/// Returns true if [reader] starts with gzip data, the reader is rewound afterwards.
+pub fn is_gzip<R: Read + Seek>(reader: &mut R) -> io::Result<bool> {
+    let mut magic = [0u8; 2];
+    let found = reader.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC;
+    reader.rewind()?;
+    Ok(found)
+}
+
+/// Streams the decompressed [reader] to the memory or, if it is bigger than [max_in_memory],
+/// to a temp file of [name_provider]. Aborts as soon as more than [limit] bytes are read,
+/// hence a zip bomb never unpacks more than [limit] bytes.
+fn unpack_bounded<R: Read>(
+    reader: R,
+    limit: u64,
+    max_in_memory: u64,
+    name_provider: &UniquePathProviderWithTemplate,
+) -> Result<RawVecData, LinkExtractionError> {
+    let mut reader = reader.take(limit.saturating_add(1));
+    let mut data = Vec::new();
+    reader
+        .by_ref()
+        .take(max_in_memory.saturating_add(1))
+        .read_to_end(&mut data)?;
+    let mut len = data.len() as u64;
+    if len > limit {
+        return Err(LinkExtractionError::DecompressionLimitExceeded { limit });
+    }
+    if len <= max_in_memory {
+        return Ok(RawVecData::from_in_memory(data));
+    }
+    let path = name_provider
+        .provide_path_no_args()
+        .map_err(io::Error::other)?;
+    let mut output = BufWriter::new(File::options().write(true).create_new(true).open(&path)?);
+    output.write_all(&data)?;
+    drop(data);
+    len += io::copy(&mut reader, &mut output)?;
+    output.flush()?;
+    if len > limit {
+        return Err(LinkExtractionError::DecompressionLimitExceeded { limit });
+    }
+    Ok(RawVecData::from_external(path))
+}
+
+/// Determines the format of the unpacked [data] and extracts its links with the
+/// extractors of the crawl, None if no extractor supports the format.
+async fn extract_from_unpacked<C>(
+    root_url: &UrlWithDepth,
+    file_name: &str,
+    mut data: RawVecData,
+    nesting: usize,
+    context: &C,
+) -> Option<ExtractorResult>
+where
+    C: SupportsGdbrRegistry + SupportsConfigs + SupportsFileSystemAccess,
+{
+    let file_info = determine_format(
+        context,
+        FileFormatData::new(None, &mut data, None, Some(file_name)),
+    );
+
+    if !context
+        .configs()
+        .crawl
+        .link_extractors
+        .can_extract_anything(&file_info)
+    {
+        log::debug!("Can not extract from {file_name}");
+        return None;
+    }
+
+    let result = match decode(context, &data, file_name, None, &file_info)
+        .await
+        .map(|value| value.map_in_memory(|value| value.into_owned()))
+    {
+        Ok(decoded) => {
+            let lang = detect_language(context, &file_info, &decoded)
+                .ok()
+                .flatten();
+
+            context
+                .configs()
+                .crawl
+                .link_extractors
+                .extract(
+                    context,
+                    nesting + 1,
+                    ExtractorData::new(
+                        root_url,
+                        Some(file_name),
+                        &data,
+                        &file_info,
+                        &decoded,
+                        lang.as_ref(),
+                    ),
+                )
+                .await
+        }
+        Err(_) => {
+            // If we have an encoding error at this stage we simply do not care.
+            context
+                .configs()
+                .crawl
+                .link_extractors
+                .extract(
+                    context,
+                    nesting + 1,
+                    ExtractorData::new(
+                        root_url,
+                        Some(file_name),
+                        &data,
+                        &file_info,
+                        &Decoded::None,
+                        None,
+                    ),
+                )
+                .await
+        }
+    };
+    Some(result)
+}
+
+/// Extract data fom the members of a zip archive.
 pub async fn extract_from_zip<C, R>(
     root_url: &UrlWithDepth,
     reader: R,
     nesting: usize,
     context: &C,
-) -> Result<(
-    Vec<(String, ExtractorResult)>,
-    Vec<(String, LinkExtractionError)>,
-), LinkExtractionError>
+) -> Result<
+    (
+        Vec<(String, ExtractorResult)>,
+        Vec<(String, LinkExtractionError)>,
+    ),
+    LinkExtractionError,
+>
 where
     C: SupportsGdbrRegistry + SupportsConfigs + SupportsFileSystemAccess,
     R: Read + Seek,
@@ -46,121 +204,23 @@ where
     let mut archive = zip::read::ZipArchive::new(reader)?;
     let mut extracted = Vec::new();
     let mut errors = Vec::with_capacity(0);
-    // let extracted_result = HashSet::new();
     let len = archive.len();
     let temp_dir = Utf8TempDir::new()?;
-
-    static TEMPLATE: LazyLock<FileNameTemplate> = LazyLock::new(
-        || file_name_template!("unpacked" _ serial ".tmp").unwrap()
-    );
-
-    let name_provider = UniquePathProviderWithTemplate::new(
-        UniquePathProvider::new(
-            temp_dir.path(),
-            SerialProvider::new(SerialProviderKind::Long),
-        ),
-        TEMPLATE.clone(),
-    );
+    let name_provider = temp_name_provider(&temp_dir);
+    let limit = decompression_limit(context);
+    let max_in_memory = context.configs().system.max_file_size_in_memory;
 
     for idx in 0..len {
         yield_now().await;
-        let mut content = ZipFileContent::new(
-            &mut archive,
-            idx,
-            context.configs().system.max_file_size_in_memory as usize,
-            None,
-        );
-
-        let (file_name, data, file_info) = match content.file_name_and_len() {
-            Ok(Some((file_name, len))) => {
-                if len == 0 {
-                    log::debug!("Only found empty file!");
-                    continue;
-                }
-                let cfg_sys = &context.configs().system;
-                let mut data =
-                    if cfg_sys.max_file_size_in_memory < len && len <= cfg_sys.max_temp_file_size_on_disc {
-                        let temp_file_name = match name_provider.provide_path_no_args() {
-                            Ok(value) => {
-                                value
-                            }
-                            Err(err) => {
-                                log::error!("Failed to use template for temp files: {err}");
-                                continue;
-                            }
-                        };
-                        match content.zip_reader().by_index(idx) {
-                            Ok(mut inp) => {
-                                match File::options().write(true).create_new(true).open(&temp_file_name) {
-                                    Ok(outp) => {
-                                        let mut outp = BufWriter::new(outp);
-                                        match std::io::copy(
-                                            &mut inp,
-                                            &mut outp
-                                        ) {
-                                            Ok(_) => {}
-                                            Err(err) => {
-                                                errors.push((file_name, err.into()));
-                                                continue
-                                            }
-                                        }
-                                    }
-                                    Err(err) => {
-                                        errors.push((file_name, err.into()));
-                                        continue
-                                    }
-                                }
-
-                            }
-                            Err(err) => {
-                                errors.push((file_name, err.into()));
-                                continue
-                            }
-                        }
-                        RawVecData::from_external(temp_file_name)
-                    } else {
-                        match content.zip_reader().by_index(idx) {
-                            Ok(mut value) => {
-                                if len <= cfg_sys.max_file_size_in_memory {
-                                    let mut data = Vec::with_capacity(len as usize);
-                                    match value.read_to_end(&mut data) {
-                                        Ok(_) => {}
-                                        Err(err) => {
-                                            errors.push((file_name, err.into()));
-                                            continue;
-                                        }
-                                    }
-                                    RawVecData::from_in_memory(data)
-                                } else {
-                                    log::warn!("A file was too big for extracting data.");
-                                    continue;
-                                }
-                            }
-                            Err(err) => {
-                                errors.push((file_name, err.into()));
-                                continue;
-                            }
-                        }
-                    };
-
-
-                let determined = determine_format(
-                    context,
-                    FileFormatData::new(None, &mut data, None, Some(&file_name)),
-                );
-
-                if !context
-                    .configs()
-                    .crawl
-                    .link_extractors
-                    .can_extract_anything(&determined)
-                {
-                    log::debug!("Can not extract from {file_name}");
-                    continue;
-                }
+        let mut content = ZipFileContent::new(&mut archive, idx, max_in_memory as usize, None);
 
-                (file_name, data, determined)
+        let file_name = match content.file_name_and_len() {
+            Ok(Some((_, 0))) => {
+                log::debug!("Only found empty file!");
+                continue;
             }
+            // The declared size can lie, the limit is checked while unpacking.
+            Ok(Some((file_name, _))) => file_name,
             Ok(None) => {
                 /*Do nothing, it is a dir or whatever*/
                 continue;
@@ -171,78 +231,219 @@ where
             }
         };
 
-        let result = match decode(context, &data, &file_name, None, &file_info)
-            .await
-            .map(|value| value.map_in_memory(|value| value.into_owned()))
-        {
-            Ok(decoded) => {
-                let lang = detect_language(context, &file_info, &decoded)
-                    .ok()
-                    .flatten();
-
-                context
-                    .configs()
-                    .crawl
-                    .link_extractors
-                    .extract(
-                        context,
-                        nesting + 1,
-                        ExtractorData::new(
-                            root_url,
-                            Some(&file_name),
-                            &data,
-                            &file_info,
-                            &decoded,
-                            lang.as_ref(),
-                        ),
-                    ).await
-            }
-            Err(_) => {
-                // If we have an encoding error at this stage we simply do not care.
-                context
-                    .configs()
-                    .crawl
-                    .link_extractors
-                    .extract(
-                        context,
-                        nesting + 1,
-                        ExtractorData::new(
-                            root_url,
-                            Some(&file_name),
-                            &data,
-                            &file_info,
-                            &Decoded::None,
-                            None,
-                        ),
-                    ).await
+        let data = match content.zip_reader().by_index(idx) {
+            Ok(member) => unpack_bounded(member, limit, max_in_memory, &name_provider),
+            Err(err) => Err(err.into()),
+        };
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                log::debug!("Failed to unpack {file_name} of {}: {err}", root_url);
+                errors.push((file_name, err));
+                continue;
             }
         };
 
-        println!("{file_name} {result:?}");
-
-        extracted.push((file_name, result))
+        if let Some(result) =
+            extract_from_unpacked(root_url, &file_name, data, nesting, context).await
+        {
+            extracted.push((file_name, result))
+        }
     }
 
     Ok((extracted, errors))
 }
 
+/// Extract data from the decompressed content of gzip data named [file_name].
+/// Returns the name of the decompressed file, [file_name] without the `.gz`, and its links.
+pub async fn extract_from_gzip<C, R>(
+    root_url: &UrlWithDepth,
+    reader: R,
+    file_name: &str,
+    nesting: usize,
+    context: &C,
+) -> Result<Option<(String, ExtractorResult)>, LinkExtractionError>
+where
+    C: SupportsGdbrRegistry + SupportsConfigs + SupportsFileSystemAccess,
+    R: Read,
+{
+    let temp_dir = Utf8TempDir::new()?;
+    let data = unpack_bounded(
+        MultiGzDecoder::new(reader),
+        decompression_limit(context),
+        context.configs().system.max_file_size_in_memory,
+        &temp_name_provider(&temp_dir),
+    )?;
+    let file_name = file_name
+        .strip_suffix(".gz")
+        .or_else(|| file_name.strip_suffix(".gzip"))
+        .unwrap_or(file_name)
+        .to_string();
+    Ok(
+        extract_from_unpacked(root_url, &file_name, data, nesting, context)
+            .await
+            .map(|result| (file_name, result)),
+    )
+}
 
 #[cfg(test)]
 mod test {
-    use log4rs::append::console::ConsoleAppender;
-    use log4rs::config::{Appender, Logger, Root};
-    use log4rs::encode::pattern::PatternEncoder;
-    use log::LevelFilter;
+    use super::{temp_name_provider, unpack_bounded};
     use crate::config::Config;
-    use crate::data::RawVecData;
+    use crate::contexts::traits::SupportsConfigs;
+    use crate::data::{Decoded, RawVecData};
     use crate::extraction::deflate::extract_from_zip;
-    use crate::format::{determine_format, FileFormatData};
+    use crate::extraction::extractor::ExtractorData;
+    use crate::extraction::LinkExtractionError;
     use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::{determine_format, FileFormatData};
     use crate::test_impls::{DefaultAtraProvider, TestContext};
     use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use log::LevelFilter;
+    use log4rs::append::console::ConsoleAppender;
+    use log4rs::config::{Appender, Logger, Root};
+    use log4rs::encode::pattern::PatternEncoder;
+    use std::collections::HashSet;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::ZipWriter;
+
+    const SITEMAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://www.example.com/first.html</loc></url>
+  <url><loc>https://www.example.com/second.html</loc></url>
+</urlset>"#;
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Extracts the links of the compressed [data] found at [url] like the crawler does.
+    async fn extract_links(config: Config, url: &str, data: Vec<u8>) -> HashSet<String> {
+        let context = TestContext::new(config, DefaultAtraProvider::default());
+        let url = UrlWithDepth::from_url(url).unwrap();
+        let mut data = RawVecData::from_in_memory(data);
+        let format = determine_format(
+            &context,
+            FileFormatData::new(None, &mut data, Some(&url), None),
+        );
+        assert_eq!(InterpretedProcessibleFileFormat::ZIP, format.format);
+        let result = context
+            .configs()
+            .crawl
+            .link_extractors
+            .extract(
+                &context,
+                0,
+                ExtractorData::new(&url, None, &data, &format, &Decoded::None, None),
+            )
+            .await;
+        result
+            .links
+            .iter()
+            .filter_map(|link| link.url())
+            .map(|link| link.as_str().into_owned())
+            .collect()
+    }
 
     #[tokio::test]
-    async fn can_extract_from_jar_file(){
+    async fn extracts_the_links_of_a_gzipped_sitemap() {
+        let links = extract_links(
+            Config::default(),
+            "https://www.example.com/sitemap.xml.gz",
+            gzip(SITEMAP.as_bytes()),
+        )
+        .await;
+        assert!(
+            links.contains("https://www.example.com/first.html"),
+            "{links:?}"
+        );
+        assert!(
+            links.contains("https://www.example.com/second.html"),
+            "{links:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn extracts_the_links_of_every_member_of_a_zip() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, target) in [
+            ("first.html", "first-target"),
+            ("second.html", "second-target"),
+        ] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            write!(
+                writer,
+                r#"<html><body><a href="https://www.example.com/{target}.html">Next</a></body></html>"#
+            )
+            .unwrap();
+        }
+        let archive = writer.finish().unwrap().into_inner();
+
+        let links = extract_links(
+            Config::default(),
+            "https://www.example.com/bundle.zip",
+            archive,
+        )
+        .await;
+        assert!(
+            links.contains("https://www.example.com/first-target.html"),
+            "{links:?}"
+        );
+        assert!(
+            links.contains("https://www.example.com/second-target.html"),
+            "{links:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn the_decompressed_size_is_limited() {
+        let mut config = Config::default();
+        config.crawl.max_file_size = std::num::NonZeroU64::new(64);
+        let links = extract_links(
+            config,
+            "https://www.example.com/sitemap.xml.gz",
+            gzip(SITEMAP.as_bytes()),
+        )
+        .await;
+        assert!(links.is_empty(), "{links:?}");
+    }
+
+    #[test]
+    fn unpacking_aborts_at_the_limit() {
+        let temp_dir = Utf8TempDir::new().unwrap();
+        let name_provider = temp_name_provider(&temp_dir);
+        let data = vec![0u8; 4096];
+
+        match unpack_bounded(data.as_slice(), 4096, 1024, &name_provider).unwrap() {
+            RawVecData::ExternalFile { path } => {
+                assert_eq!(data, std::fs::read(path).unwrap())
+            }
+            other => panic!("Expected a temp file, got {other:?}"),
+        }
+        assert!(matches!(
+            unpack_bounded(data.as_slice(), 4095, 1024, &name_provider),
+            Err(LinkExtractionError::DecompressionLimitExceeded { limit: 4095 })
+        ));
+        assert!(matches!(
+            unpack_bounded(data.as_slice(), 100, 1024, &name_provider),
+            Err(LinkExtractionError::DecompressionLimitExceeded { limit: 100 })
+        ));
+        assert!(matches!(
+            unpack_bounded(data.as_slice(), 4096, 8192, &name_provider),
+            Ok(RawVecData::InMemory { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn can_extract_from_jar_file() {
         let console_logger = ConsoleAppender::builder()
             .encoder(Box::new(PatternEncoder::new("{l}{I} - {d} - {m}{n}")))
             .build();
@@ -258,35 +459,36 @@ mod test {
         const JAR_FILE_1: &[u8] = include_bytes!("../../testdata/samples/expressionless-0.1.0.jar");
         const JAR_FILE_2: &[u8] = include_bytes!("../../testdata/samples/Test2.zip");
 
-        let cont = TestContext::new(
-            Config::default(),
-            DefaultAtraProvider::default()
-        );
+        let cont = TestContext::new(Config::default(), DefaultAtraProvider::default());
 
         for file in [JAR_FILE_1, JAR_FILE_2] {
-            let mut dat = RawVecData::from_in_memory(
-                Vec::from(file)
-            );
+            let mut dat = RawVecData::from_in_memory(Vec::from(file));
 
             let format = determine_format(
                 &cont,
                 FileFormatData::new(
                     None,
                     &mut dat,
-                    Some(&UrlWithDepth::from_url("https://www.google.de/expressionless.jar").unwrap()),
-                    None
-                )
+                    Some(
+                        &UrlWithDepth::from_url("https://www.google.de/expressionless.jar")
+                            .unwrap(),
+                    ),
+                    None,
+                ),
             );
 
             assert_eq!(format.format, InterpretedProcessibleFileFormat::ZIP);
 
-
             let (result, err) = extract_from_zip(
                 &"https://www.google.de/expressionless.jar".parse().unwrap(),
-                dat.cursor().expect("There was an error when getting data").expect("There is nothing to read."),
+                dat.cursor()
+                    .expect("There was an error when getting data")
+                    .expect("There is nothing to read."),
                 0,
-                &cont
-            ).await.unwrap();
+                &cont,
+            )
+            .await
+            .unwrap();
 
             println!("----------------");
 
@@ -301,4 +503,4 @@ mod test {
             }
         }
     }
-}
\ No newline at end of file
+}
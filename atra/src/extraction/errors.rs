@@ -30,6 +30,10 @@ pub enum LinkExtractionError {
     },
     #[error(transparent)]
     ZipError(#[from] ZipError),
+    #[error("The decompressed data exceeds the limit of {limit} bytes!")]
+    DecompressionLimitExceeded { limit: u64 },
+    #[error("The document has {size} bytes and exceeds the limit of {limit} bytes!")]
+    DocumentTooLarge { size: u64, limit: u64 },
 }
 
 #[derive(Debug, Error)]
@@ -51,4 +55,8 @@ pub enum LinkExtractionSubError {
     Svg(#[from] link_scraper::formats::xml::svg::SvgScrapingError),
     #[error(transparent)]
     Xlink(#[from] link_scraper::formats::xml::xlink::XLinkFormatError),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
 }
@@ -0,0 +1,81 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::extraction::extractor::ExtractorFailure;
+use crate::toolkit::unwind::{panic_message, CatchUnwind};
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Runs the [future] until it finishes or the [limit] is reached.
+/// A panic while polling is caught and returned as [ExtractorFailure::Panicked]. A future
+/// that blocks past the [limit] can not be interrupted, but it is still reported as
+/// [ExtractorFailure::TimedOut].
+pub(super) async fn run_isolated<F>(
+    limit: Option<Duration>,
+    future: F,
+) -> Result<F::Output, ExtractorFailure>
+where
+    F: Future,
+{
+    let started = Instant::now();
+    let future = CatchUnwind::new(future);
+    let outcome = match limit {
+        Some(limit) => match tokio::time::timeout(limit, future).await {
+            Ok(outcome) => outcome,
+            Err(_) => return Err(ExtractorFailure::TimedOut),
+        },
+        None => future.await,
+    };
+    match outcome {
+        Ok(_) if limit.is_some_and(|limit| started.elapsed() > limit) => {
+            Err(ExtractorFailure::TimedOut)
+        }
+        Ok(value) => Ok(value),
+        Err(payload) => Err(ExtractorFailure::Panicked(panic_message(payload.as_ref()))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::run_isolated;
+    use crate::extraction::extractor::ExtractorFailure;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn catches_panics_and_timeouts() {
+        assert_eq!(
+            Ok(5),
+            run_isolated(Some(Duration::from_secs(5)), async { 5 }).await
+        );
+        assert_eq!(
+            Err(ExtractorFailure::Panicked("broken".to_string())),
+            run_isolated(None, async { panic!("broken") }).await
+        );
+        assert_eq!(
+            Err(ExtractorFailure::TimedOut),
+            run_isolated(Some(Duration::from_millis(10)), async {
+                tokio::time::sleep(Duration::from_secs(10)).await
+            })
+            .await
+        );
+        assert_eq!(
+            Err(ExtractorFailure::TimedOut),
+            run_isolated(Some(Duration::from_millis(10)), async {
+                std::thread::sleep(Duration::from_millis(20))
+            })
+            .await
+        );
+    }
+}
@@ -15,11 +15,13 @@
 mod apply_when;
 mod command;
 mod data_holder;
+mod isolation;
 mod result;
 
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess, SupportsGdbrRegistry};
 use crate::data::Decoded;
 use crate::extraction::extractor_method::ExtractorMethod;
+use crate::extraction::LinkExtractionError;
 use crate::fetching::ResponseData;
 use crate::format::AtraFileInformation;
 use crate::toolkit::LanguageInformation;
@@ -27,9 +29,11 @@ pub use apply_when::*;
 use camino::Utf8PathBuf;
 pub use command::*;
 pub(crate) use data_holder::*;
+use isolation::run_isolated;
 pub use result::*;
 use serde::{Deserialize, Serialize};
 use strum::IntoEnumIterator;
+use tokio::time::Instant;
 
 /// Wrapps multiple extractor commands to an extractor.
 #[derive(Debug, Serialize, Deserialize, Clone, Eq)]
@@ -48,16 +52,23 @@ impl Extractor {
     }
 
     /// If the flag [`FALLBACK_MODE`] is set, it makes sure that either the used extractor is
-    /// a fallback or a non fallback
+    /// a fallback or a non fallback.
+    /// Every extractor runs isolated until the [deadline], a failure is recorded in the [result].
     async fn apply_extractors<const FALLBACK_MODE: bool, C>(
         &self,
         context: &C,
         data: ExtractorData<'_>,
         nesting: usize,
+        deadline: Option<Instant>,
         result: &mut ExtractorResult,
     ) where
         C: SupportsConfigs + SupportsGdbrRegistry + SupportsFileSystemAccess,
     {
+        let method_timeout = context
+            .configs()
+            .crawl
+            .extractor_method_timeout
+            .map(|value| value.unsigned_abs());
         for extractor in &self.0 {
             // Require that both are either true or false
             if FALLBACK_MODE ^ extractor.is_fallback() {
@@ -65,15 +76,51 @@ impl Extractor {
             }
             if FALLBACK_MODE || extractor.can_apply(data.file_info) {
                 if result.apply_extractor(extractor.extractor_method) {
-                    match extractor
-                        .extractor_method
-                        .extract_links(context, &data, nesting, result)
-                        .await
-                    {
-                        Ok(value) => {
+                    let limit = match deadline {
+                        Some(deadline) => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+                            Some(method_timeout.map_or(remaining, |cap| cap.min(remaining)))
+                        }
+                        None => method_timeout,
+                    };
+                    if limit.is_some_and(|limit| limit.is_zero()) {
+                        log::warn!(
+                            "Skipped {extractor} for {} :: {:?} {} because the deadline passed.",
+                            data.url.url,
+                            data.file_name,
+                            data.file_info
+                        );
+                        result.register_failure(
+                            extractor.extractor_method,
+                            ExtractorFailure::TimedOut,
+                        );
+                        continue;
+                    }
+                    let mut output = ExtractorResult::default();
+                    let outcome = run_isolated(
+                        limit,
+                        extractor.extractor_method.extract_links(
+                            context,
+                            &data,
+                            nesting,
+                            &mut output,
+                        ),
+                    )
+                    .await;
+                    match outcome {
+                        Ok(Ok(value)) => {
                             log::debug!("Extracted {value} links with {extractor}.");
+                            result.links.extend(output.links);
+                        }
+                        Ok(Err(LinkExtractionError::NotCompatible)) => {
+                            log::debug!(
+                                "{extractor} declined {} :: {:?} {}!",
+                                data.url.url,
+                                data.file_name,
+                                data.file_info
+                            );
                         }
-                        Err(err) => {
+                        Ok(Err(err)) => {
                             log::warn!(
                                 "Failed {extractor} for {} :: {:?} {} with: {}",
                                 data.url.url,
@@ -81,6 +128,21 @@ impl Extractor {
                                 data.file_info,
                                 err
                             );
+                            result.links.extend(output.links);
+                            result.register_failure(
+                                extractor.extractor_method,
+                                ExtractorFailure::Error(err.to_string()),
+                            );
+                        }
+                        Err(failure) => {
+                            log::warn!(
+                                "Discarded {extractor} for {} :: {:?} {} because of: {:?}",
+                                data.url.url,
+                                data.file_name,
+                                data.file_info,
+                                failure
+                            );
+                            result.register_failure(extractor.extractor_method, failure);
                         }
                     }
                 } else {
@@ -127,6 +189,11 @@ impl Extractor {
                 return ExtractorResult::default()
             }
         }
+        let deadline = context
+            .configs()
+            .crawl
+            .extraction_timeout
+            .map(|value| Instant::now() + value.unsigned_abs());
         let mut result = ExtractorResult::default();
         log::trace!(
             "Extractor: {}::{:?} - {}",
@@ -134,13 +201,20 @@ impl Extractor {
             data.file_name,
             data.file_info.format,
         );
-        self.apply_extractors::<false, _>(context, data, nesting, &mut result)
+        self.apply_extractors::<false, _>(context, data, nesting, deadline, &mut result)
             .await;
-        if result.no_extractor_applied() || result.is_empty() {
-            if !result.no_extractor_applied() {
+        // A failed extractor did not decline the data, but its links can not be trusted.
+        if result.no_extractor_succeeded() || result.is_empty() {
+            if !result.failed_extractors.is_empty() {
+                log::debug!(
+                    "Extractor: Fallback for {} because {} extractors failed.",
+                    data.url.url,
+                    result.failed_extractors.len()
+                );
+            } else if !result.no_extractor_applied() {
                 log::debug!("Extractor: Unsupported type: {:?}", data.file_info.format);
             }
-            self.apply_extractors::<true, _>(context, data, nesting, &mut result)
+            self.apply_extractors::<true, _>(context, data, nesting, deadline, &mut result)
                 .await;
         }
         result
@@ -163,12 +237,17 @@ mod test {
     use crate::data::process;
     use crate::data::RawData;
     use crate::extraction::extractor::Extractor;
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::extraction::ExtractedLink;
     use crate::fetching::FetchedRequestData;
     use crate::fetching::ResponseData;
     use crate::format::determine_format_for_response;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
     use crate::test_impls::TestContext;
     use crate::toolkit::LanguageInformation;
     use crate::url::UrlWithDepth;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use std::collections::HashSet;
 
     #[tokio::test]
     async fn can_extract_data() {
@@ -212,4 +291,230 @@ mod test {
             println!("{}", link);
         }
     }
+
+    #[tokio::test]
+    async fn can_extract_from_css() {
+        let mut page = ResponseData::from_response(
+            FetchedRequestData::new(
+                RawData::from_vec(
+                    include_bytes!("../../../testdata/samples/stylesheet.css").to_vec(),
+                ),
+                None,
+                reqwest::StatusCode::OK,
+                None,
+                None,
+                false,
+            ),
+            UrlWithDepth::from_url("https://www.example.com/css/style.css").unwrap(),
+        );
+
+        let context = TestContext::default();
+        let identified_type = determine_format_for_response(&context, &mut page);
+        let preprocessed = process(&context, &page, &identified_type).await.unwrap();
+
+        let extracted = Extractor::default()
+            .extract_from_response(&context, &page, &identified_type, &preprocessed, None)
+            .await
+            .to_optional_links()
+            .unwrap();
+
+        let mut found = HashSet::new();
+        for link in extracted {
+            let (url, method) = match link {
+                ExtractedLink::OnSeed {
+                    url,
+                    extraction_method,
+                }
+                | ExtractedLink::Outgoing {
+                    url,
+                    extraction_method,
+                }
+                | ExtractedLink::Data {
+                    url,
+                    extraction_method,
+                    ..
+                } => (url, extraction_method),
+            };
+            assert_eq!(ExtractorMethod::Css, method.used_method);
+            found.insert(url.url.to_string());
+        }
+
+        let expected: HashSet<_> = [
+            "https://www.example.com/css/base.css",
+            "https://fonts.example.com/css?family=Open+Sans",
+            "https://www.example.com/print.css",
+            "https://www.example.com/css/fonts/open-sans.woff2",
+            "https://www.example.com/css/fonts/open-sans.woff",
+            "https://www.example.com/img/background%20image.png",
+            "https://www.example.com/css/img/icon.svg",
+            "https://cdn.example.com/sprite.png",
+            "https://www.example.com/css/img/escaped).png",
+            "data:image/png;base64,iVBORw0KGgo=",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(expected, found);
+    }
+
+    /// Extracts the links of a page with the [format] and returns the ones found by the [method].
+    /// Other compatible extractors, like the plain text extractor for a csv, are ignored.
+    async fn extract_with_method(
+        content: &[u8],
+        headers: Option<HeaderMap>,
+        url: &str,
+        format: InterpretedProcessibleFileFormat,
+        method: ExtractorMethod,
+    ) -> HashSet<String> {
+        let mut page = ResponseData::from_response(
+            FetchedRequestData::new(
+                RawData::from_vec(content.to_vec()),
+                headers,
+                reqwest::StatusCode::OK,
+                None,
+                None,
+                false,
+            ),
+            UrlWithDepth::from_url(url).unwrap(),
+        );
+
+        let context = TestContext::default();
+        let identified_type = determine_format_for_response(&context, &mut page);
+        assert_eq!(format, identified_type.format);
+        let preprocessed = process(&context, &page, &identified_type).await.unwrap();
+
+        Extractor::default()
+            .extract_from_response(&context, &page, &identified_type, &preprocessed, None)
+            .await
+            .to_optional_links()
+            .unwrap()
+            .into_iter()
+            .filter_map(|link| match link {
+                ExtractedLink::OnSeed {
+                    url,
+                    extraction_method,
+                }
+                | ExtractedLink::Outgoing {
+                    url,
+                    extraction_method,
+                }
+                | ExtractedLink::Data {
+                    url,
+                    extraction_method,
+                    ..
+                } => (method == extraction_method.used_method).then(|| url.url.to_string()),
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn can_extract_from_rss_feeds() {
+        let found = extract_with_method(
+            include_bytes!("../../../testdata/samples/feed_rss2.xml"),
+            None,
+            "https://www.example.com/blog/feed.xml",
+            InterpretedProcessibleFileFormat::Feed,
+            ExtractorMethod::Feed,
+        )
+        .await;
+
+        let expected: HashSet<_> = [
+            "https://www.example.com/blog/",
+            "https://www.example.com/blog/feed.xml",
+            "https://www.example.com/blog/first-post.html",
+            "https://www.example.com/blog/?p=1",
+            "https://www.example.com/blog/media/episode-1.mp3",
+            "https://www.example.com/blog/second-post.html",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(expected, found);
+    }
+
+    #[tokio::test]
+    async fn can_extract_from_atom_feeds() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/atom+xml; charset=utf-8"),
+        );
+        let found = extract_with_method(
+            include_bytes!("../../../testdata/samples/feed_atom1.xml"),
+            Some(headers),
+            "https://www.example.org/news/atom",
+            InterpretedProcessibleFileFormat::Feed,
+            ExtractorMethod::Feed,
+        )
+        .await;
+
+        let expected: HashSet<_> = [
+            "https://www.example.org/news/atom.xml",
+            "https://www.example.org/news/",
+            "https://www.example.org/news/2024/first-entry.html",
+            "https://cdn.example.org/first-entry.pdf",
+            "https://www.example.org/news/2024/second-entry.html",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(expected, found);
+    }
+
+    #[tokio::test]
+    async fn can_extract_from_json_api_responses() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let found = extract_with_method(
+            include_bytes!("../../../testdata/samples/api_items.json"),
+            Some(headers),
+            "https://api.example.com/v2/items",
+            InterpretedProcessibleFileFormat::JSON,
+            ExtractorMethod::Json,
+        )
+        .await;
+
+        let expected: HashSet<_> = [
+            "https://api.example.com/v2/items?page=1",
+            "https://api.example.com/v2/items?page=2",
+            "https://www.example.com/items/17",
+            "https://cdn.example.com/items/17/cover.jpg",
+            "http://video.example.net/watch?v=17",
+            "https://www.example.com/authors/jane",
+            "https://www.example.com/items/18",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(expected, found);
+    }
+
+    #[tokio::test]
+    async fn can_extract_from_csv_exports() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/csv; charset=utf-8"),
+        );
+        let found = extract_with_method(
+            include_bytes!("../../../testdata/samples/url_export.csv"),
+            Some(headers),
+            "https://www.example.com/export/urls.csv",
+            InterpretedProcessibleFileFormat::StructuredPlainText,
+            ExtractorMethod::Csv,
+        )
+        .await;
+
+        let expected: HashSet<_> = [
+            "https://www.example.com/",
+            "https://docs.example.com/start",
+            "https://www.example.org/about?lang=en",
+            "https://docs.example.org/",
+            "https://docs.example.com/search?q=a;b",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        assert_eq!(expected, found);
+    }
 }
@@ -14,13 +14,26 @@
 
 use crate::extraction::extractor_method::ExtractorMethod;
 use crate::extraction::ExtractedLink;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+
+/// Why an applied extractor method failed.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ExtractorFailure {
+    /// The method returned an error, the links found before the error are kept.
+    Error(String),
+    /// The method panicked, its links are discarded.
+    Panicked(String),
+    /// The method exceeded its time limit, its links are discarded.
+    TimedOut,
+}
 
 /// The result of an extraction, contains the extracted links as well es the applied extractors.
 #[derive(Debug, Default)]
 pub struct ExtractorResult {
     pub links: HashSet<ExtractedLink>,
     pub applied_extractors: HashSet<ExtractorMethod>,
+    /// The applied extractors that failed.
+    pub failed_extractors: HashMap<ExtractorMethod, ExtractorFailure>,
 }
 
 impl ExtractorResult {
@@ -29,6 +42,11 @@ impl ExtractorResult {
         self.applied_extractors.insert(extractor)
     }
 
+    /// Records the [failure] of an applied [extractor].
+    pub fn register_failure(&mut self, extractor: ExtractorMethod, failure: ExtractorFailure) {
+        self.failed_extractors.insert(extractor, failure);
+    }
+
     pub fn register_link(&mut self, link: ExtractedLink) -> bool {
         self.links.insert(link)
     }
@@ -43,6 +61,13 @@ impl ExtractorResult {
         self.applied_extractors.is_empty()
     }
 
+    /// Returns true if every applied extractor failed or none was applied.
+    pub fn no_extractor_succeeded(&self) -> bool {
+        self.applied_extractors
+            .iter()
+            .all(|value| self.failed_extractors.contains_key(value))
+    }
+
     /// Converts the result to an optional hashset
     pub fn to_optional_links(self) -> Option<HashSet<ExtractedLink>> {
         if self.is_empty() {
@@ -13,19 +13,24 @@
 // limitations under the License.
 
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess, SupportsGdbrRegistry};
-use crate::data::{Decoded, RawVecData};
-use crate::extraction::deflate::extract_from_zip;
+use crate::data::{Decoded, ExternalFileReader, RawVecData, UncompressedFile};
+use crate::extraction::deflate::{extract_from_gzip, extract_from_zip, is_gzip};
 use crate::extraction::extractor::{ExtractorData, ExtractorResult};
+use crate::extraction::js::ScriptLinkKind;
 use crate::extraction::links::ExtractedLink;
 use crate::extraction::marker::{
     ExtractorMethodHint, ExtractorMethodMeta, ExtractorMethodMetaFactory,
 };
 use crate::extraction::raw::extract_possible_urls;
+use crate::extraction::structured::{
+    extract_links_from_csv, extract_links_from_json, StructuredLinks,
+};
 use crate::extraction::LinkExtractionError;
 use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::format::AtraFileInformation;
 use crate::toolkit::utf8::RobustUtf8Reader;
 use bytes::Buf;
+use file_format::FileFormat;
 use serde::{Deserialize, Serialize};
 use std::fs::File;
 use std::io::{BufReader, Cursor, Read};
@@ -66,6 +71,20 @@ pub enum ExtractorMethod {
     Xlink,
     #[serde(alias = "zip")]
     Zip,
+    // New variants are appended, the stored links encode the index of the variant.
+    #[serde(alias = "css")]
+    #[serde(alias = "CSS")]
+    Css,
+    #[serde(alias = "feed")]
+    #[serde(alias = "rss")]
+    #[serde(alias = "atom")]
+    Feed,
+    #[serde(alias = "json")]
+    #[serde(alias = "JSON")]
+    Json,
+    #[serde(alias = "csv")]
+    #[serde(alias = "CSV")]
+    Csv,
     #[cfg(all(not(windows), feature = "with_pdf"))]
     #[serde(alias = "pdf_v1")]
     PdfV1,
@@ -82,6 +101,10 @@ impl ExtractorMethod {
     where
         C: SupportsConfigs + SupportsGdbrRegistry + SupportsFileSystemAccess,
     {
+        #[cfg(test)]
+        if let Some(mocked) = mock::mocked(*self) {
+            return mocked.run().await;
+        }
         if !self.is_compatible(page.file_info) {
             return Err(LinkExtractionError::NotCompatible);
         }
@@ -89,7 +112,10 @@ impl ExtractorMethod {
             ExtractorMethod::BinaryHeuristic => Box::pin(extract_links_raw(self, page, nesting == 0, output)).await,
             ExtractorMethod::Zip => Box::pin(extract_links_zip(self, context, page, nesting, output)).await,
             ExtractorMethod::HtmlV1 => Box::pin(extract_links_html(self, context, page, nesting == 0, output)).await,
-            ExtractorMethod::JSV1 => Box::pin(extract_links_javascript(self, page, nesting == 0, output)).await,
+            ExtractorMethod::JSV1 => Box::pin(extract_links_javascript(self, context, page, nesting == 0, output)).await,
+            ExtractorMethod::Css => Box::pin(extract_links_css(self, page, nesting == 0, output)).await,
+            ExtractorMethod::Json => Box::pin(extract_links_json(self, context, page, nesting == 0, output)).await,
+            ExtractorMethod::Csv => Box::pin(extract_links_csv(self, context, page, nesting == 0, output)).await,
             ExtractorMethod::PlainText => Box::pin(extract_links_plain_text(self, page, nesting == 0, output)).await,
             ExtractorMethod::Rtf => Box::pin(extract_links_rtf(self, page, nesting == 0, output)).await,
             ExtractorMethod::Ooxml => Box::pin(extract_links_ooxml(self, page, nesting == 0, output)).await,
@@ -98,12 +124,55 @@ impl ExtractorMethod {
             ExtractorMethod::Xml => Box::pin(extract_links_xml(self, page, nesting == 0, output)).await,
             ExtractorMethod::Svg => Box::pin(extract_links_svg(self, page, nesting == 0, output)).await,
             ExtractorMethod::Xlink => Box::pin(extract_links_xlink(self, page, nesting == 0, output)).await,
+            ExtractorMethod::Feed => Box::pin(extract_links_feed(self, page, nesting == 0, output)).await,
             #[cfg(all(not(windows), feature = "with_pdf"))]
             ExtractorMethod::PdfV1 => Box::pin(extract_links_pdf(self, page, nesting == 0, output)).await,
         }
     }
 }
 
+/// Replaces the behaviour of extractor methods on the current thread for tests.
+#[cfg(test)]
+pub(crate) mod mock {
+    use crate::extraction::extractor_method::ExtractorMethod;
+    use crate::extraction::LinkExtractionError;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    thread_local! {
+        static MOCKED: RefCell<HashMap<ExtractorMethod, MockedExtractor>> = RefCell::default();
+    }
+
+    /// The behaviour of a mocked extractor method.
+    #[derive(Debug, Copy, Clone)]
+    pub enum MockedExtractor {
+        Panic,
+        Sleep(Duration),
+    }
+
+    impl MockedExtractor {
+        pub(super) async fn run(self) -> Result<usize, LinkExtractionError> {
+            match self {
+                MockedExtractor::Panic => panic!("The mocked extractor panicked."),
+                MockedExtractor::Sleep(duration) => {
+                    tokio::time::sleep(duration).await;
+                    Ok(0)
+                }
+            }
+        }
+    }
+
+    /// Replaces the [method] with the [behaviour] until the thread ends.
+    pub fn register(method: ExtractorMethod, behaviour: MockedExtractor) {
+        MOCKED.with(|mocked| mocked.borrow_mut().insert(method, behaviour));
+    }
+
+    pub(super) fn mocked(method: ExtractorMethod) -> Option<MockedExtractor> {
+        MOCKED.with(|mocked| mocked.borrow().get(&method).copied())
+    }
+}
+
 impl ExtractorMethodMetaFactory for ExtractorMethod {
     fn new_without_meta(&self) -> ExtractorMethodHint {
         ExtractorMethodHint::new_without_meta(self.clone())
@@ -126,6 +195,31 @@ impl ExtractorMethod {
                     InterpretedProcessibleFileFormat::JavaScript
                 )
             }
+            ExtractorMethod::Css => {
+                matches!(file_info.format, InterpretedProcessibleFileFormat::CSS)
+                    || file_info.mime.as_ref().is_some_and(|mimes| {
+                        mimes.iter().any(|value| {
+                            value.type_() == mime::TEXT && value.subtype() == mime::CSS
+                        })
+                    })
+            }
+            ExtractorMethod::Json => {
+                matches!(file_info.format, InterpretedProcessibleFileFormat::JSON)
+            }
+            ExtractorMethod::Csv => {
+                let csv_mime = file_info
+                    .mime
+                    .as_ref()
+                    .is_some_and(|mimes| mimes.iter().any(|value| value.subtype() == "csv"));
+                // Calendars and vcards are structured plain text as well.
+                let undetected_structured_text = matches!(
+                    file_info.format,
+                    InterpretedProcessibleFileFormat::StructuredPlainText
+                ) && file_info.detected.as_ref().map_or(true, |detected| {
+                    matches!(detected.most_probable_file_format(), FileFormat::PlainText)
+                });
+                csv_mime || undetected_structured_text
+            }
             ExtractorMethod::PlainText => {
                 matches!(
                     file_info.format,
@@ -162,6 +256,9 @@ impl ExtractorMethod {
             ExtractorMethod::Zip => {
                 matches!(file_info.format, InterpretedProcessibleFileFormat::ZIP)
             }
+            ExtractorMethod::Feed => {
+                matches!(file_info.format, InterpretedProcessibleFileFormat::Feed)
+            }
             ExtractorMethod::BinaryHeuristic => {
                 !matches!(file_info.format, InterpretedProcessibleFileFormat::ZIP)
             }
@@ -234,38 +331,54 @@ where
         ct
     }
 
-    if let Some(value) = data.raw_data.cursor()? {
-        match extract_from_zip(data.url, BufReader::new(value), nesting, context).await {
-            Ok((result, errors)) => {
-                if !errors.is_empty() {
-                    if log::max_level() <= log::LevelFilter::Trace {
-                        let mut message = String::new();
-                        for (path, err) in errors {
-                            message.push_str(&format!("Error at {path} in zip:\n"));
-                            message.push_str(err.to_string().as_str());
-                            message.push('\n');
-                        }
-                        log::trace!("Error parsing '{}'\n---START---\n{message}\n---END---\n",data.url)
+    let Some(mut value) = data.raw_data.cursor()? else {
+        return Ok(0);
+    };
+    if is_gzip(&mut value)? {
+        let url = data.url.as_str();
+        let file_name = data.file_name.unwrap_or_else(|| {
+            let path = url.split(['?', '#']).next().unwrap_or_default();
+            path.rsplit('/').next().unwrap_or_default()
+        });
+        let unpacked =
+            extract_from_gzip(data.url, BufReader::new(value), file_name, nesting, context).await;
+        return match unpacked {
+            Ok(Some(unpacked)) => Ok(map_extracted_links(extractor, unpacked, output)),
+            Ok(None) => Ok(0),
+            Err(err) => {
+                log::debug!("Failed to extract from gzip file {}:\n{err}", data.url.url);
+                Ok(0)
+            }
+        };
+    }
+    match extract_from_zip(data.url, BufReader::new(value), nesting, context).await {
+        Ok((result, errors)) => {
+            if !errors.is_empty() {
+                if log::max_level() <= log::LevelFilter::Trace {
+                    let mut message = String::new();
+                    for (path, err) in errors {
+                        message.push_str(&format!("Error at {path} in zip:\n"));
+                        message.push_str(err.to_string().as_str());
+                        message.push('\n');
                     }
+                    log::trace!("Error parsing '{}'\n---START---\n{message}\n---END---\n",data.url)
                 }
-
-                let mut ct = 0usize;
-                for link in result {
-                    ct += map_extracted_links(
-                        extractor,
-                        link,
-                        output
-                    );
-                }
-                Ok(ct)
             }
-            Err(err) => {
-                log::debug!("Failed to extract from zip file {}:\n{err}", data.url.url);
-                Ok(0)
+
+            let mut ct = 0usize;
+            for link in result {
+                ct += map_extracted_links(
+                    extractor,
+                    link,
+                    output
+                );
             }
+            Ok(ct)
+        }
+        Err(err) => {
+            log::debug!("Failed to extract from zip file {}:\n{err}", data.url.url);
+            Ok(0)
         }
-    } else {
-        Ok(0)
     }
 }
 
@@ -336,17 +449,182 @@ where
     }
 }
 
+async fn extract_links_feed(
+    extractor: &impl ExtractorMethodMetaFactory,
+    data: &ExtractorData<'_>,
+    use_base: bool,
+    output: &mut ExtractorResult,
+) -> Result<usize, LinkExtractionError> {
+    let (extracted, error) = match &data.decoded {
+        Decoded::InMemory { data: result, .. } => {
+            crate::extraction::feed::extract_links(result.as_bytes())
+        }
+        Decoded::OffMemory { reference, .. } => crate::extraction::feed::extract_links(
+            BufReader::new(File::options().read(true).open(reference)?),
+        ),
+        Decoded::None => return Ok(0),
+    };
+    if let Some(error) = error {
+        log::debug!(
+            "Failed to parse the feed {} completely: {}",
+            data.url,
+            error
+        );
+    }
+    let mut ct = 0usize;
+    for (origin, link) in extracted {
+        match ExtractedLink::pack(
+            &data.url,
+            &link,
+            extractor.new_with_meta(ExtractorMethodMeta::Feed(origin)),
+            use_base,
+        ) {
+            Ok(link) => {
+                if output.register_link(link) {
+                    ct += 1;
+                }
+            }
+            Err(error) => {
+                log::debug!(
+                    "Was not able to parse link {} from feed. Error: {}",
+                    link,
+                    error
+                )
+            }
+        }
+    }
+    Ok(ct)
+}
+
+/// Fails if the document with [size] bytes exceeds the limit for structured documents.
+fn check_document_size(
+    context: &impl SupportsConfigs,
+    size: u64,
+) -> Result<(), LinkExtractionError> {
+    let limit = context.configs().crawl.structured_extraction.max_document_size;
+    if size > limit {
+        Err(LinkExtractionError::DocumentTooLarge { size, limit })
+    } else {
+        Ok(())
+    }
+}
+
+/// Registers the absolute urls [found] in a structured document with the [name].
+fn register_structured_links(
+    extractor: &impl ExtractorMethodMetaFactory,
+    data: &ExtractorData<'_>,
+    name: &str,
+    found: StructuredLinks,
+    use_base: bool,
+    output: &mut ExtractorResult,
+) -> usize {
+    if found.truncated {
+        log::debug!(
+            "Stopped the {name} extraction of {} after {} links.",
+            data.url,
+            found.links.len()
+        );
+    }
+    let mut ct = 0usize;
+    for entry in found.links {
+        match ExtractedLink::pack(&data.url, &entry, extractor.new_without_meta(), use_base) {
+            Ok(link) => {
+                if output.register_link(link) {
+                    ct += 1;
+                }
+            }
+            Err(error) => {
+                log::debug!(
+                    "Was not able to parse {} from {name}. Error: {}",
+                    entry,
+                    error
+                )
+            }
+        }
+    }
+    ct
+}
+
+async fn extract_links_json(
+    extractor: &impl ExtractorMethodMetaFactory,
+    context: &impl SupportsConfigs,
+    data: &ExtractorData<'_>,
+    use_base: bool,
+    output: &mut ExtractorResult,
+) -> Result<usize, LinkExtractionError> {
+    let parsed = match &data.decoded {
+        Decoded::InMemory { data: result, .. } => {
+            check_document_size(context, result.len() as u64)?;
+            serde_json::from_str(result.as_str())
+        }
+        Decoded::OffMemory { reference, .. } => {
+            check_document_size(context, std::fs::metadata(reference)?.len())?;
+            serde_json::from_reader(BufReader::new(File::options().read(true).open(reference)?))
+        }
+        Decoded::None => return Ok(0),
+    };
+    match parsed {
+        Ok(value) => {
+            let max_links = context.configs().crawl.structured_extraction.max_links;
+            let found = extract_links_from_json(&value, max_links);
+            Ok(register_structured_links(extractor, data, "json", found, use_base, output))
+        }
+        Err(err) => Err(LinkExtractionError::ExtractionErrors {
+            successes: 0,
+            errors: vec![err.into()],
+        }),
+    }
+}
+
+async fn extract_links_csv(
+    extractor: &impl ExtractorMethodMetaFactory,
+    context: &impl SupportsConfigs,
+    data: &ExtractorData<'_>,
+    use_base: bool,
+    output: &mut ExtractorResult,
+) -> Result<usize, LinkExtractionError> {
+    let max_links = context.configs().crawl.structured_extraction.max_links;
+    let (found, error) = match &data.decoded {
+        Decoded::InMemory { data: result, .. } => {
+            check_document_size(context, result.len() as u64)?;
+            extract_links_from_csv(result.as_bytes(), max_links)
+        }
+        Decoded::OffMemory { reference, .. } => {
+            check_document_size(context, std::fs::metadata(reference)?.len())?;
+            extract_links_from_csv(
+                BufReader::new(File::options().read(true).open(reference)?),
+                max_links,
+            )
+        }
+        Decoded::None => return Ok(0),
+    };
+    let ct = register_structured_links(extractor, data, "csv", found, use_base, output);
+    match error {
+        None => Ok(ct),
+        Some(err) => Err(LinkExtractionError::ExtractionErrors {
+            successes: ct,
+            errors: vec![err.into()],
+        }),
+    }
+}
+
 async fn extract_links_javascript(
     extractor: &impl ExtractorMethodMetaFactory,
+    context: &impl SupportsConfigs,
     data: &ExtractorData<'_>,
     use_base: bool,
     output: &mut ExtractorResult,
 ) -> Result<usize, LinkExtractionError> {
+    let crawl_redirect_hints = context.configs().crawl.crawl_redirect_hints;
     match &data.decoded {
         Decoded::InMemory { data: result, .. } => {
             let mut ct = 0usize;
-            for entry in crate::extraction::js::extract_links(result.as_str()) {
-                match ExtractedLink::pack(&data.url, entry.as_str(), extractor.new_without_meta(), use_base) {
+            for (kind, entry) in crate::extraction::js::extract_links(result.as_str()) {
+                if kind == ScriptLinkKind::Redirect && !crawl_redirect_hints {
+                    continue;
+                }
+                let hint = extractor.new_with_meta(ExtractorMethodMeta::JavaScript(kind));
+                match ExtractedLink::pack(&data.url, entry.as_str(), hint, use_base) {
                     Ok(link) => {
                         if output.register_link(link) {
                             ct += 1;
@@ -368,6 +646,38 @@ async fn extract_links_javascript(
     }
 }
 
+async fn extract_links_css(
+    extractor: &impl ExtractorMethodMetaFactory,
+    data: &ExtractorData<'_>,
+    use_base: bool,
+    output: &mut ExtractorResult,
+) -> Result<usize, LinkExtractionError> {
+    match &data.decoded {
+        Decoded::InMemory { data: result, .. } => {
+            let mut ct = 0usize;
+            for entry in crate::extraction::css::extract_links(result.as_str()) {
+                match ExtractedLink::pack(&data.url, entry.as_str(), extractor.new_without_meta(), use_base) {
+                    Ok(link) => {
+                        if output.register_link(link) {
+                            ct += 1;
+                        }
+                    }
+                    Err(error) => {
+                        log::debug!(
+                            "Was not able to parse {} from css. Error: {}",
+                            entry,
+                            error
+                        )
+                    }
+                }
+            }
+            Ok(ct)
+        }
+        Decoded::OffMemory { .. } => Err(LinkExtractionError::CanNotStoreInMemory),
+        Decoded::None => Ok(0),
+    }
+}
+
 async fn extract_links_plain_text(
     extractor: &impl ExtractorMethodMetaFactory,
     data: &ExtractorData<'_>,
@@ -476,7 +786,7 @@ async fn extract_links_raw(
             RawVecData::ExternalFile { path } => {
                 execute(
                     extractor,
-                    RobustUtf8Reader::new(BufReader::new(File::options().read(true).open(path)?)),
+                    RobustUtf8Reader::new(BufReader::new(ExternalFileReader::open(path)?)),
                     data,
                     use_base,
                     output,
@@ -522,7 +832,9 @@ macro_rules! define_method {
                         }
                     }
                 }
-                RawVecData::ExternalFile { path: $off_var } => {
+                RawVecData::ExternalFile { path } => {
+                    let uncompressed = UncompressedFile::provide(path)?;
+                    let $off_var = uncompressed.path();
                     match $off_block {
                         Ok(result) => {
                             let mut ct = 0;
@@ -785,4 +1097,27 @@ define_method! {
             link_scraper::formats::pdf::scrape_from_slice(data)
         }
     )
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use crate::extraction::extractor_method::ExtractorMethod;
+
+    #[test]
+    fn the_variants_keep_their_encoding() {
+        for (method, index) in [
+            (ExtractorMethod::HtmlV1, 0u32),
+            (ExtractorMethod::PlainText, 2),
+            (ExtractorMethod::Zip, 11),
+            (ExtractorMethod::Css, 12),
+            (ExtractorMethod::Feed, 13),
+            (ExtractorMethod::Json, 14),
+            (ExtractorMethod::Csv, 15),
+        ] {
+            assert_eq!(
+                index.to_le_bytes().as_slice(),
+                bincode::serialize(&method).unwrap(),
+                "{method}"
+            );
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use compact_str::CompactString;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::io::Read;
+use xml::attribute::OwnedAttribute;
+use xml::reader::{ParserConfig2, XmlEvent};
+use xml::EventReader;
+
+/// The namespace of Atom 1.0
+const ATOM_NAMESPACE: &str = "http://www.w3.org/2005/Atom";
+/// The namespace of RSS 1.0, RSS 2.0 has no namespace.
+const RSS_1_NAMESPACE: &str = "http://purl.org/rss/1.0/";
+
+/// Describes the origin of a link extracted from a feed
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum FeedLinkOrigin {
+    /// The `<link>` of RSS or an Atom link without a special relation
+    Link,
+    /// A `<guid isPermaLink="true">` of RSS
+    Guid,
+    /// An RSS `<enclosure>` or an Atom link with the relation `enclosure`
+    Enclosure,
+    /// The Atom link with the relation `self`, the feed itself
+    SelfLink,
+}
+
+/// Returns the value of the attribute without namespace called [name].
+fn attribute<'a>(attributes: &'a [OwnedAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|value| value.name.namespace.is_none() && value.name.local_name == name)
+        .map(|value| value.value.as_str())
+}
+
+/// Extracts the links from an RSS or Atom feed. The links are not resolved.
+/// If the feed is malformed, the links found before the error are returned with the error.
+pub fn extract_links<R: Read>(
+    feed: R,
+) -> (
+    HashSet<(FeedLinkOrigin, CompactString)>,
+    Option<xml::reader::Error>,
+) {
+    let cfg = ParserConfig2::new()
+        .ignore_invalid_encoding_declarations(true)
+        .ignore_comments(true)
+        .cdata_to_characters(true)
+        .replace_unknown_entity_references(true);
+
+    let mut links = HashSet::new();
+    // The text of the current element is the link.
+    let mut text_link: Option<(FeedLinkOrigin, String)> = None;
+    for event in EventReader::new_with_config(feed, cfg) {
+        match event {
+            Ok(XmlEvent::StartElement {
+                name, attributes, ..
+            }) => match (name.namespace.as_deref(), name.local_name.as_str()) {
+                (Some(ATOM_NAMESPACE), "link") => {
+                    if let Some(href) = attribute(&attributes, "href") {
+                        let origin = match attribute(&attributes, "rel") {
+                            Some("self") => FeedLinkOrigin::SelfLink,
+                            Some("enclosure") => FeedLinkOrigin::Enclosure,
+                            _ => FeedLinkOrigin::Link,
+                        };
+                        links.insert((origin, CompactString::from(href.trim())));
+                    }
+                }
+                (None | Some(RSS_1_NAMESPACE), "link") => {
+                    text_link = Some((FeedLinkOrigin::Link, String::new()));
+                }
+                (None, "guid") => {
+                    if attribute(&attributes, "isPermaLink")
+                        .is_some_and(|value| value.trim().eq_ignore_ascii_case("true"))
+                    {
+                        text_link = Some((FeedLinkOrigin::Guid, String::new()));
+                    }
+                }
+                (None, "enclosure") => {
+                    if let Some(url) = attribute(&attributes, "url") {
+                        links.insert((FeedLinkOrigin::Enclosure, CompactString::from(url.trim())));
+                    }
+                }
+                _ => {}
+            },
+            Ok(XmlEvent::Characters(text)) => {
+                if let Some((_, collected)) = &mut text_link {
+                    collected.push_str(&text);
+                }
+            }
+            Ok(XmlEvent::EndElement { .. }) => {
+                if let Some((origin, collected)) = text_link.take() {
+                    let collected = collected.trim();
+                    if !collected.is_empty() {
+                        links.insert((origin, CompactString::from(collected)));
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(err) => return (links, Some(err)),
+        }
+    }
+    (links, None)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_links, FeedLinkOrigin};
+    use compact_str::CompactString;
+    use std::collections::HashSet;
+
+    fn expected(links: &[(FeedLinkOrigin, &str)]) -> HashSet<(FeedLinkOrigin, CompactString)> {
+        links
+            .iter()
+            .map(|(origin, link)| (*origin, CompactString::from(*link)))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_from_rss_2() {
+        let (links, error) =
+            extract_links(include_bytes!("../../testdata/samples/feed_rss2.xml").as_slice());
+        assert!(error.is_none());
+        assert_eq!(
+            expected(&[
+                (FeedLinkOrigin::Link, "https://www.example.com/blog/"),
+                (
+                    FeedLinkOrigin::SelfLink,
+                    "https://www.example.com/blog/feed.xml"
+                ),
+                (FeedLinkOrigin::Link, "/blog/first-post.html"),
+                (FeedLinkOrigin::Guid, "https://www.example.com/blog/?p=1"),
+                (FeedLinkOrigin::Enclosure, "media/episode-1.mp3"),
+                (
+                    FeedLinkOrigin::Link,
+                    "https://www.example.com/blog/second-post.html"
+                ),
+            ]),
+            links
+        );
+    }
+
+    #[test]
+    fn extracts_from_atom_1() {
+        let (links, error) =
+            extract_links(include_bytes!("../../testdata/samples/feed_atom1.xml").as_slice());
+        assert!(error.is_none());
+        assert_eq!(
+            expected(&[
+                (FeedLinkOrigin::SelfLink, "/news/atom.xml"),
+                (FeedLinkOrigin::Link, "https://www.example.org/news/"),
+                (FeedLinkOrigin::Link, "2024/first-entry.html"),
+                (
+                    FeedLinkOrigin::Enclosure,
+                    "https://cdn.example.org/first-entry.pdf"
+                ),
+                (
+                    FeedLinkOrigin::Link,
+                    "https://www.example.org/news/2024/second-entry.html"
+                ),
+            ]),
+            links
+        );
+    }
+
+    #[test]
+    fn keeps_the_links_before_an_error() {
+        let (links, error) = extract_links(
+            b"<rss><channel><link>https://www.example.com/</link><item><link>broken".as_slice(),
+        );
+        assert!(error.is_some());
+        assert_eq!(
+            expected(&[(FeedLinkOrigin::Link, "https://www.example.com/")]),
+            links
+        );
+    }
+}
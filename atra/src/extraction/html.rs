@@ -13,9 +13,11 @@
 // limitations under the License.
 
 use crate::contexts::traits::{SupportsConfigs, SupportsGdbrRegistry};
+use crate::extraction::js::ScriptLinkKind;
 use crate::gdbr::identifier::GdbrRegistry;
+use crate::robots::RobotsMetaDirectives;
 use crate::toolkit::LanguageInformation;
-use crate::url::UrlWithDepth;
+use crate::url::{AtraOriginProvider, UrlWithDepth};
 use compact_str::{CompactString, ToCompactString};
 use scraper::Html;
 use serde::{Deserialize, Serialize};
@@ -32,6 +34,14 @@ pub enum LinkOrigin {
     JavaScript,
     JavaScriptEmbedded,
     OnClick,
+    Style,
+    /// The target of a `<meta http-equiv="refresh">`.
+    MetaRefresh,
+    /// A literal assigned to the `location` in an embedded script.
+    JavaScriptRedirect,
+    /// An [LinkOrigin::Href] with `nofollow` in its `rel`, only kept if the nofollow
+    /// is not respected.
+    HrefNoFollow,
 }
 
 /// Extracts links from an html
@@ -50,16 +60,32 @@ where
 {
     let cfg = context.configs();
 
+    let origin = root_url.atra_origin();
+    let profile = origin
+        .as_ref()
+        .and_then(|origin| cfg.crawl.origin_profile(origin));
+
     let respect_nofollow: bool = cfg.crawl.respect_nofollow;
-    let crawl_embedded_data: bool = cfg.crawl.crawl_embedded_data;
-    let crawl_forms: bool = cfg.crawl.crawl_forms;
-    let crawl_javascript: bool = cfg.crawl.crawl_javascript;
-    let crawl_onclick_by_heuristic: bool = cfg.crawl.crawl_onclick_by_heuristic;
+    let crawl_embedded_data: bool = profile
+        .and_then(|found| found.crawl_embedded_data)
+        .unwrap_or(cfg.crawl.crawl_embedded_data);
+    let crawl_forms: bool = profile
+        .and_then(|found| found.crawl_forms)
+        .unwrap_or(cfg.crawl.crawl_forms);
+    let crawl_javascript: bool = profile
+        .and_then(|found| found.crawl_javascript)
+        .unwrap_or(cfg.crawl.crawl_javascript);
+    let crawl_onclick_by_heuristic: bool = profile
+        .and_then(|found| found.crawl_onclick_by_heuristic)
+        .unwrap_or(cfg.crawl.crawl_onclick_by_heuristic);
+    let crawl_redirect_hints: bool = cfg.crawl.crawl_redirect_hints;
 
     let mut html = Html::parse_document(html);
 
     if cfg.crawl.apply_gdbr_filter_if_possible {
-        if let Some(registry) = context.gdbr_registry() {
+        if language.is_some_and(|value| value.is_uncertain()) {
+            log::debug!("Failed to clean because the language is uncertain.")
+        } else if let Some(registry) = context.gdbr_registry() {
             if let Some(found) = registry.get_by_language_or_default(language) {
                 found.remove_gdbr(&mut html);
             } else {
@@ -70,11 +96,9 @@ where
         }
     }
 
-    if respect_nofollow {
-        if html.select(&selectors::META_NO_FOLLOW).next().is_some() {
-            log::debug!("Respecting no-follow metatag of {}", root_url);
-            return None;
-        }
+    if cfg.crawl.respect_robots_meta && RobotsMetaDirectives::from_document(&html).nofollow {
+        log::debug!("Respecting no-follow metatag of {}", root_url);
+        return None;
     }
 
     let mut result = HashSet::new();
@@ -106,16 +130,35 @@ where
     };
 
     for element in html.select(&selectors::HREF_HOLDER) {
-        if respect_nofollow {
-            if let Some(rel) = element.attr("rel") {
-                if rel == "nofollow" {
-                    log::trace!("Respecting no-follow");
-                    continue;
-                }
-            }
+        let nofollow = element.attr("rel").is_some_and(|rel| {
+            rel.split_ascii_whitespace()
+                .any(|token| token.eq_ignore_ascii_case("nofollow"))
+        });
+        if nofollow && respect_nofollow {
+            log::trace!("Respecting no-follow");
+            continue;
         }
         if let Some(href) = element.attr("href") {
-            result.insert((LinkOrigin::Href, href.to_compact_string()));
+            let origin = if nofollow {
+                LinkOrigin::HrefNoFollow
+            } else {
+                LinkOrigin::Href
+            };
+            result.insert((origin, href.to_compact_string()));
+        }
+    }
+
+    if crawl_redirect_hints {
+        for element in html.select(&selectors::META_HTTP_EQUIV) {
+            let is_refresh = element
+                .attr("http-equiv")
+                .is_some_and(|value| value.trim().eq_ignore_ascii_case("refresh"));
+            if !is_refresh {
+                continue;
+            }
+            if let Some(target) = element.attr("content").and_then(parse_meta_refresh) {
+                result.insert((LinkOrigin::MetaRefresh, target.to_compact_string()));
+            }
         }
     }
 
@@ -127,6 +170,21 @@ where
         }
     }
 
+    if crawl_embedded_data {
+        for element in html.select(&selectors::STYLE_ATTRIBUTE_HOLDER) {
+            for entry in crate::extraction::css::extract_links(element.attr("style").unwrap()) {
+                result.insert((LinkOrigin::Style, entry));
+            }
+        }
+        for element in html.select(&selectors::STYLE_HOLDER) {
+            for entry in
+                crate::extraction::css::extract_links(element.text().collect::<String>().as_str())
+            {
+                result.insert((LinkOrigin::Style, entry));
+            }
+        }
+    }
+
     if crawl_forms {
         for element in html.select(&selectors::FORM_HOLDER) {
             if let Some(src) = element.attr("action") {
@@ -140,10 +198,18 @@ where
             if let Some(src) = element.attr("src") {
                 result.insert((LinkOrigin::JavaScript, src.to_compact_string()));
             } else {
-                for entry in crate::extraction::js::extract_links(
+                for (kind, entry) in crate::extraction::js::extract_links(
                     element.text().collect::<String>().as_str(),
                 ) {
-                    result.insert((LinkOrigin::JavaScriptEmbedded, entry));
+                    match kind {
+                        ScriptLinkKind::Href => {
+                            result.insert((LinkOrigin::JavaScriptEmbedded, entry));
+                        }
+                        ScriptLinkKind::Redirect if crawl_redirect_hints => {
+                            result.insert((LinkOrigin::JavaScriptRedirect, entry));
+                        }
+                        ScriptLinkKind::Redirect => {}
+                    }
                 }
             }
         }
@@ -165,6 +231,43 @@ where
     Some((base, result, html.errors))
 }
 
+/// Returns the target of the `content` of a meta refresh, None if it only reloads the page.
+/// Follows the declarative refresh of the html standard: the delay is required but ignored,
+/// the `url=` prefix and the quotes around the target are optional.
+pub fn parse_meta_refresh(content: &str) -> Option<&str> {
+    let is_space = |c: char| c.is_ascii_whitespace();
+    let content = content.trim_start_matches(is_space);
+    if !content.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+        return None;
+    }
+    let rest = content.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.');
+    // Also rejects an empty rest, a refresh of the page itself.
+    if !rest.starts_with([';', ',']) && !rest.starts_with(is_space) {
+        return None;
+    }
+    let rest = rest.trim_start_matches(is_space);
+    let mut target = rest
+        .strip_prefix([';', ','])
+        .unwrap_or(rest)
+        .trim_start_matches(is_space);
+    if target
+        .get(..3)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("url"))
+    {
+        if let Some(after_equals) = target[3..].trim_start_matches(is_space).strip_prefix('=') {
+            target = after_equals.trim_start_matches(is_space);
+        }
+    }
+    if let Some(quote) = target.chars().next().filter(|c| *c == '\'' || *c == '"') {
+        target = &target[1..];
+        if let Some(end) = target.find(quote) {
+            target = &target[..end];
+        }
+    }
+    let target = target.trim();
+    (!target.is_empty()).then_some(target)
+}
+
 mod selectors {
     use crate::static_selectors;
     use regex::Regex;
@@ -249,15 +352,23 @@ mod selectors {
             SRC_HOLDER = "audio,embed,iframe,img,input,source,track,video"
             SCRIPT_HOLDER = "script"
             ON_CLICK = "[onclick]"
+            STYLE_ATTRIBUTE_HOLDER = "[style]"
+            STYLE_HOLDER = "style"
             FORM_HOLDER = "form[action]"
-            META_NO_FOLLOW = "meta[name=\"robots\"][content=\"nofollow\"]"
+            META_HTTP_EQUIV = "meta[http-equiv]"
         ]
     }
 }
 
 #[cfg(test)]
 mod test {
+    use crate::config::{Config, CrawlConfig};
+    use crate::extraction::html::{extract_links, parse_meta_refresh, LinkOrigin};
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::url::UrlWithDepth;
+    use compact_str::CompactString;
     use scraper::Html;
+    use std::collections::HashSet;
 
     #[test]
     fn can_recognize_properly() {
@@ -278,4 +389,131 @@ mod test {
         }
         panic!("The on click was not found!");
     }
+
+    #[test]
+    fn extracts_links_from_styles() {
+        const HTML: &str = r#"
+            <html>
+            <head><style>@import "theme.css"; body { background: url(img/bg.png) }</style></head>
+            <body>
+                <div style="background-image: url('img/hero.jpg'); color: red"></div>
+                <span style="content: 'url(no-link.png)'"></span>
+            </body>
+            </html>
+        "#;
+
+        let mut crawl = CrawlConfig::default();
+        crawl.crawl_embedded_data = true;
+        let context = TestContext::new(
+            Config::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                crawl,
+            ),
+            FakeClientProvider::new(),
+        );
+        let root = UrlWithDepth::from_url("https://www.example.com/page/index.html").unwrap();
+        let (_, found, _) = extract_links(&root, HTML, &context, None).unwrap();
+        let expected: HashSet<_> = ["theme.css", "img/bg.png", "img/hero.jpg"]
+            .into_iter()
+            .map(|value| (LinkOrigin::Style, CompactString::new(value)))
+            .collect();
+        assert_eq!(expected, found);
+    }
+
+    #[test]
+    fn parses_the_variants_of_meta_refresh() {
+        for (content, expected) in [
+            ("0;url=/target", "/target"),
+            ("0; URL=/target", "/target"),
+            ("0;URL='/target'", "/target"),
+            ("5 ; url = \"/quoted\" ignored", "/quoted"),
+            ("3;/without-prefix", "/without-prefix"),
+            ("0 /separated-by-space", "/separated-by-space"),
+            ("0, url=/comma", "/comma"),
+            ("1.5; url=/fraction", "/fraction"),
+            (".5;url=/only-fraction", "/only-fraction"),
+            ("0;urlish.html", "urlish.html"),
+            ("0;'/quoted-without-prefix'", "/quoted-without-prefix"),
+        ] {
+            assert_eq!(Some(expected), parse_meta_refresh(content), "{content}");
+        }
+        for content in ["", " ", "0", "10; ", "url=/no-delay", "0url=/glued"] {
+            assert_eq!(None, parse_meta_refresh(content), "{content}");
+        }
+    }
+
+    #[test]
+    fn extracts_redirect_hints() {
+        const HTML: &str = include_str!("../../testdata/samples/meta_refresh.html");
+        let root = UrlWithDepth::from_url("https://www.example.com/old/index.html").unwrap();
+
+        for crawl_redirect_hints in [true, false] {
+            let mut crawl = CrawlConfig::default();
+            crawl.crawl_redirect_hints = crawl_redirect_hints;
+            let context = TestContext::new(
+                Config::new(
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                    crawl,
+                ),
+                FakeClientProvider::new(),
+            );
+            let (_, found, _) = extract_links(&root, HTML, &context, None).unwrap();
+            let mut expected = vec![(LinkOrigin::JavaScriptEmbedded, "/anchor.html")];
+            if crawl_redirect_hints {
+                expected.push((
+                    LinkOrigin::MetaRefresh,
+                    "https://www.example.org/moved.html",
+                ));
+                expected.push((LinkOrigin::JavaScriptRedirect, "/legacy/"));
+            }
+            let expected: HashSet<_> = expected
+                .into_iter()
+                .map(|(origin, value)| (origin, CompactString::new(value)))
+                .collect();
+            assert_eq!(expected, found);
+        }
+    }
+
+    #[test]
+    fn marks_nofollow_links() {
+        const HTML: &str = r#"
+            <html>
+            <body>
+                <a href="/follow.html">follow</a>
+                <a href="/sponsored.html" rel="sponsored NoFollow">sponsored</a>
+                <a href="/nofollow.html" rel="nofollow">nofollow</a>
+            </body>
+            </html>
+        "#;
+        let root = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        for respect_nofollow in [true, false] {
+            let mut crawl = CrawlConfig::default();
+            crawl.respect_nofollow = respect_nofollow;
+            let context = TestContext::new(
+                Config::new(
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                    crawl,
+                ),
+                FakeClientProvider::new(),
+            );
+            let (_, found, _) = extract_links(&root, HTML, &context, None).unwrap();
+            let mut expected = vec![(LinkOrigin::Href, "/follow.html")];
+            if !respect_nofollow {
+                expected.push((LinkOrigin::HrefNoFollow, "/sponsored.html"));
+                expected.push((LinkOrigin::HrefNoFollow, "/nofollow.html"));
+            }
+            let expected: HashSet<_> = expected
+                .into_iter()
+                .map(|(origin, value)| (origin, CompactString::new(value)))
+                .collect();
+            assert_eq!(expected, found);
+        }
+    }
 }
@@ -16,61 +16,174 @@ use compact_str::{CompactString, ToCompactString};
 use ress::prelude::{StringLit, Token};
 use ress::tokens::Punct;
 use ress::Scanner;
+use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
+/// How a link was found in a script.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
+pub enum ScriptLinkKind {
+    /// A string after some `href`.
+    Href,
+    /// A string literal assigned to the `location`, its `href` or passed to
+    /// `location.assign` or `location.replace`.
+    Redirect,
+}
+
 /// Tries to extract all links from a js-script.
-pub fn extract_links(script: &str) -> HashSet<CompactString> {
-    let scanner = Scanner::new(script);
+/// Redirects are only recognized with a single string literal, never with a computed value.
+pub fn extract_links(script: &str) -> HashSet<(ScriptLinkKind, CompactString)> {
+    let tokens = Scanner::new(script)
+        .filter_map(Result::ok)
+        .map(|item| item.token)
+        .filter(|token| !matches!(token, Token::Comment(_)))
+        .collect::<Vec<_>>();
 
     let mut links = HashSet::new();
     let mut href_found = false;
+    let mut position = 0;
 
-    for item in scanner {
-        match item {
-            Ok(ref value) => {
-                match &value.token {
-                    Token::Ident(identifier) => {
-                        if "href" == identifier.as_ref() {
-                            href_found = true;
-                        }
-                    }
-                    Token::Punct(Punct::SemiColon) => {
-                        if href_found {
-                            log::trace!("JS_Extract: Missed some href at {item:?}!");
-                            href_found = false
-                        }
-                    }
-                    // TODO: handle string concat?????
-                    Token::String(value) => {
-                        if !href_found {
-                            continue;
-                        }
-                        let link = match value {
-                            StringLit::Single(value) => value.content.to_compact_string(),
-                            StringLit::Double(value) => value.content.to_compact_string(),
-                        };
-                        href_found = false;
-                        links.insert(link);
-                    }
-                    _ => {}
+    while let Some(token) = tokens.get(position) {
+        if let Some((link, next)) = location_redirect(&tokens, position) {
+            if let Some(link) = link {
+                links.insert((ScriptLinkKind::Redirect, link));
+            }
+            href_found = false;
+            position = next;
+            continue;
+        }
+        match token {
+            Token::Ident(identifier) => {
+                if "href" == identifier.as_ref() {
+                    href_found = true;
+                }
+            }
+            Token::Punct(Punct::SemiColon) => {
+                if href_found {
+                    log::trace!("JS_Extract: Missed some href at {token:?}!");
+                    href_found = false
+                }
+            }
+            // TODO: handle string concat?????
+            Token::String(value) => {
+                if href_found {
+                    href_found = false;
+                    links.insert((ScriptLinkKind::Href, string_content(value)));
                 }
             }
-            Err(_) => {}
+            _ => {}
         }
+        position += 1;
     }
 
-    return links;
+    links
+}
+
+fn string_content(value: &StringLit<&str>) -> CompactString {
+    match value {
+        StringLit::Single(value) => value.content.to_compact_string(),
+        StringLit::Double(value) => value.content.to_compact_string(),
+    }
+}
+
+/// Matches `location = "…"`, `location.href = "…"`, `location.assign("…")` and
+/// `location.replace("…")` starting at [position].
+/// Returns the literal and the position to continue at. An assignment of a computed value is
+/// matched without a literal, so its strings are not mistaken for a plain href.
+fn location_redirect(
+    tokens: &[Token<&str>],
+    position: usize,
+) -> Option<(Option<CompactString>, usize)> {
+    fn is_ident(token: Option<&Token<&str>>, names: &[&str]) -> bool {
+        matches!(token, Some(Token::Ident(identifier)) if names.contains(&identifier.as_ref()))
+    }
+    fn is_punct(token: Option<&Token<&str>>, punct: Punct) -> bool {
+        matches!(token, Some(Token::Punct(found)) if *found == punct)
+    }
+    fn literal_at(tokens: &[Token<&str>], position: usize) -> Option<CompactString> {
+        match tokens.get(position) {
+            Some(Token::String(value)) => Some(string_content(value)),
+            _ => None,
+        }
+    }
+    /// A literal followed by an operator like `+` is part of a computed value.
+    fn ends_statement(token: Option<&Token<&str>>) -> bool {
+        match token {
+            None | Some(Token::EoF) => true,
+            Some(Token::Punct(punct)) => matches!(
+                punct,
+                Punct::SemiColon | Punct::CloseBrace | Punct::CloseParen | Punct::Comma
+            ),
+            Some(_) => true,
+        }
+    }
+
+    if !is_ident(tokens.get(position), &["location"]) {
+        return None;
+    }
+    let assigned_at = if is_punct(tokens.get(position + 1), Punct::Equal) {
+        Some(position + 2)
+    } else if is_punct(tokens.get(position + 1), Punct::Period)
+        && is_ident(tokens.get(position + 2), &["href"])
+        && is_punct(tokens.get(position + 3), Punct::Equal)
+    {
+        Some(position + 4)
+    } else {
+        None
+    };
+    if let Some(at) = assigned_at {
+        let literal = literal_at(tokens, at).filter(|_| ends_statement(tokens.get(at + 1)));
+        return Some((literal, at));
+    }
+    if is_punct(tokens.get(position + 1), Punct::Period)
+        && is_ident(tokens.get(position + 2), &["assign", "replace"])
+        && is_punct(tokens.get(position + 3), Punct::OpenParen)
+        && is_punct(tokens.get(position + 5), Punct::CloseParen)
+    {
+        let literal = literal_at(tokens, position + 4)?;
+        return Some((Some(literal), position + 6));
+    }
+    None
 }
 
 #[cfg(test)]
 mod test {
-    use crate::extraction::js::extract_links;
-    const SCRIPT: &str = r###"
+    use super::{extract_links, ScriptLinkKind};
+    use compact_str::CompactString;
+    use std::collections::HashSet;
+
+    fn links(found: &[(ScriptLinkKind, &str)]) -> HashSet<(ScriptLinkKind, CompactString)> {
+        found
+            .iter()
+            .map(|(kind, link)| (*kind, CompactString::new(link)))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_hrefs() {
+        const SCRIPT: &str = r###"
         var ele = document.createElement('a');
         ele.href = 'https://a11ywatch.com';
         "###;
+        assert_eq!(
+            links(&[(ScriptLinkKind::Href, "https://a11ywatch.com")]),
+            extract_links(SCRIPT)
+        );
+    }
+
     #[test]
-    fn test() {
-        println!("{:?}", extract_links(SCRIPT))
+    fn extracts_location_redirects_with_literals() {
+        let script = include_str!("../../testdata/samples/location_redirects.js");
+        assert_eq!(
+            links(&[
+                (ScriptLinkKind::Redirect, "/assigned"),
+                (ScriptLinkKind::Redirect, "https://www.example.org/window"),
+                (ScriptLinkKind::Redirect, "href.html"),
+                (ScriptLinkKind::Redirect, "../document/href.html"),
+                (ScriptLinkKind::Redirect, "/assign?from=script"),
+                (ScriptLinkKind::Redirect, "/replace"),
+                (ScriptLinkKind::Redirect, "/without-semicolon"),
+            ]),
+            extract_links(script)
+        );
     }
 }
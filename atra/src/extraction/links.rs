@@ -65,6 +65,27 @@ impl ExtractedLink {
     //     }
     // }
 
+    /// Returns the url of an on seed or outgoing link, None for a data url.
+    pub fn url(&self) -> Option<&UrlWithDepth> {
+        match self {
+            ExtractedLink::OnSeed { url, .. } => Some(url),
+            ExtractedLink::Outgoing { url, .. } => Some(url),
+            ExtractedLink::Data { .. } => None,
+        }
+    }
+
+    /// Marks the link as found in the body of a response without a 2xx status code.
+    pub fn from_error_page(mut self) -> Self {
+        match &mut self {
+            ExtractedLink::OnSeed { extraction_method, .. }
+            | ExtractedLink::Outgoing { extraction_method, .. }
+            | ExtractedLink::Data { extraction_method, .. } => {
+                extraction_method.from_error_page = true
+            }
+        }
+        self
+    }
+
     /// Makes sure that the extracted link is nor the same as the base link.
     pub fn is_not(&self, url: &UrlWithDepth) -> bool {
         match self {
@@ -13,7 +13,9 @@
 // limitations under the License.
 
 use crate::extraction::extractor_method::ExtractorMethod;
+use crate::extraction::feed::FeedLinkOrigin;
 use crate::extraction::html::LinkOrigin;
+use crate::extraction::js::ScriptLinkKind;
 use serde::{Deserialize, Serialize};
 
 /// Holds information about the used extraction information
@@ -22,11 +24,18 @@ pub struct ExtractorMethodHint {
     pub used_method: ExtractorMethod,
     #[serde(default)]
     pub meta: Option<ExtractorMethodMeta>,
+    /// The link was found in the body of a response without a 2xx status code.
+    #[serde(default)]
+    pub from_error_page: bool,
 }
 
 impl ExtractorMethodHint {
     pub fn new(used_method: ExtractorMethod, meta: Option<ExtractorMethodMeta>) -> Self {
-        Self { used_method, meta }
+        Self {
+            used_method,
+            meta,
+            from_error_page: false,
+        }
     }
 
     pub fn new_with_meta(used_method: ExtractorMethod, meta: ExtractorMethodMeta) -> Self {
@@ -46,6 +55,8 @@ pub enum ExtractorMethodMeta {
         path: String,
         underlying: Box<ExtractorMethodHint>,
     },
+    Feed(FeedLinkOrigin),
+    JavaScript(ScriptLinkKind),
 }
 
 pub trait ExtractorMethodMetaFactory {
@@ -12,16 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod css;
 mod deflate;
 mod errors;
 pub mod extractor;
 pub mod extractor_method;
-mod html;
+mod feed;
+pub(crate) mod html;
 mod js;
 pub mod links;
 pub mod marker;
 mod raw;
+mod structured;
 
+pub use feed::FeedLinkOrigin;
+pub use js::ScriptLinkKind;
 pub use links::ExtractedLink;
+pub use structured::StructuredExtractionConfig;
 
 pub use errors::*;
@@ -0,0 +1,243 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::BufRead;
+use ubyte::ByteUnit;
+
+/// The delimiters a csv may use, the first one is used if none of them is found.
+const CSV_DELIMITERS: [u8; 4] = [b',', b';', b'\t', b'|'];
+/// The number of lines inspected to sniff the delimiter of a csv.
+const SNIFFED_LINES: usize = 20;
+
+/// The limits of the link extraction from json and csv documents.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct StructuredExtractionConfig {
+    /// Larger documents are not parsed. (in Bytes) (default: 16MB)
+    pub max_document_size: u64,
+    /// The extraction of a document stops after this number of links. (default: 10000)
+    pub max_links: usize,
+}
+
+impl Default for StructuredExtractionConfig {
+    fn default() -> Self {
+        Self {
+            max_document_size: ByteUnit::Megabyte(16).as_u64(),
+            max_links: 10_000,
+        }
+    }
+}
+
+/// The distinct absolute http(s) urls found in a document, in the order they were found.
+#[derive(Debug, Default)]
+pub struct StructuredLinks {
+    pub links: Vec<String>,
+    /// The extraction stopped because the limit of links was reached.
+    pub truncated: bool,
+    seen: HashSet<String>,
+}
+
+impl StructuredLinks {
+    /// Adds the [value] if it is an absolute http(s) url.
+    /// Returns false if the limit of [max_links] was reached.
+    fn offer(&mut self, value: &str, max_links: usize) -> bool {
+        let value = value.trim();
+        if !is_web_url(value) || self.seen.contains(value) {
+            return true;
+        }
+        if self.links.len() >= max_links {
+            self.truncated = true;
+            return false;
+        }
+        self.seen.insert(value.to_string());
+        self.links.push(value.to_string());
+        true
+    }
+}
+
+/// Returns true if [value] is an absolute http(s) url.
+fn is_web_url(value: &str) -> bool {
+    if !value
+        .get(..4)
+        .is_some_and(|scheme| scheme.eq_ignore_ascii_case("http"))
+    {
+        return false;
+    }
+    url::Url::parse(value)
+        .is_ok_and(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+}
+
+/// Collects the strings in the fields of the json [value] that are absolute http(s) urls,
+/// at most [max_links]. The names of the fields are not inspected.
+pub fn extract_links_from_json(value: &Value, max_links: usize) -> StructuredLinks {
+    let mut found = StructuredLinks::default();
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::String(value) => {
+                if !found.offer(value, max_links) {
+                    break;
+                }
+            }
+            // Reversed to visit the values in their order.
+            Value::Array(values) => stack.extend(values.iter().rev()),
+            Value::Object(values) => stack.extend(values.values().rev()),
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+    found
+}
+
+/// Sniffs the delimiter of a csv from the first lines of the [sample]. The delimiter found
+/// the same number of times in the most lines wins, quoted values are ignored.
+fn sniff_delimiter(sample: &[u8]) -> u8 {
+    let lines: Vec<&[u8]> = sample
+        .split(|value| *value == b'\n')
+        .filter(|line| !line.trim_ascii().is_empty())
+        .take(SNIFFED_LINES)
+        .collect();
+    let mut best = (CSV_DELIMITERS[0], 0usize);
+    for delimiter in CSV_DELIMITERS {
+        let counts: Vec<usize> = lines
+            .iter()
+            .map(|line| {
+                let mut quoted = false;
+                line.iter()
+                    .filter(|value| {
+                        if **value == b'"' {
+                            quoted = !quoted;
+                        }
+                        !quoted && **value == delimiter
+                    })
+                    .count()
+            })
+            .collect();
+        let consistent = counts
+            .iter()
+            .filter(|count| **count > 0)
+            .map(|count| counts.iter().filter(|other| *other == count).count())
+            .max()
+            .unwrap_or_default();
+        if consistent > best.1 {
+            best = (delimiter, consistent);
+        }
+    }
+    best.0
+}
+
+/// Collects the cells of the csv in [reader] that are absolute http(s) urls, at most
+/// [max_links]. If the csv is malformed, the links found before the error are returned
+/// with the error.
+pub fn extract_links_from_csv<R: BufRead>(
+    mut reader: R,
+    max_links: usize,
+) -> (StructuredLinks, Option<csv::Error>) {
+    let delimiter = match reader.fill_buf() {
+        Ok(sample) => sniff_delimiter(sample),
+        Err(err) => return (StructuredLinks::default(), Some(err.into())),
+    };
+    let mut found = StructuredLinks::default();
+    let mut csv = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(reader);
+    let mut record = csv::ByteRecord::new();
+    loop {
+        match csv.read_byte_record(&mut record) {
+            Ok(true) => {
+                for cell in record.iter() {
+                    if !found.offer(&String::from_utf8_lossy(cell), max_links) {
+                        return (found, None);
+                    }
+                }
+            }
+            Ok(false) => return (found, None),
+            Err(err) => return (found, Some(err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{extract_links_from_csv, extract_links_from_json, sniff_delimiter};
+
+    #[test]
+    fn extracts_the_urls_from_nested_json() {
+        let value = serde_json::json!({
+            "self": "https://api.example.com/v1/items?page=2",
+            "items": [
+                {"id": 1, "url": "https://www.example.com/items/1", "tags": ["a", "b"]},
+                {"id": 2, "links": {"html": "http://www.example.com/items/2"}},
+                {"id": 3, "url": "https://www.example.com/items/1"}
+            ],
+            "relative": "/items/4",
+            "mail": "mailto:info@example.com",
+            "text": "see https://www.example.com/in/text"
+        });
+        let found = extract_links_from_json(&value, 100);
+        assert!(!found.truncated);
+        assert_eq!(
+            vec![
+                "https://www.example.com/items/1",
+                "http://www.example.com/items/2",
+                "https://api.example.com/v1/items?page=2",
+            ],
+            found.links
+        );
+    }
+
+    #[test]
+    fn the_json_extraction_stops_at_the_limit() {
+        let value = serde_json::json!([
+            "https://www.example.com/1",
+            "https://www.example.com/2",
+            "https://www.example.com/3"
+        ]);
+        let found = extract_links_from_json(&value, 2);
+        assert!(found.truncated);
+        assert_eq!(
+            vec!["https://www.example.com/1", "https://www.example.com/2"],
+            found.links
+        );
+    }
+
+    #[test]
+    fn sniffs_the_delimiter() {
+        assert_eq!(b',', sniff_delimiter(b"a,b,c\n1,2,3\n"));
+        assert_eq!(b';', sniff_delimiter(b"a;b;c\n\"1,5\";2;3\n"));
+        assert_eq!(b'\t', sniff_delimiter(b"a\tb\n1\t2\n"));
+        assert_eq!(b',', sniff_delimiter(b"single column\nvalue\n"));
+    }
+
+    #[test]
+    fn extracts_the_urls_from_all_cells() {
+        let csv = "name;homepage;feed\n\
+                   first;https://www.example.com/;\"https://www.example.com/feed?a=1;b=2\"\n\
+                   second; http://www.example.org/ ;none\n";
+        let (found, error) = extract_links_from_csv(csv.as_bytes(), 100);
+        assert!(error.is_none());
+        assert_eq!(
+            vec![
+                "https://www.example.com/",
+                "https://www.example.com/feed?a=1;b=2",
+                "http://www.example.org/",
+            ],
+            found.links
+        );
+    }
+}
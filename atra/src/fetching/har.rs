@@ -0,0 +1,273 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The parts of a HAR 1.2 file exported by a browser that are needed to import its
+//! responses like fetched pages.
+
+use crate::crawl::FetchTimings;
+use crate::data::RawVecData;
+use crate::fetching::FetchedRequestData;
+use crate::url::UrlWithDepth;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_LENGTH};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use thiserror::Error;
+use time::format_description::well_known::Rfc3339;
+use time::{Duration, OffsetDateTime};
+
+/// A HAR file, only the entries are read.
+#[derive(Debug, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarLog {
+    pub version: Option<String>,
+    pub entries: Vec<HarEntry>,
+}
+
+/// A single request of a HAR file with its response.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: Option<String>,
+    /// The total time of the request in milliseconds.
+    pub time: Option<f64>,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub timings: Option<HarTimings>,
+    #[serde(rename = "serverIPAddress")]
+    pub server_ip_address: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarResponse {
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<HarHeader>,
+    pub content: HarContent,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HarHeader {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HarContent {
+    pub mime_type: Option<String>,
+    /// The body, missing if the browser did not keep it.
+    pub text: Option<String>,
+    /// `base64` for binary bodies.
+    pub encoding: Option<String>,
+}
+
+/// The timings of the phases of a request in milliseconds, -1 if a phase does not apply.
+#[derive(Debug, Deserialize)]
+pub struct HarTimings {
+    pub blocked: Option<f64>,
+    pub dns: Option<f64>,
+    pub connect: Option<f64>,
+    pub ssl: Option<f64>,
+    pub send: Option<f64>,
+    pub wait: Option<f64>,
+    pub receive: Option<f64>,
+}
+
+/// Why an entry of a HAR file can not be imported.
+#[derive(Debug, Error)]
+pub enum HarEntryError {
+    #[error("The url {0} is invalid: {1}")]
+    InvalidUrl(String, crate::url::ParseError),
+    #[error("The scheme of {0} is not supported.")]
+    UnsupportedScheme(String),
+    #[error("The response of {0} has no body.")]
+    MissingBody(String),
+    #[error("The base64 body of {0} is invalid: {1}")]
+    InvalidBase64(String, data_encoding::DecodeError),
+    #[error("The status {1} of {0} is invalid.")]
+    InvalidStatus(String, u16),
+}
+
+/// An entry of a HAR file converted to a fetched request.
+#[derive(Debug)]
+pub struct ImportedResponse {
+    pub url: UrlWithDepth,
+    pub page: FetchedRequestData,
+    pub timings: Option<FetchTimings>,
+}
+
+fn milliseconds(value: f64) -> Option<Duration> {
+    (value >= 0.0).then(|| Duration::microseconds((value * 1000.0).round() as i64))
+}
+
+impl HarEntry {
+    /// Converts the entry to a fetched request, the body is kept in memory.
+    pub fn to_response(&self) -> Result<ImportedResponse, HarEntryError> {
+        let raw_url = &self.request.url;
+        let url = UrlWithDepth::from_url(raw_url.as_str())
+            .map_err(|err| HarEntryError::InvalidUrl(raw_url.clone(), err))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(HarEntryError::UnsupportedScheme(raw_url.clone()));
+        }
+        let status_code = StatusCode::from_u16(self.response.status)
+            .map_err(|_| HarEntryError::InvalidStatus(raw_url.clone(), self.response.status))?;
+        let Some(text) = self.response.content.text.as_ref() else {
+            return Err(HarEntryError::MissingBody(raw_url.clone()));
+        };
+        let body = if self
+            .response
+            .content
+            .encoding
+            .as_deref()
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("base64"))
+        {
+            data_encoding::BASE64
+                .decode(text.as_bytes())
+                .map_err(|err| HarEntryError::InvalidBase64(raw_url.clone(), err))?
+        } else {
+            text.as_bytes().to_vec()
+        };
+
+        let address = self.server_ip_address.as_deref().and_then(|address| {
+            let ip: IpAddr = address.trim_matches(['[', ']']).parse().ok()?;
+            Some(SocketAddr::new(
+                ip,
+                url.url.as_url()?.port_or_known_default()?,
+            ))
+        });
+
+        let page = FetchedRequestData {
+            content: if body.is_empty() {
+                RawVecData::None
+            } else {
+                RawVecData::from_vec(body)
+            },
+            headers: Some(self.headers()),
+            raw_headers: None,
+            status_code,
+            final_url: None,
+            address,
+            defect: false,
+            not_modified: false,
+            proxy: None,
+            rejected: None,
+            truncated: false,
+            tls_certificate: None,
+            request_headers: None,
+            http_version: None,
+        };
+        Ok(ImportedResponse {
+            url,
+            page,
+            timings: self.fetch_timings(),
+        })
+    }
+
+    /// The headers of the response. The browser stores the decoded body, the headers
+    /// describing the transfer are dropped. Pseudo headers of HTTP/2 are skipped.
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::with_capacity(self.response.headers.len());
+        for header in &self.response.headers {
+            if header.name.starts_with(':') {
+                continue;
+            }
+            let (Ok(name), Ok(value)) = (
+                HeaderName::from_bytes(header.name.as_bytes()),
+                HeaderValue::from_str(&header.value),
+            ) else {
+                log::debug!("Skip the invalid header {}: {}", header.name, header.value);
+                continue;
+            };
+            if name == CONTENT_ENCODING || name == CONTENT_LENGTH {
+                continue;
+            }
+            headers.append(name, value);
+        }
+        headers
+    }
+
+    fn fetch_timings(&self) -> Option<FetchTimings> {
+        let started_at =
+            OffsetDateTime::parse(self.started_date_time.as_deref()?, &Rfc3339).ok()?;
+        let total = milliseconds(self.time?)?;
+        let phase = |value: Option<f64>| value.and_then(milliseconds);
+        let timings = self.timings.as_ref();
+        Some(FetchTimings {
+            started_at,
+            total,
+            blocked: phase(timings.and_then(|value| value.blocked)),
+            dns: phase(timings.and_then(|value| value.dns)),
+            connect: phase(timings.and_then(|value| value.connect)),
+            ssl: phase(timings.and_then(|value| value.ssl)),
+            send: phase(timings.and_then(|value| value.send)),
+            wait: phase(timings.and_then(|value| value.wait)),
+            receive: phase(timings.and_then(|value| value.receive)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(url: &str, content: &str) -> HarEntry {
+        serde_json::from_str(&format!(
+            r#"{{
+                "request": {{ "method": "GET", "url": "{url}" }},
+                "response": {{ "status": 200, "headers": [], "content": {content} }}
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn unsupported_entries_are_rejected() {
+        assert!(matches!(
+            entry("data:text/plain,hello", r#"{ "text": "hello" }"#).to_response(),
+            Err(HarEntryError::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            entry("https://example.com/", r#"{ "mimeType": "text/html" }"#).to_response(),
+            Err(HarEntryError::MissingBody(_))
+        ));
+        assert!(matches!(
+            entry(
+                "https://example.com/",
+                r#"{ "text": "not base64!", "encoding": "base64" }"#
+            )
+            .to_response(),
+            Err(HarEntryError::InvalidBase64(..))
+        ));
+    }
+
+    #[test]
+    fn timings_are_optional() {
+        let imported = entry("https://example.com/", r#"{ "text": "" }"#)
+            .to_response()
+            .unwrap();
+        assert_eq!(None, imported.timings);
+        assert_eq!(RawVecData::None, imported.page.content);
+    }
+}
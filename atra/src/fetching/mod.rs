@@ -12,7 +12,15 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod har;
+mod raw_headers;
 mod requests;
 mod response;
+mod retry_after;
+mod validators;
+pub mod warc_record;
+pub use raw_headers::*;
 pub use requests::*;
 pub use response::*;
+pub use retry_after::*;
+pub use validators::*;
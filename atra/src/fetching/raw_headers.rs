@@ -0,0 +1,93 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::header::HeaderName;
+use serde::{Deserialize, Serialize};
+
+/// The header lines of a response in the order they were received, with every duplicate
+/// and the case of their names. Only known if the raw head of the response is available,
+/// e.g. for responses imported from warc files. Only written to the warc, everything else
+/// reads the normalized headers.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct RawHeaders(Vec<(Vec<u8>, Vec<u8>)>);
+
+impl RawHeaders {
+    /// Reads the header lines of a http [head] following its status line, the empty line
+    /// ending the head is optional. The whitespace around a value is not part of it.
+    /// Returns None if a line is not a header field, e.g. a folded line.
+    pub fn parse(head: &[u8]) -> Option<Self> {
+        let mut lines = Vec::new();
+        for line in head.split(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.is_empty() {
+                continue;
+            }
+            let colon = memchr::memchr(b':', line)?;
+            let name = &line[..colon];
+            if name.is_empty() || name.iter().any(|b| b.is_ascii_whitespace()) {
+                return None;
+            }
+            lines.push((name.to_vec(), line[colon + 1..].trim_ascii().to_vec()));
+        }
+        Some(Self(lines))
+    }
+
+    /// Removes every line of the header [name], regardless of the case of the line.
+    pub fn remove(&mut self, name: &HeaderName) {
+        self.0
+            .retain(|(found, _)| !found.eq_ignore_ascii_case(name.as_str().as_bytes()));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_slice(), value.as_slice()))
+    }
+}
+
+impl FromIterator<(Vec<u8>, Vec<u8>)> for RawHeaders {
+    fn from_iter<T: IntoIterator<Item = (Vec<u8>, Vec<u8>)>>(iter: T) -> Self {
+        Self(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RawHeaders;
+    use reqwest::header::CONTENT_LENGTH;
+
+    #[test]
+    fn keeps_the_order_the_duplicates_and_the_case() {
+        let mut headers = RawHeaders::parse(
+            b"Set-Cookie: a=1; Path=/\r\nContent-Length:  42 \r\nX-Mixed-CASE: Value\r\nset-cookie: b=2\r\nCONTENT-length: 42\r\n\r\n",
+        )
+        .unwrap();
+        headers.remove(&CONTENT_LENGTH);
+        assert_eq!(
+            vec![
+                (b"Set-Cookie".as_slice(), b"a=1; Path=/".as_slice()),
+                (b"X-Mixed-CASE", b"Value"),
+                (b"set-cookie", b"b=2"),
+            ],
+            headers.iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rejects_lines_that_are_no_header_fields() {
+        assert_eq!(None, RawHeaders::parse(b"X-Folded: a\r\n b\r\n"));
+        assert_eq!(None, RawHeaders::parse(b"X-Name : value\r\n"));
+        assert_eq!(None, RawHeaders::parse(b": value\r\n"));
+    }
+}
@@ -13,9 +13,72 @@
 // limitations under the License.
 
 use crate::data::RawVecData;
+use crate::fetching::{CacheValidators, RawHeaders};
+use crate::tls_info::CertificateInfo;
 use reqwest::header::HeaderMap;
-use reqwest::StatusCode;
+use reqwest::{StatusCode, Version};
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use thiserror::Error;
+
+/// Why the body of a response was not downloaded. Unlike a failed fetch this is final,
+/// the url is not retried.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize, Error)]
+pub enum DownloadRejection {
+    /// The mime type is denied by the download filter.
+    #[error("The content type {0} is filtered.")]
+    ContentType(String),
+    /// The body is larger than the max file size, the download was skipped or aborted.
+    #[error("The body is larger than {limit} bytes.")]
+    TooLarge { limit: u64 },
+    /// The body of the mime type is streamed without an end.
+    #[error("The body of the content type {0} is streamed without an end.")]
+    Streaming(String),
+}
+
+/// The version of the http protocol of a response.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum HttpVersion {
+    Http09,
+    Http10,
+    Http11,
+    Http2,
+    Http3,
+}
+
+impl HttpVersion {
+    /// All versions in the order of their labels.
+    pub const ALL: [HttpVersion; 5] = [
+        HttpVersion::Http09,
+        HttpVersion::Http10,
+        HttpVersion::Http11,
+        HttpVersion::Http2,
+        HttpVersion::Http3,
+    ];
+
+    /// The version as written in the status line.
+    pub const fn label(self) -> &'static str {
+        match self {
+            HttpVersion::Http09 => "HTTP/0.9",
+            HttpVersion::Http10 => "HTTP/1.0",
+            HttpVersion::Http11 => "HTTP/1.1",
+            HttpVersion::Http2 => "HTTP/2",
+            HttpVersion::Http3 => "HTTP/3",
+        }
+    }
+
+    /// The version negotiated for a response, None for versions unknown to atra.
+    pub fn from_version(version: Version) -> Option<Self> {
+        match version {
+            Version::HTTP_09 => Some(HttpVersion::Http09),
+            Version::HTTP_10 => Some(HttpVersion::Http10),
+            Version::HTTP_11 => Some(HttpVersion::Http11),
+            Version::HTTP_2 => Some(HttpVersion::Http2),
+            Version::HTTP_3 => Some(HttpVersion::Http3),
+            _ => None,
+        }
+    }
+}
 
 /// The response of a fetch.
 #[derive(Debug, Default, Clone)]
@@ -24,6 +87,9 @@ pub struct FetchedRequestData {
     pub content: RawVecData,
     /// The headers of the response. (Always None if a webdriver protocol is used for fetching.).
     pub headers: Option<HeaderMap>,
+    /// The header lines as received, None if only the normalized [headers] are known.
+    /// Only recorded for imported responses and for fetches with `faithful_headers`.
+    pub raw_headers: Option<RawHeaders>,
     /// The status code of the request.
     pub status_code: StatusCode,
     /// The final url destination after any redirects.
@@ -32,6 +98,21 @@ pub struct FetchedRequestData {
     pub address: Option<SocketAddr>,
     /// Set if there was an error
     pub defect: bool,
+    /// Set if the server confirmed that the resource did not change since the last crawl.
+    pub not_modified: bool,
+    /// The proxy serving the request, without credentials. None if fetched directly.
+    pub proxy: Option<String>,
+    /// Set if the body was not downloaded by choice, the content is empty.
+    pub rejected: Option<DownloadRejection>,
+    /// Set if only the start of a body streamed without an end was downloaded.
+    pub truncated: bool,
+    /// The certificate presented by the server, if the client records the tls info.
+    pub tls_certificate: Option<CertificateInfo>,
+    /// The headers sent with the request besides the ones added by the transport,
+    /// without credentials. None if they are not known.
+    pub request_headers: Option<HeaderMap>,
+    /// The http version of the response. None if it is not known.
+    pub http_version: Option<HttpVersion>,
 }
 
 impl FetchedRequestData {
@@ -47,10 +128,34 @@ impl FetchedRequestData {
         Self {
             content,
             headers,
+            raw_headers: None,
             status_code,
             final_url,
             address,
             defect,
+            not_modified: false,
+            proxy: None,
+            rejected: None,
+            truncated: false,
+            tls_certificate: None,
+            request_headers: None,
+            http_version: None,
+        }
+    }
+
+    /// Checks a `304 Not Modified` against the [validators] sent with the request.
+    /// A 304 without any validator sent is a lying server, the response is marked as defect.
+    pub fn resolve_not_modified(&mut self, url: &str, validators: Option<&CacheValidators>) {
+        if self.status_code != StatusCode::NOT_MODIFIED {
+            self.not_modified = false;
+            return;
+        }
+        if validators.is_some_and(|value| !value.is_empty()) {
+            self.not_modified = true;
+        } else {
+            log::warn!("{url}: Got a 304 Not Modified without sending any validator.");
+            self.not_modified = false;
+            self.defect = true;
         }
     }
 }
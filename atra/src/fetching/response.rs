@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::data::RawVecData;
-use crate::fetching::FetchedRequestData;
+use crate::fetching::{FetchedRequestData, HttpVersion};
 use crate::url::AtraUri;
 use crate::url::UrlWithDepth;
 use reqwest::header::HeaderMap;
@@ -33,6 +33,12 @@ pub struct ResponseData {
     pub status_code: StatusCode,
     /// The final destination of the page if redirects were performed.
     pub final_redirect_destination: Option<String>,
+    /// The proxy serving the request, None if fetched directly.
+    pub proxy: Option<String>,
+    /// The headers sent with the request, without credentials. None if they are not known.
+    pub request_headers: Option<HeaderMap>,
+    /// The http version of the response. None if it is not known.
+    pub http_version: Option<HttpVersion>,
 }
 
 impl ResponseData {
@@ -49,6 +55,9 @@ impl ResponseData {
             headers,
             status_code,
             final_redirect_destination,
+            proxy: None,
+            request_headers: None,
+            http_version: None,
         }
     }
 
@@ -59,6 +68,9 @@ impl ResponseData {
             headers: page_response.headers,
             status_code: page_response.status_code,
             final_redirect_destination: page_response.final_url,
+            proxy: page_response.proxy,
+            request_headers: page_response.request_headers,
+            http_version: page_response.http_version,
         }
     }
 
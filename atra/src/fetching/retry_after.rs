@@ -0,0 +1,257 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::robots::unavailable_after::parse_date;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use time::{Duration, OffsetDateTime};
+
+/// The delay if a server answers with 429 but without a `Retry-After`.
+pub const DEFAULT_RETRY_AFTER: Duration = Duration::seconds(60);
+
+/// The first byte of the payload of a throttled link state.
+/// Differs from the payloads of [crate::client::traits::FetchFailureKind].
+const THROTTLED_MARKER: u8 = 0x80;
+
+/// The first byte of the payload of a seed waiting for the re-discovery of its origin.
+const REDISCOVERY_MARKER: u8 = 0x81;
+
+/// Returns the point in time when a throttled response can be retried.
+/// A response is throttled if the status is 429 or if the status is 503 with a `Retry-After`.
+pub fn throttled_until(
+    status_code: StatusCode,
+    headers: Option<&HeaderMap>,
+    now: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let retry_after = headers
+        .and_then(|headers| headers.get(RETRY_AFTER))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, now));
+    match status_code {
+        StatusCode::TOO_MANY_REQUESTS => Some(retry_after.unwrap_or(now + DEFAULT_RETRY_AFTER)),
+        StatusCode::SERVICE_UNAVAILABLE => retry_after,
+        _ => None,
+    }
+}
+
+/// Parses the value of a `Retry-After` header, either the delay in seconds or a http date.
+/// A date in the past is interpreted as [now].
+pub fn parse_retry_after(value: &str, now: OffsetDateTime) -> Option<OffsetDateTime> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u32>() {
+        return Some(now + Duration::seconds(seconds as i64));
+    }
+    parse_date(value).map(|date| date.max(now))
+}
+
+/// The state of an url waiting for the retry after a throttled response.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ThrottledRetry {
+    /// The number of throttled responses so far.
+    pub attempts: u8,
+    /// The url is not fetched before this point in time.
+    pub not_before: OffsetDateTime,
+}
+
+impl ThrottledRetry {
+    pub fn new(attempts: u8, not_before: OffsetDateTime) -> Self {
+        Self {
+            attempts,
+            not_before,
+        }
+    }
+
+    /// Encodes the retry as payload of a link state, [not_before] is rounded up to whole seconds.
+    pub fn to_payload(&self) -> [u8; 10] {
+        encode_delayed(THROTTLED_MARKER, self.attempts, self.not_before)
+    }
+
+    /// Decodes the retry from the payload of a link state.
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let (attempts, not_before) = decode_delayed(THROTTLED_MARKER, payload)?;
+        Some(Self::new(attempts, not_before))
+    }
+
+    /// Returns true if the url can be fetched at [now].
+    pub fn is_due_at(&self, now: OffsetDateTime) -> bool {
+        self.not_before <= now
+    }
+}
+
+/// The state of a seed waiting for the next attempt to reach its origin, after every request
+/// to the origin failed to connect.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RediscoveryRetry {
+    /// The number of windows the origin was unreachable so far.
+    pub attempts: u8,
+    /// The seed is not fetched before this point in time.
+    pub not_before: OffsetDateTime,
+}
+
+impl RediscoveryRetry {
+    pub fn new(attempts: u8, not_before: OffsetDateTime) -> Self {
+        Self {
+            attempts,
+            not_before,
+        }
+    }
+
+    /// Encodes the retry as payload of a link state, [not_before] is rounded up to whole seconds.
+    pub fn to_payload(&self) -> [u8; 10] {
+        encode_delayed(REDISCOVERY_MARKER, self.attempts, self.not_before)
+    }
+
+    /// Decodes the retry from the payload of a link state.
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let (attempts, not_before) = decode_delayed(REDISCOVERY_MARKER, payload)?;
+        Some(Self::new(attempts, not_before))
+    }
+
+    /// Returns true if the seed can be fetched at [now].
+    pub fn is_due_at(&self, now: OffsetDateTime) -> bool {
+        self.not_before <= now
+    }
+}
+
+/// Encodes the [attempts] and [not_before] behind the [marker].
+pub fn encode_delayed(marker: u8, attempts: u8, not_before: OffsetDateTime) -> [u8; 10] {
+    let mut seconds = not_before.unix_timestamp();
+    if not_before.nanosecond() > 0 {
+        seconds += 1;
+    }
+    let mut payload = [0u8; 10];
+    payload[0] = marker;
+    payload[1] = attempts;
+    payload[2..].copy_from_slice(&seconds.to_be_bytes());
+    payload
+}
+
+/// Decodes the attempts and the point in time of a payload starting with the [marker].
+pub fn decode_delayed(marker: u8, payload: &[u8]) -> Option<(u8, OffsetDateTime)> {
+    if *payload.first()? != marker {
+        return None;
+    }
+    let attempts = *payload.get(1)?;
+    let seconds: [u8; 8] = payload.get(2..10)?.try_into().ok()?;
+    let not_before = OffsetDateTime::from_unix_timestamp(i64::from_be_bytes(seconds)).ok()?;
+    Some((attempts, not_before))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        parse_retry_after, throttled_until, RediscoveryRetry, ThrottledRetry, DEFAULT_RETRY_AFTER,
+    };
+    use crate::client::traits::FetchFailureKind;
+    use reqwest::header::{HeaderMap, HeaderValue, RETRY_AFTER};
+    use reqwest::StatusCode;
+    use time::{Date, Duration, Month, OffsetDateTime};
+
+    fn now() -> OffsetDateTime {
+        Date::from_calendar_date(2015, Month::October, 21)
+            .unwrap()
+            .with_hms(7, 0, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[test]
+    fn parses_seconds_and_dates() {
+        let now = now();
+        assert_eq!(
+            Some(now + Duration::seconds(120)),
+            parse_retry_after(" 120 ", now)
+        );
+        assert_eq!(
+            Some(now + Duration::minutes(28)),
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT", now)
+        );
+        assert_eq!(
+            Some(now),
+            parse_retry_after("Wed, 21 Oct 2015 06:00:00 GMT", now)
+        );
+        assert_eq!(None, parse_retry_after("soon", now));
+        assert_eq!(None, parse_retry_after("-5", now));
+    }
+
+    #[test]
+    fn detects_throttled_responses() {
+        let now = now();
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+
+        assert_eq!(
+            Some(now + Duration::seconds(30)),
+            throttled_until(StatusCode::TOO_MANY_REQUESTS, Some(&headers), now)
+        );
+        assert_eq!(
+            Some(now + DEFAULT_RETRY_AFTER),
+            throttled_until(StatusCode::TOO_MANY_REQUESTS, None, now)
+        );
+        assert_eq!(
+            Some(now + Duration::seconds(30)),
+            throttled_until(StatusCode::SERVICE_UNAVAILABLE, Some(&headers), now)
+        );
+        assert_eq!(
+            None,
+            throttled_until(StatusCode::SERVICE_UNAVAILABLE, None, now)
+        );
+        assert_eq!(None, throttled_until(StatusCode::OK, Some(&headers), now));
+    }
+
+    #[test]
+    fn can_encode_the_payload() {
+        let retry = ThrottledRetry::new(2, now() + Duration::milliseconds(1500));
+        let payload = retry.to_payload();
+        let decoded = ThrottledRetry::from_payload(&payload).unwrap();
+        assert_eq!(2, decoded.attempts);
+        assert_eq!(now() + Duration::seconds(2), decoded.not_before);
+        assert!(!decoded.is_due_at(retry.not_before));
+        assert!(FetchFailureKind::from_payload(&payload).is_none());
+        assert!(ThrottledRetry::from_payload(
+            &FetchFailureKind::Connect.to_retry_payload(1, now())
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn failed_fetches_wait_for_their_retry() {
+        let payload = FetchFailureKind::Timeout.to_retry_payload(2, now() + Duration::seconds(30));
+        assert_eq!(
+            Some(FetchFailureKind::Timeout),
+            FetchFailureKind::from_payload(&payload)
+        );
+        assert_eq!(Some(2), FetchFailureKind::attempts_from_payload(&payload));
+        assert_eq!(
+            Some(now() + Duration::seconds(30)),
+            FetchFailureKind::not_before_from_payload(&payload)
+        );
+        assert!(ThrottledRetry::from_payload(&payload).is_none());
+        assert!(RediscoveryRetry::from_payload(&payload).is_none());
+        // The payloads written before the retries were delayed are due right away.
+        assert_eq!(None, FetchFailureKind::not_before_from_payload(&[0, 1]));
+    }
+
+    #[test]
+    fn rediscoveries_are_not_throttled_retries() {
+        let retry = RediscoveryRetry::new(1, now());
+        let payload = retry.to_payload();
+        assert_eq!(Some(retry), RediscoveryRetry::from_payload(&payload));
+        assert!(ThrottledRetry::from_payload(&payload).is_none());
+        assert!(FetchFailureKind::attempts_from_payload(&payload).is_none());
+        assert!(
+            RediscoveryRetry::from_payload(&ThrottledRetry::new(1, now()).to_payload()).is_none()
+        );
+    }
+}
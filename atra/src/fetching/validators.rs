@@ -0,0 +1,100 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use reqwest::header::{
+    HeaderMap, HeaderValue, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use serde::{Deserialize, Serialize};
+
+/// The validators of a response, used for a conditional recrawl.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct CacheValidators {
+    /// The value of the `ETag` header
+    pub etag: Option<String>,
+    /// The value of the `Last-Modified` header
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    pub fn new(etag: Option<String>, last_modified: Option<String>) -> Self {
+        Self {
+            etag,
+            last_modified,
+        }
+    }
+
+    /// Extracts the validators from the [headers], returns None if there are none.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        fn read(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+            let value = headers.get(name)?.to_str().ok()?.trim();
+            if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            }
+        }
+
+        let found = Self::new(read(headers, ETAG), read(headers, LAST_MODIFIED));
+        if found.is_empty() {
+            None
+        } else {
+            Some(found)
+        }
+    }
+
+    /// Returns true if there is no validator at all.
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+
+    /// Creates the `If-None-Match` and `If-Modified-Since` headers for a conditional request.
+    pub fn to_conditional_headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        if let Some(etag) = self.etag.as_ref() {
+            if let Ok(value) = HeaderValue::from_str(etag) {
+                headers.insert(IF_NONE_MATCH, value);
+            }
+        }
+        if let Some(last_modified) = self.last_modified.as_ref() {
+            if let Ok(value) = HeaderValue::from_str(last_modified) {
+                headers.insert(IF_MODIFIED_SINCE, value);
+            }
+        }
+        headers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extracts_and_converts_validators() {
+        let mut headers = HeaderMap::new();
+        headers.insert(ETAG, HeaderValue::from_static("\"abc\""));
+        headers.insert(
+            LAST_MODIFIED,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let validators = CacheValidators::from_headers(&headers).unwrap();
+        assert_eq!(Some("\"abc\""), validators.etag.as_deref());
+        let conditional = validators.to_conditional_headers();
+        assert_eq!("\"abc\"", conditional.get(IF_NONE_MATCH).unwrap());
+        assert_eq!(
+            "Wed, 21 Oct 2015 07:28:00 GMT",
+            conditional.get(IF_MODIFIED_SINCE).unwrap()
+        );
+        assert!(CacheValidators::from_headers(&HeaderMap::new()).is_none());
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The `response` records of warc files written by other crawlers, like Heritrix, converted
+//! to fetched requests.
+
+use crate::data::RawVecData;
+use crate::fetching::{FetchedRequestData, RawHeaders};
+use crate::url::UrlWithDepth;
+use crate::warc_ext::unpack_header;
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
+use reqwest::header::{HeaderMap, CONTENT_ENCODING, CONTENT_LENGTH, TRANSFER_ENCODING};
+use std::io::Read;
+use std::net::SocketAddr;
+use thiserror::Error;
+use time::OffsetDateTime;
+use warc::header::WarcHeader;
+
+/// Why a `response` record of a warc file can not be imported.
+#[derive(Debug, Error)]
+pub enum WarcResponseError {
+    #[error("A response record has no WARC-Target-URI.")]
+    MissingTargetUri,
+    #[error("The url {0} is invalid: {1}")]
+    InvalidUrl(String, crate::url::ParseError),
+    #[error("The scheme of {0} is not supported.")]
+    UnsupportedScheme(String),
+    #[error("The response record of {0} has no WARC-Date.")]
+    MissingDate(String),
+    #[error("The response record of {0} contains no http response.")]
+    NoHttpResponse(String),
+    #[error("The chunked body of {0} is malformed.")]
+    MalformedChunks(String),
+    #[error("The content encoding {1} of {0} is not supported.")]
+    UnsupportedContentEncoding(String, String),
+    #[error("The {1} encoded body of {0} is malformed: {2}")]
+    MalformedContentEncoding(String, String, std::io::Error),
+}
+
+/// A `response` record of a warc file converted to a fetched request.
+#[derive(Debug)]
+pub struct ImportedWarcResponse {
+    pub url: UrlWithDepth,
+    /// The time the response was recorded, the WARC-Date.
+    pub fetched_at: OffsetDateTime,
+    pub page: FetchedRequestData,
+}
+
+/// Converts the [block] of a `response` record with the [header] to a fetched request,
+/// the body is kept in memory. The block holds the response as it was transferred,
+/// a chunked or compressed body is decoded and the headers describing the transfer are dropped.
+pub fn warc_response_to_fetched(
+    header: &WarcHeader,
+    block: &[u8],
+) -> Result<ImportedWarcResponse, WarcResponseError> {
+    let raw_url = match header.get_target_uri() {
+        Some(Ok(value)) => String::from_utf8_lossy(AsRef::<[u8]>::as_ref(&**value))
+            // WARC 1.1 shows the uri in angle brackets, some writers followed it.
+            .trim_matches(['<', '>'])
+            .to_string(),
+        _ => return Err(WarcResponseError::MissingTargetUri),
+    };
+    let url = UrlWithDepth::from_url(raw_url.as_str())
+        .map_err(|err| WarcResponseError::InvalidUrl(raw_url.clone(), err))?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(WarcResponseError::UnsupportedScheme(raw_url));
+    }
+    let Ok(fetched_at) = header.get_date() else {
+        return Err(WarcResponseError::MissingDate(raw_url));
+    };
+    let fetched_at = *fetched_at;
+
+    let head_end = memchr::memmem::find(block, b"\r\n\r\n")
+        .filter(|_| block.starts_with(b"HTTP/"))
+        .ok_or_else(|| WarcResponseError::NoHttpResponse(raw_url.clone()))?;
+    let (status_code, mut headers) = unpack_header(&block[..head_end])
+        .ok_or_else(|| WarcResponseError::NoHttpResponse(raw_url.clone()))?;
+    // The status line is always followed by a line break, the head ends with one.
+    let mut raw_headers = memchr::memmem::find(&block[..head_end + 2], b"\r\n")
+        .and_then(|status_end| RawHeaders::parse(&block[status_end + 2..head_end + 2]));
+    let mut body = block[head_end + 4..].to_vec();
+
+    if has_token(&headers, TRANSFER_ENCODING, "chunked") {
+        body = dechunk(&body).ok_or_else(|| WarcResponseError::MalformedChunks(raw_url.clone()))?;
+    }
+    if let Some(encoding) = headers
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim().to_ascii_lowercase())
+    {
+        body = decode_content(&body, &encoding).map_err(|err| match err {
+            Some(err) => {
+                WarcResponseError::MalformedContentEncoding(raw_url.clone(), encoding, err)
+            }
+            None => WarcResponseError::UnsupportedContentEncoding(raw_url.clone(), encoding),
+        })?;
+    }
+    headers.remove(CONTENT_ENCODING);
+    headers.remove(CONTENT_LENGTH);
+    headers.remove(TRANSFER_ENCODING);
+    if let Some(raw_headers) = raw_headers.as_mut() {
+        raw_headers.remove(&CONTENT_ENCODING);
+        raw_headers.remove(&CONTENT_LENGTH);
+        raw_headers.remove(&TRANSFER_ENCODING);
+    }
+
+    let address = header.get_ip_address().and_then(Result::ok).and_then(|ip| {
+        Some(SocketAddr::new(
+            *ip,
+            url.url.as_url()?.port_or_known_default()?,
+        ))
+    });
+
+    let page = FetchedRequestData {
+        content: if body.is_empty() {
+            RawVecData::None
+        } else {
+            RawVecData::from_vec(body)
+        },
+        headers: Some(headers),
+        raw_headers,
+        status_code,
+        final_url: None,
+        address,
+        defect: false,
+        not_modified: false,
+        proxy: None,
+        rejected: None,
+        truncated: header.get_truncated_reason().is_some(),
+        tls_certificate: None,
+        request_headers: None,
+        http_version: None,
+    };
+    Ok(ImportedWarcResponse {
+        url,
+        fetched_at,
+        page,
+    })
+}
+
+/// Returns true if the comma separated values of [name] contain the [token].
+fn has_token(headers: &HeaderMap, name: reqwest::header::HeaderName, token: &str) -> bool {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|value| value.trim().eq_ignore_ascii_case(token))
+}
+
+/// Decodes a body sent with `Transfer-Encoding: chunked`, returns None if it is malformed.
+/// The trailers are dropped.
+fn dechunk(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(body.len());
+    loop {
+        let line_end = memchr::memmem::find(body, b"\r\n")?;
+        let size = std::str::from_utf8(&body[..line_end]).ok()?;
+        // The size may be followed by chunk extensions.
+        let size = size.split(';').next()?.trim();
+        let size = usize::from_str_radix(size, 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(decoded);
+        }
+        decoded.extend_from_slice(body.get(..size)?);
+        body = body[size..].strip_prefix(b"\r\n")?;
+    }
+}
+
+/// Decodes a body with the content [encoding]. Returns Err(None) if the encoding
+/// is not supported.
+fn decode_content(body: &[u8], encoding: &str) -> Result<Vec<u8>, Option<std::io::Error>> {
+    let mut decoded = Vec::with_capacity(body.len() * 4);
+    match encoding {
+        "" | "identity" => return Ok(body.to_vec()),
+        "gzip" | "x-gzip" => MultiGzDecoder::new(body).read_to_end(&mut decoded),
+        // Some servers send a raw deflate stream instead of the zlib wrapped one.
+        "deflate" => ZlibDecoder::new(body)
+            .read_to_end(&mut decoded)
+            .or_else(|_| {
+                decoded.clear();
+                DeflateDecoder::new(body).read_to_end(&mut decoded)
+            }),
+        _ => return Err(None),
+    }
+    .map_err(Some)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use reqwest::header::CONTENT_TYPE;
+    use reqwest::StatusCode;
+    use std::io::Write;
+
+    fn response_header(url: &str) -> WarcHeader {
+        let mut header = WarcHeader::new();
+        header.target_uri_string(url).unwrap();
+        header
+            .date(OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap())
+            .unwrap();
+        header
+    }
+
+    #[test]
+    fn decodes_a_chunked_and_compressed_body() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"<html><body>Hello</body></html>")
+            .unwrap();
+        let compressed = encoder.finish().unwrap();
+        let mut block = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        let (first, second) = compressed.split_at(10);
+        for chunk in [first, second] {
+            block.extend_from_slice(format!("{:x};ext=1\r\n", chunk.len()).as_bytes());
+            block.extend_from_slice(chunk);
+            block.extend_from_slice(b"\r\n");
+        }
+        block.extend_from_slice(b"0\r\n\r\n");
+
+        let imported =
+            warc_response_to_fetched(&response_header("https://www.example.com/"), &block).unwrap();
+        assert_eq!("https://www.example.com/", imported.url.to_string());
+        assert_eq!(
+            OffsetDateTime::from_unix_timestamp(1_600_000_000).unwrap(),
+            imported.fetched_at
+        );
+        assert_eq!(StatusCode::OK, imported.page.status_code);
+        assert_eq!(
+            b"<html><body>Hello</body></html>".as_slice(),
+            imported.page.content.as_in_memory().unwrap().as_slice()
+        );
+        let headers = imported.page.headers.unwrap();
+        assert_eq!("text/html", headers.get(CONTENT_TYPE).unwrap());
+        assert!(headers.get(CONTENT_ENCODING).is_none());
+        assert!(headers.get(TRANSFER_ENCODING).is_none());
+        assert_eq!(
+            vec![(b"Content-Type".as_slice(), b"text/html".as_slice())],
+            imported
+                .page
+                .raw_headers
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn rejects_what_can_not_be_imported() {
+        let block = b"HTTP/1.1 200 OK\r\nContent-Encoding: br\r\n\r\nbody";
+        assert!(matches!(
+            warc_response_to_fetched(&response_header("https://www.example.com/"), block),
+            Err(WarcResponseError::UnsupportedContentEncoding(_, encoding)) if encoding == "br"
+        ));
+        assert!(matches!(
+            warc_response_to_fetched(&response_header("dns:www.example.com"), block),
+            Err(WarcResponseError::UnsupportedScheme(_))
+        ));
+        assert!(matches!(
+            warc_response_to_fetched(
+                &response_header("https://www.example.com/"),
+                b"20240101120000\n93.184.216.34"
+            ),
+            Err(WarcResponseError::NoHttpResponse(_))
+        ));
+        assert!(matches!(
+            warc_response_to_fetched(
+                &response_header("https://www.example.com/"),
+                b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nff\r\nshort"
+            ),
+            Err(WarcResponseError::MalformedChunks(_))
+        ));
+        assert!(matches!(
+            warc_response_to_fetched(&WarcHeader::new(), block),
+            Err(WarcResponseError::MissingTargetUri)
+        ));
+    }
+}
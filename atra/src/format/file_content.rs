@@ -19,11 +19,14 @@ use std::sync::OnceLock;
 use zip::result::ZipError;
 use zip::ZipArchive;
 
-use crate::toolkit::CursorWithLifeline;
+use crate::config::system::SniffWindowConfig;
 
 /// A trait exposing the minimum of a file content.
 /// Even if this trait allows mutable access it has to be guaranteed,
 /// that the underlying data is never changed.
+///
+/// The content can only be read in bounded chunks, the detectors can not
+/// accidentally read a whole file.
 pub trait FileContentReader {
     type InMemory: AsRef<[u8]> + Sized;
 
@@ -31,19 +34,65 @@ pub trait FileContentReader {
 
     fn len(&mut self) -> Result<u64, Self::Error>;
 
-    /// Returns true if it can cal cursor without return ing a None
+    /// Returns true if it can peek without returning a None
     fn can_read(&self) -> bool;
 
-    #[allow(clippy::needless_lifetimes)]
-    /// Get a cursor like object to read the data
-    fn cursor<'a>(
-        &'a mut self,
-    ) -> Result<Option<CursorWithLifeline<'a, impl Seek + Read>>, Self::Error>;
+    /// Reads at most [n] bytes from the start of the content.
+    fn peek(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error>;
+
+    /// Reads at most [n] bytes from the end of the content.
+    fn peek_tail(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error>;
 
     /// Returns the in memory representation is possible.
     fn as_in_memory(&mut self) -> Option<&Self::InMemory>;
 }
 
+/// The part of a file content visible to the format detection.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SniffWindow {
+    /// The start of the content
+    pub prefix: Vec<u8>,
+    /// The end of the content, never overlaps with [prefix]
+    pub suffix: Option<Vec<u8>>,
+    /// The length of the whole content
+    pub total_len: u64,
+}
+
+impl SniffWindow {
+    /// Reads the window described by [config] from [content].
+    /// Returns None if there is nothing to read.
+    pub fn read<D>(content: &mut D, config: &SniffWindowConfig) -> Result<Option<Self>, D::Error>
+    where
+        D: FileContentReader,
+    {
+        if !content.can_read() {
+            return Ok(None);
+        }
+        let total_len = content.len()?;
+        let Some(prefix) = content.peek(config.prefix_bytes)? else {
+            return Ok(None);
+        };
+        let remaining = total_len.saturating_sub(prefix.len() as u64);
+        let suffix = if config.suffix_bytes > 0 && remaining > 0 {
+            let n = remaining.min(config.suffix_bytes as u64) as usize;
+            content.peek_tail(n)?
+        } else {
+            None
+        };
+        Ok(Some(Self {
+            prefix,
+            suffix,
+            total_len,
+        }))
+    }
+
+    /// Returns true if the window does not cover the whole content.
+    pub fn is_truncated(&self) -> bool {
+        let visible = self.prefix.len() + self.suffix.as_ref().map_or(0, Vec::len);
+        (visible as u64) < self.total_len
+    }
+}
+
 pub struct ZipFileContent<'a, R>
 where
     R: Seek + Read,
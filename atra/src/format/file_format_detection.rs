@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::format::mime::MimeType;
-use crate::format::{FileContentReader, FileFormatData};
+use crate::format::{FileContentReader, FileFormatData, SniffWindow};
 use file_format::FileFormat;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -37,24 +37,27 @@ impl DetectedFileFormat {
     }
 }
 
-/// Infers the file format for some kind of data.
+/// Infers the file format for some kind of data, only the content in [window] is inspected.
 pub(crate) fn infer_file_formats<D>(
     data: &mut FileFormatData<D>,
+    window: Option<&SniffWindow>,
     mime: Option<&MimeType>,
 ) -> Option<DetectedFileFormat>
 where
     D: FileContentReader,
 {
     let mut formats = HashMap::new();
-    if let Ok(Some(value)) = data.content.cursor() {
-        match FileFormat::from_reader(value) {
-            Ok(value) => {
-                formats.insert(value, 1);
-            }
-            Err(err) => {
-                log::warn!("Failed to analyze with {err}");
+    if let Some(window) = window {
+        let mut found = FileFormat::from_bytes(&window.prefix);
+        // A truncated prefix only shows the local headers of a zip, the central directory
+        // at the end names all entries.
+        let is_generic = matches!(found, FileFormat::Zip | FileFormat::ArbitraryBinaryData);
+        if is_generic && window.prefix.starts_with(b"PK\x03\x04") {
+            if let Some(refined) = window.suffix.as_deref().and_then(zip_format_from_tail) {
+                found = refined;
             }
         }
+        formats.insert(found, 1);
     }
 
     if let Some(mime) = mime {
@@ -108,3 +111,169 @@ where
         }
     }
 }
+
+/// Guesses the format of a zip based file by the entry names of the central directory
+/// found in [tail], the last bytes of the file.
+fn zip_format_from_tail(tail: &[u8]) -> Option<FileFormat> {
+    const END_OF_CENTRAL_DIRECTORY: &[u8] = b"PK\x05\x06";
+    const CENTRAL_DIRECTORY_HEADER: &[u8] = b"PK\x01\x02";
+
+    fn read_u16(data: &[u8], at: usize) -> Option<usize> {
+        Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?) as usize)
+    }
+
+    let end = tail
+        .windows(END_OF_CENTRAL_DIRECTORY.len())
+        .rposition(|value| value == END_OF_CENTRAL_DIRECTORY)?;
+    let central_directory_size =
+        u32::from_le_bytes(tail.get(end + 12..end + 16)?.try_into().ok()?) as usize;
+    // If the central directory does not fit into the tail we use the headers that are visible.
+    let mut position = match end.checked_sub(central_directory_size) {
+        Some(value) => value,
+        None => tail
+            .windows(CENTRAL_DIRECTORY_HEADER.len())
+            .position(|value| value == CENTRAL_DIRECTORY_HEADER)?,
+    };
+
+    let mut names = Vec::new();
+    while tail.get(position..position + 4) == Some(CENTRAL_DIRECTORY_HEADER) {
+        let name_len = read_u16(tail, position + 28)?;
+        let extra_len = read_u16(tail, position + 30)?;
+        let comment_len = read_u16(tail, position + 32)?;
+        names.push(tail.get(position + 46..position + 46 + name_len)?);
+        position += 46 + name_len + extra_len + comment_len;
+    }
+
+    let has = |prefix: &[u8]| names.iter().any(|name| name.starts_with(prefix));
+    if has(b"[Content_Types].xml") {
+        if has(b"word/") {
+            return Some(FileFormat::OfficeOpenXmlDocument);
+        }
+        if has(b"xl/") {
+            return Some(FileFormat::OfficeOpenXmlSpreadsheet);
+        }
+        if has(b"ppt/") {
+            return Some(FileFormat::OfficeOpenXmlPresentation);
+        }
+        if has(b"visio/") {
+            return Some(FileFormat::OfficeOpenXmlDrawing);
+        }
+    }
+    if has(b"META-INF/container.xml") && has(b"mimetype") {
+        return Some(FileFormat::ElectronicPublication);
+    }
+    if has(b"META-INF/MANIFEST.MF") {
+        return Some(FileFormat::JavaArchive);
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::infer_file_formats;
+    use crate::config::system::SniffWindowConfig;
+    use crate::data::RawVecData;
+    use crate::format::{FileContentReader, FileFormatData, SniffWindow};
+    use camino_tempfile::Utf8TempDir;
+    use file_format::FileFormat;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// Counts every byte handed out to the detection.
+    struct CountingReader {
+        inner: RawVecData,
+        read: u64,
+    }
+
+    impl FileContentReader for CountingReader {
+        type InMemory = Vec<u8>;
+        type Error = std::io::Error;
+
+        fn len(&mut self) -> Result<u64, Self::Error> {
+            self.inner.size()
+        }
+
+        fn can_read(&self) -> bool {
+            self.inner.can_read()
+        }
+
+        fn peek(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error> {
+            let result = self.inner.peek(n)?;
+            self.read += result.as_ref().map_or(0, |value| value.len() as u64);
+            Ok(result)
+        }
+
+        fn peek_tail(&mut self, n: usize) -> Result<Option<Vec<u8>>, Self::Error> {
+            let result = self.inner.peek_tail(n)?;
+            self.read += result.as_ref().map_or(0, |value| value.len() as u64);
+            Ok(result)
+        }
+
+        fn as_in_memory(&mut self) -> Option<&Self::InMemory> {
+            None
+        }
+    }
+
+    /// A docx like zip where the office specific entries come after a large blob.
+    fn create_docx_like_zip() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("customXml/blob.bin", stored).unwrap();
+        let blob: Vec<u8> = (0..4 * 1024 * 1024u32).map(|value| (value % 251) as u8).collect();
+        writer.write_all(&blob).unwrap();
+        writer.start_file("[Content_Types].xml", stored).unwrap();
+        writer.write_all(b"<?xml version=\"1.0\"?><Types/>").unwrap();
+        writer.start_file("_rels/.rels", stored).unwrap();
+        writer.write_all(b"<?xml version=\"1.0\"?><Relationships/>").unwrap();
+        writer.start_file("word/document.xml", stored).unwrap();
+        writer.write_all(b"<?xml version=\"1.0\"?><w:document/>").unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn detect(reader: &mut CountingReader, config: SniffWindowConfig) -> (FileFormat, bool) {
+        let window = SniffWindow::read(reader, &config).unwrap().unwrap();
+        let mut data = FileFormatData::new(None, reader, None, None);
+        let detected = infer_file_formats(&mut data, Some(&window), None).unwrap();
+        (*detected.most_probable_file_format(), window.is_truncated())
+    }
+
+    #[test]
+    fn suffix_peeking_finds_the_central_directory() {
+        let root = Utf8TempDir::new().unwrap();
+        let path = root.path().join("document.bin");
+        std::fs::write(&path, create_docx_like_zip()).unwrap();
+        let len = std::fs::metadata(&path).unwrap().len();
+
+        let mut reader = CountingReader {
+            inner: RawVecData::from_external(path.clone()),
+            read: 0,
+        };
+        let (found, truncated) = detect(
+            &mut reader,
+            SniffWindowConfig {
+                prefix_bytes: 4096,
+                suffix_bytes: 0,
+            },
+        );
+        assert_ne!(FileFormat::OfficeOpenXmlDocument, found);
+        assert!(truncated);
+        assert_eq!(4096, reader.read);
+
+        let mut reader = CountingReader {
+            inner: RawVecData::from_external(path),
+            read: 0,
+        };
+        let (found, truncated) = detect(
+            &mut reader,
+            SniffWindowConfig {
+                prefix_bytes: 4096,
+                suffix_bytes: 4096,
+            },
+        );
+        assert_eq!(FileFormat::OfficeOpenXmlDocument, found);
+        assert!(truncated);
+        assert_eq!(8192, reader.read);
+        assert!(reader.read < len);
+    }
+}
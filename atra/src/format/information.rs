@@ -16,9 +16,10 @@ use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
 use crate::format::file_format_detection::{infer_file_formats, DetectedFileFormat};
 use crate::format::mime::{determine_mime_information, MimeType};
 use crate::format::supported::InterpretedProcessibleFileFormat;
-use crate::format::FileContentReader;
+use crate::format::{FileContentReader, SniffWindow};
 use crate::toolkit::extension_extractor::extract_file_extensions_from_file_name;
 use crate::url::UrlWithDepth;
+use mime::MimeIter;
 use reqwest::header::HeaderMap;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
@@ -30,6 +31,10 @@ pub struct AtraFileInformation {
     pub format: InterpretedProcessibleFileFormat,
     pub mime: Option<MimeType>,
     pub detected: Option<DetectedFileFormat>,
+    /// True if the detection only saw a part of the content. Not encoded with the file
+    /// information, a stored result keeps it in [CrawlResultMeta::sniff_window_truncated].
+    #[serde(skip)]
+    pub sniff_window_truncated: bool,
 }
 
 impl AtraFileInformation {
@@ -43,11 +48,12 @@ impl AtraFileInformation {
             format,
             mime,
             detected,
+            sniff_window_truncated: false,
         }
     }
 
     /// Determines the file format for some data.
-    /// Does not change the
+    /// Only the sniff window configured in the system config is read from the content.
     pub(crate) fn determine<C, D>(context: &C, data: &mut FileFormatData<D>) -> Self
     where
         C: SupportsConfigs + SupportsFileSystemAccess,
@@ -55,19 +61,66 @@ impl AtraFileInformation {
     {
         let mime = determine_mime_information(data);
 
-        let detected = infer_file_formats(data, mime.as_ref());
+        let window = match SniffWindow::read(&mut *data.content, &context.configs().system.sniff_window) {
+            Ok(value) => value,
+            Err(err) => {
+                log::warn!("Failed to read the content for the format detection: {err}");
+                None
+            }
+        };
+
+        let detected = infer_file_formats(data, window.as_ref(), mime.as_ref());
 
         let format = InterpretedProcessibleFileFormat::guess(
             data,
+            window.as_ref(),
             mime.as_ref(),
             detected.as_ref(),
-            context,
         );
 
         Self {
             format,
             detected,
             mime,
+            sniff_window_truncated: window.is_some_and(|value| value.is_truncated()),
+        }
+    }
+
+    /// Restores the file information of an archived response from its [headers].
+    /// Only the `Content-Type` is considered, the body is never inspected. Data
+    /// that was archived as base64 is always of an unknown format.
+    pub fn restore(headers: Option<&HeaderMap>, is_base64: bool) -> Self {
+        let mime = headers
+            .and_then(|value| value.get(reqwest::header::CONTENT_TYPE))
+            .and_then(|value| value.to_str().ok())
+            .map(|value| {
+                MimeIter::new(value)
+                    .filter_map(|value| value.ok())
+                    .collect::<Vec<_>>()
+            })
+            .and_then(|value| (!value.is_empty()).then(|| MimeType::new(value)));
+
+        let format = if is_base64 {
+            InterpretedProcessibleFileFormat::Unknown
+        } else if let Some(found) = mime
+            .as_ref()
+            .and_then(InterpretedProcessibleFileFormat::guess_by_mime)
+        {
+            found
+        } else if mime
+            .as_ref()
+            .is_some_and(|value| value.iter().any(|value| value.type_() == mime::TEXT))
+        {
+            InterpretedProcessibleFileFormat::Decodeable
+        } else {
+            InterpretedProcessibleFileFormat::Unsupported
+        };
+
+        Self {
+            format,
+            mime,
+            detected: None,
+            sniff_window_truncated: false,
         }
     }
 
@@ -164,3 +217,38 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use crate::format::file_format_detection::DetectedFileFormat;
+    use crate::format::mime::MimeType;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
+    use serde::Serialize;
+
+    /// The file information before the sniff window.
+    #[derive(Serialize)]
+    struct OldAtraFileInformation {
+        format: InterpretedProcessibleFileFormat,
+        mime: Option<MimeType>,
+        detected: Option<DetectedFileFormat>,
+    }
+
+    #[test]
+    fn the_file_information_of_an_older_version_is_decoded() {
+        let old = bincode::serialize(&OldAtraFileInformation {
+            format: InterpretedProcessibleFileFormat::HTML,
+            mime: None,
+            detected: None,
+        })
+        .unwrap();
+        let decoded: AtraFileInformation = bincode::deserialize(&old).unwrap();
+        assert_eq!(InterpretedProcessibleFileFormat::HTML, decoded.format);
+        assert!(!decoded.sniff_window_truncated);
+
+        let mut current =
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::HTML, None, None);
+        current.sniff_window_truncated = true;
+        assert_eq!(old, bincode::serialize(&current).unwrap());
+    }
+}
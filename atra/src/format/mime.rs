@@ -22,13 +22,17 @@ use encoding_rs::Encoding;
 use itertools::Itertools;
 pub use mime::*;
 use mime::{Mime, MimeIter, Name, Params};
+use schemars::JsonSchema;
 use scraper::Html;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
-#[derive(Debug, Clone, PartialOrd, PartialEq, Eq, Ord, Hash, Serialize, Deserialize)]
+#[derive(
+    Debug, Clone, PartialOrd, PartialEq, Eq, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct MimeType {
     #[serde(with = "for_vec")]
+    #[schemars(with = "Vec<String>")]
     types: Vec<Mime>,
 }
 
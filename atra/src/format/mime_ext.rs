@@ -47,6 +47,8 @@ macro_rules! mime_declarations {
 mime_declarations! {
     APPLICATION_ZIP: "application" / "zip",
     APPLICATION_XML: "application" / "xml",
+    APPLICATION_RSS_XML: "application" / "rss" + "xml",
+    APPLICATION_ATOM_XML: "application" / "atom" + "xml",
     APPLICATION_RTF: "application" / "rtf",
     APPLICATION_OOXML_STAR: "application" / "vnd.openxmlformats-officedocument.wordprocessingml.*",
     APPLICATION_OOXML_DOCX: "application" / "vnd.openxmlformats-officedocument.wordprocessingml.document",
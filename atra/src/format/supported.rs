@@ -12,22 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
 use crate::format::file_format_detection::DetectedFileFormat;
 use crate::format::mime::MimeType;
-use crate::format::{mime_ext, FileContentReader, FileFormatData};
+use crate::format::{mime_ext, FileContentReader, FileFormatData, SniffWindow};
 use file_format::{FileFormat, Kind};
 use mime::Mime;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::cmp::max;
-use std::io::{Read};
+use std::cmp::min;
 use std::str::FromStr;
 use strum::{Display, EnumIs};
 // https://gonze.com/playlists/playlist-format-survey.html#M3U
 
 /// The inferred processable, type for a complete page for this crawler.
 /// Does not give detailed information about the real type.
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Display, EnumIs)]
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, JsonSchema, Display, EnumIs,
+)]
 pub enum InterpretedProcessibleFileFormat {
     HTML,
     PDF,
@@ -57,6 +58,12 @@ pub enum InterpretedProcessibleFileFormat {
     /// Usually a binary format. But can be anything that can not be decoded by normal means. (Like a ZIP-File)
     Unsupported,
     Unknown, // todo: Add identifier for binary
+
+    // New variants are appended, the stored results encode the index of the variant.
+    CSS,
+
+    /// An RSS or Atom feed
+    Feed,
 }
 
 impl InterpretedProcessibleFileFormat {
@@ -67,12 +74,21 @@ impl InterpretedProcessibleFileFormat {
         )
     }
 
+    /// Returns true if the format is already compressed, compressing it again gains nothing.
+    pub fn is_compressed(&self) -> bool {
+        matches!(
+            self,
+            Self::ZIP | Self::OOXML | Self::ODF | Self::IMAGE | Self::PDF
+        )
+    }
+
     pub fn fallback_mime_type_for_warc(&self) -> &Mime {
         match self {
             InterpretedProcessibleFileFormat::ZIP => &mime_ext::APPLICATION_ZIP,
             InterpretedProcessibleFileFormat::HTML => &mime::TEXT_HTML,
             InterpretedProcessibleFileFormat::PDF => &mime::APPLICATION_PDF,
             InterpretedProcessibleFileFormat::JavaScript => &mime::APPLICATION_JAVASCRIPT,
+            InterpretedProcessibleFileFormat::CSS => &mime::TEXT_CSS,
             InterpretedProcessibleFileFormat::PlainText => &mime::TEXT_PLAIN,
             InterpretedProcessibleFileFormat::JSON => &mime::APPLICATION_JSON,
             InterpretedProcessibleFileFormat::XML => &mime_ext::APPLICATION_XML,
@@ -82,6 +98,7 @@ impl InterpretedProcessibleFileFormat {
             InterpretedProcessibleFileFormat::IMAGE => &mime::IMAGE_STAR,
             InterpretedProcessibleFileFormat::SVG => &mime::IMAGE_SVG,
             InterpretedProcessibleFileFormat::MP3Url => &mime_ext::AUDIO_MP3_URL,
+            InterpretedProcessibleFileFormat::Feed => &mime_ext::APPLICATION_RSS_XML,
             InterpretedProcessibleFileFormat::StructuredPlainText
             | InterpretedProcessibleFileFormat::ProgrammingLanguage => &mime::TEXT_STAR,
             InterpretedProcessibleFileFormat::Unsupported
@@ -131,6 +148,55 @@ fn html_heuristic(to_check: &[u8]) -> bool {
     }
 }
 
+/// The namespace of Atom 1.0
+const ATOM_NAMESPACE: &[u8] = b"http://www.w3.org/2005/Atom";
+/// The namespace of RSS 1.0
+const RSS_1_NAMESPACE: &[u8] = b"http://purl.org/rss/1.0/";
+
+/// Checks if the root element in [to_check] is the root of an RSS or Atom feed.
+fn feed_heuristic(to_check: &[u8]) -> bool {
+    #[inline(always)]
+    fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|window| window == needle)
+    }
+
+    let mut rest = to_check.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(to_check);
+    let root = loop {
+        rest = rest.trim_ascii_start();
+        if let Some(comment) = rest.strip_prefix(b"<!--") {
+            let Some(end) = comment.windows(3).position(|value| value == b"-->") else {
+                return false;
+            };
+            rest = &comment[end + 3..];
+        } else if rest.starts_with(b"<?") || rest.starts_with(b"<!") {
+            let Some(end) = rest.iter().position(|value| *value == b'>') else {
+                return false;
+            };
+            rest = &rest[end + 1..];
+        } else if let Some(element) = rest.strip_prefix(b"<") {
+            let end = element
+                .iter()
+                .position(|value| value.is_ascii_whitespace() || matches!(value, b'>' | b'/'))
+                .unwrap_or(element.len());
+            break &element[..end];
+        } else {
+            return false;
+        }
+    };
+    let local_name = match root.iter().rposition(|value| *value == b':') {
+        Some(idx) => &root[idx + 1..],
+        None => root,
+    };
+    match local_name {
+        b"rss" => true,
+        b"feed" => contains(to_check, ATOM_NAMESPACE),
+        b"RDF" => contains(to_check, RSS_1_NAMESPACE),
+        _ => false,
+    }
+}
+
 macro_rules! supports_method {
     ($(
         $typ: ident: $pattern:pat $(if $guard:expr)? $(,)?
@@ -176,13 +242,14 @@ impl InterpretedProcessibleFileFormat {
         PDF: "pdf"
         RTF: "rtf"
         JavaScript: "js"
+        CSS: "css"
         PlainText: "txt"
         JSON: "json"
+        Feed: "rss" | "atom"
         XML: "xml"
         OOXML: "xslx" | "docx" | "pptx"
         ODF: "odt"|"ods"|"odp"|"odg"|"odc"|"odf"|"odi"|"odm"|"ott"|"ots"|"otp"|"otg"|"otf"|"oth"|"oti"|"otc"
         StructuredPlainText: "csv"
-        ProgrammingLanguage: "css"
         ZIP: "zip"|"jar"|"gz"
     }
 
@@ -192,22 +259,40 @@ impl InterpretedProcessibleFileFormat {
         PDF: (_, "pdf", _)
         RTF: (_, "rdf", _)
         JavaScript: (_, "javascript", _)
+        CSS: (_, "css", _)
         PlainText: ("text", "plain", _)
         JSON: (_, "json", _) | (_, _, Some("json"))
+        Feed: (_, "rss", Some("xml")) | (_, "atom", Some("xml"))
         XML: (_, "xml", _) | (_, _, Some("xml"))
-        ProgrammingLanguage: (_, "css", _)
         StructuredPlainText: (_, "csv", _)
     }
 
-    /// Tries to guess the supported file type.
-    pub fn guess<C, D>(
+    /// Tries to guess the supported file type, only the content in [window] is inspected.
+    /// XML with the root element of a feed is a [InterpretedProcessibleFileFormat::Feed].
+    pub fn guess<D>(
         data: &mut FileFormatData<D>,
+        window: Option<&SniffWindow>,
+        mime: Option<&MimeType>,
+        file_format: Option<&DetectedFileFormat>,
+    ) -> InterpretedProcessibleFileFormat
+    where
+        D: FileContentReader,
+    {
+        let found = Self::guess_without_feed_sniffing(data, window, mime, file_format);
+        if found == Self::XML && window.is_some_and(|value| feed_heuristic(&value.prefix)) {
+            Self::Feed
+        } else {
+            found
+        }
+    }
+
+    fn guess_without_feed_sniffing<D>(
+        data: &mut FileFormatData<D>,
+        window: Option<&SniffWindow>,
         mime: Option<&MimeType>,
         file_format: Option<&DetectedFileFormat>,
-        context: &C,
     ) -> InterpretedProcessibleFileFormat
     where
-        C: SupportsFileSystemAccess + SupportsConfigs,
         D: FileContentReader,
     {
         let mut is_text = false;
@@ -302,7 +387,12 @@ impl InterpretedProcessibleFileFormat {
                 FileFormat::Empty => return InterpretedProcessibleFileFormat::Unsupported,
                 // FileFormat::PlainText => {/* Plaintext has to be handles below due to HTML etc. */}
                 other => {
-                    if other.media_type().contains("+xml") {
+                    if matches!(
+                        other.media_type(),
+                        "application/rss+xml" | "application/atom+xml"
+                    ) {
+                        return InterpretedProcessibleFileFormat::Feed;
+                    } else if other.media_type().contains("+xml") {
                         return InterpretedProcessibleFileFormat::XML;
                     } else {
                         match other.kind() {
@@ -345,50 +435,72 @@ impl InterpretedProcessibleFileFormat {
             }
         }
 
-        fn guess_if_html<const HAYSTACK_SIZE: usize, D>(
-            page: &mut FileFormatData<D>,
-        ) -> bool
-        where
-            D: FileContentReader,
-        {
-            if let Ok(Some(mut reader)) = page.content.cursor() {
-                let mut haystack = [0u8; HAYSTACK_SIZE];
-                match reader.read(&mut haystack) {
-                    Ok(_) => html_heuristic(&haystack),
-                    Err(_) => false,
-                }
-            } else {
-                false
-            }
-        }
-
-        // Grabbing straws
+        // Grabbing straws, only the start of the content is considered.
 
         if is_text {
-            if guess_if_html::<512, _>(data) {
+            let haystack = window.map_or(&[][..], |value| value.prefix.as_slice());
+            if html_heuristic(&haystack[..min(512, haystack.len())]) {
                 Self::HTML
             } else {
                 Self::Decodeable
             }
-        } else if let Ok(Some(reader)) = data.content.cursor() {
-            let mut result = Vec::new();
-            match reader
-                .take(max(context.configs().system.max_file_size_in_memory, 512))
-                .read_to_end(&mut result)
-            {
-                Ok(_) => {
-                    if result.starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
-                        Self::ZIP
-                    } else if html_heuristic(&result) {
-                        Self::HTML
-                    } else {
-                        Self::Unknown
-                    }
-                }
-                Err(_) => Self::Unknown,
+        } else if let Some(window) = window {
+            if window.prefix.starts_with(&[0x50, 0x4b, 0x03, 0x04]) {
+                Self::ZIP
+            } else if html_heuristic(&window.prefix) {
+                Self::HTML
+            } else {
+                Self::Unknown
             }
         } else {
             Self::Unknown
         }
     }
+    /// Guesses the supported file type only by the [mime], without looking at any data.
+    pub(crate) fn guess_by_mime(mime: &MimeType) -> Option<InterpretedProcessibleFileFormat> {
+        mime.iter().find_map(Self::mime_2_supported_file_format)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{feed_heuristic, InterpretedProcessibleFileFormat};
+
+    #[test]
+    fn the_variants_keep_their_encoding() {
+        for (format, index) in [
+            (InterpretedProcessibleFileFormat::HTML, 0u32),
+            (InterpretedProcessibleFileFormat::PlainText, 3),
+            (InterpretedProcessibleFileFormat::Unknown, 17),
+            (InterpretedProcessibleFileFormat::CSS, 18),
+        ] {
+            assert_eq!(
+                index.to_le_bytes().as_slice(),
+                bincode::serialize(&format).unwrap(),
+                "{format}"
+            );
+        }
+    }
+
+    #[test]
+    fn sniffs_the_root_of_feeds() {
+        assert!(feed_heuristic(
+            b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<!-- generated -->\n<rss version=\"2.0\"><channel>"
+        ));
+        assert!(feed_heuristic(
+            b"<?xml version=\"1.0\" encoding=\"utf-8\"?><feed xmlns=\"http://www.w3.org/2005/Atom\">"
+        ));
+        assert!(feed_heuristic(
+            b"<atom:feed xmlns:atom=\"http://www.w3.org/2005/Atom\">"
+        ));
+        assert!(feed_heuristic(
+            b"<rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\" xmlns=\"http://purl.org/rss/1.0/\">"
+        ));
+        assert!(!feed_heuristic(b"<feed><entry/></feed>"));
+        assert!(!feed_heuristic(
+            b"<?xml version=\"1.0\"?><urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">"
+        ));
+        assert!(!feed_heuristic(b"<!-- <rss> -->"));
+        assert!(!feed_heuristic(b"rss"));
+    }
 }
@@ -14,8 +14,10 @@
 
 use crate::contexts::traits::SupportsStopwordsRegistry;
 use crate::contexts::BaseContext;
+use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::gdbr::scraper_ext::Text;
 use crate::html::{HtmlTag, HtmlTagCategory};
+use crate::toolkit::text_normalization::{normalize_text, TextNormalizationConfig};
 use crate::toolkit::LanguageInformation;
 #[cfg(test)]
 #[allow(unused_imports)]
@@ -30,6 +32,7 @@ use liblinear::Model;
 use scraper::{Html, Node};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
 use std::cell::Cell;
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
@@ -423,6 +426,19 @@ where
     }
 }
 
+/// The score of a gdbr classifier for the text sample of a document.
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GdbrScore(pub f64);
+
+impl Eq for GdbrScore {}
+
+impl PartialEq for GdbrScore {
+    fn eq(&self, other: &Self) -> bool {
+        float_cmp::approx_eq!(f64, self.0, other.0)
+    }
+}
+
 #[derive(Clone)]
 #[repr(transparent)]
 pub struct ScoredNodeRef<'a, T> {
@@ -518,18 +534,24 @@ where
         }
     }
 
+    /// The classifier sees the text in the same normalization as the exported text.
+    fn predict_normalized(&self, text: &str) -> f64 {
+        let normalized = normalize_text(text, None, TextNormalizationConfig::default());
+        self.predict(&normalized.text).unwrap()
+    }
+
     fn filter_fkt_without_type_filter<'a>(
         &self,
         node: NodeRef<'a, Node>,
     ) -> Option<(f64, NodeRef<'a, Node>)> {
         match node.value() {
             Node::Text(text) => {
-                let result = self.predict(text.deref()).unwrap();
+                let result = self.predict_normalized(text.deref());
                 (!result.is_nan() && result >= self.threshold).then_some((result, node))
             }
             Node::Element(_) => {
                 let values = Text::traverse(&node).join(" ");
-                let result = self.predict(&values).unwrap();
+                let result = self.predict_normalized(&values);
                 (!result.is_nan() && result >= self.threshold).then_some((result, node))
             }
             _ => None,
@@ -633,6 +655,33 @@ where
         }
     }
 
+    /// Scores the text [sample] of a document, html is reduced to its text first.
+    /// Returns None for formats without plain text or if the sample can not be scored.
+    pub fn score_document(
+        &self,
+        format: InterpretedProcessibleFileFormat,
+        sample: &str,
+    ) -> Option<GdbrScore> {
+        let text = match format {
+            InterpretedProcessibleFileFormat::HTML => {
+                Cow::Owned(Html::parse_document(sample).root_element().text().join(" "))
+            }
+            InterpretedProcessibleFileFormat::PlainText
+            | InterpretedProcessibleFileFormat::StructuredPlainText
+            | InterpretedProcessibleFileFormat::Decodeable => Cow::Borrowed(sample),
+            _ => return None,
+        };
+        let normalized = normalize_text(&text, None, TextNormalizationConfig::default());
+        match self.predict(&normalized.text) {
+            Ok(score) if !score.is_nan() => Some(GdbrScore(score)),
+            Ok(_) => None,
+            Err(err) => {
+                log::debug!("Failed to score the document: {err}");
+                None
+            }
+        }
+    }
+
     /// Removes the gbr from the parsed html
     pub fn remove_gdbr(&self, html: &mut Html) {
         if let Some(found) = self.get_most_probable(&html) {
@@ -650,9 +699,17 @@ where
 
 #[cfg(test)]
 mod test {
+    use crate::config::Config;
+    use crate::data::Decoded;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
     use crate::gdbr::identifier::{FilterMode, GdbrIdentifier};
     use crate::gdbr::scraper_ext::Text;
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::toolkit::{detect_language, read_text_sample, text_sample_limit};
     use camino::Utf8PathBuf;
+    use camino_tempfile::NamedUtf8TempFile;
+    use encoding_rs::UTF_8;
     use isolang::Language;
     use itertools::Itertools;
     use liblinear::parameter::serde::GenericParameters;
@@ -756,4 +813,48 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn off_memory_documents_are_classified_like_in_memory_documents() {
+        let identifier =
+            GdbrIdentifier::new(create_german_gdbr_svm(), 0.1, 0.5, FilterMode::OnMaxScore);
+        let mut html = String::from("<html><body>");
+        while html.len() < 3 * 1024 * 1024 {
+            html.push_str(
+                "<p>Wir verwenden Cookies und verarbeiten Ihre personenbezogenen Daten gemäß der \
+                 Datenschutz-Grundverordnung. Sie können Ihre Einwilligung jederzeit widerrufen, \
+                 Größe und Maß bleiben unverändert: äöüß €.</p>",
+            );
+        }
+        html.push_str("</body></html>");
+
+        let file = NamedUtf8TempFile::new().unwrap();
+        std::fs::write(file.path(), &html).unwrap();
+        let in_memory = Decoded::new_in_memory(html.clone(), UTF_8, false);
+        let off_memory = Decoded::new_off_memory(file.path().to_path_buf(), UTF_8, false);
+
+        let context = TestContext::new(Config::default(), FakeClientProvider::new());
+        let limit = text_sample_limit(&context);
+        let in_memory_sample = read_text_sample(&in_memory, limit).unwrap().unwrap();
+        let off_memory_sample = read_text_sample(&off_memory, limit).unwrap().unwrap();
+        assert!(off_memory_sample.len() <= limit);
+        assert_eq!(in_memory_sample, off_memory_sample);
+
+        let format = InterpretedProcessibleFileFormat::HTML;
+        let in_memory_score = identifier.score_document(format, &in_memory_sample);
+        assert!(in_memory_score.is_some());
+        assert_eq!(
+            in_memory_score,
+            identifier.score_document(format, &off_memory_sample)
+        );
+
+        let file_information = AtraFileInformation::new(format, None, None);
+        let in_memory_language = detect_language(&context, &file_information, &in_memory)
+            .unwrap()
+            .unwrap();
+        let off_memory_language = detect_language(&context, &file_information, &off_memory)
+            .unwrap()
+            .unwrap();
+        assert_eq!(in_memory_language.lang(), off_memory_language.lang());
+    }
 }
@@ -0,0 +1,190 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::data::DataCompression;
+use crate::toolkit::digest::LabeledXxh128Digester;
+use camino::{Utf8Path, Utf8PathBuf};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, ErrorKind};
+use std::sync::Mutex;
+
+/// Stores files by the digest of their content, identical files are stored only once.
+///
+/// A file with the digest `ABCDEF...` is stored as `<root>/xxh128/AB/CD/ABCDEF...`, the number
+/// of references to it is kept in `<root>/xxh128/AB/CD/ABCDEF....refs`. The file is deleted
+/// when the last reference is released.
+#[derive(Debug)]
+pub struct ContentAddressedStore {
+    root: Utf8PathBuf,
+    lock: Mutex<()>,
+}
+
+impl ContentAddressedStore {
+    /// The name of the folder in the big files folder.
+    pub const FOLDER_NAME: &'static str = "xxh128";
+
+    pub fn new(big_file_folder: impl AsRef<Utf8Path>) -> Self {
+        Self {
+            root: big_file_folder.as_ref().join(Self::FOLDER_NAME),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Returns true iff [path] points into this store.
+    pub fn contains_path(&self, path: impl AsRef<Utf8Path>) -> bool {
+        path.as_ref().starts_with(&self.root)
+    }
+
+    /// The digest of the content of [path] without label and padding.
+    fn digest_of(path: &Utf8Path) -> io::Result<String> {
+        let mut digester = LabeledXxh128Digester::default();
+        io::copy(&mut BufReader::new(File::open(path)?), &mut digester)?;
+        let digest = String::from_utf8(digester.finish())
+            .expect("The digest is always base32 encoded.");
+        Ok(digest
+            .trim_start_matches("XXH128:")
+            .trim_end_matches('=')
+            .to_string())
+    }
+
+    /// The path of the file with [digest].
+    fn path_for(&self, digest: &str) -> Utf8PathBuf {
+        self.root
+            .join(&digest[..2])
+            .join(&digest[2..4])
+            .join(digest)
+    }
+
+    fn references_path(path: &Utf8Path) -> Utf8PathBuf {
+        path.with_extension("refs")
+    }
+
+    fn read_references(path: &Utf8Path) -> io::Result<u64> {
+        match std::fs::read_to_string(path) {
+            Ok(value) => value
+                .trim()
+                .parse()
+                .map_err(|err| io::Error::new(ErrorKind::InvalidData, err)),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(0),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Moves the completely written file at [path] into the store and returns its new path.
+    /// If the same content is already stored, [path] is deleted and a reference is added.
+    /// The stored file keeps the [DataCompression] recorded in the name of [path].
+    pub fn insert(&self, path: impl AsRef<Utf8Path>) -> io::Result<Utf8PathBuf> {
+        let path = path.as_ref();
+        let mut target = self.path_for(&Self::digest_of(path)?);
+        if let Some(compression) = DataCompression::of_path(path) {
+            target = compression.apply_to_path(target);
+        }
+        let references_path = Self::references_path(&target);
+        let _guard = self.lock.lock().unwrap();
+        let references = Self::read_references(&references_path)?;
+        if references > 0 && target.exists() {
+            log::debug!("The content of {path} is already stored as {target}.");
+            std::fs::remove_file(path)?;
+        } else {
+            std::fs::create_dir_all(target.parent().unwrap())?;
+            std::fs::rename(path, &target)?;
+        }
+        std::fs::write(references_path, (references + 1).to_string())?;
+        Ok(target)
+    }
+
+    /// The number of references to the stored file at [path].
+    pub fn references(&self, path: impl AsRef<Utf8Path>) -> io::Result<u64> {
+        let _guard = self.lock.lock().unwrap();
+        Self::read_references(&Self::references_path(path.as_ref()))
+    }
+
+    /// Releases a reference to the stored file at [path] and returns the remaining references.
+    /// The file is deleted with its last reference.
+    pub fn release(&self, path: impl AsRef<Utf8Path>) -> io::Result<u64> {
+        let path = path.as_ref();
+        let references_path = Self::references_path(path);
+        let _guard = self.lock.lock().unwrap();
+        let references = Self::read_references(&references_path)?.saturating_sub(1);
+        if references == 0 {
+            log::debug!("Delete the file {path}, it has no references left.");
+            match std::fs::remove_file(path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+            match std::fs::remove_file(references_path) {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        } else {
+            std::fs::write(references_path, references.to_string())?;
+        }
+        Ok(references)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::data::DataCompression;
+    use crate::io::content_addressed::ContentAddressedStore;
+    use camino_tempfile::Utf8TempDir;
+
+    #[test]
+    fn stores_identical_content_once() {
+        let root = Utf8TempDir::new().unwrap();
+        let store = ContentAddressedStore::new(root.path());
+        let body = vec![42u8; 3 * 1024 * 1024];
+
+        let first = root.path().join("first.dat");
+        std::fs::write(&first, &body).unwrap();
+        let second = root.path().join("second.dat");
+        std::fs::write(&second, &body).unwrap();
+        let other = root.path().join("other.dat");
+        std::fs::write(&other, b"other").unwrap();
+
+        let first = store.insert(&first).unwrap();
+        let second = store.insert(&second).unwrap();
+        let other = store.insert(&other).unwrap();
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert!(store.contains_path(&first));
+        assert!(!root.path().join("first.dat").exists());
+        assert!(!root.path().join("second.dat").exists());
+        assert_eq!(2, store.references(&first).unwrap());
+        assert_eq!(1, store.references(&other).unwrap());
+        assert_eq!(body, std::fs::read(&first).unwrap());
+
+        assert_eq!(1, store.release(&second).unwrap());
+        assert!(first.exists());
+        assert_eq!(0, store.release(&first).unwrap());
+        assert!(!first.exists());
+        assert_eq!(0, store.references(&first).unwrap());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn keeps_the_compression_of_the_file() {
+        let root = Utf8TempDir::new().unwrap();
+        let store = ContentAddressedStore::new(root.path());
+        let compressed = root.path().join("page.dat.zst");
+        std::fs::write(&compressed, b"compressed").unwrap();
+
+        let stored = store.insert(&compressed).unwrap();
+        assert_eq!(Some(DataCompression::Zstd), DataCompression::of_path(&stored));
+        assert_eq!(1, store.references(&stored).unwrap());
+        assert_eq!(0, store.release(&stored).unwrap());
+        assert!(!stored.exists());
+    }
+}
@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::session::DatFileLayout;
+use crate::io::content_addressed::ContentAddressedStore;
 use crate::io::errors::{ErrorWithPath, ToErrorWithPath};
 use crate::io::serial::{SerialProvider, SerialProviderKind, SerialValue};
 use crate::io::templating::{
@@ -19,6 +21,7 @@ use crate::io::templating::{
 };
 use crate::io::unique_path_provider::{UniquePathProvider, UniquePathProviderWithTemplate};
 use crate::stores::warc::WarcFilePathProvider;
+use crate::warc_ext::WarcInfo;
 use byteorder::WriteBytesExt;
 use camino::{Utf8Path, Utf8PathBuf};
 use regex::Regex;
@@ -39,6 +42,9 @@ pub trait AtraFS {
     /// Builds the path to the data-file with a given name
     fn get_unique_path_for_data_file(&self, path: impl AsRef<Utf8Path>) -> Utf8PathBuf;
 
+    /// Moves the completely written data file at [path] to its final location and returns it.
+    fn finalize_data_file(&self, path: Utf8PathBuf) -> io::Result<Utf8PathBuf>;
+
     /// Deletes a datafile
     fn cleanup_data_file(&self, path: impl AsRef<Utf8Path>) -> io::Result<()>;
 
@@ -55,9 +61,14 @@ pub struct FileSystemAccess {
     collection_root: Utf8PathBuf,
     worker_base: FileNameTemplate,
     big_file: UniquePathProviderWithTemplate,
+    content_addressed: Option<ContentAddressedStore>,
+    warc_info: Option<Arc<WarcInfo>>,
     filesystem_lock: Mutex<()>,
 }
 
+/// The file in the big files folder recording its [DatFileLayout].
+pub(crate) const LAYOUT_FILE_NAME: &str = "layout";
+
 impl FileSystemAccess {
     pub fn new(
         service: String,
@@ -65,6 +76,7 @@ impl FileSystemAccess {
         crawl_job_id: u64,
         output_folder: Utf8PathBuf,
         big_file_folder: Utf8PathBuf,
+        layout: DatFileLayout,
     ) -> Result<Self, ErrorWithPath> {
         let collection_root = output_folder.join(&collection);
         if !collection_root.exists() {
@@ -76,6 +88,13 @@ impl FileSystemAccess {
         if !big_file_folder.exists() {
             std::fs::create_dir_all(&big_file_folder).to_error_with_path(&collection_root)?;
         }
+        Self::check_layout(&big_file_folder, layout)?;
+        let content_addressed = match layout {
+            DatFileLayout::Flat => None,
+            DatFileLayout::ContentAddressed => {
+                Some(ContentAddressedStore::new(&big_file_folder))
+            }
+        };
 
         let path_provider_big_file = UniquePathProvider::new(big_file_folder, Default::default())
             .with_template(file_name_template!(arg!@"url" _ timestamp64 _ serial ".dat").unwrap());
@@ -84,9 +103,61 @@ impl FileSystemAccess {
             collection_root,
             worker_base: template_base,
             big_file: path_provider_big_file,
+            content_addressed,
+            warc_info: None,
             filesystem_lock: Mutex::new(()),
         })
     }
+
+    /// Starts every warc file of the workers with a warcinfo record of [info].
+    pub fn with_warc_info(self, info: WarcInfo) -> Self {
+        Self {
+            warc_info: Some(Arc::new(info)),
+            ..self
+        }
+    }
+
+    /// Records the [digest] of the seeds in the warcinfo records of the warc files created
+    /// from now on.
+    pub fn set_seeds_digest(&mut self, digest: String) {
+        if let Some(info) = self.warc_info.as_mut() {
+            Arc::make_mut(info).set_seeds_digest(digest);
+        }
+    }
+
+    /// Records the [layout] in the [big_file_folder] or fails if the folder already uses
+    /// another one. A non empty folder without a record uses [DatFileLayout::Flat].
+    fn check_layout(
+        big_file_folder: &Utf8Path,
+        layout: DatFileLayout,
+    ) -> Result<(), ErrorWithPath> {
+        let layout_file = big_file_folder.join(LAYOUT_FILE_NAME);
+        let recorded = if layout_file.exists() {
+            Some(std::fs::read_to_string(&layout_file).to_error_with_path(&layout_file)?)
+        } else if big_file_folder
+            .read_dir_utf8()
+            .to_error_with_path(big_file_folder)?
+            .next()
+            .is_some()
+        {
+            Some(DatFileLayout::Flat.to_string())
+        } else {
+            None
+        };
+        match recorded {
+            Some(recorded) if recorded.trim() != layout.to_string() => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "The big files are stored with the layout {}, mixing it with {layout} is not supported.",
+                    recorded.trim()
+                ),
+            ))
+            .to_error_with_path(big_file_folder),
+            Some(_) => Ok(()),
+            None => std::fs::write(&layout_file, layout.to_string())
+                .to_error_with_path(&layout_file),
+        }
+    }
 }
 
 impl AtraFS for FileSystemAccess {
@@ -104,11 +175,22 @@ impl AtraFS for FileSystemAccess {
         self.big_file.root().join(path)
     }
 
-    /// Deletes a datafile
+    /// Moves the data file into the content addressed store, if it is used.
+    fn finalize_data_file(&self, path: Utf8PathBuf) -> io::Result<Utf8PathBuf> {
+        match self.content_addressed {
+            Some(ref store) => store.insert(path),
+            None => Ok(path),
+        }
+    }
+
+    /// Deletes a datafile, a file of the content addressed store only loses a reference.
     fn cleanup_data_file(&self, path: impl AsRef<Utf8Path>) -> io::Result<()> {
         log::debug!("Delete the file {}", path.as_ref().to_string());
         let path = self.big_file.root().join(path);
-        std::fs::remove_file(path)
+        match self.content_addressed {
+            Some(ref store) if store.contains_path(&path) => store.release(path).map(|_| ()),
+            _ => std::fs::remove_file(path),
+        }
     }
 
     fn create_worker_file_provider(
@@ -123,6 +205,7 @@ impl AtraFS for FileSystemAccess {
             worker_id,
             recrawl_iteration,
         )
+        .map(|value| value.with_warc_info(self.warc_info.clone()))
     }
 }
 
@@ -132,6 +215,7 @@ pub struct WorkerFileSystemAccess {
     root: Utf8PathBuf,
     provider: Arc<UniquePathProviderWithTemplate>,
     journal: Arc<Mutex<BufWriter<File>>>,
+    warc_info: Option<Arc<WarcInfo>>,
 }
 
 static FILE_NAME_REGEX: LazyLock<Regex> =
@@ -252,9 +336,16 @@ impl WorkerFileSystemAccess {
             root: worker_root,
             provider: Arc::new(provider),
             journal: Arc::new(Mutex::new(journal)),
+            warc_info: None,
         })
     }
 
+    /// Starts every warc file with a warcinfo record of [info], if set.
+    pub fn with_warc_info(mut self, info: Option<Arc<WarcInfo>>) -> Self {
+        self.warc_info = info;
+        self
+    }
+
     fn update_journal(&self) {
         let recover = self.provider.get_recover_information();
         let mut w = self.journal.lock().unwrap();
@@ -296,6 +387,10 @@ impl WarcFilePathProvider for WorkerFileSystemAccess {
             }
         }
     }
+
+    fn warc_info(&self) -> Option<Arc<WarcInfo>> {
+        self.warc_info.clone()
+    }
 }
 
 impl Drop for WorkerFileSystemAccess {
@@ -307,6 +402,7 @@ impl Drop for WorkerFileSystemAccess {
 #[cfg(test)]
 mod test {
     use camino::Utf8PathBuf;
+    use crate::config::session::DatFileLayout;
     use crate::io::fs::{AtraFS, FILE_NAME_REGEX, FileSystemAccess};
     use crate::stores::warc::WarcFilePathProvider;
 
@@ -330,6 +426,7 @@ mod test {
             0,
             Utf8PathBuf::from("./test3"),
             Utf8PathBuf::from("./test3/bigfile"),
+            DatFileLayout::Flat,
         ).unwrap();
 
         let worker_fs = fs.create_worker_file_provider(12, 0).unwrap();
@@ -341,4 +438,45 @@ mod test {
         let y = worker_fs.create_new_warc_file_path().unwrap();
         println!("WP2: {y}");
     }
+
+    #[test]
+    fn shares_identical_data_files_and_rejects_mixed_layouts() {
+        let root = camino_tempfile::Utf8TempDir::new().unwrap();
+        let big_files = root.path().join("big_files");
+        let create = |layout| {
+            FileSystemAccess::new(
+                "service".to_string(),
+                "collection".to_string(),
+                0,
+                root.path().to_path_buf(),
+                big_files.clone(),
+                layout,
+            )
+        };
+        let fs = create(DatFileLayout::ContentAddressed).unwrap();
+
+        let body = vec![1u8; 2 * 1024 * 1024];
+        let mut stored = Vec::new();
+        for url in ["https://www.example.com/a.bin", "https://www.example.com/b.bin"] {
+            let path = fs.create_unique_path_for_dat_file(url);
+            std::fs::write(&path, &body).unwrap();
+            stored.push(fs.finalize_data_file(path).unwrap());
+        }
+        assert_eq!(stored[0], stored[1]);
+        assert_eq!(body, std::fs::read(&stored[0]).unwrap());
+        let dat_files = big_files
+            .read_dir_utf8()
+            .unwrap()
+            .filter(|value| value.as_ref().unwrap().path().extension() == Some("dat"))
+            .count();
+        assert_eq!(0, dat_files);
+
+        fs.cleanup_data_file(&stored[0]).unwrap();
+        assert!(stored[1].exists());
+        fs.cleanup_data_file(&stored[1]).unwrap();
+        assert!(!stored[1].exists());
+
+        assert!(create(DatFileLayout::Flat).is_err());
+        assert!(create(DatFileLayout::ContentAddressed).is_ok());
+    }
 }
@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod content_addressed;
 pub mod errors;
 pub mod file_owner;
 pub mod fs;
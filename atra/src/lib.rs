@@ -0,0 +1,63 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Atra is a web crawler. The [AtraBuilder] allows to embed a crawl into another application,
+//! the binary uses the same entry point via [exec_args].
+
+pub use app::{exec_args, ApplicationMode, AtraArgs, AtraBuilder, AtraCrawlHandle, AtraRunError};
+pub use client::traits::FetchFailureKind;
+pub use config::Config;
+pub use crawl::{CrawlEvent, CrawlResult, ExitState};
+pub use extraction::extractor_method::ExtractorMethod;
+pub use runtime::AtraRuntime;
+pub use seed::SeedDefinition;
+pub use url::filter::{UrlFilter, UrlFilterDecision};
+pub use url::{AtraUrlOrigin, UrlWithDepth};
+
+mod app;
+#[cfg(any(test, feature = "bench"))]
+pub mod bench;
+mod blacklist;
+mod client;
+pub mod config;
+mod contexts;
+mod cooperative;
+mod crawl;
+mod data;
+mod database;
+mod decoding;
+mod extraction;
+mod fetching;
+mod format;
+mod gdbr;
+mod html;
+mod io;
+mod link_state;
+mod origin_metadata;
+mod queue;
+mod recrawl_management;
+mod robots;
+mod runtime;
+mod schemas;
+mod seed;
+mod stores;
+mod sync;
+#[cfg(test)]
+mod test_impls;
+mod tls_info;
+mod toolkit;
+mod unique_content;
+mod url;
+mod warc_ext;
+mod web_graph;
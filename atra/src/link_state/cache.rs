@@ -0,0 +1,372 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::LinkStateCacheCapacity;
+use crate::link_state::{
+    LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike, RawLinkState,
+};
+use crate::url::UrlWithDepth;
+use lru::LruCache;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::num::NonZeroUsize;
+use std::ops::RangeBounds;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// The number of independently locked shards of a [LinkStateCache].
+const SHARDS: usize = 16;
+
+/// The hits and misses of a [LinkStateCache] since its creation.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct LinkStateCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// A part of the cache, the states are None if the url has no link state.
+#[derive(Debug)]
+struct CacheShard {
+    entries: LruCache<Vec<u8>, Option<RawLinkState>>,
+    bytes: usize,
+    max_bytes: Option<usize>,
+}
+
+impl CacheShard {
+    /// Panics if [capacity] is disabled.
+    fn new(capacity: LinkStateCacheCapacity) -> Self {
+        match capacity {
+            LinkStateCacheCapacity::Entries(entries) => Self {
+                entries: LruCache::new(NonZeroUsize::new(entries).unwrap()),
+                bytes: 0,
+                max_bytes: None,
+            },
+            LinkStateCacheCapacity::Bytes(bytes) => Self {
+                entries: LruCache::unbounded(),
+                bytes: 0,
+                max_bytes: Some(bytes),
+            },
+        }
+    }
+
+    fn weight(key: &[u8], state: &Option<RawLinkState>) -> usize {
+        key.len() + state.as_ref().map_or(0, |value| value.len())
+    }
+
+    fn insert(&mut self, key: &[u8], state: Option<RawLinkState>) {
+        self.bytes += Self::weight(key, &state);
+        if let Some((key, state)) = self.entries.push(key.to_vec(), state) {
+            self.bytes -= Self::weight(&key, &state);
+        }
+        if let Some(max_bytes) = self.max_bytes {
+            while self.bytes > max_bytes {
+                match self.entries.pop_lru() {
+                    Some((key, state)) => self.bytes -= Self::weight(&key, &state),
+                    None => break,
+                }
+            }
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) -> Option<Option<RawLinkState>> {
+        let state = self.entries.pop(key)?;
+        self.bytes -= Self::weight(key, &state);
+        Some(state)
+    }
+}
+
+/// A sharded LRU cache of the link states, including the urls without a link state.
+///
+/// Every access holds the lock of the shard of the url while the database is read or written,
+/// the cached state of an url is therefore always the state in the database.
+#[derive(Debug)]
+pub struct LinkStateCache {
+    shards: Vec<Mutex<CacheShard>>,
+    hasher: RandomState,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LinkStateCache {
+    /// Returns None if [capacity] is disabled.
+    pub fn new(capacity: LinkStateCacheCapacity) -> Option<Self> {
+        if capacity.is_disabled() {
+            return None;
+        }
+        let per_shard = match capacity {
+            LinkStateCacheCapacity::Entries(entries) => {
+                LinkStateCacheCapacity::Entries(entries.div_ceil(SHARDS))
+            }
+            LinkStateCacheCapacity::Bytes(bytes) => {
+                LinkStateCacheCapacity::Bytes(bytes.div_ceil(SHARDS))
+            }
+        };
+        Some(Self {
+            shards: (0..SHARDS)
+                .map(|_| Mutex::new(CacheShard::new(per_shard)))
+                .collect(),
+            hasher: RandomState::new(),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        })
+    }
+
+    fn shard(&self, key: &[u8]) -> MutexGuard<CacheShard> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+
+    /// Returns the cached state of [key] or caches the state returned by [load].
+    pub fn get_or_load<E>(
+        &self,
+        key: &[u8],
+        load: impl FnOnce() -> Result<Option<RawLinkState>, E>,
+    ) -> Result<Option<RawLinkState>, E> {
+        let mut shard = self.shard(key);
+        if let Some(state) = shard.entries.get(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(state.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let state = load()?;
+        shard.insert(key, state.clone());
+        Ok(state)
+    }
+
+    /// Writes [state] with [write] and caches it.
+    pub fn set<E>(
+        &self,
+        key: &[u8],
+        state: &RawLinkState,
+        write: impl FnOnce() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut shard = self.shard(key);
+        match write() {
+            Ok(()) => {
+                shard.insert(key, Some(state.clone()));
+                Ok(())
+            }
+            Err(err) => {
+                shard.remove(key);
+                Err(err)
+            }
+        }
+    }
+
+    /// Merges [upsert] with [write] and applies the merge operator of the database
+    /// to the cached state. Uncached states stay uncached.
+    pub fn upsert<E>(
+        &self,
+        key: &[u8],
+        upsert: &RawLinkState,
+        write: impl FnOnce() -> Result<(), E>,
+    ) -> Result<(), E> {
+        let mut shard = self.shard(key);
+        let cached = shard.remove(key);
+        write()?;
+        if let Some(cached) = cached {
+            let merged = RawLinkState::merge_linkstate_simulated(key, cached, [upsert])
+                .map(RawLinkState::from_vec);
+            match merged {
+                Some(Ok(merged)) => shard.insert(key, Some(merged)),
+                Some(Err(err)) => {
+                    log::warn!("Failed to cache the merged link state: {err}");
+                }
+                None => {}
+            }
+        }
+        Ok(())
+    }
+
+    pub fn stats(&self) -> LinkStateCacheStats {
+        LinkStateCacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A [LinkStateDB] with an optional [LinkStateCache] in front of the reads and writes of
+/// single urls. The scans always read the database. Writes that bypass this db,
+/// e.g. by another handle to the same database, are not seen by the cache.
+#[derive(Debug)]
+pub struct CachedLinkStateDB<DB: LinkStateDB> {
+    db: DB,
+    cache: Option<LinkStateCache>,
+}
+
+impl<DB: LinkStateDB> CachedLinkStateDB<DB> {
+    pub fn new(db: DB, cache: Option<LinkStateCache>) -> Self {
+        Self { db, cache }
+    }
+
+    /// The uncached database.
+    pub fn inner(&self) -> &DB {
+        &self.db
+    }
+
+    /// The stats of the cache, None without a cache.
+    pub fn cache_stats(&self) -> Option<LinkStateCacheStats> {
+        self.cache.as_ref().map(LinkStateCache::stats)
+    }
+}
+
+impl<DB: LinkStateDB> LinkStateDB for CachedLinkStateDB<DB> {
+    fn set_state(
+        &self,
+        url: &UrlWithDepth,
+        new_state: &impl LinkStateLike,
+    ) -> Result<(), LinkStateDBError> {
+        match &self.cache {
+            Some(cache) => {
+                let raw = new_state.as_raw_link_state();
+                cache.set(url.as_ref(), &raw, || self.db.set_state(url, &*raw))
+            }
+            None => self.db.set_state(url, new_state),
+        }
+    }
+
+    fn get_state(&self, url: &UrlWithDepth) -> Result<Option<RawLinkState>, LinkStateDBError> {
+        match &self.cache {
+            Some(cache) => cache.get_or_load(url.as_ref(), || self.db.get_state(url)),
+            None => self.db.get_state(url),
+        }
+    }
+
+    fn upsert_state(
+        &self,
+        url: &UrlWithDepth,
+        upsert: &impl LinkStateLike,
+    ) -> Result<(), LinkStateDBError> {
+        match &self.cache {
+            Some(cache) => {
+                let raw = upsert.as_raw_link_state();
+                cache.upsert(url.as_ref(), &raw, || self.db.upsert_state(url, &*raw))
+            }
+            None => self.db.upsert_state(url, upsert),
+        }
+    }
+
+    fn count_state(&self, link_state_type: LinkStateKind) -> Result<u64, LinkStateDBError> {
+        self.db.count_state(link_state_type)
+    }
+
+    async fn scan_for_any_link_state<T: RangeBounds<LinkStateKind>>(&self, states: T) -> bool {
+        self.db.scan_for_any_link_state(states).await
+    }
+
+    async fn scan_for_value<F>(&self, scanner: F) -> bool
+    where
+        F: Fn(&[u8], &[u8]) -> bool,
+    {
+        self.db.scan_for_value(scanner).await
+    }
+
+    fn collect_values<F>(&self, collector: F)
+    where
+        F: Fn(u64, &[u8], &[u8]) -> bool,
+    {
+        self.db.collect_values(collector)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CacheShard, LinkStateCache};
+    use crate::config::LinkStateCacheCapacity;
+    use crate::link_state::{IsSeedYesNo, LinkStateKind, RawLinkState, RecrawlYesNo};
+    use crate::url::UrlWithDepth;
+    use std::convert::Infallible;
+
+    fn state(url: &str, kind: LinkStateKind) -> RawLinkState {
+        RawLinkState::new_preconfigured_upsert_no_payload(
+            &UrlWithDepth::from_url(url).unwrap(),
+            kind,
+            Some(IsSeedYesNo::No),
+            Some(RecrawlYesNo::No),
+        )
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_states() {
+        let mut shard = CacheShard::new(LinkStateCacheCapacity::Entries(2));
+        shard.insert(b"a", None);
+        shard.insert(b"b", None);
+        assert!(shard.entries.get(b"a".as_slice()).is_some());
+        shard.insert(b"c", None);
+        assert!(shard.entries.get(b"b".as_slice()).is_none());
+        assert!(shard.entries.get(b"a".as_slice()).is_some());
+        assert_eq!(2, shard.bytes);
+
+        let value = state("https://www.example.com/", LinkStateKind::Discovered);
+        let mut shard = CacheShard::new(LinkStateCacheCapacity::Bytes(2 * (value.len() + 1)));
+        shard.insert(b"a", Some(value.clone()));
+        shard.insert(b"b", Some(value.clone()));
+        shard.insert(b"b", Some(value.clone()));
+        assert_eq!(2, shard.entries.len());
+        shard.insert(b"c", Some(value.clone()));
+        assert_eq!(2, shard.entries.len());
+        assert!(shard.entries.get(b"a".as_slice()).is_none());
+        assert_eq!(2 * (value.len() + 1), shard.bytes);
+        assert!(shard.remove(b"c").is_some());
+        assert_eq!(value.len() + 1, shard.bytes);
+    }
+
+    #[test]
+    fn can_be_disabled() {
+        assert!(LinkStateCache::new(LinkStateCacheCapacity::Entries(0)).is_none());
+        assert!(LinkStateCache::new(LinkStateCacheCapacity::Bytes(0)).is_none());
+    }
+
+    #[test]
+    fn applies_upserts_to_the_cached_state() {
+        let cache = LinkStateCache::new(LinkStateCacheCapacity::Entries(64)).unwrap();
+        let key = b"https://www.example.com/".as_slice();
+        let loaded = cache
+            .get_or_load(key, || Ok::<_, Infallible>(None))
+            .unwrap();
+        assert_eq!(None, loaded);
+
+        // A missing state is replaced by the upsert, like in the database.
+        let discovered = state("https://www.example.com/", LinkStateKind::Discovered);
+        cache
+            .upsert(key, &discovered, || Ok::<_, Infallible>(()))
+            .unwrap();
+        let cached = cache
+            .get_or_load(key, || -> Result<_, Infallible> { panic!("Not cached!") })
+            .unwrap();
+        assert_eq!(Some(discovered.clone()), cached);
+
+        let crawled = state("https://www.example.com/", LinkStateKind::Crawled);
+        cache
+            .upsert(key, &crawled, || Ok::<_, Infallible>(()))
+            .unwrap();
+        let expected =
+            RawLinkState::merge_linkstate_simulated(key, Some(&discovered), [&crawled]).unwrap();
+        let cached = cache
+            .get_or_load(key, || -> Result<_, Infallible> { panic!("Not cached!") })
+            .unwrap()
+            .unwrap();
+        assert_eq!(RawLinkState::from_vec(expected).unwrap(), cached);
+
+        // A failed write leaves the state uncached.
+        assert!(cache.upsert(key, &discovered, || Err(())).is_err());
+        let reloaded = cache
+            .get_or_load(key, || Ok::<_, Infallible>(None))
+            .unwrap();
+        assert_eq!(None, reloaded);
+        assert_eq!(2, cache.stats().hits);
+        assert_eq!(2, cache.stats().misses);
+    }
+}
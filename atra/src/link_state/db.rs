@@ -14,14 +14,18 @@
 
 use crate::database::DBActionType::{Merge, Read, Write};
 use crate::database::{execute_iter, get_len, DBActionType, RawDatabaseError, LINK_STATE_DB_CF};
+use crate::link_state::origins::{
+    origin_index_key, origin_index_prefix, origin_index_upper_bound, ORIGIN_INDEX_SEPARATOR,
+};
 use crate::link_state::{
-    LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike, RawLinkState,
+    LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStatePage,
+    OriginLinkStateSummary, OriginUrlLinkState, RawLinkState,
 };
-use crate::url::UrlWithDepth;
+use crate::url::{AtraOriginProvider, AtraUri, UrlWithDepth};
 use crate::{db_health_check, declare_column_families};
 use rocksdb::{
     BoundColumnFamily, DBIteratorWithThreadMode, DBWithThreadMode, IteratorMode, MultiThreaded,
-    ReadOptions, DB,
+    ReadOptions, WriteBatch, DB,
 };
 use std::ops::RangeBounds;
 use std::sync::Arc;
@@ -36,17 +40,90 @@ pub struct LinkStateRockDB {
 impl LinkStateRockDB {
     declare_column_families! {
         self.db => cf_handle(LINK_STATE_DB_CF)
+        self.db => origin_index_handle(LINK_STATE_ORIGIN_INDEX_DB_CF)
     }
 
     /// Panics if the needed CFs are not configured.
+    /// Builds the origin index if the link states predate it.
     pub fn new(db: Arc<DB>) -> Self {
         db_health_check!(db: [
             Self::LINK_STATE_DB_CF => (
                 if test link_state_cf_options
                 else "The column family for the link states was not properly configured."
             )
+            Self::LINK_STATE_ORIGIN_INDEX_DB_CF => (
+                if test link_state_origin_index_cf_options
+                else "The column family for the origin index of the link states was not properly configured."
+            )
         ]);
-        Self { db }
+        let db = Self { db };
+        if let Err(err) = db.build_missing_origin_index() {
+            log::error!("Failed to build the origin index of the link states: {err}");
+        }
+        db
+    }
+
+    /// Indexes all link states by their origin if the index is empty.
+    fn build_missing_origin_index(&self) -> Result<(), LinkStateDBError> {
+        const BATCH_SIZE: usize = 10_000;
+
+        let index = self.origin_index_handle();
+        let mut index_iter = self.db.raw_iterator_cf(&index);
+        index_iter.seek_to_first();
+        if index_iter.valid() {
+            return Ok(());
+        }
+        drop(index_iter);
+
+        let mut options = ReadOptions::default();
+        options.fill_cache(false);
+        let mut iter = self.db.raw_iterator_cf_opt(&self.cf_handle(), options);
+        iter.seek_to_first();
+        let mut batch = WriteBatch::default();
+        let mut indexed = 0usize;
+        while iter.valid() {
+            if let Some(key) = iter.key() {
+                let origin = String::from_utf8_lossy(key)
+                    .parse::<AtraUri>()
+                    .ok()
+                    .and_then(|uri| uri.atra_origin());
+                if let Some(origin) = origin {
+                    batch.put_cf(&index, origin_index_key(origin.as_ref(), key), b"");
+                    indexed += 1;
+                    if batch.len() >= BATCH_SIZE {
+                        self.db
+                            .write(std::mem::take(&mut batch))
+                            .enrich_no_key(Self::LINK_STATE_ORIGIN_INDEX_DB_CF, Write)?;
+                    }
+                }
+            }
+            iter.next();
+        }
+        if !batch.is_empty() {
+            self.db
+                .write(batch)
+                .enrich_no_key(Self::LINK_STATE_ORIGIN_INDEX_DB_CF, Write)?;
+        }
+        if indexed > 0 {
+            log::info!("Indexed {indexed} link states by their origin.");
+        }
+        Ok(())
+    }
+
+    /// Writes [url] to the origin index in the same [batch] as its link state.
+    fn write_with_origin_index(
+        &self,
+        mut batch: WriteBatch,
+        url: &UrlWithDepth,
+    ) -> Result<(), rocksdb::Error> {
+        if let Some(origin) = url.atra_origin() {
+            batch.put_cf(
+                &self.origin_index_handle(),
+                origin_index_key(origin.as_ref(), url.as_ref()),
+                b"",
+            );
+        }
+        self.db.write(batch)
     }
 
     fn set_state_internal(
@@ -56,7 +133,9 @@ impl LinkStateRockDB {
         url_state: &impl LinkStateLike,
     ) -> Result<(), LinkStateDBError> {
         let raw = url_state.as_raw_link_state().into_owned();
-        Ok(self.db.put_cf(cf, url, &raw).enrich_with_entry(
+        let mut batch = WriteBatch::default();
+        batch.put_cf(cf, url, &raw);
+        Ok(self.write_with_origin_index(batch, url).enrich_with_entry(
             Self::LINK_STATE_DB_CF,
             Write,
             url,
@@ -88,7 +167,9 @@ impl LinkStateRockDB {
         upsert: &impl LinkStateLike,
     ) -> Result<(), LinkStateDBError> {
         let raw = upsert.as_raw_link_state().into_owned();
-        Ok(self.db.merge_cf(cf, url, &raw).enrich_with_entry(
+        let mut batch = WriteBatch::default();
+        batch.merge_cf(cf, url, &raw);
+        Ok(self.write_with_origin_index(batch, url).enrich_with_entry(
             Self::LINK_STATE_DB_CF,
             Merge,
             url,
@@ -155,6 +236,127 @@ impl LinkStateRockDB {
     ) -> DBIteratorWithThreadMode<DBWithThreadMode<MultiThreaded>> {
         execute_iter(&self.db, self.cf_handle(), mode)
     }
+
+    /// Summarizes the link states of [limit] origins after the first [offset] origins,
+    /// ordered by their name. Reads a snapshot, hence the summaries are consistent
+    /// while the crawl goes on, but blocks for a while on large origins.
+    pub fn origin_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<LinkStatePage<OriginLinkStateSummary>, LinkStateDBError> {
+        let snapshot = self.db.snapshot();
+        let states = self.cf_handle();
+        let mut options = ReadOptions::default();
+        options.fill_cache(false);
+        let mut iter = snapshot.raw_iterator_cf_opt(&self.origin_index_handle(), options);
+        iter.seek_to_first();
+
+        let mut skipped = 0usize;
+        let mut items = Vec::new();
+        let mut next_offset = None;
+        while let Some(key) = iter.key() {
+            let Some(split) = key
+                .iter()
+                .position(|value| *value == ORIGIN_INDEX_SEPARATOR)
+            else {
+                iter.next();
+                continue;
+            };
+            let origin = key[..split].to_vec();
+            if skipped < offset {
+                skipped += 1;
+                iter.seek(origin_index_upper_bound(&origin));
+                continue;
+            }
+            if items.len() == limit {
+                next_offset = Some(offset + limit);
+                break;
+            }
+            let prefix = origin_index_prefix(&origin);
+            let mut summary = OriginLinkStateSummary::new(String::from_utf8_lossy(&origin).into());
+            while let Some(key) = iter.key() {
+                let Some(url) = key.strip_prefix(prefix.as_slice()) else {
+                    break;
+                };
+                let state = snapshot.get_cf(&states, url).enrich_without_entry(
+                    Self::LINK_STATE_DB_CF,
+                    Read,
+                    url,
+                )?;
+                if let Some(state) = state {
+                    summary.count(
+                        RawLinkState::read_kind(&state)?,
+                        RawLinkState::read_timestamp(&state)?,
+                    );
+                }
+                iter.next();
+            }
+            items.push(summary);
+        }
+        Ok(LinkStatePage {
+            offset,
+            limit,
+            items,
+            next_offset,
+        })
+    }
+
+    /// Lists the link states of [limit] urls of [origin] after the first [offset] urls,
+    /// ordered by the url. Only urls in the state [kind] are listed if it is set.
+    /// Reads a snapshot, like [Self::origin_summaries].
+    pub fn origin_urls(
+        &self,
+        origin: &str,
+        kind: Option<LinkStateKind>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<LinkStatePage<OriginUrlLinkState>, LinkStateDBError> {
+        let snapshot = self.db.snapshot();
+        let states = self.cf_handle();
+        let prefix = origin_index_prefix(origin.as_bytes());
+        let mut options = ReadOptions::default();
+        options.fill_cache(false);
+        options.set_iterate_upper_bound(origin_index_upper_bound(origin.as_bytes()));
+        let mut iter = snapshot.raw_iterator_cf_opt(&self.origin_index_handle(), options);
+        iter.seek(&prefix);
+
+        let mut skipped = 0usize;
+        let mut items = Vec::new();
+        let mut next_offset = None;
+        while let Some(key) = iter.key() {
+            let url = &key[prefix.len()..];
+            let state = snapshot.get_cf(&states, url).enrich_without_entry(
+                Self::LINK_STATE_DB_CF,
+                Read,
+                url,
+            )?;
+            if let Some(state) = state {
+                let found = RawLinkState::read_kind(&state)?;
+                if kind.map_or(true, |kind| kind == found) {
+                    if skipped < offset {
+                        skipped += 1;
+                    } else if items.len() == limit {
+                        next_offset = Some(offset + limit);
+                        break;
+                    } else {
+                        items.push(OriginUrlLinkState {
+                            url: String::from_utf8_lossy(url).into_owned(),
+                            kind: found,
+                            timestamp: RawLinkState::read_timestamp(&state)?,
+                        });
+                    }
+                }
+            }
+            iter.next();
+        }
+        Ok(LinkStatePage {
+            offset,
+            limit,
+            items,
+            next_offset,
+        })
+    }
 }
 
 impl LinkStateDB for LinkStateRockDB {
@@ -324,14 +526,16 @@ impl<'a> LinkStateDB for WeakLinkStateDB<'a> {
 
 #[cfg(test)]
 mod test {
-    use crate::database::{destroy_db, open_db};
+    use crate::database::{destroy_db, open_db, LINK_STATE_ORIGIN_INDEX_DB_CF};
     use crate::link_state::{
         DatabaseLinkStateManager, IsSeedYesNo, LinkStateDB, LinkStateKind, LinkStateLike,
-        LinkStateManager, LinkStateRockDB, RawLinkState, RecrawlYesNo,
+        LinkStateManager, LinkStatePage, LinkStateRockDB, OriginUrlLinkState, RawLinkState,
+        RecrawlYesNo,
     };
     use crate::queue::{SupportsForcedQueueElement, UrlQueue, UrlQueueElement};
     use crate::test_impls::{InMemoryLinkStateManager, TestUrlQueue};
     use crate::url::{Depth, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
     use rocksdb::DB;
     use std::sync::Arc;
     use time::{Duration, OffsetDateTime};
@@ -634,4 +838,132 @@ mod test {
             real_values_ebay
         );
     }
+
+    async fn set_kinds(manager: &impl LinkStateManager, states: &[(&str, LinkStateKind)]) {
+        for (url, kind) in states {
+            manager
+                .update_link_state_no_meta_and_payload(&url.parse().unwrap(), *kind)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn link_states_are_listed_by_origin() {
+        let dir = Utf8TempDir::new().unwrap();
+        let db: Arc<DB> = open_db(dir.path().join("db")).unwrap().into();
+        let manager = DatabaseLinkStateManager::new(db);
+        set_kinds(
+            &manager,
+            &[
+                (
+                    "https://www.example.com/a",
+                    LinkStateKind::ProcessedAndStored,
+                ),
+                ("http://example.com/b", LinkStateKind::Crawled),
+                ("https://example.com/c", LinkStateKind::Discovered),
+                ("https://shop.example.com/d", LinkStateKind::InternalError),
+                ("https://example.org/", LinkStateKind::Discovered),
+                (
+                    "https://www.rust-lang.org/",
+                    LinkStateKind::ReservedForCrawl,
+                ),
+            ],
+        )
+        .await;
+        // A second state of the same url is indexed once.
+        set_kinds(
+            &manager,
+            &[("https://example.com/c", LinkStateKind::Discovered)],
+        )
+        .await;
+
+        let first = manager.origin_summaries(0, 2).unwrap();
+        assert_eq!(Some(2), first.next_offset);
+        assert_eq!(
+            vec![("example.com", 2, 1, 1), ("example.org", 0, 0, 1)],
+            first
+                .items
+                .iter()
+                .map(|value| (
+                    value.origin.as_str(),
+                    value.crawled,
+                    value.errored,
+                    value.queued
+                ))
+                .collect::<Vec<_>>()
+        );
+        assert!(first.items[0].last_crawled_at.is_some());
+        assert!(first.items[1].last_crawled_at.is_none());
+        let second = manager.origin_summaries(2, 2).unwrap();
+        assert_eq!(None, second.next_offset);
+        assert_eq!(1, second.items.len());
+        assert_eq!("rust-lang.org", second.items[0].origin);
+        assert!(manager.origin_summaries(3, 2).unwrap().items.is_empty());
+
+        let urls = |page: LinkStatePage<OriginUrlLinkState>| {
+            page.items
+                .into_iter()
+                .map(|value| value.url)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            vec![
+                "http://example.com/b",
+                "https://example.com/c",
+                "https://shop.example.com/d",
+                "https://www.example.com/a",
+            ],
+            urls(manager.origin_urls("example.com", None, 0, 10).unwrap())
+        );
+        let page = manager.origin_urls("example.com", None, 1, 2).unwrap();
+        assert_eq!(Some(3), page.next_offset);
+        assert_eq!(
+            vec!["https://example.com/c", "https://shop.example.com/d"],
+            urls(page)
+        );
+        let discovered = manager
+            .origin_urls("example.com", Some(LinkStateKind::Discovered), 0, 10)
+            .unwrap();
+        assert_eq!(None, discovered.next_offset);
+        assert_eq!(LinkStateKind::Discovered, discovered.items[0].kind);
+        assert_eq!(vec!["https://example.com/c"], urls(discovered));
+        assert!(manager
+            .origin_urls("example", None, 0, 10)
+            .unwrap()
+            .items
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_origin_index_is_built_for_older_link_states() {
+        let dir = Utf8TempDir::new().unwrap();
+        let db: Arc<DB> = open_db(dir.path().join("db")).unwrap().into();
+        set_kinds(
+            &DatabaseLinkStateManager::new(db.clone()),
+            &[
+                ("https://www.example.com/a", LinkStateKind::Crawled),
+                ("https://example.org/", LinkStateKind::Discovered),
+            ],
+        )
+        .await;
+        let index = db.cf_handle(LINK_STATE_ORIGIN_INDEX_DB_CF).unwrap();
+        db.delete_range_cf(&index, b"", b"\xff").unwrap();
+        let mut index_iter = db.raw_iterator_cf(&index);
+        index_iter.seek_to_first();
+        assert!(!index_iter.valid());
+        drop(index_iter);
+
+        let summaries = LinkStateRockDB::new(db.clone())
+            .origin_summaries(0, 10)
+            .unwrap();
+        assert_eq!(
+            vec!["example.com", "example.org"],
+            summaries
+                .items
+                .iter()
+                .map(|value| value.origin.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
 }
@@ -12,43 +12,113 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::{LinkStateCacheCapacity, SeenFilterConfig};
 use crate::database::DatabaseError;
 use crate::link_state::traits::LinkStateManager;
 use crate::link_state::{
-    IsSeedYesNo, LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStateRockDB,
-    RawLinkState, RecrawlYesNo,
+    CachedLinkStateDB, IsSeedYesNo, LinkStateCache, LinkStateCacheStats, LinkStateDB,
+    LinkStateDBError, LinkStateKind, LinkStateLike, LinkStatePage, LinkStateRockDB,
+    OriginLinkStateSummary, OriginUrlLinkState, RawLinkState, RecrawlYesNo, SeenFilter,
+    SeenFilterStats,
 };
 use crate::url::{AtraUri, UrlWithDepth};
 use rocksdb::{DBIteratorWithThreadMode, DBWithThreadMode, IteratorMode, MultiThreaded, DB};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use time::OffsetDateTime;
 use tokio::sync::RwLock;
 use tokio::task::yield_now;
 
 #[derive(Debug)]
 pub struct DatabaseLinkStateManager<DB: LinkStateDB> {
-    db: DB,
+    db: CachedLinkStateDB<DB>,
+    seen: Option<SeenFilter>,
     last_scan_over_link_states: RwLock<Option<(bool, OffsetDateTime)>>,
 }
 
 impl DatabaseLinkStateManager<LinkStateRockDB> {
+    /// Creates a manager without a cache, it sees the writes of other handles to [db].
     pub fn new(db: Arc<DB>) -> Self {
-        Self {
-            db: LinkStateRockDB::new(db),
-            last_scan_over_link_states: RwLock::new(None),
-        }
+        Self::with_db(LinkStateRockDB::new(db), None)
+    }
+
+    /// Creates a manager that caches the link states. All writes to the link states
+    /// have to use this manager.
+    pub fn with_cache(db: Arc<DB>, capacity: LinkStateCacheCapacity) -> Self {
+        Self::with_db(LinkStateRockDB::new(db), LinkStateCache::new(capacity))
     }
 
     pub fn len(&self) -> usize {
-        self.db.len()
+        self.db.inner().len()
     }
 
     pub fn iter(
         &self,
         mode: IteratorMode,
     ) -> DBIteratorWithThreadMode<DBWithThreadMode<MultiThreaded>> {
-        self.db.iter(mode)
+        self.db.inner().iter(mode)
+    }
+
+    /// See [LinkStateRockDB::origin_summaries].
+    pub fn origin_summaries(
+        &self,
+        offset: usize,
+        limit: usize,
+    ) -> Result<LinkStatePage<OriginLinkStateSummary>, LinkStateDBError> {
+        self.db.inner().origin_summaries(offset, limit)
+    }
+
+    /// See [LinkStateRockDB::origin_urls].
+    pub fn origin_urls(
+        &self,
+        origin: &str,
+        kind: Option<LinkStateKind>,
+        offset: usize,
+        limit: usize,
+    ) -> Result<LinkStatePage<OriginUrlLinkState>, LinkStateDBError> {
+        self.db.inner().origin_urls(origin, kind, offset, limit)
+    }
+}
+
+impl<DB: LinkStateDB> DatabaseLinkStateManager<DB> {
+    fn with_db(db: DB, cache: Option<LinkStateCache>) -> Self {
+        Self {
+            db: CachedLinkStateDB::new(db, cache),
+            seen: None,
+            last_scan_over_link_states: RwLock::new(None),
+        }
+    }
+
+    /// Puts a filter of all urls with a link state in front of the database, it is built from
+    /// the database. All writes to the link states have to use this manager.
+    pub fn with_seen_filter(mut self, config: &SeenFilterConfig) -> Self {
+        let started = Instant::now();
+        let seen = SeenFilter::new(config.expected_urls);
+        self.db.inner().collect_values(|_, key, _| {
+            seen.insert(key);
+            true
+        });
+        let stats = seen.stats();
+        log::info!(
+            "Built the filter of {} seen urls in {:?}, its false positive rate is about {}.",
+            stats.keys,
+            started.elapsed(),
+            stats.estimated_false_positive_rate
+        );
+        self.seen = Some(seen);
+        self
+    }
+
+    /// Executes [write] while the url can not be discovered by an other worker.
+    fn write_seen(
+        &self,
+        url: &UrlWithDepth,
+        write: impl FnOnce() -> Result<(), LinkStateDBError>,
+    ) -> Result<(), LinkStateDBError> {
+        match self.seen {
+            Some(ref seen) => seen.write(url.as_ref(), write),
+            None => write(),
+        }
     }
 }
 
@@ -59,6 +129,14 @@ impl<DB: LinkStateDB> LinkStateManager for DatabaseLinkStateManager<DB> {
         self.db.count_state(LinkStateKind::ProcessedAndStored)
     }
 
+    fn cache_stats(&self) -> Option<LinkStateCacheStats> {
+        self.db.cache_stats()
+    }
+
+    fn seen_filter_stats(&self) -> Option<SeenFilterStats> {
+        self.seen.as_ref().map(SeenFilter::stats)
+    }
+
     async fn update_link_state<P>(
         &self,
         url: &UrlWithDepth,
@@ -70,10 +148,69 @@ impl<DB: LinkStateDB> LinkStateManager for DatabaseLinkStateManager<DB> {
     where
         P: ?Sized + AsRef<[u8]>,
     {
-        match self.db.update_state(url, state, is_seed, recrawl, payload) {
+        let update = || {
+            self.write_seen(url, || {
+                self.db.update_state(url, state, is_seed, recrawl, payload)
+            })
+        };
+        match update() {
             Err(LinkStateDBError::Database(DatabaseError::RecoverableFailure { .. })) => {
                 yield_now().await;
-                self.db.update_state(url, state, is_seed, recrawl, payload)
+                update()
+            }
+            escalate => escalate,
+        }
+    }
+
+    async fn update_link_state_at(
+        &self,
+        url: &UrlWithDepth,
+        state: LinkStateKind,
+        timestamp: OffsetDateTime,
+    ) -> Result<(), Self::Error> {
+        let mut upsert = RawLinkState::new_preconfigured_upsert_no_payload(url, state, None, None);
+        upsert.set_timestamp(timestamp);
+        let update = || self.write_seen(url, || self.db.upsert_state(url, &upsert));
+        match update() {
+            Err(LinkStateDBError::Database(DatabaseError::RecoverableFailure { .. })) => {
+                yield_now().await;
+                update()
+            }
+            escalate => escalate,
+        }
+    }
+
+    async fn discover_link_state(
+        &self,
+        url: &UrlWithDepth,
+        is_seed: Option<IsSeedYesNo>,
+        recrawl: Option<RecrawlYesNo>,
+    ) -> Result<bool, Self::Error> {
+        let Some(ref seen) = self.seen else {
+            if self.get_link_state(url).await?.is_some() {
+                return Ok(false);
+            }
+            self.update_link_state_no_payload(url, LinkStateKind::Discovered, is_seed, recrawl)
+                .await?;
+            return Ok(true);
+        };
+        let upsert = RawLinkState::new_preconfigured_upsert_no_payload(
+            url,
+            LinkStateKind::Discovered,
+            is_seed,
+            recrawl,
+        );
+        let discover = || {
+            seen.discover(
+                url.as_ref(),
+                || self.db.get_state(url).map(|state| state.is_some()),
+                || self.db.upsert_state(url, &upsert),
+            )
+        };
+        match discover() {
+            Err(LinkStateDBError::Database(DatabaseError::RecoverableFailure { .. })) => {
+                yield_now().await;
+                discover()
             }
             escalate => escalate,
         }
@@ -161,4 +298,237 @@ impl<DB: LinkStateDB> LinkStateManager for DatabaseLinkStateManager<DB> {
 }
 
 #[cfg(test)]
-mod test {}
+mod test {
+    use super::DatabaseLinkStateManager;
+    use crate::config::{LinkStateCacheCapacity, SeenFilterConfig};
+    use crate::database::{destroy_db, open_db};
+    use crate::link_state::{
+        IsSeedYesNo, LinkStateCache, LinkStateDB, LinkStateDBError, LinkStateKind, LinkStateLike,
+        LinkStateManager, LinkStateRockDB, RawLinkState, RecrawlYesNo,
+    };
+    use crate::url::UrlWithDepth;
+    use scopeguard::defer;
+    use std::ops::RangeBounds;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Counts the reads of single link states.
+    #[derive(Debug)]
+    struct CountingLinkStateDB {
+        db: LinkStateRockDB,
+        reads: AtomicUsize,
+    }
+
+    impl LinkStateDB for CountingLinkStateDB {
+        fn set_state(
+            &self,
+            url: &UrlWithDepth,
+            new_state: &impl LinkStateLike,
+        ) -> Result<(), LinkStateDBError> {
+            self.db.set_state(url, new_state)
+        }
+
+        fn get_state(&self, url: &UrlWithDepth) -> Result<Option<RawLinkState>, LinkStateDBError> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.db.get_state(url)
+        }
+
+        fn upsert_state(
+            &self,
+            url: &UrlWithDepth,
+            upsert: &impl LinkStateLike,
+        ) -> Result<(), LinkStateDBError> {
+            self.db.upsert_state(url, upsert)
+        }
+
+        fn count_state(&self, link_state_type: LinkStateKind) -> Result<u64, LinkStateDBError> {
+            self.db.count_state(link_state_type)
+        }
+
+        async fn scan_for_any_link_state<T: RangeBounds<LinkStateKind>>(&self, states: T) -> bool {
+            self.db.scan_for_any_link_state(states).await
+        }
+
+        async fn scan_for_value<F>(&self, scanner: F) -> bool
+        where
+            F: Fn(&[u8], &[u8]) -> bool,
+        {
+            self.db.scan_for_value(scanner).await
+        }
+
+        fn collect_values<F>(&self, collector: F)
+        where
+            F: Fn(u64, &[u8], &[u8]) -> bool,
+        {
+            self.db.collect_values(collector)
+        }
+    }
+
+    #[tokio::test]
+    async fn the_cache_follows_the_database() {
+        defer!(destroy_db("test/lnk_cache_db").unwrap(););
+        std::fs::create_dir_all("test").unwrap();
+        let db = LinkStateRockDB::new(open_db("test/lnk_cache_db").unwrap().into());
+        let manager = DatabaseLinkStateManager::with_db(
+            CountingLinkStateDB {
+                db: db.clone(),
+                reads: AtomicUsize::new(0),
+            },
+            LinkStateCache::new(LinkStateCacheCapacity::Entries(64)),
+        );
+        let reads = || manager.db.inner().reads.load(Ordering::SeqCst);
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+
+        assert_eq!(None, manager.get_link_state(&url).await.unwrap());
+        assert_eq!(None, manager.get_link_state(&url).await.unwrap());
+        assert_eq!(1, reads());
+
+        manager
+            .update_link_state_no_payload(
+                &url,
+                LinkStateKind::Discovered,
+                Some(IsSeedYesNo::Yes),
+                Some(RecrawlYesNo::No),
+            )
+            .await
+            .unwrap();
+        let first = manager.get_link_state(&url).await.unwrap().unwrap();
+        let second = manager.get_link_state(&url).await.unwrap().unwrap();
+        assert_eq!(first, second);
+        assert_eq!(1, reads());
+        assert_eq!(Some(first), db.get_state(&url).unwrap());
+
+        // The merge operator keeps the seed flag and tracks the last significant kind.
+        for kind in [
+            LinkStateKind::ReservedForCrawl,
+            LinkStateKind::Crawled,
+            LinkStateKind::ProcessedAndStored,
+        ] {
+            manager
+                .update_link_state_no_payload(&url, kind, None, Some(RecrawlYesNo::Yes))
+                .await
+                .unwrap();
+            let cached = manager.get_link_state_sync(&url).unwrap().unwrap();
+            assert_eq!(Some(cached.clone()), db.get_state(&url).unwrap());
+            assert_eq!(kind, cached.kind());
+            assert_eq!(IsSeedYesNo::Yes, cached.is_seed());
+        }
+        assert_eq!(1, reads());
+
+        let stats = manager.cache_stats().unwrap();
+        assert_eq!(1, stats.misses);
+        assert_eq!(5, stats.hits);
+    }
+
+    fn counting_manager(path: &str) -> DatabaseLinkStateManager<CountingLinkStateDB> {
+        std::fs::create_dir_all("test").unwrap();
+        DatabaseLinkStateManager::with_db(
+            CountingLinkStateDB {
+                db: LinkStateRockDB::new(open_db(path).unwrap().into()),
+                reads: AtomicUsize::new(0),
+            },
+            None,
+        )
+    }
+
+    async fn discover_all(
+        manager: &DatabaseLinkStateManager<CountingLinkStateDB>,
+        urls: &[UrlWithDepth],
+    ) -> usize {
+        let mut discovered = 0;
+        for url in urls {
+            if manager
+                .discover_link_state(url, Some(IsSeedYesNo::No), Some(RecrawlYesNo::No))
+                .await
+                .unwrap()
+            {
+                discovered += 1;
+            }
+        }
+        discovered
+    }
+
+    #[tokio::test]
+    async fn the_seen_filter_skips_the_reads_of_new_urls() {
+        defer!(
+            destroy_db("test/lnk_seen_db_unfiltered").unwrap();
+            destroy_db("test/lnk_seen_db").unwrap();
+        );
+        let urls = (0..1000)
+            .map(|i| UrlWithDepth::from_url(format!("https://www.example.com/{i}")).unwrap())
+            .collect::<Vec<_>>();
+
+        let unfiltered = counting_manager("test/lnk_seen_db_unfiltered");
+        assert_eq!(1000, discover_all(&unfiltered, &urls).await);
+        assert_eq!(0, discover_all(&unfiltered, &urls).await);
+        assert_eq!(2000, unfiltered.db.inner().reads.load(Ordering::SeqCst));
+        assert_eq!(None, unfiltered.seen_filter_stats());
+
+        let config = SeenFilterConfig { expected_urls: 100 };
+        let filtered = counting_manager("test/lnk_seen_db").with_seen_filter(&config);
+        assert_eq!(1000, discover_all(&filtered, &urls).await);
+        let reads = filtered.db.inner().reads.load(Ordering::SeqCst);
+        // Only the false positives are read.
+        assert!(reads < 50, "{reads} reads of new urls");
+        assert_eq!(0, discover_all(&filtered, &urls).await);
+        assert_eq!(
+            reads + 1000,
+            filtered.db.inner().reads.load(Ordering::SeqCst)
+        );
+        let stats = filtered.seen_filter_stats().unwrap();
+        assert_eq!(1000, stats.skipped_reads + stats.false_positives);
+        assert_eq!(reads as u64, stats.false_positives);
+        assert!(stats.layers > 16, "The filter did not grow: {stats:?}");
+
+        // The filter is built from the database.
+        let crawled = UrlWithDepth::from_url("https://www.example.com/crawled").unwrap();
+        filtered
+            .update_link_state_no_payload(&crawled, LinkStateKind::Crawled, None, None)
+            .await
+            .unwrap();
+        let rebuilt = DatabaseLinkStateManager::with_db(
+            CountingLinkStateDB {
+                db: filtered.db.inner().db.clone(),
+                reads: AtomicUsize::new(0),
+            },
+            None,
+        )
+        .with_seen_filter(&config);
+        assert_eq!(1001, rebuilt.seen_filter_stats().unwrap().keys);
+        assert_eq!(0, discover_all(&rebuilt, &urls).await);
+        assert_eq!(0, discover_all(&rebuilt, &[crawled]).await);
+        assert_eq!(1001, rebuilt.db.inner().reads.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_discoveries_report_an_url_once() {
+        defer!(destroy_db("test/lnk_seen_db_concurrent").unwrap(););
+        let manager = Arc::new(
+            counting_manager("test/lnk_seen_db_concurrent")
+                .with_seen_filter(&SeenFilterConfig { expected_urls: 16 }),
+        );
+        let urls = Arc::new(
+            (0..200)
+                .map(|i| UrlWithDepth::from_url(format!("https://www.example.com/{i}")).unwrap())
+                .collect::<Vec<_>>(),
+        );
+        let workers = (0..8)
+            .map(|_| {
+                let manager = manager.clone();
+                let urls = urls.clone();
+                tokio::spawn(async move { discover_all(&manager, &urls).await })
+            })
+            .collect::<Vec<_>>();
+        let mut discovered = 0;
+        for worker in workers {
+            discovered += worker.await.unwrap();
+        }
+        assert_eq!(200, discovered);
+        for url in urls.iter() {
+            assert_eq!(
+                LinkStateKind::Discovered,
+                manager.get_link_state(url).await.unwrap().unwrap().kind()
+            );
+        }
+    }
+}
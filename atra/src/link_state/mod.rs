@@ -12,17 +12,23 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod cache;
 mod db;
 mod errors;
 mod kind;
 mod manager;
+mod origins;
+mod seen;
 mod state;
 mod traits;
 
+pub use cache::*;
 pub use db::*;
 pub use errors::*;
 pub use kind::*;
 pub use manager::DatabaseLinkStateManager;
+pub use origins::{LinkStatePage, OriginLinkStateSummary, OriginUrlLinkState};
+pub use seen::*;
 pub use state::*;
 pub use traits::*;
 
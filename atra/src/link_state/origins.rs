@@ -0,0 +1,106 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::link_state::LinkStateKind;
+use serde::Serialize;
+use time::OffsetDateTime;
+
+/// The separator between the origin and the url in a key of the origin index.
+/// Sorts before every character of an origin, hence the urls of an origin are contiguous.
+pub(crate) const ORIGIN_INDEX_SEPARATOR: u8 = 0;
+
+/// The key of [url] in the origin index.
+pub(crate) fn origin_index_key(origin: &str, url: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + 1 + url.len());
+    key.extend_from_slice(origin.as_bytes());
+    key.push(ORIGIN_INDEX_SEPARATOR);
+    key.extend_from_slice(url);
+    key
+}
+
+/// The prefix of all keys of [origin] in the origin index.
+pub(crate) fn origin_index_prefix(origin: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + 1);
+    key.extend_from_slice(origin);
+    key.push(ORIGIN_INDEX_SEPARATOR);
+    key
+}
+
+/// The first key after all keys of [origin] in the origin index.
+pub(crate) fn origin_index_upper_bound(origin: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + 1);
+    key.extend_from_slice(origin);
+    key.push(ORIGIN_INDEX_SEPARATOR + 1);
+    key
+}
+
+/// A page of a listing, [next_offset] is set if there are more entries.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct LinkStatePage<T> {
+    pub offset: usize,
+    pub limit: usize,
+    pub items: Vec<T>,
+    pub next_offset: Option<usize>,
+}
+
+/// The link states of the urls of an origin, counted by their kind.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct OriginLinkStateSummary {
+    pub origin: String,
+    /// The crawled urls, including the processed and stored ones.
+    pub crawled: u64,
+    /// The urls failed with an internal error.
+    pub errored: u64,
+    /// The discovered urls and the urls reserved for a crawl.
+    pub queued: u64,
+    /// The latest state change of a crawled url.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_crawled_at: Option<OffsetDateTime>,
+}
+
+impl OriginLinkStateSummary {
+    pub fn new(origin: String) -> Self {
+        Self {
+            origin,
+            crawled: 0,
+            errored: 0,
+            queued: 0,
+            last_crawled_at: None,
+        }
+    }
+
+    /// Counts an url in the state [kind] set at [timestamp].
+    pub fn count(&mut self, kind: LinkStateKind, timestamp: OffsetDateTime) {
+        match kind {
+            LinkStateKind::Discovered | LinkStateKind::ReservedForCrawl => self.queued += 1,
+            LinkStateKind::Crawled | LinkStateKind::ProcessedAndStored => {
+                self.crawled += 1;
+                if self.last_crawled_at.map_or(true, |last| last < timestamp) {
+                    self.last_crawled_at = Some(timestamp);
+                }
+            }
+            LinkStateKind::InternalError => self.errored += 1,
+            LinkStateKind::Unset | LinkStateKind::Unknown(_) => {}
+        }
+    }
+}
+
+/// The link state of a single url of an origin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct OriginUrlLinkState {
+    pub url: String,
+    pub kind: LinkStateKind,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+}
@@ -0,0 +1,174 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::toolkit::bloom::ScalableBloomFilter;
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+/// The number of independently locked shards of a [SeenFilter].
+const SHARDS: usize = 16;
+
+/// The false positive rate the [SeenFilter] is sized for.
+pub const SEEN_FILTER_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+/// The state of a [SeenFilter] since its creation.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct SeenFilterStats {
+    /// The urls in the filter.
+    pub keys: u64,
+    /// The layers of all shards, a shard grows by a layer when it is full.
+    pub layers: usize,
+    /// The estimated probability that an unknown url is reported as seen.
+    pub estimated_false_positive_rate: f64,
+    /// The discovered urls that were not looked up in the database.
+    pub skipped_reads: u64,
+    /// The discovered urls reported as seen but missing in the database.
+    pub false_positives: u64,
+}
+
+/// A sharded bloom filter of the urls with a link state. An url not in the filter has
+/// no link state, an url in the filter may have one.
+///
+/// Every write holds the lock of the shard of the url while the database is written,
+/// a discovered url is therefore only reported as new once, even by concurrent workers.
+#[derive(Debug)]
+pub struct SeenFilter {
+    shards: Vec<Mutex<ScalableBloomFilter>>,
+    hasher: RandomState,
+    skipped_reads: AtomicU64,
+    false_positives: AtomicU64,
+}
+
+impl SeenFilter {
+    /// Creates a filter for [expected_urls], it grows if more urls are inserted.
+    pub fn new(expected_urls: usize) -> Self {
+        let per_shard = expected_urls.div_ceil(SHARDS);
+        Self {
+            shards: (0..SHARDS)
+                .map(|_| {
+                    Mutex::new(ScalableBloomFilter::new(
+                        per_shard,
+                        SEEN_FILTER_FALSE_POSITIVE_RATE,
+                    ))
+                })
+                .collect(),
+            hasher: RandomState::new(),
+            skipped_reads: AtomicU64::new(0),
+            false_positives: AtomicU64::new(0),
+        }
+    }
+
+    fn shard(&self, key: &[u8]) -> MutexGuard<ScalableBloomFilter> {
+        let index = self.hasher.hash_one(key) as usize % self.shards.len();
+        self.shards[index].lock().unwrap()
+    }
+
+    /// Adds [key] without writing anything, used while the filter is built.
+    pub fn insert(&self, key: &[u8]) {
+        self.shard(key).insert(key);
+    }
+
+    /// Writes the state of [key] with [write] and adds it to the filter.
+    pub fn write<E>(&self, key: &[u8], write: impl FnOnce() -> Result<(), E>) -> Result<(), E> {
+        let mut shard = self.shard(key);
+        write()?;
+        shard.insert(key);
+        Ok(())
+    }
+
+    /// Writes the first state of [key] with [write] and returns true if it had none.
+    /// [is_known] is only asked if the filter may contain [key].
+    pub fn discover<E>(
+        &self,
+        key: &[u8],
+        is_known: impl FnOnce() -> Result<bool, E>,
+        write: impl FnOnce() -> Result<(), E>,
+    ) -> Result<bool, E> {
+        let mut shard = self.shard(key);
+        if !shard.may_contain(key) {
+            self.skipped_reads.fetch_add(1, Ordering::Relaxed);
+        } else if is_known()? {
+            return Ok(false);
+        } else {
+            self.false_positives.fetch_add(1, Ordering::Relaxed);
+        }
+        write()?;
+        shard.insert(key);
+        Ok(true)
+    }
+
+    pub fn stats(&self) -> SeenFilterStats {
+        let mut stats = SeenFilterStats {
+            skipped_reads: self.skipped_reads.load(Ordering::Relaxed),
+            false_positives: self.false_positives.load(Ordering::Relaxed),
+            ..SeenFilterStats::default()
+        };
+        for shard in &self.shards {
+            let shard = shard.lock().unwrap();
+            stats.keys += shard.keys();
+            stats.layers += shard.layers();
+            // An url belongs to a single shard, every shard is equally likely.
+            stats.estimated_false_positive_rate +=
+                shard.estimated_false_positive_rate() / self.shards.len() as f64;
+        }
+        stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SeenFilter;
+    use std::collections::HashSet;
+    use std::convert::Infallible;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn reports_every_url_as_new_once() {
+        let filter = Arc::new(SeenFilter::new(64));
+        let written = Arc::new(Mutex::new(HashSet::new()));
+        let threads = (0..8)
+            .map(|_| {
+                let filter = filter.clone();
+                let written = written.clone();
+                std::thread::spawn(move || {
+                    (0..1000)
+                        .filter(|i| {
+                            let key = format!("https://www.example.com/{i}");
+                            filter
+                                .discover(
+                                    key.as_bytes(),
+                                    || Ok::<_, Infallible>(written.lock().unwrap().contains(&key)),
+                                    || {
+                                        assert!(written.lock().unwrap().insert(key.clone()));
+                                        Ok(())
+                                    },
+                                )
+                                .unwrap()
+                        })
+                        .count()
+                })
+            })
+            .collect::<Vec<_>>();
+        let discovered: usize = threads.into_iter().map(|value| value.join().unwrap()).sum();
+        assert_eq!(1000, discovered);
+
+        let stats = filter.stats();
+        assert_eq!(1000, stats.skipped_reads + stats.false_positives);
+        assert!(stats.keys <= 1000);
+        assert!(stats.layers > 16, "The filter did not grow: {stats:?}");
+        assert!(stats.estimated_false_positive_rate < 0.02, "{stats:?}");
+    }
+}
@@ -310,7 +310,7 @@ impl RawLinkState {
         Self::fold_merge_linkstate(merge_result, key, operand)
     }
 
-    #[cfg(test)]
+    /// Applies the merge operator of the database to [existing_val] and [operands].
     pub fn merge_linkstate_simulated<I, T>(
         key: impl AsRef<[u8]>,
         existing_val: Option<impl AsRef<[u8]>>,
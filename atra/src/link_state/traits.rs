@@ -14,11 +14,14 @@
 
 use crate::link_state::kind::LinkStateKind;
 use crate::link_state::state::LinkStateLike;
-use crate::link_state::{IsSeedYesNo, LinkStateDBError, RawLinkState, RecrawlYesNo};
+use crate::link_state::{
+    IsSeedYesNo, LinkStateCacheStats, LinkStateDBError, RawLinkState, RecrawlYesNo, SeenFilterStats,
+};
 use crate::url::UrlWithDepth;
 use std::error::Error;
 use std::ops::RangeBounds;
 use std::time::Duration;
+use time::OffsetDateTime;
 
 /// Manages the linkstate
 pub trait LinkStateManager {
@@ -27,6 +30,16 @@ pub trait LinkStateManager {
     /// The number of crawled websites
     fn crawled_websites(&self) -> Result<u64, Self::Error>;
 
+    /// The hits and misses of the cache of the link states, None without a cache.
+    fn cache_stats(&self) -> Option<LinkStateCacheStats> {
+        None
+    }
+
+    /// The state of the filter of the seen urls, None without a filter.
+    fn seen_filter_stats(&self) -> Option<SeenFilterStats> {
+        None
+    }
+
     /// Sets the state of the link
     async fn update_link_state<P>(
         &self,
@@ -72,6 +85,31 @@ pub trait LinkStateManager {
             .await
     }
 
+    /// Sets the state of the link as if it was set at [timestamp], e.g. the fetch date of an
+    /// imported page. A state set later than [timestamp] is kept.
+    async fn update_link_state_at(
+        &self,
+        url: &UrlWithDepth,
+        state: LinkStateKind,
+        timestamp: OffsetDateTime,
+    ) -> Result<(), Self::Error>;
+
+    /// Sets the state of a discovered link to [LinkStateKind::Discovered] if it has no state.
+    /// Returns true if the link had no state and has to be queued.
+    async fn discover_link_state(
+        &self,
+        url: &UrlWithDepth,
+        is_seed: Option<IsSeedYesNo>,
+        recrawl: Option<RecrawlYesNo>,
+    ) -> Result<bool, Self::Error> {
+        if self.get_link_state(url).await?.is_some() {
+            return Ok(false);
+        }
+        self.update_link_state_no_payload(url, LinkStateKind::Discovered, is_seed, recrawl)
+            .await?;
+        Ok(true)
+    }
+
     fn get_link_state_sync(&self, url: &UrlWithDepth) -> Result<Option<RawLinkState>, Self::Error>;
 
     /// Gets the state of the current url
@@ -12,39 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::process::ExitCode;
-use crate::app::{exec_args, AtraArgs};
+use atra::{exec_args, AtraArgs};
 use clap::Parser;
-
-mod app;
-mod blacklist;
-mod client;
-mod config;
-mod contexts;
-mod crawl;
-mod data;
-mod database;
-mod decoding;
-mod extraction;
-mod fetching;
-mod format;
-mod gdbr;
-mod html;
-mod io;
-mod link_state;
-mod queue;
-mod recrawl_management;
-mod robots;
-mod runtime;
-mod seed;
-mod stores;
-mod sync;
-#[cfg(test)]
-mod test_impls;
-mod toolkit;
-mod url;
-mod warc_ext;
-mod web_graph;
+use std::process::ExitCode;
 
 fn main() -> ExitCode {
     exec_args(AtraArgs::parse())
@@ -0,0 +1,637 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod signals;
+
+pub use signals::*;
+
+use crate::client::traits::{AtraClient, AtraResponse};
+use crate::contexts::traits::{SupportsConfigs, SupportsOriginMetadata};
+use crate::crawl::CrawlResult;
+use crate::database::{DBActionType, DatabaseError, LazyBase64Value, RawDatabaseError};
+use crate::robots::RobotsInformation;
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use crate::{db_health_check, declare_column_families};
+use rocksdb::{IteratorMode, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+/// Longer values are cut, a signal is no place for a document.
+const MAX_VALUE_LENGTH: usize = 256;
+
+/// The path of the security.txt of an origin.
+const SECURITY_TXT_PATH: &str = "/.well-known/security.txt";
+
+/// The values of a signal counted by their occurrences. Conflicting values are all kept,
+/// but only up to a limit of distinct values, the occurrences of further values are counted
+/// as overflow.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ValueDistribution {
+    pub counts: BTreeMap<String, u64>,
+    /// The occurrences of the values that did not fit anymore.
+    pub overflow: u64,
+}
+
+impl ValueDistribution {
+    /// Counts an occurrence of [value], a new value is only kept if there are less than
+    /// [max_distinct_values] values.
+    pub fn add(&mut self, value: &str, max_distinct_values: usize) {
+        let value = match value.char_indices().nth(MAX_VALUE_LENGTH) {
+            Some((end, _)) => &value[..end],
+            None => value,
+        };
+        if let Some(count) = self.counts.get_mut(value) {
+            *count += 1;
+        } else if self.counts.len() < max_distinct_values {
+            self.counts.insert(value.to_string(), 1);
+        } else {
+            self.overflow += 1;
+        }
+    }
+
+    /// The number of all occurrences.
+    pub fn total(&self) -> u64 {
+        self.counts.values().sum::<u64>() + self.overflow
+    }
+}
+
+/// The well-known uris of an origin, fetched at the first contact in the crawl.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct WellKnownCapture {
+    pub captured_at: OffsetDateTime,
+    /// The size of the robots.txt in bytes, None if the origin has none.
+    pub robots_txt_size: Option<u64>,
+    /// The security.txt, None if the origin has none or it was not fetched.
+    pub security_txt: Option<SecurityTxt>,
+}
+
+/// The metadata of an origin, aggregated from the [PageSignals] of its pages.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OriginMetadata {
+    /// The number of pages the signals were taken from.
+    pub pages: u64,
+    pub generators: ValueDistribution,
+    pub feeds: ValueDistribution,
+    /// The `lang` attributes of the html pages.
+    pub languages: ValueDistribution,
+    /// The values of the `Server` header.
+    pub servers: ValueDistribution,
+    pub well_known: Option<WellKnownCapture>,
+}
+
+impl OriginMetadata {
+    /// Adds the [signals] of a page, every signal keeps at most [max_distinct_values] values.
+    pub fn observe(&mut self, signals: &PageSignals, max_distinct_values: usize) {
+        self.pages += 1;
+        if let Some(ref generator) = signals.generator {
+            self.generators.add(generator, max_distinct_values);
+        }
+        for feed in &signals.feeds {
+            self.feeds.add(feed, max_distinct_values);
+        }
+        if let Some(ref lang) = signals.lang {
+            self.languages.add(lang, max_distinct_values);
+        }
+        if let Some(ref server) = signals.server {
+            self.servers.add(server, max_distinct_values);
+        }
+    }
+}
+
+/// The metadata of all origins and the origins with a pending capture of the well-known uris.
+#[derive(Debug, Clone, Default)]
+pub struct OriginMetadataRegistry {
+    metadata: HashMap<AtraUrlOrigin, OriginMetadata>,
+    reserved: HashSet<AtraUrlOrigin>,
+}
+
+impl OriginMetadataRegistry {
+    /// Returns true if the well-known uris of [origin] were neither captured nor reserved before.
+    pub fn reserve(&mut self, origin: &AtraUrlOrigin) -> bool {
+        let captured = self
+            .metadata
+            .get(origin)
+            .is_some_and(|metadata| metadata.well_known.is_some());
+        !captured && self.reserved.insert(origin.clone())
+    }
+
+    pub fn insert(&mut self, origin: &AtraUrlOrigin, metadata: OriginMetadata) {
+        self.metadata.insert(origin.clone(), metadata);
+    }
+
+    pub fn get(&self, origin: &AtraUrlOrigin) -> Option<&OriginMetadata> {
+        self.metadata.get(origin)
+    }
+
+    /// The metadata of all origins, ordered by the origin.
+    pub fn origins(&self) -> Vec<(AtraUrlOrigin, OriginMetadata)> {
+        let mut origins = self
+            .metadata
+            .iter()
+            .map(|(origin, metadata)| (origin.clone(), metadata.clone()))
+            .collect::<Vec<_>>();
+        origins.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        origins
+    }
+}
+
+/// Keeps track of the metadata of each origin.
+pub trait OriginMetadataManager {
+    /// Reserves the capture of the well-known uris of [origin]. Returns false if they were
+    /// already captured or reserved, every origin is captured once per crawl.
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool;
+
+    /// Applies [update] to the metadata of [origin] and stores it.
+    /// An origin without metadata starts with an empty one.
+    fn update<F>(&self, origin: &AtraUrlOrigin, update: F) -> Result<(), DatabaseError>
+    where
+        F: FnOnce(&mut OriginMetadata);
+
+    /// The metadata of [origin], if any of its pages was observed.
+    fn get(&self, origin: &AtraUrlOrigin) -> Option<OriginMetadata>;
+
+    /// The metadata of all origins, ordered by the origin.
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginMetadata)>;
+}
+
+/// Stores the metadata in the database, the metadata survives a restart.
+#[derive(Debug)]
+pub struct OriginMetadataDatabaseManager {
+    db: Arc<DB>,
+    registry: Mutex<OriginMetadataRegistry>,
+}
+
+impl OriginMetadataDatabaseManager {
+    declare_column_families! {
+        self.db => cf_handle(ORIGIN_METADATA_DB_CF)
+    }
+
+    pub fn new(db: Arc<DB>) -> Result<Self, DatabaseError> {
+        db_health_check!(db: [
+            Self::ORIGIN_METADATA_DB_CF => (
+                if test origin_metadata_cf_options
+                else "The origin metadata cf is missing!"
+            )
+        ]);
+
+        let mut manager = Self {
+            db,
+            registry: Mutex::default(),
+        };
+        manager.registry = Mutex::new(manager.load_registry()?);
+        Ok(manager)
+    }
+
+    /// Reads the stored metadata of all origins.
+    fn load_registry(&self) -> Result<OriginMetadataRegistry, DatabaseError> {
+        let mut registry = OriginMetadataRegistry::default();
+        let iter = self.db.iterator_cf(&self.cf_handle(), IteratorMode::Start);
+        for entry in iter {
+            let (key, value) =
+                entry.enrich_no_key(Self::ORIGIN_METADATA_DB_CF, DBActionType::Read)?;
+            let metadata: OriginMetadata = bincode::deserialize(&value).map_err(|err| {
+                DatabaseError::from_deserialisation(
+                    Self::ORIGIN_METADATA_DB_CF,
+                    String::from_utf8_lossy(&key).into_owned(),
+                    LazyBase64Value(value.to_vec()),
+                    err,
+                )
+            })?;
+            let origin = AtraUrlOrigin::from(String::from_utf8_lossy(&key).as_ref());
+            registry.insert(&origin, metadata);
+        }
+        Ok(registry)
+    }
+
+    fn put(&self, origin: &AtraUrlOrigin, metadata: &OriginMetadata) -> Result<(), DatabaseError> {
+        let value = bincode::serialize(metadata).map_err(|err| {
+            DatabaseError::from_serialisation(
+                Self::ORIGIN_METADATA_DB_CF,
+                origin.to_string(),
+                metadata.clone(),
+                err,
+            )
+        })?;
+        self.db
+            .put_cf(&self.cf_handle(), origin.as_bytes(), value)
+            .enrich_no_key(Self::ORIGIN_METADATA_DB_CF, DBActionType::Write)
+    }
+}
+
+impl OriginMetadataManager for OriginMetadataDatabaseManager {
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool {
+        self.registry.lock().unwrap().reserve(origin)
+    }
+
+    fn update<F>(&self, origin: &AtraUrlOrigin, update: F) -> Result<(), DatabaseError>
+    where
+        F: FnOnce(&mut OriginMetadata),
+    {
+        // Locked until the metadata is written, concurrent updates are never lost.
+        let mut registry = self.registry.lock().unwrap();
+        let mut metadata = registry.get(origin).cloned().unwrap_or_default();
+        update(&mut metadata);
+        self.put(origin, &metadata)?;
+        registry.insert(origin, metadata);
+        Ok(())
+    }
+
+    fn get(&self, origin: &AtraUrlOrigin) -> Option<OriginMetadata> {
+        self.registry.lock().unwrap().get(origin).cloned()
+    }
+
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginMetadata)> {
+        self.registry.lock().unwrap().origins()
+    }
+}
+
+/// Adds the signals of the page in [result] to the metadata of its origin.
+/// A failed update is only logged and never affects the crawl.
+pub fn observe_origin_metadata<C>(context: &C, result: &CrawlResult)
+where
+    C: SupportsOriginMetadata + SupportsConfigs,
+{
+    let Some(config) = context.configs().crawl.origin_metadata.as_ref() else {
+        return;
+    };
+    let (Some(signals), Some(origin)) = (
+        result.origin_signals.as_ref(),
+        result.meta.url.atra_origin(),
+    ) else {
+        return;
+    };
+    let updated = context
+        .get_origin_metadata_manager()
+        .update(&origin, |metadata| {
+            metadata.observe(signals, config.max_distinct_values)
+        });
+    if let Err(err) = updated {
+        log::error!("Failed to store the metadata of {origin}. {err}");
+    }
+}
+
+/// Captures the robots.txt and the security.txt of the origin of [url] at the first contact
+/// in the crawl. A failed capture is only logged and never affects the crawl.
+pub async fn capture_well_known<C, Client, R>(
+    context: &C,
+    client: &Client,
+    robots: &R,
+    url: &UrlWithDepth,
+) where
+    C: SupportsOriginMetadata + SupportsConfigs,
+    Client: AtraClient,
+    R: RobotsInformation,
+{
+    let Some(config) = context.configs().crawl.origin_metadata.as_ref() else {
+        return;
+    };
+    let Some(origin) = url.atra_origin() else {
+        return;
+    };
+    let manager = context.get_origin_metadata_manager();
+    if !manager.reserve_capture(&origin) {
+        return;
+    }
+    let robots_txt_size = match robots.get_or_retrieve(client, url).await {
+        Ok(robots) => robots.size().map(|size| size as u64),
+        Err(err) => {
+            log::debug!("Failed to retrieve the robots.txt of {origin}. {err}");
+            None
+        }
+    };
+    let security_txt = if config.capture_security_txt {
+        fetch_security_txt(client, url).await
+    } else {
+        None
+    };
+    let capture = WellKnownCapture {
+        captured_at: OffsetDateTime::now_utc(),
+        robots_txt_size,
+        security_txt,
+    };
+    if let Err(err) = manager.update(&origin, |metadata| metadata.well_known = Some(capture)) {
+        log::error!("Failed to store the metadata of {origin}. {err}");
+    }
+}
+
+/// Fetches and parses the security.txt of the origin of [url].
+async fn fetch_security_txt<Client: AtraClient>(
+    client: &Client,
+    url: &UrlWithDepth,
+) -> Option<SecurityTxt> {
+    let target = url.url.as_url()?.join(SECURITY_TXT_PATH).ok()?;
+    let response = match client.get(target.as_str()).await {
+        Ok(response) => response,
+        Err(err) => {
+            log::debug!("Failed to fetch {target}. {err}");
+            return None;
+        }
+    };
+    if !response.status().is_success() {
+        return None;
+    }
+    match response.bytes().await {
+        Ok(body) => SecurityTxt::parse(body.as_ref()),
+        Err(err) => {
+            log::debug!("Failed to read {target}. {err}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        capture_well_known, observe_origin_metadata, OriginMetadata, OriginMetadataDatabaseManager,
+        OriginMetadataManager, SecurityTxt, ValueDistribution, WellKnownCapture,
+    };
+    use crate::client::traits::AtraClient;
+    use crate::config::{Config, OriginMetadataConfig};
+    use crate::contexts::traits::{SupportsOriginMetadata, SupportsRobotsManager};
+    use crate::crawl::analyze_page;
+    use crate::data::RawVecData;
+    use crate::database::open_db;
+    use crate::fetching::FetchedRequestData;
+    use crate::robots::GeneralRobotsInformation;
+    use crate::test_impls::{FakeClient, FakeClientProvider, FakeResponse, TestContext};
+    use crate::url::{AtraUrlOrigin, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, SERVER};
+    use reqwest::StatusCode;
+    use std::collections::BTreeMap;
+    use std::sync::Arc;
+    use time::OffsetDateTime;
+
+    /// The metadata of an origin with two pages.
+    pub fn sample_origin_metadata() -> OriginMetadata {
+        let mut metadata = OriginMetadata {
+            pages: 2,
+            well_known: Some(WellKnownCapture {
+                captured_at: OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap(),
+                robots_txt_size: Some(42),
+                security_txt: Some(SecurityTxt {
+                    size: 64,
+                    contacts: vec!["mailto:security@example.com".to_string()],
+                    expires: None,
+                    policies: Vec::new(),
+                }),
+            }),
+            ..OriginMetadata::default()
+        };
+        metadata.generators.add("Hugo 0.120", 16);
+        metadata.languages.add("en", 16);
+        metadata.languages.add("de", 16);
+        metadata.servers.add("nginx", 16);
+        metadata.servers.add("nginx", 16);
+        metadata
+    }
+
+    fn html_page(server: &'static str, html: &str) -> FetchedRequestData {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        headers.insert(SERVER, HeaderValue::from_static(server));
+        FetchedRequestData::new(
+            RawVecData::from_vec(html.as_bytes().to_vec()),
+            Some(headers),
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        )
+    }
+
+    fn context(max_distinct_values: usize) -> TestContext<FakeClientProvider> {
+        let mut config = Config::default();
+        config.crawl.origin_metadata = Some(OriginMetadataConfig {
+            max_distinct_values,
+            ..OriginMetadataConfig::default()
+        });
+        TestContext::new(config, FakeClientProvider::new())
+    }
+
+    fn counts(values: &[(&str, u64)]) -> BTreeMap<String, u64> {
+        values
+            .iter()
+            .map(|(value, count)| (value.to_string(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn distributions_are_bounded() {
+        let mut distribution = ValueDistribution::default();
+        for value in ["a", "b", "a", "c", "b", "a", "d"] {
+            distribution.add(value, 2);
+        }
+        assert_eq!(counts(&[("a", 3), ("b", 2)]), distribution.counts);
+        assert_eq!(2, distribution.overflow);
+        assert_eq!(7, distribution.total());
+
+        distribution.add(&"x".repeat(1000), 3);
+        assert_eq!(
+            Some(&1),
+            distribution
+                .counts
+                .get(&"x".repeat(super::MAX_VALUE_LENGTH))
+        );
+    }
+
+    #[tokio::test]
+    async fn aggregates_the_pages_of_an_origin() {
+        let context = context(2);
+        let pages = [
+            (
+                "https://www.example.com/",
+                "nginx",
+                r#"<html lang="en"><head><meta name="generator" content="WordPress 6.4"><link rel="alternate" type="application/rss+xml" href="/feed/"></head><body>Home</body></html>"#,
+            ),
+            (
+                "https://www.example.com/about",
+                "nginx",
+                r#"<html lang="EN"><head><meta name="generator" content="WordPress 6.4"><link rel="alternate" type="application/rss+xml" href="https://www.example.com/feed/"></head><body>About</body></html>"#,
+            ),
+            (
+                "https://www.example.com/de/",
+                "Apache",
+                r#"<html lang="de"><head><meta name="generator" content="Hugo 0.120"></head><body>Start</body></html>"#,
+            ),
+            (
+                "https://www.example.com/fr/",
+                "cloudflare",
+                r#"<html lang="fr"><head><link rel="alternate" type="application/atom+xml" href="/fr/atom.xml"></head><body>Accueil</body></html>"#,
+            ),
+            (
+                "https://www.example.com/plain",
+                "nginx",
+                "<html><body>No signals</body></html>",
+            ),
+        ];
+        for (url, server, html) in pages {
+            let target = UrlWithDepth::from_url(url).unwrap();
+            let result = analyze_page(&context, html_page(server, html), &target)
+                .await
+                .unwrap();
+            observe_origin_metadata(&context, &result);
+        }
+        // Another origin is kept apart.
+        let other = UrlWithDepth::from_url("https://www.example.org/").unwrap();
+        let result = analyze_page(
+            &context,
+            html_page("nginx", r#"<html lang="it"></html>"#),
+            &other,
+        )
+        .await
+        .unwrap();
+        observe_origin_metadata(&context, &result);
+
+        let manager = context.get_origin_metadata_manager();
+        assert_eq!(2, manager.origins().len());
+        let metadata = manager
+            .get(&AtraUrlOrigin::from("www.example.com"))
+            .unwrap();
+        assert_eq!(5, metadata.pages);
+        assert_eq!(
+            counts(&[("Hugo 0.120", 1), ("WordPress 6.4", 2)]),
+            metadata.generators.counts
+        );
+        assert_eq!(0, metadata.generators.overflow);
+        assert_eq!(
+            counts(&[
+                ("https://www.example.com/feed/", 2),
+                ("https://www.example.com/fr/atom.xml", 1)
+            ]),
+            metadata.feeds.counts
+        );
+        // The third language does not fit anymore.
+        assert_eq!(counts(&[("de", 1), ("en", 2)]), metadata.languages.counts);
+        assert_eq!(1, metadata.languages.overflow);
+        assert_eq!(4, metadata.languages.total());
+        assert_eq!(
+            counts(&[("Apache", 1), ("nginx", 3)]),
+            metadata.servers.counts
+        );
+        assert_eq!(1, metadata.servers.overflow);
+        assert_eq!(None, metadata.well_known);
+    }
+
+    #[tokio::test]
+    async fn nothing_is_observed_if_disabled() {
+        let context = TestContext::new(Config::default(), FakeClientProvider::new());
+        let target = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let result = analyze_page(
+            &context,
+            html_page("nginx", r#"<html lang="en"></html>"#),
+            &target,
+        )
+        .await
+        .unwrap();
+        assert_eq!(None, result.origin_signals);
+        observe_origin_metadata(&context, &result);
+        assert!(context.get_origin_metadata_manager().origins().is_empty());
+    }
+
+    #[tokio::test]
+    async fn captures_the_well_known_uris_once() {
+        let context = context(16);
+        let client = FakeClient::new();
+        let security_txt =
+            b"Contact: mailto:security@example.com\nExpires: 2030-01-01T00:00:00.000Z\n";
+        let robots_txt = b"User-agent: *\nDisallow: /private/\n";
+        for (path, body) in [
+            ("https://www.example.com/robots.txt", robots_txt.as_slice()),
+            (
+                "https://www.example.com/.well-known/security.txt",
+                security_txt.as_slice(),
+            ),
+        ] {
+            client.insert(
+                path.parse().unwrap(),
+                Ok(FakeResponse::new(
+                    Some(FetchedRequestData::new(
+                        RawVecData::from_vec(body.to_vec()),
+                        None,
+                        StatusCode::OK,
+                        None,
+                        None,
+                        false,
+                    )),
+                    0,
+                )),
+            );
+        }
+        let robots = GeneralRobotsInformation::new(
+            context.get_robots_manager(),
+            client.user_agent().to_string(),
+            None,
+        );
+        let url = UrlWithDepth::from_url("https://www.example.com/index.html").unwrap();
+        capture_well_known(&context, &client, &robots, &url).await;
+        capture_well_known(&context, &client, &robots, &url).await;
+
+        let metadata = context
+            .get_origin_metadata_manager()
+            .get(&AtraUrlOrigin::from("www.example.com"))
+            .unwrap();
+        assert_eq!(0, metadata.pages);
+        let well_known = metadata.well_known.unwrap();
+        assert_eq!(Some(robots_txt.len() as u64), well_known.robots_txt_size);
+        let security_txt = well_known.security_txt.unwrap();
+        assert_eq!(
+            vec!["mailto:security@example.com".to_string()],
+            security_txt.contacts
+        );
+        assert_eq!(
+            1,
+            client
+                .requests()
+                .iter()
+                .filter(|(uri, _)| uri.to_string().ends_with("/security.txt"))
+                .count()
+        );
+
+        // An origin without both files.
+        let url = UrlWithDepth::from_url("https://www.example.org/").unwrap();
+        capture_well_known(&context, &client, &robots, &url).await;
+        let well_known = context
+            .get_origin_metadata_manager()
+            .get(&AtraUrlOrigin::from("www.example.org"))
+            .unwrap()
+            .well_known
+            .unwrap();
+        assert_eq!(None, well_known.robots_txt_size);
+        assert_eq!(None, well_known.security_txt);
+    }
+
+    #[test]
+    fn the_metadata_survives_a_restart() {
+        let dir = Utf8TempDir::new().unwrap();
+        let origin = AtraUrlOrigin::from("example.com");
+        {
+            let db = Arc::new(open_db(dir.path()).unwrap());
+            let manager = OriginMetadataDatabaseManager::new(db).unwrap();
+            assert!(manager.reserve_capture(&origin));
+            manager
+                .update(&origin, |metadata| *metadata = sample_origin_metadata())
+                .unwrap();
+        }
+        let db = Arc::new(open_db(dir.path()).unwrap());
+        let manager = OriginMetadataDatabaseManager::new(db).unwrap();
+        assert_eq!(
+            vec![(origin.clone(), sample_origin_metadata())],
+            manager.origins()
+        );
+        assert!(!manager.reserve_capture(&origin));
+    }
+}
@@ -0,0 +1,250 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::static_selectors;
+use crate::url::UrlWithDepth;
+use reqwest::header::{HeaderMap, SERVER};
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+
+static_selectors! {
+    [
+        HTML_WITH_LANG = "html[lang]"
+        META_GENERATOR = "meta[name][content]"
+        LINK_WITH_TYPE = "link[rel][type][href]"
+    ]
+}
+
+/// The types of the `link` elements announcing a feed.
+const FEED_TYPES: [&str; 3] = [
+    "application/rss+xml",
+    "application/atom+xml",
+    "application/feed+json",
+];
+
+/// The feeds taken from a single page, a page announcing more is most likely no blog.
+const MAX_FEEDS_PER_PAGE: usize = 8;
+
+/// The values of a security.txt kept per field.
+const MAX_SECURITY_TXT_VALUES: usize = 8;
+
+/// Only the start of a larger security.txt is read.
+pub const MAX_SECURITY_TXT_SIZE: usize = 32 * 1024;
+
+/// The cheap signals of a single page, aggregated into the metadata of its origin.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PageSignals {
+    /// The content of the `generator` meta tag, like `WordPress 6.4`.
+    pub generator: Option<String>,
+    /// The absolute urls of the feeds announced by the `link` elements.
+    pub feeds: Vec<String>,
+    /// The `lang` attribute of the `html` element in lowercase.
+    pub lang: Option<String>,
+    /// The value of the `Server` header.
+    pub server: Option<String>,
+}
+
+impl PageSignals {
+    /// Extracts the signals from the [headers] of the response of [url] and its [html],
+    /// if it is an html page.
+    pub fn extract(url: &UrlWithDepth, headers: Option<&HeaderMap>, html: Option<&str>) -> Self {
+        let server = headers
+            .and_then(|headers| headers.get(SERVER))
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(str::to_string);
+        let Some(html) = html else {
+            return Self {
+                server,
+                ..Self::default()
+            };
+        };
+        let html = Html::parse_document(html);
+        let lang = html
+            .select(&HTML_WITH_LANG)
+            .next()
+            .and_then(|element| element.attr("lang"))
+            .map(|lang| lang.trim().to_ascii_lowercase())
+            .filter(|lang| !lang.is_empty());
+        let generator = html
+            .select(&META_GENERATOR)
+            .find(|element| {
+                element
+                    .attr("name")
+                    .is_some_and(|name| name.trim().eq_ignore_ascii_case("generator"))
+            })
+            .and_then(|element| element.attr("content"))
+            .map(str::trim)
+            .filter(|content| !content.is_empty())
+            .map(str::to_string);
+        let base = url.url.as_url();
+        let mut feeds = Vec::new();
+        for element in html.select(&LINK_WITH_TYPE) {
+            let is_alternate = element.attr("rel").is_some_and(|rel| {
+                rel.split_ascii_whitespace()
+                    .any(|value| value.eq_ignore_ascii_case("alternate"))
+            });
+            let is_feed = element.attr("type").is_some_and(|kind| {
+                FEED_TYPES
+                    .iter()
+                    .any(|feed| kind.trim().eq_ignore_ascii_case(feed))
+            });
+            if !is_alternate || !is_feed {
+                continue;
+            }
+            let href = element.attr("href").unwrap_or_default().trim();
+            let Some(feed) = base.and_then(|base| base.join(href).ok()) else {
+                continue;
+            };
+            let feed = feed.to_string();
+            if !feeds.contains(&feed) {
+                feeds.push(feed);
+                if feeds.len() == MAX_FEEDS_PER_PAGE {
+                    break;
+                }
+            }
+        }
+        Self {
+            generator,
+            feeds,
+            lang,
+            server,
+        }
+    }
+}
+
+/// The fields of a security.txt (RFC 9116) relevant for an overview of the origin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SecurityTxt {
+    /// The size of the file in bytes.
+    pub size: u64,
+    /// The values of the `Contact` fields, like `mailto:security@example.com`.
+    pub contacts: Vec<String>,
+    /// The value of the `Expires` field.
+    pub expires: Option<String>,
+    /// The values of the `Policy` fields.
+    pub policies: Vec<String>,
+}
+
+impl SecurityTxt {
+    /// Parses the security.txt in [body]. Returns None if it has no `Contact` field, which is
+    /// required, servers answering every path with a html page fail here.
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let text = String::from_utf8_lossy(&body[..body.len().min(MAX_SECURITY_TXT_SIZE)]);
+        let mut contacts = Vec::new();
+        let mut expires = None;
+        let mut policies = Vec::new();
+        for line in text.lines() {
+            let line = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("contact") {
+                if contacts.len() < MAX_SECURITY_TXT_VALUES {
+                    contacts.push(value.to_string());
+                }
+            } else if name.eq_ignore_ascii_case("expires") {
+                expires.get_or_insert_with(|| value.to_string());
+            } else if name.eq_ignore_ascii_case("policy") {
+                if policies.len() < MAX_SECURITY_TXT_VALUES {
+                    policies.push(value.to_string());
+                }
+            }
+        }
+        if contacts.is_empty() {
+            return None;
+        }
+        Some(Self {
+            size: body.len() as u64,
+            contacts,
+            expires,
+            policies,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PageSignals, SecurityTxt};
+    use crate::url::UrlWithDepth;
+    use reqwest::header::{HeaderMap, HeaderValue, SERVER};
+
+    #[test]
+    fn extracts_the_signals_of_a_page() {
+        let mut headers = HeaderMap::new();
+        headers.insert(SERVER, HeaderValue::from_static(" nginx/1.25 "));
+        let url = UrlWithDepth::from_url("https://www.example.com/blog/post.html").unwrap();
+        let signals = PageSignals::extract(
+            &url,
+            Some(&headers),
+            Some(
+                r#"<html lang="en-US"><head>
+                <meta name="Generator" content="WordPress 6.4">
+                <link rel="alternate" type="application/rss+xml" href="/feed/">
+                <link rel="alternate nofollow" type="application/atom+xml" href="atom.xml">
+                <link rel="alternate" type="application/rss+xml" href="/feed/">
+                <link rel="alternate" type="text/html" href="/de/">
+                <link rel="stylesheet" type="text/css" href="/style.css">
+                </head><body></body></html>"#,
+            ),
+        );
+        assert_eq!(
+            PageSignals {
+                generator: Some("WordPress 6.4".to_string()),
+                feeds: vec![
+                    "https://www.example.com/feed/".to_string(),
+                    "https://www.example.com/blog/atom.xml".to_string()
+                ],
+                lang: Some("en-us".to_string()),
+                server: Some("nginx/1.25".to_string()),
+            },
+            signals
+        );
+
+        let signals = PageSignals::extract(&url, Some(&headers), None);
+        assert_eq!(Some("nginx/1.25"), signals.server.as_deref());
+        assert_eq!(None, signals.lang);
+        assert!(signals.feeds.is_empty());
+    }
+
+    #[test]
+    fn parses_a_security_txt() {
+        let body = b"# Our security policy\r\nContact: mailto:security@example.com\r\nContact: https://example.com/security\r\nExpires: 2030-01-01T00:00:00.000Z\r\nPolicy: https://example.com/policy\r\nUnknown: value\r\n";
+        assert_eq!(
+            Some(SecurityTxt {
+                size: body.len() as u64,
+                contacts: vec![
+                    "mailto:security@example.com".to_string(),
+                    "https://example.com/security".to_string()
+                ],
+                expires: Some("2030-01-01T00:00:00.000Z".to_string()),
+                policies: vec!["https://example.com/policy".to_string()],
+            }),
+            SecurityTxt::parse(body)
+        );
+        assert_eq!(
+            None,
+            SecurityTxt::parse(b"<html><body>Not Found</body></html>")
+        );
+    }
+}
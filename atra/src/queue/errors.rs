@@ -13,13 +13,34 @@
 // limitations under the License.
 
 use crate::url::ParseError;
+use std::path::PathBuf;
 use thiserror::Error;
 
+/// Error of a file of a [crate::queue::RawAgingQueueFile]
+#[derive(Debug, Error)]
+pub enum QueueFileError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("The queue file {0} is corrupted: {1}")]
+    Corrupted(PathBuf, &'static str),
+    #[error("The queue file {0} has the unsupported version {1}.")]
+    UnsupportedVersion(PathBuf, u16),
+    #[error("An element with {0} bytes is too big for the queue file.")]
+    ElementTooBig(usize),
+}
+
+impl QueueFileError {
+    /// Returns true if the file can not be opened, no matter how often it is tried.
+    pub fn is_unusable(&self) -> bool {
+        matches!(self, Self::Corrupted(..) | Self::UnsupportedVersion(..))
+    }
+}
+
 /// Error of an url queue file
 #[derive(Debug, Error)]
 pub enum QueueError {
     #[error(transparent)]
-    QueueFileError(#[from] queue_file::Error),
+    QueueFileError(#[from] QueueFileError),
     #[error(transparent)]
     EncodingError(#[from] bincode::Error),
     #[error(transparent)]
@@ -46,7 +67,7 @@ impl<T> TryFrom<RawQueueError<T>> for QueueError {
 #[derive(Debug, Error)]
 pub enum RawQueueError<T> {
     #[error(transparent)]
-    QueueFileError(#[from] queue_file::Error),
+    QueueFileError(#[from] QueueFileError),
     #[error(transparent)]
     EncodingError(#[from] bincode::Error),
     #[error(transparent)]
@@ -0,0 +1,292 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::Mutex;
+use time::OffsetDateTime;
+
+/// The journal is only compacted if it has at least this many records.
+const COMPACT_MIN_RECORDS: usize = 4096;
+/// The journal is compacted if it has this many records per url in progress.
+const COMPACT_RATIO: usize = 4;
+
+/// An url that was dequeued and not completed yet.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub origin: Option<AtraUrlOrigin>,
+    pub url: UrlWithDepth,
+    pub is_seed: bool,
+    #[serde(with = "time::serde::timestamp")]
+    pub dequeued_at: OffsetDateTime,
+}
+
+impl JournalEntry {
+    pub fn new(url: UrlWithDepth, is_seed: bool, dequeued_at: OffsetDateTime) -> Self {
+        Self {
+            origin: url.atra_origin(),
+            url,
+            is_seed,
+            dequeued_at,
+        }
+    }
+
+    fn key(&self) -> String {
+        self.url.try_as_str().into_owned()
+    }
+}
+
+/// A line of the journal file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum JournalRecord {
+    Dequeued(JournalEntry),
+    Completed { url: String },
+}
+
+#[derive(Debug)]
+struct JournalState {
+    file: File,
+    open: HashMap<String, JournalEntry>,
+    records: usize,
+}
+
+/// An append-only journal of the urls that were dequeued but not completed yet.
+/// After a crash the open entries are exactly the urls that were in progress.
+///
+/// Every record is a json line written with a single write, it survives a crash of the
+/// process but not necessarily of the system. The file is rewritten with only the open entries
+/// when it is opened and when most of its records are completed.
+#[derive(Debug)]
+pub struct DequeueJournal {
+    path: Utf8PathBuf,
+    state: Mutex<JournalState>,
+}
+
+impl DequeueJournal {
+    /// Opens the journal at [path] and replays it. Lines that can not be read,
+    /// like a torn last line, are skipped.
+    pub fn open(path: impl AsRef<Utf8Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut open = HashMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    let line = line?;
+                    if line.is_empty() {
+                        continue;
+                    }
+                    match serde_json::from_str(&line) {
+                        Ok(JournalRecord::Dequeued(entry)) => {
+                            open.insert(entry.key(), entry);
+                        }
+                        Ok(JournalRecord::Completed { url }) => {
+                            open.remove(&url);
+                        }
+                        Err(err) => log::warn!("Skip the damaged record {line:?} of {path}: {err}"),
+                    }
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        // Rewriting also removes a torn tail, the next record starts on a new line.
+        let file = Self::rewrite(&path, open.values())?;
+        let records = open.len();
+        Ok(Self {
+            path,
+            state: Mutex::new(JournalState {
+                file,
+                open,
+                records,
+            }),
+        })
+    }
+
+    /// Writes [entries] to a temporary file, replaces the journal with it and
+    /// returns the new journal opened for appending.
+    fn rewrite<'a>(
+        path: &Utf8Path,
+        entries: impl Iterator<Item = &'a JournalEntry>,
+    ) -> io::Result<File> {
+        let tmp = Utf8PathBuf::from(format!("{path}.tmp"));
+        {
+            let mut file = io::BufWriter::new(File::create(&tmp)?);
+            for entry in entries {
+                serde_json::to_writer(&mut file, &JournalRecord::Dequeued(entry.clone()))?;
+                file.write_all(b"\n")?;
+            }
+            file.into_inner()?.sync_all()?;
+        }
+        std::fs::rename(&tmp, path)?;
+        OpenOptions::new().append(true).open(path)
+    }
+
+    fn append(state: &mut JournalState, record: &JournalRecord) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        state.file.write_all(&line)?;
+        state.records += 1;
+        Ok(())
+    }
+
+    /// Records that [entry] was dequeued.
+    pub fn record_dequeued(&self, entry: JournalEntry) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        Self::append(&mut state, &JournalRecord::Dequeued(entry.clone()))?;
+        state.open.insert(entry.key(), entry);
+        Ok(())
+    }
+
+    /// Records that the work on [url] is completed, the journal is compacted if necessary.
+    pub fn record_completed(&self, url: &UrlWithDepth) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let url = url.try_as_str().into_owned();
+        if state.open.remove(&url).is_none() {
+            return Ok(());
+        }
+        Self::append(&mut state, &JournalRecord::Completed { url })?;
+        if state.records >= COMPACT_MIN_RECORDS && state.records > COMPACT_RATIO * state.open.len()
+        {
+            Self::compact_locked(&self.path, &mut state)?;
+        }
+        Ok(())
+    }
+
+    fn compact_locked(path: &Utf8Path, state: &mut JournalState) -> io::Result<()> {
+        state.file = Self::rewrite(path, state.open.values())?;
+        state.records = state.open.len();
+        Ok(())
+    }
+
+    /// Removes every entry, e.g. after the open entries were requeued.
+    pub fn clear(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        state.open.clear();
+        Self::compact_locked(&self.path, &mut state)
+    }
+
+    /// The entries that were dequeued but not completed, ordered by the time of the dequeue.
+    pub fn open_entries(&self) -> Vec<JournalEntry> {
+        let state = self.state.lock().unwrap();
+        let mut entries: Vec<_> = state.open.values().cloned().collect();
+        entries.sort_by_key(|entry| entry.dequeued_at);
+        entries
+    }
+
+    /// The number of records in the journal file.
+    #[cfg(test)]
+    pub fn records(&self) -> usize {
+        self.state.lock().unwrap().records
+    }
+
+    #[cfg(test)]
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DequeueJournal, JournalEntry, COMPACT_MIN_RECORDS};
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+    use std::io::Write;
+    use time::OffsetDateTime;
+
+    fn entry(url: &str) -> JournalEntry {
+        JournalEntry::new(
+            UrlWithDepth::from_url(url).unwrap(),
+            false,
+            OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap(),
+        )
+    }
+
+    #[test]
+    fn replays_the_open_entries() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue.tmp.journal");
+        {
+            let journal = DequeueJournal::open(&path).unwrap();
+            journal
+                .record_dequeued(entry("https://www.example.com/a"))
+                .unwrap();
+            journal
+                .record_dequeued(entry("https://www.example.com/b"))
+                .unwrap();
+            journal
+                .record_dequeued(entry("https://www.other.de/c"))
+                .unwrap();
+            journal
+                .record_completed(&UrlWithDepth::from_url("https://www.example.com/b").unwrap())
+                .unwrap();
+        }
+        // A crash while writing a record.
+        std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(br#"{"op":"dequeued","url":"#)
+            .unwrap();
+
+        let journal = DequeueJournal::open(&path).unwrap();
+        let urls: Vec<_> = journal
+            .open_entries()
+            .into_iter()
+            .map(|entry| entry.url.try_as_str().into_owned())
+            .collect();
+        assert_eq!(2, urls.len());
+        assert!(urls.contains(&"https://www.example.com/a".to_string()));
+        assert!(urls.contains(&"https://www.other.de/c".to_string()));
+        assert_eq!(2, journal.records());
+
+        journal
+            .record_dequeued(entry("https://www.example.com/d"))
+            .unwrap();
+        drop(journal);
+        let journal = DequeueJournal::open(&path).unwrap();
+        assert_eq!(3, journal.open_entries().len());
+
+        journal.clear().unwrap();
+        drop(journal);
+        assert!(DequeueJournal::open(&path)
+            .unwrap()
+            .open_entries()
+            .is_empty());
+    }
+
+    #[test]
+    fn is_compacted_when_most_records_are_completed() {
+        let dir = Utf8TempDir::new().unwrap();
+        let journal = DequeueJournal::open(dir.path().join("queue.tmp.journal")).unwrap();
+        journal
+            .record_dequeued(entry("https://www.example.com/open"))
+            .unwrap();
+        for i in 0..COMPACT_MIN_RECORDS {
+            let entry = entry(&format!("https://www.example.com/{i}"));
+            let url = entry.url.clone();
+            journal.record_dequeued(entry).unwrap();
+            journal.record_completed(&url).unwrap();
+            assert!(journal.records() < COMPACT_MIN_RECORDS);
+        }
+        assert_eq!(1, journal.open_entries().len());
+        let content = std::fs::read_to_string(journal.path()).unwrap();
+        assert!(content.lines().count() < COMPACT_MIN_RECORDS);
+    }
+}
@@ -13,10 +13,14 @@
 // limitations under the License.
 
 pub mod errors;
+mod journal;
+pub mod priority;
 mod raw;
 mod url;
 
-pub use errors::QueueError;
+pub use errors::{QueueError, QueueFileError};
+pub use journal::{DequeueJournal, JournalEntry};
+pub use raw::band::QueueFileState;
 pub use raw::implementation::RawAgingQueueFile;
 pub use raw::AgingQueueElement;
 pub use raw::EnqueueCalled;
@@ -0,0 +1,608 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::queue::priority::features::{UrlFeature, UrlFeatures};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// Errors when compiling a [PriorityExpression].
+#[derive(Debug, Clone, Error, Eq, PartialEq)]
+pub enum PriorityExpressionError {
+    #[error("The priority expression is empty.")]
+    Empty,
+    #[error("Unexpected end of the priority expression, expected {expected}.")]
+    UnexpectedEnd { expected: &'static str },
+    #[error("Unexpected '{found}' at position {position} of the priority expression, expected {expected}.")]
+    Unexpected {
+        position: usize,
+        found: String,
+        expected: &'static str,
+    },
+    #[error("Invalid number '{value}' at position {position} of the priority expression.")]
+    InvalidNumber { position: usize, value: String },
+    #[error("Unknown feature '{name}' at position {position} of the priority expression, known features are {}.", UrlFeature::NAMES.join(", "))]
+    UnknownFeature { position: usize, name: String },
+    #[error("Unknown function '{name}' at position {position} of the priority expression, known functions are matches, min, max and if.")]
+    UnknownFunction { position: usize, name: String },
+    #[error("Unknown keyword list '{name}' at position {position} of the priority expression.")]
+    UnknownKeywordList { position: usize, name: String },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum UnaryOperator {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum BinaryOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Equal,
+    NotEqual,
+    And,
+    Or,
+}
+
+impl BinaryOperator {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "+" => Some(Self::Add),
+            "-" => Some(Self::Subtract),
+            "*" => Some(Self::Multiply),
+            "/" => Some(Self::Divide),
+            "<" => Some(Self::Less),
+            "<=" => Some(Self::LessOrEqual),
+            ">" => Some(Self::Greater),
+            ">=" => Some(Self::GreaterOrEqual),
+            "==" => Some(Self::Equal),
+            "!=" => Some(Self::NotEqual),
+            "&&" => Some(Self::And),
+            "||" => Some(Self::Or),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn apply(self, left: f64, right: f64) -> f64 {
+        match self {
+            Self::Add => left + right,
+            Self::Subtract => left - right,
+            Self::Multiply => left * right,
+            Self::Divide => left / right,
+            Self::Less => as_number(left < right),
+            Self::LessOrEqual => as_number(left <= right),
+            Self::Greater => as_number(left > right),
+            Self::GreaterOrEqual => as_number(left >= right),
+            Self::Equal => as_number(left == right),
+            Self::NotEqual => as_number(left != right),
+            Self::And => as_number(is_true(left) && is_true(right)),
+            Self::Or => as_number(is_true(left) || is_true(right)),
+        }
+    }
+}
+
+#[inline(always)]
+fn as_number(value: bool) -> f64 {
+    if value {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+#[inline(always)]
+fn is_true(value: f64) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+/// A node of a compiled expression.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(f64),
+    Feature(UrlFeature),
+    /// The index of the keyword list in the expression.
+    Matches(usize),
+    Unary(UnaryOperator, Box<Node>),
+    Binary(BinaryOperator, Box<Node>, Box<Node>),
+    Min(Box<Node>, Box<Node>),
+    Max(Box<Node>, Box<Node>),
+    If(Box<Node>, Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, features: &UrlFeatures, keywords: &[(String, Vec<String>)]) -> f64 {
+        match self {
+            Node::Number(value) => *value,
+            Node::Feature(feature) => features.get(*feature),
+            Node::Matches(idx) => count_matches(features.url, &keywords[*idx].1) as f64,
+            Node::Unary(UnaryOperator::Negate, value) => -value.evaluate(features, keywords),
+            Node::Unary(UnaryOperator::Not, value) => {
+                as_number(!is_true(value.evaluate(features, keywords)))
+            }
+            Node::Binary(operator, left, right) => operator.apply(
+                left.evaluate(features, keywords),
+                right.evaluate(features, keywords),
+            ),
+            Node::Min(left, right) => left
+                .evaluate(features, keywords)
+                .min(right.evaluate(features, keywords)),
+            Node::Max(left, right) => left
+                .evaluate(features, keywords)
+                .max(right.evaluate(features, keywords)),
+            Node::If(condition, then, otherwise) => {
+                if is_true(condition.evaluate(features, keywords)) {
+                    then.evaluate(features, keywords)
+                } else {
+                    otherwise.evaluate(features, keywords)
+                }
+            }
+        }
+    }
+
+    fn uses(&self, feature: UrlFeature) -> bool {
+        match self {
+            Node::Number(_) | Node::Matches(_) => false,
+            Node::Feature(value) => *value == feature,
+            Node::Unary(_, value) => value.uses(feature),
+            Node::Binary(_, left, right) | Node::Min(left, right) | Node::Max(left, right) => {
+                left.uses(feature) || right.uses(feature)
+            }
+            Node::If(condition, then, otherwise) => {
+                condition.uses(feature) || then.uses(feature) || otherwise.uses(feature)
+            }
+        }
+    }
+}
+
+/// Counts the keywords contained in the url, ignoring the ascii case.
+pub(super) fn count_matches(url: &str, keywords: &[String]) -> usize {
+    let url = url.as_bytes();
+    keywords
+        .iter()
+        .filter(|keyword| {
+            let keyword = keyword.as_bytes();
+            !keyword.is_empty()
+                && url
+                    .windows(keyword.len())
+                    .any(|window| window.eq_ignore_ascii_case(keyword))
+        })
+        .count()
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Token<'a> {
+    Number(f64),
+    Ident(&'a str),
+    Symbol(&'static str),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+impl Display for Token<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(value) => write!(f, "{value}"),
+            Token::Ident(value) => write!(f, "{value}"),
+            Token::Symbol(value) => write!(f, "{value}"),
+            Token::OpenParen => write!(f, "("),
+            Token::CloseParen => write!(f, ")"),
+            Token::Comma => write!(f, ","),
+        }
+    }
+}
+
+/// Two char symbols have to come first.
+const SYMBOLS: [&str; 13] = [
+    "&&", "||", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "!",
+];
+
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, PriorityExpressionError> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut position = 0;
+    'tokens: while position < bytes.len() {
+        let start = position;
+        match bytes[position] {
+            value if value.is_ascii_whitespace() => {
+                position += 1;
+            }
+            b'(' => {
+                tokens.push((start, Token::OpenParen));
+                position += 1;
+            }
+            b')' => {
+                tokens.push((start, Token::CloseParen));
+                position += 1;
+            }
+            b',' => {
+                tokens.push((start, Token::Comma));
+                position += 1;
+            }
+            value if value.is_ascii_digit() || value == b'.' => {
+                while position < bytes.len()
+                    && (bytes[position].is_ascii_digit() || bytes[position] == b'.')
+                {
+                    position += 1;
+                }
+                let value = &source[start..position];
+                let number = value
+                    .parse()
+                    .map_err(|_| PriorityExpressionError::InvalidNumber {
+                        position: start,
+                        value: value.to_string(),
+                    })?;
+                tokens.push((start, Token::Number(number)));
+            }
+            value if value.is_ascii_alphabetic() || value == b'_' => {
+                while position < bytes.len()
+                    && (bytes[position].is_ascii_alphanumeric() || bytes[position] == b'_')
+                {
+                    position += 1;
+                }
+                tokens.push((start, Token::Ident(&source[start..position])));
+            }
+            _ => {
+                for symbol in SYMBOLS {
+                    if source[position..].starts_with(symbol) {
+                        tokens.push((start, Token::Symbol(symbol)));
+                        position += symbol.len();
+                        continue 'tokens;
+                    }
+                }
+                return Err(PriorityExpressionError::Unexpected {
+                    position,
+                    found: source[position..]
+                        .chars()
+                        .next()
+                        .map(String::from)
+                        .unwrap_or_default(),
+                    expected: "a number, feature, function or operator",
+                });
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// A recursive descent parser for the priority expressions.
+struct Parser<'a, 'b> {
+    tokens: Vec<(usize, Token<'a>)>,
+    position: usize,
+    available_keywords: &'b BTreeMap<String, Vec<String>>,
+    used_keywords: Vec<(String, Vec<String>)>,
+}
+
+impl<'a, 'b> Parser<'a, 'b> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.position).map(|(_, token)| *token)
+    }
+
+    fn next(&mut self, expected: &'static str) -> Result<(usize, Token<'a>), PriorityExpressionError> {
+        match self.tokens.get(self.position) {
+            None => Err(PriorityExpressionError::UnexpectedEnd { expected }),
+            Some(found) => {
+                self.position += 1;
+                Ok(*found)
+            }
+        }
+    }
+
+    fn unexpected(position: usize, found: Token, expected: &'static str) -> PriorityExpressionError {
+        PriorityExpressionError::Unexpected {
+            position,
+            found: found.to_string(),
+            expected,
+        }
+    }
+
+    fn expect(&mut self, token: Token, expected: &'static str) -> Result<(), PriorityExpressionError> {
+        let (position, found) = self.next(expected)?;
+        if found == token {
+            Ok(())
+        } else {
+            Err(Self::unexpected(position, found, expected))
+        }
+    }
+
+    fn parse(mut self) -> Result<(Node, Vec<(String, Vec<String>)>), PriorityExpressionError> {
+        if self.tokens.is_empty() {
+            return Err(PriorityExpressionError::Empty);
+        }
+        let node = self.parse_binary(0)?;
+        if let Some((position, found)) = self.tokens.get(self.position) {
+            return Err(Self::unexpected(*position, *found, "an operator or the end"));
+        }
+        Ok((node, self.used_keywords))
+    }
+
+    /// The binary operators from the lowest to the highest precedence.
+    const PRECEDENCE: [&'static [&'static str]; 5] = [
+        &["||"],
+        &["&&"],
+        &["<", "<=", ">", ">=", "==", "!="],
+        &["+", "-"],
+        &["*", "/"],
+    ];
+
+    fn parse_binary(&mut self, level: usize) -> Result<Node, PriorityExpressionError> {
+        if level == Self::PRECEDENCE.len() {
+            return self.parse_unary();
+        }
+        let mut left = self.parse_binary(level + 1)?;
+        while let Some(Token::Symbol(symbol)) = self.peek() {
+            if !Self::PRECEDENCE[level].contains(&symbol) {
+                break;
+            }
+            self.position += 1;
+            let right = self.parse_binary(level + 1)?;
+            let operator = BinaryOperator::from_symbol(symbol).unwrap();
+            left = Node::Binary(operator, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn parse_unary(&mut self) -> Result<Node, PriorityExpressionError> {
+        match self.peek() {
+            Some(Token::Symbol("-")) => {
+                self.position += 1;
+                Ok(Node::Unary(UnaryOperator::Negate, Box::new(self.parse_unary()?)))
+            }
+            Some(Token::Symbol("!")) => {
+                self.position += 1;
+                Ok(Node::Unary(UnaryOperator::Not, Box::new(self.parse_unary()?)))
+            }
+            _ => self.parse_atom(),
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, PriorityExpressionError> {
+        const EXPECTED: &str = "a number, feature, function or '('";
+        let (position, token) = self.next(EXPECTED)?;
+        match token {
+            Token::Number(value) => Ok(Node::Number(value)),
+            Token::OpenParen => {
+                let node = self.parse_binary(0)?;
+                self.expect(Token::CloseParen, "')'")?;
+                Ok(node)
+            }
+            Token::Ident(name) if self.peek() == Some(Token::OpenParen) => {
+                self.position += 1;
+                self.parse_function(position, name)
+            }
+            Token::Ident(name) => match UrlFeature::from_str(name) {
+                Ok(feature) => Ok(Node::Feature(feature)),
+                Err(_) => Err(PriorityExpressionError::UnknownFeature {
+                    position,
+                    name: name.to_string(),
+                }),
+            },
+            found => Err(Self::unexpected(position, found, EXPECTED)),
+        }
+    }
+
+    fn parse_function(
+        &mut self,
+        position: usize,
+        name: &str,
+    ) -> Result<Node, PriorityExpressionError> {
+        let node = match name {
+            "matches" => {
+                let (position, token) = self.next("the name of a keyword list")?;
+                let Token::Ident(list) = token else {
+                    return Err(Self::unexpected(position, token, "the name of a keyword list"));
+                };
+                let idx = match self.used_keywords.iter().position(|(name, _)| name == list) {
+                    Some(idx) => idx,
+                    None => {
+                        let Some(keywords) = self.available_keywords.get(list) else {
+                            return Err(PriorityExpressionError::UnknownKeywordList {
+                                position,
+                                name: list.to_string(),
+                            });
+                        };
+                        self.used_keywords.push((list.to_string(), keywords.clone()));
+                        self.used_keywords.len() - 1
+                    }
+                };
+                Node::Matches(idx)
+            }
+            "min" | "max" => {
+                let left = self.parse_binary(0)?;
+                self.expect(Token::Comma, "','")?;
+                let right = self.parse_binary(0)?;
+                if name == "min" {
+                    Node::Min(Box::new(left), Box::new(right))
+                } else {
+                    Node::Max(Box::new(left), Box::new(right))
+                }
+            }
+            "if" => {
+                let condition = self.parse_binary(0)?;
+                self.expect(Token::Comma, "','")?;
+                let then = self.parse_binary(0)?;
+                self.expect(Token::Comma, "','")?;
+                let otherwise = self.parse_binary(0)?;
+                Node::If(Box::new(condition), Box::new(then), Box::new(otherwise))
+            }
+            _ => {
+                return Err(PriorityExpressionError::UnknownFunction {
+                    position,
+                    name: name.to_string(),
+                })
+            }
+        };
+        self.expect(Token::CloseParen, "')'")?;
+        Ok(node)
+    }
+}
+
+/// A compiled priority expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriorityExpression {
+    root: Node,
+    /// The keyword lists used by the expression.
+    keywords: Vec<(String, Vec<String>)>,
+}
+
+impl PriorityExpression {
+    /// Compiles the [source], `matches(name)` can refer to all lists in [keywords].
+    pub fn compile(
+        source: &str,
+        keywords: &BTreeMap<String, Vec<String>>,
+    ) -> Result<Self, PriorityExpressionError> {
+        let parser = Parser {
+            tokens: tokenize(source)?,
+            position: 0,
+            available_keywords: keywords,
+            used_keywords: Vec::new(),
+        };
+        let (root, keywords) = parser.parse()?;
+        Ok(Self { root, keywords })
+    }
+
+    /// Evaluates the expression for the [features].
+    pub fn evaluate(&self, features: &UrlFeatures) -> f64 {
+        self.root.evaluate(features, &self.keywords)
+    }
+
+    /// Returns true if the [feature] is used by the expression.
+    pub fn uses(&self, feature: UrlFeature) -> bool {
+        self.root.uses(feature)
+    }
+
+    /// The keyword lists used by the expression.
+    pub fn keywords(&self) -> &[(String, Vec<String>)] {
+        &self.keywords
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PriorityExpression, PriorityExpressionError};
+    use crate::queue::priority::features::UrlFeatures;
+    use std::collections::BTreeMap;
+
+    fn keywords() -> BTreeMap<String, Vec<String>> {
+        BTreeMap::from([(
+            "blog".to_string(),
+            vec!["/blog/".to_string(), "/news/".to_string()],
+        )])
+    }
+
+    fn features(url: &str) -> UrlFeatures {
+        UrlFeatures {
+            url,
+            path_depth: 2,
+            query_params: 3,
+            url_length: url.len() as u32,
+            origin_pages: 4,
+            depth: 1,
+            is_recrawl: false,
+        }
+    }
+
+    fn evaluate(source: &str, url: &str) -> f64 {
+        PriorityExpression::compile(source, &keywords())
+            .unwrap()
+            .evaluate(&features(url))
+    }
+
+    #[test]
+    fn evaluates_with_precedence() {
+        assert_eq!(7.0, evaluate("1 + 2 * 3", "https://a.de/"));
+        assert_eq!(9.0, evaluate("(1 + 2) * 3", "https://a.de/"));
+        assert_eq!(-1.0, evaluate("path_depth - query_params", "https://a.de/"));
+        assert_eq!(1.0, evaluate("depth < 2 && !is_recrawl", "https://a.de/"));
+        assert_eq!(0.0, evaluate("origin_pages >= 5 || 0", "https://a.de/"));
+        assert_eq!(10.0, evaluate("if(query_params > 2, 10, -10)", "https://a.de/"));
+        assert_eq!(2.0, evaluate("min(path_depth, 5) + max(-1, 0)", "https://a.de/"));
+        assert_eq!(2.0, evaluate("matches(blog) * 2", "https://a.de/Blog/1"));
+        assert_eq!(0.0, evaluate("matches(blog)", "https://a.de/blogs"));
+        assert_eq!(9.5, evaluate("url_length / 2 - 0.5", "https://a.de/blog/1/"));
+    }
+
+    #[test]
+    fn reports_parsing_errors() {
+        let compile = |source: &str| PriorityExpression::compile(source, &keywords()).unwrap_err();
+        assert_eq!(PriorityExpressionError::Empty, compile("  "));
+        assert_eq!(
+            PriorityExpressionError::UnexpectedEnd {
+                expected: "a number, feature, function or '('"
+            },
+            compile("1 +")
+        );
+        assert_eq!(
+            PriorityExpressionError::Unexpected {
+                position: 6,
+                found: "2".to_string(),
+                expected: "an operator or the end"
+            },
+            compile("1 + 2 2")
+        );
+        assert_eq!(
+            PriorityExpressionError::UnknownFeature {
+                position: 2,
+                name: "size".to_string()
+            },
+            compile("1+size")
+        );
+        assert_eq!(
+            PriorityExpressionError::UnknownFunction {
+                position: 0,
+                name: "sqrt".to_string()
+            },
+            compile("sqrt(depth)")
+        );
+        assert_eq!(
+            PriorityExpressionError::UnknownKeywordList {
+                position: 8,
+                name: "shop".to_string()
+            },
+            compile("matches(shop)")
+        );
+        assert_eq!(
+            PriorityExpressionError::Unexpected {
+                position: 2,
+                found: "$".to_string(),
+                expected: "a number, feature, function or operator"
+            },
+            compile("1 $ 2")
+        );
+        assert_eq!(
+            PriorityExpressionError::InvalidNumber {
+                position: 0,
+                value: "1.2.3".to_string()
+            },
+            compile("1.2.3")
+        );
+        assert_eq!(
+            PriorityExpressionError::UnexpectedEnd { expected: "')'" },
+            compile("min(1, 2")
+        );
+        assert_eq!(
+            "Unknown feature 'size' at position 2 of the priority expression, known features are path_depth, query_params, url_length, origin_pages, depth, is_recrawl.",
+            compile("1+size").to_string()
+        );
+    }
+}
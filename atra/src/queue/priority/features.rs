@@ -0,0 +1,118 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::UrlWithDepth;
+use std::fmt::{Display, Formatter};
+use strum::EnumString;
+
+/// A feature of an url usable in a priority expression.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString)]
+#[strum(serialize_all = "snake_case")]
+pub enum UrlFeature {
+    /// The number of non-empty path segments.
+    PathDepth,
+    /// The number of query parameters.
+    QueryParams,
+    /// The length of the url in bytes.
+    UrlLength,
+    /// The number of urls of the same origin scored before.
+    OriginPages,
+    /// The total distance to the seed.
+    Depth,
+    /// 1 if the url is queued for a recrawl, otherwise 0.
+    IsRecrawl,
+}
+
+impl UrlFeature {
+    /// The names of all features as used in the expressions.
+    pub const NAMES: [&'static str; 6] = [
+        "path_depth",
+        "query_params",
+        "url_length",
+        "origin_pages",
+        "depth",
+        "is_recrawl",
+    ];
+}
+
+/// The features of an url used to evaluate a priority expression.
+/// Creating them does not allocate.
+#[derive(Debug, Copy, Clone)]
+pub struct UrlFeatures<'a> {
+    pub url: &'a str,
+    pub path_depth: u32,
+    pub query_params: u32,
+    pub url_length: u32,
+    pub origin_pages: u64,
+    pub depth: u64,
+    pub is_recrawl: bool,
+}
+
+impl<'a> UrlFeatures<'a> {
+    /// Extracts the features of the [url].
+    pub fn new(url: &'a UrlWithDepth, origin_pages: u64, is_recrawl: bool) -> Self {
+        let (url_str, path_depth, query_params) = match url.url.as_url() {
+            Some(value) => (
+                value.as_str(),
+                value
+                    .path_segments()
+                    .map_or(0, |segments| segments.filter(|value| !value.is_empty()).count()),
+                value.query().map_or(0, |query| {
+                    query.split('&').filter(|value| !value.is_empty()).count()
+                }),
+            ),
+            None => ("", 0, 0),
+        };
+        Self {
+            url: url_str,
+            path_depth: path_depth as u32,
+            query_params: query_params as u32,
+            url_length: url_str.len() as u32,
+            origin_pages,
+            depth: url.depth.total_distance_to_seed,
+            is_recrawl,
+        }
+    }
+
+    /// Returns the value of the [feature].
+    #[inline]
+    pub fn get(&self, feature: UrlFeature) -> f64 {
+        match feature {
+            UrlFeature::PathDepth => self.path_depth as f64,
+            UrlFeature::QueryParams => self.query_params as f64,
+            UrlFeature::UrlLength => self.url_length as f64,
+            UrlFeature::OriginPages => self.origin_pages as f64,
+            UrlFeature::Depth => self.depth as f64,
+            UrlFeature::IsRecrawl => {
+                if self.is_recrawl {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl Display for UrlFeatures<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "url: {}", self.url)?;
+        writeln!(f, "path_depth: {}", self.path_depth)?;
+        writeln!(f, "query_params: {}", self.query_params)?;
+        writeln!(f, "url_length: {}", self.url_length)?;
+        writeln!(f, "origin_pages: {}", self.origin_pages)?;
+        writeln!(f, "depth: {}", self.depth)?;
+        write!(f, "is_recrawl: {}", self.is_recrawl)
+    }
+}
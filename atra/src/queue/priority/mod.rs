@@ -0,0 +1,320 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod expression;
+mod features;
+
+pub use expression::{PriorityExpression, PriorityExpressionError};
+pub use features::{UrlFeature, UrlFeatures};
+
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{Display, Formatter};
+use std::sync::Mutex;
+
+/// The configuration of the url priority in the queue.
+/// The expression is compiled when the config is loaded.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(try_from = "PriorityConfigDef", into = "PriorityConfigDef")]
+pub struct PriorityConfig {
+    expression: Option<String>,
+    keywords: BTreeMap<String, Vec<String>>,
+    compiled: Option<PriorityExpression>,
+}
+
+/// The serialized form of [PriorityConfig]
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+struct PriorityConfigDef {
+    /// The expression scoring an url, a higher score is crawled first.
+    expression: Option<String>,
+    /// Named keyword lists, usable in the expression with `matches(name)`.
+    keywords: BTreeMap<String, Vec<String>>,
+}
+
+impl PriorityConfig {
+    /// Creates and compiles the config.
+    pub fn new(
+        expression: Option<String>,
+        keywords: BTreeMap<String, Vec<String>>,
+    ) -> Result<Self, PriorityExpressionError> {
+        let compiled = expression
+            .as_deref()
+            .map(|value| PriorityExpression::compile(value, &keywords))
+            .transpose()?;
+        Ok(Self {
+            expression,
+            keywords,
+            compiled,
+        })
+    }
+
+    /// The compiled expression, if one is configured.
+    pub fn compiled(&self) -> Option<&PriorityExpression> {
+        self.compiled.as_ref()
+    }
+}
+
+impl TryFrom<PriorityConfigDef> for PriorityConfig {
+    type Error = PriorityExpressionError;
+
+    fn try_from(value: PriorityConfigDef) -> Result<Self, Self::Error> {
+        Self::new(value.expression, value.keywords)
+    }
+}
+
+impl From<PriorityConfig> for PriorityConfigDef {
+    fn from(value: PriorityConfig) -> Self {
+        Self {
+            expression: value.expression,
+            keywords: value.keywords,
+        }
+    }
+}
+
+impl PartialEq for PriorityConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.expression == other.expression && self.keywords == other.keywords
+    }
+}
+
+impl Eq for PriorityConfig {}
+
+/// Converts a score to the priority band used by the queue.
+/// Higher bands are dequeued first, NaN falls into the default band 0.
+pub fn score_to_priority(score: f64) -> i8 {
+    if score.is_nan() {
+        0
+    } else {
+        score.round().clamp(i8::MIN as f64, i8::MAX as f64) as i8
+    }
+}
+
+/// Scores the urls before they are put into the queue.
+#[derive(Debug)]
+pub struct UrlPriorityScorer {
+    expression: Option<PriorityExpression>,
+    uses_origin_pages: bool,
+    origin_pages: Mutex<HashMap<AtraUrlOrigin, u64>>,
+}
+
+impl UrlPriorityScorer {
+    pub fn new(config: &PriorityConfig) -> Self {
+        let expression = config.compiled().cloned();
+        Self {
+            uses_origin_pages: expression
+                .as_ref()
+                .is_some_and(|value| value.uses(UrlFeature::OriginPages)),
+            expression,
+            origin_pages: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Returns true if there is an expression to evaluate.
+    pub fn is_active(&self) -> bool {
+        self.expression.is_some()
+    }
+
+    /// Returns the number of urls scored for [origin] and counts the current one.
+    fn count_origin_page(&self, origin: Option<&AtraUrlOrigin>) -> u64 {
+        let Some(origin) = origin else {
+            return 0;
+        };
+        let Ok(mut counter) = self.origin_pages.lock() else {
+            return 0;
+        };
+        if let Some(value) = counter.get_mut(origin) {
+            let current = *value;
+            *value += 1;
+            current
+        } else {
+            counter.insert(origin.clone(), 1);
+            0
+        }
+    }
+
+    /// Returns the priority of the [url], the [origin] is only needed if the
+    /// expression uses `origin_pages`.
+    pub fn score(
+        &self,
+        url: &UrlWithDepth,
+        origin: Option<&AtraUrlOrigin>,
+        is_recrawl: bool,
+    ) -> i8 {
+        let Some(ref expression) = self.expression else {
+            return 0;
+        };
+        let origin_pages = if self.uses_origin_pages {
+            self.count_origin_page(origin)
+        } else {
+            0
+        };
+        score_to_priority(expression.evaluate(&UrlFeatures::new(url, origin_pages, is_recrawl)))
+    }
+
+    /// Explains the score of the [url] without counting it for its origin.
+    pub fn explain<'a>(&'a self, url: &'a UrlWithDepth, is_recrawl: bool) -> PriorityExplanation<'a> {
+        let origin_pages = if self.uses_origin_pages {
+            url.atra_origin()
+                .and_then(|origin| {
+                    self.origin_pages
+                        .lock()
+                        .ok()
+                        .and_then(|counter| counter.get(&origin).copied())
+                })
+                .unwrap_or(0)
+        } else {
+            0
+        };
+        let features = UrlFeatures::new(url, origin_pages, is_recrawl);
+        PriorityExplanation {
+            score: self
+                .expression
+                .as_ref()
+                .map(|expression| expression.evaluate(&features)),
+            features,
+            expression: self.expression.as_ref(),
+        }
+    }
+}
+
+/// The features and the resulting score of an url.
+#[derive(Debug)]
+pub struct PriorityExplanation<'a> {
+    pub features: UrlFeatures<'a>,
+    pub expression: Option<&'a PriorityExpression>,
+    pub score: Option<f64>,
+}
+
+impl Display for PriorityExplanation<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.features)?;
+        if let Some(expression) = self.expression {
+            for (name, keywords) in expression.keywords() {
+                writeln!(
+                    f,
+                    "matches({name}): {}",
+                    expression::count_matches(self.features.url, keywords)
+                )?;
+            }
+        }
+        match self.score {
+            None => write!(f, "score: no expression configured\npriority: 0"),
+            Some(score) => write!(f, "score: {score}\npriority: {}", score_to_priority(score)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PriorityConfig, UrlPriorityScorer};
+    use crate::queue::{UrlQueue, UrlQueueElement, UrlQueueWrapper};
+    use crate::url::{AtraOriginProvider, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use std::collections::BTreeMap;
+
+    const URLS: [&str; 4] = [
+        "https://www.test1.de/a/b/c/d?x=1&y=2",
+        "https://www.test1.de/blog/entry",
+        "https://www.test2.de/",
+        "https://www.test2.de/shop?page=1",
+    ];
+
+    fn scorer(expression: &str) -> UrlPriorityScorer {
+        let config = PriorityConfig::new(
+            Some(expression.to_string()),
+            BTreeMap::from([("blog".to_string(), vec!["/blog/".to_string()])]),
+        )
+        .unwrap();
+        UrlPriorityScorer::new(&config)
+    }
+
+    async fn dequeue_order(expression: &str) -> Vec<String> {
+        let dir = Utf8TempDir::new().unwrap();
+        let queue = UrlQueueWrapper::open(dir.path().join("queue")).unwrap();
+        let scorer = scorer(expression);
+        for url in URLS {
+            let url = UrlWithDepth::from_url(url).unwrap();
+            let priority = scorer.score(&url, url.atra_origin().as_ref(), false);
+            queue
+                .enqueue(UrlQueueElement::new(false, 0, false, url).with_priority(priority))
+                .await
+                .unwrap();
+        }
+        let mut order = Vec::new();
+        while let Some(entry) = queue.dequeue().await.unwrap() {
+            order.push(entry.take().target.try_as_str().into_owned());
+        }
+        order
+    }
+
+    #[tokio::test]
+    async fn expressions_change_the_dequeue_order() {
+        assert_eq!(
+            vec![URLS[0], URLS[1], URLS[2], URLS[3]],
+            dequeue_order("0").await
+        );
+        assert_eq!(
+            vec![URLS[2], URLS[3], URLS[1], URLS[0]],
+            dequeue_order("-path_depth").await
+        );
+        assert_eq!(
+            vec![URLS[1], URLS[2], URLS[3], URLS[0]],
+            dequeue_order("matches(blog) * 10 - query_params").await
+        );
+        assert_eq!(
+            vec![URLS[0], URLS[2], URLS[1], URLS[3]],
+            dequeue_order("-origin_pages").await
+        );
+    }
+
+    #[test]
+    fn explains_the_score() {
+        let scorer = scorer("matches(blog) * 10 - path_depth");
+        let url = UrlWithDepth::from_url(URLS[1]).unwrap();
+        assert_eq!(
+            "url: https://www.test1.de/blog/entry\n\
+            path_depth: 2\n\
+            query_params: 0\n\
+            url_length: 31\n\
+            origin_pages: 0\n\
+            depth: 0\n\
+            is_recrawl: false\n\
+            matches(blog): 1\n\
+            score: 8\n\
+            priority: 8",
+            scorer.explain(&url, false).to_string()
+        );
+        let inactive = UrlPriorityScorer::new(&PriorityConfig::default());
+        assert!(inactive
+            .explain(&url, false)
+            .to_string()
+            .ends_with("score: no expression configured\npriority: 0"));
+    }
+
+    #[test]
+    fn invalid_expressions_fail_to_load() {
+        let result = serde_json::from_str::<PriorityConfig>(r#"{"expression": "depth +"}"#);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Unexpected end of the priority expression"));
+        let config: PriorityConfig = serde_json::from_str(
+            r#"{"expression": "matches(blog)", "keywords": {"blog": ["/blog/"]}}"#,
+        )
+        .unwrap();
+        assert!(config.compiled().is_some());
+    }
+}
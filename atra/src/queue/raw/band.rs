@@ -0,0 +1,539 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The file of a single priority band.
+//!
+//! Layout (little endian):
+//! ```text
+//! header: magic [4] | version u16 | flags u16 | head u64 | xxh3 of the previous 16 bytes u64
+//! record: length u32 | xxh3 of the data u64 | data [length]
+//! ```
+//! The records between `head` and the end of the file are queued. New records are appended,
+//! dequeuing only moves `head`. The flag [CLEAN_SHUTDOWN] is removed while the file is open.
+
+use crate::queue::errors::QueueFileError;
+use std::fmt::{Display, Formatter};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: [u8; 4] = *b"ATRQ";
+const VERSION: u16 = 1;
+const HEADER_LEN: u64 = 24;
+const RECORD_HEADER_LEN: u64 = 12;
+/// Set in the flags of the header if the file was closed properly.
+const CLEAN_SHUTDOWN: u16 = 1;
+/// The dequeued bytes at the start of the file before it is compacted.
+const COMPACTION_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// The state of a queue file when it was opened.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum QueueFileState {
+    /// The file did not exist or was empty.
+    New,
+    /// The file was closed properly.
+    Clean,
+    /// The file was not closed properly or was damaged, every record was validated.
+    /// The [truncated_bytes] of a torn or corrupted tail were removed.
+    Recovered { truncated_bytes: u64 },
+    /// The file was unusable and moved to [backup], the queue starts empty.
+    Replaced { backup: PathBuf },
+}
+
+impl QueueFileState {
+    /// Returns true if the queue holds everything that was enqueued before, except for the
+    /// elements that were dequeued but not finished.
+    pub fn is_usable(&self) -> bool {
+        matches!(self, Self::Clean | Self::Recovered { .. })
+    }
+
+    /// Combines the states of two bands of the same queue.
+    pub(super) fn merge(self, other: Self) -> Self {
+        match (self, other) {
+            (value @ Self::Replaced { .. }, _) | (_, value @ Self::Replaced { .. }) => value,
+            (Self::Recovered { truncated_bytes: a }, Self::Recovered { truncated_bytes: b }) => {
+                Self::Recovered {
+                    truncated_bytes: a + b,
+                }
+            }
+            (value @ Self::Recovered { .. }, _) | (_, value @ Self::Recovered { .. }) => value,
+            (Self::New, Self::New) => Self::New,
+            _ => Self::Clean,
+        }
+    }
+}
+
+impl Display for QueueFileState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueueFileState::New => write!(f, "new"),
+            QueueFileState::Clean => write!(f, "closed properly"),
+            QueueFileState::Recovered { truncated_bytes } => {
+                write!(f, "recovered, truncated {truncated_bytes} bytes")
+            }
+            QueueFileState::Replaced { backup } => {
+                write!(f, "unusable, moved to {}", backup.display())
+            }
+        }
+    }
+}
+
+/// Creates the header for a file with the [head].
+fn header_bytes(head: u64, clean: bool) -> [u8; HEADER_LEN as usize] {
+    let mut header = [0u8; HEADER_LEN as usize];
+    header[0..4].copy_from_slice(&MAGIC);
+    header[4..6].copy_from_slice(&VERSION.to_le_bytes());
+    let flags = if clean { CLEAN_SHUTDOWN } else { 0 };
+    header[6..8].copy_from_slice(&flags.to_le_bytes());
+    header[8..16].copy_from_slice(&head.to_le_bytes());
+    let checksum = twox_hash::xxh3::hash64(&header[0..16]);
+    header[16..24].copy_from_slice(&checksum.to_le_bytes());
+    header
+}
+
+/// A file based fifo for the encoded elements of a single priority band.
+#[derive(Debug)]
+pub(super) struct BandFile {
+    path: PathBuf,
+    file: File,
+    /// The offset of the first queued record.
+    head: u64,
+    /// The end of the last queued record.
+    end: u64,
+    len: usize,
+}
+
+impl BandFile {
+    /// Opens or creates the band at [path]. Records after the first invalid record are removed.
+    pub fn open(path: &Path) -> Result<(Self, QueueFileState), QueueFileError> {
+        let corrupted = |reason| QueueFileError::Corrupted(path.to_path_buf(), reason);
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        let file_len = file.metadata()?.len();
+        if file_len == 0 {
+            file.write_all(&header_bytes(HEADER_LEN, false))?;
+            let band = Self {
+                path: path.to_path_buf(),
+                file,
+                head: HEADER_LEN,
+                end: HEADER_LEN,
+                len: 0,
+            };
+            return Ok((band, QueueFileState::New));
+        }
+        if file_len < HEADER_LEN {
+            return Err(corrupted("The header is incomplete."));
+        }
+
+        let mut header = [0u8; HEADER_LEN as usize];
+        file.read_exact(&mut header)?;
+        if header[0..4] != MAGIC {
+            return Err(corrupted("The magic bytes are missing."));
+        }
+        if twox_hash::xxh3::hash64(&header[0..16]).to_le_bytes() != header[16..24] {
+            return Err(corrupted("The checksum of the header does not match."));
+        }
+        let version = u16::from_le_bytes([header[4], header[5]]);
+        if version != VERSION {
+            return Err(QueueFileError::UnsupportedVersion(
+                path.to_path_buf(),
+                version,
+            ));
+        }
+        let clean = u16::from_le_bytes([header[6], header[7]]) & CLEAN_SHUTDOWN != 0;
+        let head = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        if head < HEADER_LEN || head > file_len {
+            return Err(corrupted("The head is outside of the file."));
+        }
+
+        let mut end = head;
+        let mut len = 0;
+        let mut reader = BufReader::new(&file);
+        reader.seek(SeekFrom::Start(head))?;
+        while file_len - end >= RECORD_HEADER_LEN {
+            let mut record_header = [0u8; RECORD_HEADER_LEN as usize];
+            reader.read_exact(&mut record_header)?;
+            let length = u32::from_le_bytes(record_header[0..4].try_into().unwrap()) as u64;
+            if length > file_len - end - RECORD_HEADER_LEN {
+                break;
+            }
+            let mut data = vec![0u8; length as usize];
+            reader.read_exact(&mut data)?;
+            if twox_hash::xxh3::hash64(&data).to_le_bytes() != record_header[4..12] {
+                break;
+            }
+            end += RECORD_HEADER_LEN + length;
+            len += 1;
+        }
+        drop(reader);
+
+        let truncated_bytes = file_len - end;
+        if truncated_bytes > 0 {
+            log::warn!(
+                "Truncate {truncated_bytes} bytes after the last valid record of {}.",
+                path.display()
+            );
+            file.set_len(end)?;
+            file.sync_all()?;
+        }
+        let mut band = Self {
+            path: path.to_path_buf(),
+            file,
+            head,
+            end,
+            len,
+        };
+        band.write_header(false)?;
+        let state = if clean && truncated_bytes == 0 {
+            QueueFileState::Clean
+        } else {
+            QueueFileState::Recovered { truncated_bytes }
+        };
+        Ok((band, state))
+    }
+
+    fn write_header(&mut self, clean: bool) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header_bytes(self.head, clean))
+    }
+
+    fn encode_into(buffer: &mut Vec<u8>, data: &[u8]) -> Result<(), QueueFileError> {
+        let length =
+            u32::try_from(data.len()).map_err(|_| QueueFileError::ElementTooBig(data.len()))?;
+        buffer.extend_from_slice(&length.to_le_bytes());
+        buffer.extend_from_slice(&twox_hash::xxh3::hash64(data).to_le_bytes());
+        buffer.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn append(&mut self, buffer: &[u8], count: usize) -> Result<(), QueueFileError> {
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(buffer)?;
+        self.end += buffer.len() as u64;
+        self.len += count;
+        Ok(())
+    }
+
+    pub fn add(&mut self, data: &[u8]) -> Result<(), QueueFileError> {
+        let mut buffer = Vec::with_capacity(data.len() + RECORD_HEADER_LEN as usize);
+        Self::encode_into(&mut buffer, data)?;
+        self.append(&buffer, 1)
+    }
+
+    pub fn add_n<'a>(
+        &mut self,
+        data: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(), QueueFileError> {
+        let mut buffer = Vec::new();
+        let mut count = 0;
+        for value in data {
+            Self::encode_into(&mut buffer, value)?;
+            count += 1;
+        }
+        self.append(&buffer, count)
+    }
+
+    /// Reads the record at the current position of the [reader].
+    fn read_record(
+        reader: &mut impl Read,
+        path: &Path,
+    ) -> Result<(Box<[u8]>, u64), QueueFileError> {
+        let mut record_header = [0u8; RECORD_HEADER_LEN as usize];
+        reader.read_exact(&mut record_header)?;
+        let length = u32::from_le_bytes(record_header[0..4].try_into().unwrap());
+        let mut data = vec![0u8; length as usize];
+        reader.read_exact(&mut data)?;
+        if twox_hash::xxh3::hash64(&data).to_le_bytes() != record_header[4..12] {
+            return Err(QueueFileError::Corrupted(
+                path.to_path_buf(),
+                "The checksum of a record does not match.",
+            ));
+        }
+        Ok((data.into_boxed_slice(), RECORD_HEADER_LEN + length as u64))
+    }
+
+    /// Removes up to [n] records from the head.
+    pub fn pop_n(&mut self, n: usize) -> Result<Vec<Box<[u8]>>, QueueFileError> {
+        let n = n.min(self.len);
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let mut found = Vec::with_capacity(n);
+        let mut head = self.head;
+        let mut reader = BufReader::new(&self.file);
+        reader.seek(SeekFrom::Start(head))?;
+        for _ in 0..n {
+            let (data, read) = Self::read_record(&mut reader, &self.path)?;
+            head += read;
+            found.push(data);
+        }
+        drop(reader);
+        self.head = head;
+        self.len -= n;
+        if self.len == 0 {
+            self.file.set_len(HEADER_LEN)?;
+            self.head = HEADER_LEN;
+            self.end = HEADER_LEN;
+        } else if self.head - HEADER_LEN >= COMPACTION_THRESHOLD
+            && self.head - HEADER_LEN > self.end - self.head
+        {
+            self.compact()?;
+        }
+        self.write_header(false)?;
+        Ok(found)
+    }
+
+    pub fn pop(&mut self) -> Result<Option<Box<[u8]>>, QueueFileError> {
+        Ok(self.pop_n(1)?.pop())
+    }
+
+    /// Moves the queued records to the start of a new file.
+    fn compact(&mut self) -> Result<(), QueueFileError> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".compact");
+        let temp_path = PathBuf::from(temp_path);
+        let mut compacted = File::create(&temp_path)?;
+        compacted.write_all(&header_bytes(HEADER_LEN, false))?;
+        self.file.seek(SeekFrom::Start(self.head))?;
+        io::copy(&mut (&self.file).take(self.end - self.head), &mut compacted)?;
+        compacted.sync_all()?;
+        drop(compacted);
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.end = HEADER_LEN + (self.end - self.head);
+        self.head = HEADER_LEN;
+        Ok(())
+    }
+
+    /// Replaces the queued records by [data]. The records are written to a new file that is
+    /// swapped in, a crash keeps either all old or all new records.
+    pub fn replace<'a>(
+        &mut self,
+        data: impl IntoIterator<Item = &'a [u8]>,
+    ) -> Result<(), QueueFileError> {
+        let mut buffer = Vec::new();
+        let mut count = 0;
+        for value in data {
+            Self::encode_into(&mut buffer, value)?;
+            count += 1;
+        }
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".replace");
+        let temp_path = PathBuf::from(temp_path);
+        let mut replacement = File::create(&temp_path)?;
+        replacement.write_all(&header_bytes(HEADER_LEN, false))?;
+        replacement.write_all(&buffer)?;
+        replacement.sync_all()?;
+        drop(replacement);
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.head = HEADER_LEN;
+        self.end = HEADER_LEN + buffer.len() as u64;
+        self.len = count;
+        Ok(())
+    }
+
+    /// Iterates over the queued records without removing them.
+    pub fn iter(&mut self) -> impl Iterator<Item = Result<Box<[u8]>, QueueFileError>> + '_ {
+        let mut reader = BufReader::new(&self.file);
+        let path = self.path.as_path();
+        let head = self.head;
+        let len = self.len;
+        let mut remaining = len;
+        std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            if remaining == len {
+                if let Err(err) = reader.seek(SeekFrom::Start(head)) {
+                    remaining = 0;
+                    return Some(Err(err.into()));
+                }
+            }
+            remaining -= 1;
+            let result = Self::read_record(&mut reader, path).map(|(data, _)| data);
+            if result.is_err() {
+                remaining = 0;
+            }
+            Some(result)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Drop for BandFile {
+    fn drop(&mut self) {
+        let closed = self.write_header(true).and_then(|_| self.file.sync_all());
+        if let Err(err) = closed {
+            log::error!(
+                "Failed to close the queue file {}: {err}",
+                self.path.display()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BandFile, QueueFileState, HEADER_LEN, RECORD_HEADER_LEN};
+    use camino_tempfile::Utf8TempDir;
+    use std::fs::OpenOptions;
+    use std::io::{Seek, SeekFrom, Write};
+    use std::path::Path;
+
+    fn record(i: usize) -> Vec<u8> {
+        format!("record number {i}").into_bytes()
+    }
+
+    fn fill(path: &Path, count: usize) -> Vec<u64> {
+        let (mut band, state) = BandFile::open(path).unwrap();
+        assert_eq!(QueueFileState::New, state);
+        let mut ends = Vec::with_capacity(count);
+        for i in 0..count {
+            band.add(&record(i)).unwrap();
+            ends.push(band.end);
+        }
+        ends
+    }
+
+    fn records(band: &mut BandFile) -> Vec<Vec<u8>> {
+        band.iter().map(|value| value.unwrap().into_vec()).collect()
+    }
+
+    #[test]
+    fn reopens_cleanly_closed_files() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        fill(&path, 10);
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(QueueFileState::Clean, state);
+        assert_eq!(record(0), band.pop().unwrap().unwrap().into_vec());
+        assert_eq!(9, band.len());
+        std::mem::forget(band);
+
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(QueueFileState::Recovered { truncated_bytes: 0 }, state);
+        assert_eq!((1..10).map(record).collect::<Vec<_>>(), records(&mut band));
+    }
+
+    #[test]
+    fn keeps_all_records_before_the_corruption() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        let ends = fill(&path, 20);
+        let file_len = *ends.last().unwrap();
+        let original = std::fs::read(&path).unwrap();
+
+        for offset in (HEADER_LEN..file_len).step_by(7) {
+            std::fs::write(&path, &original).unwrap();
+            let mut file = OpenOptions::new().write(true).open(&path).unwrap();
+            file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(&[original[offset as usize] ^ 0xFF]).unwrap();
+            drop(file);
+
+            let intact = ends.iter().take_while(|end| **end <= offset).count();
+            let valid_end = if intact == 0 {
+                HEADER_LEN
+            } else {
+                ends[intact - 1]
+            };
+            let (mut band, state) = BandFile::open(&path).unwrap();
+            assert_eq!(
+                QueueFileState::Recovered {
+                    truncated_bytes: file_len - valid_end
+                },
+                state,
+                "Corrupted at {offset}"
+            );
+            assert_eq!(
+                (0..intact).map(record).collect::<Vec<_>>(),
+                records(&mut band),
+                "Corrupted at {offset}"
+            );
+        }
+    }
+
+    #[test]
+    fn truncates_a_torn_tail() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        let ends = fill(&path, 5);
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(ends[3] + RECORD_HEADER_LEN + 2).unwrap();
+        drop(file);
+
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(
+            QueueFileState::Recovered {
+                truncated_bytes: RECORD_HEADER_LEN + 2
+            },
+            state
+        );
+        assert_eq!((0..4).map(record).collect::<Vec<_>>(), records(&mut band));
+        band.add(&record(4)).unwrap();
+        drop(band);
+
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(QueueFileState::Clean, state);
+        assert_eq!((0..5).map(record).collect::<Vec<_>>(), records(&mut band));
+    }
+
+    #[test]
+    fn replaces_the_queued_records() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        fill(&path, 5);
+        let (mut band, _) = BandFile::open(&path).unwrap();
+        band.pop().unwrap();
+        band.replace([record(1).as_slice(), record(3).as_slice()])
+            .unwrap();
+        assert_eq!(2, band.len());
+        band.add(&record(5)).unwrap();
+        assert_eq!(vec![record(1), record(3), record(5)], records(&mut band));
+        drop(band);
+
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(QueueFileState::Clean, state);
+        assert_eq!(vec![record(1), record(3), record(5)], records(&mut band));
+        assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn a_damaged_header_is_unusable() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        fill(&path, 3);
+        let original = std::fs::read(&path).unwrap();
+        for offset in 0..HEADER_LEN as usize {
+            let mut damaged = original.clone();
+            damaged[offset] ^= 0xFF;
+            std::fs::write(&path, &damaged).unwrap();
+            let err = BandFile::open(&path).unwrap_err();
+            assert!(err.is_unusable(), "Corrupted at {offset}: {err}");
+        }
+        std::fs::write(&path, &original[..HEADER_LEN as usize - 1]).unwrap();
+        assert!(BandFile::open(&path).unwrap_err().is_unusable());
+    }
+}
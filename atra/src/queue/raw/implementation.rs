@@ -12,52 +12,284 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::queue::errors::RawQueueError;
+use crate::queue::errors::{QueueFileError, RawQueueError};
+use crate::queue::raw::band::{BandFile, QueueFileState};
 use crate::queue::raw::{
-    AgingQueueElement, EnqueueCalled, RawAgingQueue, RawSupportsForcedQueueElement,
+    legacy, AgingQueueElement, EncodedQueueElement, EnqueueCalled, RawAgingQueue,
+    RawSupportsForcedQueueElement,
 };
 use crate::queue::QueueError;
-use itertools::{Either, Itertools};
-use queue_file::QueueFile;
+use itertools::Either;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
 use std::fmt::Debug;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock, TryLockError};
 use tokio::sync::watch::Receiver;
 
+/// The queue files of a [RawAgingQueueFile], one for every used priority band.
+/// The default band 0 uses the path of the queue, all other bands use `<path>.p<band>`.
+/// Therefore a queue without priorities consists of a single file.
+#[derive(Debug)]
+struct PriorityBands {
+    path: PathBuf,
+    bands: BTreeMap<i8, BandFile>,
+}
+
+impl PriorityBands {
+    /// Opens all bands of the queue at [path]. If a band is unusable, all bands are moved
+    /// to `<file>.corrupt` and the queue starts empty.
+    fn open(path: &Path) -> Result<(Self, QueueFileState), QueueFileError> {
+        let paths = Self::band_paths(path);
+        match Self::open_bands(path, &paths) {
+            Err(err) if err.is_unusable() => {
+                log::error!("The queue is unusable and starts empty: {err}");
+                let mut backup = None;
+                for band_path in paths.values() {
+                    let mut moved_to = band_path.clone().into_os_string();
+                    moved_to.push(".corrupt");
+                    let moved_to = PathBuf::from(moved_to);
+                    std::fs::rename(band_path, &moved_to)?;
+                    backup.get_or_insert(moved_to);
+                }
+                let (bands, _) =
+                    Self::open_bands(path, &BTreeMap::from([(0, path.to_path_buf())]))?;
+                Ok((
+                    bands,
+                    QueueFileState::Replaced {
+                        backup: backup.unwrap_or_else(|| path.to_path_buf()),
+                    },
+                ))
+            }
+            other => other,
+        }
+    }
+
+    /// The paths of the existing bands, including band 0.
+    fn band_paths(path: &Path) -> BTreeMap<i8, PathBuf> {
+        let mut paths = BTreeMap::new();
+        paths.insert(0, path.to_path_buf());
+        if let Some(name) = path.file_name().and_then(|value| value.to_str()) {
+            let prefix = format!("{name}.p");
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            if let Ok(entries) = std::fs::read_dir(parent) {
+                for entry in entries.flatten() {
+                    let band = entry
+                        .file_name()
+                        .to_str()
+                        .and_then(|value| value.strip_prefix(&prefix))
+                        .and_then(|value| value.parse::<i8>().ok());
+                    if let Some(band) = band {
+                        if band != 0 {
+                            paths.insert(band, entry.path());
+                        }
+                    }
+                }
+            }
+        }
+        paths
+    }
+
+    fn open_bands(
+        path: &Path,
+        paths: &BTreeMap<i8, PathBuf>,
+    ) -> Result<(Self, QueueFileState), QueueFileError> {
+        let mut bands = BTreeMap::new();
+        let mut state = None;
+        for (band, band_path) in paths {
+            if let Some(imported) = legacy::import(band_path)? {
+                log::info!(
+                    "Imported {imported} elements of the older queue file {}.",
+                    band_path.display()
+                );
+            }
+            let (file, band_state) = BandFile::open(band_path)?;
+            bands.insert(*band, file);
+            state = Some(match state {
+                Some(state) => band_state.merge(state),
+                None => band_state,
+            });
+        }
+        Ok((
+            Self {
+                path: path.to_path_buf(),
+                bands,
+            },
+            state.unwrap_or(QueueFileState::New),
+        ))
+    }
+
+    fn band(&mut self, priority: i8) -> Result<&mut BandFile, QueueFileError> {
+        match self.bands.entry(priority) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let mut path = self.path.clone().into_os_string();
+                path.push(format!(".p{priority}"));
+                Ok(entry.insert(BandFile::open(Path::new(&path))?.0))
+            }
+        }
+    }
+
+    fn add(&mut self, element: &EncodedQueueElement) -> Result<(), QueueFileError> {
+        self.band(element.priority)?.add(&element.data)
+    }
+
+    fn add_n(&mut self, elements: &[EncodedQueueElement]) -> Result<(), QueueFileError> {
+        let mut by_band: BTreeMap<i8, Vec<&[u8]>> = BTreeMap::new();
+        for element in elements {
+            by_band
+                .entry(element.priority)
+                .or_default()
+                .push(element.data.as_slice());
+        }
+        for (priority, data) in by_band {
+            self.band(priority)?.add_n(data)?;
+        }
+        Ok(())
+    }
+
+    /// Removes the oldest element of the highest band.
+    fn pop(&mut self) -> Result<Option<Box<[u8]>>, QueueFileError> {
+        for queue in self.bands.values_mut().rev() {
+            if let Some(value) = queue.pop()? {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Removes up to [n] elements, starting with the highest band.
+    fn pop_n(&mut self, n: usize) -> Result<Vec<Box<[u8]>>, QueueFileError> {
+        let mut found = Vec::with_capacity(n);
+        for queue in self.bands.values_mut().rev() {
+            let missing = n - found.len();
+            if missing == 0 {
+                break;
+            }
+            found.extend(queue.pop_n(missing)?);
+        }
+        Ok(found)
+    }
+
+    /// Keeps the elements of every band where [keep] returns true, in their order.
+    /// Returns the number of removed elements.
+    fn retain<F>(&mut self, mut keep: F) -> Result<usize, RawQueueError<()>>
+    where
+        F: FnMut(&[u8]) -> Result<bool, bincode::Error>,
+    {
+        let mut removed = 0;
+        for queue in self.bands.values_mut() {
+            // Decide first, a failing decision must not change the band.
+            let mut kept = Vec::with_capacity(queue.len());
+            let mut removed_in_band = 0;
+            for value in queue.iter() {
+                let value = value?;
+                if keep(value.as_ref())? {
+                    kept.push(value);
+                } else {
+                    removed_in_band += 1;
+                }
+            }
+            if removed_in_band == 0 {
+                continue;
+            }
+            queue.replace(kept.iter().map(|value| &value[..]))?;
+            removed += removed_in_band;
+        }
+        Ok(removed)
+    }
+
+    /// Iterates over the elements of all bands without removing them.
+    fn iter(&mut self) -> impl Iterator<Item = Result<Box<[u8]>, QueueFileError>> + '_ {
+        self.bands.values_mut().flat_map(BandFile::iter)
+    }
+
+    fn size(&self) -> usize {
+        self.bands.values().map(BandFile::len).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bands.values().all(BandFile::is_empty)
+    }
+}
+
 /// A mutexed queue for urls that are supported by spider.
+/// The elements are dequeued by their priority band, within a band in the order of insertion.
 #[derive(Debug, Clone)]
 pub struct RawAgingQueueFile {
     broadcast: tokio::sync::watch::Sender<EnqueueCalled>,
-    queue: Arc<RwLock<QueueFile>>,
+    queue: Arc<RwLock<PriorityBands>>,
+    state: QueueFileState,
 }
 
 impl RawAgingQueueFile {
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, queue_file::Error> {
-        Ok(Self::new_with(QueueFile::open(path)?))
+    /// Opens the queue at [path], the torn tail of a file that was not closed properly is
+    /// removed. An unusable queue is moved aside and replaced by an empty queue.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, QueueFileError> {
+        let (queue, state) = PriorityBands::open(path.as_ref())?;
+        match &state {
+            QueueFileState::New | QueueFileState::Clean => {
+                log::info!("Opened the queue ({state}).")
+            }
+            _ => log::warn!("Opened the queue ({state})."),
+        }
+        Ok(Self::new_with(queue, state))
     }
 
-    fn new_with(queue: QueueFile) -> Self {
+    fn new_with(queue: PriorityBands, state: QueueFileState) -> Self {
         Self {
             queue: Arc::new(RwLock::new(queue)),
             broadcast: tokio::sync::watch::Sender::new(EnqueueCalled),
+            state,
+        }
+    }
+
+    /// The state of the queue files when they were opened.
+    pub fn opened_state(&self) -> &QueueFileState {
+        &self.state
+    }
+
+    /// Visits every queued element without removing it.
+    pub fn for_each_queued<E, F>(&self, mut visitor: F) -> Result<(), QueueError>
+    where
+        E: AgingQueueElement + DeserializeOwned,
+        F: FnMut(E),
+    {
+        let mut lock = self.queue.write().unwrap();
+        for value in lock.iter() {
+            visitor(E::decode(value?.as_ref()).map_err(QueueError::EncodingError)?);
         }
+        Ok(())
+    }
+
+    fn encode<T>(mut entry: T) -> Result<EncodedQueueElement, bincode::Error>
+    where
+        T: AgingQueueElement + Serialize,
+    {
+        entry.age_by_one();
+        Ok(EncodedQueueElement {
+            priority: entry.priority(),
+            data: bincode::serialize(&entry)?,
+        })
     }
 }
 
 impl RawSupportsForcedQueueElement for RawAgingQueueFile {
-    unsafe fn force_enqueue<T>(&self, mut entry: T) -> Result<(), QueueError>
+    unsafe fn force_enqueue<T>(&self, entry: T) -> Result<(), QueueError>
     where
         T: AgingQueueElement + Serialize + Debug,
     {
         log::trace!("Encode {:?}", entry);
-        entry.age_by_one();
-        let encoded = bincode::serialize(&entry).map_err(QueueError::EncodingError)?;
+        let encoded = Self::encode(entry).map_err(QueueError::EncodingError)?;
 
         log::trace!("Acquire lock.");
         let mut lock = self.queue.write().unwrap();
-        log::trace!("Enqueue the entry {:?}", entry);
+        log::trace!("Enqueue the entry {:?}", encoded);
         lock.add(&encoded).map_err(QueueError::QueueFileError)?;
         drop(lock);
 
@@ -69,13 +301,10 @@ impl RawSupportsForcedQueueElement for RawAgingQueueFile {
 impl RawAgingQueue for RawAgingQueueFile {
     unsafe fn enqueue_any<E: AgingQueueElement + Serialize + Debug>(
         &self,
-        entry: Either<E, Vec<u8>>,
-    ) -> Result<(), RawQueueError<Vec<u8>>> {
+        entry: Either<E, EncodedQueueElement>,
+    ) -> Result<(), RawQueueError<EncodedQueueElement>> {
         let encoded = match entry {
-            Either::Left(mut entry) => {
-                entry.age_by_one();
-                bincode::serialize(&entry).map_err(RawQueueError::EncodingError)?
-            }
+            Either::Left(entry) => Self::encode(entry).map_err(RawQueueError::EncodingError)?,
             Either::Right(encoded) => encoded,
         };
 
@@ -96,25 +325,22 @@ impl RawAgingQueue for RawAgingQueueFile {
 
     unsafe fn enqueue_any_all<V, I>(
         &self,
-        entries: Either<I, Vec<Vec<u8>>>,
-    ) -> Result<(), RawQueueError<Vec<Vec<u8>>>>
+        entries: Either<I, Vec<EncodedQueueElement>>,
+    ) -> Result<(), RawQueueError<Vec<EncodedQueueElement>>>
     where
         V: AgingQueueElement + Serialize + Debug,
         I: IntoIterator<Item = V>,
     {
-        let urls: Vec<Vec<u8>> = match entries {
+        let urls: Vec<EncodedQueueElement> = match entries {
             Either::Left(entries) => entries
                 .into_iter()
-                .map(|mut entry| {
-                    entry.age_by_one();
-                    bincode::serialize(&entry).map_err(RawQueueError::EncodingError)
-                })
+                .map(|entry| Self::encode(entry).map_err(RawQueueError::EncodingError))
                 .collect::<Result<_, _>>()?,
             Either::Right(urls) => urls,
         };
         match self.queue.try_write() {
             Ok(mut lock) => {
-                lock.add_n(urls).map_err(RawQueueError::QueueFileError)?;
+                lock.add_n(&urls).map_err(RawQueueError::QueueFileError)?;
                 drop(lock);
             }
             Err(err) => match err {
@@ -137,11 +363,10 @@ impl RawAgingQueue for RawAgingQueueFile {
                 TryLockError::WouldBlock => return Err(RawQueueError::Blocked(())),
             },
         };
-        let extracted = lock.peek()?;
+        let extracted = lock.pop()?;
+        drop(lock);
         if let Some(extracted) = extracted {
-            lock.remove()?;
-            drop(lock);
-            let value: E = bincode::deserialize(extracted.as_ref())?;
+            let value = E::decode(extracted.as_ref())?;
             Ok(Some(value))
         } else {
             Ok(None)
@@ -159,18 +384,32 @@ impl RawAgingQueue for RawAgingQueueFile {
                 TryLockError::WouldBlock => return Err(RawQueueError::Blocked(())),
             },
         };
-        let found = lock.iter().take(n).collect_vec();
-        lock.remove_n(n)?;
+        let found = lock.pop_n(n)?;
         drop(lock);
         found
             .into_iter()
-            .map(|value| match bincode::deserialize(value.as_ref()) {
+            .map(|value| match E::decode(value.as_ref()) {
                 Ok(value) => Ok(value),
                 Err(err) => Err(RawQueueError::EncodingError(err)),
             })
             .collect::<Result<Vec<_>, _>>()
     }
 
+    unsafe fn retain_any<E, F>(&self, mut keep: F) -> Result<usize, RawQueueError<()>>
+    where
+        E: AgingQueueElement + DeserializeOwned + Debug,
+        F: FnMut(&E) -> bool,
+    {
+        let mut lock = match self.queue.try_write() {
+            Ok(lock) => lock,
+            Err(err) => match err {
+                TryLockError::Poisoned(_) => return Err(RawQueueError::LockPoisoned),
+                TryLockError::WouldBlock => return Err(RawQueueError::Blocked(())),
+            },
+        };
+        lock.retain(|value| Ok(keep(&E::decode(value)?)))
+    }
+
     fn len(&self) -> usize {
         let lock = self.queue.read().unwrap();
         lock.size()
@@ -214,11 +453,116 @@ impl Default for RawAgingQueueFile {
         temp_queue_file.push(uuid::Uuid::new_v4().as_simple().to_string());
         std::fs::create_dir_all(temp_queue_file.clone()).unwrap();
         temp_queue_file.push("queue");
-        Self {
-            queue: Arc::new(RwLock::new(
-                QueueFile::open(temp_queue_file.as_path()).unwrap(),
-            )),
-            broadcast: tokio::sync::watch::Sender::new(EnqueueCalled),
+        let (queue, state) = PriorityBands::open(temp_queue_file.as_path()).unwrap();
+        Self::new_with(queue, state)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::queue::raw::legacy::test::write_queue_file;
+    use crate::queue::url::element::test::OldUrlQueueElement;
+    use crate::queue::{QueueFileState, UrlQueue, UrlQueueElement, UrlQueueWrapper};
+    use crate::url::UrlWithDepth;
+    use camino_tempfile::Utf8TempDir;
+
+    #[tokio::test]
+    async fn an_unusable_queue_is_replaced() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue");
+        {
+            let queue = UrlQueueWrapper::open(&path).unwrap();
+            assert_eq!(&QueueFileState::New, queue.opened_state());
+            let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+            queue
+                .enqueue(UrlQueueElement::new(false, 0, false, url.clone()))
+                .await
+                .unwrap();
+            queue
+                .enqueue(UrlQueueElement::new(false, 0, false, url).with_priority(3))
+                .await
+                .unwrap();
+        }
+        {
+            let queue = UrlQueueWrapper::open(&path).unwrap();
+            assert_eq!(&QueueFileState::Clean, queue.opened_state());
+            assert_eq!(2, queue.len().await);
         }
+
+        std::fs::write(&path, b"not a queue").unwrap();
+        let queue = UrlQueueWrapper::open(&path).unwrap();
+        assert!(matches!(
+            queue.opened_state(),
+            QueueFileState::Replaced { .. }
+        ));
+        assert!(!queue.opened_state().is_usable());
+        assert!(queue.is_empty().await);
+        assert_eq!(
+            b"not a queue".to_vec(),
+            std::fs::read(dir.path().join("queue.corrupt")).unwrap()
+        );
+        assert!(dir.path().join("queue.p3.corrupt").exists());
+    }
+
+    #[tokio::test]
+    async fn imports_a_queue_of_an_older_version() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue");
+        let element = |url: &str, priority: i8| {
+            bincode::serialize(
+                &UrlQueueElement::new(false, 0, false, UrlWithDepth::from_url(url).unwrap())
+                    .with_priority(priority),
+            )
+            .unwrap()
+        };
+        let first = element("https://www.example.com/first", 0);
+        let second = element("https://www.example.com/second", 0);
+        let prioritized = element("https://www.example.com/prioritized", 3);
+        write_queue_file(path.as_std_path(), 4096, 32, &[&first, &second]);
+        write_queue_file(
+            dir.path().join("queue.p3").as_std_path(),
+            4096,
+            32,
+            &[&prioritized],
+        );
+
+        let queue = UrlQueueWrapper::open(&path).unwrap();
+        assert!(queue.opened_state().is_usable());
+        assert_eq!(3, queue.len().await);
+        let mut dequeued = Vec::new();
+        while let Some(element) = queue.dequeue().await.unwrap() {
+            dequeued.push(element.take().target.try_as_str().into_owned());
+        }
+        assert_eq!(
+            vec![
+                "https://www.example.com/prioritized",
+                "https://www.example.com/first",
+                "https://www.example.com/second",
+            ],
+            dequeued
+        );
+    }
+
+    #[tokio::test]
+    async fn imports_a_queue_without_priorities() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue");
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let old = bincode::serialize(&OldUrlQueueElement {
+            is_seed: true,
+            age: 2,
+            host_was_in_use: false,
+            target: url.clone(),
+        })
+        .unwrap();
+        write_queue_file(path.as_std_path(), 4096, 32, &[&old]);
+
+        let queue = UrlQueueWrapper::open(&path).unwrap();
+        let element = queue.dequeue().await.unwrap().unwrap().take();
+        assert!(element.is_seed);
+        assert_eq!(2, element.age);
+        assert_eq!(url, element.target);
+        assert_eq!(0, element.priority);
+        assert!(queue.is_empty().await);
     }
 }
@@ -0,0 +1,212 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The files of the `queue-file` crate, the format of the queue before the band files.
+//! They are only read to import their elements into a band file.
+//!
+//! Layout (big endian), the elements are a ring buffer after the header:
+//! ```text
+//! header: version 0x80000001 u32 | file length u64 | count u32 | head u64 | tail u64
+//! element: length u32 | data [length]
+//! ```
+
+use crate::queue::errors::QueueFileError;
+use crate::queue::raw::band::BandFile;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+const VERSIONED_HEADER: u32 = 0x8000_0001;
+const HEADER_LEN: u64 = 32;
+const ELEMENT_HEADER_LEN: u64 = 4;
+
+/// The header of a queue file.
+struct Header {
+    file_len: u64,
+    count: u32,
+    head: u64,
+}
+
+impl Header {
+    /// Wraps a [position] after the end of the file around to the start of the elements.
+    fn wrap(&self, position: u64) -> u64 {
+        if position < self.file_len {
+            position
+        } else {
+            HEADER_LEN + position - self.file_len
+        }
+    }
+
+    /// Reads [buffer] at the [position] of the ring buffer.
+    fn read_at(&self, file: &mut File, position: u64, buffer: &mut [u8]) -> io::Result<()> {
+        let position = self.wrap(position);
+        let until_end = (self.file_len - position) as usize;
+        file.seek(SeekFrom::Start(position))?;
+        if buffer.len() <= until_end {
+            file.read_exact(buffer)
+        } else {
+            let (first, second) = buffer.split_at_mut(until_end);
+            file.read_exact(first)?;
+            file.seek(SeekFrom::Start(HEADER_LEN))?;
+            file.read_exact(second)
+        }
+    }
+}
+
+/// Reads the elements of the queue file at [path], None if the file does not exist or has
+/// an other format.
+fn read(path: &Path) -> Result<Option<Vec<Box<[u8]>>>, QueueFileError> {
+    let corrupted = |reason| QueueFileError::Corrupted(path.to_path_buf(), reason);
+    let mut file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err.into()),
+    };
+    let actual_len = file.metadata()?.len();
+    if actual_len < HEADER_LEN {
+        return Ok(None);
+    }
+    let mut bytes = [0u8; HEADER_LEN as usize];
+    file.read_exact(&mut bytes)?;
+    // Any other file, e.g. a band file starting with its magic, is not a queue file.
+    if u32::from_be_bytes(bytes[0..4].try_into().unwrap()) != VERSIONED_HEADER {
+        return Ok(None);
+    }
+    let header = Header {
+        file_len: u64::from_be_bytes(bytes[4..12].try_into().unwrap()),
+        count: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+        head: u64::from_be_bytes(bytes[16..24].try_into().unwrap()),
+    };
+    if header.file_len < HEADER_LEN || header.file_len > actual_len {
+        return Err(corrupted(
+            "The length of the older queue file does not match.",
+        ));
+    }
+    if header.count > 0 && (header.head < HEADER_LEN || header.head >= header.file_len) {
+        return Err(corrupted(
+            "The head of the older queue file is outside of the file.",
+        ));
+    }
+
+    let capacity = header.file_len - HEADER_LEN;
+    if header.count as u64 * ELEMENT_HEADER_LEN > capacity {
+        return Err(corrupted(
+            "The older queue file has more elements than fit into it.",
+        ));
+    }
+    let mut elements = Vec::with_capacity(header.count as usize);
+    let mut position = header.head;
+    for _ in 0..header.count {
+        let mut length = [0u8; ELEMENT_HEADER_LEN as usize];
+        header.read_at(&mut file, position, &mut length)?;
+        let length = u32::from_be_bytes(length) as u64;
+        if length > capacity - ELEMENT_HEADER_LEN {
+            return Err(corrupted(
+                "An element of the older queue file is longer than the file.",
+            ));
+        }
+        let mut data = vec![0u8; length as usize];
+        header.read_at(&mut file, position + ELEMENT_HEADER_LEN, &mut data)?;
+        elements.push(data.into_boxed_slice());
+        position = header.wrap(header.wrap(position + ELEMENT_HEADER_LEN) + length);
+    }
+    Ok(Some(elements))
+}
+
+/// Imports the elements of the queue file at [path] into a band file at the same path.
+/// Returns the number of imported elements, None if there is no queue file at [path].
+pub(super) fn import(path: &Path) -> Result<Option<usize>, QueueFileError> {
+    let Some(elements) = read(path)? else {
+        return Ok(None);
+    };
+    let mut importing = path.to_path_buf().into_os_string();
+    importing.push(".import");
+    let importing = PathBuf::from(importing);
+    // Left by an interrupted import, the queue file is imported again.
+    if importing.exists() {
+        std::fs::remove_file(&importing)?;
+    }
+    let (mut band, _) = BandFile::open(&importing)?;
+    band.add_n(elements.iter().map(|element| &element[..]))?;
+    drop(band);
+    // Swapped in at once, an interrupted import keeps the queue file.
+    std::fs::rename(&importing, path)?;
+    Ok(Some(elements.len()))
+}
+
+#[cfg(test)]
+pub(super) mod test {
+    use super::{import, HEADER_LEN, VERSIONED_HEADER};
+    use crate::queue::raw::band::{BandFile, QueueFileState};
+    use camino_tempfile::Utf8TempDir;
+    use std::path::Path;
+
+    /// Writes a queue file of [file_len] bytes with the [elements] starting at [head].
+    pub fn write_queue_file(path: &Path, file_len: u64, head: u64, elements: &[&[u8]]) {
+        let wrap = |position: u64| {
+            if position < file_len {
+                position
+            } else {
+                HEADER_LEN + position - file_len
+            }
+        };
+        let mut bytes = vec![0u8; file_len as usize];
+        let mut position = head;
+        let mut tail = head;
+        for element in elements {
+            tail = position;
+            let length = (element.len() as u32).to_be_bytes();
+            for byte in length.iter().chain(element.iter()) {
+                bytes[position as usize] = *byte;
+                position = wrap(position + 1);
+            }
+        }
+        bytes[0..4].copy_from_slice(&VERSIONED_HEADER.to_be_bytes());
+        bytes[4..12].copy_from_slice(&file_len.to_be_bytes());
+        bytes[12..16].copy_from_slice(&(elements.len() as u32).to_be_bytes());
+        bytes[16..24].copy_from_slice(&head.to_be_bytes());
+        bytes[24..32].copy_from_slice(&tail.to_be_bytes());
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn imports_the_elements_of_a_queue_file() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        let elements: [&[u8]; 3] = [b"first element", b"second", b"third element"];
+        // The third element wraps around the end of the file.
+        write_queue_file(&path, HEADER_LEN + 48, HEADER_LEN + 20, &elements);
+
+        assert_eq!(Some(3), import(&path).unwrap());
+        let (mut band, state) = BandFile::open(&path).unwrap();
+        assert_eq!(QueueFileState::Clean, state);
+        let imported: Vec<Vec<u8>> = band.iter().map(|value| value.unwrap().into_vec()).collect();
+        let expected: Vec<Vec<u8>> = elements.iter().map(|element| element.to_vec()).collect();
+        assert_eq!(expected, imported);
+        drop(band);
+        assert_eq!(None, import(&path).unwrap());
+        assert_eq!(1, std::fs::read_dir(dir.path()).unwrap().count());
+    }
+
+    #[test]
+    fn a_damaged_queue_file_is_unusable() {
+        let dir = Utf8TempDir::new().unwrap();
+        let path = dir.path().join("queue").into_std_path_buf();
+        write_queue_file(&path, HEADER_LEN + 48, HEADER_LEN, &[b"element"]);
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[HEADER_LEN as usize] = 0xFF;
+        std::fs::write(&path, bytes).unwrap();
+        assert!(import(&path).unwrap_err().is_unusable());
+    }
+}
@@ -12,7 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod band;
 pub mod implementation;
+mod legacy;
 
 use crate::queue::errors::RawQueueError;
 use crate::queue::QueueError;
@@ -29,6 +31,26 @@ pub struct EnqueueCalled;
 /// An aging queue element
 pub trait AgingQueueElement {
     fn age_by_one(&mut self);
+
+    /// The priority band of the element, higher bands are dequeued first.
+    fn priority(&self) -> i8 {
+        0
+    }
+
+    /// Decodes an element read from the queue.
+    fn decode(value: &[u8]) -> Result<Self, bincode::Error>
+    where
+        Self: Sized + DeserializeOwned,
+    {
+        bincode::deserialize(value)
+    }
+}
+
+/// An element that was already encoded for the queue.
+#[derive(Debug)]
+pub struct EncodedQueueElement {
+    pub priority: i8,
+    pub data: Vec<u8>,
 }
 
 pub trait RawSupportsForcedQueueElement {
@@ -42,16 +64,16 @@ pub trait RawAgingQueue: Send + Sync + RawSupportsForcedQueueElement {
     /// Enqueue a value of type [E].
     unsafe fn enqueue_any<T>(
         &self,
-        entry: Either<T, Vec<u8>>,
-    ) -> Result<(), RawQueueError<Vec<u8>>>
+        entry: Either<T, EncodedQueueElement>,
+    ) -> Result<(), RawQueueError<EncodedQueueElement>>
     where
         T: AgingQueueElement + Serialize + Debug;
 
     /// Enqueue all values of type [E].
     unsafe fn enqueue_any_all<T, I>(
         &self,
-        entries: Either<I, Vec<Vec<u8>>>,
-    ) -> Result<(), RawQueueError<Vec<Vec<u8>>>>
+        entries: Either<I, Vec<EncodedQueueElement>>,
+    ) -> Result<(), RawQueueError<Vec<EncodedQueueElement>>>
     where
         T: AgingQueueElement + Serialize + Debug,
         I: IntoIterator<Item = T>;
@@ -66,6 +88,13 @@ pub trait RawAgingQueue: Send + Sync + RawSupportsForcedQueueElement {
     where
         T: AgingQueueElement + DeserializeOwned + Debug;
 
+    /// Keeps only the values of type [E] where [keep] returns true, in their order.
+    /// Returns the number of removed values.
+    unsafe fn retain_any<T, F>(&self, keep: F) -> Result<usize, RawQueueError<()>>
+    where
+        T: AgingQueueElement + DeserializeOwned + Debug,
+        F: FnMut(&T) -> bool;
+
     /// Returns the len of the queue
     fn len(&self) -> usize;
 
@@ -14,6 +14,7 @@
 
 use crate::queue::AgingQueueElement;
 use crate::url::UrlWithDepth;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
@@ -29,6 +30,8 @@ pub struct UrlQueueElement<T = UrlWithDepth> {
     pub host_was_in_use: bool,
     /// The target
     pub target: T,
+    /// The priority band, higher bands are dequeued first.
+    pub priority: i8,
 }
 
 impl<T> Debug for UrlQueueElement<T>
@@ -40,6 +43,7 @@ where
             .field("is_seed", &self.is_seed)
             .field("age", &self.age)
             .field("host_was_in_use", &self.host_was_in_use)
+            .field("priority", &self.priority)
             .field("target", &self.target)
             .finish()
     }
@@ -49,6 +53,27 @@ impl<T> AgingQueueElement for UrlQueueElement<T> {
     fn age_by_one(&mut self) {
         self.age += 1
     }
+
+    fn priority(&self) -> i8 {
+        self.priority
+    }
+
+    /// The elements of older versions end before the priority, they are in the band 0.
+    /// They are read from the imported queue files of older versions.
+    fn decode(value: &[u8]) -> Result<Self, bincode::Error>
+    where
+        Self: Sized + DeserializeOwned,
+    {
+        match bincode::deserialize(value) {
+            Ok(found) => Ok(found),
+            Err(err) => {
+                let mut migrated = Vec::with_capacity(value.len() + 1);
+                migrated.extend_from_slice(value);
+                migrated.push(0);
+                bincode::deserialize(&migrated).map_err(|_| err)
+            }
+        }
+    }
 }
 
 impl<T> UrlQueueElement<T> {
@@ -58,9 +83,16 @@ impl<T> UrlQueueElement<T> {
             age,
             host_was_in_use,
             target,
+            priority: 0,
         }
     }
 
+    /// Sets the priority band of the element.
+    pub fn with_priority(mut self, priority: i8) -> Self {
+        self.priority = priority;
+        self
+    }
+
     #[cfg(test)]
     pub fn map<R, F>(self, mapping: F) -> UrlQueueElement<R>
     where
@@ -72,6 +104,7 @@ impl<T> UrlQueueElement<T> {
             self.host_was_in_use,
             mapping(self.target),
         )
+        .with_priority(self.priority)
     }
 
     #[cfg(test)]
@@ -84,7 +117,8 @@ impl<T> UrlQueueElement<T> {
             self.age,
             self.host_was_in_use,
             mapping(self.target)?,
-        ))
+        )
+        .with_priority(self.priority))
     }
 
     #[cfg(test)]
@@ -97,7 +131,8 @@ impl<T> UrlQueueElement<T> {
             self.age,
             self.host_was_in_use,
             mapping(self.target)?,
-        ))
+        )
+        .with_priority(self.priority))
     }
 }
 
@@ -108,6 +143,7 @@ impl<T: Clone> Clone for UrlQueueElement<T> {
             age: self.age,
             host_was_in_use: self.host_was_in_use,
             target: self.target.clone(),
+            priority: self.priority,
         }
     }
 }
@@ -125,8 +161,8 @@ impl<T: Display> Display for UrlQueueElement<T> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "CrawlElement(is_seed: {}, age: {}, host_was_in_use: {}, target: {})",
-            self.is_seed, self.age, self.host_was_in_use, self.target
+            "CrawlElement(is_seed: {}, age: {}, host_was_in_use: {}, priority: {}, target: {})",
+            self.is_seed, self.age, self.host_was_in_use, self.priority, self.target
         )
     }
 }
@@ -142,3 +178,45 @@ impl<T: PartialEq> PartialEq<UrlQueueElement<T>> for UrlQueueElement<T> {
         self.target.eq(&other.target)
     }
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::queue::{AgingQueueElement, UrlQueueElement};
+    use crate::url::UrlWithDepth;
+    use serde::Serialize;
+
+    /// The element before the priority bands.
+    #[derive(Serialize)]
+    pub(crate) struct OldUrlQueueElement {
+        pub is_seed: bool,
+        pub age: u32,
+        pub host_was_in_use: bool,
+        pub target: UrlWithDepth,
+    }
+
+    #[test]
+    fn an_element_of_an_older_version_is_decoded_into_band_0() {
+        let url = UrlWithDepth::from_url("https://www.example.com/a.html").unwrap();
+        let old = bincode::serialize(&OldUrlQueueElement {
+            is_seed: true,
+            age: 3,
+            host_was_in_use: true,
+            target: url.clone(),
+        })
+        .unwrap();
+        let decoded = UrlQueueElement::<UrlWithDepth>::decode(&old).unwrap();
+        assert!(decoded.is_seed);
+        assert_eq!(3, decoded.age);
+        assert!(decoded.host_was_in_use);
+        assert_eq!(url, decoded.target);
+        assert_eq!(0, decoded.priority);
+
+        let new = bincode::serialize(
+            &UrlQueueElement::new(false, 1, false, url.clone()).with_priority(-2),
+        )
+        .unwrap();
+        let decoded = UrlQueueElement::<UrlWithDepth>::decode(&new).unwrap();
+        assert_eq!(url, decoded.target);
+        assert_eq!(-2, decoded.priority);
+    }
+}
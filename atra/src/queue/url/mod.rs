@@ -58,6 +58,12 @@ where
         n: usize,
     ) -> Result<Vec<UrlQueueElementRef<'a, T>>, QueueError>;
 
+    /// Keeps only the queued elements where [keep] returns true.
+    /// Returns the number of removed elements, the dequeued elements are not affected.
+    async fn retain<F>(&self, keep: F) -> Result<usize, QueueError>
+    where
+        F: FnMut(&UrlQueueElement<T>) -> bool;
+
     /// Number of elements in the queue
     async fn len(&self) -> usize;
 
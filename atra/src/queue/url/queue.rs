@@ -12,7 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::queue::errors::{QueueError, RawQueueError};
+use crate::queue::errors::{QueueError, QueueFileError, RawQueueError};
+use crate::queue::raw::band::QueueFileState;
 use crate::queue::raw::implementation::RawAgingQueueFile;
 use crate::queue::raw::RawAgingQueue;
 use crate::queue::url::{
@@ -40,9 +41,22 @@ unsafe impl<T> Sync for UrlQueueWrapper<T> where T: RawAgingQueue {}
 
 impl UrlQueueWrapper<RawAgingQueueFile> {
     /// Opens as a raw file
-    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, queue_file::Error> {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, QueueFileError> {
         Ok(Self::new(RawAgingQueueFile::open(path)?))
     }
+
+    /// The state of the queue files when they were opened.
+    pub fn opened_state(&self) -> &QueueFileState {
+        self.inner.opened_state()
+    }
+
+    /// Visits every queued element without removing it.
+    pub fn for_each_queued<F>(&self, visitor: F) -> Result<(), QueueError>
+    where
+        F: FnMut(UrlQueueElement<UrlWithDepth>),
+    {
+        self.inner.for_each_queued(visitor)
+    }
 }
 
 impl<T> UrlQueueWrapper<T>
@@ -180,6 +194,21 @@ impl<T: RawAgingQueue> UrlQueue<UrlWithDepth> for UrlQueueWrapper<T> {
         }
     }
 
+    async fn retain<F>(&self, mut keep: F) -> Result<usize, QueueError>
+    where
+        F: FnMut(&UrlQueueElement<UrlWithDepth>) -> bool,
+    {
+        loop {
+            match Self::convert_result(unsafe {
+                self.inner
+                    .retain_any::<UrlQueueElement<UrlWithDepth>, _>(&mut keep)
+            }) {
+                ControlFlow::Break(result) => return result,
+                ControlFlow::Continue(_) => yield_now().await,
+            }
+        }
+    }
+
     /// Number of elements in the queue
     #[inline]
     async fn len(&self) -> usize {
@@ -390,6 +419,52 @@ mod test {
         assert_eq!(3, q.len().await);
     }
 
+    async fn test_queue4(q: impl UrlQueue<UrlWithDepth>) {
+        let element =
+            |url: &str| UrlQueueElement::new(false, 0, false, UrlWithDepth::from_url(url).unwrap());
+        q.enqueue_all([
+            element("https://www.test1.de/a"),
+            element("https://www.test1.de/private/b").with_priority(1),
+            element("https://www.test2.de/private/c"),
+            element("https://www.test1.de/private/d"),
+        ])
+        .await
+        .unwrap();
+
+        let removed = q
+            .retain(|element| {
+                !element
+                    .target
+                    .try_as_str()
+                    .starts_with("https://www.test1.de/private/")
+            })
+            .await
+            .unwrap();
+        assert_eq!(2, removed);
+        assert_eq!(2, q.len().await);
+        assert_eq!(0, q.retain(|_| true).await.unwrap());
+        assert_eq!(
+            "https://www.test1.de/a",
+            q.dequeue()
+                .await
+                .unwrap()
+                .unwrap()
+                .take()
+                .as_ref()
+                .try_as_str()
+        );
+        assert_eq!(
+            "https://www.test2.de/private/c",
+            q.dequeue()
+                .await
+                .unwrap()
+                .unwrap()
+                .take()
+                .as_ref()
+                .try_as_str()
+        );
+    }
+
     #[tokio::test]
     async fn can_initialize() {
         defer! {
@@ -417,10 +492,22 @@ mod test {
         test_queue3(UrlQueueWrapper::open("test2.q").unwrap()).await
     }
 
+    #[tokio::test]
+    async fn can_retain() {
+        defer! {
+            let _ = std::fs::remove_file("test3.q");
+            let _ = std::fs::remove_file("test3.q.p1");
+        }
+        let _ = std::fs::remove_file("test3.q");
+        let _ = std::fs::remove_file("test3.q.p1");
+        test_queue4(UrlQueueWrapper::open("test3.q").unwrap()).await
+    }
+
     #[tokio::test]
     async fn test_impl_behaves_similar() {
         test_queue1(crate::test_impls::TestUrlQueue::default()).await;
         test_queue2(crate::test_impls::TestUrlQueue::default()).await;
         test_queue3(crate::test_impls::TestUrlQueue::default()).await;
+        test_queue4(crate::test_impls::TestUrlQueue::default()).await;
     }
 }
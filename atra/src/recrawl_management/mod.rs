@@ -15,18 +15,35 @@
 use crate::url::AtraUrlOrigin;
 use crate::{db_health_check, declare_column_families};
 use rocksdb::DB;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use time::OffsetDateTime;
 
+mod sitemap_hints;
+
+pub use sitemap_hints::*;
+
 pub trait DomainLastCrawledManager {
     async fn register_access(&self, origin: &AtraUrlOrigin);
 
     async fn get_last_access(&self, origin: &AtraUrlOrigin) -> Option<OffsetDateTime>;
+
+    /// Counts a page fetch of the [origin] since the last refresh of its robots.txt.
+    async fn register_fetch(&self, origin: &AtraUrlOrigin);
+
+    /// Returns the page fetches of the [origin] since the last refresh of its robots.txt.
+    async fn get_fetches_since_robots_refresh(&self, origin: &AtraUrlOrigin) -> u64;
+
+    /// Resets the page fetches of the [origin] after its robots.txt was refreshed.
+    async fn reset_fetches_since_robots_refresh(&self, origin: &AtraUrlOrigin);
 }
 
+/// The fetch counts since the last robots.txt refresh are only kept in memory,
+/// after a restart the counting starts over.
 #[derive(Debug, Clone)]
 pub struct DomainLastCrawledDatabaseManager {
     db: Arc<DB>,
+    fetches: Arc<Mutex<HashMap<AtraUrlOrigin, u64>>>,
 }
 
 impl DomainLastCrawledDatabaseManager {
@@ -42,7 +59,10 @@ impl DomainLastCrawledDatabaseManager {
             )
         ]);
 
-        Self { db }
+        Self {
+            db,
+            fetches: Default::default(),
+        }
     }
 }
 
@@ -68,4 +88,26 @@ impl DomainLastCrawledManager for DomainLastCrawledDatabaseManager {
             None
         }
     }
+
+    async fn register_fetch(&self, domain: &AtraUrlOrigin) {
+        *self
+            .fetches
+            .lock()
+            .unwrap()
+            .entry(domain.clone())
+            .or_default() += 1;
+    }
+
+    async fn get_fetches_since_robots_refresh(&self, domain: &AtraUrlOrigin) -> u64 {
+        self.fetches
+            .lock()
+            .unwrap()
+            .get(domain)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    async fn reset_fetches_since_robots_refresh(&self, domain: &AtraUrlOrigin) {
+        self.fetches.lock().unwrap().remove(domain);
+    }
 }
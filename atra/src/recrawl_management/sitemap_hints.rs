@@ -0,0 +1,175 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use time::{Duration, OffsetDateTime};
+
+/// The `changefreq` of an url listed in a sitemap.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, strum::Display)]
+#[strum(serialize_all = "lowercase")]
+pub enum ChangeFrequency {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl ChangeFrequency {
+    /// The time after which a page probably changed, None if it never changes.
+    pub fn period(self) -> Option<Duration> {
+        match self {
+            ChangeFrequency::Always => Some(Duration::ZERO),
+            ChangeFrequency::Hourly => Some(Duration::HOUR),
+            ChangeFrequency::Daily => Some(Duration::DAY),
+            ChangeFrequency::Weekly => Some(Duration::WEEK),
+            ChangeFrequency::Monthly => Some(Duration::days(30)),
+            ChangeFrequency::Yearly => Some(Duration::days(365)),
+            ChangeFrequency::Never => None,
+        }
+    }
+}
+
+/// The recrawl hints of an url listed in a sitemap.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct SitemapHint {
+    /// The `lastmod` of the url, None if it is missing or malformed.
+    pub lastmod: Option<OffsetDateTime>,
+    /// The `changefreq` of the url, None if it is missing or malformed.
+    pub changefreq: Option<ChangeFrequency>,
+}
+
+impl SitemapHint {
+    /// True iff the sitemap gave no usable hint.
+    pub fn is_empty(&self) -> bool {
+        self.lastmod.is_none() && self.changefreq.is_none()
+    }
+}
+
+/// Returns when the url crawled at [crawled_at] is due for a recrawl, None if the [hint]
+/// tells that it did not change since.
+///
+/// A `lastmod` after [crawled_at] makes the url due at once, an older one skips it.
+/// Without a `lastmod` the period of the `changefreq` replaces the [interval], `never` skips it.
+/// Without any hint the [interval] applies. A `lastmod` after [now] is wrong and ignored.
+pub fn next_crawl_at(
+    hint: Option<&SitemapHint>,
+    crawled_at: OffsetDateTime,
+    interval: Duration,
+    now: OffsetDateTime,
+) -> Option<OffsetDateTime> {
+    let Some(hint) = hint else {
+        return Some(crawled_at + interval);
+    };
+    if let Some(lastmod) = hint.lastmod.filter(|value| *value <= now) {
+        return (lastmod > crawled_at).then_some(lastmod);
+    }
+    match hint.changefreq {
+        Some(changefreq) => changefreq.period().map(|period| crawled_at + period),
+        None => Some(crawled_at + interval),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{next_crawl_at, ChangeFrequency, SitemapHint};
+    use time::{Duration, OffsetDateTime};
+
+    fn at(seconds: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(1_700_000_000 + seconds).unwrap()
+    }
+
+    #[test]
+    fn lastmod_wins_over_changefreq_and_interval() {
+        let crawled_at = at(0);
+        let now = at(Duration::DAY.whole_seconds() * 3);
+        let interval = Duration::DAY * 10;
+
+        let changed = SitemapHint {
+            lastmod: Some(at(3600)),
+            changefreq: Some(ChangeFrequency::Yearly),
+        };
+        assert_eq!(
+            Some(at(3600)),
+            next_crawl_at(Some(&changed), crawled_at, interval, now)
+        );
+
+        let unchanged = SitemapHint {
+            lastmod: Some(at(-3600)),
+            changefreq: Some(ChangeFrequency::Always),
+        };
+        assert_eq!(
+            None,
+            next_crawl_at(Some(&unchanged), crawled_at, interval, now)
+        );
+
+        // A lastmod from the future is not trusted, the changefreq applies.
+        let future = SitemapHint {
+            lastmod: Some(now + Duration::DAY),
+            changefreq: Some(ChangeFrequency::Daily),
+        };
+        assert_eq!(
+            Some(crawled_at + Duration::DAY),
+            next_crawl_at(Some(&future), crawled_at, interval, now)
+        );
+    }
+
+    #[test]
+    fn changefreq_replaces_the_interval() {
+        let crawled_at = at(0);
+        let now = at(0);
+        let interval = Duration::DAY * 10;
+        let expected = [
+            (ChangeFrequency::Always, Some(crawled_at)),
+            (ChangeFrequency::Hourly, Some(crawled_at + Duration::HOUR)),
+            (ChangeFrequency::Daily, Some(crawled_at + Duration::DAY)),
+            (ChangeFrequency::Weekly, Some(crawled_at + Duration::WEEK)),
+            (
+                ChangeFrequency::Monthly,
+                Some(crawled_at + Duration::days(30)),
+            ),
+            (
+                ChangeFrequency::Yearly,
+                Some(crawled_at + Duration::days(365)),
+            ),
+            (ChangeFrequency::Never, None),
+        ];
+        for (changefreq, next) in expected {
+            let hint = SitemapHint {
+                lastmod: None,
+                changefreq: Some(changefreq),
+            };
+            assert_eq!(
+                next,
+                next_crawl_at(Some(&hint), crawled_at, interval, now),
+                "{changefreq}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_hints_fall_back_to_the_interval() {
+        let crawled_at = at(0);
+        let interval = Duration::DAY;
+        assert_eq!(
+            Some(crawled_at + interval),
+            next_crawl_at(None, crawled_at, interval, at(10))
+        );
+        assert_eq!(
+            Some(crawled_at + interval),
+            next_crawl_at(Some(&SitemapHint::default()), crawled_at, interval, at(10))
+        );
+    }
+}
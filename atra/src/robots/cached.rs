@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::crawl::RobotsFallback;
+use crate::robots::diff::rule_paths;
 use reqwest::StatusCode;
 use texting_robots::Robot;
 use time::ext::NumericalDuration;
@@ -22,15 +24,35 @@ use time::{Duration, OffsetDateTime};
 pub enum CachedRobots {
     HasRobots {
         robot: Robot,
+        /// The paths of the rules of all groups, see [rule_paths].
+        rule_paths: Vec<String>,
+        /// The size of the robots.txt in bytes.
+        size: usize,
         retrieved_at: OffsetDateTime,
     },
     NoRobots {
-        _status_code: StatusCode,
+        status_code: StatusCode,
+        /// Decides what is allowed without a robots.txt, follows the config for the status.
+        fallback: RobotsFallback,
         retrieved_at: OffsetDateTime,
     },
 }
 
 impl CachedRobots {
+    /// Parses the robots.txt in [body] for the [agent].
+    pub fn parse(
+        agent: &str,
+        body: &[u8],
+        retrieved_at: OffsetDateTime,
+    ) -> Result<Self, anyhow::Error> {
+        Ok(CachedRobots::HasRobots {
+            robot: Robot::new(agent, body)?,
+            rule_paths: rule_paths(body),
+            size: body.len(),
+            retrieved_at,
+        })
+    }
+
     #[cfg(test)]
     pub fn map<R, F>(&self, on_has_robot: F) -> Option<R>
     where
@@ -66,7 +88,10 @@ impl CachedRobots {
 
     /// Checks if the url is allowed
     pub fn allowed(&self, url: &str) -> bool {
-        self.map_or(true, |it| it.allowed(url))
+        match self {
+            CachedRobots::HasRobots { robot, .. } => robot.allowed(url),
+            CachedRobots::NoRobots { fallback, .. } => *fallback == RobotsFallback::AllowAll,
+        }
     }
 
     /// Returns the sitemaps, if there are any.
@@ -77,6 +102,22 @@ impl CachedRobots {
         }
     }
 
+    /// Returns the paths of the allow and disallow rules of all groups.
+    pub fn rule_paths(&self) -> &[String] {
+        match self {
+            CachedRobots::HasRobots { rule_paths, .. } => rule_paths,
+            CachedRobots::NoRobots { .. } => &[],
+        }
+    }
+
+    /// Returns the size of the robots.txt in bytes, None if there is none.
+    pub fn size(&self) -> Option<usize> {
+        match self {
+            CachedRobots::HasRobots { size, .. } => Some(*size),
+            CachedRobots::NoRobots { .. } => None,
+        }
+    }
+
     /// Returns the delay, if there is one configured
     pub fn delay(&self) -> Option<Duration> {
         self.map_or(None, |it| {
@@ -84,6 +125,15 @@ impl CachedRobots {
         })
     }
 
+    /// Returns true if there is no robots.txt because the origin answered with a 5xx status
+    /// or the body was not readable, the download should be retried soon.
+    pub fn is_temporary_failure(&self) -> bool {
+        match self {
+            CachedRobots::HasRobots { .. } => false,
+            CachedRobots::NoRobots { status_code, .. } => !status_code.is_client_error(),
+        }
+    }
+
     /// Returns the timestamp when it was retrieved.
     pub fn retrieved_at(&self) -> OffsetDateTime {
         match self {
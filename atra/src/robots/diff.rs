@@ -0,0 +1,160 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::robots::CachedRobots;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use time::Duration;
+
+/// The changes between two versions of a robots.txt as seen by the matcher for our agent.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct RobotsDiff {
+    /// The rule paths that were allowed before and are disallowed now.
+    pub disallowed: Vec<String>,
+    /// The rule paths that were disallowed before and are allowed now.
+    pub allowed: Vec<String>,
+    /// The crawl delay before and after, if it changed.
+    pub delay: Option<(Option<Duration>, Option<Duration>)>,
+}
+
+impl RobotsDiff {
+    /// Compares the rule paths of both versions with their matchers.
+    /// Rules of other agents or rules overruled by others do not show up.
+    pub fn between(previous: &CachedRobots, current: &CachedRobots) -> Self {
+        let paths: BTreeSet<&str> = previous
+            .rule_paths()
+            .iter()
+            .chain(current.rule_paths())
+            .map(String::as_str)
+            .collect();
+        let mut diff = Self::default();
+        for path in paths {
+            match (previous.allowed(path), current.allowed(path)) {
+                (true, false) => diff.disallowed.push(path.to_string()),
+                (false, true) => diff.allowed.push(path.to_string()),
+                _ => {}
+            }
+        }
+        let (before, after) = (previous.delay(), current.delay());
+        if before != after {
+            diff.delay = Some((before, after));
+        }
+        diff
+    }
+
+    /// Returns true if nothing changed for our agent.
+    pub fn is_empty(&self) -> bool {
+        self.disallowed.is_empty() && self.allowed.is_empty() && self.delay.is_none()
+    }
+}
+
+impl Display for RobotsDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        fn fmt_delay(delay: &Option<Duration>) -> String {
+            match delay {
+                Some(delay) => delay.to_string(),
+                None => "none".to_string(),
+            }
+        }
+        write!(
+            f,
+            "disallowed=[{}] allowed=[{}]",
+            self.disallowed.join(", "),
+            self.allowed.join(", ")
+        )?;
+        if let Some((before, after)) = &self.delay {
+            write!(f, " delay={}->{}", fmt_delay(before), fmt_delay(after))?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the paths of the allow and disallow rules of all groups in [body].
+/// Wildcards are cut off, the remaining prefix is what the matcher is asked about.
+pub(crate) fn rule_paths(body: &[u8]) -> Vec<String> {
+    let body = String::from_utf8_lossy(body);
+    let mut paths = BTreeSet::new();
+    for line in body.lines() {
+        let line = match line.split_once('#') {
+            Some((line, _)) => line,
+            None => line,
+        };
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        if !key.eq_ignore_ascii_case("allow") && !key.eq_ignore_ascii_case("disallow") {
+            continue;
+        }
+        let value = value.trim();
+        let value = match value.split_once('*') {
+            Some((prefix, _)) => prefix,
+            None => value.strip_suffix('$').unwrap_or(value),
+        };
+        if value.starts_with('/') {
+            paths.insert(value.to_string());
+        }
+    }
+    paths.into_iter().collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rule_paths, RobotsDiff};
+    use crate::config::crawl::RobotsFallback;
+    use crate::robots::CachedRobots;
+    use reqwest::StatusCode;
+    use time::{Duration, OffsetDateTime};
+
+    fn robots(body: &str) -> CachedRobots {
+        CachedRobots::parse("atra", body.as_bytes(), OffsetDateTime::now_utc()).unwrap()
+    }
+
+    #[test]
+    fn collects_the_rule_paths_of_all_groups() {
+        let body = b"User-agent: *\nDisallow: /private/ # secret\nAllow: /private/public$\n\nUser-agent: other\ndisallow: /search*?q=\nDisallow:\nSitemap: https://example.com/sitemap.xml";
+        assert_eq!(
+            vec!["/private/", "/private/public", "/search"],
+            rule_paths(body)
+        );
+    }
+
+    #[test]
+    fn reports_the_paths_that_changed_for_the_agent() {
+        let previous = robots("User-agent: *\nDisallow: /old/\n\nUser-agent: other\nDisallow: /");
+        let current = robots("User-agent: *\nDisallow: /private/\nCrawl-delay: 5");
+        let diff = RobotsDiff::between(&previous, &current);
+        assert_eq!(vec!["/private/"], diff.disallowed);
+        assert_eq!(vec!["/old/"], diff.allowed);
+        assert_eq!(Some((None, Some(Duration::seconds(5)))), diff.delay);
+        assert_eq!(
+            "disallowed=[/private/] allowed=[/old/] delay=none->5s",
+            diff.to_string()
+        );
+
+        assert!(RobotsDiff::between(&current, &robots(
+            "User-agent: *\nDisallow: /private/\nCrawl-delay: 5\n\nUser-agent: other\nDisallow: /"
+        ))
+        .is_empty());
+
+        let missing = CachedRobots::NoRobots {
+            status_code: StatusCode::NOT_FOUND,
+            fallback: RobotsFallback::AllowAll,
+            retrieved_at: OffsetDateTime::now_utc(),
+        };
+        let diff = RobotsDiff::between(&missing, &current);
+        assert_eq!(vec!["/private/"], diff.disallowed);
+        assert!(diff.allowed.is_empty());
+    }
+}
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::database::DatabaseError;
+use camino::Utf8PathBuf;
 use thiserror::Error;
 use url::ParseError;
 
@@ -31,4 +32,6 @@ pub enum RobotsError<ClientError: std::error::Error> {
     Database(#[from] DatabaseError),
     #[error("The serialisation had some kind of issue")]
     Serialisation(#[from] bincode::Error),
+    #[error("The robots.txt override {0} is not readable: {1}")]
+    OverrideNotReadable(Utf8PathBuf, std::io::Error),
 }
@@ -13,10 +13,11 @@
 // limitations under the License.
 
 use crate::client::traits::AtraClient;
+use crate::config::crawl::RobotsFallback;
 use crate::robots::{CachedRobots, RobotsError, RobotsManager};
 use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
 use std::error::Error;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use thiserror::Error;
 use time::Duration;
 
@@ -61,6 +62,21 @@ pub enum AnyRobotsInformation<'a, R: RobotsManager> {
     General(GeneralRobotsInformation<'a, R>),
 }
 
+impl<'a, R: RobotsManager> AnyRobotsInformation<'a, R> {
+    /// Downloads the robots.txt of [url] again and uses it from now on.
+    /// Returns the robots.txt used before, if there was one, and the refreshed one.
+    pub async fn refresh<Client: AtraClient>(
+        &self,
+        client: &Client,
+        url: &UrlWithDepth,
+    ) -> Result<(Option<Arc<CachedRobots>>, Arc<CachedRobots>), RobotsError<Client::Error>> {
+        match self {
+            AnyRobotsInformation::Origin(a) => a.refresh(client, url).await,
+            AnyRobotsInformation::General(b) => b.refresh(client, url).await,
+        }
+    }
+}
+
 impl<'a, R: RobotsManager> RobotsInformation for AnyRobotsInformation<'a, R> {
     ///Try to get the underlying robots.txt if it exists in any cache layer.
     ///Does return None if it needs a download.
@@ -125,10 +141,34 @@ impl<'a, R: RobotsManager> RobotsInformation for AnyRobotsInformation<'a, R> {
 /// Same as [GeneralRobotsInformation] but is bound to a specific domain
 pub struct OriginSpecificRobotsInformation<'a, R: RobotsManager> {
     origin: AtraUrlOrigin,
-    origin_cached: Arc<CachedRobots>,
+    origin_cached: RwLock<Arc<CachedRobots>>,
     general: GeneralRobotsInformation<'a, R>,
 }
 
+impl<'a, R: RobotsManager> OriginSpecificRobotsInformation<'a, R> {
+    fn cached(&self) -> Arc<CachedRobots> {
+        self.origin_cached.read().unwrap().clone()
+    }
+
+    /// Downloads the robots.txt of [url] again, replaces the bound one if [url] belongs to
+    /// the origin.
+    pub async fn refresh<Client: AtraClient>(
+        &self,
+        client: &Client,
+        url: &UrlWithDepth,
+    ) -> Result<(Option<Arc<CachedRobots>>, Arc<CachedRobots>), RobotsError<Client::Error>> {
+        let (previous, refreshed) = self.general.refresh(client, url).await?;
+        if let Some(origin) = url.atra_origin() {
+            if origin == self.origin {
+                let previous =
+                    std::mem::replace(&mut *self.origin_cached.write().unwrap(), refreshed.clone());
+                return Ok((Some(previous), refreshed));
+            }
+        }
+        Ok((previous, refreshed))
+    }
+}
+
 // impl<R: RobotsManager> DomainSpecificRobotsInformation<R> {
 //     pub fn into_inner(self) -> GeneralRobotsInformation<R> {
 //         self.general
@@ -143,7 +183,7 @@ impl<'a, R: RobotsManager> RobotsInformation for OriginSpecificRobotsInformation
         if let Some(origin) = url.atra_origin() {
             if origin == self.origin {
                 log::trace!("Robots: Fast");
-                return Ok(Some(self.origin_cached.clone()));
+                return Ok(Some(self.cached()));
             }
         }
         self.general.get(url).await
@@ -157,7 +197,7 @@ impl<'a, R: RobotsManager> RobotsInformation for OriginSpecificRobotsInformation
         if let Some(origin) = url.atra_origin() {
             if origin == self.origin {
                 log::trace!("Robots: Fast");
-                return Ok(self.origin_cached.clone());
+                return Ok(self.cached());
             }
         }
         self.general.get_or_retrieve(client, url).await
@@ -171,7 +211,7 @@ impl<'a, R: RobotsManager> RobotsInformation for OriginSpecificRobotsInformation
         if let Some(origin) = url.atra_origin() {
             if origin == self.origin {
                 log::trace!("Robots: Fast");
-                return self.origin_cached.delay();
+                return self.cached().delay();
             }
         }
         self.general.get_or_retrieve_delay(client, url).await
@@ -181,7 +221,7 @@ impl<'a, R: RobotsManager> RobotsInformation for OriginSpecificRobotsInformation
         if let Some(origin) = url.atra_origin() {
             if origin == self.origin {
                 log::trace!("Robots: Fast");
-                return Some(self.origin_cached.allowed(&url.try_as_str()));
+                return Some(self.cached().allowed(&url.try_as_str()));
             }
         }
         self.general.check_if_allowed_fast(url).await
@@ -195,7 +235,7 @@ impl<'a, R: RobotsManager> RobotsInformation for OriginSpecificRobotsInformation
         if let Some(origin) = url.atra_origin() {
             if origin == self.origin {
                 log::trace!("Robots: Fast");
-                return self.origin_cached.allowed(&url.try_as_str());
+                return self.cached().allowed(&url.try_as_str());
             }
         }
         self.general.check_if_allowed(client, url).await
@@ -225,6 +265,18 @@ impl<'a, R: RobotsManager> GeneralRobotsInformation<'a, R> {
     //     return self.inner
     // }
 
+    /// Downloads the robots.txt of [url] again, even if the cached one is not too old yet.
+    /// Returns the cached robots.txt, if there was one, and the refreshed one.
+    pub async fn refresh<Client: AtraClient>(
+        &self,
+        client: &Client,
+        url: &UrlWithDepth,
+    ) -> Result<(Option<Arc<CachedRobots>>, Arc<CachedRobots>), RobotsError<Client::Error>> {
+        let previous = self.get::<Client::Error>(url).await?;
+        let refreshed = self.inner.refresh(client, self.agent.as_str(), url).await?;
+        Ok((previous, refreshed))
+    }
+
     pub async fn bind_to_domain(
         self,
         client: &impl AtraClient,
@@ -238,11 +290,23 @@ impl<'a, R: RobotsManager> GeneralRobotsInformation<'a, R> {
             Some(found) => found,
         };
         match self.get_or_retrieve(client, url).await {
-            Ok(domain_cached) => AnyRobotsInformation::Origin(OriginSpecificRobotsInformation {
-                origin_cached: domain_cached,
-                general: self,
-                origin: domain,
-            }),
+            Ok(domain_cached) => {
+                if let CachedRobots::NoRobots {
+                    status_code,
+                    fallback: RobotsFallback::DenyAll,
+                    ..
+                } = domain_cached.as_ref()
+                {
+                    log::info!(
+                        "The robots.txt of {domain} failed with {status_code}, nothing is allowed."
+                    );
+                }
+                AnyRobotsInformation::Origin(OriginSpecificRobotsInformation {
+                    origin_cached: RwLock::new(domain_cached),
+                    general: self,
+                    origin: domain,
+                })
+            }
             Err(err) => {
                 log::debug!("Failed to retrieve the robots.txt for {url} with {err}");
                 AnyRobotsInformation::General(self)
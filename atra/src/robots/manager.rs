@@ -39,4 +39,13 @@ pub trait RobotsManager: Sync + Send {
         url: &UrlWithDepth,
         max_age: Option<&Duration>,
     ) -> Result<Arc<CachedRobots>, RobotsError<C::Error>>;
+
+    /// Downloads the robots.txt with the client, even if the cached one is not too old yet,
+    /// and replaces the cached one.
+    async fn refresh<C: AtraClient>(
+        &self,
+        client: &C,
+        agent: &str,
+        url: &UrlWithDepth,
+    ) -> Result<Arc<CachedRobots>, RobotsError<C::Error>>;
 }
@@ -13,26 +13,35 @@
 // limitations under the License.
 
 use crate::client::traits::{AtraClient, AtraResponse};
+use crate::config::crawl::RobotsFallback;
 use crate::database::DBActionType::{Delete, Read, Write};
 use crate::database::RawDatabaseError;
-use crate::robots::{CachedRobots, RobotsError, RobotsManager};
+use crate::robots::{CachedRobots, RobotsError, RobotsManager, RobotsPolicy};
+use crate::toolkit::serde_ext::status_code;
 use crate::url::UrlWithDepth;
 use crate::url::{AtraOriginProvider, AtraUrlOrigin};
 use crate::{db_health_check, declare_column_families};
+use reqwest::StatusCode;
 use rocksdb::{BoundColumnFamily, DB};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::num::NonZeroUsize;
 use std::sync::Arc;
-use texting_robots::{get_robots_url, Robot};
+use texting_robots::get_robots_url;
 use time::{Duration, OffsetDateTime};
 use tokio::task::yield_now;
 
+/// How long the fallback of a robots.txt that failed temporarily is used before it is
+/// downloaded again, regardless of the max age of the robots.txt.
+pub const FAILED_ROBOTS_TTL: Duration = Duration::minutes(5);
+
 /// A manager for robots.txt, threadsafe, with some caching
 #[derive(Debug)]
 pub struct OffMemoryRobotsManager {
     db: Arc<DB>,
     cache: moka::future::Cache<AtraUrlOrigin, Arc<CachedRobots>>,
+    policy: RobotsPolicy,
+    failed_ttl: Duration,
 }
 
 impl OffMemoryRobotsManager {
@@ -41,7 +50,7 @@ impl OffMemoryRobotsManager {
     }
 
     /// Panics if the [Self::COLUMN_FAMILY] is not configured!
-    pub fn new(db: Arc<DB>, cache_size: NonZeroUsize) -> Self {
+    pub fn new(db: Arc<DB>, cache_size: NonZeroUsize, policy: RobotsPolicy) -> Self {
         db_health_check!(db: [
             Self::ROBOTS_TXT_DB_CF => (
                 if test robots_txt_cf_options
@@ -52,6 +61,49 @@ impl OffMemoryRobotsManager {
         Self {
             db,
             cache: moka::future::Cache::new(cache_size.get() as u64),
+            policy,
+            failed_ttl: FAILED_ROBOTS_TTL,
+        }
+    }
+
+    #[cfg(test)]
+    fn with_failed_ttl(mut self, failed_ttl: Duration) -> Self {
+        self.failed_ttl = failed_ttl;
+        self
+    }
+
+    /// The max age of a robots.txt, a temporary failure expires after the [FAILED_ROBOTS_TTL].
+    fn _max_age_for(
+        &self,
+        is_temporary_failure: bool,
+        max_age: Option<&Duration>,
+    ) -> Option<Duration> {
+        if is_temporary_failure {
+            Some(max_age.map_or(self.failed_ttl, |max_age| self.failed_ttl.min(*max_age)))
+        } else {
+            max_age.copied()
+        }
+    }
+
+    /// Returns the local robots.txt of [key], None if it has no override. It is read again
+    /// from its file if [reload] is set.
+    async fn _get_override<E: Error>(
+        &self,
+        agent: &str,
+        key: &AtraUrlOrigin,
+        reload: bool,
+    ) -> Result<Option<Arc<CachedRobots>>, RobotsError<E>> {
+        if !self.policy.has_override(key) {
+            return Ok(None);
+        }
+        if !reload {
+            if let Some(found) = self.cache.get(key).await {
+                return Ok(Some(found));
+            }
+        }
+        match self.policy.read_override(agent, key)? {
+            Some(found) => Ok(Some(self._set_cache(key.clone(), found).await)),
+            None => Ok(None),
         }
     }
 
@@ -74,8 +126,8 @@ impl OffMemoryRobotsManager {
     ) -> Option<Arc<CachedRobots>> {
         if let Some(found) = self.cache.get(&key).await {
             log::trace!("Robots-Cache-Hit: {:?}", key);
-            if let Some(max_age) = max_age {
-                if (now - found.retrieved_at()).le(max_age) {
+            if let Some(max_age) = self._max_age_for(found.is_temporary_failure(), max_age) {
+                if (now - found.retrieved_at()).le(&max_age) {
                     let found = found.clone();
                     return Some(found);
                 }
@@ -109,7 +161,19 @@ impl OffMemoryRobotsManager {
         if let Some(found) = self._get_db0(agent, key, now, max_age, &self.cf_handle())? {
             return Ok(found);
         }
+        self._retrieve(client, agent, key, url).await
+    }
 
+    /// Downloads the robots.txt and stores it in the db. Without a robots.txt the status and
+    /// the fallback of the [RobotsPolicy] for it are stored, unless the failure is temporary,
+    /// i.e. a 5xx status or an unreadable body. These are only kept in the cache.
+    async fn _retrieve<C: AtraClient>(
+        &self,
+        client: &C,
+        agent: &str,
+        key: &AtraUrlOrigin,
+        url: &UrlWithDepth,
+    ) -> Result<CachedRobots, RobotsError<C::Error>> {
         let result = client
             .get(&get_robots_url(&url.try_as_str())?)
             .await
@@ -117,41 +181,34 @@ impl OffMemoryRobotsManager {
         let retrieved_at = OffsetDateTime::now_utc();
         let status_code = result.status();
 
-        if status_code.is_client_error() || status_code.is_server_error() {
-            return Ok(CachedRobots::NoRobots {
-                retrieved_at,
-                _status_code: status_code,
-            });
-        }
-
-        let result = result.bytes().await;
-
-        let result = match result {
-            Ok(result) => result,
-            _ => {
-                return Ok(CachedRobots::NoRobots {
-                    retrieved_at,
-                    _status_code: status_code,
-                })
-            }
+        let body = if status_code.is_client_error() || status_code.is_server_error() {
+            None
+        } else {
+            result.bytes().await.ok()
         };
 
-        let bytes = BytesWithAge {
-            bytes: result.as_ref(),
+        let stored = StoredRobots {
+            bytes: body.as_ref().map(AsRef::as_ref).unwrap_or_default(),
             retrieved_at,
+            status_code,
+            fallback: body
+                .is_none()
+                .then(|| self.policy.fallback_for(status_code)),
         };
-        let value = bincode::serialize(&bytes)?;
-        self.db
-            .put_cf(&self.cf_handle(), key.as_bytes(), &value)
-            .enrich_with_entry(Self::ROBOTS_TXT_DB_CF, Write, key.as_bytes(), &value)?;
-        drop(value);
-        yield_now().await;
-
-        let robot = Robot::new(agent, result.as_ref()).map_err(RobotsError::InvalidRobotsTxt)?;
-        return Ok(CachedRobots::HasRobots {
-            robot,
-            retrieved_at,
-        });
+        if stored.is_temporary_failure() {
+            log::debug!("Robots-Temporary-Failure: {:?} with {}", key, status_code);
+        } else {
+            let value = bincode::serialize(&stored)?;
+            self.db
+                .put_cf(&self.cf_handle(), key.as_bytes(), &value)
+                .enrich_with_entry(Self::ROBOTS_TXT_DB_CF, Write, key.as_bytes(), &value)?;
+            drop(value);
+            yield_now().await;
+        }
+
+        stored
+            .to_cached(agent)
+            .map_err(RobotsError::InvalidRobotsTxt)
     }
 
     fn _get_db0<'a, E: Error>(
@@ -167,15 +224,14 @@ impl OffMemoryRobotsManager {
             .get_pinned_cf(cf, key.as_bytes())
             .enrich_without_entry(Self::ROBOTS_TXT_DB_CF, Read, key.as_bytes())?;
         if let Some(result) = result {
-            let found: BytesWithAge = bincode::deserialize(&result)?;
-            if let Some(max_age) = max_age {
-                if (now - found.retrieved_at).le(max_age) {
-                    let robot =
-                        Robot::new(agent, found.bytes).map_err(RobotsError::InvalidRobotsTxt)?;
-                    return Ok(Some(CachedRobots::HasRobots {
-                        robot,
-                        retrieved_at: found.retrieved_at,
-                    }));
+            let found = StoredRobots::read(&result)?;
+            // Older versions stored the temporary failures too.
+            if let Some(max_age) = self._max_age_for(found.is_temporary_failure(), max_age) {
+                if (now - found.retrieved_at).le(&max_age) {
+                    return found
+                        .to_cached(agent)
+                        .map(Some)
+                        .map_err(RobotsError::InvalidRobotsTxt);
                 } else {
                     drop(result);
                     self.db.delete_cf(cf, key.as_bytes()).enrich_without_entry(
@@ -185,12 +241,10 @@ impl OffMemoryRobotsManager {
                     )?;
                 }
             } else {
-                let robot =
-                    Robot::new(agent, found.bytes).map_err(RobotsError::InvalidRobotsTxt)?;
-                return Ok(Some(CachedRobots::HasRobots {
-                    robot,
-                    retrieved_at: found.retrieved_at,
-                }));
+                return found
+                    .to_cached(agent)
+                    .map(Some)
+                    .map_err(RobotsError::InvalidRobotsTxt);
             }
         }
         Ok(None)
@@ -212,6 +266,9 @@ impl RobotsManager for OffMemoryRobotsManager {
     ) -> Result<Option<Arc<CachedRobots>>, RobotsError<E>> {
         let now = OffsetDateTime::now_utc();
         let key = url.url().atra_origin().ok_or(RobotsError::NoDomainForUrl)?;
+        if let Some(found) = self._get_override(agent, &key, false).await? {
+            return Ok(Some(found));
+        }
         let found = self._get_cached(&key, now.clone(), max_age.clone()).await;
         if found.is_some() {
             return Ok(found);
@@ -235,6 +292,9 @@ impl RobotsManager for OffMemoryRobotsManager {
     ) -> Result<Arc<CachedRobots>, RobotsError<C::Error>> {
         let now = OffsetDateTime::now_utc();
         let key = url.url().atra_origin().ok_or(RobotsError::NoDomainForUrl)?;
+        if let Some(found) = self._get_override(agent, &key, false).await? {
+            return Ok(found);
+        }
         match self._get_cached(&key, now.clone(), max_age).await {
             Some(found) => return Ok(found),
             _ => {}
@@ -244,14 +304,76 @@ impl RobotsManager for OffMemoryRobotsManager {
             .await?;
         Ok(self._set_cache(key, retrieved).await)
     }
+
+    async fn refresh<C: AtraClient>(
+        &self,
+        client: &C,
+        agent: &str,
+        url: &UrlWithDepth,
+    ) -> Result<Arc<CachedRobots>, RobotsError<C::Error>> {
+        let key = url.url().atra_origin().ok_or(RobotsError::NoDomainForUrl)?;
+        if let Some(found) = self._get_override(agent, &key, true).await? {
+            return Ok(found);
+        }
+        let retrieved = self._retrieve(client, agent, &key, url).await?;
+        Ok(self._set_cache(key, retrieved).await)
+    }
 }
 
+/// The format of the older versions, only the found robots.txt were stored.
 #[derive(Debug, Serialize, Deserialize)]
 struct BytesWithAge<'a> {
     bytes: &'a [u8],
     retrieved_at: OffsetDateTime,
 }
 
+/// A robots.txt in the db, with the status it was answered with.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredRobots<'a> {
+    bytes: &'a [u8],
+    retrieved_at: OffsetDateTime,
+    #[serde(with = "status_code")]
+    status_code: StatusCode,
+    /// Only set if there is no robots.txt, what was allowed at the time of the download.
+    fallback: Option<RobotsFallback>,
+}
+
+impl<'a> StoredRobots<'a> {
+    fn read(value: &'a [u8]) -> Result<Self, bincode::Error> {
+        match bincode::deserialize(value) {
+            Ok(found) => Ok(found),
+            Err(err) => match bincode::deserialize::<BytesWithAge>(value) {
+                Ok(BytesWithAge {
+                    bytes,
+                    retrieved_at,
+                }) => Ok(Self {
+                    bytes,
+                    retrieved_at,
+                    status_code: StatusCode::OK,
+                    fallback: None,
+                }),
+                Err(_) => Err(err),
+            },
+        }
+    }
+
+    /// Returns true if there is no robots.txt because of a 5xx status or an unreadable body.
+    fn is_temporary_failure(&self) -> bool {
+        self.fallback.is_some() && !self.status_code.is_client_error()
+    }
+
+    fn to_cached(&self, agent: &str) -> Result<CachedRobots, anyhow::Error> {
+        match self.fallback {
+            None => CachedRobots::parse(agent, self.bytes, self.retrieved_at),
+            Some(fallback) => Ok(CachedRobots::NoRobots {
+                status_code: self.status_code,
+                fallback,
+                retrieved_at: self.retrieved_at,
+            }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     // use crate::config::system::DEFAULT_CACHE_SIZE_ROBOTS;
@@ -348,4 +470,219 @@ mod test {
     //     println!("with_cache: {:?}", Instant::now() - now);
     //     println!("{:?}", robots);
     // }
+
+    use crate::config::crawl::RobotsFallback;
+    use crate::config::CrawlConfig;
+    use crate::data::RawData;
+    use crate::database::open_db;
+    use crate::fetching::FetchedRequestData;
+    use crate::robots::{CachedRobots, OffMemoryRobotsManager, RobotsManager, RobotsPolicy};
+    use crate::test_impls::{FakeClient, FakeResponse, FakeResponseError};
+    use crate::url::{AtraOriginProvider, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use reqwest::StatusCode;
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use time::Duration;
+
+    const AGENT: &str = "FakeClient";
+    const ROBOTS_URL: &str = "https://www.example.com/robots.txt";
+    const PRIVATE_URL: &str = "https://www.example.com/private/a.html";
+
+    fn robots_txt(body: &'static str, status_code: StatusCode) -> FakeResponse {
+        FakeResponse::new(
+            Some(FetchedRequestData::new(
+                RawData::from_vec(body.as_bytes().to_vec()),
+                None,
+                status_code,
+                None,
+                None,
+                false,
+            )),
+            1,
+        )
+    }
+
+    fn create_manager(root: &Utf8TempDir, config: &CrawlConfig) -> OffMemoryRobotsManager {
+        let db = Arc::new(open_db(root.path().join("db")).unwrap());
+        OffMemoryRobotsManager::new(
+            db,
+            NonZeroUsize::new(16).unwrap(),
+            RobotsPolicy::new(config),
+        )
+    }
+
+    fn robots_requests(client: &FakeClient) -> usize {
+        client
+            .requests()
+            .iter()
+            .filter(|(url, _)| url.as_str() == ROBOTS_URL)
+            .count()
+    }
+
+    #[tokio::test]
+    async fn the_robots_txt_is_downloaded_again_after_the_ttl() {
+        let root = Utf8TempDir::new().unwrap();
+        let client = FakeClient::new();
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt(
+                "User-agent: *\nDisallow: /private/",
+                StatusCode::OK,
+            )),
+        );
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let ttl = Duration::seconds(1);
+        let manager = create_manager(&root, &CrawlConfig::default());
+
+        let first = manager
+            .get_or_retrieve(&client, AGENT, &url, Some(&ttl))
+            .await
+            .unwrap();
+        assert!(!first.allowed(PRIVATE_URL));
+        manager
+            .get_or_retrieve(&client, AGENT, &url, Some(&ttl))
+            .await
+            .unwrap();
+        assert_eq!(1, robots_requests(&client));
+
+        // A restart keeps the robots.txt.
+        drop(manager);
+        let manager = create_manager(&root, &CrawlConfig::default());
+        let stored = manager
+            .get::<FakeResponseError>(AGENT, &url, Some(&ttl))
+            .await
+            .unwrap()
+            .expect("The robots.txt should be stored.");
+        assert!(!stored.allowed(PRIVATE_URL));
+
+        tokio::time::sleep(std::time::Duration::from_millis(1_100)).await;
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt("User-agent: *\nAllow: /", StatusCode::OK)),
+        );
+        let refetched = manager
+            .get_or_retrieve(&client, AGENT, &url, Some(&ttl))
+            .await
+            .unwrap();
+        assert_eq!(2, robots_requests(&client));
+        assert!(refetched.allowed(PRIVATE_URL));
+    }
+
+    #[tokio::test]
+    async fn the_fallback_of_a_failed_robots_txt_is_recorded() {
+        let root = Utf8TempDir::new().unwrap();
+        let client = FakeClient::new();
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt("Forbidden", StatusCode::FORBIDDEN)),
+        );
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut config = CrawlConfig::default();
+        config.robots_on_client_error = RobotsFallback::DenyAll;
+        let manager = create_manager(&root, &config);
+        let found = manager
+            .get_or_retrieve(&client, AGENT, &url, None)
+            .await
+            .unwrap();
+        assert!(!found.allowed(PRIVATE_URL));
+
+        // Recorded with the fallback of the download, even if the config changed since then.
+        drop(manager);
+        let manager = create_manager(&root, &CrawlConfig::default());
+        let stored = manager
+            .get::<FakeResponseError>(AGENT, &url, None)
+            .await
+            .unwrap()
+            .expect("The failed robots.txt should be stored.");
+        match stored.as_ref() {
+            CachedRobots::NoRobots {
+                status_code,
+                fallback,
+                ..
+            } => {
+                assert_eq!(StatusCode::FORBIDDEN, *status_code);
+                assert_eq!(RobotsFallback::DenyAll, *fallback);
+            }
+            CachedRobots::HasRobots { .. } => panic!("There is no robots.txt."),
+        }
+        assert_eq!(1, robots_requests(&client));
+    }
+
+    #[tokio::test]
+    async fn a_server_error_is_retried_without_a_max_age() {
+        let root = Utf8TempDir::new().unwrap();
+        let client = FakeClient::new();
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt("Unavailable", StatusCode::SERVICE_UNAVAILABLE)),
+        );
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut config = CrawlConfig::default();
+        config.robots_on_server_error = RobotsFallback::DenyAll;
+        let manager = create_manager(&root, &config).with_failed_ttl(Duration::seconds(1));
+
+        let failed = manager
+            .get_or_retrieve(&client, AGENT, &url, None)
+            .await
+            .unwrap();
+        assert!(failed.is_temporary_failure());
+        assert!(!failed.allowed(PRIVATE_URL));
+        manager
+            .get_or_retrieve(&client, AGENT, &url, None)
+            .await
+            .unwrap();
+        assert_eq!(1, robots_requests(&client));
+
+        // The failure is not stored.
+        let key = url.atra_origin().unwrap();
+        assert!(manager
+            .db
+            .get_cf(&manager.cf_handle(), key.as_bytes())
+            .unwrap()
+            .is_none());
+
+        tokio::time::sleep(std::time::Duration::from_millis(1_100)).await;
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt("User-agent: *\nAllow: /", StatusCode::OK)),
+        );
+        let found = manager
+            .get_or_retrieve(&client, AGENT, &url, None)
+            .await
+            .unwrap();
+        assert_eq!(2, robots_requests(&client));
+        assert!(!found.is_temporary_failure());
+        assert!(found.allowed(PRIVATE_URL));
+    }
+
+    #[tokio::test]
+    async fn the_override_wins_over_the_network() {
+        let root = Utf8TempDir::new().unwrap();
+        let path = root.path().join("robots.txt");
+        std::fs::write(&path, "User-agent: *\nDisallow: /private/").unwrap();
+        let client = FakeClient::new();
+        client.insert(
+            ROBOTS_URL.parse().unwrap(),
+            Ok(robots_txt("User-agent: *\nAllow: /", StatusCode::OK)),
+        );
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let mut config = CrawlConfig::default();
+        config
+            .robots_overrides
+            .insert(url.atra_origin().unwrap(), path.clone());
+        let manager = create_manager(&root, &config);
+
+        let found = manager
+            .get_or_retrieve(&client, AGENT, &url, None)
+            .await
+            .unwrap();
+        assert!(!found.allowed(PRIVATE_URL));
+
+        // A refresh reads the file again.
+        std::fs::write(&path, "User-agent: *\nDisallow: /").unwrap();
+        let refreshed = manager.refresh(&client, AGENT, &url).await.unwrap();
+        assert!(!refreshed.allowed("https://www.example.com/"));
+        assert_eq!(0, robots_requests(&client));
+    }
 }
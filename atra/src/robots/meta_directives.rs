@@ -0,0 +1,209 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::robots::unavailable_after::{find_ignore_ascii_case, X_ROBOTS_TAG};
+use crate::static_selector;
+use reqwest::header::HeaderMap;
+use scraper::Html;
+
+static_selector!(META_ROBOTS = "meta[name][content]");
+
+/// Directives that carry a value after a colon, everything else in front
+/// of a colon names a user agent.
+const VALUE_DIRECTIVES: [&str; 4] = [
+    "unavailable_after",
+    "max-snippet",
+    "max-image-preview",
+    "max-video-preview",
+];
+
+/// The `noindex` and `nofollow` directives of the robots meta tags and the `X-Robots-Tag` header.
+/// Only directives addressed to all robots are considered, e.g. `googlebot: noindex` is ignored.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RobotsMetaDirectives {
+    /// The page must not be archived.
+    pub noindex: bool,
+    /// The links of the page must not be followed.
+    pub nofollow: bool,
+}
+
+impl RobotsMetaDirectives {
+    /// Parses a directive list like `noindex, nofollow` or `none`.
+    pub fn from_directives(directives: &str) -> Self {
+        let mut result = Self::default();
+        let mut addressed_to_all = true;
+        for token in directives.split(',') {
+            let token = token.trim();
+            let directive = match token.split_once(':') {
+                Some((name, value)) => {
+                    let name = name.trim();
+                    // A name with whitespace is the rest of a date split at its comma.
+                    if name.is_empty()
+                        || name.contains(char::is_whitespace)
+                        || VALUE_DIRECTIVES
+                            .iter()
+                            .any(|value| name.eq_ignore_ascii_case(value))
+                    {
+                        continue;
+                    }
+                    // Everything after `agent:` belongs to this agent.
+                    addressed_to_all = false;
+                    value.trim()
+                }
+                None => token,
+            };
+            if !addressed_to_all {
+                continue;
+            }
+            if directive.eq_ignore_ascii_case("noindex") {
+                result.noindex = true;
+            } else if directive.eq_ignore_ascii_case("nofollow") {
+                result.nofollow = true;
+            } else if directive.eq_ignore_ascii_case("none") {
+                result.noindex = true;
+                result.nofollow = true;
+            }
+        }
+        result
+    }
+
+    /// Reads the directives from the `X-Robots-Tag` [headers].
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get_all(X_ROBOTS_TAG)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(Self::from_directives)
+            .fold(Self::default(), Self::merge)
+    }
+
+    /// Reads the directives from the robots meta tags of an [html] page.
+    pub fn from_html(html: &str) -> Self {
+        // Parsing the document is expensive, most pages do not have a robots meta tag.
+        if find_ignore_ascii_case(html, "robots").is_none() {
+            return Self::default();
+        }
+        Self::from_document(&Html::parse_document(html))
+    }
+
+    /// Reads the directives from the robots meta tags of a parsed [document].
+    pub fn from_document(document: &Html) -> Self {
+        document
+            .select(&META_ROBOTS)
+            .filter(|element| {
+                element
+                    .attr("name")
+                    .is_some_and(|name| name.trim().eq_ignore_ascii_case("robots"))
+            })
+            .filter_map(|element| element.attr("content"))
+            .map(Self::from_directives)
+            .fold(Self::default(), Self::merge)
+    }
+
+    /// Combines two sets of directives, the more restrictive one wins.
+    pub fn merge(self, other: Self) -> Self {
+        Self {
+            noindex: self.noindex || other.noindex,
+            nofollow: self.nofollow || other.nofollow,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RobotsMetaDirectives;
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    const NOINDEX: RobotsMetaDirectives = RobotsMetaDirectives {
+        noindex: true,
+        nofollow: false,
+    };
+    const NOFOLLOW: RobotsMetaDirectives = RobotsMetaDirectives {
+        noindex: false,
+        nofollow: true,
+    };
+    const NONE: RobotsMetaDirectives = RobotsMetaDirectives {
+        noindex: true,
+        nofollow: true,
+    };
+
+    #[test]
+    fn parses_the_directives() {
+        assert_eq!(NOINDEX, RobotsMetaDirectives::from_directives("noindex"));
+        assert_eq!(NOFOLLOW, RobotsMetaDirectives::from_directives(" NoFollow "));
+        assert_eq!(NONE, RobotsMetaDirectives::from_directives("noindex, nofollow"));
+        assert_eq!(NONE, RobotsMetaDirectives::from_directives("none"));
+        assert_eq!(
+            NOINDEX,
+            RobotsMetaDirectives::from_directives(
+                "noindex, unavailable_after: 25 Jun 2010 15:00:00 PST"
+            )
+        );
+        assert_eq!(
+            RobotsMetaDirectives::default(),
+            RobotsMetaDirectives::from_directives("index, follow, max-snippet: 20")
+        );
+        assert_eq!(
+            NOINDEX,
+            RobotsMetaDirectives::from_directives("noindex, otherbot: nofollow, none")
+        );
+        assert_eq!(
+            NOFOLLOW,
+            RobotsMetaDirectives::from_directives(
+                "unavailable_after: Friday, 25-Jun-10 15:00:00 GMT, nofollow"
+            )
+        );
+    }
+
+    #[test]
+    fn reads_the_header() {
+        let mut headers = HeaderMap::new();
+        headers.append("X-Robots-Tag", HeaderValue::from_static("noindex"));
+        assert_eq!(NOINDEX, RobotsMetaDirectives::from_headers(&headers));
+        headers.append("X-Robots-Tag", HeaderValue::from_static("nofollow"));
+        assert_eq!(NONE, RobotsMetaDirectives::from_headers(&headers));
+
+        let mut headers = HeaderMap::new();
+        headers.append("X-Robots-Tag", HeaderValue::from_static("none"));
+        assert_eq!(NONE, RobotsMetaDirectives::from_headers(&headers));
+    }
+
+    #[test]
+    fn reads_the_meta_tags() {
+        assert_eq!(
+            NOFOLLOW,
+            RobotsMetaDirectives::from_html(
+                r#"<html><head><meta name="robots" content="nofollow"></head></html>"#
+            )
+        );
+        assert_eq!(
+            NONE,
+            RobotsMetaDirectives::from_html(
+                r#"<html><head><meta name="ROBOTS" content="none"></head></html>"#
+            )
+        );
+        assert_eq!(
+            NONE,
+            RobotsMetaDirectives::from_html(
+                r#"<html><head><meta name="robots" content="noindex"><meta name="robots" content="nofollow"></head></html>"#
+            )
+        );
+        assert_eq!(
+            RobotsMetaDirectives::default(),
+            RobotsMetaDirectives::from_html(
+                r#"<html><head><meta name="googlebot" content="none"></head></html>"#
+            )
+        );
+    }
+}
@@ -13,13 +13,21 @@
 // limitations under the License.
 
 pub mod cached;
+pub mod diff;
 pub mod error;
 pub mod information;
 pub mod manager;
 pub mod manager_impl;
+pub mod meta_directives;
+pub mod policy;
+pub mod unavailable_after;
 
 pub use cached::CachedRobots;
+pub use diff::RobotsDiff;
 pub use error::RobotsError;
 pub use information::*;
 pub use manager::RobotsManager;
 pub use manager_impl::*;
+pub use meta_directives::RobotsMetaDirectives;
+pub use policy::RobotsPolicy;
+pub use unavailable_after::UnavailableAfter;
@@ -0,0 +1,74 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::crawl::RobotsFallback;
+use crate::config::CrawlConfig;
+use crate::robots::{CachedRobots, RobotsError};
+use crate::url::AtraUrlOrigin;
+use camino::Utf8PathBuf;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::error::Error;
+use time::OffsetDateTime;
+
+/// The rules of the config for the robots.txt that are not downloaded.
+#[derive(Debug, Clone, Default)]
+pub struct RobotsPolicy {
+    overrides: HashMap<AtraUrlOrigin, Utf8PathBuf>,
+    on_client_error: RobotsFallback,
+    on_server_error: RobotsFallback,
+}
+
+impl RobotsPolicy {
+    pub fn new(config: &CrawlConfig) -> Self {
+        Self {
+            overrides: config.robots_overrides.clone(),
+            on_client_error: config.robots_on_client_error,
+            on_server_error: config.robots_on_server_error,
+        }
+    }
+
+    /// Returns true if the robots.txt of [origin] is read from a local file.
+    pub fn has_override(&self, origin: &AtraUrlOrigin) -> bool {
+        self.overrides.contains_key(origin)
+    }
+
+    /// Reads the local robots.txt of [origin], None if there is no override for it.
+    pub fn read_override<E: Error>(
+        &self,
+        agent: &str,
+        origin: &AtraUrlOrigin,
+    ) -> Result<Option<CachedRobots>, RobotsError<E>> {
+        let Some(path) = self.overrides.get(origin) else {
+            return Ok(None);
+        };
+        let body = std::fs::read(path)
+            .map_err(|err| RobotsError::OverrideNotReadable(path.clone(), err))?;
+        CachedRobots::parse(agent, &body, OffsetDateTime::now_utc())
+            .map(Some)
+            .map_err(RobotsError::InvalidRobotsTxt)
+    }
+
+    /// What is allowed if the robots.txt was answered with [status_code] or had no readable
+    /// body.
+    pub fn fallback_for(&self, status_code: StatusCode) -> RobotsFallback {
+        if status_code.is_client_error() {
+            self.on_client_error
+        } else if status_code.is_server_error() {
+            self.on_server_error
+        } else {
+            RobotsFallback::AllowAll
+        }
+    }
+}
@@ -0,0 +1,368 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::static_selector;
+use reqwest::header::HeaderMap;
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+use time::{Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+const DIRECTIVE: &str = "unavailable_after";
+
+/// The name of the header carrying the robots directives.
+pub const X_ROBOTS_TAG: &str = "x-robots-tag";
+
+static_selector!(META_ROBOTS = "meta[name][content]");
+
+/// The `unavailable_after` robots directive of a page.
+/// The raw value is kept, even if the date could not be parsed.
+#[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq, Hash)]
+pub struct UnavailableAfter {
+    /// The value of the directive as published.
+    pub raw: String,
+    /// The parsed point in time, None if the date format is not supported.
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+impl UnavailableAfter {
+    /// Creates the directive from the [raw] value, a failed parsing is logged.
+    pub fn parse(raw: &str) -> Self {
+        let raw = raw.trim();
+        let expires_at = parse_date(raw);
+        if expires_at.is_none() {
+            log::warn!("Failed to parse the unavailable_after date: {raw:?}");
+        }
+        Self {
+            raw: raw.to_string(),
+            expires_at,
+        }
+    }
+
+    /// Searches the directive in a robots directive list like `noindex, unavailable_after: 25 Jun 2010 15:00:00 PST`.
+    pub fn from_directives(directives: &str) -> Option<Self> {
+        let start = find_ignore_ascii_case(directives, DIRECTIVE)?;
+        let value = directives[start + DIRECTIVE.len()..].trim_start();
+        let value = value.strip_prefix(':').unwrap_or(value).trim();
+        if value.is_empty() {
+            return None;
+        }
+        // Dates may contain commas, therefore the longest prefix that can be parsed wins.
+        if parse_date(value).is_some() {
+            return Some(Self::parse(value));
+        }
+        for (idx, _) in value.rmatch_indices(',') {
+            let candidate = value[..idx].trim();
+            if parse_date(candidate).is_some() {
+                return Some(Self::parse(candidate));
+            }
+        }
+        Some(Self::parse(value.split(',').next().unwrap_or(value)))
+    }
+
+    /// Reads the directive from the `X-Robots-Tag` [headers].
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        headers
+            .get_all(X_ROBOTS_TAG)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(Self::from_directives)
+            .reduce(Self::earliest)
+    }
+
+    /// Reads the directive from the robots meta tags of an [html] page.
+    pub fn from_html(html: &str) -> Option<Self> {
+        // Parsing the document is expensive, most pages do not use the directive at all.
+        find_ignore_ascii_case(html, DIRECTIVE)?;
+        let document = Html::parse_document(html);
+        document
+            .select(&META_ROBOTS)
+            .filter(|element| {
+                element
+                    .attr("name")
+                    .is_some_and(|name| name.trim().eq_ignore_ascii_case("robots"))
+            })
+            .filter_map(|element| element.attr("content"))
+            .filter_map(Self::from_directives)
+            .reduce(Self::earliest)
+    }
+
+    /// Returns the directive that expires first, a parsed date wins over an unparsable one.
+    pub fn earliest(self, other: Self) -> Self {
+        match (self.expires_at, other.expires_at) {
+            (Some(a), Some(b)) if b < a => other,
+            (None, Some(_)) => other,
+            _ => self,
+        }
+    }
+
+    /// Returns true if the entry is expired at [now].
+    pub fn is_expired_at(&self, now: OffsetDateTime) -> bool {
+        self.expires_at.is_some_and(|value| value <= now)
+    }
+
+    /// Encodes the expiry as payload of a link state.
+    pub fn to_payload(&self) -> Option<[u8; 8]> {
+        self.expires_at
+            .map(|value| value.unix_timestamp().to_be_bytes())
+    }
+
+    /// Decodes the expiry from the payload of a link state.
+    pub fn expiry_from_payload(payload: &[u8]) -> Option<OffsetDateTime> {
+        let value: [u8; 8] = payload.get(..8)?.try_into().ok()?;
+        OffsetDateTime::from_unix_timestamp(i64::from_be_bytes(value)).ok()
+    }
+}
+
+pub(crate) fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+/// Parses the date formats commonly used for `unavailable_after`:
+/// ISO 8601 / RFC 3339 (with or without time and offset), RFC 850 (`Sunday, 25-Aug-24 15:00:00 GMT`)
+/// and RFC 822/1123 (`Sun, 25 Aug 2024 15:00:00 GMT`, `25 Jun 2010 15:00:00 PST`).
+/// A missing zone is interpreted as UTC.
+pub fn parse_date(raw: &str) -> Option<OffsetDateTime> {
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    if raw.as_bytes()[0].is_ascii_digit() && raw.as_bytes().get(4) == Some(&b'-') {
+        parse_iso(raw)
+    } else {
+        parse_textual(raw)
+    }
+}
+
+/// `YYYY-MM-DD`, optionally followed by `THH:MM[:SS]` or ` HH:MM[:SS]` and a zone.
+fn parse_iso(raw: &str) -> Option<OffsetDateTime> {
+    let date = raw.get(..10)?;
+    let rest = raw.get(10..)?;
+    let mut parts = date.split('-');
+    let year: i32 = parts.next()?.parse().ok()?;
+    let month: u8 = parts.next()?.parse().ok()?;
+    let day: u8 = parts.next()?.parse().ok()?;
+    let date = Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()?;
+    let rest = rest.trim_start_matches(|c: char| c == 'T' || c == 't' || c == ' ');
+    if rest.is_empty() {
+        return Some(date.midnight().assume_utc());
+    }
+    let zone_start = rest
+        .find(|c: char| c == 'Z' || c == 'z' || c == '+' || c == '-' || c == ' ')
+        .unwrap_or(rest.len());
+    let time = parse_time(&rest[..zone_start])?;
+    let offset = parse_zone(rest[zone_start..].trim())?;
+    Some(PrimitiveDateTime::new(date, time).assume_offset(offset))
+}
+
+/// RFC 850 and RFC 822 like dates with an optional weekday.
+fn parse_textual(raw: &str) -> Option<OffsetDateTime> {
+    let mut tokens = raw
+        .split(|c: char| c.is_ascii_whitespace() || c == ',')
+        .filter(|value| !value.is_empty())
+        .peekable();
+    if tokens
+        .peek()
+        .is_some_and(|value| value.as_bytes()[0].is_ascii_alphabetic() && parse_month(value).is_none())
+    {
+        // the weekday
+        tokens.next();
+    }
+    let first = tokens.next()?;
+    let (day, month, year) = if first.contains('-') {
+        // RFC 850: 25-Aug-24
+        let mut parts = first.split('-');
+        let day = parts.next()?;
+        let month = parts.next()?;
+        let year = parts.next()?;
+        (day, month, year)
+    } else if parse_month(first).is_some() {
+        // asctime like: Aug 25 2024
+        let day = tokens.next()?;
+        let year = tokens.next()?;
+        (day, first, year)
+    } else {
+        let month = tokens.next()?;
+        let year = tokens.next()?;
+        (first, month, year)
+    };
+    let day: u8 = day.parse().ok()?;
+    let month = parse_month(month)?;
+    let year: i32 = match year.parse::<i32>().ok()? {
+        value @ 0..=69 if year.len() == 2 => 2000 + value,
+        value @ 70..=99 if year.len() == 2 => 1900 + value,
+        value => value,
+    };
+    let date = Date::from_calendar_date(year, month, day).ok()?;
+    let time = match tokens.next() {
+        Some(value) => parse_time(value)?,
+        None => return Some(date.midnight().assume_utc()),
+    };
+    let zone: Vec<&str> = tokens.collect();
+    let offset = parse_zone(&zone.join(""))?;
+    Some(PrimitiveDateTime::new(date, time).assume_offset(offset))
+}
+
+fn parse_month(value: &str) -> Option<Month> {
+    const MONTHS: [(&str, Month); 12] = [
+        ("jan", Month::January),
+        ("feb", Month::February),
+        ("mar", Month::March),
+        ("apr", Month::April),
+        ("may", Month::May),
+        ("jun", Month::June),
+        ("jul", Month::July),
+        ("aug", Month::August),
+        ("sep", Month::September),
+        ("oct", Month::October),
+        ("nov", Month::November),
+        ("dec", Month::December),
+    ];
+    if value.len() < 3 || !value.is_char_boundary(3) {
+        return None;
+    }
+    let prefix = &value[..3];
+    MONTHS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(prefix))
+        .map(|(_, month)| *month)
+}
+
+fn parse_time(value: &str) -> Option<Time> {
+    let value = value.split('.').next()?;
+    let mut parts = value.split(':');
+    let hour: u8 = parts.next()?.parse().ok()?;
+    let minute: u8 = parts.next()?.parse().ok()?;
+    let second: u8 = match parts.next() {
+        Some(value) => value.parse().ok()?,
+        None => 0,
+    };
+    Time::from_hms(hour, minute, second).ok()
+}
+
+/// Parses `Z`, `GMT`, `UTC`, the north american zones and numeric offsets like `+0200` or `-07:00`.
+fn parse_zone(value: &str) -> Option<UtcOffset> {
+    let value = value.trim();
+    let hours = match value.to_ascii_uppercase().as_str() {
+        "" | "Z" | "GMT" | "UTC" | "UT" => 0,
+        "EDT" => -4,
+        "EST" | "CDT" => -5,
+        "CST" | "MDT" => -6,
+        "MST" | "PDT" => -7,
+        "PST" => -8,
+        _ => {
+            let value = value
+                .strip_prefix("GMT")
+                .or_else(|| value.strip_prefix("UTC"))
+                .unwrap_or(value);
+            let (sign, digits) = match value.as_bytes().first()? {
+                b'+' => (1i8, &value[1..]),
+                b'-' => (-1i8, &value[1..]),
+                _ => return None,
+            };
+            let digits = digits.replace(':', "");
+            let (h, m) = match digits.len() {
+                1 | 2 => (digits.as_str(), "0"),
+                4 => digits.split_at(2),
+                _ => return None,
+            };
+            let h: i8 = h.parse().ok()?;
+            let m: i8 = m.parse().ok()?;
+            return UtcOffset::from_hms(sign * h, sign * m, 0).ok();
+        }
+    };
+    UtcOffset::from_hms(hours, 0, 0).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_date, UnavailableAfter};
+    use reqwest::header::{HeaderMap, HeaderValue};
+    use time::{Date, Month, OffsetDateTime};
+
+    fn utc(year: i32, month: Month, day: u8, hour: u8) -> OffsetDateTime {
+        Date::from_calendar_date(year, month, day)
+            .unwrap()
+            .with_hms(hour, 0, 0)
+            .unwrap()
+            .assume_utc()
+    }
+
+    #[test]
+    fn parses_the_common_formats() {
+        let expected = utc(2024, Month::August, 25, 15);
+        assert_eq!(Some(expected), parse_date("2024-08-25T15:00:00Z"));
+        assert_eq!(Some(expected), parse_date("2024-08-25T17:00:00+02:00"));
+        assert_eq!(Some(expected), parse_date("2024-08-25T15:00"));
+        assert_eq!(Some(expected), parse_date("Sunday, 25-Aug-24 15:00:00 GMT"));
+        assert_eq!(Some(expected), parse_date("Sun, 25 Aug 2024 15:00:00 GMT"));
+        assert_eq!(Some(expected), parse_date("25 Aug 2024 08:00:00 PDT"));
+        assert_eq!(Some(expected), parse_date("25-Aug-2024 15:00:00 +0000"));
+        assert_eq!(
+            Some(utc(2024, Month::August, 25, 0)),
+            parse_date("2024-08-25")
+        );
+        assert_eq!(
+            Some(utc(2010, Month::June, 25, 23)),
+            parse_date("25 Jun 2010 15:00:00 PST")
+        );
+        assert_eq!(None, parse_date("next tuesday"));
+        assert_eq!(None, parse_date("2024-13-01"));
+    }
+
+    #[test]
+    fn finds_the_directive() {
+        let found =
+            UnavailableAfter::from_directives("noindex, unavailable_after: Sunday, 25-Aug-24 15:00:00 GMT, nofollow")
+                .unwrap();
+        assert_eq!("Sunday, 25-Aug-24 15:00:00 GMT", found.raw);
+        assert_eq!(Some(utc(2024, Month::August, 25, 15)), found.expires_at);
+
+        let broken = UnavailableAfter::from_directives("Unavailable_After: whenever").unwrap();
+        assert_eq!("whenever", broken.raw);
+        assert_eq!(None, broken.expires_at);
+
+        assert!(UnavailableAfter::from_directives("noindex, nofollow").is_none());
+    }
+
+    #[test]
+    fn reads_headers_and_meta_tags() {
+        let mut headers = HeaderMap::new();
+        headers.append("X-Robots-Tag", HeaderValue::from_static("unavailable_after: 2030-01-01"));
+        headers.append("X-Robots-Tag", HeaderValue::from_static("unavailable_after: 2029-01-01"));
+        assert_eq!(
+            Some(utc(2029, Month::January, 1, 0)),
+            UnavailableAfter::from_headers(&headers).unwrap().expires_at
+        );
+
+        let html = r#"<html><head><meta name="ROBOTS" content="unavailable_after: 25 Jun 2010 15:00:00 PST"></head><body></body></html>"#;
+        assert_eq!(
+            Some(utc(2010, Month::June, 25, 23)),
+            UnavailableAfter::from_html(html).unwrap().expires_at
+        );
+        assert!(UnavailableAfter::from_html("<html><body>Hello</body></html>").is_none());
+    }
+
+    #[test]
+    fn roundtrips_the_payload() {
+        let value = UnavailableAfter::parse("2024-08-25T15:00:00Z");
+        let payload = value.to_payload().unwrap();
+        assert_eq!(
+            value.expires_at,
+            UnavailableAfter::expiry_from_payload(&payload)
+        );
+    }
+}
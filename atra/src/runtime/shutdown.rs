@@ -230,6 +230,15 @@ mod shutdown {
         inner: CancellationToken,
     }
 
+    impl ShutdownChild {
+        /// Creates a child that can be shut down without shutting down this one.
+        pub fn create_child(&self) -> ShutdownChild {
+            ShutdownChild {
+                inner: self.inner.child_token(),
+            }
+        }
+    }
+
     impl CancellationTokenProvider for ShutdownChild {
         fn clone_token(&self) -> CancellationToken {
             self.inner.clone()
@@ -0,0 +1,274 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use camino::{Utf8Path, Utf8PathBuf};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use time::format_description::well_known::Rfc3339;
+use time::OffsetDateTime;
+
+/// The name of the audit trail in the root of the session.
+pub const AUDIT_FILE: &str = "audit.jsonl";
+
+/// Who requested a change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditSource {
+    /// A signal like SIGINT sent to the process.
+    Signal { name: String },
+    /// A client of the http endpoint, identified by its address.
+    Http { peer: String },
+    /// The watched blacklist file.
+    BlacklistFile { path: String },
+    /// A command of the command line interface.
+    Cli { command: String },
+}
+
+/// The requested change with its values before and after where meaningful.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditAction {
+    /// Entries were added to the blacklist.
+    BlacklistPatch {
+        entries: Vec<String>,
+        version_before: u64,
+        /// Unchanged if the patch was rejected.
+        version_after: u64,
+    },
+    /// The shutdown of the crawl was requested.
+    Shutdown,
+    /// Failed urls were requeued.
+    Requeue {
+        /// The selection of the failures.
+        filter: String,
+        requeued: usize,
+    },
+}
+
+/// The result of a change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Applied,
+    Rejected { reason: String },
+}
+
+/// A line of the [AUDIT_FILE].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct AuditRecord {
+    #[serde(default = "default_name::<AuditRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<AuditRecord>")]
+    pub version: SchemaVersion,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "String")]
+    pub timestamp: OffsetDateTime,
+    pub source: AuditSource,
+    pub action: AuditAction,
+    pub outcome: AuditOutcome,
+}
+
+impl AuditRecord {
+    pub fn new(source: AuditSource, action: AuditAction, outcome: AuditOutcome) -> Self {
+        Self {
+            schema: Self::NAME.to_string(),
+            version: Self::VERSION,
+            timestamp: OffsetDateTime::now_utc(),
+            source,
+            action,
+            outcome,
+        }
+    }
+}
+
+impl Display for AuditSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditSource::Signal { name } => write!(f, "signal {name}"),
+            AuditSource::Http { peer } => write!(f, "http {peer}"),
+            AuditSource::BlacklistFile { path } => write!(f, "file {path}"),
+            AuditSource::Cli { command } => write!(f, "cli {command}"),
+        }
+    }
+}
+
+impl Display for AuditAction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditAction::BlacklistPatch {
+                entries,
+                version_before,
+                version_after,
+            } => write!(
+                f,
+                "blacklist patch {entries:?} (version {version_before} -> {version_after})"
+            ),
+            AuditAction::Shutdown => f.write_str("shutdown"),
+            AuditAction::Requeue { filter, requeued } => {
+                write!(f, "requeue of {requeued} urls ({filter})")
+            }
+        }
+    }
+}
+
+impl Display for AuditOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditOutcome::Applied => f.write_str("applied"),
+            AuditOutcome::Rejected { reason } => write!(f, "rejected: {reason}"),
+        }
+    }
+}
+
+impl Display for AuditRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let timestamp = self
+            .timestamp
+            .format(&Rfc3339)
+            .unwrap_or_else(|_| self.timestamp.to_string());
+        write!(
+            f,
+            "{timestamp}    {}    {}    {}",
+            self.source, self.action, self.outcome
+        )
+    }
+}
+
+impl VersionedSchema for AuditRecord {
+    const NAME: &'static str = "audit-record";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
+
+/// Appends the changes applied to a running crawl to the [AUDIT_FILE].
+/// Every record is flushed as a single line, existing lines are never touched.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: Utf8PathBuf,
+    file: Arc<Mutex<File>>,
+}
+
+impl AuditLog {
+    /// Opens the audit trail in the session [root] for appending.
+    pub fn open(root: &Utf8Path) -> io::Result<Self> {
+        let path = root.join(AUDIT_FILE);
+        let file = File::options().create(true).append(true).open(&path)?;
+        Ok(Self {
+            path,
+            file: Arc::new(Mutex::new(file)),
+        })
+    }
+
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Appends the [record]. A failure is logged, the change itself is not undone.
+    pub fn record(&self, record: AuditRecord) {
+        let mut line = match serde_json::to_vec(&record) {
+            Ok(line) => line,
+            Err(err) => {
+                log::error!("Failed to serialize the audit record {record:?}: {err}");
+                return;
+            }
+        };
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap();
+        if let Err(err) = file.write_all(&line).and_then(|_| file.flush()) {
+            log::error!("Failed to write to the audit trail {}: {err}", self.path);
+        }
+    }
+
+    /// Appends a record with the current time.
+    pub fn append(&self, source: AuditSource, action: AuditAction, outcome: AuditOutcome) {
+        self.record(AuditRecord::new(source, action, outcome))
+    }
+}
+
+/// Reads the records of the audit trail in the session [root], an empty trail if it is missing.
+pub fn read_audit_trail(root: &Utf8Path) -> io::Result<Vec<AuditRecord>> {
+    let file = match File::open(root.join(AUDIT_FILE)) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+    let mut records = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        records.push(serde_json::from_str(&line)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_audit_trail, AuditAction, AuditLog, AuditOutcome, AuditSource, AUDIT_FILE};
+    use crate::schemas::test::assert_matches_schema;
+    use crate::schemas::{AuditRecord, VersionedSchema};
+    use camino_tempfile::Utf8TempDir;
+
+    #[test]
+    fn records_are_appended_as_lines() {
+        let dir = Utf8TempDir::new().unwrap();
+        assert!(read_audit_trail(dir.path()).unwrap().is_empty());
+        let log = AuditLog::open(dir.path()).unwrap();
+        log.append(
+            AuditSource::Signal {
+                name: "SIGINT".to_string(),
+            },
+            AuditAction::Shutdown,
+            AuditOutcome::Applied,
+        );
+        drop(log);
+        // Reopening keeps the existing lines.
+        let log = AuditLog::open(dir.path()).unwrap();
+        log.append(
+            AuditSource::Http {
+                peer: "127.0.0.1:1234".to_string(),
+            },
+            AuditAction::BlacklistPatch {
+                entries: vec!["(".to_string()],
+                version_before: 1,
+                version_after: 1,
+            },
+            AuditOutcome::Rejected {
+                reason: "invalid".to_string(),
+            },
+        );
+
+        let content = std::fs::read_to_string(dir.path().join(AUDIT_FILE)).unwrap();
+        let lines = content.lines().collect::<Vec<_>>();
+        assert_eq!(2, lines.len());
+        for line in &lines {
+            assert_matches_schema(AuditRecord::NAME, &serde_json::from_str(line).unwrap());
+        }
+        let records = read_audit_trail(dir.path()).unwrap();
+        assert_eq!(AuditAction::Shutdown, records[0].action);
+        assert_eq!(
+            AuditOutcome::Rejected {
+                reason: "invalid".to_string()
+            },
+            records[1].outcome
+        );
+        assert!(records[0].timestamp <= records[1].timestamp);
+    }
+}
@@ -0,0 +1,101 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The Arrow schemas of the Parquet files written by `view --export-parquet`.
+//!
+//! A Parquet file has no `schema` and `version` column, both are stored in the metadata of
+//! the Arrow schema under [PARQUET_SCHEMA_KEY] and [PARQUET_VERSION_KEY].
+//!
+//! Evolution rules: a minor version only appends nullable columns, readers of an older minor
+//! version select their columns by name and ignore the new ones. Renaming, removing,
+//! reordering or retyping a column and making a column non-nullable increment the major version.
+
+use crate::schemas::SchemaVersion;
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The metadata key with the name of the schema.
+pub const PARQUET_SCHEMA_KEY: &str = "atra.schema";
+/// The metadata key with the version of the schema.
+pub const PARQUET_VERSION_KEY: &str = "atra.version";
+
+/// The name of the schema of `pages.parquet`.
+pub const PAGES_PARQUET_NAME: &str = "atra.pages";
+/// The version of the schema of `pages.parquet`.
+pub const PAGES_PARQUET_VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+
+/// The name of the schema of `edges.parquet`.
+pub const EDGES_PARQUET_NAME: &str = "atra.edges";
+/// The version of the schema of `edges.parquet`.
+pub const EDGES_PARQUET_VERSION: SchemaVersion = SchemaVersion::new(1, 1);
+
+fn schema_metadata(name: &str, version: SchemaVersion) -> HashMap<String, String> {
+    HashMap::from([
+        (PARQUET_SCHEMA_KEY.to_string(), name.to_string()),
+        (PARQUET_VERSION_KEY.to_string(), version.to_string()),
+    ])
+}
+
+/// The schema of `pages.parquet`, one row per stored crawl result.
+///
+/// - `url`: the crawled url
+/// - `origin`: the origin of the url, missing for urls without a host
+/// - `status`: the status code of the response
+/// - `format`: the interpreted file format like `HTML`
+/// - `language`: the ISO 639-3 code of the detected language
+/// - `timestamp`: when the page was crawled, in UTC
+/// - `bytes`: the size of the stored body, missing if it can not be read
+/// - `digest`: the labeled xxh128 digest of the stored body, missing if it can not be read
+pub fn pages_schema() -> Arc<Schema> {
+    Arc::new(Schema::new_with_metadata(
+        vec![
+            Field::new("url", DataType::Utf8, false),
+            Field::new("origin", DataType::Utf8, true),
+            Field::new("status", DataType::UInt16, false),
+            Field::new("format", DataType::Utf8, false),
+            Field::new("language", DataType::Utf8, true),
+            Field::new(
+                "timestamp",
+                DataType::Timestamp(TimeUnit::Millisecond, Some("UTC".into())),
+                false,
+            ),
+            Field::new("bytes", DataType::UInt64, true),
+            Field::new("digest", DataType::Utf8, true),
+        ],
+        schema_metadata(PAGES_PARQUET_NAME, PAGES_PARQUET_VERSION),
+    ))
+}
+
+/// The schema of `edges.parquet`, one row per extracted link.
+///
+/// - `src`: the url of the page the link was found on
+/// - `dst`: the url of the link
+/// - `kind`: `on_seed`, `outgoing` or `data`
+/// - `rel`: how the link was found, like `href`, `embedded` or `meta_refresh`
+/// - `nofollow`: the link was marked with `rel="nofollow"`
+/// - `from_error_page`: the link was found on a page without a 2xx status code (since 1.1)
+pub fn edges_schema() -> Arc<Schema> {
+    Arc::new(Schema::new_with_metadata(
+        vec![
+            Field::new("src", DataType::Utf8, false),
+            Field::new("dst", DataType::Utf8, false),
+            Field::new("kind", DataType::Utf8, false),
+            Field::new("rel", DataType::Utf8, true),
+            Field::new("nofollow", DataType::Boolean, false),
+            Field::new("from_error_page", DataType::Boolean, true),
+        ],
+        schema_metadata(EDGES_PARQUET_NAME, EDGES_PARQUET_VERSION),
+    ))
+}
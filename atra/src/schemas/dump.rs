@@ -0,0 +1,58 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crawl::SlimCrawlResult;
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use crate::url::AtraUri;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A line of the `meta.jsonbulk` written by `dump`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct DumpRecord {
+    #[serde(default = "default_name::<DumpRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<DumpRecord>")]
+    pub version: SchemaVersion,
+    /// The url of the page.
+    #[schemars(with = "UriRecord")]
+    pub url: AtraUri,
+    /// The internal crawl result of the page. Only its presence is covered by the schema,
+    /// its fields follow the crawl database.
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub meta: SlimCrawlResult,
+}
+
+impl DumpRecord {
+    pub fn new(url: AtraUri, meta: SlimCrawlResult) -> Self {
+        Self {
+            schema: Self::NAME.to_string(),
+            version: Self::VERSION,
+            url,
+            meta,
+        }
+    }
+}
+
+impl VersionedSchema for DumpRecord {
+    const NAME: &'static str = "dump-record";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
+
+/// The serialized form of an [AtraUri].
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+enum UriRecord {
+    Url(String),
+}
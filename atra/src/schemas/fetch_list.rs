@@ -0,0 +1,87 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use camino::Utf8Path;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use time::OffsetDateTime;
+
+/// The name of the progress file in the root of the session.
+pub const FETCH_LIST_PROGRESS_FILE: &str = "fetchlist_progress.json";
+
+/// How much of a fetch list is finished, written to the [FETCH_LIST_PROGRESS_FILE].
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct FetchListProgress {
+    #[serde(default = "default_name::<FetchListProgress>")]
+    pub schema: String,
+    #[serde(default = "default_version::<FetchListProgress>")]
+    pub version: SchemaVersion,
+    /// The distinct urls of the list.
+    pub total: usize,
+    /// The urls fetched and stored.
+    pub fetched: usize,
+    /// The urls that failed and are not retried.
+    pub failed: usize,
+    /// The urls neither fetched nor failed.
+    pub pending: usize,
+    /// The lines of the list that are not a valid url.
+    pub invalid: usize,
+    /// The fetched and failed urls in percent of the total.
+    pub completion_percent: f64,
+    #[schemars(with = "String")]
+    pub updated_at: OffsetDateTime,
+}
+
+impl FetchListProgress {
+    pub fn new(total: usize, fetched: usize, failed: usize, invalid: usize) -> Self {
+        let finished = fetched + failed;
+        Self {
+            schema: Self::NAME.to_string(),
+            version: Self::VERSION,
+            total,
+            fetched,
+            failed,
+            pending: total - finished,
+            invalid,
+            completion_percent: if total == 0 {
+                100.0
+            } else {
+                finished as f64 * 100.0 / total as f64
+            },
+            updated_at: OffsetDateTime::now_utc(),
+        }
+    }
+
+    /// Returns true if no url is pending.
+    pub fn is_complete(&self) -> bool {
+        self.pending == 0
+    }
+
+    /// Replaces the [FETCH_LIST_PROGRESS_FILE] in [root], a reader never sees a partial file.
+    pub fn write_to(&self, root: &Utf8Path) -> io::Result<()> {
+        let path = root.join(FETCH_LIST_PROGRESS_FILE);
+        let tmp = path.with_extension("json.tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), self)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+impl VersionedSchema for FetchListProgress {
+    const NAME: &'static str = "fetch-list-progress";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
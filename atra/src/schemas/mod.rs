@@ -0,0 +1,552 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The machine readable outputs of Atra. Every output carries the fields `schema` and `version`.
+//!
+//! Compatibility policy: a minor version only adds fields that are optional or have a default,
+//! readers of an older minor version ignore them. Renaming, removing or retyping a field and
+//! adding a required field increment the major version.
+//! The Parquet exports follow the same policy with nullable columns, see [columnar].
+
+mod audit;
+mod columnar;
+mod dump;
+mod fetch_list;
+mod origin_metadata;
+mod page;
+mod stats;
+mod tls;
+mod wave;
+
+pub use audit::*;
+pub use columnar::*;
+pub use dump::*;
+pub use fetch_list::*;
+pub use origin_metadata::*;
+pub use page::*;
+pub use stats::*;
+pub use tls::*;
+pub use wave::*;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{
+    InstanceType, Metadata, RootSchema, Schema, SchemaObject, StringValidation,
+};
+use schemars::{schema_for, JsonSchema};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// The field with the name of the schema.
+pub const SCHEMA_FIELD: &str = "schema";
+/// The field with the version of the schema.
+pub const VERSION_FIELD: &str = "version";
+
+/// A machine readable output of Atra.
+pub trait VersionedSchema: Serialize + DeserializeOwned + JsonSchema {
+    /// The name of the schema, written to the field `schema`.
+    const NAME: &'static str;
+    /// The current version of the schema, written to the field `version`.
+    const VERSION: SchemaVersion;
+}
+
+/// The version of a schema, serialized as `<major>.<minor>`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl SchemaVersion {
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+/// A version that is not of the form `<major>.<minor>`.
+#[derive(Debug, Clone, Error)]
+#[error("The schema version {0:?} is not of the form <major>.<minor>!")]
+pub struct InvalidSchemaVersion(String);
+
+impl FromStr for SchemaVersion {
+    type Err = InvalidSchemaVersion;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split_once('.')
+            .and_then(|(major, minor)| Some(Self::new(major.parse().ok()?, minor.parse().ok()?)))
+            .ok_or_else(|| InvalidSchemaVersion(s.to_string()))
+    }
+}
+
+impl Serialize for SchemaVersion {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SchemaVersion {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonSchema for SchemaVersion {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        "SchemaVersion".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        version_schema(None)
+    }
+}
+
+/// The schema of a version, restricted to the [major] version if set.
+fn version_schema(major: Option<u32>) -> Schema {
+    let pattern = match major {
+        None => r"^\d+\.\d+$".to_string(),
+        Some(major) => format!(r"^{major}\.\d+$"),
+    };
+    SchemaObject {
+        metadata: Some(Box::new(Metadata {
+            description: Some("The version of the schema as <major>.<minor>.".to_string()),
+            ..Default::default()
+        })),
+        instance_type: Some(InstanceType::String.into()),
+        string: Some(Box::new(StringValidation {
+            pattern: Some(pattern),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+/// The name of [T], used for outputs written before the field existed.
+fn default_name<T: VersionedSchema>() -> String {
+    T::NAME.to_string()
+}
+
+/// The version of [T], used for outputs written before the field existed.
+fn default_version<T: VersionedSchema>() -> SchemaVersion {
+    T::VERSION
+}
+
+/// Generates the JSON Schema of [T]. The schema requires the name of [T]
+/// and accepts every version with the same major version.
+fn generate<T: VersionedSchema>() -> RootSchema {
+    let mut root = schema_for!(T);
+    root.schema.extensions.insert(
+        VERSION_FIELD.to_string(),
+        serde_json::Value::String(T::VERSION.to_string()),
+    );
+    let object = root.schema.object();
+    object.properties.insert(
+        SCHEMA_FIELD.to_string(),
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some("The name of the schema.".to_string()),
+                ..Default::default()
+            })),
+            const_value: Some(serde_json::Value::String(T::NAME.to_string())),
+            ..Default::default()
+        }
+        .into(),
+    );
+    object.properties.insert(
+        VERSION_FIELD.to_string(),
+        version_schema(Some(T::VERSION.major)),
+    );
+    object.required.insert(SCHEMA_FIELD.to_string());
+    object.required.insert(VERSION_FIELD.to_string());
+    root
+}
+
+macro_rules! declare_schemas {
+    ($($typ: ty),+ $(,)?) => {
+        /// The names of all schemas.
+        pub const SCHEMA_NAMES: &[&str] = &[$(<$typ as VersionedSchema>::NAME),+];
+
+        /// Generates the JSON Schema called [name].
+        pub fn json_schema(name: &str) -> Option<RootSchema> {
+            $(
+                if name == <$typ as VersionedSchema>::NAME {
+                    return Some(generate::<$typ>());
+                }
+            )+
+            None
+        }
+    };
+}
+
+declare_schemas!(
+    PageRecord,
+    DumpRecord,
+    FetchListProgress,
+    AuditRecord,
+    OriginTlsRecord,
+    OriginMetadataRecord,
+    WaveSummary,
+    CrawlStatisticsRecord
+);
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        json_schema, AuditRecord, CrawlStatisticsRecord, DumpRecord, FetchListProgress,
+        OriginMetadataRecord, OriginStatisticsRecord, OriginTlsRecord, PageRecord, SchemaVersion,
+        SeenFilterStatisticsRecord, VersionedSchema, WaveSummary, WorkerStatisticsRecord,
+        SCHEMA_FIELD, SCHEMA_NAMES, VERSION_FIELD,
+    };
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{SlimCrawlResult, StoredDataHint};
+    use crate::origin_metadata::test::sample_origin_metadata;
+    use crate::tls_info::test::sample_tls_info;
+    use crate::url::{AtraUrlOrigin, UrlWithDepth};
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+    use time::OffsetDateTime;
+
+    /// Asserts that [value] is a valid output of the schema called [name].
+    pub fn assert_matches_schema(name: &str, value: &Value) {
+        let schema = serde_json::to_value(json_schema(name).expect("Unknown schema!")).unwrap();
+        let validator = jsonschema::validator_for(&schema).unwrap();
+        let errors: Vec<String> = validator
+            .iter_errors(value)
+            .map(|err| err.to_string())
+            .collect();
+        assert!(
+            errors.is_empty(),
+            "{value} does not match {name}: {errors:?}"
+        );
+        assert_eq!(name, value[SCHEMA_FIELD]);
+        let version: SchemaVersion = value[VERSION_FIELD].as_str().unwrap().parse().unwrap();
+        assert!(version.major >= 1);
+    }
+
+    fn dump_sample() -> Value {
+        let url = UrlWithDepth::from_url("https://www.example.com/").unwrap();
+        let result = SlimCrawlResult::new(
+            &create_test_data(url.clone(), None),
+            StoredDataHint::InMemory(vec![1, 2, 3]),
+        );
+        serde_json::to_value(DumpRecord::new(url.url, result)).unwrap()
+    }
+
+    fn fetch_list_sample() -> Value {
+        serde_json::to_value(FetchListProgress::new(4, 2, 1, 1)).unwrap()
+    }
+
+    fn tls_sample() -> Value {
+        let info = sample_tls_info(OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap());
+        serde_json::to_value(OriginTlsRecord::new(
+            &AtraUrlOrigin::from("example.com"),
+            &info,
+            &info.capture.chain,
+        ))
+        .unwrap()
+    }
+
+    fn origin_metadata_sample() -> Value {
+        serde_json::to_value(OriginMetadataRecord::new(
+            &AtraUrlOrigin::from("example.com"),
+            &sample_origin_metadata(),
+        ))
+        .unwrap()
+    }
+
+    fn wave_sample() -> Value {
+        serde_json::to_value(WaveSummary {
+            schema: WaveSummary::NAME.to_string(),
+            version: WaveSummary::VERSION,
+            wave: 1,
+            pages: 12,
+            failed: 1,
+            bytes: 4096,
+            origins: 3,
+            new_origins: 2,
+            next_wave: Some(2),
+            next_wave_urls: 5,
+            finished_at: OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap(),
+        })
+        .unwrap()
+    }
+
+    fn statistics_sample() -> Value {
+        serde_json::to_value(CrawlStatisticsRecord {
+            schema: CrawlStatisticsRecord::NAME.to_string(),
+            version: CrawlStatisticsRecord::VERSION,
+            started_at: OffsetDateTime::from_unix_timestamp(1_893_456_000).unwrap(),
+            finished_at: OffsetDateTime::from_unix_timestamp(1_893_459_600).unwrap(),
+            origins: vec![OriginStatisticsRecord {
+                origin: "www.example.com".to_string(),
+                pages: 12,
+                bytes: 4096,
+                fetches: 13,
+                average_fetch_millis: 120.5,
+                errors: [("Timeout".to_string(), 1)].into(),
+                budget_exhausted: true,
+                filtered: 3,
+                baseline_skipped: 0,
+            }],
+            workers: vec![WorkerStatisticsRecord {
+                worker_id: 0,
+                pages: 12,
+                fetch_millis: 1566,
+                extract_millis: 230,
+                store_millis: 40,
+            }],
+            stop_reason: Some("Reached the limit of 12 stored pages.".to_string()),
+            paused_millis: 2500,
+            seen_filter: Some(SeenFilterStatisticsRecord {
+                keys: 1024,
+                layers: 16,
+                estimated_false_positive_rate: 0.0012,
+                skipped_reads: 980,
+                false_positives: 2,
+            }),
+        })
+        .unwrap()
+    }
+
+    fn required(name: &str) -> BTreeSet<String> {
+        let schema = serde_json::to_value(json_schema(name).unwrap()).unwrap();
+        schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|value| value.as_str().unwrap().to_string())
+            .collect()
+    }
+
+    /// Removes every field of [sample] that is not required by the schema of [T],
+    /// like in an output written before the optional fields were added, and parses it.
+    pub fn parse_minimal<T: VersionedSchema>(sample: &Value) -> T {
+        let required = required(T::NAME);
+        let mut minimal = sample.clone();
+        minimal
+            .as_object_mut()
+            .unwrap()
+            .retain(|key, _| required.contains(key));
+        serde_json::from_value(minimal).unwrap()
+    }
+
+    #[test]
+    fn every_schema_can_be_generated() {
+        assert_eq!(
+            SCHEMA_NAMES.len(),
+            SCHEMA_NAMES.iter().collect::<BTreeSet<_>>().len()
+        );
+        for name in SCHEMA_NAMES {
+            let schema = serde_json::to_value(json_schema(name).unwrap()).unwrap();
+            assert_eq!(*name, schema["properties"][SCHEMA_FIELD]["const"]);
+            assert!(jsonschema::validator_for(&schema).is_ok());
+        }
+        assert!(json_schema("unknown").is_none());
+    }
+
+    #[test]
+    fn samples_match_their_schema() {
+        assert_matches_schema(DumpRecord::NAME, &dump_sample());
+        assert_matches_schema(FetchListProgress::NAME, &fetch_list_sample());
+        assert_matches_schema(OriginTlsRecord::NAME, &tls_sample());
+        assert_matches_schema(OriginMetadataRecord::NAME, &origin_metadata_sample());
+        assert_matches_schema(WaveSummary::NAME, &wave_sample());
+        assert_matches_schema(CrawlStatisticsRecord::NAME, &statistics_sample());
+        assert_eq!(
+            "2030-01-01T00:00:00Z",
+            origin_metadata_sample()["well_known_captured_at"]
+        );
+        assert_eq!(2, origin_metadata_sample()["servers"]["counts"]["nginx"]);
+        assert_eq!(
+            "2030-01-01T00:00:00Z",
+            tls_sample()["chain"][0]["not_after"]
+        );
+
+        let mut wrong = fetch_list_sample();
+        wrong[SCHEMA_FIELD] = Value::String(DumpRecord::NAME.to_string());
+        let schema = serde_json::to_value(json_schema(FetchListProgress::NAME).unwrap()).unwrap();
+        assert!(!jsonschema::validator_for(&schema).unwrap().is_valid(&wrong));
+    }
+
+    /// Adding a required field breaks the readers of the major version.
+    #[test]
+    fn required_fields_are_stable_within_the_major_version() {
+        fn fields(values: &[&str]) -> BTreeSet<String> {
+            values.iter().map(|value| value.to_string()).collect()
+        }
+
+        assert_eq!(1, PageRecord::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "url",
+                "status_code",
+                "format",
+                "detection_truncated",
+                "created_at",
+                "warc"
+            ]),
+            required(PageRecord::NAME)
+        );
+        assert_eq!(1, DumpRecord::VERSION.major);
+        assert_eq!(
+            fields(&["schema", "version", "url", "meta"]),
+            required(DumpRecord::NAME)
+        );
+        assert_eq!(1, FetchListProgress::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "total",
+                "fetched",
+                "failed",
+                "pending",
+                "invalid",
+                "completion_percent",
+                "updated_at"
+            ]),
+            required(FetchListProgress::NAME)
+        );
+        assert_eq!(1, AuditRecord::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "timestamp",
+                "source",
+                "action",
+                "outcome"
+            ]),
+            required(AuditRecord::NAME)
+        );
+        assert_eq!(1, OriginTlsRecord::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "origin",
+                "host",
+                "port",
+                "captured_at",
+                "protocol",
+                "chain",
+                "fingerprint"
+            ]),
+            required(OriginTlsRecord::NAME)
+        );
+        assert_eq!(1, OriginMetadataRecord::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "origin",
+                "pages",
+                "generators",
+                "feeds",
+                "languages",
+                "servers"
+            ]),
+            required(OriginMetadataRecord::NAME)
+        );
+        assert_eq!(1, WaveSummary::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "wave",
+                "pages",
+                "failed",
+                "bytes",
+                "origins",
+                "new_origins",
+                "next_wave_urls",
+                "finished_at"
+            ]),
+            required(WaveSummary::NAME)
+        );
+        assert_eq!(1, CrawlStatisticsRecord::VERSION.major);
+        assert_eq!(
+            fields(&[
+                "schema",
+                "version",
+                "started_at",
+                "finished_at",
+                "origins",
+                "workers"
+            ]),
+            required(CrawlStatisticsRecord::NAME)
+        );
+    }
+
+    #[test]
+    fn optional_fields_default_when_older_outputs_are_parsed() {
+        let dump: DumpRecord = parse_minimal(&dump_sample());
+        assert_eq!(
+            serde_json::from_value::<DumpRecord>(dump_sample()).unwrap(),
+            dump
+        );
+
+        let progress: FetchListProgress = parse_minimal(&fetch_list_sample());
+        assert_eq!(2, progress.fetched);
+
+        let tls: OriginTlsRecord = parse_minimal(&tls_sample());
+        assert_eq!(None, tls.cipher);
+        assert!(tls.previous_fingerprints.is_empty());
+
+        let metadata: OriginMetadataRecord = parse_minimal(&origin_metadata_sample());
+        assert_eq!(None, metadata.well_known_captured_at);
+        assert_eq!(None, metadata.security_txt);
+        assert_eq!(2, metadata.servers.counts["nginx"]);
+
+        // Outputs written before the schema fields existed.
+        let mut legacy = fetch_list_sample();
+        let object = legacy.as_object_mut().unwrap();
+        object.remove(SCHEMA_FIELD);
+        object.remove(VERSION_FIELD);
+        let progress: FetchListProgress = serde_json::from_value(legacy).unwrap();
+        assert_eq!(FetchListProgress::NAME, progress.schema);
+        assert_eq!(FetchListProgress::VERSION, progress.version);
+    }
+
+    #[test]
+    fn versions_are_written_as_major_and_minor() {
+        let version: SchemaVersion = "1.12".parse().unwrap();
+        assert_eq!(SchemaVersion::new(1, 12), version);
+        assert_eq!("1.12", version.to_string());
+        assert!("1".parse::<SchemaVersion>().is_err());
+        assert!("1.x".parse::<SchemaVersion>().is_err());
+    }
+}
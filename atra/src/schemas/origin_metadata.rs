@@ -0,0 +1,117 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::origin_metadata::{OriginMetadata, SecurityTxt, ValueDistribution};
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use crate::url::AtraUrlOrigin;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use time::OffsetDateTime;
+
+/// The values of a signal with the number of pages they were seen on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct ValueCountsRecord {
+    pub counts: BTreeMap<String, u64>,
+    /// The occurrences of the values not kept because of the limit of distinct values.
+    #[serde(default)]
+    pub overflow: u64,
+}
+
+impl From<&ValueDistribution> for ValueCountsRecord {
+    fn from(value: &ValueDistribution) -> Self {
+        Self {
+            counts: value.counts.clone(),
+            overflow: value.overflow,
+        }
+    }
+}
+
+/// The fields of the security.txt of an origin.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct SecurityTxtRecord {
+    /// The size of the file in bytes.
+    pub size: u64,
+    pub contacts: Vec<String>,
+    #[serde(default)]
+    pub expires: Option<String>,
+    #[serde(default)]
+    pub policies: Vec<String>,
+}
+
+impl From<&SecurityTxt> for SecurityTxtRecord {
+    fn from(value: &SecurityTxt) -> Self {
+        Self {
+            size: value.size,
+            contacts: value.contacts.clone(),
+            expires: value.expires.clone(),
+            policies: value.policies.clone(),
+        }
+    }
+}
+
+/// A line of the `origin_metadata.jsonl` written by `dump` and the answer of `GET /origin`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OriginMetadataRecord {
+    #[serde(default = "default_name::<OriginMetadataRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<OriginMetadataRecord>")]
+    pub version: SchemaVersion,
+    pub origin: String,
+    /// The number of pages the signals were taken from.
+    pub pages: u64,
+    /// The contents of the `generator` meta tags.
+    pub generators: ValueCountsRecord,
+    /// The feeds announced by the pages.
+    pub feeds: ValueCountsRecord,
+    /// The `lang` attributes of the html pages.
+    pub languages: ValueCountsRecord,
+    /// The values of the `Server` header.
+    pub servers: ValueCountsRecord,
+    /// When the well-known uris were fetched, missing if they were not.
+    #[serde(default, with = "time::serde::rfc3339::option")]
+    #[schemars(with = "Option<String>")]
+    pub well_known_captured_at: Option<OffsetDateTime>,
+    /// The size of the robots.txt in bytes, missing if there is none.
+    #[serde(default)]
+    pub robots_txt_size: Option<u64>,
+    #[serde(default)]
+    pub security_txt: Option<SecurityTxtRecord>,
+}
+
+impl OriginMetadataRecord {
+    pub fn new(origin: &AtraUrlOrigin, metadata: &OriginMetadata) -> Self {
+        let well_known = metadata.well_known.as_ref();
+        Self {
+            schema: Self::NAME.to_string(),
+            version: Self::VERSION,
+            origin: origin.to_string(),
+            pages: metadata.pages,
+            generators: (&metadata.generators).into(),
+            feeds: (&metadata.feeds).into(),
+            languages: (&metadata.languages).into(),
+            servers: (&metadata.servers).into(),
+            well_known_captured_at: well_known.map(|value| value.captured_at),
+            robots_txt_size: well_known.and_then(|value| value.robots_txt_size),
+            security_txt: well_known
+                .and_then(|value| value.security_txt.as_ref())
+                .map(SecurityTxtRecord::from),
+        }
+    }
+}
+
+impl VersionedSchema for OriginMetadataRecord {
+    const NAME: &'static str = "origin-metadata-record";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
@@ -0,0 +1,201 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::crawl::{PublicationConfidence, PublicationSource, SitemapSummary};
+use crate::format::mime::MimeType;
+use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use crate::toolkit::text_normalization::TextProvenance;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// A crawled page, written as a line by `view --format jsonl`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageRecord {
+    #[serde(default = "default_name::<PageRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<PageRecord>")]
+    pub version: SchemaVersion,
+    pub url: String,
+    pub final_redirect_destination: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    pub status_code: u16,
+    pub format: InterpretedProcessibleFileFormat,
+    pub mime: Option<MimeType>,
+    pub detected_format: Option<String>,
+    /// The format was detected on a part of the content.
+    pub detection_truncated: bool,
+    pub language: Option<PageLanguage>,
+    /// The `Content-Language` of the response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_language: Option<String>,
+    /// Only for the language variants of a page, the language asked for with
+    /// `Accept-Language`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language_variant: Option<String>,
+    /// RFC 3339
+    pub created_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unavailable_after: Option<PageUnavailableAfter>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sitemap: Option<SitemapSummary>,
+    /// Only if a publication window was configured for the crawl.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub publication: Option<PagePublication>,
+    /// Only for pages of an origin with a tagged structured seed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    /// Only for pages stored without their body because of its type or size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download_rejection: Option<String>,
+    /// Only for pages found with an url rewritten by the url normalization.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_url: Option<String>,
+    /// Only for pages imported from a file, e.g. a HAR file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub imported_from: Option<String>,
+    /// Only if the timings of the request are known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetch_timings: Option<PageFetchTimings>,
+    /// The ssdeep style fuzzy hash of the body, `<block size>:<hash>:<hash>`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fuzzy_hash: Option<String>,
+    /// The 64 bit simhash of the text as hex.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub simhash: Option<String>,
+    /// Only if the header persistence dropped or redacted headers of the page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_fidelity: Option<PageHeaderFidelity>,
+    /// Only for pages received over tls while the tls info is captured.
+    /// The certificate is listed with the origin in the `tls_origins.jsonl` of `dump`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<PageTls>,
+    pub warc: Vec<PageWarcPointer>,
+    /// Only with `--internals`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub internals: Option<PageInternals>,
+    /// Only with `--headers`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub headers: Option<BTreeMap<String, Vec<String>>>,
+    /// Only with `--extracted-links`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub links: Option<Vec<String>>,
+    /// Only with `--text`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// Only with `--text`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub text_provenance: Option<TextProvenance>,
+}
+
+impl VersionedSchema for PageRecord {
+    const NAME: &'static str = "page-record";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 9);
+}
+
+/// The detected language of a page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageLanguage {
+    /// ISO 639-3
+    pub lang: String,
+    pub confidence: f64,
+    /// Below `crawl.min_language_confidence` or `crawl.min_language_tokens`, the GDBR
+    /// classifier was skipped.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub uncertain: bool,
+}
+
+/// The `unavailable_after` directive of a page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageUnavailableAfter {
+    pub raw: String,
+    /// RFC 3339, missing if [raw] can not be parsed.
+    pub expires_at: Option<String>,
+}
+
+/// The estimated publication date of a page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PagePublication {
+    /// RFC 3339
+    pub published_at: String,
+    pub source: PublicationSource,
+    pub confidence: PublicationConfidence,
+}
+
+/// The timings of the request of a page in milliseconds.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageFetchTimings {
+    /// RFC 3339
+    pub started_at: String,
+    pub total: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blocked: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ssl: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub send: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receive: Option<f64>,
+}
+
+/// The number of header values not persisted as received.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageHeaderFidelity {
+    pub dropped: u32,
+    pub redacted: u32,
+}
+
+/// The certificate of the connection of a page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageTls {
+    /// The SHA-256 fingerprint of the certificate as lowercase hex.
+    pub fingerprint: String,
+    /// The certificate was expired or not yet valid when the page was fetched.
+    pub expired: bool,
+    /// Why the chain is not trusted, missing if it is trusted or was not verified.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_error: Option<String>,
+}
+
+/// A part of the body of a page in a warc file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageWarcPointer {
+    pub path: String,
+    pub file_offset: u64,
+    pub warc_header_octet_count: u32,
+    pub body_octet_count: u64,
+}
+
+/// How the body of a page is stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PageInternals {
+    /// One of `none`, `external`, `warc` or `in_memory`.
+    pub stored_as: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub in_memory_size: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub header_signature_octet_count: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub is_base64: Option<bool>,
+}
@@ -0,0 +1,212 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use camino::Utf8Path;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::BufWriter;
+use time::OffsetDateTime;
+
+/// The number of origins listed by the human readable summary.
+const SUMMARY_ORIGINS: usize = 10;
+
+/// What was crawled from a single origin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct OriginStatisticsRecord {
+    pub origin: String,
+    /// The stored pages, including error pages and pages stored without their body.
+    pub pages: u64,
+    /// The bytes of the fetched bodies, as stored.
+    pub bytes: u64,
+    /// The fetches, including the failed ones.
+    pub fetches: u64,
+    pub average_fetch_millis: f64,
+    /// The failed fetches by their kind and the stored pages with a `4xx` or `5xx` status code.
+    /// Classes without an error are missing.
+    pub errors: BTreeMap<String, u64>,
+    /// Set if a link of the origin was dropped because it exceeds the budget.
+    pub budget_exhausted: bool,
+    /// The found urls of the origin rejected by the url filters.
+    #[serde(default)]
+    pub filtered: u64,
+    /// The urls of the origin not fetched because the baseline of an incremental crawl
+    /// stored them.
+    #[serde(default)]
+    pub baseline_skipped: u64,
+}
+
+/// What a single worker did and where it spent its time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WorkerStatisticsRecord {
+    pub worker_id: usize,
+    /// The processed and stored pages.
+    pub pages: u64,
+    pub fetch_millis: u64,
+    pub extract_millis: u64,
+    pub store_millis: u64,
+}
+
+/// The state of the bloom filter of the seen urls in front of the link states.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct SeenFilterStatisticsRecord {
+    /// The urls in the filter.
+    pub keys: u64,
+    /// The layers of the filter, it grows by a layer when a layer is full.
+    pub layers: usize,
+    /// The estimated probability that an unknown url is reported as seen.
+    pub estimated_false_positive_rate: f64,
+    /// The discovered urls that were not looked up in the link states.
+    pub skipped_reads: u64,
+    /// The discovered urls reported as seen but missing in the link states.
+    pub false_positives: u64,
+}
+
+/// The statistics of a crawl by origin and by worker, written to `stats.json`
+/// in the root of the session when the crawl stops.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CrawlStatisticsRecord {
+    #[serde(default = "default_name::<CrawlStatisticsRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<CrawlStatisticsRecord>")]
+    pub version: SchemaVersion,
+    #[schemars(with = "String")]
+    pub started_at: OffsetDateTime,
+    #[schemars(with = "String")]
+    pub finished_at: OffsetDateTime,
+    pub origins: Vec<OriginStatisticsRecord>,
+    pub workers: Vec<WorkerStatisticsRecord>,
+    /// The global limit that stopped the crawl, missing if no limit was reached.
+    #[serde(default)]
+    pub stop_reason: Option<String>,
+    /// The milliseconds the workers were paused.
+    #[serde(default)]
+    pub paused_millis: u64,
+    /// The seen filter at the end of the crawl, missing if no filter is configured.
+    #[serde(default)]
+    pub seen_filter: Option<SeenFilterStatisticsRecord>,
+}
+
+impl CrawlStatisticsRecord {
+    pub const FILE_NAME: &'static str = "stats.json";
+
+    /// Replaces the statistics in [root], a reader never sees a partial file.
+    pub fn write_to(&self, root: &Utf8Path) -> io::Result<()> {
+        let path = root.join(Self::FILE_NAME);
+        let tmp = path.with_extension("json.tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), self)?;
+        std::fs::rename(tmp, path)
+    }
+}
+
+impl Display for CrawlStatisticsRecord {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let pages = self.origins.iter().map(|value| value.pages).sum::<u64>();
+        let bytes = self.origins.iter().map(|value| value.bytes).sum::<u64>();
+        let errors = self
+            .origins
+            .iter()
+            .flat_map(|value| value.errors.values())
+            .sum::<u64>();
+        let exhausted = self
+            .origins
+            .iter()
+            .filter(|value| value.budget_exhausted)
+            .count();
+        let filtered = self.origins.iter().map(|value| value.filtered).sum::<u64>();
+        let baseline_skipped = self
+            .origins
+            .iter()
+            .map(|value| value.baseline_skipped)
+            .sum::<u64>();
+        writeln!(
+            f,
+            "Duration:         {}",
+            self.finished_at - self.started_at
+        )?;
+        writeln!(f, "Origins:          {}", self.origins.len())?;
+        writeln!(f, "Pages:            {pages}")?;
+        writeln!(f, "Bytes:            {bytes}")?;
+        writeln!(f, "Errors:           {errors}")?;
+        writeln!(f, "Budget exhausted: {exhausted}")?;
+        writeln!(f, "Filtered urls:    {filtered}")?;
+        if baseline_skipped > 0 {
+            writeln!(f, "Baseline skips:   {baseline_skipped}")?;
+        }
+        if let Some(reason) = &self.stop_reason {
+            writeln!(f, "Stopped:          {reason}")?;
+        }
+        if self.paused_millis > 0 {
+            writeln!(
+                f,
+                "Paused:           {}",
+                time::Duration::milliseconds(self.paused_millis as i64)
+            )?;
+        }
+
+        if let Some(seen) = &self.seen_filter {
+            writeln!(
+                f,
+                "Seen filter:      {} urls, {:.4} false positive rate, {} reads skipped",
+                seen.keys, seen.estimated_false_positive_rate, seen.skipped_reads
+            )?;
+        }
+
+        let mut origins = self.origins.iter().collect::<Vec<_>>();
+        origins.sort_by(|a, b| b.pages.cmp(&a.pages));
+        if origins.len() > SUMMARY_ORIGINS {
+            writeln!(f, "Top {SUMMARY_ORIGINS} origins:")?;
+        } else {
+            writeln!(f, "Origins:")?;
+        }
+        for origin in origins.into_iter().take(SUMMARY_ORIGINS) {
+            writeln!(
+                f,
+                "    {}: {} pages, {} bytes, {:.1} ms per fetch, {} errors{}",
+                origin.origin,
+                origin.pages,
+                origin.bytes,
+                origin.average_fetch_millis,
+                origin.errors.values().sum::<u64>(),
+                if origin.budget_exhausted {
+                    ", budget exhausted"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        writeln!(f, "Workers:")?;
+        for worker in &self.workers {
+            writeln!(
+                f,
+                "    {}: {} pages, fetch {} ms, extract {} ms, store {} ms",
+                worker.worker_id,
+                worker.pages,
+                worker.fetch_millis,
+                worker.extract_millis,
+                worker.store_millis
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl VersionedSchema for CrawlStatisticsRecord {
+    const NAME: &'static str = "crawl-statistics";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 4);
+}
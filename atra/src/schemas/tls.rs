@@ -0,0 +1,117 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use crate::tls_info::{CertificateInfo, OriginTlsInfo};
+use crate::url::AtraUrlOrigin;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+/// A certificate of the chain presented by a server.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CertificateRecord {
+    pub subject: String,
+    pub issuer: String,
+    /// The subject alternative names like `DNS:example.com`.
+    #[serde(default)]
+    pub subject_alt_names: Vec<String>,
+    /// The serial number as uppercase hex.
+    pub serial_number: String,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "String")]
+    pub not_before: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "String")]
+    pub not_after: OffsetDateTime,
+    /// The SHA-256 fingerprint of the DER encoding as lowercase hex.
+    pub fingerprint: String,
+}
+
+impl From<&CertificateInfo> for CertificateRecord {
+    fn from(value: &CertificateInfo) -> Self {
+        Self {
+            subject: value.subject.clone(),
+            issuer: value.issuer.clone(),
+            subject_alt_names: value.subject_alt_names.clone(),
+            serial_number: value.serial_number.clone(),
+            not_before: value.not_before,
+            not_after: value.not_after,
+            fingerprint: value.fingerprint.clone(),
+        }
+    }
+}
+
+/// A line of the `tls_origins.jsonl` written by `dump`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct OriginTlsRecord {
+    #[serde(default = "default_name::<OriginTlsRecord>")]
+    pub schema: String,
+    #[serde(default = "default_version::<OriginTlsRecord>")]
+    pub version: SchemaVersion,
+    pub origin: String,
+    /// The host and port the capture connected to.
+    pub host: String,
+    pub port: u16,
+    #[serde(with = "time::serde::rfc3339")]
+    #[schemars(with = "String")]
+    pub captured_at: OffsetDateTime,
+    /// The negotiated protocol like `TLSv1.3`.
+    pub protocol: String,
+    #[serde(default)]
+    pub cipher: Option<String>,
+    /// Why the chain is not trusted, missing if it is.
+    #[serde(default)]
+    pub verification_error: Option<String>,
+    /// The chain as presented by the server, starting with its own certificate.
+    pub chain: Vec<CertificateRecord>,
+    /// The fingerprint of the certificate seen last.
+    pub fingerprint: String,
+    /// The fingerprints of the certificates replaced during the crawl, oldest first.
+    #[serde(default)]
+    pub previous_fingerprints: Vec<String>,
+    /// The certificates of the responses from the origin, each listed once.
+    #[serde(default)]
+    pub certificates: Vec<CertificateRecord>,
+}
+
+impl OriginTlsRecord {
+    pub fn new(
+        origin: &AtraUrlOrigin,
+        info: &OriginTlsInfo,
+        certificates: &[CertificateInfo],
+    ) -> Self {
+        let capture = &info.capture;
+        Self {
+            schema: Self::NAME.to_string(),
+            version: Self::VERSION,
+            origin: origin.to_string(),
+            host: capture.host.clone(),
+            port: capture.port,
+            captured_at: capture.captured_at,
+            protocol: capture.protocol.clone(),
+            cipher: capture.cipher.clone(),
+            verification_error: capture.verification_error.clone(),
+            chain: capture.chain.iter().map(CertificateRecord::from).collect(),
+            fingerprint: info.fingerprint.clone(),
+            previous_fingerprints: info.previous_fingerprints.clone(),
+            certificates: certificates.iter().map(CertificateRecord::from).collect(),
+        }
+    }
+}
+
+impl VersionedSchema for OriginTlsRecord {
+    const NAME: &'static str = "origin-tls-record";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 1);
+}
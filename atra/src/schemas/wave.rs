@@ -0,0 +1,99 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::schemas::{default_name, default_version, SchemaVersion, VersionedSchema};
+use camino::{Utf8Path, Utf8PathBuf};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter};
+use time::OffsetDateTime;
+
+/// What a finished wave of a crawl in wave mode crawled and what it left for the next wave.
+/// Written to `wave_<wave>.json` in the root of the session.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+pub struct WaveSummary {
+    #[serde(default = "default_name::<WaveSummary>")]
+    pub schema: String,
+    #[serde(default = "default_version::<WaveSummary>")]
+    pub version: SchemaVersion,
+    /// The wave, the distance of its urls to the seed.
+    pub wave: u64,
+    /// The pages stored in the wave, including the pages stored without their body.
+    pub pages: u64,
+    /// The fetches that failed in the wave.
+    pub failed: u64,
+    /// The bytes written to the warc and big files in the wave.
+    pub bytes: u64,
+    /// The origins crawled in the wave.
+    pub origins: usize,
+    /// The origins of the next wave that were not crawled in any wave so far.
+    pub new_origins: usize,
+    /// The wave started after this one, None if no url is left for a further wave.
+    pub next_wave: Option<u64>,
+    /// The urls queued for the next wave. Only an estimate of its size, the urls of an
+    /// origin are crawled in one go and some urls may already be stored.
+    pub next_wave_urls: usize,
+    #[schemars(with = "String")]
+    pub finished_at: OffsetDateTime,
+}
+
+impl WaveSummary {
+    /// The name of the summary file of the [wave].
+    pub fn file_name(wave: u64) -> String {
+        format!("wave_{wave}.json")
+    }
+
+    /// The file in [root] confirming the start of the [wave].
+    pub fn confirmation_file(root: &Utf8Path, wave: u64) -> Utf8PathBuf {
+        root.join(format!("wave_{wave}.continue"))
+    }
+
+    /// Replaces the summary file of the wave in [root], a reader never sees a partial file.
+    pub fn write_to(&self, root: &Utf8Path) -> io::Result<()> {
+        let path = root.join(Self::file_name(self.wave));
+        let tmp = path.with_extension("json.tmp");
+        serde_json::to_writer_pretty(BufWriter::new(File::create(&tmp)?), self)?;
+        std::fs::rename(tmp, path)
+    }
+
+    /// Reads the summary of the latest wave written to [root], None if no wave finished yet.
+    pub fn read_latest(root: &Utf8Path) -> io::Result<Option<Self>> {
+        let mut latest = None;
+        for entry in root.read_dir_utf8()? {
+            let entry = entry?;
+            let wave = entry
+                .file_name()
+                .strip_prefix("wave_")
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|wave| wave.parse::<u64>().ok());
+            if let Some(wave) = wave {
+                if !latest.as_ref().is_some_and(|(latest, _)| *latest >= wave) {
+                    latest = Some((wave, entry.into_path()));
+                }
+            }
+        }
+        let Some((_, path)) = latest else {
+            return Ok(None);
+        };
+        let file = BufReader::new(File::open(path)?);
+        Ok(Some(serde_json::from_reader(file)?))
+    }
+}
+
+impl VersionedSchema for WaveSummary {
+    const NAME: &'static str = "wave-summary";
+    const VERSION: SchemaVersion = SchemaVersion::new(1, 0);
+}
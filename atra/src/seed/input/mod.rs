@@ -14,3 +14,4 @@
 
 pub(super) mod lines;
 pub(super) mod seed_data;
+pub(super) mod structured;
@@ -13,7 +13,8 @@
 // limitations under the License.
 
 use crate::queue::{SupportsSeeding, UrlQueue};
-use crate::seed::read_seeds;
+use crate::seed::{read_seeds, StructuredSeeds};
+use crate::toolkit::digest::LabeledXxh128Digester;
 use crate::url::UrlWithDepth;
 use camino::Utf8PathBuf;
 use nom::branch::alt;
@@ -28,12 +29,14 @@ use nom::IResult;
 use nom::Parser;
 use serde::{Deserialize, Serialize};
 use std::convert::Infallible;
+use std::io;
 use std::str::FromStr;
 
 /// Defines what kind of seed is used
 /// CLI Syntax:
 /// ```text
 /// - command... file: path to a file>
+/// - command... structured: path to a file with one JSON object per line>
 /// - command... single: url
 /// - command... single: "url"
 /// - command... multi: "url","url"....
@@ -47,11 +50,21 @@ pub enum SeedDefinition {
     Single(String),
     Multi(Vec<String>),
     File(Utf8PathBuf),
+    /// A file with a JSON object per line with the fields `url`, `depth`, `recrawl_interval`
+    /// and `tag`, see [StructuredSeeds].
+    Structured(Utf8PathBuf),
 }
 
 impl SeedDefinition {
+    /// Fills the seeds into the [queue]. The recrawl intervals and tags of
+    /// [SeedDefinition::Structured] are applied with [StructuredSeeds::apply_to].
     pub async fn fill_queue(&self, queue: &impl UrlQueue<UrlWithDepth>) {
         match self {
+            SeedDefinition::Structured(path) => StructuredSeeds::read(path)
+                .expect("Was not able to read file")
+                .fill_queue(queue)
+                .await
+                .expect("Can not write any kind of seeds to the queue!"),
             SeedDefinition::File(path) => queue
                 .enqueue_seeds(read_seeds(path).expect("Was not able to read file"))
                 .await
@@ -70,6 +83,24 @@ impl SeedDefinition {
             }
         }
     }
+
+    /// The labeled digest of the seeds, the files are digested by their content.
+    pub fn digest(&self) -> io::Result<String> {
+        let mut digester = LabeledXxh128Digester::default();
+        match self {
+            SeedDefinition::Single(entry) => digester.update(entry.as_bytes()),
+            SeedDefinition::Multi(entries) => {
+                for entry in entries {
+                    digester.update(entry.as_bytes());
+                    digester.update(b"\n");
+                }
+            }
+            SeedDefinition::File(path) | SeedDefinition::Structured(path) => {
+                io::copy(&mut std::fs::File::open(path)?, &mut digester)?;
+            }
+        }
+        Ok(String::from_utf8(digester.finish()).expect("The digest is ASCII."))
+    }
 }
 
 fn parse(s: &str) -> IResult<&str, SeedDefinition> {
@@ -122,6 +153,13 @@ fn parse(s: &str) -> IResult<&str, SeedDefinition> {
                 |value| SeedDefinition::File(Utf8PathBuf::from(value)),
             ),
         ),
+        preceded(
+            ws(tag("structured:")),
+            map(
+                alt((delimited_str, map(rest, |s: &str| s.to_string()))),
+                |value| SeedDefinition::Structured(Utf8PathBuf::from(value)),
+            ),
+        ),
         preceded(
             ws(tag("single:")),
             map(
@@ -171,6 +209,12 @@ mod test {
             ))),
             "file:./testdata/blacklist.txt".parse()
         );
+        assert_eq!(
+            Ok(SeedDefinition::Structured(Utf8PathBuf::from(
+                "./testdata/structured_seeds.jsonl"
+            ))),
+            "structured: ./testdata/structured_seeds.jsonl".parse()
+        );
 
         assert_eq!(
             Ok(SeedDefinition::Multi(vec![
@@ -0,0 +1,340 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::CrawlConfig;
+use crate::queue::{QueueError, UrlQueue, UrlQueueElement};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, Depth, ParseError, UrlWithDepth};
+use serde::Deserialize;
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use thiserror::Error;
+use time::Duration;
+
+/// A row of a structured seed file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedRow {
+    url: String,
+    #[serde(default)]
+    depth: Option<u64>,
+    #[serde(default)]
+    recrawl_interval: Option<Duration>,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+/// A row of a structured seed file that can not be used.
+#[derive(Debug, Error)]
+pub enum StructuredSeedError {
+    #[error("Line {line}: {source}")]
+    Malformed {
+        line: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+    #[error("Line {line}: The url {url} is invalid: {source}")]
+    InvalidUrl {
+        line: usize,
+        url: String,
+        #[source]
+        source: ParseError,
+    },
+    #[error("Line {line}: The url {url} has no origin!")]
+    NoOrigin { line: usize, url: String },
+    #[error("Line {line}: The tag is empty!")]
+    EmptyTag { line: usize },
+}
+
+impl StructuredSeedError {
+    /// The line of the row, starting at 1.
+    pub fn line(&self) -> usize {
+        match self {
+            StructuredSeedError::Malformed { line, .. }
+            | StructuredSeedError::InvalidUrl { line, .. }
+            | StructuredSeedError::NoOrigin { line, .. }
+            | StructuredSeedError::EmptyTag { line } => *line,
+        }
+    }
+}
+
+/// A seed with its own depth, recrawl interval and tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StructuredSeed {
+    /// The seed, starting at the depth of the row.
+    pub url: UrlWithDepth,
+    pub origin: AtraUrlOrigin,
+    /// Overrides the recrawl interval of the budget of the origin.
+    pub recrawl_interval: Option<Duration>,
+    /// Recorded in the meta of every page crawled from the origin.
+    pub tag: Option<String>,
+}
+
+/// The seeds of a structured seed file, one JSON object per line with the fields
+/// `url`, `depth`, `recrawl_interval` and `tag`. Only `url` is required.
+/// Empty lines and lines starting with # are ignored, malformed rows are skipped.
+#[derive(Debug)]
+pub struct StructuredSeeds {
+    seeds: Vec<StructuredSeed>,
+    errors: Vec<StructuredSeedError>,
+}
+
+impl StructuredSeeds {
+    /// Reads the seed file at [path], the skipped rows are logged.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::from_reader(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the seeds from [reader], the skipped rows are logged.
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut seeds = Vec::new();
+        let mut errors = Vec::new();
+        for (idx, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            match Self::parse_row(idx + 1, line) {
+                Ok(seed) => seeds.push(seed),
+                Err(err) => {
+                    log::warn!("Skip a row of the seed file: {err}");
+                    errors.push(err);
+                }
+            }
+        }
+        Ok(Self { seeds, errors })
+    }
+
+    fn parse_row(line: usize, value: &str) -> Result<StructuredSeed, StructuredSeedError> {
+        let row: SeedRow = serde_json::from_str(value)
+            .map_err(|source| StructuredSeedError::Malformed { line, source })?;
+        let mut url = UrlWithDepth::from_url(row.url.as_str()).map_err(|source| {
+            StructuredSeedError::InvalidUrl {
+                line,
+                url: row.url.clone(),
+                source,
+            }
+        })?;
+        if let Some(depth) = row.depth {
+            // Like a page found by following [depth] links on the website.
+            url.depth = Depth::new(depth, 0, depth);
+        }
+        let origin = url
+            .atra_origin()
+            .ok_or_else(|| StructuredSeedError::NoOrigin { line, url: row.url })?;
+        let tag = match row.tag {
+            Some(tag) if tag.trim().is_empty() => {
+                return Err(StructuredSeedError::EmptyTag { line });
+            }
+            tag => tag,
+        };
+        Ok(StructuredSeed {
+            url,
+            origin,
+            recrawl_interval: row.recrawl_interval,
+            tag,
+        })
+    }
+
+    /// The valid seeds in the order of the file.
+    pub fn seeds(&self) -> &[StructuredSeed] {
+        &self.seeds
+    }
+
+    /// The skipped rows.
+    pub fn errors(&self) -> &[StructuredSeedError] {
+        &self.errors
+    }
+
+    /// Writes the recrawl intervals to the budgets and the tags to the seed tags of [config].
+    /// If the seeds of an origin disagree, the first one wins.
+    pub fn apply_to(&self, config: &mut CrawlConfig) {
+        let mut intervals = HashMap::new();
+        for seed in &self.seeds {
+            if let Some(interval) = seed.recrawl_interval {
+                match intervals.entry(seed.origin.clone()) {
+                    Entry::Occupied(found) => {
+                        if found.get() != &interval {
+                            log::warn!(
+                                "The seed {} has another recrawl interval than the first seed of {}, it is ignored.",
+                                seed.url,
+                                seed.origin
+                            );
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(interval);
+                        if config
+                            .origin_profile(&seed.origin)
+                            .is_some_and(|profile| profile.budget.is_some())
+                        {
+                            log::warn!(
+                                "The budget of the profile of {} beats the recrawl interval of the seed {}.",
+                                seed.origin,
+                                seed.url
+                            );
+                        }
+                        let mut budget = config.budget_for(&seed.origin).clone();
+                        budget.set_recrawl_interval(Some(interval));
+                        config
+                            .budget
+                            .per_host
+                            .get_or_insert_with(HashMap::new)
+                            .insert(seed.origin.clone(), budget);
+                    }
+                }
+            }
+            if let Some(tag) = &seed.tag {
+                match config.seed_tags.entry(seed.origin.clone()) {
+                    Entry::Occupied(found) => {
+                        if found.get() != tag {
+                            log::warn!(
+                                "The seed {} has another tag than the first seed of {}, it is ignored.",
+                                seed.url,
+                                seed.origin
+                            );
+                        }
+                    }
+                    Entry::Vacant(entry) => {
+                        entry.insert(tag.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Enqueues the seeds at their depth.
+    pub async fn fill_queue(&self, queue: &impl UrlQueue<UrlWithDepth>) -> Result<(), QueueError> {
+        queue
+            .enqueue_all(
+                self.seeds
+                    .iter()
+                    .map(|seed| UrlQueueElement::new(true, 0, false, seed.url.clone()))
+                    .collect::<Vec<_>>(),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{StructuredSeedError, StructuredSeeds};
+    use crate::config::{BudgetSetting, CrawlConfig};
+    use crate::url::{AtraOriginProvider, AtraUrlOrigin, Depth, UrlWithDepth};
+    use time::Duration;
+
+    fn fixture() -> StructuredSeeds {
+        StructuredSeeds::read("./testdata/structured_seeds.jsonl").unwrap()
+    }
+
+    fn origin(url: &str) -> AtraUrlOrigin {
+        UrlWithDepth::from_url(url).unwrap().atra_origin().unwrap()
+    }
+
+    #[test]
+    fn valid_rows_are_read_and_malformed_rows_are_reported() {
+        let seeds = fixture();
+        assert_eq!(
+            vec![
+                "https://www.example.com/",
+                "https://news.example.org/2024/",
+                "https://www.example.com/about",
+                "https://blog.example.net/",
+            ],
+            seeds
+                .seeds()
+                .iter()
+                .map(|seed| seed.url.try_as_str().into_owned())
+                .collect::<Vec<_>>()
+        );
+        let news = &seeds.seeds()[1];
+        assert_eq!(Depth::new(2, 0, 2), news.url.depth);
+        assert_eq!(Some(Duration::days(1)), news.recrawl_interval);
+        assert_eq!(Some("news"), news.tag.as_deref());
+        assert_eq!(Depth::ZERO, seeds.seeds()[0].url.depth);
+
+        assert_eq!(
+            vec![5, 6, 7, 8, 10],
+            seeds
+                .errors()
+                .iter()
+                .map(StructuredSeedError::line)
+                .collect::<Vec<_>>()
+        );
+        assert!(matches!(
+            seeds.errors()[0],
+            StructuredSeedError::Malformed { .. }
+        ));
+        assert!(matches!(
+            seeds.errors()[1],
+            StructuredSeedError::Malformed { .. }
+        ));
+        assert!(matches!(
+            seeds.errors()[2],
+            StructuredSeedError::InvalidUrl { .. }
+        ));
+        assert!(matches!(
+            seeds.errors()[3],
+            StructuredSeedError::EmptyTag { .. }
+        ));
+        assert!(matches!(
+            seeds.errors()[4],
+            StructuredSeedError::Malformed { .. }
+        ));
+    }
+
+    #[test]
+    fn overrides_are_applied_to_the_config() {
+        let mut config = CrawlConfig::default();
+        config.budget.default = BudgetSetting::Absolute {
+            depth: 3,
+            recrawl_interval: None,
+            request_timeout: Some(Duration::seconds(10)),
+        };
+        fixture().apply_to(&mut config);
+
+        let news = origin("https://news.example.org/");
+        assert_eq!(
+            &BudgetSetting::Absolute {
+                depth: 3,
+                recrawl_interval: Some(Duration::days(1)),
+                request_timeout: Some(Duration::seconds(10)),
+            },
+            config.budget_for(&news)
+        );
+        assert_eq!(
+            Some("news"),
+            config.seed_tags.get(&news).map(String::as_str)
+        );
+
+        // The first seed of an origin wins.
+        let example = origin("https://www.example.com/");
+        assert_eq!(
+            Some(&Duration::hours(6)),
+            config.budget_for(&example).get_recrawl_interval()
+        );
+        assert_eq!(
+            Some("shop"),
+            config.seed_tags.get(&example).map(String::as_str)
+        );
+
+        let blog = origin("https://blog.example.net/");
+        assert_eq!(&config.budget.default, config.budget_for(&blog));
+        assert!(!config.seed_tags.contains_key(&blog));
+    }
+}
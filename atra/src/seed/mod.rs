@@ -23,6 +23,7 @@ use cfg_if::cfg_if;
 pub use guarded::GuardedSeed;
 pub use input::lines::read_seeds;
 pub use input::seed_data::SeedDefinition;
+pub use input::structured::{StructuredSeed, StructuredSeedError, StructuredSeeds};
 pub use unguarded::UnguardedSeed;
 
 cfg_if! {
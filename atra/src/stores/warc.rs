@@ -15,9 +15,10 @@
 use crate::io::errors::{ErrorWithPath, ToErrorWithPath};
 use crate::io::file_owner::FileOwner;
 use crate::io::fs::WorkerFileSystemAccess;
-use crate::warc_ext::SpecialWarcWriter;
+use crate::warc_ext::{write_warc_info, SpecialWarcWriter, WarcInfo};
 use camino::{Utf8Path, Utf8PathBuf};
 use std::fs::File;
+use std::io;
 use std::io::{BufWriter, Read, Write};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -27,6 +28,11 @@ use warc::writer::{WarcWriter, WarcWriterError};
 pub trait WarcFilePathProvider {
     /// Creates a fresh warc file
     fn create_new_warc_file_path(&self) -> Result<Utf8PathBuf, ErrorWithPath>;
+
+    /// The description of the crawl written at the start of every warc file, if any.
+    fn warc_info(&self) -> Option<Arc<WarcInfo>> {
+        None
+    }
 }
 
 pub trait RawWriter: Write {
@@ -72,20 +78,20 @@ impl<W: Write + RawWriter, P: WarcFilePathProvider> TryFrom<Arc<P>>
                 value,
                 WarcWriter::new(BufWriter::new(writer)),
                 path,
-            ))),
+            )?)),
         })
     }
 }
 
 impl<W: Write + RawWriter, P: WarcFilePathProvider> ThreadsafeMultiFileWarcWriter<W, P> {
-    pub fn new(writer: W, provider: P, path: Utf8PathBuf) -> Self {
-        Self {
+    pub fn new(writer: W, provider: P, path: Utf8PathBuf) -> Result<Self, ErrorWithPath> {
+        Ok(Self {
             writer: Arc::new(RwLock::new(RawMultifileWarcWriter::new(
                 Arc::new(provider),
                 WarcWriter::new(BufWriter::new(writer)),
                 path,
-            ))),
-        }
+            )?)),
+        })
     }
 
     pub async fn current_file(&self) -> Utf8PathBuf {
@@ -153,11 +159,46 @@ pub struct RawMultifileWarcWriter<W: Write + RawWriter, P: WarcFilePathProvider>
     fp: Arc<P>,
     writer: WarcWriter<BufWriter<W>>,
     path: Utf8PathBuf,
+    info: Option<Arc<WarcInfo>>,
+    /// The id of the warcinfo record at the start of the current file.
+    info_id: Option<String>,
 }
 
 impl<W: Write + RawWriter, P: WarcFilePathProvider> RawMultifileWarcWriter<W, P> {
-    pub fn new(fp: Arc<P>, writer: WarcWriter<BufWriter<W>>, path: Utf8PathBuf) -> Self {
-        Self { fp, writer, path }
+    /// Creates the writer for the fresh file at [path], it starts with the warcinfo record
+    /// of [fp], if there is one.
+    pub fn new(
+        fp: Arc<P>,
+        writer: WarcWriter<BufWriter<W>>,
+        path: Utf8PathBuf,
+    ) -> Result<Self, ErrorWithPath> {
+        let info = fp.warc_info();
+        let mut new = Self {
+            fp,
+            writer,
+            path,
+            info,
+            info_id: None,
+        };
+        new.write_warc_info()?;
+        Ok(new)
+    }
+
+    /// Writes the warcinfo record at the start of the current file.
+    fn write_warc_info(&mut self) -> Result<(), ErrorWithPath> {
+        self.info_id = None;
+        let Some(info) = self.info.clone() else {
+            return Ok(());
+        };
+        let file_name = self
+            .path
+            .file_name()
+            .unwrap_or(self.path.as_str())
+            .to_string();
+        let id = write_warc_info(self, &info, &file_name)
+            .map_err(|err| ErrorWithPath::new(self.path.clone(), io::Error::other(err)))?;
+        self.info_id = Some(id);
+        Ok(())
     }
 
     fn flush(&mut self) -> Result<(), ErrorWithPath> {
@@ -194,6 +235,11 @@ impl<W: Write + RawWriter, P: WarcFilePathProvider> SpecialWarcWriter
         self.writer.bytes_written()
     }
 
+    #[inline]
+    fn warc_info_id(&self) -> Option<String> {
+        self.info_id.clone()
+    }
+
     #[inline]
     fn write_header(&mut self, header: WarcHeader) -> Result<usize, WarcWriterError> {
         self.writer.write_header(&header)
@@ -221,6 +267,7 @@ impl<W: Write + RawWriter, P: WarcFilePathProvider> SpecialWarcWriter
             path,
         );
         old_writer.flush().to_error_with_path(&path)?;
+        self.write_warc_info()?;
         Ok(path)
     }
 }
@@ -15,7 +15,9 @@
 use crate::contexts::traits::{SupportsUrlGuarding, SupportsUrlQueue, SupportsWorkerId};
 use crate::queue::UrlQueue;
 use crate::sync::CancellationTokenProvider;
+use crate::toolkit::unwind::{panic_message, CatchUnwind};
 use crate::url::guard::UrlGuardian;
+use std::future::Future;
 use std::num::NonZeroUsize;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use tokio::select;
@@ -30,8 +32,12 @@ pub enum ContinueOrStop<T, C = T> {
 
 /// A barrier to help with the synchronisation of the workers.
 /// Allows to recover if the workload changes.
+/// A worker run by [WorkerBarrier::run_worker] leaves the barrier if it panics,
+/// hence the remaining workers never wait for it.
+#[derive(Debug)]
 pub struct WorkerBarrier {
     number_of_workers: NonZeroUsize,
+    left_workers: AtomicUsize,
     cancel_requester_count_plus_one: AtomicUsize,
     cancellation_token: CancellationToken,
 }
@@ -40,6 +46,7 @@ impl WorkerBarrier {
     pub fn new(number_of_workers: NonZeroUsize, cancellation_token: CancellationToken) -> Self {
         Self {
             number_of_workers,
+            left_workers: AtomicUsize::new(0),
             // Start one greater than 0, this way we can make sure that increment counter returns true if all decide to quit.
             cancel_requester_count_plus_one: AtomicUsize::new(1),
             cancellation_token,
@@ -63,6 +70,55 @@ impl WorkerBarrier {
         self.cancellation_token.cancel()
     }
 
+    /// The number of workers synchronized by this barrier
+    pub fn number_of_workers(&self) -> usize {
+        self.number_of_workers.get()
+    }
+
+    /// The number of workers that did not leave the barrier
+    pub fn active_workers(&self) -> usize {
+        self.number_of_workers()
+            .saturating_sub(self.left_workers.load(Ordering::SeqCst))
+    }
+
+    /// The number of workers currently waiting for the cancellation
+    pub fn waiting_workers(&self) -> usize {
+        self.cancel_requester_count_plus_one
+            .load(Ordering::SeqCst)
+            .saturating_sub(1)
+    }
+
+    /// Removes the worker [worker_id] from the barrier, the remaining workers stop
+    /// waiting for it. Sends the cancellation if all of them are already waiting.
+    pub fn worker_left(&self, worker_id: usize) {
+        let left = self.left_workers.fetch_add(1, Ordering::SeqCst) + 1;
+        let active = self.number_of_workers().saturating_sub(left);
+        log::warn!("Worker {worker_id} left the barrier, {active} workers remain.");
+        if self.waiting_workers() >= active {
+            log::debug!("Worker {worker_id} Send cancellation!");
+            self.cancellation_token.cancel();
+        }
+    }
+
+    /// Runs the [worker] with the id [worker_id]. If the worker panics, the panic is logged,
+    /// the worker leaves the barrier and None is returned.
+    pub async fn run_worker<F>(&self, worker_id: usize, worker: F) -> Option<F::Output>
+    where
+        F: Future,
+    {
+        match CatchUnwind::new(worker).await {
+            Ok(value) => Some(value),
+            Err(payload) => {
+                log::error!(
+                    "Worker {worker_id} panicked: {}",
+                    panic_message(payload.as_ref())
+                );
+                self.worker_left(worker_id);
+                None
+            }
+        }
+    }
+
     fn subscription_triggered<C, T, F>(
         &self,
         context: &C,
@@ -132,14 +188,14 @@ impl WorkerBarrier {
             "Worker {} encountered an illegal state with the barrier!",
             context.worker_id()
         );
-        if count == self.number_of_workers.get() {
+        if count >= self.active_workers() {
             log::debug!("Worker {} Send cancellation!", context.worker_id());
             self.cancellation_token.cancel();
         } else {
             log::debug!(
                 "Worker {} Wait for cancellation! ({count}|{})",
                 context.worker_id(),
-                self.number_of_workers.get()
+                self.active_workers()
             );
         }
 
@@ -151,9 +207,70 @@ impl WorkerBarrier {
             _ = queue_changed_subscription.changed() => {
                 self.subscription_triggered(context, cause_provider, "queue")
             }
-            _ = guardian_changed_subscription.changed() => {
+            _ = guardian_changed_subscription.recv() => {
                 self.subscription_triggered(context, cause_provider, "guardian")
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{ContinueOrStop, WorkerBarrier};
+    use crate::test_impls::TestContext;
+    use std::num::NonZeroUsize;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::task::JoinSet;
+    use tokio_util::sync::CancellationToken;
+
+    #[tokio::test]
+    async fn a_panicked_worker_leaves_the_barrier() {
+        let barrier = Arc::new(WorkerBarrier::new(
+            NonZeroUsize::new(3).unwrap(),
+            CancellationToken::new(),
+        ));
+        let context = Arc::new(TestContext::default());
+        let mut set = JoinSet::new();
+        for worker_id in 0..2 {
+            let barrier = barrier.clone();
+            let context = context.clone();
+            set.spawn(async move {
+                barrier
+                    .run_worker(worker_id, async {
+                        barrier
+                            .wait_for_is_cancelled(context.as_ref(), worker_id)
+                            .await
+                    })
+                    .await
+            });
+        }
+        let panicking = {
+            let barrier = barrier.clone();
+            tokio::spawn(async move {
+                barrier
+                    .run_worker(2, async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        panic!("Malformed url!")
+                    })
+                    .await
+            })
+        };
+
+        assert!(panicking.await.unwrap().is_none());
+        assert_eq!(2, barrier.active_workers());
+        let mut stopped = Vec::new();
+        while let Some(result) = tokio::time::timeout(Duration::from_secs(5), set.join_next())
+            .await
+            .expect("The waiting workers should not hang.")
+        {
+            match result.unwrap() {
+                Some(ContinueOrStop::Cancelled(worker_id)) => stopped.push(worker_id),
+                other => panic!("Expected a cancelled worker, got {other:?}"),
+            }
+        }
+        stopped.sort();
+        assert_eq!(vec![0, 1], stopped);
+        assert!(barrier.is_cancelled());
+    }
+}
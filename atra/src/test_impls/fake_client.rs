@@ -12,36 +12,86 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use crate::client::traits::{AtraClient, AtraResponse};
+use crate::client::traits::{AtraClient, AtraResponse, FetchFailureKind};
 use crate::contexts::traits::{SupportsConfigs, SupportsFileSystemAccess};
 use crate::data::RawData;
-use crate::fetching::FetchedRequestData;
+use crate::fetching::{CacheValidators, FetchedRequestData};
 use crate::url::AtraUri;
 use reqwest::{IntoUrl, StatusCode};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::{Arc, RwLock};
 use thiserror::Error;
+use tokio::time::Instant;
 
 /// A fake client for simulating downloads.
 #[derive(Clone)]
 pub struct FakeClient {
     value: Arc<RwLock<HashMap<AtraUri, Result<FakeResponse, FakeResponseError>>>>,
+    sequences: Arc<RwLock<HashMap<AtraUri, VecDeque<Result<FakeResponse, FakeResponseError>>>>>,
+    variants: Arc<RwLock<HashMap<(AtraUri, String), Result<FakeResponse, FakeResponseError>>>>,
+    requests: Arc<RwLock<Vec<(AtraUri, Instant)>>>,
+    validators: Arc<RwLock<Vec<(AtraUri, Option<CacheValidators>)>>>,
 }
 
 impl FakeClient {
     pub fn new() -> Self {
         Self {
             value: Default::default(),
+            sequences: Default::default(),
+            variants: Default::default(),
+            requests: Default::default(),
+            validators: Default::default(),
         }
     }
 
+    /// Returns all requested urls with the point in time of the request.
+    pub fn requests(&self) -> Vec<(AtraUri, Instant)> {
+        self.requests.read().unwrap().clone()
+    }
+
+    /// Returns the validators sent with each call of retrieve.
+    pub fn validators(&self) -> Vec<(AtraUri, Option<CacheValidators>)> {
+        self.validators.read().unwrap().clone()
+    }
+
     pub fn clear(&self) {
-        self.value.write().unwrap().clear()
+        self.value.write().unwrap().clear();
+        self.sequences.write().unwrap().clear();
+        self.variants.write().unwrap().clear();
     }
 
     pub fn insert(&self, key: AtraUri, value: Result<FakeResponse, FakeResponseError>) {
         self.value.write().unwrap().insert(key, value);
     }
+
+    /// Answers the requests of [key] asking for the [language], the other languages get the
+    /// inserted value.
+    pub fn insert_variant(
+        &self,
+        key: AtraUri,
+        language: &str,
+        value: Result<FakeResponse, FakeResponseError>,
+    ) {
+        self.variants
+            .write()
+            .unwrap()
+            .insert((key, language.to_string()), value);
+    }
+
+    /// Answers the next requests of [key] with the [values] in order, afterward the
+    /// inserted value is used.
+    pub fn insert_sequence(
+        &self,
+        key: AtraUri,
+        values: impl IntoIterator<Item = Result<FakeResponse, FakeResponseError>>,
+    ) {
+        self.sequences
+            .write()
+            .unwrap()
+            .entry(key)
+            .or_default()
+            .extend(values);
+    }
 }
 
 impl AtraClient for FakeClient {
@@ -53,23 +103,85 @@ impl AtraClient for FakeClient {
         "FakeClient"
     }
 
+    fn classify_error(&self, _url: &str, error: &Self::Error) -> FetchFailureKind {
+        match error.1 {
+            FakeErrorKind::Connect => FetchFailureKind::Connect,
+            FakeErrorKind::Timeout => FetchFailureKind::Timeout,
+            _ => FetchFailureKind::Other,
+        }
+    }
+
     async fn get<U>(&self, url: U) -> Result<Self::Response, Self::Error>
     where
         U: IntoUrl,
     {
         let url: AtraUri = url.as_str().parse().unwrap();
+        self.requests
+            .write()
+            .unwrap()
+            .push((url.clone(), Instant::now()));
+        if let Some(next) = self
+            .sequences
+            .write()
+            .unwrap()
+            .get_mut(&url)
+            .and_then(VecDeque::pop_front)
+        {
+            return next;
+        }
         match self.value.read().unwrap().get(&url) {
             None => Ok(FakeResponse::new(Some(empty()), 1)),
             Some(value) => value.clone(),
         }
     }
 
-    async fn retrieve<C, U>(&self, _: &C, url: U) -> Result<FetchedRequestData, Self::Error>
+    async fn retrieve<C, U>(
+        &self,
+        _: &C,
+        url: U,
+        validators: Option<&CacheValidators>,
+    ) -> Result<FetchedRequestData, Self::Error>
     where
         C: SupportsConfigs + SupportsFileSystemAccess,
         U: IntoUrl,
     {
-        Ok(self.get(url).await?.req_data())
+        let target: AtraUri = url.as_str().parse().unwrap();
+        self.validators
+            .write()
+            .unwrap()
+            .push((target, validators.cloned()));
+        let mut fetched = self.get(url.as_str()).await?.req_data();
+        fetched.resolve_not_modified(url.as_str(), validators);
+        Ok(fetched)
+    }
+
+    async fn retrieve_in_language<C, U>(
+        &self,
+        _: &C,
+        url: U,
+        language: &str,
+    ) -> Result<FetchedRequestData, Self::Error>
+    where
+        C: SupportsConfigs + SupportsFileSystemAccess,
+        U: IntoUrl,
+    {
+        let target: AtraUri = url.as_str().parse().unwrap();
+        let variant = self
+            .variants
+            .read()
+            .unwrap()
+            .get(&(target.clone(), language.to_string()))
+            .cloned();
+        match variant {
+            Some(value) => {
+                self.requests
+                    .write()
+                    .unwrap()
+                    .push((target, Instant::now()));
+                Ok(value?.req_data())
+            }
+            None => Ok(self.get(url.as_str()).await?.req_data()),
+        }
     }
 }
 
@@ -81,6 +193,22 @@ pub struct FakeResponseError(usize, FakeErrorKind);
 pub enum FakeErrorKind {
     NoData,
     NoUtf8,
+    /// The origin can not be reached.
+    Connect,
+    /// The origin did not answer in time.
+    Timeout,
+}
+
+impl FakeResponseError {
+    /// The error of a request to an origin that can not be reached.
+    pub fn connect(error_id: usize) -> Self {
+        Self(error_id, FakeErrorKind::Connect)
+    }
+
+    /// The error of a request that timed out.
+    pub fn timeout(error_id: usize) -> Self {
+        Self(error_id, FakeErrorKind::Timeout)
+    }
 }
 
 #[derive(Clone)]
@@ -13,17 +13,21 @@
 // limitations under the License.
 
 use crate::blacklist::{
-    create_managed_blacklist, Blacklist, BlacklistError, BlacklistManager, BlacklistType,
-    ManagedBlacklist, ManagedBlacklistSender, PolyBlackList, RegexBlackList,
+    create_managed_blacklist, Allowlist, Blacklist, BlacklistError, BlacklistManager,
+    BlacklistType, ManagedBlacklist, ManagedBlacklistSender, PolyBlackList, RegexBlackList,
 };
 use crate::client::traits::{AtraClient, AtraResponse};
 use crate::config::Config;
 use crate::contexts::local::LinkHandlingError;
 use crate::contexts::traits::*;
 use crate::contexts::{BaseContext, Context};
-use crate::crawl::{CrawlResult, CrawlTask, SlimCrawlResult, StoredDataHint};
+use crate::crawl::{
+    Baseline, CrawlEvent, CrawlEvents, CrawlLimits, CrawlResult, CrawlTask, SlimCrawlResult,
+    StoredDataHint, WaveFrontier,
+};
 use crate::data::RawVecData;
 use crate::database::DatabaseError;
+use crate::extraction::marker::ExtractorMethodHint;
 use crate::extraction::ExtractedLink;
 use crate::gdbr::identifier::GdbrIdentifierRegistry;
 use crate::io::fs::{AtraFS, WorkerFileSystemAccess};
@@ -31,15 +35,25 @@ use crate::link_state::{
     IsSeedYesNo, LinkStateDBError, LinkStateKind, LinkStateLike, LinkStateManager, RawLinkState,
     RecrawlYesNo,
 };
+use crate::origin_metadata::{OriginMetadata, OriginMetadataManager, OriginMetadataRegistry};
+use crate::queue::priority::UrlPriorityScorer;
 use crate::queue::{EnqueueCalled, UrlQueue, UrlQueueElement};
 use crate::queue::{QueueError, SupportsForcedQueueElement, UrlQueueElementRef};
 use crate::recrawl_management::DomainLastCrawledManager;
-use crate::robots::{CachedRobots, RobotsError, RobotsManager};
+use crate::robots::{CachedRobots, RobotsError, RobotsManager, RobotsPolicy, FAILED_ROBOTS_TTL};
 use crate::seed::{BasicSeed, UnguardedSeed};
 use crate::test_impls::providers::{ClientProvider, DefaultAtraProvider};
+use crate::tls_info::{CertificateInfo, OriginTlsInfo, TlsInfoManager, TlsInfoRegistry};
+use crate::unique_content::{
+    RegisteredPayload, UniqueContentCounters, UniqueContentCounts, UniqueContentManager,
+};
 use crate::url::guard::InMemoryUrlGuardian;
+use crate::url::filter::UrlFilterChain;
+use crate::url::normalization::UrlNormalizer;
+use crate::url::traps::TrapDetector;
 use crate::url::{AtraOriginProvider, AtraUri};
 use crate::url::{AtraUrlOrigin, UrlWithDepth};
+use crate::warc_ext::WarcSkipInstruction;
 use crate::web_graph::{WebGraphEntry, WebGraphError, WebGraphManager};
 use indexmap::IndexSet;
 use itertools::Itertools;
@@ -57,7 +71,7 @@ use camino::{Utf8Path, Utf8PathBuf};
 use camino_tempfile::Utf8TempDir;
 use text_processing::stopword_registry::StopWordRegistry;
 use text_processing::tf_idf::{Idf, Tf};
-use texting_robots::{get_robots_url, Robot};
+use texting_robots::get_robots_url;
 use time::{Duration, OffsetDateTime};
 use tokio::sync::watch::Receiver;
 use tokio::sync::Mutex;
@@ -71,7 +85,10 @@ pub struct TestContext<Provider = DefaultAtraProvider> {
     pub link_state_manager: InMemoryLinkStateManager,
     pub robots_manager: InMemoryRobotsManager,
     pub blacklist_manager: TestBlacklistManager,
+    pub allowlist: Allowlist,
     pub crawled_websites: std::sync::RwLock<HashMap<AtraUri, SlimCrawlResult>>,
+    /// The language variants of the crawled websites by url and language.
+    pub language_variants: std::sync::RwLock<HashMap<(AtraUri, String), SlimCrawlResult>>,
     pub data_urls: Mutex<Vec<(UrlWithDepth, UrlWithDepth)>>,
     pub configs: Config,
     pub host_manager: InMemoryUrlGuardian,
@@ -83,6 +100,17 @@ pub struct TestContext<Provider = DefaultAtraProvider> {
     pub fs: Arc<TestFS>,
     pub provider: Provider,
     pub domain_manager: InMemoryDomainManager,
+    pub unique_content_manager: InMemoryUniqueContentManager,
+    pub tls_info_manager: InMemoryTlsInfoManager,
+    pub origin_metadata_manager: InMemoryOriginMetadataManager,
+    pub url_normalizer: UrlNormalizer,
+    pub trap_detector: Option<TrapDetector>,
+    pub url_filter: UrlFilterChain,
+    pub crawl_events: CrawlEvents,
+    pub crawl_limits: CrawlLimits,
+    pub baseline: Option<Baseline>,
+    pub url_priority: UrlPriorityScorer,
+    pub wave_frontier: Option<WaveFrontier>,
 }
 
 impl<Provider> TestContext<Provider>
@@ -91,22 +119,41 @@ where
 {
     pub fn new(configs: Config, provider: Provider) -> Self {
         Self {
+            url_normalizer: UrlNormalizer::new(configs.crawl.url_normalization.clone()),
+            trap_detector: configs.crawl.trap_detection.clone().map(TrapDetector::new),
+            url_filter: UrlFilterChain::new(&configs.crawl.url_filters),
+            url_priority: UrlPriorityScorer::new(&configs.crawl.url_priority),
+            wave_frontier: configs.crawl.wave_mode.then(|| {
+                WaveFrontier::open(configs.paths.file_wave_frontier())
+                    .expect("The wave frontier should open!")
+            }),
+            crawl_limits: CrawlLimits::new(&configs.crawl, None),
+            baseline: None,
             ct_crawled_websites: AtomicUsize::new(0),
             ct_found_websites: AtomicUsize::new(0),
-            robots_manager: InMemoryRobotsManager::new(),
+            robots_manager: InMemoryRobotsManager::new(RobotsPolicy::new(&configs.crawl)),
             blacklist_manager: TestBlacklistManager::new(Default::default()),
+            allowlist: Allowlist::new(&configs.crawl.allowlist)
+                .expect("The allowlist should compile!"),
             crawled_websites: RwLock::new(HashMap::new()),
+            language_variants: RwLock::new(HashMap::new()),
             link_state_manager: InMemoryLinkStateManager::new(),
             links_queue: TestUrlQueue::default(),
             data_urls: Default::default(),
             stop_word_registry: StopWordRegistry::default(),
+            host_manager: InMemoryUrlGuardian::with_max_parallel(
+                configs.crawl.max_parallel_per_origin,
+            ),
             configs,
-            host_manager: Default::default(),
             fs: Arc::new(TestFS::new()),
             started_at: OffsetDateTime::now_utc(),
             link_net_manager: TestLinkNetManager::default(),
             gdbr_registry: None,
             domain_manager: Default::default(),
+            unique_content_manager: Default::default(),
+            tls_info_manager: Default::default(),
+            origin_metadata_manager: Default::default(),
+            crawl_events: CrawlEvents::new(),
             provider,
         }
     }
@@ -122,6 +169,22 @@ where
         }
     }
 
+    /// Returns true if the trap detection drops the found [url].
+    fn is_trap(&self, url: &UrlWithDepth) -> bool {
+        self.trap_detector
+            .as_ref()
+            .is_some_and(|detector| detector.check(url).is_some())
+    }
+
+    /// Returns the score of the found [url], None if the url filters reject it.
+    fn url_filter_score(&self, url: &UrlWithDepth, hint: &ExtractorMethodHint) -> Option<i8> {
+        let score = self.url_filter.decide(url, Some(hint.used_method));
+        if score.is_none() {
+            self.crawl_events.statistics().record_filtered(url);
+        }
+        score
+    }
+
     /// Returns the crawled websites on the left the results, on the right the data.
     pub fn get_all_crawled_websites(
         &self,
@@ -227,44 +290,67 @@ where
         &self,
         from: &UrlWithDepth,
         links: &HashSet<ExtractedLink>,
-    ) -> Result<Vec<UrlWithDepth>, LinkHandlingError> {
+    ) -> Result<Vec<(UrlWithDepth, i8)>, LinkHandlingError> {
         let mut for_queue = Vec::with_capacity(links.len() / 2);
         let mut for_insert = Vec::with_capacity(links.len() / 2);
+        let allowlist_guard = if self.allowlist.is_empty() {
+            None
+        } else {
+            Some(self.blacklist_manager.get_blacklist().await)
+        };
+        let is_denied = |url: &UrlWithDepth| {
+            allowlist_guard
+                .as_ref()
+                .is_some_and(|blacklist| !self.allowlist.admits(blacklist, url))
+        };
         for link in links {
             self.ct_found_websites.fetch_add(1, Ordering::Relaxed);
             match link {
-                ExtractedLink::OnSeed { url, .. } => {
+                ExtractedLink::OnSeed {
+                    url,
+                    extraction_method,
+                } => {
+                    let url = self.url_normalizer.normalized(url);
                     self.link_net_manager
-                        .add(WebGraphEntry::create_link(from, url))
+                        .add(WebGraphEntry::create_link(from, &url, extraction_method))
                         .await
                         .unwrap();
-                    for_insert.push(url.clone());
+                    if is_denied(&url) || self.is_trap(&url) {
+                        continue;
+                    }
+                    let Some(score) = self.url_filter_score(&url, extraction_method) else {
+                        continue;
+                    };
+                    for_insert.push((url, score));
                 }
-                ExtractedLink::Outgoing { url, .. } => {
+                ExtractedLink::Outgoing {
+                    url,
+                    extraction_method,
+                } => {
+                    let url = &self.url_normalizer.normalized(url);
                     self.link_net_manager
-                        .add(WebGraphEntry::create_link(from, url))
+                        .add(WebGraphEntry::create_link(from, url, extraction_method))
                         .await
                         .unwrap();
-                    if self.link_state_manager.get_link_state(url).await?.is_none() {
-                        let recrawl: Option<RecrawlYesNo> = if let Some(origin) = url.atra_origin()
-                        {
-                            let budget = self.configs.crawl.budget.get_budget_for(&origin);
-                            if budget.is_in_budget(url) {
-                                for_queue.push(UrlQueueElement::new(false, 0, false, url.clone()));
-                            }
-                            Some(budget.get_recrawl_interval().is_some().into())
-                        } else {
-                            None
-                        };
-
-                        self.link_state_manager
-                            .update_link_state_no_payload(
-                                url,
-                                LinkStateKind::Discovered,
-                                Some(IsSeedYesNo::No),
-                                recrawl,
-                            )
-                            .await?;
+                    if is_denied(url) || self.is_trap(url) {
+                        continue;
+                    }
+                    let Some(score) = self.url_filter_score(url, extraction_method) else {
+                        continue;
+                    };
+                    let budget = url
+                        .atra_origin()
+                        .map(|origin| self.configs.crawl.budget_for(&origin));
+                    let recrawl: Option<RecrawlYesNo> =
+                        budget.map(|budget| budget.get_recrawl_interval().is_some().into());
+                    let is_new = self
+                        .link_state_manager
+                        .discover_link_state(url, Some(IsSeedYesNo::No), recrawl)
+                        .await?;
+                    if is_new && budget.is_some_and(|budget| budget.is_in_budget(url)) {
+                        for_queue.push(
+                            UrlQueueElement::new(false, 0, false, url.clone()).with_priority(score),
+                        );
                     }
                 }
                 ExtractedLink::Data { base, url, .. } => self
@@ -275,10 +361,82 @@ where
             }
         }
         if !for_queue.is_empty() {
+            for element in &for_queue {
+                self.crawl_events.emit(CrawlEvent::Queued {
+                    url: element.target.clone(),
+                });
+            }
             self.links_queue.enqueue_all(for_queue).await?;
         }
         Ok(for_insert)
     }
+
+    async fn register_sitemap_links(
+        &self,
+        sitemap: &UrlWithDepth,
+        listed: &[UrlWithDepth],
+    ) -> Result<(), LinkHandlingError> {
+        for url in listed {
+            self.link_net_manager
+                .add(WebGraphEntry::create_sitemap_link(sitemap, url))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl<Provider> SupportsUrlNormalization for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn url_normalizer(&self) -> &UrlNormalizer {
+        &self.url_normalizer
+    }
+}
+
+impl<Provider> SupportsUrlFilter for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn url_filter(&self) -> &UrlFilterChain {
+        &self.url_filter
+    }
+}
+
+impl<Provider> SupportsCrawlEvents for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn crawl_events(&self) -> &CrawlEvents {
+        &self.crawl_events
+    }
+}
+
+impl<Provider> SupportsCrawlLimits for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn crawl_limits(&self) -> &CrawlLimits {
+        &self.crawl_limits
+    }
+}
+
+impl<Provider> SupportsBaseline for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn baseline(&self) -> Option<&Baseline> {
+        self.baseline.as_ref()
+    }
+}
+
+impl<Provider> SupportsUrlPriority for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    fn url_priority(&self) -> &UrlPriorityScorer {
+        &self.url_priority
+    }
 }
 
 impl<Provider> SupportsDomainHandling for TestContext<Provider>
@@ -291,6 +449,36 @@ where
     }
 }
 
+impl<Provider> SupportsUniqueContent for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    type UniqueContentManager = InMemoryUniqueContentManager;
+    fn get_unique_content_manager(&self) -> &InMemoryUniqueContentManager {
+        &self.unique_content_manager
+    }
+}
+
+impl<Provider> SupportsTlsInfo for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    type TlsInfoManager = InMemoryTlsInfoManager;
+    fn get_tls_info_manager(&self) -> &InMemoryTlsInfoManager {
+        &self.tls_info_manager
+    }
+}
+
+impl<Provider> SupportsOriginMetadata for TestContext<Provider>
+where
+    Provider: Send + Sync + 'static,
+{
+    type OriginMetadataManager = InMemoryOriginMetadataManager;
+    fn get_origin_metadata_manager(&self) -> &InMemoryOriginMetadataManager {
+        &self.origin_metadata_manager
+    }
+}
+
 impl<Provider> SupportsLinkState for TestContext<Provider>
 where
     Provider: Send + Sync + 'static,
@@ -368,6 +556,10 @@ where
     fn url_queue(&self) -> &Self::UrlQueue {
         &self.links_queue
     }
+
+    fn wave_frontier(&self) -> Option<&WaveFrontier> {
+        self.wave_frontier.as_ref()
+    }
 }
 
 impl<Provider> SupportsFileSystemAccess for TestContext<Provider>
@@ -402,6 +594,10 @@ impl AtraFS for TestFS {
         todo!()
     }
 
+    fn finalize_data_file(&self, path: Utf8PathBuf) -> std::io::Result<Utf8PathBuf> {
+        Ok(path)
+    }
+
     fn cleanup_data_file(&self, path: impl AsRef<Utf8Path>) -> std::io::Result<()> {
         std::fs::remove_file(path.as_ref())
     }
@@ -469,10 +665,30 @@ where
         result: SlimCrawlResult,
     ) -> Result<(), DatabaseError> {
         self.ct_crawled_websites.fetch_add(1, Ordering::Relaxed);
+        if let Some(language) = result.meta.language_variant.clone() {
+            let mut variants = self.language_variants.write().unwrap();
+            variants.insert((result.meta.url.url().clone(), language), result);
+            return Ok(());
+        }
         let mut crawled = self.crawled_websites.write().unwrap();
         crawled.insert(result.meta.url.url().clone(), result);
         Ok(())
     }
+
+    async fn retrieve_stored_payload(
+        &self,
+        _: &[u8],
+    ) -> Result<Option<WarcSkipInstruction>, DatabaseError> {
+        Ok(None)
+    }
+
+    async fn register_stored_payload(
+        &self,
+        _: &[u8],
+        _: &WarcSkipInstruction,
+    ) -> Result<(), DatabaseError> {
+        Ok(())
+    }
 }
 
 impl<Provider> SupportsCrawlResults for TestContext<Provider>
@@ -596,6 +812,13 @@ pub struct TestLinkNetManager {
     link_net: Arc<Mutex<Vec<WebGraphEntry>>>,
 }
 
+impl TestLinkNetManager {
+    /// Returns all added entries.
+    pub async fn entries(&self) -> Vec<WebGraphEntry> {
+        self.link_net.lock().await.clone()
+    }
+}
+
 impl WebGraphManager for TestLinkNetManager {
     async fn add(&self, link_net_entry: WebGraphEntry) -> Result<(), WebGraphError> {
         self.link_net.lock().await.push(link_net_entry);
@@ -644,7 +867,8 @@ impl UrlQueue<UrlWithDepth> for TestUrlQueue {
             entry.age + 1,
             entry.host_was_in_use,
             entry.target.clone(),
-        ));
+        )
+        .with_priority(entry.priority));
         Ok(())
     }
 
@@ -685,6 +909,16 @@ impl UrlQueue<UrlWithDepth> for TestUrlQueue {
             .collect_vec())
     }
 
+    async fn retain<F>(&self, mut keep: F) -> Result<usize, QueueError>
+    where
+        F: FnMut(&UrlQueueElement<UrlWithDepth>) -> bool,
+    {
+        let mut lock = self.links_queue.lock().unwrap();
+        let before = lock.len();
+        lock.retain(|value| keep(value));
+        Ok(before - lock.len())
+    }
+
     async fn len(&self) -> usize {
         let lock = self.links_queue.lock().unwrap();
         lock.len() + self.counter.get_count()
@@ -750,6 +984,24 @@ impl LinkStateManager for InMemoryLinkStateManager {
         Ok(())
     }
 
+    async fn update_link_state_at(
+        &self,
+        url: &UrlWithDepth,
+        state: LinkStateKind,
+        timestamp: OffsetDateTime,
+    ) -> Result<(), Self::Error> {
+        let mut lock = self.state.write().unwrap();
+        let raw_url = url.url();
+        let mut upsert = RawLinkState::new_preconfigured_upsert_no_payload(url, state, None, None);
+        upsert.set_timestamp(timestamp);
+        if let Some(target) = lock.get_mut(raw_url) {
+            RawLinkState::fold_merge_linkstate_test(target, url.as_bytes(), &upsert)
+        } else {
+            lock.insert(raw_url.clone(), upsert.deref().to_vec());
+        }
+        Ok(())
+    }
+
     fn get_link_state_sync(&self, url: &UrlWithDepth) -> Result<Option<RawLinkState>, Self::Error> {
         let lock = self.state.read().unwrap();
         Ok(lock
@@ -807,6 +1059,7 @@ impl LinkStateManager for InMemoryLinkStateManager {
 #[derive(Clone, Default, Debug)]
 pub struct InMemoryDomainManager {
     inner: Arc<RwLock<HashMap<AtraUrlOrigin, OffsetDateTime>>>,
+    fetches: Arc<RwLock<HashMap<AtraUrlOrigin, u64>>>,
 }
 
 impl DomainLastCrawledManager for InMemoryDomainManager {
@@ -820,6 +1073,148 @@ impl DomainLastCrawledManager for InMemoryDomainManager {
     async fn get_last_access(&self, domain: &AtraUrlOrigin) -> Option<OffsetDateTime> {
         self.inner.read().unwrap().get(domain).cloned()
     }
+
+    async fn register_fetch(&self, domain: &AtraUrlOrigin) {
+        *self
+            .fetches
+            .write()
+            .unwrap()
+            .entry(domain.clone())
+            .or_default() += 1;
+    }
+
+    async fn get_fetches_since_robots_refresh(&self, domain: &AtraUrlOrigin) -> u64 {
+        self.fetches
+            .read()
+            .unwrap()
+            .get(domain)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    async fn reset_fetches_since_robots_refresh(&self, domain: &AtraUrlOrigin) {
+        self.fetches.write().unwrap().remove(domain);
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct InMemoryUniqueContentManager {
+    digests: RwLock<HashSet<(Option<AtraUrlOrigin>, Vec<u8>)>>,
+    counters: RwLock<UniqueContentCounters>,
+}
+
+impl UniqueContentManager for InMemoryUniqueContentManager {
+    async fn register_payload(
+        &self,
+        origin: &AtraUrlOrigin,
+        digest: &[u8],
+    ) -> Result<RegisteredPayload, DatabaseError> {
+        let mut digests = self.digests.write().unwrap();
+        let unique_on_origin = digests.insert((Some(origin.clone()), digest.to_vec()));
+        let unique_globally = digests.insert((None, digest.to_vec()));
+        Ok(self
+            .counters
+            .write()
+            .unwrap()
+            .register(origin, unique_on_origin, unique_globally))
+    }
+
+    fn global_counts(&self) -> UniqueContentCounts {
+        self.counters.read().unwrap().global()
+    }
+
+    fn origin_counts(&self) -> Vec<(AtraUrlOrigin, UniqueContentCounts)> {
+        self.counters.read().unwrap().origins()
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct InMemoryTlsInfoManager {
+    registry: RwLock<TlsInfoRegistry>,
+}
+
+impl TlsInfoManager for InMemoryTlsInfoManager {
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool {
+        self.registry.write().unwrap().reserve(origin)
+    }
+
+    fn store(&self, origin: &AtraUrlOrigin, info: OriginTlsInfo) -> Result<(), DatabaseError> {
+        self.registry.write().unwrap().insert(origin, info);
+        Ok(())
+    }
+
+    fn update<F>(
+        &self,
+        origin: &AtraUrlOrigin,
+        update: F,
+    ) -> Result<Option<OriginTlsInfo>, DatabaseError>
+    where
+        F: FnOnce(&mut OriginTlsInfo) -> bool,
+    {
+        let mut registry = self.registry.write().unwrap();
+        let Some(mut info) = registry.get(origin).cloned() else {
+            return Ok(None);
+        };
+        if !update(&mut info) {
+            return Ok(None);
+        }
+        registry.insert(origin, info.clone());
+        Ok(Some(info))
+    }
+
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginTlsInfo)> {
+        self.registry.read().unwrap().origins()
+    }
+
+    fn info(&self, origin: &AtraUrlOrigin) -> Option<OriginTlsInfo> {
+        self.registry.read().unwrap().get(origin).cloned()
+    }
+
+    fn store_certificate(
+        &self,
+        origin: &AtraUrlOrigin,
+        certificate: &CertificateInfo,
+    ) -> Result<bool, DatabaseError> {
+        Ok(self
+            .registry
+            .write()
+            .unwrap()
+            .insert_certificate(origin, certificate))
+    }
+
+    fn certificates(&self, origin: &AtraUrlOrigin) -> Vec<CertificateInfo> {
+        self.registry.read().unwrap().certificates(origin).to_vec()
+    }
+}
+
+#[derive(Default, Debug)]
+pub struct InMemoryOriginMetadataManager {
+    registry: RwLock<OriginMetadataRegistry>,
+}
+
+impl OriginMetadataManager for InMemoryOriginMetadataManager {
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool {
+        self.registry.write().unwrap().reserve(origin)
+    }
+
+    fn update<F>(&self, origin: &AtraUrlOrigin, update: F) -> Result<(), DatabaseError>
+    where
+        F: FnOnce(&mut OriginMetadata),
+    {
+        let mut registry = self.registry.write().unwrap();
+        let mut metadata = registry.get(origin).cloned().unwrap_or_default();
+        update(&mut metadata);
+        registry.insert(origin, metadata);
+        Ok(())
+    }
+
+    fn get(&self, origin: &AtraUrlOrigin) -> Option<OriginMetadata> {
+        self.registry.read().unwrap().get(origin).cloned()
+    }
+
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginMetadata)> {
+        self.registry.read().unwrap().origins()
+    }
 }
 
 /// An in memory variant of a robots.txt manager
@@ -827,13 +1222,15 @@ impl DomainLastCrawledManager for InMemoryDomainManager {
 #[derive(Debug, Default)]
 pub struct InMemoryRobotsManager {
     cache: tokio::sync::RwLock<HashMap<AtraUrlOrigin, Arc<CachedRobots>>>,
+    policy: RobotsPolicy,
 }
 
 impl InMemoryRobotsManager {
     #[cfg(test)]
-    pub fn new() -> Self {
+    pub fn new(policy: RobotsPolicy) -> Self {
         Self {
             cache: tokio::sync::RwLock::new(HashMap::new()),
+            policy,
         }
     }
 }
@@ -848,8 +1245,13 @@ impl RobotsManager for InMemoryRobotsManager {
         let domain = url.atra_origin().ok_or(RobotsError::NoDomainForUrl)?;
         let cache = self.cache.read().await;
         let found = if let Some(found) = cache.get(&domain) {
+            let max_age = if found.is_temporary_failure() {
+                Some(max_age.map_or(FAILED_ROBOTS_TTL, |it| FAILED_ROBOTS_TTL.min(*it)))
+            } else {
+                max_age.copied()
+            };
             if let Some(max_age) = max_age {
-                if (OffsetDateTime::now_utc() - found.retrieved_at()).le(max_age) {
+                if (OffsetDateTime::now_utc() - found.retrieved_at()).le(&max_age) {
                     Some(found.clone())
                 } else {
                     drop(cache);
@@ -876,8 +1278,22 @@ impl RobotsManager for InMemoryRobotsManager {
         if let Some(found) = self.get(agent, url, max_age).await? {
             return Ok(found);
         }
+        self.refresh(client, agent, url).await
+    }
+
+    async fn refresh<C: AtraClient>(
+        &self,
+        client: &C,
+        agent: &str,
+        url: &UrlWithDepth,
+    ) -> Result<Arc<CachedRobots>, RobotsError<C::Error>> {
         // Later used but cheaper than downloading and then recognizing invalidity for manager.
         let origin = url.atra_origin().ok_or(RobotsError::NoDomainForUrl)?;
+        if let Some(found) = self.policy.read_override(agent, &origin)? {
+            let found = Arc::new(found);
+            self.cache.write().await.insert(origin, found.clone());
+            return Ok(found);
+        }
         let result = client
             .get(&get_robots_url(&url.try_as_str())?)
             .await
@@ -886,25 +1302,16 @@ impl RobotsManager for InMemoryRobotsManager {
         let status_code = result.status();
         let result = result.bytes().await;
 
-        let retrieved = if let Ok(result) = result {
-            if status_code.is_client_error() || status_code.is_server_error() {
-                CachedRobots::NoRobots {
-                    retrieved_at,
-                    _status_code: status_code,
-                }
-            } else {
-                let robot =
-                    Robot::new(agent, result.as_ref()).map_err(RobotsError::InvalidRobotsTxt)?;
-                CachedRobots::HasRobots {
-                    robot,
-                    retrieved_at,
-                }
+        let retrieved = match result {
+            Ok(result) if !status_code.is_client_error() && !status_code.is_server_error() => {
+                CachedRobots::parse(agent, result.as_ref(), retrieved_at)
+                    .map_err(RobotsError::InvalidRobotsTxt)?
             }
-        } else {
-            CachedRobots::NoRobots {
+            _ => CachedRobots::NoRobots {
+                status_code,
+                fallback: self.policy.fallback_for(status_code),
                 retrieved_at,
-                _status_code: status_code,
-            }
+            },
         };
 
         let retrieved = Arc::new(retrieved);
@@ -13,12 +13,17 @@
 // limitations under the License.
 
 use crate::client::traits::AtraClient;
-use crate::client::{build_classic_client, ClientWithUserAgent};
+use crate::client::proxy::ProxyRouter;
+use crate::client::{build_classic_client, build_recording_client, ClientWithUserAgent};
 use crate::contexts::traits::{SupportsConfigs, SupportsCrawling};
+use crate::fetching::CacheValidators;
 use crate::seed::BasicSeed;
 use crate::test_impls::{FakeClient, FakeResponse, FakeResponseError};
 use crate::url::AtraUri;
+use std::borrow::Cow;
 use std::error::Error;
+use std::sync::Arc;
+use tokio::time::Instant;
 
 /// A provider for a client used to download things.
 pub trait ClientProvider {
@@ -52,13 +57,28 @@ impl ClientProvider for DefaultAtraProvider {
             .user_agent
             .get_user_agent()
             .to_string();
-        let client = build_classic_client(context, seed, &useragent)?;
-        let client = ClientWithUserAgent::new(useragent, client);
+        let proxies = Arc::new(ProxyRouter::from_config(&context.configs().crawl));
+        let client = build_classic_client(context, seed, &useragent, &proxies)?;
+        let client = ClientWithUserAgent::new(
+            useragent,
+            client,
+            proxies,
+            context.configs().crawl.auth.clone(),
+        )
+        .with_default_headers(
+            context
+                .configs()
+                .crawl
+                .headers_for(seed.origin())
+                .map(Cow::into_owned),
+        )
+        .with_recording(build_recording_client(context, seed));
         Ok(client)
     }
 }
 
-/// A fake client provider.
+/// A fake client provider, the clones share the fake client.
+#[derive(Clone)]
 pub struct FakeClientProvider {
     inner: FakeClient,
 }
@@ -77,6 +97,22 @@ impl FakeClientProvider {
     pub fn insert(&self, key: AtraUri, value: Result<FakeResponse, FakeResponseError>) {
         self.inner.insert(key, value);
     }
+
+    pub fn insert_sequence(
+        &self,
+        key: AtraUri,
+        values: impl IntoIterator<Item = Result<FakeResponse, FakeResponseError>>,
+    ) {
+        self.inner.insert_sequence(key, values);
+    }
+
+    pub fn requests(&self) -> Vec<(AtraUri, Instant)> {
+        self.inner.requests()
+    }
+
+    pub fn validators(&self) -> Vec<(AtraUri, Option<CacheValidators>)> {
+        self.inner.validators()
+    }
 }
 
 impl ClientProvider for FakeClientProvider {
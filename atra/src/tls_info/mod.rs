@@ -0,0 +1,959 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+mod probe;
+
+pub use probe::*;
+
+use crate::config::TlsCaptureConfig;
+use crate::contexts::traits::{SupportsConfigs, SupportsTlsInfo};
+use crate::database::{DBActionType, DatabaseError, LazyBase64Value, RawDatabaseError};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
+use crate::{db_health_check, declare_column_families};
+use rocksdb::{Direction, IteratorMode, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use time::OffsetDateTime;
+
+/// The key prefix of the tls info of an origin.
+const TLS_PREFIX: u8 = b't';
+/// The key prefix of a certificate seen in the responses of an origin.
+const CERTIFICATE_PREFIX: u8 = b'c';
+/// The separator between the origin and the fingerprint in the key of a certificate.
+const CERTIFICATE_SEPARATOR: u8 = 0;
+
+/// A certificate of the chain presented by a server.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct CertificateInfo {
+    /// The subject like `CN=example.com, O=Example`.
+    pub subject: String,
+    pub issuer: String,
+    /// The subject alternative names like `DNS:example.com` or `IP:127.0.0.1`.
+    pub subject_alt_names: Vec<String>,
+    /// The serial number as uppercase hex.
+    pub serial_number: String,
+    pub not_before: OffsetDateTime,
+    pub not_after: OffsetDateTime,
+    /// The SHA-256 fingerprint of the DER encoding as lowercase hex.
+    pub fingerprint: String,
+}
+
+/// The negotiated connection and the certificate chain of a server at one point in time.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TlsCapture {
+    pub captured_at: OffsetDateTime,
+    /// The host and port the capture connected to.
+    pub host: String,
+    pub port: u16,
+    /// The negotiated protocol like `TLSv1.3`.
+    pub protocol: String,
+    /// The negotiated cipher suite.
+    pub cipher: Option<String>,
+    /// The chain as presented by the server, starting with its own certificate.
+    pub chain: Vec<CertificateInfo>,
+    /// Why the chain is not trusted, none if it is. The host name is not checked.
+    pub verification_error: Option<String>,
+}
+
+impl TlsCapture {
+    /// The certificate of the server itself.
+    pub fn leaf(&self) -> Option<&CertificateInfo> {
+        self.chain.first()
+    }
+}
+
+/// The tls info of a single response. The details of its certificate are stored once
+/// per origin, see [TlsInfoManager::certificate].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TlsInfo {
+    /// The SHA-256 fingerprint of the certificate of the connection.
+    pub fingerprint: String,
+    /// The certificate was expired or not yet valid at the time of the response.
+    pub expired: bool,
+    /// Why the chain of the certificate is not trusted, as found by the capture of the origin.
+    /// None if it is trusted or the certificate was not captured.
+    pub verification_error: Option<String>,
+}
+
+/// The tls info of an origin.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct OriginTlsInfo {
+    /// The latest capture of the origin.
+    pub capture: TlsCapture,
+    /// The fingerprint of the leaf certificate seen last, by the capture or by a response.
+    pub fingerprint: String,
+    /// The fingerprints of the leaf certificates replaced during the crawl, oldest first.
+    pub previous_fingerprints: Vec<String>,
+}
+
+impl OriginTlsInfo {
+    pub fn new(capture: TlsCapture) -> Self {
+        Self {
+            fingerprint: capture
+                .leaf()
+                .map(|leaf| leaf.fingerprint.clone())
+                .unwrap_or_default(),
+            capture,
+            previous_fingerprints: Vec::new(),
+        }
+    }
+
+    /// Returns true if the certificate changed during the crawl.
+    pub fn changed(&self) -> bool {
+        !self.previous_fingerprints.is_empty()
+    }
+
+    /// The end of the validity of the leaf certificate, if it lies before the [deadline].
+    pub fn expires_before(&self, deadline: OffsetDateTime) -> Option<OffsetDateTime> {
+        let not_after = self.capture.leaf()?.not_after;
+        (not_after < deadline).then_some(not_after)
+    }
+
+    /// Registers the [fingerprint] of the leaf certificate of a response.
+    /// Returns true if it differs from the one seen last.
+    fn register_fingerprint(&mut self, fingerprint: &str) -> bool {
+        if self.fingerprint == fingerprint {
+            return false;
+        }
+        let previous = std::mem::replace(&mut self.fingerprint, fingerprint.to_string());
+        self.previous_fingerprints.push(previous);
+        true
+    }
+}
+
+/// The tls info of all captured origins and the origins with a pending capture.
+#[derive(Debug, Clone, Default)]
+pub struct TlsInfoRegistry {
+    infos: HashMap<AtraUrlOrigin, OriginTlsInfo>,
+    reserved: HashSet<AtraUrlOrigin>,
+    certificates: HashMap<AtraUrlOrigin, Vec<CertificateInfo>>,
+}
+
+impl TlsInfoRegistry {
+    /// Returns true if [origin] was neither captured nor reserved before.
+    pub fn reserve(&mut self, origin: &AtraUrlOrigin) -> bool {
+        !self.infos.contains_key(origin) && self.reserved.insert(origin.clone())
+    }
+
+    pub fn insert(&mut self, origin: &AtraUrlOrigin, info: OriginTlsInfo) {
+        self.infos.insert(origin.clone(), info);
+    }
+
+    pub fn get(&self, origin: &AtraUrlOrigin) -> Option<&OriginTlsInfo> {
+        self.infos.get(origin)
+    }
+
+    /// Adds the [certificate] seen on [origin]. Returns false if it is already known.
+    pub fn insert_certificate(
+        &mut self,
+        origin: &AtraUrlOrigin,
+        certificate: &CertificateInfo,
+    ) -> bool {
+        let certificates = self.certificates.entry(origin.clone()).or_default();
+        if certificates
+            .iter()
+            .any(|known| known.fingerprint == certificate.fingerprint)
+        {
+            return false;
+        }
+        certificates.push(certificate.clone());
+        true
+    }
+
+    /// The certificates seen on [origin], in the order they were seen.
+    pub fn certificates(&self, origin: &AtraUrlOrigin) -> &[CertificateInfo] {
+        self.certificates
+            .get(origin)
+            .map(|certificates| certificates.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The tls info of all captured origins, ordered by the origin.
+    pub fn origins(&self) -> Vec<(AtraUrlOrigin, OriginTlsInfo)> {
+        let mut origins = self
+            .infos
+            .iter()
+            .map(|(origin, info)| (origin.clone(), info.clone()))
+            .collect::<Vec<_>>();
+        origins.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        origins
+    }
+}
+
+/// Keeps track of the tls info of each origin.
+pub trait TlsInfoManager {
+    /// Reserves the capture of [origin]. Returns false if it was already captured or reserved,
+    /// every origin is captured once per crawl.
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool;
+
+    /// Stores the tls [info] of [origin].
+    fn store(&self, origin: &AtraUrlOrigin, info: OriginTlsInfo) -> Result<(), DatabaseError>;
+
+    /// Applies [update] to the tls info of [origin] and stores it if [update] returns true.
+    /// Returns the updated info, none if [origin] was not captured or nothing changed.
+    fn update<F>(
+        &self,
+        origin: &AtraUrlOrigin,
+        update: F,
+    ) -> Result<Option<OriginTlsInfo>, DatabaseError>
+    where
+        F: FnOnce(&mut OriginTlsInfo) -> bool;
+
+    /// The tls info of all captured origins, ordered by the origin.
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginTlsInfo)>;
+
+    /// The tls info of [origin], none if it was not captured.
+    fn info(&self, origin: &AtraUrlOrigin) -> Option<OriginTlsInfo>;
+
+    /// Stores the [certificate] of a response from [origin] once per origin.
+    /// Returns false if it was already stored.
+    fn store_certificate(
+        &self,
+        origin: &AtraUrlOrigin,
+        certificate: &CertificateInfo,
+    ) -> Result<bool, DatabaseError>;
+
+    /// The certificates seen in the responses from [origin], in the order they were seen.
+    fn certificates(&self, origin: &AtraUrlOrigin) -> Vec<CertificateInfo>;
+}
+
+/// Stores the tls info in the database, the info survives a restart.
+#[derive(Debug)]
+pub struct TlsInfoDatabaseManager {
+    db: Arc<DB>,
+    registry: Mutex<TlsInfoRegistry>,
+}
+
+impl TlsInfoDatabaseManager {
+    declare_column_families! {
+        self.db => cf_handle(ORIGIN_INFO_DB_CF)
+    }
+
+    pub fn new(db: Arc<DB>) -> Result<Self, DatabaseError> {
+        db_health_check!(db: [
+            Self::ORIGIN_INFO_DB_CF => (
+                if test origin_info_cf_options
+                else "The origin info cf is missing!"
+            )
+        ]);
+
+        let mut manager = Self {
+            db,
+            registry: Mutex::default(),
+        };
+        manager.registry = Mutex::new(manager.load_registry()?);
+        Ok(manager)
+    }
+
+    /// Reads the stored tls info of all origins.
+    fn load_registry(&self) -> Result<TlsInfoRegistry, DatabaseError> {
+        let mut registry = TlsInfoRegistry::default();
+        let iter = self.db.iterator_cf(
+            &self.cf_handle(),
+            IteratorMode::From(&[TLS_PREFIX], Direction::Forward),
+        );
+        for entry in iter {
+            let (key, value) = entry.enrich_no_key(Self::ORIGIN_INFO_DB_CF, DBActionType::Read)?;
+            let Some(origin) = key.strip_prefix(&[TLS_PREFIX]) else {
+                break;
+            };
+            let info: OriginTlsInfo = bincode::deserialize(&value).map_err(|err| {
+                DatabaseError::from_deserialisation(
+                    Self::ORIGIN_INFO_DB_CF,
+                    String::from_utf8_lossy(&key).into_owned(),
+                    LazyBase64Value(value.to_vec()),
+                    err,
+                )
+            })?;
+            let origin = AtraUrlOrigin::from(String::from_utf8_lossy(origin).as_ref());
+            registry.insert(&origin, info);
+        }
+        let iter = self.db.iterator_cf(
+            &self.cf_handle(),
+            IteratorMode::From(&[CERTIFICATE_PREFIX], Direction::Forward),
+        );
+        for entry in iter {
+            let (key, value) = entry.enrich_no_key(Self::ORIGIN_INFO_DB_CF, DBActionType::Read)?;
+            let Some(origin_and_fingerprint) = key.strip_prefix(&[CERTIFICATE_PREFIX]) else {
+                break;
+            };
+            let certificate: CertificateInfo = bincode::deserialize(&value).map_err(|err| {
+                DatabaseError::from_deserialisation(
+                    Self::ORIGIN_INFO_DB_CF,
+                    String::from_utf8_lossy(&key).into_owned(),
+                    LazyBase64Value(value.to_vec()),
+                    err,
+                )
+            })?;
+            let origin = origin_and_fingerprint
+                .split(|value| *value == CERTIFICATE_SEPARATOR)
+                .next()
+                .unwrap_or_default();
+            let origin = AtraUrlOrigin::from(String::from_utf8_lossy(origin).as_ref());
+            registry.insert_certificate(&origin, &certificate);
+        }
+        Ok(registry)
+    }
+
+    fn put(&self, origin: &AtraUrlOrigin, info: &OriginTlsInfo) -> Result<(), DatabaseError> {
+        let key = tls_key(origin.as_ref());
+        let value = bincode::serialize(info).map_err(|err| {
+            DatabaseError::from_serialisation(
+                Self::ORIGIN_INFO_DB_CF,
+                String::from_utf8_lossy(&key).into_owned(),
+                info.clone(),
+                err,
+            )
+        })?;
+        self.db
+            .put_cf(&self.cf_handle(), key, value)
+            .enrich_no_key(Self::ORIGIN_INFO_DB_CF, DBActionType::Write)
+    }
+}
+
+impl TlsInfoManager for TlsInfoDatabaseManager {
+    fn reserve_capture(&self, origin: &AtraUrlOrigin) -> bool {
+        self.registry.lock().unwrap().reserve(origin)
+    }
+
+    fn store(&self, origin: &AtraUrlOrigin, info: OriginTlsInfo) -> Result<(), DatabaseError> {
+        let mut registry = self.registry.lock().unwrap();
+        self.put(origin, &info)?;
+        registry.insert(origin, info);
+        Ok(())
+    }
+
+    fn update<F>(
+        &self,
+        origin: &AtraUrlOrigin,
+        update: F,
+    ) -> Result<Option<OriginTlsInfo>, DatabaseError>
+    where
+        F: FnOnce(&mut OriginTlsInfo) -> bool,
+    {
+        // Locked until the info is written, concurrent updates are never lost.
+        let mut registry = self.registry.lock().unwrap();
+        let Some(mut info) = registry.get(origin).cloned() else {
+            return Ok(None);
+        };
+        if !update(&mut info) {
+            return Ok(None);
+        }
+        self.put(origin, &info)?;
+        registry.insert(origin, info.clone());
+        Ok(Some(info))
+    }
+
+    fn origins(&self) -> Vec<(AtraUrlOrigin, OriginTlsInfo)> {
+        self.registry.lock().unwrap().origins()
+    }
+
+    fn info(&self, origin: &AtraUrlOrigin) -> Option<OriginTlsInfo> {
+        self.registry.lock().unwrap().get(origin).cloned()
+    }
+
+    fn store_certificate(
+        &self,
+        origin: &AtraUrlOrigin,
+        certificate: &CertificateInfo,
+    ) -> Result<bool, DatabaseError> {
+        let mut registry = self.registry.lock().unwrap();
+        if registry
+            .certificates(origin)
+            .iter()
+            .any(|known| known.fingerprint == certificate.fingerprint)
+        {
+            return Ok(false);
+        }
+        let key = certificate_key(origin.as_ref(), &certificate.fingerprint);
+        let value = bincode::serialize(certificate).map_err(|err| {
+            DatabaseError::from_serialisation(
+                Self::ORIGIN_INFO_DB_CF,
+                String::from_utf8_lossy(&key).into_owned(),
+                certificate.clone(),
+                err,
+            )
+        })?;
+        self.db
+            .put_cf(&self.cf_handle(), key, value)
+            .enrich_no_key(Self::ORIGIN_INFO_DB_CF, DBActionType::Write)?;
+        Ok(registry.insert_certificate(origin, certificate))
+    }
+
+    fn certificates(&self, origin: &AtraUrlOrigin) -> Vec<CertificateInfo> {
+        self.registry.lock().unwrap().certificates(origin).to_vec()
+    }
+}
+
+fn tls_key(origin: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + 1);
+    key.push(TLS_PREFIX);
+    key.extend_from_slice(origin.as_bytes());
+    key
+}
+
+fn certificate_key(origin: &str, fingerprint: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + fingerprint.len() + 2);
+    key.push(CERTIFICATE_PREFIX);
+    key.extend_from_slice(origin.as_bytes());
+    key.push(CERTIFICATE_SEPARATOR);
+    key.extend_from_slice(fingerprint.as_bytes());
+    key
+}
+
+/// The origin and the address of the server of an https [url].
+fn tls_target(url: &UrlWithDepth) -> Option<(AtraUrlOrigin, String, u16)> {
+    let parsed = url.url.as_url()?;
+    if parsed.scheme() != "https" {
+        return None;
+    }
+    Some((
+        url.atra_origin()?,
+        parsed.host_str()?.to_string(),
+        parsed.port_or_known_default()?,
+    ))
+}
+
+/// Captures the tls info of the origin of [url] at the first contact in the crawl.
+/// A failed capture is only logged and never affects the crawl.
+pub async fn capture_tls_info<C>(context: &C, url: &UrlWithDepth)
+where
+    C: SupportsTlsInfo + SupportsConfigs,
+{
+    let Some(config) = context.configs().crawl.tls_capture.as_ref() else {
+        return;
+    };
+    let Some((origin, host, port)) = tls_target(url) else {
+        return;
+    };
+    let manager = context.get_tls_info_manager();
+    if !manager.reserve_capture(&origin) {
+        return;
+    }
+    let capture = match probe_tls(&host, port, config.probe_timeout).await {
+        Ok(capture) => capture,
+        Err(err) => {
+            log::warn!("Failed to capture the tls info of {origin} at {host}:{port}. {err}");
+            return;
+        }
+    };
+    log::debug!(
+        "Captured the tls info of {origin}: {} with {} certificates.",
+        capture.protocol,
+        capture.chain.len()
+    );
+    if let Err(err) = manager.store(&origin, OriginTlsInfo::new(capture)) {
+        log::error!("Failed to store the tls info of {origin}. {err}");
+    }
+}
+
+/// Compares the [fingerprint] of the leaf certificate of a response from [url] with the one
+/// seen last on its origin. A changed certificate flags the origin and is captured again.
+async fn check_tls_fingerprint<C>(context: &C, url: &UrlWithDepth, fingerprint: Option<&str>)
+where
+    C: SupportsTlsInfo + SupportsConfigs,
+{
+    let Some(config) = context.configs().crawl.tls_capture.as_ref() else {
+        return;
+    };
+    let (Some(fingerprint), Some((origin, host, port))) = (fingerprint, tls_target(url)) else {
+        return;
+    };
+    let manager = context.get_tls_info_manager();
+    // Other hosts of the origin may present other certificates.
+    let changed = manager.update(&origin, |info| {
+        info.capture.host == host
+            && info.capture.port == port
+            && info.register_fingerprint(fingerprint)
+    });
+    let previous = match changed {
+        Ok(Some(info)) => info
+            .previous_fingerprints
+            .last()
+            .cloned()
+            .unwrap_or_default(),
+        Ok(None) => return,
+        Err(err) => {
+            log::error!("Failed to store the tls info of {origin}. {err}");
+            return;
+        }
+    };
+    log::warn!(
+        "!!! The tls certificate of {origin} changed during the crawl from {previous} to {fingerprint}. !!!"
+    );
+    let capture = match probe_tls(&host, port, config.probe_timeout).await {
+        Ok(capture) => capture,
+        Err(err) => {
+            log::warn!("Failed to capture the changed tls info of {origin}. {err}");
+            return;
+        }
+    };
+    let updated = manager.update(&origin, |info| {
+        // The server may have changed the certificate again in the meantime.
+        let matches = capture
+            .leaf()
+            .is_some_and(|leaf| leaf.fingerprint == info.fingerprint);
+        if matches {
+            info.capture = capture;
+        }
+        matches
+    });
+    if let Err(err) = updated {
+        log::error!("Failed to store the tls info of {origin}. {err}");
+    }
+}
+
+/// Records the [certificate] of a response from [url]: compares it with the one seen last
+/// like [check_tls_fingerprint], stores it once per origin and returns the tls info of the
+/// response. None if the response was not received over tls.
+pub async fn record_tls_certificate<C>(
+    context: &C,
+    url: &UrlWithDepth,
+    certificate: Option<&CertificateInfo>,
+) -> Option<TlsInfo>
+where
+    C: SupportsTlsInfo + SupportsConfigs,
+{
+    let certificate = certificate?;
+    check_tls_fingerprint(context, url, Some(&certificate.fingerprint)).await;
+    let manager = context.get_tls_info_manager();
+    let mut verification_error = None;
+    if let Some(origin) = url.atra_origin() {
+        if let Err(err) = manager.store_certificate(&origin, certificate) {
+            log::error!("Failed to store the tls certificate of {origin}. {err}");
+        }
+        // The capture verified the chain, a response of a client accepting invalid
+        // certificates is not verified at all.
+        verification_error = manager.info(&origin).and_then(|info| {
+            let captured = info.capture.leaf()?.fingerprint == certificate.fingerprint;
+            captured
+                .then_some(info.capture.verification_error)
+                .flatten()
+        });
+    }
+    let now = OffsetDateTime::now_utc();
+    Some(TlsInfo {
+        fingerprint: certificate.fingerprint.clone(),
+        expired: now < certificate.not_before || certificate.not_after < now,
+        verification_error,
+    })
+}
+
+/// Logs the origins with a changed certificate and the certificates expiring within the
+/// configured window after [now].
+pub fn log_tls_summary(
+    infos: &[(AtraUrlOrigin, OriginTlsInfo)],
+    config: &TlsCaptureConfig,
+    now: OffsetDateTime,
+) {
+    let changed = infos.iter().filter(|(_, info)| info.changed()).count();
+    log::info!(
+        "Captured the tls info of {} origins, {changed} of them changed their certificate during the crawl",
+        infos.len()
+    );
+    for (origin, info) in infos {
+        if info.changed() {
+            log::warn!(
+                "The tls certificate of {origin} changed {} times during the crawl.",
+                info.previous_fingerprints.len()
+            );
+        }
+    }
+    let deadline = now + config.expiry_warning_window;
+    for (origin, info) in infos {
+        if let Some(not_after) = info.expires_before(deadline) {
+            if not_after < now {
+                log::warn!("!!! The tls certificate of {origin} expired at {not_after}. !!!");
+            } else {
+                log::warn!("!!! The tls certificate of {origin} expires at {not_after}. !!!");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        capture_tls_info, check_tls_fingerprint, probe_tls, record_tls_certificate,
+        CertificateInfo, OriginTlsInfo, TlsCapture, TlsInfoDatabaseManager, TlsInfoManager,
+    };
+    use crate::client::proxy::ProxyRouter;
+    use crate::client::traits::AtraClient;
+    use crate::client::ClientWithUserAgent;
+    use crate::config::{Config, TlsCaptureConfig};
+    use crate::contexts::traits::{SupportsConfigs, SupportsTlsInfo};
+    use crate::database::open_db;
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::url::{AtraUrlOrigin, UrlWithDepth};
+    use camino_tempfile::Utf8TempDir;
+    use openssl::asn1::Asn1Time;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::hash::MessageDigest;
+    use openssl::nid::Nid;
+    use openssl::pkey::{PKey, Private};
+    use openssl::ssl::{SslAcceptor, SslMethod};
+    use openssl::x509::extension::SubjectAlternativeName;
+    use openssl::x509::{X509Builder, X509NameBuilder, X509};
+    use std::io::{Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::sync::{Arc, Mutex};
+    use time::{Duration, OffsetDateTime};
+
+    /// The tls info of a server with a single certificate valid until [not_after].
+    pub fn sample_tls_info(not_after: OffsetDateTime) -> OriginTlsInfo {
+        OriginTlsInfo::new(TlsCapture {
+            captured_at: not_after - Duration::days(30),
+            host: "www.example.com".to_string(),
+            port: 443,
+            protocol: "TLSv1.3".to_string(),
+            cipher: Some("TLS_AES_256_GCM_SHA384".to_string()),
+            chain: vec![CertificateInfo {
+                subject: "CN=www.example.com".to_string(),
+                issuer: "CN=Example CA, O=Example".to_string(),
+                subject_alt_names: vec!["DNS:www.example.com".to_string()],
+                serial_number: "0A".to_string(),
+                not_before: not_after - Duration::days(90),
+                not_after,
+                fingerprint: "cd".repeat(32),
+            }],
+            verification_error: None,
+        })
+    }
+
+    /// A self signed certificate for `localhost` and `127.0.0.1`, valid from now on for [days].
+    fn self_signed(common_name: &str, serial: u32, days: u32) -> (X509, PKey<Private>) {
+        let now = OffsetDateTime::now_utc();
+        self_signed_between(common_name, serial, now, now + Duration::days(days as i64))
+    }
+
+    /// A self signed certificate for `localhost` and `127.0.0.1`, valid between
+    /// [not_before] and [not_after].
+    fn self_signed_between(
+        common_name: &str,
+        serial: u32,
+        not_before: OffsetDateTime,
+        not_after: OffsetDateTime,
+    ) -> (X509, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let key = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_nid(Nid::COMMONNAME, common_name)
+            .unwrap();
+        name.append_entry_by_nid(Nid::ORGANIZATIONNAME, "Atra Test")
+            .unwrap();
+        let name = name.build();
+        let mut builder = X509Builder::new().unwrap();
+        builder.set_version(2).unwrap();
+        builder
+            .set_serial_number(&BigNum::from_u32(serial).unwrap().to_asn1_integer().unwrap())
+            .unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_issuer_name(&name).unwrap();
+        builder.set_pubkey(&key).unwrap();
+        builder
+            .set_not_before(&Asn1Time::from_unix(not_before.unix_timestamp()).unwrap())
+            .unwrap();
+        builder
+            .set_not_after(&Asn1Time::from_unix(not_after.unix_timestamp()).unwrap())
+            .unwrap();
+        let alt_names = SubjectAlternativeName::new()
+            .dns("localhost")
+            .ip("127.0.0.1")
+            .build(&builder.x509v3_context(None, None))
+            .unwrap();
+        builder.append_extension(alt_names).unwrap();
+        builder.sign(&key, MessageDigest::sha256()).unwrap();
+        (builder.build(), key)
+    }
+
+    fn acceptor(certificate: &(X509, PKey<Private>)) -> Arc<SslAcceptor> {
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        builder.set_private_key(&certificate.1).unwrap();
+        builder.set_certificate(&certificate.0).unwrap();
+        builder.check_private_key().unwrap();
+        Arc::new(builder.build())
+    }
+
+    /// A https server answering every request with a small page.
+    /// The certificate can be swapped between two connections.
+    struct TlsTestServer {
+        address: SocketAddr,
+        acceptor: Arc<Mutex<Arc<SslAcceptor>>>,
+    }
+
+    impl TlsTestServer {
+        fn start(certificate: &(X509, PKey<Private>)) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap();
+            let acceptor = Arc::new(Mutex::new(acceptor(certificate)));
+            let current = acceptor.clone();
+            std::thread::spawn(move || {
+                for stream in listener.incoming() {
+                    let Ok(stream) = stream else {
+                        continue;
+                    };
+                    let acceptor = current.lock().unwrap().clone();
+                    std::thread::spawn(move || {
+                        let Ok(mut stream) = acceptor.accept(stream) else {
+                            return;
+                        };
+                        let mut request = Vec::new();
+                        let mut buffer = [0u8; 1024];
+                        while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+                            match stream.read(&mut buffer) {
+                                Ok(0) | Err(_) => return,
+                                Ok(read) => request.extend_from_slice(&buffer[..read]),
+                            }
+                        }
+                        let _ = stream.write_all(
+                            b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: 13\r\nConnection: close\r\n\r\n<p>Hello!</p>",
+                        );
+                        let _ = stream.shutdown();
+                    });
+                }
+            });
+            Self { address, acceptor }
+        }
+
+        /// Presents the [certificate] to the following connections.
+        fn swap(&self, certificate: &(X509, PKey<Private>)) {
+            *self.acceptor.lock().unwrap() = acceptor(certificate);
+        }
+    }
+
+    /// Fetches [url] like a crawler with a client recording the tls info.
+    async fn fetch_certificate(
+        context: &TestContext<FakeClientProvider>,
+        url: &str,
+    ) -> CertificateInfo {
+        let inner = reqwest::Client::builder()
+            .danger_accept_invalid_certs(true)
+            .tls_info(true)
+            .build()
+            .unwrap();
+        let client = ClientWithUserAgent::new(
+            "atra-test".to_string(),
+            reqwest_middleware::ClientBuilder::new(inner).build(),
+            Arc::new(ProxyRouter::from_config(&context.configs().crawl)),
+            context.configs().crawl.auth.clone(),
+        );
+        let fetched = client.retrieve(context, url, None).await.unwrap();
+        fetched
+            .tls_certificate
+            .expect("The client should record the certificate.")
+    }
+
+    #[tokio::test]
+    async fn captures_the_certificate_once_and_detects_a_swap() {
+        let first = self_signed("first.atra.test", 1, 10);
+        let second = self_signed("second.atra.test", 2, 90);
+        let server = TlsTestServer::start(&first);
+
+        let mut config = Config::default();
+        config.crawl.tls_capture = Some(TlsCaptureConfig::default());
+        let context = TestContext::new(config, FakeClientProvider::new());
+        let page = format!("https://{}/index.html", server.address);
+        let url = UrlWithDepth::from_url(&page).unwrap();
+        let origin = AtraUrlOrigin::from("127.0.0.1");
+
+        capture_tls_info(&context, &url).await;
+        let infos = context.get_tls_info_manager().origins();
+        assert_eq!(1, infos.len());
+        let (captured_origin, info) = &infos[0];
+        assert_eq!(&origin, captured_origin);
+        let capture = &info.capture;
+        assert_eq!("127.0.0.1", capture.host);
+        assert_eq!(server.address.port(), capture.port);
+        assert!(
+            capture.protocol.starts_with("TLSv1."),
+            "{}",
+            capture.protocol
+        );
+        assert!(capture.cipher.is_some());
+        assert!(capture.verification_error.is_some());
+        let leaf = capture.leaf().unwrap();
+        assert_eq!("CN=first.atra.test, O=Atra Test", leaf.subject);
+        assert_eq!(leaf.subject, leaf.issuer);
+        assert_eq!(
+            vec!["DNS:localhost".to_string(), "IP:127.0.0.1".to_string()],
+            leaf.subject_alt_names
+        );
+        assert_eq!("01", leaf.serial_number);
+        assert_eq!(Duration::days(10), leaf.not_after - leaf.not_before,);
+        assert_eq!(64, leaf.fingerprint.len());
+        assert_eq!(leaf.fingerprint, info.fingerprint);
+        assert!(!info.changed());
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(
+            Some(leaf.not_after),
+            info.expires_before(now + Duration::days(30))
+        );
+        assert_eq!(None, info.expires_before(now + Duration::days(5)));
+
+        // Every origin is captured once per crawl.
+        server.swap(&second);
+        capture_tls_info(&context, &url).await;
+        assert_eq!(infos, context.get_tls_info_manager().origins());
+
+        // The response carries the new certificate.
+        let fingerprint = fetch_certificate(&context, &page).await.fingerprint;
+        assert_ne!(info.fingerprint, fingerprint);
+        check_tls_fingerprint(&context, &url, Some(&fingerprint)).await;
+        let (_, changed) = context.get_tls_info_manager().origins().remove(0);
+        assert!(changed.changed());
+        assert_eq!(
+            vec![info.fingerprint.clone()],
+            changed.previous_fingerprints
+        );
+        assert_eq!(fingerprint, changed.fingerprint);
+        let leaf = changed.capture.leaf().unwrap();
+        assert_eq!("CN=second.atra.test, O=Atra Test", leaf.subject);
+        assert_eq!(fingerprint, leaf.fingerprint);
+        assert_eq!(None, changed.expires_before(now + Duration::days(30)));
+
+        // The same certificate again is no change.
+        check_tls_fingerprint(&context, &url, Some(&fingerprint)).await;
+        assert_eq!(
+            changed,
+            context.get_tls_info_manager().origins().remove(0).1
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_captures_are_ignored_and_plain_http_is_skipped() {
+        let mut config = Config::default();
+        config.crawl.tls_capture = Some(TlsCaptureConfig {
+            probe_timeout: Duration::seconds(1),
+            ..TlsCaptureConfig::default()
+        });
+        let context = TestContext::new(config, FakeClientProvider::new());
+
+        // Nobody listens on the port of a closed listener.
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let url = UrlWithDepth::from_url(&format!("https://{address}/")).unwrap();
+        assert!(probe_tls("127.0.0.1", address.port(), Duration::seconds(1))
+            .await
+            .is_err());
+        capture_tls_info(&context, &url).await;
+        check_tls_fingerprint(&context, &url, Some("00")).await;
+        let http = UrlWithDepth::from_url("http://www.example.com/").unwrap();
+        capture_tls_info(&context, &http).await;
+        assert!(context.get_tls_info_manager().origins().is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_tls_info_survives_a_restart() {
+        let server = TlsTestServer::start(&self_signed("stored.atra.test", 7, 30));
+        let capture = probe_tls("127.0.0.1", server.address.port(), Duration::seconds(5))
+            .await
+            .unwrap();
+        let origin = AtraUrlOrigin::from("127.0.0.1");
+
+        let dir = Utf8TempDir::new().unwrap();
+        let db = Arc::new(open_db(dir.path()).unwrap());
+        let manager = TlsInfoDatabaseManager::new(db.clone()).unwrap();
+        assert!(manager.reserve_capture(&origin));
+        assert!(!manager.reserve_capture(&origin));
+        manager
+            .store(&origin, OriginTlsInfo::new(capture.clone()))
+            .unwrap();
+        let updated = manager
+            .update(&origin, |info| info.register_fingerprint("ab"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec![updated.capture.leaf().unwrap().fingerprint.clone()],
+            updated.previous_fingerprints
+        );
+        assert_eq!(
+            None,
+            manager
+                .update(&origin, |info| info.register_fingerprint("ab"))
+                .unwrap()
+        );
+
+        let leaf = capture.leaf().unwrap();
+        assert!(manager.store_certificate(&origin, leaf).unwrap());
+        assert!(!manager.store_certificate(&origin, leaf).unwrap());
+
+        drop(manager);
+        drop(db);
+        let db = Arc::new(open_db(dir.path()).unwrap());
+        let manager = TlsInfoDatabaseManager::new(db).unwrap();
+        assert_eq!(vec![(origin.clone(), updated)], manager.origins());
+        assert!(!manager.reserve_capture(&origin));
+        assert_eq!(vec![leaf.clone()], manager.certificates(&origin));
+        assert!(!manager.store_certificate(&origin, leaf).unwrap());
+        assert!(manager
+            .certificates(&AtraUrlOrigin::from("127.0.0.2"))
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn records_the_certificate_of_every_response() {
+        let now = OffsetDateTime::now_utc();
+        let expired = self_signed_between(
+            "expired.atra.test",
+            3,
+            now - Duration::days(60),
+            now - Duration::days(1),
+        );
+        let server = TlsTestServer::start(&self_signed("valid.atra.test", 4, 30));
+
+        let mut config = Config::default();
+        config.crawl.tls_capture = Some(TlsCaptureConfig::default());
+        let context = TestContext::new(config, FakeClientProvider::new());
+        let page = format!("https://{}/index.html", server.address);
+        let url = UrlWithDepth::from_url(&page).unwrap();
+        let origin = AtraUrlOrigin::from("127.0.0.1");
+        capture_tls_info(&context, &url).await;
+
+        let certificate = fetch_certificate(&context, &page).await;
+        assert_eq!("CN=valid.atra.test, O=Atra Test", certificate.subject);
+        assert_eq!(certificate.subject, certificate.issuer);
+        assert_eq!("04", certificate.serial_number);
+        let tls = record_tls_certificate(&context, &url, Some(&certificate))
+            .await
+            .unwrap();
+        assert_eq!(certificate.fingerprint, tls.fingerprint);
+        assert!(!tls.expired);
+        // The self signed certificate is accepted by the client but flagged by the capture.
+        assert!(tls.verification_error.is_some());
+        // The same certificate is stored once per origin.
+        assert_eq!(
+            Some(tls.clone()),
+            record_tls_certificate(&context, &url, Some(&certificate)).await
+        );
+        let manager = context.get_tls_info_manager();
+        assert_eq!(vec![certificate.clone()], manager.certificates(&origin));
+
+        server.swap(&expired);
+        let replaced = fetch_certificate(&context, &page).await;
+        let tls = record_tls_certificate(&context, &url, Some(&replaced))
+            .await
+            .unwrap();
+        assert_eq!(replaced.fingerprint, tls.fingerprint);
+        assert!(tls.expired);
+        // The changed certificate was captured again.
+        assert!(tls.verification_error.is_some());
+        assert_eq!(vec![certificate, replaced], manager.certificates(&origin));
+
+        assert_eq!(None, record_tls_certificate(&context, &url, None).await);
+    }
+}
@@ -0,0 +1,176 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::tls_info::{CertificateInfo, TlsCapture};
+use data_encoding::HEXLOWER;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::error::ErrorStack;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::{GeneralNameRef, X509NameRef, X509Ref, X509VerifyResult, X509};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, TcpStream, ToSocketAddrs};
+use thiserror::Error;
+use time::{Duration, OffsetDateTime};
+
+/// Errors of the tls probe.
+#[derive(Debug, Error)]
+pub enum TlsProbeError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Ssl(#[from] ErrorStack),
+    #[error("The handshake failed: {0}")]
+    Handshake(String),
+    #[error("The server presented no certificate.")]
+    NoCertificate,
+    #[error("The probe was cancelled: {0}")]
+    Cancelled(#[from] tokio::task::JoinError),
+}
+
+/// The SHA-256 fingerprint of the [der] encoding of a certificate as lowercase hex.
+pub fn certificate_fingerprint(der: &[u8]) -> String {
+    HEXLOWER.encode(&openssl::sha::sha256(der))
+}
+
+/// Connects to [host]:[port] and records the negotiated connection and the certificate chain.
+/// The chain is recorded as presented, even if it is not trusted.
+pub async fn probe_tls(
+    host: &str,
+    port: u16,
+    timeout: Duration,
+) -> Result<TlsCapture, TlsProbeError> {
+    let host = host.to_string();
+    tokio::task::spawn_blocking(move || probe_tls_blocking(&host, port, timeout.unsigned_abs()))
+        .await?
+}
+
+fn probe_tls_blocking(
+    host: &str,
+    port: u16,
+    timeout: std::time::Duration,
+) -> Result<TlsCapture, TlsProbeError> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{host} has no address")))?;
+    let stream = TcpStream::connect_timeout(&address, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))?;
+
+    let mut builder = SslConnector::builder(SslMethod::tls_client())?;
+    // The result of the verification is recorded instead of failing the handshake.
+    builder.set_verify(SslVerifyMode::NONE);
+    let mut configuration = builder.build().configure()?;
+    configuration.set_verify_hostname(false);
+    let mut stream = configuration
+        .connect(host, stream)
+        .map_err(|err| TlsProbeError::Handshake(err.to_string()))?;
+
+    let ssl = stream.ssl();
+    let mut chain = match ssl.peer_cert_chain() {
+        Some(chain) => chain
+            .iter()
+            .map(CertificateInfo::from_x509)
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+    if chain.is_empty() {
+        if let Some(leaf) = ssl.peer_certificate() {
+            chain.push(CertificateInfo::from_x509(&leaf)?);
+        }
+    }
+    if chain.is_empty() {
+        return Err(TlsProbeError::NoCertificate);
+    }
+    let verify_result = ssl.verify_result();
+    let capture = TlsCapture {
+        captured_at: OffsetDateTime::now_utc(),
+        host: host.to_string(),
+        port,
+        protocol: ssl.version_str().to_string(),
+        cipher: ssl.current_cipher().map(|cipher| cipher.name().to_string()),
+        chain,
+        verification_error: (verify_result != X509VerifyResult::OK)
+            .then(|| verify_result.error_string().to_string()),
+    };
+    let _ = stream.shutdown();
+    Ok(capture)
+}
+
+impl CertificateInfo {
+    /// Reads the [der] encoding of a certificate.
+    pub fn from_der(der: &[u8]) -> Result<Self, ErrorStack> {
+        Self::from_x509(&X509::from_der(der)?)
+    }
+
+    fn from_x509(certificate: &X509Ref) -> Result<Self, ErrorStack> {
+        Ok(Self {
+            subject: format_name(certificate.subject_name()),
+            issuer: format_name(certificate.issuer_name()),
+            subject_alt_names: certificate
+                .subject_alt_names()
+                .map(|names| names.iter().filter_map(format_general_name).collect())
+                .unwrap_or_default(),
+            serial_number: certificate
+                .serial_number()
+                .to_bn()?
+                .to_hex_str()?
+                .to_string(),
+            not_before: to_date_time(certificate.not_before())?,
+            not_after: to_date_time(certificate.not_after())?,
+            fingerprint: certificate_fingerprint(&certificate.to_der()?),
+        })
+    }
+}
+
+/// Formats a name like `CN=example.com, O=Example`.
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = match entry.data().as_utf8() {
+                Ok(value) => value.to_string(),
+                Err(_) => String::from_utf8_lossy(entry.data().as_slice()).into_owned(),
+            };
+            format!("{key}={value}")
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a subject alternative name like `DNS:example.com` or `IP:127.0.0.1`.
+fn format_general_name(name: &GeneralNameRef) -> Option<String> {
+    if let Some(dns) = name.dnsname() {
+        return Some(format!("DNS:{dns}"));
+    }
+    if let Some(ip) = name.ipaddress() {
+        let ip = match ip.len() {
+            4 => IpAddr::V4(Ipv4Addr::from(<[u8; 4]>::try_from(ip).ok()?)),
+            16 => IpAddr::V6(Ipv6Addr::from(<[u8; 16]>::try_from(ip).ok()?)),
+            _ => return None,
+        };
+        return Some(format!("IP:{ip}"));
+    }
+    if let Some(email) = name.email() {
+        return Some(format!("email:{email}"));
+    }
+    name.uri().map(|uri| format!("URI:{uri}"))
+}
+
+fn to_date_time(time: &Asn1TimeRef) -> Result<OffsetDateTime, ErrorStack> {
+    let diff = Asn1Time::from_unix(0)?.diff(time)?;
+    Ok(OffsetDateTime::UNIX_EPOCH
+        + Duration::days(diff.days as i64)
+        + Duration::seconds(diff.secs as i64))
+}
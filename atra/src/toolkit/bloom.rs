@@ -0,0 +1,209 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::f64::consts::LN_2;
+
+/// The smallest number of bits of a filter, even an empty one.
+const MIN_BITS: u64 = 64;
+
+/// A bloom filter over byte keys. It never misses an inserted key, but tells
+/// that a key may be contained that was never inserted with the configured probability.
+///
+/// The bit positions are derived from the two halves of the xxh3 128 bit hash of a key.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hashes: u32,
+}
+
+impl BloomFilter {
+    /// Creates a filter for [expected_keys] with the [false_positive_rate] between 0 and 1.
+    pub fn new(expected_keys: usize, false_positive_rate: f64) -> Self {
+        let keys = expected_keys.max(1) as f64;
+        let rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5);
+        let bit_count = ((-keys * rate.ln() / (LN_2 * LN_2)).ceil() as u64).max(MIN_BITS);
+        let hashes = ((bit_count as f64 / keys) * LN_2).round().max(1.0) as u32;
+        Self {
+            bits: vec![0; bit_count.div_ceil(64) as usize],
+            bit_count,
+            hashes,
+        }
+    }
+
+    /// Adds the [key] to the filter.
+    pub fn insert(&mut self, key: &[u8]) {
+        let (first, second) = Self::hash(key);
+        for i in 0..self.hashes {
+            let bit = self.position(first, second, i);
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    /// Returns false if the [key] was never inserted, true if it may have been.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        let (first, second) = Self::hash(key);
+        (0..self.hashes).all(|i| {
+            let bit = self.position(first, second, i);
+            self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0
+        })
+    }
+
+    /// The probability that [may_contain] is true for a key that was never inserted,
+    /// estimated from the share of set bits.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let set = self
+            .bits
+            .iter()
+            .map(|value| value.count_ones() as u64)
+            .sum::<u64>();
+        (set as f64 / self.bit_count as f64).powi(self.hashes as i32)
+    }
+
+    fn hash(key: &[u8]) -> (u64, u64) {
+        let hash = twox_hash::xxh3::hash128(key);
+        (hash as u64, (hash >> 64) as u64)
+    }
+
+    fn position(&self, first: u64, second: u64, i: u32) -> u64 {
+        first.wrapping_add((i as u64).wrapping_mul(second)) % self.bit_count
+    }
+}
+
+/// A bloom filter that grows with the inserted keys. A full layer is kept and a new one with
+/// twice the capacity and half the false positive rate is added, the false positive rate of
+/// all layers together stays below the configured one.
+#[derive(Debug, Clone)]
+pub struct ScalableBloomFilter {
+    layers: Vec<BloomFilter>,
+    /// The keys the last layer was sized for.
+    capacity: usize,
+    /// The false positive rate of the last layer.
+    rate: f64,
+    /// The keys in the last layer.
+    filled: usize,
+    keys: u64,
+}
+
+impl ScalableBloomFilter {
+    /// Creates a filter for [expected_keys] with the [false_positive_rate] between 0 and 1.
+    /// More keys can be inserted, the filter grows.
+    pub fn new(expected_keys: usize, false_positive_rate: f64) -> Self {
+        let capacity = expected_keys.max(1);
+        // The rates of the layers halve, their sum is below twice the rate of the first one.
+        let rate = false_positive_rate / 2.0;
+        Self {
+            layers: vec![BloomFilter::new(capacity, rate)],
+            capacity,
+            rate,
+            filled: 0,
+            keys: 0,
+        }
+    }
+
+    /// Adds the [key] to the filter, returns false if it may already be contained.
+    pub fn insert(&mut self, key: &[u8]) -> bool {
+        if self.may_contain(key) {
+            return false;
+        }
+        if self.filled >= self.capacity {
+            self.capacity = self.capacity.saturating_mul(2);
+            self.rate /= 2.0;
+            self.filled = 0;
+            self.layers.push(BloomFilter::new(self.capacity, self.rate));
+        }
+        self.layers
+            .last_mut()
+            .expect("A filter has at least one layer.")
+            .insert(key);
+        self.filled += 1;
+        self.keys += 1;
+        true
+    }
+
+    /// Returns false if the [key] was never inserted, true if it may have been.
+    pub fn may_contain(&self, key: &[u8]) -> bool {
+        self.layers.iter().any(|layer| layer.may_contain(key))
+    }
+
+    /// The number of inserted keys, without the keys that were reported as contained.
+    pub fn keys(&self) -> u64 {
+        self.keys
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// The probability that [may_contain] is true for a key that was never inserted,
+    /// estimated from the share of set bits of every layer.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        1.0 - self
+            .layers
+            .iter()
+            .map(|layer| 1.0 - layer.estimated_false_positive_rate())
+            .product::<f64>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BloomFilter, ScalableBloomFilter};
+
+    #[test]
+    fn never_misses_an_inserted_key() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+        for i in 0..1000 {
+            filter.insert(format!("https://www.example.com/{i}").as_bytes());
+        }
+        for i in 0..1000 {
+            assert!(filter.may_contain(format!("https://www.example.com/{i}").as_bytes()));
+        }
+        let false_positives = (1000..11000)
+            .filter(|i| filter.may_contain(format!("https://www.example.com/{i}").as_bytes()))
+            .count();
+        // One percent are expected, the bound leaves room for an unlucky hash.
+        assert!(false_positives < 300, "{false_positives} false positives");
+    }
+
+    #[test]
+    fn an_empty_filter_contains_nothing() {
+        let filter = BloomFilter::new(0, 0.01);
+        assert!(!filter.may_contain(b"https://www.example.com/"));
+    }
+
+    #[test]
+    fn grows_beyond_the_expected_keys() {
+        let mut filter = ScalableBloomFilter::new(100, 0.01);
+        assert_eq!(0.0, filter.estimated_false_positive_rate());
+        let inserted = (0..10_000)
+            .filter(|i| filter.insert(format!("https://www.example.com/{i}").as_bytes()))
+            .count();
+        assert_eq!(inserted as u64, filter.keys());
+        // A few keys collide with the earlier ones, the filter can not tell them apart.
+        assert!(inserted > 9_900, "{inserted} keys inserted");
+        assert!(filter.layers() > 5, "{} layers", filter.layers());
+        for i in 0..10_000 {
+            assert!(filter.may_contain(format!("https://www.example.com/{i}").as_bytes()));
+        }
+        assert!(!filter.insert(b"https://www.example.com/1"));
+
+        let false_positives = (10_000..20_000)
+            .filter(|i| filter.may_contain(format!("https://www.example.com/{i}").as_bytes()))
+            .count();
+        let estimated = filter.estimated_false_positive_rate();
+        assert!(estimated < 0.02, "{estimated} estimated");
+        assert!(false_positives < 400, "{false_positives} false positives");
+    }
+}
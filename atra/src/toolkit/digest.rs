@@ -13,7 +13,10 @@
 // limitations under the License.
 
 use data_encoding::BASE32;
+use std::hash::Hasher;
+use std::io::Write;
 use std::sync::LazyLock;
+use twox_hash::xxh3::HasherExt;
 
 static EMPTY_HASH: LazyLock<Vec<u8>> = LazyLock::new(|| labeled_xxh128_digest_impl(b""));
 
@@ -36,3 +39,36 @@ pub fn labeled_xxh128_digest<B: AsRef<[u8]>>(data: B) -> Vec<u8> {
     }
     labeled_xxh128_digest_impl(data)
 }
+
+/// A streaming variant of [labeled_xxh128_digest], allows to digest data
+/// that is too big to be held in memory at once.
+#[derive(Default)]
+pub struct LabeledXxh128Digester {
+    hasher: twox_hash::xxh3::Hash128,
+}
+
+impl LabeledXxh128Digester {
+    /// Adds [data] to the digest.
+    pub fn update(&mut self, data: &[u8]) {
+        self.hasher.write(data)
+    }
+
+    /// Returns the labeled digest of all the data seen so far.
+    pub fn finish(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend(b"XXH128:");
+        output.extend(BASE32.encode(&self.hasher.finish_ext().to_be_bytes()).as_bytes());
+        output
+    }
+}
+
+impl Write for LabeledXxh128Digester {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,563 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Similarity hashes of page bodies for a near-duplicate analysis.
+//!
+//! [FuzzyHash] is a context triggered piecewise hash in the style of ssdeep over the raw bytes,
+//! [SimHash] a 64 bit simhash over the word shingles of a text.
+
+use crate::data::{ExternalFileReader, RawData, RawVecData};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{BufReader, Write};
+use std::str::FromStr;
+use thiserror::Error;
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCK_SIZE: u32 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const NUM_BLOCK_HASHES: usize = 31;
+const HASH_INIT: u32 = 0x28021967;
+const HASH_PRIME: u32 = 0x01000193;
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const fn block_size(level: usize) -> u32 {
+    MIN_BLOCK_SIZE << level
+}
+
+/// The rolling hash over the last [ROLLING_WINDOW] bytes deciding where a piece ends.
+#[derive(Debug, Default)]
+struct RollingHash {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl RollingHash {
+    fn update(&mut self, c: u8) {
+        self.h2 = self
+            .h2
+            .wrapping_sub(self.h1)
+            .wrapping_add(ROLLING_WINDOW as u32 * c as u32);
+        self.h1 = self
+            .h1
+            .wrapping_add(c as u32)
+            .wrapping_sub(self.window[self.n] as u32);
+        self.window[self.n] = c;
+        self.n = (self.n + 1) % ROLLING_WINDOW;
+        self.h3 = (self.h3 << 5) ^ c as u32;
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+/// The pieces hashed for a single block size.
+#[derive(Debug, Clone)]
+struct BlockHash {
+    h: u32,
+    half_h: u32,
+    digest: Vec<u8>,
+    /// The last piece after the digest reached its maximum length.
+    last: Option<u8>,
+    /// The last piece of the half digest after it reached its maximum length.
+    half_last: Option<u8>,
+}
+
+impl BlockHash {
+    fn new(h: u32, half_h: u32) -> Self {
+        Self {
+            h,
+            half_h,
+            digest: Vec::with_capacity(SPAMSUM_LENGTH),
+            last: None,
+            half_last: None,
+        }
+    }
+}
+
+/// Computes a [FuzzyHash] of data that is too big to be held in memory at once.
+/// The result does not depend on the way the data is split into chunks.
+#[derive(Debug)]
+pub struct FuzzyHasher {
+    roll: RollingHash,
+    levels: Vec<BlockHash>,
+    start: usize,
+    total: u64,
+}
+
+impl Default for FuzzyHasher {
+    fn default() -> Self {
+        Self {
+            roll: RollingHash::default(),
+            levels: vec![BlockHash::new(HASH_INIT, HASH_INIT)],
+            start: 0,
+            total: 0,
+        }
+    }
+}
+
+impl FuzzyHasher {
+    /// Adds [data] to the hash.
+    pub fn update(&mut self, data: &[u8]) {
+        for &c in data {
+            self.step(c);
+        }
+    }
+
+    fn step(&mut self, c: u8) {
+        self.total += 1;
+        self.roll.update(c);
+        let sum = self.roll.sum();
+        for level in &mut self.levels[self.start..] {
+            level.h = level.h.wrapping_mul(HASH_PRIME) ^ c as u32;
+            level.half_h = level.half_h.wrapping_mul(HASH_PRIME) ^ c as u32;
+        }
+        // The block sizes double, a piece that does not end for a level does not end for the larger ones.
+        let mut i = self.start;
+        while i < self.levels.len() && sum % block_size(i) == block_size(i) - 1 {
+            if self.levels[i].digest.is_empty()
+                && self.levels[i].last.is_none()
+                && self.levels.len() < NUM_BLOCK_HASHES
+            {
+                let largest = &self.levels[self.levels.len() - 1];
+                let forked = BlockHash::new(largest.h, largest.half_h);
+                self.levels.push(forked);
+            }
+            let level = &mut self.levels[i];
+            let piece = BASE64[(level.h % 64) as usize];
+            level.half_last = Some(BASE64[(level.half_h % 64) as usize]);
+            if level.digest.len() < SPAMSUM_LENGTH - 1 {
+                level.digest.push(piece);
+                level.h = HASH_INIT;
+                if level.digest.len() < SPAMSUM_LENGTH / 2 {
+                    level.half_h = HASH_INIT;
+                    level.half_last = None;
+                }
+            } else {
+                level.last = Some(piece);
+                self.try_reduce();
+            }
+            i += 1;
+        }
+    }
+
+    /// Drops the smallest block size once it is too small for the data seen so far.
+    fn try_reduce(&mut self) {
+        if self.levels.len() - self.start < 2
+            || (block_size(self.start) as u64 * SPAMSUM_LENGTH as u64) >= self.total
+            || self.levels[self.start + 1].digest.len() < SPAMSUM_LENGTH / 2
+        {
+            return;
+        }
+        self.start += 1;
+    }
+
+    /// Returns the hash of all the data seen so far.
+    pub fn finish(&self) -> FuzzyHash {
+        let tail = self.roll.sum() != 0;
+        let mut i = self.start;
+        while (block_size(i) as u64 * SPAMSUM_LENGTH as u64) < self.total
+            && i < self.levels.len() - 1
+        {
+            i += 1;
+        }
+        while i > self.start && self.levels[i].digest.len() < SPAMSUM_LENGTH / 2 {
+            i -= 1;
+        }
+
+        let level = &self.levels[i];
+        let mut first = level.digest.clone();
+        if tail {
+            first.push(BASE64[(level.h % 64) as usize]);
+        } else if let Some(last) = level.last {
+            first.push(last);
+        }
+        let mut second = Vec::with_capacity(SPAMSUM_LENGTH / 2);
+        if let Some(next) = self.levels.get(i + 1) {
+            second.extend_from_slice(&next.digest[..next.digest.len().min(SPAMSUM_LENGTH / 2 - 1)]);
+            if tail {
+                second.push(BASE64[(next.half_h % 64) as usize]);
+            } else if let Some(last) = next.half_last {
+                second.push(last);
+            }
+        } else if tail {
+            second.push(BASE64[(level.h % 64) as usize]);
+        }
+        FuzzyHash {
+            block_size: block_size(i),
+            // Only contains chars of BASE64.
+            first: String::from_utf8(first).unwrap(),
+            second: String::from_utf8(second).unwrap(),
+        }
+    }
+}
+
+impl Write for FuzzyHasher {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A fuzzy hash of the form `<block size>:<hash>:<hash of the double block size>`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FuzzyHash {
+    block_size: u32,
+    first: String,
+    second: String,
+}
+
+impl FuzzyHash {
+    /// The fuzzy hash of [data].
+    pub fn of(data: &[u8]) -> Self {
+        let mut hasher = FuzzyHasher::default();
+        hasher.update(data);
+        hasher.finish()
+    }
+
+    /// The fuzzy hash of the payload of [content], none if there is no payload.
+    /// External files are read in chunks.
+    pub fn of_content(content: &RawVecData) -> io::Result<Option<Self>> {
+        match content {
+            RawData::None => Ok(None),
+            RawData::InMemory { data } => Ok(Some(Self::of(data))),
+            RawData::ExternalFile { path } => {
+                let mut hasher = FuzzyHasher::default();
+                io::copy(
+                    &mut BufReader::new(ExternalFileReader::open(path)?),
+                    &mut hasher,
+                )?;
+                Ok(Some(hasher.finish()))
+            }
+        }
+    }
+
+    /// The similarity of two hashes from 0 (unrelated) to 100 (identical or nearly identical).
+    /// Hashes of different block sizes are comparable if one block size is the double of the other.
+    pub fn similarity(&self, other: &FuzzyHash) -> u32 {
+        let (a, b) = (self.block_size, other.block_size);
+        if a != b && a.checked_mul(2) != Some(b) && b.checked_mul(2) != Some(a) {
+            return 0;
+        }
+        let self_first = eliminate_sequences(&self.first);
+        let self_second = eliminate_sequences(&self.second);
+        let other_first = eliminate_sequences(&other.first);
+        let other_second = eliminate_sequences(&other.second);
+        if a == b && self_first == other_first && self_second == other_second {
+            return 100;
+        }
+        if a == b {
+            score_strings(&self_first, &other_first, a).max(score_strings(
+                &self_second,
+                &other_second,
+                a.saturating_mul(2),
+            ))
+        } else if a == b * 2 {
+            score_strings(&self_first, &other_second, a)
+        } else {
+            score_strings(&self_second, &other_first, b)
+        }
+    }
+}
+
+/// Shortens runs of the same char to three, they carry little information.
+fn eliminate_sequences(value: &str) -> Vec<u8> {
+    let mut result: Vec<u8> = Vec::with_capacity(value.len());
+    for c in value.bytes() {
+        if result.len() >= 3 && result[result.len() - 3..].iter().all(|&last| last == c) {
+            continue;
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn has_common_substring(a: &[u8], b: &[u8]) -> bool {
+    a.windows(ROLLING_WINDOW)
+        .any(|window| b.windows(ROLLING_WINDOW).any(|other| window == other))
+}
+
+/// The edit distance where a substitution costs as much as a deletion and an insertion.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 2 };
+            current[j + 1] = (previous[j + 1] + 1).min(current[j] + 1).min(substitution);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+fn score_strings(a: &[u8], b: &[u8], block_size: u32) -> u32 {
+    if a.len() > SPAMSUM_LENGTH || b.len() > SPAMSUM_LENGTH || !has_common_substring(a, b) {
+        return 0;
+    }
+    let distance = edit_distance(a, b) * SPAMSUM_LENGTH / (a.len() + b.len());
+    let distance = (100 * distance / SPAMSUM_LENGTH) as u32;
+    if distance >= 100 {
+        return 0;
+    }
+    let score = 100 - distance;
+    // Short hashes of small block sizes match by chance, their score is limited.
+    if block_size >= (99 + ROLLING_WINDOW as u32) / ROLLING_WINDOW as u32 * MIN_BLOCK_SIZE {
+        return score;
+    }
+    score.min(block_size / MIN_BLOCK_SIZE * a.len().min(b.len()) as u32)
+}
+
+impl Display for FuzzyHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.block_size, self.first, self.second)
+    }
+}
+
+/// A fuzzy hash that is not of the form `<block size>:<hash>:<hash>`.
+#[derive(Debug, Clone, Error)]
+#[error("The fuzzy hash {0:?} is not of the form <block size>:<hash>:<hash>!")]
+pub struct InvalidFuzzyHash(String);
+
+impl FromStr for FuzzyHash {
+    type Err = InvalidFuzzyHash;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (Some(block_size), Some(first), Some(second)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(InvalidFuzzyHash(s.to_string()));
+        };
+        let block_size = block_size
+            .parse()
+            .map_err(|_| InvalidFuzzyHash(s.to_string()))?;
+        let is_hash = |value: &str| {
+            value.len() <= SPAMSUM_LENGTH && value.bytes().all(|c| BASE64.contains(&c))
+        };
+        if !is_hash(first) || !is_hash(second) {
+            return Err(InvalidFuzzyHash(s.to_string()));
+        }
+        Ok(Self {
+            block_size,
+            first: first.to_string(),
+            second: second.to_string(),
+        })
+    }
+}
+
+impl Serialize for FuzzyHash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for FuzzyHash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A 64 bit simhash over the shingles of three words of a text, similar texts differ in few bits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SimHash(pub u64);
+
+impl SimHash {
+    /// The number of words in a shingle.
+    const SHINGLE: usize = 3;
+
+    /// The simhash of the words of [text], words are compared case insensitive.
+    pub fn of_text(text: &str) -> Self {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            .map(str::to_lowercase)
+            .collect();
+        let mut weights = [0i64; 64];
+        for shingle in words.windows(Self::SHINGLE.min(words.len()).max(1)) {
+            let hash = fnv1a(shingle);
+            for (bit, weight) in weights.iter_mut().enumerate() {
+                if (hash >> bit) & 1 == 1 {
+                    *weight += 1;
+                } else {
+                    *weight -= 1;
+                }
+            }
+        }
+        Self(
+            weights
+                .iter()
+                .enumerate()
+                .filter(|(_, weight)| **weight > 0)
+                .fold(0, |hash, (bit, _)| hash | 1 << bit),
+        )
+    }
+
+    /// The share of equal bits from 0 to 100.
+    pub fn similarity(&self, other: &SimHash) -> u32 {
+        (64 - (self.0 ^ other.0).count_ones()) * 100 / 64
+    }
+}
+
+impl Display for SimHash {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// The 64 bit FNV-1a hash of the words joined by spaces.
+fn fnv1a(words: &[String]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for (i, word) in words.iter().enumerate() {
+        let separator = if i == 0 { None } else { Some(b' ') };
+        for c in separator.into_iter().chain(word.bytes()) {
+            hash ^= c as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const WORDS: [&str; 32] = [
+        "crawler",
+        "archive",
+        "origin",
+        "budget",
+        "robots",
+        "sitemap",
+        "politeness",
+        "header",
+        "payload",
+        "digest",
+        "queue",
+        "seed",
+        "link",
+        "depth",
+        "language",
+        "encoding",
+        "warc",
+        "record",
+        "body",
+        "status",
+        "redirect",
+        "cookie",
+        "proxy",
+        "session",
+        "timeout",
+        "retry",
+        "content",
+        "format",
+        "extractor",
+        "graph",
+        "score",
+        "filter",
+    ];
+
+    /// A page of 60 generated paragraphs, [changed] replaces one of them.
+    fn page(seed: u64, title: &str, changed: Option<usize>) -> String {
+        let mut state = seed;
+        let mut lines = vec![format!(
+            "<!DOCTYPE html><html><head><title>{title}</title></head><body>"
+        )];
+        for paragraph in 0..60 {
+            let mut words = Vec::with_capacity(24);
+            for _ in 0..24 {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                words.push(WORDS[(state >> 33) as usize % WORDS.len()]);
+            }
+            if changed == Some(paragraph) {
+                words = vec![
+                    "this",
+                    "paragraph",
+                    "was",
+                    "rewritten",
+                    "by",
+                    "the",
+                    "editor",
+                ];
+            }
+            lines.push(format!("<p>{}</p>", words.join(" ")));
+        }
+        lines.push("</body></html>".to_string());
+        lines.join("\n")
+    }
+
+    #[test]
+    fn near_identical_pages_are_similar() {
+        let original = page(1, "Atra", None);
+        let edited = page(1, "Atra news", Some(30));
+        let unrelated = page(2, "Other", None);
+
+        let original_hash = FuzzyHash::of(original.as_bytes());
+        let edited_hash = FuzzyHash::of(edited.as_bytes());
+        let unrelated_hash = FuzzyHash::of(unrelated.as_bytes());
+        assert_eq!(100, original_hash.similarity(&original_hash));
+        assert!(
+            original_hash.similarity(&edited_hash) >= 80,
+            "{original_hash} {edited_hash}"
+        );
+        assert!(
+            original_hash.similarity(&unrelated_hash) <= 20,
+            "{original_hash} {unrelated_hash}"
+        );
+
+        let original_sim = SimHash::of_text(&original);
+        assert!(original_sim.similarity(&SimHash::of_text(&edited)) >= 80);
+        assert!(original_sim.similarity(&SimHash::of_text(&unrelated)) < 75);
+    }
+
+    #[test]
+    fn streaming_does_not_change_the_hash() {
+        let data = page(3, "Chunks", None);
+        let mut hasher = FuzzyHasher::default();
+        for chunk in data.as_bytes().chunks(100) {
+            hasher.update(chunk);
+        }
+        assert_eq!(FuzzyHash::of(data.as_bytes()), hasher.finish());
+    }
+
+    #[test]
+    fn hashes_survive_a_round_trip() {
+        let hash = FuzzyHash::of(page(4, "Round trip", None).as_bytes());
+        let parsed: FuzzyHash = hash.to_string().parse().unwrap();
+        assert_eq!(hash, parsed);
+        assert!("96:abc".parse::<FuzzyHash>().is_err());
+        assert!("x:abc:def".parse::<FuzzyHash>().is_err());
+    }
+}
@@ -12,18 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::cmp::min;
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use std::path::Path;
 
 use camino::Utf8PathBuf;
 use isolang::Language;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use ubyte::ByteUnit;
-use whatlang::{Info, Script};
+use whatlang::Script;
 use xml::reader::{ParserConfig2, XmlEvent};
 use xml::EventReader;
 
@@ -32,12 +30,19 @@ use crate::data::Decoded;
 use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::format::AtraFileInformation;
 use crate::toolkit::isolang_ext::ToIsoLang;
+use crate::toolkit::plain_text::html_to_text;
+use crate::toolkit::utf8::{read_utf8_sample, truncate_at_char_boundary};
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 pub struct LanguageInformation {
     script: Script,
     lang: Language,
     confidence: f64,
+    /// Set if the confidence or the number of words of the text is below the limits of the
+    /// crawl config, see [LanguageCertainty]. Not encoded with the language, a stored result
+    /// keeps it in [CrawlResultMeta::language_uncertain].
+    #[serde(skip)]
+    uncertain: bool,
 }
 
 impl LanguageInformation {
@@ -61,12 +66,24 @@ impl LanguageInformation {
         self.confidence
     }
 
+    /// Returns true if the language is not reliable enough to run the GDBR classifier.
+    pub fn is_uncertain(&self) -> bool {
+        self.uncertain
+    }
+
+    /// Returns the language marked as [uncertain].
+    pub fn with_uncertain(mut self, uncertain: bool) -> Self {
+        self.uncertain = uncertain;
+        self
+    }
+
     #[cfg(test)]
     pub const fn new(script: Script, lang: Language, confidence: f64) -> Self {
         Self {
             script,
             lang,
             confidence,
+            uncertain: false,
         }
     }
 
@@ -84,14 +101,35 @@ impl PartialEq for LanguageInformation {
     }
 }
 
-impl From<Info> for LanguageInformation {
-    fn from(value: Info) -> Self {
+/// The limits below which a detected language is marked as uncertain.
+#[derive(Debug, Copy, Clone)]
+struct LanguageCertainty {
+    min_confidence: f64,
+    min_tokens: usize,
+}
+
+impl LanguageCertainty {
+    fn of(context: &impl SupportsConfigs) -> Self {
+        let crawl = &context.configs().crawl;
         Self {
-            script: value.script(),
-            lang: value.lang().to_isolang(),
-            confidence: value.confidence(),
+            min_confidence: f64::from(crawl.min_language_confidence.min(100)) / 100.0,
+            min_tokens: crawl.min_language_tokens,
         }
     }
+
+    /// Detects the language of [text]. It is uncertain if the confidence of the detector is
+    /// below the limit or if [text] has fewer words than the limit.
+    fn detect(&self, text: &str) -> Option<LanguageInformation> {
+        let info = whatlang::detect(text)?;
+        let uncertain = info.confidence() < self.min_confidence
+            || (self.min_tokens > 0 && text.split_whitespace().count() < self.min_tokens);
+        Some(LanguageInformation {
+            script: info.script(),
+            lang: info.lang().to_isolang(),
+            confidence: info.confidence(),
+            uncertain,
+        })
+    }
 }
 
 pub fn detect_language<'a>(
@@ -99,51 +137,25 @@ pub fn detect_language<'a>(
     file_type: &AtraFileInformation,
     decoded: &Decoded<String, Utf8PathBuf>,
 ) -> Result<Option<LanguageInformation>, std::io::Error> {
-    const MAX_IN_MEMORY_FOR_LANG: u64 = 1u64 * ByteUnit::MB.as_u64();
-
-    fn create_limited_sample_file_reader(
-        context: &impl SupportsConfigs,
-        path: impl AsRef<Path>,
-    ) -> std::io::Result<impl Read> {
-        let max_bytes = if let Some(mfs) = context.configs().crawl.max_file_size {
-            min(mfs.get(), MAX_IN_MEMORY_FOR_LANG)
-        } else {
-            MAX_IN_MEMORY_FOR_LANG
-        };
-        Ok(BufReader::new(
-            File::options().read(true).open(path)?.take(max_bytes),
-        ))
-    }
-
-    fn read_sample_file(
-        context: &impl SupportsConfigs,
-        path: impl AsRef<Path>,
-    ) -> std::io::Result<Vec<u8>> {
-        let mut reader = create_limited_sample_file_reader(context, path)?;
-        let mut v = Vec::new();
-        reader.read_to_end(&mut v)?;
-        Ok(v)
-    }
+    let sample_limit = text_sample_limit(context);
+    let certainty = LanguageCertainty::of(context);
 
     match file_type.format {
         InterpretedProcessibleFileFormat::HTML => match decoded {
-            Decoded::InMemory { data, .. } => {
-                let text = scraper::html::Html::parse_document(data.as_str())
-                    .root_element()
-                    .text()
-                    .collect::<String>();
-                Ok(whatlang::detect(&text).map(From::from))
+            Decoded::InMemory { data, .. } => Ok(certainty.detect(&html_to_text(data))),
+            Decoded::OffMemory { .. } => {
+                let sample = read_text_sample(decoded, sample_limit)?.unwrap_or_default();
+                Ok(certainty.detect(&html_to_text(&sample)))
             }
-            _ => Ok(None),
+            Decoded::None => Ok(None),
         },
         InterpretedProcessibleFileFormat::PlainText
         | InterpretedProcessibleFileFormat::StructuredPlainText
         | InterpretedProcessibleFileFormat::Decodeable => match decoded {
-            Decoded::InMemory { data, .. } => Ok(whatlang::detect(&data).map(From::from)),
-            Decoded::OffMemory { reference, .. } => {
-                let data = read_sample_file(context, reference)?;
-                let text = String::from_utf8_lossy(&data);
-                Ok(whatlang::detect(&text).map(From::from))
+            Decoded::InMemory { data, .. } => Ok(certainty.detect(data)),
+            Decoded::OffMemory { .. } => {
+                let sample = read_text_sample(decoded, sample_limit)?.unwrap_or_default();
+                Ok(certainty.detect(&sample))
             }
             Decoded::None => Ok(None),
         },
@@ -171,26 +183,29 @@ pub fn detect_language<'a>(
             match decoded {
                 Decoded::InMemory { data, .. } => {
                     if let Ok(deser) = serde_json::from_str::<Value>(data.as_str()) {
-                        Ok(whatlang::detect(&extract_string(deser)).map(From::from))
+                        Ok(certainty.detect(&extract_string(deser)))
                     } else {
-                        Ok(whatlang::detect(data).map(From::from))
+                        Ok(certainty.detect(data))
                     }
                 }
-                Decoded::OffMemory { reference, .. } => {
-                    let data = read_sample_file(context, reference)?;
-                    let text = String::from_utf8_lossy(&data);
+                Decoded::OffMemory { .. } => {
+                    let text = read_text_sample(decoded, sample_limit)?.unwrap_or_default();
                     if let Ok(deser) = serde_json::from_str::<Value>(&text) {
-                        Ok(whatlang::detect(&extract_string(deser)).map(From::from))
+                        Ok(certainty.detect(&extract_string(deser)))
                     } else {
-                        Ok(whatlang::detect(&text).map(From::from))
+                        Ok(certainty.detect(&text))
                     }
                 }
                 Decoded::None => Ok(None),
             }
         }
-        InterpretedProcessibleFileFormat::XML => {
-            fn analyze_xml<R: Read>(s: EventReader<R>) -> Option<LanguageInformation> {
-                let mut collected = String::with_capacity(MAX_IN_MEMORY_FOR_LANG as usize);
+        InterpretedProcessibleFileFormat::XML | InterpretedProcessibleFileFormat::Feed => {
+            fn analyze_xml<R: Read>(
+                s: EventReader<R>,
+                sample_limit: usize,
+                certainty: LanguageCertainty,
+            ) -> Option<LanguageInformation> {
+                let mut collected = String::with_capacity(sample_limit);
                 for event in s {
                     if let Ok(event) = event {
                         match event {
@@ -202,7 +217,7 @@ pub fn detect_language<'a>(
                         }
                     }
                 }
-                whatlang::detect(&collected).map(From::from)
+                certainty.detect(&collected)
             }
 
             let cfg = ParserConfig2::new()
@@ -210,32 +225,41 @@ pub fn detect_language<'a>(
                 .ignore_comments(true);
 
             match decoded {
-                Decoded::InMemory { data, .. } => Ok(analyze_xml(EventReader::new_with_config(
-                    data.as_bytes(),
-                    cfg,
-                ))),
+                Decoded::InMemory { data, .. } => Ok(analyze_xml(
+                    EventReader::new_with_config(data.as_bytes(), cfg),
+                    sample_limit,
+                    certainty,
+                )),
                 Decoded::OffMemory { reference, .. } => {
-                    let reader = create_limited_sample_file_reader(context, reference)?;
-                    Ok(analyze_xml(EventReader::new_with_config(reader, cfg)))
+                    let reader = BufReader::new(
+                        File::options()
+                            .read(true)
+                            .open(reference)?
+                            .take(sample_limit as u64),
+                    );
+                    Ok(analyze_xml(
+                        EventReader::new_with_config(reader, cfg),
+                        sample_limit,
+                        certainty,
+                    ))
                 }
                 Decoded::None => Ok(None),
             }
         }
         InterpretedProcessibleFileFormat::RTF => {
-            fn analyze_rdf(s: &str) -> Option<LanguageInformation> {
+            fn analyze_rdf(s: &str, certainty: LanguageCertainty) -> Option<LanguageInformation> {
                 if let Ok(value) = rtf_parser::document::RtfDocument::try_from(s) {
-                    whatlang::detect(&value.get_text()).map(From::from)
+                    certainty.detect(&value.get_text())
                 } else {
-                    whatlang::detect(s).map(From::from)
+                    certainty.detect(s)
                 }
             }
 
             match decoded {
-                Decoded::InMemory { data, .. } => Ok(analyze_rdf(data)),
-                Decoded::OffMemory { reference, .. } => {
-                    let data = read_sample_file(context, reference)?;
-                    let text = String::from_utf8_lossy(&data);
-                    Ok(analyze_rdf(&text))
+                Decoded::InMemory { data, .. } => Ok(analyze_rdf(data, certainty)),
+                Decoded::OffMemory { .. } => {
+                    let text = read_text_sample(decoded, sample_limit)?.unwrap_or_default();
+                    Ok(analyze_rdf(&text, certainty))
                 }
                 Decoded::None => Ok(None),
             }
@@ -251,3 +275,111 @@ pub fn detect_language<'a>(
         _ => Ok(None),
     }
 }
+
+/// The size of the text samples in bytes, see [read_text_sample].
+pub fn text_sample_limit(context: &impl SupportsConfigs) -> usize {
+    usize::try_from(context.configs().crawl.text_sample_size).unwrap_or(usize::MAX)
+}
+
+/// Returns at most [max_bytes] bytes of the [decoded] text without splitting a code point.
+/// Documents that are not kept in memory are read in chunks until the sample is complete,
+/// for the same text both variants return the same sample.
+pub fn read_text_sample<'a>(
+    decoded: &'a Decoded<String, Utf8PathBuf>,
+    max_bytes: usize,
+) -> std::io::Result<Option<Cow<'a, str>>> {
+    match decoded {
+        Decoded::InMemory { data, .. } => Ok(Some(Cow::Borrowed(truncate_at_char_boundary(
+            data, max_bytes,
+        )))),
+        Decoded::OffMemory { reference, .. } => {
+            let file = File::options().read(true).open(reference)?;
+            Ok(Some(Cow::Owned(read_utf8_sample(file, max_bytes)?)))
+        }
+        Decoded::None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::config::Config;
+    use crate::data::Decoded;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
+    use crate::test_impls::{FakeClientProvider, TestContext};
+    use crate::toolkit::{detect_language, LanguageInformation};
+    use encoding_rs::UTF_8;
+    use isolang::Language;
+    use serde::Serialize;
+    use whatlang::Script;
+
+    fn detect(config: &Config, text: &str) -> Option<LanguageInformation> {
+        let context = TestContext::new(config.clone(), FakeClientProvider::new());
+        let file_information =
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::PlainText, None, None);
+        let decoded = Decoded::new_in_memory(text.to_string(), UTF_8, false);
+        detect_language(&context, &file_information, &decoded).unwrap()
+    }
+
+    #[test]
+    fn marks_the_languages_below_the_limits_as_uncertain() {
+        let mut config = Config::default();
+        config.crawl.min_language_confidence = 80;
+        config.crawl.min_language_tokens = 5;
+
+        let german = detect(
+            &config,
+            "Wir verwenden Cookies, um Ihnen das bestmögliche Nutzererlebnis auf unserer \
+            Webseite zu bieten. Wenn Sie die Seite weiterhin nutzen, stimmen Sie der \
+            Verwendung von Cookies und der Verarbeitung Ihrer Daten zu.",
+        )
+        .unwrap();
+        assert_eq!(Language::Deu, german.lang());
+        assert!(german.confidence() >= 0.8);
+        assert!(!german.is_uncertain());
+
+        let english = detect(
+            &config,
+            "We use cookies to give you the best possible experience on our website. If you \
+            continue to use this site, you agree to the use of cookies and the processing \
+            of your data.",
+        )
+        .unwrap();
+        assert_eq!(Language::Eng, english.lang());
+        assert!(english.confidence() >= 0.8);
+        assert!(!english.is_uncertain());
+
+        // Long enough to be detected, but fewer words than the limit.
+        let snippet = "Datenschutzerklärung und Impressum";
+        let short = detect(&config, snippet).unwrap();
+        assert!(short.is_uncertain());
+        let without_limits = detect(&Config::default(), snippet).unwrap();
+        assert!(!without_limits.is_uncertain());
+    }
+
+    /// The language before the certainty.
+    #[derive(Serialize)]
+    struct OldLanguageInformation {
+        script: Script,
+        lang: Language,
+        confidence: f64,
+    }
+
+    #[test]
+    fn the_language_of_an_older_version_is_decoded() {
+        let old = bincode::serialize(&OldLanguageInformation {
+            script: Script::Latin,
+            lang: Language::Deu,
+            confidence: 0.5,
+        })
+        .unwrap();
+        let decoded: LanguageInformation = bincode::deserialize(&old).unwrap();
+        assert_eq!(Language::Deu, decoded.lang());
+        assert_eq!(0.5, decoded.confidence());
+        assert!(!decoded.is_uncertain());
+
+        let current =
+            LanguageInformation::new(Script::Latin, Language::Deu, 0.5).with_uncertain(true);
+        assert_eq!(old, bincode::serialize(&current).unwrap());
+    }
+}
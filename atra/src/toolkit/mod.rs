@@ -12,25 +12,27 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod bloom;
 mod case_insensitive_str;
 pub mod digest;
 pub mod domains;
 pub mod dropping;
 pub mod extension_extractor;
-mod generic_cursor;
+pub mod fuzzy_hash;
 pub mod header_map_extensions;
 pub mod isolang_ext;
 mod language_detection;
+pub mod plain_text;
 pub mod selectors;
 pub mod serde_ext;
+pub mod text_normalization;
+pub mod unwind;
 pub mod utf8;
 
 pub use language_detection::*;
 
 pub use case_insensitive_str::*;
 
-pub use generic_cursor::CursorWithLifeline;
-
 /// Compare two optionals by a function.
 #[cfg(test)]
 pub fn comp_opt<T, F: FnOnce(T, T) -> bool>(a: Option<T>, b: Option<T>, f: F) -> bool {
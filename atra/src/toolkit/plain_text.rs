@@ -0,0 +1,106 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::data::Decoded;
+use crate::format::supported::InterpretedProcessibleFileFormat;
+use crate::format::AtraFileInformation;
+use camino::Utf8PathBuf;
+use std::borrow::Cow;
+
+/// Returns the text content of the [html] document, the same text the language detection sees.
+pub fn html_to_text(html: &str) -> String {
+    scraper::html::Html::parse_document(html)
+        .root_element()
+        .text()
+        .collect()
+}
+
+/// Returns the plain text of the [decoded] document. HTML is reduced to its text content,
+/// plain text is kept as it is. Returns None for every other format and if nothing was decoded.
+pub fn plain_text(
+    file_information: &AtraFileInformation,
+    decoded: &Decoded<String, Utf8PathBuf>,
+) -> std::io::Result<Option<String>> {
+    let is_html = match file_information.format {
+        InterpretedProcessibleFileFormat::HTML => true,
+        InterpretedProcessibleFileFormat::PlainText
+        | InterpretedProcessibleFileFormat::StructuredPlainText => false,
+        _ => return Ok(None),
+    };
+    let text = match decoded {
+        Decoded::InMemory { data, .. } => Cow::Borrowed(data.as_str()),
+        Decoded::OffMemory { reference, .. } => Cow::Owned(std::fs::read_to_string(reference)?),
+        Decoded::None => return Ok(None),
+    };
+    Ok(Some(if is_html {
+        html_to_text(&text)
+    } else {
+        text.into_owned()
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::plain_text;
+    use crate::data::Decoded;
+    use crate::format::supported::InterpretedProcessibleFileFormat;
+    use crate::format::AtraFileInformation;
+    use camino::Utf8PathBuf;
+
+    fn text_of(format: InterpretedProcessibleFileFormat, data: &str) -> Option<String> {
+        plain_text(
+            &AtraFileInformation::new(format, None, None),
+            &Decoded::<String, Utf8PathBuf>::new_in_memory(
+                data.to_string(),
+                encoding_rs::UTF_8,
+                false,
+            ),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reduces_html_to_its_text() {
+        assert_eq!(
+            Some("TitleHello World!".to_string()),
+            text_of(
+                InterpretedProcessibleFileFormat::HTML,
+                "<html><head><title>Title</title></head><body><p>Hello <b>World</b>!</p></body></html>"
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_plain_text_and_ignores_other_formats() {
+        assert_eq!(
+            Some("<b>not html</b>".to_string()),
+            text_of(
+                InterpretedProcessibleFileFormat::PlainText,
+                "<b>not html</b>"
+            )
+        );
+        assert_eq!(
+            None,
+            text_of(InterpretedProcessibleFileFormat::JSON, r#"{"a": "b"}"#)
+        );
+        assert_eq!(
+            None,
+            plain_text(
+                &AtraFileInformation::new(InterpretedProcessibleFileFormat::HTML, None, None),
+                &Decoded::None
+            )
+            .unwrap()
+        );
+    }
+}
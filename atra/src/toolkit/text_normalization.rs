@@ -0,0 +1,249 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use encoding_rs::Encoding;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+const BOM: char = '\u{FEFF}';
+
+/// The configuration of the normalization applied to decoded text before it is persisted.
+#[derive(Debug, Copy, Clone, Default, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct TextNormalizationConfig {
+    /// Collapses every run of whitespace inside a line to a single space. (default: false)
+    pub collapse_whitespace: bool,
+}
+
+/// Records how a text was transformed, a flag is only set if the step changed the text.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, Eq, PartialEq, JsonSchema)]
+pub struct TextProvenance {
+    /// The encoding the text was decoded from.
+    pub original_encoding: Option<String>,
+    /// At least one byte order mark was removed.
+    pub bom_stripped: bool,
+    /// `\r\n` or `\r` were replaced by `\n`.
+    pub line_endings_normalized: bool,
+    /// Runs of whitespace were collapsed to a single space.
+    pub whitespace_collapsed: bool,
+    /// The text was not in Unicode NFC.
+    pub nfc_normalized: bool,
+}
+
+impl TextProvenance {
+    /// Returns true if the text was not changed.
+    pub fn is_unchanged(&self) -> bool {
+        !(self.bom_stripped
+            || self.line_endings_normalized
+            || self.whitespace_collapsed
+            || self.nfc_normalized)
+    }
+}
+
+/// A text in NFC with `\n` line endings and without byte order marks.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NormalizedText<'a> {
+    pub text: Cow<'a, str>,
+    pub provenance: TextProvenance,
+}
+
+/// Normalizes the decoded [text] according to [config]. The [original_encoding] is only
+/// recorded in the provenance. Normalizing an already normalized text changes nothing.
+pub fn normalize_text<'a>(
+    text: &'a str,
+    original_encoding: Option<&'static Encoding>,
+    config: TextNormalizationConfig,
+) -> NormalizedText<'a> {
+    let mut provenance = TextProvenance {
+        original_encoding: original_encoding.map(|value| value.name().to_string()),
+        ..TextProvenance::default()
+    };
+    let mut text = Cow::Borrowed(text);
+
+    if text.contains(BOM) {
+        text = Cow::Owned(text.replace(BOM, ""));
+        provenance.bom_stripped = true;
+    }
+
+    if text.contains('\r') {
+        text = Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"));
+        provenance.line_endings_normalized = true;
+    }
+
+    if config.collapse_whitespace {
+        if let Some(collapsed) = collapse_whitespace(&text) {
+            text = Cow::Owned(collapsed);
+            provenance.whitespace_collapsed = true;
+        }
+    }
+
+    let is_nfc = match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => true,
+        IsNormalized::No => false,
+        IsNormalized::Maybe => text.nfc().eq(text.chars()),
+    };
+    if !is_nfc {
+        text = Cow::Owned(text.nfc().collect());
+        provenance.nfc_normalized = true;
+    }
+
+    NormalizedText { text, provenance }
+}
+
+/// Replaces every run of whitespace except `\n` with a single space.
+/// Returns None if there is nothing to collapse.
+fn collapse_whitespace(text: &str) -> Option<String> {
+    let is_collapsible = |c: char| c.is_whitespace() && c != '\n';
+    let mut previous_was_whitespace = false;
+    let needs_collapse = text.chars().any(|c| {
+        let collapse = is_collapsible(c) && (previous_was_whitespace || c != ' ');
+        previous_was_whitespace = is_collapsible(c);
+        collapse
+    });
+    if !needs_collapse {
+        return None;
+    }
+    let mut result = String::with_capacity(text.len());
+    previous_was_whitespace = false;
+    for c in text.chars() {
+        if is_collapsible(c) {
+            if !previous_was_whitespace {
+                result.push(' ');
+            }
+            previous_was_whitespace = true;
+        } else {
+            result.push(c);
+            previous_was_whitespace = false;
+        }
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{normalize_text, TextNormalizationConfig, BOM};
+    use encoding_rs::UTF_16LE;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use unicode_normalization::UnicodeNormalization;
+
+    const PIECES: &[&str] = &[
+        "a",
+        "e\u{0301}",
+        "\u{00E9}",
+        "A\u{030A}",
+        "\u{212B}",
+        "o\u{0308}\u{0304}",
+        "\u{0301}",
+        "\u{1100}\u{1161}",
+        " ",
+        "  ",
+        "\t",
+        "\u{00A0}",
+        "\u{2000}",
+        "\r\n",
+        "\r",
+        "\n",
+        "\n\n",
+        "\u{FEFF}",
+        "Stra\u{00DF}e",
+        "\u{1F600}",
+    ];
+
+    fn random_text(rng: &mut StdRng) -> String {
+        let len = rng.gen_range(0..24);
+        (0..len)
+            .map(|_| PIECES[rng.gen_range(0..PIECES.len())])
+            .collect()
+    }
+
+    #[test]
+    fn normalization_is_idempotent() {
+        let mut rng = StdRng::seed_from_u64(0x1515);
+        for collapse_whitespace in [false, true] {
+            let config = TextNormalizationConfig {
+                collapse_whitespace,
+            };
+            for _ in 0..2000 {
+                let text = random_text(&mut rng);
+                let once = normalize_text(&text, None, config);
+                let twice = normalize_text(&once.text, None, config);
+                assert_eq!(once.text, twice.text, "Not idempotent for {text:?}");
+                assert!(twice.provenance.is_unchanged(), "Changed again: {text:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn provenance_flags_match_the_input() {
+        let mut rng = StdRng::seed_from_u64(0xBEEF);
+        for collapse_whitespace in [false, true] {
+            let config = TextNormalizationConfig {
+                collapse_whitespace,
+            };
+            for _ in 0..2000 {
+                let text = random_text(&mut rng);
+                let normalized = normalize_text(&text, Some(UTF_16LE), config);
+                let provenance = &normalized.provenance;
+                assert_eq!(Some("UTF-16LE"), provenance.original_encoding.as_deref());
+                assert_eq!(text.contains(BOM), provenance.bom_stripped, "{text:?}");
+                assert_eq!(
+                    text.contains('\r'),
+                    provenance.line_endings_normalized,
+                    "{text:?}"
+                );
+                assert!(collapse_whitespace || !provenance.whitespace_collapsed);
+
+                let result = normalized.text.as_ref();
+                assert!(!result.contains(BOM));
+                assert!(!result.contains('\r'));
+                assert!(result.nfc().eq(result.chars()));
+                if collapse_whitespace {
+                    assert!(!result.contains("  "));
+                    assert!(!result
+                        .chars()
+                        .any(|c| c.is_whitespace() && c != ' ' && c != '\n'));
+                }
+                assert_eq!(provenance.is_unchanged(), result == text, "{text:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn normalizes_the_examples() {
+        let collapse = TextNormalizationConfig {
+            collapse_whitespace: true,
+        };
+        let normalized = normalize_text(
+            "\u{FEFF}Cafe\u{0301}\r\nna\u{0308}ive \t text\r",
+            None,
+            collapse,
+        );
+        assert_eq!("Caf\u{00E9}\nn\u{00E4}ive text\n", normalized.text);
+        assert!(normalized.provenance.bom_stripped);
+        assert!(normalized.provenance.line_endings_normalized);
+        assert!(normalized.provenance.whitespace_collapsed);
+        assert!(normalized.provenance.nfc_normalized);
+
+        let unchanged = normalize_text("Plain text\nwith two lines", None, collapse);
+        assert!(unchanged.provenance.is_unchanged());
+        assert!(matches!(unchanged.text, std::borrow::Cow::Borrowed(_)));
+
+        let kept = normalize_text("a  b", None, TextNormalizationConfig::default());
+        assert_eq!("a  b", kept.text);
+        assert!(!kept.provenance.whitespace_collapsed);
+    }
+}
@@ -441,9 +441,103 @@ where
     }
 }
 
+/// The size of the chunks read by [read_utf8_sample].
+const SAMPLE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns the longest prefix of [text] with at most [max_bytes] bytes that does not split a code point.
+pub fn truncate_at_char_boundary(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+/// Reads at most [max_bytes] bytes of UTF-8 text from [reader] in chunks. A code point split
+/// by a chunk boundary is completed with the next chunk and the sample never ends inside
+/// of a code point. Invalid sequences are replaced with the REPLACEMENT CHARACTER.
+///
+/// For valid UTF-8 the sample is the same as [truncate_at_char_boundary] of the complete text.
+pub fn read_utf8_sample<R: Read>(reader: R, max_bytes: usize) -> std::io::Result<String> {
+    read_utf8_sample_chunked(reader, max_bytes, SAMPLE_CHUNK_SIZE)
+}
+
+fn read_utf8_sample_chunked<R: Read>(
+    mut reader: R,
+    max_bytes: usize,
+    chunk_size: usize,
+) -> std::io::Result<String> {
+    /// Appends as much of [value] as fits, returns false if the sample is full.
+    fn push_limited(sample: &mut String, value: &str, max_bytes: usize) -> bool {
+        let fitting = truncate_at_char_boundary(value, max_bytes - sample.len());
+        sample.push_str(fitting);
+        fitting.len() == value.len() && sample.len() < max_bytes
+    }
+
+    let mut sample = String::new();
+    // An incomplete code point has at most 3 bytes.
+    let mut buffer = vec![0u8; chunk_size + 3];
+    let mut filled = 0usize;
+    'reading: while sample.len() < max_bytes {
+        let read = match reader.read(&mut buffer[filled..filled + chunk_size]) {
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        if read == 0 {
+            if filled > 0 {
+                // The text ends inside of a code point.
+                push_limited(&mut sample, "\u{FFFD}", max_bytes);
+            }
+            break;
+        }
+        filled += read;
+        let mut start = 0usize;
+        while start < filled {
+            match std::str::from_utf8(&buffer[start..filled]) {
+                Ok(value) => {
+                    if !push_limited(&mut sample, value, max_bytes) {
+                        break 'reading;
+                    }
+                    start = filled;
+                }
+                Err(err) => {
+                    let valid_up_to = start + err.valid_up_to();
+                    // SAFETY: from_utf8 validated the bytes up to valid_up_to.
+                    let valid =
+                        unsafe { std::str::from_utf8_unchecked(&buffer[start..valid_up_to]) };
+                    if !push_limited(&mut sample, valid, max_bytes) {
+                        break 'reading;
+                    }
+                    start = valid_up_to;
+                    match err.error_len() {
+                        Some(len) => {
+                            if !push_limited(&mut sample, "\u{FFFD}", max_bytes) {
+                                break 'reading;
+                            }
+                            start += len;
+                        }
+                        // The code point continues in the next chunk.
+                        None => break,
+                    }
+                }
+            }
+        }
+        buffer.copy_within(start..filled, 0);
+        filled -= start;
+    }
+    Ok(sample)
+}
+
 #[cfg(test)]
 mod test {
-    use crate::toolkit::utf8::{DecodedChar, RobustUtf8Reader, Utf8Reader};
+    use crate::toolkit::utf8::{
+        read_utf8_sample, read_utf8_sample_chunked, truncate_at_char_boundary, DecodedChar,
+        RobustUtf8Reader, Utf8Reader,
+    };
     use itertools::Itertools;
     use std::io::Cursor;
 
@@ -480,4 +574,35 @@ mod test {
             assert_eq!(c_original, value);
         }
     }
+
+    #[test]
+    fn samples_do_not_split_code_points() {
+        let text = "aä€𒀀".repeat(1000);
+        for chunk_size in [1, 2, 3, 5, 7, 64] {
+            for max_bytes in [0, 1, 2, 3, 4, 9, 10, 11, 4000, text.len(), text.len() + 5] {
+                let sample =
+                    read_utf8_sample_chunked(Cursor::new(text.as_bytes()), max_bytes, chunk_size)
+                        .unwrap();
+                assert_eq!(truncate_at_char_boundary(&text, max_bytes), sample);
+                assert!(!sample.contains('\u{FFFD}'));
+            }
+        }
+        assert_eq!(
+            text,
+            read_utf8_sample(Cursor::new(text.as_bytes()), usize::MAX).unwrap()
+        );
+    }
+
+    #[test]
+    fn samples_replace_invalid_sequences() {
+        let mut bytes = "ab".as_bytes().to_vec();
+        bytes.push(0b1000_0000);
+        bytes.extend_from_slice("cä".as_bytes());
+        bytes.extend_from_slice(&"€".as_bytes()[..2]);
+        for chunk_size in [1, 2, 3, 64] {
+            let sample =
+                read_utf8_sample_chunked(Cursor::new(bytes.as_slice()), 100, chunk_size).unwrap();
+            assert_eq!("ab\u{FFFD}cä\u{FFFD}", sample);
+        }
+    }
 }
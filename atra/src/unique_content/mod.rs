@@ -0,0 +1,468 @@
+// Copyright 2024. Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::contexts::traits::{SupportsConfigs, SupportsUniqueContent};
+use crate::crawl::CrawlResult;
+use crate::data::{ExternalFileReader, RawData, RawVecData};
+use crate::database::{DBActionType, DatabaseError, LazyBase64Value, RawDatabaseError};
+use crate::toolkit::digest::{labeled_xxh128_digest, LabeledXxh128Digester};
+use crate::url::{AtraOriginProvider, AtraUrlOrigin};
+use crate::{db_health_check, declare_column_families};
+use rocksdb::{Direction, IteratorMode, WriteBatch, DB};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::io::BufReader;
+use std::sync::{Arc, Mutex};
+
+/// The key prefix of the counts of an origin.
+const COUNTS_PREFIX: u8 = b'c';
+/// The key prefix of the payload digests seen on an origin.
+const DIGEST_PREFIX: u8 = b'd';
+/// The counts and digests of the whole crawl are stored with this origin.
+const GLOBAL_ORIGIN: &str = "";
+
+/// The number of fetched pages and of the unique payloads among them.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct UniqueContentCounts {
+    pub fetched: u64,
+    pub unique_payloads: u64,
+}
+
+impl UniqueContentCounts {
+    pub const fn new(fetched: u64, unique_payloads: u64) -> Self {
+        Self {
+            fetched,
+            unique_payloads,
+        }
+    }
+
+    /// The share of unique payloads among the fetched pages, none if nothing was fetched.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.fetched > 0).then(|| self.unique_payloads as f64 / self.fetched as f64)
+    }
+
+    /// Returns true if at least [min_samples] pages were fetched and less than [floor] percent
+    /// of them have a unique payload.
+    pub fn is_below(&self, floor: u8, min_samples: u64) -> bool {
+        self.fetched > 0
+            && self.fetched >= min_samples
+            && self.unique_payloads * 100 < self.fetched * floor as u64
+    }
+
+    fn registered(self, unique: bool) -> Self {
+        Self::new(self.fetched + 1, self.unique_payloads + unique as u64)
+    }
+}
+
+/// The counts of an origin and of the whole crawl after registering a payload.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RegisteredPayload {
+    /// True if the payload was not seen on the origin before.
+    pub unique: bool,
+    pub origin: UniqueContentCounts,
+    pub global: UniqueContentCounts,
+}
+
+impl RegisteredPayload {
+    /// Returns true if this payload pushed the ratio of the origin below the [floor].
+    pub fn crossed_floor(&self, floor: u8, min_samples: u64) -> bool {
+        let before = UniqueContentCounts::new(
+            self.origin.fetched - 1,
+            self.origin.unique_payloads - self.unique as u64,
+        );
+        self.origin.is_below(floor, min_samples) && !before.is_below(floor, min_samples)
+    }
+}
+
+/// The counts of all origins and of the whole crawl.
+#[derive(Debug, Clone, Default)]
+pub struct UniqueContentCounters {
+    global: UniqueContentCounts,
+    origins: HashMap<AtraUrlOrigin, UniqueContentCounts>,
+}
+
+impl UniqueContentCounters {
+    /// Returns the counts after registering a page of [origin] without changing them.
+    pub fn next(
+        &self,
+        origin: &AtraUrlOrigin,
+        unique_on_origin: bool,
+        unique_globally: bool,
+    ) -> RegisteredPayload {
+        RegisteredPayload {
+            unique: unique_on_origin,
+            origin: self
+                .origins
+                .get(origin)
+                .copied()
+                .unwrap_or_default()
+                .registered(unique_on_origin),
+            global: self.global.registered(unique_globally),
+        }
+    }
+
+    /// Sets the counts to the ones of the [registered] payload of [origin].
+    pub fn apply(&mut self, origin: &AtraUrlOrigin, registered: &RegisteredPayload) {
+        self.global = registered.global;
+        self.origins.insert(origin.clone(), registered.origin);
+    }
+
+    /// Registers a page of [origin].
+    pub fn register(
+        &mut self,
+        origin: &AtraUrlOrigin,
+        unique_on_origin: bool,
+        unique_globally: bool,
+    ) -> RegisteredPayload {
+        let registered = self.next(origin, unique_on_origin, unique_globally);
+        self.apply(origin, &registered);
+        registered
+    }
+
+    pub fn global(&self) -> UniqueContentCounts {
+        self.global
+    }
+
+    /// The counts of all origins, ordered by the origin.
+    pub fn origins(&self) -> Vec<(AtraUrlOrigin, UniqueContentCounts)> {
+        let mut origins = self
+            .origins
+            .iter()
+            .map(|(origin, counts)| (origin.clone(), *counts))
+            .collect::<Vec<_>>();
+        origins.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        origins
+    }
+}
+
+/// Keeps track of the unique payloads fetched from each origin.
+pub trait UniqueContentManager {
+    /// Registers the [digest] of a payload fetched from [origin].
+    async fn register_payload(
+        &self,
+        origin: &AtraUrlOrigin,
+        digest: &[u8],
+    ) -> Result<RegisteredPayload, DatabaseError>;
+
+    /// The counts of the whole crawl.
+    fn global_counts(&self) -> UniqueContentCounts;
+
+    /// The counts of all origins seen so far, ordered by the origin.
+    fn origin_counts(&self) -> Vec<(AtraUrlOrigin, UniqueContentCounts)>;
+}
+
+/// Stores the digests and the counts in the database, the counts survive a restart.
+#[derive(Debug)]
+pub struct UniqueContentDatabaseManager {
+    db: Arc<DB>,
+    counters: Mutex<UniqueContentCounters>,
+}
+
+impl UniqueContentDatabaseManager {
+    declare_column_families! {
+        self.db => cf_handle(UNIQUE_CONTENT_DB_CF)
+    }
+
+    pub fn new(db: Arc<DB>) -> Result<Self, DatabaseError> {
+        db_health_check!(db: [
+            Self::UNIQUE_CONTENT_DB_CF => (
+                if test unique_content_cf_options
+                else "The unique content cf is missing!"
+            )
+        ]);
+
+        let mut manager = Self {
+            db,
+            counters: Mutex::default(),
+        };
+        manager.counters = Mutex::new(manager.load_counters()?);
+        Ok(manager)
+    }
+
+    /// Reads the stored counts of all origins.
+    fn load_counters(&self) -> Result<UniqueContentCounters, DatabaseError> {
+        let mut counters = UniqueContentCounters::default();
+        let iter = self.db.iterator_cf(
+            &self.cf_handle(),
+            IteratorMode::From(&[COUNTS_PREFIX], Direction::Forward),
+        );
+        for entry in iter {
+            let (key, value) =
+                entry.enrich_no_key(Self::UNIQUE_CONTENT_DB_CF, DBActionType::Read)?;
+            let Some(origin) = key.strip_prefix(&[COUNTS_PREFIX]) else {
+                break;
+            };
+            let counts: UniqueContentCounts = bincode::deserialize(&value).map_err(|err| {
+                DatabaseError::from_deserialisation(
+                    Self::UNIQUE_CONTENT_DB_CF,
+                    String::from_utf8_lossy(&key).into_owned(),
+                    LazyBase64Value(value.to_vec()),
+                    err,
+                )
+            })?;
+            if origin.is_empty() {
+                counters.global = counts;
+            } else {
+                let origin = AtraUrlOrigin::from(String::from_utf8_lossy(origin).as_ref());
+                counters.origins.insert(origin, counts);
+            }
+        }
+        Ok(counters)
+    }
+
+    fn contains(&self, key: &[u8]) -> Result<bool, DatabaseError> {
+        let handle = self.cf_handle();
+        if !self.db.key_may_exist_cf(&handle, key) {
+            return Ok(false);
+        }
+        Ok(self
+            .db
+            .get_pinned_cf(&handle, key)
+            .enrich_without_entry(Self::UNIQUE_CONTENT_DB_CF, DBActionType::Read, key)?
+            .is_some())
+    }
+
+    fn put_counts(
+        &self,
+        batch: &mut WriteBatch,
+        origin: &str,
+        counts: &UniqueContentCounts,
+    ) -> Result<(), DatabaseError> {
+        let key = counts_key(origin);
+        let value = bincode::serialize(counts).map_err(|err| {
+            DatabaseError::from_serialisation(
+                Self::UNIQUE_CONTENT_DB_CF,
+                String::from_utf8_lossy(&key).into_owned(),
+                *counts,
+                err,
+            )
+        })?;
+        batch.put_cf(&self.cf_handle(), key, value);
+        Ok(())
+    }
+}
+
+impl UniqueContentManager for UniqueContentDatabaseManager {
+    async fn register_payload(
+        &self,
+        origin: &AtraUrlOrigin,
+        digest: &[u8],
+    ) -> Result<RegisteredPayload, DatabaseError> {
+        // Locked until the counts are written, the same payload is never counted twice as unique.
+        let mut counters = self.counters.lock().unwrap();
+        let origin_key = digest_key(origin.as_ref(), digest);
+        let global_key = digest_key(GLOBAL_ORIGIN, digest);
+        let unique_on_origin = !self.contains(&origin_key)?;
+        let unique_globally = !self.contains(&global_key)?;
+        let registered = counters.next(origin, unique_on_origin, unique_globally);
+
+        let mut batch = WriteBatch::default();
+        let handle = self.cf_handle();
+        if unique_on_origin {
+            batch.put_cf(&handle, origin_key, b"");
+        }
+        if unique_globally {
+            batch.put_cf(&handle, global_key, b"");
+        }
+        self.put_counts(&mut batch, origin.as_ref(), &registered.origin)?;
+        self.put_counts(&mut batch, GLOBAL_ORIGIN, &registered.global)?;
+        self.db
+            .write(batch)
+            .enrich_no_key(Self::UNIQUE_CONTENT_DB_CF, DBActionType::Write)?;
+
+        counters.apply(origin, &registered);
+        Ok(registered)
+    }
+
+    fn global_counts(&self) -> UniqueContentCounts {
+        self.counters.lock().unwrap().global()
+    }
+
+    fn origin_counts(&self) -> Vec<(AtraUrlOrigin, UniqueContentCounts)> {
+        self.counters.lock().unwrap().origins()
+    }
+}
+
+fn counts_key(origin: &str) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + 1);
+    key.push(COUNTS_PREFIX);
+    key.extend_from_slice(origin.as_bytes());
+    key
+}
+
+fn digest_key(origin: &str, digest: &[u8]) -> Vec<u8> {
+    let mut key = Vec::with_capacity(origin.len() + digest.len() + 2);
+    key.push(DIGEST_PREFIX);
+    key.extend_from_slice(origin.as_bytes());
+    key.push(0);
+    key.extend_from_slice(digest);
+    key
+}
+
+/// The digest of the payload of [content], none if there is no payload.
+pub fn payload_digest(content: &RawVecData) -> io::Result<Option<Vec<u8>>> {
+    match content {
+        RawData::None => Ok(None),
+        RawData::InMemory { data } => Ok(Some(labeled_xxh128_digest(data))),
+        RawData::ExternalFile { path } => {
+            let mut digester = LabeledXxh128Digester::default();
+            io::copy(
+                &mut BufReader::new(ExternalFileReader::open(path)?),
+                &mut digester,
+            )?;
+            Ok(Some(digester.finish()))
+        }
+    }
+}
+
+/// Registers the payload of the stored [result] in the unique content of its origin and
+/// warns if the ratio of the origin falls below the configured floor.
+pub async fn register_unique_content<C>(context: &C, result: &CrawlResult)
+where
+    C: SupportsUniqueContent + SupportsConfigs,
+{
+    let url = &result.meta.url;
+    let Some(origin) = url.atra_origin() else {
+        return;
+    };
+    let digest = match payload_digest(&result.content) {
+        Ok(Some(digest)) => digest,
+        Ok(None) => return,
+        Err(err) => {
+            log::warn!("Failed to digest the payload of {url}. {err}");
+            return;
+        }
+    };
+    let registered = match context
+        .get_unique_content_manager()
+        .register_payload(&origin, &digest)
+        .await
+    {
+        Ok(registered) => registered,
+        Err(err) => {
+            log::error!("Failed to register the payload of {url}. {err}");
+            return;
+        }
+    };
+    let config = &context.configs().crawl;
+    if let Some(floor) = config.unique_content_floor {
+        if registered.crossed_floor(floor, config.unique_content_min_samples) {
+            log::warn!(
+                "!!! Only {} of {} pages fetched from {origin} have a unique payload, below the floor of {floor}%. The origin may be a crawler trap. !!!",
+                registered.origin.unique_payloads,
+                registered.origin.fetched
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        payload_digest, UniqueContentCounts, UniqueContentDatabaseManager, UniqueContentManager,
+    };
+    use crate::data::RawVecData;
+    use crate::database::open_db;
+    use crate::url::AtraUrlOrigin;
+    use camino_tempfile::Utf8TempDir;
+    use std::sync::Arc;
+
+    /// Registers the payloads and returns the number of payloads crossing the floor.
+    async fn register_all(
+        manager: &UniqueContentDatabaseManager,
+        origin: &str,
+        payloads: &[&str],
+        floor: u8,
+        min_samples: u64,
+    ) -> Vec<usize> {
+        let origin = AtraUrlOrigin::from(origin);
+        let mut crossed = Vec::new();
+        for (i, payload) in payloads.iter().enumerate() {
+            let digest = payload_digest(&RawVecData::from_vec(payload.as_bytes().to_vec()))
+                .unwrap()
+                .unwrap();
+            let registered = manager.register_payload(&origin, &digest).await.unwrap();
+            if registered.crossed_floor(floor, min_samples) {
+                crossed.push(i);
+            }
+        }
+        crossed
+    }
+
+    #[tokio::test]
+    async fn counts_unique_payloads_and_warns_at_the_floor() {
+        let dir = Utf8TempDir::new().unwrap();
+        let db = Arc::new(open_db(dir.path()).unwrap());
+        let manager = UniqueContentDatabaseManager::new(db.clone()).unwrap();
+
+        // The trap repeats the same calendar page after two unique pages.
+        let crossed = register_all(
+            &manager,
+            "trap.example.com",
+            &[
+                "a", "b", "cal", "cal", "cal", "cal", "cal", "cal", "cal", "cal",
+            ],
+            40,
+            5,
+        )
+        .await;
+        // 3 of 7 (42%) is above the floor, 3 of 8 (37%) is the first one below it.
+        assert_eq!(vec![7], crossed);
+        let crossed = register_all(&manager, "good.example.com", &["a", "b", "c"], 40, 1).await;
+        assert!(crossed.is_empty());
+
+        let trap = UniqueContentCounts::new(10, 3);
+        assert_eq!(Some(0.3), trap.ratio());
+        assert!(trap.is_below(40, 5));
+        assert!(!trap.is_below(40, 11));
+        assert_eq!(
+            vec![
+                (
+                    AtraUrlOrigin::from("good.example.com"),
+                    UniqueContentCounts::new(3, 3)
+                ),
+                (AtraUrlOrigin::from("trap.example.com"), trap),
+            ],
+            manager.origin_counts()
+        );
+        // "a" and "b" were already fetched from the trap.
+        assert_eq!(UniqueContentCounts::new(13, 4), manager.global_counts());
+
+        drop(manager);
+        drop(db);
+        let db = Arc::new(open_db(dir.path()).unwrap());
+        let manager = UniqueContentDatabaseManager::new(db).unwrap();
+        assert_eq!(UniqueContentCounts::new(13, 4), manager.global_counts());
+        let crossed = register_all(&manager, "trap.example.com", &["cal", "d"], 40, 5).await;
+        assert!(crossed.is_empty());
+        assert_eq!(
+            (
+                AtraUrlOrigin::from("trap.example.com"),
+                UniqueContentCounts::new(12, 4)
+            ),
+            manager.origin_counts()[1]
+        );
+        assert_eq!(UniqueContentCounts::new(15, 5), manager.global_counts());
+    }
+
+    #[test]
+    fn nothing_fetched_is_never_below_the_floor() {
+        let empty = UniqueContentCounts::default();
+        assert_eq!(None, empty.ratio());
+        assert!(!empty.is_below(100, 0));
+        assert!(UniqueContentCounts::new(1, 0).is_below(1, 0));
+        assert!(!UniqueContentCounts::new(1, 1).is_below(100, 0));
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+use time::{Date, Month};
+
+/// `/2023/07/15/slug` or `/2023/7/15.html`
+static SLASHED_DAY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:^|/)((?:19|20)\d{2})/(0?[1-9]|1[0-2])/(0?[1-9]|[12]\d|3[01])(?:$|[^0-9])")
+        .unwrap()
+});
+/// `/2023-07-15-slug` or `/news_2023_07_15.html`
+static JOINED_DAY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|[^0-9])((?:19|20)\d{2})[-_](0[1-9]|1[0-2])[-_](0[1-9]|[12]\d|3[01])(?:$|[^0-9])",
+    )
+    .unwrap()
+});
+/// `/2023/07/slug`
+static SLASHED_MONTH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^|/)((?:19|20)\d{2})/(0?[1-9]|1[0-2])(?:$|/)").unwrap());
+
+/// How exact a date is.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum DatePrecision {
+    /// Only the year and the month are known, the day is the first of the month.
+    Month,
+    /// The day is known.
+    Day,
+}
+
+/// A date found in the path of an url.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct UrlDate {
+    pub date: Date,
+    pub precision: DatePrecision,
+}
+
+/// Finds the first date in the [path] of an url, like `/2023/07/15/slug`, `/2023-07-15-slug`
+/// or `/2023/07/slug`. Only years from 1900 to 2099 are recognized, a day is preferred over
+/// a month. Impossible days like in `/2023-02-30-slug` are ignored.
+pub fn date_from_path(path: &str) -> Option<UrlDate> {
+    fn to_date(captures: Captures, with_day: bool) -> Option<Date> {
+        let year: i32 = captures.get(1)?.as_str().parse().ok()?;
+        let month: u8 = captures.get(2)?.as_str().parse().ok()?;
+        let day: u8 = if with_day {
+            captures.get(3)?.as_str().parse().ok()?
+        } else {
+            1
+        };
+        Date::from_calendar_date(year, Month::try_from(month).ok()?, day).ok()
+    }
+
+    let day = SLASHED_DAY
+        .captures_iter(path)
+        .chain(JOINED_DAY.captures_iter(path))
+        .find_map(|captures| to_date(captures, true));
+    if let Some(date) = day {
+        return Some(UrlDate {
+            date,
+            precision: DatePrecision::Day,
+        });
+    }
+    SLASHED_MONTH
+        .captures_iter(path)
+        .find_map(|captures| to_date(captures, false))
+        .map(|date| UrlDate {
+            date,
+            precision: DatePrecision::Month,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{date_from_path, DatePrecision, UrlDate};
+    use time::{Date, Month};
+
+    fn day(year: i32, month: Month, day: u8) -> Option<UrlDate> {
+        Some(UrlDate {
+            date: Date::from_calendar_date(year, month, day).unwrap(),
+            precision: DatePrecision::Day,
+        })
+    }
+
+    #[test]
+    fn finds_days() {
+        assert_eq!(
+            day(2023, Month::July, 15),
+            date_from_path("/2023/07/15/slug")
+        );
+        assert_eq!(
+            day(2023, Month::July, 5),
+            date_from_path("/blog/2023/7/5.html")
+        );
+        assert_eq!(
+            day(2023, Month::July, 15),
+            date_from_path("/2023-07-15-slug")
+        );
+        assert_eq!(
+            day(2023, Month::July, 15),
+            date_from_path("/news/report_2023_07_15.html")
+        );
+        assert_eq!(
+            day(1999, Month::December, 31),
+            date_from_path("/1999/12/31")
+        );
+    }
+
+    #[test]
+    fn finds_months() {
+        assert_eq!(
+            Some(UrlDate {
+                date: Date::from_calendar_date(2023, Month::July, 1).unwrap(),
+                precision: DatePrecision::Month,
+            }),
+            date_from_path("/2023/07/slug")
+        );
+    }
+
+    #[test]
+    fn ignores_numbers_that_are_no_dates() {
+        assert_eq!(None, date_from_path("/2023-02-30-slug"));
+        assert_eq!(None, date_from_path("/product/123456/2023"));
+        assert_eq!(None, date_from_path("/12023-07-15/"));
+        assert_eq!(None, date_from_path("/2023/13/slug"));
+        assert_eq!(None, date_from_path("/3023/07/15/"));
+        assert_eq!(None, date_from_path("/"));
+    }
+}
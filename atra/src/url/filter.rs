@@ -0,0 +1,469 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::extraction::extractor_method::ExtractorMethod;
+use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Display, Formatter};
+use std::sync::Arc;
+
+/// What a [UrlFilter] decides for a found url.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UrlFilterDecision {
+    /// The url is enqueued, the following filters are not asked.
+    Accept,
+    /// The url is dropped, the following filters are not asked.
+    Reject,
+    /// The score is added to the priority of the url and the following filters decide.
+    /// A score of 0 means that the filter has no opinion.
+    Score(i8),
+}
+
+impl UrlFilterDecision {
+    /// The decision of a filter without an opinion about the url.
+    pub const PASS: UrlFilterDecision = UrlFilterDecision::Score(0);
+}
+
+/// Decides if a found url is enqueued. Implementations can be added to the [UrlFilterChain]
+/// of an embedded crawl in addition to the configured [UrlFilterConfig]s.
+pub trait UrlFilter: Debug + Send + Sync {
+    /// Decides about the [url] found at its depth. The [origin] is None if the url has none,
+    /// the [extractor] is None if the url was not extracted from a page, e.g. from a sitemap.
+    fn decide(
+        &self,
+        url: &UrlWithDepth,
+        origin: Option<&AtraUrlOrigin>,
+        extractor: Option<ExtractorMethod>,
+    ) -> UrlFilterDecision;
+}
+
+/// A regex matched against the full url, compiled when the config is read.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UrlFilterRegex(Regex);
+
+impl UrlFilterRegex {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+}
+
+impl PartialEq for UrlFilterRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+impl Eq for UrlFilterRegex {}
+
+impl Display for UrlFilterRegex {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+impl TryFrom<String> for UrlFilterRegex {
+    type Error = regex::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<UrlFilterRegex> for String {
+    fn from(value: UrlFilterRegex) -> Self {
+        value.to_string()
+    }
+}
+
+fn reject() -> UrlFilterDecision {
+    UrlFilterDecision::Reject
+}
+
+/// A built-in [UrlFilter]. Every filter except [UrlFilterConfig::MaxPathDepth] decides
+/// `on_match` if it matches and has no opinion otherwise. (default on_match: reject)
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UrlFilterConfig {
+    /// Matches if the regex matches somewhere in the full url.
+    Regex {
+        pattern: UrlFilterRegex,
+        #[serde(default = "reject")]
+        on_match: UrlFilterDecision,
+    },
+    /// Matches if the path of the url starts with the prefix, e.g. `/print/`.
+    PathPrefix {
+        prefix: String,
+        #[serde(default = "reject")]
+        on_match: UrlFilterDecision,
+    },
+    /// Matches if the query of the url has a parameter with the name, with or without a value.
+    QueryParameter {
+        name: String,
+        #[serde(default = "reject")]
+        on_match: UrlFilterDecision,
+    },
+    /// Matches if the last segment of the path ends with one of the extensions,
+    /// without the dot and ignoring the case, e.g. `pdf`.
+    Extension {
+        extensions: Vec<String>,
+        #[serde(default = "reject")]
+        on_match: UrlFilterDecision,
+    },
+    /// Rejects the urls with more than `max` non-empty segments in their path.
+    MaxPathDepth { max: usize },
+}
+
+impl UrlFilterConfig {
+    /// Returns true if the filter matches [url].
+    fn matches(&self, url: &UrlWithDepth) -> bool {
+        let AtraUri::Url(value) = &url.url;
+        match self {
+            UrlFilterConfig::Regex { pattern, .. } => pattern.0.is_match(value.as_str()),
+            UrlFilterConfig::PathPrefix { prefix, .. } => value.path().starts_with(prefix.as_str()),
+            UrlFilterConfig::QueryParameter { name, .. } => {
+                value.query_pairs().any(|(key, _)| key == name.as_str())
+            }
+            UrlFilterConfig::Extension { extensions, .. } => value
+                .path_segments()
+                .and_then(|mut segments| segments.next_back())
+                .and_then(|file_name| file_name.rsplit_once('.'))
+                .is_some_and(|(_, extension)| {
+                    extensions
+                        .iter()
+                        .any(|expected| expected.eq_ignore_ascii_case(extension))
+                }),
+            UrlFilterConfig::MaxPathDepth { max } => {
+                value.path_segments().is_some_and(|segments| {
+                    segments.filter(|segment| !segment.is_empty()).count() > *max
+                })
+            }
+        }
+    }
+}
+
+impl UrlFilter for UrlFilterConfig {
+    fn decide(
+        &self,
+        url: &UrlWithDepth,
+        _origin: Option<&AtraUrlOrigin>,
+        _extractor: Option<ExtractorMethod>,
+    ) -> UrlFilterDecision {
+        if !self.matches(url) {
+            return UrlFilterDecision::PASS;
+        }
+        match self {
+            UrlFilterConfig::Regex { on_match, .. }
+            | UrlFilterConfig::PathPrefix { on_match, .. }
+            | UrlFilterConfig::QueryParameter { on_match, .. }
+            | UrlFilterConfig::Extension { on_match, .. } => *on_match,
+            UrlFilterConfig::MaxPathDepth { .. } => UrlFilterDecision::Reject,
+        }
+    }
+}
+
+/// The [UrlFilter]s asked in their order before a found url is enqueued.
+/// The first filter accepting or rejecting the url decides, the scores of the filters
+/// asked before are summed up.
+#[derive(Debug, Clone, Default)]
+pub struct UrlFilterChain {
+    filters: Vec<Arc<dyn UrlFilter>>,
+}
+
+impl UrlFilterChain {
+    /// Creates the chain of the configured filters.
+    pub fn new(configs: &[UrlFilterConfig]) -> Self {
+        Self {
+            filters: configs
+                .iter()
+                .cloned()
+                .map(|config| Arc::new(config) as Arc<dyn UrlFilter>)
+                .collect(),
+        }
+    }
+
+    /// Appends [filter] to the chain, it is asked after all filters already in the chain.
+    pub fn push(&mut self, filter: Arc<dyn UrlFilter>) {
+        self.filters.push(filter);
+    }
+
+    /// Returns the score added to the priority of [url], None if it is rejected.
+    pub fn decide(&self, url: &UrlWithDepth, extractor: Option<ExtractorMethod>) -> Option<i8> {
+        if self.filters.is_empty() {
+            return Some(0);
+        }
+        let origin = url.atra_origin();
+        let mut score = 0i8;
+        for filter in &self.filters {
+            match filter.decide(url, origin.as_ref(), extractor) {
+                UrlFilterDecision::Accept => break,
+                UrlFilterDecision::Reject => return None,
+                UrlFilterDecision::Score(value) => score = score.saturating_add(value),
+            }
+        }
+        Some(score)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::url::Depth;
+
+    fn url(value: &str) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    fn decide(filter: &UrlFilterConfig, value: &str) -> UrlFilterDecision {
+        filter.decide(&url(value), None, None)
+    }
+
+    #[test]
+    fn regex_matches_the_full_url() {
+        let filter = UrlFilterConfig::Regex {
+            pattern: UrlFilterRegex::new(r"/print/|[?&]session=").unwrap(),
+            on_match: UrlFilterDecision::Reject,
+        };
+        assert_eq!(
+            UrlFilterDecision::Reject,
+            decide(&filter, "https://www.example.com/news/print/1")
+        );
+        assert_eq!(
+            UrlFilterDecision::Reject,
+            decide(&filter, "https://www.example.com/?a=1&session=abc")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/news/1")
+        );
+    }
+
+    #[test]
+    fn path_prefix_only_matches_the_path() {
+        let filter = UrlFilterConfig::PathPrefix {
+            prefix: "/publications/".to_string(),
+            on_match: UrlFilterDecision::Score(5),
+        };
+        assert_eq!(
+            UrlFilterDecision::Score(5),
+            decide(&filter, "https://www.example.com/publications/paper.pdf")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/de/publications/paper.pdf")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/?next=/publications/")
+        );
+    }
+
+    #[test]
+    fn query_parameter_matches_with_and_without_value() {
+        let filter = UrlFilterConfig::QueryParameter {
+            name: "replytocom".to_string(),
+            on_match: UrlFilterDecision::Reject,
+        };
+        assert_eq!(
+            UrlFilterDecision::Reject,
+            decide(&filter, "https://www.example.com/post?replytocom=12")
+        );
+        assert_eq!(
+            UrlFilterDecision::Reject,
+            decide(&filter, "https://www.example.com/post?a=b&replytocom")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/post?noreplytocom=12")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/replytocom")
+        );
+    }
+
+    #[test]
+    fn extension_ignores_the_case_and_the_directories() {
+        let filter = UrlFilterConfig::Extension {
+            extensions: vec!["pdf".to_string(), "ps".to_string()],
+            on_match: UrlFilterDecision::Accept,
+        };
+        assert_eq!(
+            UrlFilterDecision::Accept,
+            decide(&filter, "https://www.example.com/paper.PDF")
+        );
+        assert_eq!(
+            UrlFilterDecision::Accept,
+            decide(&filter, "https://www.example.com/a/paper.ps?download=1")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/v1.pdf/index.html")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/pdf")
+        );
+    }
+
+    #[test]
+    fn max_path_depth_rejects_deeper_paths() {
+        let filter = UrlFilterConfig::MaxPathDepth { max: 2 };
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com/a/b/")
+        );
+        assert_eq!(
+            UrlFilterDecision::PASS,
+            decide(&filter, "https://www.example.com//a//b")
+        );
+        assert_eq!(
+            UrlFilterDecision::Reject,
+            decide(&filter, "https://www.example.com/a/b/c")
+        );
+    }
+
+    /// Accepts everything found by a specific extractor.
+    #[derive(Debug)]
+    struct AcceptExtractor(ExtractorMethod);
+
+    impl UrlFilter for AcceptExtractor {
+        fn decide(
+            &self,
+            _url: &UrlWithDepth,
+            _origin: Option<&AtraUrlOrigin>,
+            extractor: Option<ExtractorMethod>,
+        ) -> UrlFilterDecision {
+            if extractor == Some(self.0) {
+                UrlFilterDecision::Accept
+            } else {
+                UrlFilterDecision::PASS
+            }
+        }
+    }
+
+    #[test]
+    fn the_first_decision_of_the_chain_wins() {
+        // Only the pdfs below /publications/ are crawled.
+        let chain = UrlFilterChain::new(&[
+            UrlFilterConfig::PathPrefix {
+                prefix: "/publications/".to_string(),
+                on_match: UrlFilterDecision::Accept,
+            },
+            UrlFilterConfig::Extension {
+                extensions: vec!["pdf".to_string()],
+                on_match: UrlFilterDecision::Reject,
+            },
+        ]);
+        assert_eq!(
+            Some(0),
+            chain.decide(&url("https://www.example.com/publications/a.pdf"), None)
+        );
+        assert_eq!(
+            None,
+            chain.decide(&url("https://www.example.com/downloads/a.pdf"), None)
+        );
+        assert_eq!(
+            Some(0),
+            chain.decide(&url("https://www.example.com/downloads/a.html"), None)
+        );
+
+        // The same filters in the other order reject every pdf.
+        let chain = UrlFilterChain::new(&[
+            UrlFilterConfig::Extension {
+                extensions: vec!["pdf".to_string()],
+                on_match: UrlFilterDecision::Reject,
+            },
+            UrlFilterConfig::PathPrefix {
+                prefix: "/publications/".to_string(),
+                on_match: UrlFilterDecision::Accept,
+            },
+        ]);
+        assert_eq!(
+            None,
+            chain.decide(&url("https://www.example.com/publications/a.pdf"), None)
+        );
+    }
+
+    #[test]
+    fn the_chain_sums_the_scores_until_a_decision() {
+        let mut chain = UrlFilterChain::new(&[
+            UrlFilterConfig::PathPrefix {
+                prefix: "/news/".to_string(),
+                on_match: UrlFilterDecision::Score(100),
+            },
+            UrlFilterConfig::QueryParameter {
+                name: "page".to_string(),
+                on_match: UrlFilterDecision::Score(100),
+            },
+        ]);
+        chain.push(Arc::new(AcceptExtractor(ExtractorMethod::HtmlV1)));
+        chain.push(Arc::new(UrlFilterConfig::MaxPathDepth { max: 1 }));
+
+        let news = url("https://www.example.com/news/today?page=2");
+        assert_eq!(
+            Some(i8::MAX),
+            chain.decide(&news, Some(ExtractorMethod::HtmlV1))
+        );
+        assert_eq!(None, chain.decide(&news, Some(ExtractorMethod::Css)));
+        assert_eq!(None, chain.decide(&news, None));
+        assert_eq!(
+            Some(100),
+            chain.decide(&url("https://www.example.com/news/"), None)
+        );
+        let deep = UrlWithDepth::new("https://www.example.com/a/b".parse().unwrap(), Depth::ZERO);
+        assert_eq!(Some(0), chain.decide(&deep, Some(ExtractorMethod::HtmlV1)));
+        assert_eq!(Some(0), UrlFilterChain::default().decide(&deep, None));
+    }
+
+    #[test]
+    fn can_be_configured() {
+        let configs: Vec<UrlFilterConfig> = serde_json::from_str(
+            r#"[
+                {"type": "regex", "pattern": "/print/"},
+                {"type": "path_prefix", "prefix": "/publications/", "on_match": "accept"},
+                {"type": "query_parameter", "name": "page", "on_match": {"score": -3}},
+                {"type": "extension", "extensions": ["pdf"]},
+                {"type": "max_path_depth", "max": 8}
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            UrlFilterConfig::Regex {
+                pattern: UrlFilterRegex::new("/print/").unwrap(),
+                on_match: UrlFilterDecision::Reject,
+            },
+            configs[0]
+        );
+        assert_eq!(
+            UrlFilterConfig::QueryParameter {
+                name: "page".to_string(),
+                on_match: UrlFilterDecision::Score(-3),
+            },
+            configs[2]
+        );
+        assert_eq!(UrlFilterConfig::MaxPathDepth { max: 8 }, configs[4]);
+        let serialized = serde_json::to_string(&configs).unwrap();
+        assert_eq!(
+            configs,
+            serde_json::from_str::<Vec<UrlFilterConfig>>(&serialized).unwrap()
+        );
+        assert!(serde_json::from_str::<UrlFilterConfig>(
+            r#"{"type": "regex", "pattern": "(unclosed"}"#
+        )
+        .is_err());
+    }
+}
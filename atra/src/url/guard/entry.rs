@@ -17,9 +17,10 @@ use serde::{Deserialize, Serialize};
 use std::time::SystemTime;
 
 /// The entry for an origin
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct GuardEntry {
-    pub(super) is_in_use: bool,
+    /// The reservation timestamps of the guards currently holding the origin.
+    pub(super) reservations: Vec<SystemTime>,
     pub(super) last_modification: Option<SystemTime>,
     pub(super) depth: Depth,
 }
@@ -27,7 +28,12 @@ pub struct GuardEntry {
 impl GuardEntry {
     /// Returns true if the guarded entry is in use.
     pub fn is_in_use(&self) -> bool {
-        self.is_in_use
+        !self.reservations.is_empty()
+    }
+
+    /// Returns the number of guards currently holding the entry.
+    pub fn in_use(&self) -> usize {
+        self.reservations.len()
     }
 
     /// Returns the last modification timestamp
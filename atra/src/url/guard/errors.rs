@@ -22,7 +22,7 @@ use thiserror::Error;
 pub enum GuardianError {
     #[error("There was no host in the url")]
     NoOriginError(UrlWithDepth),
-    #[error("The host is already in use {0:?}")]
+    #[error("The host is already in use by the maximum number of guards {0:?}")]
     AlreadyOccupied(AtraUrlOrigin),
 }
 
@@ -33,8 +33,8 @@ pub enum GuardPoisonedError {
     OriginMissing(AtraUrlOrigin),
     #[error("The guard flag of the origin {0} is not set!")]
     InUseNotSet(AtraUrlOrigin),
-    #[error("The guard timestamp of the origin {0} is not set!")]
-    NoTimestampSet(AtraUrlOrigin),
-    #[error("The guard timestamp of the origin {0} is set to {2:?} but should be {1:?}!")]
-    WrongTimestampSet(AtraUrlOrigin, SystemTime, SystemTime),
+    #[error("The guard timestamp {1:?} of the origin {0} is not among the reservations {2:?}!")]
+    ReservationMissing(AtraUrlOrigin, SystemTime, Vec<SystemTime>),
+    #[error("The guardian of the origin {0} was dropped before the guard!")]
+    GuardianDropped(AtraUrlOrigin),
 }
@@ -0,0 +1,139 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::{AtraUrlOrigin, Depth};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
+
+/// The number of events a subscriber can lag behind before it misses some.
+const GUARDIAN_EVENT_CAPACITY: usize = 1024;
+
+/// An event fired when an url guardian changes
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum GuardianChangedEvent {
+    /// A guard reserved the origin for an url with the [depth].
+    OriginReserved { origin: AtraUrlOrigin, depth: Depth },
+    /// A guard of the origin was released after holding it for [duration_held].
+    OriginReleased {
+        origin: AtraUrlOrigin,
+        duration_held: Duration,
+    },
+    /// A guard keeps the origin until [until] to respect the politeness delay.
+    OriginCooldown {
+        origin: AtraUrlOrigin,
+        until: SystemTime,
+    },
+    /// The last guard of the origin was released, the origin is free again.
+    OriginCompleted { origin: AtraUrlOrigin },
+}
+
+/// Distributes the [GuardianChangedEvent]s of a guardian to all subscribers.
+/// Emitting never waits for a subscriber, a subscriber lagging more than the capacity
+/// behind misses the oldest events. The missed events are counted as dropped.
+#[derive(Debug, Clone)]
+pub struct GuardianEvents {
+    sender: broadcast::Sender<GuardianChangedEvent>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl GuardianEvents {
+    pub fn new() -> Self {
+        Self::with_capacity(GUARDIAN_EVENT_CAPACITY)
+    }
+
+    /// Creates the events with room for [capacity] events per subscriber.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self {
+            sender,
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Subscribe to all events emitted after this call.
+    pub fn subscribe(&self) -> GuardianEventReceiver {
+        GuardianEventReceiver {
+            receiver: self.sender.subscribe(),
+            dropped: self.dropped.clone(),
+            missed: 0,
+        }
+    }
+
+    /// Emits the event to all current subscribers.
+    pub fn emit(&self, event: GuardianChangedEvent) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(event);
+        }
+    }
+
+    /// The number of events missed by lagging subscribers so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for GuardianEvents {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Receives the [GuardianChangedEvent]s emitted after its creation.
+#[derive(Debug)]
+pub struct GuardianEventReceiver {
+    receiver: broadcast::Receiver<GuardianChangedEvent>,
+    dropped: Arc<AtomicU64>,
+    missed: u64,
+}
+
+impl GuardianEventReceiver {
+    /// Waits for the next event, returns None if the guardian is gone.
+    /// Missed events are counted as dropped and skipped.
+    pub async fn recv(&mut self) -> Option<GuardianChangedEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => return Some(event),
+                Err(RecvError::Lagged(missed)) => {
+                    self.missed += missed;
+                    self.dropped.fetch_add(missed, Ordering::Relaxed);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Returns the next event if one was already emitted.
+    /// Missed events are counted as dropped and skipped.
+    pub fn try_recv(&mut self) -> Option<GuardianChangedEvent> {
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => return Some(event),
+                Err(TryRecvError::Lagged(missed)) => {
+                    self.missed += missed;
+                    self.dropped.fetch_add(missed, Ordering::Relaxed);
+                }
+                Err(TryRecvError::Empty | TryRecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// The number of events this receiver missed, the guardian counts the missed events
+    /// of all receivers.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+}
@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use crate::url::guard::entry::GuardEntry;
-use crate::url::guard::{GuardPoisonedError, UrlGuardian};
+use crate::url::guard::{GuardPoisonedError, UrlGuardian, WeakUrlGuardian};
 use crate::url::AtraOriginProvider;
 use crate::url::AtraUrlOrigin;
 use crate::url::Depth;
@@ -24,6 +24,9 @@ use std::time::SystemTime;
 
 /// A guard that works basically like a Mutex or RwLock guard.
 /// Allows to block a domain until the guard is dropped.
+///
+/// The guard only keeps a weak reference to its guardian. If the guardian is dropped
+/// first, the guard is poisoned and releasing it does nothing.
 #[clippy::has_significant_drop]
 pub struct UrlGuard<'a, Guardian>
 where
@@ -31,14 +34,11 @@ where
 {
     pub(super) reserved_at: SystemTime,
     pub(super) origin: AtraUrlOrigin,
-    pub(super) origin_manager: *const Guardian,
+    pub(super) guardian: Guardian::Weak,
     pub(super) entry: GuardEntry,
     pub(super) _marker: PhantomData<&'a Guardian>,
 }
 
-unsafe impl<'a, Guardian: UrlGuardian> Sync for UrlGuard<'a, Guardian> where Guardian: UrlGuardian {}
-unsafe impl<'a, Guardian: UrlGuardian> Send for UrlGuard<'a, Guardian> where Guardian: UrlGuardian {}
-
 impl<'a, Guardian> fmt::Debug for UrlGuard<'a, Guardian>
 where
     Guardian: UrlGuardian,
@@ -58,9 +58,18 @@ where
 {
     /// Checks the guard is poisoned.
     pub async fn check_for_poison(&self) -> Result<(), GuardPoisonedError> {
-        unsafe { &*self.origin_manager }
-            .check_if_poisoned(self)
-            .await
+        match self.guardian.upgrade() {
+            Some(guardian) => guardian.check_if_poisoned(self).await,
+            None => Err(GuardPoisonedError::GuardianDropped(self.origin.clone())),
+        }
+    }
+
+    /// Announces that the guard keeps the origin until [until] to respect the politeness delay.
+    /// Nothing is announced if the guardian was already dropped.
+    pub fn announce_cooldown(&self, until: SystemTime) {
+        if let Some(guardian) = self.guardian.upgrade() {
+            guardian.announce_cooldown(self, until)
+        }
     }
 
     /// When was the guard reserved?
@@ -100,8 +109,12 @@ where
     Guardian: UrlGuardian,
 {
     fn drop(&mut self) {
-        unsafe {
-            (&*self.origin_manager).release(self.origin.clone());
+        match self.guardian.upgrade() {
+            Some(guardian) => guardian.release(self.origin.clone(), self.reserved_at),
+            None => log::warn!(
+                "The guardian of {} was dropped before the guard, nothing to release.",
+                self.origin
+            ),
         }
     }
 }
@@ -14,24 +14,29 @@
 
 mod entry;
 mod errors;
+mod events;
 mod guard;
 mod traits;
 
 use std::collections::HashMap;
 use std::marker::PhantomData;
-use std::sync::{Arc, LockResult, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+use std::num::NonZeroUsize;
+use std::sync::{
+    Arc, LockResult, PoisonError, RwLockReadGuard, RwLockWriteGuard, TryLockError, Weak,
+};
 use std::time::SystemTime;
-use tokio::sync::watch::Receiver;
 use tokio::task::yield_now;
 pub use traits::*;
 
 pub use errors::*;
+pub use events::*;
 
 use crate::url::guard::entry::GuardEntry;
 use crate::url::{AtraOriginProvider, AtraUrlOrigin, UrlWithDepth};
 pub use guard::UrlGuard;
 
-/// Manages the crawl state of the domains in the current crawl
+/// Manages the crawl state of the domains in the current crawl.
+/// An origin can be held by up to `max_parallel` guards at the same time.
 #[derive(Debug)]
 #[repr(transparent)]
 pub struct InMemoryUrlGuardian {
@@ -39,27 +44,83 @@ pub struct InMemoryUrlGuardian {
 }
 
 impl InMemoryUrlGuardian {
+    /// Creates a guardian allowing a single guard per origin.
     pub fn new() -> Self {
+        Self::with_max_parallel(NonZeroUsize::MIN)
+    }
+
+    /// Creates a guardian allowing up to [max_parallel] guards per origin.
+    pub fn with_max_parallel(max_parallel: NonZeroUsize) -> Self {
+        Self::with_events(max_parallel, GuardianEvents::new())
+    }
+
+    /// Creates a guardian allowing up to [max_parallel] guards per origin, emitting to [events].
+    pub fn with_events(max_parallel: NonZeroUsize, events: GuardianEvents) -> Self {
         Self {
-            inner: Arc::new(InMemoryUrlGuardianState::new()),
+            inner: Arc::new(InMemoryUrlGuardianState::new(max_parallel, events)),
         }
     }
+
+    /// The events emitted by this guardian.
+    pub fn events(&self) -> &GuardianEvents {
+        &self.inner.events
+    }
 }
 
-unsafe impl UnsafeUrlGuardian for InMemoryUrlGuardian {
-    unsafe fn release(&self, origin: AtraUrlOrigin) {
-        let mut holder = self.inner.write_blocking().unwrap();
-        let _ = self.inner.broadcast.send(GuardianChangedEvent);
-        if let Some(value) = holder.get_mut(&origin) {
-            value.is_in_use = false;
-            value.last_modification = Some(SystemTime::now());
-        } else {
-            unreachable!();
-        }
+/// The reference the guards of an [InMemoryUrlGuardian] keep to its state.
+#[derive(Debug, Clone)]
+pub struct WeakInMemoryUrlGuardian {
+    inner: Weak<InMemoryUrlGuardianState>,
+}
+
+impl WeakUrlGuardian<InMemoryUrlGuardian> for WeakInMemoryUrlGuardian {
+    fn upgrade(&self) -> Option<InMemoryUrlGuardian> {
+        self.inner.upgrade().map(|inner| InMemoryUrlGuardian { inner })
     }
 }
 
 impl UrlGuardian for InMemoryUrlGuardian {
+    type Weak = WeakInMemoryUrlGuardian;
+
+    fn downgrade(&self) -> Self::Weak {
+        WeakInMemoryUrlGuardian {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
+    fn release(&self, origin: AtraUrlOrigin, reserved_at: SystemTime) {
+        // Called while dropping a guard, a poisoned lock is still released.
+        let mut holder = self
+            .inner
+            .write_blocking()
+            .unwrap_or_else(PoisonError::into_inner);
+        let Some(value) = holder.get_mut(&origin) else {
+            log::warn!("The released origin {origin} is not registered!");
+            return;
+        };
+        if let Some(position) = value
+            .reservations
+            .iter()
+            .position(|current| reserved_at.eq(current))
+        {
+            value.reservations.swap_remove(position);
+        }
+        let now = SystemTime::now();
+        value.last_modification = Some(now);
+        let completed = !value.is_in_use();
+        self.inner
+            .events
+            .emit(GuardianChangedEvent::OriginReleased {
+                origin: origin.clone(),
+                duration_held: now.duration_since(reserved_at).unwrap_or_default(),
+            });
+        if completed {
+            self.inner
+                .events
+                .emit(GuardianChangedEvent::OriginCompleted { origin });
+        }
+    }
+
     async fn try_reserve<'a>(
         &'a self,
         url: &UrlWithDepth,
@@ -69,17 +130,23 @@ impl UrlGuardian for InMemoryUrlGuardian {
             .ok_or_else(|| GuardianError::NoOriginError(url.clone()))?;
         let mut holder = self.inner.write().await;
         if let Some(found) = holder.get_mut(&origin) {
-            if found.is_in_use {
+            if found.in_use() >= self.inner.max_parallel.get() {
                 return Err(GuardianError::AlreadyOccupied(origin));
             }
             let reserved_at = SystemTime::now();
             found.last_modification = Some(reserved_at.clone());
             found.depth = found.depth.merge_to_lowes(url.depth());
-            found.is_in_use = true;
+            found.reservations.push(reserved_at);
+            self.inner
+                .events
+                .emit(GuardianChangedEvent::OriginReserved {
+                    origin: origin.clone(),
+                    depth: *url.depth(),
+                });
 
             return Ok(UrlGuard {
                 reserved_at,
-                origin_manager: self as *const InMemoryUrlGuardian,
+                guardian: self.downgrade(),
                 entry: found.clone(),
                 origin,
                 _marker: PhantomData,
@@ -87,14 +154,20 @@ impl UrlGuardian for InMemoryUrlGuardian {
         }
         let reserved_at = SystemTime::now();
         let entry = GuardEntry {
-            is_in_use: true,
+            reservations: vec![reserved_at],
             last_modification: None,
             depth: url.depth().clone(),
         };
         holder.insert(origin.clone(), entry.clone());
+        self.inner
+            .events
+            .emit(GuardianChangedEvent::OriginReserved {
+                origin: origin.clone(),
+                depth: *url.depth(),
+            });
         Ok(UrlGuard {
             reserved_at,
-            origin_manager: self as *const InMemoryUrlGuardian,
+            guardian: self.downgrade(),
             origin,
             entry,
             _marker: PhantomData,
@@ -130,7 +203,7 @@ impl UrlGuardian for InMemoryUrlGuardian {
         let read = self.inner.read().await;
         read.iter()
             .filter_map(|(host, state)| {
-                if state.is_in_use {
+                if state.is_in_use() {
                     Some(host.clone())
                 } else {
                     None
@@ -145,30 +218,42 @@ impl UrlGuardian for InMemoryUrlGuardian {
     ) -> Result<(), GuardPoisonedError> {
         let read = self.inner.read().await;
         if let Some(found) = read.get(&guard.origin) {
-            if found.is_in_use {
-                if let Some(ref modification) = found.last_modification {
-                    if guard.reserved_at.eq(modification) {
-                        Ok(())
-                    } else {
-                        Err(GuardPoisonedError::WrongTimestampSet(
-                            guard.origin.clone(),
-                            guard.reserved_at,
-                            modification.clone(),
-                        ))
-                    }
-                } else {
-                    Err(GuardPoisonedError::NoTimestampSet(guard.origin.clone()))
-                }
-            } else {
+            if !found.is_in_use() {
                 Err(GuardPoisonedError::InUseNotSet(guard.origin.clone()))
+            } else if found.reservations.contains(&guard.reserved_at) {
+                Ok(())
+            } else {
+                Err(GuardPoisonedError::ReservationMissing(
+                    guard.origin.clone(),
+                    guard.reserved_at,
+                    found.reservations.clone(),
+                ))
             }
         } else {
             Err(GuardPoisonedError::OriginMissing(guard.origin.clone()))
         }
     }
 
-    fn subscribe(&self) -> Receiver<GuardianChangedEvent> {
-        self.inner.broadcast.subscribe()
+    fn announce_cooldown<'a>(&self, guard: &UrlGuard<'a, Self>, until: SystemTime) {
+        // Hold the lock, the events of an origin are emitted in the order of the changes.
+        let _holder = self
+            .inner
+            .write_blocking()
+            .unwrap_or_else(PoisonError::into_inner);
+        self.inner
+            .events
+            .emit(GuardianChangedEvent::OriginCooldown {
+                origin: guard.origin.clone(),
+                until,
+            });
+    }
+
+    fn subscribe(&self) -> GuardianEventReceiver {
+        self.inner.events.subscribe()
+    }
+
+    fn dropped_events(&self) -> u64 {
+        self.inner.events.dropped()
     }
 }
 
@@ -189,17 +274,19 @@ impl Default for InMemoryUrlGuardian {
 #[derive(Debug)]
 struct InMemoryUrlGuardianState {
     data_holder: std::sync::RwLock<HashMap<AtraUrlOrigin, GuardEntry>>,
-    broadcast: tokio::sync::watch::Sender<GuardianChangedEvent>,
+    events: GuardianEvents,
+    max_parallel: NonZeroUsize,
 }
 
 type ReadResult<'a> = LockResult<RwLockReadGuard<'a, HashMap<AtraUrlOrigin, GuardEntry>>>;
 type WriteResult<'a> = LockResult<RwLockWriteGuard<'a, HashMap<AtraUrlOrigin, GuardEntry>>>;
 
 impl InMemoryUrlGuardianState {
-    pub fn new() -> Self {
+    pub fn new(max_parallel: NonZeroUsize, events: GuardianEvents) -> Self {
         Self {
             data_holder: Default::default(),
-            broadcast: tokio::sync::watch::Sender::new(GuardianChangedEvent),
+            events,
+            max_parallel,
         }
     }
 
@@ -244,12 +331,19 @@ impl InMemoryUrlGuardianState {
 
 #[cfg(test)]
 mod test {
-    use crate::url::guard::{GuardianError, UrlGuardian};
+    use crate::url::guard::entry::GuardEntry;
+    use crate::url::guard::{
+        GuardPoisonedError, GuardianChangedEvent, GuardianError, GuardianEvents,
+        InMemoryUrlGuardian, UrlGuard, UrlGuardian,
+    };
     use crate::url::{AtraOriginProvider, UrlWithDepth};
     use itertools::{Itertools, Position};
     use smallvec::SmallVec;
+    use std::marker::PhantomData;
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
-    use std::time::Duration;
+    use std::time::{Duration, SystemTime};
 
     #[tokio::test]
     async fn the_domain_works_as_expected() {
@@ -359,4 +453,187 @@ mod test {
             )
         }
     }
+
+    #[tokio::test]
+    async fn multiple_guards_per_origin_are_not_poisoned() {
+        let host_manager = InMemoryUrlGuardian::with_max_parallel(NonZeroUsize::new(2).unwrap());
+        let url = "https://www.example.com/".parse::<UrlWithDepth>().unwrap();
+        let first = host_manager.try_reserve(&url).await.unwrap();
+        let second = host_manager.try_reserve(&url).await.unwrap();
+        assert!(matches!(
+            host_manager.try_reserve(&url).await,
+            Err(GuardianError::AlreadyOccupied(_))
+        ));
+        first.check_for_poison().await.unwrap();
+        second.check_for_poison().await.unwrap();
+        assert_eq!(2, host_manager.current_origin_state(&url).await.unwrap().in_use());
+
+        drop(first);
+        second.check_for_poison().await.unwrap();
+        let third = host_manager.try_reserve(&url).await.unwrap();
+        third.check_for_poison().await.unwrap();
+        drop(second);
+        drop(third);
+        let state = host_manager.current_origin_state(&url).await.unwrap();
+        assert!(!state.is_in_use());
+        assert!(state.last_modification().is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn the_parallel_limit_is_never_exceeded() {
+        const LIMIT: usize = 3;
+        let host_manager = InMemoryUrlGuardian::with_max_parallel(NonZeroUsize::new(LIMIT).unwrap());
+        let active = Arc::new(AtomicUsize::new(0));
+        let max_active = Arc::new(AtomicUsize::new(0));
+
+        let mut handles = Vec::new();
+        for i in 0..64 {
+            let host_manager = host_manager.clone();
+            let active = active.clone();
+            let max_active = max_active.clone();
+            handles.push(tokio::task::spawn(async move {
+                let url = format!("https://www.example.com/page{i}.html")
+                    .parse::<UrlWithDepth>()
+                    .unwrap();
+                let guard = loop {
+                    match host_manager.try_reserve(&url).await {
+                        Ok(guard) => break guard,
+                        Err(GuardianError::AlreadyOccupied(_)) => tokio::task::yield_now().await,
+                        Err(err) => panic!("Unexpected error: {err}"),
+                    }
+                };
+                let now_active = active.fetch_add(1, Ordering::SeqCst) + 1;
+                max_active.fetch_max(now_active, Ordering::SeqCst);
+                assert!(now_active <= LIMIT, "{now_active} guards for one origin!");
+                guard.check_for_poison().await.unwrap();
+                tokio::time::sleep(Duration::from_millis(2)).await;
+                guard.check_for_poison().await.unwrap();
+                active.fetch_sub(1, Ordering::SeqCst);
+                drop(guard);
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let max_active = max_active.load(Ordering::SeqCst);
+        assert!(max_active <= LIMIT);
+        assert!(max_active > 1, "The origin was never crawled in parallel.");
+        let state = host_manager
+            .current_origin_state(&"https://www.example.com/".parse::<UrlWithDepth>().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(0, state.in_use());
+    }
+
+    #[tokio::test]
+    async fn subscribers_receive_the_exact_sequence_of_events() {
+        let host_manager = InMemoryUrlGuardian::with_max_parallel(NonZeroUsize::new(2).unwrap());
+        let first_url = "https://www.example.com/a".parse::<UrlWithDepth>().unwrap();
+        let second_url = "https://www.example.com/b".parse::<UrlWithDepth>().unwrap();
+        let origin = first_url.atra_origin().unwrap();
+        let mut receiver = host_manager.subscribe();
+
+        let first = host_manager.try_reserve(&first_url).await.unwrap();
+        let second = host_manager.try_reserve(&second_url).await.unwrap();
+        let until = SystemTime::now() + Duration::from_secs(1);
+        first.announce_cooldown(until);
+        drop(first);
+        drop(second);
+
+        let mut events = Vec::new();
+        while let Some(mut event) = receiver.try_recv() {
+            if let GuardianChangedEvent::OriginReleased { duration_held, .. } = &mut event {
+                *duration_held = Duration::ZERO;
+            }
+            events.push(event);
+        }
+        assert_eq!(
+            vec![
+                GuardianChangedEvent::OriginReserved {
+                    origin: origin.clone(),
+                    depth: *first_url.depth(),
+                },
+                GuardianChangedEvent::OriginReserved {
+                    origin: origin.clone(),
+                    depth: *second_url.depth(),
+                },
+                GuardianChangedEvent::OriginCooldown {
+                    origin: origin.clone(),
+                    until,
+                },
+                GuardianChangedEvent::OriginReleased {
+                    origin: origin.clone(),
+                    duration_held: Duration::ZERO,
+                },
+                GuardianChangedEvent::OriginReleased {
+                    origin: origin.clone(),
+                    duration_held: Duration::ZERO,
+                },
+                GuardianChangedEvent::OriginCompleted { origin },
+            ],
+            events
+        );
+        assert_eq!(0, host_manager.dropped_events());
+    }
+
+    #[tokio::test]
+    async fn stalled_subscribers_count_the_dropped_events() {
+        let host_manager = InMemoryUrlGuardian::with_events(
+            NonZeroUsize::new(1).unwrap(),
+            GuardianEvents::with_capacity(2),
+        );
+        let url = "https://www.example.com/".parse::<UrlWithDepth>().unwrap();
+        let origin = url.atra_origin().unwrap();
+        let mut receiver = host_manager.subscribe();
+
+        // Every cycle emits a reservation, a release and a completion.
+        for _ in 0..3 {
+            drop(host_manager.try_reserve(&url).await.unwrap());
+        }
+        assert_eq!(0, host_manager.dropped_events());
+
+        let mut events = Vec::new();
+        while let Some(event) = receiver.try_recv() {
+            events.push(event);
+        }
+        assert_eq!(7, host_manager.dropped_events());
+        assert_eq!(7, receiver.missed());
+        assert_eq!(2, events.len());
+        assert!(matches!(
+            &events[0],
+            GuardianChangedEvent::OriginReleased { origin: found, .. } if found == &origin
+        ));
+        assert_eq!(GuardianChangedEvent::OriginCompleted { origin }, events[1]);
+    }
+
+    #[tokio::test]
+    async fn a_guard_can_outlive_its_guardian() {
+        let host_manager = InMemoryUrlGuardian::new();
+        let url = "https://www.example.com/".parse::<UrlWithDepth>().unwrap();
+        let origin = url.atra_origin().unwrap();
+        let mut receiver = host_manager.subscribe();
+        let reserved_at = SystemTime::now();
+        // The borrow of try_reserve prevents this, a guard without it shows the worst case.
+        let guard: UrlGuard<'static, InMemoryUrlGuardian> = UrlGuard {
+            reserved_at,
+            origin: origin.clone(),
+            guardian: host_manager.downgrade(),
+            entry: GuardEntry {
+                reservations: vec![reserved_at],
+                last_modification: None,
+                depth: *url.depth(),
+            },
+            _marker: PhantomData,
+        };
+        drop(host_manager);
+
+        assert!(matches!(
+            guard.check_for_poison().await,
+            Err(GuardPoisonedError::GuardianDropped(found)) if found == origin
+        ));
+        guard.announce_cooldown(SystemTime::now() + Duration::from_secs(1));
+        drop(guard);
+        assert_eq!(None, receiver.try_recv());
+    }
 }
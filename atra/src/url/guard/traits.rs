@@ -13,31 +13,37 @@
 // limitations under the License.
 
 use crate::url::guard::entry::GuardEntry;
-use crate::url::guard::{GuardPoisonedError, GuardianError, UrlGuard};
+use crate::url::guard::{GuardPoisonedError, GuardianError, GuardianEventReceiver, UrlGuard};
 use crate::url::AtraUrlOrigin;
 use crate::url::UrlWithDepth;
 use std::fmt::Debug;
+use std::time::SystemTime;
 
-/// An event fired when an url guardian changes
-#[derive(Debug, Copy, Clone)]
-pub struct GuardianChangedEvent;
-
-/// Basic api that is not public to the rest of the code
-pub unsafe trait UnsafeUrlGuardian {
-    /// Lazily releases the host. This code may be unsafe or cause
-    /// unforeseen crashes when not handled properly.
-    ///
-    /// This method is ONLY called when a guard is released. (see [super::UrlGuard])
-    unsafe fn release(&self, origin: AtraUrlOrigin);
+/// The reference a guard keeps to its guardian. It does not keep the guardian alive,
+/// a guard may outlive the guardian that created it.
+pub trait WeakUrlGuardian<Guardian>: Send + Sync {
+    /// Returns the guardian, none if it was already dropped.
+    fn upgrade(&self) -> Option<Guardian>;
 }
 
-/// A class capable of managing origins
-pub trait UrlGuardian: UnsafeUrlGuardian
+/// A class capable of managing origins, shared by all workers of a crawl.
+pub trait UrlGuardian: Send + Sync
 where
     Self: Sized,
 {
+    /// The reference kept by the guards of this guardian.
+    type Weak: WeakUrlGuardian<Self>;
+
+    /// Returns the reference kept by the guards of this guardian.
+    fn downgrade(&self) -> Self::Weak;
+
+    /// Releases the reservation of the [origin] made at [reserved_at].
+    ///
+    /// This method is ONLY called when a guard is released. (see [super::UrlGuard])
+    fn release(&self, origin: AtraUrlOrigin, reserved_at: SystemTime);
+
     /// Returns a guard if the reserve was successful.
-    /// Returns an error if there is the domain is already in use.
+    /// Returns an error if the domain is already held by the maximum number of guards.
     async fn try_reserve<'a>(
         &'a self,
         url: &UrlWithDepth,
@@ -62,6 +68,12 @@ where
         guard: &UrlGuard<'a, Self>,
     ) -> Result<(), GuardPoisonedError>;
 
+    /// Announces that the [guard] keeps its origin until [until] to respect the politeness delay.
+    fn announce_cooldown<'a>(&self, guard: &UrlGuard<'a, Self>, until: SystemTime);
+
     /// Provides a way to subscribe to an url guardian to receive changes.
-    fn subscribe(&self) -> tokio::sync::watch::Receiver<GuardianChangedEvent>;
+    fn subscribe(&self) -> GuardianEventReceiver;
+
+    /// The number of events missed by lagging subscribers so far.
+    fn dropped_events(&self) -> u64;
 }
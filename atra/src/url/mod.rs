@@ -14,10 +14,14 @@
 
 mod atra_uri;
 pub mod cleaner;
+pub mod dates;
 mod depth;
+pub mod filter;
 pub mod guard;
 mod guarded;
+pub mod normalization;
 mod origin;
+pub mod traps;
 mod url_with_depth;
 
 pub use atra_uri::*;
@@ -0,0 +1,484 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use strum::Display;
+use url::Url;
+
+/// The configuration for the url normalization.
+/// The scheme and host are always lowercased, default ports are removed, dot segments are
+/// resolved and fragments are dropped while parsing the url.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct UrlNormalizationConfig {
+    /// Sorts the query parameters by their key. (default: false)
+    pub sort_query_parameters: bool,
+    /// Origins where the order of the query parameters matters, the sorting is skipped for them.
+    pub ordered_query_origins: HashSet<AtraUrlOrigin>,
+    /// Query keys marking a signed url. If any of them is present the query is not rewritten.
+    /// The keys are compared case-insensitive.
+    pub signed_url_params: Vec<String>,
+    /// Removes the [UrlNormalizationConfig::tracking_params] from the query. (default: false)
+    pub strip_tracking_params: bool,
+    /// Query keys used for tracking, a trailing `*` matches any key with the prefix.
+    /// The keys are compared case-insensitive.
+    pub tracking_params: Vec<String>,
+    /// Removes the trailing slash of a path, except for the root path. (default: false)
+    pub strip_trailing_slash: bool,
+    /// Lowercases the path, only for sites with case-insensitive paths. (default: false)
+    pub lowercase_path: bool,
+}
+
+impl Default for UrlNormalizationConfig {
+    fn default() -> Self {
+        Self {
+            sort_query_parameters: false,
+            ordered_query_origins: HashSet::new(),
+            signed_url_params: vec![
+                "X-Amz-Signature".to_string(),
+                "X-Goog-Signature".to_string(),
+                "Signature".to_string(),
+                "sig".to_string(),
+                "token".to_string(),
+            ],
+            strip_tracking_params: false,
+            tracking_params: vec![
+                "utm_*".to_string(),
+                "gclid".to_string(),
+                "fbclid".to_string(),
+                "msclkid".to_string(),
+                "mc_eid".to_string(),
+            ],
+            strip_trailing_slash: false,
+            lowercase_path: false,
+        }
+    }
+}
+
+/// The outcome of a normalization
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Display)]
+pub enum NormalizationOutcome {
+    /// Nothing to do for the url.
+    Unchanged,
+    /// The url was rewritten.
+    Normalized,
+    /// The origin of the url is marked as order sensitive.
+    SuppressedByOrigin,
+    /// The url contains a signature parameter.
+    SuppressedBySignature,
+}
+
+impl UrlNormalizationConfig {
+    /// Returns true if the normalization does anything at all.
+    pub fn is_active(&self) -> bool {
+        self.sort_query_parameters
+            || self.strip_tracking_params
+            || self.strip_trailing_slash
+            || self.lowercase_path
+    }
+
+    /// Returns true if the query contains any of the configured signature parameters.
+    pub fn is_signed(&self, url: &Url) -> bool {
+        let Some(query) = url.query() else {
+            return false;
+        };
+        query_keys(query).any(|key| {
+            self.signed_url_params
+                .iter()
+                .any(|param| param.eq_ignore_ascii_case(key))
+        })
+    }
+
+    /// Returns true if the query [key] is one of the configured tracking parameters.
+    pub fn is_tracking_param(&self, key: &str) -> bool {
+        self.tracking_params
+            .iter()
+            .any(|param| match param.strip_suffix('*') {
+                Some(prefix) => key
+                    .get(..prefix.len())
+                    .is_some_and(|start| start.eq_ignore_ascii_case(prefix)),
+                None => param.eq_ignore_ascii_case(key),
+            })
+    }
+
+    /// Normalizes the [url] in place and returns what happened.
+    pub fn normalize(&self, url: &mut AtraUri) -> NormalizationOutcome {
+        if !self.is_active() {
+            return NormalizationOutcome::Unchanged;
+        }
+        match url {
+            AtraUri::Url(value) => self.normalize_url(value),
+        }
+    }
+
+    fn normalize_url(&self, url: &mut Url) -> NormalizationOutcome {
+        let path_changed = self.normalize_path(url);
+        match self.normalize_query(url) {
+            NormalizationOutcome::Unchanged if path_changed => NormalizationOutcome::Normalized,
+            outcome => outcome,
+        }
+    }
+
+    /// Returns true if the path was rewritten.
+    fn normalize_path(&self, url: &mut Url) -> bool {
+        if url.cannot_be_a_base() {
+            return false;
+        }
+        let mut path = url.path().to_string();
+        if self.lowercase_path {
+            path = path.to_lowercase();
+        }
+        if self.strip_trailing_slash {
+            let trimmed = path.trim_end_matches('/');
+            path = if trimmed.is_empty() {
+                "/".to_string()
+            } else {
+                trimmed.to_string()
+            };
+        }
+        if path == url.path() {
+            return false;
+        }
+        url.set_path(&path);
+        true
+    }
+
+    fn normalize_query(&self, url: &mut Url) -> NormalizationOutcome {
+        let Some(query) = url.query() else {
+            return NormalizationOutcome::Unchanged;
+        };
+        if query.is_empty() {
+            return NormalizationOutcome::Unchanged;
+        }
+        if self.is_signed(url) {
+            return NormalizationOutcome::SuppressedBySignature;
+        }
+        let mut segments = query.split('&').collect::<Vec<_>>();
+        if self.strip_tracking_params {
+            segments.retain(|segment| !self.is_tracking_param(segment_key(segment)));
+        }
+        let mut suppressed = false;
+        if self.sort_query_parameters {
+            let ordered = !self.ordered_query_origins.is_empty()
+                && url
+                    .atra_origin()
+                    .is_some_and(|origin| self.ordered_query_origins.contains(&origin));
+            if ordered {
+                suppressed = true;
+            } else {
+                // Stable, the order of repeated keys is kept.
+                segments.sort_by(|a, b| segment_key(a).cmp(segment_key(b)));
+            }
+        }
+        let rewritten = segments.join("&");
+        if rewritten == query {
+            return if suppressed {
+                NormalizationOutcome::SuppressedByOrigin
+            } else {
+                NormalizationOutcome::Unchanged
+            };
+        }
+        url.set_query((!rewritten.is_empty()).then_some(rewritten.as_str()));
+        NormalizationOutcome::Normalized
+    }
+}
+
+#[inline]
+fn segment_key(segment: &str) -> &str {
+    match segment.find('=') {
+        None => segment,
+        Some(pos) => &segment[..pos],
+    }
+}
+
+#[inline]
+fn query_keys(query: &str) -> impl Iterator<Item = &str> {
+    query.split('&').map(segment_key)
+}
+
+/// Counts what the normalization did during a crawl.
+#[derive(Debug, Default)]
+pub struct UrlNormalizationStats {
+    normalized: AtomicU64,
+    suppressed_by_origin: AtomicU64,
+    suppressed_by_signature: AtomicU64,
+}
+
+impl UrlNormalizationStats {
+    pub fn register(&self, outcome: NormalizationOutcome) {
+        match outcome {
+            NormalizationOutcome::Unchanged => {}
+            NormalizationOutcome::Normalized => {
+                self.normalized.fetch_add(1, Ordering::Relaxed);
+            }
+            NormalizationOutcome::SuppressedByOrigin => {
+                self.suppressed_by_origin.fetch_add(1, Ordering::Relaxed);
+            }
+            NormalizationOutcome::SuppressedBySignature => {
+                self.suppressed_by_signature.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// The number of rewritten urls
+    pub fn normalized(&self) -> u64 {
+        self.normalized.load(Ordering::Relaxed)
+    }
+
+    /// The number of urls where an ordered origin suppressed the normalization
+    pub fn suppressed_by_origin(&self) -> u64 {
+        self.suppressed_by_origin.load(Ordering::Relaxed)
+    }
+
+    /// The number of urls where a signature parameter suppressed the normalization
+    pub fn suppressed_by_signature(&self) -> u64 {
+        self.suppressed_by_signature.load(Ordering::Relaxed)
+    }
+
+    /// The number of urls where any exception suppressed the normalization
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed_by_origin() + self.suppressed_by_signature()
+    }
+}
+
+/// Applies the normalization and keeps track of the stats.
+/// The original of every rewritten url is kept until it is taken after the crawl of the url.
+#[derive(Debug, Default)]
+pub struct UrlNormalizer {
+    config: UrlNormalizationConfig,
+    stats: UrlNormalizationStats,
+    originals: Mutex<HashMap<String, String>>,
+}
+
+impl UrlNormalizer {
+    pub fn new(config: UrlNormalizationConfig) -> Self {
+        Self {
+            config,
+            stats: UrlNormalizationStats::default(),
+            originals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &UrlNormalizationConfig {
+        &self.config
+    }
+
+    pub fn stats(&self) -> &UrlNormalizationStats {
+        &self.stats
+    }
+
+    /// Normalizes the [url] in place.
+    pub fn normalize(&self, url: &mut UrlWithDepth) -> NormalizationOutcome {
+        if !self.config.is_active() {
+            return NormalizationOutcome::Unchanged;
+        }
+        let original = url.url.to_string();
+        let outcome = self.config.normalize(&mut url.url);
+        self.stats.register(outcome);
+        if outcome == NormalizationOutcome::Normalized {
+            self.originals
+                .lock()
+                .unwrap()
+                .entry(url.url.to_string())
+                .or_insert(original);
+        }
+        outcome
+    }
+
+    /// Returns a normalized copy of the [url].
+    pub fn normalized(&self, url: &UrlWithDepth) -> UrlWithDepth {
+        let mut url = url.clone();
+        self.normalize(&mut url);
+        url
+    }
+
+    /// Returns the url before the normalization of the normalized [url] and forgets it.
+    /// Returns None if the url was not rewritten or the original is already taken.
+    pub fn take_original(&self, url: &UrlWithDepth) -> Option<String> {
+        self.originals
+            .lock()
+            .unwrap()
+            .remove(url.url.to_string().as_str())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn normalizer() -> UrlNormalizer {
+        let mut config = UrlNormalizationConfig::default();
+        config.sort_query_parameters = true;
+        config
+            .ordered_query_origins
+            .insert(AtraUrlOrigin::from("legacy-cgi.org"));
+        UrlNormalizer::new(config)
+    }
+
+    #[test]
+    fn sorts_query_and_keeps_repeated_keys_in_order() {
+        let normalizer = normalizer();
+        let url = normalizer.normalized(
+            &UrlWithDepth::from_url("https://www.example.com/page?b=2&a=1&c=3&a=0").unwrap(),
+        );
+        assert_eq!(
+            "https://www.example.com/page?a=1&a=0&b=2&c=3",
+            url.url.as_str()
+        );
+        assert_eq!(1, normalizer.stats().normalized());
+    }
+
+    #[test]
+    fn signed_url_passes_through_untouched() {
+        let normalizer = normalizer();
+        let signed = "https://bucket.s3.amazonaws.com/file.pdf?X-Amz-Expires=300&X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Signature=abcdef0123&X-Amz-Date=20240101T000000Z";
+        let ordinary = "https://bucket.s3.amazonaws.com/index.html?z=1&y=2";
+
+        let signed_url = normalizer.normalized(&UrlWithDepth::from_url(signed).unwrap());
+        let ordinary_url = normalizer.normalized(&UrlWithDepth::from_url(ordinary).unwrap());
+
+        assert_eq!(signed, signed_url.url.as_str());
+        assert_eq!(
+            "https://bucket.s3.amazonaws.com/index.html?y=2&z=1",
+            ordinary_url.url.as_str()
+        );
+        assert_eq!(1, normalizer.stats().suppressed_by_signature());
+        assert_eq!(1, normalizer.stats().normalized());
+    }
+
+    #[test]
+    fn ordered_origin_is_not_sorted() {
+        let normalizer = normalizer();
+        let raw = "https://www.legacy-cgi.org/cgi-bin/run.cgi?id=5&cmd=show";
+        let url = normalizer.normalized(&UrlWithDepth::from_url(raw).unwrap());
+        assert_eq!(raw, url.url.as_str());
+        assert_eq!(1, normalizer.stats().suppressed_by_origin());
+        assert_eq!(1, normalizer.stats().suppressed());
+
+        // The same query of any other origin is sorted.
+        let other = normalizer.normalized(
+            &UrlWithDepth::from_url("https://www.example.com/cgi-bin/run.cgi?id=5&cmd=show")
+                .unwrap(),
+        );
+        assert_eq!(
+            "https://www.example.com/cgi-bin/run.cgi?cmd=show&id=5",
+            other.url.as_str()
+        );
+    }
+
+    #[test]
+    fn inactive_does_nothing() {
+        let normalizer = UrlNormalizer::default();
+        let raw = "https://www.example.com/?b=1&a=2";
+        let url = normalizer.normalized(&UrlWithDepth::from_url(raw).unwrap());
+        assert_eq!(raw, url.url.as_str());
+        assert_eq!(0, normalizer.stats().normalized());
+    }
+
+    #[test]
+    fn canonicalizes_the_table() {
+        let mut config = UrlNormalizationConfig::default();
+        config.sort_query_parameters = true;
+        config.strip_tracking_params = true;
+        config.strip_trailing_slash = true;
+        let normalizer = UrlNormalizer::new(config);
+        for (input, expected) in [
+            (
+                "HTTPS://WWW.Example.COM/Path",
+                "https://www.example.com/Path",
+            ),
+            ("https://www.example.com:443/a", "https://www.example.com/a"),
+            ("http://www.example.com:80/a", "http://www.example.com/a"),
+            (
+                "http://www.example.com:8080/a",
+                "http://www.example.com:8080/a",
+            ),
+            (
+                "https://www.example.com/a/./b/../c",
+                "https://www.example.com/a/c",
+            ),
+            (
+                "https://www.example.com/a#section",
+                "https://www.example.com/a",
+            ),
+            ("https://www.example.com/a/", "https://www.example.com/a"),
+            ("https://www.example.com/", "https://www.example.com/"),
+            (
+                "https://www.example.com/a?b=1&utm_source=x",
+                "https://www.example.com/a?b=1",
+            ),
+            (
+                "https://www.example.com/a?UTM_Medium=x&gclid=1&fbclid=2",
+                "https://www.example.com/a",
+            ),
+            (
+                "https://www.example.com/a?c=3&utm=1&a=1",
+                "https://www.example.com/a?a=1&c=3&utm=1",
+            ),
+            (
+                "https://www.example.com/a/?z=1#top",
+                "https://www.example.com/a?z=1",
+            ),
+        ] {
+            let url = normalizer.normalized(&UrlWithDepth::from_url(input).unwrap());
+            assert_eq!(expected, url.url.as_str(), "for {input}");
+        }
+    }
+
+    #[test]
+    fn paths_keep_their_case_by_default() {
+        let raw = "https://www.example.com/Docs/Index.HTML";
+        let mut config = UrlNormalizationConfig::default();
+        config.strip_tracking_params = true;
+        let url = UrlNormalizer::new(config).normalized(&UrlWithDepth::from_url(raw).unwrap());
+        assert_eq!(raw, url.url.as_str());
+
+        let mut config = UrlNormalizationConfig::default();
+        config.lowercase_path = true;
+        let url = UrlNormalizer::new(config).normalized(&UrlWithDepth::from_url(raw).unwrap());
+        assert_eq!("https://www.example.com/docs/index.html", url.url.as_str());
+    }
+
+    #[test]
+    fn signed_url_keeps_its_tracking_params() {
+        let mut config = UrlNormalizationConfig::default();
+        config.strip_tracking_params = true;
+        let normalizer = UrlNormalizer::new(config);
+        let raw = "https://cdn.example.com/file.pdf?utm_source=x&token=abc";
+        let url = normalizer.normalized(&UrlWithDepth::from_url(raw).unwrap());
+        assert_eq!(raw, url.url.as_str());
+        assert_eq!(1, normalizer.stats().suppressed_by_signature());
+    }
+
+    #[test]
+    fn keeps_the_original_until_taken() {
+        let mut config = UrlNormalizationConfig::default();
+        config.strip_tracking_params = true;
+        let normalizer = UrlNormalizer::new(config);
+        let raw = "https://www.example.com/a?utm_source=x";
+        let url = normalizer.normalized(&UrlWithDepth::from_url(raw).unwrap());
+        normalizer.normalized(&UrlWithDepth::from_url("https://www.example.com/b").unwrap());
+
+        assert_eq!(Some(raw.to_string()), normalizer.take_original(&url));
+        assert_eq!(None, normalizer.take_original(&url));
+        assert_eq!(
+            None,
+            normalizer.take_original(&UrlWithDepth::from_url("https://www.example.com/b").unwrap())
+        );
+    }
+}
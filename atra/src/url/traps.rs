@@ -0,0 +1,368 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The configuration of the detection of infinite url spaces like calendars, faceted
+/// search or session ids in urls.
+#[derive(Debug, Clone, Deserialize, Serialize, Eq, PartialEq)]
+#[serde(default)]
+pub struct TrapDetectionConfig {
+    /// The number of distinct urls of an origin that only differ in their numbers or ids
+    /// before the other urls of the same shape are dropped. (default: 10000)
+    pub max_variants: usize,
+    /// How often the same segment may appear in a single path. (default: 3)
+    pub max_segment_repetitions: usize,
+    /// The maximum number of segments of a path. (default: 32)
+    pub max_path_depth: usize,
+    /// Origins that are never checked, e.g. shops with a lot of numbered products.
+    pub allowed_origins: HashSet<AtraUrlOrigin>,
+}
+
+impl Default for TrapDetectionConfig {
+    fn default() -> Self {
+        Self {
+            max_variants: 10_000,
+            max_segment_repetitions: 3,
+            max_path_depth: 32,
+            allowed_origins: HashSet::new(),
+        }
+    }
+}
+
+/// The inferred pattern of an infinite url space.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum TrapPattern {
+    /// Too many urls only differ in the numbers or ids of the shape.
+    Variants { shape: String },
+    /// A segment repeats itself in the path.
+    RepeatedSegment { segment: String },
+    /// The path is deeper than allowed.
+    TooDeep { depth: usize },
+}
+
+impl Display for TrapPattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TrapPattern::Variants { shape } => write!(f, "variants={shape}"),
+            TrapPattern::RepeatedSegment { segment } => write!(f, "repeated_segment={segment}"),
+            TrapPattern::TooDeep { depth } => write!(f, "path_depth={depth}"),
+        }
+    }
+}
+
+/// The placeholder of a variable part of an url, if the [token] is one.
+fn placeholder(token: &str) -> Option<&'static str> {
+    if token.is_empty() {
+        return None;
+    }
+    // Numbers and dates like 2024-05-01 or 12:30
+    if token.bytes().any(|b| b.is_ascii_digit())
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_digit() || matches!(b, b'-' | b'_' | b'.' | b':'))
+    {
+        return Some("{n}");
+    }
+    // Session ids, uuids and hashes
+    if token.len() >= 16
+        && token.bytes().any(|b| b.is_ascii_digit())
+        && token.bytes().any(|b| b.is_ascii_alphabetic())
+        && token
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+    {
+        return Some("{id}");
+    }
+    None
+}
+
+/// The shape of an url with placeholders for the variable parts and the variable parts.
+/// Returns None if the url has no variable parts.
+fn shape_of(path: &[&str], query: Option<&str>) -> Option<(String, String)> {
+    let mut shape = String::new();
+    let mut variables = String::new();
+    let mut push = |shape_part: &str, token: &str| match placeholder(token) {
+        Some(placeholder) => {
+            shape.push_str(shape_part);
+            shape.push_str(placeholder);
+            variables.push_str(token);
+            variables.push('\u{1f}');
+        }
+        None => {
+            shape.push_str(shape_part);
+            shape.push_str(token);
+        }
+    };
+    for segment in path {
+        push("/", segment);
+    }
+    if let Some(query) = query {
+        for (i, pair) in query.split('&').enumerate() {
+            let separator = if i == 0 { "?" } else { "&" };
+            match pair.split_once('=') {
+                Some((key, value)) => push(&format!("{separator}{key}="), value),
+                None => push(separator, pair),
+            }
+        }
+    }
+    (!variables.is_empty()).then_some((shape, variables))
+}
+
+#[derive(Debug, Default)]
+struct OriginStatistics {
+    /// The hashes of the distinct variables of each shape, at most the max variants.
+    shapes: HashMap<String, HashSet<u64>>,
+    reported: HashSet<TrapPattern>,
+}
+
+/// Decides for every found url if it belongs to an infinite url space of its origin.
+/// The statistics of the origins are kept in memory.
+#[derive(Debug, Default)]
+pub struct TrapDetector {
+    config: TrapDetectionConfig,
+    origins: Mutex<HashMap<AtraUrlOrigin, OriginStatistics>>,
+    suppressed: AtomicU64,
+}
+
+impl TrapDetector {
+    pub fn new(config: TrapDetectionConfig) -> Self {
+        Self {
+            config,
+            origins: Mutex::new(HashMap::new()),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+
+    pub fn config(&self) -> &TrapDetectionConfig {
+        &self.config
+    }
+
+    /// The number of urls dropped as part of a trap.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    /// Returns the pattern of the trap if [url] must not be enqueued.
+    /// The first time a pattern is found for an origin it is logged as warning.
+    pub fn check(&self, url: &UrlWithDepth) -> Option<TrapPattern> {
+        let origin = url.atra_origin()?;
+        if self.config.allowed_origins.contains(&origin) {
+            return None;
+        }
+        let AtraUri::Url(value) = &url.url;
+        let path: Vec<&str> = value
+            .path_segments()?
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let mut origins = self.origins.lock().unwrap();
+        let statistics = origins.entry(origin.clone()).or_default();
+        let pattern = self
+            .check_path(&path)
+            .or_else(|| Self::check_shape(&self.config, statistics, &path, value.query()))?;
+        self.suppressed.fetch_add(1, Ordering::Relaxed);
+        if statistics.reported.insert(pattern.clone()) {
+            log::warn!(
+                "!!! Stop enqueueing urls of {origin} with {pattern}, probably a crawler trap. First dropped: {url} !!!"
+            );
+        } else {
+            log::debug!("Dropped {url}, it matches the trap {pattern} of {origin}.");
+        }
+        Some(pattern)
+    }
+
+    fn check_path(&self, path: &[&str]) -> Option<TrapPattern> {
+        if path.len() > self.config.max_path_depth {
+            return Some(TrapPattern::TooDeep { depth: path.len() });
+        }
+        let mut repetitions = HashMap::with_capacity(path.len());
+        for segment in path {
+            let count = repetitions.entry(*segment).or_insert(0usize);
+            *count += 1;
+            if *count > self.config.max_segment_repetitions {
+                return Some(TrapPattern::RepeatedSegment {
+                    segment: segment.to_string(),
+                });
+            }
+        }
+        None
+    }
+
+    fn check_shape(
+        config: &TrapDetectionConfig,
+        statistics: &mut OriginStatistics,
+        path: &[&str],
+        query: Option<&str>,
+    ) -> Option<TrapPattern> {
+        let (shape, variables) = shape_of(path, query)?;
+        let mut hasher = DefaultHasher::new();
+        variables.hash(&mut hasher);
+        let hash = hasher.finish();
+        let seen = statistics.shapes.entry(shape.clone()).or_default();
+        // Already known variants stay allowed, only new ones beyond the limit are dropped.
+        if seen.contains(&hash) || seen.len() < config.max_variants {
+            seen.insert(hash);
+            return None;
+        }
+        Some(TrapPattern::Variants { shape })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn detector() -> TrapDetector {
+        TrapDetector::new(TrapDetectionConfig {
+            max_variants: 1_000,
+            ..TrapDetectionConfig::default()
+        })
+    }
+
+    fn url(value: String) -> UrlWithDepth {
+        UrlWithDepth::from_url(value).unwrap()
+    }
+
+    /// A calendar with a link to the next month, forever.
+    fn calendar(origin: &str) -> impl Iterator<Item = UrlWithDepth> + '_ {
+        (2000..2200).flat_map(move |year| {
+            (1..=12).map(move |month| {
+                url(format!(
+                    "https://{origin}/calendar?view=month&year={year}&month={month}"
+                ))
+            })
+        })
+    }
+
+    /// Articles, dated news, categories and a few hundred numbered products.
+    fn large_site() -> impl Iterator<Item = UrlWithDepth> {
+        let words = ["alpha", "beta", "gamma", "delta", "epsilon", "zeta", "eta"];
+        let articles = (0..3_000).map(move |i| {
+            url(format!(
+                "https://www.example.com/wiki/{}_{}_{}",
+                words[i % 7],
+                words[(i / 7) % 7],
+                words[(i / 49) % 7]
+            ) + &"_x".repeat(i / 343))
+        });
+        let news = (0..2_000).map(move |i| {
+            url(format!(
+                "https://www.example.com/news/2024/{:02}/{}-{}",
+                i % 12 + 1,
+                words[i % 7],
+                words[(i / 7) % 7].repeat(i / 49 + 1)
+            ))
+        });
+        let products =
+            (0..800).map(|i| url(format!("https://www.example.com/products/{i}?ref=list")));
+        articles.chain(news).chain(products)
+    }
+
+    #[test]
+    fn calendar_trap_is_suppressed() {
+        let detector = detector();
+        let suppressed = calendar("calendar.example.com")
+            .filter(|url| detector.check(url).is_some())
+            .count();
+        assert_eq!(2_400 - 1_000, suppressed);
+        assert_eq!(
+            Some(TrapPattern::Variants {
+                shape: "/calendar?view=month&year={n}&month={n}".to_string()
+            }),
+            detector.check(&url(
+                "https://calendar.example.com/calendar?view=month&year=3000&month=1".to_string()
+            ))
+        );
+        // Already known variants and other shapes of the origin are not affected.
+        assert_eq!(
+            None,
+            detector.check(&url(
+                "https://calendar.example.com/calendar?view=month&year=2000&month=1".to_string()
+            ))
+        );
+        assert_eq!(
+            None,
+            detector.check(&url("https://calendar.example.com/about".to_string()))
+        );
+    }
+
+    #[test]
+    fn large_site_is_not_suppressed() {
+        let detector = detector();
+        let suppressed: Vec<_> = large_site()
+            .filter_map(|url| detector.check(&url).map(|pattern| (url, pattern)))
+            .collect();
+        assert!(suppressed.is_empty(), "{suppressed:?}");
+        assert_eq!(0, detector.suppressed());
+    }
+
+    #[test]
+    fn allowed_origins_are_never_suppressed() {
+        let mut config = detector().config().clone();
+        config
+            .allowed_origins
+            .insert(AtraUrlOrigin::from("calendar.example.com"));
+        let detector = TrapDetector::new(config);
+        assert_eq!(
+            0,
+            calendar("calendar.example.com")
+                .filter(|url| detector.check(url).is_some())
+                .count()
+        );
+        assert!(calendar("other.example.com").any(|url| detector.check(&url).is_some()));
+    }
+
+    #[test]
+    fn repeated_segments_and_deep_paths_are_suppressed() {
+        let detector = detector();
+        assert_eq!(
+            None,
+            detector.check(&url("https://example.com/a/b/a/b/a/b/".to_string()))
+        );
+        assert_eq!(
+            Some(TrapPattern::RepeatedSegment {
+                segment: "a".to_string()
+            }),
+            detector.check(&url("https://example.com/a/b/a/b/a/b/a/b".to_string()))
+        );
+        let deep = (0..40)
+            .map(|i| format!("s{i}"))
+            .collect::<Vec<_>>()
+            .join("/");
+        assert_eq!(
+            Some(TrapPattern::TooDeep { depth: 40 }),
+            detector.check(&url(format!("https://example.com/{deep}")))
+        );
+    }
+
+    #[test]
+    fn session_ids_are_variables() {
+        assert_eq!(
+            Some((
+                "/shop/{id}/cart?step={n}".to_string(),
+                "a81f3c9d2e7b4a6f\u{1f}2\u{1f}".to_string()
+            )),
+            shape_of(&["shop", "a81f3c9d2e7b4a6f", "cart"], Some("step=2"))
+        );
+        assert_eq!(None, shape_of(&["wiki", "Rust_2024"], Some("lang=en")));
+    }
+}
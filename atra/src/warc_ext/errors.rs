@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use crate::io::errors::ErrorWithPath;
+use camino::Utf8PathBuf;
 use data_encoding::DecodeError;
 use thiserror::Error;
 use warc::field::{WarcFieldName, WarcFieldValue};
@@ -31,6 +32,13 @@ pub enum ReaderError {
     Utf8(#[from] std::string::FromUtf8Error),
     #[error("The field value is {1:?} but this is not a valid value for {0} in the header!!!")]
     IllegalFieldValue(WarcFieldName, WarcFieldValue),
+    #[error("The block of the record at {offset} in {path} has the digest {actual} but {expected} is stored!")]
+    DigestMismatch {
+        expected: String,
+        actual: String,
+        path: Utf8PathBuf,
+        offset: u64,
+    },
 }
 
 #[derive(Debug, Error)]
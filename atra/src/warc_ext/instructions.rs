@@ -20,11 +20,11 @@ use serde::{Deserialize, Serialize};
 use strum::{Display, EnumIs};
 use warc::field::WarcFieldName::ExternalBinFile;
 use crate::data::RawVecData;
-use crate::io::errors::{ErrorWithPath, ToErrorWithPath};
+use crate::io::errors::ToErrorWithPath;
 use crate::io::file_owner::FileOwner;
 use crate::warc_ext::skip_pointer::WarcSkipPointerWithPath;
 use crate::warc_ext::{read_body, ReaderError};
-use crate::warc_ext::read::read_meta;
+use crate::warc_ext::read::{read_block_digests, read_meta};
 
 /// The kind of the single warc instruction.
 #[derive(Serialize, Deserialize, Display, Copy, Clone, Debug, Eq, PartialEq, EnumIs, Default)]
@@ -94,17 +94,29 @@ impl WarcSkipInstruction {
         }
     }
 
-    /// Reads this in the context of [file_owner].
+    /// The number of octets of the headers and bodies of all records of this instruction.
+    pub fn octet_count(&self) -> u64 {
+        fn count(pointer: &WarcSkipPointerWithPath) -> u64 {
+            pointer.warc_header_octet_count() as u64 + pointer.body_octet_count()
+        }
+        match self {
+            WarcSkipInstruction::Single { pointer, .. } => count(pointer),
+            WarcSkipInstruction::Multiple { pointers, .. } => pointers.iter().map(count).sum(),
+        }
+    }
+
+    /// Reads this in the context of [file_owner], see [Self::read_with] for [verify_digests].
     pub async fn read_in_context(
         &self,
         file_owner: Option<&impl FileOwner>,
+        verify_digests: bool,
     ) -> Result<RawVecData, ReaderError> {
         match self {
             value @ WarcSkipInstruction::Single { pointer, .. } => {
                 if let Some(file_owner) = file_owner {
                     file_owner.wait_until_free_path(pointer.path()).await?;
                 }
-                value.read()
+                value.read_with(verify_digests)
             }
             value @ WarcSkipInstruction::Multiple { pointers, .. } => {
                 if let Some(file_owner) = file_owner {
@@ -112,23 +124,34 @@ impl WarcSkipInstruction {
                         file_owner.wait_until_free_path(value.path()).await?;
                     }
                 }
-                value.read()
+                value.read_with(verify_digests)
             }
         }
     }
 
     /// Reads this from the pointer.
     pub fn read(&self) -> Result<RawVecData, ReaderError> {
+        self.read_with(false)
+    }
+
+    /// Reads this from the pointer. If [verify_digests] is set, the block of every read record
+    /// is digested again and compared to the block digest stored in its header.
+    pub fn read_with(&self, verify_digests: bool) -> Result<RawVecData, ReaderError> {
         fn read_impl(
             pointer: &WarcSkipPointerWithPath,
             header_signature_octet_count: u32,
-        ) -> Result<Option<Vec<u8>>, ErrorWithPath> {
+            verify_digests: bool,
+        ) -> Result<Option<Vec<u8>>, ReaderError> {
             let mut file = File::options()
                 .read(true)
                 .open(pointer.path())
                 .to_error_with_path(pointer.path())?;
-            return read_body(&mut file, pointer.pointer(), header_signature_octet_count)
-                .to_error_with_path(pointer.path());
+            if verify_digests {
+                verify_block(&mut file, pointer)?;
+            }
+            read_body(&mut file, pointer.pointer(), header_signature_octet_count)
+                .to_error_with_path(pointer.path())
+                .map_err(ReaderError::from)
         }
 
         match self {
@@ -139,10 +162,10 @@ impl WarcSkipInstruction {
             } => {
                 let result = match kind {
                     WarcSkipInstructionKind::Normal => {
-                        read_impl(pointer, *header_signature_octet_count)?.into()
+                        read_impl(pointer, *header_signature_octet_count, verify_digests)?.into()
                     }
                     WarcSkipInstructionKind::Base64 => {
-                        match read_impl(pointer, *header_signature_octet_count)? {
+                        match read_impl(pointer, *header_signature_octet_count, verify_digests)? {
                             None => {
                                 RawVecData::None
                             }
@@ -205,12 +228,14 @@ impl WarcSkipInstruction {
                 for (pos, value) in pointers.iter().with_position() {
                     match pos {
                         Position::First | Position::Only => {
-                            match read_impl(value, *header_signature_octet_count)? {
+                            let read =
+                                read_impl(value, *header_signature_octet_count, verify_digests)?;
+                            match read {
                                 None => {}
                                 Some(value) => collected_data.extend(value),
                             }
                         }
-                        _ => match read_impl(value, 0)? {
+                        _ => match read_impl(value, 0, verify_digests)? {
                             None => {}
                             Some(value) => collected_data.extend(value),
                         },
@@ -230,3 +255,19 @@ impl WarcSkipInstruction {
         }
     }
 }
+
+/// Fails with [ReaderError::DigestMismatch] if the block of the record at [pointer] does not
+/// match the block digest in its header. A record without a block digest is accepted.
+fn verify_block(file: &mut File, pointer: &WarcSkipPointerWithPath) -> Result<(), ReaderError> {
+    if let Some((expected, actual)) = read_block_digests(file, pointer.pointer())? {
+        if expected != actual {
+            return Err(ReaderError::DigestMismatch {
+                expected: String::from_utf8_lossy(&expected).into_owned(),
+                actual: String::from_utf8_lossy(&actual).into_owned(),
+                path: pointer.path().to_path_buf(),
+                offset: pointer.pointer().file_offset(),
+            });
+        }
+    }
+    Ok(())
+}
@@ -15,36 +15,60 @@
 mod errors;
 mod instructions;
 mod read;
+mod scan;
 mod skip_pointer;
 mod special_writer;
+mod warc_info;
 mod write;
 
 pub use errors::*;
 pub use instructions::*;
-pub use read::read_body;
-#[cfg(test)]
+pub use read::{read_body, unpack_header};
+pub use scan::*;
 pub use skip_pointer::*;
-// pub use skip_pointer::*;
 pub use special_writer::SpecialWarcWriter;
-pub use write::write_warc;
+pub use warc_info::WarcInfo;
+pub use write::{write_warc, write_warc_info};
 
 #[cfg(test)]
 mod test {
-    use crate::crawl::CrawlResult;
+    use crate::client::proxy::ProxyRouter;
+    use crate::client::traits::AtraClient;
+    use crate::client::{build_recording_client, ClientWithUserAgent};
+    use crate::config::{Config, CrawlConfig};
+    use crate::contexts::traits::SupportsConfigs;
+    use crate::crawl::test::create_test_data;
+    use crate::crawl::{analyze_page, CrawlResult};
     use crate::data::RawVecData;
+    use crate::fetching::warc_record::warc_response_to_fetched;
     use crate::fetching::FetchedRequestData;
     use crate::fetching::ResponseData;
     use crate::format::mime::MimeType;
     use crate::format::supported::InterpretedProcessibleFileFormat;
     use crate::format::AtraFileInformation;
+    use crate::io::errors::ErrorWithPath;
+    use crate::io::fs::{AtraFS, FileSystemAccess};
+    use crate::seed::{SeedDefinition, UnguardedSeed};
+    use crate::stores::warc::ThreadsafeMultiFileWarcWriter;
+    use crate::test_impls::{FakeClientProvider, TestContext};
     use crate::toolkit::LanguageInformation;
     use crate::url::UrlWithDepth;
     use crate::warc_ext::special_writer::MockSpecialWarcWriter;
-    use crate::warc_ext::write_warc;
+    use crate::warc_ext::{read_body, unpack_header, write_warc, SpecialWarcWriter};
+    use crate::warc_ext::{WarcInfo, WarcSkipInstruction, WriterError};
     use camino::Utf8PathBuf;
+    use camino_tempfile::Utf8TempDir;
     use encoding_rs;
+    use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE, USER_AGENT};
     use reqwest::StatusCode;
+    use std::collections::HashMap;
+    use std::io::{Cursor, Read};
+    use std::sync::Arc;
     use time::OffsetDateTime;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use warc::header::WarcHeader;
+    use warc::writer::{WarcWriter, WarcWriterError};
 
     #[test]
     fn can_write_html() {
@@ -78,16 +102,21 @@ mod test {
             .expect_get_skip_pointer()
             .returning(|| Ok((Utf8PathBuf::new(), 0)));
 
-        special.expect_write_header().return_once(|value| {
+        special.expect_write_header().times(2).returning(|value| {
             let value = value.to_string();
             println!("Header:\n{value}");
             Ok(value.len())
         });
 
-        special.expect_write_body_complete().return_once(|value| {
-            println!("Body:\n{}", String::from_utf8_lossy(value));
-            Ok(value.len())
-        });
+        special
+            .expect_write_body_complete()
+            .times(2)
+            .returning(|value| {
+                println!("Body:\n{}", String::from_utf8_lossy(value));
+                Ok(value.len())
+            });
+
+        special.expect_warc_info_id().returning(|| None);
 
         special.expect_forward_if_filesize().returning(|_| Ok(None));
 
@@ -124,16 +153,21 @@ mod test {
             .expect_get_skip_pointer()
             .returning(|| Ok((Utf8PathBuf::new(), 0)));
 
-        special.expect_write_header().return_once(|value| {
+        special.expect_write_header().times(2).returning(|value| {
             let value = value.to_string();
             println!("Header:\n{value}");
             Ok(value.len())
         });
 
-        special.expect_write_body_complete().return_once(|value| {
-            println!("Body:\n{}", String::from_utf8_lossy(value));
-            Ok(value.len())
-        });
+        special
+            .expect_write_body_complete()
+            .times(2)
+            .returning(|value| {
+                println!("Body:\n{}", String::from_utf8_lossy(value));
+                Ok(value.len())
+            });
+
+        special.expect_warc_info_id().returning(|| None);
 
         special.expect_forward_if_filesize().returning(|_| Ok(None));
 
@@ -141,4 +175,589 @@ mod test {
 
         println!("{instruction:?}")
     }
+
+    /// A warc writer into memory.
+    struct InMemoryWarcWriter {
+        writer: WarcWriter<Vec<u8>>,
+    }
+
+    impl SpecialWarcWriter for InMemoryWarcWriter {
+        fn get_skip_pointer(&self) -> Result<(Utf8PathBuf, u64), WarcWriterError> {
+            self.writer
+                .check_if_state(warc::states::State::ExpectHeader)?;
+            Ok(unsafe { self.get_skip_pointer_unchecked() })
+        }
+
+        unsafe fn get_skip_pointer_unchecked(&self) -> (Utf8PathBuf, u64) {
+            (
+                Utf8PathBuf::from("memory.warc"),
+                self.writer.bytes_written() as u64,
+            )
+        }
+
+        fn bytes_written(&self) -> usize {
+            self.writer.bytes_written()
+        }
+
+        fn warc_info_id(&self) -> Option<String> {
+            None
+        }
+
+        fn write_header(&mut self, header: WarcHeader) -> Result<usize, WarcWriterError> {
+            self.writer.write_header(&header)
+        }
+
+        fn write_body_complete(&mut self, buf: &[u8]) -> Result<usize, WarcWriterError> {
+            self.writer.write_complete_body(buf)
+        }
+
+        fn write_body<R: Read>(&mut self, body: &mut R) -> Result<usize, WarcWriterError> {
+            self.writer.write_body(body)
+        }
+
+        fn write_empty_body(&mut self) -> Result<usize, WarcWriterError> {
+            self.writer.write_complete_body(&[])
+        }
+
+        fn forward(&mut self) -> Result<Utf8PathBuf, ErrorWithPath> {
+            Ok(Utf8PathBuf::from("memory.warc"))
+        }
+    }
+
+    /// A record read by [parse_records].
+    struct ParsedRecord {
+        fields: HashMap<String, String>,
+        block: Vec<u8>,
+    }
+
+    /// A minimal WARC parser following the grammar of WARC/1.1, independent of the parser of
+    /// the warc crate.
+    fn parse_records(mut data: &[u8]) -> Vec<ParsedRecord> {
+        fn take_line<'a>(data: &mut &'a [u8]) -> &'a str {
+            let end = data
+                .windows(2)
+                .position(|value| value == b"\r\n")
+                .expect("Every line ends with CRLF.");
+            let line = std::str::from_utf8(&data[..end]).expect("The header is UTF-8.");
+            *data = &data[end + 2..];
+            line
+        }
+
+        let mut records = Vec::new();
+        while !data.is_empty() {
+            assert_eq!("WARC/1.1", take_line(&mut data));
+            let mut fields = HashMap::new();
+            loop {
+                let line = take_line(&mut data);
+                if line.is_empty() {
+                    break;
+                }
+                let (name, value) = line.split_once(':').expect("A field has a name.");
+                let previous = fields.insert(name.to_ascii_lowercase(), value.trim().to_string());
+                assert!(previous.is_none(), "{name} is set twice.");
+            }
+            for mandatory in ["warc-record-id", "warc-type", "warc-date", "content-length"] {
+                assert!(fields.contains_key(mandatory), "{mandatory} is missing.");
+            }
+            let content_length: usize = fields["content-length"].parse().unwrap();
+            let block = data[..content_length].to_vec();
+            data = &data[content_length..];
+            assert!(
+                data.starts_with(b"\r\n\r\n"),
+                "The record ends with two CRLF."
+            );
+            data = &data[4..];
+            records.push(ParsedRecord { fields, block });
+        }
+        records
+    }
+
+    /// Splits an http message into its head lines and its body.
+    fn split_http_message(block: &[u8]) -> (Vec<String>, &[u8]) {
+        let end = block
+            .windows(4)
+            .position(|value| value == b"\r\n\r\n")
+            .expect("The head ends with an empty line.");
+        let head = String::from_utf8(block[..end].to_vec()).unwrap();
+        (
+            head.split("\r\n").map(str::to_string).collect(),
+            &block[end + 4..],
+        )
+    }
+
+    #[test]
+    fn writes_a_response_record_paired_with_its_request() {
+        const HTML_DATA: &str = "<html><body>Hello World!</body></html>";
+        let mut response_headers = HeaderMap::new();
+        response_headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let mut fetched = FetchedRequestData::new(
+            RawVecData::from_vec(HTML_DATA.as_bytes().to_vec()),
+            Some(response_headers),
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        let mut request_headers = HeaderMap::new();
+        request_headers.insert(USER_AGENT, HeaderValue::from_static("Atra/test"));
+        request_headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic c2VjcmV0"));
+        fetched.request_headers = Some(request_headers);
+        let result = CrawlResult::new(
+            OffsetDateTime::now_utc(),
+            ResponseData::from_response(
+                fetched,
+                UrlWithDepth::from_url("https://www.google.de:8443/0?q=atra").unwrap(),
+            ),
+            None,
+            Some(encoding_rs::UTF_8),
+            AtraFileInformation::new(
+                InterpretedProcessibleFileFormat::HTML,
+                Some(MimeType::new_single(mime::TEXT_HTML_UTF_8)),
+                None,
+            ),
+            Some(LanguageInformation::ENG),
+        );
+
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        let instruction = write_warc(&mut writer, &result).expect("Should work!");
+        let data = writer.writer.into_inner();
+
+        let records = parse_records(&data);
+        assert_eq!(2, records.len());
+        let (request, response) = (&records[0], &records[1]);
+
+        assert_eq!("request", request.fields["warc-type"]);
+        assert_eq!(
+            "application/http;msgtype=request",
+            request.fields["content-type"]
+        );
+        assert_eq!(
+            response.fields["warc-record-id"],
+            request.fields["warc-concurrent-to"]
+        );
+        assert_eq!(
+            response.fields["warc-target-uri"],
+            request.fields["warc-target-uri"]
+        );
+        let (head, body) = split_http_message(&request.block);
+        assert_eq!(
+            vec![
+                "GET /0?q=atra HTTP/1.1",
+                "host: www.google.de:8443",
+                "user-agent: Atra/test"
+            ],
+            head
+        );
+        assert!(body.is_empty());
+
+        assert_eq!("response", response.fields["warc-type"]);
+        assert_eq!(
+            "application/http;msgtype=response",
+            response.fields["content-type"]
+        );
+        let (head, body) = split_http_message(&response.block);
+        assert_eq!(vec!["HTTP/1.1 200 OK", "content-type: text/html"], head);
+        assert_eq!(HTML_DATA.as_bytes(), body);
+
+        let (status_code, headers) = unpack_header(&response.block).unwrap();
+        assert_eq!(StatusCode::OK, status_code);
+        assert_eq!("text/html", headers.get(CONTENT_TYPE).unwrap());
+
+        let WarcSkipInstruction::Single {
+            pointer,
+            header_signature_octet_count,
+            ..
+        } = &instruction
+        else {
+            panic!("Expected a single record but got {instruction:?}");
+        };
+        assert_eq!(
+            response.block.len() - body.len(),
+            *header_signature_octet_count as usize
+        );
+        let payload = read_body(
+            &mut Cursor::new(&data),
+            pointer.pointer(),
+            *header_signature_octet_count,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(HTML_DATA.as_bytes(), payload);
+    }
+
+    #[tokio::test]
+    async fn writes_the_plain_text_as_conversion_record() {
+        const HTML_DATA: &str = "<html><head><title>Atra</title></head><body><p>Hello <b>World</b>!</p><script>var x = 1;</script></body></html>";
+        let mut config = CrawlConfig::default();
+        config.text_sidecar = true;
+        let context = TestContext::new(
+            Config::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html"));
+        let page = FetchedRequestData::new(
+            RawVecData::from_vec(HTML_DATA.as_bytes().to_vec()),
+            Some(headers),
+            StatusCode::OK,
+            None,
+            None,
+            false,
+        );
+        let target = UrlWithDepth::from_url("https://www.google.de/").unwrap();
+        let result = analyze_page(&context, page, &target)
+            .await
+            .expect("The page can be analyzed.");
+        assert_eq!(Some("AtraHello World!var x = 1;"), result.text.as_deref());
+
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        let instruction = write_warc(&mut writer, &result).expect("Should work!");
+        let data = writer.writer.into_inner();
+
+        let records = parse_records(&data);
+        assert_eq!(3, records.len());
+        let (response, conversion) = (&records[1], &records[2]);
+        assert_eq!("response", response.fields["warc-type"]);
+        assert_eq!("conversion", conversion.fields["warc-type"]);
+        assert_eq!(
+            response.fields["warc-record-id"],
+            conversion.fields["warc-refers-to"]
+        );
+        assert_eq!(
+            response.fields["warc-target-uri"],
+            conversion.fields["warc-target-uri"]
+        );
+        assert_eq!(
+            "text/plain;charset=utf-8",
+            conversion.fields["content-type"]
+        );
+        assert_eq!(b"AtraHello World!var x = 1;", conversion.block.as_slice());
+
+        let WarcSkipInstruction::Single {
+            pointer,
+            header_signature_octet_count,
+            ..
+        } = &instruction
+        else {
+            panic!("Expected a single record but got {instruction:?}");
+        };
+        let payload = read_body(
+            &mut Cursor::new(&data),
+            pointer.pointer(),
+            *header_signature_octet_count,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(HTML_DATA.as_bytes(), payload);
+    }
+
+    #[test]
+    fn writes_a_request_without_recorded_headers() {
+        let result = CrawlResult::new(
+            OffsetDateTime::now_utc(),
+            ResponseData::from_response(
+                FetchedRequestData::new(
+                    RawVecData::None,
+                    None,
+                    StatusCode::NOT_FOUND,
+                    Some("https://www.google.de/moved".to_string()),
+                    None,
+                    false,
+                ),
+                UrlWithDepth::from_url("https://www.google.de/0").unwrap(),
+            ),
+            None,
+            None,
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::Unknown, None, None),
+            None,
+        );
+
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        let instruction = write_warc(&mut writer, &result).expect("Should work!");
+        let data = writer.writer.into_inner();
+
+        let records = parse_records(&data);
+        assert_eq!(2, records.len());
+        assert_eq!(
+            "https://www.google.de/moved",
+            records[0].fields["warc-target-uri"]
+        );
+        let (head, _) = split_http_message(&records[0].block);
+        assert_eq!(vec!["GET /moved HTTP/1.1", "host: www.google.de"], head);
+        let (head, body) = split_http_message(&records[1].block);
+        assert_eq!(vec!["HTTP/1.1 404 Not Found"], head);
+        assert!(body.is_empty());
+
+        let WarcSkipInstruction::Single {
+            pointer,
+            header_signature_octet_count,
+            ..
+        } = &instruction
+        else {
+            panic!("Expected a single record but got {instruction:?}");
+        };
+        assert_eq!(
+            None,
+            read_body(
+                &mut Cursor::new(&data),
+                pointer.pointer(),
+                *header_signature_octet_count
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn marks_captured_streams_as_truncated() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/event-stream"));
+        let mut result = CrawlResult::new(
+            OffsetDateTime::now_utc(),
+            ResponseData::from_response(
+                FetchedRequestData::new(
+                    RawVecData::from_vec(b"data: 1\n\ndata: 2\n\nda".to_vec()),
+                    Some(headers),
+                    StatusCode::OK,
+                    None,
+                    None,
+                    false,
+                ),
+                UrlWithDepth::from_url("https://www.google.de/events").unwrap(),
+            ),
+            None,
+            None,
+            AtraFileInformation::new(InterpretedProcessibleFileFormat::PlainText, None, None),
+            None,
+        );
+
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        write_warc(&mut writer, &result).expect("Should work!");
+        let records = parse_records(&writer.writer.into_inner());
+        assert!(!records[1].fields.contains_key("warc-truncated"));
+
+        result.meta.truncated = true;
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        write_warc(&mut writer, &result).expect("Should work!");
+        let records = parse_records(&writer.writer.into_inner());
+        assert_eq!("response", records[1].fields["warc-type"]);
+        assert_eq!("length", records[1].fields["warc-truncated"]);
+        let (_, body) = split_http_message(&records[1].block);
+        assert_eq!(b"data: 1\n\ndata: 2\n\nda", body);
+    }
+
+    #[tokio::test]
+    async fn writes_the_response_headers_as_received() {
+        const HEAD: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nSet-Cookie: a=1; Path=/\r\nX-Mixed-CASE: Value\r\nset-cookie: b=2\r\n\r\n";
+        const HTML_DATA: &[u8] = b"<html><body>Hello World!</body></html>";
+        let mut header = WarcHeader::new();
+        header.target_uri_string("https://www.google.de/").unwrap();
+        header.date(OffsetDateTime::now_utc()).unwrap();
+        let block = [HEAD, HTML_DATA].concat();
+
+        let mut written_heads = Vec::new();
+        for faithful_headers in [false, true] {
+            let mut config = CrawlConfig::default();
+            config.faithful_headers = faithful_headers;
+            let context = TestContext::new(
+                Config::new(
+                    Default::default(),
+                    Default::default(),
+                    Default::default(),
+                    config,
+                ),
+                FakeClientProvider::new(),
+            );
+            let imported = warc_response_to_fetched(&header, &block).unwrap();
+            let result = analyze_page(&context, imported.page, &imported.url)
+                .await
+                .expect("The page can be analyzed.");
+            // The parsed headers are used as before.
+            assert_eq!(
+                2,
+                result
+                    .meta
+                    .headers
+                    .as_ref()
+                    .unwrap()
+                    .get_all("set-cookie")
+                    .iter()
+                    .count()
+            );
+
+            let mut writer = InMemoryWarcWriter {
+                writer: WarcWriter::new(Vec::new()),
+            };
+            write_warc(&mut writer, &result).expect("Should work!");
+            let records = parse_records(&writer.writer.into_inner());
+            let response = &records[1];
+            assert_eq!("response", response.fields["warc-type"]);
+            let (_, body) = split_http_message(&response.block);
+            assert_eq!(HTML_DATA, body);
+            written_heads.push(response.block[..response.block.len() - body.len()].to_vec());
+        }
+
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\ncontent-type: text/html\r\nset-cookie: a=1; Path=/\r\nset-cookie: b=2\r\nx-mixed-case: Value\r\n\r\n".as_slice(),
+            written_heads[0]
+        );
+        assert_eq!(HEAD, written_heads[1]);
+    }
+
+    #[tokio::test]
+    async fn fetched_responses_keep_the_header_lines_as_received() {
+        const HTML_DATA: &str = "<html><body>Hello World!</body></html>";
+        const HEAD: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nSet-Cookie: a=1; Path=/\r\nX-Mixed-CASE: Value\r\nset-cookie: b=2\r\nContent-Length: 38\r\nConnection: close\r\n\r\n";
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            loop {
+                let mut line = String::new();
+                match stream.read_line(&mut line).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) if line.trim_end().is_empty() => break,
+                    Ok(_) => {}
+                }
+            }
+            let mut stream = stream.into_inner();
+            stream.write_all(HEAD).await.unwrap();
+            stream.write_all(HTML_DATA.as_bytes()).await.unwrap();
+            stream.shutdown().await.unwrap();
+        });
+
+        let mut config = CrawlConfig::default();
+        config.faithful_headers = true;
+        let context = TestContext::new(
+            Config::new(
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                config,
+            ),
+            FakeClientProvider::new(),
+        );
+        let url = format!("http://{address}/");
+        let seed = UnguardedSeed::from_url(&url).unwrap();
+        let client = ClientWithUserAgent::new(
+            "atra-test".to_string(),
+            reqwest_middleware::ClientBuilder::new(
+                reqwest::Client::builder().no_proxy().build().unwrap(),
+            )
+            .build(),
+            Arc::new(ProxyRouter::from_config(&context.configs().crawl)),
+            HashMap::new(),
+        )
+        .with_recording(build_recording_client(&context, &seed));
+        let page = client.retrieve(&context, &url, None).await.unwrap();
+        assert!(page.raw_headers.is_some());
+        let result = analyze_page(&context, page, &UrlWithDepth::from_url(&url).unwrap())
+            .await
+            .expect("The page can be analyzed.");
+
+        let mut writer = InMemoryWarcWriter {
+            writer: WarcWriter::new(Vec::new()),
+        };
+        write_warc(&mut writer, &result).expect("Should work!");
+        let records = parse_records(&writer.writer.into_inner());
+        let (_, body) = split_http_message(&records[1].block);
+        assert_eq!(HTML_DATA.as_bytes(), body);
+        // The duplicates, their order and the case of the names survive byte for byte.
+        assert_eq!(
+            HEAD,
+            &records[1].block[..records[1].block.len() - body.len()]
+        );
+    }
+
+    #[tokio::test]
+    async fn every_warc_file_starts_with_a_warcinfo_record() {
+        let root = Utf8TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.session.crawl_job_id = 7;
+        let mut fs = FileSystemAccess::new(
+            config.session.service.clone(),
+            config.session.collection.clone(),
+            config.session.crawl_job_id,
+            root.path().join("output"),
+            root.path().join("big_files"),
+            config.session.dat_file_layout,
+        )
+        .unwrap()
+        .with_warc_info(WarcInfo::new(&config));
+        let seeds = SeedDefinition::Single("https://www.example.com/".to_string());
+        fs.set_seeds_digest(seeds.digest().unwrap());
+        let writer = ThreadsafeMultiFileWarcWriter::new_for_worker(Arc::new(
+            fs.create_worker_file_provider(0, 0).unwrap(),
+        ))
+        .unwrap();
+
+        let first = writer.current_file().await;
+        writer
+            .execute_on_writer::<_, WriterError, _>(|writer| {
+                for url in ["https://www.example.com/a", "https://www.example.com/b"] {
+                    let result = create_test_data(UrlWithDepth::from_url(url).unwrap(), None);
+                    write_warc(writer, &result)?;
+                    let _ = writer.forward_if_filesize(0)?;
+                }
+                Ok(())
+            })
+            .await
+            .unwrap();
+        writer.flush().await.unwrap();
+        let last = writer.current_file().await;
+
+        let mut info_ids = Vec::new();
+        let mut files = first
+            .parent()
+            .unwrap()
+            .read_dir_utf8()
+            .unwrap()
+            .map(|value| value.unwrap().into_path())
+            .filter(|value| value.extension() == Some("warc"))
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(3, files.len());
+        assert_eq!(first, files[0]);
+        assert_eq!(last, files[2]);
+        for path in &files {
+            let data = std::fs::read(path).unwrap();
+            let records = parse_records(&data);
+            let (info, others) = records.split_first().unwrap();
+            assert_eq!("warcinfo", info.fields["warc-type"]);
+            assert_eq!(path.file_name().unwrap(), info.fields["warc-filename"]);
+            assert_eq!("application/warc-fields", info.fields["content-type"]);
+            let block = String::from_utf8(info.block.clone()).unwrap();
+            assert!(block.contains("atra-crawl-job-id: 7\r\n"), "{block}");
+            assert!(block.contains(&format!(
+                "atra-seeds-digest: {}\r\n",
+                seeds.digest().unwrap()
+            )));
+            // The last file was started by the rotation and has only its warcinfo record.
+            assert_eq!(path == &last, others.is_empty());
+            for record in others {
+                assert_eq!(
+                    info.fields["warc-record-id"],
+                    record.fields["warc-warcinfo-id"]
+                );
+            }
+            info_ids.push(info.fields["warc-record-id"].clone());
+        }
+        info_ids.dedup();
+        assert_eq!(3, info_ids.len());
+    }
 }
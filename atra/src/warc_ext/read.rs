@@ -12,9 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::io::{Error, Read, Seek, SeekFrom};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::StatusCode;
+use std::io::{Error, ErrorKind, Read, Seek, SeekFrom};
 use warc::header::WarcHeader;
-use warc::reader::{WarcCursor, WarcCursorReadError};
+use warc::reader::{ReadTarget, WarcCursor, WarcCursorReadError};
+use crate::toolkit::digest::LabeledXxh128Digester;
 use crate::warc_ext::skip_pointer::WarcSkipPointer;
 
 /// Reads the body from [reader] for a provided [pointer]
@@ -27,13 +30,27 @@ pub fn read_body<R: Seek + Read>(
     reader.seek(SeekFrom::Start(
         pointer.file_offset() + pointer.warc_header_octet_count() as u64 + header_octet_count,
     ))?;
-    let to_read = pointer.body_octet_count() - header_octet_count;
+    let Some(to_read) = pointer.body_octet_count().checked_sub(header_octet_count) else {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "The header with {header_octet_count} bytes is longer than the body with {} bytes.",
+                pointer.body_octet_count()
+            ),
+        ));
+    };
     if to_read == 0 {
         return Ok(None);
     }
     let mut data = Vec::new();
-    reader.take(to_read).read_to_end(&mut data)?;
-    return Ok(Some(data));
+    let read = reader.take(to_read).read_to_end(&mut data)? as u64;
+    if read != to_read {
+        return Err(Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("Expected a body with {to_read} bytes but the file ended after {read} bytes."),
+        ));
+    }
+    Ok(Some(data))
 }
 
 /// Reads the meta from [reader] for the [pointer].
@@ -48,4 +65,117 @@ pub fn read_meta<R: Seek + Read>(
     WarcCursor::new(reader)
         .read_or_get_header()
         .map(|value| value.cloned())
-}
\ No newline at end of file
+}
+
+/// Recomputes the digest of the block of the record at [pointer]. Returns the block digest
+/// stored in the header and the recomputed one, None if the header has no block digest.
+pub fn read_block_digests<R: Seek + Read>(
+    reader: &mut R,
+    pointer: &WarcSkipPointer,
+) -> Result<Option<(Vec<u8>, Vec<u8>)>, WarcCursorReadError> {
+    let Some(header) = read_meta(reader, pointer)? else {
+        return Ok(None);
+    };
+    let Some(Ok(expected)) = header.get_block_digest() else {
+        return Ok(None);
+    };
+    let expected: &[u8] = expected.as_ref();
+    let expected = expected.to_vec();
+    reader.seek(SeekFrom::Start(
+        pointer.file_offset() + pointer.warc_header_octet_count() as u64,
+    ))?;
+    let mut digester = LabeledXxh128Digester::default();
+    let copied = std::io::copy(
+        &mut reader.by_ref().take(pointer.body_octet_count()),
+        &mut digester,
+    )?;
+    if copied != pointer.body_octet_count() {
+        return Err(WarcCursorReadError::UnexpectedEos(ReadTarget::Body, copied as usize));
+    }
+    Ok(Some((expected, digester.finish())))
+}
+
+/// Unpacks the http header block stored in front of the body of a warc record.
+/// Returns None if the block is not well formed.
+pub fn unpack_header(header_signature: &[u8]) -> Option<(StatusCode, HeaderMap)> {
+    let mut lines = header_signature.split(|value| *value == b'\n').map(|line| {
+        line.strip_suffix(b"\r").unwrap_or(line)
+    });
+    let status_line = lines.next()?;
+    let status_code = status_line.split(|value| *value == b' ').nth(1)?;
+    let status_code = StatusCode::from_bytes(status_code).ok()?;
+    let mut headers = HeaderMap::new();
+    for line in lines {
+        if line.is_empty() {
+            break;
+        }
+        let separator = memchr::memchr(b':', line)?;
+        let name = HeaderName::from_bytes(&line[..separator]).ok()?;
+        let value = line[separator + 1..].strip_prefix(b" ").unwrap_or(&line[separator + 1..]);
+        headers.append(name, HeaderValue::from_bytes(value).ok()?);
+    }
+    Some((status_code, headers))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::warc_ext::read::read_body;
+    use crate::warc_ext::skip_pointer::WarcSkipPointer;
+    use std::io::{Cursor, ErrorKind, Read, Seek, SeekFrom};
+
+    /// A reader returning at most one byte per read.
+    struct OneByteReader<R>(R);
+
+    impl<R: Read> Read for OneByteReader<R> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl<R: Seek> Seek for OneByteReader<R> {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            self.0.seek(pos)
+        }
+    }
+
+    /// A warc header of 4 bytes, followed by a header signature of 3 bytes and the body.
+    fn record(body: &[u8]) -> Vec<u8> {
+        let mut data = b"WARCsig".to_vec();
+        data.extend_from_slice(body);
+        data
+    }
+
+    #[test]
+    fn reads_the_body_on_short_reads() {
+        let body = (0..=255u8).cycle().take(10_000).collect::<Vec<_>>();
+        let pointer = WarcSkipPointer::new(0, 4, 3 + body.len() as u64);
+        let mut reader = OneByteReader(Cursor::new(record(&body)));
+        assert_eq!(Some(body), read_body(&mut reader, &pointer, 3).unwrap());
+    }
+
+    #[test]
+    fn fails_on_a_truncated_file() {
+        let body = vec![1u8; 100];
+        let pointer = WarcSkipPointer::new(0, 4, 3 + 200);
+        let mut reader = OneByteReader(Cursor::new(record(&body)));
+        let err = read_body(&mut reader, &pointer, 3).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, err.kind());
+
+        let err = read_body(&mut Cursor::new(record(&body)), &pointer, 3).unwrap_err();
+        assert_eq!(ErrorKind::UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn fails_on_a_header_longer_than_the_body() {
+        let pointer = WarcSkipPointer::new(0, 4, 2);
+        let err = read_body(&mut Cursor::new(record(b"")), &pointer, 3).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn reads_an_empty_body() {
+        let pointer = WarcSkipPointer::new(0, 4, 3);
+        assert_eq!(None, read_body(&mut Cursor::new(record(b"")), &pointer, 3).unwrap());
+    }
+}
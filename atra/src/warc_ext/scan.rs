@@ -0,0 +1,198 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::toolkit::digest::LabeledXxh128Digester;
+use crate::warc_ext::skip_pointer::WarcSkipPointer;
+use std::io::{BufRead, Read};
+use warc::field::WarcFieldName;
+use warc::header::WarcHeader;
+use warc::parser::parse_warc_header;
+use warc::reader::{ReadTarget, WarcCursorReadError};
+
+/// A record found while scanning a warc file.
+#[derive(Debug)]
+pub struct ScannedWarcRecord {
+    /// The parsed header of the record
+    pub header: WarcHeader,
+    /// The pointer to the record in the scanned file
+    pub pointer: WarcSkipPointer,
+    /// The leading `atra_header_length` octets of the body, if the record has them.
+    pub header_signature: Option<Vec<u8>>,
+    /// The digest of the body as read.
+    pub block_digest: Vec<u8>,
+    /// Some(true) iff the block digest of the header matches the body.
+    /// None if there is no block digest to check.
+    pub block_digest_matches: Option<bool>,
+}
+
+/// Reads a warc file record by record while keeping track of the offsets.
+/// Only the header signature of a body is held in memory, the rest is streamed
+/// through the digester.
+#[derive(Debug)]
+pub struct WarcRecordScanner<R> {
+    reader: R,
+    offset: u64,
+}
+
+impl<R: BufRead> WarcRecordScanner<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, offset: 0 }
+    }
+
+    /// The offset of the next record in the file.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Reads the next record, returns None if the end of the file is reached.
+    pub fn next_record(&mut self) -> Result<Option<ScannedWarcRecord>, WarcCursorReadError> {
+        let Some((raw_header, header, content_length)) = self.read_header()? else {
+            return Ok(None);
+        };
+
+        let header_signature_octet_count = header
+            .get_atra_header_length()
+            .and_then(Result::ok)
+            .map(|value| (*value).min(content_length));
+
+        let mut digester = LabeledXxh128Digester::default();
+
+        let header_signature = if let Some(octet_count) = header_signature_octet_count {
+            let mut signature = vec![0u8; octet_count as usize];
+            self.reader.read_exact(&mut signature)?;
+            digester.update(&signature);
+            Some(signature)
+        } else {
+            None
+        };
+
+        let remaining = content_length - header_signature_octet_count.unwrap_or(0);
+        let copied = std::io::copy(&mut (&mut self.reader).take(remaining), &mut digester)?;
+        if copied != remaining {
+            return Err(WarcCursorReadError::UnexpectedEos(
+                ReadTarget::Body,
+                (content_length - remaining + copied) as usize,
+            ));
+        }
+
+        self.read_record_end(content_length)?;
+
+        let block_digest = digester.finish();
+        let block_digest_matches = header
+            .get_block_digest()
+            .and_then(Result::ok)
+            .map(|value| value.as_ref() == block_digest.as_slice());
+
+        let pointer = self.advance(raw_header.len(), content_length);
+
+        Ok(Some(ScannedWarcRecord {
+            header,
+            pointer,
+            header_signature,
+            block_digest,
+            block_digest_matches,
+        }))
+    }
+
+    /// Reads the next record with its whole body in memory, returns None if the end of
+    /// the file is reached.
+    pub fn next_record_with_body(
+        &mut self,
+    ) -> Result<Option<(WarcHeader, WarcSkipPointer, Vec<u8>)>, WarcCursorReadError> {
+        let Some((raw_header, header, content_length)) = self.read_header()? else {
+            return Ok(None);
+        };
+        let mut body = Vec::with_capacity(content_length.min(1024 * 1024) as usize);
+        let read = (&mut self.reader)
+            .take(content_length)
+            .read_to_end(&mut body)? as u64;
+        if read != content_length {
+            return Err(WarcCursorReadError::UnexpectedEos(
+                ReadTarget::Body,
+                read as usize,
+            ));
+        }
+        self.read_record_end(content_length)?;
+        let pointer = self.advance(raw_header.len(), content_length);
+        Ok(Some((header, pointer, body)))
+    }
+
+    /// Reads and parses the header of the next record, returns None if the end of the file
+    /// is reached.
+    fn read_header(&mut self) -> Result<Option<(Vec<u8>, WarcHeader, u64)>, WarcCursorReadError> {
+        let mut raw_header = Vec::with_capacity(1024);
+        loop {
+            let read = self.reader.read_until(b'\n', &mut raw_header)?;
+            if read == 0 {
+                return if raw_header.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(WarcCursorReadError::UnexpectedEos(
+                        ReadTarget::Header,
+                        raw_header.len(),
+                    ))
+                };
+            }
+            if raw_header.ends_with(b"\r\n\r\n") {
+                break;
+            }
+        }
+
+        let header = match parse_warc_header(&raw_header) {
+            Ok((_, header)) => header,
+            Err(nom::Err::Incomplete(_)) => {
+                return Err(WarcCursorReadError::UnexpectedEos(
+                    ReadTarget::Header,
+                    raw_header.len(),
+                ))
+            }
+            Err(nom::Err::Error(err)) => {
+                return Err(WarcCursorReadError::NomError(err.code, raw_header.clone()))
+            }
+            Err(nom::Err::Failure(fail)) => {
+                return Err(WarcCursorReadError::NomFailure(fail.code, raw_header.clone()))
+            }
+        };
+
+        let content_length = match header.get_content_length() {
+            Ok(value) => *value,
+            Err(_) => {
+                return Err(WarcCursorReadError::RequiredFieldMissing(
+                    WarcFieldName::ContentLength,
+                ))
+            }
+        };
+        Ok(Some((raw_header, header, content_length)))
+    }
+
+    /// Consumes the `\r\n\r\n` closing a record.
+    fn read_record_end(&mut self, content_length: u64) -> Result<(), WarcCursorReadError> {
+        let mut tail = [0u8; 4];
+        self.reader.read_exact(&mut tail)?;
+        if !tail.eq(b"\r\n\r\n") {
+            return Err(WarcCursorReadError::BadRecordEnd(
+                (content_length as usize, false),
+                tail.to_vec(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Returns the pointer to the record just read and moves the offset to the next one.
+    fn advance(&mut self, header_octet_count: usize, content_length: u64) -> WarcSkipPointer {
+        let pointer = WarcSkipPointer::new(self.offset, header_octet_count as u32, content_length);
+        self.offset += header_octet_count as u64 + content_length + 4;
+        pointer
+    }
+}
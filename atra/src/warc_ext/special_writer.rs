@@ -33,6 +33,9 @@ pub trait SpecialWarcWriter {
     /// Returns the number of bytes written to the file
     fn bytes_written(&self) -> usize;
 
+    /// Returns the id of the warcinfo record at the start of the current file, if any.
+    fn warc_info_id(&self) -> Option<String>;
+
     /// Writes a warc header to the file.
     /// Returns the number of bytes written.
     fn write_header(&mut self, header: WarcHeader) -> Result<usize, WarcWriterError>;
@@ -0,0 +1,100 @@
+// Copyright 2024 Felix Engl
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::config::crawl::{RobotsFallback, UserAgent};
+use crate::config::Config;
+use std::fmt::Write;
+
+/// Describes the crawl in the `warcinfo` record at the start of every warc file.
+#[derive(Debug, Clone)]
+pub struct WarcInfo {
+    service: String,
+    collection: String,
+    crawl_job_id: u64,
+    /// None if the user agent is spoofed.
+    user_agent: Option<String>,
+    respect_robots_txt: bool,
+    robots_on_client_error: RobotsFallback,
+    robots_on_server_error: RobotsFallback,
+    /// The default budget as JSON.
+    budget: String,
+    seeds_digest: Option<String>,
+}
+
+impl WarcInfo {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            service: config.session.service.clone(),
+            collection: config.session.collection.clone(),
+            crawl_job_id: config.session.crawl_job_id,
+            user_agent: match config.crawl.user_agent {
+                UserAgent::Spoof => None,
+                ref other => Some(other.get_user_agent().to_string()),
+            },
+            respect_robots_txt: config.crawl.respect_robots_txt,
+            robots_on_client_error: config.crawl.robots_on_client_error,
+            robots_on_server_error: config.crawl.robots_on_server_error,
+            budget: serde_json::to_string(&config.crawl.budget.default)
+                .expect("The budget can be serialized."),
+            seeds_digest: None,
+        }
+    }
+
+    /// Sets the digest of the seed list the crawl was started with.
+    pub fn set_seeds_digest(&mut self, digest: String) {
+        self.seeds_digest = Some(digest);
+    }
+
+    /// The block of the record as `application/warc-fields`.
+    pub(super) fn fields(&self) -> String {
+        let mut fields = String::new();
+        let mut field = |name: &str, value: &dyn std::fmt::Display| {
+            write!(fields, "{name}: {value}\r\n").expect("Writing to a string never fails.");
+        };
+        field(
+            "software",
+            &concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION")),
+        );
+        field("format", &"WARC File Format 1.1");
+        field("isPartOf", &self.collection);
+        field("atra-service", &self.service);
+        field("atra-collection", &self.collection);
+        field("atra-crawl-job-id", &self.crawl_job_id);
+        field(
+            "http-header-user-agent",
+            &self.user_agent.as_deref().unwrap_or("spoofed"),
+        );
+        field(
+            "robots",
+            &if self.respect_robots_txt {
+                "obey"
+            } else {
+                "ignore"
+            },
+        );
+        field(
+            "atra-robots-on-client-error",
+            &format_args!("{:?}", self.robots_on_client_error),
+        );
+        field(
+            "atra-robots-on-server-error",
+            &format_args!("{:?}", self.robots_on_server_error),
+        );
+        field("atra-budget", &self.budget);
+        if let Some(ref digest) = self.seeds_digest {
+            field("atra-seeds-digest", digest);
+        }
+        fields
+    }
+}
@@ -12,26 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::config::profiles::is_sensitive_header;
 use crate::crawl::CrawlResult;
 use crate::data::RawVecData;
+use crate::fetching::RawHeaders;
 use crate::format::supported::InterpretedProcessibleFileFormat;
 use crate::toolkit::digest::labeled_xxh128_digest;
 use crate::warc_ext::errors::WriterError;
 use crate::warc_ext::instructions::{WarcSkipInstructionKind, WarcSkipInstruction};
 use crate::warc_ext::skip_pointer::WarcSkipPointerWithPath;
 use crate::warc_ext::special_writer::SpecialWarcWriter;
+use crate::warc_ext::warc_info::WarcInfo;
 use data_encoding::BASE64;
 use itertools::{Itertools, Position};
-use reqwest::header::CONTENT_TYPE;
+use reqwest::header::{HeaderMap, HeaderName, CONTENT_TYPE, HOST};
 use std::borrow::Cow;
+use time::OffsetDateTime;
 use ubyte::ToByteUnit;
+use url::Url;
 use uuid::Uuid;
 use warc::field::UriLikeFieldValue;
 use warc::header::WarcHeader;
-use warc::media_type::parse_media_type;
+use warc::media_type::{parse_media_type, MediaType};
 use warc::record_type::WarcRecordType;
 use warc::truncated_reason::TruncatedReason;
 
+/// The media type of a request record with a http request.
+const HTTP_REQUEST_MEDIA_TYPE: &[u8] = b"application/http;msgtype=request";
+/// The media type of a response record with a http response.
+const HTTP_RESPONSE_MEDIA_TYPE: &[u8] = b"application/http;msgtype=response";
+/// The media type of a conversion record with the plain text of a response.
+const PLAIN_TEXT_MEDIA_TYPE: &[u8] = b"text/plain;charset=utf-8";
+/// The media type of a warcinfo record.
+const WARC_FIELDS_MEDIA_TYPE: &[u8] = b"application/warc-fields";
+
 macro_rules! log_consume {
     ($e: expr) => {{
         log::trace!(stringify!($e))
@@ -45,44 +59,216 @@ macro_rules! log_consume {
     }};
 }
 
-/// Packs the header
+/// Packs the http response head stored in front of the payload of the response record.
 fn pack_header(page: &CrawlResult) -> Vec<u8> {
     log::trace!("Pack header");
     let mut output = Vec::new();
-    // todo: Different rest requests?
-    output.extend(b"GET ");
+    // The protocol version is not recorded, every fetch is archived as HTTP/1.1.
+    output.extend(b"HTTP/1.1 ");
     output.extend(page.meta.status_code.as_str().as_bytes());
     if let Some(reason) = page.meta.status_code.canonical_reason() {
         output.extend(b" ");
         output.extend(reason.as_bytes());
     }
     output.extend(b"\r\n");
-    if let Some(headers) = &page.meta.headers {
-        for (k, v) in headers {
-            output.extend(k.as_str().as_bytes());
-            output.extend(b": ");
-            output.extend(v.as_bytes());
-            output.extend(b"\r\n");
-        }
+    if let Some(raw_headers) = &page.meta.raw_headers {
+        pack_raw_header_fields(&mut output, raw_headers);
+    } else if let Some(headers) = &page.meta.headers {
+        pack_header_fields(&mut output, headers);
     }
     output.extend(b"\r\n");
     log::trace!("Finished packing header");
     output
 }
 
-/// Creates a war entry
-pub fn write_warc<W: SpecialWarcWriter>(
+/// Packs the http request head of the request record for the [target_uri].
+/// The `Host` is taken from the [target_uri], the other headers are the ones recorded with
+/// the fetch, if any.
+fn pack_request(page: &CrawlResult, target_uri: &str) -> Vec<u8> {
+    let (request_target, host) = match Url::parse(target_uri) {
+        Ok(url) => {
+            let mut request_target = url.path().to_string();
+            if let Some(query) = url.query() {
+                request_target.push('?');
+                request_target.push_str(query);
+            }
+            let host = url.host_str().map(|host| match url.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host.to_string(),
+            });
+            (request_target, host)
+        }
+        Err(_) => (target_uri.to_string(), None),
+    };
+    let mut output = Vec::new();
+    output.extend(b"GET ");
+    output.extend(request_target.as_bytes());
+    output.extend(b" HTTP/1.1\r\n");
+    if let Some(host) = host {
+        output.extend(HOST.as_str().as_bytes());
+        output.extend(b": ");
+        output.extend(host.as_bytes());
+        output.extend(b"\r\n");
+    }
+    if let Some(headers) = &page.meta.request_headers {
+        let mut headers = headers.clone();
+        headers.remove(HOST);
+        pack_header_fields(&mut output, &headers);
+    }
+    output.extend(b"\r\n");
+    output
+}
+
+fn pack_header_fields(output: &mut Vec<u8>, headers: &HeaderMap) {
+    // Credentials never end up in the archive.
+    for (k, v) in headers.iter().filter(|(k, _)| !is_sensitive_header(k)) {
+        output.extend(k.as_str().as_bytes());
+        output.extend(b": ");
+        output.extend(v.as_bytes());
+        output.extend(b"\r\n");
+    }
+}
+
+/// Packs the header lines as received, with their order and the case of their names.
+fn pack_raw_header_fields(output: &mut Vec<u8>, raw_headers: &RawHeaders) {
+    for (k, v) in raw_headers.iter() {
+        // Credentials never end up in the archive, whatever the case of their name.
+        if HeaderName::from_bytes(k).is_ok_and(|name| is_sensitive_header(&name)) {
+            continue;
+        }
+        output.extend(k);
+        output.extend(b": ");
+        output.extend(v);
+        output.extend(b"\r\n");
+    }
+}
+
+/// Writes the request record paired with the response record [response_id].
+fn write_request<W: SpecialWarcWriter>(
     worker_warc_writer: &mut W,
     content: &CrawlResult,
-) -> Result<WarcSkipInstruction, WriterError> {
+    target_uri: &str,
+    response_id: &str,
+) -> Result<(), WriterError> {
+    let request = pack_request(content, target_uri);
     let mut builder = WarcHeader::new();
-    log_consume!(builder.warc_type(WarcRecordType::Response));
-    let first_id = Uuid::new_v5(
+    log_consume!(builder.warc_type(WarcRecordType::Request));
+    log_consume!(builder.warc_record_id_string(&Uuid::new_v4().as_urn().to_string()));
+    log_consume!(builder.concurrent_to_string(response_id));
+    refer_to_warc_info(&mut builder, worker_warc_writer);
+    log_consume!(builder.date(content.meta.created_at));
+    let urilike = unsafe { UriLikeFieldValue::from_string_unchecked(target_uri) };
+    log_consume!(builder.target_uri(urilike));
+    log_consume!(builder.content_type(http_media_type(HTTP_REQUEST_MEDIA_TYPE)));
+    log_consume!(builder.block_digest_bytes(labeled_xxh128_digest(&request)));
+    log_consume!(builder.content_length(request.len() as u64));
+    worker_warc_writer.write_header(builder)?;
+    worker_warc_writer.write_body_complete(&request)?;
+    Ok(())
+}
+
+/// Writes the plain [text] of [content] as conversion record referring to the response
+/// record [response_id].
+fn write_conversion<W: SpecialWarcWriter>(
+    worker_warc_writer: &mut W,
+    content: &CrawlResult,
+    response_id: &str,
+    text: &str,
+) -> Result<(), WriterError> {
+    let page_url = content.meta.url.try_as_str();
+    let target_uri = match content.meta.final_redirect_destination {
+        Some(ref redir) => redir.as_str(),
+        None => page_url.as_ref(),
+    };
+    let mut builder = WarcHeader::new();
+    log_consume!(builder.warc_type(WarcRecordType::Conversion));
+    log_consume!(builder.warc_record_id_string(&Uuid::new_v4().as_urn().to_string()));
+    log_consume!(builder.refers_to_string(response_id));
+    refer_to_warc_info(&mut builder, worker_warc_writer);
+    log_consume!(builder.date(content.meta.created_at));
+    let urilike = unsafe { UriLikeFieldValue::from_string_unchecked(target_uri) };
+    log_consume!(builder.target_uri(urilike));
+    log_consume!(builder.content_type(http_media_type(PLAIN_TEXT_MEDIA_TYPE)));
+    log_consume!(builder.block_digest_bytes(labeled_xxh128_digest(text.as_bytes())));
+    log_consume!(builder.content_length(text.len() as u64));
+    worker_warc_writer.write_header(builder)?;
+    worker_warc_writer.write_body_complete(text.as_bytes())?;
+    Ok(())
+}
+
+/// Sets the `WARC-Warcinfo-ID` of [builder] to the warcinfo record of the current file of
+/// [worker_warc_writer], if it has one.
+fn refer_to_warc_info<W: SpecialWarcWriter>(builder: &mut WarcHeader, worker_warc_writer: &W) {
+    if let Some(id) = worker_warc_writer.warc_info_id() {
+        log_consume!(builder.info_id_string(&id));
+    }
+}
+
+/// Writes the warcinfo record describing the crawl of [info] at the start of the warc file
+/// [file_name]. Returns the id of the record.
+pub fn write_warc_info<W: SpecialWarcWriter>(
+    worker_warc_writer: &mut W,
+    info: &WarcInfo,
+    file_name: &str,
+) -> Result<String, WriterError> {
+    let block = info.fields();
+    let id = Uuid::new_v4().as_urn().to_string();
+    let mut builder = WarcHeader::new();
+    log_consume!(builder.warc_type(WarcRecordType::WarcInfo));
+    log_consume!(builder.warc_record_id_string(&id));
+    log_consume!(builder.date(OffsetDateTime::now_utc()));
+    log_consume!(builder.file_name_string(file_name));
+    log_consume!(builder.content_type(http_media_type(WARC_FIELDS_MEDIA_TYPE)));
+    log_consume!(builder.block_digest_bytes(labeled_xxh128_digest(block.as_bytes())));
+    log_consume!(builder.content_length(block.len() as u64));
+    worker_warc_writer.write_header(builder)?;
+    worker_warc_writer.write_body_complete(block.as_bytes())?;
+    Ok(id)
+}
+
+fn http_media_type(media_type: &[u8]) -> MediaType {
+    parse_media_type::<true>(media_type)
+        .expect("The http media types are valid.")
+        .1
+}
+
+/// The record id of the response record of [content].
+fn response_record_id(content: &CrawlResult) -> String {
+    Uuid::new_v5(
         &Uuid::NAMESPACE_URL,
         (&content.meta.url).try_as_str().as_bytes(),
     )
     .as_urn()
-    .to_string();
+    .to_string()
+}
+
+/// Creates a warc entry, the plain text of the page is written as conversion record after the
+/// response record. A failed conversion record is only logged, the response record is kept.
+pub fn write_warc<W: SpecialWarcWriter>(
+    worker_warc_writer: &mut W,
+    content: &CrawlResult,
+) -> Result<WarcSkipInstruction, WriterError> {
+    let instruction = write_response(worker_warc_writer, content)?;
+    if let Some(ref text) = content.text {
+        let response_id = response_record_id(content);
+        if let Err(err) = write_conversion(worker_warc_writer, content, &response_id, text) {
+            log::error!(
+                "Failed to write the plain text of {} to the warc: {err}",
+                content.meta.url
+            );
+        }
+    }
+    Ok(instruction)
+}
+
+/// Writes the response record of [content] with the request record in front of it.
+fn write_response<W: SpecialWarcWriter>(
+    worker_warc_writer: &mut W,
+    content: &CrawlResult,
+) -> Result<WarcSkipInstruction, WriterError> {
+    let mut builder = WarcHeader::new();
+    log_consume!(builder.warc_type(WarcRecordType::Response));
+    let first_id = response_record_id(content);
     log_consume!(builder.warc_record_id_string(&first_id));
     log_consume!(builder.date(content.meta.created_at));
 
@@ -94,14 +280,13 @@ pub fn write_warc<W: SpecialWarcWriter>(
         log_consume!(builder.atra_language_hint(language.lang()));
     }
 
-    if let Some(ref redir) = content.meta.final_redirect_destination {
-        let urilike = unsafe { UriLikeFieldValue::from_string_unchecked(redir) };
-        log_consume!(builder.target_uri(urilike));
-    } else {
-        let urilike_page =
-            unsafe { UriLikeFieldValue::from_string_unchecked(&content.meta.url.try_as_str()) };
-        log_consume!(builder.target_uri(urilike_page));
-    }
+    let page_url = content.meta.url.try_as_str();
+    let target_uri = match content.meta.final_redirect_destination {
+        Some(ref redir) => redir.as_str(),
+        None => page_url.as_ref(),
+    };
+    let urilike = unsafe { UriLikeFieldValue::from_string_unchecked(target_uri) };
+    log_consume!(builder.target_uri(urilike));
 
     let found_ll = if let Some(ref found) = content.meta.headers {
         if let Some(found) = found.get(CONTENT_TYPE) {
@@ -129,11 +314,17 @@ pub fn write_warc<W: SpecialWarcWriter>(
         content.meta.file_information.get_best_media_type_for_warc()
     };
 
-    log_consume!(builder.content_type(found));
+    log_consume!(builder.content_type(http_media_type(HTTP_RESPONSE_MEDIA_TYPE)));
+    log_consume!(builder.indentified_payload_type(found));
 
     let header = pack_header(&content);
     let header_signature_octet_count = header.len();
 
+    // The request is written in front of the response, the skip pointers of the response
+    // are taken after it.
+    write_request(worker_warc_writer, content, target_uri, &first_id)?;
+    refer_to_warc_info(&mut builder, worker_warc_writer);
+
     let data = match &content.content {
         RawVecData::ExternalFile { path } => {
             log::trace!("Warc-Write: External");
@@ -200,6 +391,10 @@ pub fn write_warc<W: SpecialWarcWriter>(
         }
     };
 
+    if content.meta.truncated {
+        log_consume!(builder.truncated_reason(TruncatedReason::Length));
+    }
+
     let mut body = header;
 
     let (data, is_base64) = match content.meta.file_information.format {
@@ -227,6 +422,8 @@ pub fn write_warc<W: SpecialWarcWriter>(
             .with_position()
         {
             let mut sub_builder = builder.clone();
+            // A previous segment may have forwarded to the next file.
+            refer_to_warc_info(&mut sub_builder, worker_warc_writer);
             match position {
                 Position::First => {
                     // warc_type set beforehand
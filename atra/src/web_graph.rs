@@ -12,6 +12,8 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use crate::extraction::extractor_method::ExtractorMethod;
+use crate::extraction::marker::ExtractorMethodHint;
 use crate::runtime::{AtraHandleOption, RuntimeContext};
 use crate::seed::BasicSeed;
 use crate::url::{AtraOriginProvider, AtraUri, AtraUrlOrigin, UrlWithDepth};
@@ -32,7 +34,7 @@ use tokio::sync::mpsc::Sender;
 use ubyte::ByteUnit;
 
 /// The entry of a webgraph.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum WebGraphEntry {
     /// A seed
     Seed {
@@ -40,15 +42,38 @@ pub enum WebGraphEntry {
         seed: AtraUri,
     },
     /// A normal link
-    Link { from: AtraUri, to: AtraUri },
+    Link {
+        from: AtraUri,
+        to: AtraUri,
+        /// The extractor that found the link, if known.
+        extractor: Option<ExtractorMethod>,
+        /// The link was found on an error page.
+        from_error_page: bool,
+    },
+    /// A url listed in a sitemap
+    SitemapLink { sitemap: AtraUri, to: AtraUri },
 }
 
 impl WebGraphEntry {
     #[inline]
-    pub fn create_link(from: &UrlWithDepth, to: &UrlWithDepth) -> Self {
+    pub fn create_link(
+        from: &UrlWithDepth,
+        to: &UrlWithDepth,
+        extraction_method: &ExtractorMethodHint,
+    ) -> Self {
         Self::Link {
             from: from.url.clone(),
             to: to.url.clone(),
+            extractor: Some(extraction_method.used_method),
+            from_error_page: extraction_method.from_error_page,
+        }
+    }
+
+    #[inline]
+    pub fn create_sitemap_link(sitemap: &UrlWithDepth, to: &UrlWithDepth) -> Self {
+        Self::SitemapLink {
+            sitemap: sitemap.url.clone(),
+            to: to.url.clone(),
         }
     }
 
@@ -61,7 +86,7 @@ impl WebGraphEntry {
     }
 
     /// A helper method for consuming lines.
-    fn collect(&self, out: &mut impl EntryLineConsumer) {
+    pub(crate) fn collect(&self, out: &mut impl EntryLineConsumer) {
         fn recognize_atra_uri(uri: &AtraUri, out: &mut impl EntryLineConsumer) -> String {
             let result = match uri.try_as_str() {
                 None => {
@@ -86,17 +111,43 @@ impl WebGraphEntry {
                 let seed = recognize_atra_uri(seed, out);
                 out.push(format!("o:{origin} :has_seed {seed} .\n"))
             }
-            WebGraphEntry::Link { from, to } => {
+            WebGraphEntry::Link {
+                from,
+                to,
+                extractor,
+                from_error_page,
+            } => {
                 let from = recognize_atra_uri(from, out);
                 let to = recognize_atra_uri(to, out);
-                out.push(format!("{} :links_to {} .\n", from.as_str(), to.as_str()))
+                let predicate = if *from_error_page {
+                    ":error_page_links_to"
+                } else {
+                    ":links_to"
+                };
+                out.push(format!("{} {predicate} {} .\n", from.as_str(), to.as_str()));
+                if let Some(extractor) = extractor {
+                    out.push(format!(
+                        "{} {LINKS_TO_WITH}{extractor} {} .\n",
+                        from.as_str(),
+                        to.as_str()
+                    ))
+                }
+            }
+            WebGraphEntry::SitemapLink { sitemap, to } => {
+                let sitemap = recognize_atra_uri(sitemap, out);
+                let to = recognize_atra_uri(to, out);
+                out.push(format!("{} :sitemap_lists {} .\n", sitemap.as_str(), to.as_str()))
             }
         }
     }
 }
 
+/// The predicate prefix of the triple naming the extractor of a link,
+/// written directly after the `:links_to` or `:error_page_links_to` triple of the link.
+const LINKS_TO_WITH: &str = ":links_to_with_";
+
 /// A consumer for an entry line
-trait EntryLineConsumer {
+pub(crate) trait EntryLineConsumer {
     fn push(&mut self, value: String);
 }
 
@@ -294,13 +345,104 @@ impl WebGraphManager for QueuingWebGraphManager {
     }
 }
 
+/// A statement read back from a stored webgraph.
+/// Urls that are not valid UTF-8 are represented lossy.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum WebGraphStatement {
+    /// The [seed] of an [origin]
+    Seed { origin: String, seed: String },
+    /// A link from [from] to [to]
+    Link { from: String, to: String },
+    /// A link from the error page [from] to [to]
+    ErrorPageLink { from: String, to: String },
+    /// The [extractor] that found the link from [from] to [to],
+    /// written directly after the [WebGraphStatement::Link] or [WebGraphStatement::ErrorPageLink].
+    LinkExtractor {
+        from: String,
+        to: String,
+        extractor: String,
+    },
+    /// A url listed in a sitemap
+    SitemapLink { sitemap: String, to: String },
+}
+
+impl WebGraphStatement {
+    /// Parses a single line of a stored webgraph.
+    /// Returns none for prefixes, labels, origins of urls and unknown lines.
+    pub fn parse_line(line: &str) -> Option<Self> {
+        fn next_term(value: &str) -> Option<(&str, &str)> {
+            let value = value.trim_start();
+            let end = if value.starts_with('<') {
+                value.find('>')? + 1
+            } else {
+                value.find(char::is_whitespace)?
+            };
+            Some(value.split_at(end))
+        }
+
+        fn decode_url(term: &str) -> Option<String> {
+            if let Some(url) = term.strip_prefix('<') {
+                Some(url.strip_suffix('>')?.to_string())
+            } else {
+                let encoded = term.strip_prefix("ol:")?;
+                let decoded = BASE32_NOPAD.decode(encoded.as_bytes()).ok()?;
+                Some(String::from_utf8_lossy(&decoded).into_owned())
+            }
+        }
+
+        let line = line.trim();
+        if line.starts_with('@') || !line.ends_with('.') {
+            return None;
+        }
+        let (subject, rest) = next_term(line)?;
+        let (predicate, rest) = next_term(rest)?;
+        let (object, _) = next_term(rest)?;
+        match predicate {
+            ":has_seed" => Some(Self::Seed {
+                origin: subject.strip_prefix("o:")?.to_string(),
+                seed: decode_url(object)?,
+            }),
+            ":links_to" => Some(Self::Link {
+                from: decode_url(subject)?,
+                to: decode_url(object)?,
+            }),
+            ":error_page_links_to" => Some(Self::ErrorPageLink {
+                from: decode_url(subject)?,
+                to: decode_url(object)?,
+            }),
+            ":sitemap_lists" => Some(Self::SitemapLink {
+                sitemap: decode_url(subject)?,
+                to: decode_url(object)?,
+            }),
+            other => Some(Self::LinkExtractor {
+                extractor: other.strip_prefix(LINKS_TO_WITH)?.to_string(),
+                from: decode_url(subject)?,
+                to: decode_url(object)?,
+            }),
+        }
+    }
+}
+
+/// Streams the statements of the webgraph stored in [reader], line by line.
+pub fn read_web_graph<R: BufRead>(
+    reader: R,
+) -> impl Iterator<Item = io::Result<WebGraphStatement>> {
+    reader.lines().filter_map(|line| match line {
+        Ok(line) => WebGraphStatement::parse_line(&line).map(Ok),
+        Err(err) => Some(Err(err)),
+    })
+}
+
 #[cfg(test)]
 mod test {
     use crate::runtime::{
         GracefulShutdownWithGuard, OptionalAtraHandle, RuntimeContext,
     };
+    use crate::extraction::extractor_method::ExtractorMethod;
     use crate::url::AtraUri;
-    use crate::web_graph::{QueuingWebGraphManager, WebGraphEntry, WebGraphManager};
+    use crate::web_graph::{
+        read_web_graph, QueuingWebGraphManager, WebGraphEntry, WebGraphManager, WebGraphStatement,
+    };
     use log::LevelFilter;
     use log4rs::append::console::ConsoleAppender;
     use log4rs::config::{Appender, Logger, Root};
@@ -351,6 +493,8 @@ mod test {
                 to: (format!("http://www.test.de/{}", i + 1)
                     .parse::<AtraUri>()
                     .unwrap()),
+                extractor: None,
+                from_error_page: false,
             };
             handles.spawn(async move {
                 let wait_result = c.wait().await;
@@ -374,4 +518,72 @@ mod test {
         let read = std::fs::read_to_string(Path::new("./atra_data/example.ttl")).unwrap();
         println!("Turtle-File:\n\n{read}")
     }
+
+    #[test]
+    fn can_read_the_written_statements() {
+        let from = "https://www.test.de/a?b=1&c=2".parse::<AtraUri>().unwrap();
+        let to = "https://www.other.de/".parse::<AtraUri>().unwrap();
+        let mut written = String::new();
+        WebGraphEntry::Seed {
+            origin: "www.test.de".into(),
+            seed: from.clone(),
+        }
+        .collect(&mut written);
+        WebGraphEntry::Link {
+            from: from.clone(),
+            to: to.clone(),
+            extractor: Some(ExtractorMethod::HtmlV1),
+            from_error_page: false,
+        }
+        .collect(&mut written);
+        WebGraphEntry::Link {
+            from: to.clone(),
+            to: from.clone(),
+            extractor: Some(ExtractorMethod::HtmlV1),
+            from_error_page: true,
+        }
+        .collect(&mut written);
+        WebGraphEntry::SitemapLink {
+            sitemap: to.clone(),
+            to: from.clone(),
+        }
+        .collect(&mut written);
+
+        let read = read_web_graph(written.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let from = from.to_string();
+        let to = to.to_string();
+        assert_eq!(
+            vec![
+                WebGraphStatement::Seed {
+                    origin: "www.test.de".to_string(),
+                    seed: from.clone()
+                },
+                WebGraphStatement::Link {
+                    from: from.clone(),
+                    to: to.clone()
+                },
+                WebGraphStatement::LinkExtractor {
+                    from: from.clone(),
+                    to: to.clone(),
+                    extractor: "HtmlV1".to_string()
+                },
+                WebGraphStatement::ErrorPageLink {
+                    from: to.clone(),
+                    to: from.clone()
+                },
+                WebGraphStatement::LinkExtractor {
+                    from: to.clone(),
+                    to: from.clone(),
+                    extractor: "HtmlV1".to_string()
+                },
+                WebGraphStatement::SitemapLink {
+                    sitemap: to,
+                    to: from
+                },
+            ],
+            read
+        );
+    }
 }
@@ -0,0 +1,17 @@
+// Redirects with a single string literal.
+location = "/assigned";
+window.location = 'https://www.example.org/window';
+window.location.href = "href.html"
+document.location.href = '../document/href.html';
+location.assign("/assign?from=script");
+top.location.replace('/replace');
+if (expired) { location.href = "/without-semicolon" }
+
+// Computed values are not followed.
+location = "/prefix/" + path;
+window.location.href = base + "/suffix";
+location.assign(target);
+location.replace("/a" + "/b");
+location.href = `/template/${id}`;
+var url = "/not-a-redirect";
+/* location = "/commented"; */
@@ -21,7 +21,7 @@ use liblinear::Model;
 use liblinear::{Parameters, PredictionInput, TrainingInput};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::fmt::{Debug, Formatter};
+use std::fmt::{Debug, Display, Formatter};
 use text_processing::tf_idf::{IdfAlgorithm, TfAlgorithm};
 use text_processing::tokenizer::Tokenizer;
 use text_processing::vectorizer::DocumentVectorizer;
@@ -157,9 +157,33 @@ impl<TF, IDF, SOLVER> DocumentClassifier<TF, IDF, SOLVER> {
     }
 }
 
+/// The label of a class, liblinear stores the labels of the classification solvers as integers.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ClassLabel(pub i32);
+
+impl ClassLabel {
+    /// Converts a label returned by liblinear.
+    pub fn from_label(label: f64) -> Self {
+        Self(label.round() as i32)
+    }
+
+    /// The label as used by liblinear.
+    pub fn as_label(self) -> f64 {
+        self.0 as f64
+    }
+}
+
+impl Display for ClassLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
 /// A struct implementing this is used as train data.
 pub trait TrainDataEntry {
-    /// The label of the entry
+    /// The label of the entry, either ±1 for a binary classifier or the
+    /// label of the class, see [ClassLabel].
     fn get_label(&self) -> f64;
 
     /// The text of the entry
@@ -179,6 +203,19 @@ where
     }
 }
 
+impl<Text> TrainDataEntry for (ClassLabel, Text)
+where
+    Text: AsRef<str>,
+{
+    fn get_label(&self) -> f64 {
+        self.0.as_label()
+    }
+
+    fn get_text(&self) -> &str {
+        self.1.as_ref()
+    }
+}
+
 impl<TF, IDF, SOLVER> DocumentClassifier<TF, IDF, SOLVER>
 where
     TF: TfAlgorithm,
@@ -259,4 +296,107 @@ where
             doc.sparse_features(),
         )?)?)
     }
+
+    /// Predicts the class of [doc], requires a classification solver.
+    /// Unlike [predict] this works for any number of classes.
+    pub fn predict_class(&self, doc: &str) -> Result<ClassLabel, LibLinearError> {
+        let prediction = self.predict(doc)?;
+        if prediction.is_nan() {
+            return Err(LibLinearError::NotClassifiable);
+        }
+        Ok(ClassLabel::from_label(prediction))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::classifier::{ClassLabel, DocumentClassifier};
+    use crate::error::LibLinearError;
+    use isolang::Language;
+    use liblinear::parameter::serde::GenericParameters;
+    use liblinear::solver::L2R_LR;
+    use liblinear::Parameters;
+    use text_processing::tf_idf::defaults::TERM_FREQUENCY_INVERSE;
+    use text_processing::tf_idf::{Idf, Tf};
+    use text_processing::tokenizer::Tokenizer;
+    use text_processing::vectorizer::create_vectorizer;
+
+    const SPORT: ClassLabel = ClassLabel(1);
+    const COOKING: ClassLabel = ClassLabel(2);
+    const WEATHER: ClassLabel = ClassLabel(3);
+
+    fn train_data() -> Vec<(ClassLabel, &'static str)> {
+        vec![
+            (SPORT, "the team won the football match with a late goal"),
+            (SPORT, "the striker scored a goal and the team won"),
+            (SPORT, "football fans cheered for the team in the match"),
+            (COOKING, "boil the pasta and add salt to the water"),
+            (COOKING, "the recipe needs butter and sugar for the cake"),
+            (COOKING, "bake the cake in the oven and serve the pasta hot"),
+            (WEATHER, "heavy rain and strong wind are expected tomorrow"),
+            (WEATHER, "the forecast says sunny weather with light wind"),
+            (WEATHER, "snow and rain will cool the weather this weekend"),
+        ]
+    }
+
+    fn create_classifier() -> DocumentClassifier<Tf, Idf, L2R_LR> {
+        let tokenizer = Tokenizer::new(Language::Eng, true, None, None);
+        let vectorizer = create_vectorizer(
+            train_data().into_iter().map(|(_, text)| text),
+            &tokenizer,
+            TERM_FREQUENCY_INVERSE,
+        )
+        .unwrap();
+        let parameters: Parameters<L2R_LR> = GenericParameters {
+            cost: Some(10.0),
+            ..GenericParameters::default()
+        }
+        .try_into()
+        .map_err(LibLinearError::from)
+        .unwrap();
+        DocumentClassifier::train(
+            &Language::Eng,
+            vectorizer,
+            tokenizer,
+            train_data(),
+            &parameters,
+            2,
+            0,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn predicts_more_than_two_classes() {
+        let classifier = create_classifier();
+        assert_eq!(
+            SPORT,
+            classifier
+                .predict_class("the team scored a late goal in the football match")
+                .unwrap()
+        );
+        assert_eq!(
+            COOKING,
+            classifier
+                .predict_class("add butter and sugar to the cake in the oven")
+                .unwrap()
+        );
+        assert_eq!(
+            WEATHER,
+            classifier
+                .predict_class("rain and wind in the forecast for tomorrow")
+                .unwrap()
+        );
+        assert!(matches!(
+            classifier.predict_class("goal"),
+            Err(LibLinearError::NotClassifiable)
+        ));
+
+        let serialized = bincode::serialize(&classifier).unwrap();
+        let loaded: DocumentClassifier<Tf, Idf, L2R_LR> =
+            bincode::deserialize(&serialized).unwrap();
+        for (label, text) in train_data() {
+            assert_eq!(label, loaded.predict_class(text).unwrap());
+        }
+    }
 }
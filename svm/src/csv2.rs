@@ -1,5 +1,5 @@
 use crate::classifier::TrainDataEntry;
-use csv::{Reader, StringRecord, StringRecordsIntoIter};
+use csv::{Position, Reader, StringRecord, StringRecordsIntoIter};
 use serde::de::DeserializeOwned;
 use serde::Deserialize;
 use std::io;
@@ -57,6 +57,11 @@ where
     }
 }
 
+/// The line of the [position], 0 if it is unknown.
+fn line_of(position: Option<&Position>) -> u64 {
+    position.map_or(0, Position::line)
+}
+
 impl<T, R> Iterator for CsvProvider<T, R>
 where
     T: DeserializeOwned,
@@ -64,8 +69,22 @@ where
 {
     type Item = T;
 
+    /// Skips the rows that can not be read, with a warning naming their line.
     fn next(&mut self) -> Option<Self::Item> {
-        let next = self.string_records_iter.next()?.ok()?;
-        next.deserialize(Some(&self.header)).ok()
+        loop {
+            match self.string_records_iter.next()? {
+                Ok(record) => match record.deserialize(Some(&self.header)) {
+                    Ok(value) => return Some(value),
+                    Err(err) => log::warn!(
+                        "Skip line {} of the train data: {err}",
+                        line_of(record.position())
+                    ),
+                },
+                Err(err) => log::warn!(
+                    "Skip line {} of the train data: {err}",
+                    line_of(err.position())
+                ),
+            }
+        }
     }
 }
@@ -25,6 +25,8 @@ pub enum LibLinearError {
     Build(#[from] ModelError),
     #[error(transparent)]
     Prediction(#[from] PredictionInputError),
+    #[error("The document is shorter than the minimum length for a classification.")]
+    NotClassifiable,
 }
 
 /// An error from creating a svm classifier
@@ -19,7 +19,7 @@ mod toolkit;
 
 mod csv2;
 
-use crate::classifier::{DocumentClassifier, TrainDataEntry};
+use crate::classifier::{ClassLabel, DocumentClassifier, TrainDataEntry};
 use crate::config::{DocumentClassifierConfig, SvmRecognizerConfig};
 use crate::error::{LibLinearError, SvmCreationError};
 pub use csv2::CsvProvider;
@@ -28,7 +28,7 @@ use liblinear::parameter::serde::{GenericParameters, SupportsParametersCreation}
 use liblinear::solver::GenericSolver;
 use liblinear::Model;
 use serde::de::DeserializeOwned;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Read};
@@ -130,20 +130,37 @@ where
     Ok(model)
 }
 
-/// An entry of a train csv
+/// An entry of a train csv, either with an `is_class` column for a binary classifier
+/// or with an integer `label` column for any number of classes.
 #[derive(Debug, Deserialize)]
 pub struct CsvTrainModelEntry {
-    #[serde(alias = "is_gdbr")]
+    /// An empty value, like in an export that is not labeled yet, is false.
+    #[serde(alias = "is_gdbr", default, deserialize_with = "empty_as_false")]
     pub is_class: bool,
+    /// The label of the class, takes precedence over [is_class]. A row with a label that is
+    /// not an integer is skipped by [read_train_data].
+    #[serde(default)]
+    pub label: Option<ClassLabel>,
     pub text: String,
 }
 
+fn empty_as_false<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    Ok(Option::<bool>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+impl CsvTrainModelEntry {
+    /// The class of the entry.
+    pub fn class(&self) -> ClassLabel {
+        ClassLabel::from_label(self.get_label())
+    }
+}
+
 impl TrainDataEntry for crate::CsvTrainModelEntry {
     fn get_label(&self) -> f64 {
-        if self.is_class {
-            1.0
-        } else {
-            -1.0
+        match self.label {
+            Some(label) => label.as_label(),
+            None if self.is_class => 1.0,
+            None => -1.0,
         }
     }
 
@@ -236,7 +253,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use crate::classifier::DocumentClassifier;
+    use crate::classifier::{ClassLabel, DocumentClassifier};
     use crate::config::DocumentClassifierConfig;
     use crate::csv2::CsvProvider;
     use crate::{read_train_data, train, CsvTrainModelEntry};
@@ -300,4 +317,40 @@ mod test {
             serde_json::from_str(&x).unwrap();
         drop(x);
     }
+
+    #[test]
+    fn reads_unlabeled_rows_as_not_in_class() {
+        let file = camino_tempfile::NamedUtf8TempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "is_class,text\n,\"first, \"\"quoted\"\"\nline\"\ntrue,second\n",
+        )
+        .unwrap();
+        let read: Vec<CsvTrainModelEntry> = read_train_data::<Idf>(file.path()).unwrap().collect();
+        assert_eq!(2, read.len());
+        assert!(!read[0].is_class);
+        assert_eq!("first, \"quoted\"\nline", read[0].text);
+        assert!(read[1].is_class);
+    }
+
+    #[test]
+    fn reads_integer_labels_only() {
+        let file = camino_tempfile::NamedUtf8TempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "label,text
+2,first
+1.5,second
+,third
+",
+        )
+        .unwrap();
+        // The row with the invalid label is skipped, the rows after it are read.
+        let read: Vec<CsvTrainModelEntry> = read_train_data::<Idf>(file.path()).unwrap().collect();
+        assert_eq!(2, read.len());
+        assert_eq!(ClassLabel(2), read[0].class());
+        assert_eq!("first", read[0].text);
+        assert_eq!(ClassLabel(-1), read[1].class());
+        assert_eq!("third", read[1].text);
+    }
 }